@@ -2,6 +2,8 @@
 //! Provides IDE features for the CBU DSL including syntax highlighting,
 //! code completion, error diagnostics, and validation.
 
+pub mod reference_data;
+
 use std::collections::HashMap;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
@@ -9,22 +11,56 @@ use tower_lsp::{Client, LanguageServer, LspService, Server};
 use tracing::{info, warn, error};
 
 // Import CBU DSL components
-use data_designer_core::lisp_cbu_dsl::{LispCbuParser, LispValue, LispDslError};
+use data_designer_core::cbu_constraints::{CbuConstraintSet, ConstraintContext};
+use data_designer_core::error_codes::ErrorCode;
+use data_designer_core::lisp_cbu_dsl::{cbu_indent_for_line, format_cbu_dsl, CbuFormatOptions, LispCbuParser, LispValue, LispDslError, PendingOperation};
 use data_designer_core::cbu_dsl::CbuDslParser;
 use data_designer_core::parser::parse_expression;
+use data_designer_core::source_structure::{
+    folds, matching_delimiters, regions, selection_spans, unbalanced_delimiters, RegionKind, LISP_SYNTAX,
+};
+use data_designer_core::db::{DbPool, EditorDatabase};
+use reference_data::{ReferenceCache, ReferenceData};
+
+/// Reloads the entity and CBU IDs offered as completions
+const REFRESH_REFERENCE_DATA: &str = "cbu-dsl.refreshReferenceData";
+
+/// Shows what a top-level form would change, from its code lens
+const PREVIEW_CHANGES: &str = "cbu-dsl.previewChanges";
+
+/// Semantic token modifiers parentheses cycle through by depth, for rainbow colouring
+const PAREN_DEPTHS: [&str; 6] = ["depth1", "depth2", "depth3", "depth4", "depth5", "depth6"];
 
 pub struct CbuDslLanguageServer {
     client: Client,
     document_map: tokio::sync::RwLock<HashMap<Url, String>>,
     lisp_parser: tokio::sync::RwLock<LispCbuParser>,
+    /// Line width from `initializationOptions.format`; the indent comes from each request's tab size
+    format_options: tokio::sync::RwLock<CbuFormatOptions>,
+    /// Entity and CBU IDs completed inside `(entity "…")` and `(update-cbu "…")`
+    reference_data: ReferenceCache,
+    /// What the CBUs a document creates are checked against
+    constraints: CbuConstraintSet,
 }
 
 impl CbuDslLanguageServer {
     pub fn new(client: Client) -> Self {
+        Self::with_database(client, EditorDatabase::default())
+    }
+
+    /// A server completing IDs from a pool the caller has already connected
+    pub fn with_pool(client: Client, pool: DbPool) -> Self {
+        Self::with_database(client, EditorDatabase::with_pool(pool, None))
+    }
+
+    fn with_database(client: Client, database: EditorDatabase) -> Self {
         Self {
             client,
             document_map: tokio::sync::RwLock::new(HashMap::new()),
             lisp_parser: tokio::sync::RwLock::new(LispCbuParser::new(None)),
+            format_options: tokio::sync::RwLock::new(CbuFormatOptions::default()),
+            reference_data: ReferenceCache::new(database),
+            constraints: CbuConstraintSet::standard(),
         }
     }
 
@@ -32,6 +68,38 @@ impl CbuDslLanguageServer {
     async fn validate_document(&self, uri: &Url, text: &str) -> Vec<Diagnostic> {
         let mut diagnostics = Vec::new();
 
+        // Unbalanced parentheses are reported where they are; the parser
+        // could only fail on them without saying where
+        let unbalanced = unbalanced_delimiters(text, &LISP_SYNTAX);
+        if !unbalanced.is_empty() {
+            return unbalanced
+                .iter()
+                .map(|delimiter| Diagnostic {
+                    range: Range {
+                        start: position_at(text, delimiter.offset),
+                        end: position_at(text, delimiter.offset + delimiter.delimiter.len_utf8()),
+                    },
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    code: Some(NumberOrString::String(ErrorCode::CbuUnbalanced.code().to_string())),
+                    source: Some("cbu-dsl-lsp".to_string()),
+                    message: delimiter.message(),
+                    ..Default::default()
+                })
+                .collect();
+        }
+
+        // Business constraints on the CBUs created, where the forms are
+        let data = self.reference_data.get().await;
+        let context = constraint_context(&data);
+        diagnostics.extend(self.constraints.check(text, &context).into_iter().map(|violation| Diagnostic {
+            range: Range { start: position_at(text, violation.start), end: position_at(text, violation.end) },
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String(ErrorCode::CbuConstraint.code().to_string())),
+            source: Some("cbu-dsl-lsp".to_string()),
+            message: format!("{} ({})", violation.message, violation.constraint),
+            ..Default::default()
+        }));
+
         // Try parsing as S-expression first
         let mut parser = self.lisp_parser.write().await;
         match parser.parse_and_eval(text) {
@@ -43,7 +111,7 @@ impl CbuDslLanguageServer {
                             end: Position { line: 0, character: text.len() as u32 },
                         },
                         severity: Some(DiagnosticSeverity::ERROR),
-                        code: Some(NumberOrString::String("CBU_DSL_ERROR".to_string())),
+                        code: Some(NumberOrString::String(ErrorCode::CbuRejected.code().to_string())),
                         code_description: None,
                         source: Some("cbu-dsl-lsp".to_string()),
                         message: result.message,
@@ -61,7 +129,7 @@ impl CbuDslLanguageServer {
                         end: Position { line, character: character + 10 },
                     },
                     severity: Some(DiagnosticSeverity::ERROR),
-                    code: Some(NumberOrString::String("PARSE_ERROR".to_string())),
+                    code: Some(NumberOrString::String(ErrorCode::CbuSyntax.code().to_string())),
                     code_description: None,
                     source: Some("cbu-dsl-lsp".to_string()),
                     message: format!("{}", error),
@@ -86,7 +154,7 @@ impl CbuDslLanguageServer {
     }
 
     /// Provide code completion suggestions
-    fn get_completion_items(&self, _text: &str, _position: Position) -> Vec<CompletionItem> {
+    fn get_completion_items(&self, text: &str, _position: Position) -> Vec<CompletionItem> {
         let mut items = Vec::new();
 
         // S-expression functions
@@ -173,6 +241,114 @@ impl CbuDslLanguageServer {
             },
         ]);
 
+        // Changes inside update-cbu
+        items.extend(vec![
+            CompletionItem {
+                label: "set-name".to_string(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some("Rename the CBU".to_string()),
+                insert_text: Some("set-name \"$1\"".to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: "set-description".to_string(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some("Replace the CBU's description".to_string()),
+                insert_text: Some("set-description \"$1\"".to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: "add-member".to_string(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some("Add an entity to the CBU in a role".to_string()),
+                insert_text: Some("add-member \"$1\" \"$2\" $3".to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: "remove-member".to_string(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some("Remove an entity from one of its roles".to_string()),
+                insert_text: Some("remove-member \"$1\" $2".to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: "change-role".to_string(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some("Move an entity from one role to another".to_string()),
+                insert_text: Some("change-role \"$1\" $2 $3".to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            },
+        ]);
+
+        // Binding and iteration forms
+        items.extend(vec![
+            CompletionItem {
+                label: "let".to_string(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                detail: Some("Bind names for the forms inside".to_string()),
+                documentation: Some(Documentation::String(
+                    "(let ((name value) ...) body ...)".to_string()
+                )),
+                insert_text: Some("let (($1 $2))\n  $0".to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: "map".to_string(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some("Apply a lambda to each item of a list".to_string()),
+                documentation: Some(Documentation::String(
+                    "(map (lambda (item) ...) (list ...))".to_string()
+                )),
+                insert_text: Some("map (lambda ($1)\n  $0)\n  (list $2)".to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: "lambda".to_string(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                detail: Some("A function for map".to_string()),
+                documentation: Some(Documentation::String(
+                    "(lambda (params ...) body ...)".to_string()
+                )),
+                insert_text: Some("lambda ($1) $0".to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: "list".to_string(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some("A list of values".to_string()),
+                insert_text: Some("list $0".to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: "quasiquote".to_string(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                detail: Some("A template: ,x inserts a value, ,@x the items of a list".to_string()),
+                documentation: Some(Documentation::String(
+                    "`((entity ,id \"name\" role) ,@more)".to_string()
+                )),
+                ..Default::default()
+            },
+        ]);
+
+        // Names the script binds
+        for name in bound_names(text) {
+            items.push(CompletionItem {
+                label: name,
+                kind: Some(CompletionItemKind::VARIABLE),
+                detail: Some("Bound by let or lambda".to_string()),
+                ..Default::default()
+            });
+        }
+
         items
     }
 
@@ -244,6 +420,7 @@ impl CbuDslLanguageServer {
     fn get_semantic_tokens(&self, text: &str) -> Vec<SemanticToken> {
         let mut tokens = Vec::new();
         let lines: Vec<&str> = text.lines().collect();
+        let mut depth = 0usize;
 
         for (line_idx, line) in lines.iter().enumerate() {
             let mut char_idx = 0;
@@ -252,13 +429,32 @@ impl CbuDslLanguageServer {
             while let Some(ch) = chars.next() {
                 match ch {
                     '(' | ')' => {
+                        // Each depth gets its own modifier, so clients can colour the pairs apart
+                        if ch == ')' {
+                            depth = depth.saturating_sub(1);
+                        }
                         tokens.push(SemanticToken {
                             delta_line: if tokens.is_empty() { line_idx as u32 } else { 0 },
                             delta_start: if tokens.is_empty() { char_idx as u32 } else { 1 },
                             length: 1,
                             token_type: 0, // Delimiter
+                            token_modifiers_bitset: 1 << (depth % PAREN_DEPTHS.len()),
+                        });
+                        if ch == '(' {
+                            depth += 1;
+                        }
+                    }
+                    '\'' | '`' | ',' => {
+                        // Quote, quasiquote and unquote prefixes (,@ is one token)
+                        let length = if ch == ',' && chars.next_if_eq(&'@').is_some() { 2 } else { 1 };
+                        tokens.push(SemanticToken {
+                            delta_line: if tokens.is_empty() { line_idx as u32 } else { 0 },
+                            delta_start: if tokens.is_empty() { char_idx as u32 } else { 1 },
+                            length,
+                            token_type: 5, // Keyword
                             token_modifiers_bitset: 0,
                         });
+                        char_idx += length as usize - 1;
                     }
                     '"' => {
                         // String literal
@@ -307,10 +503,13 @@ impl CbuDslLanguageServer {
                         }
 
                         let token_type = match word.as_str() {
-                            "create-cbu" | "update-cbu" | "delete-cbu" | "query-cbu" | "entity" | "entities" => 3, // Function
+                            "create-cbu" | "update-cbu" | "delete-cbu" | "query-cbu" | "entity" | "entities" |
+                            "set-name" | "set-description" | "add-member" | "remove-member" | "change-role" |
+                            "map" | "list" => 3, // Function
                             "asset-owner" | "investment-manager" | "managing-company" | "custodian" |
                             "administrator" | "prime-broker" | "general-partner" | "limited-partner" => 4, // Enum
-                            "true" | "false" | "nil" => 5, // Keyword
+                            "true" | "false" | "nil" | "let" | "lambda" | "quote" | "quasiquote" |
+                            "unquote" | "unquote-splicing" => 5, // Keyword
                             _ => 6, // Variable
                         };
 
@@ -330,13 +529,71 @@ impl CbuDslLanguageServer {
 
         tokens
     }
+
+    /// The top-level forms that would change something, each with what it would do
+    fn planned_forms(text: &str) -> Vec<(Range, Vec<PendingOperation>)> {
+        let mut forms = Vec::new();
+        let mut end = 0;
+        for group in regions(text, &LISP_SYNTAX).into_iter().filter(|region| region.kind == RegionKind::Group) {
+            if group.start < end {
+                continue;
+            }
+            end = group.end;
+            // Planned on its own, so one bad form doesn't hide the others' lenses
+            let Ok(plan) = LispCbuParser::new(None).eval_plan(&text[group.start..group.end]) else {
+                continue;
+            };
+            let operations: Vec<PendingOperation> = plan.into_iter().flatten().collect();
+            if !operations.is_empty() {
+                forms.push((Range { start: position_at(text, group.start), end: position_at(text, group.end) }, operations));
+            }
+        }
+        forms
+    }
+
+    /// Shows the operations of the form at `[uri, range]` without executing them
+    async fn preview_changes(&self, arguments: &[serde_json::Value]) -> Option<serde_json::Value> {
+        let uri: Url = serde_json::from_value(arguments.first()?.clone()).ok()?;
+        let range: Range = serde_json::from_value(arguments.get(1)?.clone()).ok()?;
+        let text = self.document_map.read().await.get(&uri)?.clone();
+        let (_, operations) = Self::planned_forms(&text).into_iter().find(|(form, _)| *form == range)?;
+
+        let summaries: Vec<String> = operations.iter().map(|operation| format!("• {}", operation.summary())).collect();
+        let message = format!("Running this would (nothing has been changed):\n{}", summaries.join("\n"));
+        self.client.show_message(MessageType::INFO, message).await;
+        serde_json::to_value(operations).ok()
+    }
+
+    /// The configured width, indented by the editor's tab size
+    async fn format_options(&self, options: &FormattingOptions) -> CbuFormatOptions {
+        CbuFormatOptions { indent: options.tab_size as usize, ..*self.format_options.read().await }
+    }
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for CbuDslLanguageServer {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
         info!("CBU DSL Language Server initializing...");
 
+        // e.g. {"format": {"width": 100}}
+        let format = params.initialization_options.as_ref().and_then(|options| options.get("format"));
+        if let Some(format) = format {
+            match serde_json::from_value::<CbuFormatOptions>(format.clone()) {
+                Ok(options) => *self.format_options.write().await = options,
+                Err(e) => warn!("Ignoring invalid format options: {}", e),
+            }
+        }
+
+        // e.g. {"databaseUrl": "postgres://localhost/data_designer", "authToken": "dde_..."}
+        let option = |name: &str| {
+            let options = params.initialization_options.as_ref()?;
+            options.get(name)?.as_str().map(str::to_string)
+        };
+        let (database_url, auth_token) = (option("databaseUrl"), option("authToken"));
+        if database_url.is_some() || auth_token.is_some() {
+            self.reference_data.connect(EditorDatabase::new(database_url, auth_token)).await;
+        }
+
         Ok(InitializeResult {
             server_info: Some(ServerInfo {
                 name: "CBU DSL Language Server".to_string(),
@@ -348,7 +605,7 @@ impl LanguageServer for CbuDslLanguageServer {
                 )),
                 completion_provider: Some(CompletionOptions {
                     resolve_provider: Some(false),
-                    trigger_characters: Some(vec!["(".to_string(), " ".to_string()]),
+                    trigger_characters: Some(vec!["(".to_string(), " ".to_string(), "\"".to_string()]),
                     work_done_progress_options: Default::default(),
                     all_commit_characters: None,
                     completion_item: None,
@@ -376,13 +633,26 @@ impl LanguageServer for CbuDslLanguageServer {
                                     SemanticTokenType::new("keyword"),
                                     SemanticTokenType::new("variable"),
                                 ],
-                                token_modifiers: vec![],
+                                token_modifiers: PAREN_DEPTHS.into_iter().map(SemanticTokenModifier::new).collect(),
                             },
                             range: Some(true),
                             full: Some(SemanticTokensFullOptions::Bool(true)),
                         }
                     )
                 ),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                code_lens_provider: Some(CodeLensOptions { resolve_provider: Some(false) }),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: "\n".to_string(),
+                    more_trigger_character: Some(vec![")".to_string()]),
+                }),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: vec![REFRESH_REFERENCE_DATA.to_string(), PREVIEW_CHANGES.to_string()],
+                    ..Default::default()
+                }),
                 ..ServerCapabilities::default()
             },
         })
@@ -437,11 +707,41 @@ impl LanguageServer for CbuDslLanguageServer {
         let uri = &params.text_document_position.text_document.uri;
         let position = params.text_document_position.position;
 
-        if let Some(text) = self.document_map.read().await.get(uri) {
-            let items = self.get_completion_items(text, position);
-            Ok(Some(CompletionResponse::Array(items)))
-        } else {
-            Ok(None)
+        let Some(text) = self.document_map.read().await.get(uri).cloned() else {
+            return Ok(None);
+        };
+        let items = match string_argument_at(&text, offset_at(&text, position)) {
+            Some(argument) if argument.takes_stored_id() => {
+                let data = self.reference_data.get().await;
+                reference_completions(&text, &argument, &data)
+            }
+            _ => self.get_completion_items(&text, position),
+        };
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<serde_json::Value>> {
+        if !self.reference_data.database().await.allows_command(&params.command).await {
+            let message = format!("{} is outside what this editor's token grants", params.command);
+            self.client.show_message(MessageType::WARNING, message).await;
+            return Ok(None);
+        }
+        if params.command == PREVIEW_CHANGES {
+            return Ok(self.preview_changes(&params.arguments).await);
+        }
+        if params.command != REFRESH_REFERENCE_DATA {
+            return Ok(None);
+        }
+        match self.reference_data.refresh().await {
+            Ok(data) => {
+                let message = format!("Loaded {} entities and {} CBUs for completion", data.entities.len(), data.cbus.len());
+                self.client.show_message(MessageType::INFO, message).await;
+                Ok(Some(serde_json::json!({ "entities": data.entities.len(), "cbus": data.cbus.len() })))
+            }
+            Err(e) => {
+                self.client.show_message(MessageType::ERROR, format!("Couldn't load entities and CBUs: {}", e)).await;
+                Ok(None)
+            }
         }
     }
 
@@ -456,6 +756,22 @@ impl LanguageServer for CbuDslLanguageServer {
         }
     }
 
+    /// Pull diagnostics, the same ones published when the document opens or changes
+    async fn diagnostic(&self, params: DocumentDiagnosticParams) -> Result<DocumentDiagnosticReportResult> {
+        let uri = params.text_document.uri;
+        let text = self.document_map.read().await.get(&uri).cloned();
+        let items = match text {
+            Some(text) => self.validate_document(&uri, &text).await,
+            None => Vec::new(),
+        };
+        Ok(DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(
+            RelatedFullDocumentDiagnosticReport {
+                related_documents: None,
+                full_document_diagnostic_report: FullDocumentDiagnosticReport { result_id: None, items },
+            },
+        )))
+    }
+
     async fn semantic_tokens_full(&self, params: SemanticTokensParams) -> Result<Option<SemanticTokensResult>> {
         let uri = &params.text_document.uri;
 
@@ -469,6 +785,347 @@ impl LanguageServer for CbuDslLanguageServer {
             Ok(None)
         }
     }
+
+    /// Folds multi-line S-expressions, such as `(entities ...)` groups, and runs of `;` comments
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        let Some(text) = self.document_map.read().await.get(&params.text_document.uri).cloned() else {
+            return Ok(None);
+        };
+        let ranges = folds(&text, &regions(&text, &LISP_SYNTAX))
+            .into_iter()
+            .map(|fold| FoldingRange {
+                start_line: fold.start_line as u32,
+                end_line: fold.end_line as u32,
+                kind: Some(match fold.kind {
+                    RegionKind::Comment => FoldingRangeKind::Comment,
+                    RegionKind::Group | RegionKind::Block => FoldingRangeKind::Region,
+                }),
+                ..Default::default()
+            })
+            .collect();
+        Ok(Some(ranges))
+    }
+
+    /// Re-indents the whole document; one that doesn't parse is left alone
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let Some(text) = self.document_map.read().await.get(&params.text_document.uri).cloned() else {
+            return Ok(None);
+        };
+        let options = self.format_options(&params.options).await;
+        let formatted = match format_cbu_dsl(&text, &options) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                warn!("Not formatting {}: {}", params.text_document.uri, e);
+                return Ok(None);
+            }
+        };
+        if formatted == text {
+            return Ok(Some(Vec::new()));
+        }
+        Ok(Some(vec![TextEdit {
+            range: Range { start: Position { line: 0, character: 0 }, end: position_at(&text, text.len()) },
+            new_text: formatted,
+        }]))
+    }
+
+    /// Indents the line a newline starts, or a `)` is typed at the start of,
+    /// to where the formatter would put it
+    async fn on_type_formatting(&self, params: DocumentOnTypeFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let position = params.text_document_position.position;
+        let Some(text) = self.document_map.read().await.get(&params.text_document_position.text_document.uri).cloned() else {
+            return Ok(None);
+        };
+        let Some(line) = text.split('\n').nth(position.line as usize) else {
+            return Ok(None);
+        };
+        let options = self.format_options(&params.options).await;
+        let indent = cbu_indent_for_line(&text, position.line as usize, &options);
+        let current = line.len() - line.trim_start_matches([' ', '\t']).len();
+        if line[..current] == " ".repeat(indent) {
+            return Ok(Some(Vec::new()));
+        }
+        Ok(Some(vec![TextEdit {
+            range: Range {
+                start: Position { line: position.line, character: 0 },
+                end: Position { line: position.line, character: current as u32 },
+            },
+            new_text: " ".repeat(indent),
+        }]))
+    }
+
+    /// The parenthesis at or just before the cursor and its partner
+    async fn document_highlight(&self, params: DocumentHighlightParams) -> Result<Option<Vec<DocumentHighlight>>> {
+        let position = params.text_document_position_params.position;
+        let Some(text) = self.document_map.read().await.get(&params.text_document_position_params.text_document.uri).cloned() else {
+            return Ok(None);
+        };
+        let structure = regions(&text, &LISP_SYNTAX);
+        let Some((open, close)) = matching_delimiters(offset_at(&text, position), &structure) else {
+            return Ok(None);
+        };
+        let highlight = |offset: usize| DocumentHighlight {
+            range: Range { start: position_at(&text, offset), end: position_at(&text, offset + 1) },
+            kind: Some(DocumentHighlightKind::TEXT),
+        };
+        Ok(Some(vec![highlight(open), highlight(close)]))
+    }
+
+    /// A "Preview changes" lens over each top-level form that would change the database
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let uri = params.text_document.uri;
+        let Some(text) = self.document_map.read().await.get(&uri).cloned() else {
+            return Ok(None);
+        };
+
+        let lenses = Self::planned_forms(&text)
+            .into_iter()
+            .map(|(range, operations)| CodeLens {
+                range,
+                command: Some(Command {
+                    title: format!("Preview changes ({})", operations.len()),
+                    command: PREVIEW_CHANGES.to_string(),
+                    arguments: Some(vec![serde_json::json!(uri), serde_json::json!(range)]),
+                }),
+                data: None,
+            })
+            .collect();
+        Ok(Some(lenses))
+    }
+
+    /// Expand-selection: the atom under the cursor, then each enclosing list's
+    /// contents and the list itself, out to the whole document
+    async fn selection_range(&self, params: SelectionRangeParams) -> Result<Option<Vec<SelectionRange>>> {
+        let Some(text) = self.document_map.read().await.get(&params.text_document.uri).cloned() else {
+            return Ok(None);
+        };
+        let structure = regions(&text, &LISP_SYNTAX);
+        let ranges = params
+            .positions
+            .into_iter()
+            .map(|position| {
+                let spans = selection_spans(&text, offset_at(&text, position), &LISP_SYNTAX, &structure);
+                let mut selection: Option<SelectionRange> = None;
+                for (start, end) in spans.into_iter().rev() {
+                    selection = Some(SelectionRange {
+                        range: Range { start: position_at(&text, start), end: position_at(&text, end) },
+                        parent: selection.map(Box::new),
+                    });
+                }
+                selection.unwrap_or(SelectionRange { range: Range { start: position, end: position }, parent: None })
+            })
+            .collect();
+        Ok(Some(ranges))
+    }
+}
+
+/// A quoted argument the cursor is in, of the form it belongs to
+struct StringArgument<'a> {
+    form: &'a str,
+    /// Which argument, counting from 0 after the form's name
+    index: usize,
+    /// The quoted arguments before it, unquoted
+    previous: Vec<&'a str>,
+    /// The string's contents, from its opening quote to its closing one or the line's end
+    contents: (usize, usize),
+}
+
+impl StringArgument<'_> {
+    /// Whether this is where an entity or CBU ID goes
+    fn takes_stored_id(&self) -> bool {
+        matches!((self.form, self.index), ("entity" | "add-member", 0 | 1) | ("update-cbu" | "delete-cbu" | "remove-member" | "change-role", 0))
+    }
+}
+
+/// The quoted argument `offset` is in, if any, skipping comments and the
+/// strings and forms closed before it
+/// Names bound anywhere in the script by `(let ((name value) ...)` or
+/// `(lambda (name ...)`, sorted and without duplicates. Read from the text
+/// rather than parsed, so they complete while the form is still unbalanced.
+fn bound_names(text: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    for (keyword, is_let) in [("(let", true), ("(lambda", false)] {
+        for (at, _) in text.match_indices(keyword) {
+            let rest = &text[at + keyword.len()..];
+            if !rest.starts_with(char::is_whitespace) {
+                continue;
+            }
+            let Some(list) = rest.trim_start().strip_prefix('(') else {
+                continue;
+            };
+            // A let binds the first symbol of each pair, a lambda every symbol
+            let mut depth = 0usize;
+            let mut expecting_name = !is_let;
+            let mut chars = list.chars().peekable();
+            while let Some(ch) = chars.next() {
+                match ch {
+                    '(' => {
+                        depth += 1;
+                        expecting_name = is_let && depth == 1;
+                    }
+                    ')' if depth == 0 => break,
+                    ')' => {
+                        depth -= 1;
+                        expecting_name = false;
+                    }
+                    '"' => {
+                        expecting_name = false;
+                        for ch in chars.by_ref() {
+                            if ch == '"' {
+                                break;
+                            }
+                        }
+                    }
+                    ch if ch.is_whitespace() => {}
+                    ch => {
+                        let mut name = ch.to_string();
+                        while let Some(ch) = chars.next_if(|ch| !ch.is_whitespace() && !matches!(ch, '(' | ')' | '"')) {
+                            name.push(ch);
+                        }
+                        if expecting_name {
+                            names.push(name);
+                        }
+                        expecting_name = !is_let;
+                    }
+                }
+            }
+        }
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn string_argument_at(text: &str, offset: usize) -> Option<StringArgument<'_>> {
+    // The forms open at `offset`, each with its name and arguments so far
+    let mut forms: Vec<(usize, Vec<(usize, usize)>)> = Vec::new();
+    let mut open_string = None;
+    let mut chars = text[..offset].char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let span = match c {
+            '"' => {
+                let mut span = None;
+                while let Some((j, next)) = chars.next() {
+                    if next == '"' {
+                        span = Some((i, j + 1));
+                        break;
+                    }
+                    if next == '\\' {
+                        chars.next();
+                    }
+                }
+                if span.is_none() {
+                    open_string = Some(i);
+                }
+                span
+            }
+            ';' => {
+                while chars.next_if(|(_, next)| *next != '\n').is_some() {}
+                None
+            }
+            '(' => {
+                forms.push((i, Vec::new()));
+                None
+            }
+            // A closed form counts as one argument of the form around it
+            ')' => forms.pop().map(|(start, _)| (start, i + 1)),
+            c if c.is_whitespace() => None,
+            _ => {
+                let mut end = i + c.len_utf8();
+                while let Some((j, next)) = chars.next_if(|(_, next)| !next.is_whitespace() && !"()\";".contains(*next)) {
+                    end = j + next.len_utf8();
+                }
+                Some((i, end))
+            }
+        };
+        if let (Some(span), Some((_, arguments))) = (span, forms.last_mut()) {
+            arguments.push(span);
+        }
+    }
+
+    let start = open_string? + 1;
+    let (name, arguments) = forms.last()?.1.split_first()?;
+    let line_end = text[offset..].find(['"', '\n']).map_or(text.len(), |end| offset + end);
+    Some(StringArgument {
+        form: &text[name.0..name.1],
+        index: arguments.len(),
+        previous: arguments.iter().map(|(start, end)| text[*start..*end].trim_matches('"')).collect(),
+        contents: (start, line_end),
+    })
+}
+
+/// Stored entity IDs and names, or CBU IDs, replacing the argument's contents
+/// The stored data constraints check against; what didn't load is left out
+/// so the checks that need it are skipped rather than failing every member
+fn constraint_context(data: &ReferenceData) -> ConstraintContext {
+    ConstraintContext {
+        entity_leis: (!data.entities.is_empty())
+            .then(|| data.entities.iter().map(|entity| (entity.entity_id.clone(), entity.lei_code.clone())).collect()),
+        role_codes: (!data.role_codes.is_empty()).then(|| data.role_codes.iter().cloned().collect()),
+    }
+}
+
+fn reference_completions(text: &str, argument: &StringArgument, data: &ReferenceData) -> Vec<CompletionItem> {
+    let range = Range { start: position_at(text, argument.contents.0), end: position_at(text, argument.contents.1) };
+    let item = |label: &str, detail: String, filter: String, sort: String| CompletionItem {
+        label: label.to_string(),
+        kind: Some(CompletionItemKind::VALUE),
+        detail: Some(detail),
+        filter_text: Some(filter),
+        sort_text: Some(sort),
+        text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text: label.to_string() })),
+        ..Default::default()
+    };
+    let lei = |lei: &Option<String>| lei.as_ref().map_or(String::new(), |lei| format!(" (LEI {})", lei));
+
+    match (argument.form, argument.index) {
+        ("entity" | "add-member" | "remove-member" | "change-role", 0) => data
+            .entities
+            .iter()
+            .map(|entity| {
+                let detail = format!("{}{}", entity.entity_name, lei(&entity.lei_code));
+                let filter = format!("{} {} {}", entity.entity_id, entity.entity_name, entity.lei_code.as_deref().unwrap_or_default());
+                item(&entity.entity_id, detail, filter, entity.entity_name.clone())
+            })
+            .collect(),
+        // The name of the entity whose ID came first is offered first
+        ("entity" | "add-member", _) => data
+            .entities
+            .iter()
+            .map(|entity| {
+                let rank = if argument.previous.first() == Some(&entity.entity_id.as_str()) { "0" } else { "1" };
+                let detail = format!("{}{}", entity.entity_id, lei(&entity.lei_code));
+                item(&entity.entity_name, detail, entity.entity_name.clone(), format!("{}{}", rank, entity.entity_name))
+            })
+            .collect(),
+        _ => data
+            .cbus
+            .iter()
+            .map(|cbu| {
+                let detail = format!("{} ({})", cbu.cbu_name, cbu.status);
+                item(&cbu.cbu_id, detail, format!("{} {}", cbu.cbu_id, cbu.cbu_name), cbu.cbu_name.clone())
+            })
+            .collect(),
+    }
+}
+
+/// Byte offset of an LSP position, clamped to its line
+fn offset_at(text: &str, position: Position) -> usize {
+    let line_start: usize = text.split_inclusive('\n').take(position.line as usize).map(str::len).sum();
+    let line = text[line_start..].split('\n').next().unwrap_or_default();
+    let mut offset = line_start + (position.character as usize).min(line.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+fn position_at(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: (offset - line_start) as u32,
+    }
 }
 
 /// Create and configure the LSP service
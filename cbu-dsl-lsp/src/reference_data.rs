@@ -0,0 +1,98 @@
+//! Entity and CBU IDs from the database, offered as completions, and the
+//! LEIs and role codes scripts are checked against
+//!
+//! Loaded by the first completion that needs them and kept for
+//! `REFRESH_AFTER`; the refresh command reloads them sooner. A load that
+//! fails is kept too, empty, so an unreachable database isn't tried again on
+//! every keystroke and completion carries on with the static items. An
+//! editor token the database refuses counts as a failed load.
+
+use data_designer_core::db::{CbuReference, DbOperations, EditorDatabase, EntityReference};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::warn;
+
+/// How long loaded IDs are offered before they are read again
+pub const REFRESH_AFTER: Duration = Duration::from_secs(300);
+
+/// How long a completion waits for the database before going without
+const LOAD_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Default)]
+pub struct ReferenceData {
+    pub entities: Vec<EntityReference>,
+    pub cbus: Vec<CbuReference>,
+    /// Active `cbu_roles` codes
+    pub role_codes: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ReferenceCache {
+    database: RwLock<Arc<EditorDatabase>>,
+    loaded: RwLock<Option<(Arc<ReferenceData>, Instant)>>,
+}
+
+impl ReferenceCache {
+    pub fn new(database: EditorDatabase) -> Self {
+        Self { database: RwLock::new(Arc::new(database)), loaded: RwLock::new(None) }
+    }
+
+    /// Switches to another database, forgetting what was loaded from the old one
+    pub async fn connect(&self, database: EditorDatabase) {
+        *self.database.write().await = Arc::new(database);
+        *self.loaded.write().await = None;
+    }
+
+    /// The loaded IDs, read again once older than `REFRESH_AFTER`
+    pub async fn get(&self) -> Arc<ReferenceData> {
+        if let Some((data, at)) = &*self.loaded.read().await {
+            if at.elapsed() < REFRESH_AFTER {
+                return data.clone();
+            }
+        }
+
+        let mut loaded = self.loaded.write().await;
+        // Another completion may have loaded them while this one waited
+        if let Some((data, at)) = &*loaded {
+            if at.elapsed() < REFRESH_AFTER {
+                return data.clone();
+            }
+        }
+        let data = match tokio::time::timeout(LOAD_TIMEOUT, self.load()).await {
+            Ok(Ok(data)) => Arc::new(data),
+            Ok(Err(e)) => {
+                warn!("Completing without stored entities and CBUs: {}", e);
+                Arc::default()
+            }
+            Err(_) => {
+                warn!("Completing without stored entities and CBUs: the database didn't answer in {:?}", LOAD_TIMEOUT);
+                Arc::default()
+            }
+        };
+        *loaded = Some((data.clone(), Instant::now()));
+        data
+    }
+
+    /// Reads the IDs again now, keeping the old ones if that fails
+    pub async fn refresh(&self) -> Result<Arc<ReferenceData>, String> {
+        let data = Arc::new(self.load().await?);
+        *self.loaded.write().await = Some((data.clone(), Instant::now()));
+        Ok(data)
+    }
+
+    /// The database, to check what the editor's token grants
+    pub async fn database(&self) -> Arc<EditorDatabase> {
+        self.database.read().await.clone()
+    }
+
+    async fn load(&self) -> Result<ReferenceData, String> {
+        let database = self.database().await;
+        let (pool, _) = database.session().await?;
+        Ok(ReferenceData {
+            entities: DbOperations::list_entity_references(pool).await?,
+            cbus: DbOperations::list_cbu_references(pool).await?,
+            role_codes: DbOperations::list_active_role_codes(pool).await?,
+        })
+    }
+}
@@ -0,0 +1,195 @@
+//! File checks behind `data-designer-cli check` and `watch`.
+//!
+//! `.rules` files go through the DSL transpiler (parse + validation), `.cbu`
+//! files through the LISP CBU parser. Each file produces a `FileReport` with
+//! a flat list of diagnostics that the CLI prints.
+
+use anyhow::{Context, Result};
+use data_designer_core::lisp_cbu_dsl::LispCbuParser;
+use data_designer_core::transpiler::{DslTranspiler, ErrorType};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Rules,
+    Cbu,
+}
+
+impl FileKind {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("rules") => Some(FileKind::Rules),
+            Some("cbu") => Some(FileKind::Cbu),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub kind: FileKind,
+    pub rule_count: usize,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl FileReport {
+    pub fn error_count(&self) -> usize {
+        self.diagnostics.iter().filter(|d| d.severity == Severity::Error).count()
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.diagnostics.iter().filter(|d| d.severity == Severity::Warning).count()
+    }
+}
+
+/// Read and check a single `.rules` or `.cbu` file
+pub fn check_file(path: &Path) -> Result<FileReport> {
+    let kind = FileKind::from_path(path)
+        .with_context(|| format!("Unsupported file type: {}", path.display()))?;
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    let (rule_count, diagnostics) = check_source(kind, &text);
+
+    Ok(FileReport {
+        path: path.to_path_buf(),
+        kind,
+        rule_count,
+        diagnostics,
+    })
+}
+
+/// Check source text, returning the number of rules/forms found and any diagnostics
+pub fn check_source(kind: FileKind, text: &str) -> (usize, Vec<Diagnostic>) {
+    match kind {
+        FileKind::Rules => check_rules(text),
+        FileKind::Cbu => check_cbu(text),
+    }
+}
+
+fn check_rules(text: &str) -> (usize, Vec<Diagnostic>) {
+    if text.trim().is_empty() {
+        return (0, Vec::new());
+    }
+
+    match DslTranspiler::new().transpile_dsl_to_rules(text) {
+        Ok(rules) => (rules.len(), Vec::new()),
+        Err(errors) => {
+            let diagnostics = errors
+                .into_iter()
+                .map(|error| Diagnostic {
+                    // Undefined dependencies usually refer to dictionary attributes,
+                    // so they are reported but do not fail the file.
+                    severity: match error.error_type {
+                        ErrorType::SemanticError => Severity::Warning,
+                        _ => Severity::Error,
+                    },
+                    line: error.line,
+                    message: match &error.rule_name {
+                        Some(rule) => format!("{} (rule '{}')", error.message, rule),
+                        None => error.message,
+                    },
+                })
+                .collect();
+            (0, diagnostics)
+        }
+    }
+}
+
+fn check_cbu(text: &str) -> (usize, Vec<Diagnostic>) {
+    if text.trim().is_empty() {
+        return (0, Vec::new());
+    }
+
+    let mut parser = LispCbuParser::new(None);
+    match parser.parse_and_eval(text) {
+        Ok(result) if result.success => (1, Vec::new()),
+        Ok(result) => {
+            let mut diagnostics: Vec<Diagnostic> = result
+                .errors
+                .into_iter()
+                .map(|message| Diagnostic { severity: Severity::Error, line: None, message })
+                .collect();
+            if diagnostics.is_empty() {
+                diagnostics.push(Diagnostic { severity: Severity::Error, line: None, message: result.message });
+            }
+            (0, diagnostics)
+        }
+        Err(error) => (
+            0,
+            vec![Diagnostic {
+                severity: Severity::Error,
+                line: None,
+                message: error.to_string(),
+            }],
+        ),
+    }
+}
+
+/// Recursively collect all `.rules` and `.cbu` files under `dir`, sorted by path
+pub fn collect_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    collect_files_into(dir, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn collect_files_into(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read directory {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files_into(&path, files)?;
+        } else if FileKind::from_path(&path).is_some() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_kind_from_extension() {
+        assert_eq!(FileKind::from_path(Path::new("kyc/risk.rules")), Some(FileKind::Rules));
+        assert_eq!(FileKind::from_path(Path::new("funds/alpha.cbu")), Some(FileKind::Cbu));
+        assert_eq!(FileKind::from_path(Path::new("README.md")), None);
+    }
+
+    #[test]
+    fn test_valid_rules_have_no_diagnostics() {
+        let (count, diagnostics) = check_source(FileKind::Rules, "total = 100 + 25\nfee = total * 2\n");
+        assert_eq!(count, 2);
+        assert!(diagnostics.is_empty(), "unexpected diagnostics: {:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_undefined_dependency_is_a_warning() {
+        let (_, diagnostics) = check_source(FileKind::Rules, "fee = balance * 2\n");
+        assert!(!diagnostics.is_empty());
+        assert!(diagnostics.iter().all(|d| d.severity == Severity::Warning));
+    }
+
+    #[test]
+    fn test_invalid_cbu_is_an_error() {
+        let (_, diagnostics) = check_source(FileKind::Cbu, "(create-cbu \"Alpha Fund\"");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+}
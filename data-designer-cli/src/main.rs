@@ -0,0 +1,203 @@
+mod checks;
+
+use anyhow::{bail, Result};
+use checks::{FileReport, Severity};
+use clap::{Parser, Subcommand};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(name = "data-designer-cli", about = "Command line tools for Data Designer rule files")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Parse and lint all .rules and .cbu files under a directory once
+    Check {
+        dir: PathBuf,
+    },
+    /// Re-check .rules and .cbu files whenever they change
+    Watch {
+        dir: PathBuf,
+        /// Quiet period before re-checking a burst of file events
+        #[arg(long, default_value_t = 200)]
+        debounce_ms: u64,
+    },
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let style = Style::detect();
+
+    match cli.command {
+        Command::Check { dir } => {
+            let reports = check_dir(&dir, &style)?;
+            if reports.iter().any(|r| r.error_count() > 0) {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Watch { dir, debounce_ms } => watch(&dir, Duration::from_millis(debounce_ms), &style),
+    }
+}
+
+fn check_dir(dir: &Path, style: &Style) -> Result<Vec<FileReport>> {
+    if !dir.is_dir() {
+        bail!("Not a directory: {}", dir.display());
+    }
+
+    let mut reports = Vec::new();
+    for path in checks::collect_files(dir)? {
+        let report = checks::check_file(&path)?;
+        print_report(&report, style);
+        reports.push(report);
+    }
+    print_summary(&reports, style);
+    Ok(reports)
+}
+
+fn watch(dir: &Path, debounce: Duration, style: &Style) -> Result<()> {
+    check_dir(dir, style)?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(dir, RecursiveMode::Recursive)?;
+    println!("{}", style.dim(&format!("Watching {} for changes (Ctrl-C to stop)", dir.display())));
+
+    loop {
+        let mut changed = BTreeSet::new();
+        collect_changed(rx.recv()?, &mut changed);
+
+        // Editors often emit several events per save; wait for the burst to settle.
+        while let Ok(event) = rx.recv_timeout(debounce) {
+            collect_changed(event, &mut changed);
+        }
+
+        if changed.is_empty() {
+            continue;
+        }
+
+        let mut reports = Vec::new();
+        for path in changed {
+            if !path.exists() {
+                println!("{} {}", style.dim("removed"), path.display());
+                continue;
+            }
+            match checks::check_file(&path) {
+                Ok(report) => {
+                    print_report(&report, style);
+                    reports.push(report);
+                }
+                Err(e) => println!("{} {}: {:#}", style.red("error"), path.display(), e),
+            }
+        }
+        print_summary(&reports, style);
+    }
+}
+
+fn collect_changed(event: notify::Result<notify::Event>, changed: &mut BTreeSet<PathBuf>) {
+    let event = match event {
+        Ok(event) => event,
+        Err(e) => {
+            eprintln!("watch error: {}", e);
+            return;
+        }
+    };
+
+    if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) {
+        return;
+    }
+
+    changed.extend(
+        event
+            .paths
+            .into_iter()
+            .filter(|path| checks::FileKind::from_path(path).is_some()),
+    );
+}
+
+fn print_report(report: &FileReport, style: &Style) {
+    if report.diagnostics.is_empty() {
+        let unit = match report.kind {
+            checks::FileKind::Rules => "rules",
+            checks::FileKind::Cbu => "forms",
+        };
+        println!("{} {} ({} {})", style.green("ok"), report.path.display(), report.rule_count, unit);
+        return;
+    }
+
+    for diagnostic in &report.diagnostics {
+        let label = match diagnostic.severity {
+            Severity::Error => style.red("error"),
+            Severity::Warning => style.yellow("warning"),
+        };
+        let location = match diagnostic.line {
+            Some(line) => format!("{}:{}", report.path.display(), line),
+            None => report.path.display().to_string(),
+        };
+        println!("{} {}: {}", label, location, diagnostic.message);
+    }
+}
+
+fn print_summary(reports: &[FileReport], style: &Style) {
+    let errors: usize = reports.iter().map(FileReport::error_count).sum();
+    let warnings: usize = reports.iter().map(FileReport::warning_count).sum();
+    let summary = format!(
+        "{} files checked, {} errors, {} warnings",
+        reports.len(),
+        errors,
+        warnings
+    );
+
+    if errors > 0 {
+        println!("{}", style.red(&summary));
+    } else if warnings > 0 {
+        println!("{}", style.yellow(&summary));
+    } else {
+        println!("{}", style.green(&summary));
+    }
+}
+
+/// ANSI colouring, disabled when stdout is not a terminal or NO_COLOR is set
+struct Style {
+    enabled: bool,
+}
+
+impl Style {
+    fn detect() -> Self {
+        Self {
+            enabled: std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+        }
+    }
+
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.enabled {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn red(&self, text: &str) -> String {
+        self.paint("1;31", text)
+    }
+
+    fn yellow(&self, text: &str) -> String {
+        self.paint("1;33", text)
+    }
+
+    fn green(&self, text: &str) -> String {
+        self.paint("32", text)
+    }
+
+    fn dim(&self, text: &str) -> String {
+        self.paint("2", text)
+    }
+}
@@ -0,0 +1,104 @@
+//! Times writing evaluation results to `dsl_execution_logs` one INSERT per
+//! row (the old path) versus through a `BatchWriter`, which buffers rows and
+//! writes each chunk with one UNNEST insert. Needs the database from
+//! config.toml; the rows it writes are deleted afterwards.
+//!
+//!     cargo run --release -p data-designer-core --example batch_insert_bench
+
+use chrono::Utc;
+use data_designer_core::batch_writer::EvaluationResultRecord;
+use data_designer_core::config::{BatchWriteConfig, Config};
+use data_designer_core::db::{init_db, BatchWriter, DbPool};
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+const RECORDS: usize = 20_000;
+const EXECUTED_BY: &str = "batch_insert_bench";
+
+fn records() -> Vec<EvaluationResultRecord> {
+    (0..RECORDS)
+        .map(|i| EvaluationResultRecord {
+            instance_id: Some(format!("BENCH_{:06}", i)),
+            execution_type: "rule_evaluation".to_string(),
+            dsl_script: "risk_score * 1.5 + (IF is_pep THEN 40 ELSE 0)".to_string(),
+            execution_status: if i % 50 == 0 { "error" } else { "success" }.to_string(),
+            input_data: Some(json!({"risk_score": i % 100, "is_pep": i % 7 == 0})),
+            output_data: Some(json!({"score": (i % 100) as f64 * 1.5})),
+            log_messages: vec![format!("Evaluated for BENCH_{:06}", i)],
+            error_details: (i % 50 == 0).then(|| "Division by zero".to_string()),
+            execution_time_ms: (i % 13) as i32,
+            executed_by: Some(EXECUTED_BY.to_string()),
+            executed_at: Utc::now(),
+        })
+        .collect()
+}
+
+// The old path: one INSERT and one round trip per row
+async fn per_row(pool: &DbPool, records: &[EvaluationResultRecord]) -> Duration {
+    let started = Instant::now();
+    for record in records {
+        sqlx::query("
+            INSERT INTO dsl_execution_logs (
+                instance_id, execution_type, dsl_script, execution_status, input_data, output_data,
+                log_messages, error_details, execution_time_ms, executed_by, executed_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        ")
+            .bind(&record.instance_id)
+            .bind(&record.execution_type)
+            .bind(&record.dsl_script)
+            .bind(&record.execution_status)
+            .bind(&record.input_data)
+            .bind(&record.output_data)
+            .bind(json!(record.log_messages))
+            .bind(&record.error_details)
+            .bind(record.execution_time_ms)
+            .bind(&record.executed_by)
+            .bind(record.executed_at)
+            .execute(pool)
+            .await
+            .expect("per-row insert");
+    }
+    started.elapsed()
+}
+
+async fn batched(pool: &DbPool, records: Vec<EvaluationResultRecord>, config: &BatchWriteConfig) -> Duration {
+    let started = Instant::now();
+    let (writer, handle) = BatchWriter::spawn(pool.clone(), config).expect("valid batch config");
+    for record in records {
+        writer.write(record).await.expect("batch write");
+    }
+    drop(writer);
+    let stats = handle.await.expect("writer task");
+    assert_eq!((stats.records_written as usize, stats.records_failed), (RECORDS, 0));
+    started.elapsed()
+}
+
+async fn cleanup(pool: &DbPool) {
+    sqlx::query("DELETE FROM dsl_execution_logs WHERE executed_by = $1")
+        .bind(EXECUTED_BY)
+        .execute(pool)
+        .await
+        .expect("cleanup");
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let pool = init_db().await?;
+    cleanup(&pool).await;
+
+    let before = per_row(&pool, &records()).await;
+    cleanup(&pool).await;
+    let after = batched(&pool, records(), &config.batch_writes).await;
+    cleanup(&pool).await;
+
+    println!(
+        "{} evaluation results, chunks of {}, flushed every {} ms",
+        RECORDS, config.batch_writes.chunk_size, config.batch_writes.flush_interval_ms
+    );
+    println!("INSERT per row: {:>10.2?} ({:.0} rows/s)", before, RECORDS as f64 / before.as_secs_f64());
+    println!("BatchWriter:    {:>10.2?} ({:.0} rows/s)", after, RECORDS as f64 / after.as_secs_f64());
+    println!("speedup: {:.1}x", before.as_secs_f64() / after.as_secs_f64());
+    Ok(())
+}
@@ -0,0 +1,88 @@
+//! Times MATCHES-heavy rules over many fact sets, compiling each pattern per
+//! evaluation (the old behaviour) versus looking it up in `regex_cache`.
+//!
+//!     cargo run --release -p data-designer-core --example regex_cache_bench
+
+use data_designer_core::evaluator::{evaluate, Facts};
+use data_designer_core::models::Value;
+use data_designer_core::parser::parse_expression;
+use regex::Regex;
+use std::time::{Duration, Instant};
+
+const CONTEXTS: usize = 2_000;
+
+const RULES: &[(&str, &str)] = &[
+    ("client_id", r#"client_id MATCHES /^INST_\d{4}_\d{5}$/"#),
+    ("tax_id", r#"tax_id MATCHES /^\d{2}-\d{7}$/"#),
+    ("giin", r#"giin MATCHES /^[A-Z0-9]{6}\.\d{5}\.[A-Z]{2}\.\d{3}$/"#),
+    ("email", r#"email MATCHES /^[\w.+-]+@[\w-]+\.[\w.-]+$/"#),
+];
+
+fn contexts() -> Vec<Facts> {
+    (0..CONTEXTS)
+        .map(|i| {
+            let mut facts = Facts::new();
+            facts.insert("client_id".to_string(), Value::String(format!("INST_2024_{:05}", i)));
+            facts.insert("tax_id".to_string(), Value::String(format!("12-{:07}", i)));
+            facts.insert("giin".to_string(), Value::String(format!("98Q96B.{:05}.LE.250", i % 100_000)));
+            facts.insert("email".to_string(), Value::String(format!("client{}@example.com", i)));
+            facts
+        })
+        .collect()
+}
+
+// The evaluator before the cache: one Regex::new per MATCHES evaluation
+fn uncached(contexts: &[Facts], patterns: &[(&str, String)]) -> (Duration, usize) {
+    let started = Instant::now();
+    let mut matched = 0;
+    for facts in contexts {
+        for (field, pattern) in patterns {
+            let Some(Value::String(text)) = facts.get(*field) else { continue };
+            if Regex::new(pattern).unwrap().is_match(text) {
+                matched += 1;
+            }
+        }
+    }
+    (started.elapsed(), matched)
+}
+
+fn cached(contexts: &[Facts]) -> (Duration, usize) {
+    let rules: Vec<_> = RULES
+        .iter()
+        .map(|(_, rule)| match parse_expression(rule) {
+            Ok(("", ast)) => ast,
+            other => panic!("Benchmark rule did not parse: {:?}", other),
+        })
+        .collect();
+    let started = Instant::now();
+    let mut matched = 0;
+    for facts in contexts {
+        for rule in &rules {
+            if evaluate(rule, facts).unwrap() == Value::Boolean(true) {
+                matched += 1;
+            }
+        }
+    }
+    (started.elapsed(), matched)
+}
+
+fn main() {
+    let contexts = contexts();
+    let patterns: Vec<_> = RULES
+        .iter()
+        .map(|(field, rule)| {
+            let pattern = &rule[rule.find('/').unwrap() + 1..rule.rfind('/').unwrap()];
+            (*field, pattern.to_string())
+        })
+        .collect();
+
+    let (before, before_matches) = uncached(&contexts, &patterns);
+    let (after, after_matches) = cached(&contexts);
+    assert_eq!(before_matches, after_matches);
+
+    let evaluations = contexts.len() * RULES.len();
+    println!("{} contexts x {} MATCHES rules = {} evaluations", contexts.len(), RULES.len(), evaluations);
+    println!("Regex::new per evaluation: {:>10.2?} ({:.2} us/eval)", before, before.as_secs_f64() * 1e6 / evaluations as f64);
+    println!("regex_cache:               {:>10.2?} ({:.2} us/eval)", after, after.as_secs_f64() * 1e6 / evaluations as f64);
+    println!("speedup: {:.1}x", before.as_secs_f64() / after.as_secs_f64());
+}
@@ -0,0 +1,86 @@
+//! Times evaluating rules by walking their AST (what `RulesEngine` does)
+//! versus running them as precompiled scripts in the Rhai sandbox, over the
+//! same fact sets, and checks both give the same answers.
+//!
+//!     cargo run --release -p data-designer-core --example rhai_vs_ast_bench
+
+use data_designer_core::evaluator::{evaluate_with_functions, Facts, FunctionLibrary};
+use data_designer_core::models::{Expression, Value};
+use data_designer_core::parser::parse_rule;
+use data_designer_core::rhai_runtime::{CompiledScript, RhaiSandbox};
+use std::time::{Duration, Instant};
+
+const CONTEXTS: usize = 20_000;
+
+const RULES: &[&str] = &[
+    "fee = IF volume > 100000 THEN volume * 0.0005 ELSE volume * 0.001 + 25",
+    r#"tier = CASE WHEN aum >= 1000000000 THEN "institutional" WHEN aum >= 10000000 THEN "professional" ELSE "retail" END"#,
+    r#"eligible = jurisdiction IN ["GB", "IE", "LU"] AND risk_score BETWEEN 0 AND 60 AND NOT is_pep"#,
+    "utilisation = drawn / limit",
+];
+
+fn contexts() -> Vec<Facts> {
+    let jurisdictions = ["GB", "IE", "LU", "US", "SG"];
+    (0..CONTEXTS)
+        .map(|i| {
+            let mut facts = Facts::new();
+            facts.insert("volume".to_string(), Value::Integer((i as i64 * 7_919) % 500_000));
+            facts.insert("aum".to_string(), Value::Integer((i as i64 * 104_729) % 2_000_000_000));
+            facts.insert("jurisdiction".to_string(), Value::String(jurisdictions[i % jurisdictions.len()].to_string()));
+            facts.insert("risk_score".to_string(), Value::Integer((i % 100) as i64));
+            facts.insert("is_pep".to_string(), Value::Boolean(i % 11 == 0));
+            facts.insert("drawn".to_string(), Value::Float((i % 1_000) as f64 * 1_250.0));
+            facts.insert("limit".to_string(), Value::Integer(2_000_000));
+            facts
+        })
+        .collect()
+}
+
+fn ast_walk(rules: &[Expression], contexts: &[Facts]) -> (Duration, Vec<Value>) {
+    let functions = FunctionLibrary::new();
+    let started = Instant::now();
+    let mut results = Vec::with_capacity(contexts.len() * rules.len());
+    for facts in contexts {
+        for rule in rules {
+            results.push(evaluate_with_functions(rule, facts, &functions).unwrap());
+        }
+    }
+    (started.elapsed(), results)
+}
+
+fn rhai(sandbox: &RhaiSandbox, scripts: &[CompiledScript], contexts: &[Facts]) -> (Duration, Vec<Value>) {
+    let started = Instant::now();
+    let mut results = Vec::with_capacity(contexts.len() * scripts.len());
+    for facts in contexts {
+        for script in scripts {
+            results.push(sandbox.execute_compiled(script, facts).unwrap());
+        }
+    }
+    (started.elapsed(), results)
+}
+
+fn main() {
+    let contexts = contexts();
+    let rules: Vec<_> = RULES
+        .iter()
+        .map(|rule| match parse_rule(rule) {
+            Ok((remaining, ast)) if remaining.trim().is_empty() => ast,
+            other => panic!("Benchmark rule did not parse: {:?}", other),
+        })
+        .collect();
+
+    let sandbox = RhaiSandbox::default();
+    let compile_started = Instant::now();
+    let scripts: Vec<_> = rules.iter().map(|rule| sandbox.compile_rule(rule).unwrap()).collect();
+    let compile_time = compile_started.elapsed();
+
+    let (walked, walked_results) = ast_walk(&rules, &contexts);
+    let (scripted, scripted_results) = rhai(&sandbox, &scripts, &contexts);
+    assert_eq!(walked_results, scripted_results);
+
+    let evaluations = contexts.len() * RULES.len();
+    println!("{} contexts x {} rules = {} evaluations", contexts.len(), RULES.len(), evaluations);
+    println!("AST walk:      {:>10.2?} ({:.2} us/eval)", walked, walked.as_secs_f64() * 1e6 / evaluations as f64);
+    println!("Rhai compiled: {:>10.2?} ({:.2} us/eval, plus {:.2?} to compile)", scripted, scripted.as_secs_f64() * 1e6 / evaluations as f64, compile_time);
+    println!("Rhai / AST walk: {:.2}x", scripted.as_secs_f64() / walked.as_secs_f64());
+}
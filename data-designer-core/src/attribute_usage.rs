@@ -0,0 +1,131 @@
+//! Attribute usage across rules, onboarding plans and resource UIs
+//!
+//! Each source reports the attribute names it references; the tally matches
+//! them against the data dictionary and counts, per attribute, how many
+//! distinct rules, plans and UI resources use it. The result feeds the usage
+//! heatmap, so data stewards can document the most used attributes first.
+//! Attributes nothing references are kept with zero counts.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageSource {
+    Rule,
+    Plan,
+    Ui,
+}
+
+/// A data dictionary attribute references are matched against
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DictionaryAttribute {
+    pub full_path: String,
+    pub attribute_name: String,
+    pub entity_name: String,
+    pub attribute_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttributeUsage {
+    pub full_path: String,
+    pub entity_name: String,
+    pub attribute_type: String,
+    pub rule_count: usize,
+    pub plan_count: usize,
+    pub ui_count: usize,
+    pub total: usize,
+}
+
+/// Collects references by source and the id of the rule, plan or resource making them
+#[derive(Debug, Default)]
+pub struct UsageTally {
+    references: BTreeMap<String, BTreeSet<(UsageSource, String)>>,
+}
+
+impl UsageTally {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that `referrer` (a rule id, plan id or resource name) uses `attribute`,
+    /// given as a full path or a bare attribute name. Repeats count once.
+    pub fn record(&mut self, source: UsageSource, referrer: &str, attribute: &str) {
+        self.references
+            .entry(attribute.trim().to_string())
+            .or_default()
+            .insert((source, referrer.to_string()));
+    }
+
+    /// Usage of every dictionary attribute, most used first. A bare name
+    /// counts towards each attribute of that name, whatever its entity.
+    pub fn usage(&self, dictionary: &[DictionaryAttribute]) -> Vec<AttributeUsage> {
+        let mut usage: Vec<AttributeUsage> = dictionary
+            .iter()
+            .map(|attribute| {
+                let referrers: BTreeSet<&(UsageSource, String)> = [&attribute.full_path, &attribute.attribute_name]
+                    .into_iter()
+                    .filter_map(|name| self.references.get(name.as_str()))
+                    .flatten()
+                    .collect();
+                let count = |source: UsageSource| referrers.iter().filter(|(s, _)| *s == source).count();
+                let (rule_count, plan_count, ui_count) =
+                    (count(UsageSource::Rule), count(UsageSource::Plan), count(UsageSource::Ui));
+                AttributeUsage {
+                    full_path: attribute.full_path.clone(),
+                    entity_name: attribute.entity_name.clone(),
+                    attribute_type: attribute.attribute_type.clone(),
+                    rule_count,
+                    plan_count,
+                    ui_count,
+                    total: rule_count + plan_count + ui_count,
+                }
+            })
+            .collect();
+
+        usage.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.full_path.cmp(&b.full_path)));
+        usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attribute(entity_name: &str, attribute_name: &str) -> DictionaryAttribute {
+        DictionaryAttribute {
+            full_path: format!("{}.{}", entity_name, attribute_name),
+            attribute_name: attribute_name.to_string(),
+            entity_name: entity_name.to_string(),
+            attribute_type: "business".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_usage_counts_distinct_referrers_per_source() {
+        let dictionary = vec![
+            attribute("Client", "legal_entity_name"),
+            attribute("Client", "risk_rating"),
+            attribute("Client", "tax_id"),
+        ];
+
+        let mut tally = UsageTally::new();
+        tally.record(UsageSource::Rule, "r1", "Client.risk_rating");
+        tally.record(UsageSource::Rule, "r1", "risk_rating");
+        tally.record(UsageSource::Rule, "r2", "risk_rating");
+        tally.record(UsageSource::Plan, "7", "risk_rating");
+        tally.record(UsageSource::Ui, "kyc/ClientForm", "legal_entity_name");
+        tally.record(UsageSource::Rule, "r3", "unknown_attribute");
+
+        let usage = tally.usage(&dictionary);
+        let summary: Vec<_> = usage
+            .iter()
+            .map(|u| (u.full_path.as_str(), u.rule_count, u.plan_count, u.ui_count, u.total))
+            .collect();
+        assert_eq!(summary, vec![
+            ("Client.risk_rating", 2, 1, 0, 3),
+            ("Client.legal_entity_name", 0, 0, 1, 1),
+            ("Client.tax_id", 0, 0, 0, 0),
+        ]);
+    }
+}
@@ -0,0 +1,545 @@
+//! Derived attribute workbooks for business sign-off
+//!
+//! `write_workbook` lays the derived attributes out as an XLSX sheet, one row
+//! per rule: the attribute, its type and category, the formula, the
+//! attributes the formula reads and how its test cases fare. Reviewers edit
+//! the Formula and Description columns; `read_workbook` reads those back,
+//! whether the file was saved by us, Excel or LibreOffice, and
+//! `WorkbookReview` compares them with the current rules. Every edited
+//! formula must parse, still assign its row's attribute, respect its
+//! category's policy and type-check. The other columns are for reading only,
+//! and rows deleted from the sheet leave their rules as they are.
+
+use crate::models::Expression;
+use crate::rule_categories::CategoryTree;
+use crate::rule_graph::extract_dependencies_from_ast;
+use crate::rule_rewrite::parse_complete;
+use crate::type_checker::{typecheck_with_env, TypeEnv};
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read, Write};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+pub const SHEET_NAME: &str = "Derived Attributes";
+
+pub const COLUMNS: [&str; 9] = [
+    "Rule ID", "Attribute", "Data Type", "Category", "Formula", "Dependencies", "Tests", "Description", "Status",
+];
+
+// Where the sheet lives in the package; Excel and LibreOffice keep the first
+// sheet here when they save
+const SHEET_PATH: &str = "xl/worksheets/sheet1.xml";
+const SHARED_STRINGS_PATH: &str = "xl/sharedStrings.xml";
+
+/// A rule as it is exported
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkbookRow {
+    pub rule_id: String,
+    pub attribute: String,
+    pub data_type: Option<String>,
+    /// Category key
+    pub category: Option<String>,
+    pub formula: String,
+    pub description: Option<String>,
+    pub status: String,
+    pub tests: usize,
+    pub tests_passed: usize,
+}
+
+impl WorkbookRow {
+    /// The attributes the formula reads; empty if it doesn't parse
+    pub fn dependencies(&self) -> Vec<String> {
+        parse_complete(&self.formula).map(|ast| extract_dependencies_from_ast(&ast)).unwrap_or_default()
+    }
+
+    fn tests_summary(&self) -> String {
+        match self.tests {
+            0 => "none".to_string(),
+            tests => format!("{} of {} passing", self.tests_passed, tests),
+        }
+    }
+}
+
+/// The editable columns of a row read back from a reviewed workbook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewedRow {
+    /// Row number in the sheet, as the spreadsheet shows it
+    pub row: usize,
+    pub rule_id: String,
+    pub formula: String,
+    pub description: Option<String>,
+}
+
+/// The sheet as an XLSX file
+pub fn write_workbook(rows: &[WorkbookRow]) -> Result<Vec<u8>, String> {
+    let mut sheet = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+        r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">"#,
+        r#"<sheetViews><sheetView workbookViewId="0"><pane ySplit="1" topLeftCell="A2" activePane="bottomLeft" state="frozen"/></sheetView></sheetViews>"#,
+        r#"<cols><col min="1" max="4" width="18" customWidth="1"/><col min="5" max="5" width="60" customWidth="1"/>"#,
+        r#"<col min="6" max="6" width="30" customWidth="1"/><col min="7" max="7" width="16" customWidth="1"/>"#,
+        r#"<col min="8" max="8" width="40" customWidth="1"/><col min="9" max="9" width="16" customWidth="1"/></cols>"#,
+        "<sheetData>",
+    ));
+
+    sheet.push_str(r#"<row r="1">"#);
+    for (column, header) in COLUMNS.iter().enumerate() {
+        push_string_cell(&mut sheet, column, 1, header, 1);
+    }
+    sheet.push_str("</row>");
+
+    for (index, row) in rows.iter().enumerate() {
+        let number = index + 2;
+        let cells = [
+            row.rule_id.clone(),
+            row.attribute.clone(),
+            row.data_type.clone().unwrap_or_default(),
+            row.category.clone().unwrap_or_default(),
+            row.formula.clone(),
+            row.dependencies().join(", "),
+            row.tests_summary(),
+            row.description.clone().unwrap_or_default(),
+            row.status.clone(),
+        ];
+        sheet.push_str(&format!(r#"<row r="{}">"#, number));
+        for (column, value) in cells.iter().enumerate() {
+            // Formulas and descriptions wrap; the rest stay on one line
+            let style = if matches!(column, 4 | 7) { 2 } else { 0 };
+            push_string_cell(&mut sheet, column, number, value, style);
+        }
+        sheet.push_str("</row>");
+    }
+    sheet.push_str("</sheetData></worksheet>");
+
+    let parts = [
+        ("[Content_Types].xml", CONTENT_TYPES.to_string()),
+        ("_rels/.rels", ROOT_RELS.to_string()),
+        ("xl/workbook.xml", WORKBOOK.replace("{sheet}", &escape(SHEET_NAME))),
+        ("xl/_rels/workbook.xml.rels", WORKBOOK_RELS.to_string()),
+        ("xl/styles.xml", STYLES.to_string()),
+        (SHEET_PATH, sheet),
+    ];
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (path, content) in parts {
+        zip.start_file(path, options).map_err(|e| format!("Failed to write workbook: {}", e))?;
+        zip.write_all(content.as_bytes()).map_err(|e| format!("Failed to write workbook: {}", e))?;
+    }
+    let cursor = zip.finish().map_err(|e| format!("Failed to write workbook: {}", e))?;
+    Ok(cursor.into_inner())
+}
+
+/// The Rule ID, Formula and Description of every row of a reviewed
+/// workbook. Columns are found by their header, so reordering them is fine;
+/// blank rows are skipped.
+pub fn read_workbook(bytes: &[u8]) -> Result<Vec<ReviewedRow>, String> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("Not an XLSX workbook: {}", e))?;
+    let shared_strings = match read_part(&mut archive, SHARED_STRINGS_PATH)? {
+        Some(xml) => parse_shared_strings(&xml)?,
+        None => Vec::new(),
+    };
+    let sheet = read_part(&mut archive, SHEET_PATH)?.ok_or("The workbook has no worksheet")?;
+    let mut rows = parse_sheet(&sheet, &shared_strings)?.into_iter();
+
+    let (_, headers) = rows.next().ok_or("The worksheet is empty")?;
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header.trim().eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("The worksheet has no {} column", name))
+    };
+    let (rule_id, formula, description) = (column("Rule ID")?, column("Formula")?, column("Description")?);
+
+    let cell = |cells: &[String], index: usize| cells.get(index).map(|value| value.trim()).unwrap_or("").to_string();
+    Ok(rows
+        .filter(|(_, cells)| cells.iter().any(|value| !value.trim().is_empty()))
+        .map(|(row, cells)| ReviewedRow {
+            row,
+            rule_id: cell(&cells, rule_id),
+            formula: cell(&cells, formula).replace("\r\n", "\n"),
+            description: Some(cell(&cells, description)).filter(|value| !value.is_empty()),
+        })
+        .collect())
+}
+
+/// A reviewed row that differs from its rule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkbookEdit {
+    pub row: usize,
+    pub rule_id: String,
+    pub attribute: String,
+    pub formula_before: String,
+    pub formula_after: String,
+    pub description_before: Option<String>,
+    pub description_after: Option<String>,
+    /// The attributes the edited formula reads
+    pub dependencies: Vec<String>,
+    /// Why the edit can't be imported; empty when it can
+    pub errors: Vec<String>,
+}
+
+impl WorkbookEdit {
+    pub fn formula_changed(&self) -> bool {
+        self.formula_before.trim() != self.formula_after.trim()
+    }
+}
+
+/// What importing a reviewed workbook would change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkbookReview {
+    pub edits: Vec<WorkbookEdit>,
+    pub unchanged: usize,
+    /// Rows that match no exported rule, or repeat one
+    pub rejected_rows: Vec<String>,
+}
+
+impl WorkbookReview {
+    /// Compares the reviewed rows with the rules as they are now; the
+    /// categories and types are those the rules are saved under
+    pub fn new(current: &[WorkbookRow], reviewed: &[ReviewedRow], categories: &CategoryTree, types: &TypeEnv) -> Self {
+        let rules: HashMap<&str, &WorkbookRow> = current.iter().map(|rule| (rule.rule_id.as_str(), rule)).collect();
+        let mut seen = HashSet::new();
+        let mut review = Self { edits: Vec::new(), unchanged: 0, rejected_rows: Vec::new() };
+
+        for reviewed in reviewed {
+            let Some(rule) = rules.get(reviewed.rule_id.as_str()) else {
+                let reason = if reviewed.rule_id.is_empty() { "has no rule ID".to_string() } else { format!("{} is not an exported rule", reviewed.rule_id) };
+                review.rejected_rows.push(format!("Row {}: {}", reviewed.row, reason));
+                continue;
+            };
+            if !seen.insert(reviewed.rule_id.as_str()) {
+                review.rejected_rows.push(format!("Row {}: {} appears more than once", reviewed.row, reviewed.rule_id));
+                continue;
+            }
+
+            let formula_changed = reviewed.formula != rule.formula.trim();
+            let description_changed = reviewed.description.as_deref() != rule.description.as_deref().map(str::trim).filter(|d| !d.is_empty());
+            if !formula_changed && !description_changed {
+                review.unchanged += 1;
+                continue;
+            }
+
+            let (dependencies, errors) = if formula_changed {
+                check_formula(rule, &reviewed.formula, categories, types)
+            } else {
+                (rule.dependencies(), Vec::new())
+            };
+            review.edits.push(WorkbookEdit {
+                row: reviewed.row,
+                rule_id: rule.rule_id.clone(),
+                attribute: rule.attribute.clone(),
+                formula_before: rule.formula.clone(),
+                formula_after: if formula_changed { reviewed.formula.clone() } else { rule.formula.clone() },
+                description_before: rule.description.clone(),
+                description_after: reviewed.description.clone(),
+                dependencies,
+                errors,
+            });
+        }
+        review
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.rejected_rows.is_empty() && self.edits.iter().all(|edit| edit.errors.is_empty())
+    }
+
+    /// Every problem, one line each, for error messages
+    pub fn problems(&self) -> Vec<String> {
+        self.rejected_rows
+            .iter()
+            .cloned()
+            .chain(self.edits.iter().flat_map(|edit| {
+                edit.errors.iter().map(move |error| format!("Row {} ({}): {}", edit.row, edit.rule_id, error))
+            }))
+            .collect()
+    }
+}
+
+fn check_formula(rule: &WorkbookRow, formula: &str, categories: &CategoryTree, types: &TypeEnv) -> (Vec<String>, Vec<String>) {
+    let ast = match parse_complete(formula) {
+        Ok(ast) => ast,
+        Err(e) => return (Vec::new(), vec![e]),
+    };
+    let mut errors = Vec::new();
+    if let Expression::Assignment { target, .. } = &ast {
+        if *target != rule.attribute {
+            errors.push(format!("The formula assigns {}, not {}", target, rule.attribute));
+        }
+    }
+    if let Some(category) = rule.category.as_deref().and_then(|key| categories.find_by_key(key)) {
+        errors.extend(categories.check_rule(category.id, &ast).iter().map(|violation| violation.to_string()));
+    }
+    errors.extend(
+        typecheck_with_env(&ast, types)
+            .diagnostics
+            .into_iter()
+            .map(|diagnostic| format!("{} in `{}`", diagnostic.message, diagnostic.expression)),
+    );
+    (extract_dependencies_from_ast(&ast), errors)
+}
+
+fn push_string_cell(sheet: &mut String, column: usize, row: usize, value: &str, style: usize) {
+    let value: String = value.chars().filter(|c| !c.is_control() || matches!(c, '\n' | '\t')).collect();
+    sheet.push_str(&format!(
+        r#"<c r="{}{}" t="inlineStr" s="{}"><is><t xml:space="preserve">{}</t></is></c>"#,
+        column_name(column),
+        row,
+        style,
+        escape(&value)
+    ));
+}
+
+// A, B, ..., Z, AA, ...
+fn column_name(mut column: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'A' + (column % 26) as u8);
+        if column < 26 {
+            break;
+        }
+        column = column / 26 - 1;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap_or_default()
+}
+
+// The zero-based column of a cell reference such as "AB12"
+fn column_index(reference: &str) -> Option<usize> {
+    let letters: Vec<u8> = reference.bytes().take_while(u8::is_ascii_alphabetic).collect();
+    if letters.is_empty() {
+        return None;
+    }
+    Some(letters.iter().fold(0, |index, letter| index * 26 + (letter.to_ascii_uppercase() - b'A') as usize + 1) - 1)
+}
+
+fn read_part(archive: &mut ZipArchive<Cursor<&[u8]>>, path: &str) -> Result<Option<String>, String> {
+    let mut file = match archive.by_name(path) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path, e)),
+    };
+    let mut content = String::new();
+    file.read_to_string(&mut content).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    Ok(Some(content))
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Result<Option<String>, String> {
+    let attribute = element.try_get_attribute(name).map_err(|e| format!("Invalid worksheet XML: {}", e))?;
+    attribute
+        .map(|attribute| attribute.unescape_value().map(|value| value.into_owned()))
+        .transpose()
+        .map_err(|e| format!("Invalid worksheet XML: {}", e))
+}
+
+// The text of each <si>, rich text runs joined; phonetic hints are left out
+fn parse_shared_strings(xml: &str) -> Result<Vec<String>, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut strings = Vec::new();
+    let (mut current, mut in_text, mut in_phonetic) = (String::new(), false, false);
+    loop {
+        match reader.read_event().map_err(|e| format!("Invalid shared strings XML: {}", e))? {
+            Event::Start(element) => match element.local_name().as_ref() {
+                b"si" => current.clear(),
+                b"t" => in_text = true,
+                b"rPh" => in_phonetic = true,
+                _ => {}
+            },
+            Event::Text(text) if in_text && !in_phonetic => {
+                current.push_str(&text.unescape().map_err(|e| format!("Invalid shared strings XML: {}", e))?);
+            }
+            Event::End(element) => match element.local_name().as_ref() {
+                b"si" => strings.push(std::mem::take(&mut current)),
+                b"t" => in_text = false,
+                b"rPh" => in_phonetic = false,
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(strings)
+}
+
+// Each row's number and its cells' text by column
+fn parse_sheet(xml: &str, shared_strings: &[String]) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut rows: Vec<(usize, Vec<String>)> = Vec::new();
+    // The cell being read: its column, type and text so far
+    let mut cell: Option<(usize, Option<String>, String)> = None;
+    let mut in_value = false;
+
+    loop {
+        match reader.read_event().map_err(|e| format!("Invalid worksheet XML: {}", e))? {
+            Event::Start(element) | Event::Empty(element) if element.local_name().as_ref() == b"row" => {
+                let number = attribute(&element, "r")?.and_then(|r| r.parse().ok()).unwrap_or(rows.len() + 1);
+                rows.push((number, Vec::new()));
+            }
+            Event::Start(element) if element.local_name().as_ref() == b"c" => {
+                let row_cells = rows.last().map_or(0, |(_, cells)| cells.len());
+                let column = attribute(&element, "r")?.as_deref().and_then(column_index).unwrap_or(row_cells);
+                cell = Some((column, attribute(&element, "t")?, String::new()));
+            }
+            Event::Start(element) if matches!(element.local_name().as_ref(), b"v" | b"t") => in_value = true,
+            Event::Text(text) if in_value => {
+                if let Some((_, _, value)) = cell.as_mut() {
+                    value.push_str(&text.unescape().map_err(|e| format!("Invalid worksheet XML: {}", e))?);
+                }
+            }
+            Event::End(element) => match element.local_name().as_ref() {
+                b"v" | b"t" => in_value = false,
+                b"c" => {
+                    let (Some((column, kind, value)), Some((_, cells))) = (cell.take(), rows.last_mut()) else { continue };
+                    let value = match kind.as_deref() {
+                        Some("s") => value.trim().parse::<usize>().ok().and_then(|index| shared_strings.get(index)).cloned().unwrap_or_default(),
+                        _ => value,
+                    };
+                    if cells.len() <= column {
+                        cells.resize(column + 1, String::new());
+                    }
+                    cells[column] = value;
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(rows)
+}
+
+const CONTENT_TYPES: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">"#,
+    r#"<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>"#,
+    r#"<Default Extension="xml" ContentType="application/xml"/>"#,
+    r#"<Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>"#,
+    r#"<Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>"#,
+    r#"<Override PartName="/xl/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/>"#,
+    "</Types>",
+);
+
+const ROOT_RELS: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+    r#"<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/>"#,
+    "</Relationships>",
+);
+
+const WORKBOOK: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    r#"<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">"#,
+    r#"<sheets><sheet name="{sheet}" sheetId="1" r:id="rId1"/></sheets>"#,
+    "</workbook>",
+);
+
+const WORKBOOK_RELS: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+    r#"<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/>"#,
+    r#"<Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/>"#,
+    "</Relationships>",
+);
+
+// Style 0 is plain, 1 the bold header, 2 wrapped text
+const STYLES: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    r#"<styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">"#,
+    r#"<fonts count="2"><font><sz val="11"/><name val="Calibri"/></font><font><b/><sz val="11"/><name val="Calibri"/></font></fonts>"#,
+    r#"<fills count="2"><fill><patternFill patternType="none"/></fill><fill><patternFill patternType="gray125"/></fill></fills>"#,
+    r#"<borders count="1"><border><left/><right/><top/><bottom/><diagonal/></border></borders>"#,
+    r#"<cellStyleXfs count="1"><xf numFmtId="0" fontId="0" fillId="0" borderId="0"/></cellStyleXfs>"#,
+    r#"<cellXfs count="3"><xf numFmtId="0" fontId="0" fillId="0" borderId="0" xfId="0"/>"#,
+    r#"<xf numFmtId="0" fontId="1" fillId="0" borderId="0" xfId="0" applyFont="1"/>"#,
+    r#"<xf numFmtId="0" fontId="0" fillId="0" borderId="0" xfId="0" applyAlignment="1"><alignment vertical="top" wrapText="1"/></xf></cellXfs>"#,
+    "</styleSheet>",
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_categories::{CategoryPolicy, RuleCategory};
+    use crate::type_checker::RuleType;
+
+    fn row(rule_id: &str, attribute: &str, formula: &str) -> WorkbookRow {
+        WorkbookRow {
+            rule_id: rule_id.to_string(),
+            attribute: attribute.to_string(),
+            data_type: Some("number".to_string()),
+            category: Some("pricing".to_string()),
+            formula: formula.to_string(),
+            description: Some("Fee for the <client> & tier".to_string()),
+            status: "active".to_string(),
+            tests: 2,
+            tests_passed: 1,
+        }
+    }
+
+    #[test]
+    fn test_workbook_round_trips_edits_and_validates_them() {
+        let rules = vec![
+            row("FEE_1", "fee", "fee = base_fee * 2"),
+            row("RISK_1", "score", "score = IF risk > 3 THEN \"high\" ELSE \"low\""),
+            row("TAX_1", "tax", "tax = fee * 0.2"),
+        ];
+        let bytes = write_workbook(&rules).unwrap();
+        let read = read_workbook(&bytes).unwrap();
+        assert_eq!(read.len(), 3);
+        assert_eq!(read[0].row, 2);
+        assert_eq!(read[1].formula, rules[1].formula);
+        assert_eq!(read[0].description.as_deref(), Some("Fee for the <client> & tier"));
+
+        // As saved by a reviewer: shared strings, columns moved, a row added
+        let shared = r#"<sst><si><t>Formula</t></si><si><r><t>Rule </t></r><r><t>ID</t></r></si><si><t>Description</t></si>
+            <si><t>fee = base_fee * 3</t></si><si><t>FEE_1</t></si><si><t>tax = fee + "x"</t></si><si><t>TAX_1</t></si><si><t>NEW_1</t></si></sst>"#;
+        let sheet = r#"<worksheet><sheetData>
+            <row r="1"><c r="A1" t="s"><v>0</v></c><c r="B1" t="s"><v>1</v></c><c r="D1" t="s"><v>2</v></c></row>
+            <row r="2"><c r="A2" t="s"><v>3</v></c><c r="B2" t="s"><v>4</v></c></row>
+            <row r="4"><c r="A4" t="s"><v>5</v></c><c r="B4" t="s"><v>6</v></c><c r="D4" t="inlineStr"><is><t>Tax</t></is></c></row>
+            <row r="5"><c r="B5" t="s"><v>7</v></c><c r="A5"><v>42</v></c></row>
+            <row r="6"/></sheetData></worksheet>"#;
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, content) in [(SHARED_STRINGS_PATH, shared), (SHEET_PATH, sheet)] {
+            zip.start_file(path, FileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        let reviewed = read_workbook(&zip.finish().unwrap().into_inner()).unwrap();
+        assert_eq!(reviewed.iter().map(|r| r.row).collect::<Vec<_>>(), [2, 4, 5]);
+        assert_eq!(reviewed[0].rule_id, "FEE_1");
+        assert_eq!(reviewed[2].formula, "42");
+
+        let categories = CategoryTree::new(vec![RuleCategory {
+            id: 1,
+            category_key: "pricing".to_string(),
+            name: "Pricing".to_string(),
+            parent_id: None,
+            policy: CategoryPolicy::default(),
+        }]);
+        let mut types = TypeEnv::new();
+        types.insert("base_fee", RuleType::Number);
+        types.insert("fee", RuleType::Number);
+
+        let review = WorkbookReview::new(&rules, &read, &categories, &types);
+        assert!(review.edits.is_empty() && review.is_valid());
+        assert_eq!(review.unchanged, 3);
+
+        let review = WorkbookReview::new(&rules, &reviewed, &categories, &types);
+        assert_eq!(review.unchanged, 0);
+        assert_eq!(review.edits.len(), 2);
+        assert!(review.edits[0].formula_changed() && review.edits[0].errors.is_empty());
+        assert_eq!(review.edits[0].description_after, None);
+        assert_eq!(review.edits[0].dependencies, ["base_fee"]);
+        assert_eq!(review.edits[1].rule_id, "TAX_1");
+        assert!(!review.edits[1].errors.is_empty());
+        assert_eq!(review.rejected_rows, ["Row 5: NEW_1 is not an exported rule"]);
+        assert!(!review.is_valid());
+
+        let renamed = [ReviewedRow { row: 2, rule_id: "FEE_1".to_string(), formula: "charge = 1".to_string(), description: None }];
+        let review = WorkbookReview::new(&rules, &renamed, &categories, &types);
+        assert_eq!(review.problems(), ["Row 2 (FEE_1): The formula assigns charge, not fee"]);
+    }
+}
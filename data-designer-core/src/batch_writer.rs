@@ -0,0 +1,202 @@
+//! Buffered, chunked writes of evaluation results and audit entries
+//!
+//! High-volume scoring produces a result row per evaluation. Writing each
+//! with its own INSERT costs a round trip per row; instead rows are buffered
+//! and written a chunk at a time with one `INSERT ... SELECT FROM UNNEST`
+//! per chunk (see `db::batch_writes`). A chunk is written when it is full or
+//! when its oldest row has waited `flush_interval_ms`, whichever is first.
+//!
+//! Buffering is bounded: once `max_pending` rows are accepted but not yet
+//! written, `BatchWriter::write` waits and `BatchWriter::try_write` refuses,
+//! so a slow database slows its callers instead of growing memory. Callers
+//! can watch the `Backpressure` level to shed or defer work before that.
+
+use crate::config::BatchWriteConfig;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::time::{Duration, Instant};
+
+/// How close the pending rows are to `max_pending`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backpressure {
+    /// Under three quarters full
+    Clear,
+    /// Three quarters full or more; callers should slow down or defer optional writes
+    Rising,
+    /// Full; `write` waits for the next flush and `try_write` refuses
+    Full,
+}
+
+impl Backpressure {
+    pub fn from_pending(pending: usize, max_pending: usize) -> Self {
+        if pending >= max_pending {
+            Backpressure::Full
+        } else if pending * 4 >= max_pending * 3 {
+            Backpressure::Rising
+        } else {
+            Backpressure::Clear
+        }
+    }
+}
+
+impl BatchWriteConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.chunk_size == 0 {
+            return Err("chunk_size must be at least 1".to_string());
+        }
+        if self.max_pending < self.chunk_size {
+            return Err(format!(
+                "max_pending ({}) must be at least chunk_size ({})",
+                self.max_pending, self.chunk_size
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn flush_interval(&self) -> Duration {
+        Duration::from_millis(self.flush_interval_ms.max(1))
+    }
+}
+
+/// Rows waiting to be written, in arrival order
+#[derive(Debug)]
+pub struct BatchBuffer<T> {
+    records: Vec<T>,
+    chunk_size: usize,
+    flush_interval: Duration,
+    oldest: Option<Instant>,
+}
+
+impl<T> BatchBuffer<T> {
+    pub fn new(config: &BatchWriteConfig) -> Self {
+        Self {
+            records: Vec::new(),
+            chunk_size: config.chunk_size.max(1),
+            flush_interval: config.flush_interval(),
+            oldest: None,
+        }
+    }
+
+    /// Buffers a row; true when a full chunk is ready
+    pub fn push(&mut self, record: T, now: Instant) -> bool {
+        self.oldest.get_or_insert(now);
+        self.records.push(record);
+        self.records.len() >= self.chunk_size
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// A chunk is full, or the oldest row has waited the flush interval
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.records.len() >= self.chunk_size
+            || self.oldest.is_some_and(|oldest| now.duration_since(oldest) >= self.flush_interval)
+    }
+
+    /// Empties the buffer into chunks of at most `chunk_size` rows
+    pub fn take_chunks(&mut self) -> Vec<Vec<T>> {
+        self.oldest = None;
+        let mut chunks = Vec::new();
+        let mut records = std::mem::take(&mut self.records).into_iter().peekable();
+        while records.peek().is_some() {
+            chunks.push(records.by_ref().take(self.chunk_size).collect());
+        }
+        chunks
+    }
+}
+
+/// Running totals of one writer
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchWriteStats {
+    pub table: String,
+    pub records_written: u64,
+    pub chunks_written: u64,
+    /// Rows rejected by the database; the rest of their chunk is still written
+    pub records_failed: u64,
+    pub pending: usize,
+    pub max_pending: usize,
+    pub backpressure: Option<Backpressure>,
+    pub last_error: Option<String>,
+}
+
+/// One rule evaluation, for `dsl_execution_logs`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvaluationResultRecord {
+    pub instance_id: Option<String>,
+    /// "rule_evaluation", "validation", "transformation", ...
+    pub execution_type: String,
+    pub dsl_script: String,
+    /// "success", "error", "warning" or "partial"
+    pub execution_status: String,
+    pub input_data: Option<JsonValue>,
+    pub output_data: Option<JsonValue>,
+    pub log_messages: Vec<String>,
+    pub error_details: Option<String>,
+    pub execution_time_ms: i32,
+    pub executed_by: Option<String>,
+    pub executed_at: DateTime<Utc>,
+}
+
+/// One onboarding execution audit entry, for `onboarding_execution_log`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntryRecord {
+    pub onboarding_request_id: i32,
+    pub execution_plan_id: Option<i32>,
+    /// "running", "completed" or "failed"
+    pub status: String,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub execution_log: Vec<String>,
+    pub error_message: Option<String>,
+    pub metadata: JsonValue,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(chunk_size: usize, flush_interval_ms: u64, max_pending: usize) -> BatchWriteConfig {
+        BatchWriteConfig { chunk_size, flush_interval_ms, max_pending }
+    }
+
+    #[test]
+    fn test_buffer_flushes_on_full_chunk_or_interval() {
+        let started = Instant::now();
+        let mut buffer = BatchBuffer::new(&config(3, 100, 10));
+        assert!(!buffer.is_due(started));
+
+        assert!(!buffer.push(1, started));
+        assert!(!buffer.push(2, started + Duration::from_millis(50)));
+        assert!(!buffer.is_due(started + Duration::from_millis(99)));
+        // Measured from the oldest row, not the latest
+        assert!(buffer.is_due(started + Duration::from_millis(100)));
+
+        assert!(buffer.push(3, started + Duration::from_millis(60)));
+        assert!(buffer.push(4, started + Duration::from_millis(60)));
+        assert_eq!(buffer.take_chunks(), vec![vec![1, 2, 3], vec![4]]);
+        assert!(buffer.is_empty());
+        assert!(!buffer.is_due(started + Duration::from_secs(10)));
+        assert!(buffer.take_chunks().is_empty());
+    }
+
+    #[test]
+    fn test_backpressure_levels_and_config_validation() {
+        assert_eq!(Backpressure::from_pending(0, 100), Backpressure::Clear);
+        assert_eq!(Backpressure::from_pending(74, 100), Backpressure::Clear);
+        assert_eq!(Backpressure::from_pending(75, 100), Backpressure::Rising);
+        assert_eq!(Backpressure::from_pending(100, 100), Backpressure::Full);
+        assert!(Backpressure::Rising < Backpressure::Full);
+
+        assert!(BatchWriteConfig::default().validate().is_ok());
+        assert!(config(0, 100, 10).validate().is_err());
+        assert!(config(500, 100, 100).validate().is_err());
+        assert_eq!(config(1, 0, 1).flush_interval(), Duration::from_millis(1));
+    }
+}
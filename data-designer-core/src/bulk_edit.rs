@@ -0,0 +1,312 @@
+//! Bulk edits to data dictionary attributes
+//!
+//! A bulk edit applies one change (set the source system or category, add or
+//! remove a tag) to every attribute a filter selects. Planning the change
+//! against the attributes' current values gives the preview: the rows that
+//! change, from what to what, and the rows that are skipped and why. An
+//! applied plan keeps each row's previous value so the whole batch can be
+//! undone for `UNDO_WINDOW_MINUTES` afterwards.
+
+use crate::storage::normalize_tag;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How long an applied bulk edit can still be undone
+pub const UNDO_WINDOW_MINUTES: i64 = 30;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BulkChange {
+    /// Source system key from `attribute_sources`; `None` clears it
+    SetSourceSystem { source_system: Option<String> },
+    SetCategory { category: Option<String> },
+    AddTag { tag: String },
+    RemoveTag { tag: String },
+}
+
+impl BulkChange {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            BulkChange::AddTag { tag } | BulkChange::RemoveTag { tag } if normalize_tag(tag).is_empty() => {
+                Err("Tag name is required".to_string())
+            }
+            BulkChange::SetSourceSystem { source_system: Some(value) }
+            | BulkChange::SetCategory { category: Some(value) } if value.trim().is_empty() => {
+                Err("Use null to clear a value rather than an empty string".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The changed field's current value on `attribute`; for tag changes, the tag if present
+    pub fn current_value(&self, attribute: &AttributeSnapshot) -> Option<String> {
+        match self {
+            BulkChange::SetSourceSystem { .. } => attribute.source_system.clone(),
+            BulkChange::SetCategory { .. } => attribute.category.clone(),
+            BulkChange::AddTag { tag } | BulkChange::RemoveTag { tag } => {
+                let tag = normalize_tag(tag);
+                attribute.tags.iter().any(|t| normalize_tag(t) == tag).then_some(tag)
+            }
+        }
+    }
+
+    /// The value the field has once the change is applied
+    pub fn target_value(&self) -> Option<String> {
+        match self {
+            BulkChange::SetSourceSystem { source_system } => source_system.as_ref().map(|s| s.trim().to_string()),
+            BulkChange::SetCategory { category } => category.as_ref().map(|c| c.trim().to_string()),
+            BulkChange::AddTag { tag } => Some(normalize_tag(tag)),
+            BulkChange::RemoveTag { .. } => None,
+        }
+    }
+
+    /// Why the change cannot be applied to this kind of attribute
+    fn unsupported_reason(&self, attribute_type: &str) -> Option<&'static str> {
+        match (self, attribute_type) {
+            (BulkChange::SetSourceSystem { .. }, "business") => None,
+            (BulkChange::SetSourceSystem { .. }, _) => Some("only business attributes have a source system"),
+            (BulkChange::SetCategory { .. }, "business" | "derived") => None,
+            (BulkChange::SetCategory { .. }, _) => Some("system attributes are read-only"),
+            (BulkChange::AddTag { .. } | BulkChange::RemoveTag { .. }, _) => None,
+        }
+    }
+}
+
+impl fmt::Display for BulkChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BulkChange::SetSourceSystem { source_system: Some(source) } => write!(f, "set source system to '{}'", source),
+            BulkChange::SetSourceSystem { source_system: None } => write!(f, "clear source system"),
+            BulkChange::SetCategory { category: Some(category) } => write!(f, "set category to '{}'", category),
+            BulkChange::SetCategory { category: None } => write!(f, "clear category"),
+            BulkChange::AddTag { tag } => write!(f, "add tag '{}'", normalize_tag(tag)),
+            BulkChange::RemoveTag { tag } => write!(f, "remove tag '{}'", normalize_tag(tag)),
+        }
+    }
+}
+
+/// The attribute fields a bulk change can touch, as currently stored
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AttributeSnapshot {
+    pub full_path: String,
+    pub attribute_type: String,
+    pub source_system: Option<String>,
+    pub category: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BulkEditRow {
+    pub full_path: String,
+    pub attribute_type: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkippedAttribute {
+    pub full_path: String,
+    pub reason: String,
+}
+
+/// What a bulk change does to a set of attributes; doubles as the preview
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BulkEditPlan {
+    pub change: BulkChange,
+    pub rows: Vec<BulkEditRow>,
+    pub skipped: Vec<SkippedAttribute>,
+}
+
+impl BulkEditPlan {
+    pub fn new(change: BulkChange, attributes: &[AttributeSnapshot]) -> Self {
+        let target = change.target_value();
+        let mut rows = Vec::new();
+        let mut skipped = Vec::new();
+
+        for attribute in attributes {
+            let skip = |reason: &str| SkippedAttribute {
+                full_path: attribute.full_path.clone(),
+                reason: reason.to_string(),
+            };
+            if let Some(reason) = change.unsupported_reason(&attribute.attribute_type) {
+                skipped.push(skip(reason));
+                continue;
+            }
+            let before = change.current_value(attribute);
+            if before == target {
+                skipped.push(skip("already has this value"));
+                continue;
+            }
+            rows.push(BulkEditRow {
+                full_path: attribute.full_path.clone(),
+                attribute_type: attribute.attribute_type.clone(),
+                before,
+                after: target.clone(),
+            });
+        }
+
+        Self { change, rows, skipped }
+    }
+}
+
+/// An applied bulk edit, recorded with enough detail to undo it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BulkEditBatch {
+    pub id: i32,
+    pub change: BulkChange,
+    pub rows: Vec<BulkEditRow>,
+    pub applied_by: Option<String>,
+    pub applied_at: DateTime<Utc>,
+    pub undo_expires_at: DateTime<Utc>,
+    pub undone_at: Option<DateTime<Utc>>,
+}
+
+impl BulkEditBatch {
+    pub fn undo_deadline(applied_at: DateTime<Utc>) -> DateTime<Utc> {
+        applied_at + Duration::minutes(UNDO_WINDOW_MINUTES)
+    }
+
+    pub fn can_undo(&self, now: DateTime<Utc>) -> Result<(), String> {
+        if let Some(undone_at) = self.undone_at {
+            return Err(format!("Bulk edit {} was already undone at {}", self.id, undone_at.to_rfc3339()));
+        }
+        if now > self.undo_expires_at {
+            return Err(format!(
+                "Bulk edit {} can no longer be undone; the undo window closed at {}",
+                self.id,
+                self.undo_expires_at.to_rfc3339()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Rows that restore the previous values. Attributes edited again since
+    /// the batch was applied, or since removed, are left alone and reported.
+    pub fn undo_plan(&self, current: &[AttributeSnapshot]) -> BulkEditPlan {
+        let mut rows = Vec::new();
+        let mut skipped = Vec::new();
+
+        for row in &self.rows {
+            let attribute = current.iter().find(|a| a.full_path == row.full_path);
+            let reason = match attribute {
+                None => Some("attribute no longer exists"),
+                Some(attribute) if self.change.current_value(attribute) != row.after => {
+                    Some("changed again since the bulk edit")
+                }
+                Some(_) => None,
+            };
+            match reason {
+                Some(reason) => skipped.push(SkippedAttribute {
+                    full_path: row.full_path.clone(),
+                    reason: reason.to_string(),
+                }),
+                None => rows.push(BulkEditRow {
+                    full_path: row.full_path.clone(),
+                    attribute_type: row.attribute_type.clone(),
+                    before: row.after.clone(),
+                    after: row.before.clone(),
+                }),
+            }
+        }
+
+        BulkEditPlan {
+            change: self.change.clone(),
+            rows,
+            skipped,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attribute(full_path: &str, attribute_type: &str, source_system: Option<&str>, tags: &[&str]) -> AttributeSnapshot {
+        AttributeSnapshot {
+            full_path: full_path.to_string(),
+            attribute_type: attribute_type.to_string(),
+            source_system: source_system.map(str::to_string),
+            category: None,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    fn attributes() -> Vec<AttributeSnapshot> {
+        vec![
+            attribute("Client.name", "business", Some("crm"), &["pii"]),
+            attribute("Client.tax_id", "business", None, &[]),
+            attribute("Client.risk_score", "derived", None, &[]),
+            attribute("investment_mandates.id", "system", None, &["PII"]),
+        ]
+    }
+
+    #[test]
+    fn test_plan_previews_changes_and_skips() {
+        let change = BulkChange::SetSourceSystem { source_system: Some("crm".to_string()) };
+        let plan = BulkEditPlan::new(change, &attributes());
+
+        assert_eq!(plan.rows, vec![BulkEditRow {
+            full_path: "Client.tax_id".to_string(),
+            attribute_type: "business".to_string(),
+            before: None,
+            after: Some("crm".to_string()),
+        }]);
+        let skipped: Vec<_> = plan.skipped.iter().map(|s| (s.full_path.as_str(), s.reason.as_str())).collect();
+        assert_eq!(skipped, vec![
+            ("Client.name", "already has this value"),
+            ("Client.risk_score", "only business attributes have a source system"),
+            ("investment_mandates.id", "only business attributes have a source system"),
+        ]);
+    }
+
+    #[test]
+    fn test_tag_changes_match_case_insensitively() {
+        let plan = BulkEditPlan::new(BulkChange::AddTag { tag: " PII ".to_string() }, &attributes());
+        let changed: Vec<_> = plan.rows.iter().map(|r| r.full_path.as_str()).collect();
+        assert_eq!(changed, vec!["Client.tax_id", "Client.risk_score"]);
+        assert_eq!(plan.rows[0].after.as_deref(), Some("pii"));
+
+        let plan = BulkEditPlan::new(BulkChange::RemoveTag { tag: "pii".to_string() }, &attributes());
+        assert_eq!(plan.rows.len(), 2);
+        assert!(plan.rows.iter().all(|r| r.before.as_deref() == Some("pii") && r.after.is_none()));
+
+        assert!(BulkChange::AddTag { tag: "  ".to_string() }.validate().is_err());
+        assert!(BulkChange::SetCategory { category: None }.validate().is_ok());
+    }
+
+    #[test]
+    fn test_undo_restores_untouched_rows_within_the_window() {
+        let change = BulkChange::SetCategory { category: Some("kyc".to_string()) };
+        let plan = BulkEditPlan::new(change.clone(), &attributes());
+        let applied_at = Utc::now();
+        let batch = BulkEditBatch {
+            id: 7,
+            change,
+            rows: plan.rows,
+            applied_by: Some("alice".to_string()),
+            applied_at,
+            undo_expires_at: BulkEditBatch::undo_deadline(applied_at),
+            undone_at: None,
+        };
+
+        // Client.name was recategorised again after the batch, risk_score was deleted
+        let mut current = attributes();
+        current.truncate(2);
+        current[0].category = Some("crm".to_string());
+        current[1].category = Some("kyc".to_string());
+
+        let undo = batch.undo_plan(&current);
+        assert_eq!(undo.rows.len(), 1);
+        assert_eq!(undo.rows[0].full_path, "Client.tax_id");
+        assert_eq!(undo.rows[0].after, None);
+        let reasons: Vec<_> = undo.skipped.iter().map(|s| s.reason.as_str()).collect();
+        assert_eq!(reasons, vec!["changed again since the bulk edit", "attribute no longer exists"]);
+
+        assert!(batch.can_undo(applied_at + Duration::minutes(UNDO_WINDOW_MINUTES - 1)).is_ok());
+        assert!(batch.can_undo(applied_at + Duration::minutes(UNDO_WINDOW_MINUTES + 1)).is_err());
+        let undone = BulkEditBatch { undone_at: Some(applied_at), ..batch };
+        assert!(undone.can_undo(applied_at).is_err());
+    }
+}
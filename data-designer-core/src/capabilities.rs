@@ -0,0 +1,177 @@
+//! Optional capabilities a deployment can switch off
+//!
+//! AI assistance, rule embeddings and live-data connectors all reach out to
+//! services a locked-down deployment may not have. Each is listed under
+//! `[capabilities]` in config.toml, or switched off with
+//! `DATA_DESIGNER_DISABLED_CAPABILITIES=ai,embeddings`; the code paths behind
+//! a disabled one degrade to their offline behaviour without trying the
+//! network. Some capabilities build on others: LSP AI completions need AI.
+//!
+//! A process installs its capabilities once at startup; until it does,
+//! everything is enabled.
+
+use crate::config::CapabilitiesConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::OnceLock;
+
+static INSTALLED: OnceLock<Capabilities> = OnceLock::new();
+
+/// Lists capabilities to disable on top of those off in config.toml
+pub const DISABLE_ENV_VAR: &str = "DATA_DESIGNER_DISABLED_CAPABILITIES";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Suggestions from OpenAI or Anthropic; without it the offline suggestions are used
+    Ai,
+    /// Rule embeddings for similarity search
+    Embeddings,
+    /// Reads from the systems behind persistence locators (EntityMasterDB, Redis, ..)
+    LiveData,
+    /// AI suggestions in the language server's completion lists
+    LspAiCompletions,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 4] =
+        [Capability::Ai, Capability::Embeddings, Capability::LiveData, Capability::LspAiCompletions];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Capability::Ai => "ai",
+            Capability::Embeddings => "embeddings",
+            Capability::LiveData => "live_data",
+            Capability::LspAiCompletions => "lsp_ai_completions",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|capability| capability.as_str() == value)
+    }
+
+    /// The capability this one can't work without
+    pub fn requires(&self) -> Option<Capability> {
+        match self {
+            Capability::LspAiCompletions => Some(Capability::Ai),
+            _ => None,
+        }
+    }
+}
+
+/// Which capabilities a deployment runs with
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    disabled: BTreeSet<Capability>,
+}
+
+impl Capabilities {
+    /// Everything enabled
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    pub fn from_config(config: &CapabilitiesConfig) -> Self {
+        let mut capabilities = Self::all();
+        for (capability, enabled) in [
+            (Capability::Ai, config.ai),
+            (Capability::Embeddings, config.embeddings),
+            (Capability::LiveData, config.live_data),
+            (Capability::LspAiCompletions, config.lsp_ai_completions),
+        ] {
+            if !enabled {
+                capabilities.disable(capability);
+            }
+        }
+        capabilities
+    }
+
+    /// Those enabled in `config`, less any listed in `DISABLE_ENV_VAR`, with the
+    /// listed names that aren't capabilities
+    pub fn for_deployment(config: &CapabilitiesConfig) -> (Self, Vec<String>) {
+        let mut capabilities = Self::from_config(config);
+        let unknown = match std::env::var(DISABLE_ENV_VAR) {
+            Ok(list) => capabilities.disable_listed(&list),
+            Err(_) => Vec::new(),
+        };
+        (capabilities, unknown)
+    }
+
+    /// Also disable those listed, comma-separated, in `list`. Returns the names
+    /// that aren't capabilities.
+    pub fn disable_listed(&mut self, list: &str) -> Vec<String> {
+        let mut unknown = Vec::new();
+        for name in list.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            match Capability::parse(name) {
+                Some(capability) => self.disable(capability),
+                None => unknown.push(name.to_string()),
+            }
+        }
+        unknown
+    }
+
+    pub fn disable(&mut self, capability: Capability) {
+        self.disabled.insert(capability);
+    }
+
+    /// Enabled itself and in everything it requires
+    pub fn is_enabled(&self, capability: Capability) -> bool {
+        !self.disabled.contains(&capability) && capability.requires().is_none_or(|required| self.is_enabled(required))
+    }
+
+    /// Every capability with whether it is enabled, for logging and the capabilities endpoint
+    pub fn matrix(&self) -> Vec<(Capability, bool)> {
+        Capability::ALL.into_iter().map(|capability| (capability, self.is_enabled(capability))).collect()
+    }
+}
+
+/// Make `capabilities` the ones this process runs with; once per process
+pub fn install(capabilities: Capabilities) -> Result<(), String> {
+    INSTALLED.set(capabilities).map_err(|_| "Capabilities are already installed".to_string())
+}
+
+/// The installed capabilities, or all of them if none were installed
+pub fn current() -> &'static Capabilities {
+    static ALL: OnceLock<Capabilities> = OnceLock::new();
+    INSTALLED.get().unwrap_or_else(|| ALL.get_or_init(Capabilities::all))
+}
+
+pub fn enabled(capability: Capability) -> bool {
+    current().is_enabled(capability)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabling_ai_disables_lsp_completions() {
+        let config = CapabilitiesConfig { ai: false, ..Default::default() };
+        let capabilities = Capabilities::from_config(&config);
+        assert!(!capabilities.is_enabled(Capability::Ai));
+        assert!(!capabilities.is_enabled(Capability::LspAiCompletions));
+        assert!(capabilities.is_enabled(Capability::Embeddings));
+        assert!(capabilities.is_enabled(Capability::LiveData));
+
+        assert!(Capabilities::all().matrix().iter().all(|(_, enabled)| *enabled));
+        for capability in Capability::ALL {
+            assert_eq!(Capability::parse(capability.as_str()), Some(capability));
+        }
+    }
+
+    #[test]
+    fn test_listed_capabilities_are_disabled() {
+        let mut capabilities = Capabilities::all();
+        let unknown = capabilities.disable_listed(" embeddings, live_data,,telepathy ");
+        assert_eq!(unknown, vec!["telepathy".to_string()]);
+        assert_eq!(
+            capabilities.matrix(),
+            vec![
+                (Capability::Ai, true),
+                (Capability::Embeddings, false),
+                (Capability::LiveData, false),
+                (Capability::LspAiCompletions, true),
+            ]
+        );
+    }
+}
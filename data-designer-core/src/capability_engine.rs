@@ -276,10 +276,15 @@ impl CapabilityEngine {
             Value::Boolean(b) => serde_json::Value::Bool(*b),
             Value::Null => serde_json::Value::Null,
             Value::Regex(pattern) => serde_json::Value::String(format!("regex:{}", pattern)),
+            Value::Percent(p) => serde_json::Value::String(format!("{}%", p)),
+            Value::Money { amount, currency } => serde_json::Value::String(format!("{} {}", amount, currency)),
             Value::List(items) => {
                 let json_items: Vec<serde_json::Value> = items.iter().map(|item| self.value_to_json(item)).collect();
                 serde_json::Value::Array(json_items)
             }
+            Value::Object(fields) => {
+                serde_json::Value::Object(fields.iter().map(|(name, item)| (name.clone(), self.value_to_json(item))).collect())
+            }
         }
     }
 
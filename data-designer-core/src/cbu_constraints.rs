@@ -0,0 +1,323 @@
+//! Business constraints on the CBUs a script creates
+//!
+//! A script can be well formed and still describe a CBU the business doesn't
+//! allow, such as one with two asset owners. Each `CbuConstraint` checks one
+//! such rule against every `(create-cbu ...)` in a script, after evaluation,
+//! so members built with `let`, `map` or quasiquotation are checked too.
+//! Violations carry byte offsets into the script: the `(entity ...)` form at
+//! fault where one is written out, otherwise the `create-cbu` it belongs to.
+//!
+//! `CbuConstraintSet::standard()` holds the built-in constraints; callers add
+//! their own with `add`. Constraints that need stored data, like LEIs and
+//! role codes, read it from a `ConstraintContext` and are skipped when it
+//! isn't there.
+
+use crate::lisp_cbu_dsl::{LispCbuEntity, LispCbuParser, LispEntityRole, LispValue, PendingOperation};
+use crate::source_structure::{regions, Region, RegionKind, LISP_SYNTAX};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// A member of a scripted CBU and the text it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptMember {
+    pub entity: LispCbuEntity,
+    /// Its `(entity ...)` form, or the CBU's head when the form was computed
+    pub span: (usize, usize),
+}
+
+/// A CBU a script creates
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptCbu {
+    pub name: String,
+    /// The `(create-cbu` that opens its form, or the top-level form producing it
+    pub head: (usize, usize),
+    pub members: Vec<ScriptMember>,
+}
+
+/// Stored data constraints may check against; `None` when it isn't available
+#[derive(Debug, Clone, Default)]
+pub struct ConstraintContext {
+    /// Known entity IDs and their LEI codes
+    pub entity_leis: Option<HashMap<String, Option<String>>>,
+    /// The active `cbu_roles` codes
+    pub role_codes: Option<HashSet<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstraintViolation {
+    /// Which constraint was broken
+    pub constraint: &'static str,
+    pub message: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl ConstraintViolation {
+    fn new(constraint: &dyn CbuConstraint, span: (usize, usize), message: String) -> Self {
+        Self { constraint: constraint.name(), message, start: span.0, end: span.1 }
+    }
+}
+
+pub trait CbuConstraint: Send + Sync {
+    /// A short kebab-case name, reported with each violation
+    fn name(&self) -> &'static str;
+
+    fn check(&self, cbu: &ScriptCbu, context: &ConstraintContext) -> Vec<ConstraintViolation>;
+}
+
+/// Every CBU has exactly one asset-owner
+pub struct OneAssetOwner;
+
+impl CbuConstraint for OneAssetOwner {
+    fn name(&self) -> &'static str {
+        "one-asset-owner"
+    }
+
+    fn check(&self, cbu: &ScriptCbu, _context: &ConstraintContext) -> Vec<ConstraintViolation> {
+        let owners: Vec<&ScriptMember> = cbu.members.iter().filter(|member| member.entity.role == LispEntityRole::AssetOwner).collect();
+        match owners.split_first() {
+            None => vec![ConstraintViolation::new(self, cbu.head, format!("'{}' has no asset-owner", cbu.name))],
+            Some((first, extra)) => extra
+                .iter()
+                .map(|member| ConstraintViolation::new(
+                    self,
+                    member.span,
+                    format!("'{}' already has an asset-owner, {} ({})", cbu.name, first.entity.name, first.entity.id),
+                ))
+                .collect(),
+        }
+    }
+}
+
+/// A custodian must have an LEI on record
+pub struct CustodianHasLei;
+
+impl CbuConstraint for CustodianHasLei {
+    fn name(&self) -> &'static str {
+        "custodian-lei"
+    }
+
+    fn check(&self, cbu: &ScriptCbu, context: &ConstraintContext) -> Vec<ConstraintViolation> {
+        let Some(leis) = &context.entity_leis else { return Vec::new() };
+        cbu.members
+            .iter()
+            .filter(|member| member.entity.role == LispEntityRole::Custodian)
+            .filter(|member| matches!(leis.get(&member.entity.id), Some(lei) if lei.as_deref().is_none_or(|lei| lei.trim().is_empty())))
+            .map(|member| ConstraintViolation::new(
+                self,
+                member.span,
+                format!("Custodian {} ({}) has no LEI", member.entity.name, member.entity.id),
+            ))
+            .collect()
+    }
+}
+
+/// An entity holds each role in a CBU at most once
+pub struct UniqueMembers;
+
+impl CbuConstraint for UniqueMembers {
+    fn name(&self) -> &'static str {
+        "unique-members"
+    }
+
+    fn check(&self, cbu: &ScriptCbu, _context: &ConstraintContext) -> Vec<ConstraintViolation> {
+        let mut seen = BTreeSet::new();
+        cbu.members
+            .iter()
+            .filter(|member| !seen.insert((&member.entity.id, member.entity.role)))
+            .map(|member| ConstraintViolation::new(
+                self,
+                member.span,
+                format!("{} is already a {} of '{}'", member.entity.id, member.entity.role.symbol(), cbu.name),
+            ))
+            .collect()
+    }
+}
+
+/// Every role is an active entry in `cbu_roles`
+pub struct KnownRoleCodes;
+
+impl CbuConstraint for KnownRoleCodes {
+    fn name(&self) -> &'static str {
+        "known-role-codes"
+    }
+
+    fn check(&self, cbu: &ScriptCbu, context: &ConstraintContext) -> Vec<ConstraintViolation> {
+        let Some(role_codes) = &context.role_codes else { return Vec::new() };
+        cbu.members
+            .iter()
+            .filter(|member| !role_codes.contains(member.entity.role.role_code()))
+            .map(|member| ConstraintViolation::new(
+                self,
+                member.span,
+                format!("Role {} ({}) is not an active CBU role", member.entity.role.symbol(), member.entity.role.role_code()),
+            ))
+            .collect()
+    }
+}
+
+/// The constraints a script is checked against
+pub struct CbuConstraintSet {
+    constraints: Vec<Box<dyn CbuConstraint>>,
+}
+
+impl CbuConstraintSet {
+    /// A set with no constraints
+    pub fn empty() -> Self {
+        Self { constraints: Vec::new() }
+    }
+
+    /// The built-in constraints
+    pub fn standard() -> Self {
+        let mut set = Self::empty();
+        set.add(Box::new(OneAssetOwner));
+        set.add(Box::new(CustodianHasLei));
+        set.add(Box::new(UniqueMembers));
+        set.add(Box::new(KnownRoleCodes));
+        set
+    }
+
+    pub fn add(&mut self, constraint: Box<dyn CbuConstraint>) {
+        self.constraints.push(constraint);
+    }
+
+    /// Violations in the CBUs `script` creates, ordered by offset. Top-level
+    /// forms that don't evaluate are skipped; their errors are reported elsewhere.
+    pub fn check(&self, script: &str, context: &ConstraintContext) -> Vec<ConstraintViolation> {
+        let mut violations: Vec<ConstraintViolation> = script_cbus(script)
+            .iter()
+            .flat_map(|cbu| self.constraints.iter().flat_map(move |constraint| constraint.check(cbu, context)))
+            .collect();
+        violations.sort_by_key(|violation| (violation.start, violation.end));
+        violations
+    }
+}
+
+impl Default for CbuConstraintSet {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+/// The CBUs `script` creates, with where their forms are
+pub fn script_cbus(script: &str) -> Vec<ScriptCbu> {
+    let groups: Vec<Region> = regions(script, &LISP_SYNTAX).into_iter().filter(|region| region.kind == RegionKind::Group).collect();
+    let mut top_level: Vec<Region> = Vec::new();
+    for group in &groups {
+        if top_level.last().is_none_or(|last| group.start >= last.end) {
+            top_level.push(*group);
+        }
+    }
+
+    let mut cbus = Vec::new();
+    for form in top_level {
+        // Forms are independent, so each is evaluated on its own to keep its offsets
+        let Ok(plan) = LispCbuParser::new(None).eval_plan(&script[form.start..form.end]) else { continue };
+        let inner: Vec<&Region> = groups.iter().filter(|group| group.start >= form.start && group.end <= form.end).collect();
+        let mut heads = inner.iter().filter(|group| form_name(script, group) == Some("create-cbu"));
+        let mut claimed = HashSet::new();
+
+        for operation in plan.into_iter().flatten() {
+            match operation {
+                PendingOperation::CreateCbu { name, .. } => {
+                    let (start, end) = heads.next().map_or((form.start, form.end), |head| (head.start, head.end));
+                    let head = (start, (start + "(create-cbu".len()).min(end));
+                    cbus.push((ScriptCbu { name, head, members: Vec::new() }, (start, end)));
+                }
+                PendingOperation::AddMember { cbu, entity_id, entity_name, role } => {
+                    let Some((script_cbu, (start, end))) = cbus.last_mut().filter(|(script_cbu, _)| script_cbu.name == cbu) else { continue };
+                    let Some(role) = LispEntityRole::from_symbol(&role) else { continue };
+                    let written = inner.iter().find(|group| {
+                        group.start >= *start
+                            && group.end <= *end
+                            && !claimed.contains(&group.start)
+                            && entity_form(script, group).is_some_and(|(id, form_role)| id == entity_id && form_role == role)
+                    });
+                    let span = match written {
+                        Some(group) => {
+                            claimed.insert(group.start);
+                            (group.start, group.end)
+                        }
+                        None => script_cbu.head,
+                    };
+                    script_cbu.members.push(ScriptMember { entity: LispCbuEntity { id: entity_id, name: entity_name, role }, span });
+                }
+                _ => {}
+            }
+        }
+    }
+    cbus.into_iter().map(|(cbu, _)| cbu).collect()
+}
+
+// The symbol a group starts with, e.g. `entity` for `(entity "X" ...)`
+fn form_name<'a>(script: &'a str, group: &Region) -> Option<&'a str> {
+    let inside = script[group.start + 1..group.end].trim_start();
+    let name = &inside[..inside.find(|c: char| c.is_whitespace() || c == '(' || c == ')').unwrap_or(inside.len())];
+    (!name.is_empty()).then_some(name)
+}
+
+// The ID and role of an `(entity "id" "name" role)` written out literally
+fn entity_form(script: &str, group: &Region) -> Option<(String, LispEntityRole)> {
+    if form_name(script, group) != Some("entity") {
+        return None;
+    }
+    let parsed = LispCbuParser::new(None).parse(&script[group.start..group.end]).ok()?;
+    let [LispValue::List(items)] = parsed.as_slice() else { return None };
+    match items.as_slice() {
+        [_, LispValue::String(id), _, LispValue::Symbol(role) | LispValue::String(role)] => {
+            Some((id.clone(), LispEntityRole::from_symbol(role)?))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_standard_constraints_point_at_the_offending_forms() {
+        let script = r#"
+; Two owners, a custodian without an LEI and Beta twice
+(create-cbu "Fund A" "Growth"
+  (entities
+    (entity "AO-1" "Alpha Pension" asset-owner)
+    (entity "AO-2" "Second Pension" asset-owner)
+    (entity "CU-1" "Gamma Trust" custodian)
+    (entity "IM-1" "Beta Capital" investment-manager)
+    (entity "IM-1" "Beta Capital" investment-manager)))
+
+(create-cbu "Fund B" "No owner"
+  (entities
+    (entity "PB-1" "Delta Securities" prime-broker)))
+
+(create-cbu "Fund C" "Fine"
+  (entities (entity "AO-1" "Alpha Pension" asset-owner)))
+"#;
+        let context = ConstraintContext {
+            entity_leis: Some(HashMap::from([
+                ("AO-1".to_string(), Some("LEI-ALPHA".to_string())),
+                ("CU-1".to_string(), None),
+            ])),
+            role_codes: Some(["ASSET_OWNER", "INVESTMENT_MANAGER", "CUSTODIAN"].into_iter().map(str::to_string).collect()),
+        };
+
+        let violations = CbuConstraintSet::standard().check(script, &context);
+        let found: Vec<(&str, &str)> = violations.iter().map(|v| (v.constraint, &script[v.start..v.end])).collect();
+        assert_eq!(found, [
+            ("one-asset-owner", r#"(entity "AO-2" "Second Pension" asset-owner)"#),
+            ("custodian-lei", r#"(entity "CU-1" "Gamma Trust" custodian)"#),
+            ("unique-members", r#"(entity "IM-1" "Beta Capital" investment-manager)"#),
+            ("one-asset-owner", "(create-cbu"),
+            ("known-role-codes", r#"(entity "PB-1" "Delta Securities" prime-broker)"#),
+        ]);
+        // The duplicate is the second of the two identical forms
+        let first_beta = script.find(r#"(entity "IM-1""#).unwrap();
+        assert!(violations[2].start > first_beta);
+        assert!(violations[3].message.contains("Fund B"));
+
+        // Without stored data only the structural constraints apply
+        let structural = CbuConstraintSet::standard().check(script, &ConstraintContext::default());
+        assert_eq!(structural.iter().map(|v| v.constraint).collect::<Vec<_>>(), ["one-asset-owner", "unique-members", "one-asset-owner"]);
+        assert!(CbuConstraintSet::empty().check(script, &context).is_empty());
+    }
+}
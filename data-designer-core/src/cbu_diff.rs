@@ -0,0 +1,246 @@
+//! Semantic diff between two versions of a CBU
+//!
+//! Compares what two `(create-cbu ...)` definitions mean rather than how
+//! they are written: a member is the pair of an entity ID and a role, so
+//! reordering, reformatting or renaming an entity is not a change. An entity
+//! that leaves one role and takes another is reported as a role change. The
+//! diff can be written out as the smallest `(update-cbu ...)` script that
+//! takes the stored CBU from the old definition to the new one, for review
+//! before it is applied.
+
+use crate::lisp_cbu_dsl::{format_cbu_dsl, quoted, CbuFormatOptions, LispCbuEntity, LispCbuParser, LispDslError, LispEntityRole, PendingOperation};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What a `(create-cbu ...)` form says a CBU is, members in canonical order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CbuDefinition {
+    pub name: String,
+    pub description: String,
+    pub entities: Vec<LispCbuEntity>,
+}
+
+impl CbuDefinition {
+    /// Read the definition from a document holding exactly one `create-cbu`.
+    /// Nothing is written; the document is only planned.
+    pub fn parse(source: &str) -> Result<Self, LispDslError> {
+        let plan = LispCbuParser::new(None).eval_plan(source)?;
+        let mut definitions = Vec::new();
+        for operation in plan.into_iter().flatten() {
+            match operation {
+                PendingOperation::CreateCbu { name, description } => {
+                    definitions.push(CbuDefinition { name, description, entities: Vec::new() });
+                }
+                PendingOperation::AddMember { entity_id, entity_name, role, .. } => {
+                    let definition = definitions.last_mut()
+                        .ok_or_else(|| LispDslError::ValidationError("Member outside a create-cbu".to_string()))?;
+                    let role = LispEntityRole::from_symbol(&role)
+                        .ok_or_else(|| LispDslError::ValidationError(format!("Unknown entity role: {}", role)))?;
+                    definition.entities.push(LispCbuEntity { id: entity_id, name: entity_name, role });
+                }
+                other => {
+                    return Err(LispDslError::ValidationError(format!("Expected only a create-cbu, found: {}", other.summary())));
+                }
+            }
+        }
+        match definitions.len() {
+            1 => Ok(definitions.remove(0).canonical()),
+            count => Err(LispDslError::ValidationError(format!("Expected one create-cbu, found {}", count))),
+        }
+    }
+
+    // Members ordered as cbu_sexpr writes them, each (id, role) once
+    fn canonical(mut self) -> Self {
+        self.entities.sort_by(|a, b| (a.role, &a.id, &a.name).cmp(&(b.role, &b.id, &b.name)));
+        self.entities.dedup_by(|a, b| (a.role, &a.id) == (b.role, &b.id));
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoleChange {
+    pub entity_id: String,
+    pub entity_name: String,
+    pub from: LispEntityRole,
+    pub to: LispEntityRole,
+}
+
+/// The changes from one definition of a CBU to another
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CbuDiff {
+    /// The new name, if it changed
+    pub name: Option<String>,
+    /// The new description, if it changed
+    pub description: Option<String>,
+    pub added: Vec<LispCbuEntity>,
+    pub removed: Vec<LispCbuEntity>,
+    pub role_changes: Vec<RoleChange>,
+}
+
+impl CbuDiff {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.description.is_none()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.role_changes.is_empty()
+    }
+
+    /// The `(update-cbu ...)` script that makes these changes to the stored
+    /// CBU `cbu_id`, or `None` when there is nothing to change
+    pub fn patch_script(&self, cbu_id: &str) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut form = format!("(update-cbu {}", quoted(cbu_id));
+        if let Some(name) = &self.name {
+            form.push_str(&format!(" (set-name {})", quoted(name)));
+        }
+        if let Some(description) = &self.description {
+            form.push_str(&format!(" (set-description {})", quoted(description)));
+        }
+        // Removals first, so a member can't briefly hold a role twice
+        for entity in &self.removed {
+            form.push_str(&format!(" (remove-member {} {})", quoted(&entity.id), entity.role.symbol()));
+        }
+        for change in &self.role_changes {
+            form.push_str(&format!(" (change-role {} {} {})", quoted(&change.entity_id), change.from.symbol(), change.to.symbol()));
+        }
+        for entity in &self.added {
+            form.push_str(&format!(" (add-member {} {} {})", quoted(&entity.id), quoted(&entity.name), entity.role.symbol()));
+        }
+        form.push(')');
+        // The form is balanced and its strings terminated, so formatting can't fail
+        Some(format_cbu_dsl(&form, &CbuFormatOptions::default()).unwrap_or(form))
+    }
+
+    /// The definition these changes turn `old` into
+    pub fn apply(&self, old: &CbuDefinition) -> CbuDefinition {
+        let mut entities: Vec<LispCbuEntity> = old.entities.iter()
+            .filter(|entity| !self.removed.iter().any(|removed| same_member(removed, entity)))
+            .cloned()
+            .collect();
+        for change in &self.role_changes {
+            if let Some(entity) = entities.iter_mut().find(|e| e.id == change.entity_id && e.role == change.from) {
+                entity.role = change.to;
+            }
+        }
+        entities.extend(self.added.iter().cloned());
+
+        CbuDefinition {
+            name: self.name.clone().unwrap_or_else(|| old.name.clone()),
+            description: self.description.clone().unwrap_or_else(|| old.description.clone()),
+            entities,
+        }.canonical()
+    }
+}
+
+/// The changes that take `old` to `new`. Members are matched on entity ID and
+/// role; where an entity loses some roles and gains others, they are paired
+/// up in role order as role changes.
+pub fn diff_cbus(old: &CbuDefinition, new: &CbuDefinition) -> CbuDiff {
+    let members = |definition: &CbuDefinition| -> BTreeMap<(String, LispEntityRole), LispCbuEntity> {
+        definition.entities.iter().map(|entity| ((entity.id.clone(), entity.role), entity.clone())).collect()
+    };
+    let (old_members, new_members) = (members(old), members(new));
+
+    let mut removed: BTreeMap<&str, Vec<&LispCbuEntity>> = BTreeMap::new();
+    for (key, entity) in &old_members {
+        if !new_members.contains_key(key) {
+            removed.entry(&entity.id).or_default().push(entity);
+        }
+    }
+    let mut added: BTreeMap<&str, Vec<&LispCbuEntity>> = BTreeMap::new();
+    for (key, entity) in &new_members {
+        if !old_members.contains_key(key) {
+            added.entry(&entity.id).or_default().push(entity);
+        }
+    }
+
+    let mut diff = CbuDiff {
+        name: (old.name != new.name).then(|| new.name.clone()),
+        description: (old.description != new.description).then(|| new.description.clone()),
+        ..CbuDiff::default()
+    };
+    let ids: BTreeSet<&str> = removed.keys().chain(added.keys()).copied().collect();
+    for id in ids {
+        let lost = removed.remove(id).unwrap_or_default();
+        let gained = added.remove(id).unwrap_or_default();
+        let paired = lost.len().min(gained.len());
+        for (from, to) in lost.iter().zip(&gained) {
+            diff.role_changes.push(RoleChange { entity_id: id.to_string(), entity_name: to.name.clone(), from: from.role, to: to.role });
+        }
+        diff.removed.extend(lost[paired..].iter().map(|entity| (*entity).clone()));
+        diff.added.extend(gained[paired..].iter().map(|entity| (*entity).clone()));
+    }
+    diff
+}
+
+fn same_member(a: &LispCbuEntity, b: &LispCbuEntity) -> bool {
+    a.id == b.id && a.role == b.role
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = r#"
+        (create-cbu "Growth Fund" "Long-only equity"
+          (entities
+            (entity "AO-1" "Alpha Pension" asset-owner)
+            (entity "IM-1" "Beta Capital" investment-manager)
+            (entity "CU-1" "Gamma Trust" custodian)))
+    "#;
+
+    const NEW: &str = r#"
+        ; Reordered, Beta moved to administrator, Gamma gone, a prime broker added
+        (create-cbu "Growth Fund II" "Long-only equity"
+          (entities
+            (entity "PB-1" "Delta Securities" prime-broker)
+            (entity "IM-1" "Beta Capital" administrator)
+            (entity "AO-1" "Alpha Pension" asset-owner)))
+    "#;
+
+    #[test]
+    fn test_diff_patch_takes_old_definition_to_new() {
+        let (old, new) = (CbuDefinition::parse(OLD).unwrap(), CbuDefinition::parse(NEW).unwrap());
+        let diff = diff_cbus(&old, &new);
+
+        assert_eq!(diff.name.as_deref(), Some("Growth Fund II"));
+        assert_eq!(diff.description, None);
+        assert_eq!(diff.removed.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), ["CU-1"]);
+        assert_eq!(diff.added.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), ["PB-1"]);
+        assert_eq!(diff.role_changes, [RoleChange {
+            entity_id: "IM-1".to_string(),
+            entity_name: "Beta Capital".to_string(),
+            from: LispEntityRole::InvestmentManager,
+            to: LispEntityRole::Administrator,
+        }]);
+        assert_eq!(diff.apply(&old), new);
+
+        let script = diff.patch_script("CBU-000042").unwrap();
+        let plan = LispCbuParser::new(None).eval_plan(&script).unwrap();
+        let cbu_id = "CBU-000042".to_string();
+        assert_eq!(plan, [vec![
+            PendingOperation::UpdateCbu { cbu_id: cbu_id.clone(), name: Some("Growth Fund II".to_string()), description: None },
+            PendingOperation::RemoveMember { cbu_id: cbu_id.clone(), entity_id: "CU-1".to_string(), role: "custodian".to_string() },
+            PendingOperation::ChangeRole {
+                cbu_id: cbu_id.clone(),
+                entity_id: "IM-1".to_string(),
+                from: "investment-manager".to_string(),
+                to: "administrator".to_string(),
+            },
+            PendingOperation::AddMember {
+                cbu: cbu_id,
+                entity_id: "PB-1".to_string(),
+                entity_name: "Delta Securities".to_string(),
+                role: "prime-broker".to_string(),
+            },
+        ]]);
+
+        // Layout alone is not a change
+        let reformatted = CbuDefinition::parse(&crate::lisp_cbu_dsl::cbu_sexpr(&old.name, &old.description, &old.entities, &[])).unwrap();
+        assert!(diff_cbus(&old, &reformatted).is_empty());
+        assert_eq!(diff_cbus(&old, &reformatted).patch_script("CBU-000042"), None);
+    }
+}
@@ -38,6 +38,138 @@ pub struct GrammarConfig {
     pub validation_enabled: bool,
 }
 
+/// A public key allowed to sign rule bundles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrustedKey {
+    pub name: String,
+    /// Hex-encoded ed25519 public key
+    pub public_key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SecurityConfig {
+    /// Engines refuse unsigned or untrusted rule bundles when set
+    #[serde(default)]
+    pub require_signed_bundles: bool,
+    #[serde(default)]
+    pub trusted_keys: Vec<TrustedKey>,
+    /// The language servers keep the database from editors without a valid
+    /// token when set (see `crate::editor_access`)
+    #[serde(default)]
+    pub require_editor_tokens: bool,
+}
+
+/// OTLP export of tracing spans and metrics (see `crate::telemetry`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// Export to the collector at `otlp_endpoint`; logs stay on stdout either way
+    pub enabled: bool,
+    /// OTLP/gRPC collector endpoint
+    pub otlp_endpoint: String,
+    pub service_name: String,
+    /// Fraction of root traces to sample, 0.0 to 1.0
+    pub sample_ratio: f64,
+    pub export_metrics: bool,
+    pub metrics_interval_seconds: u64,
+}
+
+/// Export of explain traces and rule evaluation metrics to Elasticsearch (see `crate::trace_export`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TraceExportConfig {
+    pub enabled: bool,
+    pub elasticsearch_url: String,
+    /// Documents go to `<index_prefix>-<YYYY-MM>`
+    pub index_prefix: String,
+    /// Stored as the documents' `test_run_id`, so the test harness can query them as a run
+    pub source: String,
+    /// Export a document per rule evaluation as well as the explain traces
+    pub export_metrics: bool,
+    /// Documents per bulk request
+    pub chunk_size: usize,
+    pub flush_interval_ms: u64,
+    /// Documents queued before new ones are dropped
+    pub max_pending: usize,
+}
+
+/// Scheduled purging of records past their retention policy (see `crate::retention`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Run the purge on a schedule; policies can still be applied by hand when off
+    pub purge_enabled: bool,
+    pub purge_interval_hours: u64,
+}
+
+/// Buffered batch inserts of evaluation results and audit entries (see `crate::batch_writer`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatchWriteConfig {
+    /// Rows per INSERT
+    pub chunk_size: usize,
+    /// Longest a buffered row waits before it is written
+    pub flush_interval_ms: u64,
+    /// Rows accepted but not yet written before writers are held back
+    pub max_pending: usize,
+}
+
+/// Where field encryption keys are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    /// The OS keychain, service `data-designer`, entry `field-key-<id>`
+    Keychain,
+    /// `DATA_DESIGNER_FIELD_KEY_<ID>`, as injected by a KMS agent or secrets manager
+    Env,
+}
+
+/// Encryption at rest of attributes tagged sensitive (see `crate::field_encryption`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    /// Load the keys and encrypt sensitive values on write, decrypting them on read
+    pub enabled: bool,
+    pub key_source: KeySource,
+    /// The key new values are encrypted with
+    pub current_key_id: String,
+    /// Keys still needed to read values until a rotation re-encrypts them
+    pub previous_key_ids: Vec<String>,
+}
+
+/// Latency budgets rules are checked against before activation (see `crate::rule_cost`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LatencyBudget {
+    /// Microseconds per rule and record when scoring records in batches
+    pub batch_scoring_us: f64,
+}
+
+/// Optional capabilities this deployment runs with (see `crate::capabilities`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CapabilitiesConfig {
+    /// OpenAI and Anthropic suggestions; off, the assistant answers offline
+    pub ai: bool,
+    /// Rule embeddings for similarity search
+    pub embeddings: bool,
+    /// Reads from the systems behind persistence locators
+    pub live_data: bool,
+    /// AI suggestions in language server completion; needs `ai`
+    pub lsp_ai_completions: bool,
+}
+
+/// Remote registry of shared rule packs (see `crate::rule_registry`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RegistryConfig {
+    /// HTTPS URL of the registry's `index.json`; empty for no registry
+    pub index_url: String,
+    /// Git rule repository that installed packs are committed to
+    pub repository_path: String,
+    pub timeout_seconds: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[derive(Default)]
 pub struct Config {
@@ -45,6 +177,24 @@ pub struct Config {
     pub application: ApplicationConfig,
     pub lsp: LspConfig,
     pub grammar: GrammarConfig,
+    #[serde(default)]
+    pub security: SecurityConfig,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    #[serde(default)]
+    pub trace_export: TraceExportConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub batch_writes: BatchWriteConfig,
+    #[serde(default)]
+    pub latency_budget: LatencyBudget,
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+    #[serde(default)]
+    pub capabilities: CapabilitiesConfig,
+    #[serde(default)]
+    pub registry: RegistryConfig,
 }
 
 impl Default for DatabaseConfig {
@@ -94,12 +244,98 @@ impl Default for GrammarConfig {
     }
 }
 
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        TelemetryConfig {
+            enabled: false,
+            otlp_endpoint: "http://localhost:4317".to_string(),
+            service_name: "data-designer".to_string(),
+            sample_ratio: 1.0,
+            export_metrics: true,
+            metrics_interval_seconds: 30,
+        }
+    }
+}
+
+impl Default for TraceExportConfig {
+    fn default() -> Self {
+        TraceExportConfig {
+            enabled: false,
+            elasticsearch_url: "http://localhost:9200".to_string(),
+            index_prefix: "evaluation-traces".to_string(),
+            source: "production".to_string(),
+            export_metrics: true,
+            chunk_size: 200,
+            flush_interval_ms: 2000,
+            max_pending: 10_000,
+        }
+    }
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        RetentionConfig {
+            purge_enabled: false,
+            purge_interval_hours: 24,
+        }
+    }
+}
+
+impl Default for BatchWriteConfig {
+    fn default() -> Self {
+        BatchWriteConfig {
+            chunk_size: 500,
+            flush_interval_ms: 1000,
+            max_pending: 10_000,
+        }
+    }
+}
+
+impl Default for EncryptionConfig {
+    fn default() -> Self {
+        EncryptionConfig {
+            enabled: false,
+            key_source: KeySource::Keychain,
+            current_key_id: "v1".to_string(),
+            previous_key_ids: Vec::new(),
+        }
+    }
+}
+
+impl Default for CapabilitiesConfig {
+    fn default() -> Self {
+        CapabilitiesConfig {
+            ai: true,
+            embeddings: true,
+            live_data: true,
+            lsp_ai_completions: true,
+        }
+    }
+}
+
+impl Default for RegistryConfig {
+    fn default() -> Self {
+        RegistryConfig {
+            index_url: String::new(),
+            repository_path: "rules-repo".to_string(),
+            timeout_seconds: 30,
+        }
+    }
+}
+
+impl Default for LatencyBudget {
+    fn default() -> Self {
+        LatencyBudget {
+            batch_scoring_us: 1_000.0,
+        }
+    }
+}
 
 impl Config {
     /// Load configuration from file with environment variable overrides
     pub fn load() -> Result<Self, String> {
         let mut config = Self::load_from_file().unwrap_or_else(|_| {
-            println!("⚠️ Could not load config.toml, using defaults");
+            eprintln!("⚠️ Could not load config.toml, using defaults");
             Config::default()
         });
 
@@ -163,6 +399,26 @@ impl Config {
             }
         }
 
+        // Telemetry settings (standard OpenTelemetry variable names)
+        if let Ok(endpoint) = env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+            self.telemetry.otlp_endpoint = endpoint;
+            self.telemetry.enabled = true;
+        }
+        if let Ok(service_name) = env::var("OTEL_SERVICE_NAME") {
+            self.telemetry.service_name = service_name;
+        }
+        if let Ok(disabled) = env::var("OTEL_SDK_DISABLED") {
+            if disabled.to_lowercase() == "true" {
+                self.telemetry.enabled = false;
+            }
+        }
+
+        // Trace export settings
+        if let Ok(url) = env::var("TRACE_EXPORT_ELASTICSEARCH_URL") {
+            self.trace_export.elasticsearch_url = url;
+            self.trace_export.enabled = true;
+        }
+
         // Application settings
         if let Ok(debug) = env::var("DEBUG") {
             self.application.debug_mode = debug.to_lowercase() == "true" || debug == "1";
@@ -247,6 +503,48 @@ mod tests {
         assert_eq!(config.database, "mydb");
     }
 
+    #[test]
+    fn test_telemetry_section_is_optional() {
+        let config: Config = toml::from_str(
+            r#"
+            [database]
+            host = "localhost"
+            port = 5432
+            database = "data_designer"
+            username = "dd"
+            max_connections = 5
+            min_connections = 1
+            acquire_timeout_seconds = 30
+            idle_timeout_seconds = 600
+
+            [application]
+            name = "Data Designer IDE"
+            version = "1.0.0"
+            debug_mode = false
+
+            [lsp]
+            port = 3030
+            auto_start = false
+            reconnect_attempts = 3
+            timeout_ms = 5000
+
+            [grammar]
+            auto_refresh = false
+            cache_enabled = true
+            validation_enabled = true
+
+            [telemetry]
+            enabled = true
+            sample_ratio = 0.25
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.telemetry.enabled);
+        assert_eq!(config.telemetry.sample_ratio, 0.25);
+        assert_eq!(config.telemetry.otlp_endpoint, "http://localhost:4317");
+    }
+
     #[test]
     fn test_database_url_generation() {
         let config = Config::default();
@@ -0,0 +1,217 @@
+//! Comparing evaluations across contexts
+//!
+//! When a rule scores the same CBU differently in two environments, the
+//! cause is in their facts. `diff_contexts` lists the attributes that differ,
+//! fields of records included (`client.aum`), and `diff_results` traces the
+//! rule in both contexts and lists the sub-expressions whose values diverge.
+//! Those marked `origin` are where a difference starts: an attribute read
+//! with another value, or a branch only one context took.
+
+use crate::evaluator::{evaluate_traced, Facts, FunctionLibrary, TraceNode};
+use crate::models::{Expression, Value};
+use crate::rule_graph::extract_dependencies_from_ast;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// An attribute with different values in the two contexts; None where a
+/// context doesn't have it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttributeDiff {
+    pub attribute: String,
+    pub a: Option<Value>,
+    pub b: Option<Value>,
+}
+
+/// What a sub-expression produced: a value, or the error it failed with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Outcome {
+    pub value: Option<Value>,
+    pub error: Option<String>,
+}
+
+impl Outcome {
+    fn of(node: &TraceNode) -> Self {
+        Self { value: node.value.clone(), error: node.error.clone() }
+    }
+}
+
+/// A sub-expression that came out differently in the two contexts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Divergence {
+    /// The sub-expression as the formatter prints it
+    pub expression: String,
+    /// How deep in the rule it sits; the rule itself is 0
+    pub depth: usize,
+    pub a: Outcome,
+    pub b: Outcome,
+    /// None of its inputs diverge, so the difference starts here
+    pub origin: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResultDiff {
+    pub a: Outcome,
+    pub b: Outcome,
+    /// The differing attributes the rule reads
+    pub attributes: Vec<AttributeDiff>,
+    /// Diverging sub-expressions, each ahead of its inputs
+    pub divergences: Vec<Divergence>,
+}
+
+impl ResultDiff {
+    /// Whether the rule itself came out differently
+    pub fn differs(&self) -> bool {
+        !same_outcome(&self.a, &self.b)
+    }
+
+    /// The divergences a difference starts at
+    pub fn origins(&self) -> impl Iterator<Item = &Divergence> {
+        self.divergences.iter().filter(|divergence| divergence.origin)
+    }
+}
+
+/// The attributes whose values differ between `a` and `b`, by name. Records
+/// are compared field by field; numbers compare by value, so 5 and 5.0 match.
+pub fn diff_contexts(a: &Facts, b: &Facts) -> Vec<AttributeDiff> {
+    let names: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+    let mut diffs = Vec::new();
+    for name in names {
+        diff_values(name, a.get(name), b.get(name), &mut diffs);
+    }
+    diffs
+}
+
+/// Evaluates `rule` in both contexts and compares them
+pub fn diff_results(rule: &Expression, a: &Facts, b: &Facts) -> ResultDiff {
+    diff_results_with(rule, a, b, &FunctionLibrary::new())
+}
+
+pub fn diff_results_with(rule: &Expression, a: &Facts, b: &Facts, functions: &FunctionLibrary) -> ResultDiff {
+    let trace_a = evaluate_traced(rule, a, functions);
+    let trace_b = evaluate_traced(rule, b, functions);
+    let mut divergences = Vec::new();
+    compare(&trace_a, &trace_b, 0, &mut divergences);
+
+    let reads = extract_dependencies_from_ast(rule);
+    let attributes = diff_contexts(a, b)
+        .into_iter()
+        .filter(|diff| reads.iter().any(|read| same_or_nested(read, &diff.attribute) || same_or_nested(&diff.attribute, read)))
+        .collect();
+
+    ResultDiff { a: Outcome::of(&trace_a), b: Outcome::of(&trace_b), attributes, divergences }
+}
+
+// Whether `path` is `attribute` or one of its fields
+fn same_or_nested(attribute: &str, path: &str) -> bool {
+    path.strip_prefix(attribute).is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+fn diff_values(path: &str, a: Option<&Value>, b: Option<&Value>, diffs: &mut Vec<AttributeDiff>) {
+    match (a, b) {
+        (Some(Value::Object(a)), Some(Value::Object(b))) => {
+            let fields: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+            for field in fields {
+                diff_values(&format!("{}.{}", path, field), a.get(field), b.get(field), diffs);
+            }
+        }
+        (Some(a), Some(b)) if same_value(a, b) => {}
+        (None, None) => {}
+        _ => diffs.push(AttributeDiff { attribute: path.to_string(), a: a.cloned(), b: b.cloned() }),
+    }
+}
+
+// Records a divergence for `a` and `b` if they differ and walks their inputs
+// while both traces evaluated the same sub-expressions. Returns whether
+// anything diverged.
+fn compare(a: &TraceNode, b: &TraceNode, depth: usize, divergences: &mut Vec<Divergence>) -> bool {
+    let (a_outcome, b_outcome) = (Outcome::of(a), Outcome::of(b));
+    let differs = !same_outcome(&a_outcome, &b_outcome);
+    let index = divergences.len();
+    if differs {
+        divergences.push(Divergence { expression: a.expression.clone(), depth, a: a_outcome, b: b_outcome, origin: true });
+    }
+
+    let mut inputs_diverge = false;
+    for (a, b) in a.inputs.iter().zip(&b.inputs) {
+        // The contexts took different branches from here on
+        if a.expression != b.expression {
+            break;
+        }
+        inputs_diverge |= compare(a, b, depth + 1, divergences);
+    }
+    if differs {
+        divergences[index].origin = !inputs_diverge;
+    }
+    differs || inputs_diverge
+}
+
+fn same_outcome(a: &Outcome, b: &Outcome) -> bool {
+    match (&a.value, &b.value) {
+        (Some(a), Some(b)) => same_value(a, b),
+        (None, None) => a.error == b.error,
+        _ => false,
+    }
+}
+
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::List(a), Value::List(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_value(a, b)),
+        _ => match (number(a), number(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => a == b,
+        },
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(i) => Some(*i as f64),
+        Value::Float(f) | Value::Number(f) => Some(*f),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_rule;
+
+    fn facts(json: serde_json::Value) -> Facts {
+        json.as_object().unwrap().iter().map(|(name, value)| (name.clone(), Value::from_json(value))).collect()
+    }
+
+    #[test]
+    fn test_contexts_differ_by_attribute_and_record_field() {
+        let uat = facts(serde_json::json!({ "risk": 3, "region": "EU", "client": { "aum": 5, "tier": "gold" }, "old": true }));
+        let prod = facts(serde_json::json!({ "risk": 3.0, "region": "US", "client": { "aum": 5, "tier": "silver" }, "new": 1 }));
+        let diffs = diff_contexts(&uat, &prod);
+        let attributes: Vec<&str> = diffs.iter().map(|diff| diff.attribute.as_str()).collect();
+        assert_eq!(attributes, ["client.tier", "new", "old", "region"]);
+        assert_eq!(diffs[1], AttributeDiff { attribute: "new".to_string(), a: None, b: Some(Value::Integer(1)) });
+        assert!(diff_contexts(&uat, &uat).is_empty());
+        let added = diff_contexts(&Facts::new(), &facts(serde_json::json!({ "client": { "x": { "y": 1 } } })));
+        assert_eq!(added[0].attribute, "client");
+    }
+
+    #[test]
+    fn test_results_diverge_from_the_attribute_that_differs() {
+        let (_, rule) = parse_rule("score = IF client.aum > 1000000 AND risk < 5 THEN risk * 2 ELSE risk").unwrap();
+        let uat = facts(serde_json::json!({ "risk": 3, "region": "EU", "client": { "aum": 2000000 } }));
+        let prod = facts(serde_json::json!({ "risk": 3, "region": "US", "client": { "aum": 500000 } }));
+
+        let diff = diff_results(&rule, &uat, &prod);
+        assert!(diff.differs());
+        assert_eq!(diff.a.value, Some(Value::Integer(6)));
+        assert_eq!(diff.b.value, Some(Value::Integer(3)));
+        assert_eq!(diff.attributes.len(), 1);
+        assert_eq!(diff.attributes[0].attribute, "client.aum");
+
+        let origins: Vec<&str> = diff.origins().map(|divergence| divergence.expression.as_str()).collect();
+        assert_eq!(origins, ["client.aum"]);
+        assert_eq!(diff.divergences[0].depth, 0);
+        assert!(diff.divergences.iter().all(|divergence| !divergence.expression.starts_with("risk")));
+
+        let same = diff_results(&rule, &uat, &uat);
+        assert!(!same.differs() && same.divergences.is_empty());
+    }
+}
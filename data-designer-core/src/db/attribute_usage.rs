@@ -0,0 +1,83 @@
+use super::DbPool;
+use crate::attribute_usage::{AttributeUsage, DictionaryAttribute, UsageSource, UsageTally};
+use crate::parser::parse_rule;
+use crate::transpiler::DslTranspiler;
+use sqlx::Row;
+
+// Attribute usage analytics
+pub struct AttributeUsageOperations;
+
+impl AttributeUsageOperations {
+    // How many rules, onboarding plans and resource UIs use each dictionary
+    // attribute, most used first; optionally limited to one entity
+    pub async fn usage_heatmap(
+        pool: &DbPool,
+        entity_name: Option<&str>,
+    ) -> Result<Vec<AttributeUsage>, String> {
+        let dictionary = sqlx::query("
+            SELECT full_path, attribute_name, entity_name, attribute_type
+            FROM mv_data_dictionary
+            WHERE ($1::text IS NULL OR entity_name = $1)
+        ")
+            .bind(entity_name)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?
+            .iter()
+            .map(|row| DictionaryAttribute {
+                full_path: row.get("full_path"),
+                attribute_name: row.get("attribute_name"),
+                entity_name: row.get("entity_name"),
+                attribute_type: row.get("attribute_type"),
+            })
+            .collect::<Vec<_>>();
+
+        let mut tally = UsageTally::new();
+
+        // Rules: the attributes each active rule's definition reads
+        let rules = sqlx::query("SELECT rule_id, rule_definition FROM rules WHERE status != 'deprecated'")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        let transpiler = DslTranspiler::new();
+        for row in &rules {
+            let rule_id: &str = row.get("rule_id");
+            // Rules that no longer parse have no dependencies to report
+            if let Ok((_, ast)) = parse_rule(row.get("rule_definition")) {
+                for dependency in transpiler.extract_dependencies(&ast) {
+                    tally.record(UsageSource::Rule, rule_id, &dependency);
+                }
+            }
+        }
+
+        // Onboarding plans: the attributes bound in each active plan's dependency diagram
+        let plan_bindings = sqlx::query("
+            SELECT id::text AS plan_id, jsonb_object_keys(idd_data -> 'schema') AS attribute_name
+            FROM onboarding_execution_plan
+            WHERE is_active AND jsonb_typeof(idd_data -> 'schema') = 'object'
+        ")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        for row in &plan_bindings {
+            tally.record(UsageSource::Plan, row.get("plan_id"), row.get("attribute_name"));
+        }
+
+        // Resource dictionary UIs: the attributes each active resource renders
+        let ui_fields = sqlx::query("
+            SELECT rd.dictionary_name || '/' || ro.resource_name AS resource, ao.attribute_name
+            FROM attribute_objects ao
+            JOIN resource_objects ro ON ro.id = ao.resource_id
+            JOIN resource_dictionaries rd ON rd.id = ro.dictionary_id
+            WHERE ro.status = 'active' AND rd.status = 'active'
+        ")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        for row in &ui_fields {
+            tally.record(UsageSource::Ui, row.get("resource"), row.get("attribute_name"));
+        }
+
+        Ok(tally.usage(&dictionary))
+    }
+}
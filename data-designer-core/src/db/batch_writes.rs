@@ -0,0 +1,269 @@
+use super::DbPool;
+use crate::batch_writer::{AuditEntryRecord, Backpressure, BatchBuffer, BatchWriteStats, EvaluationResultRecord};
+use crate::config::BatchWriteConfig;
+use async_trait::async_trait;
+use serde_json::Value as JsonValue;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::{mpsc, oneshot, Semaphore};
+use tokio::task::JoinHandle;
+
+// A row type that can be inserted a chunk at a time
+#[async_trait]
+pub trait BatchInsert: Send + Sync + Sized + 'static {
+    const TABLE: &'static str;
+
+    // One statement for the whole chunk; returns the rows inserted
+    async fn insert_chunk(pool: &DbPool, chunk: &[Self]) -> Result<u64, String>;
+}
+
+#[async_trait]
+impl BatchInsert for EvaluationResultRecord {
+    const TABLE: &'static str = "dsl_execution_logs";
+
+    async fn insert_chunk(pool: &DbPool, chunk: &[Self]) -> Result<u64, String> {
+        let result = sqlx::query("
+            INSERT INTO dsl_execution_logs (
+                instance_id, execution_type, dsl_script, execution_status, input_data, output_data,
+                log_messages, error_details, execution_time_ms, executed_by, executed_at
+            )
+            SELECT * FROM UNNEST(
+                $1::varchar[], $2::varchar[], $3::text[], $4::varchar[], $5::jsonb[], $6::jsonb[],
+                $7::jsonb[], $8::text[], $9::integer[], $10::varchar[], $11::timestamptz[]
+            )
+        ")
+            .bind(chunk.iter().map(|r| r.instance_id.clone()).collect::<Vec<_>>())
+            .bind(chunk.iter().map(|r| r.execution_type.clone()).collect::<Vec<_>>())
+            .bind(chunk.iter().map(|r| r.dsl_script.clone()).collect::<Vec<_>>())
+            .bind(chunk.iter().map(|r| r.execution_status.clone()).collect::<Vec<_>>())
+            .bind(chunk.iter().map(|r| r.input_data.clone()).collect::<Vec<_>>())
+            .bind(chunk.iter().map(|r| r.output_data.clone()).collect::<Vec<_>>())
+            .bind(chunk.iter().map(|r| JsonValue::from(r.log_messages.clone())).collect::<Vec<_>>())
+            .bind(chunk.iter().map(|r| r.error_details.clone()).collect::<Vec<_>>())
+            .bind(chunk.iter().map(|r| r.execution_time_ms).collect::<Vec<_>>())
+            .bind(chunk.iter().map(|r| r.executed_by.clone()).collect::<Vec<_>>())
+            .bind(chunk.iter().map(|r| r.executed_at).collect::<Vec<_>>())
+            .execute(pool)
+            .await
+            .map_err(|e| format!("Failed to insert evaluation results: {}", e))?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl BatchInsert for AuditEntryRecord {
+    const TABLE: &'static str = "onboarding_execution_log";
+
+    async fn insert_chunk(pool: &DbPool, chunk: &[Self]) -> Result<u64, String> {
+        // execution_log is TEXT[]; UNNEST flattens arrays of arrays, so each
+        // row's log travels as a JSON array and is unpacked per row
+        let result = sqlx::query("
+            INSERT INTO onboarding_execution_log (
+                onboarding_request_id, execution_plan_id, status, started_at, completed_at,
+                execution_log, error_message, metadata
+            )
+            SELECT request_id, plan_id, status, started_at, completed_at,
+                   ARRAY(SELECT jsonb_array_elements_text(execution_log)), error_message, metadata
+            FROM UNNEST(
+                $1::integer[], $2::integer[], $3::varchar[], $4::timestamptz[], $5::timestamptz[],
+                $6::jsonb[], $7::text[], $8::jsonb[]
+            ) AS entry(request_id, plan_id, status, started_at, completed_at, execution_log, error_message, metadata)
+        ")
+            .bind(chunk.iter().map(|r| r.onboarding_request_id).collect::<Vec<_>>())
+            .bind(chunk.iter().map(|r| r.execution_plan_id).collect::<Vec<_>>())
+            .bind(chunk.iter().map(|r| r.status.clone()).collect::<Vec<_>>())
+            .bind(chunk.iter().map(|r| r.started_at).collect::<Vec<_>>())
+            .bind(chunk.iter().map(|r| r.completed_at).collect::<Vec<_>>())
+            .bind(chunk.iter().map(|r| JsonValue::from(r.execution_log.clone())).collect::<Vec<_>>())
+            .bind(chunk.iter().map(|r| r.error_message.clone()).collect::<Vec<_>>())
+            .bind(chunk.iter().map(|r| r.metadata.clone()).collect::<Vec<_>>())
+            .execute(pool)
+            .await
+            .map_err(|e| format!("Failed to insert audit entries: {}", e))?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+enum Command<T> {
+    Write(T),
+    Flush(oneshot::Sender<()>),
+}
+
+// Buffers rows and writes them in chunks from a background task.
+// Cloning shares the same buffer; the last clone dropped flushes what is left.
+pub struct BatchWriter<T: BatchInsert> {
+    sender: mpsc::UnboundedSender<Command<T>>,
+    // One permit per row that may be pending; returned once the row is written
+    permits: Arc<Semaphore>,
+    max_pending: usize,
+    stats: Arc<Mutex<BatchWriteStats>>,
+}
+
+impl<T: BatchInsert> Clone for BatchWriter<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            permits: self.permits.clone(),
+            max_pending: self.max_pending,
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+impl<T: BatchInsert> BatchWriter<T> {
+    // Must be called inside a Tokio runtime
+    pub fn spawn(pool: DbPool, config: &BatchWriteConfig) -> Result<(Self, JoinHandle<BatchWriteStats>), String> {
+        config.validate()?;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let permits = Arc::new(Semaphore::new(config.max_pending));
+        let stats = Arc::new(Mutex::new(BatchWriteStats {
+            table: T::TABLE.to_string(),
+            max_pending: config.max_pending,
+            ..BatchWriteStats::default()
+        }));
+
+        let handle = tokio::spawn(run_writer::<T>(pool, config.clone(), receiver, permits.clone(), stats.clone()));
+        Ok((Self { sender, permits, max_pending: config.max_pending, stats }, handle))
+    }
+
+    // Queue a row, waiting while max_pending rows are already queued
+    pub async fn write(&self, record: T) -> Result<Backpressure, String> {
+        let permit = self.permits
+            .acquire()
+            .await
+            .map_err(|_| format!("Batch writer for {} is closed", T::TABLE))?;
+        permit.forget();
+        self.send(record).map_err(|_| format!("Batch writer for {} is closed", T::TABLE))
+    }
+
+    // Queue a row without waiting; when the buffer is full the row is handed back
+    pub fn try_write(&self, record: T) -> Result<Backpressure, T> {
+        match self.permits.try_acquire() {
+            Ok(permit) => permit.forget(),
+            Err(_) => return Err(record),
+        }
+        self.send(record)
+    }
+
+    fn send(&self, record: T) -> Result<Backpressure, T> {
+        match self.sender.send(Command::Write(record)) {
+            Ok(()) => Ok(self.backpressure()),
+            Err(mpsc::error::SendError(command)) => {
+                self.permits.add_permits(1);
+                match command {
+                    Command::Write(record) => Err(record),
+                    Command::Flush(_) => unreachable!("only rows are sent here"),
+                }
+            }
+        }
+    }
+
+    // Rows accepted but not yet written
+    pub fn pending(&self) -> usize {
+        self.max_pending - self.permits.available_permits()
+    }
+
+    pub fn backpressure(&self) -> Backpressure {
+        Backpressure::from_pending(self.pending(), self.max_pending)
+    }
+
+    // Write everything queued so far and wait for it
+    pub async fn flush(&self) -> Result<(), String> {
+        let (done, finished) = oneshot::channel();
+        self.sender
+            .send(Command::Flush(done))
+            .map_err(|_| format!("Batch writer for {} is closed", T::TABLE))?;
+        finished.await.map_err(|_| format!("Batch writer for {} stopped", T::TABLE))
+    }
+
+    pub fn stats(&self) -> BatchWriteStats {
+        let mut stats = self.stats.lock().unwrap().clone();
+        stats.pending = self.pending();
+        stats.backpressure = Some(self.backpressure());
+        stats
+    }
+}
+
+async fn run_writer<T: BatchInsert>(
+    pool: DbPool,
+    config: BatchWriteConfig,
+    mut receiver: mpsc::UnboundedReceiver<Command<T>>,
+    permits: Arc<Semaphore>,
+    stats: Arc<Mutex<BatchWriteStats>>,
+) -> BatchWriteStats {
+    let mut buffer = BatchBuffer::new(&config);
+    let mut ticker = tokio::time::interval(config.flush_interval() / 2);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            command = receiver.recv() => match command {
+                Some(Command::Write(record)) => {
+                    if buffer.push(record, Instant::now()) {
+                        write_buffered(&pool, &mut buffer, &permits, &stats).await;
+                    }
+                }
+                Some(Command::Flush(done)) => {
+                    write_buffered(&pool, &mut buffer, &permits, &stats).await;
+                    let _ = done.send(());
+                }
+                None => break,
+            },
+            _ = ticker.tick() => {
+                if buffer.is_due(Instant::now()) {
+                    write_buffered(&pool, &mut buffer, &permits, &stats).await;
+                }
+            }
+        }
+    }
+
+    write_buffered(&pool, &mut buffer, &permits, &stats).await;
+    let stats = stats.lock().unwrap();
+    stats.clone()
+}
+
+async fn write_buffered<T: BatchInsert>(
+    pool: &DbPool,
+    buffer: &mut BatchBuffer<T>,
+    permits: &Semaphore,
+    stats: &Mutex<BatchWriteStats>,
+) {
+    for chunk in buffer.take_chunks() {
+        let (written, failed, error) = match T::insert_chunk(pool, &chunk).await {
+            Ok(written) => (written, 0, None),
+            // One bad row fails the whole statement; retry row by row so only it is lost
+            Err(e) => {
+                tracing::warn!("Batch insert into {} failed, retrying row by row: {}", T::TABLE, e);
+                let mut written = 0;
+                let mut failed = 0;
+                let mut last_error = Some(e);
+                for record in chunk.chunks(1) {
+                    match T::insert_chunk(pool, record).await {
+                        Ok(rows) => written += rows,
+                        Err(e) => {
+                            failed += 1;
+                            last_error = Some(e);
+                        }
+                    }
+                }
+                if failed > 0 {
+                    tracing::error!("Dropped {} rows for {}: {:?}", failed, T::TABLE, last_error);
+                }
+                (written, failed, last_error.filter(|_| failed > 0))
+            }
+        };
+
+        {
+            let mut stats = stats.lock().unwrap();
+            stats.records_written += written;
+            stats.records_failed += failed;
+            stats.chunks_written += 1;
+            if error.is_some() {
+                stats.last_error = error;
+            }
+        }
+        permits.add_permits(chunk.len());
+    }
+}
@@ -0,0 +1,305 @@
+use super::DbPool;
+use super::tags::{assign_tag_on, tag_list_expr, tag_match_clause, unassign_tags_on, TagFilter, TagTarget};
+use crate::bulk_edit::{AttributeSnapshot, BulkChange, BulkEditBatch, BulkEditPlan, BulkEditRow, SkippedAttribute};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, Row};
+
+/// Which attributes a bulk edit applies to; an empty selection matches the whole dictionary
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AttributeSelection {
+    #[serde(default)]
+    pub search: Option<String>,
+    #[serde(default)]
+    pub entity_name: Option<String>,
+    #[serde(default)]
+    pub attribute_type: Option<String>,
+    #[serde(default, flatten)]
+    pub tag_filter: TagFilter,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkUndoResult {
+    pub batch_id: i32,
+    pub restored: Vec<BulkEditRow>,
+    pub skipped: Vec<SkippedAttribute>,
+}
+
+// Bulk attribute edit operations
+pub struct BulkEditOperations;
+
+impl BulkEditOperations {
+    // Plan a change against the selected attributes without writing anything
+    pub async fn preview(
+        pool: &DbPool,
+        selection: &AttributeSelection,
+        change: BulkChange,
+    ) -> Result<BulkEditPlan, String> {
+        change.validate()?;
+        let mut conn = pool.acquire()
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        let attributes = load_snapshots(&mut conn, selection, None).await?;
+        Ok(BulkEditPlan::new(change, &attributes))
+    }
+
+    // Apply a change and record it for undo. `expected_rows` is the row count
+    // the caller previewed; if the selection has changed since, nothing is written.
+    pub async fn apply(
+        pool: &DbPool,
+        selection: &AttributeSelection,
+        change: BulkChange,
+        expected_rows: Option<usize>,
+        applied_by: Option<&str>,
+    ) -> Result<BulkEditBatch, String> {
+        change.validate()?;
+        let mut tx = pool.begin()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        if let BulkChange::SetSourceSystem { source_system: Some(source_key) } = &change {
+            let (exists,): (bool,) = sqlx::query_as("SELECT EXISTS (SELECT 1 FROM attribute_sources WHERE source_key = $1)")
+                .bind(source_key.trim())
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|e| format!("Database error: {}", e))?;
+            if !exists {
+                return Err(format!("Unknown source system '{}'", source_key));
+            }
+        }
+
+        let attributes = load_snapshots(&mut tx, selection, None).await?;
+        let plan = BulkEditPlan::new(change, &attributes);
+        if let Some(expected) = expected_rows {
+            if plan.rows.len() != expected {
+                return Err(format!(
+                    "The preview showed {} attributes to change but {} would change now; preview again",
+                    expected,
+                    plan.rows.len()
+                ));
+            }
+        }
+        if plan.rows.is_empty() {
+            return Err("No attributes would change".to_string());
+        }
+
+        for row in &plan.rows {
+            write_row(&mut tx, &plan.change, row, applied_by).await?;
+        }
+
+        let applied_at = Utc::now();
+        let undo_expires_at = BulkEditBatch::undo_deadline(applied_at);
+        let (id,): (i32,) = sqlx::query_as("
+            INSERT INTO bulk_edit_batches (change, edited_rows, applied_by, applied_at, undo_expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id
+        ")
+            .bind(serde_json::to_value(&plan.change).map_err(|e| e.to_string())?)
+            .bind(serde_json::to_value(&plan.rows).map_err(|e| e.to_string())?)
+            .bind(applied_by)
+            .bind(applied_at)
+            .bind(undo_expires_at)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to record bulk edit: {}", e))?;
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+        Ok(BulkEditBatch {
+            id,
+            change: plan.change,
+            rows: plan.rows,
+            applied_by: applied_by.map(str::to_string),
+            applied_at,
+            undo_expires_at,
+            undone_at: None,
+        })
+    }
+
+    // Restore the values a batch replaced, skipping attributes edited again since
+    pub async fn undo(
+        pool: &DbPool,
+        batch_id: i32,
+        undone_by: Option<&str>,
+    ) -> Result<BulkUndoResult, String> {
+        let mut tx = pool.begin()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let row = sqlx::query(&format!("{} WHERE id = $1 FOR UPDATE", BATCH_QUERY))
+            .bind(batch_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?
+            .ok_or_else(|| format!("Bulk edit {} not found", batch_id))?;
+        let batch = batch_from_row(&row)?;
+        batch.can_undo(Utc::now())?;
+
+        let paths: Vec<String> = batch.rows.iter().map(|r| r.full_path.clone()).collect();
+        let current = load_snapshots(&mut tx, &AttributeSelection::default(), Some(&paths)).await?;
+        let undo = batch.undo_plan(&current);
+
+        for row in &undo.rows {
+            write_row(&mut tx, &undo.change, row, undone_by).await?;
+        }
+
+        sqlx::query("UPDATE bulk_edit_batches SET undone_at = CURRENT_TIMESTAMP, undone_by = $2 WHERE id = $1")
+            .bind(batch_id)
+            .bind(undone_by)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to mark bulk edit as undone: {}", e))?;
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+        Ok(BulkUndoResult {
+            batch_id,
+            restored: undo.rows,
+            skipped: undo.skipped,
+        })
+    }
+
+    // Most recent bulk edits first
+    pub async fn list_batches(
+        pool: &DbPool,
+        limit: i64,
+    ) -> Result<Vec<BulkEditBatch>, String> {
+        let rows = sqlx::query(&format!("{} ORDER BY applied_at DESC LIMIT $1", BATCH_QUERY))
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        rows.iter().map(batch_from_row).collect()
+    }
+}
+
+const BATCH_QUERY: &str = "
+    SELECT id, change, edited_rows, applied_by, applied_at, undo_expires_at, undone_at
+    FROM bulk_edit_batches
+";
+
+fn batch_from_row(row: &sqlx::postgres::PgRow) -> Result<BulkEditBatch, String> {
+    Ok(BulkEditBatch {
+        id: row.get("id"),
+        change: serde_json::from_value(row.get("change"))
+            .map_err(|e| format!("Invalid stored bulk change: {}", e))?,
+        rows: serde_json::from_value(row.get("edited_rows"))
+            .map_err(|e| format!("Invalid stored bulk edit rows: {}", e))?,
+        applied_by: row.get("applied_by"),
+        applied_at: row.get("applied_at"),
+        undo_expires_at: row.get("undo_expires_at"),
+        undone_at: row.get("undone_at"),
+    })
+}
+
+// Current source system, category and tags of the selected attributes,
+// optionally limited to the given full paths
+async fn load_snapshots(
+    conn: &mut PgConnection,
+    selection: &AttributeSelection,
+    full_paths: Option<&[String]>,
+) -> Result<Vec<AttributeSnapshot>, String> {
+    let query = format!(
+        r#"
+        SELECT d.full_path, d.attribute_type,
+               src.source_key AS source_system,
+               COALESCE(ba.metadata, da.metadata) ->> 'category' AS category,
+               {} AS tags
+        FROM mv_data_dictionary d
+        LEFT JOIN business_attributes ba ON d.attribute_type = 'business' AND ba.full_path = d.full_path
+        LEFT JOIN attribute_sources src ON src.id = ba.source_id
+        LEFT JOIN derived_attributes da ON d.attribute_type = 'derived' AND da.full_path = d.full_path
+        WHERE ($1::text IS NULL OR d.attribute_name ILIKE $1 OR d.description ILIKE $1)
+          AND {}
+          AND ($4::text IS NULL OR d.entity_name = $4)
+          AND ($5::text IS NULL OR d.attribute_type = $5)
+          AND ($6::text[] IS NULL OR d.full_path = ANY($6))
+        ORDER BY d.entity_name, d.attribute_name
+        "#,
+        tag_list_expr(TagTarget::Attribute, "d.full_path"),
+        tag_match_clause(TagTarget::Attribute, "d.full_path", 2, 3),
+    );
+
+    let search = selection.search.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    let rows = sqlx::query(&query)
+        .bind(search.map(|term| format!("%{}%", term)))
+        .bind(selection.tag_filter.normalized_tags())
+        .bind(selection.tag_filter.match_all)
+        .bind(&selection.entity_name)
+        .bind(&selection.attribute_type)
+        .bind(full_paths)
+        .fetch_all(conn)
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?;
+
+    Ok(rows.iter().map(|row| AttributeSnapshot {
+        full_path: row.get("full_path"),
+        attribute_type: row.get("attribute_type"),
+        source_system: row.get("source_system"),
+        category: row.get("category"),
+        tags: row.get("tags"),
+    }).collect())
+}
+
+// Write a row's `after` value to the field the change targets
+async fn write_row(
+    conn: &mut PgConnection,
+    change: &BulkChange,
+    row: &BulkEditRow,
+    edited_by: Option<&str>,
+) -> Result<(), String> {
+    let result = match change {
+        BulkChange::SetSourceSystem { .. } => {
+            sqlx::query("
+                UPDATE business_attributes
+                SET source_id = (SELECT id FROM attribute_sources WHERE source_key = $2),
+                    updated_at = CURRENT_TIMESTAMP
+                WHERE full_path = $1
+            ")
+                .bind(&row.full_path)
+                .bind(&row.after)
+                .execute(conn)
+                .await
+        }
+        BulkChange::SetCategory { .. } => {
+            let table = match row.attribute_type.as_str() {
+                "business" => "business_attributes",
+                "derived" => "derived_attributes",
+                other => return Err(format!("Cannot set the category of a {} attribute", other)),
+            };
+            sqlx::query(&format!(
+                "UPDATE {}
+                SET metadata = CASE
+                        WHEN $2::text IS NULL THEN COALESCE(metadata, '{{}}'::jsonb) - 'category'
+                        ELSE COALESCE(metadata, '{{}}'::jsonb) || jsonb_build_object('category', $2::text)
+                    END,
+                    updated_at = CURRENT_TIMESTAMP
+                WHERE full_path = $1",
+                table
+            ))
+                .bind(&row.full_path)
+                .bind(&row.after)
+                .execute(conn)
+                .await
+        }
+        BulkChange::AddTag { .. } | BulkChange::RemoveTag { .. } => {
+            return match (&row.after, &row.before) {
+                (Some(tag), _) => assign_tag_on(conn, tag, TagTarget::Attribute, &row.full_path, edited_by).await,
+                (None, Some(tag)) => unassign_tags_on(conn, std::slice::from_ref(tag), TagTarget::Attribute, &row.full_path)
+                    .await
+                    .map(|_| ()),
+                (None, None) => Ok(()),
+            };
+        }
+    };
+
+    result
+        .map(|_| ())
+        .map_err(|e| format!("Failed to update {}: {}", row.full_path, e))
+}
@@ -3,7 +3,12 @@ use sqlx::FromRow;
 use std::collections::HashMap;
 use chrono::{NaiveDate, DateTime, Utc};
 
-use super::DbOperations;
+use sqlx::{Postgres, Transaction};
+
+use super::{DbOperations, DbPool};
+use crate::cbu_diff::CbuDefinition;
+use crate::lisp_cbu_dsl::{cbu_sexpr, LispCbuEntity, LispEntityRole, PendingOperation};
+use crate::telemetry::hash_cbu_id;
 
 // Core CBU structures
 
@@ -140,6 +145,23 @@ pub struct CbuMemberDetail {
     pub updated_at: DateTime<Utc>,
 }
 
+/// An active legal entity, as offered when completing `(entity "…")`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct EntityReference {
+    pub entity_id: String,
+    pub entity_name: String,
+    pub entity_type: Option<String>,
+    pub lei_code: Option<String>,
+}
+
+/// A CBU's ID and name, as offered when completing `(update-cbu "…")`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CbuReference {
+    pub cbu_id: String,
+    pub cbu_name: String,
+    pub status: String,
+}
+
 impl DbOperations {
     // === CBU MANAGEMENT ===
 
@@ -147,8 +169,7 @@ impl DbOperations {
     pub async fn create_cbu(request: CreateCbuRequest) -> Result<ClientBusinessUnit, String> {
         let pool = Self::get_pool().await.map_err(|e| e.to_string())?;
 
-        // Generate unique CBU ID (could be customized based on business rules)
-        let cbu_id = format!("CBU-{:06}", chrono::Utc::now().timestamp_millis() % 1000000);
+        let cbu_id = generate_cbu_id();
 
         let query = r#"
             INSERT INTO client_business_units (
@@ -174,6 +195,7 @@ impl DbOperations {
     }
 
     /// Get CBU by external ID
+    #[tracing::instrument(name = "db.cbu.get", skip_all, fields(cbu_id_hash = %hash_cbu_id(cbu_id)))]
     pub async fn get_cbu_by_id(cbu_id: &str) -> Result<Option<ClientBusinessUnit>, String> {
         let pool = Self::get_pool().await.map_err(|e| e.to_string())?;
 
@@ -263,6 +285,7 @@ impl DbOperations {
     }
 
     /// Get all members of a CBU with role details
+    #[tracing::instrument(name = "db.cbu.members", skip_all, fields(cbu_id_hash = %hash_cbu_id(cbu_id)))]
     pub async fn get_cbu_members(cbu_id: &str) -> Result<Vec<CbuMemberDetail>, String> {
         let pool = Self::get_pool().await.map_err(|e| e.to_string())?;
 
@@ -305,6 +328,7 @@ impl DbOperations {
     }
 
     /// Remove a member from a CBU (soft delete)
+    #[tracing::instrument(name = "db.cbu.remove_member", skip_all, fields(cbu_id_hash = %hash_cbu_id(cbu_id)))]
     pub async fn remove_cbu_member(
         cbu_id: &str,
         entity_id: &str,
@@ -354,6 +378,39 @@ impl DbOperations {
             .map_err(|e| format!("Failed to search CBUs: {}", e))
     }
 
+    /// Active legal entities by name, for editor completion
+    pub async fn list_entity_references(pool: &DbPool) -> Result<Vec<EntityReference>, String> {
+        let query = r#"
+            SELECT entity_id, entity_name, entity_type, lei_code
+            FROM legal_entities
+            WHERE status = 'active'
+            ORDER BY entity_name
+        "#;
+
+        sqlx::query_as::<_, EntityReference>(query)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Failed to list legal entities: {}", e))
+    }
+
+    /// Every CBU's ID and name, for editor completion
+    pub async fn list_cbu_references(pool: &DbPool) -> Result<Vec<CbuReference>, String> {
+        let query = "SELECT cbu_id, cbu_name, status FROM client_business_units ORDER BY cbu_name";
+
+        sqlx::query_as::<_, CbuReference>(query)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Failed to list CBUs: {}", e))
+    }
+
+    /// The codes of the active CBU roles, for checking scripts in the editor
+    pub async fn list_active_role_codes(pool: &DbPool) -> Result<Vec<String>, String> {
+        sqlx::query_scalar("SELECT role_code FROM cbu_roles WHERE is_active = true ORDER BY role_code")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Failed to list CBU roles: {}", e))
+    }
+
     /// Get CBU roles grouped by category
     pub async fn get_cbu_roles_by_category() -> Result<HashMap<String, Vec<CbuRole>>, String> {
         let roles = Self::get_cbu_roles().await?;
@@ -368,6 +425,7 @@ impl DbOperations {
     }
 
     /// Update CBU basic information
+    #[tracing::instrument(name = "db.cbu.update", skip_all, fields(cbu_id_hash = %hash_cbu_id(cbu_id)))]
     pub async fn update_cbu(
         cbu_id: &str,
         cbu_name: Option<String>,
@@ -398,4 +456,179 @@ impl DbOperations {
             .await
             .map_err(|e| format!("Failed to update CBU: {}", e))
     }
-}
\ No newline at end of file
+
+    /// A stored CBU and its active members as canonical CBU DSL, to export,
+    /// edit and apply again. Members whose role the DSL has no symbol for are
+    /// listed in comments above the form.
+    #[tracing::instrument(name = "db.cbu.export", skip_all, fields(cbu_id_hash = %hash_cbu_id(cbu_id)))]
+    pub async fn cbu_to_sexpr(cbu_id: &str) -> Result<String, String> {
+        let (definition, unexpressible) = Self::cbu_definition(cbu_id).await?;
+        let mut comments = vec![format!("Exported from {}", cbu_id)];
+        comments.extend(unexpressible.into_iter().map(|member| format!("Not expressible in the DSL: {}", member)));
+        Ok(cbu_sexpr(&definition.name, &definition.description, &definition.entities, &comments))
+    }
+
+    /// A stored CBU and its active members as the DSL sees them, along with a
+    /// description of each member whose role the DSL has no symbol for
+    pub async fn cbu_definition(cbu_id: &str) -> Result<(CbuDefinition, Vec<String>), String> {
+        let cbu = Self::get_cbu_by_id(cbu_id).await?
+            .ok_or_else(|| format!("CBU not found: {}", cbu_id))?;
+
+        let mut entities = Vec::new();
+        let mut unexpressible = Vec::new();
+        for member in Self::get_cbu_members(cbu_id).await? {
+            match LispEntityRole::from_role_code(&member.role_code) {
+                Some(role) => entities.push(LispCbuEntity { id: member.entity_id, name: member.entity_name, role }),
+                None => unexpressible.push(format!("{} ({}) as {}", member.entity_name, member.entity_id, member.role_code)),
+            }
+        }
+
+        let definition = CbuDefinition { name: cbu.cbu_name, description: cbu.description.unwrap_or_default(), entities };
+        Ok((definition, unexpressible))
+    }
+
+    /// Apply one operation of an executed CBU script inside its transaction,
+    /// returning the ID of the CBU it touched. `created` maps the names of CBUs
+    /// created earlier in the script to their internal IDs; members added to
+    /// any other CBU go to the stored one with that ID.
+    pub async fn apply_cbu_operation(
+        tx: &mut Transaction<'_, Postgres>,
+        operation: &PendingOperation,
+        created: &mut HashMap<String, (i32, String)>,
+    ) -> Result<String, String> {
+        match operation {
+            PendingOperation::CreateCbu { name, description } => {
+                let (id, cbu_id): (i32, String) = sqlx::query_as(
+                    "INSERT INTO client_business_units (cbu_id, cbu_name, description) VALUES ($1, $2, $3) RETURNING id, cbu_id",
+                )
+                    .bind(generate_cbu_id())
+                    .bind(name)
+                    .bind(description)
+                    .fetch_one(&mut **tx)
+                    .await
+                    .map_err(|e| format!("Failed to create CBU: {}", e))?;
+                created.insert(name.clone(), (id, cbu_id.clone()));
+                Ok(cbu_id)
+            }
+            PendingOperation::AddMember { cbu, entity_id, entity_name, role } => {
+                let (id, cbu_id) = match created.get(cbu) {
+                    Some(created) => created.clone(),
+                    None => sqlx::query_as("SELECT id, cbu_id FROM client_business_units WHERE cbu_id = $1")
+                        .bind(cbu)
+                        .fetch_optional(&mut **tx)
+                        .await
+                        .map_err(|e| format!("Failed to look up CBU: {}", e))?
+                        .ok_or_else(|| format!("CBU not found: {}", cbu))?,
+                };
+                let role_id = active_role_id(tx, role).await?;
+                sqlx::query("INSERT INTO cbu_members (cbu_id, role_id, entity_id, entity_name) VALUES ($1, $2, $3, $4)")
+                    .bind(id)
+                    .bind(role_id)
+                    .bind(entity_id)
+                    .bind(entity_name)
+                    .execute(&mut **tx)
+                    .await
+                    .map_err(|e| format!("Failed to add CBU member: {}", e))?;
+                Ok(cbu_id)
+            }
+            PendingOperation::UpdateCbu { cbu_id, name, description } => {
+                let result = sqlx::query(
+                    "UPDATE client_business_units SET cbu_name = COALESCE($2, cbu_name), description = COALESCE($3, description), updated_at = CURRENT_TIMESTAMP WHERE cbu_id = $1",
+                )
+                    .bind(cbu_id)
+                    .bind(name)
+                    .bind(description)
+                    .execute(&mut **tx)
+                    .await
+                    .map_err(|e| format!("Failed to update CBU: {}", e))?;
+                if result.rows_affected() == 0 {
+                    return Err(format!("CBU not found: {}", cbu_id));
+                }
+                Ok(cbu_id.clone())
+            }
+            PendingOperation::RemoveMember { cbu_id, entity_id, role } => {
+                let result = sqlx::query(
+                    r#"
+                    UPDATE cbu_members
+                    SET is_active = false, updated_at = CURRENT_TIMESTAMP
+                    WHERE cbu_id = (SELECT id FROM client_business_units WHERE cbu_id = $1)
+                      AND entity_id = $2
+                      AND role_id = (SELECT id FROM cbu_roles WHERE role_code = $3)
+                      AND is_active = true
+                    "#,
+                )
+                    .bind(cbu_id)
+                    .bind(entity_id)
+                    .bind(cbu_role_code(role))
+                    .execute(&mut **tx)
+                    .await
+                    .map_err(|e| format!("Failed to remove CBU member: {}", e))?;
+                if result.rows_affected() == 0 {
+                    return Err(format!("{} is not a {} of {}", entity_id, role, cbu_id));
+                }
+                Ok(cbu_id.clone())
+            }
+            PendingOperation::ChangeRole { cbu_id, entity_id, from, to } => {
+                let role_id = active_role_id(tx, to).await?;
+                let result = sqlx::query(
+                    r#"
+                    UPDATE cbu_members
+                    SET role_id = $4, updated_at = CURRENT_TIMESTAMP
+                    WHERE cbu_id = (SELECT id FROM client_business_units WHERE cbu_id = $1)
+                      AND entity_id = $2
+                      AND role_id = (SELECT id FROM cbu_roles WHERE role_code = $3)
+                      AND is_active = true
+                    "#,
+                )
+                    .bind(cbu_id)
+                    .bind(entity_id)
+                    .bind(cbu_role_code(from))
+                    .bind(role_id)
+                    .execute(&mut **tx)
+                    .await
+                    .map_err(|e| format!("Failed to change member role: {}", e))?;
+                if result.rows_affected() == 0 {
+                    return Err(format!("{} is not a {} of {}", entity_id, from, cbu_id));
+                }
+                Ok(cbu_id.clone())
+            }
+            PendingOperation::DeleteCbu { cbu_id } => {
+                // Soft delete, like members
+                let result = sqlx::query(
+                    "UPDATE client_business_units SET status = 'inactive', updated_at = CURRENT_TIMESTAMP WHERE cbu_id = $1 AND status <> 'inactive'",
+                )
+                    .bind(cbu_id)
+                    .execute(&mut **tx)
+                    .await
+                    .map_err(|e| format!("Failed to delete CBU: {}", e))?;
+                if result.rows_affected() == 0 {
+                    return Err(format!("CBU not found or already deleted: {}", cbu_id));
+                }
+                Ok(cbu_id.clone())
+            }
+        }
+    }
+}
+
+// Generate unique CBU ID (could be customized based on business rules)
+fn generate_cbu_id() -> String {
+    format!("CBU-{:06}", chrono::Utc::now().timestamp_millis() % 1000000)
+}
+
+// The ID of the active cbu_roles row for a DSL role symbol
+async fn active_role_id(tx: &mut Transaction<'_, Postgres>, role: &str) -> Result<i32, String> {
+    let role_id: (i32,) = sqlx::query_as("SELECT id FROM cbu_roles WHERE role_code = $1 AND is_active = true")
+        .bind(cbu_role_code(role))
+        .fetch_one(&mut **tx)
+        .await
+        .map_err(|e| format!("Role not found or inactive: {}", e))?;
+    Ok(role_id.0)
+}
+
+// The cbu_roles code of a DSL role symbol, e.g. asset-owner -> ASSET_OWNER
+fn cbu_role_code(symbol: &str) -> String {
+    match LispEntityRole::from_symbol(symbol) {
+        Some(role) => role.role_code().to_string(),
+        None => symbol.replace('-', "_").to_uppercase(),
+    }
+}
@@ -1,4 +1,7 @@
 use crate::db::DbPool;
+use crate::models::Expression;
+use crate::parser::parse_rule;
+use crate::transpiler::postgres::{self, ColumnMap};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
@@ -83,6 +86,54 @@ pub struct FullResourceConfiguration {
     pub attributes: Vec<AttributeWithPerspectives>,
 }
 
+impl FullResourceConfiguration {
+    /// Columns of the attributes that have a persistence locator
+    pub fn column_map(&self) -> ColumnMap {
+        let mut columns = ColumnMap::new();
+        for item in &self.attributes {
+            let attr = &item.attribute;
+            columns.add_locator(
+                &attr.attribute_name,
+                attr.persistence_system.as_deref(),
+                attr.persistence_entity.as_deref(),
+                attr.persistence_identifier.as_deref(),
+            );
+        }
+        columns
+    }
+
+    /// `CREATE OR REPLACE VIEW <resource>_derived` computing every attribute
+    /// with a rule, joined on `key_column`
+    pub fn postgres_view(&self, key_column: &str) -> Result<String, String> {
+        let mut rules: Vec<(&str, Expression)> = Vec::new();
+        for item in &self.attributes {
+            let attr = &item.attribute;
+            let Some(rules_dsl) = attr.rules_dsl.as_deref() else {
+                continue;
+            };
+            // Rules are stored either as `DERIVE <attribute> FROM <expression>` or as an expression
+            let text = match rules_dsl.trim().strip_prefix("DERIVE ").and_then(|rest| rest.split_once(" FROM ")) {
+                Some((_, expression)) => expression,
+                None => rules_dsl,
+            };
+            let expression = match parse_rule(text) {
+                Ok((remaining, expression)) if remaining.trim().is_empty() => expression,
+                _ => return Err(format!("Failed to parse rule of '{}': {}", attr.attribute_name, rules_dsl)),
+            };
+            let expression = match expression {
+                Expression::Assignment { value, .. } => *value,
+                other => other,
+            };
+            rules.push((&attr.attribute_name, expression));
+        }
+
+        let rules: Vec<(&str, &Expression)> = rules.iter().map(|(target, expression)| (*target, expression)).collect();
+        let view_name = format!("{}_derived", self.resource.resource_name);
+        postgres::create_view(&view_name, key_column, &rules, &self.column_map())
+            .map_err(|e| format!("Failed to generate view for '{}': {}", self.resource.resource_name, e))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AttributeWithPerspectives {
     pub attribute: AttributeObject,
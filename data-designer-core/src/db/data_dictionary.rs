@@ -1,4 +1,5 @@
 use super::{DbPool, DbOperations};
+use super::tags::{tag_list_expr, tag_match_clause, TagFilter, TagTarget};
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, Row};
 use std::collections::HashMap;
@@ -59,6 +60,41 @@ impl DataDictionaryOperations {
             }
         };
 
+        Self::dictionary_response(rows)
+    }
+
+    /// Data dictionary narrowed by search text and tags, with each attribute's tags included
+    pub async fn filter_data_dictionary(
+        pool: &DbPool,
+        search_term: Option<&str>,
+        tag_filter: &TagFilter,
+    ) -> Result<DataDictionaryResponse, String> {
+        let query = format!(
+            r#"
+            SELECT attribute_name, full_path, data_type, description,
+                   attribute_type, entity_name, required,
+                   {} AS tags
+            FROM mv_data_dictionary
+            WHERE ($1::text IS NULL OR attribute_name ILIKE $1 OR description ILIKE $1)
+              AND {}
+            ORDER BY entity_name, attribute_name
+            "#,
+            tag_list_expr(TagTarget::Attribute, "full_path"),
+            tag_match_clause(TagTarget::Attribute, "full_path", 2, 3),
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(search_term.map(|term| format!("%{}%", term)))
+            .bind(tag_filter.normalized_tags())
+            .bind(tag_filter.match_all)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database query error: {}", e))?;
+
+        Self::dictionary_response(rows)
+    }
+
+    fn dictionary_response(rows: Vec<sqlx::postgres::PgRow>) -> Result<DataDictionaryResponse, String> {
         let mut attributes = Vec::new();
         let mut business_count = 0i64;
         let mut derived_count = 0i64;
@@ -89,7 +125,8 @@ impl DataDictionaryOperations {
                     .map_err(|e| format!("Failed to get entity_name: {}", e))?,
                 "is_key": false,
                 "is_nullable": !row.try_get::<bool, _>("required")
-                    .unwrap_or(true)
+                    .unwrap_or(true),
+                "tags": row.try_get::<Vec<String>, _>("tags").unwrap_or_default()
             });
             attributes.push(attr);
         }
@@ -0,0 +1,160 @@
+use super::{init_db_with_url, DbOperations, DbPool};
+use crate::editor_access::{EditorScope, EditorSession};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::Row;
+use tokio::sync::OnceCell;
+
+// Tokens editors present to the language servers; only their hashes are stored
+pub struct EditorTokenOperations;
+
+impl EditorTokenOperations {
+    // Issues a token for `subject`. This is the only time the token itself is seen.
+    pub async fn issue_editor_token(
+        pool: &DbPool,
+        subject: &str,
+        scope: &EditorScope,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<String, String> {
+        let token = format!("dde_{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+        sqlx::query("
+            INSERT INTO editor_access_tokens (token_hash, subject, attribute_patterns, rule_patterns, command_patterns, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+        ")
+            .bind(hash_token(&token))
+            .bind(subject)
+            .bind(&scope.attributes)
+            .bind(&scope.rules)
+            .bind(&scope.commands)
+            .bind(expires_at)
+            .execute(pool)
+            .await
+            .map_err(|e| format!("Failed to issue editor token: {}", e))?;
+        Ok(token)
+    }
+
+    // Revokes a token; false if it was unknown or already revoked
+    pub async fn revoke_editor_token(pool: &DbPool, token: &str) -> Result<bool, String> {
+        let result = sqlx::query("
+            UPDATE editor_access_tokens SET revoked_at = CURRENT_TIMESTAMP
+            WHERE token_hash = $1 AND revoked_at IS NULL
+        ")
+            .bind(hash_token(token))
+            .execute(pool)
+            .await
+            .map_err(|e| format!("Failed to revoke editor token: {}", e))?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // The session a token opens. Unknown, revoked and expired tokens are refused alike.
+    pub async fn validate_editor_token(pool: &DbPool, token: &str) -> Result<EditorSession, String> {
+        let row = sqlx::query("
+            UPDATE editor_access_tokens SET last_used_at = CURRENT_TIMESTAMP
+            WHERE token_hash = $1
+              AND revoked_at IS NULL
+              AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)
+            RETURNING subject, attribute_patterns, rule_patterns, command_patterns, expires_at
+        ")
+            .bind(hash_token(token))
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| format!("Failed to check editor token: {}", e))?
+            .ok_or_else(|| "Editor token is unknown, revoked or expired".to_string())?;
+
+        Ok(EditorSession {
+            subject: row.get("subject"),
+            scope: EditorScope {
+                attributes: row.get("attribute_patterns"),
+                rules: row.get("rule_patterns"),
+                commands: row.get("command_patterns"),
+            },
+            expires_at: row.get("expires_at"),
+        })
+    }
+
+    // The session of an editor presenting `token`. Without a token an editor
+    // reaches everything, unless [security] require_editor_tokens is set.
+    pub async fn authorize_editor(pool: &DbPool, token: Option<&str>) -> Result<EditorSession, String> {
+        match token {
+            Some(token) => Self::validate_editor_token(pool, token).await,
+            None if editor_tokens_required() => Err("This database requires an editor token (initializationOptions.authToken)".to_string()),
+            None => Ok(EditorSession::anonymous()),
+        }
+    }
+}
+
+fn editor_tokens_required() -> bool {
+    crate::config::Config::load().is_ok_and(|config| config.security.require_editor_tokens)
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.trim().as_bytes()))
+}
+
+/// The database as one editor sees it: connected on first use to the
+/// configured URL or else to the one in the app's configuration, and opened
+/// only to the session the editor's token grants
+#[derive(Default)]
+pub struct EditorDatabase {
+    url: Option<String>,
+    token: Option<String>,
+    pool: OnceCell<DbPool>,
+    session: OnceCell<EditorSession>,
+}
+
+// Leaves the token out of logs
+impl std::fmt::Debug for EditorDatabase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EditorDatabase")
+            .field("url", &self.url)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("session", &self.session.get())
+            .finish()
+    }
+}
+
+impl EditorDatabase {
+    pub fn new(url: Option<String>, token: Option<String>) -> Self {
+        Self { url, token, pool: OnceCell::new(), session: OnceCell::new() }
+    }
+
+    /// A database already connected by the embedding application
+    pub fn with_pool(pool: DbPool, token: Option<String>) -> Self {
+        Self { url: None, token, pool: OnceCell::new_with(Some(pool)), session: OnceCell::new() }
+    }
+
+    async fn pool(&self) -> Result<&DbPool, String> {
+        self.pool
+            .get_or_try_init(|| async {
+                match &self.url {
+                    Some(url) => init_db_with_url(url).await,
+                    None => DbOperations::get_pool().await,
+                }
+            })
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// The pool and what the editor may reach through it; an invalid token
+    /// is checked again on each use, a valid one until it expires
+    pub async fn session(&self) -> Result<(&DbPool, &EditorSession), String> {
+        let pool = self.pool().await?;
+        let session = self
+            .session
+            .get_or_try_init(|| EditorTokenOperations::authorize_editor(pool, self.token.as_deref()))
+            .await?;
+        if session.is_expired() {
+            return Err("Editor token has expired".to_string());
+        }
+        Ok((pool, session))
+    }
+
+    /// Whether the editor may run `command`: any without a token where none
+    /// is required, else those its token's scope lists
+    pub async fn allows_command(&self, command: &str) -> bool {
+        if self.token.is_none() && !editor_tokens_required() {
+            return true;
+        }
+        self.session().await.is_ok_and(|(_, session)| session.scope.allows_command(command))
+    }
+}
@@ -1,4 +1,5 @@
 use super::{DbPool, DbOperations};
+use crate::capabilities::{self, Capability};
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
 
@@ -13,12 +14,16 @@ pub struct SimilarRule {
 pub struct EmbeddingOperations;
 
 impl EmbeddingOperations {
-    /// Update embedding for a rule
+    /// Update embedding for a rule; skipped while embeddings are disabled
     pub async fn update_rule_embedding(
         pool: &DbPool,
         rule_id: &str,
         dsl_text: &str,
     ) -> Result<(), String> {
+        if !capabilities::enabled(Capability::Embeddings) {
+            return Ok(());
+        }
+
         // Generate embedding (placeholder - would use actual embedding service)
         let embedding_vec = Self::generate_embedding_placeholder(dsl_text);
 
@@ -32,12 +37,16 @@ impl EmbeddingOperations {
         Ok(())
     }
 
-    /// Find similar rules using vector similarity
+    /// Find similar rules using vector similarity; none while embeddings are disabled
     pub async fn find_similar_rules(
         pool: &DbPool,
         dsl_text: &str,
         limit: i32,
     ) -> Result<Vec<SimilarRule>, String> {
+        if !capabilities::enabled(Capability::Embeddings) {
+            return Ok(Vec::new());
+        }
+
         let embedding_vec = Self::generate_embedding_placeholder(dsl_text);
 
         let query = r#"
@@ -54,6 +63,10 @@ impl EmbeddingOperations {
 
     /// Generate embeddings for all rules (batch operation)
     pub async fn generate_all_embeddings(pool: &DbPool) -> Result<(), String> {
+        if !capabilities::enabled(Capability::Embeddings) {
+            return Ok(());
+        }
+
         let query = "SELECT rule_id, rule_definition FROM rules WHERE embedding_data IS NULL";
         let rules: Vec<(String, String)> = DbOperations::query_all(pool, query).await?;
 
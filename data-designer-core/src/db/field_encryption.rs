@@ -0,0 +1,138 @@
+use super::DbPool;
+use crate::field_encryption::{FieldCipher, SensitiveAttributes, SENSITIVE_TAG};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sqlx::Row;
+
+// Tables holding attribute values by attribute_name, encrypted when sensitive
+const VALUE_TABLES: [&str; 3] = ["attribute_value_snapshots", "attribute_derivations", "attribute_derivation_inputs"];
+
+// Rows read and rewritten per statement during a rotation
+const ROTATION_CHUNK: i64 = 500;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TableRotation {
+    pub table: String,
+    /// Values moved from an older key to the current one
+    pub reencrypted: i64,
+    /// Plain values of sensitive attributes, stored before they were tagged
+    pub encrypted: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RotationReport {
+    pub key_id: String,
+    pub tables: Vec<TableRotation>,
+}
+
+impl RotationReport {
+    pub fn reencrypted(&self) -> i64 {
+        self.tables.iter().map(|table| table.reencrypted).sum()
+    }
+
+    pub fn encrypted(&self) -> i64 {
+        self.tables.iter().map(|table| table.encrypted).sum()
+    }
+}
+
+// Sensitive attributes and rotation of the keys their values are encrypted with
+pub struct EncryptionOperations;
+
+impl EncryptionOperations {
+    // Attributes tagged sensitive in the dictionary, by full path
+    pub async fn sensitive_attributes(
+        pool: &DbPool,
+    ) -> Result<SensitiveAttributes, String> {
+        let rows = sqlx::query("
+            SELECT ta.target_key
+            FROM tag_assignments ta
+            JOIN tags t ON t.id = ta.tag_id
+            WHERE t.name = $1 AND ta.target_type = 'attribute'
+        ")
+            .bind(SENSITIVE_TAG)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Failed to load sensitive attributes: {}", e))?;
+
+        Ok(rows.iter().map(|row| row.get::<String, _>("target_key")).collect())
+    }
+
+    // Re-encrypt every value under an older key with the current one, and
+    // encrypt plain values of attributes tagged sensitive since they were stored.
+    // Rows are rewritten a chunk at a time, so a rotation can be rerun after a failure.
+    pub async fn rotate_keys(
+        pool: &DbPool,
+        cipher: &FieldCipher,
+        rotated_by: Option<&str>,
+    ) -> Result<RotationReport, String> {
+        let sensitive = Self::sensitive_attributes(pool).await?;
+        let mut tables = Vec::new();
+        for table in VALUE_TABLES {
+            tables.push(rotate_table(pool, table, cipher, &sensitive).await?);
+        }
+        let report = RotationReport { key_id: cipher.current_key_id().to_string(), tables };
+
+        sqlx::query("
+            INSERT INTO field_key_rotations (key_id, rows_reencrypted, rows_encrypted, rotated_by)
+            VALUES ($1, $2, $3, $4)
+        ")
+            .bind(&report.key_id)
+            .bind(report.reencrypted() as i32)
+            .bind(report.encrypted() as i32)
+            .bind(rotated_by)
+            .execute(pool)
+            .await
+            .map_err(|e| format!("Failed to log key rotation: {}", e))?;
+
+        Ok(report)
+    }
+}
+
+async fn rotate_table(
+    pool: &DbPool,
+    table: &str,
+    cipher: &FieldCipher,
+    sensitive: &SensitiveAttributes,
+) -> Result<TableRotation, String> {
+    let mut rotation = TableRotation { table: table.to_string(), ..Default::default() };
+    let mut after = 0i64;
+    loop {
+        let rows = sqlx::query(&format!("SELECT id, attribute_name, value FROM {} WHERE id > $1 ORDER BY id LIMIT $2", table))
+            .bind(after)
+            .bind(ROTATION_CHUNK)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", table, e))?;
+        let Some(last) = rows.last() else {
+            return Ok(rotation);
+        };
+        after = last.get("id");
+
+        let mut tx = pool.begin()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        for row in &rows {
+            let attribute: String = row.get("attribute_name");
+            let value: JsonValue = row.get("value");
+            let rewritten = if cipher.needs_rotation(&value) {
+                rotation.reencrypted += 1;
+                cipher.decrypt(&attribute, &value).map_err(|e| e.to_string())?
+            } else if !crate::field_encryption::is_encrypted(&value) && sensitive.contains(&attribute) {
+                rotation.encrypted += 1;
+                value
+            } else {
+                continue;
+            };
+            let sealed = cipher.encrypt(&attribute, &rewritten).map_err(|e| e.to_string())?;
+            sqlx::query(&format!("UPDATE {} SET value = $1 WHERE id = $2", table))
+                .bind(sealed)
+                .bind(row.get::<i64, _>("id"))
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to rewrite {} value: {}", table, e))?;
+        }
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to commit rotation of {}: {}", table, e))?;
+    }
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use crate::db::{DbPool, DbOperations};
+use crate::function_registry::FunctionRegistry;
+use crate::rule_bundle::GrammarExtensionSpec;
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct GrammarRule {
@@ -45,6 +47,27 @@ pub struct CompactGrammarInfo {
     pub kyc_attributes: Vec<String>,
 }
 
+impl CompactGrammarInfo {
+    /// Adds the host functions registered at runtime, so completion offers them too
+    pub fn with_registered_functions(mut self, registry: &FunctionRegistry) -> Self {
+        for function in registry.functions() {
+            if self.functions.iter().any(|known| known.name.eq_ignore_ascii_case(&function.name)) {
+                continue;
+            }
+            self.keywords.push(function.name.clone());
+            self.functions.push(FunctionInfo {
+                name: function.name,
+                signature: function.signature,
+                description: "Registered by the host application".to_string(),
+            });
+        }
+        self.keywords.sort();
+        self.keywords.dedup();
+        self.functions.sort_by(|a, b| a.name.cmp(&b.name));
+        self
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunctionInfo {
     pub name: String,
@@ -70,6 +93,18 @@ pub struct CreateGrammarExtensionRequest {
     pub category: Option<String>,
 }
 
+impl From<GrammarExtensionSpec> for CreateGrammarExtensionRequest {
+    fn from(spec: GrammarExtensionSpec) -> Self {
+        CreateGrammarExtensionRequest {
+            name: spec.name,
+            extension_type: spec.extension_type,
+            signature: spec.signature,
+            description: spec.description,
+            category: spec.category,
+        }
+    }
+}
+
 pub struct GrammarOperations;
 
 impl GrammarOperations {
@@ -209,6 +244,21 @@ impl GrammarOperations {
         Ok(row.0)
     }
 
+    /// Create a grammar extension, or update the one with the same name and type
+    pub async fn upsert_extension(pool: &DbPool, request: CreateGrammarExtensionRequest) -> Result<i32, String> {
+        let existing: Option<(i32,)> = sqlx::query_as("SELECT id FROM grammar_extensions WHERE name = $1 AND type = $2")
+            .bind(&request.name)
+            .bind(&request.extension_type)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| format!("Database query error: {}", e))?;
+
+        match existing {
+            Some((id,)) => Self::update_extension(pool, id, request).await.map(|_| id),
+            None => Self::create_extension(pool, request).await,
+        }
+    }
+
     /// Update a grammar rule
     pub async fn update_rule(pool: &DbPool, id: i32, request: CreateGrammarRuleRequest) -> Result<(), String> {
         let query = "UPDATE grammar_rules SET name = $2, definition = $3, rule_type = $4, description = $5, category = $6, updated_at = CURRENT_TIMESTAMP WHERE id = $1";
@@ -0,0 +1,167 @@
+use super::rules::{record_rule_version, RuleOperations};
+use super::DbPool;
+use crate::lookup_extraction::{extract_lookup_table, inline_lists_in, InlineList, LookupExtraction};
+use crate::rule_rewrite::parse_complete;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::Row;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManagedLookupTable {
+    pub name: String,
+    pub description: Option<String>,
+    pub entry_count: usize,
+    /// The rule the table was extracted from, if it was
+    pub source_rule_id: Option<String>,
+}
+
+// Lookup tables rules read with LOOKUP, and extracting them from inline lists
+pub struct LookupTableOperations;
+
+impl LookupTableOperations {
+    pub async fn list_lookup_tables(pool: &DbPool) -> Result<Vec<ManagedLookupTable>, String> {
+        let rows = sqlx::query("
+            SELECT name, description, (SELECT COUNT(*) FROM jsonb_object_keys(entries)) AS entry_count, source_rule_id
+            FROM lookup_tables
+            ORDER BY name
+        ")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Failed to list lookup tables: {}", e))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| ManagedLookupTable {
+                name: row.get("name"),
+                description: row.get("description"),
+                entry_count: row.get::<Option<i64>, _>("entry_count").unwrap_or(0) as usize,
+                source_rule_id: row.get("source_rule_id"),
+            })
+            .collect())
+    }
+
+    // Every table's entries, for a FunctionLibrary's lookup_tables
+    pub async fn load_lookup_tables(pool: &DbPool) -> Result<HashMap<String, HashMap<String, String>>, String> {
+        let rows: Vec<(String, Json<HashMap<String, String>>)> = sqlx::query_as("SELECT name, entries FROM lookup_tables")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Failed to load lookup tables: {}", e))?;
+
+        Ok(rows.into_iter().map(|(name, Json(entries))| (name, entries)).collect())
+    }
+
+    // The rule's definition and the inline lists of at least `min_values` values in it,
+    // with suggested table names that are not taken yet
+    pub async fn find_inline_lists(
+        pool: &DbPool,
+        rule_id: &str,
+        min_values: usize,
+    ) -> Result<(String, Vec<InlineList>), String> {
+        let definition: Option<String> = sqlx::query_scalar("SELECT rule_definition FROM rules WHERE rule_id = $1")
+            .bind(rule_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        let definition = definition.ok_or_else(|| format!("Rule not found: {}", rule_id))?;
+        let ast = parse_complete(&definition)?;
+
+        let existing: Vec<String> = sqlx::query_scalar("SELECT name FROM lookup_tables")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        let lists = inline_lists_in(&ast, min_values, |name| existing.iter().any(|taken| taken == name));
+
+        Ok((definition, lists))
+    }
+
+    // Move one inline list of a rule into the lookup table `table` and save the
+    // rewritten rule as a new version, in one transaction. A table of that name
+    // is reused if it holds exactly the list's values. `expected_definition` is
+    // what the lists were found in, so a rule changed since is refused.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn extract_lookup_table(
+        pool: &DbPool,
+        rule_id: &str,
+        expected_definition: &str,
+        index: usize,
+        table: &str,
+        description: Option<&str>,
+        min_values: usize,
+        applied_by: Option<&str>,
+    ) -> Result<LookupExtraction, String> {
+        let categories = RuleOperations::get_rule_categories(pool).await?;
+        let mut tx = pool.begin()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let current: Option<(String, Option<i32>)> = sqlx::query_as("SELECT rule_definition, category_id FROM rules WHERE rule_id = $1 FOR UPDATE")
+            .bind(rule_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        let (current, category_id) = current.ok_or_else(|| format!("Rule not found: {}", rule_id))?;
+        if current.trim() != expected_definition.trim() {
+            return Err(format!("Rule {} has changed since its lists were found; look again", rule_id));
+        }
+
+        let extraction = extract_lookup_table(&current, index, table, min_values)?;
+        let ast = parse_complete(&extraction.definition)?;
+        if let Some(category_id) = category_id {
+            let violations = categories.check_rule(category_id, &ast);
+            if !violations.is_empty() {
+                let messages: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+                return Err(messages.join("; "));
+            }
+        }
+
+        let existing: Option<Json<BTreeMap<String, String>>> = sqlx::query_scalar("SELECT entries FROM lookup_tables WHERE name = $1 FOR UPDATE")
+            .bind(table)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        match existing {
+            Some(Json(entries)) if entries == extraction.entries => {}
+            Some(_) => return Err(format!("A lookup table named '{}' already exists with other entries", table)),
+            None => {
+                sqlx::query("
+                    INSERT INTO lookup_tables (name, description, entries, source_rule_id, created_by)
+                    VALUES ($1, $2, $3, $4, $5)
+                ")
+                    .bind(table)
+                    .bind(description)
+                    .bind(Json(&extraction.entries))
+                    .bind(rule_id)
+                    .bind(applied_by)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| format!("Failed to register lookup table: {}", e))?;
+            }
+        }
+
+        let parsed_ast = serde_json::to_value(&ast)
+            .map_err(|e| format!("Failed to serialize rule AST: {}", e))?;
+        sqlx::query("
+            UPDATE rules
+            SET rule_definition = $2, parsed_ast = $3, version = COALESCE(version, 1) + 1,
+                updated_by = $4, updated_at = CURRENT_TIMESTAMP
+            WHERE rule_id = $1
+        ")
+            .bind(rule_id)
+            .bind(&extraction.definition)
+            .bind(&parsed_ast)
+            .bind(applied_by)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to update rule: {}", e))?;
+
+        let change = format!("Extract {} values into lookup table '{}'", extraction.entries.len(), table);
+        record_rule_version(&mut tx, rule_id, &change, applied_by).await?;
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+        Ok(extraction)
+    }
+}
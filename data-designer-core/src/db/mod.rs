@@ -40,6 +40,19 @@ pub async fn init_db_with_config(config: Option<crate::config::Config>) -> Resul
     Ok(pool)
 }
 
+// Connect to the database at `database_url`, with the configured pool sizes
+pub async fn init_db_with_url(database_url: &str) -> Result<DbPool> {
+    let config = crate::config::Config::load().unwrap_or_default();
+    let pool = PgPoolOptions::new()
+        .max_connections(config.database.max_connections)
+        .min_connections(config.database.min_connections)
+        .acquire_timeout(std::time::Duration::from_secs(config.database.acquire_timeout_seconds))
+        .idle_timeout(std::time::Duration::from_secs(config.database.idle_timeout_seconds))
+        .connect(database_url)
+        .await?;
+    Ok(pool)
+}
+
 // Centralized database operations interface for the entire IDE
 pub struct DbOperations;
 
@@ -61,6 +74,7 @@ impl DbOperations {
     // === QUERY HELPERS ===
 
     /// Execute a simple count query with one parameter
+    #[tracing::instrument(name = "db.query", skip_all, fields(db.statement = query))]
     pub async fn query_count(
         pool: &DbPool,
         query: &str,
@@ -76,6 +90,7 @@ impl DbOperations {
 
 
     /// Execute a query that returns multiple typed results
+    #[tracing::instrument(name = "db.query", skip_all, fields(db.statement = query))]
     pub async fn query_all<T>(
         pool: &DbPool,
         query: &str,
@@ -92,6 +107,7 @@ impl DbOperations {
 
 
     /// Execute a query with two parameters
+    #[tracing::instrument(name = "db.query", skip_all, fields(db.statement = query))]
     pub async fn query_all_with_two_params<T, P1, P2>(
         pool: &DbPool,
         query: &str,
@@ -112,6 +128,7 @@ impl DbOperations {
     }
 
     /// Execute a command (INSERT, UPDATE, DELETE) and return affected rows
+    #[tracing::instrument(name = "db.query", skip_all, fields(db.statement = query))]
     pub async fn execute(
         pool: &DbPool,
         query: &str,
@@ -126,6 +143,7 @@ impl DbOperations {
 
 
     /// Execute a command with two parameters
+    #[tracing::instrument(name = "db.query", skip_all, fields(db.statement = query))]
     pub async fn execute_with_two_params<P1, P2>(
         pool: &DbPool,
         query: &str,
@@ -146,6 +164,7 @@ impl DbOperations {
     }
 
     // Query helper that returns raw rows - no params version
+    #[tracing::instrument(name = "db.query", skip_all, fields(db.statement = query))]
     pub async fn query_raw_all_no_params(
         pool: &DbPool,
         query: &str,
@@ -157,6 +176,7 @@ impl DbOperations {
     }
 
     // Query helper that returns raw rows - one string param
+    #[tracing::instrument(name = "db.query", skip_all, fields(db.statement = query))]
     pub async fn query_raw_all_one_param(
         pool: &DbPool,
         query: &str,
@@ -170,6 +190,7 @@ impl DbOperations {
     }
 
     /// Execute a parameterized query with one parameter
+    #[tracing::instrument(name = "db.query", skip_all, fields(db.statement = query))]
     pub async fn query_one_with_param<T>(
         pool: &DbPool,
         query: &str,
@@ -186,6 +207,7 @@ impl DbOperations {
     }
 
     /// Execute a query that returns a single typed result
+    #[tracing::instrument(name = "db.query", skip_all, fields(db.statement = query))]
     pub async fn query_one<T>(
         pool: &DbPool,
         query: &str,
@@ -200,6 +222,7 @@ impl DbOperations {
     }
 
     /// Execute a parameterized query that returns multiple results
+    #[tracing::instrument(name = "db.query", skip_all, fields(db.statement = query))]
     pub async fn query_all_with_param<T>(
         pool: &DbPool,
         query: &str,
@@ -216,6 +239,7 @@ impl DbOperations {
     }
 
     /// Execute a parameterized command
+    #[tracing::instrument(name = "db.query", skip_all, fields(db.statement = query))]
     pub async fn execute_with_param<P>(
         pool: &DbPool,
         query: &str,
@@ -233,6 +257,7 @@ impl DbOperations {
     }
 
     // Query helper that returns raw rows - two params
+    #[tracing::instrument(name = "db.query", skip_all, fields(db.statement = query))]
     pub async fn query_raw_all_two_params(
         pool: &DbPool,
         query: &str,
@@ -248,6 +273,7 @@ impl DbOperations {
     }
 
     // Query helper that returns raw rows - one i32 param
+    #[tracing::instrument(name = "db.query", skip_all, fields(db.statement = query))]
     pub async fn query_raw_all_one_i32_param(
         pool: &DbPool,
         query: &str,
@@ -264,6 +290,9 @@ impl DbOperations {
 
 // Database entity modules
 pub mod rules;
+pub mod rule_store;
+pub mod rule_tests;
+pub mod rule_templates;
 pub mod attributes;
 pub mod schema;
 pub mod embeddings;
@@ -274,9 +303,22 @@ pub mod products;
 pub mod config_driven;
 pub mod persistence;
 pub mod resource_sheets;
+pub mod tags;
+pub mod bulk_edits;
+pub mod retention;
+pub mod batch_writes;
+pub mod attribute_usage;
+pub mod provenance;
+pub mod read_models;
+pub mod field_encryption;
+pub mod lookup_tables;
+pub mod editor_tokens;
 
 // Re-export all database entities and operations
 pub use rules::*;
+pub use rule_store::*;
+pub use rule_tests::*;
+pub use rule_templates::*;
 pub use schema::*;
 pub use persistence::*;
 pub use embeddings::*;
@@ -285,6 +327,16 @@ pub use cbu::*;
 pub use products::*;
 pub use config_driven::*;
 pub use resource_sheets::*;
+pub use tags::*;
+pub use bulk_edits::*;
+pub use retention::*;
+pub use batch_writes::*;
+pub use attribute_usage::*;
+pub use provenance::*;
+pub use read_models::*;
+pub use field_encryption::*;
+pub use lookup_tables::*;
+pub use editor_tokens::*;
 
 // Legacy compatibility
 pub use self::rules::CreateRuleRequest;
\ No newline at end of file
@@ -2,9 +2,13 @@
 // This module provides the live data connection layer for the AI Context Engine
 
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use crate::capabilities::{self, Capability};
 use crate::db::DbPool;
 use anyhow::Result;
 use sqlx::Row;
@@ -69,6 +73,80 @@ impl From<LiteralValue> for JsonValue {
     }
 }
 
+/// A connector that couldn't be reached at all, as opposed to one rejecting
+/// the request; only these take it out of service
+#[derive(Debug, thiserror::Error)]
+#[error("{service} is unavailable: {reason}")]
+pub struct ConnectorUnavailable {
+    pub service: &'static str,
+    pub reason: String,
+}
+
+/// A live value, and whether it is the last one read before its connector went down
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveValue {
+    pub value: LiteralValue,
+    pub stale: bool,
+    /// When the connector returned it
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// Consecutive failures after which a connector is taken out of service
+const FAILURE_THRESHOLD: u32 = 3;
+/// How long a connector stays out of service before a read tries it again
+const RETRY_AFTER_SECONDS: i64 = 30;
+/// Values kept to fall back on; the oldest goes first
+const MAX_CACHED_VALUES: usize = 10_000;
+
+/// How a connector has been answering reads and heartbeats
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectorHealth {
+    pub service: String,
+    pub available: bool,
+    pub consecutive_failures: u32,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_failure: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+impl ConnectorHealth {
+    fn new(service: &str) -> Self {
+        Self {
+            service: service.to_string(),
+            available: true,
+            consecutive_failures: 0,
+            last_success: None,
+            last_failure: None,
+            last_error: None,
+        }
+    }
+
+    fn record_success(&mut self) {
+        if !self.available {
+            log::info!("{} recovered after {} failures", self.service, self.consecutive_failures);
+        }
+        self.available = true;
+        self.consecutive_failures = 0;
+        self.last_success = Some(Utc::now());
+    }
+
+    fn record_failure(&mut self, error: &anyhow::Error) {
+        self.consecutive_failures += 1;
+        self.last_failure = Some(Utc::now());
+        self.last_error = Some(error.to_string());
+        if self.available && self.consecutive_failures >= FAILURE_THRESHOLD {
+            log::warn!("{} is down after {} failures, serving cached values: {}", self.service, self.consecutive_failures, error);
+            self.available = false;
+        }
+    }
+
+    // Out of service connectors are tried again once in a while, so a read
+    // notices recovery even without heartbeats
+    fn should_try(&self) -> bool {
+        self.available || self.last_failure.is_none_or(|at| Utc::now() - at >= chrono::Duration::seconds(RETRY_AFTER_SECONDS))
+    }
+}
+
 // Core trait for data access abstraction
 #[async_trait]
 pub trait PersistenceService: Send + Sync {
@@ -86,6 +164,11 @@ pub trait PersistenceService: Send + Sync {
 
     /// Get service name for debugging
     fn service_name(&self) -> &'static str;
+
+    /// Check the connection is alive, without reading anything
+    async fn heartbeat(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 // PostgreSQL-based persistence service for EntityMasterDB, ComplianceDB, etc.
@@ -136,7 +219,8 @@ impl PersistenceService for PostgresPersistenceService {
         let row = sqlx::query(&query)
             .bind(key)
             .fetch_optional(&self.pool)
-            .await?;
+            .await
+            .map_err(|e| self.connection_error(e))?;
 
         if let Some(row) = row {
             // Extract value by column name
@@ -157,6 +241,7 @@ impl PersistenceService for PostgresPersistenceService {
                 Ok(value) => {
                     results.insert(key.clone(), value);
                 },
+                Err(e) if e.is::<ConnectorUnavailable>() => return Err(e),
                 Err(_) => {
                     results.insert(key.clone(), LiteralValue::Null);
                 }
@@ -180,9 +265,32 @@ impl PersistenceService for PostgresPersistenceService {
     fn service_name(&self) -> &'static str {
         "PostgresPersistenceService"
     }
+
+    async fn heartbeat(&self) -> Result<()> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| self.connection_error(e))?;
+        Ok(())
+    }
 }
 
 impl PostgresPersistenceService {
+    // Failures to reach the database, as opposed to a bad query
+    fn connection_error(&self, error: sqlx::Error) -> anyhow::Error {
+        match error {
+            sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::Protocol(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed => {
+                ConnectorUnavailable { service: self.service_name(), reason: error.to_string() }.into()
+            }
+            other => other.into(),
+        }
+    }
+
     // Generate realistic mock data for demonstration
     fn generate_mock_data(&self, locator: &PersistenceLocator, key: &str) -> LiteralValue {
         match (locator.system.as_str(), locator.entity.as_str(), locator.identifier.as_str()) {
@@ -288,9 +396,14 @@ impl PersistenceService for RedisPersistenceService {
     }
 }
 
-// Composite service that routes to appropriate implementations
+// Composite service that routes to appropriate implementations. A connector
+// that stops answering is taken out of service and reads fall back to the
+// last values it returned, marked stale, until a heartbeat or a retried read
+// finds it back.
 pub struct CompositePersistenceService {
     services: Vec<Box<dyn PersistenceService>>,
+    health: Mutex<Vec<ConnectorHealth>>,
+    cache: Mutex<HashMap<(String, String, String, String), LiveValue>>,
 }
 
 impl Default for CompositePersistenceService {
@@ -303,10 +416,13 @@ impl CompositePersistenceService {
     pub fn new() -> Self {
         Self {
             services: Vec::new(),
+            health: Mutex::new(Vec::new()),
+            cache: Mutex::new(HashMap::new()),
         }
     }
 
     pub fn add_service(&mut self, service: Box<dyn PersistenceService>) {
+        self.health.lock().unwrap().push(ConnectorHealth::new(service.service_name()));
         self.services.push(service);
     }
 
@@ -319,40 +435,146 @@ impl CompositePersistenceService {
         self.add_service(Box::new(RedisPersistenceService::new(connection_string)));
         self
     }
+
+    // The service handling `locator`; none while live data is disabled, so
+    // no connection to the system behind it is attempted
+    fn route(&self, locator: &PersistenceLocator) -> Result<(usize, &dyn PersistenceService)> {
+        if !capabilities::enabled(Capability::LiveData) {
+            return Err(anyhow::anyhow!("Live data is disabled in this deployment: {} is not read", locator.system));
+        }
+        self.services
+            .iter()
+            .position(|service| service.can_handle(locator))
+            .map(|index| (index, self.services[index].as_ref()))
+            .ok_or_else(|| anyhow::anyhow!("No service can handle system: {}", locator.system))
+    }
+
+    /// Reads `keys`, falling back to the last values read for them while the
+    /// connector is down; those come back with `stale` set
+    pub async fn fetch_live_data(&self, locator: &PersistenceLocator, keys: &[String]) -> Result<HashMap<String, LiveValue>> {
+        let (index, service) = self.route(locator)?;
+        self.read(index, locator, keys, service.get_values(locator, keys)).await
+    }
+
+    // Awaits `fetch` unless the connector is out of service, recording how
+    // it answered; the cache stands in when it doesn't
+    async fn read(
+        &self,
+        index: usize,
+        locator: &PersistenceLocator,
+        keys: &[String],
+        fetch: impl std::future::Future<Output = Result<HashMap<String, LiteralValue>>>,
+    ) -> Result<HashMap<String, LiveValue>> {
+        let error = if self.health.lock().unwrap()[index].should_try() {
+            match fetch.await {
+                Ok(values) => {
+                    self.health.lock().unwrap()[index].record_success();
+                    return Ok(self.remember(locator, values));
+                }
+                Err(e) if e.is::<ConnectorUnavailable>() => {
+                    self.health.lock().unwrap()[index].record_failure(&e);
+                    e
+                }
+                Err(e) => return Err(e),
+            }
+        } else {
+            anyhow::anyhow!("{} is out of service", self.services[index].service_name())
+        };
+
+        let values = self.cached(locator, keys);
+        if values.is_empty() {
+            return Err(error.context(format!("No cached values for {}.{}", locator.system, locator.entity)));
+        }
+        log::warn!("Serving {} stale values for {}.{}: {}", values.len(), locator.system, locator.entity, error);
+        Ok(values)
+    }
+
+    /// Heartbeats every connector, marking it up or down
+    pub async fn check_health(&self) -> Vec<ConnectorHealth> {
+        for (index, service) in self.services.iter().enumerate() {
+            let result = service.heartbeat().await;
+            let mut health = self.health.lock().unwrap();
+            match result {
+                Ok(()) => health[index].record_success(),
+                Err(e) => health[index].record_failure(&e),
+            }
+        }
+        self.connector_health()
+    }
+
+    pub fn connector_health(&self) -> Vec<ConnectorHealth> {
+        self.health.lock().unwrap().clone()
+    }
+
+    /// Heartbeats the connectors every `interval`, so one that comes back is
+    /// used again without waiting for a read to retry it
+    pub fn spawn_heartbeat(self: Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                self.check_health().await;
+            }
+        })
+    }
+
+    // Keeps freshly read values to fall back on
+    fn remember(&self, locator: &PersistenceLocator, values: HashMap<String, LiteralValue>) -> HashMap<String, LiveValue> {
+        let fetched_at = Utc::now();
+        let mut cache = self.cache.lock().unwrap();
+        values
+            .into_iter()
+            .map(|(key, value)| {
+                let live = LiveValue { value, stale: false, fetched_at };
+                if cache.len() >= MAX_CACHED_VALUES {
+                    let oldest = cache.iter().min_by_key(|(_, cached)| cached.fetched_at).map(|(key, _)| key.clone());
+                    oldest.map(|oldest| cache.remove(&oldest));
+                }
+                cache.insert(cache_key(locator, &key), live.clone());
+                (key, live)
+            })
+            .collect()
+    }
+
+    fn cached(&self, locator: &PersistenceLocator, keys: &[String]) -> HashMap<String, LiveValue> {
+        let cache = self.cache.lock().unwrap();
+        keys.iter()
+            .filter_map(|key| {
+                let cached = cache.get(&cache_key(locator, key))?;
+                Some((key.clone(), LiveValue { stale: true, ..cached.clone() }))
+            })
+            .collect()
+    }
+}
+
+fn cache_key(locator: &PersistenceLocator, key: &str) -> (String, String, String, String) {
+    (locator.system.clone(), locator.entity.clone(), locator.identifier.clone(), key.to_string())
 }
 
 #[async_trait]
 impl PersistenceService for CompositePersistenceService {
     async fn get_value(&self, locator: &PersistenceLocator, key: &str) -> Result<LiteralValue> {
-        for service in &self.services {
-            if service.can_handle(locator) {
-                log::debug!("Using {} for {}.{}", service.service_name(), locator.system, locator.entity);
-                return service.get_value(locator, key).await;
-            }
-        }
-        Err(anyhow::anyhow!("No service can handle system: {}", locator.system))
+        let (index, service) = self.route(locator)?;
+        log::debug!("Using {} for {}.{}", service.service_name(), locator.system, locator.entity);
+        let fetch = async { Ok(HashMap::from([(key.to_string(), service.get_value(locator, key).await?)])) };
+        let mut values = self.read(index, locator, &[key.to_string()], fetch).await?;
+        values
+            .remove(key)
+            .map(|live| live.value)
+            .ok_or_else(|| anyhow::anyhow!("No value for {} in {}.{}", key, locator.system, locator.entity))
     }
 
     async fn get_values(&self, locator: &PersistenceLocator, keys: &[String]) -> Result<HashMap<String, LiteralValue>> {
-        for service in &self.services {
-            if service.can_handle(locator) {
-                return service.get_values(locator, keys).await;
-            }
-        }
-        Err(anyhow::anyhow!("No service can handle system: {}", locator.system))
+        let values = self.fetch_live_data(locator, keys).await?;
+        Ok(values.into_iter().map(|(key, live)| (key, live.value)).collect())
     }
 
     async fn set_value(&self, locator: &PersistenceLocator, key: &str, value: LiteralValue) -> Result<()> {
-        for service in &self.services {
-            if service.can_handle(locator) {
-                return service.set_value(locator, key, value).await;
-            }
-        }
-        Err(anyhow::anyhow!("No service can handle system: {}", locator.system))
+        self.route(locator)?.1.set_value(locator, key, value).await
     }
 
     fn can_handle(&self, locator: &PersistenceLocator) -> bool {
-        self.services.iter().any(|s| s.can_handle(locator))
+        self.route(locator).is_ok()
     }
 
     fn service_name(&self) -> &'static str {
@@ -0,0 +1,184 @@
+use super::{DbPool, EncryptionOperations};
+use crate::field_encryption::{self, SensitiveAttributes};
+use crate::provenance::{provenance_tree, AttributeProvenance, DerivationRecord, InputRecord, Materialization};
+use chrono::{DateTime, Utc};
+use serde_json::Value as JsonValue;
+use sqlx::Row;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+// Recorded materializations of derived attributes, and explaining their values
+pub struct ProvenanceOperations;
+
+impl ProvenanceOperations {
+    // Record how a client's derived attributes were computed: the source values
+    // read, as snapshots taken at captured_at, and each derivation with its inputs.
+    // Returns the id shared by the materialization's derivations.
+    pub async fn record_materialization(
+        pool: &DbPool,
+        cbu_id: &str,
+        materialization: &Materialization,
+        captured_at: DateTime<Utc>,
+    ) -> Result<Uuid, String> {
+        let materialization_id = Uuid::new_v4();
+        let materialized_at = Utc::now();
+        // Values of sensitive attributes are stored encrypted when a key is configured
+        let sensitive = match field_encryption::installed() {
+            Some(_) => EncryptionOperations::sensitive_attributes(pool).await?,
+            None => SensitiveAttributes::default(),
+        };
+        let seal = |attribute: &str, value: &JsonValue| {
+            field_encryption::seal(attribute, value, &sensitive).map_err(|e| e.to_string())
+        };
+        let mut tx = pool.begin()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let mut snapshots: HashMap<&str, i64> = HashMap::new();
+        for (attribute, value) in materialization.source_values() {
+            let (id,): (i64,) = sqlx::query_as("
+                INSERT INTO attribute_value_snapshots (cbu_id, attribute_name, value, captured_at)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id
+            ")
+                .bind(cbu_id)
+                .bind(attribute)
+                .bind(seal(attribute, value)?)
+                .bind(captured_at)
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to snapshot {}: {}", attribute, e))?;
+            snapshots.insert(attribute, id);
+        }
+
+        // Derivations come in evaluation order, so derived inputs are always recorded first
+        let mut derivations: HashMap<&str, i64> = HashMap::new();
+        for derivation in &materialization.derivations {
+            let (id,): (i64,) = sqlx::query_as("
+                INSERT INTO attribute_derivations
+                    (materialization_id, cbu_id, attribute_name, value, rule_id, rule_version, materialized_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                RETURNING id
+            ")
+                .bind(materialization_id)
+                .bind(cbu_id)
+                .bind(&derivation.attribute)
+                .bind(seal(&derivation.attribute, &derivation.value)?)
+                .bind(&derivation.rule_id)
+                .bind(derivation.rule_version)
+                .bind(materialized_at)
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to record derivation of {}: {}", derivation.attribute, e))?;
+
+            for input in &derivation.inputs {
+                let (snapshot_id, derived_from) = if input.derived {
+                    (None, derivations.get(input.attribute.as_str()).copied())
+                } else {
+                    (snapshots.get(input.attribute.as_str()).copied(), None)
+                };
+                sqlx::query("
+                    INSERT INTO attribute_derivation_inputs (derivation_id, attribute_name, value, snapshot_id, derived_from)
+                    VALUES ($1, $2, $3, $4, $5)
+                ")
+                    .bind(id)
+                    .bind(&input.attribute)
+                    .bind(seal(&input.attribute, &input.value)?)
+                    .bind(snapshot_id)
+                    .bind(derived_from)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| format!("Failed to record input {} of {}: {}", input.attribute, derivation.attribute, e))?;
+            }
+            derivations.insert(&derivation.attribute, id);
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+        Ok(materialization_id)
+    }
+
+    // Why a client's attribute has its value: the latest recorded derivation of it,
+    // with its inputs traced back through other derivations to the snapshots read
+    pub async fn get_provenance(
+        pool: &DbPool,
+        cbu_id: &str,
+        attribute: &str,
+    ) -> Result<Option<AttributeProvenance>, String> {
+        let latest = sqlx::query("
+            SELECT id, materialization_id
+            FROM attribute_derivations
+            WHERE cbu_id = $1 AND attribute_name = $2
+            ORDER BY materialized_at DESC, id DESC
+            LIMIT 1
+        ")
+            .bind(cbu_id)
+            .bind(attribute)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        let Some(latest) = latest else {
+            return Ok(None);
+        };
+        let root: i64 = latest.get("id");
+        let materialization_id: Uuid = latest.get("materialization_id");
+
+        let derivation_rows = sqlx::query("
+            SELECT id, attribute_name, value, rule_id, rule_version, materialized_at
+            FROM attribute_derivations
+            WHERE materialization_id = $1
+        ")
+            .bind(materialization_id)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        let input_rows = sqlx::query("
+            SELECT i.derivation_id, i.attribute_name, i.value, i.snapshot_id, s.captured_at, i.derived_from
+            FROM attribute_derivation_inputs i
+            JOIN attribute_derivations d ON d.id = i.derivation_id
+            LEFT JOIN attribute_value_snapshots s ON s.id = i.snapshot_id
+            WHERE d.materialization_id = $1
+            ORDER BY i.id
+        ")
+            .bind(materialization_id)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        let derivations: Vec<DerivationRecord> = derivation_rows.iter().map(|row| {
+            let attribute: String = row.get("attribute_name");
+            Ok(DerivationRecord {
+                id: row.get("id"),
+                value: open_value(&attribute, row)?,
+                attribute,
+                rule_id: row.get("rule_id"),
+                rule_version: row.get("rule_version"),
+                materialized_at: row.get("materialized_at"),
+            })
+        }).collect::<Result<_, String>>()?;
+
+        let inputs: Vec<InputRecord> = input_rows.iter().map(|row| {
+            let attribute: String = row.get("attribute_name");
+            let snapshot_id: Option<i64> = row.get("snapshot_id");
+            let captured_at: Option<DateTime<Utc>> = row.get("captured_at");
+            Ok(InputRecord {
+                derivation_id: row.get("derivation_id"),
+                value: open_value(&attribute, row)?,
+                attribute,
+                snapshot: snapshot_id.zip(captured_at),
+                derived_from: row.get("derived_from"),
+            })
+        }).collect::<Result<_, String>>()?;
+
+        Ok(provenance_tree(cbu_id, root, &derivations, &inputs))
+    }
+}
+
+// A row's value column as it was recorded, decrypted if it is sensitive
+fn open_value(attribute: &str, row: &sqlx::postgres::PgRow) -> Result<JsonValue, String> {
+    field_encryption::open(attribute, &row.get::<JsonValue, _>("value")).map_err(|e| e.to_string())
+}
@@ -0,0 +1,109 @@
+use super::DbPool;
+use crate::read_models::{ReadModel, ReadModelVersion};
+use serde_json::Value as JsonValue;
+use sqlx::Row;
+
+// Materialized read models behind the product, taxonomy and onboarding views
+pub struct ReadModelOperations;
+
+impl ReadModelOperations {
+    pub async fn versions(
+        pool: &DbPool,
+    ) -> Result<Vec<ReadModelVersion>, String> {
+        let rows = sqlx::query("SELECT read_model, version, stale, refreshed_at FROM read_model_versions ORDER BY read_model")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        rows.iter().map(version_from_row).collect()
+    }
+
+    pub async fn version(
+        pool: &DbPool,
+        model: ReadModel,
+    ) -> Result<ReadModelVersion, String> {
+        let row = sqlx::query("SELECT read_model, version, stale, refreshed_at FROM read_model_versions WHERE read_model = $1")
+            .bind(model.as_str())
+            .fetch_one(pool)
+            .await
+            .map_err(|e| format!("Failed to get {} version: {}", model.as_str(), e))?;
+
+        version_from_row(&row)
+    }
+
+    // Refresh without blocking readers, then bump the version. The stale flag is
+    // cleared first, so a write landing during the refresh marks it stale again.
+    pub async fn refresh(
+        pool: &DbPool,
+        model: ReadModel,
+    ) -> Result<ReadModelVersion, String> {
+        sqlx::query("UPDATE read_model_versions SET stale = FALSE WHERE read_model = $1")
+            .bind(model.as_str())
+            .execute(pool)
+            .await
+            .map_err(|e| format!("Failed to clear {} stale flag: {}", model.as_str(), e))?;
+
+        sqlx::query(&format!("REFRESH MATERIALIZED VIEW CONCURRENTLY {}", model.view()))
+            .execute(pool)
+            .await
+            .map_err(|e| format!("Failed to refresh {}: {}", model.view(), e))?;
+
+        let row = sqlx::query("
+            UPDATE read_model_versions SET version = version + 1, refreshed_at = CURRENT_TIMESTAMP
+            WHERE read_model = $1
+            RETURNING read_model, version, stale, refreshed_at
+        ")
+            .bind(model.as_str())
+            .fetch_one(pool)
+            .await
+            .map_err(|e| format!("Failed to bump {} version: {}", model.as_str(), e))?;
+
+        version_from_row(&row)
+    }
+
+    // Refresh every read model whose source tables changed since its last refresh
+    pub async fn refresh_stale(
+        pool: &DbPool,
+    ) -> Result<Vec<ReadModelVersion>, String> {
+        let mut refreshed = Vec::new();
+        for version in Self::versions(pool).await?.into_iter().filter(|version| version.stale) {
+            refreshed.push(Self::refresh(pool, version.read_model).await?);
+        }
+        Ok(refreshed)
+    }
+
+    // Rows of a read model as JSON objects, optionally only those whose
+    // filter column equals `filter`
+    pub async fn read(
+        pool: &DbPool,
+        model: ReadModel,
+        filter: Option<&str>,
+    ) -> Result<Vec<JsonValue>, String> {
+        let condition = match filter {
+            Some(_) => format!("WHERE m.{} = $1", model.filter_column()),
+            None => String::new(),
+        };
+        let query = format!("SELECT to_jsonb(m) AS row FROM {} m {} ORDER BY {}", model.view(), condition, model.order_by());
+
+        let mut query = sqlx::query(&query);
+        if let Some(filter) = filter {
+            query = query.bind(filter);
+        }
+        let rows = query
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", model.view(), e))?;
+
+        Ok(rows.iter().map(|row| row.get("row")).collect())
+    }
+}
+
+fn version_from_row(row: &sqlx::postgres::PgRow) -> Result<ReadModelVersion, String> {
+    let read_model: String = row.get("read_model");
+    Ok(ReadModelVersion {
+        read_model: ReadModel::parse(&read_model).ok_or_else(|| format!("Unknown read model '{}'", read_model))?,
+        version: row.get("version"),
+        stale: row.get("stale"),
+        refreshed_at: row.get("refreshed_at"),
+    })
+}
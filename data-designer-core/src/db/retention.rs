@@ -0,0 +1,334 @@
+use super::DbPool;
+use crate::retention::{
+    erasure_pseudonym, ErasureReport, PurgeReport, RetentionAction, RetentionEntity, RetentionPolicy, SubjectIdentifiers,
+};
+use chrono::{DateTime, Utc};
+use serde_json::Value as JsonValue;
+use sqlx::{PgConnection, Row};
+
+// Retention policy and GDPR erasure operations
+pub struct RetentionOperations;
+
+impl RetentionOperations {
+    // One policy per entity type
+    pub async fn list_policies(
+        pool: &DbPool,
+    ) -> Result<Vec<RetentionPolicy>, String> {
+        let rows = sqlx::query("SELECT entity_type, retain_days, action, enabled FROM retention_policies ORDER BY entity_type")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        rows.iter().map(policy_from_row).collect()
+    }
+
+    pub async fn set_policy(
+        pool: &DbPool,
+        policy: &RetentionPolicy,
+        updated_by: Option<&str>,
+    ) -> Result<RetentionPolicy, String> {
+        policy.validate()?;
+        sqlx::query("
+            INSERT INTO retention_policies (entity_type, retain_days, action, enabled, updated_by, updated_at)
+            VALUES ($1, $2, $3, $4, $5, CURRENT_TIMESTAMP)
+            ON CONFLICT (entity_type) DO UPDATE SET
+                retain_days = EXCLUDED.retain_days,
+                action = EXCLUDED.action,
+                enabled = EXCLUDED.enabled,
+                updated_by = EXCLUDED.updated_by,
+                updated_at = EXCLUDED.updated_at
+        ")
+            .bind(policy.entity_type.as_str())
+            .bind(policy.retain_days)
+            .bind(policy.action.as_str())
+            .bind(policy.enabled)
+            .bind(updated_by)
+            .execute(pool)
+            .await
+            .map_err(|e| format!("Failed to save retention policy: {}", e))?;
+
+        Ok(policy.clone())
+    }
+
+    // Delete or anonymize every record older than its enabled policy allows
+    pub async fn purge_expired(
+        pool: &DbPool,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<PurgeReport>, String> {
+        let mut reports = Vec::new();
+        for policy in Self::list_policies(pool).await?.into_iter().filter(|policy| policy.enabled) {
+            let entity = policy.entity_type;
+            let cutoff = policy.cutoff(now);
+            let query = match policy.action {
+                RetentionAction::Delete => {
+                    format!("DELETE FROM {} WHERE {} < $1", entity.table(), entity.timestamp_column())
+                }
+                // anonymized_at keeps rows from being counted again on the next run
+                RetentionAction::Anonymize => format!(
+                    "UPDATE {} SET {}, anonymized_at = CURRENT_TIMESTAMP WHERE {} < $1 AND anonymized_at IS NULL",
+                    entity.table(),
+                    entity.anonymize_assignments(),
+                    entity.timestamp_column()
+                ),
+            };
+            let result = sqlx::query(&query)
+                .bind(cutoff)
+                .execute(pool)
+                .await
+                .map_err(|e| format!("Failed to purge {}: {}", entity.as_str(), e))?;
+
+            reports.push(PurgeReport { entity_type: entity, action: policy.action, cutoff, rows: result.rows_affected() });
+        }
+        Ok(reports)
+    }
+
+    // Replace a data subject with a pseudonym in CBU memberships, documents,
+    // evaluation traces and audit logs. Rows are kept, so aggregates still add up;
+    // the erasure itself is recorded without any personal data.
+    pub async fn erase_subject(
+        pool: &DbPool,
+        entity_id: &str,
+        requested_by: Option<&str>,
+    ) -> Result<ErasureReport, String> {
+        let mut subject = SubjectIdentifiers::new(entity_id);
+        if subject.entity_id.is_empty() {
+            return Err("Entity id is required".to_string());
+        }
+
+        let mut tx = pool.begin()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let members = sqlx::query("
+            SELECT entity_name, entity_lei, contact_email, contact_phone
+            FROM cbu_members
+            WHERE entity_id = $1
+            FOR UPDATE
+        ")
+            .bind(&subject.entity_id)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        for member in &members {
+            subject.names.push(member.get("entity_name"));
+            subject.leis.extend(member.get::<Option<String>, _>("entity_lei"));
+            subject.emails.extend(member.get::<Option<String>, _>("contact_email"));
+            subject.phones.extend(member.get::<Option<String>, _>("contact_phone"));
+        }
+
+        let pseudonym = erasure_pseudonym();
+        let cbu_members = sqlx::query("
+            UPDATE cbu_members
+            SET entity_id = $2, entity_name = $2, entity_lei = NULL, contact_email = NULL, contact_phone = NULL,
+                authorized_persons = NULL, notes = NULL, metadata = NULL,
+                is_active = false, receives_notifications = false,
+                updated_by = $3, updated_at = CURRENT_TIMESTAMP
+            WHERE entity_id = $1
+        ")
+            .bind(&subject.entity_id)
+            .bind(&pseudonym)
+            .bind(requested_by)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to erase CBU members: {}", e))?
+            .rows_affected();
+
+        let documents = redact_rows(
+            &mut tx,
+            RedactedColumns {
+                table: "resource_instances",
+                json: &["instance_data", "validation_results", "change_history"],
+                text: &[],
+                text_array: &[],
+            },
+            &subject,
+            &pseudonym,
+        ).await?;
+        let traces = redact_rows(
+            &mut tx,
+            RedactedColumns {
+                table: "dsl_execution_logs",
+                json: &["input_data", "output_data", "log_messages", "context_metadata"],
+                text: &["instance_id", "error_details", "stack_trace"],
+                text_array: &[],
+            },
+            &subject,
+            &pseudonym,
+        ).await?;
+        let audit_logs = redact_rows(
+            &mut tx,
+            RedactedColumns {
+                table: "onboarding_execution_log",
+                json: &["metadata"],
+                text: &["error_message"],
+                text_array: &["execution_log"],
+            },
+            &subject,
+            &pseudonym,
+        ).await?;
+
+        let completed_at = Utc::now();
+        let (erasure_id,): (i32,) = sqlx::query_as("
+            INSERT INTO gdpr_erasures (pseudonym, cbu_members, documents, traces, audit_logs, requested_by, completed_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id
+        ")
+            .bind(&pseudonym)
+            .bind(cbu_members as i64)
+            .bind(documents as i64)
+            .bind(traces as i64)
+            .bind(audit_logs as i64)
+            .bind(requested_by)
+            .bind(completed_at)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to record erasure: {}", e))?;
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+        Ok(ErasureReport {
+            erasure_id,
+            pseudonym,
+            cbu_members,
+            documents,
+            traces,
+            audit_logs,
+            requested_by: requested_by.map(str::to_string),
+            completed_at,
+        })
+    }
+
+    // Most recent erasures first
+    pub async fn list_erasures(
+        pool: &DbPool,
+        limit: i64,
+    ) -> Result<Vec<ErasureReport>, String> {
+        let rows = sqlx::query("
+            SELECT id, pseudonym, cbu_members, documents, traces, audit_logs, requested_by, completed_at
+            FROM gdpr_erasures
+            ORDER BY completed_at DESC
+            LIMIT $1
+        ")
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| ErasureReport {
+                erasure_id: row.get("id"),
+                pseudonym: row.get("pseudonym"),
+                cbu_members: row.get::<i64, _>("cbu_members") as u64,
+                documents: row.get::<i64, _>("documents") as u64,
+                traces: row.get::<i64, _>("traces") as u64,
+                audit_logs: row.get::<i64, _>("audit_logs") as u64,
+                requested_by: row.get("requested_by"),
+                completed_at: row.get("completed_at"),
+            })
+            .collect())
+    }
+}
+
+fn policy_from_row(row: &sqlx::postgres::PgRow) -> Result<RetentionPolicy, String> {
+    let entity_type: String = row.get("entity_type");
+    let action: String = row.get("action");
+    Ok(RetentionPolicy {
+        entity_type: RetentionEntity::parse(&entity_type)
+            .ok_or_else(|| format!("Unknown retention entity type '{}'", entity_type))?,
+        retain_days: row.get("retain_days"),
+        action: RetentionAction::parse(&action).ok_or_else(|| format!("Unknown retention action '{}'", action))?,
+        enabled: row.get("enabled"),
+    })
+}
+
+// The columns of a table that may hold a subject's identifiers
+struct RedactedColumns {
+    table: &'static str,
+    json: &'static [&'static str],
+    text: &'static [&'static str],
+    text_array: &'static [&'static str],
+}
+
+impl RedactedColumns {
+    fn all(&self) -> impl Iterator<Item = &&'static str> {
+        self.json.iter().chain(self.text).chain(self.text_array)
+    }
+}
+
+fn like_pattern(value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+// Redact the subject in every row of the table mentioning them; returns the rows changed
+async fn redact_rows(
+    conn: &mut PgConnection,
+    columns: RedactedColumns,
+    subject: &SubjectIdentifiers,
+    pseudonym: &str,
+) -> Result<u64, String> {
+    let patterns: Vec<String> = subject.values().into_iter().map(like_pattern).collect();
+    let searched = columns.all().map(|column| format!("COALESCE({}::text, '')", column)).collect::<Vec<_>>().join(" || ' ' || ");
+    let select = format!(
+        "SELECT id, {} FROM {} WHERE ({}) LIKE ANY($1) FOR UPDATE",
+        columns.all().copied().collect::<Vec<_>>().join(", "),
+        columns.table,
+        searched
+    );
+    let rows = sqlx::query(&select)
+        .bind(&patterns)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to search {}: {}", columns.table, e))?;
+
+    let assignments = columns.all().enumerate().map(|(i, column)| format!("{} = ${}", column, i + 2)).collect::<Vec<_>>().join(", ");
+    let update = format!("UPDATE {} SET {} WHERE id = $1", columns.table, assignments);
+
+    let mut changed = 0;
+    for row in rows {
+        let mut replaced = 0;
+        let mut query = sqlx::query(&update).bind(row.get::<i32, _>("id"));
+        for column in columns.json {
+            let mut value: Option<JsonValue> = row.get(*column);
+            if let Some(value) = value.as_mut() {
+                replaced += subject.redact_json(value, pseudonym);
+            }
+            query = query.bind(value);
+        }
+        for column in columns.text {
+            let value = row.get::<Option<String>, _>(*column).map(|text| {
+                let (text, count) = subject.redact_text(&text, pseudonym);
+                replaced += count;
+                text
+            });
+            query = query.bind(value);
+        }
+        for column in columns.text_array {
+            let value = row.get::<Option<Vec<String>>, _>(*column).map(|lines| {
+                lines
+                    .iter()
+                    .map(|line| {
+                        let (line, count) = subject.redact_text(line, pseudonym);
+                        replaced += count;
+                        line
+                    })
+                    .collect::<Vec<_>>()
+            });
+            query = query.bind(value);
+        }
+
+        // The row matched on text the JSON encoding escaped differently; nothing to change
+        if replaced == 0 {
+            continue;
+        }
+        query
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to redact {}: {}", columns.table, e))?;
+        changed += 1;
+    }
+    Ok(changed)
+}
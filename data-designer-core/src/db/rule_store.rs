@@ -0,0 +1,127 @@
+use super::rules::record_rule_version;
+use super::{DbPool, RuleOperations, RuleTestOperations};
+use crate::rule_history::{validate_effective_period, RuleVersion};
+use crate::rule_tests::RuleTestRunner;
+use crate::storage::{Rule, RuleStore};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+const RULE_QUERY: &str = "
+    SELECT id, rule_id, rule_name, description, category_id, target_attribute_id,
+           rule_definition, parsed_ast, COALESCE(status, 'draft') AS status,
+           COALESCE(version, 1) AS version, tags, performance_metrics,
+           NULL::jsonb AS embedding_data, created_by,
+           COALESCE(created_at, CURRENT_TIMESTAMP)::timestamptz AS created_at, updated_by,
+           COALESCE(updated_at, CURRENT_TIMESTAMP)::timestamptz AS updated_at, valid_from, valid_to
+    FROM rules
+";
+
+// RuleStore over the rules and rule_versions tables
+#[derive(Clone)]
+pub struct PostgresRuleStore {
+    pool: DbPool,
+}
+
+impl PostgresRuleStore {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    pub fn pool(&self) -> &DbPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl RuleStore for PostgresRuleStore {
+    async fn list_rules(&self) -> Result<Vec<Rule>, String> {
+        sqlx::query_as::<_, Rule>(&format!("{} ORDER BY rule_id", RULE_QUERY))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
+    async fn get_rule(&self, rule_id: &str) -> Result<Option<Rule>, String> {
+        sqlx::query_as::<_, Rule>(&format!("{} WHERE rule_id = $1", RULE_QUERY))
+            .bind(rule_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
+    async fn save_rule(&self, rule: Rule) -> Result<i32, String> {
+        validate_effective_period(rule.valid_from, rule.valid_to)?;
+        // An active rule must pass its test cases as saved
+        if rule.status == "active" {
+            let cases = RuleTestOperations::list_test_cases(&self.pool, &rule.rule_id).await?;
+            let report = RuleTestRunner::new().run(&rule.rule_id, &rule.rule_definition, &cases)?;
+            if !report.all_passed() {
+                return Err(format!("Rule {} can't be saved as active: {}", rule.rule_id, report.failure_summary()));
+            }
+        }
+        let saved_by = rule.updated_by.as_deref().or(rule.created_by.as_deref()).unwrap_or("system");
+
+        let mut tx = self.pool.begin()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let (version,): (i32,) = sqlx::query_as("
+            INSERT INTO rules (
+                rule_id, rule_name, description, category_id, target_attribute_id,
+                rule_definition, parsed_ast, status, tags, performance_metrics,
+                valid_from, valid_to, created_by
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            ON CONFLICT (rule_id) DO UPDATE SET
+                rule_name = EXCLUDED.rule_name,
+                description = EXCLUDED.description,
+                category_id = EXCLUDED.category_id,
+                target_attribute_id = EXCLUDED.target_attribute_id,
+                rule_definition = EXCLUDED.rule_definition,
+                parsed_ast = EXCLUDED.parsed_ast,
+                status = EXCLUDED.status,
+                tags = EXCLUDED.tags,
+                performance_metrics = EXCLUDED.performance_metrics,
+                valid_from = EXCLUDED.valid_from,
+                valid_to = EXCLUDED.valid_to,
+                version = COALESCE(rules.version, 1) + 1,
+                updated_by = EXCLUDED.created_by,
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING version
+        ")
+            .bind(&rule.rule_id)
+            .bind(&rule.rule_name)
+            .bind(&rule.description)
+            .bind(rule.category_id)
+            .bind(rule.target_attribute_id)
+            .bind(&rule.rule_definition)
+            .bind(&rule.parsed_ast)
+            .bind(&rule.status)
+            .bind(&rule.tags)
+            .bind(&rule.performance_metrics)
+            .bind(rule.valid_from)
+            .bind(rule.valid_to)
+            .bind(saved_by)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to save rule: {}", e))?;
+
+        let change = if version == 1 { "Created" } else { "Saved" };
+        record_rule_version(&mut tx, &rule.rule_id, change, Some(saved_by)).await?;
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+        Ok(version)
+    }
+
+    async fn rule_history(&self, rule_id: &str) -> Result<Vec<RuleVersion>, String> {
+        RuleOperations::get_rule_history(&self.pool, rule_id).await
+    }
+
+    // One query instead of a history lookup per rule
+    async fn rules_as_of(&self, at: DateTime<Utc>) -> Result<Vec<RuleVersion>, String> {
+        RuleOperations::get_rules_as_of(&self.pool, at).await
+    }
+}
@@ -0,0 +1,218 @@
+use super::DbPool;
+use crate::rule_templates::{InstantiatedRule, RuleTemplate, TemplateInstance, TemplateScope};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredRuleTemplate {
+    pub template_name: String,
+    pub parameters: Vec<String>,
+    pub definition: String,
+    pub description: Option<String>,
+    pub instance_count: i64,
+}
+
+// Parameterized rules and the per-product and per-jurisdiction arguments they're instantiated with
+pub struct RuleTemplateOperations;
+
+impl RuleTemplateOperations {
+    pub async fn list_rule_templates(
+        pool: &DbPool,
+    ) -> Result<Vec<StoredRuleTemplate>, String> {
+        let rows = sqlx::query("
+            SELECT t.template_name, t.parameters, t.definition, t.description,
+                   (SELECT COUNT(*) FROM rule_template_instances i WHERE i.template_id = t.id) AS instance_count
+            FROM rule_templates t
+            ORDER BY t.template_name
+        ")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        Ok(rows.iter().map(template_from_row).collect())
+    }
+
+    // Create or replace a template. A changed template must still instantiate for every
+    // stored instance, so a renamed or added parameter can't strand a jurisdiction.
+    pub async fn save_rule_template(
+        pool: &DbPool,
+        definition: &str,
+        description: Option<&str>,
+        created_by: Option<&str>,
+    ) -> Result<StoredRuleTemplate, String> {
+        let template = RuleTemplate::parse(definition)?;
+
+        let mut tx = pool.begin()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let rows = sqlx::query(&format!("{} WHERE t.template_name = $1 FOR UPDATE OF t", INSTANCE_QUERY))
+            .bind(&template.name)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        for row in &rows {
+            template.instantiate_rule(&instance_from_row(row)?)?;
+        }
+
+        let row = sqlx::query("
+            INSERT INTO rule_templates (template_name, parameters, definition, description, created_by)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (template_name) DO UPDATE SET
+                parameters = EXCLUDED.parameters,
+                definition = EXCLUDED.definition,
+                description = COALESCE(EXCLUDED.description, rule_templates.description),
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING template_name, parameters, definition, description,
+                      (SELECT COUNT(*) FROM rule_template_instances i WHERE i.template_id = rule_templates.id) AS instance_count
+        ")
+            .bind(&template.name)
+            .bind(&template.parameters)
+            .bind(definition.trim())
+            .bind(description)
+            .bind(created_by)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to save rule template: {}", e))?;
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to commit rule template: {}", e))?;
+
+        Ok(template_from_row(&row))
+    }
+
+    // Set one product's or jurisdiction's arguments; they're checked by instantiating
+    // the template, and the resulting rule is returned
+    pub async fn save_template_instance(
+        pool: &DbPool,
+        instance: &TemplateInstance,
+        created_by: Option<&str>,
+    ) -> Result<InstantiatedRule, String> {
+        if instance.scope_value.trim().is_empty() {
+            return Err(format!("A {} is required", instance.scope));
+        }
+        let template = Self::get_rule_template(pool, &instance.template_name).await?;
+        let rule = template.instantiate_rule(instance)?;
+        let arguments = serde_json::to_value(&instance.arguments)
+            .map_err(|e| format!("Failed to serialize template arguments: {}", e))?;
+
+        sqlx::query("
+            INSERT INTO rule_template_instances (template_id, scope, scope_value, arguments, created_by)
+            SELECT id, $2, $3, $4, $5 FROM rule_templates WHERE template_name = $1
+            ON CONFLICT (template_id, scope, scope_value) DO UPDATE SET
+                arguments = EXCLUDED.arguments,
+                updated_at = CURRENT_TIMESTAMP
+        ")
+            .bind(&instance.template_name)
+            .bind(instance.scope.as_str())
+            .bind(instance.scope_value.trim())
+            .bind(&arguments)
+            .bind(created_by)
+            .execute(pool)
+            .await
+            .map_err(|e| format!("Failed to save template instance: {}", e))?;
+
+        Ok(rule)
+    }
+
+    // Returns whether the instance existed
+    pub async fn delete_template_instance(
+        pool: &DbPool,
+        template_name: &str,
+        scope: TemplateScope,
+        scope_value: &str,
+    ) -> Result<bool, String> {
+        let result = sqlx::query("
+            DELETE FROM rule_template_instances i
+            USING rule_templates t
+            WHERE i.template_id = t.id AND t.template_name = $1 AND i.scope = $2 AND i.scope_value = $3
+        ")
+            .bind(template_name)
+            .bind(scope.as_str())
+            .bind(scope_value)
+            .execute(pool)
+            .await
+            .map_err(|e| format!("Failed to delete template instance: {}", e))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Every instance of one template, as the rules they instantiate
+    pub async fn get_template_instances(
+        pool: &DbPool,
+        template_name: &str,
+    ) -> Result<Vec<InstantiatedRule>, String> {
+        let rows = sqlx::query(&format!("{} WHERE t.template_name = $1 ORDER BY i.scope, i.scope_value", INSTANCE_QUERY))
+            .bind(template_name)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        rows.iter().map(instantiate_row).collect()
+    }
+
+    // The rules every template instantiates for one product or jurisdiction
+    pub async fn get_rules_for_scope(
+        pool: &DbPool,
+        scope: TemplateScope,
+        scope_value: &str,
+    ) -> Result<Vec<InstantiatedRule>, String> {
+        let rows = sqlx::query(&format!("{} WHERE i.scope = $1 AND i.scope_value = $2 ORDER BY t.template_name", INSTANCE_QUERY))
+            .bind(scope.as_str())
+            .bind(scope_value)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        rows.iter().map(instantiate_row).collect()
+    }
+
+    async fn get_rule_template(
+        pool: &DbPool,
+        template_name: &str,
+    ) -> Result<RuleTemplate, String> {
+        let definition: Option<String> = sqlx::query_scalar("SELECT definition FROM rule_templates WHERE template_name = $1")
+            .bind(template_name)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        RuleTemplate::parse(&definition.ok_or_else(|| format!("Rule template not found: {}", template_name))?)
+    }
+}
+
+// Templates with their instances; the inner join means templates without any yield no rows
+const INSTANCE_QUERY: &str = "
+    SELECT t.template_name, t.definition, i.scope, i.scope_value, i.arguments
+    FROM rule_template_instances i
+    JOIN rule_templates t ON t.id = i.template_id
+";
+
+fn template_from_row(row: &sqlx::postgres::PgRow) -> StoredRuleTemplate {
+    StoredRuleTemplate {
+        template_name: row.get("template_name"),
+        parameters: row.get("parameters"),
+        definition: row.get("definition"),
+        description: row.get("description"),
+        instance_count: row.get("instance_count"),
+    }
+}
+
+fn instance_from_row(row: &sqlx::postgres::PgRow) -> Result<TemplateInstance, String> {
+    let arguments: serde_json::Value = row.get("arguments");
+    let arguments: BTreeMap<String, String> =
+        serde_json::from_value(arguments).map_err(|e| format!("Invalid template arguments: {}", e))?;
+    Ok(TemplateInstance {
+        template_name: row.get("template_name"),
+        scope: TemplateScope::parse(row.get("scope"))?,
+        scope_value: row.get("scope_value"),
+        arguments,
+    })
+}
+
+fn instantiate_row(row: &sqlx::postgres::PgRow) -> Result<InstantiatedRule, String> {
+    let definition: String = row.get("definition");
+    RuleTemplate::parse(&definition)?.instantiate_rule(&instance_from_row(row)?)
+}
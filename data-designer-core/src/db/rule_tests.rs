@@ -0,0 +1,109 @@
+use super::DbPool;
+use crate::rule_tests::{RuleTestCase, RuleTestReport, RuleTestRunner};
+use sqlx::Row;
+
+// Saved test cases of rules, and running them
+pub struct RuleTestOperations;
+
+impl RuleTestOperations {
+    // Cases of one rule, in the order they were added
+    pub async fn list_test_cases(
+        pool: &DbPool,
+        rule_id: &str,
+    ) -> Result<Vec<RuleTestCase>, String> {
+        let rows = sqlx::query("
+            SELECT rule_id, name, inputs, expected, expected_error, generated
+            FROM rule_test_cases
+            WHERE rule_id = $1
+            ORDER BY id
+        ")
+            .bind(rule_id)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        rows.iter().map(case_from_row).collect()
+    }
+
+    // Add a case, or replace the rule's case with the same name
+    pub async fn save_test_case(
+        pool: &DbPool,
+        rule_id: &str,
+        case: &RuleTestCase,
+        created_by: Option<&str>,
+    ) -> Result<RuleTestCase, String> {
+        if case.name.trim().is_empty() {
+            return Err("Test case name is required".to_string());
+        }
+        let inputs = serde_json::to_value(&case.inputs)
+            .map_err(|e| format!("Failed to serialize test inputs: {}", e))?;
+
+        let row = sqlx::query("
+            INSERT INTO rule_test_cases (rule_id, name, inputs, expected, expected_error, generated, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (rule_id, name) DO UPDATE SET
+                inputs = EXCLUDED.inputs,
+                expected = EXCLUDED.expected,
+                expected_error = EXCLUDED.expected_error,
+                generated = EXCLUDED.generated,
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING rule_id, name, inputs, expected, expected_error, generated
+        ")
+            .bind(rule_id)
+            .bind(case.name.trim())
+            .bind(&inputs)
+            .bind(&case.expected)
+            .bind(&case.expected_error)
+            .bind(case.generated)
+            .bind(created_by)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| format!("Failed to save test case: {}", e))?;
+
+        case_from_row(&row)
+    }
+
+    // Returns whether the case existed
+    pub async fn delete_test_case(
+        pool: &DbPool,
+        rule_id: &str,
+        name: &str,
+    ) -> Result<bool, String> {
+        let result = sqlx::query("DELETE FROM rule_test_cases WHERE rule_id = $1 AND name = $2")
+            .bind(rule_id)
+            .bind(name)
+            .execute(pool)
+            .await
+            .map_err(|e| format!("Failed to delete test case: {}", e))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Run every saved case against the rule's current definition
+    pub async fn run_rule_tests(
+        pool: &DbPool,
+        rule_id: &str,
+    ) -> Result<RuleTestReport, String> {
+        let definition: Option<String> = sqlx::query_scalar("SELECT rule_definition FROM rules WHERE rule_id = $1")
+            .bind(rule_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        let definition = definition.ok_or_else(|| format!("Rule not found: {}", rule_id))?;
+
+        let cases = Self::list_test_cases(pool, rule_id).await?;
+        RuleTestRunner::new().run(rule_id, &definition, &cases)
+    }
+}
+
+fn case_from_row(row: &sqlx::postgres::PgRow) -> Result<RuleTestCase, String> {
+    let inputs: serde_json::Value = row.get("inputs");
+    Ok(RuleTestCase {
+        rule: row.get("rule_id"),
+        name: row.get("name"),
+        inputs: serde_json::from_value(inputs).map_err(|e| format!("Invalid test inputs: {}", e))?,
+        expected: row.get("expected"),
+        expected_error: row.get("expected_error"),
+        generated: row.get("generated"),
+    })
+}
@@ -1,32 +1,38 @@
 use super::{DbPool, DbOperations};
+use super::data_dictionary::DataDictionaryOperations;
+use super::rule_tests::RuleTestOperations;
+use super::tags::{tag_list_expr, tag_match_clause, TagFilter, TagOperations, TagTarget};
+use crate::parser::parse_rule;
+use crate::attribute_workbook::{read_workbook, write_workbook, WorkbookReview, WorkbookRow};
+use crate::models::Expression;
+use crate::rule_categories::{CategoryPolicy, CategoryTree, RuleCategory, Severity};
+use crate::rule_conflicts::{find_conflicts, RuleConflict};
+use crate::rule_cost::{estimate_rule_cost, LatencyHistory, RuleCost};
+use crate::rule_graph::{dependency_graph_payload, DependencyGraphPayload, GraphRule, GraphScope};
+use crate::rule_bundle::RuleBundle;
+use crate::rule_integrity::{check_rules, IntegrityReport};
+use crate::rule_repair::RepairPlan;
+use crate::rule_statistics::{rule_statistics, RuleStatistics, StatsChange, StatsRule};
+use crate::rule_naming::apply_naming;
+use crate::rule_history::{validate_effective_period, versions_in_effect, RuleVersion, RuleVersionDiff};
+use crate::rule_repository::ExportedRule;
+use crate::rule_tests::RuleTestReport;
+use crate::rule_variants::Applicability;
+use crate::type_checker::{RuleType, TypeEnv};
+use crate::transpiler::project::{generate_rust_project, ProjectRule, RustProject};
+use crate::rule_rewrite::{RewriteMode, RewritePlan, RuleRewrite, StoredRule};
 use serde::{Deserialize, Serialize};
-use sqlx::Row;
+use sqlx::types::Json;
+use sqlx::{PgConnection, Row};
 use chrono::{DateTime, Utc};
 
 // Rule-related DTOs
-#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
-pub struct Rule {
-    pub id: i32,
-    pub rule_id: String,
-    pub rule_name: String,
-    pub description: Option<String>,
-    pub category_id: Option<i32>,
-    pub target_attribute_id: Option<i32>,
-    pub rule_definition: String,
-    pub parsed_ast: Option<serde_json::Value>,
-    pub status: String,
-    pub version: i32,
-    pub tags: Option<Vec<String>>,
-    pub performance_metrics: Option<serde_json::Value>,
-    pub embedding_data: Option<serde_json::Value>,
-    pub created_by: Option<String>,
-    pub created_at: DateTime<Utc>,
-    pub updated_by: Option<String>,
-    pub updated_at: DateTime<Utc>,
-}
+pub use crate::storage::Rule;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateRuleRequest {
+    /// Generated from the category's naming convention when empty
+    #[serde(default)]
     pub rule_id: String,
     pub rule_name: String,
     pub description: Option<String>,
@@ -35,6 +41,15 @@ pub struct CreateRuleRequest {
     pub source_attributes: Vec<String>,
     pub rule_definition: String,
     pub tags: Option<Vec<String>>,
+    /// Effective period of this version; open on either side when absent
+    #[serde(default)]
+    pub valid_from: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub valid_to: Option<DateTime<Utc>>,
+    /// The jurisdiction, product line or CBU type this variant of the rule is
+    /// for; a rule with none is the default for its attribute
+    #[serde(default)]
+    pub applies_to: Applicability,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -47,6 +62,47 @@ pub struct CreateRuleWithTemplateRequest {
     pub rule_definition: String,
 }
 
+/// Result of saving a rule under its category's policy
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedRule {
+    pub rule_id: String,
+    pub rule_name: String,
+    /// The rule ID generated or the name corrected under the category's naming convention
+    pub naming_notes: Vec<String>,
+    pub category_id: i32,
+    pub status: String,
+    pub severity: Severity,
+    /// Version the save was recorded as in the rule's history
+    pub version: i32,
+    /// The rule's saved test cases, run against what was saved
+    pub tests: RuleTestReport,
+}
+
+/// Health of the active rule set: rules that no longer parse, and rules
+/// writing the same attribute for the same inputs
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RuleStateReport {
+    pub active_rules: usize,
+    pub invalid_rules: Vec<InvalidRule>,
+    pub conflicts: Vec<RuleConflict>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InvalidRule {
+    pub rule_id: String,
+    pub message: String,
+}
+
+impl RuleStateReport {
+    pub fn warnings(&self) -> Vec<String> {
+        self.invalid_rules
+            .iter()
+            .map(|rule| format!("{} doesn't parse: {}", rule.rule_id, rule.message))
+            .chain(self.conflicts.iter().map(RuleConflict::message))
+            .collect()
+    }
+}
+
 // Rule database operations
 pub struct RuleOperations;
 
@@ -184,6 +240,370 @@ impl RuleOperations {
         Ok(())
     }
 
+    // Load the category hierarchy with its policies
+    pub async fn get_rule_categories(
+        pool: &DbPool,
+    ) -> Result<CategoryTree, String> {
+        let query = "
+            SELECT id, category_key, name, parent_id,
+                   approval_required, allowed_functions, default_severity,
+                   rule_name_pattern, rule_id_prefix, auto_correct_names
+            FROM rule_categories
+        ";
+
+        let rows = sqlx::query(query)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        let categories = rows.into_iter().map(|row| RuleCategory {
+            id: row.get("id"),
+            category_key: row.get("category_key"),
+            name: row.get("name"),
+            parent_id: row.get("parent_id"),
+            policy: CategoryPolicy {
+                approval_required: row.get("approval_required"),
+                allowed_functions: row.get("allowed_functions"),
+                default_severity: row
+                    .get::<Option<&str>, _>("default_severity")
+                    .and_then(Severity::parse),
+                rule_name_pattern: row.get("rule_name_pattern"),
+                rule_id_prefix: row.get("rule_id_prefix"),
+                auto_correct_names: row.get("auto_correct_names"),
+            },
+        });
+
+        Ok(CategoryTree::new(categories))
+    }
+
+    // Save a rule after enforcing the policy of its category and its ancestors,
+    // naming convention included. A rule without a rule_id gets one generated.
+    // Saving an existing rule_id replaces its definition as a new version; every
+    // version is kept in rule_versions.
+    pub async fn save_rule_with_validation(
+        pool: &DbPool,
+        mut request: CreateRuleRequest,
+    ) -> Result<SavedRule, String> {
+        let (remaining, ast) = parse_rule(&request.rule_definition)
+            .map_err(|e| format!("Failed to parse rule: {}", e))?;
+        if !remaining.trim().is_empty() {
+            return Err(format!("Unexpected input after rule: {}", remaining.trim()));
+        }
+        validate_effective_period(request.valid_from, request.valid_to)?;
+
+        let categories = Self::get_rule_categories(pool).await?;
+        let category = categories
+            .find_by_key(&request.category_key)
+            .ok_or_else(|| format!("Unknown rule category: {}", request.category_key))?;
+
+        let naming = apply_naming(
+            &categories,
+            category.id,
+            &request.rule_id,
+            &request.rule_name,
+            &request.target_attribute,
+            &request.applies_to,
+        )
+        .map_err(|violations| violations.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("; "))?;
+        request.rule_id = naming.rule_id;
+        request.rule_name = naming.rule_name;
+
+        let violations = categories.check_rule(category.id, &ast);
+        if !violations.is_empty() {
+            let messages: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+            return Err(messages.join("; "));
+        }
+
+        let policy = categories.effective_policy(category.id);
+        let status = if policy.approval_required { "pending_approval" } else { "draft" };
+        let parsed_ast = serde_json::to_value(&ast)
+            .map_err(|e| format!("Failed to serialize rule AST: {}", e))?;
+        let applies_to = serde_json::to_value(&request.applies_to)
+            .map_err(|e| format!("Failed to serialize rule applicability: {}", e))?;
+
+        let query = "
+            INSERT INTO rules (
+                rule_id, rule_name, description, category_id, target_attribute_id,
+                rule_definition, parsed_ast, status, severity, tags, valid_from, valid_to, applies_to, created_by
+            )
+            VALUES (
+                $1, $2, $3, $4,
+                (SELECT id FROM derived_attributes WHERE name = $5),
+                $6, $7, $8, $9, $10, $11, $12, $13, 'system'
+            )
+            ON CONFLICT (rule_id) DO UPDATE SET
+                rule_name = EXCLUDED.rule_name,
+                description = EXCLUDED.description,
+                category_id = EXCLUDED.category_id,
+                target_attribute_id = EXCLUDED.target_attribute_id,
+                rule_definition = EXCLUDED.rule_definition,
+                parsed_ast = EXCLUDED.parsed_ast,
+                status = EXCLUDED.status,
+                severity = EXCLUDED.severity,
+                tags = EXCLUDED.tags,
+                valid_from = EXCLUDED.valid_from,
+                valid_to = EXCLUDED.valid_to,
+                applies_to = EXCLUDED.applies_to,
+                version = COALESCE(rules.version, 1) + 1,
+                updated_by = 'system',
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING version
+        ";
+
+        let mut tx = pool.begin()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let (version,): (i32,) = sqlx::query_as(query)
+            .bind(&request.rule_id)
+            .bind(&request.rule_name)
+            .bind(&request.description)
+            .bind(category.id)
+            .bind(&request.target_attribute)
+            .bind(&request.rule_definition)
+            .bind(&parsed_ast)
+            .bind(status)
+            .bind(policy.default_severity.as_str())
+            .bind(&request.tags)
+            .bind(request.valid_from)
+            .bind(request.valid_to)
+            .bind(&applies_to)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to save rule: {}", e))?;
+
+        let change = if version == 1 { "Created" } else { "Saved" };
+        record_rule_version(&mut tx, &request.rule_id, change, Some("system")).await?;
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+        if let Some(tags) = &request.tags {
+            TagOperations::assign_tags(pool, TagTarget::Rule, &request.rule_id, tags, None).await?;
+        }
+        let tests = RuleTestOperations::run_rule_tests(pool, &request.rule_id).await?;
+
+        Ok(SavedRule {
+            rule_id: request.rule_id,
+            rule_name: request.rule_name,
+            naming_notes: naming.notes,
+            category_id: category.id,
+            status: status.to_string(),
+            severity: policy.default_severity,
+            version,
+            tests,
+        })
+    }
+
+    // Move a rule to another status. A rule only becomes active while every
+    // one of its test cases passes; the failures are returned otherwise.
+    pub async fn set_rule_status(
+        pool: &DbPool,
+        rule_id: &str,
+        status: &str,
+        changed_by: Option<&str>,
+    ) -> Result<i32, String> {
+        if !RULE_STATUSES.contains(&status) {
+            return Err(format!("Unknown rule status: {}", status));
+        }
+        if status == "active" {
+            let report = RuleTestOperations::run_rule_tests(pool, rule_id).await?;
+            if !report.all_passed() {
+                return Err(format!(
+                    "Rule {} can't be made active: {} of {} test cases failed: {}",
+                    rule_id,
+                    report.failed,
+                    report.outcomes.len(),
+                    report.failure_summary()
+                ));
+            }
+        }
+
+        let mut tx = pool.begin()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let previous: Option<(Option<String>,)> = sqlx::query_as("SELECT status FROM rules WHERE rule_id = $1 FOR UPDATE")
+            .bind(rule_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        let Some((previous,)) = previous else {
+            return Err(format!("Rule not found: {}", rule_id));
+        };
+
+        let (version,): (i32,) = sqlx::query_as("
+            UPDATE rules
+            SET status = $2, version = COALESCE(version, 1) + 1, updated_by = $3, updated_at = CURRENT_TIMESTAMP
+            WHERE rule_id = $1
+            RETURNING version
+        ")
+            .bind(rule_id)
+            .bind(status)
+            .bind(changed_by)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to update rule status: {}", e))?;
+
+        let change = format!("Status {} -> {}", previous.as_deref().unwrap_or("draft"), status);
+        record_rule_version(&mut tx, rule_id, &change, changed_by).await?;
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+        Ok(version)
+    }
+
+    // Parse and typecheck every active rule against the current grammar and data
+    // dictionary, moving the ones that no longer validate to in_repair. The version
+    // recorded with each move carries what broke; the report summarizes it all.
+    pub async fn check_active_rules_integrity(
+        pool: &DbPool,
+        checked_by: Option<&str>,
+    ) -> Result<IntegrityReport, String> {
+        let type_env = Self::get_dictionary_type_env(pool).await?;
+
+        let mut tx = pool.begin()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let rules: Vec<(String, String)> = sqlx::query_as("
+            SELECT rule_id, rule_definition FROM rules
+            WHERE status = 'active'
+            ORDER BY rule_id
+            FOR UPDATE
+        ")
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        let report = check_rules(rules.iter().map(|(rule_id, definition)| (rule_id.as_str(), definition.as_str())), &type_env);
+
+        for broken in &report.broken {
+            sqlx::query("
+                UPDATE rules
+                SET status = 'in_repair', version = COALESCE(version, 1) + 1, updated_by = $2, updated_at = CURRENT_TIMESTAMP
+                WHERE rule_id = $1
+            ")
+                .bind(&broken.rule_id)
+                .bind(checked_by)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to update rule status: {}", e))?;
+
+            let change = format!("Status active -> in_repair: {}", broken.problems.join("; "));
+            record_rule_version(&mut tx, &broken.rule_id, &change, checked_by).await?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+        Ok(report)
+    }
+
+    // Attribute types from the data dictionary, by attribute name and full path
+    pub async fn get_dictionary_type_env(
+        pool: &DbPool,
+    ) -> Result<TypeEnv, String> {
+        let dictionary = DataDictionaryOperations::get_data_dictionary(pool, None).await?;
+        let mut type_env = TypeEnv::new();
+        for attribute in &dictionary.attributes {
+            let rule_type = RuleType::from_type_name(attribute["data_type"].as_str().unwrap_or(""));
+            for key in ["attribute_name", "full_path"] {
+                if let Some(name) = attribute[key].as_str() {
+                    type_env.insert(name, rule_type);
+                }
+            }
+        }
+        Ok(type_env)
+    }
+
+    // Diagnose a rule and propose restoring its most similar earlier version that
+    // still validates; the caller adds the AI assistant's fixes with propose_fixes
+    pub async fn plan_rule_repair(
+        pool: &DbPool,
+        rule_id: &str,
+        type_env: &TypeEnv,
+    ) -> Result<RepairPlan, String> {
+        let definition: Option<String> = sqlx::query_scalar("SELECT rule_definition FROM rules WHERE rule_id = $1")
+            .bind(rule_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        let definition = definition.ok_or_else(|| format!("Rule not found: {}", rule_id))?;
+
+        let versions = Self::get_rule_history(pool, rule_id).await?;
+        Ok(RepairPlan::new(rule_id, &definition, &versions, type_env))
+    }
+
+    // Apply one step of a repair plan: save its definition as a new version of the
+    // rule, leaving the status alone. `expected_definition` is what the plan was made
+    // against, so a plan gone stale since is refused rather than overwriting newer work.
+    pub async fn apply_rule_repair(
+        pool: &DbPool,
+        rule_id: &str,
+        expected_definition: &str,
+        definition: &str,
+        description: &str,
+        applied_by: Option<&str>,
+    ) -> Result<i32, String> {
+        let (remaining, ast) = parse_rule(definition)
+            .map_err(|e| format!("Failed to parse rule: {}", e))?;
+        if !remaining.trim().is_empty() {
+            return Err(format!("Unexpected input after rule: {}", remaining.trim()));
+        }
+        let parsed_ast = serde_json::to_value(&ast)
+            .map_err(|e| format!("Failed to serialize rule AST: {}", e))?;
+        let categories = Self::get_rule_categories(pool).await?;
+
+        let mut tx = pool.begin()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let current: Option<(String, Option<i32>)> = sqlx::query_as("SELECT rule_definition, category_id FROM rules WHERE rule_id = $1 FOR UPDATE")
+            .bind(rule_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        let (current, category_id) = current.ok_or_else(|| format!("Rule not found: {}", rule_id))?;
+        if current.trim() != expected_definition.trim() {
+            return Err(format!("Rule {} has changed since the repair was planned; plan it again", rule_id));
+        }
+        if let Some(category_id) = category_id {
+            let violations = categories.check_rule(category_id, &ast);
+            if !violations.is_empty() {
+                let messages: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+                return Err(messages.join("; "));
+            }
+        }
+
+        let (version,): (i32,) = sqlx::query_as("
+            UPDATE rules
+            SET rule_definition = $2, parsed_ast = $3, version = COALESCE(version, 1) + 1,
+                updated_by = $4, updated_at = CURRENT_TIMESTAMP
+            WHERE rule_id = $1
+            RETURNING version
+        ")
+            .bind(rule_id)
+            .bind(definition.trim())
+            .bind(&parsed_ast)
+            .bind(applied_by)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to update rule: {}", e))?;
+
+        record_rule_version(&mut tx, rule_id, &format!("Repair: {}", description), applied_by).await?;
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+        Ok(version)
+    }
+
     // Get existing rules
     pub async fn get_existing_rules(
         pool: &DbPool,
@@ -215,6 +635,235 @@ impl RuleOperations {
         Ok(rules)
     }
 
+    // Search rules by text and tags
+    pub async fn search_rules(
+        pool: &DbPool,
+        search_term: Option<&str>,
+        tag_filter: &TagFilter,
+    ) -> Result<Vec<serde_json::Value>, String> {
+        let query = format!(
+            "
+            SELECT rule_id, rule_name, description, status, created_at, {} AS tags
+            FROM rules
+            WHERE status != 'deprecated'
+              AND ($1::text IS NULL OR rule_id ILIKE $1 OR rule_name ILIKE $1
+                   OR description ILIKE $1 OR rule_definition ILIKE $1)
+              AND {}
+            ORDER BY created_at DESC
+            ",
+            tag_list_expr(TagTarget::Rule, "rule_id"),
+            tag_match_clause(TagTarget::Rule, "rule_id", 2, 3),
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(search_term.map(|term| format!("%{}%", term)))
+            .bind(tag_filter.normalized_tags())
+            .bind(tag_filter.match_all)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        let rules = rows.iter().map(|row| serde_json::json!({
+            "rule_id": row.get::<&str, _>("rule_id"),
+            "rule_name": row.get::<&str, _>("rule_name"),
+            "description": row.get::<Option<&str>, _>("description"),
+            "status": row.get::<&str, _>("status"),
+            "created_at": row.get::<DateTime<Utc>, _>("created_at").to_rfc3339(),
+            "tags": row.get::<Vec<String>, _>("tags")
+        })).collect();
+
+        Ok(rules)
+    }
+
+    // Plan a search-and-replace across all stored rule definitions without writing anything
+    pub async fn preview_rule_rewrite(
+        pool: &DbPool,
+        rewrite: RuleRewrite,
+    ) -> Result<RewritePlan, String> {
+        rewrite.validate()?;
+        let categories = Self::get_rule_categories(pool).await?;
+        let mut conn = pool.acquire()
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        let rules = load_rules_matching(&mut conn, &rewrite.pattern, false).await?;
+        Ok(RewritePlan::new(rewrite, &rules, &categories))
+    }
+
+    // Rewrite the stored definitions, but only if every rewritten rule is still
+    // valid and the number of affected rules matches what the caller previewed
+    pub async fn apply_rule_rewrite(
+        pool: &DbPool,
+        rewrite: RuleRewrite,
+        expected_rules: Option<usize>,
+        applied_by: Option<&str>,
+    ) -> Result<RewritePlan, String> {
+        rewrite.validate()?;
+        let categories = Self::get_rule_categories(pool).await?;
+        let mut tx = pool.begin()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let plan = rewrite_stored_rules(&mut tx, rewrite, &categories, expected_rules, applied_by).await?;
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+        Ok(plan)
+    }
+
+    // Rename a derived attribute and every stored rule that reads or assigns it, in one
+    // transaction. Rules are rewritten as an AST-mode rewrite, so `expected_rules` comes
+    // from preview_rule_rewrite with the same names in that mode.
+    pub async fn rename_attribute(
+        pool: &DbPool,
+        old_name: &str,
+        new_name: &str,
+        expected_rules: Option<usize>,
+        renamed_by: Option<&str>,
+    ) -> Result<RewritePlan, String> {
+        let rewrite = RuleRewrite {
+            pattern: old_name.to_string(),
+            replacement: new_name.to_string(),
+            mode: RewriteMode::Ast,
+        };
+        rewrite.validate()?;
+        let categories = Self::get_rule_categories(pool).await?;
+        let mut tx = pool.begin()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let taken: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM derived_attributes WHERE name = $1)")
+            .bind(new_name)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        if taken {
+            return Err(format!("A derived attribute named '{}' already exists", new_name));
+        }
+
+        sqlx::query("
+            UPDATE derived_attributes
+            SET name = $2, updated_at = CURRENT_TIMESTAMP
+            WHERE name = $1
+        ")
+            .bind(old_name)
+            .bind(new_name)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to rename attribute: {}", e))?;
+
+        let plan = rewrite_stored_rules(&mut tx, rewrite, &categories, expected_rules, renamed_by).await?;
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+        Ok(plan)
+    }
+
+    // Every non-deprecated rule with its attribute, category and how its test
+    // cases fare against the current definition, as an XLSX workbook for sign-off
+    pub async fn export_attribute_workbook(
+        pool: &DbPool,
+    ) -> Result<Vec<u8>, String> {
+        let mut conn = pool.acquire()
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        let mut rows = load_workbook_rows(&mut conn, false).await?;
+        drop(conn);
+
+        for row in rows.iter_mut().filter(|row| row.tests > 0) {
+            // A rule that no longer parses passes none of its tests
+            if let Ok(report) = RuleTestOperations::run_rule_tests(pool, &row.rule_id).await {
+                row.tests_passed = report.passed;
+            }
+        }
+        write_workbook(&rows)
+    }
+
+    // Compare a reviewed workbook with the stored rules without writing anything
+    pub async fn preview_attribute_workbook_import(
+        pool: &DbPool,
+        workbook: &[u8],
+    ) -> Result<WorkbookReview, String> {
+        let reviewed = read_workbook(workbook)?;
+        let categories = Self::get_rule_categories(pool).await?;
+        let types = Self::get_dictionary_type_env(pool).await?;
+        let mut conn = pool.acquire()
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        let rows = load_workbook_rows(&mut conn, false).await?;
+        Ok(WorkbookReview::new(&rows, &reviewed, &categories, &types))
+    }
+
+    // Save the formulas and descriptions edited in a reviewed workbook, but only if
+    // every row is valid and the number of edits matches what the caller previewed
+    pub async fn import_attribute_workbook(
+        pool: &DbPool,
+        workbook: &[u8],
+        expected_edits: Option<usize>,
+        imported_by: Option<&str>,
+    ) -> Result<WorkbookReview, String> {
+        let reviewed = read_workbook(workbook)?;
+        let categories = Self::get_rule_categories(pool).await?;
+        let types = Self::get_dictionary_type_env(pool).await?;
+        let mut tx = pool.begin()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let rows = load_workbook_rows(&mut tx, true).await?;
+        let review = WorkbookReview::new(&rows, &reviewed, &categories, &types);
+        if let Some(expected) = expected_edits {
+            if review.edits.len() != expected {
+                return Err(format!(
+                    "The preview showed {} edited rules but {} differ now; preview again",
+                    expected,
+                    review.edits.len()
+                ));
+            }
+        }
+        if !review.is_valid() {
+            return Err(format!("The workbook has problems: {}", review.problems().join(" | ")));
+        }
+        if review.edits.is_empty() {
+            return Err("No rules would change".to_string());
+        }
+
+        for edit in &review.edits {
+            let (_, ast) = parse_rule(&edit.formula_after)
+                .map_err(|e| format!("Failed to parse rule {}: {}", edit.rule_id, e))?;
+            let parsed_ast = serde_json::to_value(&ast)
+                .map_err(|e| format!("Failed to serialize rule AST: {}", e))?;
+
+            sqlx::query("
+                UPDATE rules
+                SET rule_definition = $2, parsed_ast = $3, description = $4, version = version + 1,
+                    updated_by = $5, updated_at = CURRENT_TIMESTAMP
+                WHERE rule_id = $1
+            ")
+                .bind(&edit.rule_id)
+                .bind(&edit.formula_after)
+                .bind(&parsed_ast)
+                .bind(&edit.description_after)
+                .bind(imported_by)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to update rule {}: {}", edit.rule_id, e))?;
+
+            let change = format!("Workbook review, row {}", edit.row);
+            record_rule_version(&mut tx, &edit.rule_id, &change, imported_by).await?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+        Ok(review)
+    }
+
     // Get rule by ID
     pub async fn get_rule_by_id(
         pool: &DbPool,
@@ -243,6 +892,211 @@ impl RuleOperations {
         Ok(rule)
     }
 
+    // Every saved version of a rule, newest first
+    pub async fn get_rule_history(
+        pool: &DbPool,
+        rule_id: &str,
+    ) -> Result<Vec<RuleVersion>, String> {
+        let rows = sqlx::query(&format!("{} WHERE rule_id = $1 ORDER BY version DESC", VERSION_QUERY))
+            .bind(rule_id)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        Ok(rows.iter().map(version_from_row).collect())
+    }
+
+    pub async fn get_rule_version(
+        pool: &DbPool,
+        rule_id: &str,
+        version: i32,
+    ) -> Result<RuleVersion, String> {
+        let row = sqlx::query(&format!("{} WHERE rule_id = $1 AND version = $2", VERSION_QUERY))
+            .bind(rule_id)
+            .bind(version)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?
+            .ok_or_else(|| format!("Rule {} has no version {}", rule_id, version))?;
+
+        Ok(version_from_row(&row))
+    }
+
+    // Structural diff from one version of a rule to another
+    pub async fn diff_rule_versions(
+        pool: &DbPool,
+        rule_id: &str,
+        from_version: i32,
+        to_version: i32,
+    ) -> Result<RuleVersionDiff, String> {
+        let from = Self::get_rule_version(pool, rule_id, from_version).await?;
+        let to = Self::get_rule_version(pool, rule_id, to_version).await?;
+        Ok(RuleVersionDiff::new(&from, &to))
+    }
+
+    // What every rule said at an instant: the version of each in effect then
+    pub async fn get_rules_as_of(
+        pool: &DbPool,
+        at: DateTime<Utc>,
+    ) -> Result<Vec<RuleVersion>, String> {
+        // Every version whose period contains the instant; the newest per rule wins
+        let rows = sqlx::query(&format!(
+            "{} WHERE COALESCE(valid_from, created_at) <= $1 AND (valid_to IS NULL OR valid_to > $1)",
+            VERSION_QUERY
+        ))
+            .bind(at)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        let versions: Vec<RuleVersion> = rows.iter().map(version_from_row).collect();
+        Ok(versions_in_effect(&versions, at).into_iter().cloned().collect())
+    }
+
+    // Every version of every rule with its effective period, for loading into
+    // RulesEngine and evaluating with evaluate_as_of
+    pub async fn get_dated_rule_bundle(
+        pool: &DbPool,
+    ) -> Result<RuleBundle, String> {
+        let rows = sqlx::query(&format!("{} ORDER BY rule_id, version", VERSION_QUERY))
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        Ok(RuleBundle::new(rows.iter().map(|row| ExportedRule::from(&version_from_row(row))).collect()))
+    }
+
+    // Every non-deprecated rule with the attribute it derives, for the dependency graph panel
+    pub async fn get_rule_dependency_graph(
+        pool: &DbPool,
+        scope: &GraphScope,
+    ) -> Result<DependencyGraphPayload, String> {
+        let query = "
+            SELECT r.rule_id, r.rule_name, r.status, r.rule_definition, da.attribute_name AS target_attribute
+            FROM rules r
+            LEFT JOIN derived_attributes da ON da.id = r.target_attribute_id
+            WHERE r.status != 'deprecated'
+            ORDER BY r.rule_id
+        ";
+
+        let rows = sqlx::query(query)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        let rules: Vec<GraphRule> = rows.iter().map(|row| GraphRule {
+            rule_id: row.get("rule_id"),
+            rule_name: row.get("rule_name"),
+            status: row.get("status"),
+            target_attribute: row.get("target_attribute"),
+            definition: row.get("rule_definition"),
+        }).collect();
+
+        Ok(dependency_graph_payload(&rules, scope))
+    }
+
+    // Check the active rules: each must parse, and no two may write the same attribute
+    // for the same inputs. A rule's target is its derived attribute, else what it assigns.
+    // Variants of a rule are compared under their applicability conditions, and only with
+    // each other: the default variant only runs where none of them applies.
+    pub async fn check_database_rule_state(
+        pool: &DbPool,
+    ) -> Result<RuleStateReport, String> {
+        let query = "
+            SELECT r.rule_id, r.rule_definition, r.applies_to, da.attribute_name AS target_attribute
+            FROM rules r
+            LEFT JOIN derived_attributes da ON da.id = r.target_attribute_id
+            WHERE r.status = 'active'
+            ORDER BY r.rule_id
+        ";
+
+        let rows = sqlx::query(query)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        let mut invalid_rules = Vec::new();
+        let mut parsed: Vec<(String, String, Expression)> = Vec::new();
+        let mut variants: Vec<(String, String, Expression)> = Vec::new();
+        for row in &rows {
+            let rule_id: String = row.get("rule_id");
+            let definition: String = row.get("rule_definition");
+            let expression = match parse_rule(&definition) {
+                Ok((remaining, _)) if !remaining.trim().is_empty() => {
+                    let message = format!("Unexpected input after rule: {}", remaining.trim());
+                    invalid_rules.push(InvalidRule { rule_id, message });
+                    continue;
+                }
+                Ok((_, expression)) => expression,
+                Err(e) => {
+                    let message = format!("Failed to parse rule: {}", e);
+                    invalid_rules.push(InvalidRule { rule_id, message });
+                    continue;
+                }
+            };
+            let target = match (row.get::<Option<String>, _>("target_attribute"), &expression) {
+                (Some(attribute), _) => attribute,
+                (None, Expression::Assignment { target, .. }) => target.clone(),
+                (None, _) => rule_id.clone(),
+            };
+            match row.get::<Json<Applicability>, _>("applies_to").0.condition() {
+                Some(condition) => {
+                    let value = match expression {
+                        Expression::Assignment { value, .. } => *value,
+                        expression => expression,
+                    };
+                    let guarded = Expression::Conditional {
+                        condition: Box::new(condition),
+                        then_expr: Box::new(value),
+                        else_expr: None,
+                    };
+                    variants.push((rule_id, target, guarded));
+                }
+                None => parsed.push((rule_id, target, expression)),
+            }
+        }
+
+        let mut conflicts = find_conflicts(
+            parsed.iter().map(|(rule_id, target, expression)| (rule_id.as_str(), target.as_str(), expression)),
+        );
+        conflicts.extend(find_conflicts(
+            variants.iter().map(|(rule_id, target, expression)| (rule_id.as_str(), target.as_str(), expression)),
+        ));
+        Ok(RuleStateReport {
+            active_rules: rows.len(),
+            invalid_rules,
+            conflicts,
+        })
+    }
+
+    // Every active rule, ordered by dependency into one Rust crate with a function per
+    // derived attribute; write it out with RustProject::write_to and build it with cargo
+    pub async fn generate_rust_project(
+        pool: &DbPool,
+        crate_name: &str,
+    ) -> Result<RustProject, String> {
+        let query = "
+            SELECT r.rule_id, r.rule_definition, da.attribute_name AS target_attribute
+            FROM rules r
+            LEFT JOIN derived_attributes da ON da.id = r.target_attribute_id
+            WHERE r.status = 'active'
+            ORDER BY r.rule_id
+        ";
+
+        let rows = sqlx::query(query)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        let rules: Vec<ProjectRule> = rows.iter().map(|row| ProjectRule {
+            rule_id: row.get("rule_id"),
+            attribute: row.get("target_attribute"),
+            definition: row.get("rule_definition"),
+        }).collect();
+
+        generate_rust_project(crate_name, &rules).map_err(|e| format!("{:#}", e))
+    }
+
     // Log rule execution (future use)
     pub async fn log_rule_execution(
         pool: &DbPool,
@@ -267,4 +1121,286 @@ impl RuleOperations {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    // p95 latency of a rule's successful executions recorded in rule_executions
+    pub async fn get_rule_latency_history(
+        pool: &DbPool,
+        rule_id: &str,
+    ) -> Result<LatencyHistory, String> {
+        let query = "
+            SELECT COUNT(*), percentile_cont(0.95) WITHIN GROUP (ORDER BY e.execution_duration_ms)
+            FROM rule_executions e
+            JOIN rules r ON r.id = e.rule_id
+            WHERE r.rule_id = $1 AND e.success AND e.execution_duration_ms IS NOT NULL
+        ";
+
+        let (samples, p95_ms): (i64, Option<f64>) = sqlx::query_as(query)
+            .bind(rule_id)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| format!("Failed to load rule latency: {}", e))?;
+
+        Ok(LatencyHistory {
+            samples: samples as u64,
+            p95_us: p95_ms.unwrap_or(0.0) * 1_000.0,
+        })
+    }
+
+    // Static cost of a rule's current definition, with its observed latency
+    // once enough executions are recorded; shown before the rule is activated
+    pub async fn estimate_rule_cost(
+        pool: &DbPool,
+        rule_id: &str,
+    ) -> Result<RuleCost, String> {
+        let definition: Option<String> = sqlx::query_scalar("SELECT rule_definition FROM rules WHERE rule_id = $1")
+            .bind(rule_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        let definition = definition.ok_or_else(|| format!("Rule not found: {}", rule_id))?;
+
+        let expression = match parse_rule(&definition) {
+            Ok((remaining, expression)) if remaining.trim().is_empty() => expression,
+            Ok((remaining, _)) => return Err(format!("Rule {} has unparsed input: {}", rule_id, remaining.trim())),
+            Err(e) => return Err(format!("Rule {} doesn't parse: {}", rule_id, e)),
+        };
+
+        let history = Self::get_rule_latency_history(pool, rule_id).await?;
+        Ok(estimate_rule_cost(&expression).with_history(&history))
+    }
+
+    // Statistics over every stored rule and the versions saved in the last `weeks` weeks,
+    // for the dashboard panel. A rule in repair has been since the first in_repair
+    // version after its last version in another status.
+    pub async fn get_rule_statistics(pool: &DbPool, weeks: usize) -> Result<RuleStatistics, String> {
+        let rows = sqlx::query("
+            SELECT r.rule_id, r.status, rc.name AS category, r.rule_definition,
+                   CASE WHEN r.status = 'in_repair' THEN COALESCE((
+                       SELECT MIN(v.created_at) FROM rule_versions v
+                       WHERE v.rule_id = r.rule_id AND v.status = 'in_repair'
+                         AND v.version > COALESCE((
+                             SELECT MAX(w.version) FROM rule_versions w
+                             WHERE w.rule_id = r.rule_id AND w.status <> 'in_repair'
+                         ), 0)
+                   ), r.updated_at) END AS in_repair_since
+            FROM rules r
+            LEFT JOIN rule_categories rc ON rc.id = r.category_id
+            ORDER BY r.rule_id
+        ")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        let rules: Vec<StatsRule> = rows.iter().map(|row| StatsRule {
+            rule_id: row.get("rule_id"),
+            status: row.get("status"),
+            category: row.get("category"),
+            definition: row.get("rule_definition"),
+            in_repair_since: row.get("in_repair_since"),
+        }).collect();
+
+        let now = Utc::now();
+        let changes = sqlx::query("SELECT created_by, created_at FROM rule_versions WHERE created_at >= $1")
+            .bind(now - chrono::Duration::weeks(weeks as i64 + 1))
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        let changes: Vec<StatsChange> = changes.iter().map(|row| StatsChange {
+            created_by: row.get("created_by"),
+            created_at: row.get("created_at"),
+        }).collect();
+
+        Ok(rule_statistics(&rules, &changes, weeks, now))
+    }
+}
+
+// Rewrite the stored definitions inside the caller's transaction; see apply_rule_rewrite
+async fn rewrite_stored_rules(
+    conn: &mut PgConnection,
+    rewrite: RuleRewrite,
+    categories: &CategoryTree,
+    expected_rules: Option<usize>,
+    applied_by: Option<&str>,
+) -> Result<RewritePlan, String> {
+    let rules = load_rules_matching(conn, &rewrite.pattern, true).await?;
+    let plan = RewritePlan::new(rewrite, &rules, categories);
+    if let Some(expected) = expected_rules {
+        if plan.rules.len() != expected {
+            return Err(format!(
+                "The preview showed {} rules to rewrite but {} would change now; preview again",
+                expected,
+                plan.rules.len()
+            ));
+        }
+    }
+    if plan.rules.is_empty() {
+        return Err("No rules would change".to_string());
+    }
+    if !plan.is_valid() {
+        let invalid: Vec<String> = plan.rules
+            .iter()
+            .filter(|rule| !rule.errors.is_empty())
+            .map(|rule| format!("{}: {}", rule.rule_id, rule.errors.join("; ")))
+            .collect();
+        return Err(format!("Rewritten rules would be invalid: {}", invalid.join(" | ")));
+    }
+
+    for rule in &plan.rules {
+        let (_, ast) = parse_rule(&rule.after)
+            .map_err(|e| format!("Failed to parse rule {}: {}", rule.rule_id, e))?;
+        let parsed_ast = serde_json::to_value(&ast)
+            .map_err(|e| format!("Failed to serialize rule AST: {}", e))?;
+
+        sqlx::query("
+            UPDATE rules
+            SET rule_definition = $2, parsed_ast = $3, version = version + 1,
+                updated_by = $4, updated_at = CURRENT_TIMESTAMP
+            WHERE rule_id = $1
+        ")
+            .bind(&rule.rule_id)
+            .bind(&rule.after)
+            .bind(&parsed_ast)
+            .bind(applied_by)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to update rule {}: {}", rule.rule_id, e))?;
+
+        let change = format!("Rewrite '{}' -> '{}'", plan.rewrite.pattern, plan.rewrite.replacement);
+        record_rule_version(conn, &rule.rule_id, &change, applied_by).await?;
+    }
+
+    Ok(plan)
+}
+
+// Non-deprecated rules as workbook rows, with how many test cases each has.
+// A rule without a derived attribute is listed under the attribute it assigns.
+async fn load_workbook_rows(
+    conn: &mut PgConnection,
+    for_update: bool,
+) -> Result<Vec<WorkbookRow>, String> {
+    let query = format!(
+        "
+        SELECT r.rule_id, r.rule_definition, r.description, r.status,
+               da.attribute_name, da.data_type, c.category_key,
+               (SELECT count(*) FROM rule_test_cases t WHERE t.rule_id = r.rule_id) AS tests
+        FROM rules r
+        LEFT JOIN derived_attributes da ON da.id = r.target_attribute_id
+        LEFT JOIN rule_categories c ON c.id = r.category_id
+        WHERE r.status != 'deprecated'
+        ORDER BY r.rule_id
+        {}
+        ",
+        if for_update { "FOR UPDATE OF r" } else { "" }
+    );
+
+    let rows = sqlx::query(&query)
+        .fetch_all(conn)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    let mut workbook_rows: Vec<WorkbookRow> = rows.iter().map(|row| {
+        let formula: String = row.get("rule_definition");
+        let attribute = row.get::<Option<String>, _>("attribute_name").unwrap_or_else(|| match parse_rule(&formula) {
+            Ok((_, Expression::Assignment { target, .. })) => target,
+            _ => String::new(),
+        });
+        WorkbookRow {
+            rule_id: row.get("rule_id"),
+            attribute,
+            data_type: row.get("data_type"),
+            category: row.get("category_key"),
+            formula,
+            description: row.get("description"),
+            status: row.get("status"),
+            tests: row.get::<i64, _>("tests") as usize,
+            tests_passed: 0,
+        }
+    }).collect();
+
+    workbook_rows.sort_by(|a, b| a.attribute.cmp(&b.attribute).then_with(|| a.rule_id.cmp(&b.rule_id)));
+    Ok(workbook_rows)
+}
+
+// Active rules whose definition mentions `pattern`, ignoring case so AST-mode
+// renames of upper-cased function names still find their rules
+async fn load_rules_matching(
+    conn: &mut PgConnection,
+    pattern: &str,
+    for_update: bool,
+) -> Result<Vec<StoredRule>, String> {
+    let query = format!(
+        "
+        SELECT rule_id, rule_name, category_id, rule_definition
+        FROM rules
+        WHERE status != 'deprecated'
+          AND strpos(lower(rule_definition), lower($1)) > 0
+        ORDER BY rule_id
+        {}
+        ",
+        if for_update { "FOR UPDATE" } else { "" }
+    );
+
+    let rows = sqlx::query(&query)
+        .bind(pattern)
+        .fetch_all(conn)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    Ok(rows.iter().map(|row| StoredRule {
+        rule_id: row.get("rule_id"),
+        rule_name: row.get("rule_name"),
+        category_id: row.get("category_id"),
+        definition: row.get("rule_definition"),
+    }).collect())
+}
+
+const RULE_STATUSES: [&str; 6] = ["draft", "pending_approval", "active", "inactive", "deprecated", "in_repair"];
+
+const VERSION_QUERY: &str = "
+    SELECT rule_id, version, rule_name, description, rule_definition, status,
+           change_description, created_by, created_at, valid_from, valid_to, applies_to
+    FROM rule_versions
+";
+
+fn version_from_row(row: &sqlx::postgres::PgRow) -> RuleVersion {
+    RuleVersion {
+        rule_id: row.get("rule_id"),
+        version: row.get("version"),
+        rule_name: row.get("rule_name"),
+        description: row.get("description"),
+        rule_definition: row.get("rule_definition"),
+        status: row.get("status"),
+        change_description: row.get("change_description"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        valid_from: row.get("valid_from"),
+        valid_to: row.get("valid_to"),
+        applies_to: row.get::<Json<Applicability>, _>("applies_to").0,
+    }
+}
+
+// Snapshot a rule as it now stands into its history
+pub(super) async fn record_rule_version(
+    conn: &mut PgConnection,
+    rule_id: &str,
+    change_description: &str,
+    created_by: Option<&str>,
+) -> Result<(), String> {
+    sqlx::query("
+        INSERT INTO rule_versions (
+            rule_id, version, rule_name, description, rule_definition, parsed_ast, status,
+            change_description, created_by, valid_from, valid_to, applies_to
+        )
+        SELECT rule_id, COALESCE(version, 1), rule_name, description, rule_definition, parsed_ast, status, $2, $3,
+               valid_from, valid_to, applies_to
+        FROM rules
+        WHERE rule_id = $1
+    ")
+        .bind(rule_id)
+        .bind(change_description)
+        .bind(created_by)
+        .execute(conn)
+        .await
+        .map_err(|e| format!("Failed to record version of rule {}: {}", rule_id, e))?;
+
+    Ok(())
+}
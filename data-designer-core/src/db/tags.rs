@@ -0,0 +1,336 @@
+use super::DbPool;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, Row};
+
+// Tag-related DTOs
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Tag {
+    pub id: i32,
+    pub name: String,
+    pub color: Option<String>,
+    pub usage_count: i64,
+}
+
+/// What a tag is attached to; the key is an attribute full_path or a rule_id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagTarget {
+    Attribute,
+    Rule,
+}
+
+impl TagTarget {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TagTarget::Attribute => "attribute",
+            TagTarget::Rule => "rule",
+        }
+    }
+}
+
+/// Tag filter shared by dictionary and rule search; an empty filter matches everything
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TagFilter {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Require every tag rather than any of them
+    #[serde(default)]
+    pub match_all: bool,
+}
+
+impl TagFilter {
+    /// Tag names trimmed, lower-cased and de-duplicated, as they are stored
+    pub fn normalized_tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self.tags.iter()
+            .map(|tag| normalize_tag(tag))
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+}
+
+pub use crate::storage::normalize_tag;
+
+/// SQL condition matching `target_key` against a `$n::text[]` tag list and a `$m::bool` match-all flag
+pub(crate) fn tag_match_clause(target: TagTarget, target_key: &str, tags_param: usize, match_all_param: usize) -> String {
+    format!(
+        "(cardinality(${tags}::text[]) = 0 OR (
+            SELECT COUNT(DISTINCT t.name)
+            FROM tag_assignments ta
+            JOIN tags t ON t.id = ta.tag_id
+            WHERE ta.target_type = '{target}' AND ta.target_key = {key} AND t.name = ANY(${tags}::text[])
+        ) >= CASE WHEN ${all}::bool THEN cardinality(${tags}::text[]) ELSE 1 END)",
+        tags = tags_param,
+        all = match_all_param,
+        target = target.as_str(),
+        key = target_key,
+    )
+}
+
+/// SQL expression listing the tag names on `target_key`
+pub(crate) fn tag_list_expr(target: TagTarget, target_key: &str) -> String {
+    format!(
+        "ARRAY(
+            SELECT t.name FROM tag_assignments ta JOIN tags t ON t.id = ta.tag_id
+            WHERE ta.target_type = '{}' AND ta.target_key = {}
+            ORDER BY t.name
+        )",
+        target.as_str(),
+        target_key,
+    )
+}
+
+/// Attach one normalized tag on an open connection, creating the tag if needed
+pub(crate) async fn assign_tag_on(
+    conn: &mut PgConnection,
+    tag: &str,
+    target: TagTarget,
+    target_key: &str,
+    assigned_by: Option<&str>,
+) -> Result<(), String> {
+    let tag_id: (i32,) = sqlx::query_as("
+        INSERT INTO tags (name) VALUES ($1)
+        ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name
+        RETURNING id
+    ")
+        .bind(tag)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to create tag '{}': {}", tag, e))?;
+
+    sqlx::query("
+        INSERT INTO tag_assignments (tag_id, target_type, target_key, assigned_by)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT DO NOTHING
+    ")
+        .bind(tag_id.0)
+        .bind(target.as_str())
+        .bind(target_key)
+        .bind(assigned_by)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to assign tag '{}': {}", tag, e))?;
+
+    Ok(())
+}
+
+/// Detach normalized tags on an open connection; returns how many assignments were removed
+pub(crate) async fn unassign_tags_on(
+    conn: &mut PgConnection,
+    tags: &[String],
+    target: TagTarget,
+    target_key: &str,
+) -> Result<u64, String> {
+    let query = "
+        DELETE FROM tag_assignments ta
+        USING tags t
+        WHERE ta.tag_id = t.id
+          AND ta.target_type = $1 AND ta.target_key = $2
+          AND t.name = ANY($3)
+    ";
+
+    sqlx::query(query)
+        .bind(target.as_str())
+        .bind(target_key)
+        .bind(tags)
+        .execute(conn)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(|e| format!("Failed to remove tags: {}", e))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterScope {
+    Dictionary,
+    Rules,
+}
+
+impl FilterScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FilterScope::Dictionary => "dictionary",
+            FilterScope::Rules => "rules",
+        }
+    }
+}
+
+/// A named search preset for one user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedFilter {
+    #[serde(default)]
+    pub id: Option<i32>,
+    pub user_id: String,
+    pub name: String,
+    pub scope: FilterScope,
+    #[serde(default)]
+    pub search_text: Option<String>,
+    #[serde(default)]
+    pub tag_filter: TagFilter,
+}
+
+// Tag database operations
+pub struct TagOperations;
+
+impl TagOperations {
+    // List all tags with how often each is used
+    pub async fn list_tags(
+        pool: &DbPool,
+    ) -> Result<Vec<Tag>, String> {
+        let query = "
+            SELECT t.id, t.name, t.color, COUNT(ta.tag_id) AS usage_count
+            FROM tags t
+            LEFT JOIN tag_assignments ta ON ta.tag_id = t.id
+            GROUP BY t.id, t.name, t.color
+            ORDER BY t.name
+        ";
+
+        sqlx::query_as::<_, Tag>(query)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
+    // Tags on one attribute or rule
+    pub async fn get_tags_for(
+        pool: &DbPool,
+        target: TagTarget,
+        target_key: &str,
+    ) -> Result<Vec<String>, String> {
+        let query = "
+            SELECT t.name
+            FROM tag_assignments ta
+            JOIN tags t ON t.id = ta.tag_id
+            WHERE ta.target_type = $1 AND ta.target_key = $2
+            ORDER BY t.name
+        ";
+
+        let rows = sqlx::query(query)
+            .bind(target.as_str())
+            .bind(target_key)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        Ok(rows.iter().map(|row| row.get::<String, _>("name")).collect())
+    }
+
+    // Attach tags, creating any that do not exist yet
+    pub async fn assign_tags(
+        pool: &DbPool,
+        target: TagTarget,
+        target_key: &str,
+        tags: &[String],
+        assigned_by: Option<&str>,
+    ) -> Result<(), String> {
+        let tags = TagFilter { tags: tags.to_vec(), match_all: false }.normalized_tags();
+        let mut tx = pool.begin()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        for tag in &tags {
+            assign_tag_on(&mut tx, tag, target, target_key, assigned_by).await?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to commit transaction: {}", e))
+    }
+
+    // Detach tags; returns how many assignments were removed
+    pub async fn unassign_tags(
+        pool: &DbPool,
+        target: TagTarget,
+        target_key: &str,
+        tags: &[String],
+    ) -> Result<u64, String> {
+        let tags = TagFilter { tags: tags.to_vec(), match_all: false }.normalized_tags();
+        let mut conn = pool.acquire()
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        unassign_tags_on(&mut conn, &tags, target, target_key).await
+    }
+
+    // Saved filter presets for a user, optionally limited to one scope
+    pub async fn list_saved_filters(
+        pool: &DbPool,
+        user_id: &str,
+        scope: Option<FilterScope>,
+    ) -> Result<Vec<SavedFilter>, String> {
+        let query = "
+            SELECT id, user_id, name, scope, search_text, tags, match_all
+            FROM saved_filters
+            WHERE user_id = $1 AND ($2::text IS NULL OR scope = $2)
+            ORDER BY scope, name
+        ";
+
+        let rows = sqlx::query(query)
+            .bind(user_id)
+            .bind(scope.map(|s| s.as_str()))
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+
+        Ok(rows.iter().map(|row| SavedFilter {
+            id: Some(row.get("id")),
+            user_id: row.get("user_id"),
+            name: row.get("name"),
+            scope: match row.get::<&str, _>("scope") {
+                "rules" => FilterScope::Rules,
+                _ => FilterScope::Dictionary,
+            },
+            search_text: row.get("search_text"),
+            tag_filter: TagFilter {
+                tags: row.get("tags"),
+                match_all: row.get("match_all"),
+            },
+        }).collect())
+    }
+
+    // Create or replace a preset; presets are unique per user, scope and name
+    pub async fn save_filter(
+        pool: &DbPool,
+        filter: &SavedFilter,
+    ) -> Result<i32, String> {
+        let query = "
+            INSERT INTO saved_filters (user_id, name, scope, search_text, tags, match_all)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (user_id, scope, name) DO UPDATE SET
+                search_text = EXCLUDED.search_text,
+                tags = EXCLUDED.tags,
+                match_all = EXCLUDED.match_all,
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING id
+        ";
+
+        let row: (i32,) = sqlx::query_as(query)
+            .bind(&filter.user_id)
+            .bind(&filter.name)
+            .bind(filter.scope.as_str())
+            .bind(&filter.search_text)
+            .bind(filter.tag_filter.normalized_tags())
+            .bind(filter.tag_filter.match_all)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| format!("Failed to save filter: {}", e))?;
+
+        Ok(row.0)
+    }
+
+    pub async fn delete_saved_filter(
+        pool: &DbPool,
+        user_id: &str,
+        filter_id: i32,
+    ) -> Result<bool, String> {
+        sqlx::query("DELETE FROM saved_filters WHERE id = $1 AND user_id = $2")
+            .bind(filter_id)
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .map(|result| result.rows_affected() > 0)
+            .map_err(|e| format!("Failed to delete filter: {}", e))
+    }
+}
@@ -0,0 +1,110 @@
+//! What an editor session may reach through the language servers
+//!
+//! Editors present a token in `initializationOptions.authToken`, and the
+//! database-backed features of dsl-lsp and cbu-dsl-lsp then work within the
+//! token's scope. A scope lists patterns of attribute names, rule IDs and
+//! command names, where `*` matches any run of characters: `["KYC_*"]`
+//! reaches the KYC rules only, `["*"]` everything and `[]` nothing.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EditorScope {
+    #[serde(default)]
+    pub attributes: Vec<String>,
+    #[serde(default)]
+    pub rules: Vec<String>,
+    #[serde(default)]
+    pub commands: Vec<String>,
+}
+
+impl EditorScope {
+    pub fn unrestricted() -> Self {
+        let all = vec!["*".to_string()];
+        Self { attributes: all.clone(), rules: all.clone(), commands: all }
+    }
+
+    pub fn allows_attribute(&self, attribute: &str) -> bool {
+        self.attributes.iter().any(|pattern| pattern_matches(pattern, attribute))
+    }
+
+    pub fn allows_rule(&self, rule_id: &str) -> bool {
+        self.rules.iter().any(|pattern| pattern_matches(pattern, rule_id))
+    }
+
+    pub fn allows_command(&self, command: &str) -> bool {
+        self.commands.iter().any(|pattern| pattern_matches(pattern, command))
+    }
+}
+
+/// Who is editing, and what they may reach until when
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EditorSession {
+    pub subject: String,
+    pub scope: EditorScope,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl EditorSession {
+    /// An editor presenting no token where none is required
+    pub fn anonymous() -> Self {
+        Self { subject: "anonymous".to_string(), scope: EditorScope::unrestricted(), expires_at: None }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+}
+
+/// Whether `value` matches `pattern`, `*` in it standing for any run of characters
+pub fn pattern_matches(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*`: the whole value must be the pattern
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scopes_match_names_by_pattern() {
+        assert!(pattern_matches("KYC_*", "KYC_RISK_SCORE"));
+        assert!(pattern_matches("*_score", "client_risk_score"));
+        assert!(pattern_matches("client.*.name", "client.ubo.name"));
+        assert!(pattern_matches("cbu-dsl.*", "cbu-dsl.previewChanges"));
+        assert!(pattern_matches("fee", "fee"));
+        assert!(!pattern_matches("fee", "fees"));
+        assert!(!pattern_matches("ab*ba", "aba"));
+        assert!(!pattern_matches("KYC_*", "AML_RISK"));
+
+        let scope = EditorScope {
+            attributes: vec!["client.*".to_string()],
+            rules: vec!["KYC_*".to_string(), "PRICING_FEE".to_string()],
+            commands: Vec::new(),
+        };
+        assert!(scope.allows_attribute("client.risk"));
+        assert!(!scope.allows_attribute("trade.notional"));
+        assert!(scope.allows_rule("PRICING_FEE") && !scope.allows_rule("PRICING_FEE_LU"));
+        assert!(!scope.allows_command("cbu-dsl.refreshReferenceData"));
+        assert!(EditorSession::anonymous().scope.allows_command("anything"));
+
+        let expired = EditorSession { expires_at: Some(Utc::now() - chrono::Duration::minutes(1)), ..EditorSession::anonymous() };
+        assert!(expired.is_expired() && !EditorSession::anonymous().is_expired());
+    }
+}
@@ -38,6 +38,10 @@ struct AnthropicEmbeddingResponse {
 
 /// Generate embedding for a text string (placeholder implementation)
 pub async fn generate_embedding(text: &str, api_key: Option<&str>) -> Result<Vec<f32>> {
+    if !crate::capabilities::enabled(crate::capabilities::Capability::Embeddings) {
+        anyhow::bail!("Embeddings are disabled in this deployment");
+    }
+
     // Placeholder implementation - would use actual OpenAI/Anthropic API
     let _ = api_key; // Suppress unused warning
 
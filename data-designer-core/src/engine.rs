@@ -1,31 +1,434 @@
 use crate::models::{DataDictionary, Value};
-use crate::evaluator::Facts; // <-- Import the new evaluator
-use anyhow::{Context, Result};
+use crate::evaluator::{
+    evaluate_fail_soft_with_failed_facts, evaluate_traced, evaluate_with_functions, EvaluationError, Facts, FunctionLibrary,
+};
+use crate::config::SecurityConfig;
+use crate::function_registry::FunctionRegistry;
+use crate::provenance::{Derivation, DerivationInput, Materialization};
+use crate::rule_bundle::{RuleBundle, SignedRuleBundle};
+use crate::rule_coverage::{CoverageReport, RuleCoverage};
+use crate::rule_graph::RuleGraph;
+use crate::rule_history::is_effective;
+use crate::rule_variants::{find_variant_conflicts, resolve_variant, Applicability, VariantConflict};
+use crate::transpiler::{DslRule, DslTranspiler};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use rayon::prelude::*;
+use serde_json::Value as JsonValue;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Instant;
 
 /// The RulesEngine is now an orchestrator that parses rules on demand.
 pub struct RulesEngine {
     dictionary: DataDictionary,
+    /// Every loaded version of every variant of each bundle rule, each with
+    /// its effective period
+    rules: HashMap<String, Vec<LoadedRule>>,
+    /// Bundle rules in dependency order, each after the rules it reads in any version
+    execution_order: Vec<String>,
+    /// Host functions every loaded rule can call
+    functions: FunctionRegistry,
+}
+
+/// Failures met by `evaluate_chain_fail_soft` so far
+#[derive(Default)]
+struct ChainFailures {
+    /// Attributes whose rules failed; rules reading them fail too
+    failed: HashSet<String>,
+    errors: BTreeMap<String, Vec<EvaluationError>>,
+}
+
+/// A bundle rule with its own function library, so the regex patterns it
+/// uses are compiled once rather than on every evaluation.
+struct LoadedRule {
+    rule: DslRule,
+    functions: FunctionLibrary,
+    rule_id: String,
+    version: i32,
+    valid_from: Option<DateTime<Utc>>,
+    valid_to: Option<DateTime<Utc>>,
+    /// A deprecated version retires the rule over its effective period
+    retired: bool,
+    /// The contexts this variant of the rule is for
+    applies_to: Applicability,
 }
 
 impl RulesEngine {
     /// Creates a new RulesEngine.
     pub fn new(dict: DataDictionary) -> Result<Self> {
-        Ok(Self { dictionary: dict })
+        Ok(Self {
+            dictionary: dict,
+            rules: HashMap::new(),
+            execution_order: Vec::new(),
+            functions: FunctionRegistry::new(),
+        })
+    }
+
+    /// Registers a host function, e.g. SCREEN_SANCTIONS, that rules can call
+    /// with exactly `arity` arguments. Rules already loaded see it at once.
+    /// Fails for names that aren't identifiers or belong to built-ins.
+    pub fn register_function(
+        &self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[JsonValue]) -> Result<JsonValue> + Send + Sync + 'static,
+    ) -> Result<()> {
+        self.functions.register(name, arity, move |args| {
+            let args: Vec<JsonValue> = args.iter().map(Value::to_json).collect();
+            Ok(Value::from_json(&function(&args)?))
+        })
+    }
+
+    /// The registry behind `register_function`, for listing registered
+    /// functions or sharing them with other function libraries
+    pub fn function_registry(&self) -> &FunctionRegistry {
+        &self.functions
+    }
+
+    /// Verifies a signed bundle against the trusted keys and loads its rules.
+    /// Returns the number of rules loaded.
+    pub fn load_bundle(&mut self, signed: &SignedRuleBundle, security: &SecurityConfig) -> Result<usize> {
+        let bundle = signed
+            .verify(&security.trusted_keys)
+            .context("Refusing to load rule bundle")?;
+        self.install_bundle(bundle)
+    }
+
+    /// Loads an unsigned bundle, unless the configuration requires signatures.
+    pub fn load_unsigned_bundle(&mut self, bundle: RuleBundle, security: &SecurityConfig) -> Result<usize> {
+        if security.require_signed_bundles {
+            bail!("Refusing to load unsigned rule bundle: signed bundles are required");
+        }
+        self.install_bundle(bundle)
+    }
+
+    fn install_bundle(&mut self, bundle: RuleBundle) -> Result<usize> {
+        let transpiler = DslTranspiler {
+            validation_enabled: true,
+            dependency_analysis: false,
+        };
+
+        let mut rules: HashMap<String, Vec<LoadedRule>> = HashMap::new();
+        for exported in &bundle.rules {
+            let parsed = transpiler.transpile_dsl_to_rules(&exported.definition).map_err(|errors| {
+                anyhow!(
+                    "Rule '{}' is invalid: {}",
+                    exported.metadata.rule_id,
+                    errors.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; ")
+                )
+            })?;
+            for rule in parsed {
+                let functions = FunctionLibrary::with_registry(self.functions.clone());
+                let metadata = &exported.metadata;
+                rules.entry(rule.name.clone()).or_default().push(LoadedRule {
+                    rule,
+                    functions,
+                    rule_id: metadata.rule_id.clone(),
+                    version: metadata.version,
+                    valid_from: metadata.valid_from,
+                    valid_to: metadata.valid_to,
+                    retired: metadata.status == "deprecated",
+                    applies_to: metadata.applies_to.clone(),
+                });
+            }
+        }
+
+        // One order that holds at every instant: each rule after what any of its versions reads
+        let dependencies: HashMap<&str, Vec<String>> = rules
+            .iter()
+            .map(|(name, versions)| {
+                let mut reads: Vec<String> = versions.iter().flat_map(|v| v.rule.dependencies.iter().cloned()).collect();
+                reads.sort();
+                reads.dedup();
+                (name.as_str(), reads)
+            })
+            .collect();
+        let graph = RuleGraph::new(dependencies.iter().map(|(name, reads)| (*name, reads.as_slice())));
+        let execution_order = graph.execution_order().context("Refusing to load rule bundle")?;
+
+        // Only replace the active rule set once the whole bundle is valid
+        let count = rules.values().map(Vec::len).sum();
+        self.rules = rules;
+        self.execution_order = execution_order;
+        Ok(count)
+    }
+
+    /// Evaluates every bundle rule in dependency order, feeding each rule's
+    /// output into the facts of the rules after it. Attributes already in
+    /// `initial_facts` are kept rather than recomputed, as in `evaluate_chain`.
+    /// Uses the version of each rule in effect now.
+    pub fn evaluate_all(&self, initial_facts: &Facts) -> Result<Facts> {
+        self.evaluate_as_of(Utc::now(), initial_facts)
+    }
+
+    /// Evaluates like `evaluate_all`, with the version of each rule that was
+    /// (or will be) in effect at `at`: the newest loaded version whose
+    /// effective period contains it. Rules with no version in effect then,
+    /// or retired by a deprecated one, are skipped.
+    #[tracing::instrument(name = "rules.evaluate_all", skip_all, fields(rules = self.execution_order.len(), as_of = %at))]
+    pub fn evaluate_as_of(&self, at: DateTime<Utc>, initial_facts: &Facts) -> Result<Facts> {
+        let mut facts = initial_facts.clone();
+        for name in &self.execution_order {
+            if facts.contains_key(name) {
+                continue;
+            }
+            let Some(loaded) = self.rule_as_of(name, at, &facts)? else { continue };
+            let value = self.evaluate_loaded_rule(name, loaded, &facts)?;
+            facts.insert(name.clone(), value);
+        }
+        Ok(facts)
+    }
+
+    /// Evaluates like `evaluate_all` and records, for each attribute derived,
+    /// the rule version that produced it and the values it read, for
+    /// `ProvenanceOperations::record_materialization`. Attributes given in
+    /// `initial_facts` count as source values even where a rule derives them.
+    #[tracing::instrument(name = "rules.materialize", skip_all, fields(rules = self.execution_order.len()))]
+    pub fn materialize(&self, initial_facts: &Facts) -> Result<(Facts, Materialization)> {
+        let at = Utc::now();
+        let mut facts = initial_facts.clone();
+        let mut materialization = Materialization::default();
+        for name in &self.execution_order {
+            if facts.contains_key(name) {
+                continue;
+            }
+            let Some(loaded) = self.rule_as_of(name, at, &facts)? else { continue };
+            let value = self.evaluate_loaded_rule(name, loaded, &facts)?;
+            let inputs = loaded
+                .rule
+                .dependencies
+                .iter()
+                .filter_map(|dependency| {
+                    Some(DerivationInput {
+                        attribute: dependency.clone(),
+                        value: facts.get(dependency)?.to_json(),
+                        derived: materialization.derivation(dependency).is_some(),
+                    })
+                })
+                .collect();
+            materialization.derivations.push(Derivation {
+                attribute: name.clone(),
+                value: value.to_json(),
+                rule_id: loaded.rule_id.clone(),
+                rule_version: loaded.version,
+                inputs,
+            });
+            facts.insert(name.clone(), value);
+        }
+        Ok((facts, materialization))
+    }
+
+    /// The variant of a bundle rule, in the version in effect at `at`, that
+    /// applies to the context in `facts`; an error when several variants do
+    fn rule_as_of(&self, name: &str, at: DateTime<Utc>, facts: &Facts) -> Result<Option<&LoadedRule>> {
+        let variants = self.variants_as_of(name, at);
+        Ok(resolve_variant(name, variants.into_iter().map(|loaded| (loaded.rule_id.as_str(), &loaded.applies_to, loaded)), facts)?)
+    }
+
+    /// Every variant of a bundle rule in the version in effect at `at`,
+    /// leaving out variants a deprecated version retires
+    fn variants_as_of(&self, name: &str, at: DateTime<Utc>) -> Vec<&LoadedRule> {
+        let mut newest: BTreeMap<&str, &LoadedRule> = BTreeMap::new();
+        for loaded in self.rules.get(name).into_iter().flatten() {
+            if !is_effective(loaded.valid_from, loaded.valid_to, at) {
+                continue;
+            }
+            let current = newest.entry(loaded.rule_id.as_str()).or_insert(loaded);
+            if loaded.version > current.version {
+                *current = loaded;
+            }
+        }
+        newest.into_values().filter(|loaded| !loaded.retired).collect()
+    }
+
+    /// Pairs of variants in effect now that some context would make both
+    /// apply, e.g. one for jurisdiction DE and one for product line custody
+    pub fn variant_conflicts(&self) -> Vec<VariantConflict> {
+        let at = Utc::now();
+        let variants: Vec<(&str, &LoadedRule)> = self
+            .execution_order
+            .iter()
+            .flat_map(|name| self.variants_as_of(name, at).into_iter().map(move |loaded| (name.as_str(), loaded)))
+            .collect();
+        find_variant_conflicts(variants.iter().map(|(name, loaded)| (*name, loaded.rule_id.as_str(), &loaded.applies_to)))
+    }
+
+    /// Evaluates every bundle rule against each context in parallel, as
+    /// `evaluate_all` does for one. Contexts are isolated: a rule failing for
+    /// one only fails that context's entry. Results follow the order of
+    /// `contexts`, each holding the context's facts and the derived attributes.
+    #[tracing::instrument(name = "rules.evaluate_batch", skip_all, fields(contexts = contexts.len()))]
+    pub fn evaluate_batch(&self, contexts: &[HashMap<String, JsonValue>]) -> Vec<Result<HashMap<String, JsonValue>>> {
+        let at = Utc::now();
+        contexts
+            .par_iter()
+            .map(|context| {
+                let facts: Facts = context.iter().map(|(name, value)| (name.clone(), Value::from_json(value))).collect();
+                let facts = self.evaluate_as_of(at, &facts)?;
+                Ok(facts.into_iter().map(|(name, value)| (name, value.to_json())).collect())
+            })
+            .collect()
+    }
+
+    /// Evaluates every rule in effect now over each context, as `evaluate_all`
+    /// does, and reports what the dataset exercised: how often each rule
+    /// fired, which IF and CASE arms were never taken, and which dictionary or
+    /// context attributes no rule reads. A rule failing for a context is
+    /// counted and left out of that context's facts instead of stopping it.
+    /// Each variant of a rule is covered separately, as `attribute (rule_id)`;
+    /// a context that several variants apply to evaluates none of them.
+    #[tracing::instrument(name = "rules.coverage_report", skip_all, fields(contexts = contexts.len()))]
+    pub fn coverage_report(&self, contexts: &[HashMap<String, JsonValue>]) -> CoverageReport {
+        let at = Utc::now();
+        let variants: Vec<(&String, Vec<&LoadedRule>)> =
+            self.execution_order.iter().map(|name| (name, self.variants_as_of(name, at))).collect();
+        let mut coverage: Vec<(&LoadedRule, RuleCoverage)> = variants
+            .iter()
+            .flat_map(|(name, variants)| {
+                variants.iter().map(move |loaded| {
+                    let label = if variants.len() > 1 { format!("{} ({})", name, loaded.rule_id) } else { name.to_string() };
+                    (*loaded, RuleCoverage::new(&label, loaded.version, &loaded.rule.expression))
+                })
+            })
+            .collect();
+
+        for context in contexts {
+            let mut facts: Facts = context.iter().map(|(name, value)| (name.clone(), Value::from_json(value))).collect();
+            for (name, variants) in &variants {
+                if facts.contains_key(*name) {
+                    continue;
+                }
+                let resolved = resolve_variant(
+                    name,
+                    variants.iter().map(|loaded| (loaded.rule_id.as_str(), &loaded.applies_to, *loaded)),
+                    &facts,
+                );
+                let Ok(Some(loaded)) = resolved else { continue };
+                let Some((_, rule_coverage)) = coverage.iter_mut().find(|(covered, _)| std::ptr::eq(*covered, loaded)) else {
+                    continue;
+                };
+                let trace = evaluate_traced(&loaded.rule.expression, &facts, &loaded.functions);
+                rule_coverage.record(&loaded.rule.expression, &trace);
+                if let Some(value) = trace.value {
+                    facts.insert(loaded.rule.name.clone(), value);
+                }
+            }
+        }
+
+        let read: HashSet<&str> = self
+            .rules
+            .values()
+            .flatten()
+            .flat_map(|loaded| loaded.rule.dependencies.iter().map(String::as_str))
+            .collect();
+        let unreferenced = self
+            .dictionary
+            .datasets
+            .iter()
+            .flat_map(|dataset| dataset.attributes.keys())
+            .chain(contexts.iter().flat_map(|context| context.keys()))
+            .filter(|name| !read.contains(name.as_str()) && !self.rules.contains_key(*name))
+            .cloned()
+            .collect();
+
+        CoverageReport::new(contexts.len(), coverage.into_iter().map(|(_, rule)| rule).collect(), unreferenced)
     }
 
     /// Evaluates a chain of dependencies.
+    #[tracing::instrument(name = "rules.evaluate_chain", skip_all, fields(targets = targets.len()))]
     pub fn evaluate_chain(&self, targets: &[String], initial_facts: &Facts) -> Result<Facts> {
+        let at = Utc::now();
         let mut facts = initial_facts.clone();
         for target in targets {
-            self.calculate_attribute_recursive(target, &mut facts)?;
+            self.calculate_attribute_recursive(target, at, &mut facts, None)?;
         }
         Ok(facts)
     }
 
-    fn calculate_attribute_recursive(&self, attr_name: &str, facts: &mut Facts) -> Result<()> {
+    /// Evaluates a chain like `evaluate_chain`, but a failing rule doesn't
+    /// abort the whole row: its attribute is left out of the facts, the rules
+    /// reading it fail quietly in turn, and every other rule is still computed.
+    /// Returns the facts computed and the errors of each rule that hit any.
+    #[tracing::instrument(name = "rules.evaluate_chain_fail_soft", skip_all, fields(targets = targets.len()))]
+    pub fn evaluate_chain_fail_soft(
+        &self,
+        targets: &[String],
+        initial_facts: &Facts,
+    ) -> Result<(Facts, BTreeMap<String, Vec<EvaluationError>>)> {
+        let at = Utc::now();
+        let mut facts = initial_facts.clone();
+        let mut failures = ChainFailures::default();
+        for target in targets {
+            self.calculate_attribute_recursive(target, at, &mut facts, Some(&mut failures))?;
+        }
+        Ok((facts, failures.errors))
+    }
+
+    fn calculate_attribute_recursive(
+        &self,
+        attr_name: &str,
+        at: DateTime<Utc>,
+        facts: &mut Facts,
+        mut failures: Option<&mut ChainFailures>,
+    ) -> Result<()> {
         if facts.contains_key(attr_name) {
             return Ok(()); // Already calculated.
         }
+        if failures.as_ref().is_some_and(|f| f.failed.contains(attr_name)) {
+            return Ok(()); // Already failed.
+        }
+
+        // Rules loaded from a bundle take precedence over dictionary definitions
+        let resolved = match (self.rule_as_of(attr_name, at, facts), failures.as_deref_mut()) {
+            (Err(conflict), Some(failures)) => {
+                let error = EvaluationError { expression: attr_name.to_string(), message: conflict.to_string() };
+                failures.errors.insert(attr_name.to_string(), vec![error]);
+                failures.failed.insert(attr_name.to_string());
+                return Ok(());
+            }
+            (resolved, _) => resolved?,
+        };
+        if let Some(loaded) = resolved {
+            let LoadedRule { rule, functions, .. } = loaded;
+            for dep in &rule.dependencies {
+                if self.is_defined(dep, at) {
+                    self.calculate_attribute_recursive(dep, at, facts, failures.as_deref_mut())?;
+                }
+            }
+
+            if let Some(failures) = failures {
+                let span = tracing::info_span!("rule.evaluate", rule_id = attr_name, error = tracing::field::Empty);
+                let _entered = span.enter();
+                let started = Instant::now();
+                let evaluation = evaluate_fail_soft_with_failed_facts(&rule.expression, facts, functions, &failures.failed);
+                tracing::debug!(
+                    histogram.rule_evaluation_ms = started.elapsed().as_secs_f64() * 1000.0,
+                    monotonic_counter.rule_evaluations = 1u64,
+                    rule_id = attr_name,
+                    ok = evaluation.errors.is_empty(),
+                );
+                if let Some(first) = evaluation.errors.first() {
+                    span.record("error", tracing::field::display(&first.message));
+                    failures.errors.insert(attr_name.to_string(), evaluation.errors);
+                }
+                match evaluation.value {
+                    Some(value) => {
+                        facts.insert(attr_name.to_string(), value);
+                    }
+                    None => {
+                        failures.failed.insert(attr_name.to_string());
+                    }
+                }
+                return Ok(());
+            }
+
+            let value = self.evaluate_loaded_rule(attr_name, loaded, facts)?;
+            facts.insert(attr_name.to_string(), value);
+            return Ok(());
+        }
+        if !self.variants_as_of(attr_name, at).is_empty() {
+            return Ok(()); // No variant applies to this context.
+        }
 
         // Look for derived attribute definition
         let attr_def = self.dictionary.derived_attributes.iter()
@@ -34,7 +437,7 @@ impl RulesEngine {
 
         // Calculate dependencies first
         for dep in &attr_def.dependencies {
-            self.calculate_attribute_recursive(dep, facts)?;
+            self.calculate_attribute_recursive(dep, at, facts, failures.as_deref_mut())?;
         }
 
         // TODO: Implement proper derived attributes support
@@ -43,4 +446,27 @@ impl RulesEngine {
 
         Ok(())
     }
+
+    fn evaluate_loaded_rule(&self, name: &str, loaded: &LoadedRule, facts: &Facts) -> Result<Value> {
+        let span = tracing::info_span!("rule.evaluate", rule_id = name, error = tracing::field::Empty);
+        let _entered = span.enter();
+        let started = Instant::now();
+
+        let result = evaluate_with_functions(&loaded.rule.expression, facts, &loaded.functions);
+        tracing::debug!(
+            histogram.rule_evaluation_ms = started.elapsed().as_secs_f64() * 1000.0,
+            monotonic_counter.rule_evaluations = 1u64,
+            rule_id = name,
+            ok = result.is_ok(),
+        );
+        if let Err(e) = &result {
+            span.record("error", tracing::field::display(e));
+        }
+        result.with_context(|| format!("Failed to evaluate rule '{}'", name))
+    }
+
+    fn is_defined(&self, attr_name: &str, at: DateTime<Utc>) -> bool {
+        !self.variants_as_of(attr_name, at).is_empty()
+            || self.dictionary.derived_attributes.iter().any(|attr| attr.name == attr_name)
+    }
 }
@@ -0,0 +1,182 @@
+//! Catalogue of diagnostic codes
+//!
+//! Every problem the parser, type checker and rule analyses report to an
+//! editor carries a stable code, `DSL0001` onwards, so clients can filter on
+//! it and link to its explanation. Codes are never reused: a retired check
+//! keeps its number. `DSL00xx` are rule DSL codes, `DSL01xx` CBU DSL ones.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ErrorCode {
+    /// A rule that doesn't parse
+    Syntax,
+    /// Text after a complete rule on the same line
+    UnexpectedText,
+    /// An operand or argument of the wrong type
+    TypeMismatch,
+    /// A call to a function nothing defines
+    UnknownFunction,
+    /// A name neither the dictionary nor the document defines
+    UnknownAttribute,
+    /// Two rules assigning the same attribute for the same inputs
+    RuleConflict,
+    /// A rule estimated slower than the batch scoring budget
+    LatencyBudget,
+    /// An issue the AI agent found
+    AiValidation,
+    /// A rule name another open document also assigns
+    DuplicateRule,
+    /// A CBU S-expression that doesn't parse
+    CbuSyntax,
+    /// A CBU command that parsed but was rejected
+    CbuRejected,
+    /// A CBU parenthesis without a partner
+    CbuUnbalanced,
+    /// A CBU that breaks a membership constraint
+    CbuConstraint,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 13] = [
+        ErrorCode::Syntax,
+        ErrorCode::UnexpectedText,
+        ErrorCode::TypeMismatch,
+        ErrorCode::UnknownFunction,
+        ErrorCode::UnknownAttribute,
+        ErrorCode::RuleConflict,
+        ErrorCode::LatencyBudget,
+        ErrorCode::AiValidation,
+        ErrorCode::DuplicateRule,
+        ErrorCode::CbuSyntax,
+        ErrorCode::CbuRejected,
+        ErrorCode::CbuUnbalanced,
+        ErrorCode::CbuConstraint,
+    ];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorCode::Syntax => "DSL0001",
+            ErrorCode::UnexpectedText => "DSL0002",
+            ErrorCode::TypeMismatch => "DSL0003",
+            ErrorCode::UnknownFunction => "DSL0004",
+            ErrorCode::UnknownAttribute => "DSL0005",
+            ErrorCode::RuleConflict => "DSL0006",
+            ErrorCode::LatencyBudget => "DSL0007",
+            ErrorCode::AiValidation => "DSL0008",
+            ErrorCode::DuplicateRule => "DSL0009",
+            ErrorCode::CbuSyntax => "DSL0101",
+            ErrorCode::CbuRejected => "DSL0102",
+            ErrorCode::CbuUnbalanced => "DSL0103",
+            ErrorCode::CbuConstraint => "DSL0104",
+        }
+    }
+
+    pub fn parse(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|error| error.code().eq_ignore_ascii_case(code))
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ErrorCode::Syntax => "Syntax error",
+            ErrorCode::UnexpectedText => "Unexpected text after a rule",
+            ErrorCode::TypeMismatch => "Type mismatch",
+            ErrorCode::UnknownFunction => "Unknown function",
+            ErrorCode::UnknownAttribute => "Unknown attribute",
+            ErrorCode::RuleConflict => "Conflicting rules",
+            ErrorCode::LatencyBudget => "Over the latency budget",
+            ErrorCode::AiValidation => "AI review finding",
+            ErrorCode::DuplicateRule => "Rule defined in another file",
+            ErrorCode::CbuSyntax => "CBU syntax error",
+            ErrorCode::CbuRejected => "CBU command rejected",
+            ErrorCode::CbuUnbalanced => "Unbalanced parenthesis",
+            ErrorCode::CbuConstraint => "CBU membership constraint",
+        }
+    }
+
+    /// What the problem is and how it is usually fixed, for the code's documentation page
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            ErrorCode::Syntax => {
+                "The rule can't be parsed from where the error points. Parsing resumes at the next \
+                 statement, so the rules after it are still checked. A missing `)` or `END` is the \
+                 usual cause; the quick fix inserts a missing `)`."
+            }
+            ErrorCode::UnexpectedText => {
+                "A complete rule was parsed but more text follows it on the same line, such as an \
+                 operator with no right-hand side or an unclosed `(`. Finish the expression or move \
+                 the text to a rule of its own."
+            }
+            ErrorCode::TypeMismatch => {
+                "An operator or function is applied to a value of the wrong type, judged by the \
+                 data dictionary's types and what the document's rules assign. Convert the value \
+                 or compare it with one of the same type."
+            }
+            ErrorCode::UnknownFunction => {
+                "The function is neither built in nor registered by the host application. The \
+                 quick fix changes it to the closest known function."
+            }
+            ErrorCode::UnknownAttribute => {
+                "The name isn't in the data dictionary and no rule in the open documents assigns \
+                 it. Quote it if it was meant as a string, assign it in an earlier rule, or add it \
+                 to the dictionary with the quick fix."
+            }
+            ErrorCode::RuleConflict => {
+                "Two rules assign the same attribute and can both apply to the same inputs, so \
+                 which value wins depends on rule order. The related information points at the \
+                 other rule; make their conditions exclusive or merge them."
+            }
+            ErrorCode::LatencyBudget => {
+                "The rule's estimated latency, from its LOOKUPs, regex matches and host function \
+                 calls, exceeds the batch scoring budget in `[latency_budget]`. Move expensive \
+                 calls behind cheaper conditions or precompute them."
+            }
+            ErrorCode::AiValidation => {
+                "The active AI agent flagged the rule. These findings are advisory and are only \
+                 reported when the deployment has AI enabled."
+            }
+            ErrorCode::DuplicateRule => {
+                "Another open rule file assigns the same name, so which file's rule is used depends \
+                 on load order. The related information points at the other definitions; rename one \
+                 of them or keep the rule in a single file."
+            }
+            ErrorCode::CbuSyntax => {
+                "The CBU document isn't a well-formed S-expression, or uses a form the CBU DSL \
+                 doesn't know. Check that parentheses balance and strings are closed."
+            }
+            ErrorCode::CbuRejected => {
+                "The CBU command parsed but couldn't be carried out, for example because an \
+                 entity has an unknown role or the CBU it names doesn't exist."
+            }
+            ErrorCode::CbuUnbalanced => {
+                "A `(` is never closed or a `)` closes nothing; parentheses inside strings and \
+                 comments don't count. An unclosed `(` is reported where it opens, usually the \
+                 form whose closing `)` was forgotten."
+            }
+            ErrorCode::CbuConstraint => {
+                "The CBU is well formed but breaks a business rule about its members: it needs \
+                 exactly one asset-owner, a custodian needs an LEI, an entity holds each role at \
+                 most once, and every role must be an active entry in `cbu_roles`. The checks \
+                 that need stored entities or roles are skipped while the database is unavailable."
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_codes_are_unique_and_round_trip() {
+        let codes: HashSet<&str> = ErrorCode::ALL.iter().map(|error| error.code()).collect();
+        assert_eq!(codes.len(), ErrorCode::ALL.len());
+        for error in ErrorCode::ALL {
+            assert_eq!(ErrorCode::parse(error.code()), Some(error));
+            assert!(error.code().len() == 7 && error.code().starts_with("DSL"));
+        }
+        assert_eq!(ErrorCode::parse("dsl0006"), Some(ErrorCode::RuleConflict));
+        assert_eq!(ErrorCode::parse("DSL9999"), None);
+    }
+}
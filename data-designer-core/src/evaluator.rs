@@ -1,13 +1,43 @@
+use crate::formatter::format_expression;
+use crate::function_registry::FunctionRegistry;
+use crate::locale;
+use crate::regex_cache;
 use crate::models::{Expression, Value, BinaryOperator, UnaryOperator};
 use anyhow::{Result, bail};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
 use regex::Regex;
 
 pub type Facts = HashMap<String, Value>;
 
+/// Loads a lookup table by name, e.g. from the database; None if there is no such table
+pub type LookupResolver = Box<dyn Fn(&str) -> Option<HashMap<String, String>> + Send + Sync>;
+
+/// Functions the evaluator implements itself, higher-order ones included;
+/// any other name is looked up in the host's `FunctionRegistry`
+pub const BUILTIN_FUNCTIONS: &[&str] = &[
+    "CONCAT", "SUBSTRING", "UPPER", "LOWER", "LENGTH", "TRIM", "LOOKUP", "ABS", "ROUND", "FLOOR", "CEIL",
+    "MIN", "MAX", "SUM", "AVG", "COUNT", "HAS", "IS_NULL", "IS_EMPTY", "TO_STRING", "TO_NUMBER", "TO_BOOLEAN",
+    "TO_PCT", "TO_BASIS_POINTS", "FORMAT_NUMBER", "FORMAT_DATE", "FIRST", "LAST", "GET", "EXTRACT",
+    "EXTRACT_ALL", "MATCH_COUNT", "MAP", "FILTER", "ANY", "ALL",
+];
+
 /// Comprehensive function library for DSL evaluation
 pub struct FunctionLibrary {
     pub lookup_tables: HashMap<String, HashMap<String, String>>,
+    /// Resolves tables missing from `lookup_tables` the first time a LOOKUP
+    /// actually runs against them, so rules that never reach a LOOKUP never load its table
+    lookup_resolver: Option<LookupResolver>,
+    /// What the resolver returned for each table it was asked for, unknown tables included
+    resolved_lookups: Mutex<HashMap<String, Option<HashMap<String, String>>>>,
+    /// Host functions, called when no built-in matches
+    registry: FunctionRegistry,
+    /// Patterns the regex functions have used, kept for as long as the library lives
+    /// so a rule skips even the shared `regex_cache` lock after its first evaluation
+    regex_cache: Mutex<HashMap<String, Arc<Regex>>>,
 }
 
 impl Default for FunctionLibrary {
@@ -20,13 +50,33 @@ impl FunctionLibrary {
     pub fn new() -> Self {
         Self {
             lookup_tables: HashMap::new(),
+            lookup_resolver: None,
+            resolved_lookups: Mutex::new(HashMap::new()),
+            registry: FunctionRegistry::new(),
+            regex_cache: Mutex::new(HashMap::new()),
         }
     }
 
+    /// A library that can also call the host functions in `registry`
+    pub fn with_registry(registry: FunctionRegistry) -> Self {
+        Self { registry, ..Self::new() }
+    }
+
+    pub fn registry(&self) -> &FunctionRegistry {
+        &self.registry
+    }
+
     pub fn add_lookup_table(&mut self, name: String, table: HashMap<String, String>) {
         self.lookup_tables.insert(name, table);
     }
 
+    /// Loads lookup tables on demand instead of up front; each table is
+    /// resolved at most once per library
+    pub fn set_lookup_resolver(&mut self, resolver: impl Fn(&str) -> Option<HashMap<String, String>> + Send + Sync + 'static) {
+        self.lookup_resolver = Some(Box::new(resolver));
+        self.resolved_lookups.lock().unwrap().clear();
+    }
+
     pub fn call_function(&self, name: &str, args: &[Value]) -> Result<Value> {
         match name.to_uppercase().as_str() {
             "CONCAT" => self.concat(args),
@@ -51,10 +101,20 @@ impl FunctionLibrary {
             "TO_STRING" => self.to_string(args),
             "TO_NUMBER" => self.to_number(args),
             "TO_BOOLEAN" => self.to_boolean(args),
+            "TO_PCT" => self.to_pct(args),
+            "TO_BASIS_POINTS" => self.to_basis_points(args),
+            "FORMAT_NUMBER" => self.format_number(args),
+            "FORMAT_DATE" => self.format_date(args),
             "FIRST" => self.first(args),
             "LAST" => self.last(args),
             "GET" => self.get(args),
-            _ => bail!("Unknown function '{}'", name),
+            "EXTRACT" => self.extract(args),
+            "EXTRACT_ALL" => self.extract_all(args),
+            "MATCH_COUNT" => self.match_count(args),
+            _ => match self.registry.call(name, args) {
+                Some(result) => result,
+                None => bail!("Unknown function '{}'", name),
+            },
         }
     }
 
@@ -133,16 +193,19 @@ impl FunctionLibrary {
 
         let key = value_to_string(&args[0]);
         let table_name = value_to_string(&args[1]);
+        let found = |table: &HashMap<String, String>| table.get(&key).map_or(Value::Null, |value| Value::String(value.clone()));
 
         if let Some(table) = self.lookup_tables.get(&table_name) {
-            if let Some(value) = table.get(&key) {
-                Ok(Value::String(value.clone()))
-            } else {
-                Ok(Value::Null)
+            return Ok(found(table));
+        }
+        if let Some(resolver) = &self.lookup_resolver {
+            let mut resolved = self.resolved_lookups.lock().unwrap();
+            let table = resolved.entry(table_name.clone()).or_insert_with(|| resolver(&table_name));
+            if let Some(table) = table {
+                return Ok(found(table));
             }
-        } else {
-            bail!("Lookup table '{}' not found", table_name);
         }
+        bail!("Lookup table '{}' not found", table_name);
     }
 
     // Math functions
@@ -316,12 +379,22 @@ impl FunctionLibrary {
         Ok(Value::String(value_to_string(&args[0])))
     }
 
+    /// TO_NUMBER(text) or TO_NUMBER(text, "de-DE") for locale-formatted input
     fn to_number(&self, args: &[Value]) -> Result<Value> {
-        if args.len() != 1 {
-            bail!("TO_NUMBER requires exactly 1 argument");
+        if args.is_empty() || args.len() > 2 {
+            bail!("TO_NUMBER requires 1 or 2 arguments");
         }
-        match &args[0] {
-            Value::String(s) => {
+        match (&args[0], args.get(1)) {
+            (Value::String(s), Some(locale_tag)) => {
+                let locale = locale::find_locale(&value_to_string(locale_tag))?;
+                let number = locale::parse_number(s, locale)?;
+                if s.contains(locale.decimal_separator) {
+                    Ok(Value::Float(number))
+                } else {
+                    Ok(Value::Integer(number as i64))
+                }
+            }
+            (Value::String(s), None) => {
                 if let Ok(i) = s.parse::<i64>() {
                     Ok(Value::Integer(i))
                 } else if let Ok(f) = s.parse::<f64>() {
@@ -330,8 +403,8 @@ impl FunctionLibrary {
                     bail!("Cannot convert '{}' to number", s);
                 }
             }
-            Value::Boolean(b) => Ok(Value::Integer(if *b { 1 } else { 0 })),
-            v => Ok(v.clone()),
+            (Value::Boolean(b), _) => Ok(Value::Integer(if *b { 1 } else { 0 })),
+            (v, _) => Ok(v.clone()),
         }
     }
 
@@ -348,11 +421,84 @@ impl FunctionLibrary {
             Value::Null => false,
             Value::List(l) => !l.is_empty(),
             Value::Regex(_) => true,
+            Value::Percent(p) => *p != 0.0,
+            Value::Money { amount, .. } => *amount != 0.0,
+            Value::Object(fields) => !fields.is_empty(),
         };
         Ok(Value::Boolean(bool_val))
     }
 
+    // Unit conversion functions
+    /// TO_PCT(0.05) is 5%; percentages pass through unchanged
+    fn to_pct(&self, args: &[Value]) -> Result<Value> {
+        if args.len() != 1 {
+            bail!("TO_PCT requires exactly 1 argument");
+        }
+        match &args[0] {
+            Value::Percent(p) => Ok(Value::Percent(*p)),
+            Value::Integer(i) => Ok(Value::Percent(*i as f64 * 100.0)),
+            Value::Float(f) | Value::Number(f) => Ok(Value::Percent(f * 100.0)),
+            Value::Null => Ok(Value::Null),
+            other => bail!("TO_PCT expects a fraction or a percentage but got {:?}", other),
+        }
+    }
+
+    /// TO_BASIS_POINTS(5%) and TO_BASIS_POINTS(0.05) are both 500
+    fn to_basis_points(&self, args: &[Value]) -> Result<Value> {
+        if args.len() != 1 {
+            bail!("TO_BASIS_POINTS requires exactly 1 argument");
+        }
+        match &args[0] {
+            Value::Percent(p) => Ok(Value::Float(p * 100.0)),
+            Value::Integer(i) => Ok(Value::Float(*i as f64 * 10_000.0)),
+            Value::Float(f) | Value::Number(f) => Ok(Value::Float(f * 10_000.0)),
+            Value::Null => Ok(Value::Null),
+            other => bail!("TO_BASIS_POINTS expects a fraction or a percentage but got {:?}", other),
+        }
+    }
+
     // List access functions
+    // Locale formatting functions
+    /// FORMAT_NUMBER(value, "de-DE") or FORMAT_NUMBER(value, "de-DE", decimals);
+    /// integers default to 0 decimals, money to its currency's minor units,
+    /// everything else to 2. Money keeps its currency code, percentages their `%`.
+    fn format_number(&self, args: &[Value]) -> Result<Value> {
+        if args.len() < 2 || args.len() > 3 {
+            bail!("FORMAT_NUMBER requires 2 or 3 arguments (value, locale, decimals)");
+        }
+        let locale = locale::find_locale(&value_to_string(&args[1]))?;
+        let decimals = match args.get(2) {
+            Some(Value::Integer(d)) if *d >= 0 => Some(*d as usize),
+            Some(other) => bail!("FORMAT_NUMBER decimals must be a non-negative integer, got {:?}", other),
+            None => None,
+        };
+        let formatted = match &args[0] {
+            Value::Integer(i) => locale::format_number(*i as f64, decimals.unwrap_or(0), locale),
+            Value::Float(f) | Value::Number(f) => locale::format_number(*f, decimals.unwrap_or(2), locale),
+            Value::Percent(p) => format!("{}%", locale::format_number(*p, decimals.unwrap_or(2), locale)),
+            Value::Money { amount, currency } => locale::format_money(*amount, currency, decimals, locale),
+            Value::Null => return Ok(Value::Null),
+            other => bail!("FORMAT_NUMBER expects a number but got {:?}", other),
+        };
+        Ok(Value::String(formatted))
+    }
+
+    /// FORMAT_DATE(value, pattern) or FORMAT_DATE(value, pattern, "fr-FR");
+    /// strftime patterns, an empty pattern uses the locale's short date
+    fn format_date(&self, args: &[Value]) -> Result<Value> {
+        if args.len() < 2 || args.len() > 3 {
+            bail!("FORMAT_DATE requires 2 or 3 arguments (value, pattern, locale)");
+        }
+        let date = match &args[0] {
+            Value::String(s) => locale::parse_date(s)?,
+            Value::Null => return Ok(Value::Null),
+            other => bail!("FORMAT_DATE expects a date string but got {:?}", other),
+        };
+        let locale_tag = args.get(2).map(value_to_string);
+        let locale = locale::find_locale(locale_tag.as_deref().unwrap_or(locale::DEFAULT_LOCALE))?;
+        Ok(Value::String(locale::format_date(&date, &value_to_string(&args[1]), locale)))
+    }
+
     fn first(&self, args: &[Value]) -> Result<Value> {
         if args.len() != 1 {
             bail!("FIRST requires exactly 1 argument");
@@ -401,6 +547,63 @@ impl FunctionLibrary {
             _ => bail!("GET requires a list and an integer index"),
         }
     }
+
+    // Regex functions
+    fn regex(&self, pattern: &Value) -> Result<Arc<Regex>> {
+        let pattern = match pattern {
+            Value::Regex(pattern) => pattern.clone(),
+            other => value_to_string(other),
+        };
+        let mut cache = self.regex_cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(regex) = cache.get(&pattern) {
+            return Ok(regex.clone());
+        }
+        let regex = regex_cache::compiled(&pattern)?;
+        cache.insert(pattern, regex.clone());
+        Ok(regex)
+    }
+
+    fn extract(&self, args: &[Value]) -> Result<Value> {
+        if args.len() < 2 || args.len() > 3 {
+            bail!("EXTRACT requires 2 or 3 arguments (value, pattern, group)");
+        }
+        if args[0] == Value::Null {
+            return Ok(Value::Null);
+        }
+        let regex = self.regex(&args[1])?;
+        let text = value_to_string(&args[0]);
+        match regex.captures(&text) {
+            Some(captures) => capture_group(&regex, &captures, args.get(2)),
+            None => Ok(Value::Null),
+        }
+    }
+
+    fn extract_all(&self, args: &[Value]) -> Result<Value> {
+        if args.len() < 2 || args.len() > 3 {
+            bail!("EXTRACT_ALL requires 2 or 3 arguments (value, pattern, group)");
+        }
+        if args[0] == Value::Null {
+            return Ok(Value::List(Vec::new()));
+        }
+        let regex = self.regex(&args[1])?;
+        let text = value_to_string(&args[0]);
+        let matches = regex
+            .captures_iter(&text)
+            .map(|captures| capture_group(&regex, &captures, args.get(2)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Value::List(matches.into_iter().filter(|m| *m != Value::Null).collect()))
+    }
+
+    fn match_count(&self, args: &[Value]) -> Result<Value> {
+        if args.len() != 2 {
+            bail!("MATCH_COUNT requires exactly 2 arguments (value, pattern)");
+        }
+        if args[0] == Value::Null {
+            return Ok(Value::Integer(0));
+        }
+        let regex = self.regex(&args[1])?;
+        Ok(Value::Integer(regex.find_iter(&value_to_string(&args[0])).count() as i64))
+    }
 }
 
 /// Evaluates a parsed AST `Expression` against a set of facts.
@@ -413,16 +616,8 @@ pub fn evaluate_with_functions(expr: &Expression, facts: &Facts, functions: &Fun
     match expr {
         Expression::Literal(val) => Ok(val.clone()),
 
-        Expression::Identifier(name) => {
-            Ok(facts.get(name)
-                 .cloned()
-                 .unwrap_or(Value::Null))  // Return null instead of error for missing facts
-        }
-
-        Expression::Variable(name) => {
-            Ok(facts.get(name)
-                 .cloned()
-                 .unwrap_or(Value::Null))  // Return null instead of error for missing facts
+        Expression::Identifier(name) | Expression::Variable(name) => {
+            Ok(lookup_fact(facts, name).unwrap_or(Value::Null))  // Return null instead of error for missing facts
         }
 
         Expression::Assignment { target: _, value } => {
@@ -432,8 +627,22 @@ pub fn evaluate_with_functions(expr: &Expression, facts: &Facts, functions: &Fun
             Ok(result)
         }
 
+        Expression::BinaryOp { op: op @ (BinaryOperator::And | BinaryOperator::Or), left, right } => {
+            // Short-circuit: the right side, and any LOOKUP in it, only runs when it can change the answer
+            let decisive = *op == BinaryOperator::Or;
+            if to_bool(&evaluate_with_functions(left, facts, functions)?) == decisive {
+                return Ok(Value::Boolean(decisive));
+            }
+            Ok(Value::Boolean(to_bool(&evaluate_with_functions(right, facts, functions)?)))
+        }
+
         Expression::BinaryOp { op, left, right } => {
             let left_val = evaluate_with_functions(left, facts, functions)?;
+            if let Expression::Range { start, end, inclusive } = right.as_ref() {
+                let start_val = evaluate_with_functions(start, facts, functions)?;
+                let end_val = evaluate_with_functions(end, facts, functions)?;
+                return evaluate_range_op(*op, &left_val, &start_val, &end_val, *inclusive);
+            }
             let right_val = evaluate_with_functions(right, facts, functions)?;
             evaluate_binary_op(*op, &left_val, &right_val)
         }
@@ -444,6 +653,10 @@ pub fn evaluate_with_functions(expr: &Expression, facts: &Facts, functions: &Fun
         }
 
         Expression::FunctionCall { name, args } => {
+            if args.iter().any(|arg| matches!(arg, Expression::Lambda { .. })) {
+                return evaluate_higher_order(name, args, facts, functions);
+            }
+
             let mut arg_values = Vec::new();
             for arg_expr in args {
                 arg_values.push(evaluate_with_functions(arg_expr, facts, functions)?);
@@ -464,6 +677,36 @@ pub fn evaluate_with_functions(expr: &Expression, facts: &Facts, functions: &Fun
             Ok(Value::List(values))
         }
 
+        Expression::Comprehension { element, var, source, condition } => {
+            let items = match evaluate_with_functions(source, facts, functions)? {
+                Value::List(items) => items,
+                Value::Null => Vec::new(),
+                other => bail!("FOR ... IN expects a list but got {:?}", other),
+            };
+
+            // The loop variable shadows any fact of the same name
+            let mut scope = facts.clone();
+            let mut values = Vec::new();
+            for item in items {
+                scope.insert(var.clone(), item);
+                if let Some(condition) = condition {
+                    if !to_bool(&evaluate_with_functions(condition, &scope, functions)?) {
+                        continue;
+                    }
+                }
+                values.push(evaluate_with_functions(element, &scope, functions)?);
+            }
+            Ok(Value::List(values))
+        }
+
+        Expression::Lambda { .. } => {
+            bail!("Lambda expressions can only be used as arguments to MAP, FILTER, SUM, ANY or ALL")
+        }
+
+        Expression::Range { .. } => {
+            bail!("Ranges can only be used with BETWEEN, IN or NOT IN")
+        }
+
         Expression::Conditional { condition, then_expr, else_expr } => {
             let condition_val = evaluate_with_functions(condition, facts, functions)?;
             let condition_bool = match condition_val {
@@ -475,6 +718,9 @@ pub fn evaluate_with_functions(expr: &Expression, facts: &Facts, functions: &Fun
                 Value::String(s) => !s.is_empty(),
                 Value::List(l) => !l.is_empty(),
                 Value::Regex(_) => true,
+                Value::Percent(p) => p != 0.0,
+                Value::Money { amount, .. } => amount != 0.0,
+                Value::Object(fields) => !fields.is_empty(),
             };
 
             if condition_bool {
@@ -486,6 +732,19 @@ pub fn evaluate_with_functions(expr: &Expression, facts: &Facts, functions: &Fun
             }
         }
 
+        Expression::Case { branches, else_expr } => {
+            // First matching WHEN wins; no match and no ELSE yields null
+            for (condition, result) in branches {
+                if to_bool(&evaluate_with_functions(condition, facts, functions)?) {
+                    return evaluate_with_functions(result, facts, functions);
+                }
+            }
+            match else_expr {
+                Some(else_expr) => evaluate_with_functions(else_expr, facts, functions),
+                None => Ok(Value::Null),
+            }
+        }
+
         // Fund Accounting DSL Expressions
         Expression::ConfigureSystem { capability_name, arguments } => {
             // Evaluate configuration system call
@@ -539,9 +798,445 @@ pub fn evaluate_with_functions(expr: &Expression, facts: &Facts, functions: &Fun
     }
 }
 
+/// A sub-expression that failed during fail-soft evaluation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvaluationError {
+    /// The failing sub-expression, as the formatter prints it
+    pub expression: String,
+    pub message: String,
+}
+
+/// What fail-soft evaluation could compute, and every failure it met
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailSoftEvaluation {
+    /// None when a failure reached the top of the rule
+    pub value: Option<Value>,
+    pub errors: Vec<EvaluationError>,
+}
+
+/// Evaluates like `evaluate_with_functions`, but a failing sub-expression is
+/// recorded and turns into an error value instead of aborting the rule.
+/// Error values propagate like SQL nulls: anything computed from one is an
+/// error value too, without a second error, unless the answer doesn't depend
+/// on it (`false AND x`, `true OR x`, a CASE branch that isn't selected).
+pub fn evaluate_fail_soft(expr: &Expression, facts: &Facts, functions: &FunctionLibrary) -> FailSoftEvaluation {
+    evaluate_fail_soft_with_failed_facts(expr, facts, functions, &HashSet::new())
+}
+
+/// `evaluate_fail_soft` where the facts named in `failed_facts` are error
+/// values, e.g. attributes whose own rules failed earlier in the chain
+pub fn evaluate_fail_soft_with_failed_facts(
+    expr: &Expression,
+    facts: &Facts,
+    functions: &FunctionLibrary,
+    failed_facts: &HashSet<String>,
+) -> FailSoftEvaluation {
+    let mut evaluation = FailSoft { facts, functions, failed_facts, errors: Vec::new() };
+    let value = evaluation.eval(expr).ok();
+    FailSoftEvaluation { value, errors: evaluation.errors }
+}
+
+/// An error value: the failure behind it has already been recorded
+struct Failed;
+
+struct FailSoft<'a> {
+    facts: &'a Facts,
+    functions: &'a FunctionLibrary,
+    failed_facts: &'a HashSet<String>,
+    errors: Vec<EvaluationError>,
+}
+
+impl FailSoft<'_> {
+    fn check(&mut self, expr: &Expression, result: Result<Value>) -> std::result::Result<Value, Failed> {
+        result.map_err(|e| {
+            self.errors.push(EvaluationError {
+                expression: format_expression(expr),
+                message: e.to_string(),
+            });
+            Failed
+        })
+    }
+
+    /// Every sub-expression is evaluated, so all of their failures are recorded
+    fn eval_all(&mut self, exprs: &[Expression]) -> std::result::Result<Vec<Value>, Failed> {
+        let values: Vec<_> = exprs.iter().map(|expr| self.eval(expr)).collect();
+        values.into_iter().collect()
+    }
+
+    fn eval(&mut self, expr: &Expression) -> std::result::Result<Value, Failed> {
+        match expr {
+            Expression::Identifier(name) | Expression::Variable(name) if self.failed_facts.contains(name) => Err(Failed),
+
+            Expression::Assignment { value, .. } => self.eval(value),
+
+            Expression::BinaryOp { op: op @ (BinaryOperator::And | BinaryOperator::Or), left, right } => {
+                // Short-circuits like the strict evaluator; a failed left side
+                // still lets a decisive right side answer
+                let decisive = *op == BinaryOperator::Or;
+                match self.eval(left) {
+                    Ok(known) if to_bool(&known) == decisive => Ok(Value::Boolean(decisive)),
+                    Ok(_) => Ok(Value::Boolean(to_bool(&self.eval(right)?))),
+                    Err(Failed) => match self.eval(right) {
+                        Ok(known) if to_bool(&known) == decisive => Ok(Value::Boolean(decisive)),
+                        _ => Err(Failed),
+                    },
+                }
+            }
+
+            Expression::BinaryOp { op, left, right } => {
+                let left_val = self.eval(left);
+                if let Expression::Range { start, end, inclusive } = right.as_ref() {
+                    let (start_val, end_val) = (self.eval(start), self.eval(end));
+                    let (left_val, start_val, end_val) = (left_val?, start_val?, end_val?);
+                    return self.check(expr, evaluate_range_op(*op, &left_val, &start_val, &end_val, *inclusive));
+                }
+                let right_val = self.eval(right);
+                let (left_val, right_val) = (left_val?, right_val?);
+                self.check(expr, evaluate_binary_op(*op, &left_val, &right_val))
+            }
+
+            Expression::UnaryOp { op, operand } => {
+                let operand_val = self.eval(operand)?;
+                self.check(expr, evaluate_unary_op(*op, &operand_val))
+            }
+
+            Expression::FunctionCall { name, args } if !args.iter().any(|arg| matches!(arg, Expression::Lambda { .. })) => {
+                let arg_values = self.eval_all(args)?;
+                let result = self.functions.call_function(name, &arg_values);
+                self.check(expr, result)
+            }
+
+            Expression::Cast { expr: inner, data_type } => {
+                let value = self.eval(inner)?;
+                self.check(expr, cast_value(value, data_type))
+            }
+
+            Expression::List(exprs) => Ok(Value::List(self.eval_all(exprs)?)),
+
+            Expression::Conditional { condition, then_expr, else_expr } => {
+                if to_bool(&self.eval(condition)?) {
+                    self.eval(then_expr)
+                } else if let Some(else_expr) = else_expr {
+                    self.eval(else_expr)
+                } else {
+                    Ok(Value::Null)
+                }
+            }
+
+            Expression::Case { branches, else_expr } => {
+                // A failed condition leaves the branch unknown, so the CASE fails with it
+                for (condition, result) in branches {
+                    if to_bool(&self.eval(condition)?) {
+                        return self.eval(result);
+                    }
+                }
+                match else_expr {
+                    Some(else_expr) => self.eval(else_expr),
+                    None => Ok(Value::Null),
+                }
+            }
+
+            // Leaves, lambdas over lists and workflow verbs fail as a whole
+            _ => {
+                let result = evaluate_with_functions(expr, self.facts, self.functions);
+                self.check(expr, result)
+            }
+        }
+    }
+}
+
+/// One node of an explain tree: a sub-expression, the inputs it was computed
+/// from and what it produced
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceNode {
+    /// The sub-expression as the formatter prints it
+    pub expression: String,
+    /// None when the sub-expression failed
+    pub value: Option<Value>,
+    pub error: Option<String>,
+    /// Time spent on this node, its inputs included; always 0 on wasm32
+    pub duration_us: u64,
+    /// Sub-expressions in evaluation order; branches not taken are absent
+    pub inputs: Vec<TraceNode>,
+}
+
+impl TraceNode {
+    pub fn result(&self) -> Result<Value> {
+        match (&self.value, &self.error) {
+            (Some(value), _) => Ok(value.clone()),
+            (None, error) => bail!("{}", error.as_deref().unwrap_or("evaluation failed")),
+        }
+    }
+}
+
+/// Evaluates like `evaluate_with_functions` and records every sub-expression
+/// on the way, so a rule tester can show why a rule produced its value. The
+/// root node holds the rule's own value or error.
+pub fn evaluate_traced(expr: &Expression, facts: &Facts, functions: &FunctionLibrary) -> TraceNode {
+    Tracer { facts, functions }.trace(expr)
+}
+
+// Instant::now panics on wasm32-unknown-unknown, so traces there carry no timings
+#[cfg(not(target_arch = "wasm32"))]
+fn stopwatch() -> impl Fn() -> u64 {
+    let started = Instant::now();
+    move || started.elapsed().as_micros() as u64
+}
+
+#[cfg(target_arch = "wasm32")]
+fn stopwatch() -> impl Fn() -> u64 {
+    || 0
+}
+
+struct Tracer<'a> {
+    facts: &'a Facts,
+    functions: &'a FunctionLibrary,
+}
+
+impl Tracer<'_> {
+    fn trace(&self, expr: &Expression) -> TraceNode {
+        let elapsed_us = stopwatch();
+        let mut inputs = Vec::new();
+        let result = self.eval(expr, &mut inputs);
+        TraceNode {
+            expression: format_expression(expr),
+            value: result.as_ref().ok().cloned(),
+            error: result.err().map(|e| e.to_string()),
+            duration_us: elapsed_us(),
+            inputs,
+        }
+    }
+
+    /// Traces `expr` as an input of the node being evaluated
+    fn input(&self, expr: &Expression, inputs: &mut Vec<TraceNode>) -> Result<Value> {
+        let node = self.trace(expr);
+        let result = node.result();
+        inputs.push(node);
+        result
+    }
+
+    fn input_all(&self, exprs: &[Expression], inputs: &mut Vec<TraceNode>) -> Result<Vec<Value>> {
+        exprs.iter().map(|expr| self.input(expr, inputs)).collect()
+    }
+
+    fn eval(&self, expr: &Expression, inputs: &mut Vec<TraceNode>) -> Result<Value> {
+        match expr {
+            Expression::Assignment { value, .. } => self.input(value, inputs),
+
+            Expression::BinaryOp { op: op @ (BinaryOperator::And | BinaryOperator::Or), left, right } => {
+                let decisive = *op == BinaryOperator::Or;
+                if to_bool(&self.input(left, inputs)?) == decisive {
+                    return Ok(Value::Boolean(decisive));
+                }
+                Ok(Value::Boolean(to_bool(&self.input(right, inputs)?)))
+            }
+
+            Expression::BinaryOp { op, left, right } => {
+                let left_val = self.input(left, inputs)?;
+                if let Expression::Range { start, end, inclusive } = right.as_ref() {
+                    let start_val = self.input(start, inputs)?;
+                    let end_val = self.input(end, inputs)?;
+                    return evaluate_range_op(*op, &left_val, &start_val, &end_val, *inclusive);
+                }
+                let right_val = self.input(right, inputs)?;
+                evaluate_binary_op(*op, &left_val, &right_val)
+            }
+
+            Expression::UnaryOp { op, operand } => {
+                let operand_val = self.input(operand, inputs)?;
+                evaluate_unary_op(*op, &operand_val)
+            }
+
+            Expression::FunctionCall { name, args } if !args.iter().any(|arg| matches!(arg, Expression::Lambda { .. })) => {
+                let arg_values = self.input_all(args, inputs)?;
+                self.functions.call_function(name, &arg_values)
+            }
+
+            Expression::Cast { expr: inner, data_type } => {
+                let value = self.input(inner, inputs)?;
+                cast_value(value, data_type)
+            }
+
+            Expression::List(exprs) => Ok(Value::List(self.input_all(exprs, inputs)?)),
+
+            Expression::Conditional { condition, then_expr, else_expr } => {
+                if to_bool(&self.input(condition, inputs)?) {
+                    self.input(then_expr, inputs)
+                } else if let Some(else_expr) = else_expr {
+                    self.input(else_expr, inputs)
+                } else {
+                    Ok(Value::Null)
+                }
+            }
+
+            Expression::Case { branches, else_expr } => {
+                for (condition, result) in branches {
+                    if to_bool(&self.input(condition, inputs)?) {
+                        return self.input(result, inputs);
+                    }
+                }
+                match else_expr {
+                    Some(else_expr) => self.input(else_expr, inputs),
+                    None => Ok(Value::Null),
+                }
+            }
+
+            // Leaves, lambdas over lists and workflow verbs are traced as a whole
+            _ => evaluate_with_functions(expr, self.facts, self.functions),
+        }
+    }
+}
+
+/// What partial evaluation could tell about an expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum PartialValue {
+    /// Every fact it reads is known, or its value doesn't depend on the others
+    Known(Value),
+    /// It reads facts that aren't known. `residual` is what is left once the
+    /// known facts are substituted and the parts built only from them folded.
+    Residual { missing: Vec<String>, residual: Expression },
+    Failed(String),
+}
+
+/// Evaluates `expr` with whatever facts are known. Unlike
+/// `evaluate_with_functions`, a fact missing from `facts` is not read as
+/// null, so part of a rule (a lambda body, an operand naming an attribute no
+/// test case sets) gives a residual expression rather than a wrong value.
+pub fn evaluate_partial(expr: &Expression, facts: &Facts, functions: &FunctionLibrary) -> PartialValue {
+    let missing: Vec<String> = crate::rule_graph::extract_dependencies_from_ast(expr)
+        .into_iter()
+        .filter(|name| lookup_fact(facts, name).is_none())
+        .collect();
+    if missing.is_empty() {
+        return match evaluate_with_functions(expr, facts, functions) {
+            Ok(value) => PartialValue::Known(value),
+            Err(e) => PartialValue::Failed(e.to_string()),
+        };
+    }
+    match substitute_facts(expr, facts, &[]).optimize() {
+        Expression::Literal(value) => PartialValue::Known(value),
+        residual => PartialValue::Residual {
+            missing: crate::rule_graph::extract_dependencies_from_ast(&residual),
+            residual,
+        },
+    }
+}
+
+/// `expr` with every known fact it reads replaced by its value; names in
+/// `bound` belong to an enclosing lambda or comprehension and are kept
+fn substitute_facts(expr: &Expression, facts: &Facts, bound: &[&str]) -> Expression {
+    let sub = |e: &Expression| Box::new(substitute_facts(e, facts, bound));
+    let all = |items: &[Expression]| items.iter().map(|item| substitute_facts(item, facts, bound)).collect();
+    match expr {
+        Expression::Variable(name) | Expression::Identifier(name) => {
+            let root = name.split('.').next().unwrap_or(name);
+            match lookup_fact(facts, name) {
+                Some(value) if !bound.contains(&root) => Expression::Literal(value),
+                _ => expr.clone(),
+            }
+        }
+        Expression::BinaryOp { left, op, right } => Expression::BinaryOp { left: sub(left), op: *op, right: sub(right) },
+        Expression::UnaryOp { op, operand } => Expression::UnaryOp { op: *op, operand: sub(operand) },
+        Expression::FunctionCall { name, args } => Expression::FunctionCall { name: name.clone(), args: all(args) },
+        Expression::Conditional { condition, then_expr, else_expr } => Expression::Conditional {
+            condition: sub(condition),
+            then_expr: sub(then_expr),
+            else_expr: else_expr.as_deref().map(sub),
+        },
+        Expression::Assignment { target, value } => Expression::Assignment { target: target.clone(), value: sub(value) },
+        Expression::List(items) => Expression::List(all(items)),
+        Expression::Cast { expr, data_type } => Expression::Cast { expr: sub(expr), data_type: data_type.clone() },
+        Expression::Lambda { param, body } => {
+            let bound: Vec<&str> = bound.iter().copied().chain([param.as_str()]).collect();
+            Expression::Lambda { param: param.clone(), body: Box::new(substitute_facts(body, facts, &bound)) }
+        }
+        Expression::Case { branches, else_expr } => Expression::Case {
+            branches: branches
+                .iter()
+                .map(|(condition, result)| (substitute_facts(condition, facts, bound), substitute_facts(result, facts, bound)))
+                .collect(),
+            else_expr: else_expr.as_deref().map(sub),
+        },
+        Expression::Range { start, end, inclusive } => Expression::Range { start: sub(start), end: sub(end), inclusive: *inclusive },
+        Expression::Comprehension { element, var, source, condition } => {
+            let item_bound: Vec<&str> = bound.iter().copied().chain([var.as_str()]).collect();
+            Expression::Comprehension {
+                element: Box::new(substitute_facts(element, facts, &item_bound)),
+                var: var.clone(),
+                source: sub(source),
+                condition: condition.as_deref().map(|c| Box::new(substitute_facts(c, facts, &item_bound))),
+            }
+        }
+        // Literals and workflow verbs
+        _ => expr.clone(),
+    }
+}
+
+/// MAP, FILTER, SUM, ANY and ALL with a lambda: FILTER(amounts, x -> x > 10)
+fn evaluate_higher_order(name: &str, args: &[Expression], facts: &Facts, functions: &FunctionLibrary) -> Result<Value> {
+    let upper = name.to_uppercase();
+    let (list_expr, param, body) = match args {
+        [list_expr, Expression::Lambda { param, body }] => (list_expr, param, body),
+        _ => bail!("{} expects a list and a lambda, e.g. {}(items, x -> x > 10)", upper, upper),
+    };
+
+    let items = match evaluate_with_functions(list_expr, facts, functions)? {
+        Value::List(items) => items,
+        Value::Null => Vec::new(),
+        other => bail!("{} expects a list but got {:?}", upper, other),
+    };
+
+    // The lambda parameter shadows any fact of the same name
+    let mut scope = facts.clone();
+    let mut apply = |item: &Value| -> Result<Value> {
+        scope.insert(param.clone(), item.clone());
+        evaluate_with_functions(body, &scope, functions)
+    };
+
+    match upper.as_str() {
+        "MAP" => Ok(Value::List(items.iter().map(&mut apply).collect::<Result<_>>()?)),
+        "FILTER" => {
+            let mut kept = Vec::new();
+            for item in items {
+                if to_bool(&apply(&item)?) {
+                    kept.push(item);
+                }
+            }
+            Ok(Value::List(kept))
+        }
+        "SUM" => {
+            let mapped = items.iter().map(&mut apply).collect::<Result<Vec<_>>>()?;
+            functions.call_function("SUM", &[Value::List(mapped)])
+        }
+        "ANY" => {
+            for item in &items {
+                if to_bool(&apply(item)?) {
+                    return Ok(Value::Boolean(true));
+                }
+            }
+            Ok(Value::Boolean(false))
+        }
+        "ALL" => {
+            for item in &items {
+                if !to_bool(&apply(item)?) {
+                    return Ok(Value::Boolean(false));
+                }
+            }
+            Ok(Value::Boolean(true))
+        }
+        _ => bail!("Function '{}' does not accept a lambda argument", name),
+    }
+}
+
 fn evaluate_binary_op(op: BinaryOperator, left: &Value, right: &Value) -> Result<Value> {
     match op {
         // Arithmetic operators
+        BinaryOperator::Add
+        | BinaryOperator::Subtract
+        | BinaryOperator::Multiply
+        | BinaryOperator::Divide
+        | BinaryOperator::Modulo
+        | BinaryOperator::Power if has_unit(left) || has_unit(right) => unit_arithmetic(op, left, right),
         BinaryOperator::Add => arithmetic_add(left, right),
         BinaryOperator::Subtract => arithmetic_subtract(left, right),
         BinaryOperator::Multiply => arithmetic_multiply(left, right),
@@ -574,6 +1269,22 @@ fn evaluate_binary_op(op: BinaryOperator, left: &Value, right: &Value) -> Result
         // Set operations
         BinaryOperator::In => value_in_list(left, right),
         BinaryOperator::NotIn => Ok(Value::Boolean(!to_bool(&value_in_list(left, right)?))),
+        BinaryOperator::Between => bail!("BETWEEN requires a range: x BETWEEN lo AND hi"),
+    }
+}
+
+/// BETWEEN, IN and NOT IN against a range; a non-inclusive range leaves out its end
+fn evaluate_range_op(op: BinaryOperator, value: &Value, start: &Value, end: &Value, inclusive: bool) -> Result<Value> {
+    let above_start = compare_values(value, start)? >= 0;
+    let below_end = if inclusive {
+        compare_values(value, end)? <= 0
+    } else {
+        compare_values(value, end)? < 0
+    };
+    match op {
+        BinaryOperator::Between | BinaryOperator::In => Ok(Value::Boolean(above_start && below_end)),
+        BinaryOperator::NotIn => Ok(Value::Boolean(!(above_start && below_end))),
+        _ => bail!("{:?} cannot be applied to a range", op),
     }
 }
 
@@ -583,10 +1294,12 @@ fn evaluate_unary_op(op: UnaryOperator, operand: &Value) -> Result<Value> {
         UnaryOperator::Minus => match operand {
             Value::Integer(i) => Ok(Value::Integer(-i)),
             Value::Float(f) => Ok(Value::Float(-f)),
+            Value::Percent(p) => Ok(Value::Percent(-p)),
+            Value::Money { amount, currency } => Ok(Value::Money { amount: -amount, currency: currency.clone() }),
             _ => bail!("Cannot apply unary minus to {:?}", operand),
         },
         UnaryOperator::Plus => match operand {
-            Value::Integer(_) | Value::Float(_) => Ok(operand.clone()),
+            Value::Integer(_) | Value::Float(_) | Value::Percent(_) | Value::Money { .. } => Ok(operand.clone()),
             _ => bail!("Cannot apply unary plus to {:?}", operand),
         },
     }
@@ -671,7 +1384,101 @@ fn arithmetic_power(left: &Value, right: &Value) -> Result<Value> {
     }
 }
 
+/// A number as it takes part in unit arithmetic
+#[derive(Clone, Copy)]
+enum Quantity<'a> {
+    Plain(f64),
+    Percent(f64),
+    Money(f64, &'a str),
+}
+
+fn quantity(value: &Value) -> Option<Quantity<'_>> {
+    match value {
+        Value::Integer(i) => Some(Quantity::Plain(*i as f64)),
+        Value::Float(f) | Value::Number(f) => Some(Quantity::Plain(*f)),
+        Value::Percent(p) => Some(Quantity::Percent(*p)),
+        Value::Money { amount, currency } => Some(Quantity::Money(*amount, currency)),
+        _ => None,
+    }
+}
+
+fn has_unit(value: &Value) -> bool {
+    matches!(value, Value::Percent(_) | Value::Money { .. })
+}
+
+/// Arithmetic with a percentage or an amount of money on either side.
+/// A percentage scales what it multiplies (`5% * 200` is 10), amounts keep
+/// their currency, and mixing currencies or adding a plain number to a
+/// percentage is an error rather than a silent guess.
+fn unit_arithmetic(op: BinaryOperator, left: &Value, right: &Value) -> Result<Value> {
+    use BinaryOperator::{Add, Divide, Multiply, Subtract};
+    use Quantity::{Money, Percent, Plain};
+
+    let (Some(l), Some(r)) = (quantity(left), quantity(right)) else {
+        bail!("Cannot apply {:?} to {:?} and {:?}", op, left, right);
+    };
+    let money = |amount: f64, currency: &str| Ok(Value::Money { amount, currency: currency.to_string() });
+
+    match (op, l, r) {
+        (Divide, _, Plain(d) | Percent(d) | Money(d, _)) if d == 0.0 => bail!("Division by zero"),
+        (_, Money(_, a), Money(_, b)) if a != b => bail!("Cannot mix {} and {} amounts", a, b),
+
+        (Add, Percent(a), Percent(b)) => Ok(Value::Percent(a + b)),
+        (Subtract, Percent(a), Percent(b)) => Ok(Value::Percent(a - b)),
+        (Multiply, Percent(a), Percent(b)) => Ok(Value::Percent(a * b / 100.0)),
+        (Divide, Percent(a), Percent(b)) => Ok(Value::Float(a / b)),
+        (Multiply, Percent(p), Plain(n)) | (Multiply, Plain(n), Percent(p)) => Ok(Value::Float(n * p / 100.0)),
+        (Divide, Percent(p), Plain(n)) => Ok(Value::Percent(p / n)),
+        (Divide, Plain(n), Percent(p)) => Ok(Value::Float(n / (p / 100.0))),
+        (Add | Subtract, Percent(_), Plain(_)) | (Add | Subtract, Plain(_), Percent(_)) => bail!(
+            "Cannot {:?} a percentage and a plain number; convert a fraction with TO_PCT first",
+            op
+        ),
+
+        (Add, Money(a, c), Money(b, _)) => money(a + b, c),
+        (Subtract, Money(a, c), Money(b, _)) => money(a - b, c),
+        (Divide, Money(a, _), Money(b, _)) => Ok(Value::Float(a / b)),
+        (Add, Money(a, c), Plain(n)) | (Add, Plain(n), Money(a, c)) => money(a + n, c),
+        (Subtract, Money(a, c), Plain(n)) => money(a - n, c),
+        (Subtract, Plain(n), Money(a, c)) => money(n - a, c),
+        (Multiply, Money(a, c), Plain(n)) | (Multiply, Plain(n), Money(a, c)) => money(a * n, c),
+        (Multiply, Money(a, c), Percent(p)) | (Multiply, Percent(p), Money(a, c)) => money(a * p / 100.0, c),
+        (Divide, Money(a, c), Plain(n)) => money(a / n, c),
+
+        _ => bail!("Cannot apply {:?} to {} and {}", op, value_to_string(left), value_to_string(right)),
+    }
+}
+
+/// Percentages compare with plain numbers as fractions (`5% = 0.05`),
+/// amounts of money by amount, and only within one currency
+fn compare_quantities(left: &Value, right: &Value) -> Result<i32> {
+    use Quantity::{Money, Percent, Plain};
+
+    let (l, r) = match (quantity(left), quantity(right)) {
+        (Some(Percent(a)), Some(Percent(b))) => (a, b),
+        (Some(Percent(p)), Some(Plain(n))) => (p / 100.0, n),
+        (Some(Plain(n)), Some(Percent(p))) => (n, p / 100.0),
+        (Some(Money(a, c)), Some(Money(b, d))) if c == d => (a, b),
+        (Some(Money(_, c)), Some(Money(_, d))) => bail!("Cannot compare {} and {} amounts", c, d),
+        (Some(Money(a, _)), Some(Plain(n))) => (a, n),
+        (Some(Plain(n)), Some(Money(a, _))) => (n, a),
+        _ => bail!("Cannot compare {:?} and {:?}", left, right),
+    };
+    Ok(l.partial_cmp(&r).unwrap_or(std::cmp::Ordering::Equal) as i32)
+}
+
 // Helper functions
+
+/// A fact by name; `x.amount` reads the amount field of the record `x` when
+/// no fact has the dotted name itself
+fn lookup_fact(facts: &Facts, name: &str) -> Option<Value> {
+    if let Some(value) = facts.get(name) {
+        return Some(value.clone());
+    }
+    let (root, path) = name.split_once('.')?;
+    facts.get(root)?.field(path).cloned()
+}
+
 fn value_to_string(value: &Value) -> String {
     match value {
         Value::String(s) => s.clone(),
@@ -685,10 +1492,13 @@ fn value_to_string(value: &Value) -> String {
             format!("[{}]", items.join(", "))
         },
         Value::Regex(pattern) => format!("/{}/", pattern),
+        Value::Percent(p) => format!("{}%", p),
+        Value::Money { amount, currency } => format!("{} {}", amount, currency),
+        Value::Object(_) => value.to_json().to_string(),
     }
 }
 
-fn to_bool(value: &Value) -> bool {
+pub(crate) fn to_bool(value: &Value) -> bool {
     match value {
         Value::Boolean(b) => *b,
         Value::Integer(i) => *i != 0,
@@ -698,11 +1508,15 @@ fn to_bool(value: &Value) -> bool {
         Value::Null => false,
         Value::List(l) => !l.is_empty(),
         Value::Regex(_) => true,
+        Value::Percent(p) => *p != 0.0,
+        Value::Money { amount, .. } => *amount != 0.0,
+        Value::Object(fields) => !fields.is_empty(),
     }
 }
 
 fn values_equal(left: &Value, right: &Value) -> bool {
     match (left, right) {
+        (l, r) if has_unit(l) || has_unit(r) => matches!(compare_quantities(l, r), Ok(0)),
         (Value::Integer(l), Value::Integer(r)) => l == r,
         (Value::Float(l), Value::Float(r)) => (l - r).abs() < f64::EPSILON,
         (Value::Integer(l), Value::Float(r)) => (*l as f64 - r).abs() < f64::EPSILON,
@@ -718,6 +1532,7 @@ fn values_equal(left: &Value, right: &Value) -> bool {
 
 fn compare_values(left: &Value, right: &Value) -> Result<i32> {
     match (left, right) {
+        (l, r) if has_unit(l) || has_unit(r) => compare_quantities(l, r),
         (Value::Integer(l), Value::Integer(r)) => Ok(l.cmp(r) as i32),
         (Value::Float(l), Value::Float(r)) => Ok(l.partial_cmp(r).unwrap_or(std::cmp::Ordering::Equal) as i32),
         (Value::Integer(l), Value::Float(r)) => Ok((*l as f64).partial_cmp(r).unwrap_or(std::cmp::Ordering::Equal) as i32),
@@ -734,10 +1549,24 @@ fn pattern_matches(text: &Value, pattern: &Value) -> Result<Value> {
         _ => value_to_string(pattern),
     };
 
-    match Regex::new(&pattern_str) {
-        Ok(regex) => Ok(Value::Boolean(regex.is_match(&text_str))),
-        Err(_) => bail!("Invalid regex pattern: {}", pattern_str),
-    }
+    let regex = regex_cache::compiled(&pattern_str)?;
+    Ok(Value::Boolean(regex.is_match(&text_str)))
+}
+
+/// The whole match, or the group named or numbered by `group`; a group that
+/// took no part in the match is null
+fn capture_group(regex: &Regex, captures: &regex::Captures, group: Option<&Value>) -> Result<Value> {
+    let matched = match group {
+        None => captures.get(0),
+        Some(Value::Integer(index)) if *index >= 0 && (*index as usize) < regex.captures_len() => {
+            captures.get(*index as usize)
+        }
+        Some(Value::String(name)) if regex.capture_names().flatten().any(|n| n == name) => {
+            captures.name(name)
+        }
+        Some(other) => bail!("Pattern /{}/ has no capture group {}", regex.as_str(), value_to_string(other)),
+    };
+    Ok(matched.map_or(Value::Null, |m| Value::String(m.as_str().to_string())))
 }
 
 fn value_in_list(value: &Value, list: &Value) -> Result<Value> {
@@ -767,10 +1596,395 @@ fn cast_value(value: Value, data_type: &str) -> Result<Value> {
         "FLOAT" => match value {
             Value::Float(f) => Ok(Value::Float(f)),
             Value::Integer(i) => Ok(Value::Float(i as f64)),
+            Value::Percent(p) => Ok(Value::Float(p / 100.0)),
+            Value::Money { amount, .. } => Ok(Value::Float(amount)),
             Value::String(s) => s.parse::<f64>().map(Value::Float).map_err(|_| anyhow::anyhow!("Cannot cast '{}' to float", s)),
             _ => bail!("Cannot cast {:?} to float", value),
         },
         "BOOLEAN" => Ok(Value::Boolean(to_bool(&value))),
         _ => bail!("Unknown data type: {}", data_type),
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_expression;
+
+    fn eval(input: &str, facts: &Facts) -> Value {
+        let (remaining, ast) = parse_expression(input).unwrap();
+        assert!(remaining.trim().is_empty(), "unparsed input: {}", remaining);
+        evaluate(&ast, facts).unwrap()
+    }
+
+    fn amounts() -> Facts {
+        let mut facts = Facts::new();
+        facts.insert(
+            "amounts".to_string(),
+            Value::List(vec![Value::Integer(5), Value::Integer(12), Value::Integer(30)]),
+        );
+        facts
+    }
+
+    #[test]
+    fn test_builtin_functions_are_all_implemented() {
+        let library = FunctionLibrary::new();
+        for name in BUILTIN_FUNCTIONS.iter().filter(|name| !matches!(**name, "MAP" | "FILTER" | "ANY" | "ALL")) {
+            if let Err(e) = library.call_function(name, &[Value::String("x".to_string())]) {
+                assert!(!e.to_string().starts_with("Unknown function"), "{} is not implemented", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_filter_and_map_over_context_arrays() {
+        assert_eq!(
+            eval("FILTER(amounts, x -> x > 10)", &amounts()),
+            Value::List(vec![Value::Integer(12), Value::Integer(30)])
+        );
+        assert_eq!(
+            eval("MAP(amounts, a -> a * 2)", &amounts()),
+            Value::List(vec![Value::Integer(10), Value::Integer(24), Value::Integer(60)])
+        );
+    }
+
+    fn transactions() -> Facts {
+        let json = serde_json::json!([
+            { "amount": 120, "currency": "USD", "counterparty": { "country": "US" } },
+            { "amount": 80, "currency": "EUR", "counterparty": { "country": "DE" } },
+            { "amount": 45, "currency": "USD", "counterparty": { "country": "GB" } },
+        ]);
+        let mut facts = Facts::new();
+        facts.insert("transactions".to_string(), Value::from_json(&json));
+        facts
+    }
+
+    #[test]
+    fn test_list_comprehension_reads_record_fields() {
+        let facts = transactions();
+        assert_eq!(
+            eval(r#"[x.amount FOR x IN transactions IF x.currency == "USD"]"#, &facts),
+            Value::List(vec![Value::Integer(120), Value::Integer(45)])
+        );
+        assert_eq!(
+            eval("[x.counterparty.country FOR x IN transactions]", &facts),
+            Value::List(vec![
+                Value::String("US".to_string()),
+                Value::String("DE".to_string()),
+                Value::String("GB".to_string()),
+            ])
+        );
+        assert_eq!(eval(r#"SUM([x.amount FOR x IN transactions IF x.currency == "USD"])"#, &facts), Value::Integer(165));
+        assert_eq!(eval("[n * 2 FOR n IN [1, 2, 3] IF n > 1]", &facts), Value::List(vec![Value::Integer(4), Value::Integer(6)]));
+        assert_eq!(eval("[x.amount FOR x IN missing]", &facts), Value::List(vec![]));
+        assert_eq!(eval("SUM(transactions, x -> x.amount)", &facts), Value::Integer(245));
+    }
+
+    #[test]
+    fn test_list_comprehension_requires_list() {
+        let (_, ast) = parse_expression("[x FOR x IN 42]").unwrap();
+        assert!(evaluate(&ast, &Facts::new()).is_err());
+    }
+
+    #[test]
+    fn test_aggregates_over_list_literals() {
+        let facts = Facts::new();
+        assert_eq!(eval("SUM([1, 2, 3], x -> x * 10)", &facts), Value::Integer(60));
+        assert_eq!(eval("ANY([1, 2, 3], x -> x > 2)", &facts), Value::Boolean(true));
+        assert_eq!(eval("ALL([1, 2, 3], x -> x > 2)", &facts), Value::Boolean(false));
+        assert_eq!(eval("SUM(FILTER(amounts, x -> x > 10))", &amounts()), Value::Integer(42));
+    }
+
+    #[test]
+    fn test_lambda_sees_outer_facts() {
+        let mut facts = amounts();
+        facts.insert("threshold".to_string(), Value::Integer(20));
+        assert_eq!(eval("ANY(amounts, x -> x > threshold)", &facts), Value::Boolean(true));
+        assert_eq!(eval("ALL(amounts, x -> x > threshold)", &facts), Value::Boolean(false));
+    }
+
+    #[test]
+    fn test_case_rate_bands() {
+        let band = "CASE WHEN balance >= 100000 THEN 0.03 WHEN balance >= 10000 THEN 0.02 ELSE 0.01 END";
+        let mut facts = Facts::new();
+        for (balance, rate) in [(250000, 0.03), (10000, 0.02), (500, 0.01)] {
+            facts.insert("balance".to_string(), Value::Integer(balance));
+            assert_eq!(eval(band, &facts), Value::Float(rate));
+        }
+    }
+
+    #[test]
+    fn test_case_without_else_is_null() {
+        let mut facts = Facts::new();
+        facts.insert("tier".to_string(), Value::String("retail".to_string()));
+        assert_eq!(eval("CASE WHEN tier == \"premium\" THEN 1 END", &facts), Value::Null);
+    }
+
+    #[test]
+    fn test_template_string_interpolation() {
+        let mut facts = Facts::new();
+        facts.insert("base_rate".to_string(), Value::Integer(2));
+        facts.insert("client".to_string(), Value::String("Acme".to_string()));
+        assert_eq!(
+            eval("`Rate: ${ (base_rate + 1) * 100 }%`", &facts),
+            Value::String("Rate: 300%".to_string())
+        );
+        assert_eq!(eval("`${client}`", &facts), Value::String("Acme".to_string()));
+        assert_eq!(eval("`${UPPER(client)}-${base_rate}`", &facts), Value::String("ACME-2".to_string()));
+    }
+
+    #[test]
+    fn test_locale_formatting_functions() {
+        let mut facts = Facts::new();
+        facts.insert("fee".to_string(), Value::Float(1234.56));
+        facts.insert("trade_date".to_string(), Value::String("2024-03-15".to_string()));
+        assert_eq!(eval("FORMAT_NUMBER(fee, \"de-DE\")", &facts), Value::String("1.234,56".to_string()));
+        assert_eq!(eval("FORMAT_NUMBER(fee, \"en-GB\", 0)", &facts), Value::String("1,235".to_string()));
+        assert_eq!(
+            eval("FORMAT_DATE(trade_date, \"%d %B %Y\", \"fr-FR\")", &facts),
+            Value::String("15 mars 2024".to_string())
+        );
+        assert_eq!(eval("TO_NUMBER(\"1.234,56\", \"de-DE\")", &facts), Value::Float(1234.56));
+        assert_eq!(eval("TO_NUMBER(\"1.234\", \"de-DE\")", &facts), Value::Integer(1234));
+    }
+
+    #[test]
+    fn test_percent_and_money_arithmetic() {
+        let mut facts = Facts::new();
+        facts.insert("notional".to_string(), Value::Money { amount: 2_500_000.0, currency: "USD".to_string() });
+        facts.insert("fee_rate".to_string(), Value::Float(0.0125));
+        let usd = |amount: f64| Value::Money { amount, currency: "USD".to_string() };
+
+        assert_eq!(eval("5% * 200", &facts), Value::Float(10.0));
+        assert_eq!(eval("5% + 2.5%", &facts), Value::Percent(7.5));
+        assert_eq!(eval("notional * 2%", &facts), usd(50_000.0));
+        assert_eq!(eval("notional - 1_000_000 USD", &facts), usd(1_500_000.0));
+        assert_eq!(eval("notional / 1_000_000 USD", &facts), Value::Float(2.5));
+        assert_eq!(eval("notional >= 1_000_000 USD", &facts), Value::Boolean(true));
+        assert_eq!(eval("TO_PCT(fee_rate) > 1%", &facts), Value::Boolean(true));
+        assert_eq!(eval("5% = 0.05", &facts), Value::Boolean(true));
+        assert_eq!(eval("TO_BASIS_POINTS(1.25%)", &facts), Value::Float(125.0));
+        assert_eq!(eval("TO_BASIS_POINTS(fee_rate)", &facts), Value::Float(125.0));
+        assert_eq!(eval("10 % 3", &facts), Value::Integer(1));
+
+        let functions = FunctionLibrary::new();
+        for mixed in ["notional + 10 EUR", "notional > 10 EUR", "5% + 1"] {
+            let (_, expr) = parse_expression(mixed).unwrap();
+            assert!(evaluate_with_functions(&expr, &facts, &functions).is_err(), "{} should fail", mixed);
+        }
+    }
+
+    #[test]
+    fn test_format_number_keeps_units() {
+        let facts = Facts::new();
+        assert_eq!(eval("FORMAT_NUMBER(1_234.5 EUR, \"de-DE\")", &facts), Value::String("1.234,50 EUR".to_string()));
+        assert_eq!(eval("FORMAT_NUMBER(150_000 JPY, \"en-US\")", &facts), Value::String("150,000 JPY".to_string()));
+        assert_eq!(eval("FORMAT_NUMBER(2.75%, \"fr-FR\", 1)", &facts), Value::String("2,8%".to_string()));
+        assert_eq!(eval("\"Fee: \" & 25 GBP", &facts), Value::String("Fee: 25 GBP".to_string()));
+    }
+
+    #[test]
+    fn test_membership_operators() {
+        let mut facts = Facts::new();
+        facts.insert("country".to_string(), Value::String("GB".to_string()));
+        assert_eq!(eval("country IN [\"US\", \"GB\", \"DE\"]", &facts), Value::Boolean(true));
+        assert_eq!(eval("country NOT IN [\"US\", \"DE\"]", &facts), Value::Boolean(true));
+        assert_eq!(eval("12 IN amounts", &amounts()), Value::Boolean(true));
+
+        let (_, ast) = parse_expression("country IN \"GB\"").unwrap();
+        assert!(evaluate(&ast, &facts).is_err());
+    }
+
+    #[test]
+    fn test_between_and_ranges() {
+        let mut facts = Facts::new();
+        facts.insert("amount".to_string(), Value::Integer(5000));
+        facts.insert("rate".to_string(), Value::Float(0.25));
+        assert_eq!(eval("amount BETWEEN 1000 AND 5000", &facts), Value::Boolean(true));
+        assert_eq!(eval("amount BETWEEN_EXCLUSIVE 1000 AND 5000", &facts), Value::Boolean(false));
+        assert_eq!(eval("amount BETWEEN 1000 + 1 AND 2000 * 3", &facts), Value::Boolean(true));
+        assert_eq!(eval("rate IN 0..1", &facts), Value::Boolean(true));
+        assert_eq!(eval("amount NOT IN 1000..5000", &facts), Value::Boolean(true));
+
+        let (_, ast) = parse_expression("amount == 1..5").unwrap();
+        assert!(evaluate(&ast, &facts).is_err());
+        let (_, ast) = parse_expression("amount BETWEEN \"a\" AND \"z\"").unwrap();
+        assert!(evaluate(&ast, &facts).is_err());
+    }
+
+    #[test]
+    fn test_fail_soft_collects_every_error() {
+        let mut facts = Facts::new();
+        facts.insert("amount".to_string(), Value::Integer(100));
+        facts.insert("name".to_string(), Value::String("ACME".to_string()));
+        let functions = FunctionLibrary::new();
+        let soft = |input: &str| evaluate_fail_soft(&parse_expression(input).unwrap().1, &facts, &functions);
+
+        let result = soft("[amount / 0, name * 2, amount + 1]");
+        assert_eq!(result.value, None);
+        let failed: Vec<&str> = result.errors.iter().map(|e| e.expression.as_str()).collect();
+        assert_eq!(failed, vec!["amount / 0", "name * 2"]);
+        assert_eq!(result.errors[0].message, "Division by zero");
+
+        // Error values propagate without piling up further errors
+        assert_eq!(soft("(amount / 0) * 2 + LENGTH(name)").errors.len(), 1);
+
+        // Answers that don't depend on the failure still come through
+        let result = soft("name > 3 OR amount > 50");
+        assert_eq!(result.value, Some(Value::Boolean(true)));
+        assert_eq!(result.errors.len(), 1);
+        // A decisive left side short-circuits, so the right side never fails
+        assert_eq!(soft("amount > 50 OR name > 3"), FailSoftEvaluation { value: Some(Value::Boolean(true)), errors: vec![] });
+        let result = soft("CASE WHEN amount > 50 THEN \"large\" ELSE name * 2 END");
+        assert_eq!(result, FailSoftEvaluation { value: Some(Value::String("large".to_string())), errors: vec![] });
+
+        // Facts whose own rules failed are error values, not nulls
+        let failed_facts = HashSet::from(["risk_score".to_string()]);
+        let expr = parse_expression("risk_score * 2").unwrap().1;
+        let result = evaluate_fail_soft_with_failed_facts(&expr, &facts, &functions, &failed_facts);
+        assert_eq!(result, FailSoftEvaluation { value: None, errors: vec![] });
+    }
+
+
+    #[test]
+    fn test_evaluate_traced_records_each_sub_expression() {
+        let mut facts = Facts::new();
+        facts.insert("income".to_string(), Value::Integer(2000));
+        facts.insert("rate".to_string(), Value::Integer(3));
+        let functions = FunctionLibrary::new();
+        let traced = |input: &str| evaluate_traced(&parse_expression(input).unwrap().1, &facts, &functions);
+
+        let trace = traced("IF income > 1000 THEN income * rate ELSE 0");
+        assert_eq!(trace.value, Some(Value::Integer(6000)));
+        // The condition and the branch taken; the ELSE branch never ran
+        let steps: Vec<(&str, Option<&Value>)> =
+            trace.inputs.iter().map(|node| (node.expression.as_str(), node.value.as_ref())).collect();
+        assert_eq!(
+            steps,
+            vec![("income > 1000", Some(&Value::Boolean(true))), ("income * rate", Some(&Value::Integer(6000)))]
+        );
+        let product = &trace.inputs[1];
+        assert_eq!(product.inputs[0].expression, "income");
+        assert_eq!(product.inputs[1].value, Some(Value::Integer(3)));
+
+        // A failure is recorded where it happened and carried up to the root
+        let trace = traced("income / (rate - 3) + 1");
+        assert_eq!(trace.value, None);
+        assert_eq!(trace.error.as_deref(), Some("Division by zero"));
+        assert_eq!(trace.inputs.len(), 1);
+        assert_eq!(trace.inputs[0].expression, "income / (rate - 3)");
+        assert_eq!(trace.inputs[0].inputs[1].value, Some(Value::Integer(0)));
+    }
+    #[test]
+    fn test_short_circuit_defers_lookup_resolution() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let resolved = Arc::new(AtomicUsize::new(0));
+        let mut functions = FunctionLibrary::new();
+        let counter = resolved.clone();
+        functions.set_lookup_resolver(move |table| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            (table == "country_risk").then(|| HashMap::from([("IR".to_string(), "high".to_string())]))
+        });
+        let mut facts = Facts::new();
+        facts.insert("country".to_string(), Value::String("IR".to_string()));
+        facts.insert("amount".to_string(), Value::Integer(50));
+        let run = |input: &str| evaluate_with_functions(&parse_expression(input).unwrap().1, &facts, &functions);
+
+        // Sides and branches that can't change the answer never resolve the table
+        assert_eq!(run("amount > 100 AND LOOKUP(country, \"country_risk\") == \"high\"").unwrap(), Value::Boolean(false));
+        assert_eq!(run("amount < 100 OR LOOKUP(country, \"country_risk\") == \"high\"").unwrap(), Value::Boolean(true));
+        assert_eq!(run("IF amount > 100 THEN LOOKUP(country, \"country_risk\") ELSE \"low\"").unwrap(), Value::String("low".to_string()));
+        let traced = evaluate_traced(&parse_expression("false AND LOOKUP(country, \"country_risk\") == \"high\"").unwrap().1, &facts, &functions);
+        assert_eq!(traced.inputs.len(), 1);
+        assert_eq!(resolved.load(Ordering::SeqCst), 0);
+
+        // Once needed, a table is resolved once however often it is used
+        assert_eq!(
+            run("amount < 100 AND LOOKUP(country, \"country_risk\") == \"high\" AND LOOKUP(\"GB\", \"country_risk\") == null").unwrap(),
+            Value::Boolean(true)
+        );
+        assert_eq!(resolved.load(Ordering::SeqCst), 1);
+
+        // Unknown tables are remembered too
+        assert!(run("LOOKUP(country, \"sanctions\")").is_err());
+        assert!(run("LOOKUP(country, \"sanctions\")").is_err());
+        assert_eq!(resolved.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_lambda_requires_list() {
+        let (_, ast) = parse_expression("MAP(42, x -> x)").unwrap();
+        assert!(evaluate(&ast, &Facts::new()).is_err());
+    }
+
+    #[test]
+    fn test_regex_extraction_functions() {
+        let mut facts = Facts::new();
+        facts.insert("lei".to_string(), Value::String("5493001KJTIIGC8Y1R12".to_string()));
+        facts.insert("refs".to_string(), Value::String("INV-12, INV-7 and CN-3".to_string()));
+
+        assert_eq!(eval(r#"EXTRACT(lei, /(?<prefix>\w{4})/, "prefix")"#, &facts), Value::String("5493".to_string()));
+        assert_eq!(eval(r#"EXTRACT(refs, /([A-Z]+)-(\d+)/, 2)"#, &facts), Value::String("12".to_string()));
+        assert_eq!(eval(r#"EXTRACT(refs, /[A-Z]+-\d+/)"#, &facts), Value::String("INV-12".to_string()));
+        assert_eq!(eval(r#"EXTRACT(refs, /XYZ/)"#, &facts), Value::Null);
+        assert_eq!(
+            eval(r#"EXTRACT_ALL(refs, /(?<kind>[A-Z]+)-\d+/, "kind")"#, &facts),
+            Value::List(vec![
+                Value::String("INV".to_string()),
+                Value::String("INV".to_string()),
+                Value::String("CN".to_string()),
+            ])
+        );
+        assert_eq!(eval(r#"EXTRACT_ALL(missing, /\d+/)"#, &facts), Value::List(vec![]));
+        assert_eq!(eval(r#"MATCH_COUNT(refs, "INV-[0-9]+")"#, &facts), Value::Integer(2));
+        assert_eq!(eval(r#"MATCH_COUNT(refs, /\d+/) > 2"#, &facts), Value::Boolean(true));
+
+        let (_, ast) = parse_expression(r#"EXTRACT(lei, /(?<prefix>\w{4})/, "suffix")"#).unwrap();
+        assert!(evaluate(&ast, &facts).is_err());
+    }
+
+    #[test]
+    fn test_regex_compiled_once_per_library() {
+        let functions = FunctionLibrary::new();
+        let (_, ast) = parse_expression(r#"MATCH_COUNT(refs, /\d+/) + MATCH_COUNT(refs, /[A-Z]+/)"#).unwrap();
+        let mut facts = Facts::new();
+        for refs in ["A1", "B2 C3"] {
+            facts.insert("refs".to_string(), Value::String(refs.to_string()));
+            evaluate_with_functions(&ast, &facts, &functions).unwrap();
+        }
+        assert_eq!(functions.regex_cache.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_partial_evaluation_keeps_what_is_unknown() {
+        let functions = FunctionLibrary::new();
+        let mut facts = Facts::new();
+        facts.insert("amount".to_string(), Value::Integer(200));
+        facts.insert("x".to_string(), Value::Integer(1));
+        let partial = |source: &str| evaluate_partial(&parse_expression(source).unwrap().1, &facts, &functions);
+
+        assert_eq!(partial("amount * 2 > 300"), PartialValue::Known(Value::Boolean(true)));
+        assert_eq!(partial("amount > 1000 AND approved"), PartialValue::Known(Value::Boolean(false)));
+        match partial("amount * 2 + fee") {
+            PartialValue::Residual { missing, residual } => {
+                assert_eq!(missing, vec!["fee".to_string()]);
+                assert_eq!(format_expression(&residual), "400 + fee");
+            }
+            other => panic!("expected a residual, got {:?}", other),
+        }
+        // A lambda's parameter is not the fact of the same name
+        match partial("x > amount") {
+            PartialValue::Known(value) => assert_eq!(value, Value::Boolean(false)),
+            other => panic!("expected a value, got {:?}", other),
+        }
+        match partial("FILTER(items, x -> x > amount)") {
+            PartialValue::Residual { missing, residual } => {
+                assert_eq!(missing, vec!["items".to_string()]);
+                assert_eq!(format_expression(&residual), "FILTER(items, x -> x > 200)");
+            }
+            other => panic!("expected a residual, got {:?}", other),
+        }
+        assert!(matches!(partial("amount / 0"), PartialValue::Failed(_)));
+    }
+}
@@ -0,0 +1,268 @@
+//! Encryption at rest of sensitive attribute values
+//!
+//! Values of attributes tagged `sensitive` in the dictionary are stored as
+//! AES-256-GCM envelopes, `{"$encrypted": {"key": "v2", "nonce": "..", "data": ".."}}`,
+//! instead of plain JSON. The attribute name is bound in as associated data,
+//! so an envelope copied onto another attribute fails to decrypt. Each
+//! envelope names its key: values stay readable with any configured key, and
+//! rotation re-encrypts those not yet under the current one. Values that
+//! aren't envelopes read back as they are, so encryption can be switched on
+//! over existing data.
+//!
+//! Sensitive values are also kept out of traces exported for full-text
+//! search, along with everything computed from them (`redact_trace`).
+
+use crate::config::EncryptionConfig;
+use crate::evaluator::TraceNode;
+use crate::models::Value;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, bail, Context as _, Result};
+use serde_json::{json, Value as JsonValue};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::OnceLock;
+
+/// The dictionary tag marking an attribute's values sensitive
+pub const SENSITIVE_TAG: &str = "sensitive";
+
+/// Shown in exported traces in place of sensitive values
+pub const REDACTED: &str = "[redacted]";
+
+const ENVELOPE: &str = "$encrypted";
+
+static INSTALLED: OnceLock<FieldCipher> = OnceLock::new();
+
+/// The environment variable a key is read from with `key_source = "env"`
+pub fn env_var(key_id: &str) -> String {
+    format!("DATA_DESIGNER_FIELD_KEY_{}", key_id.to_uppercase().replace('-', "_"))
+}
+
+/// The keychain entry, under service `data-designer`, a key is read from
+pub fn keychain_entry(key_id: &str) -> String {
+    format!("field-key-{}", key_id)
+}
+
+pub struct FieldCipher {
+    current: String,
+    keys: HashMap<String, Aes256Gcm>,
+}
+
+impl FieldCipher {
+    /// Hex-encoded 32-byte keys by id; the current key must be one of them
+    pub fn new(current: &str, keys: &[(String, String)]) -> Result<Self> {
+        let mut ciphers = HashMap::new();
+        for (id, key) in keys {
+            let bytes = hex::decode(key.trim()).with_context(|| format!("Field key '{}' is not hex", id))?;
+            let cipher = Aes256Gcm::new_from_slice(&bytes).map_err(|_| anyhow!("Field key '{}' must be 32 bytes", id))?;
+            ciphers.insert(id.clone(), cipher);
+        }
+        if !ciphers.contains_key(current) {
+            bail!("No key for the current field key id '{}'", current);
+        }
+        Ok(Self { current: current.to_string(), keys: ciphers })
+    }
+
+    /// The current and previous keys of `config`, each fetched by id
+    pub fn from_config(config: &EncryptionConfig, fetch: impl Fn(&str) -> Result<String>) -> Result<Self> {
+        let mut keys = Vec::new();
+        for id in std::iter::once(&config.current_key_id).chain(&config.previous_key_ids) {
+            let key = fetch(id).with_context(|| format!("Failed to load field key '{}'", id))?;
+            keys.push((id.clone(), key));
+        }
+        Self::new(&config.current_key_id, &keys)
+    }
+
+    pub fn current_key_id(&self) -> &str {
+        &self.current
+    }
+
+    pub fn encrypt(&self, attribute: &str, value: &JsonValue) -> Result<JsonValue> {
+        let cipher = &self.keys[&self.current];
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let plaintext = serde_json::to_vec(value)?;
+        let data = cipher
+            .encrypt(&nonce, Payload { msg: &plaintext, aad: attribute.as_bytes() })
+            .map_err(|_| anyhow!("Failed to encrypt {}", attribute))?;
+        Ok(json!({ ENVELOPE: { "key": self.current, "nonce": hex::encode(nonce), "data": hex::encode(data) } }))
+    }
+
+    /// The plain value of an envelope; anything else is returned unchanged
+    pub fn decrypt(&self, attribute: &str, value: &JsonValue) -> Result<JsonValue> {
+        let Some(envelope) = envelope(value) else {
+            return Ok(value.clone());
+        };
+        let field = |name: &str| envelope[name].as_str().ok_or_else(|| anyhow!("Encrypted {} has no {}", attribute, name));
+        let key_id = field("key")?;
+        let cipher = self.keys.get(key_id).ok_or_else(|| anyhow!("Encrypted {} needs unknown field key '{}'", attribute, key_id))?;
+        let nonce: [u8; 12] = hex::decode(field("nonce")?)?
+            .try_into()
+            .map_err(|_| anyhow!("Encrypted {} has a malformed nonce", attribute))?;
+        let data = hex::decode(field("data")?)?;
+        let plaintext = cipher
+            .decrypt(&Nonce::from(nonce), Payload { msg: &data, aad: attribute.as_bytes() })
+            .map_err(|_| anyhow!("Failed to decrypt {}: wrong key or tampered value", attribute))?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    /// Whether a value is encrypted under a key other than the current one
+    pub fn needs_rotation(&self, value: &JsonValue) -> bool {
+        key_id(value).is_some_and(|key| key != self.current)
+    }
+}
+
+pub fn is_encrypted(value: &JsonValue) -> bool {
+    envelope(value).is_some()
+}
+
+/// The id of the key an envelope was encrypted with
+pub fn key_id(value: &JsonValue) -> Option<&str> {
+    envelope(value).and_then(|envelope| envelope["key"].as_str())
+}
+
+fn envelope(value: &JsonValue) -> Option<&JsonValue> {
+    match value.as_object() {
+        Some(object) if object.len() == 1 => object.get(ENVELOPE),
+        _ => None,
+    }
+}
+
+/// Make `cipher` the one the persistence layer encrypts and decrypts with; once per process
+pub fn install(cipher: FieldCipher) -> Result<()> {
+    INSTALLED.set(cipher).map_err(|_| anyhow!("A field cipher is already installed"))
+}
+
+pub fn installed() -> Option<&'static FieldCipher> {
+    INSTALLED.get()
+}
+
+/// A value as it should be stored: encrypted when the attribute is sensitive
+/// and a cipher is installed
+pub fn seal(attribute: &str, value: &JsonValue, sensitive: &SensitiveAttributes) -> Result<JsonValue> {
+    match installed() {
+        Some(cipher) if sensitive.contains(attribute) => cipher.encrypt(attribute, value),
+        _ => Ok(value.clone()),
+    }
+}
+
+/// A stored value as it was written; an envelope with no cipher installed is an error
+pub fn open(attribute: &str, value: &JsonValue) -> Result<JsonValue> {
+    match installed() {
+        Some(cipher) => cipher.decrypt(attribute, value),
+        None if is_encrypted(value) => bail!("{} is encrypted but no field encryption key is configured", attribute),
+        None => Ok(value.clone()),
+    }
+}
+
+/// Attributes tagged sensitive. Tags are on full paths such as `Client.email`;
+/// since rules also read attributes by plain name, `email` matches too, as
+/// does `email` under any other entity, erring on the side of protecting it.
+#[derive(Debug, Clone, Default)]
+pub struct SensitiveAttributes {
+    paths: BTreeSet<String>,
+    names: BTreeSet<String>,
+}
+
+impl FromIterator<String> for SensitiveAttributes {
+    fn from_iter<I: IntoIterator<Item = String>>(paths: I) -> Self {
+        let mut sensitive = Self::default();
+        for path in paths {
+            sensitive.names.insert(attribute_name(&path).to_string());
+            sensitive.paths.insert(path);
+        }
+        sensitive
+    }
+}
+
+impl SensitiveAttributes {
+    pub fn contains(&self, attribute: &str) -> bool {
+        self.paths.contains(attribute) || self.names.contains(attribute_name(attribute))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Replace the value of every trace node that reads a sensitive attribute,
+    /// directly or through its inputs. Returns whether any was replaced.
+    pub fn redact_trace(&self, trace: &mut TraceNode) -> bool {
+        let mut redacted = self.contains(&trace.expression);
+        for input in &mut trace.inputs {
+            redacted |= self.redact_trace(input);
+        }
+        if redacted && trace.value.is_some() {
+            trace.value = Some(Value::String(REDACTED.to_string()));
+        }
+        redacted
+    }
+}
+
+fn attribute_name(path: &str) -> &str {
+    path.rsplit_once('.').map_or(path, |(_, name)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher(current: &str) -> FieldCipher {
+        let keys = [("v1".to_string(), "11".repeat(32)), ("v2".to_string(), "22".repeat(32))];
+        FieldCipher::new(current, &keys).unwrap()
+    }
+
+    #[test]
+    fn test_envelopes_round_trip_and_are_bound_to_their_attribute() {
+        let v1 = cipher("v1");
+        let value = json!({"street": "1 Main St", "zip": 12345});
+        let sealed = v1.encrypt("Client.address", &value).unwrap();
+        assert!(is_encrypted(&sealed));
+        assert_eq!(key_id(&sealed), Some("v1"));
+        assert!(!sealed.to_string().contains("Main"));
+        assert_eq!(v1.decrypt("Client.address", &sealed).unwrap(), value);
+        assert!(v1.decrypt("Client.name", &sealed).is_err());
+
+        // Plain values, as stored before encryption was switched on, pass through
+        assert_eq!(v1.decrypt("Client.address", &json!("plain")).unwrap(), json!("plain"));
+        assert!(FieldCipher::new("v3", &[("v1".to_string(), "11".repeat(32))]).is_err());
+        assert!(FieldCipher::new("v1", &[("v1".to_string(), "11".repeat(16))]).is_err());
+    }
+
+    #[test]
+    fn test_values_under_an_old_key_need_rotation() {
+        let sealed = cipher("v1").encrypt("Client.email", &json!("a@b.com")).unwrap();
+        let v2 = cipher("v2");
+        assert!(v2.needs_rotation(&sealed));
+        assert_eq!(v2.decrypt("Client.email", &sealed).unwrap(), json!("a@b.com"));
+
+        let rotated = v2.encrypt("Client.email", &v2.decrypt("Client.email", &sealed).unwrap()).unwrap();
+        assert!(!v2.needs_rotation(&rotated));
+        assert!(!v2.needs_rotation(&json!("a@b.com")));
+        assert!(FieldCipher::new("v2", &[("v2".to_string(), "22".repeat(32))]).unwrap().decrypt("Client.email", &sealed).is_err());
+    }
+
+    #[test]
+    fn test_traces_are_redacted_above_sensitive_reads() {
+        let sensitive: SensitiveAttributes = ["Client.email".to_string()].into_iter().collect();
+        assert!(sensitive.contains("email") && sensitive.contains("Client.email"));
+        assert!(!sensitive.contains("Client.name"));
+
+        let node = |expression: &str, value: Value, inputs: Vec<TraceNode>| TraceNode {
+            expression: expression.to_string(),
+            value: Some(value),
+            error: None,
+            duration_us: 0,
+            inputs,
+        };
+        let mut trace = node(
+            "CONCAT(name, \" <\", email, \">\")",
+            Value::String("Ann <ann@example.com>".into()),
+            vec![
+                node("name", Value::String("Ann".into()), vec![]),
+                node("email", Value::String("ann@example.com".into()), vec![]),
+            ],
+        );
+        assert!(sensitive.redact_trace(&mut trace));
+        assert_eq!(trace.value, Some(Value::String(REDACTED.into())));
+        assert_eq!(trace.inputs[0].value, Some(Value::String("Ann".into())));
+        assert_eq!(trace.inputs[1].value, Some(Value::String(REDACTED.into())));
+    }
+}
@@ -0,0 +1,645 @@
+//! Canonical pretty-printer for the rules DSL
+//!
+//! Rules are parsed and re-emitted with single spaces around operators, only
+//! the parentheses operator precedence requires, and `==` for equality (a
+//! bare `=` at the start of a rule is an assignment). Function calls that
+//! don't fit in `MAX_WIDTH` columns get one argument per line, and long CASE
+//! expressions one branch per line.
+//!
+//! Comments survive formatting: leading comments stay above their rule,
+//! trailing comments stay at the end of its line, and comments from inside
+//! an expression move above it. Template strings come back as the `&`
+//! concatenation the parser lowers them to.
+
+use crate::models::{BinaryOperator, CommentPlacement, CommentStyle, Expression, RuleComment, UnaryOperator, Value};
+use crate::parser::{parse_comments, parse_rule_with_comments};
+use anyhow::{bail, Result};
+
+pub const MAX_WIDTH: usize = 80;
+const INDENT: usize = 4;
+
+// Binding strength, mirroring the parser's descent from parse_expression
+// down to parse_primary. Assignments, IF/WHEN, lambdas and workflow verbs
+// swallow everything to their right, so they are parenthesized when nested.
+const LOOSE: u8 = 0;
+const OR: u8 = 1;
+const AND: u8 = 2;
+const COMPARISON: u8 = 3;
+const CONCAT: u8 = 4;
+const ADDITIVE: u8 = 5;
+const MULTIPLICATIVE: u8 = 6;
+const POWER: u8 = 7;
+const UNARY: u8 = 8;
+const PRIMARY: u8 = 9;
+
+/// Format a single rule; errors if the source doesn't parse or holds more than one rule
+pub fn format_rule(source: &str) -> Result<String> {
+    let (formatted, rule_count) = format_rules(source)?;
+    if rule_count != 1 {
+        bail!("Expected a single rule, found {}", rule_count);
+    }
+    Ok(formatted.trim_end().to_string())
+}
+
+/// Format a whole rules document, keeping comments and single blank lines between rules
+pub fn format_document(source: &str) -> Result<String> {
+    Ok(format_rules(source)?.0)
+}
+
+/// Format a single rule after passing its expression through `rewrite`, keeping its comments
+pub fn format_rewritten_rule(source: &str, rewrite: impl Fn(Expression) -> Expression) -> Result<String> {
+    let (formatted, rule_count) = format_rules_with(source, &rewrite)?;
+    if rule_count != 1 {
+        bail!("Expected a single rule, found {}", rule_count);
+    }
+    Ok(formatted.trim_end().to_string())
+}
+
+/// Pretty-print an expression on its own, starting at column zero
+pub fn format_expression(expr: &Expression) -> String {
+    Printer { width: MAX_WIDTH }.write(expr, LOOSE, 0, 0)
+}
+
+fn format_rules(source: &str) -> Result<(String, usize)> {
+    format_rules_with(source, &|expr| expr)
+}
+
+fn format_rules_with(source: &str, rewrite: &dyn Fn(Expression) -> Expression) -> Result<(String, usize)> {
+    let mut output = Output::default();
+    let mut rule_count = 0;
+    let mut rest = source;
+
+    loop {
+        let base = source.len() - rest.len();
+        if let Ok(("", comments)) = parse_comments(rest) {
+            for comment in &comments {
+                output.line(source, base + comment.offset, &render_comment(comment), comment_end(source, base, comment));
+            }
+            break;
+        }
+
+        let (remaining, rule) = match parse_rule_with_comments(rest) {
+            Ok(parsed) => parsed,
+            Err(_) => bail!(
+                "Cannot parse rule at line {}: '{}'",
+                source[..base].lines().count() + 1,
+                rest.trim_start().lines().next().unwrap_or("")
+            ),
+        };
+        rule_count += 1;
+
+        let mut leading = Vec::new();
+        let mut inline = Vec::new();
+        let mut trailing = Vec::new();
+        for comment in &rule.comments {
+            match comment.placement {
+                CommentPlacement::Leading => leading.push(comment),
+                CommentPlacement::Inline => inline.push(comment),
+                CommentPlacement::Trailing => trailing.push(comment),
+            }
+        }
+
+        for comment in &leading {
+            output.line(source, base + comment.offset, &render_comment(comment), comment_end(source, base, comment));
+        }
+        for comment in &inline {
+            output.push_line(&render_comment(comment));
+        }
+
+        let after_leading = leading.last().map_or(base, |comment| comment_end(source, base, comment));
+        let expr_start = source.len() - source[after_leading..].trim_start().len();
+        let consumed_end = base + rest.len() - remaining.len();
+        let body_end = trailing.first().map_or(consumed_end, |comment| base + comment.offset);
+        let expr_end = expr_start + source[expr_start..body_end].trim_end().len();
+
+        let mut text = format_expression(&rewrite(rule.expression));
+        let mut end = expr_end;
+        let mut trailing = trailing.into_iter().peekable();
+        if let Some(comment) = trailing.next_if(|c| !source[expr_end..base + c.offset].contains('\n')) {
+            text.push(' ');
+            text.push_str(&render_comment(comment));
+            end = comment_end(source, base, comment);
+        }
+        output.line(source, expr_start, &text, end);
+
+        for comment in trailing {
+            output.line(source, base + comment.offset, &render_comment(comment), comment_end(source, base, comment));
+        }
+
+        rest = remaining;
+        if rest.is_empty() {
+            break;
+        }
+    }
+
+    Ok((output.text, rule_count))
+}
+
+fn comment_end(source: &str, base: usize, comment: &RuleComment) -> usize {
+    let start = base + comment.offset;
+    let rest = &source[start..];
+    let len = match comment.style {
+        CommentStyle::Block => rest.find("*/").map_or(rest.len(), |i| i + 2),
+        CommentStyle::Hash | CommentStyle::DoubleSlash => rest.find('\n').unwrap_or(rest.len()),
+    };
+    start + len
+}
+
+fn render_comment(comment: &RuleComment) -> String {
+    match comment.style {
+        CommentStyle::Hash => format!("# {}", comment.text).trim_end().to_string(),
+        CommentStyle::DoubleSlash => format!("// {}", comment.text).trim_end().to_string(),
+        CommentStyle::Block => format!("/* {} */", comment.text),
+    }
+}
+
+/// Formatted lines plus the source position they came from, so one blank
+/// line between items in the source becomes one blank line in the output
+#[derive(Default)]
+struct Output {
+    text: String,
+    source_end: usize,
+}
+
+impl Output {
+    fn line(&mut self, source: &str, start: usize, line: &str, end: usize) {
+        let gap = source.get(self.source_end..start).unwrap_or("");
+        if !self.text.is_empty() && gap.matches('\n').count() >= 2 {
+            self.text.push('\n');
+        }
+        self.push_line(line);
+        self.source_end = end;
+    }
+
+    fn push_line(&mut self, line: &str) {
+        self.text.push_str(line);
+        self.text.push('\n');
+    }
+}
+
+struct Printer {
+    width: usize,
+}
+
+impl Printer {
+    /// Render `expr` starting at `column` on a line indented by `indent`,
+    /// parenthesized if it binds looser than `min`
+    fn write(&self, expr: &Expression, min: u8, column: usize, indent: usize) -> String {
+        if precedence(expr) < min {
+            format!("({})", self.write_bare(expr, column + 1, indent))
+        } else {
+            self.write_bare(expr, column, indent)
+        }
+    }
+
+    /// IF conditions and THEN/WHEN branches: a nested IF there could take
+    /// the enclosing ELSE, so it always gets parentheses
+    fn write_branch(&self, expr: &Expression, column: usize, indent: usize) -> String {
+        match expr {
+            Expression::Conditional { .. } => format!("({})", self.write_bare(expr, column + 1, indent)),
+            _ => self.write(expr, LOOSE, column, indent),
+        }
+    }
+
+    fn write_bare(&self, expr: &Expression, column: usize, indent: usize) -> String {
+        match expr {
+            Expression::Literal(value) => literal(value),
+            Expression::Variable(name) | Expression::Identifier(name) => name.clone(),
+            Expression::BinaryOp { left, op, right } => {
+                // x BETWEEN lo AND hi rather than the range it parses to
+                if let (BinaryOperator::Between, Expression::Range { start, end, inclusive }) = (op, right.as_ref()) {
+                    let mut text = self.write(left, CONCAT, column, indent);
+                    text.push_str(if *inclusive { " BETWEEN " } else { " BETWEEN_EXCLUSIVE " });
+                    text.push_str(&self.write(start, CONCAT, end_column(column, &text), indent));
+                    text.push_str(" AND ");
+                    text.push_str(&self.write(end, CONCAT, end_column(column, &text), indent));
+                    return text;
+                }
+                let level = binary_precedence(*op);
+                let (left_min, right_min) = match level {
+                    COMPARISON => (CONCAT, CONCAT),
+                    POWER => (POWER, UNARY),
+                    _ => (level, level + 1),
+                };
+                let mut text = self.write(left, left_min, column, indent);
+                text.push(' ');
+                text.push_str(operator(*op));
+                text.push(' ');
+                let right = self.write(right, right_min, end_column(column, &text), indent);
+                text + right.as_str()
+            }
+            Expression::UnaryOp { op, operand } => {
+                let prefix = match op {
+                    UnaryOperator::Not => "NOT ",
+                    UnaryOperator::Minus => "-",
+                    UnaryOperator::Plus => "+",
+                };
+                let operand = self.write(operand, UNARY, column + prefix.len(), indent);
+                format!("{}{}", prefix, operand)
+            }
+            Expression::FunctionCall { name, args } => self.write_call(name.clone(), args, column, indent),
+            Expression::Conditional { condition, then_expr, else_expr } => {
+                let mut text = String::from("IF ");
+                text.push_str(&self.write_branch(condition, end_column(column, &text), indent));
+                text.push_str(" THEN ");
+                text.push_str(&self.write_branch(then_expr, end_column(column, &text), indent));
+                if let Some(else_expr) = else_expr {
+                    text.push_str(" ELSE ");
+                    text.push_str(&self.write(else_expr, LOOSE, end_column(column, &text), indent));
+                }
+                text
+            }
+            Expression::Case { branches, else_expr } => self.write_case(branches, else_expr.as_deref(), column, indent),
+            Expression::Assignment { target, value } => {
+                let prefix = format!("{} = ", target);
+                let value = self.write(value, LOOSE, column + prefix.len(), indent);
+                prefix + value.as_str()
+            }
+            Expression::List(items) => {
+                let mut text = String::from("[");
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        text.push_str(", ");
+                    }
+                    text.push_str(&self.write(item, LOOSE, end_column(column, &text), indent));
+                }
+                text.push(']');
+                text
+            }
+            Expression::Comprehension { element, var, source, condition } => {
+                let mut text = String::from("[");
+                text.push_str(&self.write(element, LOOSE, end_column(column, &text), indent));
+                text.push_str(&format!(" FOR {} IN ", var));
+                text.push_str(&self.write(source, LOOSE, end_column(column, &text), indent));
+                if let Some(condition) = condition {
+                    text.push_str(" IF ");
+                    text.push_str(&self.write(condition, LOOSE, end_column(column, &text), indent));
+                }
+                text.push(']');
+                text
+            }
+            Expression::Range { start, end, inclusive } => {
+                let mut text = self.write(start, CONCAT, column, indent);
+                text.push_str(if *inclusive { "..=" } else { ".." });
+                text.push_str(&self.write(end, CONCAT, end_column(column, &text), indent));
+                text
+            }
+            Expression::Cast { expr, data_type } => {
+                let inner = self.write(expr, LOOSE, column + 5, indent);
+                format!("CAST({} AS {})", inner, data_type)
+            }
+            Expression::Lambda { param, body } => {
+                let prefix = format!("{} -> ", param);
+                let body = self.write(body, LOOSE, column + prefix.len(), indent);
+                prefix + body.as_str()
+            }
+            Expression::ConfigureSystem { capability_name, arguments } => {
+                self.write_verb(format!("CONFIGURE_SYSTEM {}", quote(capability_name)), arguments, column, indent)
+            }
+            Expression::Activate { target, arguments } => {
+                let head = match target {
+                    Some(target) => format!("ACTIVATE {}", quote(target)),
+                    None => "ACTIVATE".to_string(),
+                };
+                self.write_verb(head, arguments, column, indent)
+            }
+            Expression::RunHealthCheck { check_type, arguments } => {
+                self.write_verb(format!("RUN_HEALTH_CHECK {}", quote(check_type)), arguments, column, indent)
+            }
+            Expression::SetStatus { status, target } => match target {
+                Some(target) => format!("SET_STATUS {} {}", quote(status), quote(target)),
+                None => format!("SET_STATUS {}", quote(status)),
+            },
+            Expression::Workflow { name, steps } => {
+                let mut text = format!("WORKFLOW {}", quote(name));
+                let step_indent = indent + INDENT;
+                for step in steps {
+                    text.push('\n');
+                    text.push_str(&" ".repeat(step_indent));
+                    text.push_str(&self.write(step, LOOSE, step_indent, step_indent));
+                }
+                text
+            }
+        }
+    }
+
+    fn write_verb(&self, head: String, arguments: &[Expression], column: usize, indent: usize) -> String {
+        if arguments.is_empty() {
+            head
+        } else {
+            self.write_call(head + " ", arguments, column, indent)
+        }
+    }
+
+    /// `NAME(a, b)` when it fits, otherwise one argument per line
+    fn write_call(&self, head: String, args: &[Expression], column: usize, indent: usize) -> String {
+        let mut flat = format!("{}(", head);
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                flat.push_str(", ");
+            }
+            flat.push_str(&self.write(arg, LOOSE, end_column(column, &flat), indent));
+        }
+        flat.push(')');
+        if args.is_empty() || fits(column, &flat, self.width) {
+            return flat;
+        }
+
+        let arg_indent = indent + INDENT;
+        let args: Vec<String> = args
+            .iter()
+            .map(|arg| format!("{}{}", " ".repeat(arg_indent), self.write(arg, LOOSE, arg_indent, arg_indent)))
+            .collect();
+        format!("{}(\n{}\n{})", head, args.join(",\n"), " ".repeat(indent))
+    }
+
+    /// `CASE WHEN ... END` when it fits, otherwise one WHEN per line
+    fn write_case(&self, branches: &[(Expression, Expression)], else_expr: Option<&Expression>, column: usize, indent: usize) -> String {
+        let mut flat = String::from("CASE");
+        for (condition, result) in branches {
+            flat.push_str(" WHEN ");
+            flat.push_str(&self.write_branch(condition, end_column(column, &flat), indent));
+            flat.push_str(" THEN ");
+            flat.push_str(&self.write_branch(result, end_column(column, &flat), indent));
+        }
+        if let Some(else_expr) = else_expr {
+            flat.push_str(" ELSE ");
+            flat.push_str(&self.write(else_expr, LOOSE, end_column(column, &flat), indent));
+        }
+        flat.push_str(" END");
+        if fits(column, &flat, self.width) {
+            return flat;
+        }
+
+        let branch_indent = indent + INDENT;
+        let pad = " ".repeat(branch_indent);
+        let mut text = String::from("CASE");
+        for (condition, result) in branches {
+            let mut line = format!("{}WHEN ", pad);
+            line.push_str(&self.write_branch(condition, line.len(), branch_indent));
+            line.push_str(" THEN ");
+            line.push_str(&self.write_branch(result, end_column(0, &line), branch_indent));
+            text.push('\n');
+            text.push_str(&line);
+        }
+        if let Some(else_expr) = else_expr {
+            let line = format!("{}ELSE ", pad);
+            let value = self.write(else_expr, LOOSE, line.len(), branch_indent);
+            text.push('\n');
+            text.push_str(&line);
+            text.push_str(&value);
+        }
+        text.push('\n');
+        text.push_str(&" ".repeat(indent));
+        text.push_str("END");
+        text
+    }
+}
+
+fn fits(column: usize, text: &str, width: usize) -> bool {
+    !text.contains('\n') && column + text.chars().count() <= width
+}
+
+/// Column after `text` when it is written starting at `column`
+fn end_column(column: usize, text: &str) -> usize {
+    match text.rfind('\n') {
+        Some(newline) => text[newline + 1..].chars().count(),
+        None => column + text.chars().count(),
+    }
+}
+
+fn precedence(expr: &Expression) -> u8 {
+    match expr {
+        Expression::BinaryOp { op, .. } => binary_precedence(*op),
+        Expression::UnaryOp { .. } => UNARY,
+        // A negative literal is printed with a leading minus
+        Expression::Literal(Value::Integer(i)) if *i < 0 => UNARY,
+        Expression::Literal(Value::Number(n) | Value::Float(n)) if n.is_sign_negative() => UNARY,
+        Expression::Assignment { .. }
+        | Expression::Conditional { .. }
+        | Expression::Lambda { .. }
+        | Expression::ConfigureSystem { .. }
+        | Expression::Activate { .. }
+        | Expression::RunHealthCheck { .. }
+        | Expression::SetStatus { .. }
+        | Expression::Workflow { .. } => LOOSE,
+        _ => PRIMARY,
+    }
+}
+
+fn binary_precedence(op: BinaryOperator) -> u8 {
+    match op {
+        BinaryOperator::Or => OR,
+        BinaryOperator::And => AND,
+        BinaryOperator::Equals
+        | BinaryOperator::NotEquals
+        | BinaryOperator::LessThan
+        | BinaryOperator::LessThanOrEqual
+        | BinaryOperator::GreaterThan
+        | BinaryOperator::GreaterThanOrEqual
+        | BinaryOperator::Matches
+        | BinaryOperator::NotMatches
+        | BinaryOperator::Contains
+        | BinaryOperator::StartsWith
+        | BinaryOperator::EndsWith
+        | BinaryOperator::In
+        | BinaryOperator::NotIn
+        | BinaryOperator::Between => COMPARISON,
+        BinaryOperator::Concat => CONCAT,
+        BinaryOperator::Add | BinaryOperator::Subtract => ADDITIVE,
+        BinaryOperator::Multiply | BinaryOperator::Divide | BinaryOperator::Modulo => MULTIPLICATIVE,
+        BinaryOperator::Power => POWER,
+    }
+}
+
+pub(crate) fn operator(op: BinaryOperator) -> &'static str {
+    match op {
+        BinaryOperator::Add => "+",
+        BinaryOperator::Subtract => "-",
+        BinaryOperator::Multiply => "*",
+        BinaryOperator::Divide => "/",
+        BinaryOperator::Power => "**",
+        BinaryOperator::Modulo => "%",
+        BinaryOperator::Equals => "==",
+        BinaryOperator::NotEquals => "!=",
+        BinaryOperator::LessThan => "<",
+        BinaryOperator::LessThanOrEqual => "<=",
+        BinaryOperator::GreaterThan => ">",
+        BinaryOperator::GreaterThanOrEqual => ">=",
+        BinaryOperator::And => "AND",
+        BinaryOperator::Or => "OR",
+        BinaryOperator::Matches => "MATCHES",
+        BinaryOperator::NotMatches => "NOT_MATCHES",
+        BinaryOperator::Concat => "&",
+        BinaryOperator::Contains => "CONTAINS",
+        BinaryOperator::StartsWith => "STARTS_WITH",
+        BinaryOperator::EndsWith => "ENDS_WITH",
+        BinaryOperator::In => "IN",
+        BinaryOperator::NotIn => "NOT IN",
+        BinaryOperator::Between => "BETWEEN",
+    }
+}
+
+pub(crate) fn literal(value: &Value) -> String {
+    match value {
+        Value::String(s) => quote(s),
+        Value::Integer(i) => i.to_string(),
+        // Keep the decimal point so the value parses back as a float
+        Value::Number(n) | Value::Float(n) => {
+            let text = n.to_string();
+            if text.contains('.') || !n.is_finite() {
+                text
+            } else {
+                format!("{}.0", text)
+            }
+        }
+        Value::Boolean(b) => b.to_string(),
+        Value::Null => "null".to_string(),
+        Value::Regex(pattern) if pattern.contains('/') => format!("r\"{}\"", pattern),
+        Value::Regex(pattern) => format!("/{}/", pattern),
+        Value::List(values) => format!("[{}]", values.iter().map(literal).collect::<Vec<_>>().join(", ")),
+        Value::Percent(p) => format!("{}%", p),
+        Value::Money { amount, currency } => format!("{} {}", amount, currency),
+        // Records only come from facts and have no DSL syntax of their own
+        Value::Object(_) => value.to_json().to_string(),
+    }
+}
+
+pub(crate) fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_rule;
+
+    fn assert_idempotent(source: &str) -> String {
+        let formatted = format_document(source).unwrap();
+        assert_eq!(format_document(&formatted).unwrap(), formatted, "formatting is not idempotent");
+        formatted
+    }
+
+    #[test]
+    fn test_spacing_is_normalized() {
+        assert_eq!(format_rule("price*quantity+  tax").unwrap(), "price * quantity + tax");
+        assert_eq!(format_rule("IF age>=18 THEN 'adult' ELSE \"minor\"").unwrap(), r#"IF age >= 18 THEN "adult" ELSE "minor""#);
+        assert_eq!(format_rule("total=ROUND( amount ,2 )").unwrap(), "total = ROUND(amount, 2)");
+        assert_eq!(format_rule("IF status==\"active\"AND NOT closed THEN 1 ELSE 0").unwrap(), r#"IF status == "active" AND NOT closed THEN 1 ELSE 0"#);
+    }
+
+    #[test]
+    fn test_only_required_parentheses_are_kept() {
+        assert_eq!(format_rule("(a + b) * c").unwrap(), "(a + b) * c");
+        assert_eq!(format_rule("a + (b * c)").unwrap(), "a + b * c");
+        assert_eq!(format_rule("a - (b - c)").unwrap(), "a - (b - c)");
+        assert_eq!(format_rule("(a OR b) AND c").unwrap(), "(a OR b) AND c");
+        assert_eq!(format_rule("(x > 1) AND (y < 2)").unwrap(), "x > 1 AND y < 2");
+        assert_eq!(format_rule("-(a + b)").unwrap(), "-(a + b)");
+        assert_eq!(format_rule("x = (a == b)").unwrap(), "x = a == b");
+        assert_eq!(format_rule("1.0 + 2").unwrap(), "1.0 + 2");
+    }
+
+    #[test]
+    fn test_formatting_preserves_the_ast() {
+        let rules = [
+            "a - (b - c) / d ** 2 % 3",
+            "(status == 'x' OR flag) AND NOT (a IN [1, 2, 3])",
+            "name & \" (\" & code & \")\"",
+            "IF (IF a THEN b ELSE c) THEN 1 ELSE IF d THEN 2 ELSE 3",
+            "FILTER(items, x -> x > 10)",
+            "SUM([t.amount * rate FOR t IN transactions IF t.currency == \"USD\" AND NOT t.reversed])",
+            "CASE WHEN balance >= 100000 THEN \"gold\" WHEN balance >= 10000 THEN \"silver\" ELSE \"bronze\" END",
+            "`Rate: ${rate * 100}%`",
+            "email MATCHES /^[a-z]+@[a-z]+\\.com$/",
+            "amount BETWEEN 1000 AND limit * 2 AND tier NOT IN 1..3",
+            "(a BETWEEN_EXCLUSIVE 0 AND 1) == flag",
+        ];
+        for source in rules {
+            let formatted = format_rule(source).unwrap();
+            assert_eq!(parse_rule(&formatted).unwrap().1, parse_rule(source).unwrap().1, "{} -> {}", source, formatted);
+        }
+    }
+
+    #[test]
+    fn test_long_concat_is_wrapped() {
+        let source = r#"greeting = CONCAT("Dear ", title, " ", first_name, " ", last_name, ", your account ", account_number, " is ready")"#;
+        let formatted = format_rule(source).unwrap();
+        assert_eq!(
+            formatted,
+            r#"greeting = CONCAT(
+    "Dear ",
+    title,
+    " ",
+    first_name,
+    " ",
+    last_name,
+    ", your account ",
+    account_number,
+    " is ready"
+)"#
+        );
+        assert_eq!(format_rule(&formatted).unwrap(), formatted);
+        assert_eq!(parse_rule(&formatted).unwrap().1, parse_rule(source).unwrap().1);
+    }
+
+    #[test]
+    fn test_long_case_is_one_branch_per_line() {
+        let formatted = format_rule(
+            r#"tier = CASE WHEN balance >= 100000 THEN "gold" WHEN balance >= 10000 THEN "silver" ELSE "bronze" END"#,
+        )
+        .unwrap();
+        assert_eq!(
+            formatted,
+            r#"tier = CASE
+    WHEN balance >= 100000 THEN "gold"
+    WHEN balance >= 10000 THEN "silver"
+    ELSE "bronze"
+END"#
+        );
+        assert_eq!(format_rule(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_comments_are_kept() {
+        let source = "# Risk score\n\n  score=base*2 // doubled\n# next rule\nlabel = UPPER(name)";
+        let formatted = assert_idempotent(source);
+        assert_eq!(formatted, "# Risk score\n\nscore = base * 2 // doubled\n# next rule\nlabel = UPPER(name)\n");
+
+        let formatted = format_rule("total = a + /* fees */ b").unwrap();
+        assert_eq!(formatted, "/* fees */\ntotal = a + b");
+    }
+
+    #[test]
+    fn test_blank_lines_between_rules_are_collapsed() {
+        let formatted = assert_idempotent("a = 1\n\n\n\nb = 2\nc = 3\n# end\n");
+        assert_eq!(formatted, "a = 1\n\nb = 2\nc = 3\n# end\n");
+    }
+
+    #[test]
+    fn test_unit_literals_round_trip() {
+        let formatted = format_rule("fee = notional*0.25%+1_500.5 GBP").unwrap();
+        assert_eq!(formatted, "fee = notional * 0.25% + 1500.5 GBP");
+        assert_eq!(parse_rule(&formatted).unwrap().1, parse_rule("fee = notional*0.25%+1_500.5 GBP").unwrap().1);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(format_rule("a = 1 b = 2").is_err());
+        assert!(format_rule("price * (").is_err());
+        assert!(format_document("a = 1\n)").unwrap_err().to_string().contains("line 2"));
+        assert_eq!(format_document("# just a header\n").unwrap(), "# just a header\n");
+    }
+}
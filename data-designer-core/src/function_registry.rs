@@ -0,0 +1,150 @@
+//! Functions registered by host applications
+//!
+//! The IDE, the language server and the onboarding orchestrator each have
+//! domain functions of their own (SCREEN_SANCTIONS, say) that rules should
+//! be able to call without the evaluator knowing about them. A registry is
+//! shared by every function library created from it, so a function
+//! registered once is callable from all of them, including libraries
+//! created before it was registered. Built-in functions can't be replaced.
+
+use crate::models::Value;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+/// Functions the evaluator implements itself, including the higher-order ones
+pub const BUILTIN_FUNCTIONS: &[&str] = &[
+    "CONCAT", "SUBSTRING", "UPPER", "LOWER", "LENGTH", "TRIM", "LOOKUP", "ABS", "ROUND", "FLOOR", "CEIL", "MIN",
+    "MAX", "SUM", "AVG", "COUNT", "HAS", "IS_NULL", "IS_EMPTY", "TO_STRING", "TO_NUMBER", "TO_BOOLEAN", "TO_PCT",
+    "TO_BASIS_POINTS", "FORMAT_NUMBER", "FORMAT_DATE", "FIRST", "LAST", "GET", "EXTRACT", "EXTRACT_ALL",
+    "MATCH_COUNT", "MAP", "FILTER", "ANY", "ALL",
+];
+
+pub type HostFunction = dyn Fn(&[Value]) -> Result<Value> + Send + Sync;
+
+/// What completion and the grammar metadata show for a registered function
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegisteredFunctionInfo {
+    pub name: String,
+    pub arity: usize,
+    /// e.g. `SCREEN_SANCTIONS(arg1, arg2)`
+    pub signature: String,
+}
+
+#[derive(Clone)]
+struct RegisteredFunction {
+    arity: usize,
+    function: Arc<HostFunction>,
+}
+
+/// Host functions by upper-cased name; clones share the same functions
+#[derive(Clone, Default)]
+pub struct FunctionRegistry {
+    functions: Arc<RwLock<BTreeMap<String, RegisteredFunction>>>,
+}
+
+impl std::fmt::Debug for FunctionRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.functions.read().unwrap().keys()).finish()
+    }
+}
+
+impl FunctionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `function` under `name`, replacing an earlier registration
+    /// of the same name. Calls with a different number of arguments fail
+    /// before reaching it.
+    pub fn register(
+        &self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[Value]) -> Result<Value> + Send + Sync + 'static,
+    ) -> Result<()> {
+        let name = name.to_uppercase();
+        if name.is_empty()
+            || name.starts_with(|c: char| c.is_ascii_digit())
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            bail!("'{}' is not a valid function name", name);
+        }
+        if BUILTIN_FUNCTIONS.contains(&name.as_str()) {
+            bail!("{} is a built-in function and can't be replaced", name);
+        }
+        self.functions
+            .write()
+            .unwrap()
+            .insert(name, RegisteredFunction { arity, function: Arc::new(function) });
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.read().unwrap().contains_key(&name.to_uppercase())
+    }
+
+    /// Calls a registered function; None if nothing is registered under `name`
+    pub fn call(&self, name: &str, args: &[Value]) -> Option<Result<Value>> {
+        let name = name.to_uppercase();
+        // Release the lock before calling, so a host function may use the registry itself
+        let registered = self.functions.read().unwrap().get(&name).cloned()?;
+        if args.len() != registered.arity {
+            return Some(Err(anyhow::anyhow!(
+                "{} requires exactly {} argument{}",
+                name,
+                registered.arity,
+                if registered.arity == 1 { "" } else { "s" }
+            )));
+        }
+        Some((registered.function)(args))
+    }
+
+    /// Every registered function, by name
+    pub fn functions(&self) -> Vec<RegisteredFunctionInfo> {
+        self.functions
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, registered)| RegisteredFunctionInfo {
+                name: name.clone(),
+                arity: registered.arity,
+                signature: format!(
+                    "{}({})",
+                    name,
+                    (1..=registered.arity).map(|i| format!("arg{}", i)).collect::<Vec<_>>().join(", ")
+                ),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::{evaluate_with_functions, Facts, FunctionLibrary};
+    use crate::parser::parse_expression;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_registered_functions_are_shared_and_checked() {
+        let registry = FunctionRegistry::new();
+        let functions = FunctionLibrary::with_registry(registry.clone());
+        registry
+            .register("screen_sanctions", 1, |args| {
+                Ok(Value::Boolean(matches!(&args[0], Value::String(name) if name.contains("ACME"))))
+            })
+            .unwrap();
+
+        let facts: Facts = HashMap::from([("name".to_string(), Value::String("ACME Ltd".to_string()))]);
+        let run = |input: &str| evaluate_with_functions(&parse_expression(input).unwrap().1, &facts, &functions);
+        assert_eq!(run("SCREEN_SANCTIONS(name) AND LENGTH(name) > 3").unwrap(), Value::Boolean(true));
+        assert_eq!(run("screen_sanctions(name, 2)").unwrap_err().to_string(), "SCREEN_SANCTIONS requires exactly 1 argument");
+        assert!(run("SCREEN_PEPS(name)").is_err());
+
+        assert!(registry.register("UPPER", 1, |args| Ok(args[0].clone())).is_err());
+        assert!(registry.register("bad-name", 0, |_| Ok(Value::Null)).is_err());
+        assert_eq!(registry.functions()[0].signature, "SCREEN_SANCTIONS(arg1)");
+    }
+}
@@ -1,8 +1,41 @@
 pub mod models;
 pub mod engine;
 pub mod parser;
+pub mod error_codes;
 pub mod evaluator;
+pub mod function_registry;
+pub mod regex_cache;
+pub mod optimizer;
 pub mod transpiler;
+pub mod rule_graph;
+pub mod type_checker;
+pub mod formatter;
+pub mod rule_tests;
+pub mod rule_coverage;
+pub mod rule_conflicts;
+pub mod context_diff;
+pub mod rule_categories;
+pub mod rule_naming;
+pub mod editor_access;
+pub mod rule_cost;
+pub mod provenance;
+pub mod bulk_edit;
+pub mod retention;
+pub mod batch_writer;
+pub mod rule_rewrite;
+pub mod lookup_extraction;
+pub mod rule_sexpr;
+pub mod rule_history;
+pub mod rule_templates;
+pub mod rule_variants;
+pub mod rule_integrity;
+pub mod rule_repair;
+pub mod rule_statistics;
+pub mod attribute_usage;
+pub mod locale;
+pub mod read_models;
+#[cfg(feature = "native")]
+pub mod rhai_runtime;
 
 // Resource sheet orchestration system
 pub mod resource_sheets;
@@ -10,43 +43,80 @@ pub mod orchestration_dsl;
 pub mod kyc_dsl;
 
 // Runtime execution system
+#[cfg(feature = "postgres")]
 pub mod runtime_orchestrator;
 
 // Configuration
 pub mod config;
+pub mod capabilities;
+#[cfg(feature = "native")]
+pub mod telemetry;
+#[cfg(feature = "native")]
+pub mod trace_export;
+#[cfg(feature = "native")]
+pub mod field_encryption;
+
+// Storage abstraction; the Postgres implementation needs the `postgres` feature
+pub mod storage;
 
 // Database layer
+#[cfg(feature = "postgres")]
 pub mod db;
+#[cfg(feature = "postgres")]
 pub mod embeddings;
+#[cfg(feature = "postgres")]
 pub mod schema_visualizer;
 
 // Capability execution engine
+#[cfg(feature = "postgres")]
 pub mod capability_engine;
+#[cfg(feature = "native")]
 pub mod capability_execution_engine;
 
 // Onboarding orchestration engine
+#[cfg(feature = "postgres")]
 pub mod onboarding_orchestrator;
 
 // CBU DSL for CRUD operations
+#[cfg(feature = "postgres")]
 pub mod cbu_dsl;
 
 // LISP-based CBU DSL for list processing
 pub mod lisp_cbu_dsl;
+pub mod cbu_diff;
+pub mod cbu_constraints;
 
 // Onboarding Request DSL for CRUD operations with Deal Record integration
+#[cfg(feature = "postgres")]
 pub mod onboarding_request_dsl;
 
 // Deal Record DSL - Master orchestrator for comprehensive business relationship management
+#[cfg(feature = "postgres")]
 pub mod deal_record_dsl;
 
 // Opportunity DSL for investment opportunity management
+#[cfg(feature = "postgres")]
 pub mod opportunity_dsl;
 
 // Shared DSL utilities
 pub mod dsl_utils;
+// Bracket, keyword and comment structure of DSL source, for editor folding and selection
+pub mod source_structure;
+// Semantic highlighting of rule documents from the parser's spans
+pub mod semantic_tokens;
+
+// Git-backed rule export/import for review workflows
+pub mod rule_repository;
+pub mod rule_bundle;
+// Derived attribute formulas exported to XLSX for sign-off, and reviewed edits read back
+#[cfg(feature = "native")]
+pub mod attribute_workbook;
+// Shared rule packs from a remote registry
+#[cfg(feature = "native")]
+pub mod rule_registry;
 
 // CBU DSL integration tests for API validation
-#[cfg(test)]
+#[cfg(all(test, feature = "postgres"))]
 pub mod cbu_dsl_integration_tests;
 
 // S-expression DSL round trip tests
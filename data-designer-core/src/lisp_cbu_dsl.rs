@@ -6,10 +6,18 @@
 //!   (entities
 //!     (entity "AC001" "Alpha Corp" asset-owner)
 //!     (entity "BM002" "Beta Management" investment-manager)))
+//!
+//! Scripts can bind names with `let` (each binding sees the ones before it),
+//! repeat a form with `map` over a `lambda`, and build entity lists with
+//! quasiquotation:
+//! (let ((im "ALLIANZ_GI"))
+//!   (map (lambda (fund)
+//!          (create-cbu fund "Sub-fund" `((entity ,im "Allianz GI" investment-manager))))
+//!        (list "Sub-fund A" "Sub-fund B")))
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use sqlx::PgPool;
+use crate::storage::DbPool;
 use crate::dsl_utils;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -22,14 +30,14 @@ pub enum LispValue {
     Nil,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LispCbuEntity {
     pub id: String,
     pub name: String,
     pub role: LispEntityRole,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum LispEntityRole {
     AssetOwner,
     InvestmentManager,
@@ -41,6 +49,154 @@ pub enum LispEntityRole {
     Custodian,
 }
 
+impl LispEntityRole {
+    /// The symbol the DSL writes the role as
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            LispEntityRole::AssetOwner => "asset-owner",
+            LispEntityRole::InvestmentManager => "investment-manager",
+            LispEntityRole::ManagingCompany => "managing-company",
+            LispEntityRole::GeneralPartner => "general-partner",
+            LispEntityRole::LimitedPartner => "limited-partner",
+            LispEntityRole::PrimeBroker => "prime-broker",
+            LispEntityRole::Administrator => "administrator",
+            LispEntityRole::Custodian => "custodian",
+        }
+    }
+
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|role| role.symbol() == symbol)
+    }
+
+    /// The role's code in the cbu_roles table
+    pub fn role_code(&self) -> &'static str {
+        match self {
+            LispEntityRole::AssetOwner => "ASSET_OWNER",
+            LispEntityRole::InvestmentManager => "INVESTMENT_MANAGER",
+            LispEntityRole::ManagingCompany => "MANAGEMENT_COMPANY",
+            LispEntityRole::GeneralPartner => "GENERAL_PARTNER",
+            LispEntityRole::LimitedPartner => "LIMITED_PARTNER",
+            LispEntityRole::PrimeBroker => "PRIME_BROKER",
+            LispEntityRole::Administrator => "ADMINISTRATOR",
+            LispEntityRole::Custodian => "CUSTODIAN",
+        }
+    }
+
+    /// The DSL role of a cbu_roles code; None for roles the DSL has no symbol for
+    pub fn from_role_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|role| role.role_code() == code)
+    }
+
+    const ALL: [LispEntityRole; 8] = [
+        LispEntityRole::AssetOwner,
+        LispEntityRole::InvestmentManager,
+        LispEntityRole::ManagingCompany,
+        LispEntityRole::GeneralPartner,
+        LispEntityRole::LimitedPartner,
+        LispEntityRole::PrimeBroker,
+        LispEntityRole::Administrator,
+        LispEntityRole::Custodian,
+    ];
+}
+
+/// A change executing the DSL would make to the database
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum PendingOperation {
+    CreateCbu { name: String, description: String },
+    /// `cbu` is the name of the CBU being created, or the ID of the one being updated
+    AddMember { cbu: String, entity_id: String, entity_name: String, role: String },
+    UpdateCbu {
+        cbu_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+    },
+    RemoveMember { cbu_id: String, entity_id: String, role: String },
+    ChangeRole { cbu_id: String, entity_id: String, from: String, to: String },
+    DeleteCbu { cbu_id: String },
+}
+
+impl PendingOperation {
+    pub fn summary(&self) -> String {
+        match self {
+            PendingOperation::CreateCbu { name, .. } => format!("Create CBU '{}'", name),
+            PendingOperation::AddMember { cbu, entity_id, entity_name, role } => {
+                format!("Add {} ({}) to '{}' as {}", entity_name, entity_id, cbu, role)
+            }
+            PendingOperation::UpdateCbu { cbu_id, name, description } => {
+                let mut summary = format!("Update CBU {}", cbu_id);
+                if let Some(name) = name {
+                    summary.push_str(&format!(", renaming it '{}'", name));
+                }
+                if description.is_some() {
+                    summary.push_str(", replacing its description");
+                }
+                summary
+            }
+            PendingOperation::RemoveMember { cbu_id, entity_id, role } => {
+                format!("Remove {} from {} as {}", entity_id, cbu_id, role)
+            }
+            PendingOperation::ChangeRole { cbu_id, entity_id, from, to } => {
+                format!("Change {} in {} from {} to {}", entity_id, cbu_id, from, to)
+            }
+            PendingOperation::DeleteCbu { cbu_id } => format!("Delete CBU {}", cbu_id),
+        }
+    }
+}
+
+/// How one top-level statement of an executed script ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatementStatus {
+    Committed,
+    /// Succeeded, but undone because a later statement failed
+    RolledBack,
+    Failed,
+    /// Not reached because an earlier statement failed
+    NotRun,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementResult {
+    /// Position of the statement in the script, from 0
+    pub index: usize,
+    pub status: StatementStatus,
+    pub operations: Vec<PendingOperation>,
+    /// IDs of the CBUs the statement created, updated or deleted
+    pub cbu_ids: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// The outcome of `LispCbuParser::execute_script`: either every statement
+/// was committed or none of them was
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptExecution {
+    pub committed: bool,
+    pub statements: Vec<StatementResult>,
+}
+
+impl ScriptExecution {
+    // The script after statement `failed` failed: what ran before it is undone
+    // and the `not_run` statements after it never started
+    fn rolled_back(mut statements: Vec<StatementResult>, failed: StatementResult, not_run: usize) -> Self {
+        for statement in &mut statements {
+            statement.status = StatementStatus::RolledBack;
+        }
+        let next = failed.index + 1;
+        statements.push(failed);
+        statements.extend((next..next + not_run).map(|index| StatementResult {
+            index,
+            status: StatementStatus::NotRun,
+            operations: Vec::new(),
+            cbu_ids: Vec::new(),
+            error: None,
+        }));
+        Self { committed: false, statements }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LispCbuCommand {
     pub operation: LispCbuOperation,
@@ -100,15 +256,18 @@ impl std::fmt::Display for LispDslError {
 impl std::error::Error for LispDslError {}
 
 pub struct LispCbuParser {
-    pub pool: Option<PgPool>,
+    pub pool: Option<DbPool>,
     environment: HashMap<String, LispValue>,
+    /// Set while planning: operations are recorded here instead of executed
+    plan: Option<Vec<PendingOperation>>,
 }
 
 impl LispCbuParser {
-    pub fn new(pool: Option<PgPool>) -> Self {
+    pub fn new(pool: Option<DbPool>) -> Self {
         let mut parser = Self {
             pool,
             environment: HashMap::new(),
+            plan: None,
         };
         parser.initialize_environment();
         parser
@@ -160,8 +319,82 @@ impl LispCbuParser {
         }
     }
 
+    /// Evaluate like `parse_and_eval`, but only record the operations each
+    /// top-level expression would perform; nothing is written
+    pub fn eval_plan(&mut self, input: &str) -> Result<Vec<Vec<PendingOperation>>, LispDslError> {
+        let expressions = self.parse(&dsl_utils::strip_comments(input))?;
+
+        let mut plan = Vec::new();
+        for expr in expressions {
+            self.plan = Some(Vec::new());
+            let result = self.eval(&expr);
+            let operations = self.plan.take().unwrap_or_default();
+            result?;
+            plan.push(operations);
+        }
+        Ok(plan)
+    }
+
+    /// Execute a script against the database in one transaction. Each
+    /// top-level statement is planned and its operations applied in turn; if
+    /// any statement fails the transaction is rolled back, so a script never
+    /// leaves a half-created CBU behind. Parse errors are returned before
+    /// anything is written.
+    #[cfg(feature = "postgres")]
+    pub async fn execute_script(&mut self, input: &str) -> Result<ScriptExecution, LispDslError> {
+        let expressions = self.parse(&dsl_utils::strip_comments(input))?;
+        let pool = self.pool.clone()
+            .ok_or_else(|| LispDslError::DatabaseError("No database connection".to_string()))?;
+        let mut tx = crate::db::DbOperations::begin_transaction(&pool).await
+            .map_err(LispDslError::DatabaseError)?;
+
+        // CBUs created by earlier statements, by name, for the members added to them
+        let mut created = HashMap::new();
+        let mut statements = Vec::new();
+        for (index, expr) in expressions.iter().enumerate() {
+            self.plan = Some(Vec::new());
+            let evaluated = self.eval(expr);
+            let operations = self.plan.take().unwrap_or_default();
+
+            let mut applied = evaluated.map(|_| Vec::new()).map_err(|e| e.to_string());
+            if let Ok(cbu_ids) = &mut applied {
+                for operation in &operations {
+                    match crate::db::DbOperations::apply_cbu_operation(&mut tx, operation, &mut created).await {
+                        Ok(cbu_id) => cbu_ids.push(cbu_id),
+                        Err(e) => {
+                            applied = Err(format!("{}: {}", operation.summary(), e));
+                            break;
+                        }
+                    }
+                }
+            }
+
+            match applied {
+                Ok(mut cbu_ids) => {
+                    cbu_ids.dedup();
+                    statements.push(StatementResult { index, status: StatementStatus::Committed, operations, cbu_ids, error: None });
+                }
+                Err(error) => {
+                    tx.rollback().await.map_err(|e| LispDslError::DatabaseError(format!("Failed to roll back: {}", e)))?;
+                    let failed = StatementResult { index, status: StatementStatus::Failed, operations, cbu_ids: Vec::new(), error: Some(error) };
+                    return Ok(ScriptExecution::rolled_back(statements, failed, expressions.len() - index - 1));
+                }
+            }
+        }
+
+        tx.commit().await.map_err(|e| LispDslError::DatabaseError(format!("Failed to commit: {}", e)))?;
+        Ok(ScriptExecution { committed: true, statements })
+    }
+
+    // Records `operation` when planning
+    fn planned(&mut self, operation: PendingOperation) {
+        if let Some(plan) = &mut self.plan {
+            plan.push(operation);
+        }
+    }
+
     /// Parse LISP S-expressions
-    fn parse(&self, input: &str) -> Result<Vec<LispValue>, LispDslError> {
+    pub(crate) fn parse(&self, input: &str) -> Result<Vec<LispValue>, LispDslError> {
         let tokens = self.tokenize(input)?;
         self.parse_tokens(&tokens)
     }
@@ -176,9 +409,17 @@ impl LispCbuParser {
                 ' ' | '\t' | '\n' | '\r' => {
                     chars.next(); // Skip whitespace
                 }
-                '(' | ')' => {
+                '(' | ')' | '\'' | '`' => {
                     tokens.push(chars.next().unwrap().to_string());
                 }
+                ',' => {
+                    chars.next();
+                    if chars.next_if_eq(&'@').is_some() {
+                        tokens.push(",@".to_string());
+                    } else {
+                        tokens.push(",".to_string());
+                    }
+                }
                 '"' => {
                     // Parse string literal
                     chars.next(); // Skip opening quote
@@ -220,7 +461,7 @@ impl LispCbuParser {
                     // Parse symbol or number
                     let mut token = String::new();
                     while let Some(&ch) = chars.peek() {
-                        if ch.is_whitespace() || ch == '(' || ch == ')' || ch == '"' || ch == ';' {
+                        if ch.is_whitespace() || matches!(ch, '(' | ')' | '"' | ';' | ',' | '`') {
                             break;
                         }
                         token.push(chars.next().unwrap());
@@ -278,6 +519,17 @@ impl LispCbuParser {
             ")" => {
                 Err(LispDslError::ParseError("Unexpected closing parenthesis".to_string()))
             }
+            "'" | "`" | "," | ",@" => {
+                // 'x reads as (quote x), `x as (quasiquote x), ,x as (unquote x)
+                let form = match token.as_str() {
+                    "'" => "quote",
+                    "`" => "quasiquote",
+                    "," => "unquote",
+                    _ => "unquote-splicing",
+                };
+                let (expr, consumed) = self.parse_expression(tokens, start + 1)?;
+                Ok((LispValue::List(vec![LispValue::Symbol(form.to_string()), expr]), consumed + 1))
+            }
             _ => {
                 // Parse atom
                 let value = self.parse_atom(token)?;
@@ -354,10 +606,149 @@ impl LispCbuParser {
                 }
                 Ok(args[0].clone()) // Return unevaluated
             }
+            "quasiquote" => {
+                if args.len() != 1 {
+                    return Err(LispDslError::ArityMismatch { expected: 1, got: args.len() });
+                }
+                self.quasiquote(&args[0])
+            }
+            "unquote" | "unquote-splicing" => {
+                Err(LispDslError::EvalError(format!("{} used outside a quasiquote", name)))
+            }
+            "let" => self.eval_let(args),
+            "lambda" => self.eval_lambda(args),
+            "map" => self.eval_map(args),
             _ => Err(LispDslError::UnknownFunction(name.to_string())),
         }
     }
 
+    /// Evaluate `(let ((name value) ...) body...)`: each binding sees the
+    /// ones before it, and the bindings end with the body
+    fn eval_let(&mut self, args: &[LispValue]) -> Result<LispValue, LispDslError> {
+        let Some(LispValue::List(bindings)) = args.first() else {
+            return Err(LispDslError::EvalError("let expects a list of bindings".to_string()));
+        };
+
+        let mut shadowed = Vec::new();
+        let result = self.bind_all(bindings, &mut shadowed).and_then(|()| self.eval_body(&args[1..]));
+        self.restore(shadowed);
+        result
+    }
+
+    // Binds each (name value) in turn, recording what it shadows
+    fn bind_all(&mut self, bindings: &[LispValue], shadowed: &mut Vec<(String, Option<LispValue>)>) -> Result<(), LispDslError> {
+        for binding in bindings {
+            let LispValue::List(pair) = binding else {
+                return Err(LispDslError::EvalError(format!("Expected (name value), got {:?}", binding)));
+            };
+            let [LispValue::Symbol(name), value] = pair.as_slice() else {
+                return Err(LispDslError::EvalError(format!("Expected (name value), got {:?}", binding)));
+            };
+            let value = self.eval(value)?;
+            shadowed.push((name.clone(), self.environment.insert(name.clone(), value)));
+        }
+        Ok(())
+    }
+
+    // The value of the last expression of a body, nil if it is empty
+    fn eval_body(&mut self, body: &[LispValue]) -> Result<LispValue, LispDslError> {
+        let mut result = LispValue::Nil;
+        for expr in body {
+            result = self.eval(expr)?;
+        }
+        Ok(result)
+    }
+
+    /// `(lambda (params...) body...)` evaluates to itself, to be applied by `map`
+    fn eval_lambda(&mut self, args: &[LispValue]) -> Result<LispValue, LispDslError> {
+        match args.first() {
+            Some(LispValue::List(params)) if params.iter().all(|param| matches!(param, LispValue::Symbol(_))) => {
+                let mut lambda = vec![LispValue::Symbol("lambda".to_string())];
+                lambda.extend(args.iter().cloned());
+                Ok(LispValue::List(lambda))
+            }
+            _ => Err(LispDslError::EvalError("lambda expects a list of parameter names".to_string())),
+        }
+    }
+
+    /// Evaluate `(map function list)`: the function applied to each item, in order
+    fn eval_map(&mut self, args: &[LispValue]) -> Result<LispValue, LispDslError> {
+        if args.len() != 2 {
+            return Err(LispDslError::ArityMismatch { expected: 2, got: args.len() });
+        }
+        let function = self.eval(&args[0])?;
+        let items = match self.eval(&args[1])? {
+            LispValue::List(items) => items,
+            LispValue::Nil => Vec::new(),
+            other => return Err(LispDslError::TypeError(format!("map expects a list, got {:?}", other))),
+        };
+        items.into_iter().map(|item| self.apply(&function, vec![item])).collect::<Result<_, _>>().map(LispValue::List)
+    }
+
+    // Applies a lambda value to `values`, binding its parameters for the body
+    fn apply(&mut self, function: &LispValue, values: Vec<LispValue>) -> Result<LispValue, LispDslError> {
+        let LispValue::List(lambda) = function else {
+            return Err(LispDslError::TypeError(format!("Expected a lambda, got {:?}", function)));
+        };
+        let (Some(LispValue::Symbol(head)), Some(LispValue::List(params))) = (lambda.first(), lambda.get(1)) else {
+            return Err(LispDslError::TypeError(format!("Expected a lambda, got {:?}", function)));
+        };
+        if head != "lambda" {
+            return Err(LispDslError::TypeError(format!("Expected a lambda, got {:?}", function)));
+        }
+        if params.len() != values.len() {
+            return Err(LispDslError::ArityMismatch { expected: params.len(), got: values.len() });
+        }
+
+        let mut shadowed = Vec::new();
+        for (param, value) in params.iter().zip(values) {
+            if let LispValue::Symbol(name) = param {
+                shadowed.push((name.clone(), self.environment.insert(name.clone(), value)));
+            }
+        }
+        let result = self.eval_body(&lambda[2..]);
+        self.restore(shadowed);
+        result
+    }
+
+    // Puts back what bindings shadowed, innermost first
+    fn restore(&mut self, shadowed: Vec<(String, Option<LispValue>)>) {
+        for (name, previous) in shadowed.into_iter().rev() {
+            match previous {
+                Some(value) => self.environment.insert(name, value),
+                None => self.environment.remove(&name),
+            };
+        }
+    }
+
+    /// The template with each `,x` replaced by the value of `x` and each
+    /// `,@x` by the items of the list `x`; everything else is left unevaluated
+    fn quasiquote(&mut self, template: &LispValue) -> Result<LispValue, LispDslError> {
+        let LispValue::List(items) = template else {
+            return Ok(template.clone());
+        };
+        if let [LispValue::Symbol(form), expr] = items.as_slice() {
+            if form == "unquote" {
+                return self.eval(expr);
+            }
+        }
+
+        let mut result = Vec::new();
+        for item in items {
+            match item {
+                LispValue::List(splice) if matches!(splice.as_slice(), [LispValue::Symbol(form), _] if form == "unquote-splicing") => {
+                    match self.eval(&splice[1])? {
+                        LispValue::List(values) => result.extend(values),
+                        LispValue::Nil => {}
+                        other => return Err(LispDslError::TypeError(format!(",@ expects a list, got {:?}", other))),
+                    }
+                }
+                _ => result.push(self.quasiquote(item)?),
+            }
+        }
+        Ok(LispValue::List(result))
+    }
+
     /// Evaluate create-cbu function
     fn eval_create_cbu(&mut self, args: &[LispValue]) -> Result<LispValue, LispDslError> {
         if args.len() < 2 {
@@ -380,6 +771,16 @@ impl LispCbuParser {
             entities = self.extract_entities(&entities_expr)?;
         }
 
+        self.planned(PendingOperation::CreateCbu { name: name.clone(), description: description.clone() });
+        for entity in &entities {
+            self.planned(PendingOperation::AddMember {
+                cbu: name.clone(),
+                entity_id: entity.id.clone(),
+                entity_name: entity.name.clone(),
+                role: entity.role.symbol().to_string(),
+            });
+        }
+
         // Create CBU command structure
         let command = LispCbuCommand {
             operation: LispCbuOperation::Create,
@@ -399,7 +800,10 @@ impl LispCbuParser {
         ]))
     }
 
-    /// Evaluate update-cbu function
+    /// Evaluate update-cbu function. After the CBU ID come its changes:
+    /// `(set-name "…")`, `(set-description "…")`, `(add-member "id" "name" role)`,
+    /// `(remove-member "id" role)` and `(change-role "id" from-role to-role)`.
+    /// Other arguments are accepted and ignored, as they always were.
     fn eval_update_cbu(&mut self, args: &[LispValue]) -> Result<LispValue, LispDslError> {
         if args.is_empty() {
             return Err(LispDslError::ArityMismatch { expected: 1, got: 0 });
@@ -408,6 +812,54 @@ impl LispCbuParser {
         let cbu_id_val = self.eval(&args[0])?;
         let cbu_id = self.extract_string(&cbu_id_val)?;
 
+        let (mut name, mut description, mut member_changes) = (None, None, Vec::new());
+        for change in &args[1..] {
+            let LispValue::List(items) = change else { continue };
+            let Some(LispValue::Symbol(form)) = items.first() else { continue };
+            let change_args = &items[1..];
+            let expect = |count: usize| match change_args.len() {
+                got if got == count => Ok(()),
+                got => Err(LispDslError::ArityMismatch { expected: count, got }),
+            };
+            match form.as_str() {
+                "set-name" => {
+                    expect(1)?;
+                    let value = self.eval(&change_args[0])?;
+                    name = Some(self.extract_string(&value)?);
+                }
+                "set-description" => {
+                    expect(1)?;
+                    let value = self.eval(&change_args[0])?;
+                    description = Some(self.extract_string(&value)?);
+                }
+                "add-member" => {
+                    expect(3)?;
+                    let [entity_id, entity_name, role] = self.eval_member_args(change_args)?;
+                    member_changes.push(PendingOperation::AddMember { cbu: cbu_id.clone(), entity_id, entity_name, role: self.role_symbol(role)? });
+                }
+                "remove-member" => {
+                    expect(2)?;
+                    let [entity_id, role] = self.eval_member_args(change_args)?;
+                    member_changes.push(PendingOperation::RemoveMember { cbu_id: cbu_id.clone(), entity_id, role: self.role_symbol(role)? });
+                }
+                "change-role" => {
+                    expect(3)?;
+                    let [entity_id, from, to] = self.eval_member_args(change_args)?;
+                    member_changes.push(PendingOperation::ChangeRole {
+                        cbu_id: cbu_id.clone(),
+                        entity_id,
+                        from: self.role_symbol(from)?,
+                        to: self.role_symbol(to)?,
+                    });
+                }
+                _ => {}
+            }
+        }
+        self.planned(PendingOperation::UpdateCbu { cbu_id: cbu_id.clone(), name, description });
+        for change in member_changes {
+            self.planned(change);
+        }
+
         Ok(LispValue::List(vec![
             LispValue::Symbol("update-cbu-result".to_string()),
             LispValue::String(cbu_id),
@@ -415,6 +867,24 @@ impl LispCbuParser {
         ]))
     }
 
+    // The arguments of a member change, evaluated to strings
+    fn eval_member_args<const N: usize>(&mut self, args: &[LispValue]) -> Result<[String; N], LispDslError> {
+        let mut values = Vec::with_capacity(N);
+        for arg in args {
+            let value = self.eval(arg)?;
+            values.push(self.extract_string(&value)?);
+        }
+        values.try_into().map_err(|values: Vec<String>| LispDslError::ArityMismatch { expected: N, got: values.len() })
+    }
+
+    // Checks a role symbol names a known role
+    fn role_symbol(&self, role: String) -> Result<String, LispDslError> {
+        match LispEntityRole::from_symbol(&role) {
+            Some(role) => Ok(role.symbol().to_string()),
+            None => Err(LispDslError::ValidationError(format!("Unknown entity role: {}", role))),
+        }
+    }
+
     /// Evaluate delete-cbu function
     fn eval_delete_cbu(&mut self, args: &[LispValue]) -> Result<LispValue, LispDslError> {
         if args.len() != 1 {
@@ -423,6 +893,7 @@ impl LispCbuParser {
 
         let cbu_id_val = self.eval(&args[0])?;
         let cbu_id = self.extract_string(&cbu_id_val)?;
+        self.planned(PendingOperation::DeleteCbu { cbu_id: cbu_id.clone() });
 
         Ok(LispValue::List(vec![
             LispValue::Symbol("delete-cbu-result".to_string()),
@@ -446,8 +917,11 @@ impl LispCbuParser {
         for arg in args {
             let entity_expr = self.eval(arg)?;
             if let LispValue::List(entity_data) = entity_expr {
-                if !entity_data.is_empty() {
-                    entities.push(LispValue::List(entity_data));
+                match entity_data.first() {
+                    None => {}
+                    // A list of entities, e.g. from map, joins the others
+                    Some(LispValue::List(_)) => entities.extend(entity_data),
+                    Some(_) => entities.push(LispValue::List(entity_data)),
                 }
             }
         }
@@ -472,7 +946,7 @@ impl LispCbuParser {
             LispValue::Symbol("entity".to_string()),
             LispValue::String(id),
             LispValue::String(name),
-            LispValue::Symbol(format!("{:?}", role).to_lowercase()),
+            LispValue::Symbol(role.symbol().to_string()),
         ]))
     }
 
@@ -488,17 +962,8 @@ impl LispCbuParser {
     /// Extract entity role from LISP value
     fn extract_entity_role(&self, value: &LispValue) -> Result<LispEntityRole, LispDslError> {
         let role_str = self.extract_string(value)?;
-        match role_str.as_str() {
-            "asset-owner" => Ok(LispEntityRole::AssetOwner),
-            "investment-manager" => Ok(LispEntityRole::InvestmentManager),
-            "managing-company" => Ok(LispEntityRole::ManagingCompany),
-            "general-partner" => Ok(LispEntityRole::GeneralPartner),
-            "limited-partner" => Ok(LispEntityRole::LimitedPartner),
-            "prime-broker" => Ok(LispEntityRole::PrimeBroker),
-            "administrator" => Ok(LispEntityRole::Administrator),
-            "custodian" => Ok(LispEntityRole::Custodian),
-            _ => Err(LispDslError::ValidationError(format!("Unknown entity role: {}", role_str))),
-        }
+        LispEntityRole::from_symbol(&role_str)
+            .ok_or_else(|| LispDslError::ValidationError(format!("Unknown entity role: {}", role_str)))
     }
 
     /// Extract entities from LISP value
@@ -595,9 +1060,8 @@ impl LispCbuParser {
         if !entities.is_empty() {
             dsl.push_str("  (entities\n");
             for entity in entities {
-                let role_str = format!("{:?}", entity.role).to_lowercase().replace("_", "-");
                 dsl.push_str(&format!("    (entity \"{}\" \"{}\" {})\n",
-                    entity.id, entity.name, role_str));
+                    entity.id, entity.name, entity.role.symbol()));
             }
             dsl.push_str("  )");
         }
@@ -627,6 +1091,355 @@ impl LispValue {
     }
 }
 
+/// Layout of formatted CBU DSL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CbuFormatOptions {
+    /// Lists longer than this many columns are broken over several lines
+    pub width: usize,
+    /// Spaces each nesting level is indented by
+    pub indent: usize,
+}
+
+impl Default for CbuFormatOptions {
+    fn default() -> Self {
+        Self { width: 80, indent: 2 }
+    }
+}
+
+/// The canonical `(create-cbu ...)` form of a CBU: members ordered by role
+/// (in `LispEntityRole` order) and then ID, strings escaped so they read
+/// back unchanged, laid out by `format_cbu_dsl`. `comments` go above the
+/// form, e.g. members the DSL can't express.
+pub fn cbu_sexpr(name: &str, description: &str, entities: &[LispCbuEntity], comments: &[String]) -> String {
+    let mut entities: Vec<&LispCbuEntity> = entities.iter().collect();
+    entities.sort_by(|a, b| (a.role, &a.id, &a.name).cmp(&(b.role, &b.id, &b.name)));
+
+    let mut form = format!("(create-cbu {} {}", quoted(name), quoted(description));
+    if !entities.is_empty() {
+        form.push_str(" (entities");
+        for entity in entities {
+            form.push_str(&format!(" (entity {} {} {})", quoted(&entity.id), quoted(&entity.name), entity.role.symbol()));
+        }
+        form.push(')');
+    }
+    form.push(')');
+
+    let mut source: String = comments.iter().map(|comment| format!(";; {}\n", comment.replace('\n', " "))).collect();
+    source.push_str(&form);
+    // The form is balanced and its strings terminated, so formatting can't fail
+    format_cbu_dsl(&source, &CbuFormatOptions::default()).unwrap_or(source)
+}
+
+// A string literal the tokenizer reads back as `value`
+pub(crate) fn quoted(value: &str) -> String {
+    let mut literal = String::from('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            '\r' => literal.push_str("\\r"),
+            ch => literal.push(ch),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Re-indent a CBU DSL document. A list that fits within the width stays on
+/// one line; one that doesn't keeps its head and the atoms after it on the
+/// first line and puts each remaining element on a line of its own, one
+/// level in:
+///
+/// ```text
+/// (create-cbu "Growth Fund Alpha" "Diversified growth fund"
+///   (entities
+///     (entity "AC001" "Alpha Corp" asset-owner)
+///     (entity "BM002" "Beta Management" investment-manager)))
+/// ```
+///
+/// Comments and single blank lines are kept. Unbalanced parentheses or an
+/// unterminated string are a parse error, and the text is left as it is.
+pub fn format_cbu_dsl(input: &str, options: &CbuFormatOptions) -> Result<String, LispDslError> {
+    let mut reader = LayoutReader { input, pos: 0 };
+    let (forms, dangling) = reader.items()?;
+    if reader.pos < input.len() {
+        return Err(LispDslError::ParseError(format!("Unexpected ')' on line {}", reader.line_at(reader.pos))));
+    }
+
+    let mut out = String::new();
+    for (index, form) in forms.iter().enumerate() {
+        if index > 0 {
+            out.push('\n');
+            if form.blank_line_before {
+                out.push('\n');
+            }
+        }
+        for comment in &form.comments {
+            out.push_str(comment);
+            out.push('\n');
+        }
+        render_layout(&form.node, 0, options, &mut out);
+        if let Some(comment) = &form.trailing {
+            out.push(' ');
+            out.push_str(comment);
+        }
+    }
+    for comment in &dangling {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(comment);
+    }
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Indentation of `line` as `format_cbu_dsl` would give it: one level in
+/// from the innermost list open at its start, or that list's own column when
+/// the line starts by closing it
+pub fn cbu_indent_for_line(input: &str, line: usize, options: &CbuFormatOptions) -> usize {
+    let line_start: usize = input.split_inclusive('\n').take(line).map(str::len).sum();
+    let mut open = Vec::new();
+    let mut column = 0;
+    let mut chars = input[..line_start.min(input.len())].chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\n' => {
+                column = 0;
+                continue;
+            }
+            '(' => open.push(column),
+            ')' => {
+                open.pop();
+            }
+            ';' => {
+                if chars.by_ref().any(|ch| ch == '\n') {
+                    column = 0;
+                }
+                continue;
+            }
+            '"' => {
+                let mut escaped = false;
+                for ch in chars.by_ref() {
+                    column = if ch == '\n' { 0 } else { column + 1 };
+                    match ch {
+                        '"' if !escaped => break,
+                        '\\' => escaped = !escaped,
+                        _ => escaped = false,
+                    }
+                }
+            }
+            _ => {}
+        }
+        column += 1;
+    }
+
+    let closes = input[line_start.min(input.len())..].trim_start_matches([' ', '\t']).starts_with(')');
+    match open.last() {
+        Some(&column) if closes => column,
+        Some(&column) => column + options.indent,
+        None => 0,
+    }
+}
+
+// A list element or top-level form with the comments around it
+#[derive(Debug)]
+struct Layout {
+    blank_line_before: bool,
+    /// Comment lines above it
+    comments: Vec<String>,
+    node: LayoutNode,
+    /// A comment after it on the same line
+    trailing: Option<String>,
+}
+
+#[derive(Debug)]
+enum LayoutNode {
+    /// A symbol, number or string, as written
+    Atom(String),
+    /// Elements and the comment lines after the last of them
+    List { items: Vec<Layout>, dangling: Vec<String> },
+}
+
+impl LayoutNode {
+    // The node on one line, unless a comment inside it needs a line break
+    fn flat(&self) -> Option<String> {
+        match self {
+            LayoutNode::Atom(text) => Some(text.clone()),
+            LayoutNode::List { items, dangling } => {
+                if !dangling.is_empty() {
+                    return None;
+                }
+                let parts = items
+                    .iter()
+                    .map(|item| if item.comments.is_empty() && item.trailing.is_none() { item.node.flat() } else { None })
+                    .collect::<Option<Vec<_>>>()?;
+                Some(format!("({})", parts.join(" ")))
+            }
+        }
+    }
+}
+
+// Reads the document keeping the comments and blank lines the evaluator's tokenizer drops
+struct LayoutReader<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> LayoutReader<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn line_at(&self, offset: usize) -> usize {
+        self.input[..offset].matches('\n').count() + 1
+    }
+
+    // Elements up to the next unmatched ')' or the end, which is left unread
+    fn items(&mut self) -> Result<(Vec<Layout>, Vec<String>), LispDslError> {
+        let mut items: Vec<Layout> = Vec::new();
+        let mut comments = Vec::new();
+        let mut blank_line_before = false;
+        loop {
+            let rest = self.rest();
+            let space = rest.len() - rest.trim_start().len();
+            let newlines = rest[..space].matches('\n').count();
+            self.pos += space;
+            if newlines > 1 && (!items.is_empty() || !comments.is_empty()) {
+                blank_line_before = true;
+            }
+
+            let rest = self.rest();
+            match rest.chars().next() {
+                None | Some(')') => return Ok((items, comments)),
+                Some(';') => {
+                    let end = rest.find('\n').unwrap_or(rest.len());
+                    let comment = rest[..end].trim_end().to_string();
+                    self.pos += end;
+                    match items.last_mut() {
+                        Some(last) if newlines == 0 && comments.is_empty() && last.trailing.is_none() => last.trailing = Some(comment),
+                        _ => comments.push(comment),
+                    }
+                }
+                Some(_) => {
+                    let node = self.node()?;
+                    items.push(Layout { blank_line_before, comments: std::mem::take(&mut comments), node, trailing: None });
+                    blank_line_before = false;
+                }
+            }
+        }
+    }
+
+    fn node(&mut self) -> Result<LayoutNode, LispDslError> {
+        let start = self.pos;
+        let rest = self.rest();
+        if rest.starts_with('(') {
+            self.pos += 1;
+            let (items, dangling) = self.items()?;
+            if !self.rest().starts_with(')') {
+                return Err(LispDslError::ParseError(format!("Unclosed '(' on line {}", self.line_at(start))));
+            }
+            self.pos += 1;
+            return Ok(LayoutNode::List { items, dangling });
+        }
+
+        let len = if rest.starts_with('"') {
+            let mut escaped = false;
+            rest.char_indices()
+                .skip(1)
+                .find(|&(_, ch)| {
+                    let closes = ch == '"' && !escaped;
+                    escaped = ch == '\\' && !escaped;
+                    closes
+                })
+                .map(|(index, _)| index + 1)
+                .ok_or_else(|| LispDslError::ParseError(format!("Unterminated string on line {}", self.line_at(start))))?
+        } else {
+            rest.find(|ch: char| ch.is_whitespace() || matches!(ch, '(' | ')' | ';' | '"')).unwrap_or(rest.len())
+        };
+        self.pos += len;
+        Ok(LayoutNode::Atom(rest[..len].to_string()))
+    }
+}
+
+fn render_layout(node: &LayoutNode, column: usize, options: &CbuFormatOptions, out: &mut String) {
+    let LayoutNode::List { items, dangling } = node else {
+        if let LayoutNode::Atom(text) = node {
+            out.push_str(text);
+        }
+        return;
+    };
+    if let Some(flat) = node.flat() {
+        if column + flat.chars().count() <= options.width {
+            out.push_str(&flat);
+            return;
+        }
+    }
+
+    let inner = column + options.indent;
+    let new_line = |out: &mut String, blank_line: bool, indent: usize| {
+        out.push('\n');
+        if blank_line {
+            out.push('\n');
+        }
+        out.push_str(&" ".repeat(indent));
+    };
+
+    out.push('(');
+    let mut position = column + 1;
+    let mut on_head_line = true;
+    for (index, item) in items.iter().enumerate() {
+        // The head, and the atoms straight after it while they fit, share the first line
+        let stays = on_head_line
+            && item.comments.is_empty()
+            && match &item.node {
+                _ if index == 0 => true,
+                LayoutNode::Atom(text) => !item.blank_line_before && position + 1 + text.chars().count() <= options.width,
+                LayoutNode::List { .. } => false,
+            };
+        if stays {
+            if index > 0 {
+                out.push(' ');
+                position += 1;
+            }
+        } else {
+            on_head_line = false;
+            for (line, comment) in item.comments.iter().enumerate() {
+                new_line(out, item.blank_line_before && line == 0, inner);
+                out.push_str(comment);
+            }
+            new_line(out, item.blank_line_before && item.comments.is_empty(), inner);
+            position = inner;
+        }
+
+        render_layout(&item.node, position, options, out);
+        match &item.node {
+            LayoutNode::Atom(text) => position += text.chars().count(),
+            LayoutNode::List { .. } => on_head_line = false,
+        }
+        if let Some(comment) = &item.trailing {
+            out.push(' ');
+            out.push_str(comment);
+            on_head_line = false;
+        }
+    }
+
+    for comment in dangling {
+        new_line(out, false, inner);
+        out.push_str(comment);
+    }
+    // A comment ending the last line would swallow the closing paren
+    if !dangling.is_empty() || items.last().is_some_and(|item| item.trailing.is_some()) {
+        new_line(out, false, column);
+    }
+    out.push(')');
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -857,6 +1670,137 @@ mod tests {
         assert!(result.success);
     }
 
+    #[test]
+    fn test_plan_lists_pending_operations_per_expression() {
+        let mut parser = LispCbuParser::new(None);
+        let plan = parser.eval_plan(r#"
+            (create-cbu "Fund A" "Growth"
+              (entities (entity "AC001" "Alpha Corp" asset-owner)))
+            ; nothing to change
+            (query-cbu)
+            (delete-cbu "CBU001")
+        "#).unwrap();
+
+        assert_eq!(plan.len(), 3);
+        assert_eq!(plan[0].iter().map(PendingOperation::summary).collect::<Vec<_>>(), [
+            "Create CBU 'Fund A'",
+            "Add Alpha Corp (AC001) to 'Fund A' as asset-owner",
+        ]);
+        assert!(plan[1].is_empty());
+        assert_eq!(plan[2], [PendingOperation::DeleteCbu { cbu_id: "CBU001".to_string() }]);
+
+        // A failed plan leaves the parser executing again
+        assert!(parser.eval_plan(r#"(entity "X" "Y" nobody)"#).is_err());
+        assert!(parser.plan.is_none());
+    }
+
+    #[test]
+    fn test_let_map_and_quasiquote_share_an_investment_manager() {
+        let script = r#"
+            (let ((im "ALLIANZ_GI") (im-name "Allianz GI"))
+              (map (lambda (fund)
+                     (create-cbu fund "Sub-fund"
+                       (entities
+                         (entity im im-name investment-manager)
+                         (map (lambda (id) (entity id fund asset-owner)) (list "AO-1")))))
+                   (list "Fund A" "Fund B" "Fund C")))
+            (create-cbu "Fund D" "Built from a template" `((entity ,"ALLIANZ_GI" "Allianz GI" investment-manager) ,@(list)))
+        "#;
+        let mut parser = LispCbuParser::new(None);
+        let plan = parser.eval_plan(script).unwrap();
+
+        let operations: Vec<String> = plan[0].iter().map(PendingOperation::summary).collect();
+        assert_eq!(operations.len(), 9);
+        assert_eq!(operations[0], "Create CBU 'Fund A'");
+        assert_eq!(operations[1], "Add Allianz GI (ALLIANZ_GI) to 'Fund A' as investment-manager");
+        assert_eq!(operations[2], "Add Fund A (AO-1) to 'Fund A' as asset-owner");
+        assert_eq!(operations[6], "Create CBU 'Fund C'");
+        assert_eq!(plan[1][1].summary(), "Add Allianz GI (ALLIANZ_GI) to 'Fund D' as investment-manager");
+
+        // Bindings end with their form
+        assert!(matches!(parser.eval_plan("im"), Err(LispDslError::UnboundVariable(_))));
+        assert!(matches!(parser.eval_plan("(list ,im)"), Err(LispDslError::EvalError(_))));
+        assert_eq!(
+            parser.tokenize("`(a ,b ,@c 'd)").unwrap(),
+            vec!["`", "(", "a", ",", "b", ",@", "c", "'", "d", ")"]
+        );
+    }
+
+    #[test]
+    fn test_cbu_sexpr_round_trips_through_the_parser() {
+        let entity = |id: &str, name: &str, role| LispCbuEntity { id: id.to_string(), name: name.to_string(), role };
+        let entities = vec![
+            entity("CU-9", "Custody \"Global\" Ltd", LispEntityRole::Custodian),
+            entity("IM-2", "Beta Management", LispEntityRole::InvestmentManager),
+            entity("AO-1", "Alpha Corp\\Holdings", LispEntityRole::AssetOwner),
+            entity("IM-1", "Alpha Management", LispEntityRole::InvestmentManager),
+        ];
+        let comments = vec!["Not expressible in the DSL: KPMG (AU-1) as AUDITOR".to_string()];
+        let source = cbu_sexpr("Growth Fund \"Alpha\"", "Line one\nline two", &entities, &comments);
+
+        assert!(source.starts_with(";; Not expressible in the DSL: KPMG (AU-1) as AUDITOR\n(create-cbu"));
+        let plan = LispCbuParser::new(None).eval_plan(&source).unwrap();
+        let members = |cbu: &str| -> Vec<PendingOperation> {
+            [("AO-1", "Alpha Corp\\Holdings", "asset-owner"), ("IM-1", "Alpha Management", "investment-manager"),
+             ("IM-2", "Beta Management", "investment-manager"), ("CU-9", "Custody \"Global\" Ltd", "custodian")]
+                .iter()
+                .map(|(id, name, role)| PendingOperation::AddMember {
+                    cbu: cbu.to_string(),
+                    entity_id: id.to_string(),
+                    entity_name: name.to_string(),
+                    role: role.to_string(),
+                })
+                .collect()
+        };
+        let mut expected = vec![PendingOperation::CreateCbu {
+            name: "Growth Fund \"Alpha\"".to_string(),
+            description: "Line one\nline two".to_string(),
+        }];
+        expected.extend(members("Growth Fund \"Alpha\""));
+        assert_eq!(plan, vec![expected]);
+
+        // Reading the export back and exporting again gives the same text
+        let mut parser = LispCbuParser::new(None);
+        let LispValue::List(result) = parser.parse(&source).unwrap().remove(0) else { panic!("expected a form") };
+        let entities_value = parser.eval(&result[3]).unwrap();
+        let reparsed = parser.extract_entities(&entities_value).unwrap();
+        assert_eq!(cbu_sexpr("Growth Fund \"Alpha\"", "Line one\nline two", &reparsed, &comments), source);
+
+        assert_eq!(LispEntityRole::from_role_code("MANAGEMENT_COMPANY"), Some(LispEntityRole::ManagingCompany));
+        assert_eq!(LispEntityRole::from_role_code("AUDITOR"), None);
+        assert_eq!(cbu_sexpr("Empty", "", &[], &[]), "(create-cbu \"Empty\" \"\")\n");
+    }
+
+    #[test]
+    fn test_failed_statement_rolls_back_the_ones_before_it() {
+        let committed = |index| StatementResult {
+            index,
+            status: StatementStatus::Committed,
+            operations: Vec::new(),
+            cbu_ids: vec![format!("CBU-00000{}", index)],
+            error: None,
+        };
+        let failed = StatementResult {
+            index: 2,
+            status: StatementStatus::Failed,
+            operations: Vec::new(),
+            cbu_ids: Vec::new(),
+            error: Some("Role not found".to_string()),
+        };
+
+        let execution = ScriptExecution::rolled_back(vec![committed(0), committed(1)], failed, 2);
+        assert!(!execution.committed);
+        let statuses: Vec<_> = execution.statements.iter().map(|s| (s.index, s.status)).collect();
+        assert_eq!(statuses, vec![
+            (0, StatementStatus::RolledBack),
+            (1, StatementStatus::RolledBack),
+            (2, StatementStatus::Failed),
+            (3, StatementStatus::NotRun),
+            (4, StatementStatus::NotRun),
+        ]);
+        assert_eq!(execution.statements[0].cbu_ids, vec!["CBU-000000"]);
+    }
+
     #[test]
     fn test_entities_function() {
         let mut parser = LispCbuParser::new(None);
@@ -938,4 +1882,42 @@ mod tests {
         assert!(parsed_result.success);
         assert!(parsed_result.message.contains("Round Trip Fund"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_format_breaks_long_forms_and_keeps_comments() {
+        let messy = "; Growth fund\n(create-cbu \"Growth Fund Alpha\"   \"Diversified growth fund\" (entities\n(entity \"AC001\" \"Alpha Corp\" asset-owner) ; owner\n\n\n  (entity \"BM002\" \"Beta Management\" investment-manager)))\n(query-cbu)";
+        let options = CbuFormatOptions::default();
+        let formatted = format_cbu_dsl(messy, &options).unwrap();
+        assert_eq!(
+            formatted,
+            "; Growth fund\n\
+             (create-cbu \"Growth Fund Alpha\" \"Diversified growth fund\"\n  \
+               (entities\n    \
+                 (entity \"AC001\" \"Alpha Corp\" asset-owner) ; owner\n\n    \
+                 (entity \"BM002\" \"Beta Management\" investment-manager)))\n\
+             (query-cbu)\n"
+        );
+        assert_eq!(format_cbu_dsl(&formatted, &options).unwrap(), formatted);
+
+        // Wide enough, the same form goes on one line; comments at the end of a list keep its paren off them
+        let wide = format_cbu_dsl("(entities\n  (entity \"A\" \"B\" custodian))", &CbuFormatOptions { width: 200, indent: 2 }).unwrap();
+        assert_eq!(wide, "(entities (entity \"A\" \"B\" custodian))\n");
+        let commented = format_cbu_dsl("(entities (entity \"A\" \"B\" custodian) ; last\n)", &options).unwrap();
+        assert_eq!(commented, "(entities\n  (entity \"A\" \"B\" custodian) ; last\n)\n");
+
+        assert!(format_cbu_dsl("(create-cbu \"Fund\"", &options).is_err());
+        assert!(format_cbu_dsl("(create-cbu \"Fund)", &options).is_err());
+        assert!(format_cbu_dsl("(query-cbu))", &options).is_err());
+    }
+
+    #[test]
+    fn test_new_lines_are_indented_inside_the_open_list() {
+        let options = CbuFormatOptions::default();
+        let text = "(create-cbu \"Fund (Alpha)\" ; a (comment\n  (entities\n\n)\n\n";
+        assert_eq!(cbu_indent_for_line(text, 1, &options), 2);
+        assert_eq!(cbu_indent_for_line(text, 2, &options), 4);
+        assert_eq!(cbu_indent_for_line(text, 3, &options), 2);
+        assert_eq!(cbu_indent_for_line(text, 4, &options), 2);
+        assert_eq!(cbu_indent_for_line("(query-cbu)\n", 1, &options), 0);
+    }
+}
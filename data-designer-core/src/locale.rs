@@ -0,0 +1,278 @@
+//! Locale-aware number and date formatting for customer-facing rule output
+//!
+//! Backs FORMAT_NUMBER, FORMAT_DATE and the locale argument of TO_NUMBER.
+//! Locales are looked up by BCP 47 tag (`de-DE`, `de_DE` and `de` all match).
+//! Money literals (`1_000 USD`) are limited to the currencies listed here,
+//! which also give the number of decimals FORMAT_NUMBER uses for them.
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+#[derive(Debug)]
+pub struct LocaleFormat {
+    pub tag: &'static str,
+    pub decimal_separator: char,
+    pub group_separator: char,
+    /// strftime pattern used when FORMAT_DATE gets an empty pattern
+    pub date_pattern: &'static str,
+    pub month_names: [&'static str; 12],
+    /// Monday first, matching chrono's `%u`
+    pub day_names: [&'static str; 7],
+}
+
+const EN_MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December",
+];
+const EN_DAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+const DE_MONTHS: [&str; 12] = [
+    "Januar", "Februar", "März", "April", "Mai", "Juni",
+    "Juli", "August", "September", "Oktober", "November", "Dezember",
+];
+const DE_DAYS: [&str; 7] = ["Montag", "Dienstag", "Mittwoch", "Donnerstag", "Freitag", "Samstag", "Sonntag"];
+const FR_MONTHS: [&str; 12] = [
+    "janvier", "février", "mars", "avril", "mai", "juin",
+    "juillet", "août", "septembre", "octobre", "novembre", "décembre",
+];
+const FR_DAYS: [&str; 7] = ["lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche"];
+const ES_MONTHS: [&str; 12] = [
+    "enero", "febrero", "marzo", "abril", "mayo", "junio",
+    "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre",
+];
+const ES_DAYS: [&str; 7] = ["lunes", "martes", "miércoles", "jueves", "viernes", "sábado", "domingo"];
+const IT_MONTHS: [&str; 12] = [
+    "gennaio", "febbraio", "marzo", "aprile", "maggio", "giugno",
+    "luglio", "agosto", "settembre", "ottobre", "novembre", "dicembre",
+];
+const IT_DAYS: [&str; 7] = ["lunedì", "martedì", "mercoledì", "giovedì", "venerdì", "sabato", "domenica"];
+const NL_MONTHS: [&str; 12] = [
+    "januari", "februari", "maart", "april", "mei", "juni",
+    "juli", "augustus", "september", "oktober", "november", "december",
+];
+const NL_DAYS: [&str; 7] = ["maandag", "dinsdag", "woensdag", "donderdag", "vrijdag", "zaterdag", "zondag"];
+
+/// Narrow no-break space, the CLDR grouping separator for French
+const NNBSP: char = '\u{202F}';
+
+pub const DEFAULT_LOCALE: &str = "en-US";
+
+static LOCALES: &[LocaleFormat] = &[
+    LocaleFormat { tag: "en-US", decimal_separator: '.', group_separator: ',', date_pattern: "%m/%d/%Y", month_names: EN_MONTHS, day_names: EN_DAYS },
+    LocaleFormat { tag: "en-GB", decimal_separator: '.', group_separator: ',', date_pattern: "%d/%m/%Y", month_names: EN_MONTHS, day_names: EN_DAYS },
+    LocaleFormat { tag: "de-DE", decimal_separator: ',', group_separator: '.', date_pattern: "%d.%m.%Y", month_names: DE_MONTHS, day_names: DE_DAYS },
+    LocaleFormat { tag: "de-CH", decimal_separator: '.', group_separator: '\'', date_pattern: "%d.%m.%Y", month_names: DE_MONTHS, day_names: DE_DAYS },
+    LocaleFormat { tag: "fr-FR", decimal_separator: ',', group_separator: NNBSP, date_pattern: "%d/%m/%Y", month_names: FR_MONTHS, day_names: FR_DAYS },
+    LocaleFormat { tag: "es-ES", decimal_separator: ',', group_separator: '.', date_pattern: "%d/%m/%Y", month_names: ES_MONTHS, day_names: ES_DAYS },
+    LocaleFormat { tag: "it-IT", decimal_separator: ',', group_separator: '.', date_pattern: "%d/%m/%Y", month_names: IT_MONTHS, day_names: IT_DAYS },
+    LocaleFormat { tag: "nl-NL", decimal_separator: ',', group_separator: '.', date_pattern: "%d-%m-%Y", month_names: NL_MONTHS, day_names: NL_DAYS },
+];
+
+#[derive(Debug)]
+pub struct Currency {
+    pub code: &'static str,
+    /// Decimals in the currency's minor unit: 2 for cents, 0 for yen
+    pub minor_units: usize,
+}
+
+static CURRENCIES: &[Currency] = &[
+    Currency { code: "USD", minor_units: 2 },
+    Currency { code: "EUR", minor_units: 2 },
+    Currency { code: "GBP", minor_units: 2 },
+    Currency { code: "CHF", minor_units: 2 },
+    Currency { code: "JPY", minor_units: 0 },
+    Currency { code: "CAD", minor_units: 2 },
+    Currency { code: "AUD", minor_units: 2 },
+    Currency { code: "NZD", minor_units: 2 },
+    Currency { code: "HKD", minor_units: 2 },
+    Currency { code: "SGD", minor_units: 2 },
+    Currency { code: "CNY", minor_units: 2 },
+    Currency { code: "INR", minor_units: 2 },
+    Currency { code: "KRW", minor_units: 0 },
+    Currency { code: "SEK", minor_units: 2 },
+    Currency { code: "NOK", minor_units: 2 },
+    Currency { code: "DKK", minor_units: 2 },
+    Currency { code: "PLN", minor_units: 2 },
+    Currency { code: "CZK", minor_units: 2 },
+    Currency { code: "HUF", minor_units: 2 },
+    Currency { code: "ZAR", minor_units: 2 },
+    Currency { code: "BRL", minor_units: 2 },
+    Currency { code: "MXN", minor_units: 2 },
+    Currency { code: "AED", minor_units: 2 },
+    Currency { code: "SAR", minor_units: 2 },
+    Currency { code: "BHD", minor_units: 3 },
+    Currency { code: "KWD", minor_units: 3 },
+];
+
+/// A supported ISO 4217 currency; codes are upper case
+pub fn find_currency(code: &str) -> Option<&'static Currency> {
+    CURRENCIES.iter().find(|currency| currency.code == code)
+}
+
+/// `1234.5 EUR` in de-DE is `1.234,50 EUR`; `decimals` defaults to the currency's minor units
+pub fn format_money(amount: f64, currency: &str, decimals: Option<usize>, locale: &LocaleFormat) -> String {
+    let decimals = decimals.unwrap_or_else(|| find_currency(currency).map_or(2, |c| c.minor_units));
+    format!("{} {}", format_number(amount, decimals, locale), currency)
+}
+
+/// Find a supported locale; a bare language (`de`) picks its first region
+pub fn find_locale(tag: &str) -> Result<&'static LocaleFormat> {
+    let normalized = tag.trim().replace('_', "-");
+    LOCALES
+        .iter()
+        .find(|locale| locale.tag.eq_ignore_ascii_case(&normalized))
+        .or_else(|| {
+            LOCALES.iter().find(|locale| {
+                locale.tag.split('-').next().is_some_and(|lang| lang.eq_ignore_ascii_case(&normalized))
+            })
+        })
+        .ok_or_else(|| {
+            let supported: Vec<&str> = LOCALES.iter().map(|l| l.tag).collect();
+            anyhow::anyhow!("Unsupported locale '{}' (supported: {})", tag, supported.join(", "))
+        })
+}
+
+/// `1234567.891` with 2 decimals in de-DE is `1.234.567,89`
+pub fn format_number(value: f64, decimals: usize, locale: &LocaleFormat) -> String {
+    let fixed = format!("{:.*}", decimals, value.abs());
+    let (integer, fraction) = match fixed.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (fixed.as_str(), None),
+    };
+
+    let mut grouped = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(locale.group_separator);
+        }
+        grouped.push(digit);
+    }
+
+    let mut result = String::new();
+    // Rounding can turn a tiny negative into zero; don't print "-0,00"
+    if value < 0.0 && fixed.chars().any(|c| c.is_ascii_digit() && c != '0') {
+        result.push('-');
+    }
+    result.push_str(&grouped);
+    if let Some(fraction) = fraction {
+        result.push(locale.decimal_separator);
+        result.push_str(fraction);
+    }
+    result
+}
+
+/// Parse `1.234,56` (de-DE) or `1,234.56` (en-US). Group separators are
+/// optional; a decimal separator in the wrong place is an error, not a guess.
+pub fn parse_number(text: &str, locale: &LocaleFormat) -> Result<f64> {
+    let mut normalized = String::new();
+    let mut seen_decimal = false;
+    for c in text.trim().chars() {
+        if c == locale.decimal_separator {
+            if seen_decimal {
+                bail!("'{}' has more than one decimal separator for {}", text, locale.tag);
+            }
+            seen_decimal = true;
+            normalized.push('.');
+        } else if c == locale.group_separator || c == ' ' || c == '\u{00A0}' || c == NNBSP {
+            if seen_decimal {
+                bail!("'{}' has a group separator after the decimal separator for {}", text, locale.tag);
+            }
+        } else if c.is_ascii_digit() || c == '-' || c == '+' {
+            normalized.push(c);
+        } else {
+            bail!("'{}' is not a {} number", text, locale.tag);
+        }
+    }
+    normalized
+        .parse::<f64>()
+        .map_err(|_| anyhow::anyhow!("'{}' is not a {} number", text, locale.tag))
+}
+
+/// Accepts RFC 3339 timestamps, `YYYY-MM-DD HH:MM:SS` and `YYYY-MM-DD`
+pub fn parse_date(text: &str) -> Result<NaiveDateTime> {
+    let text = text.trim();
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(text) {
+        return Ok(timestamp.naive_utc());
+    }
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S") {
+        return Ok(datetime);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).expect("midnight is a valid time"));
+    }
+    bail!("'{}' is not a date (expected YYYY-MM-DD or an RFC 3339 timestamp)", text)
+}
+
+/// strftime formatting with `%B`, `%b`, `%A` and `%a` translated for the locale
+pub fn format_date(datetime: &NaiveDateTime, pattern: &str, locale: &LocaleFormat) -> String {
+    use chrono::Datelike;
+
+    let pattern = if pattern.is_empty() { locale.date_pattern } else { pattern };
+    let month = locale.month_names[datetime.month0() as usize];
+    let day = locale.day_names[datetime.weekday().num_days_from_monday() as usize];
+
+    let mut localized = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            localized.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('B') => localized.push_str(month),
+            Some('b') => localized.extend(month.chars().take(3)),
+            Some('A') => localized.push_str(day),
+            Some('a') => localized.extend(day.chars().take(3)),
+            Some(other) => {
+                localized.push('%');
+                localized.push(other);
+            }
+            None => localized.push_str("%%"),
+        }
+    }
+    datetime.format(&localized).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_number_separators() {
+        let de = find_locale("de-DE").unwrap();
+        assert_eq!(format_number(1234567.891, 2, de), "1.234.567,89");
+        assert_eq!(format_number(-1234.5, 2, find_locale("en_US").unwrap()), "-1,234.50");
+        assert_eq!(format_number(1234.0, 0, find_locale("de-CH").unwrap()), "1'234");
+        assert_eq!(format_number(-0.001, 2, de), "0,00");
+    }
+
+    #[test]
+    fn test_format_money_uses_minor_units() {
+        let de = find_locale("de-DE").unwrap();
+        assert_eq!(format_money(1234.5, "EUR", None, de), "1.234,50 EUR");
+        assert_eq!(format_money(1234.6, "JPY", None, de), "1.235 JPY");
+        assert_eq!(format_money(99.999, "USD", Some(1), find_locale("en-US").unwrap()), "100.0 USD");
+        assert!(find_currency("usd").is_none());
+    }
+
+    #[test]
+    fn test_parse_number_round_trips() {
+        let de = find_locale("de").unwrap();
+        assert_eq!(parse_number("1.234,56", de).unwrap(), 1234.56);
+        assert_eq!(parse_number("1234,56", de).unwrap(), 1234.56);
+        assert!(parse_number("1,234.56", de).is_err());
+        assert_eq!(parse_number("1,234.56", find_locale("en-GB").unwrap()).unwrap(), 1234.56);
+    }
+
+    #[test]
+    fn test_format_date_localizes_names() {
+        let date = parse_date("2024-03-15").unwrap();
+        assert_eq!(format_date(&date, "%d. %B %Y", find_locale("de-DE").unwrap()), "15. März 2024");
+        assert_eq!(format_date(&date, "%A %d %b", find_locale("fr-FR").unwrap()), "vendredi 15 mar");
+        assert_eq!(format_date(&date, "", find_locale("en-US").unwrap()), "03/15/2024");
+    }
+
+    #[test]
+    fn test_unknown_locale_is_an_error() {
+        assert!(find_locale("xx-YY").is_err());
+    }
+}
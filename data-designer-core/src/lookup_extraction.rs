@@ -0,0 +1,233 @@
+//! Moving long inline lists into lookup tables
+//!
+//! A rule that tests membership in a long list of literals, say forty country
+//! codes in `country IN [...]`, is hard to review and has to be edited in
+//! every rule repeating the list. `inline_lists` finds such lists and
+//! `extract_lookup_table` moves one into a lookup table whose entries map each
+//! value to itself: `x IN [...]` becomes `HAS(LOOKUP(x, "table"))` and
+//! `x NOT_IN [...]` becomes `IS_NULL(LOOKUP(x, "table"))`.
+//!
+//! Only lists of string literals are offered. LOOKUP compares keys as text,
+//! so a list of numbers would start matching strings like `"5"` that IN
+//! never did.
+
+use crate::formatter::{format_expression, format_rewritten_rule};
+use crate::models::{BinaryOperator, Expression, Value};
+use crate::rule_rewrite::parse_complete;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The shortest list worth a table of its own
+pub const MIN_EXTRACTED_VALUES: usize = 10;
+
+/// A membership test against an inline list of string literals
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InlineList {
+    /// Position among the rule's extractable lists, in the order they appear
+    pub index: usize,
+    /// The expression tested for membership, formatted
+    pub subject: String,
+    pub values: Vec<String>,
+    pub negated: bool,
+    pub suggested_table: String,
+}
+
+/// A rule with one of its inline lists moved into `table`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LookupExtraction {
+    pub table: String,
+    pub entries: BTreeMap<String, String>,
+    /// The rewritten rule, with its comments kept
+    pub definition: String,
+}
+
+/// The lists in `definition` with at least `min_values` values
+pub fn inline_lists(definition: &str, min_values: usize) -> Result<Vec<InlineList>, String> {
+    Ok(inline_lists_in(&parse_complete(definition)?, min_values, |_| false))
+}
+
+/// `inline_lists` for a rule already parsed, suggesting only table names
+/// that `taken` doesn't already have
+pub fn inline_lists_in(expr: &Expression, min_values: usize, taken: impl Fn(&str) -> bool) -> Vec<InlineList> {
+    let mut lists = Vec::new();
+    collect_lists(expr, min_values, &taken, &mut lists);
+    lists
+}
+
+/// Moves list `index` of `definition`, as numbered by `inline_lists`, into a
+/// lookup table named `table`
+pub fn extract_lookup_table(
+    definition: &str,
+    index: usize,
+    table: &str,
+    min_values: usize,
+) -> Result<LookupExtraction, String> {
+    if !is_table_name(table) {
+        return Err(format!("'{}' is not a valid lookup table name", table));
+    }
+    let lists = inline_lists(definition, min_values)?;
+    let list = lists
+        .get(index)
+        .ok_or_else(|| format!("The rule has {} lists of {} or more values; there is no list {}", lists.len(), min_values, index))?;
+
+    let definition = format_rewritten_rule(definition, |mut expr| {
+        replace_list(&mut expr, index, min_values, table, &mut 0);
+        expr
+    })
+    .map_err(|e| e.to_string())?;
+    Ok(LookupExtraction {
+        table: table.to_string(),
+        entries: list.values.iter().map(|value| (value.clone(), value.clone())).collect(),
+        definition,
+    })
+}
+
+/// A name for the table holding the values `subject` is tested against:
+/// `country_code_values` for `Client.country_code`, numbered from 2 when
+/// `taken` already has it
+pub fn suggest_table_name(subject: &Expression, taken: impl Fn(&str) -> bool) -> String {
+    let stem = match subject {
+        Expression::Identifier(name) | Expression::Variable(name) => {
+            name.rsplit('.').next().unwrap_or(name).to_lowercase()
+        }
+        _ => "extracted".to_string(),
+    };
+    let base = format!("{}_values", stem);
+    if !taken(&base) {
+        return base;
+    }
+    (2..).map(|n| format!("{}_{}", base, n)).find(|name| !taken(name)).expect("some suffix is free")
+}
+
+fn is_table_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// The subject, the values and whether it is NOT_IN, for a list worth extracting
+fn membership_test(expr: &Expression, min_values: usize) -> Option<(&Expression, Vec<String>, bool)> {
+    let Expression::BinaryOp { left, op, right } = expr else {
+        return None;
+    };
+    let negated = match op {
+        BinaryOperator::In => false,
+        BinaryOperator::NotIn => true,
+        _ => return None,
+    };
+    let Expression::List(items) = right.as_ref() else {
+        return None;
+    };
+    let values: Vec<String> = items
+        .iter()
+        .map(|item| match item {
+            Expression::Literal(Value::String(value)) => Some(value.clone()),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    (values.len() >= min_values).then_some((left.as_ref(), values, negated))
+}
+
+fn collect_lists(expr: &Expression, min_values: usize, taken: &dyn Fn(&str) -> bool, lists: &mut Vec<InlineList>) {
+    if let Some((subject, values, negated)) = membership_test(expr, min_values) {
+        let suggested_table =
+            suggest_table_name(subject, |name| taken(name) || lists.iter().any(|list| list.suggested_table == name));
+        lists.push(InlineList {
+            index: lists.len(),
+            subject: format_expression(subject),
+            values,
+            negated,
+            suggested_table,
+        });
+    }
+    for child in expr.children() {
+        collect_lists(child, min_values, taken, lists);
+    }
+}
+
+// Walks in the same order as `collect_lists`, counting lists in `seen`
+fn replace_list(expr: &mut Expression, index: usize, min_values: usize, table: &str, seen: &mut usize) -> bool {
+    if let Some((subject, _, negated)) = membership_test(expr, min_values) {
+        if *seen == index {
+            let lookup = Expression::FunctionCall {
+                name: "LOOKUP".to_string(),
+                args: vec![subject.clone(), Expression::Literal(Value::String(table.to_string()))],
+            };
+            *expr = Expression::FunctionCall {
+                name: if negated { "IS_NULL" } else { "HAS" }.to_string(),
+                args: vec![lookup],
+            };
+            return true;
+        }
+        *seen += 1;
+    }
+    expr.children_mut().into_iter().any(|child| replace_list(child, index, min_values, table, seen))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::{evaluate_with_functions, FunctionLibrary};
+    use crate::parser::parse_rule;
+    use std::collections::HashMap;
+
+    const COUNTRIES: &str = r#"["AF", "BY", "CU", "IR", "KP", "MM", "RU", "SY", "VE", "YE"]"#;
+
+    #[test]
+    fn test_only_long_string_lists_are_offered() {
+        let definition = format!(
+            "flag = Client.country_code IN {} AND tier NOT_IN [\"A\", \"B\"] OR score IN [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]",
+            COUNTRIES
+        );
+        let lists = inline_lists(&definition, MIN_EXTRACTED_VALUES).unwrap();
+        assert_eq!(lists.len(), 1);
+        assert_eq!(lists[0].subject, "Client.country_code");
+        assert_eq!(lists[0].values.len(), 10);
+        assert!(!lists[0].negated);
+        assert_eq!(lists[0].suggested_table, "country_code_values");
+
+        let short = inline_lists(&definition, 2).unwrap();
+        assert_eq!(short.len(), 2);
+        assert!(short[1].negated);
+    }
+
+    #[test]
+    fn test_extraction_keeps_what_the_rule_computes() {
+        let definition = format!(
+            "# sanctioned jurisdictions\nblocked = country IN {} OR residence NOT_IN {}",
+            COUNTRIES, COUNTRIES
+        );
+        let lists = inline_lists(&definition, MIN_EXTRACTED_VALUES).unwrap();
+        assert_eq!(
+            lists.iter().map(|list| list.suggested_table.as_str()).collect::<Vec<_>>(),
+            ["country_values", "residence_values"]
+        );
+
+        let extraction = extract_lookup_table(&definition, 1, "sanctioned", MIN_EXTRACTED_VALUES).unwrap();
+        assert!(extraction.definition.starts_with("# sanctioned jurisdictions\n"));
+        let compact: String = extraction.definition.split_whitespace().collect();
+        assert!(compact.contains("ORIS_NULL(LOOKUP(residence,\"sanctioned\"))"));
+        assert!(compact.contains("countryIN[\"AF\""));
+        assert_eq!(extraction.entries.get("RU").map(String::as_str), Some("RU"));
+
+        let (_, before) = parse_rule(&definition).unwrap();
+        let (_, after) = parse_rule(&extraction.definition).unwrap();
+        let mut functions = FunctionLibrary::new();
+        functions.lookup_tables.insert("sanctioned".to_string(), extraction.entries.clone().into_iter().collect());
+        for (country, residence) in [("RU", "GB"), ("GB", "GB"), ("GB", "IR")] {
+            let facts = HashMap::from([
+                ("country".to_string(), Value::String(country.to_string())),
+                ("residence".to_string(), Value::String(residence.to_string())),
+            ]);
+            assert_eq!(
+                evaluate_with_functions(&after, &facts, &functions).unwrap(),
+                evaluate_with_functions(&before, &facts, &FunctionLibrary::new()).unwrap(),
+                "{} / {}",
+                country,
+                residence
+            );
+        }
+
+        assert!(extract_lookup_table(&definition, 2, "sanctioned", MIN_EXTRACTED_VALUES).is_err());
+        assert!(extract_lookup_table(&definition, 0, "bad name", MIN_EXTRACTED_VALUES).is_err());
+    }
+}
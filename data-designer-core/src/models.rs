@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 // Core expression evaluation types
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -12,6 +12,55 @@ pub enum Value {
     Null,
     Regex(String), // Added for regex support
     List(Vec<Value>), // Added for list support
+    Percent(f64), // Percentage points: 5% is Percent(5.0)
+    Money { amount: f64, currency: String }, // 1_000 USD; currency is an ISO 4217 code
+    Object(BTreeMap<String, Value>), // A record such as a transaction; fields are read as x.amount
+}
+
+impl Value {
+    /// Converts a JSON input, such as a fact from an API request. Objects
+    /// become records whose fields rules read with dotted paths.
+    pub fn from_json(json: &serde_json::Value) -> Value {
+        match json {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Boolean(*b),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Value::Integer(i),
+                None => Value::Float(n.as_f64().unwrap_or_default()),
+            },
+            serde_json::Value::String(s) => Value::String(s.clone()),
+            serde_json::Value::Array(items) => Value::List(items.iter().map(Value::from_json).collect()),
+            serde_json::Value::Object(fields) => {
+                Value::Object(fields.iter().map(|(name, value)| (name.clone(), Value::from_json(value))).collect())
+            }
+        }
+    }
+
+    /// Converts a result to JSON; percentages and money are kept as their literal text
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::String(s) | Value::Regex(s) => serde_json::Value::String(s.clone()),
+            Value::Integer(i) => serde_json::json!(i),
+            Value::Number(n) | Value::Float(n) => serde_json::json!(n),
+            Value::Boolean(b) => serde_json::Value::Bool(*b),
+            Value::Null => serde_json::Value::Null,
+            Value::List(items) => serde_json::Value::Array(items.iter().map(Value::to_json).collect()),
+            Value::Percent(p) => serde_json::Value::String(format!("{}%", p)),
+            Value::Money { amount, currency } => serde_json::Value::String(format!("{} {}", amount, currency)),
+            Value::Object(fields) => {
+                serde_json::Value::Object(fields.iter().map(|(name, value)| (name.clone(), value.to_json())).collect())
+            }
+        }
+    }
+
+    /// The value at a dotted path of record fields, `amount` or `owner.country`;
+    /// None when a field is missing or the value isn't a record
+    pub fn field(&self, path: &str) -> Option<&Value> {
+        path.split('.').try_fold(self, |value, name| match value {
+            Value::Object(fields) => fields.get(name),
+            _ => None,
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -46,6 +95,25 @@ pub enum Expression {
         expr: Box<Expression>,
         data_type: String,
     }, // Added for type casting
+    Lambda {
+        param: String,
+        body: Box<Expression>,
+    }, // Added for higher-order list functions: x -> x > 10
+    Case {
+        branches: Vec<(Expression, Expression)>,
+        else_expr: Option<Box<Expression>>,
+    }, // CASE WHEN cond THEN expr ... ELSE expr END
+    Range {
+        start: Box<Expression>,
+        end: Box<Expression>,
+        inclusive: bool,
+    }, // 1000..=5000 includes the end, 1000..5000 excludes it
+    Comprehension {
+        element: Box<Expression>,
+        var: String,
+        source: Box<Expression>,
+        condition: Option<Box<Expression>>,
+    }, // [x.amount FOR x IN transactions IF x.currency == "USD"]
     // Fund Accounting Workflow Verbs
     ConfigureSystem {
         capability_name: String,
@@ -69,6 +137,110 @@ pub enum Expression {
     },
 }
 
+impl Expression {
+    /// Direct sub-expressions, in the order they appear in the rule text
+    pub fn children(&self) -> Vec<&Expression> {
+        match self {
+            Expression::BinaryOp { left, right, .. } => vec![left, right],
+            Expression::Range { start, end, .. } => vec![start, end],
+            Expression::UnaryOp { operand, .. } => vec![operand],
+            Expression::Assignment { value, .. } => vec![value],
+            Expression::Cast { expr, .. } => vec![expr],
+            Expression::Lambda { body, .. } => vec![body],
+            Expression::Conditional { condition, then_expr, else_expr } => {
+                let mut parts: Vec<&Expression> = vec![condition, then_expr];
+                parts.extend(else_expr.as_deref());
+                parts
+            }
+            Expression::Case { branches, else_expr } => {
+                let mut parts: Vec<&Expression> = branches.iter().flat_map(|(condition, result)| [condition, result]).collect();
+                parts.extend(else_expr.as_deref());
+                parts
+            }
+            Expression::Comprehension { element, source, condition, .. } => {
+                let mut parts: Vec<&Expression> = vec![element, source];
+                parts.extend(condition.as_deref());
+                parts
+            }
+            Expression::FunctionCall { args: items, .. }
+            | Expression::List(items)
+            | Expression::ConfigureSystem { arguments: items, .. }
+            | Expression::Activate { arguments: items, .. }
+            | Expression::RunHealthCheck { arguments: items, .. }
+            | Expression::Workflow { steps: items, .. } => items.iter().collect(),
+            Expression::Literal(_) | Expression::Variable(_) | Expression::Identifier(_) | Expression::SetStatus { .. } => {
+                Vec::new()
+            }
+        }
+    }
+
+    /// `children`, mutably and in the same order
+    pub fn children_mut(&mut self) -> Vec<&mut Expression> {
+        match self {
+            Expression::BinaryOp { left, right, .. } => vec![left, right],
+            Expression::Range { start, end, .. } => vec![start, end],
+            Expression::UnaryOp { operand, .. } => vec![operand],
+            Expression::Assignment { value, .. } => vec![value],
+            Expression::Cast { expr, .. } => vec![expr],
+            Expression::Lambda { body, .. } => vec![body],
+            Expression::Conditional { condition, then_expr, else_expr } => {
+                let mut parts: Vec<&mut Expression> = vec![condition, then_expr];
+                parts.extend(else_expr.as_deref_mut());
+                parts
+            }
+            Expression::Case { branches, else_expr } => {
+                let mut parts: Vec<&mut Expression> =
+                    branches.iter_mut().flat_map(|(condition, result)| [condition, result]).collect();
+                parts.extend(else_expr.as_deref_mut());
+                parts
+            }
+            Expression::Comprehension { element, source, condition, .. } => {
+                let mut parts: Vec<&mut Expression> = vec![element, source];
+                parts.extend(condition.as_deref_mut());
+                parts
+            }
+            Expression::FunctionCall { args: items, .. }
+            | Expression::List(items)
+            | Expression::ConfigureSystem { arguments: items, .. }
+            | Expression::Activate { arguments: items, .. }
+            | Expression::RunHealthCheck { arguments: items, .. }
+            | Expression::Workflow { steps: items, .. } => items.iter_mut().collect(),
+            Expression::Literal(_) | Expression::Variable(_) | Expression::Identifier(_) | Expression::SetStatus { .. } => {
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CommentStyle {
+    Hash,        // # comment
+    DoubleSlash, // // comment
+    Block,       // /* comment */
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CommentPlacement {
+    Leading,  // Before the rule expression
+    Inline,   // Between tokens of the expression
+    Trailing, // After the rule expression
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleComment {
+    pub text: String,
+    pub style: CommentStyle,
+    pub placement: CommentPlacement,
+    pub offset: usize, // Byte offset in the rule source
+}
+
+/// A parsed rule together with the comments the parser skipped
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommentedRule {
+    pub expression: Expression,
+    pub comments: Vec<RuleComment>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum BinaryOperator {
     Add,
@@ -93,6 +265,7 @@ pub enum BinaryOperator {
     EndsWith,   // Added for string operations
     In,         // Added for list operations
     NotIn,      // Added for list operations
+    Between,    // Added for range checks: x BETWEEN lo AND hi
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
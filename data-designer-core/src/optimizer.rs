@@ -0,0 +1,227 @@
+//! AST simplification pass run before transpiling or evaluating
+//!
+//! Constant sub-expressions are folded with the evaluator itself, so an
+//! optimized rule always produces the same value as the original.
+
+use crate::evaluator::{evaluate, to_bool, Facts};
+use crate::models::{BinaryOperator, Expression, UnaryOperator, Value};
+
+impl Expression {
+    /// Fold constant sub-expressions (`100 + 25 * 2` becomes `150`), collapse
+    /// double negation and remove branches whose condition is constant
+    pub fn optimize(&self) -> Expression {
+        optimize(self)
+    }
+}
+
+fn optimize(expr: &Expression) -> Expression {
+    match expr {
+        Expression::BinaryOp { left, op, right } => {
+            let left = optimize(left);
+            let right = optimize(right);
+            if let Some(result) = short_circuit(*op, &left, &right) {
+                return result;
+            }
+            fold(Expression::BinaryOp {
+                left: Box::new(left),
+                op: *op,
+                right: Box::new(right),
+            })
+        }
+        Expression::UnaryOp { op, operand } => {
+            let operand = optimize(operand);
+            // NOT NOT x => x, - -x => x
+            if let Expression::UnaryOp { op: inner_op, operand: inner } = &operand {
+                if inner_op == op && matches!(op, UnaryOperator::Not | UnaryOperator::Minus) {
+                    return (**inner).clone();
+                }
+            }
+            fold(Expression::UnaryOp {
+                op: *op,
+                operand: Box::new(operand),
+            })
+        }
+        Expression::FunctionCall { name, args } => fold(Expression::FunctionCall {
+            name: name.clone(),
+            args: args.iter().map(optimize).collect(),
+        }),
+        Expression::Cast { expr, data_type } => fold(Expression::Cast {
+            expr: Box::new(optimize(expr)),
+            data_type: data_type.clone(),
+        }),
+        Expression::Conditional { condition, then_expr, else_expr } => {
+            let condition = optimize(condition);
+            if let Expression::Literal(value) = &condition {
+                return if to_bool(value) {
+                    optimize(then_expr)
+                } else {
+                    else_expr
+                        .as_ref()
+                        .map(|e| optimize(e))
+                        .unwrap_or(Expression::Literal(Value::Null))
+                };
+            }
+            Expression::Conditional {
+                condition: Box::new(condition),
+                then_expr: Box::new(optimize(then_expr)),
+                else_expr: else_expr.as_ref().map(|e| Box::new(optimize(e))),
+            }
+        }
+        Expression::Case { branches, else_expr } => {
+            // Drop WHENs that can never match; stop at the first that always does
+            let mut kept = Vec::new();
+            for (condition, result) in branches {
+                let condition = optimize(condition);
+                match &condition {
+                    Expression::Literal(value) if !to_bool(value) => continue,
+                    Expression::Literal(_) if kept.is_empty() => return optimize(result),
+                    Expression::Literal(_) => {
+                        return Expression::Case {
+                            branches: kept,
+                            else_expr: Some(Box::new(optimize(result))),
+                        };
+                    }
+                    _ => kept.push((condition, optimize(result))),
+                }
+            }
+            let else_expr = else_expr.as_ref().map(|e| optimize(e));
+            if kept.is_empty() {
+                return else_expr.unwrap_or(Expression::Literal(Value::Null));
+            }
+            Expression::Case {
+                branches: kept,
+                else_expr: else_expr.map(Box::new),
+            }
+        }
+        Expression::Assignment { target, value } => Expression::Assignment {
+            target: target.clone(),
+            value: Box::new(optimize(value)),
+        },
+        Expression::List(items) => Expression::List(items.iter().map(optimize).collect()),
+        Expression::Comprehension { element, var, source, condition } => Expression::Comprehension {
+            element: Box::new(optimize(element)),
+            var: var.clone(),
+            source: Box::new(optimize(source)),
+            condition: condition.as_ref().map(|c| Box::new(optimize(c))),
+        },
+        Expression::Range { start, end, inclusive } => Expression::Range {
+            start: Box::new(optimize(start)),
+            end: Box::new(optimize(end)),
+            inclusive: *inclusive,
+        },
+        _ => expr.clone(),
+    }
+}
+
+/// `false AND x` is false and `true OR x` is true whatever `x` is
+fn short_circuit(op: BinaryOperator, left: &Expression, right: &Expression) -> Option<Expression> {
+    let constant = |e: &Expression| match e {
+        Expression::Literal(value) => Some(to_bool(value)),
+        _ => None,
+    };
+    match op {
+        BinaryOperator::And if constant(left) == Some(false) || constant(right) == Some(false) => {
+            Some(Expression::Literal(Value::Boolean(false)))
+        }
+        BinaryOperator::Or if constant(left) == Some(true) || constant(right) == Some(true) => {
+            Some(Expression::Literal(Value::Boolean(true)))
+        }
+        _ => None,
+    }
+}
+
+/// Evaluate `expr` if all its operands are literals; errors (division by zero,
+/// unknown functions, lookups) leave the expression for runtime
+fn fold(expr: Expression) -> Expression {
+    let all_literal = match &expr {
+        Expression::BinaryOp { left, right, .. } => is_literal(left) && is_literal(right),
+        Expression::UnaryOp { operand, .. } => is_literal(operand),
+        Expression::FunctionCall { args, .. } => args.iter().all(is_literal),
+        Expression::Cast { expr, .. } => is_literal(expr),
+        _ => false,
+    };
+    if !all_literal {
+        return expr;
+    }
+
+    match evaluate(&expr, &Facts::new()) {
+        Ok(value) => Expression::Literal(value),
+        Err(_) => expr,
+    }
+}
+
+fn is_literal(expr: &Expression) -> bool {
+    matches!(expr, Expression::Literal(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_expression;
+
+    fn optimized(input: &str) -> Expression {
+        let (_, ast) = parse_expression(input).unwrap();
+        ast.optimize()
+    }
+
+    #[test]
+    fn test_folds_arithmetic() {
+        assert_eq!(optimized("100 + 25 * 2"), Expression::Literal(Value::Integer(150)));
+        assert_eq!(optimized("UPPER(\"gb\") & \"-\" & \"x\""), Expression::Literal(Value::String("GB-x".to_string())));
+    }
+
+    #[test]
+    fn test_folds_inside_non_constant_expressions() {
+        assert_eq!(
+            optimized("balance * (2 + 3)"),
+            Expression::BinaryOp {
+                left: Box::new(Expression::Identifier("balance".to_string())),
+                op: BinaryOperator::Multiply,
+                right: Box::new(Expression::Literal(Value::Integer(5))),
+            }
+        );
+    }
+
+    #[test]
+    fn test_collapses_double_negation() {
+        let expr = Expression::UnaryOp {
+            op: UnaryOperator::Not,
+            operand: Box::new(Expression::UnaryOp {
+                op: UnaryOperator::Not,
+                operand: Box::new(Expression::Identifier("is_active".to_string())),
+            }),
+        };
+        assert_eq!(expr.optimize(), Expression::Identifier("is_active".to_string()));
+    }
+
+    #[test]
+    fn test_removes_constant_branches() {
+        let expr = Expression::Conditional {
+            condition: Box::new(optimized("1 > 2")),
+            then_expr: Box::new(Expression::Identifier("a".to_string())),
+            else_expr: Some(Box::new(Expression::Identifier("b".to_string()))),
+        };
+        assert_eq!(expr.optimize(), Expression::Identifier("b".to_string()));
+        assert_eq!(optimized("x > 1 AND false"), Expression::Literal(Value::Boolean(false)));
+    }
+
+    #[test]
+    fn test_prunes_constant_case_branches() {
+        assert_eq!(
+            optimized("CASE WHEN 1 > 2 THEN a WHEN 2 > 1 THEN b ELSE c END"),
+            Expression::Identifier("b".to_string())
+        );
+        assert_eq!(
+            optimized("CASE WHEN x > 1 THEN a WHEN true THEN b ELSE c END"),
+            Expression::Case {
+                branches: vec![(optimized("x > 1"), Expression::Identifier("a".to_string()))],
+                else_expr: Some(Box::new(Expression::Identifier("b".to_string()))),
+            }
+        );
+    }
+
+    #[test]
+    fn test_leaves_runtime_errors_in_place() {
+        assert!(matches!(optimized("10 / 0"), Expression::BinaryOp { .. }));
+    }
+}
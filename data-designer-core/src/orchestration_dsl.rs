@@ -644,10 +644,13 @@ impl Value {
             Value::Boolean(b) => b.to_string(),
             Value::Null => "null".to_string(),
             Value::Regex(r) => r.clone(),
+            Value::Percent(p) => format!("{}%", p),
+            Value::Money { amount, currency } => format!("{} {}", amount, currency),
             Value::List(items) => format!("[{}]", items.iter()
                 .map(|v| v.to_string())
                 .collect::<Vec<_>>()
                 .join(", ")),
+            Value::Object(_) => self.to_json().to_string(),
         }
     }
 }
\ No newline at end of file
@@ -1,21 +1,37 @@
-use crate::models::{Expression, Value, BinaryOperator, UnaryOperator};
+use crate::error_codes::ErrorCode;
+use crate::locale;
+use crate::models::{CommentPlacement, CommentStyle, CommentedRule, Expression, RuleComment, Value, BinaryOperator, UnaryOperator};
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_while},
-    character::complete::{alpha1, alphanumeric1, char, digit1, multispace0, none_of},
-    combinator::{map, recognize, map_res, opt, value},
-    error::ParseError,
-    multi::{many0, separated_list0},
-    sequence::{delimited, pair, preceded, tuple},
+    bytes::complete::{tag, take_until, take_while, take_while_m_n},
+    character::complete::{alpha1, alphanumeric1, char, digit1, multispace1, none_of, satisfy, space0},
+    combinator::{map, not, recognize, map_res, opt, value, verify},
+    error::ParseError as NomParseError,
+    multi::{many0, many1, separated_list0},
+    sequence::{delimited, pair, preceded, terminated, tuple},
     IResult,
 };
+use std::cell::RefCell;
 
-// Whitespace wrapper
-fn ws<'a, F, O, E: ParseError<&'a str>>(inner: F) -> impl FnMut(&'a str) -> IResult<&'a str, O, E>
+// Whitespace wrapper; comments count as whitespace between tokens
+fn ws<'a, F, O, E: NomParseError<&'a str>>(inner: F) -> impl FnMut(&'a str) -> IResult<&'a str, O, E>
 where
     F: FnMut(&'a str) -> IResult<&'a str, O, E>,
 {
-    delimited(multispace0, inner, multispace0)
+    delimited(trivia, inner, trivia)
+}
+
+// Parse comments: # line, // line and /* block */
+fn parse_comment<'a, E: NomParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    alt((
+        recognize(pair(alt((tag("#"), tag("//"))), take_while(|c| c != '\n'))),
+        recognize(tuple((tag("/*"), take_until("*/"), tag("*/")))),
+    ))(input)
+}
+
+// Whitespace and comments
+fn trivia<'a, E: NomParseError<&'a str>>(input: &'a str) -> IResult<&'a str, (), E> {
+    value((), many0(alt((multispace1, parse_comment))))(input)
 }
 
 // Parse identifiers (variables, function names)
@@ -23,27 +39,85 @@ fn parse_identifier(input: &str) -> IResult<&str, String> {
     map(
         recognize(pair(
             alt((alpha1, tag("_"))),
-            many0(alt((alphanumeric1, tag("_"), tag(".")))),
+            // A dot followed by another is a range (lo..hi), not a path
+            many0(alt((alphanumeric1, tag("_"), terminated(tag("."), not(char('.')))))),
         )),
         String::from,
     )(input)
 }
 
-// Parse integers and floats
+// Parse integers and floats; `_` can separate digit groups (`1_000_000`).
+// A `%` straight after the number makes it a percentage and a currency code
+// after it an amount of money: `5%`, `1_000 USD`.
 fn parse_number(input: &str) -> IResult<&str, Value> {
-    map_res(
+    let (rest, number) = map_res(
         recognize(tuple((
             opt(char('-')),
-            digit1,
-            opt(tuple((char('.'), digit1))),
+            digit_groups,
+            opt(tuple((char('.'), digit_groups))),
         ))),
         |s: &str| {
+            let s = s.replace('_', "");
             if s.contains('.') {
                 s.parse::<f64>().map(Value::Float).map_err(|_| "Invalid float")
             } else {
                 s.parse::<i64>().map(Value::Integer).map_err(|_| "Invalid integer")
             }
         },
+    )(input)?;
+
+    let amount = match number {
+        Value::Integer(i) => i as f64,
+        Value::Float(f) => f,
+        _ => unreachable!("numbers parse as integers or floats"),
+    };
+    if let Ok((rest, _)) = percent_sign(rest) {
+        return Ok((rest, Value::Percent(amount)));
+    }
+    if let Ok((rest, code)) = currency_code(rest) {
+        return Ok((rest, Value::Money { amount, currency: code.to_string() }));
+    }
+    Ok((rest, number))
+}
+
+fn digit_groups(input: &str) -> IResult<&str, &str> {
+    recognize(pair(digit1, many0(preceded(char('_'), digit1))))(input)
+}
+
+// Words that can follow a value, so `5% AND x` is a percentage rather than
+// `5 % AND`, and a line starting with one continues the rule above it
+const KEYWORDS_AFTER_VALUE: &[&str] = &[
+    "AND", "OR", "THEN", "ELSE", "WHEN", "END", "IN", "NOT", "NOT_IN", "BETWEEN", "BETWEEN_EXCLUSIVE",
+    "MATCHES", "NOT_MATCHES", "CONTAINS", "STARTS_WITH", "ENDS_WITH",
+];
+
+// `5%` is a percentage, but `5%3`, `5% x` and `5% (x)` are modulo
+fn percent_sign(input: &str) -> IResult<&str, char> {
+    let (rest, sign) = char('%')(input)?;
+    let next = rest.trim_start_matches([' ', '\t']);
+    let word = next.split(|c: char| !(c.is_alphanumeric() || c == '_')).next().unwrap_or("");
+    let operand_follows = if word.is_empty() {
+        next.starts_with(['(', '"', '\'', '['])
+    } else {
+        !KEYWORDS_AFTER_VALUE.contains(&word)
+    };
+    if operand_follows {
+        return Err(nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Verify)));
+    }
+    Ok((rest, sign))
+}
+
+// Only supported ISO 4217 codes, so `10 AND x` stays a conjunction
+fn currency_code(input: &str) -> IResult<&str, &str> {
+    verify(
+        preceded(
+            space0,
+            terminated(
+                take_while_m_n(3, 3, |c: char| c.is_ascii_uppercase()),
+                not(satisfy(|c| c.is_alphanumeric() || c == '_' || c == '.')),
+            ),
+        ),
+        |code: &str| locale::find_currency(code).is_some(),
     )(input)
 }
 
@@ -74,6 +148,55 @@ fn parse_string_literal(input: &str) -> IResult<&str, Value> {
     ))(input)
 }
 
+// Parse template strings: `Rate: ${ (base_rate + 1) * 100 }%`
+// Lowered to & concatenation, so `${x}` alone still produces a string
+fn parse_template_string(input: &str) -> IResult<&str, Expression> {
+    map(
+        delimited(
+            char('`'),
+            many0(alt((
+                delimited(tag("${"), ws(parse_expression), char('}')),
+                map(parse_template_text, |text| Expression::Literal(Value::String(text))),
+            ))),
+            char('`'),
+        ),
+        |parts| {
+            let mut parts = parts.into_iter();
+            let first = match parts.next() {
+                Some(Expression::Literal(Value::String(text))) => Expression::Literal(Value::String(text)),
+                Some(expr) => Expression::BinaryOp {
+                    left: Box::new(Expression::Literal(Value::String(String::new()))),
+                    op: BinaryOperator::Concat,
+                    right: Box::new(expr),
+                },
+                None => Expression::Literal(Value::String(String::new())),
+            };
+            parts.fold(first, |acc, part| Expression::BinaryOp {
+                left: Box::new(acc),
+                op: BinaryOperator::Concat,
+                right: Box::new(part),
+            })
+        },
+    )(input)
+}
+
+// Literal text between template interpolations; \` and \$ escape the delimiters
+fn parse_template_text(input: &str) -> IResult<&str, String> {
+    map(
+        many1(alt((
+            map(tag("\\`"), |_| '`'),
+            map(tag("\\$"), |_| '$'),
+            map(tag("\\\\"), |_| '\\'),
+            map(tag("\\n"), |_| '\n'),
+            map(tag("\\t"), |_| '\t'),
+            terminated(char('$'), not(char('{'))),
+            none_of("`$\\"),
+            char('\\'),
+        ))),
+        |chars| chars.into_iter().collect(),
+    )(input)
+}
+
 // Parse regex literals: /pattern/ or r"pattern"
 fn parse_regex_literal(input: &str) -> IResult<&str, Value> {
     alt((
@@ -118,13 +241,48 @@ fn parse_list(input: &str) -> IResult<&str, Expression> {
     )(input)
 }
 
+// Parse list comprehensions: [x.amount FOR x IN transactions IF x.currency == "USD"]
+fn parse_comprehension(input: &str) -> IResult<&str, Expression> {
+    map(
+        tuple((
+            ws(char('[')),
+            parse_expression,
+            preceded(ws(keyword("FOR")), ws(parse_identifier)),
+            preceded(ws(keyword("IN")), parse_expression),
+            opt(preceded(ws(keyword("IF")), parse_expression)),
+            ws(char(']')),
+        )),
+        |(_, element, var, source, condition, _)| Expression::Comprehension {
+            element: Box::new(element),
+            var,
+            source: Box::new(source),
+            condition: condition.map(Box::new),
+        },
+    )(input)
+}
+
+// Parse lambda arguments: x -> expr
+fn parse_lambda(input: &str) -> IResult<&str, Expression> {
+    map(
+        tuple((
+            ws(parse_identifier),
+            ws(tag("->")),
+            parse_expression,
+        )),
+        |(param, _, body)| Expression::Lambda {
+            param,
+            body: Box::new(body),
+        },
+    )(input)
+}
+
 // Parse function calls: FUNC(arg1, arg2, ...)
 fn parse_function_call(input: &str) -> IResult<&str, Expression> {
     map(
         tuple((
             parse_identifier,
             ws(char('(')),
-            separated_list0(ws(char(',')), parse_expression),
+            separated_list0(ws(char(',')), alt((parse_lambda, parse_expression))),
             ws(char(')')),
         )),
         |(name, _, args, _)| Expression::FunctionCall { name, args },
@@ -267,6 +425,30 @@ fn parse_conditional(input: &str) -> IResult<&str, Expression> {
     )(input)
 }
 
+// Parse CASE WHEN cond THEN expr ... [ELSE expr] END
+fn parse_case(input: &str) -> IResult<&str, Expression> {
+    map(
+        tuple((
+            ws(keyword("CASE")),
+            many1(tuple((
+                preceded(ws(keyword("WHEN")), parse_expression),
+                preceded(ws(keyword("THEN")), parse_expression),
+            ))),
+            opt(preceded(ws(keyword("ELSE")), parse_expression)),
+            ws(keyword("END")),
+        )),
+        |(_, branches, else_expr, _)| Expression::Case {
+            branches,
+            else_expr: else_expr.map(Box::new),
+        },
+    )(input)
+}
+
+// Keyword that is not the prefix of a longer identifier (END vs END_DATE)
+fn keyword<'a>(word: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    terminated(tag(word), not(alt((alphanumeric1, tag("_"), tag(".")))))
+}
+
 // Parse WHEN...THEN...ELSE patterns
 fn parse_when_then(input: &str) -> IResult<&str, Expression> {
     map(
@@ -285,7 +467,7 @@ fn parse_when_then(input: &str) -> IResult<&str, Expression> {
 
 // Parse primary expressions (literals, identifiers, parentheses)
 fn parse_primary(input: &str) -> IResult<&str, Expression> {
-    ws(alt((
+    ws(recorded(alt((
         // Fund Accounting Workflow Verbs (must come before function calls)
         parse_configure_system,
         parse_activate,
@@ -296,12 +478,15 @@ fn parse_primary(input: &str) -> IResult<&str, Expression> {
         // Literals
         map(parse_number, Expression::Literal),
         map(parse_string_literal, Expression::Literal),
+        parse_template_string,
         map(parse_regex_literal, Expression::Literal),
         map(parse_boolean, Expression::Literal),
         map(parse_null, Expression::Literal),
 
         // Complex expressions
+        parse_comprehension,
         parse_list,
+        parse_case,
         parse_conditional,
         parse_when_then,
         parse_function_call,
@@ -311,7 +496,27 @@ fn parse_primary(input: &str) -> IResult<&str, Expression> {
 
         // Parenthesized expression
         delimited(ws(char('(')), parse_expression, ws(char(')'))),
-    )))(input)
+    ))))(input)
+}
+
+thread_local! {
+    // Primaries parsed while `parse_rule_with_spans` runs, as the input
+    // length before and after each
+    static PRIMARY_SPANS: RefCell<Option<Vec<(usize, usize, Expression)>>> = const { RefCell::new(None) };
+}
+
+fn recorded<'a>(
+    mut inner: impl FnMut(&'a str) -> IResult<&'a str, Expression>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Expression> {
+    move |input| {
+        let (rest, expr) = inner(input)?;
+        PRIMARY_SPANS.with(|spans| {
+            if let Some(spans) = spans.borrow_mut().as_mut() {
+                spans.push((input.len(), rest.len(), expr.clone()));
+            }
+        });
+        Ok((rest, expr))
+    }
 }
 
 // Parse unary expressions: NOT expr, -expr, +expr
@@ -414,28 +619,67 @@ fn parse_concatenation(input: &str) -> IResult<&str, Expression> {
     })))
 }
 
+// Parse range literals: lo..=hi includes hi, lo..hi leaves it out
+fn parse_range_or_concatenation(input: &str) -> IResult<&str, Expression> {
+    let (input, start) = parse_concatenation(input)?;
+    let (input, end) = opt(pair(ws(alt((tag("..="), tag("..")))), parse_concatenation))(input)?;
+
+    Ok((input, match end {
+        Some((dots, end)) => Expression::Range {
+            start: Box::new(start),
+            end: Box::new(end),
+            inclusive: dots == "..=",
+        },
+        None => start,
+    }))
+}
+
+// Parse the right side of x BETWEEN lo AND hi; BETWEEN_EXCLUSIVE leaves out hi
+fn parse_between(input: &str) -> IResult<&str, Expression> {
+    map(
+        tuple((
+            ws(alt((
+                value(false, keyword("BETWEEN_EXCLUSIVE")),
+                value(true, keyword("BETWEEN")),
+            ))),
+            parse_concatenation,
+            ws(keyword("AND")),
+            parse_concatenation,
+        )),
+        |(inclusive, start, _, end)| Expression::Range {
+            start: Box::new(start),
+            end: Box::new(end),
+            inclusive,
+        },
+    )(input)
+}
+
 // Parse comparison operations
 fn parse_comparison(input: &str) -> IResult<&str, Expression> {
     let (input, left) = parse_concatenation(input)?;
-    let (input, operation) = opt(tuple((
-        ws(alt((
-            value(BinaryOperator::Matches, tag("MATCHES")),
-            value(BinaryOperator::NotMatches, tag("NOT_MATCHES")),
-            value(BinaryOperator::Contains, tag("CONTAINS")),
-            value(BinaryOperator::StartsWith, tag("STARTS_WITH")),
-            value(BinaryOperator::EndsWith, tag("ENDS_WITH")),
-            value(BinaryOperator::In, tag("IN")),
-            value(BinaryOperator::NotIn, tag("NOT_IN")),
-            value(BinaryOperator::LessThanOrEqual, tag("<=")),
-            value(BinaryOperator::GreaterThanOrEqual, tag(">=")),
-            value(BinaryOperator::NotEquals, tag("!=")),
-            value(BinaryOperator::NotEquals, tag("<>")),
-            value(BinaryOperator::Equals, tag("==")),
-            value(BinaryOperator::Equals, tag("=")),
-            value(BinaryOperator::LessThan, tag("<")),
-            value(BinaryOperator::GreaterThan, tag(">")),
-        ))),
-        parse_concatenation,
+    let (input, operation) = opt(alt((
+        map(parse_between, |range| (BinaryOperator::Between, range)),
+        tuple((
+            ws(alt((
+                value(BinaryOperator::Matches, tag("MATCHES")),
+                value(BinaryOperator::NotMatches, tag("NOT_MATCHES")),
+                value(BinaryOperator::Contains, tag("CONTAINS")),
+                value(BinaryOperator::StartsWith, tag("STARTS_WITH")),
+                value(BinaryOperator::EndsWith, tag("ENDS_WITH")),
+                value(BinaryOperator::In, keyword("IN")),
+                value(BinaryOperator::NotIn, keyword("NOT_IN")),
+                value(BinaryOperator::NotIn, tuple((tag("NOT"), multispace1, keyword("IN")))),
+                value(BinaryOperator::LessThanOrEqual, tag("<=")),
+                value(BinaryOperator::GreaterThanOrEqual, tag(">=")),
+                value(BinaryOperator::NotEquals, tag("!=")),
+                value(BinaryOperator::NotEquals, tag("<>")),
+                value(BinaryOperator::Equals, tag("==")),
+                value(BinaryOperator::Equals, tag("=")),
+                value(BinaryOperator::LessThan, tag("<")),
+                value(BinaryOperator::GreaterThan, tag(">")),
+            ))),
+            parse_range_or_concatenation,
+        )),
     )))(input)?;
 
     Ok((input, match operation {
@@ -492,7 +736,334 @@ pub fn parse_expression(input: &str) -> IResult<&str, Expression> {
 
 // Main entry point for parsing rules
 pub fn parse_rule(input: &str) -> IResult<&str, Expression> {
-    delimited(multispace0, parse_expression, multispace0)(input)
+    delimited(trivia, parse_expression, trivia)(input)
+}
+
+// Parse a parameterized rule, `RULE risk_check(threshold) risk_flag = score > threshold`,
+// into its name, parameter names and body
+pub fn parse_rule_template(input: &str) -> IResult<&str, (String, Vec<String>, Expression)> {
+    map(
+        tuple((
+            preceded(trivia, keyword("RULE")),
+            ws(parse_identifier),
+            ws(char('(')),
+            separated_list0(ws(char(',')), ws(parse_identifier)),
+            ws(char(')')),
+            terminated(parse_expression, trivia),
+        )),
+        |(_, name, _, parameters, _, body)| (name, parameters, body),
+    )(input)
+}
+
+/// A primary expression (literal, attribute, call, list, CASE, parenthesised
+/// expression...) and the byte range of the rule source it was parsed from
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpressionSpan {
+    pub expression: Expression,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Parse a rule like `parse_rule`, also returning where each primary
+/// expression came from, in the order they were parsed. Alternatives the
+/// parser backtracked out of leave spans too, so match them against the AST
+/// in order rather than taking every span as part of the result.
+pub fn parse_rule_with_spans(input: &str) -> IResult<&str, (Expression, Vec<ExpressionSpan>)> {
+    PRIMARY_SPANS.with(|spans| *spans.borrow_mut() = Some(Vec::new()));
+    let result = parse_rule(input);
+    let recorded = PRIMARY_SPANS.with(|spans| spans.borrow_mut().take()).unwrap_or_default();
+    let (rest, expr) = result?;
+    // Some primaries take the whitespace around their own tokens, e.g. after `)`
+    let spans = recorded
+        .into_iter()
+        .map(|(before, after, expression)| {
+            let text = &input[input.len() - before..input.len() - after];
+            let start = input.len() - before + (text.len() - text.trim_start().len());
+            ExpressionSpan { expression, start, end: start + text.trim().len() }
+        })
+        .collect();
+    Ok((rest, (expr, spans)))
+}
+
+/// An expression, the byte range of the rule source it was parsed from and
+/// its sub-expressions likewise. Parts the source has no text for, such as
+/// the empty strings a template literal is joined with, are left out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedExpression {
+    pub expression: Expression,
+    pub start: usize,
+    pub end: usize,
+    pub children: Vec<SpannedExpression>,
+}
+
+impl SpannedExpression {
+    /// The innermost sub-expression whose source covers byte `offset`
+    pub fn at(&self, offset: usize) -> Option<&SpannedExpression> {
+        if offset < self.start || offset >= self.end {
+            return None;
+        }
+        Some(self.children.iter().find_map(|child| child.at(offset)).unwrap_or(self))
+    }
+}
+
+/// Parse a rule into a `SpannedExpression` covering the whole rule. A
+/// sub-expression's span is the one the parser recorded for it, or else
+/// the extent of its parts, so an operator belongs to its BinaryOp.
+pub fn parse_rule_spanned(input: &str) -> IResult<&str, SpannedExpression> {
+    let (rest, (expr, spans)) = parse_rule_with_spans(input)?;
+    let consumed = &input[..input.len() - rest.len()];
+    let start = consumed.len() - consumed.trim_start().len();
+    let end = consumed.trim_end().len();
+    let mut cursor = start;
+    let children = expr
+        .children()
+        .into_iter()
+        .filter_map(|child| span_expression(child, &spans, start..end, &mut cursor))
+        .collect();
+    Ok((rest, SpannedExpression { expression: expr, start, end, children }))
+}
+
+// Recorded spans include ones from alternatives the parser backtracked out
+// of, so each one is taken only at or after the previous sibling's end
+fn span_expression(
+    expr: &Expression,
+    spans: &[ExpressionSpan],
+    within: std::ops::Range<usize>,
+    cursor: &mut usize,
+) -> Option<SpannedExpression> {
+    let recorded = spans
+        .iter()
+        .find(|span| span.start >= *cursor && span.end <= within.end && span.expression == *expr)
+        .map(|span| span.start..span.end);
+
+    let child_within = recorded.clone().unwrap_or(within);
+    let mut child_cursor = child_within.start.max(*cursor);
+    let children: Vec<SpannedExpression> = expr
+        .children()
+        .into_iter()
+        .filter_map(|child| span_expression(child, spans, child_within.clone(), &mut child_cursor))
+        .collect();
+    let parts = children.iter().map(|child| child.start).min().zip(children.iter().map(|child| child.end).max());
+
+    let (start, end) = recorded.map(|span| (span.start, span.end)).or(parts)?;
+    *cursor = end;
+    Some(SpannedExpression { expression: expr.clone(), start, end, children })
+}
+
+// Parse a rule and keep its comments, classified as leading (before the
+// expression), trailing (after it) or inline, so they can be re-emitted
+pub fn parse_rule_with_comments(input: &str) -> IResult<&str, CommentedRule> {
+    let (remaining, expression) = parse_rule(input)?;
+    let (body, _) = trivia::<nom::error::Error<&str>>(input)?;
+    let body_start = input.len() - body.len();
+
+    // Only the consumed text: later rules in the same document keep their own comments
+    let consumed = &input[..input.len() - remaining.len()];
+    let comments = scan_comments(consumed)
+        .into_iter()
+        .map(|(offset, text)| {
+            let after = &input[offset + text.len()..];
+            let placement = if offset < body_start {
+                CommentPlacement::Leading
+            } else if trivia::<nom::error::Error<&str>>(after).is_ok_and(|(rest, _)| rest.len() == remaining.len()) {
+                CommentPlacement::Trailing
+            } else {
+                CommentPlacement::Inline
+            };
+            rule_comment(offset, text, placement)
+        })
+        .collect();
+
+    Ok((remaining, CommentedRule { expression, comments }))
+}
+
+// Parse input that holds only comments and whitespace, e.g. the tail of a
+// document after its last rule or a file with just a header
+pub fn parse_comments(input: &str) -> IResult<&str, Vec<RuleComment>> {
+    let (remaining, _) = trivia(input)?;
+    let consumed = &input[..input.len() - remaining.len()];
+    let comments = scan_comments(consumed)
+        .into_iter()
+        .map(|(offset, text)| rule_comment(offset, text, CommentPlacement::Leading))
+        .collect();
+    Ok((remaining, comments))
+}
+
+/// Where `parse_rules_recovering` found a rule it could not parse. `offset`
+/// is a byte offset into the document; `line` and `column` are zero-based,
+/// as editors expect.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// `UnexpectedText` for text after a complete rule on its line, `Syntax` otherwise
+    pub code: ErrorCode,
+    pub message: String,
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    /// Where parsing resumed; the text from `offset` up to here was skipped
+    pub resume_offset: usize,
+}
+
+/// A rule parsed by `parse_rules_recovering` and the byte span it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedRule {
+    pub expression: Expression,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Parse every rule in a document without giving up at the first broken one.
+/// A rule that doesn't parse becomes an error, keeping whatever prefix of it
+/// did parse as a partial rule, and parsing resumes at the next statement
+/// boundary: a blank line, or a line starting in column zero that doesn't
+/// continue the rule above (`)`, END, ELSE, AND, operators and the like).
+pub fn parse_rules_recovering(source: &str) -> (Vec<ParsedRule>, Vec<ParseError>) {
+    let mut rules = Vec::new();
+    let mut errors = Vec::new();
+    let mut rest = source;
+    let mut previous_end = None;
+
+    loop {
+        let body = trivia::<nom::error::Error<&str>>(rest).map_or(rest, |(body, _)| body);
+        if body.is_empty() {
+            break;
+        }
+        let start = source.len() - body.len();
+
+        // What the rule before left over: more text on its line, or a
+        // continuation line such as `WHEN ...` that it didn't take
+        let after_rule = previous_end.is_some_and(|end| !source[end..start].contains('\n'));
+        let leftover = after_rule || !starts_statement(body);
+        let parsed = if leftover {
+            Err(start)
+        } else {
+            parse_expression(body).map_err(|e| match e {
+                nom::Err::Error(e) | nom::Err::Failure(e) => source.len() - e.input.len(),
+                nom::Err::Incomplete(_) => start,
+            })
+        };
+
+        match parsed {
+            Ok((remaining, expression)) => {
+                let end = start + body[..body.len() - remaining.len()].trim_end().len();
+                rules.push(ParsedRule { expression, start, end });
+                previous_end = Some(end);
+                rest = remaining;
+            }
+            Err(failed_at) => {
+                let resume_offset = next_statement(source, start);
+                let line_start = source[..failed_at].rfind('\n').map_or(0, |i| i + 1);
+                let unexpected = source[failed_at..].lines().next().unwrap_or("").trim();
+                errors.push(ParseError {
+                    code: if after_rule { ErrorCode::UnexpectedText } else { ErrorCode::Syntax },
+                    message: if (leftover || failed_at > start) && !unexpected.is_empty() {
+                        format!("Unexpected '{}'", unexpected)
+                    } else {
+                        format!("Cannot parse '{}'", source[start..].lines().next().unwrap_or("").trim_end())
+                    },
+                    offset: failed_at,
+                    line: source[..failed_at].matches('\n').count(),
+                    column: failed_at - line_start,
+                    resume_offset,
+                });
+                previous_end = None;
+                rest = &source[resume_offset..];
+            }
+        }
+    }
+
+    (rules, errors)
+}
+
+// Start of the first line after the one holding `offset` that can begin a rule
+fn next_statement(source: &str, offset: usize) -> usize {
+    let mut line_start = offset;
+    loop {
+        match source[line_start..].find('\n') {
+            Some(newline) => line_start += newline + 1,
+            None => return source.len(),
+        }
+        let line = source[line_start..].lines().next().unwrap_or("");
+        if starts_statement(line) {
+            return line_start;
+        }
+    }
+}
+
+fn starts_statement(line: &str) -> bool {
+    let Some(first) = line.chars().next() else {
+        return true;
+    };
+    if line.trim().is_empty() {
+        return true;
+    }
+    if first.is_alphanumeric() || first == '_' {
+        let word = line.split(|c: char| !(c.is_alphanumeric() || c == '_')).next().unwrap_or("");
+        return !KEYWORDS_AFTER_VALUE.contains(&word);
+    }
+    matches!(first, '(' | '[' | '"' | '\'' | '`' | '#') || line.starts_with("//") || line.starts_with("/*")
+}
+
+fn rule_comment(offset: usize, text: &str, placement: CommentPlacement) -> RuleComment {
+    let (style, body) = if let Some(line) = text.strip_prefix('#') {
+        (CommentStyle::Hash, line)
+    } else if let Some(line) = text.strip_prefix("//") {
+        (CommentStyle::DoubleSlash, line)
+    } else {
+        (CommentStyle::Block, &text[2..text.len() - 2])
+    };
+    RuleComment {
+        text: body.trim().to_string(),
+        style,
+        placement,
+        offset,
+    }
+}
+
+const WORD_OPERATORS: &[&str] = &[
+    "MATCHES", "NOT_MATCHES", "CONTAINS", "STARTS_WITH", "ENDS_WITH", "IN", "NOT_IN", "BETWEEN", "BETWEEN_EXCLUSIVE",
+    "AND", "OR", "NOT", "IF", "THEN", "ELSE", "CASE", "WHEN",
+];
+
+// Find comments outside string, template and regex literals.
+// A `/` after an operand is division, anywhere else it may start a regex.
+fn scan_comments(input: &str) -> Vec<(usize, &str)> {
+    let mut comments = Vec::new();
+    let mut rest = input;
+    let mut after_operand = false;
+
+    while let Some(c) = rest.chars().next() {
+        if let Ok((after, text)) = parse_comment::<nom::error::Error<&str>>(rest) {
+            comments.push((input.len() - rest.len(), text));
+            rest = after;
+            continue;
+        }
+        let literal_end = match c {
+            '"' | '\'' => parse_string_literal(rest).ok().map(|(after, _)| after),
+            '`' => parse_template_string(rest).ok().map(|(after, _)| after),
+            '/' if !after_operand => parse_regex_literal(rest).ok().map(|(after, _)| after),
+            _ => None,
+        };
+        if let Some(after) = literal_end {
+            rest = after;
+            after_operand = true;
+            continue;
+        }
+        if c.is_alphanumeric() || c == '_' {
+            let word_len = rest
+                .find(|w: char| !(w.is_alphanumeric() || w == '_' || w == '.'))
+                .unwrap_or(rest.len());
+            // `x MATCHES /re/`: a keyword operator is not an operand
+            after_operand = !WORD_OPERATORS.contains(&&rest[..word_len]);
+            rest = &rest[word_len..];
+            continue;
+        }
+        if !c.is_whitespace() {
+            after_operand = matches!(c, ')' | ']');
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    comments
 }
 
 #[cfg(test)]
@@ -523,6 +1094,79 @@ mod tests {
         println!("Parsed: {:?}", result);
     }
 
+    #[test]
+    fn test_case_expression() {
+        let input = r#"CASE WHEN balance >= 100000 THEN "gold" WHEN balance >= 10000 THEN "silver" ELSE "bronze" END"#;
+        let (remaining, result) = parse_rule(input).unwrap();
+        assert!(remaining.is_empty());
+        match result {
+            Expression::Case { branches, else_expr } => {
+                assert_eq!(branches.len(), 2);
+                assert_eq!(else_expr.as_deref(), Some(&Expression::Literal(Value::String("bronze".to_string()))));
+            }
+            other => panic!("expected CASE, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_case_keywords_do_not_swallow_identifiers() {
+        let (remaining, result) = parse_rule("CASE WHEN x > 1 THEN END_DATE END").unwrap();
+        assert!(remaining.is_empty());
+        assert!(matches!(result, Expression::Case { else_expr: None, .. }));
+        assert!(matches!(parse_rule("CASE_ID").unwrap().1, Expression::Identifier(_)));
+    }
+
+    #[test]
+    fn test_template_string_lowers_to_concat() {
+        let (remaining, result) = parse_rule("`Rate: ${ (base_rate + 1) * 100 }%`").unwrap();
+        assert!(remaining.is_empty());
+        match result {
+            Expression::BinaryOp { left, op: BinaryOperator::Concat, right } => {
+                assert_eq!(*right, Expression::Literal(Value::String("%".to_string())));
+                assert!(matches!(*left, Expression::BinaryOp { op: BinaryOperator::Concat, .. }));
+            }
+            other => panic!("expected concatenation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_template_string_escapes() {
+        let result = parse_rule(r"`costs \$5 and \`quoted\``").unwrap().1;
+        assert_eq!(result, Expression::Literal(Value::String("costs $5 and `quoted`".to_string())));
+        let result = parse_rule("`$ ${x}`").unwrap().1;
+        assert!(matches!(result, Expression::BinaryOp { op: BinaryOperator::Concat, .. }));
+    }
+
+    #[test]
+    fn test_comments_are_skipped() {
+        let input = "# Net amount\nprice * quantity // before tax\n  - /* flat */ discount";
+        let (remaining, result) = parse_rule(input).unwrap();
+        assert!(remaining.is_empty());
+        assert!(matches!(result, Expression::BinaryOp { op: BinaryOperator::Subtract, .. }));
+    }
+
+    #[test]
+    fn test_comments_are_preserved_with_placement() {
+        let input = "/* Tiered fee */\nbalance * /* bps */ 0.002 # per annum";
+        let (_, rule) = parse_rule_with_comments(input).unwrap();
+        let comments: Vec<_> = rule.comments.iter().map(|c| (c.text.as_str(), &c.placement)).collect();
+        assert_eq!(
+            comments,
+            vec![
+                ("Tiered fee", &CommentPlacement::Leading),
+                ("bps", &CommentPlacement::Inline),
+                ("per annum", &CommentPlacement::Trailing),
+            ]
+        );
+        assert_eq!(rule.expression, parse_rule("balance * 0.002").unwrap().1);
+    }
+
+    #[test]
+    fn test_hash_inside_literals_is_not_a_comment() {
+        let (_, rule) = parse_rule_with_comments(r##"ref MATCHES /^#\d+/ AND tag == "#vip""##).unwrap();
+        assert!(rule.comments.is_empty());
+    }
+
     #[test]
     fn test_regex() {
         let result = parse_rule("email MATCHES /^[\\w]+@[\\w]+\\.[\\w]+$/").unwrap().1;
@@ -535,6 +1179,129 @@ mod tests {
         println!("Parsed: {:?}", result);
     }
 
+    #[test]
+    fn test_membership() {
+        let (_, expr) = parse_expression("country IN [\"US\", \"GB\", \"DE\"]").unwrap();
+        assert_eq!(expr, Expression::BinaryOp {
+            left: Box::new(Expression::Identifier("country".to_string())),
+            op: BinaryOperator::In,
+            right: Box::new(Expression::List(vec![
+                Expression::Literal(Value::String("US".to_string())),
+                Expression::Literal(Value::String("GB".to_string())),
+                Expression::Literal(Value::String("DE".to_string())),
+            ])),
+        });
+
+        for input in ["tier NOT IN [1, 2]", "tier NOT_IN [1, 2]"] {
+            let (rest, expr) = parse_expression(input).unwrap();
+            assert_eq!(rest, "");
+            assert!(matches!(expr, Expression::BinaryOp { op: BinaryOperator::NotIn, .. }));
+        }
+
+        // IN is only an operator as a whole word
+        let (rest, _) = parse_expression("x INDEX").unwrap();
+        assert_eq!(rest.trim_start(), "INDEX");
+    }
+
+    #[test]
+    fn test_recovering_parse_skips_to_next_statement() {
+        let source = "a = 1\nb = price * (\nc = UPPER(name)\n\ntier = CASE\n    WHEN balance >= THEN \"gold\"\n    ELSE \"bronze\"\nEND\n# last rule\nlabel = CONCAT(a, c)\n";
+        let (rules, errors) = parse_rules_recovering(source);
+
+        let targets: Vec<&str> = rules
+            .iter()
+            .map(|rule| match &rule.expression {
+                Expression::Assignment { target, .. } => target.as_str(),
+                other => panic!("expected an assignment, got {:?}", other),
+            })
+            .collect();
+        // `b` and `tier` keep the part of the rule before the error (CASE
+        // without its branches reads as a name)
+        assert_eq!(targets, vec!["a", "b", "c", "tier", "label"]);
+        assert_eq!(&source[rules[2].start..rules[2].end], "c = UPPER(name)");
+
+        let positions: Vec<(usize, usize)> = errors.iter().map(|e| (e.line, e.column)).collect();
+        assert_eq!(positions, vec![(1, 10), (5, 4)]);
+        assert_eq!(errors[0].message, "Unexpected '* ('");
+        assert_eq!(errors[0].code, ErrorCode::UnexpectedText);
+        assert_eq!(errors[1].code, ErrorCode::Syntax);
+        // The broken CASE's continuation lines are skipped, not reported again
+        assert_eq!(&source[errors[1].resume_offset..], "# last rule\nlabel = CONCAT(a, c)\n");
+    }
+
+    #[test]
+    fn test_recovering_parse_of_valid_document_has_no_errors() {
+        let source = "# header\nscore = base * 2 // doubled\nflag = score > 10\n";
+        let (rules, errors) = parse_rules_recovering(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[1].expression, parse_rule("flag = score > 10").unwrap().1);
+        assert_eq!(parse_rules_recovering("   \n"), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_between_and_ranges() {
+        let range = |start: i64, end: i64, inclusive| Box::new(Expression::Range {
+            start: Box::new(Expression::Literal(Value::Integer(start))),
+            end: Box::new(Expression::Literal(Value::Integer(end))),
+            inclusive,
+        });
+
+        let (rest, expr) = parse_expression("amount BETWEEN 1000 AND 5000 AND active").unwrap();
+        assert_eq!(rest, "");
+        let Expression::BinaryOp { left, op: BinaryOperator::And, .. } = expr else {
+            panic!("BETWEEN should bind tighter than AND: {:?}", expr);
+        };
+        assert_eq!(*left, Expression::BinaryOp {
+            left: Box::new(Expression::Identifier("amount".to_string())),
+            op: BinaryOperator::Between,
+            right: range(1000, 5000, true),
+        });
+
+        let (_, expr) = parse_expression("amount BETWEEN_EXCLUSIVE 1000 AND 5000").unwrap();
+        assert!(matches!(expr, Expression::BinaryOp { op: BinaryOperator::Between, right, .. } if right == range(1000, 5000, false)));
+
+        let (_, expr) = parse_expression("tier IN 1..=3").unwrap();
+        assert!(matches!(expr, Expression::BinaryOp { op: BinaryOperator::In, right, .. } if right == range(1, 3, true)));
+
+        // A double dot ends an identifier
+        let (rest, expr) = parse_expression("score NOT IN low..high").unwrap();
+        assert_eq!(rest, "");
+        assert!(matches!(expr, Expression::BinaryOp { op: BinaryOperator::NotIn, right, .. }
+            if matches!(*right, Expression::Range { inclusive: false, .. })));
+    }
+
+    #[test]
+    fn test_unit_literals() {
+        let literal = |input: &str| match parse_expression(input).unwrap() {
+            ("", Expression::Literal(value)) => value,
+            other => panic!("{} should parse as one literal: {:?}", input, other),
+        };
+        let money = |amount: f64, currency: &str| Value::Money { amount, currency: currency.to_string() };
+
+        assert_eq!(literal("1_000_000"), Value::Integer(1_000_000));
+        assert_eq!(literal("5%"), Value::Percent(5.0));
+        assert_eq!(literal("0.25%"), Value::Percent(0.25));
+        assert_eq!(literal("1_000 USD"), money(1000.0, "USD"));
+        assert_eq!(literal("12.5EUR"), money(12.5, "EUR"));
+
+        // % followed by an operand is still modulo
+        for input in ["10 % 3", "10%3", "10% (x)", "10% x", "10 %x"] {
+            let (rest, expr) = parse_expression(input).unwrap();
+            assert_eq!(rest, "");
+            assert!(matches!(expr, Expression::BinaryOp { op: BinaryOperator::Modulo, .. }), "{}", input);
+        }
+
+        let (_, expr) = parse_expression("fee > 5% AND amount < 10 USD").unwrap();
+        assert!(matches!(expr, Expression::BinaryOp { op: BinaryOperator::And, .. }));
+
+        // Unknown codes and keywords are not currencies
+        let (rest, expr) = parse_expression("10 XYZ").unwrap();
+        assert_eq!((rest, expr), ("XYZ", Expression::Literal(Value::Integer(10))));
+        let (rest, _) = parse_expression("10 USDT").unwrap();
+        assert_eq!(rest, "USDT");
+    }
+
     #[test]
     fn test_configure_system() {
         let result = parse_rule("CONFIGURE_SYSTEM \"account_setup\"").unwrap().1;
@@ -578,4 +1345,23 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_spanned_rule_finds_innermost_sub_expression() {
+        let source = "  fee = (amount - rebate) * ROUND(rate, 2)\n";
+        let (_, rule) = parse_rule_spanned(source).unwrap();
+        assert_eq!(&source[rule.start..rule.end], "fee = (amount - rebate) * ROUND(rate, 2)");
+
+        let text = |offset: usize| {
+            let found = rule.at(offset).unwrap();
+            &source[found.start..found.end]
+        };
+        assert_eq!(text(source.find("rebate").unwrap()), "rebate");
+        assert_eq!(text(source.find('-').unwrap()), "(amount - rebate)");
+        assert_eq!(text(source.find('*').unwrap()), "(amount - rebate) * ROUND(rate, 2)");
+        assert_eq!(text(source.find("ROUND").unwrap()), "ROUND(rate, 2)");
+        assert_eq!(text(source.find("2)").unwrap()), "2");
+        assert_eq!(text(source.find("fee").unwrap()), "fee = (amount - rebate) * ROUND(rate, 2)");
+        assert!(rule.at(0).is_none());
+    }
+}
@@ -0,0 +1,253 @@
+//! Provenance of materialized derived attributes
+//!
+//! `RulesEngine::materialize` evaluates the rules like `evaluate_all` and
+//! notes, for each derived value, the rule version that produced it and the
+//! values it read. Once recorded (see `ProvenanceOperations`), every source
+//! value read is kept as a snapshot and every derived input points at the
+//! derivation that produced it, so `get_provenance` can answer why a client's
+//! attribute has its value with the exact inputs used, down to the stored
+//! data, even after those sources have changed.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::{BTreeMap, HashMap};
+
+/// The derived values of one evaluation, with how each was produced
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Materialization {
+    /// In evaluation order, each after the derivations it reads
+    pub derivations: Vec<Derivation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Derivation {
+    pub attribute: String,
+    pub value: JsonValue,
+    pub rule_id: String,
+    pub rule_version: i32,
+    /// The attributes the rule read, with their values at the time
+    pub inputs: Vec<DerivationInput>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DerivationInput {
+    pub attribute: String,
+    pub value: JsonValue,
+    /// Produced by an earlier derivation of the same materialization
+    pub derived: bool,
+}
+
+impl Materialization {
+    pub fn derivation(&self, attribute: &str) -> Option<&Derivation> {
+        self.derivations.iter().find(|d| d.attribute == attribute)
+    }
+
+    /// Source values read by any derivation, each once
+    pub fn source_values(&self) -> BTreeMap<&str, &JsonValue> {
+        self.derivations
+            .iter()
+            .flat_map(|d| &d.inputs)
+            .filter(|input| !input.derived)
+            .map(|input| (input.attribute.as_str(), &input.value))
+            .collect()
+    }
+}
+
+/// How a client's attribute got its value, as recorded when it was materialized
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttributeProvenance {
+    pub cbu_id: String,
+    pub attribute: String,
+    pub value: JsonValue,
+    pub rule_id: String,
+    pub rule_version: i32,
+    pub materialized_at: DateTime<Utc>,
+    pub inputs: Vec<ProvenanceInput>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProvenanceInput {
+    pub attribute: String,
+    pub value: JsonValue,
+    pub source: InputSource,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InputSource {
+    /// A stored value, as snapshotted when it was read
+    Snapshot { snapshot_id: i64, captured_at: DateTime<Utc> },
+    /// The output of another derivation, with its own provenance
+    Derived { provenance: Box<AttributeProvenance> },
+}
+
+impl AttributeProvenance {
+    /// The stored values the attribute ultimately depends on, each once, by attribute
+    pub fn source_inputs(&self) -> BTreeMap<&str, &ProvenanceInput> {
+        let mut sources = BTreeMap::new();
+        self.collect_sources(&mut sources);
+        sources
+    }
+
+    fn collect_sources<'a>(&'a self, sources: &mut BTreeMap<&'a str, &'a ProvenanceInput>) {
+        for input in &self.inputs {
+            match &input.source {
+                InputSource::Snapshot { .. } => {
+                    sources.insert(&input.attribute, input);
+                }
+                InputSource::Derived { provenance } => provenance.collect_sources(sources),
+            }
+        }
+    }
+}
+
+/// A recorded derivation, as stored by `ProvenanceOperations`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivationRecord {
+    pub id: i64,
+    pub attribute: String,
+    pub value: JsonValue,
+    pub rule_id: String,
+    pub rule_version: i32,
+    pub materialized_at: DateTime<Utc>,
+}
+
+/// A recorded input of a derivation: a snapshot or another derivation
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputRecord {
+    pub derivation_id: i64,
+    pub attribute: String,
+    pub value: JsonValue,
+    pub snapshot: Option<(i64, DateTime<Utc>)>,
+    pub derived_from: Option<i64>,
+}
+
+/// Builds the provenance tree of derivation `root` from the records of its
+/// materialization. Inputs pointing at derivations missing from `derivations`
+/// are left out, as they can't be explained.
+pub fn provenance_tree(
+    cbu_id: &str,
+    root: i64,
+    derivations: &[DerivationRecord],
+    inputs: &[InputRecord],
+) -> Option<AttributeProvenance> {
+    let derivations: HashMap<i64, &DerivationRecord> = derivations.iter().map(|d| (d.id, d)).collect();
+    let mut inputs_of: HashMap<i64, Vec<&InputRecord>> = HashMap::new();
+    for input in inputs {
+        inputs_of.entry(input.derivation_id).or_default().push(input);
+    }
+    build(cbu_id, root, &derivations, &inputs_of, &mut Vec::new())
+}
+
+fn build(
+    cbu_id: &str,
+    id: i64,
+    derivations: &HashMap<i64, &DerivationRecord>,
+    inputs_of: &HashMap<i64, Vec<&InputRecord>>,
+    path: &mut Vec<i64>,
+) -> Option<AttributeProvenance> {
+    // Derivations only read earlier ones, but stored rows are not to be trusted blindly
+    if path.contains(&id) {
+        return None;
+    }
+    let derivation = derivations.get(&id)?;
+    path.push(id);
+    let inputs = inputs_of
+        .get(&id)
+        .into_iter()
+        .flatten()
+        .filter_map(|input| {
+            let source = match (input.snapshot, input.derived_from) {
+                (_, Some(derived_from)) => InputSource::Derived {
+                    provenance: Box::new(build(cbu_id, derived_from, derivations, inputs_of, path)?),
+                },
+                (Some((snapshot_id, captured_at)), None) => InputSource::Snapshot { snapshot_id, captured_at },
+                (None, None) => return None,
+            };
+            Some(ProvenanceInput { attribute: input.attribute.clone(), value: input.value.clone(), source })
+        })
+        .collect();
+    path.pop();
+
+    Some(AttributeProvenance {
+        cbu_id: cbu_id.to_string(),
+        attribute: derivation.attribute.clone(),
+        value: derivation.value.clone(),
+        rule_id: derivation.rule_id.clone(),
+        rule_version: derivation.rule_version,
+        materialized_at: derivation.materialized_at,
+        inputs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn derivation(id: i64, attribute: &str, value: JsonValue) -> DerivationRecord {
+        DerivationRecord {
+            id,
+            attribute: attribute.to_string(),
+            value,
+            rule_id: format!("{}_rule", attribute),
+            rule_version: 2,
+            materialized_at: DateTime::parse_from_rfc3339("2025-03-01T09:00:00Z").unwrap().with_timezone(&Utc),
+        }
+    }
+
+    fn input(derivation_id: i64, attribute: &str, value: JsonValue, snapshot: Option<i64>, derived_from: Option<i64>) -> InputRecord {
+        let captured_at = DateTime::parse_from_rfc3339("2025-03-01T08:59:00Z").unwrap().with_timezone(&Utc);
+        InputRecord {
+            derivation_id,
+            attribute: attribute.to_string(),
+            value,
+            snapshot: snapshot.map(|id| (id, captured_at)),
+            derived_from,
+        }
+    }
+
+    #[test]
+    fn test_tree_explains_a_derived_value_down_to_its_snapshots() {
+        let derivations = [derivation(1, "country_risk", json!("HIGH")), derivation(2, "risk_rating", json!("HIGH"))];
+        let inputs = [
+            input(1, "domicile_country", json!("IR"), Some(10), None),
+            input(2, "country_risk", json!("HIGH"), None, Some(1)),
+            input(2, "is_pep", json!(false), Some(11), None),
+            input(2, "domicile_country", json!("IR"), Some(10), None),
+        ];
+
+        let provenance = provenance_tree("CBU000042", 2, &derivations, &inputs).unwrap();
+        assert_eq!(provenance.attribute, "risk_rating");
+        assert_eq!(provenance.inputs.len(), 3);
+        let InputSource::Derived { provenance: country_risk } = &provenance.inputs[0].source else {
+            panic!("country_risk should be derived");
+        };
+        assert_eq!(country_risk.rule_id, "country_risk_rule");
+        assert_eq!(country_risk.inputs[0].value, json!("IR"));
+
+        let sources: Vec<(&str, i64)> = provenance
+            .source_inputs()
+            .into_iter()
+            .map(|(attribute, input)| match input.source {
+                InputSource::Snapshot { snapshot_id, .. } => (attribute, snapshot_id),
+                InputSource::Derived { .. } => unreachable!(),
+            })
+            .collect();
+        assert_eq!(sources, vec![("domicile_country", 10), ("is_pep", 11)]);
+    }
+
+    #[test]
+    fn test_inputs_of_unknown_or_cyclic_derivations_are_left_out() {
+        let derivations = [derivation(1, "a", json!(1)), derivation(2, "b", json!(2))];
+        let inputs = [input(1, "b", json!(2), None, Some(2)), input(2, "a", json!(1), None, Some(1)), input(2, "c", json!(3), None, Some(9))];
+
+        let provenance = provenance_tree("CBU000042", 1, &derivations, &inputs).unwrap();
+        let InputSource::Derived { provenance: b } = &provenance.inputs[0].source else {
+            panic!("b should be derived");
+        };
+        assert!(b.inputs.is_empty());
+        assert_eq!(provenance_tree("CBU000042", 7, &derivations, &inputs), None);
+    }
+}
@@ -0,0 +1,184 @@
+//! Materialized read models for the heavy UI views
+//!
+//! The product hierarchy, taxonomy hierarchy and onboarding progress views
+//! each join several tables, and the UIs read them on every tab switch. They
+//! are kept as materialized views instead: a write to a view's source tables
+//! marks it stale and notifies `read_model_changed`, and each refresh bumps
+//! the model's version. Whatever holds rows of a read model, a client or the
+//! server's `ReadModelCache`, keys them by that version, so a read that finds
+//! the version unchanged needs no query at all.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+
+/// The channel source-table triggers notify, with the read model as payload
+pub const CHANGE_CHANNEL: &str = "read_model_changed";
+
+/// Filters cached per read model before the cache for it starts over
+const MAX_CACHED_FILTERS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadModel {
+    /// Active products with their services and resources, in `mv_product_hierarchy`
+    ProductHierarchy,
+    /// Products and services as one tree, in `mv_taxonomy_hierarchy`
+    TaxonomyHierarchy,
+    /// Task counts and completion per onboarding request, in `mv_onboarding_progress`
+    OnboardingProgress,
+}
+
+impl ReadModel {
+    pub const ALL: [ReadModel; 3] =
+        [ReadModel::ProductHierarchy, ReadModel::TaxonomyHierarchy, ReadModel::OnboardingProgress];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReadModel::ProductHierarchy => "product_hierarchy",
+            ReadModel::TaxonomyHierarchy => "taxonomy_hierarchy",
+            ReadModel::OnboardingProgress => "onboarding_progress",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|model| model.as_str() == value)
+    }
+
+    pub fn view(&self) -> &'static str {
+        match self {
+            ReadModel::ProductHierarchy => "mv_product_hierarchy",
+            ReadModel::TaxonomyHierarchy => "mv_taxonomy_hierarchy",
+            ReadModel::OnboardingProgress => "mv_onboarding_progress",
+        }
+    }
+
+    /// The column a read narrowed by a filter value matches
+    pub fn filter_column(&self) -> &'static str {
+        match self {
+            ReadModel::ProductHierarchy => "product_id",
+            ReadModel::TaxonomyHierarchy => "item_type",
+            ReadModel::OnboardingProgress => "cbu_id",
+        }
+    }
+
+    pub fn order_by(&self) -> &'static str {
+        match self {
+            ReadModel::ProductHierarchy => "product_name, service_order, resource_priority",
+            ReadModel::TaxonomyHierarchy => "level, item_id",
+            ReadModel::OnboardingProgress => "created_at DESC",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadModelVersion {
+    pub read_model: ReadModel,
+    pub version: i64,
+    /// Source tables changed since the last refresh
+    pub stale: bool,
+    pub refreshed_at: DateTime<Utc>,
+}
+
+impl ReadModelVersion {
+    /// The key rows of this version read with `filter` are cached under
+    pub fn cache_key(&self, filter: Option<&str>) -> String {
+        format!("{}@{}:{}", self.read_model.as_str(), self.version, filter.unwrap_or("*"))
+    }
+}
+
+/// Rows of each read model as last read, by filter, for the model's latest
+/// version only: seeing a newer version drops everything cached for an older one
+#[derive(Debug, Default)]
+pub struct ReadModelCache {
+    models: HashMap<ReadModel, (i64, HashMap<String, JsonValue>)>,
+}
+
+impl ReadModelCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, version: &ReadModelVersion, filter: Option<&str>) -> Option<&JsonValue> {
+        match self.models.get(&version.read_model) {
+            Some((cached, rows)) if *cached == version.version => rows.get(filter.unwrap_or("*")),
+            _ => None,
+        }
+    }
+
+    /// Cache rows read at `version`; rows of a version older than one
+    /// already seen are dropped, as a refresh finished while they were read
+    pub fn insert(&mut self, version: &ReadModelVersion, filter: Option<&str>, rows: JsonValue) {
+        self.invalidate(version);
+        let (cached, entries) = self.models.entry(version.read_model).or_default();
+        if *cached != version.version {
+            return;
+        }
+        if entries.len() >= MAX_CACHED_FILTERS {
+            entries.clear();
+        }
+        entries.insert(filter.unwrap_or("*").to_string(), rows);
+    }
+
+    /// Note a model's current version, dropping rows cached for older ones
+    pub fn invalidate(&mut self, version: &ReadModelVersion) {
+        let (cached, entries) = self.models.entry(version.read_model).or_default();
+        if version.version > *cached {
+            *cached = version.version;
+            entries.clear();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.models.values().map(|(_, entries)| entries.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn version(read_model: ReadModel, version: i64) -> ReadModelVersion {
+        ReadModelVersion { read_model, version, stale: false, refreshed_at: Utc::now() }
+    }
+
+    #[test]
+    fn test_read_model_names_round_trip() {
+        for model in ReadModel::ALL {
+            assert_eq!(ReadModel::parse(model.as_str()), Some(model));
+            assert!(model.view().ends_with(model.as_str()));
+        }
+        assert_eq!(ReadModel::parse("rules"), None);
+        assert_eq!(version(ReadModel::ProductHierarchy, 3).cache_key(Some("PROD-CUSTODY")), "product_hierarchy@3:PROD-CUSTODY");
+        assert_eq!(version(ReadModel::OnboardingProgress, 1).cache_key(None), "onboarding_progress@1:*");
+    }
+
+    #[test]
+    fn test_a_newer_version_drops_cached_rows() {
+        let mut cache = ReadModelCache::new();
+        let v1 = version(ReadModel::ProductHierarchy, 1);
+        let v2 = version(ReadModel::ProductHierarchy, 2);
+        cache.insert(&v1, None, json!([{"product_id": "P1"}]));
+        cache.insert(&v1, Some("P1"), json!([]));
+        cache.insert(&version(ReadModel::TaxonomyHierarchy, 7), None, json!([]));
+        assert_eq!(cache.get(&v1, None), Some(&json!([{"product_id": "P1"}])));
+        assert_eq!(cache.len(), 3);
+
+        cache.invalidate(&v2);
+        assert_eq!(cache.get(&v1, None), None);
+        assert_eq!(cache.len(), 1);
+
+        // Rows read before the refresh finished arrive too late to be cached
+        cache.insert(&v1, None, json!([{"product_id": "P1"}]));
+        assert_eq!(cache.get(&v2, None), None);
+        cache.insert(&v2, None, json!([{"product_id": "P2"}]));
+        assert_eq!(cache.get(&v2, None), Some(&json!([{"product_id": "P2"}])));
+        assert_eq!(cache.get(&version(ReadModel::TaxonomyHierarchy, 7), None), Some(&json!([])));
+    }
+}
@@ -0,0 +1,132 @@
+//! Compiled regex cache shared by every evaluation
+//!
+//! `MATCHES` and the regex functions look their pattern up here instead
+//! of calling `Regex::new` each time. The cache is bounded: once it holds
+//! `DEFAULT_CAPACITY` patterns, the least recently used one is dropped.
+
+use anyhow::{bail, Result};
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Patterns kept by the shared cache
+pub const DEFAULT_CAPACITY: usize = 256;
+
+/// Least-recently-used cache of compiled patterns
+pub struct RegexCache {
+    capacity: usize,
+    entries: HashMap<String, (Arc<Regex>, u64)>,
+    clock: u64,
+}
+
+impl RegexCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The compiled pattern, if cached; marks it as most recently used
+    pub fn get(&mut self, pattern: &str) -> Option<Arc<Regex>> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.entries.get_mut(pattern).map(|(regex, last_used)| {
+            *last_used = clock;
+            regex.clone()
+        })
+    }
+
+    pub fn insert(&mut self, pattern: String, regex: Arc<Regex>) {
+        if !self.entries.contains_key(&pattern) && self.entries.len() >= self.capacity {
+            let oldest = self.entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(pattern, _)| pattern.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.entries.insert(pattern, (regex, self.clock));
+    }
+
+    pub fn get_or_compile(&mut self, pattern: &str) -> Result<Arc<Regex>> {
+        if let Some(regex) = self.get(pattern) {
+            return Ok(regex);
+        }
+        let regex = compile(pattern)?;
+        self.insert(pattern.to_string(), regex.clone());
+        Ok(regex)
+    }
+}
+
+fn shared() -> &'static Mutex<RegexCache> {
+    static SHARED: OnceLock<Mutex<RegexCache>> = OnceLock::new();
+    SHARED.get_or_init(|| Mutex::new(RegexCache::new(DEFAULT_CAPACITY)))
+}
+
+fn compile(pattern: &str) -> Result<Arc<Regex>> {
+    match Regex::new(pattern) {
+        Ok(regex) => Ok(Arc::new(regex)),
+        Err(_) => bail!("Invalid regex pattern: {}", pattern),
+    }
+}
+
+/// Looks `pattern` up in the shared cache, compiling it on a miss. The lock
+/// is not held while compiling, so a slow pattern doesn't block other threads.
+pub fn compiled(pattern: &str) -> Result<Arc<Regex>> {
+    if let Some(regex) = shared().lock().unwrap_or_else(|e| e.into_inner()).get(pattern) {
+        return Ok(regex);
+    }
+    let regex = compile(pattern)?;
+    shared()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(pattern.to_string(), regex.clone());
+    Ok(regex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_least_recently_used_pattern_is_evicted() {
+        let mut cache = RegexCache::new(2);
+        let digits = cache.get_or_compile(r"\d+").unwrap();
+        cache.get_or_compile("[a-z]+").unwrap();
+        // Touch \d+ so [a-z]+ becomes the oldest
+        assert!(Arc::ptr_eq(&digits, &cache.get_or_compile(r"\d+").unwrap()));
+        cache.get_or_compile("^GB").unwrap();
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(r"\d+").is_some());
+        assert!(cache.get("[a-z]+").is_none());
+        assert!(cache.get("^GB").is_some());
+    }
+
+    #[test]
+    fn test_invalid_patterns_are_not_cached() {
+        let mut cache = RegexCache::new(4);
+        assert!(cache.get_or_compile("(unclosed").is_err());
+        assert!(cache.is_empty());
+        assert!(compiled("(unclosed").is_err());
+    }
+
+    #[test]
+    fn test_shared_cache_returns_the_same_regex() {
+        let first = compiled(r"^LEI-\w{4}$").unwrap();
+        let second = std::thread::spawn(|| compiled(r"^LEI-\w{4}$").unwrap()).join().unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+    }
+}
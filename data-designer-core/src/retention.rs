@@ -0,0 +1,275 @@
+//! Data retention policies and GDPR erasure
+//!
+//! Each kind of stored record has a retention policy: after `retain_days`
+//! the purge either deletes the record or anonymizes it, clearing its
+//! personal payload but keeping the row so counts, statuses and timings
+//! still add up. Erasing a data subject replaces every occurrence of their
+//! identifiers (id, names, emails, phone numbers, LEIs) with one pseudonym,
+//! in CBU memberships, documents and evaluation traces alike. The same
+//! pseudonym everywhere keeps aggregates and joins intact while the person
+//! can no longer be identified.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+/// The kinds of record a retention policy applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionEntity {
+    /// Rule evaluation results and traces, in `dsl_execution_logs`
+    EvaluationResults,
+    /// Onboarding execution history, in `onboarding_execution_log`
+    AuditLogs,
+    /// Collected client data and documents, in `resource_instances`
+    Documents,
+}
+
+impl RetentionEntity {
+    pub const ALL: [RetentionEntity; 3] =
+        [RetentionEntity::EvaluationResults, RetentionEntity::AuditLogs, RetentionEntity::Documents];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RetentionEntity::EvaluationResults => "evaluation_results",
+            RetentionEntity::AuditLogs => "audit_logs",
+            RetentionEntity::Documents => "documents",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|entity| entity.as_str() == value)
+    }
+
+    pub fn table(&self) -> &'static str {
+        match self {
+            RetentionEntity::EvaluationResults => "dsl_execution_logs",
+            RetentionEntity::AuditLogs => "onboarding_execution_log",
+            RetentionEntity::Documents => "resource_instances",
+        }
+    }
+
+    /// The column a record's age is measured from
+    pub fn timestamp_column(&self) -> &'static str {
+        match self {
+            RetentionEntity::EvaluationResults => "executed_at",
+            RetentionEntity::AuditLogs => "started_at",
+            RetentionEntity::Documents => "created_at",
+        }
+    }
+
+    /// SET clause that clears a record's personal payload, leaving the
+    /// columns aggregates are built from
+    pub fn anonymize_assignments(&self) -> &'static str {
+        match self {
+            RetentionEntity::EvaluationResults => {
+                "input_data = NULL, output_data = NULL, log_messages = '[]'::jsonb, error_details = NULL, \
+                 stack_trace = NULL, context_metadata = NULL"
+            }
+            RetentionEntity::AuditLogs => "execution_log = NULL, error_message = NULL, metadata = '{}'::jsonb",
+            RetentionEntity::Documents => {
+                "instance_data = '{}'::jsonb, validation_results = NULL, change_history = '[]'::jsonb"
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionAction {
+    Delete,
+    Anonymize,
+}
+
+impl RetentionAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RetentionAction::Delete => "delete",
+            RetentionAction::Anonymize => "anonymize",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "delete" => Some(RetentionAction::Delete),
+            "anonymize" => Some(RetentionAction::Anonymize),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub entity_type: RetentionEntity,
+    pub retain_days: i32,
+    pub action: RetentionAction,
+    pub enabled: bool,
+}
+
+impl RetentionPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.retain_days < 1 {
+            return Err(format!("{} must be kept for at least one day", self.entity_type.as_str()));
+        }
+        Ok(())
+    }
+
+    /// Records older than this are due for purging
+    pub fn cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now - Duration::days(self.retain_days as i64)
+    }
+}
+
+/// What one purge did for one policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PurgeReport {
+    pub entity_type: RetentionEntity,
+    pub action: RetentionAction,
+    pub cutoff: DateTime<Utc>,
+    pub rows: u64,
+}
+
+/// Everything that identifies a data subject in stored payloads
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SubjectIdentifiers {
+    pub entity_id: String,
+    #[serde(default)]
+    pub names: Vec<String>,
+    #[serde(default)]
+    pub emails: Vec<String>,
+    #[serde(default)]
+    pub phones: Vec<String>,
+    #[serde(default)]
+    pub leis: Vec<String>,
+}
+
+impl SubjectIdentifiers {
+    pub fn new(entity_id: &str) -> Self {
+        Self { entity_id: entity_id.trim().to_string(), ..Self::default() }
+    }
+
+    /// Distinct identifiers, longest first so that a name containing the id is replaced whole
+    pub fn values(&self) -> Vec<&str> {
+        let mut values: Vec<&str> = std::iter::once(&self.entity_id)
+            .chain(&self.names)
+            .chain(&self.emails)
+            .chain(&self.phones)
+            .chain(&self.leis)
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+            .collect();
+        values.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        values.dedup();
+        values
+    }
+
+    /// Replaces every identifier in `text`; returns the count of replacements
+    pub fn redact_text(&self, text: &str, pseudonym: &str) -> (String, usize) {
+        let mut redacted = text.to_string();
+        let mut replaced = 0;
+        for value in self.values() {
+            let count = redacted.matches(value).count();
+            if count > 0 {
+                redacted = redacted.replace(value, pseudonym);
+                replaced += count;
+            }
+        }
+        (redacted, replaced)
+    }
+
+    /// Redacts every string in a JSON document, keys included; returns the count of replacements
+    pub fn redact_json(&self, value: &mut JsonValue, pseudonym: &str) -> usize {
+        match value {
+            JsonValue::String(text) => {
+                let (redacted, replaced) = self.redact_text(text, pseudonym);
+                *text = redacted;
+                replaced
+            }
+            JsonValue::Array(items) => items.iter_mut().map(|item| self.redact_json(item, pseudonym)).sum(),
+            JsonValue::Object(fields) => {
+                let mut replaced = 0;
+                let entries = std::mem::take(fields);
+                for (key, mut field) in entries {
+                    let (key, key_replaced) = self.redact_text(&key, pseudonym);
+                    replaced += key_replaced + self.redact_json(&mut field, pseudonym);
+                    fields.insert(key, field);
+                }
+                replaced
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// The pseudonym an erased subject is replaced with. Random rather than
+/// derived from the subject, so it can't be linked back by hashing known ids.
+pub fn erasure_pseudonym() -> String {
+    format!("ERASED-{}", &uuid::Uuid::new_v4().simple().to_string()[..12].to_uppercase())
+}
+
+/// The outcome of erasing one data subject; holds no personal data, so it
+/// can be kept as proof of erasure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErasureReport {
+    pub erasure_id: i32,
+    pub pseudonym: String,
+    pub cbu_members: u64,
+    pub documents: u64,
+    pub traces: u64,
+    pub audit_logs: u64,
+    pub requested_by: Option<String>,
+    pub completed_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redaction_replaces_every_identifier_with_one_pseudonym() {
+        let subject = SubjectIdentifiers {
+            entity_id: "CLIENT-42".to_string(),
+            names: vec!["Jane Doe".to_string()],
+            emails: vec!["jane@example.com".to_string()],
+            ..SubjectIdentifiers::default()
+        };
+
+        let mut document = json!({
+            "applicant": {"name": "Jane Doe", "contact": "jane@example.com", "ref": "CLIENT-42"},
+            "CLIENT-42": ["Signed by Jane Doe", 3, null],
+            "status": "approved"
+        });
+        assert_eq!(subject.redact_json(&mut document, "ERASED-1"), 5);
+        assert_eq!(
+            document,
+            json!({
+                "applicant": {"name": "ERASED-1", "contact": "ERASED-1", "ref": "ERASED-1"},
+                "ERASED-1": ["Signed by ERASED-1", 3, null],
+                "status": "approved"
+            })
+        );
+
+        assert_eq!(subject.redact_text("no personal data", "ERASED-1"), ("no personal data".to_string(), 0));
+        assert!(erasure_pseudonym().starts_with("ERASED-"));
+        assert_ne!(erasure_pseudonym(), erasure_pseudonym());
+    }
+
+    #[test]
+    fn test_policy_cutoff_and_validation() {
+        let policy = RetentionPolicy {
+            entity_type: RetentionEntity::EvaluationResults,
+            retain_days: 30,
+            action: RetentionAction::Delete,
+            enabled: true,
+        };
+        let now = DateTime::parse_from_rfc3339("2025-03-31T12:00:00Z").unwrap().with_timezone(&Utc);
+        assert_eq!(policy.cutoff(now).to_rfc3339(), "2025-03-01T12:00:00+00:00");
+        assert!(policy.validate().is_ok());
+        assert!(RetentionPolicy { retain_days: 0, ..policy }.validate().is_err());
+
+        assert_eq!(RetentionEntity::parse("audit_logs"), Some(RetentionEntity::AuditLogs));
+        assert_eq!(RetentionEntity::parse("emails"), None);
+        assert_eq!(serde_json::to_value(RetentionAction::Anonymize).unwrap(), json!("anonymize"));
+    }
+}
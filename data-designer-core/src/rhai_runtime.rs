@@ -0,0 +1,398 @@
+//! Sandboxed Rhai runtime for transpiled rules
+//!
+//! Every script runs in an engine with operation, size and depth limits, no
+//! module imports, no `eval` and no print/debug output. Rhai has no file or
+//! network access of its own, so with imports disabled a script can only
+//! compute over the facts it is given. Limit violations come back as
+//! `SandboxError` variants instead of taking down the caller.
+
+use crate::evaluator::Facts;
+use crate::models::{Expression, Value};
+use crate::rule_graph::extract_dependencies_from_ast;
+use crate::parser::parse_rule;
+use crate::transpiler::source_map::SourceMap;
+use crate::transpiler::{TargetLanguage, Transpiler, TranspilerOptions};
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Dynamic, Engine, EvalAltResult, LexError, ParseErrorType, Scope, AST};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SandboxLimits {
+    pub max_operations: u64,
+    pub max_string_size: usize,
+    pub max_array_size: usize,
+    pub max_map_size: usize,
+    pub max_call_levels: usize,
+    pub max_expr_depth: usize,
+}
+
+impl Default for SandboxLimits {
+    fn default() -> Self {
+        Self {
+            max_operations: 100_000,
+            max_string_size: 64 * 1024,
+            max_array_size: 10_000,
+            max_map_size: 10_000,
+            max_call_levels: 32,
+            max_expr_depth: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, thiserror::Error)]
+pub enum SandboxError {
+    #[error("Script exceeded the limit of {limit} operations")]
+    OperationLimit { limit: u64 },
+    #[error("Script exceeded the {what} limit")]
+    DataTooLarge { what: String },
+    #[error("Script exceeded the call depth limit")]
+    CallDepth,
+    #[error("Script uses a capability that is disabled in the sandbox: {0}")]
+    Forbidden(String),
+    #[error("Script failed to compile: {0}")]
+    Compile(String),
+    #[error("Script failed at runtime: {0}")]
+    Runtime(String),
+    #[error("Script returned a value that cannot be converted: {0}")]
+    UnsupportedValue(String),
+}
+
+/// A rule transpiled to Rhai and compiled once, so evaluating it doesn't walk
+/// the rule's AST. Built with `RhaiSandbox::compile_rule`, or with
+/// `compile_rule_source` to report runtime errors against the rule text.
+#[derive(Debug, Clone)]
+pub struct CompiledScript {
+    pub script: String,
+    /// Facts the rule reads; missing ones are bound as `()`, like the
+    /// evaluator's null for an absent fact
+    pub variables: Vec<String>,
+    pub source_map: Option<SourceMap>,
+    ast: AST,
+}
+
+pub struct RhaiSandbox {
+    engine: Engine,
+    limits: SandboxLimits,
+}
+
+impl Default for RhaiSandbox {
+    fn default() -> Self {
+        Self::new(SandboxLimits::default())
+    }
+}
+
+impl RhaiSandbox {
+    pub fn new(limits: SandboxLimits) -> Self {
+        let mut engine = Engine::new();
+
+        engine.set_max_operations(limits.max_operations);
+        engine.set_max_string_size(limits.max_string_size);
+        engine.set_max_array_size(limits.max_array_size);
+        engine.set_max_map_size(limits.max_map_size);
+        engine.set_max_call_levels(limits.max_call_levels);
+        engine.set_max_expr_depths(limits.max_expr_depth, limits.max_expr_depth);
+
+        // No imports, no dynamic evaluation, no output
+        engine.set_max_modules(0);
+        engine.set_module_resolver(DummyModuleResolver::new());
+        engine.disable_symbol("eval");
+        engine.on_print(|_| {});
+        engine.on_debug(|_, _, _| {});
+
+        Self { engine, limits }
+    }
+
+    pub fn limits(&self) -> &SandboxLimits {
+        &self.limits
+    }
+
+    pub fn compile(&self, script: &str) -> Result<AST, SandboxError> {
+        self.engine.compile(script).map_err(|e| match e.err_type() {
+            ParseErrorType::ExprTooDeep => SandboxError::DataTooLarge {
+                what: "expression depth".to_string(),
+            },
+            // Disabled symbols such as `eval` are rejected by the lexer
+            ParseErrorType::Reserved(symbol) | ParseErrorType::BadInput(LexError::ImproperSymbol(symbol, _)) => {
+                SandboxError::Forbidden(symbol.clone())
+            }
+            _ => SandboxError::Compile(e.to_string()),
+        })
+    }
+
+    /// Compile and run `script` with each fact bound as a variable
+    pub fn execute(&self, script: &str, facts: &Facts) -> Result<Value, SandboxError> {
+        let ast = self.compile(script)?;
+        self.execute_ast(&ast, facts)
+    }
+
+    /// Run a precompiled script with each fact bound as a variable
+    pub fn execute_ast(&self, ast: &AST, facts: &Facts) -> Result<Value, SandboxError> {
+        let mut scope = Scope::new();
+        for (name, value) in facts {
+            scope.push_dynamic(script_variable_name(name), to_dynamic(value));
+        }
+
+        let result = self
+            .engine
+            .eval_ast_with_scope::<Dynamic>(&mut scope, ast)
+            .map_err(|e| self.classify(*e))?;
+        from_dynamic(result)
+    }
+
+    /// Transpile a parsed rule to Rhai and compile it. Fails with `Compile`
+    /// for rules using something Rhai can't express (most functions, MATCHES);
+    /// those stay on the AST evaluator.
+    pub fn compile_rule(&self, expr: &Expression) -> Result<CompiledScript, SandboxError> {
+        let transpiler = Transpiler::new(TranspilerOptions {
+            target: TargetLanguage::Rhai,
+            ..TranspilerOptions::default()
+        });
+        let script = transpiler.transpile(expr).map_err(|e| SandboxError::Compile(e.to_string()))?;
+        let ast = self.compile(&script)?;
+        Ok(CompiledScript {
+            script,
+            variables: extract_dependencies_from_ast(expr),
+            source_map: None,
+            ast,
+        })
+    }
+
+    /// Parse and compile a rule like `compile_rule`, keeping a source map so
+    /// runtime errors name the fragment of `source` that failed
+    pub fn compile_rule_source(&self, rule_id: &str, source: &str) -> Result<CompiledScript, SandboxError> {
+        let expr = match parse_rule(source) {
+            Ok((remaining, expr)) if remaining.trim().is_empty() => expr,
+            _ => return Err(SandboxError::Compile(format!("Rule '{}' does not parse", rule_id))),
+        };
+        let transpiler = Transpiler::new(TranspilerOptions {
+            target: TargetLanguage::Rhai,
+            ..TranspilerOptions::default()
+        });
+        let (script, source_map) = transpiler
+            .transpile_with_source_map(rule_id, source)
+            .map_err(|e| SandboxError::Compile(e.to_string()))?;
+        let ast = self.compile(&script)?;
+        Ok(CompiledScript {
+            script,
+            variables: extract_dependencies_from_ast(&expr),
+            source_map: Some(source_map),
+            ast,
+        })
+    }
+
+    /// Run a compiled rule, binding only the facts it reads
+    pub fn execute_compiled(&self, compiled: &CompiledScript, facts: &Facts) -> Result<Value, SandboxError> {
+        let mut scope = Scope::new();
+        for name in &compiled.variables {
+            let value = facts.get(name).map_or(Dynamic::UNIT, to_dynamic);
+            scope.push_dynamic(script_variable_name(name), value);
+        }
+
+        let result = self
+            .engine
+            .eval_ast_with_scope::<Dynamic>(&mut scope, &compiled.ast)
+            .map_err(|e| {
+                let position = e.position();
+                let location = compiled
+                    .source_map
+                    .as_ref()
+                    .and_then(|map| map.lookup(position.line()?, position.position()?));
+                match (self.classify(*e), location) {
+                    (SandboxError::Runtime(message), Some(location)) => SandboxError::Runtime(format!(
+                        "{} in `{}` (rule {}, line {}, column {})",
+                        message, location.text, location.rule_id, location.start.line, location.start.column
+                    )),
+                    (error, _) => error,
+                }
+            })?;
+        from_dynamic(result)
+    }
+
+    fn classify(&self, error: EvalAltResult) -> SandboxError {
+        match error {
+            EvalAltResult::ErrorTooManyOperations(_) => SandboxError::OperationLimit {
+                limit: self.limits.max_operations,
+            },
+            EvalAltResult::ErrorDataTooLarge(what, _) => SandboxError::DataTooLarge { what },
+            EvalAltResult::ErrorStackOverflow(_) => SandboxError::CallDepth,
+            EvalAltResult::ErrorTooManyModules(_) | EvalAltResult::ErrorModuleNotFound(..) => {
+                SandboxError::Forbidden("import".to_string())
+            }
+            // Limits hit inside a function call are reported as the underlying violation
+            EvalAltResult::ErrorInFunctionCall(_, _, inner, _) => self.classify(*inner),
+            other => SandboxError::Runtime(other.to_string()),
+        }
+    }
+}
+
+/// Facts may use dotted paths (`Client.country`), which are not Rhai identifiers
+pub fn script_variable_name(name: &str) -> String {
+    name.replace('.', "_")
+}
+
+fn to_dynamic(value: &Value) -> Dynamic {
+    match value {
+        Value::String(s) | Value::Regex(s) => Dynamic::from(s.clone()),
+        Value::Integer(i) => Dynamic::from(*i),
+        Value::Float(f) | Value::Number(f) => Dynamic::from(*f),
+        // Scripts have no units: a percentage is its fraction, money its amount
+        Value::Percent(p) => Dynamic::from(*p / 100.0),
+        Value::Money { amount, .. } => Dynamic::from(*amount),
+        Value::Boolean(b) => Dynamic::from(*b),
+        Value::Null => Dynamic::UNIT,
+        Value::List(items) => Dynamic::from_array(items.iter().map(to_dynamic).collect()),
+        Value::Object(fields) => {
+            Dynamic::from_map(fields.iter().map(|(name, value)| (name.as_str().into(), to_dynamic(value))).collect())
+        }
+    }
+}
+
+fn from_dynamic(value: Dynamic) -> Result<Value, SandboxError> {
+    if value.is_unit() {
+        return Ok(Value::Null);
+    }
+    if let Ok(b) = value.as_bool() {
+        return Ok(Value::Boolean(b));
+    }
+    if let Ok(i) = value.as_int() {
+        return Ok(Value::Integer(i));
+    }
+    if let Ok(f) = value.as_float() {
+        return Ok(Value::Float(f));
+    }
+    if value.is_string() {
+        return Ok(Value::String(value.into_string().unwrap_or_default()));
+    }
+    if value.is_array() {
+        let items = value
+            .into_array()
+            .map_err(|type_name| SandboxError::UnsupportedValue(type_name.to_string()))?;
+        return Ok(Value::List(items.into_iter().map(from_dynamic).collect::<Result<_, _>>()?));
+    }
+    Err(SandboxError::UnsupportedValue(value.type_name().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facts() -> Facts {
+        let mut facts = Facts::new();
+        facts.insert("balance".to_string(), Value::Integer(1200));
+        facts.insert("Client.country".to_string(), Value::String("GB".to_string()));
+        facts
+    }
+
+    #[test]
+    fn test_executes_with_facts() {
+        let sandbox = RhaiSandbox::default();
+        assert_eq!(sandbox.execute("balance * 2", &facts()).unwrap(), Value::Integer(2400));
+        assert_eq!(
+            sandbox.execute("Client_country == \"GB\"", &facts()).unwrap(),
+            Value::Boolean(true)
+        );
+    }
+
+    #[test]
+    fn test_infinite_loop_hits_operation_limit() {
+        let sandbox = RhaiSandbox::new(SandboxLimits {
+            max_operations: 1_000,
+            ..SandboxLimits::default()
+        });
+        assert_eq!(
+            sandbox.execute("loop { }", &facts()),
+            Err(SandboxError::OperationLimit { limit: 1_000 })
+        );
+    }
+
+    #[test]
+    fn test_string_growth_is_limited() {
+        let sandbox = RhaiSandbox::new(SandboxLimits {
+            max_string_size: 100,
+            ..SandboxLimits::default()
+        });
+        let result = sandbox.execute("let s = \"x\"; for i in 0..10 { s += s; } s", &facts());
+        assert!(matches!(result, Err(SandboxError::DataTooLarge { .. })), "{:?}", result);
+    }
+
+    #[test]
+    fn test_recursion_is_limited() {
+        let sandbox = RhaiSandbox::default();
+        let result = sandbox.execute("fn f(x) { f(x + 1) } f(0)", &facts());
+        assert_eq!(result, Err(SandboxError::CallDepth));
+    }
+
+    #[test]
+    fn test_compiled_rules_agree_with_the_evaluator() {
+        use crate::evaluator::{evaluate_with_functions, FunctionLibrary};
+        use crate::parser::parse_rule;
+
+        let rules = [
+            "fee = IF volume > 100 THEN volume * 0.01 ELSE 5",
+            "ratio = balance / 8",
+            r#"tier = CASE WHEN balance >= 1000 THEN "gold" WHEN balance >= 100 THEN "silver" ELSE "bronze" END"#,
+            r#"domestic = Client.country IN ["GB", "IE"] AND balance BETWEEN 1000 AND 5000"#,
+            r#"label = UPPER(Client.country) & "-" & balance"#,
+            "flagged = missing == null OR NOT (balance < 0)",
+        ];
+        let sandbox = RhaiSandbox::default();
+        let functions = FunctionLibrary::new();
+        let mut facts = facts();
+        facts.insert("volume".to_string(), Value::Integer(250));
+        for rule in rules {
+            let (remaining, expr) = parse_rule(rule).unwrap();
+            assert!(remaining.trim().is_empty(), "{}", rule);
+            let compiled = sandbox.compile_rule(&expr).unwrap_or_else(|e| panic!("{}: {}", rule, e));
+            assert_eq!(
+                sandbox.execute_compiled(&compiled, &facts).unwrap(),
+                evaluate_with_functions(&expr, &facts, &functions).unwrap(),
+                "{}\n{}",
+                rule,
+                compiled.script
+            );
+        }
+    }
+
+    #[test]
+    fn test_rules_rhai_cannot_express_are_not_compiled() {
+        use crate::parser::parse_rule;
+
+        let sandbox = RhaiSandbox::default();
+        let (_, expr) = parse_rule(r#"valid = Client.country MATCHES /^[A-Z]{2}$/"#).unwrap();
+        assert!(matches!(sandbox.compile_rule(&expr), Err(SandboxError::Compile(_))));
+
+        let (_, expr) = parse_rule("due = ratio / 0").unwrap();
+        let compiled = sandbox.compile_rule(&expr).unwrap();
+        let mut facts = facts();
+        facts.insert("ratio".to_string(), Value::Integer(3));
+        assert!(matches!(sandbox.execute_compiled(&compiled, &facts), Err(SandboxError::Runtime(_))));
+    }
+
+    #[test]
+    fn test_runtime_errors_name_the_failing_rule_fragment() {
+        let sandbox = RhaiSandbox::default();
+        let compiled = sandbox.compile_rule_source("ratio", "ratio = paid\n    + fees / count").unwrap();
+        let mut facts = facts();
+        facts.insert("paid".to_string(), Value::Integer(10));
+        facts.insert("fees".to_string(), Value::Integer(3));
+        facts.insert("count".to_string(), Value::Integer(0));
+
+        let Err(SandboxError::Runtime(message)) = sandbox.execute_compiled(&compiled, &facts) else {
+            panic!("expected a runtime error");
+        };
+        assert!(message.ends_with("in `fees / count` (rule ratio, line 2, column 7)"), "{}", message);
+    }
+
+    #[test]
+    fn test_imports_and_eval_are_forbidden() {
+        let sandbox = RhaiSandbox::default();
+        assert!(matches!(
+            sandbox.execute("import \"std/fs\" as fs; 1", &facts()),
+            Err(SandboxError::Forbidden(_))
+        ));
+        assert!(matches!(
+            sandbox.execute("eval(\"40 + 2\")", &facts()),
+            Err(SandboxError::Forbidden(_))
+        ));
+    }
+}
@@ -0,0 +1,185 @@
+//! Signed rule bundles
+//!
+//! A bundle is the full rule set serialized to JSON. Signing covers the exact
+//! payload bytes, so any edit to the rules (or the payload text itself)
+//! invalidates the signature. Verification only accepts keys listed in
+//! `SecurityConfig::trusted_keys`.
+//!
+//! A bundle shared through a registry (see `crate::rule_registry`) may also
+//! carry the grammar extensions its rules call.
+
+use crate::config::TrustedKey;
+use crate::rule_repository::ExportedRule;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleBundle {
+    pub format_version: u32,
+    pub created_at: DateTime<Utc>,
+    pub rules: Vec<ExportedRule>,
+    /// Left out when empty, so bundles of rules alone keep their payload
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub grammar_extensions: Vec<GrammarExtensionSpec>,
+}
+
+/// A keyword, operator or function a bundle adds to the grammar
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GrammarExtensionSpec {
+    pub name: String,
+    /// `keyword`, `operator` or `function`
+    pub extension_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+}
+
+/// A bundle payload together with the signature over it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedRuleBundle {
+    pub key_name: String,
+    pub payload: String,
+    /// Hex-encoded ed25519 signature of `payload`
+    pub signature: String,
+}
+
+impl RuleBundle {
+    pub fn new(mut rules: Vec<ExportedRule>) -> Self {
+        rules.sort_by(|a, b| a.metadata.rule_id.cmp(&b.metadata.rule_id));
+        Self {
+            format_version: BUNDLE_FORMAT_VERSION,
+            created_at: Utc::now(),
+            rules,
+            grammar_extensions: Vec::new(),
+        }
+    }
+
+    pub fn with_grammar_extensions(mut self, mut extensions: Vec<GrammarExtensionSpec>) -> Self {
+        extensions.sort_by(|a, b| a.name.cmp(&b.name));
+        self.grammar_extensions = extensions;
+        self
+    }
+
+    pub fn sign(&self, key_name: &str, signing_key: &SigningKey) -> Result<SignedRuleBundle> {
+        let payload = serde_json::to_string_pretty(self)?;
+        let signature = signing_key.sign(payload.as_bytes());
+        Ok(SignedRuleBundle {
+            key_name: key_name.to_string(),
+            payload,
+            signature: hex::encode(signature.to_bytes()),
+        })
+    }
+}
+
+impl SignedRuleBundle {
+    /// Check the signature against the trusted key named in the bundle and
+    /// return the decoded rules
+    pub fn verify(&self, trusted_keys: &[TrustedKey]) -> Result<RuleBundle> {
+        let trusted = trusted_keys
+            .iter()
+            .find(|key| key.name == self.key_name)
+            .ok_or_else(|| anyhow!("Bundle signed with untrusted key '{}'", self.key_name))?;
+        let verifying_key = verifying_key_from_hex(&trusted.public_key)
+            .with_context(|| format!("Invalid public key for trusted key '{}'", trusted.name))?;
+
+        let signature_bytes = hex::decode(&self.signature).context("Bundle signature is not valid hex")?;
+        let signature = Signature::from_slice(&signature_bytes).context("Bundle signature has the wrong length")?;
+        verifying_key
+            .verify_strict(self.payload.as_bytes(), &signature)
+            .map_err(|_| anyhow!("Bundle signature verification failed; the rule set may have been tampered with"))?;
+
+        let bundle: RuleBundle = serde_json::from_str(&self.payload).context("Invalid bundle payload")?;
+        if bundle.format_version != BUNDLE_FORMAT_VERSION {
+            bail!("Unsupported bundle format version {}", bundle.format_version);
+        }
+        Ok(bundle)
+    }
+}
+
+pub fn signing_key_from_hex(secret: &str) -> Result<SigningKey> {
+    let bytes: [u8; 32] = hex::decode(secret.trim())?
+        .try_into()
+        .map_err(|_| anyhow!("Signing key must be 32 bytes"))?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+pub fn verifying_key_from_hex(public_key: &str) -> Result<VerifyingKey> {
+    let bytes: [u8; 32] = hex::decode(public_key.trim())?
+        .try_into()
+        .map_err(|_| anyhow!("Public key must be 32 bytes"))?;
+    Ok(VerifyingKey::from_bytes(&bytes)?)
+}
+
+/// Hex-encoded public key for a signing key, as stored in `trusted_keys`
+pub fn public_key_hex(signing_key: &SigningKey) -> String {
+    hex::encode(signing_key.verifying_key().to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_repository::RuleMetadata;
+    use crate::rule_variants::Applicability;
+
+    fn bundle() -> RuleBundle {
+        RuleBundle::new(vec![ExportedRule {
+            metadata: RuleMetadata {
+                rule_id: "fee_calc".to_string(),
+                rule_name: "Fee".to_string(),
+                description: None,
+                category_id: None,
+                target_attribute_id: None,
+                status: "active".to_string(),
+                version: 2,
+                tags: Vec::new(),
+                valid_from: None,
+                valid_to: None,
+                applies_to: Applicability::default(),
+            },
+            definition: "fee = total * 2".to_string(),
+            tests: Vec::new(),
+        }])
+    }
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn trusted(name: &str, signing_key: &SigningKey) -> Vec<TrustedKey> {
+        vec![TrustedKey {
+            name: name.to_string(),
+            public_key: public_key_hex(signing_key),
+        }]
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let original = bundle();
+        let signed = original.sign("release", &key(1)).unwrap();
+        let verified = signed.verify(&trusted("release", &key(1))).unwrap();
+        assert_eq!(verified, original);
+    }
+
+    #[test]
+    fn test_tampered_payload_is_rejected() {
+        let mut signed = bundle().sign("release", &key(1)).unwrap();
+        signed.payload = signed.payload.replace("total * 2", "total * 0");
+        let err = signed.verify(&trusted("release", &key(1))).unwrap_err();
+        assert!(err.to_string().contains("tampered"));
+    }
+
+    #[test]
+    fn test_untrusted_key_is_rejected() {
+        let signed = bundle().sign("release", &key(1)).unwrap();
+        assert!(signed.verify(&trusted("other", &key(1))).is_err());
+        // Right name, wrong key material
+        assert!(signed.verify(&trusted("release", &key(2))).is_err());
+    }
+}
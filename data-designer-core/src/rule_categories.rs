@@ -0,0 +1,335 @@
+//! Hierarchical rule categories and the policies they impose on rules
+//!
+//! Categories nest (KYC → Screening → Sanctions) and each level may set a
+//! policy. A rule is governed by the combined policy of its category path:
+//! approval is required if any level requires it, allowed functions are the
+//! intersection of every level that restricts them, and the default severity
+//! and naming convention come from the nearest level that sets them.
+
+use crate::models::Expression;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    #[default]
+    Warning,
+    Error,
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+            Severity::Critical => "critical",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "info" => Some(Severity::Info),
+            "warning" => Some(Severity::Warning),
+            "error" => Some(Severity::Error),
+            "critical" => Some(Severity::Critical),
+            _ => None,
+        }
+    }
+}
+
+/// Policy settings for one category; unset fields are inherited from the parent
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CategoryPolicy {
+    pub approval_required: Option<bool>,
+    pub allowed_functions: Option<Vec<String>>,
+    pub default_severity: Option<Severity>,
+    /// Regex rule names must match in full, e.g. `[A-Z][a-z]*( [A-Z][a-z]*)*`
+    #[serde(default)]
+    pub rule_name_pattern: Option<String>,
+    /// What the IDs of the category's rules start with, before an underscore
+    #[serde(default)]
+    pub rule_id_prefix: Option<String>,
+    /// Correct names that don't match the pattern where one of their usual
+    /// spellings does, rather than rejecting them
+    #[serde(default)]
+    pub auto_correct_names: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleCategory {
+    pub id: i32,
+    pub category_key: String,
+    pub name: String,
+    pub parent_id: Option<i32>,
+    #[serde(default)]
+    pub policy: CategoryPolicy,
+}
+
+/// The policy in force for a category once its ancestors are taken into account
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EffectivePolicy {
+    pub approval_required: bool,
+    /// `None` means every function is allowed
+    pub allowed_functions: Option<BTreeSet<String>>,
+    pub default_severity: Severity,
+    pub rule_name_pattern: Option<String>,
+    pub rule_id_prefix: Option<String>,
+    pub auto_correct_names: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PolicyViolation {
+    FunctionNotAllowed { function: String, category: String },
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyViolation::FunctionNotAllowed { function, category } => {
+                write!(f, "Function {} is not allowed in category '{}'", function, category)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CategoryTree {
+    categories: HashMap<i32, RuleCategory>,
+}
+
+impl CategoryTree {
+    pub fn new(categories: impl IntoIterator<Item = RuleCategory>) -> Self {
+        Self {
+            categories: categories.into_iter().map(|c| (c.id, c)).collect(),
+        }
+    }
+
+    pub fn get(&self, id: i32) -> Option<&RuleCategory> {
+        self.categories.get(&id)
+    }
+
+    pub fn find_by_key(&self, category_key: &str) -> Option<&RuleCategory> {
+        self.categories.values().find(|c| c.category_key == category_key)
+    }
+
+    pub fn children(&self, id: i32) -> Vec<&RuleCategory> {
+        let mut children: Vec<_> = self.categories.values().filter(|c| c.parent_id == Some(id)).collect();
+        children.sort_by(|a, b| a.name.cmp(&b.name));
+        children
+    }
+
+    /// Categories from the root down to `id`; stops early on a missing parent or a cycle
+    pub fn path(&self, id: i32) -> Vec<&RuleCategory> {
+        let mut path = Vec::new();
+        let mut current = self.categories.get(&id);
+        while let Some(category) = current {
+            if path.iter().any(|c: &&RuleCategory| c.id == category.id) {
+                break;
+            }
+            path.push(category);
+            current = category.parent_id.and_then(|parent| self.categories.get(&parent));
+        }
+        path.reverse();
+        path
+    }
+
+    /// Display name such as "KYC / Screening / Sanctions"
+    pub fn qualified_name(&self, id: i32) -> String {
+        self.path(id).iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(" / ")
+    }
+
+    pub fn effective_policy(&self, id: i32) -> EffectivePolicy {
+        let mut effective = EffectivePolicy::default();
+        for category in self.path(id) {
+            let policy = &category.policy;
+            if policy.approval_required == Some(true) {
+                effective.approval_required = true;
+            }
+            if let Some(allowed) = &policy.allowed_functions {
+                let allowed: BTreeSet<String> = allowed.iter().map(|f| f.to_uppercase()).collect();
+                effective.allowed_functions = Some(match effective.allowed_functions {
+                    Some(inherited) => inherited.intersection(&allowed).cloned().collect(),
+                    None => allowed,
+                });
+            }
+            if let Some(severity) = policy.default_severity {
+                effective.default_severity = severity;
+            }
+            if let Some(pattern) = &policy.rule_name_pattern {
+                effective.rule_name_pattern = Some(pattern.clone());
+            }
+            if let Some(prefix) = &policy.rule_id_prefix {
+                effective.rule_id_prefix = Some(prefix.clone());
+            }
+            if let Some(auto_correct) = policy.auto_correct_names {
+                effective.auto_correct_names = auto_correct;
+            }
+        }
+        effective
+    }
+
+    /// Check a parsed rule against the policy of the category it is filed under
+    pub fn check_rule(&self, id: i32, expr: &Expression) -> Vec<PolicyViolation> {
+        let Some(allowed) = self.effective_policy(id).allowed_functions else {
+            return Vec::new();
+        };
+        let category = self.qualified_name(id);
+        called_functions(expr)
+            .into_iter()
+            .filter(|function| !allowed.contains(function))
+            .map(|function| PolicyViolation::FunctionNotAllowed {
+                function,
+                category: category.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Upper-cased names of every function called in `expr`
+pub fn called_functions(expr: &Expression) -> BTreeSet<String> {
+    let mut functions = BTreeSet::new();
+    collect_functions(expr, &mut functions);
+    functions
+}
+
+fn collect_functions(expr: &Expression, functions: &mut BTreeSet<String>) {
+    match expr {
+        Expression::FunctionCall { name, args } => {
+            functions.insert(name.to_uppercase());
+            args.iter().for_each(|arg| collect_functions(arg, functions));
+        }
+        Expression::BinaryOp { left, right, .. }
+        | Expression::Range { start: left, end: right, .. } => {
+            collect_functions(left, functions);
+            collect_functions(right, functions);
+        }
+        Expression::UnaryOp { operand, .. } => collect_functions(operand, functions),
+        Expression::Conditional { condition, then_expr, else_expr } => {
+            collect_functions(condition, functions);
+            collect_functions(then_expr, functions);
+            if let Some(else_expr) = else_expr {
+                collect_functions(else_expr, functions);
+            }
+        }
+        Expression::Case { branches, else_expr } => {
+            for (condition, result) in branches {
+                collect_functions(condition, functions);
+                collect_functions(result, functions);
+            }
+            if let Some(else_expr) = else_expr {
+                collect_functions(else_expr, functions);
+            }
+        }
+        Expression::Assignment { value, .. } => collect_functions(value, functions),
+        Expression::Cast { expr, .. } => collect_functions(expr, functions),
+        Expression::Lambda { body, .. } => collect_functions(body, functions),
+        Expression::Comprehension { element, source, condition, .. } => {
+            collect_functions(element, functions);
+            collect_functions(source, functions);
+            if let Some(condition) = condition {
+                collect_functions(condition, functions);
+            }
+        }
+        Expression::List(items)
+        | Expression::ConfigureSystem { arguments: items, .. }
+        | Expression::Activate { arguments: items, .. }
+        | Expression::RunHealthCheck { arguments: items, .. }
+        | Expression::Workflow { steps: items, .. } => {
+            items.iter().for_each(|item| collect_functions(item, functions));
+        }
+        Expression::Literal(_)
+        | Expression::Variable(_)
+        | Expression::Identifier(_)
+        | Expression::SetStatus { .. } => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_rule;
+
+    fn category(id: i32, key: &str, name: &str, parent_id: Option<i32>, policy: CategoryPolicy) -> RuleCategory {
+        RuleCategory {
+            id,
+            category_key: key.to_string(),
+            name: name.to_string(),
+            parent_id,
+            policy,
+        }
+    }
+
+    fn kyc_tree() -> CategoryTree {
+        CategoryTree::new(vec![
+            category(1, "kyc", "KYC", None, CategoryPolicy {
+                allowed_functions: Some(vec!["CONCAT".into(), "UPPER".into(), "LOOKUP".into()]),
+                default_severity: Some(Severity::Error),
+                ..Default::default()
+            }),
+            category(2, "kyc_screening", "Screening", Some(1), CategoryPolicy {
+                approval_required: Some(true),
+                ..Default::default()
+            }),
+            category(3, "kyc_sanctions", "Sanctions", Some(2), CategoryPolicy {
+                allowed_functions: Some(vec!["upper".into(), "LOOKUP".into(), "REGEX".into()]),
+                default_severity: Some(Severity::Critical),
+                approval_required: Some(false),
+                ..Default::default()
+            }),
+        ])
+    }
+
+    #[test]
+    fn test_policy_is_inherited_down_the_path() {
+        let tree = kyc_tree();
+        assert_eq!(tree.qualified_name(3), "KYC / Screening / Sanctions");
+        assert_eq!(tree.children(1).len(), 1);
+
+        let screening = tree.effective_policy(2);
+        assert!(screening.approval_required);
+        assert_eq!(screening.default_severity, Severity::Error);
+
+        // A child can narrow but not widen the allowed functions, or drop approval
+        let sanctions = tree.effective_policy(3);
+        assert!(sanctions.approval_required);
+        assert_eq!(sanctions.default_severity, Severity::Critical);
+        assert_eq!(
+            sanctions.allowed_functions,
+            Some(["LOOKUP", "UPPER"].iter().map(|f| f.to_string()).collect())
+        );
+
+        assert_eq!(tree.effective_policy(99), EffectivePolicy::default());
+    }
+
+    #[test]
+    fn test_check_rule_reports_disallowed_functions() {
+        let tree = kyc_tree();
+        let (_, rule) = parse_rule("hit = IF UPPER(name) == \"X\" THEN CONCAT(name, REGEX(id, \"a\")) ELSE 0").unwrap();
+
+        let violations = tree.check_rule(3, &rule);
+        let functions: Vec<_> = violations.iter().map(|v| match v {
+            PolicyViolation::FunctionNotAllowed { function, .. } => function.as_str(),
+        }).collect();
+        assert_eq!(functions, vec!["CONCAT", "REGEX"]);
+        assert_eq!(
+            violations[0].to_string(),
+            "Function CONCAT is not allowed in category 'KYC / Screening / Sanctions'"
+        );
+
+        assert!(tree.check_rule(1, &parse_rule("UPPER(name)").unwrap().1).is_empty());
+    }
+
+    #[test]
+    fn test_path_survives_cycles() {
+        let tree = CategoryTree::new(vec![
+            category(1, "a", "A", Some(2), CategoryPolicy::default()),
+            category(2, "b", "B", Some(1), CategoryPolicy::default()),
+        ]);
+        assert_eq!(tree.qualified_name(1), "B / A");
+    }
+}
@@ -0,0 +1,512 @@
+//! Rules that write the same attribute
+//!
+//! When two rules derive the same target, the value depends on which of them
+//! ran last. That's only safe when their conditions split the inputs between
+//! them, as `fee = IF volume > 100 THEN ...` and `fee = IF volume <= 100
+//! THEN ...` do: a rule produces a value only where its IF or CASE reaches a
+//! result other than null. Conditions made of AND, OR, NOT and comparisons of
+//! attributes with literals (==, <, IN, BETWEEN, ...) are compared exactly;
+//! anything else is taken to hold for any input, so such rules are reported
+//! as possibly overlapping rather than missed.
+
+use crate::models::{BinaryOperator, Expression, UnaryOperator, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// More alternatives than this and the conditions are treated as unknown
+const MAX_ALTERNATIVES: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// Both rules produce a value for the inputs in `overlap`
+    Overlap,
+    /// The conditions couldn't be fully compared, so the rules may overlap
+    PossibleOverlap,
+}
+
+/// Two rules that can both write `target` for the same inputs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleConflict {
+    pub target: String,
+    pub first: String,
+    pub second: String,
+    pub kind: ConflictKind,
+    /// Where both rules write, e.g. `volume > 100 AND region == "EU"`;
+    /// "always" when neither rule has a condition
+    pub overlap: String,
+}
+
+impl RuleConflict {
+    pub fn message(&self) -> String {
+        match self.kind {
+            ConflictKind::Overlap => format!(
+                "{} is written by both {} and {} when {}",
+                self.target, self.first, self.second, self.overlap
+            ),
+            ConflictKind::PossibleOverlap => format!(
+                "{} is written by both {} and {}, whose conditions may overlap ({})",
+                self.target, self.first, self.second, self.overlap
+            ),
+        }
+    }
+}
+
+/// Every pair of rules with the same target whose conditions overlap, in the
+/// order the rules are given. Each rule is `(id, target, expression)`; for an
+/// assignment the conditions are those of the assigned value.
+pub fn find_conflicts<'a>(rules: impl IntoIterator<Item = (&'a str, &'a str, &'a Expression)>) -> Vec<RuleConflict> {
+    let mut by_target: BTreeMap<&str, Vec<(&str, Vec<Conjunction>)>> = BTreeMap::new();
+    let mut targets = Vec::new();
+    for (id, target, expression) in rules {
+        let value = match expression {
+            Expression::Assignment { value, .. } => value.as_ref(),
+            expression => expression,
+        };
+        if !by_target.contains_key(target) {
+            targets.push(target);
+        }
+        by_target.entry(target).or_default().push((id, writes(value)));
+    }
+
+    let mut conflicts = Vec::new();
+    for target in targets {
+        let rules = &by_target[target];
+        for (index, (first, first_writes)) in rules.iter().enumerate() {
+            for (second, second_writes) in &rules[index + 1..] {
+                if let Some((kind, overlap)) = overlap(first_writes, second_writes) {
+                    conflicts.push(RuleConflict {
+                        target: target.to_string(),
+                        first: first.to_string(),
+                        second: second.to_string(),
+                        kind,
+                        overlap,
+                    });
+                }
+            }
+        }
+    }
+    conflicts
+}
+
+/// The first region where both rules write, preferring one known for certain
+fn overlap(first: &[Conjunction], second: &[Conjunction]) -> Option<(ConflictKind, String)> {
+    let mut possible = None;
+    for a in first {
+        for b in second {
+            if let Some(both) = a.and(b) {
+                if both.exact {
+                    return Some((ConflictKind::Overlap, both.to_string()));
+                }
+                possible.get_or_insert_with(|| (ConflictKind::PossibleOverlap, both.to_string()));
+            }
+        }
+    }
+    possible
+}
+
+/// A literal an attribute is compared with
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Number(f64),
+    Text(String),
+    Boolean(bool),
+}
+
+impl Literal {
+    fn from_expression(expr: &Expression) -> Option<Self> {
+        match expr {
+            Expression::Literal(Value::Integer(n)) => Some(Literal::Number(*n as f64)),
+            Expression::Literal(Value::Number(n) | Value::Float(n)) => Some(Literal::Number(*n)),
+            Expression::Literal(Value::String(s)) => Some(Literal::Text(s.clone())),
+            Expression::Literal(Value::Boolean(b)) => Some(Literal::Boolean(*b)),
+            Expression::UnaryOp { op: UnaryOperator::Minus, operand } => match Self::from_expression(operand)? {
+                Literal::Number(n) => Some(Literal::Number(-n)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn number(&self) -> Option<f64> {
+        match self {
+            Literal::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Literal::Number(n) => write!(f, "{}", n),
+            Literal::Text(s) => write!(f, "\"{}\"", s),
+            Literal::Boolean(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+/// The values one attribute can take: within the bounds, one of `allowed`
+/// when that's set, and none of `excluded`. Bounds are `(value, inclusive)`.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Constraint {
+    low: Option<(f64, bool)>,
+    high: Option<(f64, bool)>,
+    allowed: Option<Vec<Literal>>,
+    excluded: Vec<Literal>,
+}
+
+impl Constraint {
+    fn compare(op: BinaryOperator, literal: Literal) -> Option<Self> {
+        let bound = |inclusive| literal.number().map(|n| (n, inclusive));
+        Some(match op {
+            BinaryOperator::Equals => Constraint { allowed: Some(vec![literal]), ..Default::default() },
+            BinaryOperator::NotEquals => Constraint { excluded: vec![literal], ..Default::default() },
+            BinaryOperator::LessThan => Constraint { high: Some(bound(false)?), ..Default::default() },
+            BinaryOperator::LessThanOrEqual => Constraint { high: Some(bound(true)?), ..Default::default() },
+            BinaryOperator::GreaterThan => Constraint { low: Some(bound(false)?), ..Default::default() },
+            BinaryOperator::GreaterThanOrEqual => Constraint { low: Some(bound(true)?), ..Default::default() },
+            _ => return None,
+        })
+    }
+
+    fn within_bounds(&self, n: f64) -> bool {
+        self.low.is_none_or(|(low, inclusive)| n > low || (inclusive && n == low))
+            && self.high.is_none_or(|(high, inclusive)| n < high || (inclusive && n == high))
+    }
+
+    /// Both constraints at once, or None when no value satisfies them
+    fn and(&self, other: &Constraint) -> Option<Constraint> {
+        let tighter_low = match (self.low, other.low) {
+            (Some(a), Some(b)) => Some(if a.0 > b.0 || (a.0 == b.0 && !a.1) { a } else { b }),
+            (a, b) => a.or(b),
+        };
+        let tighter_high = match (self.high, other.high) {
+            (Some(a), Some(b)) => Some(if a.0 < b.0 || (a.0 == b.0 && !a.1) { a } else { b }),
+            (a, b) => a.or(b),
+        };
+        let allowed = match (&self.allowed, &other.allowed) {
+            (Some(a), Some(b)) => Some(a.iter().filter(|v| b.contains(v)).cloned().collect()),
+            (a, b) => a.clone().or_else(|| b.clone()),
+        };
+        let mut excluded = self.excluded.clone();
+        excluded.extend(other.excluded.iter().filter(|v| !self.excluded.contains(v)).cloned());
+
+        Constraint { low: tighter_low, high: tighter_high, allowed, excluded }.normalize()
+    }
+
+    /// Resolves `allowed` against the bounds and exclusions
+    fn normalize(self) -> Option<Constraint> {
+        if let Some(allowed) = &self.allowed {
+            let bounded = self.low.is_some() || self.high.is_some();
+            let allowed: Vec<Literal> = allowed
+                .iter()
+                .filter(|v| !self.excluded.contains(v))
+                .filter(|v| !bounded || v.number().is_some_and(|n| self.within_bounds(n)))
+                .cloned()
+                .collect();
+            return (!allowed.is_empty()).then(|| Constraint { allowed: Some(allowed), ..Default::default() });
+        }
+        match (self.low, self.high) {
+            (Some((low, low_inclusive)), Some((high, high_inclusive))) => {
+                let single_point = low == high && low_inclusive && high_inclusive;
+                if low < high {
+                    Some(self)
+                } else if single_point && !self.excluded.contains(&Literal::Number(low)) {
+                    Some(Constraint { allowed: Some(vec![Literal::Number(low)]), ..Default::default() })
+                } else {
+                    None
+                }
+            }
+            _ => Some(self),
+        }
+    }
+
+    fn describe(&self, attribute: &str) -> Vec<String> {
+        let mut parts = Vec::new();
+        match self.allowed.as_deref() {
+            Some([value]) => parts.push(format!("{} == {}", attribute, value)),
+            Some(values) => parts.push(format!(
+                "{} IN ({})",
+                attribute,
+                values.iter().map(Literal::to_string).collect::<Vec<_>>().join(", ")
+            )),
+            None => {}
+        }
+        if let Some((low, inclusive)) = self.low {
+            parts.push(format!("{} {} {}", attribute, if inclusive { ">=" } else { ">" }, low));
+        }
+        if let Some((high, inclusive)) = self.high {
+            parts.push(format!("{} {} {}", attribute, if inclusive { "<=" } else { "<" }, high));
+        }
+        parts.extend(self.excluded.iter().map(|value| format!("{} != {}", attribute, value)));
+        parts
+    }
+}
+
+/// Constraints that all hold; `exact` is false when conditions that couldn't
+/// be analysed were left out, making this wider than the real condition
+#[derive(Debug, Clone, PartialEq)]
+struct Conjunction {
+    constraints: BTreeMap<String, Constraint>,
+    exact: bool,
+}
+
+impl Conjunction {
+    fn always() -> Self {
+        Conjunction { constraints: BTreeMap::new(), exact: true }
+    }
+
+    fn unknown() -> Self {
+        Conjunction { constraints: BTreeMap::new(), exact: false }
+    }
+
+    fn single(attribute: &str, constraint: Constraint) -> Self {
+        Conjunction { constraints: BTreeMap::from([(attribute.to_string(), constraint)]), exact: true }
+    }
+
+    fn and(&self, other: &Conjunction) -> Option<Conjunction> {
+        let mut constraints = self.constraints.clone();
+        for (attribute, constraint) in &other.constraints {
+            let combined = match constraints.get(attribute) {
+                Some(existing) => existing.and(constraint)?,
+                None => constraint.clone(),
+            };
+            constraints.insert(attribute.clone(), combined);
+        }
+        Some(Conjunction { constraints, exact: self.exact && other.exact })
+    }
+}
+
+impl std::fmt::Display for Conjunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let parts: Vec<String> = self
+            .constraints
+            .iter()
+            .flat_map(|(attribute, constraint)| constraint.describe(attribute))
+            .collect();
+        if parts.is_empty() {
+            write!(f, "always")
+        } else {
+            write!(f, "{}", parts.join(" AND "))
+        }
+    }
+}
+
+/// Alternatives that hold together when each of `a` and `b` holds
+fn and_all(a: &[Conjunction], b: &[Conjunction]) -> Vec<Conjunction> {
+    let combined: Vec<Conjunction> = a.iter().flat_map(|x| b.iter().filter_map(move |y| x.and(y))).collect();
+    if combined.len() > MAX_ALTERNATIVES {
+        return vec![Conjunction::unknown()];
+    }
+    combined
+}
+
+fn or_all(mut a: Vec<Conjunction>, b: Vec<Conjunction>) -> Vec<Conjunction> {
+    a.extend(b);
+    if a.len() > MAX_ALTERNATIVES {
+        return vec![Conjunction::unknown()];
+    }
+    a
+}
+
+/// When `expr` produces a value other than null, as alternatives
+fn writes(expr: &Expression) -> Vec<Conjunction> {
+    match expr {
+        Expression::Literal(Value::Null) => Vec::new(),
+        Expression::Conditional { condition, then_expr, else_expr } => {
+            let taken = and_all(&holds(condition, false), &writes(then_expr));
+            let not_taken = match else_expr {
+                Some(else_expr) => and_all(&holds(condition, true), &writes(else_expr)),
+                None => Vec::new(),
+            };
+            or_all(taken, not_taken)
+        }
+        Expression::Case { branches, else_expr } => {
+            // Each arm applies when its condition holds and none before it did
+            let mut result = Vec::new();
+            let mut earlier_failed = vec![Conjunction::always()];
+            for (condition, value) in branches {
+                let arm = and_all(&earlier_failed, &holds(condition, false));
+                result = or_all(result, and_all(&arm, &writes(value)));
+                earlier_failed = and_all(&earlier_failed, &holds(condition, true));
+            }
+            if let Some(else_expr) = else_expr {
+                result = or_all(result, and_all(&earlier_failed, &writes(else_expr)));
+            }
+            result
+        }
+        _ => vec![Conjunction::always()],
+    }
+}
+
+/// When `condition` holds (or, `negated`, when it doesn't), as alternatives
+fn holds(condition: &Expression, negated: bool) -> Vec<Conjunction> {
+    let attribute = |expr: &Expression| match expr {
+        Expression::Identifier(name) | Expression::Variable(name) => Some(name.clone()),
+        _ => None,
+    };
+
+    match condition {
+        Expression::Literal(Value::Boolean(b)) => {
+            if *b != negated { vec![Conjunction::always()] } else { Vec::new() }
+        }
+        Expression::Identifier(name) | Expression::Variable(name) => {
+            let constraint = Constraint { allowed: Some(vec![Literal::Boolean(!negated)]), ..Default::default() };
+            vec![Conjunction::single(name, constraint)]
+        }
+        Expression::UnaryOp { op: UnaryOperator::Not, operand } => holds(operand, !negated),
+
+        Expression::BinaryOp { left, op: op @ (BinaryOperator::And | BinaryOperator::Or), right } => {
+            let (left, right) = (holds(left, negated), holds(right, negated));
+            // NOT (a AND b) is NOT a OR NOT b
+            if (*op == BinaryOperator::And) != negated {
+                and_all(&left, &right)
+            } else {
+                or_all(left, right)
+            }
+        }
+
+        Expression::BinaryOp { left, op: op @ (BinaryOperator::In | BinaryOperator::NotIn | BinaryOperator::Between), right } => {
+            let Some(name) = attribute(left) else {
+                return vec![Conjunction::unknown()];
+            };
+            let inside = (*op != BinaryOperator::NotIn) != negated;
+            match right.as_ref() {
+                Expression::Range { start, end, inclusive } => {
+                    let (Some(start), Some(end)) = (
+                        Literal::from_expression(start).and_then(|l| l.number()),
+                        Literal::from_expression(end).and_then(|l| l.number()),
+                    ) else {
+                        return vec![Conjunction::unknown()];
+                    };
+                    if inside {
+                        let range = Constraint { low: Some((start, true)), high: Some((end, *inclusive)), ..Default::default() };
+                        range.normalize().map(|c| Conjunction::single(&name, c)).into_iter().collect()
+                    } else {
+                        let below = Constraint { high: Some((start, false)), ..Default::default() };
+                        let above = Constraint { low: Some((end, !*inclusive)), ..Default::default() };
+                        vec![Conjunction::single(&name, below), Conjunction::single(&name, above)]
+                    }
+                }
+                Expression::List(items) if *op != BinaryOperator::Between => {
+                    let Some(values) = items.iter().map(Literal::from_expression).collect::<Option<Vec<_>>>() else {
+                        return vec![Conjunction::unknown()];
+                    };
+                    let constraint = if inside {
+                        Constraint { allowed: Some(values), ..Default::default() }
+                    } else {
+                        Constraint { excluded: values, ..Default::default() }
+                    };
+                    constraint.normalize().map(|c| Conjunction::single(&name, c)).into_iter().collect()
+                }
+                _ => vec![Conjunction::unknown()],
+            }
+        }
+
+        Expression::BinaryOp { left, op, right } => {
+            // attribute op literal, or literal op attribute with the operator mirrored
+            let (name, op, literal) = match (attribute(left), attribute(right)) {
+                (Some(name), _) => (name, *op, Literal::from_expression(right)),
+                (None, Some(name)) => (name, mirrored(*op), Literal::from_expression(left)),
+                (None, None) => return vec![Conjunction::unknown()],
+            };
+            let op = if negated { negation(op) } else { Some(op) };
+            match (op, literal) {
+                (Some(op), Some(literal)) => match Constraint::compare(op, literal) {
+                    Some(constraint) => vec![Conjunction::single(&name, constraint)],
+                    None => vec![Conjunction::unknown()],
+                },
+                _ => vec![Conjunction::unknown()],
+            }
+        }
+
+        _ => vec![Conjunction::unknown()],
+    }
+}
+
+/// `a op b` as `b op' a`
+fn mirrored(op: BinaryOperator) -> BinaryOperator {
+    match op {
+        BinaryOperator::LessThan => BinaryOperator::GreaterThan,
+        BinaryOperator::LessThanOrEqual => BinaryOperator::GreaterThanOrEqual,
+        BinaryOperator::GreaterThan => BinaryOperator::LessThan,
+        BinaryOperator::GreaterThanOrEqual => BinaryOperator::LessThanOrEqual,
+        op => op,
+    }
+}
+
+fn negation(op: BinaryOperator) -> Option<BinaryOperator> {
+    Some(match op {
+        BinaryOperator::Equals => BinaryOperator::NotEquals,
+        BinaryOperator::NotEquals => BinaryOperator::Equals,
+        BinaryOperator::LessThan => BinaryOperator::GreaterThanOrEqual,
+        BinaryOperator::LessThanOrEqual => BinaryOperator::GreaterThan,
+        BinaryOperator::GreaterThan => BinaryOperator::LessThanOrEqual,
+        BinaryOperator::GreaterThanOrEqual => BinaryOperator::LessThan,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_rule;
+
+    fn conflicts(rules: &[(&str, &str)]) -> Vec<RuleConflict> {
+        let parsed: Vec<(&str, Expression)> = rules
+            .iter()
+            .map(|(id, rule)| {
+                let (rest, expression) = parse_rule(rule).unwrap();
+                assert!(rest.is_empty(), "{} left {}", rule, rest);
+                (*id, expression)
+            })
+            .collect();
+        let targeted: Vec<(&str, String, &Expression)> = parsed
+            .iter()
+            .map(|(id, expression)| match expression {
+                Expression::Assignment { target, .. } => (*id, target.clone(), expression),
+                _ => unreachable!(),
+            })
+            .collect();
+        find_conflicts(targeted.iter().map(|(id, target, expression)| (*id, target.as_str(), *expression)))
+    }
+
+    #[test]
+    fn test_split_conditions_dont_conflict() {
+        assert!(conflicts(&[
+            ("large", "fee = IF volume > 100 THEN volume * 0.01"),
+            ("small", "fee = IF volume <= 100 THEN 5"),
+            ("tax", "tax = fee * 0.2"),
+        ]).is_empty());
+        assert!(conflicts(&[
+            ("eu", r#"fee = IF region IN ["DE", "FR"] THEN 2"#),
+            ("us", r#"fee = IF region == "US" AND volume BETWEEN 1 AND 10 THEN 3"#),
+            ("other", r#"fee = CASE WHEN region IN ["DE", "FR", "US"] THEN null ELSE 1 END"#),
+        ]).is_empty());
+    }
+
+    #[test]
+    fn test_reports_overlap_and_unknown_conditions() {
+        let found = conflicts(&[
+            ("large", "fee = IF volume > 100 THEN 1"),
+            ("vip", "fee = IF vip AND volume < 500 THEN 2"),
+            ("flat", "fee = 3"),
+        ]);
+        assert_eq!(found.len(), 3);
+        assert_eq!((found[0].first.as_str(), found[0].second.as_str()), ("large", "vip"));
+        assert_eq!(found[0].kind, ConflictKind::Overlap);
+        assert_eq!(found[0].overlap, "vip == true AND volume > 100 AND volume < 500");
+        assert_eq!(found[2].overlap, "vip == true AND volume < 500");
+
+        let found = conflicts(&[
+            ("a", "score = IF LEN(name) > 3 AND tier == 1 THEN 1"),
+            ("b", "score = IF tier == 2 THEN 2"),
+            ("c", "score = IF LEN(name) <= 3 THEN 3"),
+        ]);
+        // a and b are split by tier whatever LEN(name) is
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].kind, ConflictKind::PossibleOverlap);
+        assert_eq!(found[0].message(), "score is written by both a and c, whose conditions may overlap (tier == 1)");
+    }
+}
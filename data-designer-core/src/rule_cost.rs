@@ -0,0 +1,262 @@
+//! Static execution cost of a rule, estimated before it is activated
+//!
+//! Counts the expensive operations a rule performs (LOOKUPs, regex matches
+//! and calls to host functions, which may leave the process) and estimates
+//! its latency along the slowest path through its IF and CASE arms. Lambda
+//! bodies are charged for a list of `ASSUMED_LIST_LENGTH` items. Observed
+//! latencies replace the defaults where they are known: per host function in
+//! the `CostModel`, and for the whole rule once enough of its executions are
+//! recorded (see `RuleOperations::get_rule_latency_history`).
+
+pub use crate::config::LatencyBudget;
+use crate::function_registry::BUILTIN_FUNCTIONS;
+use crate::models::{BinaryOperator, Expression};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Items a lambda is assumed to run over; list lengths aren't known statically
+pub const ASSUMED_LIST_LENGTH: f64 = 10.0;
+
+/// Logged executions needed before a rule's own latency replaces the estimate
+pub const MIN_HISTORY_SAMPLES: u64 = 20;
+
+/// Functions that match a regular expression on every call
+pub const REGEX_FUNCTIONS: &[&str] = &[
+    "EXTRACT", "EXTRACT_ALL", "MATCH_COUNT", "MATCHES", "VALIDATE", "IS_EMAIL", "IS_LEI", "IS_SWIFT", "IS_PHONE",
+];
+
+/// Microseconds charged per operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CostModel {
+    /// Each literal, attribute read and operator
+    pub node_us: f64,
+    pub builtin_call_us: f64,
+    pub lookup_us: f64,
+    pub regex_us: f64,
+    /// A host function with no observed latency
+    pub external_call_us: f64,
+    /// Observed latency of host functions, by upper-cased name
+    pub observed_us: BTreeMap<String, f64>,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        CostModel {
+            node_us: 0.05,
+            builtin_call_us: 0.5,
+            lookup_us: 2.0,
+            regex_us: 5.0,
+            external_call_us: 2_000.0,
+            observed_us: BTreeMap::new(),
+        }
+    }
+}
+
+/// Latency of a rule's logged executions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencyHistory {
+    pub samples: u64,
+    pub p95_us: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RuleCost {
+    pub nodes: usize,
+    pub lookups: usize,
+    pub regexes: usize,
+    /// Host functions called, by upper-cased name, with the number of call sites
+    pub external_calls: BTreeMap<String, usize>,
+    /// Estimated latency of the slowest path through the rule
+    pub static_latency_us: f64,
+    /// 95th percentile of the rule's logged executions, once there are enough
+    pub observed_latency_us: Option<f64>,
+}
+
+impl RuleCost {
+    /// The observed latency when there is one, the static estimate otherwise
+    pub fn expected_latency_us(&self) -> f64 {
+        self.observed_latency_us.unwrap_or(self.static_latency_us)
+    }
+
+    pub fn with_history(mut self, history: &LatencyHistory) -> Self {
+        if history.samples >= MIN_HISTORY_SAMPLES {
+            self.observed_latency_us = Some(history.p95_us);
+        }
+        self
+    }
+
+    pub fn exceeds(&self, budget: &LatencyBudget) -> bool {
+        self.expected_latency_us() > budget.batch_scoring_us
+    }
+
+    /// Why the rule is too slow for batch scoring, or None when it fits the budget
+    pub fn budget_warning(&self, budget: &LatencyBudget) -> Option<String> {
+        if !self.exceeds(budget) {
+            return None;
+        }
+        let basis = if self.observed_latency_us.is_some() { "Observed p95" } else { "Estimated" };
+        let mut causes = Vec::new();
+        if !self.external_calls.is_empty() {
+            let names: Vec<&str> = self.external_calls.keys().map(String::as_str).collect();
+            causes.push(format!("calls {}", names.join(", ")));
+        }
+        if self.lookups > 0 {
+            causes.push(format!("{} LOOKUP{}", self.lookups, if self.lookups == 1 { "" } else { "s" }));
+        }
+        if self.regexes > 0 {
+            causes.push(format!("{} regex match{}", self.regexes, if self.regexes == 1 { "" } else { "es" }));
+        }
+        let mut message = format!(
+            "{} latency {} exceeds the batch scoring budget of {}",
+            basis,
+            format_us(self.expected_latency_us()),
+            format_us(budget.batch_scoring_us)
+        );
+        if !causes.is_empty() {
+            message.push_str(&format!(": {}", causes.join("; ")));
+        }
+        Some(message)
+    }
+}
+
+/// Cost of `ast` under the default cost model
+pub fn estimate_rule_cost(ast: &Expression) -> RuleCost {
+    CostModel::default().estimate(ast)
+}
+
+impl CostModel {
+    pub fn estimate(&self, ast: &Expression) -> RuleCost {
+        let mut cost = RuleCost::default();
+        cost.static_latency_us = self.latency(ast, &mut cost);
+        cost
+    }
+
+    fn latency(&self, expr: &Expression, cost: &mut RuleCost) -> f64 {
+        cost.nodes += 1;
+        self.node_us
+            + match expr {
+                Expression::BinaryOp { left, op, right } => {
+                    let matching = if matches!(op, BinaryOperator::Matches | BinaryOperator::NotMatches) {
+                        cost.regexes += 1;
+                        self.regex_us
+                    } else {
+                        0.0
+                    };
+                    matching + self.latency(left, cost) + self.latency(right, cost)
+                }
+                Expression::UnaryOp { operand, .. } => self.latency(operand, cost),
+                Expression::FunctionCall { name, args } => {
+                    let args: f64 = args.iter().map(|arg| self.latency(arg, cost)).sum();
+                    args + self.call(&name.to_uppercase(), cost)
+                }
+                Expression::Lambda { body, .. } => self.latency(body, cost) * ASSUMED_LIST_LENGTH,
+                Expression::Comprehension { element, source, condition, .. } => {
+                    let per_item = self.latency(element, cost) + condition.as_ref().map_or(0.0, |c| self.latency(c, cost));
+                    self.latency(source, cost) + per_item * ASSUMED_LIST_LENGTH
+                }
+                Expression::Conditional { condition, then_expr, else_expr } => {
+                    let condition = self.latency(condition, cost);
+                    let then_expr = self.latency(then_expr, cost);
+                    let else_expr = else_expr.as_ref().map_or(0.0, |e| self.latency(e, cost));
+                    condition + then_expr.max(else_expr)
+                }
+                Expression::Case { branches, else_expr } => {
+                    // Every condition may be tested before the last arm is taken
+                    let mut conditions = 0.0;
+                    let mut slowest = else_expr.as_ref().map_or(0.0, |e| self.latency(e, cost));
+                    for (condition, result) in branches {
+                        conditions += self.latency(condition, cost);
+                        slowest = slowest.max(self.latency(result, cost));
+                    }
+                    conditions + slowest
+                }
+                Expression::Assignment { value, .. } => self.latency(value, cost),
+                Expression::Cast { expr, .. } => self.latency(expr, cost),
+                Expression::List(items) => items.iter().map(|item| self.latency(item, cost)).sum(),
+                Expression::Range { start, end, .. } => self.latency(start, cost) + self.latency(end, cost),
+                // Literals, attribute reads and the workflow verbs, which the
+                // rules engine doesn't evaluate
+                _ => 0.0,
+            }
+    }
+
+    fn call(&self, name: &str, cost: &mut RuleCost) -> f64 {
+        if name == "LOOKUP" {
+            cost.lookups += 1;
+            self.lookup_us
+        } else if REGEX_FUNCTIONS.contains(&name) {
+            cost.regexes += 1;
+            self.regex_us
+        } else if BUILTIN_FUNCTIONS.contains(&name) {
+            self.builtin_call_us
+        } else {
+            *cost.external_calls.entry(name.to_string()).or_default() += 1;
+            self.observed_us.get(name).copied().unwrap_or(self.external_call_us)
+        }
+    }
+}
+
+fn format_us(us: f64) -> String {
+    if us >= 1_000.0 {
+        format!("{:.1} ms", us / 1_000.0)
+    } else {
+        format!("{:.0} µs", us)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_rule;
+
+    fn cost(input: &str) -> RuleCost {
+        let (remaining, ast) = parse_rule(input).unwrap();
+        assert!(remaining.trim().is_empty(), "unparsed: {}", remaining);
+        estimate_rule_cost(&ast)
+    }
+
+    #[test]
+    fn test_counts_lookups_regexes_and_host_calls() {
+        let rule = cost(r#"risk = IF LOOKUP(country, "countries") == "HIGH" OR name MATCHES /ACME/ THEN SCREEN_SANCTIONS(name) ELSE EXTRACT(lei, /\w{4}/)"#);
+        assert_eq!(rule.lookups, 1);
+        assert_eq!(rule.regexes, 2);
+        assert_eq!(rule.external_calls, BTreeMap::from([("SCREEN_SANCTIONS".to_string(), 1)]));
+
+        let budget = LatencyBudget::default();
+        assert!(rule.exceeds(&budget));
+        assert_eq!(
+            rule.budget_warning(&budget).unwrap(),
+            "Estimated latency 2.0 ms exceeds the batch scoring budget of 1.0 ms: calls SCREEN_SANCTIONS; 1 LOOKUP; 2 regex matches"
+        );
+    }
+
+    #[test]
+    fn test_latency_follows_the_slowest_arm() {
+        let cheap = cost("fee = IF volume > 100 THEN volume * 0.01 ELSE 25");
+        assert!(cheap.static_latency_us < 1.0);
+        assert_eq!(cheap.budget_warning(&LatencyBudget::default()), None);
+
+        let model = CostModel { observed_us: BTreeMap::from([("SCORE".to_string(), 40.0)]), ..CostModel::default() };
+        let (_, ast) = parse_rule("score = IF is_pep THEN SCORE(name) ELSE 0").unwrap();
+        let slow_arm = model.estimate(&ast);
+        assert!(slow_arm.static_latency_us > 40.0 && slow_arm.static_latency_us < 41.0);
+
+        let (_, ast) = parse_rule("flagged = ANY(names, n -> SCORE(n) > 50)").unwrap();
+        assert!(model.estimate(&ast).static_latency_us > 40.0 * ASSUMED_LIST_LENGTH);
+    }
+
+    #[test]
+    fn test_history_replaces_the_estimate_once_there_is_enough() {
+        let rule = cost("total = amount * rate");
+        let few = rule.clone().with_history(&LatencyHistory { samples: 3, p95_us: 5_000.0 });
+        assert_eq!(few.observed_latency_us, None);
+
+        let many = rule.with_history(&LatencyHistory { samples: 500, p95_us: 5_000.0 });
+        assert_eq!(many.expected_latency_us(), 5_000.0);
+        assert_eq!(
+            many.budget_warning(&LatencyBudget::default()).unwrap(),
+            "Observed p95 latency 5.0 ms exceeds the batch scoring budget of 1.0 ms"
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! Coverage of a rule set over a sample dataset
+//!
+//! Before go-live, authors run the rules over representative data to find
+//! dead code: rules that never produced a value, IF and CASE branches never
+//! taken and attributes no rule reads. Branches are counted by walking each
+//! evaluation trace (`evaluate_traced`) alongside the rule's AST, so a branch
+//! counts as taken exactly when the evaluator took it. Conditionals inside
+//! lambdas aren't traced and so aren't reported.
+
+use crate::evaluator::{to_bool, TraceNode};
+use crate::formatter::format_expression;
+use crate::models::{Expression, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// One IF or CASE of a rule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BranchCoverage {
+    pub expression: String,
+    pub arms: Vec<ArmCoverage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArmCoverage {
+    /// "THEN", "ELSE" or "WHEN <condition>"
+    pub label: String,
+    /// Evaluations that took this arm
+    pub taken: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleCoverage {
+    pub rule: String,
+    pub version: i32,
+    /// Contexts the rule was evaluated for; a context that already holds the
+    /// rule's attribute doesn't evaluate it
+    pub evaluated: usize,
+    /// Evaluations that produced a value other than null
+    pub fired: usize,
+    pub failed: usize,
+    /// In the order they appear in the rule
+    pub branches: Vec<BranchCoverage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UntakenBranch {
+    pub rule: String,
+    pub expression: String,
+    pub arm: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoverageReport {
+    pub contexts: usize,
+    pub rules: Vec<RuleCoverage>,
+    /// Rules that didn't produce a non-null value for any context
+    pub never_fired: Vec<String>,
+    pub untaken_branches: Vec<UntakenBranch>,
+    /// Attributes of the dictionary or the dataset that no rule reads
+    pub unreferenced_attributes: Vec<String>,
+}
+
+impl RuleCoverage {
+    /// Coverage of a rule before any evaluation: every branch untaken
+    pub fn new(rule: &str, version: i32, expression: &Expression) -> Self {
+        let mut branches = Vec::new();
+        visit(expression, None, &mut 0, &mut branches);
+        Self {
+            rule: rule.to_string(),
+            version,
+            evaluated: 0,
+            fired: 0,
+            failed: 0,
+            branches,
+        }
+    }
+
+    /// Counts one evaluation of `expression`, the rule's AST, from its trace
+    pub fn record(&mut self, expression: &Expression, trace: &TraceNode) {
+        self.evaluated += 1;
+        match &trace.value {
+            Some(Value::Null) => {}
+            Some(_) => self.fired += 1,
+            None => self.failed += 1,
+        }
+        visit(expression, Some(trace), &mut 0, &mut self.branches);
+    }
+}
+
+impl CoverageReport {
+    pub fn new(contexts: usize, rules: Vec<RuleCoverage>, unreferenced_attributes: BTreeSet<String>) -> Self {
+        let never_fired = rules.iter().filter(|rule| rule.fired == 0).map(|rule| rule.rule.clone()).collect();
+        let untaken_branches = rules
+            .iter()
+            .flat_map(|rule| {
+                rule.branches.iter().flat_map(move |branch| {
+                    branch.arms.iter().filter(|arm| arm.taken == 0).map(move |arm| UntakenBranch {
+                        rule: rule.rule.clone(),
+                        expression: branch.expression.clone(),
+                        arm: arm.label.clone(),
+                    })
+                })
+            })
+            .collect();
+        Self {
+            contexts,
+            rules,
+            never_fired,
+            untaken_branches,
+            unreferenced_attributes: unreferenced_attributes.into_iter().collect(),
+        }
+    }
+}
+
+/// Walks the AST in the order the tracer evaluates it, numbering the IFs and
+/// CASEs as it meets them; `node` is the trace of `expr`, or None where the
+/// evaluation never got. A first walk without a trace creates the branches.
+fn visit(expr: &Expression, node: Option<&TraceNode>, next: &mut usize, branches: &mut Vec<BranchCoverage>) {
+    let inputs = node.map_or(&[][..], |node| node.inputs.as_slice());
+    match expr {
+        Expression::Conditional { condition, then_expr, else_expr } => {
+            let id = branch(next, branches, || BranchCoverage {
+                expression: format!("IF {}", format_expression(condition)),
+                arms: vec![arm("THEN"), arm("ELSE")],
+            });
+            let condition_node = inputs.first();
+            visit(condition, condition_node, next, branches);
+            let taken = condition_node.and_then(|c| c.value.as_ref()).map(to_bool);
+            visit(then_expr, inputs.get(1).filter(|_| taken == Some(true)), next, branches);
+            if let Some(else_expr) = else_expr {
+                visit(else_expr, inputs.get(1).filter(|_| taken == Some(false)), next, branches);
+            }
+            if let Some(taken) = taken {
+                branches[id].arms[if taken { 0 } else { 1 }].taken += 1;
+            }
+        }
+
+        Expression::Case { branches: whens, else_expr } => {
+            let id = branch(next, branches, || BranchCoverage {
+                expression: whens
+                    .first()
+                    .map_or_else(|| "CASE".to_string(), |(first, _)| format!("CASE WHEN {} ...", format_expression(first))),
+                arms: whens
+                    .iter()
+                    .map(|(condition, _)| arm(&format!("WHEN {}", format_expression(condition))))
+                    .chain(std::iter::once(arm("ELSE")))
+                    .collect(),
+            });
+            // Inputs alternate condition, condition, ... until one holds, then its result
+            let mut position = 0;
+            let mut taken = node.map(|_| whens.len());
+            for (index, (condition, result)) in whens.iter().enumerate() {
+                let condition_node = taken.filter(|&t| t == whens.len()).and_then(|_| inputs.get(position));
+                visit(condition, condition_node, next, branches);
+                position += 1;
+                match condition_node.map(|c| c.value.as_ref().map(to_bool)) {
+                    Some(Some(true)) => {
+                        taken = Some(index);
+                        visit(result, inputs.get(position), next, branches);
+                    }
+                    Some(None) => {
+                        // The condition failed and the CASE with it
+                        taken = None;
+                        visit(result, None, next, branches);
+                    }
+                    _ => visit(result, None, next, branches),
+                }
+            }
+            if let Some(else_expr) = else_expr {
+                let else_node = taken.filter(|&t| t == whens.len()).and_then(|_| inputs.get(position));
+                visit(else_expr, else_node, next, branches);
+            }
+            if let Some(taken) = taken {
+                branches[id].arms[taken].taken += 1;
+            }
+        }
+
+        _ => {
+            for (index, child) in traced_inputs(expr).into_iter().enumerate() {
+                visit(child, inputs.get(index), next, branches);
+            }
+        }
+    }
+}
+
+/// The sub-expressions the tracer records as inputs of `expr`, in its order
+fn traced_inputs(expr: &Expression) -> Vec<&Expression> {
+    match expr {
+        Expression::Assignment { value, .. } => vec![value],
+        Expression::BinaryOp { left, right, .. } => match right.as_ref() {
+            Expression::Range { start, end, .. } => vec![left, start, end],
+            right => vec![left, right],
+        },
+        Expression::UnaryOp { operand, .. } => vec![operand],
+        Expression::FunctionCall { args, .. } if !args.iter().any(|arg| matches!(arg, Expression::Lambda { .. })) => {
+            args.iter().collect()
+        }
+        Expression::Cast { expr, .. } => vec![expr],
+        Expression::List(items) => items.iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn branch(next: &mut usize, branches: &mut Vec<BranchCoverage>, create: impl FnOnce() -> BranchCoverage) -> usize {
+    let id = *next;
+    *next += 1;
+    if branches.len() == id {
+        branches.push(create());
+    }
+    id
+}
+
+fn arm(label: &str) -> ArmCoverage {
+    ArmCoverage { label: label.to_string(), taken: 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::{evaluate_traced, Facts, FunctionLibrary};
+    use crate::parser::parse_rule;
+
+    fn coverage(rule: &str, contexts: &[&[(&str, Value)]]) -> RuleCoverage {
+        let (_, expression) = parse_rule(rule).unwrap();
+        let mut coverage = RuleCoverage::new("rule", 1, &expression);
+        for context in contexts {
+            let facts: Facts = context.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
+            coverage.record(&expression, &evaluate_traced(&expression, &facts, &FunctionLibrary::new()));
+        }
+        coverage
+    }
+
+    fn taken(coverage: &RuleCoverage) -> Vec<Vec<usize>> {
+        coverage.branches.iter().map(|b| b.arms.iter().map(|a| a.taken).collect()).collect()
+    }
+
+    #[test]
+    fn test_counts_arms_of_nested_conditionals() {
+        let rule = "fee = IF volume > 100 THEN (IF vip THEN 1 ELSE 2) ELSE 3";
+        let coverage = coverage(rule, &[
+            &[("volume", Value::Integer(500)), ("vip", Value::Boolean(false))],
+            &[("volume", Value::Integer(50)), ("vip", Value::Boolean(true))],
+            &[("volume", Value::Integer(80)), ("vip", Value::Boolean(true))],
+        ]);
+        assert_eq!(coverage.branches[0].expression, "IF volume > 100");
+        assert_eq!(coverage.branches[1].expression, "IF vip");
+        // The inner IF only counts when the outer one reached it
+        assert_eq!(taken(&coverage), vec![vec![1, 2], vec![0, 1]]);
+        assert_eq!((coverage.evaluated, coverage.fired, coverage.failed), (3, 3, 0));
+    }
+
+    #[test]
+    fn test_counts_case_arms_and_failures() {
+        let rule = r#"tier = CASE WHEN score / divisor > 80 THEN "gold" WHEN score > 50 THEN "silver" END"#;
+        let coverage = coverage(rule, &[
+            &[("score", Value::Integer(90)), ("divisor", Value::Integer(1))],
+            &[("score", Value::Integer(10)), ("divisor", Value::Integer(1))],
+            &[("score", Value::Integer(10)), ("divisor", Value::Integer(0))],
+        ]);
+        assert_eq!(coverage.branches[0].arms.iter().map(|a| a.label.as_str()).collect::<Vec<_>>(), vec![
+            "WHEN score / divisor > 80",
+            "WHEN score > 50",
+            "ELSE",
+        ]);
+        assert_eq!(taken(&coverage), vec![vec![1, 0, 1]]);
+        // No ELSE gives null, which isn't firing; division by zero fails
+        assert_eq!((coverage.evaluated, coverage.fired, coverage.failed), (3, 1, 1));
+
+        let report = CoverageReport::new(3, vec![coverage], BTreeSet::from(["unused".to_string()]));
+        assert!(report.never_fired.is_empty());
+        assert_eq!(report.untaken_branches.len(), 1);
+        assert_eq!(report.untaken_branches[0].arm, "WHEN score > 50");
+        assert_eq!(report.unreferenced_attributes, vec!["unused"]);
+    }
+}
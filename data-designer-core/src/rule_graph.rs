@@ -0,0 +1,666 @@
+//! Rule dependency graph
+//!
+//! Each rule derives one attribute and reads others; when it reads an
+//! attribute another rule derives, the second rule has to run first. The
+//! graph links rules by those derived attributes, detects cycles and gives
+//! an execution order in which every rule runs after the rules it reads.
+//! Attributes no rule derives are inputs and don't appear in the graph.
+//!
+//! For the IDE's graph panel, `dependency_graph_payload` lays the same
+//! relationships out as nodes (rules, attributes, lookup tables) and edges,
+//! with the rules and edges on a cycle marked.
+
+use crate::models::{Expression, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Attributes an expression reads, sorted and without duplicates.
+/// Function names and lambda parameters are not dependencies.
+pub fn extract_dependencies_from_ast(expr: &Expression) -> Vec<String> {
+    let mut deps = Vec::new();
+    collect_dependencies(expr, &mut deps);
+    deps.sort();
+    deps.dedup();
+    deps
+}
+
+fn collect_dependencies(expr: &Expression, deps: &mut Vec<String>) {
+    match expr {
+        // Skip built-in functions and literals
+        Expression::Variable(name) | Expression::Identifier(name)
+            if !is_builtin_function(name) && !name.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            deps.push(name.clone());
+        }
+        Expression::BinaryOp { left, right, .. } => {
+            collect_dependencies(left, deps);
+            collect_dependencies(right, deps);
+        }
+        Expression::UnaryOp { operand, .. } => {
+            collect_dependencies(operand, deps);
+        }
+        Expression::FunctionCall { args, .. } => {
+            for arg in args {
+                collect_dependencies(arg, deps);
+            }
+        }
+        Expression::Conditional { condition, then_expr, else_expr } => {
+            collect_dependencies(condition, deps);
+            collect_dependencies(then_expr, deps);
+            if let Some(else_branch) = else_expr {
+                collect_dependencies(else_branch, deps);
+            }
+        }
+        Expression::Case { branches, else_expr } => {
+            for (condition, result) in branches {
+                collect_dependencies(condition, deps);
+                collect_dependencies(result, deps);
+            }
+            if let Some(else_branch) = else_expr {
+                collect_dependencies(else_branch, deps);
+            }
+        }
+        Expression::Assignment { value, .. } => {
+            collect_dependencies(value, deps);
+        }
+        Expression::List(items) => {
+            for item in items {
+                collect_dependencies(item, deps);
+            }
+        }
+        Expression::Cast { expr, .. } => {
+            collect_dependencies(expr, deps);
+        }
+        Expression::Range { start, end, .. } => {
+            collect_dependencies(start, deps);
+            collect_dependencies(end, deps);
+        }
+        Expression::Lambda { param, body } => {
+            // The lambda parameter is bound per list item, not read from the facts
+            let mut body_deps = Vec::new();
+            collect_dependencies(body, &mut body_deps);
+            deps.extend(body_deps.into_iter().filter(|dep| dep != param));
+        }
+        Expression::Comprehension { element, var, source, condition } => {
+            collect_dependencies(source, deps);
+            // The loop variable and its fields are bound per item, not read from the facts
+            let mut item_deps = Vec::new();
+            collect_dependencies(element, &mut item_deps);
+            if let Some(condition) = condition {
+                collect_dependencies(condition, &mut item_deps);
+            }
+            let field_prefix = format!("{}.", var);
+            deps.extend(item_deps.into_iter().filter(|dep| dep != var && !dep.starts_with(&field_prefix)));
+        }
+        _ => {} // Literals don't have dependencies
+    }
+}
+
+/// Names that read as identifiers but are functions or keywords
+pub(crate) fn is_builtin_function(name: &str) -> bool {
+    matches!(name.to_uppercase().as_str(),
+            "CONCAT" | "UPPER" | "LOWER" | "LENGTH" | "SUBSTRING" | "TRIM" |
+            "ABS" | "ROUND" | "CEIL" | "FLOOR" | "MIN" | "MAX" | "SUM" | "AVG" |
+            "FORMAT_NUMBER" | "FORMAT_DATE" | "TO_NUMBER" |
+            "IF" | "WHEN" | "THEN" | "ELSE" | "CASE" | "END" |
+            "MATCHES" | "CONTAINS" | "STARTS_WITH" | "ENDS_WITH" |
+            "TRUE" | "FALSE" | "NULL")
+}
+
+/// A cycle of rules, each reading the attribute the next one derives;
+/// the first rule is repeated at the end
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyCycle {
+    pub rules: Vec<String>,
+}
+
+impl fmt::Display for DependencyCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Circular rule dependency: {}", self.rules.join(" -> "))
+    }
+}
+
+impl std::error::Error for DependencyCycle {}
+
+/// Rules keyed by the attribute they derive, with the derived attributes each one reads
+#[derive(Debug, Clone, Default)]
+pub struct RuleGraph {
+    reads: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl RuleGraph {
+    /// Builds the graph from each rule's name and the attributes it reads
+    pub fn new<'a>(rules: impl IntoIterator<Item = (&'a str, &'a [String])>) -> Self {
+        let rules: Vec<(&str, &[String])> = rules.into_iter().collect();
+        let derived: BTreeSet<&str> = rules.iter().map(|(name, _)| *name).collect();
+        let reads = rules
+            .iter()
+            .map(|(name, dependencies)| {
+                let upstream = dependencies
+                    .iter()
+                    .filter(|dep| derived.contains(dep.as_str()))
+                    .cloned()
+                    .collect();
+                (name.to_string(), upstream)
+            })
+            .collect();
+        Self { reads }
+    }
+
+    /// Rules whose output `rule` reads
+    pub fn dependencies_of(&self, rule: &str) -> impl Iterator<Item = &str> {
+        self.reads.get(rule).into_iter().flatten().map(String::as_str)
+    }
+
+    /// Rules that read the output of `rule`
+    pub fn dependents_of<'a>(&'a self, rule: &'a str) -> impl Iterator<Item = &'a str> {
+        self.reads
+            .iter()
+            .filter(move |(_, upstream)| upstream.contains(rule))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Every rule, each after the rules it reads; ties are broken by name so
+    /// the order is stable. Fails with one of the cycles if there is any.
+    pub fn execution_order(&self) -> Result<Vec<String>, DependencyCycle> {
+        let mut waiting_on: BTreeMap<&str, usize> =
+            self.reads.iter().map(|(name, upstream)| (name.as_str(), upstream.len())).collect();
+        let mut ready: BTreeSet<&str> =
+            waiting_on.iter().filter(|(_, count)| **count == 0).map(|(name, _)| *name).collect();
+        let mut order = Vec::with_capacity(self.reads.len());
+
+        while let Some(name) = ready.pop_first() {
+            order.push(name.to_string());
+            for dependent in self.dependents_of(name) {
+                let count = waiting_on.get_mut(dependent).expect("dependents are rules");
+                *count -= 1;
+                if *count == 0 {
+                    ready.insert(dependent);
+                }
+            }
+        }
+
+        if order.len() == self.reads.len() {
+            return Ok(order);
+        }
+        let unordered: BTreeSet<&str> =
+            waiting_on.into_iter().filter(|(_, count)| *count > 0).map(|(name, _)| name).collect();
+        Err(self.cycle_among(&unordered))
+    }
+
+    /// Every group of rules that depend on each other, each group sorted;
+    /// a rule reading its own output is a group of one
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        let mut tarjan = Tarjan {
+            graph: self,
+            next_index: 0,
+            indices: BTreeMap::new(),
+            stack: Vec::new(),
+            components: Vec::new(),
+        };
+        for name in self.reads.keys() {
+            if !tarjan.indices.contains_key(name.as_str()) {
+                tarjan.visit(name);
+            }
+        }
+        let mut cycles: Vec<Vec<String>> = tarjan
+            .components
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1 || self.dependencies_of(&component[0]).any(|dep| dep == component[0])
+            })
+            .map(|mut component| {
+                component.sort();
+                component
+            })
+            .collect();
+        cycles.sort();
+        cycles
+    }
+
+    /// Finds a cycle among the rules left unordered. Each of them still reads
+    /// another unordered rule, so walking upstream must revisit one.
+    fn cycle_among(&self, unordered: &BTreeSet<&str>) -> DependencyCycle {
+        let mut current = *unordered.first().expect("an unordered rule remains");
+        let mut path = vec![current.to_string()];
+        loop {
+            let next = self
+                .dependencies_of(current)
+                .find(|dep| unordered.contains(dep))
+                .expect("an unordered rule reads another unordered rule");
+            if let Some(position) = path.iter().position(|name| name == next) {
+                let mut rules = path.split_off(position);
+                rules.push(next.to_string());
+                return DependencyCycle { rules };
+            }
+            path.push(next.to_string());
+            current = next;
+        }
+    }
+}
+
+/// Tarjan's strongly connected components over the rules' reads
+struct Tarjan<'a> {
+    graph: &'a RuleGraph,
+    next_index: usize,
+    /// Visit index and lowest reachable index of each visited rule
+    indices: BTreeMap<&'a str, (usize, usize)>,
+    stack: Vec<&'a str>,
+    components: Vec<Vec<String>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn visit(&mut self, name: &'a str) -> usize {
+        let index = self.next_index;
+        self.next_index += 1;
+        self.indices.insert(name, (index, index));
+        self.stack.push(name);
+
+        let mut low = index;
+        for dep in self.graph.dependencies_of(name) {
+            match self.indices.get(dep) {
+                None => low = low.min(self.visit(dep)),
+                Some(&(dep_index, _)) if self.stack.contains(&dep) => low = low.min(dep_index),
+                Some(_) => {}
+            }
+        }
+        self.indices.insert(name, (index, low));
+
+        if low == index {
+            let start = self.stack.iter().rposition(|n| *n == name).expect("visited rule is on the stack");
+            self.components.push(self.stack.split_off(start).into_iter().map(str::to_string).collect());
+        }
+        low
+    }
+}
+
+/// A stored rule as the graph panel shows it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphRule {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub status: String,
+    /// The attribute the rule derives; `target = ...` definitions name it themselves
+    pub target_attribute: Option<String>,
+    pub definition: String,
+}
+
+/// Which part of the graph to return
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GraphScope {
+    #[default]
+    All,
+    /// A rule id, attribute or lookup table, with everything upstream and downstream of it
+    Around { name: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphNodeKind {
+    Rule,
+    Attribute,
+    LookupTable,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphNode {
+    /// `rule:<rule_id>`, `attribute:<name>` or `lookup:<table>`
+    pub id: String,
+    pub kind: GraphNodeKind,
+    pub label: String,
+    pub in_cycle: bool,
+    /// Rules: name, status and any parse error; attributes: whether a rule derives them
+    pub metadata: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphEdgeKind {
+    /// Attribute to the rule reading it
+    Reads,
+    /// Rule to the attribute it derives
+    Derives,
+    /// Lookup table to the rule looking values up in it
+    LooksUp,
+}
+
+/// Edges point the way changes flow: from an input to what is computed from it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphEdge {
+    pub source: String,
+    pub target: String,
+    pub kind: GraphEdgeKind,
+    pub in_cycle: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DependencyGraphPayload {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+    /// Rule ids of each group of mutually dependent rules
+    pub cycles: Vec<Vec<String>>,
+}
+
+impl GraphEdge {
+    fn new(source: String, target: String, kind: GraphEdgeKind) -> Self {
+        GraphEdge { source, target, kind, in_cycle: false }
+    }
+}
+
+fn rule_node_id(rule_id: &str) -> String {
+    format!("rule:{}", rule_id)
+}
+
+fn attribute_node_id(name: &str) -> String {
+    format!("attribute:{}", name)
+}
+
+fn lookup_node_id(table: &str) -> String {
+    format!("lookup:{}", table)
+}
+
+/// Tables named by `LOOKUP(key, "table")` calls
+fn lookup_tables(expr: &Expression, tables: &mut BTreeSet<String>) {
+    match expr {
+        Expression::FunctionCall { name, args } => {
+            if name.eq_ignore_ascii_case("LOOKUP") {
+                if let Some(Expression::Literal(Value::String(table))) = args.get(1) {
+                    tables.insert(table.clone());
+                }
+            }
+            args.iter().for_each(|arg| lookup_tables(arg, tables));
+        }
+        Expression::BinaryOp { left, right, .. } | Expression::Range { start: left, end: right, .. } => {
+            lookup_tables(left, tables);
+            lookup_tables(right, tables);
+        }
+        Expression::UnaryOp { operand, .. } => lookup_tables(operand, tables),
+        Expression::Conditional { condition, then_expr, else_expr } => {
+            lookup_tables(condition, tables);
+            lookup_tables(then_expr, tables);
+            if let Some(else_expr) = else_expr {
+                lookup_tables(else_expr, tables);
+            }
+        }
+        Expression::Case { branches, else_expr } => {
+            for (condition, result) in branches {
+                lookup_tables(condition, tables);
+                lookup_tables(result, tables);
+            }
+            if let Some(else_expr) = else_expr {
+                lookup_tables(else_expr, tables);
+            }
+        }
+        Expression::Assignment { value, .. } => lookup_tables(value, tables),
+        Expression::Cast { expr, .. } => lookup_tables(expr, tables),
+        Expression::Lambda { body, .. } => lookup_tables(body, tables),
+        Expression::Comprehension { element, source, condition, .. } => {
+            lookup_tables(element, tables);
+            lookup_tables(source, tables);
+            if let Some(condition) = condition {
+                lookup_tables(condition, tables);
+            }
+        }
+        Expression::List(items) => items.iter().for_each(|item| lookup_tables(item, tables)),
+        _ => {}
+    }
+}
+
+/// The graph panel's payload: rules, the attributes they read and derive
+/// and the lookup tables they use, limited to `scope`. Rules whose
+/// definition doesn't parse are kept as nodes, with the error in their metadata.
+pub fn dependency_graph_payload(rules: &[GraphRule], scope: &GraphScope) -> DependencyGraphPayload {
+    let mut nodes: BTreeMap<String, GraphNode> = BTreeMap::new();
+    let mut edges = Vec::new();
+    // Rule ids by the attribute they derive, and what each derived attribute's rule reads
+    let mut derived_by: BTreeMap<String, String> = BTreeMap::new();
+    let mut reads: Vec<(String, Vec<String>)> = Vec::new();
+
+    for rule in rules {
+        let rule_id = rule_node_id(&rule.rule_id);
+        let mut metadata = serde_json::json!({ "rule_name": rule.rule_name, "status": rule.status });
+        let (target, dependencies, tables) = match crate::parser::parse_rule(&rule.definition) {
+            Ok((_, expr)) => {
+                let target = match &expr {
+                    Expression::Assignment { target, .. } => Some(target.clone()),
+                    _ => None,
+                };
+                let mut tables = BTreeSet::new();
+                lookup_tables(&expr, &mut tables);
+                (rule.target_attribute.clone().or(target), extract_dependencies_from_ast(&expr), tables)
+            }
+            Err(e) => {
+                metadata["parse_error"] = serde_json::json!(e.to_string());
+                (rule.target_attribute.clone(), Vec::new(), BTreeSet::new())
+            }
+        };
+        nodes.insert(rule_id.clone(), GraphNode {
+            id: rule_id.clone(),
+            kind: GraphNodeKind::Rule,
+            label: rule.rule_id.clone(),
+            in_cycle: false,
+            metadata,
+        });
+
+        for dependency in &dependencies {
+            nodes.entry(attribute_node_id(dependency)).or_insert_with(|| attribute_node(dependency));
+            edges.push(GraphEdge::new(attribute_node_id(dependency), rule_id.clone(), GraphEdgeKind::Reads));
+        }
+        for table in tables {
+            nodes.entry(lookup_node_id(&table)).or_insert_with(|| GraphNode {
+                id: lookup_node_id(&table),
+                kind: GraphNodeKind::LookupTable,
+                label: table.clone(),
+                in_cycle: false,
+                metadata: serde_json::json!({}),
+            });
+            edges.push(GraphEdge::new(lookup_node_id(&table), rule_id.clone(), GraphEdgeKind::LooksUp));
+        }
+        if let Some(target) = target {
+            let node = nodes.entry(attribute_node_id(&target)).or_insert_with(|| attribute_node(&target));
+            node.metadata["derived"] = serde_json::json!(true);
+            edges.push(GraphEdge::new(rule_id.clone(), attribute_node_id(&target), GraphEdgeKind::Derives));
+            derived_by.insert(target.clone(), rule.rule_id.clone());
+            reads.push((target, dependencies));
+        }
+    }
+
+    // Cycles run through derived attributes; report them by rule id
+    let graph = RuleGraph::new(reads.iter().map(|(target, deps)| (target.as_str(), deps.as_slice())));
+    let cycles: Vec<Vec<String>> = graph
+        .cycles()
+        .into_iter()
+        .map(|cycle| {
+            let mut rule_ids: Vec<String> = cycle.iter().map(|target| derived_by[target].clone()).collect();
+            rule_ids.sort();
+            rule_ids
+        })
+        .collect();
+    let cycle_of: BTreeMap<String, usize> = cycles
+        .iter()
+        .enumerate()
+        .flat_map(|(i, cycle)| cycle.iter().map(move |rule_id| (rule_node_id(rule_id), i)))
+        .collect();
+    // The cycle an attribute node's deriving rule is on, if any
+    let attribute_cycle = |attribute: &str| {
+        let rule_id = derived_by.get(attribute.strip_prefix("attribute:")?)?;
+        cycle_of.get(&rule_node_id(rule_id))
+    };
+    for edge in &mut edges {
+        edge.in_cycle = match edge.kind {
+            GraphEdgeKind::Reads => {
+                attribute_cycle(&edge.source).is_some_and(|cycle| cycle_of.get(&edge.target) == Some(cycle))
+            }
+            GraphEdgeKind::Derives => cycle_of.contains_key(&edge.source),
+            GraphEdgeKind::LooksUp => false,
+        };
+    }
+    for (id, node) in &mut nodes {
+        node.in_cycle = cycle_of.contains_key(id) || attribute_cycle(id).is_some();
+    }
+
+    let mut payload = DependencyGraphPayload { nodes: nodes.into_values().collect(), edges, cycles };
+    if let GraphScope::Around { name } = scope {
+        payload = payload.around(name);
+    }
+    payload
+}
+
+fn attribute_node(name: &str) -> GraphNode {
+    GraphNode {
+        id: attribute_node_id(name),
+        kind: GraphNodeKind::Attribute,
+        label: name.to_string(),
+        in_cycle: false,
+        metadata: serde_json::json!({ "derived": false }),
+    }
+}
+
+impl DependencyGraphPayload {
+    /// The nodes `name` depends on and the nodes depending on it, with the
+    /// edges and cycles among them; empty if nothing is called `name`
+    fn around(self, name: &str) -> Self {
+        let Some(focus) = [rule_node_id(name), attribute_node_id(name), lookup_node_id(name)]
+            .into_iter()
+            .find(|id| self.nodes.iter().any(|node| node.id == *id))
+        else {
+            return Self::default();
+        };
+
+        let reach = |forward: bool| {
+            let mut seen = BTreeSet::from([focus.clone()]);
+            let mut pending = vec![focus.clone()];
+            while let Some(id) = pending.pop() {
+                for edge in &self.edges {
+                    let (from, to) = if forward { (&edge.source, &edge.target) } else { (&edge.target, &edge.source) };
+                    if *from == id && seen.insert(to.clone()) {
+                        pending.push(to.clone());
+                    }
+                }
+            }
+            seen
+        };
+        let mut kept = reach(true);
+        kept.extend(reach(false));
+
+        DependencyGraphPayload {
+            nodes: self.nodes.into_iter().filter(|node| kept.contains(&node.id)).collect(),
+            edges: self
+                .edges
+                .into_iter()
+                .filter(|edge| kept.contains(&edge.source) && kept.contains(&edge.target))
+                .collect(),
+            cycles: self
+                .cycles
+                .into_iter()
+                .filter(|cycle| cycle.iter().any(|rule_id| kept.contains(&rule_node_id(rule_id))))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_rule;
+
+    fn graph(rules: &[(&str, &str)]) -> RuleGraph {
+        let parsed: Vec<(&str, Vec<String>)> = rules
+            .iter()
+            .map(|(name, definition)| (*name, extract_dependencies_from_ast(&parse_rule(definition).unwrap().1)))
+            .collect();
+        RuleGraph::new(parsed.iter().map(|(name, deps)| (*name, deps.as_slice())))
+    }
+
+    #[test]
+    fn test_extract_dependencies_skips_functions_and_lambda_params() {
+        let (_, ast) = parse_rule("UPPER(name) & CONCAT(suffix, name) & MAP(items, x -> x * rate)").unwrap();
+        assert_eq!(extract_dependencies_from_ast(&ast), vec!["items", "name", "rate", "suffix"]);
+    }
+
+    #[test]
+    fn test_extract_dependencies_skips_comprehension_variables() {
+        let (_, ast) = parse_rule(r#"[x.amount * fx.rate FOR x IN transactions IF x.currency == base]"#).unwrap();
+        assert_eq!(extract_dependencies_from_ast(&ast), vec!["base", "fx.rate", "transactions"]);
+    }
+
+    #[test]
+    fn test_execution_order_runs_each_rule_after_its_inputs() {
+        let rules = graph(&[
+            ("total", "subtotal + tax"),
+            ("tax", "subtotal * rate"),
+            ("subtotal", "price * quantity"),
+            ("label", "UPPER(name)"),
+        ]);
+        assert_eq!(rules.execution_order().unwrap(), vec!["label", "subtotal", "tax", "total"]);
+        assert_eq!(rules.dependencies_of("total").collect::<Vec<_>>(), vec!["subtotal", "tax"]);
+        assert_eq!(rules.dependents_of("subtotal").collect::<Vec<_>>(), vec!["tax", "total"]);
+    }
+
+    #[test]
+    fn test_execution_order_reports_a_cycle() {
+        let rules = graph(&[
+            ("a", "b + 1"),
+            ("b", "c * 2"),
+            ("c", "a - 1"),
+            ("d", "a + x"),
+            ("e", "x"),
+        ]);
+        let cycle = rules.execution_order().unwrap_err();
+        assert_eq!(cycle.rules, vec!["a", "b", "c", "a"]);
+        assert_eq!(cycle.to_string(), "Circular rule dependency: a -> b -> c -> a");
+
+        assert_eq!(graph(&[("a", "a + 1")]).execution_order().unwrap_err().rules, vec!["a", "a"]);
+    }
+
+    fn stored(rule_id: &str, definition: &str) -> GraphRule {
+        GraphRule {
+            rule_id: rule_id.to_string(),
+            rule_name: rule_id.to_lowercase(),
+            status: "active".to_string(),
+            target_attribute: None,
+            definition: definition.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_cycles_finds_every_group_of_mutually_dependent_rules() {
+        let rules = graph(&[("a", "b + 1"), ("b", "a * 2"), ("c", "c + 1"), ("d", "a + x")]);
+        assert_eq!(rules.cycles(), vec![vec!["a", "b"], vec!["c"]]);
+    }
+
+    #[test]
+    fn test_dependency_graph_payload_marks_cycles_and_limits_scope() {
+        let rules = [
+            stored("R1", "risk = LOOKUP(country, \"country_risk\") * weight"),
+            stored("R2", "weight = risk / 2"),
+            stored("R3", "label = UPPER(name)"),
+            stored("R4", "rating = risk + 1"),
+        ];
+        let payload = dependency_graph_payload(&rules, &GraphScope::All);
+        assert_eq!(payload.cycles, vec![vec!["R1", "R2"]]);
+
+        let node = |id: &str| payload.nodes.iter().find(|node| node.id == id).unwrap();
+        assert!(node("rule:R1").in_cycle && node("attribute:weight").in_cycle);
+        assert!(!node("rule:R4").in_cycle && !node("attribute:country").in_cycle);
+        assert_eq!(node("lookup:country_risk").kind, GraphNodeKind::LookupTable);
+        assert_eq!(node("attribute:rating").metadata["derived"], true);
+        let reads_weight = payload.edges.iter().find(|edge| edge.source == "attribute:weight").unwrap();
+        assert_eq!((reads_weight.target.as_str(), reads_weight.in_cycle), ("rule:R1", true));
+        let reads_risk = payload.edges.iter().find(|edge| edge.target == "rule:R4").unwrap();
+        assert!(!reads_risk.in_cycle);
+
+        // Around an attribute: what feeds it and what it ripples into, but not unrelated rules
+        let scoped = dependency_graph_payload(&rules, &GraphScope::Around { name: "country".to_string() });
+        let ids: Vec<&str> = scoped.nodes.iter().map(|node| node.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec!["attribute:country", "attribute:rating", "attribute:risk", "attribute:weight", "rule:R1", "rule:R2", "rule:R4"]
+        );
+        assert_eq!(scoped.cycles, vec![vec!["R1", "R2"]]);
+        let missing = GraphScope::Around { name: "missing".to_string() };
+        assert_eq!(dependency_graph_payload(&rules, &missing), DependencyGraphPayload::default());
+    }
+}
@@ -0,0 +1,400 @@
+//! Rule version history and structural diffs between versions
+//!
+//! Every save of a rule records its definition as a new, immutable version.
+//! Two versions are compared on their parsed expressions rather than their
+//! text, so reformatting or re-commenting a rule shows no change while a
+//! changed operand shows up exactly where it is in the tree. The diff is a
+//! tree the UI renders directly: unchanged subtrees collapse to their text,
+//! changed nodes list their children's diffs.
+//!
+//! A version may also carry an effective period, `valid_from` to `valid_to`.
+//! Without one it is in effect from the moment it was saved. The version of
+//! a rule in effect at an instant is the newest one whose period contains it,
+//! so a later save supersedes earlier versions only over its own period: a
+//! change can be scheduled ahead or backdated without touching history.
+
+use crate::formatter::{format_expression, operator};
+use crate::models::{Expression, UnaryOperator};
+use crate::parser::parse_rule;
+use crate::rule_variants::Applicability;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// One saved definition of a rule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleVersion {
+    pub rule_id: String,
+    pub version: i32,
+    pub rule_name: String,
+    pub description: Option<String>,
+    pub rule_definition: String,
+    pub status: String,
+    pub change_description: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub valid_from: Option<DateTime<Utc>>,
+    pub valid_to: Option<DateTime<Utc>>,
+    /// Where this variant of the rule applies
+    #[serde(default)]
+    pub applies_to: Applicability,
+}
+
+impl RuleVersion {
+    /// Start of the effective period, defaulting to when the version was saved
+    pub fn effective_from(&self) -> DateTime<Utc> {
+        self.valid_from.unwrap_or(self.created_at)
+    }
+
+    pub fn is_effective_at(&self, at: DateTime<Utc>) -> bool {
+        is_effective(Some(self.effective_from()), self.valid_to, at)
+    }
+}
+
+/// Whether `at` falls in the period from `valid_from` (inclusive) to
+/// `valid_to` (exclusive); an absent bound leaves that side open
+pub fn is_effective(valid_from: Option<DateTime<Utc>>, valid_to: Option<DateTime<Utc>>, at: DateTime<Utc>) -> bool {
+    valid_from.is_none_or(|from| from <= at) && valid_to.is_none_or(|to| at < to)
+}
+
+/// Checks an effective period before it is saved
+pub fn validate_effective_period(valid_from: Option<DateTime<Utc>>, valid_to: Option<DateTime<Utc>>) -> Result<(), String> {
+    match (valid_from, valid_to) {
+        (Some(from), Some(to)) if to <= from => Err(format!(
+            "valid_to ({}) must be after valid_from ({})",
+            to.to_rfc3339(),
+            from.to_rfc3339()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// The version of each rule in effect at `at`, ordered by rule id. Rules
+/// whose effective version is deprecated, or with none in effect, are left out.
+pub fn versions_in_effect(versions: &[RuleVersion], at: DateTime<Utc>) -> Vec<&RuleVersion> {
+    let mut in_effect: std::collections::BTreeMap<&str, &RuleVersion> = std::collections::BTreeMap::new();
+    for version in versions.iter().filter(|version| version.is_effective_at(at)) {
+        let newest = in_effect.entry(&version.rule_id).or_insert(version);
+        if version.version > newest.version {
+            *newest = version;
+        }
+    }
+    in_effect.into_values().filter(|version| version.status != "deprecated").collect()
+}
+
+/// How one node of the newer expression relates to the older one.
+/// `slot` names the node's place in its parent: "left", "arg 2", "then", ...
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum AstDiff {
+    Unchanged { slot: String, text: String },
+    Added { slot: String, text: String },
+    Removed { slot: String, text: String },
+    /// A different kind of node took this one's place
+    Replaced { slot: String, before: String, after: String },
+    /// The same kind of node with a changed label (operator, function name, ...) or children
+    Modified { slot: String, before_label: String, after_label: String, children: Vec<AstDiff> },
+}
+
+impl AstDiff {
+    pub fn is_unchanged(&self) -> bool {
+        matches!(self, AstDiff::Unchanged { .. })
+    }
+
+    /// Changed nodes, counting a modified node only through its changed children or label
+    pub fn change_count(&self) -> usize {
+        match self {
+            AstDiff::Unchanged { .. } => 0,
+            AstDiff::Added { .. } | AstDiff::Removed { .. } | AstDiff::Replaced { .. } => 1,
+            AstDiff::Modified { before_label, after_label, children, .. } => {
+                usize::from(before_label != after_label) + children.iter().map(AstDiff::change_count).sum::<usize>()
+            }
+        }
+    }
+}
+
+/// The comparison of two versions of a rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleVersionDiff {
+    pub rule_id: String,
+    pub from_version: i32,
+    pub to_version: i32,
+    pub before: String,
+    pub after: String,
+    /// None when either version no longer parses; the texts are still there to compare
+    pub diff: Option<AstDiff>,
+    pub changes: usize,
+    pub metadata_changes: Vec<String>,
+}
+
+impl RuleVersionDiff {
+    pub fn new(from: &RuleVersion, to: &RuleVersion) -> Self {
+        let diff = match (parse_definition(&from.rule_definition), parse_definition(&to.rule_definition)) {
+            (Some(before), Some(after)) => Some(diff_expressions(&before, &after)),
+            _ => None,
+        };
+        let changes = match &diff {
+            Some(diff) => diff.change_count(),
+            None => usize::from(from.rule_definition.trim() != to.rule_definition.trim()),
+        };
+
+        let mut metadata_changes = Vec::new();
+        if from.rule_name != to.rule_name {
+            metadata_changes.push(format!("name: '{}' → '{}'", from.rule_name, to.rule_name));
+        }
+        if from.description != to.description {
+            metadata_changes.push("description changed".to_string());
+        }
+        if from.status != to.status {
+            metadata_changes.push(format!("status: {} → {}", from.status, to.status));
+        }
+
+        RuleVersionDiff {
+            rule_id: to.rule_id.clone(),
+            from_version: from.version,
+            to_version: to.version,
+            before: from.rule_definition.clone(),
+            after: to.rule_definition.clone(),
+            diff,
+            changes,
+            metadata_changes,
+        }
+    }
+}
+
+fn parse_definition(definition: &str) -> Option<Expression> {
+    match parse_rule(definition) {
+        Ok((remaining, expression)) if remaining.trim().is_empty() => Some(expression),
+        _ => None,
+    }
+}
+
+/// Structural diff of two expressions, rooted at slot "rule"
+pub fn diff_expressions(before: &Expression, after: &Expression) -> AstDiff {
+    diff_node("rule".to_string(), before, after)
+}
+
+/// A node's kind, its label and its children with their slots
+struct Shape<'a> {
+    kind: &'static str,
+    label: String,
+    children: Vec<(String, &'a Expression)>,
+}
+
+fn numbered<'a>(prefix: &str, items: &'a [Expression]) -> Vec<(String, &'a Expression)> {
+    items.iter().enumerate().map(|(i, item)| (format!("{} {}", prefix, i + 1), item)).collect()
+}
+
+fn shape(expr: &Expression) -> Shape<'_> {
+    let leaf = |kind| Shape { kind, label: format_expression(expr), children: Vec::new() };
+    match expr {
+        Expression::BinaryOp { left, op, right } => Shape {
+            kind: "binary_op",
+            label: operator(*op).to_string(),
+            children: vec![("left".to_string(), &**left), ("right".to_string(), &**right)],
+        },
+        Expression::UnaryOp { op, operand } => Shape {
+            kind: "unary_op",
+            label: match op {
+                UnaryOperator::Not => "NOT",
+                UnaryOperator::Minus => "-",
+                UnaryOperator::Plus => "+",
+            }
+            .to_string(),
+            children: vec![("operand".to_string(), &**operand)],
+        },
+        Expression::FunctionCall { name, args } => {
+            Shape { kind: "function_call", label: format!("{}()", name), children: numbered("arg", args) }
+        }
+        Expression::Conditional { condition, then_expr, else_expr } => {
+            let mut children = vec![("condition".to_string(), &**condition), ("then".to_string(), &**then_expr)];
+            children.extend(else_expr.as_deref().map(|else_expr| ("else".to_string(), else_expr)));
+            Shape { kind: "conditional", label: "IF".to_string(), children }
+        }
+        Expression::Case { branches, else_expr } => {
+            let mut children = Vec::new();
+            for (i, (when, then)) in branches.iter().enumerate() {
+                children.push((format!("when {}", i + 1), when));
+                children.push((format!("then {}", i + 1), then));
+            }
+            children.extend(else_expr.as_deref().map(|else_expr| ("else".to_string(), else_expr)));
+            Shape { kind: "case", label: "CASE".to_string(), children }
+        }
+        Expression::Assignment { target, value } => Shape {
+            kind: "assignment",
+            label: format!("{} =", target),
+            children: vec![("value".to_string(), &**value)],
+        },
+        Expression::List(items) => Shape { kind: "list", label: "[]".to_string(), children: numbered("item", items) },
+        Expression::Cast { expr: inner, data_type } => Shape {
+            kind: "cast",
+            label: format!("AS {}", data_type),
+            children: vec![("value".to_string(), &**inner)],
+        },
+        Expression::Lambda { param, body } => Shape {
+            kind: "lambda",
+            label: format!("{} ->", param),
+            children: vec![("body".to_string(), &**body)],
+        },
+        Expression::Range { start, end, inclusive } => Shape {
+            kind: "range",
+            label: if *inclusive { "..=" } else { ".." }.to_string(),
+            children: vec![("start".to_string(), &**start), ("end".to_string(), &**end)],
+        },
+        Expression::Literal(_) => leaf("literal"),
+        Expression::Variable(_) | Expression::Identifier(_) => leaf("variable"),
+        _ => leaf("statement"),
+    }
+}
+
+fn diff_node(slot: String, before: &Expression, after: &Expression) -> AstDiff {
+    if before == after {
+        return AstDiff::Unchanged { slot, text: format_expression(after) };
+    }
+
+    let (old, new) = (shape(before), shape(after));
+    if old.kind != new.kind || old.children.is_empty() || new.children.is_empty() {
+        return AstDiff::Replaced { slot, before: format_expression(before), after: format_expression(after) };
+    }
+
+    AstDiff::Modified {
+        slot,
+        before_label: old.label,
+        after_label: new.label,
+        children: diff_children(&old.children, &new.children),
+    }
+}
+
+/// Aligns two child lists on their longest common subsequence of equal
+/// subtrees; children between matches are compared pairwise and the rest
+/// are additions or removals
+fn diff_children(before: &[(String, &Expression)], after: &[(String, &Expression)]) -> Vec<AstDiff> {
+    let (n, m) = (before.len(), after.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if before[i].1 == after[j].1 { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let mut diffs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let (mut gap_before, mut gap_after) = (Vec::new(), Vec::new());
+    while i < n || j < m {
+        if i < n && j < m && before[i].1 == after[j].1 {
+            flush_gap(&mut diffs, &mut gap_before, &mut gap_after);
+            diffs.push(AstDiff::Unchanged { slot: after[j].0.clone(), text: format_expression(after[j].1) });
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            gap_after.push(&after[j]);
+            j += 1;
+        } else {
+            gap_before.push(&before[i]);
+            i += 1;
+        }
+    }
+    flush_gap(&mut diffs, &mut gap_before, &mut gap_after);
+    diffs
+}
+
+fn flush_gap(
+    diffs: &mut Vec<AstDiff>,
+    before: &mut Vec<&(String, &Expression)>,
+    after: &mut Vec<&(String, &Expression)>,
+) {
+    let paired = before.len().min(after.len());
+    for (old, new) in before.iter().zip(after.iter()) {
+        diffs.push(diff_node(new.0.clone(), old.1, new.1));
+    }
+    for old in &before[paired..] {
+        diffs.push(AstDiff::Removed { slot: old.0.clone(), text: format_expression(old.1) });
+    }
+    for new in &after[paired..] {
+        diffs.push(AstDiff::Added { slot: new.0.clone(), text: format_expression(new.1) });
+    }
+    before.clear();
+    after.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(version: i32, definition: &str) -> RuleVersion {
+        RuleVersion {
+            rule_id: "risk_score".to_string(),
+            version,
+            rule_name: "Risk score".to_string(),
+            description: None,
+            rule_definition: definition.to_string(),
+            status: "draft".to_string(),
+            change_description: None,
+            created_by: None,
+            created_at: Utc::now(),
+            valid_from: None,
+            valid_to: None,
+            applies_to: Applicability::default(),
+        }
+    }
+
+    #[test]
+    fn test_diff_pinpoints_changed_operands() {
+        let diff = RuleVersionDiff::new(
+            &version(1, "base_score * 2 + CONCAT(region, \"-\", tier)"),
+            &version(2, "base_score * 3 + CONCAT(country, region, \"-\", tier)"),
+        );
+        assert_eq!((diff.from_version, diff.to_version, diff.changes), (1, 2, 2));
+
+        let Some(AstDiff::Modified { children, .. }) = &diff.diff else { panic!("expected a modified root") };
+        let Some(AstDiff::Modified { slot, children: product, .. }) = children.first() else { panic!() };
+        assert_eq!(slot, "left");
+        assert_eq!(
+            product[1],
+            AstDiff::Replaced { slot: "right".to_string(), before: "2".to_string(), after: "3".to_string() }
+        );
+        let Some(AstDiff::Modified { children: args, .. }) = children.get(1) else { panic!() };
+        assert_eq!(args[0], AstDiff::Added { slot: "arg 1".to_string(), text: "country".to_string() });
+        assert!(args[1..].iter().all(AstDiff::is_unchanged));
+    }
+
+    #[test]
+    fn test_reformatting_is_not_a_change() {
+        let diff = RuleVersionDiff::new(&version(1, "a+b>10 AND c"), &version(2, "(a + b) > 10\n  AND c"));
+        assert_eq!(diff.changes, 0);
+        assert!(diff.diff.as_ref().unwrap().is_unchanged());
+
+        let operator_only = diff_expressions(&parse_definition("a + b").unwrap(), &parse_definition("a - b").unwrap());
+        assert_eq!(operator_only.change_count(), 1);
+
+        let unparsable = RuleVersionDiff::new(&version(1, "a + b"), &version(2, "a + + "));
+        assert!(unparsable.diff.is_none());
+        assert_eq!(unparsable.changes, 1);
+    }
+
+    #[test]
+    fn test_version_in_effect_at_an_instant() {
+        let at = |date: &str| DateTime::parse_from_rfc3339(&format!("{}T00:00:00Z", date)).unwrap().with_timezone(&Utc);
+        let saved = |number: i32, definition: &str, on: &str| RuleVersion { created_at: at(on), ..version(number, definition) };
+
+        let v1 = saved(1, "score * 2", "2025-01-01");
+        let v2 = saved(2, "score * 3", "2025-03-01");
+        // Saved in April, scheduled for June until the end of the year
+        let v3 = RuleVersion { valid_from: Some(at("2025-06-01")), valid_to: Some(at("2026-01-01")), ..saved(3, "score * 4", "2025-04-01") };
+        let versions = vec![v1, v2, v3];
+        let in_effect = |date: &str| versions_in_effect(&versions, at(date)).first().map(|v| v.version);
+
+        assert_eq!(in_effect("2024-12-31"), None);
+        assert_eq!(in_effect("2025-02-01"), Some(1));
+        assert_eq!(in_effect("2025-05-31"), Some(2));
+        assert_eq!(in_effect("2025-06-01"), Some(3));
+        // Once v3's period ends, v2 applies again
+        assert_eq!(in_effect("2026-01-01"), Some(2));
+
+        let retired = RuleVersion { status: "deprecated".to_string(), ..saved(4, "score", "2026-02-01") };
+        let versions = [versions, vec![retired]].concat();
+        assert!(versions_in_effect(&versions, at("2026-03-01")).is_empty());
+
+        assert!(validate_effective_period(Some(at("2025-06-01")), Some(at("2025-06-01"))).is_err());
+        assert!(validate_effective_period(None, Some(at("2025-06-01"))).is_ok());
+    }
+}
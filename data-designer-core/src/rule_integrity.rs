@@ -0,0 +1,102 @@
+//! Re-validating stored rules against the current grammar and dictionary
+//!
+//! A rule that parsed and typechecked when it was activated can stop doing
+//! so once the grammar changes or a dictionary attribute changes type. Run
+//! at startup, this finds those rules before their first evaluation does;
+//! the caller demotes them to `in_repair` and reports the summary.
+
+use crate::parser::parse_rule;
+use crate::type_checker::{typecheck_with_env, TypeEnv};
+use serde::{Deserialize, Serialize};
+
+/// A rule that no longer validates, with what broke
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrokenRule {
+    pub rule_id: String,
+    pub problems: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    pub checked: usize,
+    pub broken: Vec<BrokenRule>,
+}
+
+impl IntegrityReport {
+    pub fn is_clean(&self) -> bool {
+        self.broken.is_empty()
+    }
+
+    /// One line per broken rule, headed by the count
+    pub fn summary(&self) -> String {
+        if self.is_clean() {
+            return format!("All {} active rules validate", self.checked);
+        }
+        let mut summary = format!(
+            "{} of {} active rules no longer validate and were moved to in_repair:",
+            self.broken.len(),
+            self.checked
+        );
+        for rule in &self.broken {
+            summary.push_str(&format!("\n  {}: {}", rule.rule_id, rule.problems.join("; ")));
+        }
+        summary
+    }
+}
+
+/// The problems with one rule definition: a parse failure, or else its type errors
+pub fn rule_problems(definition: &str, env: &TypeEnv) -> Vec<String> {
+    let expression = match parse_rule(definition) {
+        Ok((remaining, _)) if !remaining.trim().is_empty() => {
+            return vec![format!("Unexpected input after rule: {}", remaining.trim())];
+        }
+        Ok((_, expression)) => expression,
+        Err(e) => return vec![format!("Failed to parse rule: {}", e)],
+    };
+    typecheck_with_env(&expression, env)
+        .diagnostics
+        .into_iter()
+        .map(|diagnostic| format!("{} in `{}`", diagnostic.message, diagnostic.expression))
+        .collect()
+}
+
+/// Check each `(rule_id, definition)`, in the order given
+pub fn check_rules<'a>(rules: impl IntoIterator<Item = (&'a str, &'a str)>, env: &TypeEnv) -> IntegrityReport {
+    let mut report = IntegrityReport::default();
+    for (rule_id, definition) in rules {
+        report.checked += 1;
+        let problems = rule_problems(definition, env);
+        if !problems.is_empty() {
+            report.broken.push(BrokenRule { rule_id: rule_id.to_string(), problems });
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::type_checker::RuleType;
+
+    #[test]
+    fn test_rules_that_no_longer_validate_are_reported() {
+        let mut env = TypeEnv::new();
+        env.insert("score", RuleType::String);
+        env.insert("amount", RuleType::Number);
+
+        let report = check_rules(
+            [
+                ("fee", "fee = amount * 0.01"),
+                ("risk", "risk_flag = score > 700"),
+                ("broken", "flag = amount >"),
+            ],
+            &env,
+        );
+        assert_eq!(report.checked, 3);
+        let broken: Vec<&str> = report.broken.iter().map(|rule| rule.rule_id.as_str()).collect();
+        assert_eq!(broken, ["risk", "broken"]);
+        assert!(report.summary().starts_with("2 of 3 active rules no longer validate"));
+
+        assert!(check_rules([("fee", "fee = amount * 0.01")], &env).is_clean());
+    }
+}
@@ -0,0 +1,253 @@
+//! Rule naming conventions and rule ID generation
+//!
+//! A category's policy may require rule names to match a regex and give the
+//! prefix its rule IDs start with. A rule saved without an ID gets one made
+//! from that prefix (or the category key), the attribute it derives and its
+//! applicability, so saving the same rule again finds the same ID. A name
+//! that doesn't match is rejected, or, where the category corrects names,
+//! replaced by the first of its usual spellings (Title Case, snake_case, ...)
+//! that does.
+
+use crate::regex_cache;
+use crate::rule_categories::CategoryTree;
+use crate::rule_variants::Applicability;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Longest rule ID the rules table takes
+pub const MAX_RULE_ID_LEN: usize = 50;
+
+/// The ID and name a rule is saved under
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleNaming {
+    pub rule_id: String,
+    pub rule_name: String,
+    /// What was generated or corrected, to show the author
+    pub notes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum NamingViolation {
+    MissingName,
+    NameMismatch { name: String, pattern: String, category: String },
+    IdPrefix { rule_id: String, prefix: String, category: String },
+    InvalidPattern { pattern: String, category: String },
+}
+
+impl fmt::Display for NamingViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NamingViolation::MissingName => write!(f, "Rule name is required"),
+            NamingViolation::NameMismatch { name, pattern, category } => {
+                write!(f, "Rule name '{}' doesn't follow the naming convention of category '{}' ({})", name, category, pattern)
+            }
+            NamingViolation::IdPrefix { rule_id, prefix, category } => write!(
+                f,
+                "Rule ID '{}' must start with '{}_' in category '{}'; leave it empty to have one generated",
+                rule_id, prefix, category
+            ),
+            NamingViolation::InvalidPattern { pattern, category } => {
+                write!(f, "Category '{}' has an invalid naming pattern: {}", category, pattern)
+            }
+        }
+    }
+}
+
+/// Checks a rule's ID and name against the naming policy of category `id`,
+/// generating the ID when `rule_id` is empty
+pub fn apply_naming(
+    categories: &CategoryTree,
+    id: i32,
+    rule_id: &str,
+    rule_name: &str,
+    attribute: &str,
+    applies_to: &Applicability,
+) -> Result<RuleNaming, Vec<NamingViolation>> {
+    let policy = categories.effective_policy(id);
+    let category = categories.qualified_name(id);
+    let mut violations = Vec::new();
+    let mut notes = Vec::new();
+
+    let rule_id = rule_id.trim();
+    let rule_id = match &policy.rule_id_prefix {
+        _ if rule_id.is_empty() => {
+            let category_key = categories.get(id).map_or("RULE", |c| c.category_key.as_str());
+            let prefix = policy.rule_id_prefix.as_deref().unwrap_or(category_key);
+            let generated = generate_rule_id(prefix, attribute, applies_to);
+            notes.push(format!("Generated rule ID {}", generated));
+            generated
+        }
+        Some(prefix) if !rule_id.starts_with(&format!("{}_", prefix)) => {
+            violations.push(NamingViolation::IdPrefix { rule_id: rule_id.to_string(), prefix: prefix.clone(), category: category.clone() });
+            rule_id.to_string()
+        }
+        _ => rule_id.to_string(),
+    };
+
+    let name = rule_name.split_whitespace().collect::<Vec<_>>().join(" ");
+    let rule_name = match &policy.rule_name_pattern {
+        _ if name.is_empty() => {
+            violations.push(NamingViolation::MissingName);
+            name
+        }
+        None => name,
+        Some(pattern) => match regex_cache::compiled(&format!("^(?:{})$", pattern)) {
+            Err(_) => {
+                violations.push(NamingViolation::InvalidPattern { pattern: pattern.clone(), category: category.clone() });
+                name
+            }
+            Ok(regex) if regex.is_match(&name) => name,
+            Ok(regex) => match name_spellings(&name).into_iter().find(|spelling| regex.is_match(spelling)) {
+                Some(corrected) if policy.auto_correct_names => {
+                    notes.push(format!("Renamed '{}' to '{}' to follow the convention of category '{}'", name, corrected, category));
+                    corrected
+                }
+                _ => {
+                    violations.push(NamingViolation::NameMismatch { name: name.clone(), pattern: pattern.clone(), category: category.clone() });
+                    name
+                }
+            },
+        },
+    };
+
+    if violations.is_empty() {
+        Ok(RuleNaming { rule_id, rule_name, notes })
+    } else {
+        Err(violations)
+    }
+}
+
+/// `PREFIX_ATTRIBUTE`, followed by the applicability's values, in upper snake
+/// case: `KYC_RISK_SCORE_LU` for the Luxembourg variant of `riskScore`. IDs
+/// over `MAX_RULE_ID_LEN` are cut short and end in a hash of the whole.
+pub fn generate_rule_id(prefix: &str, attribute: &str, applies_to: &Applicability) -> String {
+    let mut parts = vec![upper_snake(prefix), upper_snake(attribute)];
+    parts.extend(applies_to.conditions().into_iter().map(|(_, value)| upper_snake(value)));
+    let id = parts.into_iter().filter(|part| !part.is_empty()).collect::<Vec<_>>().join("_");
+    if id.len() <= MAX_RULE_ID_LEN {
+        return id;
+    }
+    let hash = format!("{:08X}", fnv1a(id.as_bytes()));
+    format!("{}_{}", id[..MAX_RULE_ID_LEN - hash.len() - 1].trim_end_matches('_'), hash)
+}
+
+/// The usual ways of writing the words of `name`, the name itself first
+pub fn name_spellings(name: &str) -> Vec<String> {
+    let words = words(name);
+    let capitalized: Vec<String> = words.iter().map(|word| capitalize(word)).collect();
+    let mut spellings = vec![
+        name.to_string(),
+        capitalized.join(" "),
+        capitalize(&words.join(" ")),
+        words.join("_"),
+        upper_snake(name),
+        words.join("-"),
+        capitalized.concat(),
+        words.first().map_or(String::new(), |first| format!("{}{}", first, capitalized[1..].concat())),
+    ];
+    let mut seen = std::collections::HashSet::new();
+    spellings.retain(|spelling| !spelling.is_empty() && seen.insert(spelling.clone()));
+    spellings
+}
+
+// Lower-cased words, split at anything not alphanumeric and at camelCase humps
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut previous_lower = false;
+    for c in text.chars() {
+        if !c.is_alphanumeric() {
+            words.extend((!current.is_empty()).then(|| std::mem::take(&mut current)));
+            previous_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && previous_lower {
+            words.push(std::mem::take(&mut current));
+        }
+        previous_lower = c.is_lowercase() || c.is_ascii_digit();
+        current.extend(c.to_lowercase());
+    }
+    words.extend((!current.is_empty()).then_some(current));
+    words
+}
+
+fn upper_snake(text: &str) -> String {
+    words(text).join("_").to_uppercase()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map_or(String::new(), |first| first.to_uppercase().chain(chars).collect())
+}
+
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x01000193))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_categories::{CategoryPolicy, RuleCategory};
+
+    fn tree(auto_correct_names: bool) -> CategoryTree {
+        CategoryTree::new(vec![
+            RuleCategory {
+                id: 1,
+                category_key: "kyc_validation".to_string(),
+                name: "KYC".to_string(),
+                parent_id: None,
+                policy: CategoryPolicy {
+                    rule_name_pattern: Some("[A-Z][a-z0-9]*( [A-Z][a-z0-9]*)*".to_string()),
+                    rule_id_prefix: Some("KYC".to_string()),
+                    auto_correct_names: Some(auto_correct_names),
+                    ..Default::default()
+                },
+            },
+            RuleCategory {
+                id: 2,
+                category_key: "kyc_screening".to_string(),
+                name: "Screening".to_string(),
+                parent_id: Some(1),
+                policy: CategoryPolicy::default(),
+            },
+        ])
+    }
+
+    #[test]
+    fn test_rule_ids_are_generated_from_prefix_attribute_and_applicability() {
+        let lux = Applicability { jurisdiction: Some("LU".to_string()), ..Default::default() };
+        assert_eq!(generate_rule_id("KYC", "client.riskScore", &Applicability::default()), "KYC_CLIENT_RISK_SCORE");
+        assert_eq!(generate_rule_id("kyc", "risk_score", &lux), "KYC_RISK_SCORE_LU");
+
+        let long = generate_rule_id("KYC", &"very_long_attribute_name_".repeat(4), &lux);
+        assert_eq!(long.len(), MAX_RULE_ID_LEN);
+        assert_eq!(long, generate_rule_id("KYC", &"very_long_attribute_name_".repeat(4), &lux));
+        assert_ne!(long, generate_rule_id("KYC", &"very_long_attribute_name_".repeat(4), &Applicability::default()));
+
+        let named = apply_naming(&tree(false), 2, "", "Risk Score", "riskScore", &lux).unwrap();
+        assert_eq!(named.rule_id, "KYC_RISK_SCORE_LU");
+        assert_eq!(named.notes, ["Generated rule ID KYC_RISK_SCORE_LU"]);
+        let unprefixed = CategoryTree::new(vec![RuleCategory { id: 1, category_key: "pricing".to_string(), name: "Pricing".to_string(), parent_id: None, policy: CategoryPolicy::default() }]);
+        assert_eq!(apply_naming(&unprefixed, 1, " ", "fee", "fee", &Applicability::default()).unwrap().rule_id, "PRICING_FEE");
+    }
+
+    #[test]
+    fn test_names_are_rejected_or_corrected_under_the_inherited_convention() {
+        let none = Applicability::default();
+        let violations = apply_naming(&tree(false), 2, "RULE_1", "risk_score", "risk", &none).unwrap_err();
+        assert_eq!(violations.len(), 2);
+        assert_eq!(
+            violations[0].to_string(),
+            "Rule ID 'RULE_1' must start with 'KYC_' in category 'KYC / Screening'; leave it empty to have one generated"
+        );
+        assert!(matches!(&violations[1], NamingViolation::NameMismatch { name, .. } if name == "risk_score"));
+
+        let corrected = apply_naming(&tree(true), 2, "KYC_RISK", "  risk_score ", "risk", &none).unwrap();
+        assert_eq!(corrected.rule_name, "Risk Score");
+        assert_eq!(corrected.notes, ["Renamed 'risk_score' to 'Risk Score' to follow the convention of category 'KYC / Screening'"]);
+        assert_eq!(corrected.rule_id, "KYC_RISK");
+
+        assert_eq!(name_spellings("clientRiskScore")[1..5], ["Client Risk Score", "Client risk score", "client_risk_score", "CLIENT_RISK_SCORE"]);
+        assert_eq!(apply_naming(&tree(true), 1, "KYC_X", "", "x", &none).unwrap_err(), [NamingViolation::MissingName]);
+    }
+}
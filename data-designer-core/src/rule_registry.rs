@@ -0,0 +1,257 @@
+//! Client for remote rule pack registries
+//!
+//! A registry is a plain HTTPS site. Its `index.json` lists the packs on offer,
+//! each a signed rule bundle (see `crate::rule_bundle`) that may also carry the
+//! grammar extensions its rules call:
+//!
+//! ```text
+//! index.json                        {"format_version": 1, "packs": [...]}
+//! packs/kyc-core-1.2.0.json         SignedRuleBundle
+//! ```
+//!
+//! A downloaded pack must match the SHA-256 listed in the index and be signed
+//! by one of `SecurityConfig::trusted_keys` before anything is installed. Its
+//! rules are committed to the rule repository next to those already there.
+
+use crate::config::{RegistryConfig, TrustedKey};
+use crate::rule_bundle::{RuleBundle, SignedRuleBundle};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+
+pub const REGISTRY_FORMAT_VERSION: u32 = 1;
+
+/// The grammar extension types a pack may add
+pub const EXTENSION_TYPES: [&str; 3] = ["keyword", "operator", "function"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegistryIndex {
+    pub format_version: u32,
+    pub packs: Vec<RegistryPack>,
+}
+
+/// A pack as listed in the index
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegistryPack {
+    pub name: String,
+    /// Dotted numeric version, e.g. `1.2.0`
+    pub version: String,
+    pub publisher: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The signed bundle, relative to the index or an absolute HTTPS URL
+    pub url: String,
+    /// Hex-encoded SHA-256 of the signed bundle file
+    pub sha256: String,
+}
+
+impl RegistryIndex {
+    pub fn parse(json: &str) -> Result<Self> {
+        let index: RegistryIndex = serde_json::from_str(json).context("Invalid registry index")?;
+        if index.format_version != REGISTRY_FORMAT_VERSION {
+            bail!("Unsupported registry index version {}", index.format_version);
+        }
+        Ok(index)
+    }
+
+    /// The pack called `name` at `version`, or its latest version if none is given
+    pub fn find(&self, name: &str, version: Option<&str>) -> Option<&RegistryPack> {
+        let mut candidates = self.packs.iter().filter(|pack| pack.name == name);
+        match version {
+            Some(version) => candidates.find(|pack| pack.version == version),
+            None => candidates.max_by_key(|pack| version_key(&pack.version)),
+        }
+    }
+}
+
+// Versions compare by their numeric parts; anything else sorts as 0
+fn version_key(version: &str) -> Vec<u64> {
+    version.split('.').map(|part| part.trim().parse().unwrap_or(0)).collect()
+}
+
+/// A pack whose digest and signature have been checked
+#[derive(Debug, Clone)]
+pub struct VerifiedPack {
+    pub pack: RegistryPack,
+    pub signed: SignedRuleBundle,
+    pub bundle: RuleBundle,
+}
+
+pub struct RegistryClient {
+    index_url: String,
+    client: reqwest::Client,
+}
+
+impl RegistryClient {
+    pub fn new(config: &RegistryConfig) -> Result<Self> {
+        if config.index_url.is_empty() {
+            bail!("No rule pack registry is configured");
+        }
+        require_https(&config.index_url)?;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_seconds))
+            .build()?;
+        Ok(Self { index_url: config.index_url.clone(), client })
+    }
+
+    pub async fn fetch_index(&self) -> Result<RegistryIndex> {
+        let body = self.get(&self.index_url).await?;
+        RegistryIndex::parse(&String::from_utf8_lossy(&body))
+    }
+
+    /// Download `pack`, check it against the index's digest and verify its
+    /// signature and grammar extensions
+    pub async fn download(&self, pack: &RegistryPack, trusted_keys: &[TrustedKey]) -> Result<VerifiedPack> {
+        let url = resolve_pack_url(&self.index_url, &pack.url)?;
+        let body = self.get(&url).await?;
+        verify_pack(pack, &body, trusted_keys)
+    }
+
+    async fn get(&self, url: &str) -> Result<Vec<u8>> {
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .with_context(|| format!("Registry is unreachable at {}", url))?;
+        if !response.status().is_success() {
+            bail!("Registry returned {} for {}", response.status(), url);
+        }
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+/// Check a downloaded pack file against its index entry and trusted keys
+pub fn verify_pack(pack: &RegistryPack, body: &[u8], trusted_keys: &[TrustedKey]) -> Result<VerifiedPack> {
+    let digest = hex::encode(Sha256::digest(body));
+    if !digest.eq_ignore_ascii_case(pack.sha256.trim()) {
+        bail!("Pack '{}' {} doesn't match the digest in the registry index", pack.name, pack.version);
+    }
+    let signed: SignedRuleBundle = serde_json::from_slice(body).context("Pack is not a signed rule bundle")?;
+    let bundle = signed
+        .verify(trusted_keys)
+        .with_context(|| format!("Pack '{}' {} failed verification", pack.name, pack.version))?;
+    for extension in &bundle.grammar_extensions {
+        if !EXTENSION_TYPES.contains(&extension.extension_type.as_str()) {
+            bail!(
+                "Pack '{}' adds grammar extension '{}' of unknown type '{}'",
+                pack.name,
+                extension.name,
+                extension.extension_type
+            );
+        }
+    }
+    Ok(VerifiedPack { pack: pack.clone(), signed, bundle })
+}
+
+/// Where to download a pack listed at `pack_url` in the index at `index_url`
+pub fn resolve_pack_url(index_url: &str, pack_url: &str) -> Result<String> {
+    if pack_url.contains("://") {
+        require_https(pack_url)?;
+        return Ok(pack_url.to_string());
+    }
+    if pack_url.split('/').any(|segment| segment == "..") {
+        bail!("Pack URL '{}' leaves the registry", pack_url);
+    }
+    let base = index_url.rsplit_once('/').map(|(base, _)| base).unwrap_or(index_url);
+    Ok(format!("{}/{}", base, pack_url.trim_start_matches('/')))
+}
+
+// Plain HTTP is only accepted from this machine, for registries under test
+fn require_https(url: &str) -> Result<()> {
+    let local = ["http://localhost", "http://127.0.0.1"]
+        .iter()
+        .any(|prefix| url.strip_prefix(prefix).is_some_and(|rest| rest.starts_with([':', '/'])));
+    if url.starts_with("https://") || local {
+        Ok(())
+    } else {
+        Err(anyhow!("Registry URL '{}' must use https", url))
+    }
+}
+
+/// Commit a verified pack's rules to `repository` as `author`, keeping the
+/// rules already there. Its grammar extensions are the caller's to store.
+#[cfg(feature = "git")]
+pub fn install(
+    repository: &crate::rule_repository::RuleRepository,
+    verified: &VerifiedPack,
+    trusted_keys: &[TrustedKey],
+    author: &crate::rule_repository::CommitAuthor,
+) -> Result<Option<git2::Oid>> {
+    repository.install_bundle(&verified.signed, trusted_keys, author)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_bundle::{public_key_hex, GrammarExtensionSpec};
+    use ed25519_dalek::SigningKey;
+
+    fn entry(version: &str, body: &[u8]) -> RegistryPack {
+        RegistryPack {
+            name: "kyc-core".to_string(),
+            version: version.to_string(),
+            publisher: "compliance".to_string(),
+            description: None,
+            tags: Vec::new(),
+            url: format!("packs/kyc-core-{}.json", version),
+            sha256: hex::encode(Sha256::digest(body)),
+        }
+    }
+
+    fn signed_pack(extension_type: &str) -> (Vec<u8>, Vec<TrustedKey>) {
+        let key = SigningKey::from_bytes(&[3; 32]);
+        let bundle = RuleBundle::new(Vec::new()).with_grammar_extensions(vec![GrammarExtensionSpec {
+            name: "SANCTIONED".to_string(),
+            extension_type: extension_type.to_string(),
+            signature: Some("SANCTIONED(name)".to_string()),
+            description: None,
+            category: Some("kyc".to_string()),
+        }]);
+        let signed = bundle.sign("compliance", &key).unwrap();
+        let trusted = vec![TrustedKey { name: "compliance".to_string(), public_key: public_key_hex(&key) }];
+        (serde_json::to_vec(&signed).unwrap(), trusted)
+    }
+
+    #[test]
+    fn test_verified_pack_must_match_digest_and_signature() {
+        let (body, trusted) = signed_pack("function");
+        let verified = verify_pack(&entry("1.0.0", &body), &body, &trusted).unwrap();
+        assert_eq!(verified.bundle.grammar_extensions[0].name, "SANCTIONED");
+
+        let mut tampered = body.clone();
+        tampered.push(b' ');
+        let err = verify_pack(&entry("1.0.0", &body), &tampered, &trusted).unwrap_err();
+        assert!(err.to_string().contains("digest"));
+
+        assert!(verify_pack(&entry("1.0.0", &body), &body, &[]).is_err());
+
+        let (body, trusted) = signed_pack("macro");
+        assert!(verify_pack(&entry("1.0.0", &body), &body, &trusted).is_err());
+    }
+
+    #[test]
+    fn test_index_finds_latest_version_and_resolves_urls() {
+        let index = RegistryIndex {
+            format_version: REGISTRY_FORMAT_VERSION,
+            packs: vec![entry("1.9.0", b"a"), entry("1.10.0", b"b"), entry("1.2.0", b"c")],
+        };
+        let index = RegistryIndex::parse(&serde_json::to_string(&index).unwrap()).unwrap();
+        assert_eq!(index.find("kyc-core", None).unwrap().version, "1.10.0");
+        assert_eq!(index.find("kyc-core", Some("1.2.0")).unwrap().version, "1.2.0");
+        assert!(index.find("aml-core", None).is_none());
+
+        let index_url = "https://rules.example.com/registry/index.json";
+        assert_eq!(
+            resolve_pack_url(index_url, "packs/kyc-core-1.10.0.json").unwrap(),
+            "https://rules.example.com/registry/packs/kyc-core-1.10.0.json"
+        );
+        assert!(resolve_pack_url(index_url, "../secrets.json").is_err());
+        assert!(resolve_pack_url(index_url, "http://mirror.example.com/pack.json").is_err());
+        assert!(require_https("http://localhost:8080/index.json").is_ok());
+        assert!(require_https("http://localhost.example.com/index.json").is_err());
+    }
+}
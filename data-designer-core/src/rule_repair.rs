@@ -0,0 +1,224 @@
+//! Guided repair of a rule that no longer validates
+//!
+//! A repair plan gathers what is known about a broken rule: its parse and
+//! type errors, the most similar of its earlier versions that still
+//! validates, and fixes proposed by the AI assistant. Each fix is a step
+//! carrying the definition it would save and its diff from the current one,
+//! ordered so the UI can offer them one at a time: fixes that validate
+//! first, then the ones closest to what the author wrote.
+
+use crate::parser::parse_rule;
+use crate::rule_history::{diff_expressions, AstDiff, RuleVersion};
+use crate::rule_integrity::rule_problems;
+use crate::type_checker::TypeEnv;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Where a proposed fix came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RepairSource {
+    HistoricalVersion { version: i32, created_at: DateTime<Utc> },
+    AiSuggestion { title: String, confidence: f32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepairStep {
+    pub source: RepairSource,
+    pub description: String,
+    pub definition: String,
+    /// Token similarity to the current definition, from 0 to 1
+    pub similarity: f64,
+    /// What would still be wrong after applying it; empty when it validates
+    pub remaining_problems: Vec<String>,
+    /// Structural diff from the current definition; None when that doesn't parse
+    pub diff: Option<AstDiff>,
+}
+
+impl RepairStep {
+    pub fn validates(&self) -> bool {
+        self.remaining_problems.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepairPlan {
+    pub rule_id: String,
+    pub definition: String,
+    pub problems: Vec<String>,
+    pub steps: Vec<RepairStep>,
+}
+
+impl RepairPlan {
+    /// Diagnose `definition` and propose the most similar of `versions` that
+    /// still validates; versions with the current definition are passed over
+    pub fn new(rule_id: &str, definition: &str, versions: &[RuleVersion], env: &TypeEnv) -> Self {
+        let mut plan = RepairPlan {
+            rule_id: rule_id.to_string(),
+            definition: definition.to_string(),
+            problems: rule_problems(definition, env),
+            steps: Vec::new(),
+        };
+
+        let restorable = versions
+            .iter()
+            .filter(|version| version.rule_definition.trim() != definition.trim())
+            .filter(|version| rule_problems(&version.rule_definition, env).is_empty())
+            .map(|version| (similarity(definition, &version.rule_definition), version))
+            .max_by(|(a, first), (b, second)| a.total_cmp(b).then(first.version.cmp(&second.version)));
+        if let Some((_, version)) = restorable {
+            plan.push(
+                RepairSource::HistoricalVersion { version: version.version, created_at: version.created_at },
+                format!("Restore version {}", version.version),
+                &version.rule_definition,
+                env,
+            );
+        }
+        plan
+    }
+
+    /// Add the AI assistant's proposed definitions, `(title, confidence, definition)`;
+    /// ones that repeat the current definition or an existing step are dropped
+    pub fn propose_fixes(&mut self, suggestions: impl IntoIterator<Item = (String, f32, String)>, env: &TypeEnv) {
+        for (title, confidence, definition) in suggestions {
+            let definition = definition.trim();
+            if definition.is_empty()
+                || definition == self.definition.trim()
+                || self.steps.iter().any(|step| step.definition.trim() == definition)
+            {
+                continue;
+            }
+            let description = format!("Apply suggested fix: {}", title);
+            self.push(RepairSource::AiSuggestion { title, confidence }, description, definition, env);
+        }
+    }
+
+    // Insert a step, keeping fixes that validate first and then the most similar
+    fn push(&mut self, source: RepairSource, description: String, definition: &str, env: &TypeEnv) {
+        let step = RepairStep {
+            source,
+            description,
+            definition: definition.to_string(),
+            similarity: similarity(&self.definition, definition),
+            remaining_problems: rule_problems(definition, env),
+            diff: parse_complete(&self.definition)
+                .zip(parse_complete(definition))
+                .map(|(before, after)| diff_expressions(&before, &after)),
+        };
+        let position = self
+            .steps
+            .iter()
+            .position(|other| (step.validates(), step.similarity) > (other.validates(), other.similarity))
+            .unwrap_or(self.steps.len());
+        self.steps.insert(position, step);
+    }
+}
+
+fn parse_complete(definition: &str) -> Option<crate::models::Expression> {
+    match parse_rule(definition) {
+        Ok((remaining, expression)) if remaining.trim().is_empty() => Some(expression),
+        _ => None,
+    }
+}
+
+/// Similarity of two definitions by their longest common subsequence of tokens,
+/// which still works when one of them doesn't parse
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (tokens(a), tokens(b));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let mut previous = vec![0usize; b.len() + 1];
+    for token in &a {
+        let mut current = vec![0usize; b.len() + 1];
+        for (j, other) in b.iter().enumerate() {
+            current[j + 1] = if token == other { previous[j] + 1 } else { previous[j + 1].max(current[j]) };
+        }
+        previous = current;
+    }
+    2.0 * previous[b.len()] as f64 / (a.len() + b.len()) as f64
+}
+
+// Words and numbers as one token each, every other non-space character on its own
+fn tokens(source: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut end = start + c.len_utf8();
+        if c.is_alphanumeric() || c == '_' || c == '.' {
+            while let Some(&(index, next)) = chars.peek() {
+                if !(next.is_alphanumeric() || next == '_' || next == '.') {
+                    break;
+                }
+                end = index + next.len_utf8();
+                chars.next();
+            }
+        }
+        tokens.push(&source[start..end]);
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::type_checker::RuleType;
+
+    fn version(version: i32, definition: &str) -> RuleVersion {
+        RuleVersion {
+            rule_id: "fee".to_string(),
+            version,
+            rule_name: "Fee".to_string(),
+            description: None,
+            rule_definition: definition.to_string(),
+            status: "active".to_string(),
+            change_description: None,
+            created_by: None,
+            created_at: DateTime::<Utc>::from_timestamp(1_700_000_000 + i64::from(version), 0).unwrap(),
+            valid_from: None,
+            valid_to: None,
+            applies_to: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_plan_orders_valid_fixes_by_similarity() {
+        let mut env = TypeEnv::new();
+        env.insert("amount", RuleType::Number);
+        env.insert("tier", RuleType::String);
+
+        let versions = [
+            version(1, "fee = amount * 0.02"),
+            version(2, "fee = amount * 0.01 + 5"),
+            version(3, "fee = tier * 0.01 + 5"),
+            version(4, "fee = amount * 0.01 +"),
+        ];
+        let mut plan = RepairPlan::new("fee", "fee = amount * 0.01 +", &versions, &env);
+        assert_eq!(plan.problems.len(), 1);
+        assert_eq!(plan.steps.len(), 1);
+        assert_eq!(plan.steps[0].description, "Restore version 2");
+        assert!(plan.steps[0].diff.is_none());
+
+        plan.propose_fixes(
+            [
+                ("Drop the trailing operator".to_string(), 0.8, "fee = amount * 0.01".to_string()),
+                ("Use the tier".to_string(), 0.6, "fee = tier * 2".to_string()),
+                ("Same as before".to_string(), 0.5, "fee = amount * 0.01 + 5".to_string()),
+            ],
+            &env,
+        );
+        let order: Vec<&str> = plan.steps.iter().map(|step| step.definition.as_str()).collect();
+        assert_eq!(order, ["fee = amount * 0.01 + 5", "fee = amount * 0.01", "fee = tier * 2"]);
+        assert!(plan.steps[0].validates() && !plan.steps[2].validates());
+    }
+
+    #[test]
+    fn test_similarity_counts_shared_tokens() {
+        assert_eq!(similarity("a > 1", "a > 1"), 1.0);
+        assert_eq!(similarity("score >= 700", "score > 700"), 2.0 * 3.0 / 7.0);
+        assert_eq!(similarity("x", "y"), 0.0);
+    }
+}
@@ -0,0 +1,112 @@
+//! Git-backed rule repository
+//!
+//! Rules are exported to a deterministic layout so changes can be reviewed
+//! through normal pull requests, then re-imported with validation:
+//!
+//! ```text
+//! rules/<rule_id>/rule.dsl        rule definition
+//! rules/<rule_id>/metadata.yaml   name, status, version, tags, ...
+//! rules/<rule_id>/tests.yaml      optional test fixtures
+//! ```
+//!
+//! The exchange format below needs nothing but serde; the repository itself
+//! needs libgit2 and is compiled with the `git` feature (on by default).
+
+use crate::storage::Rule;
+use crate::rule_history::RuleVersion;
+use crate::rule_variants::Applicability;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[cfg(feature = "git")]
+mod repository;
+#[cfg(feature = "git")]
+pub use repository::*;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleMetadata {
+    pub rule_id: String,
+    pub rule_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category_id: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_attribute_id: Option<i32>,
+    pub status: String,
+    pub version: i32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// When this version takes effect; absent means it always has been
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_from: Option<DateTime<Utc>>,
+    /// When it stops being in effect, exclusive; absent means open-ended
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_to: Option<DateTime<Utc>>,
+    /// The jurisdiction, product line or CBU type this variant of the rule
+    /// is for; absent means it applies everywhere
+    #[serde(default, skip_serializing_if = "Applicability::is_universal")]
+    pub applies_to: Applicability,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleTestFixture {
+    pub name: String,
+    #[serde(default)]
+    pub inputs: BTreeMap<String, serde_json::Value>,
+    pub expected: serde_json::Value,
+}
+
+/// A rule as it is stored in the repository
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedRule {
+    pub metadata: RuleMetadata,
+    pub definition: String,
+    pub tests: Vec<RuleTestFixture>,
+}
+
+impl From<&Rule> for ExportedRule {
+    fn from(rule: &Rule) -> Self {
+        Self {
+            metadata: RuleMetadata {
+                rule_id: rule.rule_id.clone(),
+                rule_name: rule.rule_name.clone(),
+                description: rule.description.clone(),
+                category_id: rule.category_id,
+                target_attribute_id: rule.target_attribute_id,
+                status: rule.status.clone(),
+                version: rule.version,
+                tags: rule.tags.clone().unwrap_or_default(),
+                valid_from: rule.valid_from,
+                valid_to: rule.valid_to,
+                applies_to: Applicability::default(),
+            },
+            definition: rule.rule_definition.clone(),
+            tests: Vec::new(),
+        }
+    }
+}
+
+/// A saved version with its effective period; undated versions take effect when saved
+impl From<&RuleVersion> for ExportedRule {
+    fn from(version: &RuleVersion) -> Self {
+        Self {
+            metadata: RuleMetadata {
+                rule_id: version.rule_id.clone(),
+                rule_name: version.rule_name.clone(),
+                description: version.description.clone(),
+                category_id: None,
+                target_attribute_id: None,
+                status: version.status.clone(),
+                version: version.version,
+                tags: Vec::new(),
+                valid_from: Some(version.effective_from()),
+                valid_to: version.valid_to,
+                applies_to: version.applies_to.clone(),
+            },
+            definition: version.rule_definition.clone(),
+            tests: Vec::new(),
+        }
+    }
+}
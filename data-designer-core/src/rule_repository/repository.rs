@@ -0,0 +1,467 @@
+use super::{ExportedRule, RuleMetadata};
+use crate::config::TrustedKey;
+use crate::rule_bundle::{RuleBundle, SignedRuleBundle};
+use crate::transpiler::DslTranspiler;
+use anyhow::{anyhow, bail, Context, Result};
+use git2::{IndexAddOption, Oid, Repository, Signature, Tree};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const RULES_DIR: &str = "rules";
+const DEFINITION_FILE: &str = "rule.dsl";
+const METADATA_FILE: &str = "metadata.yaml";
+const TESTS_FILE: &str = "tests.yaml";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitAuthor {
+    pub name: String,
+    pub email: String,
+}
+
+/// A rule read back from the repository, attributed to the commit that last touched it
+#[derive(Debug, Clone)]
+pub struct ImportedRule {
+    pub rule: ExportedRule,
+    pub last_author: Option<CommitAuthor>,
+    pub last_commit: Option<Oid>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportError {
+    pub rule_id: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    pub rules: Vec<ImportedRule>,
+    pub errors: Vec<ImportError>,
+}
+
+impl ImportReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+pub struct RuleRepository {
+    repo: Repository,
+}
+
+impl RuleRepository {
+    /// Open an existing repository at `path`, or initialise a new one
+    pub fn open_or_init(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let repo = match Repository::open(path) {
+            Ok(repo) => repo,
+            Err(_) => Repository::init(path)
+                .with_context(|| format!("Failed to initialise repository at {}", path.display()))?,
+        };
+        if repo.is_bare() {
+            bail!("Rule repository must have a working directory: {}", path.display());
+        }
+        Ok(Self { repo })
+    }
+
+    fn workdir(&self) -> &Path {
+        // Bare repositories are rejected in open_or_init
+        self.repo.workdir().expect("rule repository has a working directory")
+    }
+
+    /// Write `rules` to the working tree, replacing whatever was exported before,
+    /// and commit the result as `author`. Returns `None` if nothing changed.
+    pub fn export_rules(&self, rules: &[ExportedRule], author: &CommitAuthor, message: &str) -> Result<Option<Oid>> {
+        let rules_dir = self.workdir().join(RULES_DIR);
+        if rules_dir.exists() {
+            fs::remove_dir_all(&rules_dir)?;
+        }
+        for rule in rules {
+            write_rule(&rules_dir, rule)?;
+        }
+        self.commit_rules(author, message)
+    }
+
+    /// Write `rules` to the working tree next to those already there, replacing
+    /// any with the same id, and commit the result as `author`
+    pub fn add_rules(&self, rules: &[ExportedRule], author: &CommitAuthor, message: &str) -> Result<Option<Oid>> {
+        let rules_dir = self.workdir().join(RULES_DIR);
+        for rule in rules {
+            check_rule_id(&rule.metadata.rule_id)?;
+            let dir = rules_dir.join(&rule.metadata.rule_id);
+            if dir.exists() {
+                fs::remove_dir_all(&dir)?;
+            }
+            write_rule(&rules_dir, rule)?;
+        }
+        self.commit_rules(author, message)
+    }
+
+    // Commit the rules directory as it is in the working tree; None if unchanged
+    fn commit_rules(&self, author: &CommitAuthor, message: &str) -> Result<Option<Oid>> {
+        let mut index = self.repo.index()?;
+        index.add_all([RULES_DIR], IndexAddOption::DEFAULT, None)?;
+        index.update_all([RULES_DIR], None)?;
+        index.write()?;
+        let tree = self.repo.find_tree(index.write_tree()?)?;
+
+        let parent = match self.repo.head() {
+            Ok(head) => Some(head.peel_to_commit()?),
+            Err(_) => None,
+        };
+        if let Some(parent) = &parent {
+            if parent.tree_id() == tree.id() {
+                return Ok(None);
+            }
+        }
+
+        let signature = Signature::now(&author.name, &author.email)?;
+        let parents: Vec<_> = parent.iter().collect();
+        let oid = self.repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)?;
+        Ok(Some(oid))
+    }
+
+    /// Read every rule in the working tree, validating definitions and
+    /// attributing each rule to the last commit that changed it
+    pub fn import_rules(&self) -> Result<ImportReport> {
+        let mut report = ImportReport::default();
+        let rules_dir = self.workdir().join(RULES_DIR);
+        if !rules_dir.exists() {
+            return Ok(report);
+        }
+
+        let mut rule_dirs: Vec<PathBuf> = fs::read_dir(&rules_dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_dir())
+            .collect();
+        rule_dirs.sort();
+
+        for dir in rule_dirs {
+            let rule_id = dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+            match read_rule(&dir).and_then(|rule| validate_rule(&rule_id, &rule).map(|_| rule)) {
+                Ok(rule) => {
+                    let last_commit = self.last_commit_touching(&format!("{}/{}", RULES_DIR, rule_id))?;
+                    let last_author = match last_commit {
+                        Some(oid) => {
+                            let commit = self.repo.find_commit(oid)?;
+                            let author = commit.author();
+                            Some(CommitAuthor {
+                                name: author.name().unwrap_or_default().to_string(),
+                                email: author.email().unwrap_or_default().to_string(),
+                            })
+                        }
+                        None => None,
+                    };
+                    report.rules.push(ImportedRule { rule, last_author, last_commit });
+                }
+                Err(e) => report.errors.push(ImportError { rule_id, message: format!("{:#}", e) }),
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Rule ids whose files differ between `since` and HEAD
+    pub fn changed_rule_ids(&self, since: Oid) -> Result<Vec<String>> {
+        let old_tree = self.repo.find_commit(since)?.tree()?;
+        let new_tree = self.repo.head()?.peel_to_commit()?.tree()?;
+        let diff = self.repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)?;
+
+        let mut ids = Vec::new();
+        for delta in diff.deltas() {
+            let path = delta.new_file().path().or_else(|| delta.old_file().path());
+            if let Some(id) = path.and_then(rule_id_from_path) {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Bundle the rules in the working tree for signing; fails if any rule is invalid
+    pub fn export_bundle(&self) -> Result<RuleBundle> {
+        let report = self.import_rules()?;
+        if let Some(error) = report.errors.first() {
+            bail!("Rule '{}' is invalid: {}", error.rule_id, error.message);
+        }
+        Ok(RuleBundle::new(report.rules.into_iter().map(|imported| imported.rule).collect()))
+    }
+
+    /// Verify a signed bundle against `trusted_keys` and commit its rules as `author`
+    pub fn import_bundle(
+        &self,
+        signed: &SignedRuleBundle,
+        trusted_keys: &[TrustedKey],
+        author: &CommitAuthor,
+    ) -> Result<Option<Oid>> {
+        let bundle = signed.verify(trusted_keys)?;
+        let message = format!("Import rule bundle signed by '{}'", signed.key_name);
+        self.export_rules(&bundle.rules, author, &message)
+    }
+
+    /// Verify a signed bundle against `trusted_keys` and commit its rules as
+    /// `author` alongside the rules already here. Unlike `import_bundle` this
+    /// removes nothing, so packs from several publishers can be installed.
+    pub fn install_bundle(
+        &self,
+        signed: &SignedRuleBundle,
+        trusted_keys: &[TrustedKey],
+        author: &CommitAuthor,
+    ) -> Result<Option<Oid>> {
+        let bundle = signed.verify(trusted_keys)?;
+        let message = format!("Install rule bundle signed by '{}'", signed.key_name);
+        self.add_rules(&bundle.rules, author, &message)
+    }
+
+    fn last_commit_touching(&self, path: &str) -> Result<Option<Oid>> {
+        let head = match self.repo.head() {
+            Ok(head) => head.peel_to_commit()?,
+            Err(_) => return Ok(None),
+        };
+
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.push(head.id())?;
+        for oid in revwalk {
+            let commit = self.repo.find_commit(oid?)?;
+            let current = entry_id(&commit.tree()?, path);
+            if current.is_none() {
+                continue;
+            }
+            let previous = match commit.parent(0) {
+                Ok(parent) => entry_id(&parent.tree()?, path),
+                Err(_) => None,
+            };
+            if current != previous {
+                return Ok(Some(commit.id()));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn entry_id(tree: &Tree, path: &str) -> Option<Oid> {
+    tree.get_path(Path::new(path)).ok().map(|entry| entry.id())
+}
+
+fn rule_id_from_path(path: &Path) -> Option<String> {
+    let mut components = path.components();
+    if components.next()?.as_os_str() != RULES_DIR {
+        return None;
+    }
+    Some(components.next()?.as_os_str().to_string_lossy().to_string())
+}
+
+fn check_rule_id(rule_id: &str) -> Result<()> {
+    if rule_id.is_empty()
+        || rule_id.starts_with('.')
+        || rule_id.contains(['/', '\\'])
+    {
+        bail!("Rule id '{}' cannot be used as a directory name", rule_id);
+    }
+    Ok(())
+}
+
+fn write_rule(rules_dir: &Path, rule: &ExportedRule) -> Result<()> {
+    check_rule_id(&rule.metadata.rule_id)?;
+    let dir = rules_dir.join(&rule.metadata.rule_id);
+    fs::create_dir_all(&dir)?;
+
+    let mut definition = rule.definition.trim_end().to_string();
+    definition.push('\n');
+    fs::write(dir.join(DEFINITION_FILE), definition)?;
+    fs::write(dir.join(METADATA_FILE), serde_yaml::to_string(&rule.metadata)?)?;
+    if !rule.tests.is_empty() {
+        fs::write(dir.join(TESTS_FILE), serde_yaml::to_string(&rule.tests)?)?;
+    }
+    Ok(())
+}
+
+fn read_rule(dir: &Path) -> Result<ExportedRule> {
+    let definition = fs::read_to_string(dir.join(DEFINITION_FILE))
+        .with_context(|| format!("Missing {}", DEFINITION_FILE))?;
+    let metadata: RuleMetadata = serde_yaml::from_str(
+        &fs::read_to_string(dir.join(METADATA_FILE)).with_context(|| format!("Missing {}", METADATA_FILE))?,
+    )
+    .with_context(|| format!("Invalid {}", METADATA_FILE))?;
+    let tests_path = dir.join(TESTS_FILE);
+    let tests = if tests_path.exists() {
+        serde_yaml::from_str(&fs::read_to_string(tests_path)?).with_context(|| format!("Invalid {}", TESTS_FILE))?
+    } else {
+        Vec::new()
+    };
+
+    Ok(ExportedRule {
+        metadata,
+        definition: definition.trim_end().to_string(),
+        tests,
+    })
+}
+
+fn validate_rule(rule_id: &str, rule: &ExportedRule) -> Result<()> {
+    if rule.metadata.rule_id != rule_id {
+        bail!(
+            "metadata rule_id '{}' does not match directory '{}'",
+            rule.metadata.rule_id,
+            rule_id
+        );
+    }
+
+    // Dependencies are dictionary attributes, so only syntax and rule shape are checked here
+    let transpiler = DslTranspiler {
+        validation_enabled: true,
+        dependency_analysis: false,
+    };
+    transpiler.transpile_dsl_to_rules(&rule.definition).map_err(|errors| {
+        anyhow!(errors.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; "))
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_repository::RuleTestFixture;
+    use crate::rule_variants::Applicability;
+    use std::collections::BTreeMap;
+
+    fn temp_repo() -> (PathBuf, RuleRepository) {
+        let path = std::env::temp_dir().join(format!("rule-repo-{}", uuid::Uuid::new_v4()));
+        let repo = RuleRepository::open_or_init(&path).unwrap();
+        (path, repo)
+    }
+
+    fn sample_rule(rule_id: &str, definition: &str) -> ExportedRule {
+        ExportedRule {
+            metadata: RuleMetadata {
+                rule_id: rule_id.to_string(),
+                rule_name: format!("{} rule", rule_id),
+                description: None,
+                category_id: Some(3),
+                target_attribute_id: None,
+                status: "active".to_string(),
+                version: 1,
+                tags: vec!["kyc".to_string()],
+                valid_from: None,
+                valid_to: None,
+                applies_to: Applicability::default(),
+            },
+            definition: definition.to_string(),
+            tests: vec![RuleTestFixture {
+                name: "doubles".to_string(),
+                inputs: BTreeMap::from([("total".to_string(), serde_json::json!(5))]),
+                expected: serde_json::json!(10),
+            }],
+        }
+    }
+
+    fn author(name: &str) -> CommitAuthor {
+        CommitAuthor {
+            name: name.to_string(),
+            email: format!("{}@example.com", name),
+        }
+    }
+
+    #[test]
+    fn test_export_import_round_trip_with_attribution() {
+        let (path, repo) = temp_repo();
+        let rules = vec![sample_rule("fee_calc", "fee = total * 2"), sample_rule("risk", "score = 1 + 2")];
+
+        let first = repo.export_rules(&rules, &author("alice"), "Export rules").unwrap();
+        assert!(first.is_some());
+        // Re-exporting the same rules is a no-op
+        assert!(repo.export_rules(&rules, &author("alice"), "Export again").unwrap().is_none());
+
+        let mut edited = rules.clone();
+        edited[1].definition = "score = 2 + 2".to_string();
+        let second = repo.export_rules(&edited, &author("bob"), "Tweak risk").unwrap().unwrap();
+        assert_eq!(repo.changed_rule_ids(first.unwrap()).unwrap(), vec!["risk".to_string()]);
+
+        let report = repo.import_rules().unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        assert_eq!(report.rules.len(), 2);
+        assert_eq!(report.rules[0].rule, rules[0]);
+        assert_eq!(report.rules[0].last_author.as_ref().unwrap().name, "alice");
+        assert_eq!(report.rules[1].rule, edited[1]);
+        assert_eq!(report.rules[1].last_commit, Some(second));
+        assert_eq!(report.rules[1].last_author.as_ref().unwrap().name, "bob");
+
+        fs::remove_dir_all(path).ok();
+    }
+
+    #[test]
+    fn test_import_reports_invalid_rules() {
+        let (path, repo) = temp_repo();
+        repo.export_rules(&[sample_rule("broken", "fee = total * 2")], &author("alice"), "Export").unwrap();
+        fs::write(path.join("rules/broken/rule.dsl"), "fee = = 2\n").unwrap();
+
+        let report = repo.import_rules().unwrap();
+        assert!(!report.is_valid());
+        assert_eq!(report.errors[0].rule_id, "broken");
+
+        fs::remove_dir_all(path).ok();
+    }
+
+    #[test]
+    fn test_signed_bundle_round_trip() {
+        let (source_path, source) = temp_repo();
+        let (target_path, target) = temp_repo();
+        source.export_rules(&[sample_rule("fee_calc", "fee = total * 2")], &author("alice"), "Export").unwrap();
+
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[9; 32]);
+        let signed = source.export_bundle().unwrap().sign("release", &signing_key).unwrap();
+        let trusted = vec![TrustedKey {
+            name: "release".to_string(),
+            public_key: crate::rule_bundle::public_key_hex(&signing_key),
+        }];
+
+        assert!(target.import_bundle(&signed, &trusted, &author("deployer")).unwrap().is_some());
+        let report = target.import_rules().unwrap();
+        assert_eq!(report.rules.len(), 1);
+        assert_eq!(report.rules[0].last_author.as_ref().unwrap().name, "deployer");
+        assert!(target.import_bundle(&signed, &[], &author("deployer")).is_err());
+
+        fs::remove_dir_all(source_path).ok();
+        fs::remove_dir_all(target_path).ok();
+    }
+
+    #[test]
+    fn test_installed_bundle_keeps_existing_rules() {
+        let (path, repo) = temp_repo();
+        repo.export_rules(
+            &[sample_rule("fee_calc", "fee = total * 2"), sample_rule("kyc_risk", "risk = 1")],
+            &author("alice"),
+            "Export",
+        )
+        .unwrap();
+
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[9; 32]);
+        let pack = RuleBundle::new(vec![sample_rule("kyc_risk", "risk = 2"), sample_rule("pep_check", "pep = 0")]);
+        let signed = pack.sign("shared", &signing_key).unwrap();
+        let trusted = vec![TrustedKey {
+            name: "shared".to_string(),
+            public_key: crate::rule_bundle::public_key_hex(&signing_key),
+        }];
+
+        assert!(repo.install_bundle(&signed, &trusted, &author("deployer")).unwrap().is_some());
+        let report = repo.import_rules().unwrap();
+        let rules: Vec<(&str, &str)> = report
+            .rules
+            .iter()
+            .map(|imported| (imported.rule.metadata.rule_id.as_str(), imported.rule.definition.trim()))
+            .collect();
+        assert_eq!(rules, vec![("fee_calc", "fee = total * 2"), ("kyc_risk", "risk = 2"), ("pep_check", "pep = 0")]);
+        // Installing the same pack again changes nothing
+        assert!(repo.install_bundle(&signed, &trusted, &author("deployer")).unwrap().is_none());
+
+        fs::remove_dir_all(path).ok();
+    }
+
+    #[test]
+    fn test_rejects_unsafe_rule_ids() {
+        let (path, repo) = temp_repo();
+        let result = repo.export_rules(&[sample_rule("../escape", "x = 1")], &author("alice"), "Export");
+        assert!(result.is_err());
+        fs::remove_dir_all(path).ok();
+    }
+}
@@ -0,0 +1,465 @@
+//! Search and replace across stored rule definitions
+//!
+//! A rewrite either replaces text literally or, in AST mode, renames a
+//! symbol: attributes, function calls and `LOOKUP` table names that equal the
+//! pattern, while ignoring comments, other string literals and lambda
+//! parameters that shadow it. AST-mode output comes back in canonical format
+//! with its comments kept. Every rewritten rule is parsed and checked against
+//! its category's policy again, so a rename into a function the category
+//! doesn't allow shows up in the preview instead of after the change.
+
+use crate::formatter::format_rewritten_rule;
+use crate::models::{Expression, Value};
+use crate::parser::{parse_expression, parse_rule, parse_rules_recovering};
+use crate::rule_categories::CategoryTree;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RewriteMode {
+    #[default]
+    Text,
+    Ast,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleRewrite {
+    pub pattern: String,
+    pub replacement: String,
+    #[serde(default)]
+    pub mode: RewriteMode,
+}
+
+impl RuleRewrite {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.pattern.is_empty() {
+            return Err("Search pattern is required".to_string());
+        }
+        if self.pattern == self.replacement {
+            return Err("Replacement is the same as the pattern".to_string());
+        }
+        if self.mode == RewriteMode::Ast && !(is_name(&self.pattern) && is_name(&self.replacement)) {
+            return Err("AST rewrites rename an attribute, function or lookup table; pattern and replacement must both be names".to_string());
+        }
+        Ok(())
+    }
+
+    /// The rewritten definition, or `None` if the rule doesn't use the pattern
+    pub fn rewrite(&self, definition: &str) -> Result<Option<String>, String> {
+        match self.mode {
+            RewriteMode::Text => Ok(definition
+                .contains(&self.pattern)
+                .then(|| definition.replace(&self.pattern, &self.replacement))),
+            RewriteMode::Ast => {
+                let ast = parse_complete(definition)?;
+                if self.rename(ast.clone()) == ast {
+                    return Ok(None);
+                }
+                format_rewritten_rule(definition, |expr| self.rename(expr))
+                    .map(Some)
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    fn rename(&self, mut expr: Expression) -> Expression {
+        rename_symbol(&mut expr, &self.pattern, &self.replacement);
+        expr
+    }
+}
+
+fn is_name(text: &str) -> bool {
+    text.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+pub(crate) fn parse_complete(definition: &str) -> Result<Expression, String> {
+    let (remaining, ast) = parse_rule(definition).map_err(|e| format!("Failed to parse rule: {}", e))?;
+    if !remaining.trim().is_empty() {
+        return Err(format!("Unexpected input after rule: {}", remaining.trim()));
+    }
+    Ok(ast)
+}
+
+fn rename_symbol(expr: &mut Expression, from: &str, to: &str) {
+    match expr {
+        Expression::Identifier(name) | Expression::Variable(name) => {
+            if name == from {
+                *name = to.to_string();
+            }
+        }
+        Expression::FunctionCall { name, args } => {
+            if name.eq_ignore_ascii_case("LOOKUP") {
+                if let Some(Expression::Literal(Value::String(table))) = args.get_mut(1) {
+                    if table == from {
+                        *table = to.to_string();
+                    }
+                }
+            }
+            if name.eq_ignore_ascii_case(from) {
+                *name = to.to_string();
+            }
+            args.iter_mut().for_each(|arg| rename_symbol(arg, from, to));
+        }
+        Expression::Assignment { target, value } => {
+            if target == from {
+                *target = to.to_string();
+            }
+            rename_symbol(value, from, to);
+        }
+        // A lambda parameter with the same name hides the symbol inside the body
+        Expression::Lambda { param, body } => {
+            if param != from {
+                rename_symbol(body, from, to);
+            }
+        }
+        // So does a comprehension's loop variable, though not in its source
+        Expression::Comprehension { element, var, source, condition } => {
+            rename_symbol(source, from, to);
+            if var != from {
+                rename_symbol(element, from, to);
+                if let Some(condition) = condition {
+                    rename_symbol(condition, from, to);
+                }
+            }
+        }
+        Expression::BinaryOp { left, right, .. }
+        | Expression::Range { start: left, end: right, .. } => {
+            rename_symbol(left, from, to);
+            rename_symbol(right, from, to);
+        }
+        Expression::UnaryOp { operand, .. } => rename_symbol(operand, from, to),
+        Expression::Cast { expr, .. } => rename_symbol(expr, from, to),
+        Expression::Conditional { condition, then_expr, else_expr } => {
+            rename_symbol(condition, from, to);
+            rename_symbol(then_expr, from, to);
+            if let Some(else_expr) = else_expr {
+                rename_symbol(else_expr, from, to);
+            }
+        }
+        Expression::Case { branches, else_expr } => {
+            for (condition, result) in branches {
+                rename_symbol(condition, from, to);
+                rename_symbol(result, from, to);
+            }
+            if let Some(else_expr) = else_expr {
+                rename_symbol(else_expr, from, to);
+            }
+        }
+        Expression::List(items)
+        | Expression::ConfigureSystem { arguments: items, .. }
+        | Expression::Activate { arguments: items, .. }
+        | Expression::RunHealthCheck { arguments: items, .. }
+        | Expression::Workflow { steps: items, .. } => {
+            items.iter_mut().for_each(|item| rename_symbol(item, from, to));
+        }
+        Expression::Literal(_) | Expression::SetStatus { .. } => {}
+    }
+}
+
+/// Replace the text at `range` (byte offsets) with `text`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceEdit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+/// The edits renaming the symbol `from` to `to` throughout a document of
+/// rules, for an editor's rename. Each reference is replaced where it stands,
+/// keeping the layout and comments; a rule where that wouldn't match the AST
+/// rename, say because a lambda parameter shadows the name, is replaced whole
+/// in canonical format instead. Rules that don't parse are left alone.
+pub fn rename_edits(source: &str, from: &str, to: &str) -> Vec<SourceEdit> {
+    let (rules, _) = parse_rules_recovering(source);
+    let mut edits = Vec::new();
+    for rule in rules {
+        let mut renamed = rule.expression.clone();
+        rename_symbol(&mut renamed, from, to);
+        if renamed == rule.expression {
+            continue;
+        }
+
+        let text = &source[rule.start..rule.end];
+        let references = symbol_references(text, from);
+        let mut edited = text.to_string();
+        for range in references.iter().rev() {
+            edited.replace_range(range.clone(), to);
+        }
+        let in_place = matches!(parse_expression(&edited), Ok((rest, expr)) if rest.trim().is_empty() && expr == renamed);
+        if in_place {
+            edits.extend(references.into_iter().map(|range| SourceEdit {
+                range: rule.start + range.start..rule.start + range.end,
+                text: to.to_string(),
+            }));
+        } else if let Ok(formatted) = format_rewritten_rule(text, |mut expr| {
+            rename_symbol(&mut expr, from, to);
+            expr
+        }) {
+            edits.push(SourceEdit { range: rule.start..rule.end, text: formatted });
+        }
+    }
+    edits
+}
+
+// Where `name` stands as a whole identifier, outside strings and comments
+fn symbol_references(text: &str, name: &str) -> Vec<Range<usize>> {
+    let bytes = text.as_bytes();
+    let mut references = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &text[i..];
+        if rest.starts_with('#') || rest.starts_with("//") {
+            i += rest.find('\n').unwrap_or(rest.len());
+        } else if rest.starts_with("/*") {
+            i += rest.find("*/").map_or(rest.len(), |end| end + 2);
+        } else if bytes[i] == b'"' || bytes[i] == b'\'' {
+            let quote = bytes[i];
+            i += 1;
+            while i < bytes.len() && bytes[i] != quote {
+                i += if bytes[i] == b'\\' { 2 } else { 1 };
+            }
+            i += 1;
+        } else if bytes[i].is_ascii_alphabetic() || bytes[i] == b'_' {
+            let start = i;
+            while i < bytes.len()
+                && (bytes[i].is_ascii_alphanumeric()
+                    || bytes[i] == b'_'
+                    || (bytes[i] == b'.' && bytes.get(i + 1) != Some(&b'.')))
+            {
+                i += 1;
+            }
+            if &text[start..i] == name {
+                references.push(start..i);
+            }
+        } else {
+            i += text[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    references
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", content = "line", rename_all = "snake_case")]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// Line diff of two definitions, from their longest common subsequence
+pub fn line_diff(before: &str, after: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+
+    // common[i][j]: length of the LCS of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            diff.push(DiffLine::Same(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            diff.push(DiffLine::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    diff
+}
+
+/// A stored rule definition a rewrite is planned against
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredRule {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub category_id: Option<i32>,
+    pub definition: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RewrittenRule {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub before: String,
+    pub after: String,
+    pub diff: Vec<DiffLine>,
+    /// Why the rewritten definition can't be saved; empty if it can
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkippedRule {
+    pub rule_id: String,
+    pub reason: String,
+}
+
+/// The rules a rewrite changes, with diffs; doubles as the preview
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RewritePlan {
+    pub rewrite: RuleRewrite,
+    pub rules: Vec<RewrittenRule>,
+    pub skipped: Vec<SkippedRule>,
+}
+
+impl RewritePlan {
+    pub fn new(rewrite: RuleRewrite, stored: &[StoredRule], categories: &CategoryTree) -> Self {
+        let mut rules = Vec::new();
+        let mut skipped = Vec::new();
+
+        for rule in stored {
+            let after = match rewrite.rewrite(&rule.definition) {
+                Ok(Some(after)) => after,
+                Ok(None) => continue,
+                Err(reason) => {
+                    skipped.push(SkippedRule { rule_id: rule.rule_id.clone(), reason });
+                    continue;
+                }
+            };
+            let errors = match parse_complete(&after) {
+                Ok(ast) => rule
+                    .category_id
+                    .map(|id| categories.check_rule(id, &ast).iter().map(|v| v.to_string()).collect())
+                    .unwrap_or_default(),
+                Err(e) => vec![e],
+            };
+            rules.push(RewrittenRule {
+                rule_id: rule.rule_id.clone(),
+                rule_name: rule.rule_name.clone(),
+                diff: line_diff(&rule.definition, &after),
+                before: rule.definition.clone(),
+                after,
+                errors,
+            });
+        }
+
+        Self { rewrite, rules, skipped }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.rules.iter().all(|rule| rule.errors.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_categories::{CategoryPolicy, RuleCategory};
+
+    fn rewrite(pattern: &str, replacement: &str, mode: RewriteMode) -> RuleRewrite {
+        RuleRewrite {
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+            mode,
+        }
+    }
+
+    fn stored(rule_id: &str, definition: &str) -> StoredRule {
+        StoredRule {
+            rule_id: rule_id.to_string(),
+            rule_name: rule_id.to_string(),
+            category_id: Some(1),
+            definition: definition.to_string(),
+        }
+    }
+
+    fn categories() -> CategoryTree {
+        CategoryTree::new(vec![RuleCategory {
+            id: 1,
+            category_key: "kyc".to_string(),
+            name: "KYC".to_string(),
+            parent_id: None,
+            policy: CategoryPolicy {
+                approval_required: None,
+                allowed_functions: Some(vec!["LOOKUP".to_string(), "UPPER".to_string()]),
+                ..Default::default()
+            },
+        }])
+    }
+
+    #[test]
+    fn test_ast_rename_only_touches_symbols() {
+        let rename = rewrite("country_codes", "iso_countries", RewriteMode::Ast);
+        let definition = "# uses country_codes\nLOOKUP(country, \"country_codes\") & \" country_codes\"";
+        assert_eq!(
+            rename.rewrite(definition).unwrap().as_deref(),
+            Some("# uses country_codes\nLOOKUP(country, \"iso_countries\") & \" country_codes\"")
+        );
+
+        let rename = rewrite("rate", "fx_rate", RewriteMode::Ast);
+        assert_eq!(
+            rename.rewrite("SUM(items, rate -> rate * 2) + rate").unwrap().as_deref(),
+            Some("SUM(items, rate -> rate * 2) + fx_rate")
+        );
+        assert_eq!(rename.rewrite("amount * 2 # rate applied later").unwrap(), None);
+
+        assert!(rewrite("a b", "c", RewriteMode::Ast).validate().is_err());
+        assert!(rewrite("", "c", RewriteMode::Text).validate().is_err());
+    }
+
+
+    #[test]
+    fn test_rename_edits_keep_layout_unless_shadowed() {
+        let source = "risk = score * 2 # score from bureau\n\nlabel = IF risk > 5 THEN \"risk\" ELSE label_default\n\nscores = MAP(items, risk -> risk + 1) + risk\n";
+        let edits = rename_edits(source, "risk", "risk_score");
+
+        let mut renamed = source.to_string();
+        for edit in edits.iter().rev() {
+            renamed.replace_range(edit.range.clone(), &edit.text);
+        }
+        assert_eq!(
+            renamed,
+            "risk_score = score * 2 # score from bureau\n\nlabel = IF risk_score > 5 THEN \"risk\" ELSE label_default\n\nscores = MAP(items, risk -> risk + 1) + risk_score\n"
+        );
+        assert_eq!(edits.last().unwrap().text, "scores = MAP(items, risk -> risk + 1) + risk_score");
+        assert!(rename_edits(source, "missing", "other").is_empty());
+    }
+    #[test]
+    fn test_plan_revalidates_rewritten_rules() {
+        let rules = vec![
+            stored("r1", "UPPER(name)"),
+            stored("r2", "LOOKUP(code, \"countries\")"),
+            stored("r3", "amount > 10"),
+            stored("r4", "LOOKUP(code,"),
+        ];
+
+        let plan = RewritePlan::new(rewrite("UPPER", "LOWER", RewriteMode::Ast), &rules, &categories());
+        assert_eq!(plan.rules.len(), 1);
+        assert_eq!(plan.rules[0].after, "LOWER(name)");
+        assert_eq!(plan.rules[0].errors.len(), 1, "LOWER is not allowed in the category");
+        assert!(!plan.is_valid());
+        assert_eq!(plan.skipped.len(), 1);
+        assert_eq!(plan.skipped[0].rule_id, "r4");
+
+        let plan = RewritePlan::new(rewrite("code", "code, 1", RewriteMode::Text), &rules, &categories());
+        let rewritten: Vec<_> = plan.rules.iter().map(|r| (r.rule_id.as_str(), r.errors.is_empty())).collect();
+        assert_eq!(rewritten, vec![("r2", true), ("r4", false)]);
+    }
+
+    #[test]
+    fn test_line_diff() {
+        assert_eq!(
+            line_diff("# header\na + b\nc", "# header\na + d\nc"),
+            vec![
+                DiffLine::Same("# header".to_string()),
+                DiffLine::Removed("a + b".to_string()),
+                DiffLine::Added("a + d".to_string()),
+                DiffLine::Same("c".to_string()),
+            ]
+        );
+    }
+}
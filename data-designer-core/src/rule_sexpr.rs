@@ -0,0 +1,602 @@
+//! S-expression syntax for rules
+//!
+//! The same rules can be written in the infix DSL or as S-expressions, for
+//! teams that prefer the Lisp form; both read into the one `Expression` AST.
+//! Infix stays the stored form: `to_sexpr_document` shows a document as
+//! S-expressions and `from_sexpr_document` turns one back into canonical infix.
+//!
+//! ```text
+//! fee = IF aum > 1_000_000 THEN aum * 0.5% ELSE 100 USD
+//! (define fee (if (> aum 1000000) (* aum (percent 0.5)) (money 100 USD)))
+//! ```
+//!
+//! Operators keep their infix spelling at the head of a form (`NOT_IN` for
+//! `NOT IN`), and `AND`, `OR`, `+`, `*` and `&` take any number of operands.
+//! Function calls are `(NAME args...)`, or `(call NAME args...)` for a name
+//! that is also a special form. Comments become `;` lines ahead of their rule
+//! and come back as `#` comments.
+
+use crate::formatter::{format_document, format_expression, literal, operator, quote};
+use crate::models::{BinaryOperator, CommentPlacement, CommentStyle, Expression, RuleComment, UnaryOperator, Value};
+use crate::parser::{parse_comments, parse_rule_with_comments};
+
+const BINARY_OPERATORS: [BinaryOperator; 23] = [
+    BinaryOperator::Add,
+    BinaryOperator::Subtract,
+    BinaryOperator::Multiply,
+    BinaryOperator::Divide,
+    BinaryOperator::Power,
+    BinaryOperator::Modulo,
+    BinaryOperator::Equals,
+    BinaryOperator::NotEquals,
+    BinaryOperator::LessThan,
+    BinaryOperator::LessThanOrEqual,
+    BinaryOperator::GreaterThan,
+    BinaryOperator::GreaterThanOrEqual,
+    BinaryOperator::And,
+    BinaryOperator::Or,
+    BinaryOperator::Matches,
+    BinaryOperator::NotMatches,
+    BinaryOperator::Concat,
+    BinaryOperator::Contains,
+    BinaryOperator::StartsWith,
+    BinaryOperator::EndsWith,
+    BinaryOperator::In,
+    BinaryOperator::NotIn,
+    BinaryOperator::Between,
+];
+
+// Operators written once for a whole chain: (AND a b c) is a AND b AND c
+const VARIADIC: [BinaryOperator; 5] =
+    [BinaryOperator::And, BinaryOperator::Or, BinaryOperator::Add, BinaryOperator::Multiply, BinaryOperator::Concat];
+
+const SPECIAL_FORMS: [&str; 14] =
+    ["define", "if", "cond", "else", "list", "fn", "for", "cast", "call", "regex", "percent", "money", "..", "..="];
+
+// Forms longer than this are broken over several lines
+const LINE_WIDTH: usize = 72;
+
+#[derive(Debug, Clone, PartialEq)]
+enum SExpr {
+    Atom(String),
+    Str(String),
+    List(Vec<SExpr>),
+}
+
+fn atom(text: &str) -> SExpr {
+    SExpr::Atom(text.to_string())
+}
+
+fn form(head: &str, rest: impl IntoIterator<Item = SExpr>) -> SExpr {
+    SExpr::List(std::iter::once(atom(head)).chain(rest).collect())
+}
+
+fn head(op: BinaryOperator) -> &'static str {
+    match op {
+        BinaryOperator::NotIn => "NOT_IN",
+        op => operator(op),
+    }
+}
+
+/// One rule as an S-expression
+pub fn to_sexpr(expr: &Expression) -> Result<String, String> {
+    Ok(render(&write(expr)?, 0))
+}
+
+/// A rule written as an S-expression
+pub fn from_sexpr(input: &str) -> Result<Expression, String> {
+    let forms = read_document(input)?;
+    let mut sexprs = forms.iter().filter_map(|form| form.sexpr.as_ref());
+    match (sexprs.next(), sexprs.count()) {
+        (Some(sexpr), 0) => read(sexpr),
+        (first, rest) => Err(format!("Expected one rule, found {}", first.iter().count() + rest)),
+    }
+}
+
+/// A document of infix rules as S-expressions, one form per rule
+pub fn to_sexpr_document(source: &str) -> Result<String, String> {
+    let mut output = String::new();
+    let mut rest = source;
+    loop {
+        if let Ok(("", comments)) = parse_comments(rest) {
+            push_comments(&mut output, comments.iter());
+            break;
+        }
+        let line = source[..source.len() - rest.len()].lines().count().max(1);
+        let (remaining, rule) = parse_rule_with_comments(rest)
+            .map_err(|_| format!("Cannot parse rule at line {}: '{}'", line, rest.trim_start().lines().next().unwrap_or("")))?;
+        if !output.is_empty() {
+            output.push('\n');
+        }
+
+        // A trailing comment on the rule's last line stays on the form's last line
+        let (trailing, before): (Vec<_>, Vec<_>) =
+            rule.comments.iter().partition(|comment| comment.placement == CommentPlacement::Trailing);
+        let same_line = trailing
+            .first()
+            .copied()
+            .filter(|comment| rest[..comment.offset].rsplit('\n').next().is_some_and(|text| !text.trim().is_empty()));
+        push_comments(&mut output, before.into_iter());
+        output.push_str(&to_sexpr(&rule.expression)?);
+        if let Some(comment) = same_line {
+            output.push_str(&format!(" ; {}", comment_text(comment)).replace('\n', " "));
+        }
+        output.push('\n');
+        push_comments(&mut output, trailing.into_iter().skip(usize::from(same_line.is_some())));
+        rest = remaining;
+    }
+    Ok(output)
+}
+
+/// A document of S-expression rules as canonical infix
+pub fn from_sexpr_document(source: &str) -> Result<String, String> {
+    let mut infix = String::new();
+    for form in read_document(source)? {
+        if form.blank_line_before && !infix.is_empty() {
+            infix.push('\n');
+        }
+        for comment in form.comments {
+            infix.push_str(format!("# {}", comment).trim_end());
+            infix.push('\n');
+        }
+        if let Some(sexpr) = form.sexpr {
+            infix.push_str(&format_expression(&read(&sexpr)?));
+            if let Some(comment) = form.trailing {
+                infix.push_str(&format!(" # {}", comment));
+            }
+            infix.push('\n');
+        }
+    }
+    format_document(&infix).map_err(|e| e.to_string())
+}
+
+fn comment_text(comment: &RuleComment) -> &str {
+    match comment.style {
+        CommentStyle::Block => comment.text.trim(),
+        CommentStyle::Hash | CommentStyle::DoubleSlash => comment.text.as_str(),
+    }
+}
+
+fn push_comments<'c>(output: &mut String, comments: impl Iterator<Item = &'c RuleComment>) {
+    for comment in comments {
+        for line in comment_text(comment).lines() {
+            output.push_str(format!("; {}", line.trim_end()).trim_end());
+            output.push('\n');
+        }
+    }
+}
+
+fn write(expr: &Expression) -> Result<SExpr, String> {
+    let all = |items: &[Expression]| items.iter().map(write).collect::<Result<Vec<_>, _>>();
+    Ok(match expr {
+        Expression::Literal(value) => write_value(value)?,
+        Expression::Identifier(name) | Expression::Variable(name) => atom(name),
+        Expression::BinaryOp { left, op, right } => {
+            let mut operands = vec![right.as_ref()];
+            let mut first = left.as_ref();
+            if VARIADIC.contains(op) {
+                while let Expression::BinaryOp { left, op: inner, right } = first {
+                    if inner != op {
+                        break;
+                    }
+                    operands.push(right);
+                    first = left;
+                }
+            }
+            operands.push(first);
+            form(head(*op), operands.into_iter().rev().map(write).collect::<Result<Vec<_>, _>>()?)
+        }
+        Expression::UnaryOp { op, operand } => {
+            let op = match op {
+                UnaryOperator::Not => "NOT",
+                UnaryOperator::Minus => "-",
+                UnaryOperator::Plus => "+",
+            };
+            form(op, [write(operand)?])
+        }
+        Expression::FunctionCall { name, args } => {
+            let reserved = SPECIAL_FORMS.contains(&name.as_str())
+                || name == "NOT"
+                || BINARY_OPERATORS.iter().any(|op| head(*op) == name);
+            if reserved {
+                form("call", std::iter::once(atom(name)).chain(all(args)?))
+            } else {
+                form(name, all(args)?)
+            }
+        }
+        Expression::Conditional { condition, then_expr, else_expr } => {
+            let mut parts = vec![write(condition)?, write(then_expr)?];
+            if let Some(else_expr) = else_expr {
+                parts.push(write(else_expr)?);
+            }
+            form("if", parts)
+        }
+        Expression::Case { branches, else_expr } => {
+            let mut clauses = Vec::new();
+            for (condition, result) in branches {
+                clauses.push(SExpr::List(vec![write(condition)?, write(result)?]));
+            }
+            if let Some(else_expr) = else_expr {
+                clauses.push(form("else", [write(else_expr)?]));
+            }
+            form("cond", clauses)
+        }
+        Expression::Assignment { target, value } => form("define", [atom(target), write(value)?]),
+        Expression::List(items) => form("list", all(items)?),
+        Expression::Cast { expr, data_type } => form("cast", [write(expr)?, atom(data_type)]),
+        Expression::Lambda { param, body } => form("fn", [atom(param), write(body)?]),
+        Expression::Comprehension { element, var, source, condition } => {
+            let mut parts = vec![atom(var), write(source)?, write(element)?];
+            if let Some(condition) = condition {
+                parts.push(write(condition)?);
+            }
+            form("for", parts)
+        }
+        Expression::Range { start, end, inclusive } => {
+            form(if *inclusive { "..=" } else { ".." }, [write(start)?, write(end)?])
+        }
+        Expression::ConfigureSystem { .. }
+        | Expression::Activate { .. }
+        | Expression::RunHealthCheck { .. }
+        | Expression::SetStatus { .. }
+        | Expression::Workflow { .. } => return Err("Workflow steps have no S-expression form".to_string()),
+    })
+}
+
+fn write_value(value: &Value) -> Result<SExpr, String> {
+    Ok(match value {
+        Value::String(s) => SExpr::Str(s.clone()),
+        Value::Regex(pattern) => form("regex", [SExpr::Str(pattern.clone())]),
+        Value::Percent(p) => form("percent", [atom(&p.to_string())]),
+        Value::Money { amount, currency } => form("money", [atom(&amount.to_string()), atom(currency)]),
+        Value::List(values) => form("list", values.iter().map(write_value).collect::<Result<Vec<_>, _>>()?),
+        Value::Object(_) => return Err("Records have no rule syntax".to_string()),
+        Value::Integer(_) | Value::Number(_) | Value::Float(_) | Value::Boolean(_) | Value::Null => atom(&literal(value)),
+    })
+}
+
+fn render(sexpr: &SExpr, indent: usize) -> String {
+    let items = match sexpr {
+        SExpr::Atom(text) => return text.clone(),
+        SExpr::Str(text) => return quote(text),
+        SExpr::List(items) => items,
+    };
+    let flat: Vec<String> = items.iter().map(|item| render(item, 0)).collect();
+    let one_line = format!("({})", flat.join(" "));
+    if indent + one_line.len() <= LINE_WIDTH && !one_line.contains('\n') {
+        return one_line;
+    }
+
+    // The head and, for define/fn/for, the name it binds stay on the first line
+    let inline = match items.first() {
+        Some(SExpr::Atom(head)) if matches!(head.as_str(), "define" | "fn" | "for") => 2,
+        _ => 1,
+    };
+    let mut text = format!("({}", flat[..inline.min(items.len())].join(" "));
+    for item in items.iter().skip(inline) {
+        text.push('\n');
+        text.push_str(&" ".repeat(indent + 2));
+        text.push_str(&render(item, indent + 2));
+    }
+    text.push(')');
+    text
+}
+
+// A form with the comments on the lines before it and one after it on its
+// last line; comments after the last form come without a form
+struct Form {
+    blank_line_before: bool,
+    comments: Vec<String>,
+    sexpr: Option<SExpr>,
+    trailing: Option<String>,
+}
+
+fn read_document(source: &str) -> Result<Vec<Form>, String> {
+    let mut reader = Reader { source, offset: 0 };
+    let mut forms = Vec::new();
+    loop {
+        let rest = reader.rest();
+        let blank_line_before = rest[..rest.len() - rest.trim_start().len()].matches('\n').count() > 1;
+        let comments = reader.skip_trivia();
+        if reader.at_end() {
+            if !comments.is_empty() {
+                forms.push(Form { blank_line_before, comments, sexpr: None, trailing: None });
+            }
+            return Ok(forms);
+        }
+        let sexpr = Some(reader.read()?);
+        let trailing = reader.same_line_comment();
+        forms.push(Form { blank_line_before, comments, sexpr, trailing });
+    }
+}
+
+struct Reader<'a> {
+    source: &'a str,
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn rest(&self) -> &'a str {
+        &self.source[self.offset..]
+    }
+
+    fn at_end(&self) -> bool {
+        self.offset == self.source.len()
+    }
+
+    fn line(&self) -> usize {
+        self.source[..self.offset].matches('\n').count() + 1
+    }
+
+    // A `;` comment after the last form on its line, if there is one
+    fn same_line_comment(&mut self) -> Option<String> {
+        let gap = self.rest().len() - self.rest().trim_start_matches([' ', '\t']).len();
+        let comment = self.rest()[gap..].strip_prefix(';')?.split('\n').next().unwrap_or_default();
+        self.offset += gap + 1 + comment.len();
+        Some(comment.trim_start_matches(';').trim().to_string())
+    }
+
+    // Skips whitespace and `;` comments, returning the comments' text
+    fn skip_trivia(&mut self) -> Vec<String> {
+        let mut comments = Vec::new();
+        loop {
+            let trimmed = self.rest().trim_start();
+            self.offset = self.source.len() - trimmed.len();
+            let Some(comment) = trimmed.strip_prefix(';') else {
+                return comments;
+            };
+            let line = comment.split('\n').next().unwrap_or_default();
+            comments.push(line.trim_start_matches(';').trim().to_string());
+            self.offset += 1 + line.len();
+        }
+    }
+
+    fn read(&mut self) -> Result<SExpr, String> {
+        self.skip_trivia();
+        let mut chars = self.rest().chars();
+        match chars.next() {
+            None => Err("Unexpected end of input; a ')' is missing".to_string()),
+            Some(')') => Err(format!("Unexpected ')' at line {}", self.line())),
+            Some('(') => {
+                self.offset += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_trivia();
+                    if self.rest().starts_with(')') {
+                        self.offset += 1;
+                        return Ok(SExpr::List(items));
+                    }
+                    items.push(self.read()?);
+                }
+            }
+            Some('"') => {
+                let mut text = String::new();
+                let mut escaped = false;
+                for (i, c) in self.rest().char_indices().skip(1) {
+                    if escaped {
+                        text.push(match c {
+                            'n' => '\n',
+                            't' => '\t',
+                            'r' => '\r',
+                            c => c,
+                        });
+                        escaped = false;
+                    } else if c == '\\' {
+                        escaped = true;
+                    } else if c == '"' {
+                        self.offset += i + 1;
+                        return Ok(SExpr::Str(text));
+                    } else {
+                        text.push(c);
+                    }
+                }
+                Err(format!("Unterminated string at line {}", self.line()))
+            }
+            Some(_) => {
+                let rest = self.rest();
+                let end = rest.find(|c: char| c.is_whitespace() || matches!(c, '(' | ')' | '"' | ';')).unwrap_or(rest.len());
+                let text = rest[..end].to_string();
+                self.offset += end;
+                Ok(SExpr::Atom(text))
+            }
+        }
+    }
+}
+
+fn read(sexpr: &SExpr) -> Result<Expression, String> {
+    match sexpr {
+        SExpr::Str(text) => Ok(Expression::Literal(Value::String(text.clone()))),
+        SExpr::Atom(text) => Ok(read_atom(text)),
+        SExpr::List(items) => {
+            let Some((SExpr::Atom(head), args)) = items.split_first() else {
+                return Err(match items.first() {
+                    None => "Empty form '()'".to_string(),
+                    Some(_) => "A form must start with an operator or function name".to_string(),
+                });
+            };
+            read_form(head, args)
+        }
+    }
+}
+
+fn read_atom(text: &str) -> Expression {
+    let value = match text {
+        "true" => Value::Boolean(true),
+        "false" => Value::Boolean(false),
+        "null" => Value::Null,
+        _ => match (text.parse::<i64>(), text.parse::<f64>()) {
+            (Ok(i), _) => Value::Integer(i),
+            (_, Ok(f)) if text.starts_with(|c: char| c.is_ascii_digit() || c == '-') => Value::Float(f),
+            _ => return Expression::Identifier(text.to_string()),
+        },
+    };
+    Expression::Literal(value)
+}
+
+fn read_form(head: &str, args: &[SExpr]) -> Result<Expression, String> {
+    let arity = |expected: &str, ok: bool| {
+        if ok {
+            Ok(())
+        } else {
+            Err(format!("({} ...) takes {}, found {}", head, expected, args.len()))
+        }
+    };
+    let name = |sexpr: &SExpr| match sexpr {
+        SExpr::Atom(name) => Ok(name.clone()),
+        _ => Err(format!("({} ...) expects a name", head)),
+    };
+    let boxed = |sexpr: &SExpr| read(sexpr).map(Box::new);
+    let all = |items: &[SExpr]| items.iter().map(read).collect::<Result<Vec<_>, _>>();
+
+    match head {
+        "define" => {
+            arity("a name and a value", args.len() == 2)?;
+            Ok(Expression::Assignment { target: name(&args[0])?, value: boxed(&args[1])? })
+        }
+        "if" => {
+            arity("a condition, a result and optionally another", matches!(args.len(), 2 | 3))?;
+            Ok(Expression::Conditional {
+                condition: boxed(&args[0])?,
+                then_expr: boxed(&args[1])?,
+                else_expr: args.get(2).map(boxed).transpose()?,
+            })
+        }
+        "cond" => {
+            let mut branches = Vec::new();
+            let mut else_expr = None;
+            for (i, clause) in args.iter().enumerate() {
+                match clause {
+                    SExpr::List(parts) if matches!(parts.first(), Some(SExpr::Atom(word)) if word == "else") => {
+                        if parts.len() != 2 || i + 1 != args.len() {
+                            return Err("(else result) must be the last clause of a cond".to_string());
+                        }
+                        else_expr = Some(boxed(&parts[1])?);
+                    }
+                    SExpr::List(parts) if parts.len() == 2 => branches.push((read(&parts[0])?, read(&parts[1])?)),
+                    _ => return Err("cond clauses are (condition result)".to_string()),
+                }
+            }
+            arity("at least one clause", !branches.is_empty())?;
+            Ok(Expression::Case { branches, else_expr })
+        }
+        "list" => Ok(Expression::List(all(args)?)),
+        "fn" => {
+            arity("a parameter and a body", args.len() == 2)?;
+            Ok(Expression::Lambda { param: name(&args[0])?, body: boxed(&args[1])? })
+        }
+        "for" => {
+            arity("a variable, a source, an element and optionally a condition", matches!(args.len(), 3 | 4))?;
+            Ok(Expression::Comprehension {
+                var: name(&args[0])?,
+                source: boxed(&args[1])?,
+                element: boxed(&args[2])?,
+                condition: args.get(3).map(boxed).transpose()?,
+            })
+        }
+        "cast" => {
+            arity("a value and a type", args.len() == 2)?;
+            Ok(Expression::Cast { expr: boxed(&args[0])?, data_type: name(&args[1])? })
+        }
+        "call" => {
+            let (function, args) = args.split_first().ok_or("(call ...) needs a function name")?;
+            Ok(Expression::FunctionCall { name: name(function)?, args: all(args)? })
+        }
+        "regex" => match args {
+            [SExpr::Str(pattern)] => Ok(Expression::Literal(Value::Regex(pattern.clone()))),
+            _ => Err("(regex ...) takes one string".to_string()),
+        },
+        "percent" => match args {
+            [SExpr::Atom(amount)] => {
+                let amount = amount.parse().map_err(|_| format!("'{}' is not a number", amount))?;
+                Ok(Expression::Literal(Value::Percent(amount)))
+            }
+            _ => Err("(percent ...) takes one number".to_string()),
+        },
+        "money" => match args {
+            [SExpr::Atom(amount), SExpr::Atom(currency)] => {
+                let amount = amount.parse().map_err(|_| format!("'{}' is not a number", amount))?;
+                Ok(Expression::Literal(Value::Money { amount, currency: currency.clone() }))
+            }
+            _ => Err("(money ...) takes an amount and a currency code".to_string()),
+        },
+        ".." | "..=" => {
+            arity("a start and an end", args.len() == 2)?;
+            Ok(Expression::Range { start: boxed(&args[0])?, end: boxed(&args[1])?, inclusive: head == "..=" })
+        }
+        "else" => Err("(else ...) only appears inside a cond".to_string()),
+        "NOT" => {
+            arity("one operand", args.len() == 1)?;
+            Ok(Expression::UnaryOp { op: UnaryOperator::Not, operand: boxed(&args[0])? })
+        }
+        "-" | "+" if args.len() == 1 => {
+            let op = if head == "-" { UnaryOperator::Minus } else { UnaryOperator::Plus };
+            Ok(Expression::UnaryOp { op, operand: boxed(&args[0])? })
+        }
+        _ => match BINARY_OPERATORS.iter().find(|op| self::head(**op) == head) {
+            Some(op) => {
+                let variadic = VARIADIC.contains(op);
+                arity(if variadic { "two or more operands" } else { "two operands" }, args.len() == 2 || (variadic && args.len() > 2))?;
+                let mut operands = all(args)?.into_iter();
+                let first = operands.next().expect("at least two operands");
+                Ok(operands.fold(first, |left, right| Expression::BinaryOp {
+                    left: Box::new(left),
+                    op: *op,
+                    right: Box::new(right),
+                }))
+            }
+            None => Ok(Expression::FunctionCall { name: head.to_string(), args: all(args)? }),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_rule;
+
+    #[test]
+    fn test_rules_read_the_same_in_either_syntax() {
+        let rules = [
+            "fee = IF aum > 1_000_000 THEN aum * 0.5% ELSE 100 USD",
+            "eligible = age >= 18 AND country IN [\"GB\", \"US\"] AND NOT blocked AND score BETWEEN 1 AND 10",
+            "band = CASE WHEN x < 0 THEN \"neg\" WHEN x == 0 THEN \"zero\" ELSE \"pos\" END",
+            "total = SUM([t.amount FOR t IN transactions IF t.currency == \"USD\"]) - -rebate",
+            "big = FILTER(items, i -> i.value > 10)",
+            "label = \"Client \\\"\" & UPPER(name) & \"\\\"\\n\" & TO_STRING(a - b - c)",
+            "code = id MATCHES /^[A-Z]{2}$/ AND id NOT IN [\"XX\"] AND amount BETWEEN_EXCLUSIVE 1 AND 5",
+        ];
+        for rule in rules {
+            let (_, infix) = parse_rule(rule).unwrap();
+            let sexpr = to_sexpr(&infix).unwrap();
+            assert_eq!(from_sexpr(&sexpr).unwrap(), infix, "{}", sexpr);
+        }
+
+        let (_, fee) = parse_rule(rules[0]).unwrap();
+        assert_eq!(
+            to_sexpr(&fee).unwrap(),
+            "(define fee (if (> aum 1000000) (* aum (percent 0.5)) (money 100 USD)))"
+        );
+        let (_, eligible) = parse_rule(rules[1]).unwrap();
+        assert_eq!(
+            to_sexpr(&eligible).unwrap(),
+            "(define eligible\n  (AND\n    (>= age 18)\n    (IN country (list \"GB\" \"US\"))\n    (NOT blocked)\n    (BETWEEN score (..= 1 10))))"
+        );
+    }
+
+    #[test]
+    fn test_documents_convert_both_ways_with_their_comments() {
+        let infix = "# Fee rules\nfee = aum * 0.01 // basis\n\nnet = fee - rebate\n# end\n";
+        let sexpr = to_sexpr_document(infix).unwrap();
+        assert_eq!(sexpr, "; Fee rules\n(define fee (* aum 0.01)) ; basis\n\n(define net (- fee rebate))\n; end\n");
+        assert_eq!(from_sexpr_document(&sexpr).unwrap(), "# Fee rules\nfee = aum * 0.01 # basis\n\nnet = fee - rebate\n# end\n");
+
+        assert_eq!(from_sexpr("(define x (- 5))").unwrap(), parse_rule("x = -(5)").unwrap().1);
+        assert!(from_sexpr("(define x (+ 1 2)").unwrap_err().contains("')' is missing"));
+        assert!(from_sexpr("(< a b c)").unwrap_err().contains("two operands"));
+        assert!(from_sexpr("(x 1) (y 2)").is_err());
+        assert_eq!(
+            from_sexpr("(call if a)").unwrap(),
+            Expression::FunctionCall { name: "if".to_string(), args: vec![Expression::Identifier("a".to_string())] }
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Workspace-wide rule statistics for the dashboard panel
+//!
+//! Summarizes the stored rules (how many in each status and category, and
+//! how large their definitions are) and their version history (saves per
+//! week and who made them). Complexity is the number of nodes in a rule's
+//! AST; rules that don't parse are counted separately rather than averaged
+//! in. Rules in repair are listed oldest first with how long they have
+//! been broken, so the ones nobody has picked up stand out.
+
+use crate::models::Expression;
+use crate::parser::parse_rule;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// How many authors the dashboard lists
+pub const TOP_AUTHORS: usize = 5;
+
+/// A stored rule as the statistics see it
+#[derive(Debug, Clone)]
+pub struct StatsRule {
+    pub rule_id: String,
+    pub status: String,
+    pub category: Option<String>,
+    pub definition: String,
+    /// When the rule last moved to in_repair, if it is there now
+    pub in_repair_since: Option<DateTime<Utc>>,
+}
+
+/// One saved rule version
+#[derive(Debug, Clone)]
+pub struct StatsChange {
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeeklyChanges {
+    /// The Monday the week starts on
+    pub week_start: NaiveDate,
+    pub changes: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthorChanges {
+    pub author: String,
+    pub changes: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepairAging {
+    pub rule_id: String,
+    pub since: DateTime<Utc>,
+    pub days: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleStatistics {
+    pub total_rules: usize,
+    pub by_status: BTreeMap<String, usize>,
+    /// Rules without a category are counted under "uncategorized"
+    pub by_category: BTreeMap<String, usize>,
+    /// Oldest week first, including weeks without changes
+    pub changes_per_week: Vec<WeeklyChanges>,
+    /// Most changes first, over the same weeks
+    pub top_authors: Vec<AuthorChanges>,
+    /// Mean AST node count of the rules that parse
+    pub average_complexity: Option<f64>,
+    pub unparsed_rules: usize,
+    pub in_repair: Vec<RepairAging>,
+}
+
+/// Statistics for the dashboard, with change activity over the `weeks` weeks up to `now`
+pub fn rule_statistics(rules: &[StatsRule], changes: &[StatsChange], weeks: usize, now: DateTime<Utc>) -> RuleStatistics {
+    let mut by_status = BTreeMap::new();
+    let mut by_category = BTreeMap::new();
+    let mut sizes = Vec::new();
+    let mut in_repair = Vec::new();
+    for rule in rules {
+        *by_status.entry(rule.status.clone()).or_insert(0) += 1;
+        let category = rule.category.clone().unwrap_or_else(|| "uncategorized".to_string());
+        *by_category.entry(category).or_insert(0) += 1;
+        match parse_rule(&rule.definition) {
+            Ok((remaining, expr)) if remaining.trim().is_empty() => sizes.push(ast_size(&expr)),
+            _ => {}
+        }
+        if let (true, Some(since)) = (rule.status == "in_repair", rule.in_repair_since) {
+            in_repair.push(RepairAging { rule_id: rule.rule_id.clone(), since, days: (now - since).num_days() });
+        }
+    }
+    in_repair.sort_by(|a, b| a.since.cmp(&b.since).then_with(|| a.rule_id.cmp(&b.rule_id)));
+
+    let this_week = week_start(now.date_naive());
+    let first_week = this_week - Duration::weeks(weeks.saturating_sub(1) as i64);
+    let mut changes_per_week: Vec<WeeklyChanges> = (0..weeks)
+        .map(|week| WeeklyChanges { week_start: first_week + Duration::weeks(week as i64), changes: 0 })
+        .collect();
+    let mut authors: HashMap<&str, usize> = HashMap::new();
+    for change in changes {
+        let week = week_start(change.created_at.date_naive());
+        if week < first_week || week > this_week {
+            continue;
+        }
+        changes_per_week[((week - first_week).num_days() / 7) as usize].changes += 1;
+        *authors.entry(change.created_by.as_deref().unwrap_or("unknown")).or_insert(0) += 1;
+    }
+    let mut top_authors: Vec<AuthorChanges> = authors
+        .into_iter()
+        .map(|(author, changes)| AuthorChanges { author: author.to_string(), changes })
+        .collect();
+    top_authors.sort_by(|a, b| b.changes.cmp(&a.changes).then_with(|| a.author.cmp(&b.author)));
+    top_authors.truncate(TOP_AUTHORS);
+
+    RuleStatistics {
+        total_rules: rules.len(),
+        by_status,
+        by_category,
+        changes_per_week,
+        top_authors,
+        average_complexity: (!sizes.is_empty()).then(|| sizes.iter().sum::<usize>() as f64 / sizes.len() as f64),
+        unparsed_rules: rules.len() - sizes.len(),
+        in_repair,
+    }
+}
+
+/// Number of nodes in an expression
+pub fn ast_size(expr: &Expression) -> usize {
+    1 + expr.children().into_iter().map(ast_size).sum::<usize>()
+}
+
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn rule(rule_id: &str, status: &str, category: Option<&str>, definition: &str) -> StatsRule {
+        StatsRule {
+            rule_id: rule_id.to_string(),
+            status: status.to_string(),
+            category: category.map(str::to_string),
+            definition: definition.to_string(),
+            in_repair_since: None,
+        }
+    }
+
+    fn change(author: &str, at: DateTime<Utc>) -> StatsChange {
+        StatsChange { created_by: Some(author.to_string()), created_at: at }
+    }
+
+    #[test]
+    fn test_statistics_count_rules_and_recent_changes() {
+        // A Wednesday
+        let now = Utc.with_ymd_and_hms(2024, 6, 12, 12, 0, 0).unwrap();
+        let broken = StatsRule { in_repair_since: Some(now - Duration::days(9)), ..rule("KYC_OLD", "in_repair", Some("kyc"), "risk = (") };
+        let rules = vec![
+            rule("FEE", "active", Some("pricing"), "fee = volume * 2"),
+            rule("TAX", "active", Some("pricing"), "tax = fee"),
+            rule("DRAFT", "draft", None, "x = 1 + 2 + 3"),
+            broken,
+        ];
+        let changes = vec![
+            change("ana", now),
+            change("ana", now - Duration::days(2)),
+            change("bo", now - Duration::days(8)),
+            // Outside the four weeks
+            change("cy", now - Duration::days(60)),
+        ];
+
+        let stats = rule_statistics(&rules, &changes, 4, now);
+        assert_eq!(stats.total_rules, 4);
+        assert_eq!(stats.by_status["active"], 2);
+        assert_eq!(stats.by_category["pricing"], 2);
+        assert_eq!(stats.by_category["uncategorized"], 1);
+        assert_eq!(stats.unparsed_rules, 1);
+        assert!(stats.average_complexity.is_some_and(|average| average > 1.0));
+
+        let weeks: Vec<(NaiveDate, usize)> = stats.changes_per_week.iter().map(|w| (w.week_start, w.changes)).collect();
+        assert_eq!(weeks, vec![
+            (NaiveDate::from_ymd_opt(2024, 5, 20).unwrap(), 0),
+            (NaiveDate::from_ymd_opt(2024, 5, 27).unwrap(), 0),
+            (NaiveDate::from_ymd_opt(2024, 6, 3).unwrap(), 1),
+            (NaiveDate::from_ymd_opt(2024, 6, 10).unwrap(), 2),
+        ]);
+        assert_eq!(stats.top_authors, vec![
+            AuthorChanges { author: "ana".to_string(), changes: 2 },
+            AuthorChanges { author: "bo".to_string(), changes: 1 },
+        ]);
+        assert_eq!(stats.in_repair.len(), 1);
+        assert_eq!((stats.in_repair[0].rule_id.as_str(), stats.in_repair[0].days), ("KYC_OLD", 9));
+    }
+}
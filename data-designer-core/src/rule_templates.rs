@@ -0,0 +1,284 @@
+//! Parameterized rules instantiated per product or jurisdiction
+//!
+//! A template is a rule with named parameters,
+//! `RULE risk_check(threshold) risk_flag = score > threshold`. Each product or
+//! jurisdiction that uses it stores only its arguments, as DSL literals
+//! (`750`, `5%`, `["EUR", "USD"]`), so one logical rule serves every
+//! jurisdiction without a divergent copy per jurisdiction. Instantiating
+//! substitutes the arguments for the parameters, except where a lambda
+//! parameter or comprehension variable of the same name hides them, and
+//! gives back an ordinary rule in canonical format.
+
+use crate::formatter::format_expression;
+use crate::models::{Expression, Value};
+use crate::parser::{parse_expression, parse_rule_template};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateScope {
+    Product,
+    Jurisdiction,
+}
+
+impl TemplateScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TemplateScope::Product => "product",
+            TemplateScope::Jurisdiction => "jurisdiction",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Result<Self, String> {
+        match scope {
+            "product" => Ok(TemplateScope::Product),
+            "jurisdiction" => Ok(TemplateScope::Jurisdiction),
+            other => Err(format!("Unknown template scope '{}': expected product or jurisdiction", other)),
+        }
+    }
+}
+
+impl fmt::Display for TemplateScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleTemplate {
+    pub name: String,
+    pub parameters: Vec<String>,
+    pub body: Expression,
+}
+
+/// One product's or jurisdiction's arguments to a template, by parameter name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateInstance {
+    pub template_name: String,
+    pub scope: TemplateScope,
+    pub scope_value: String,
+    pub arguments: BTreeMap<String, String>,
+}
+
+impl TemplateInstance {
+    /// The id the instantiated rule goes by, e.g. `risk_check[jurisdiction:DE]`
+    pub fn rule_id(&self) -> String {
+        format!("{}[{}:{}]", self.template_name, self.scope, self.scope_value)
+    }
+}
+
+/// A template with one instance's arguments substituted in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstantiatedRule {
+    pub rule_id: String,
+    pub template_name: String,
+    pub scope: TemplateScope,
+    pub scope_value: String,
+    /// The attribute the rule derives: what the body assigns, else the template name
+    pub target_attribute: String,
+    pub definition: String,
+}
+
+impl RuleTemplate {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let (remaining, (name, parameters, body)) =
+            parse_rule_template(source).map_err(|e| format!("Failed to parse rule template: {}", e))?;
+        if !remaining.trim().is_empty() {
+            return Err(format!("Unexpected input after rule template: {}", remaining.trim()));
+        }
+
+        let mut seen = HashSet::new();
+        for parameter in &parameters {
+            if !seen.insert(parameter) {
+                return Err(format!("Parameter '{}' is declared twice", parameter));
+            }
+            // An unused parameter is almost always a typo in the body
+            let mut probe = body.clone();
+            substitute(&mut probe, parameter, &Expression::Literal(Value::Null));
+            if probe == body {
+                return Err(format!("Parameter '{}' is not used in the rule", parameter));
+            }
+        }
+        Ok(RuleTemplate { name, parameters, body })
+    }
+
+    pub fn target_attribute(&self) -> String {
+        match &self.body {
+            Expression::Assignment { target, .. } => target.clone(),
+            _ => self.name.clone(),
+        }
+    }
+
+    /// The body with `arguments` substituted; every parameter needs exactly
+    /// one argument, and each argument must be a literal or a list of them
+    pub fn instantiate(&self, arguments: &BTreeMap<String, String>) -> Result<Expression, String> {
+        if let Some(unknown) = arguments.keys().find(|name| !self.parameters.contains(name)) {
+            return Err(format!("Template '{}' has no parameter '{}'", self.name, unknown));
+        }
+        let mut body = self.body.clone();
+        for parameter in &self.parameters {
+            let argument = arguments
+                .get(parameter)
+                .ok_or_else(|| format!("Missing argument for parameter '{}' of template '{}'", parameter, self.name))?;
+            let value = parse_argument(argument).map_err(|e| format!("Argument '{}': {}", parameter, e))?;
+            substitute(&mut body, parameter, &value);
+        }
+        Ok(body)
+    }
+
+    pub fn instantiate_rule(&self, instance: &TemplateInstance) -> Result<InstantiatedRule, String> {
+        if instance.template_name != self.name {
+            return Err(format!("Instance of '{}' given to template '{}'", instance.template_name, self.name));
+        }
+        let body = self
+            .instantiate(&instance.arguments)
+            .map_err(|e| format!("{} for {} {}", e, instance.scope, instance.scope_value))?;
+        Ok(InstantiatedRule {
+            rule_id: instance.rule_id(),
+            template_name: self.name.clone(),
+            scope: instance.scope,
+            scope_value: instance.scope_value.clone(),
+            target_attribute: self.target_attribute(),
+            definition: format_expression(&body),
+        })
+    }
+}
+
+fn parse_argument(argument: &str) -> Result<Expression, String> {
+    let (remaining, expr) = parse_expression(argument.trim()).map_err(|e| format!("Failed to parse '{}': {}", argument, e))?;
+    if !remaining.trim().is_empty() {
+        return Err(format!("Unexpected input after argument: {}", remaining.trim()));
+    }
+    let expr = expr.optimize();
+    if !is_constant(&expr) {
+        return Err(format!("'{}' is not a literal value", argument.trim()));
+    }
+    Ok(expr)
+}
+
+fn is_constant(expr: &Expression) -> bool {
+    match expr {
+        Expression::Literal(_) => true,
+        Expression::List(items) => items.iter().all(is_constant),
+        _ => false,
+    }
+}
+
+// Replace reads of `parameter` with `value`; lambda parameters and
+// comprehension variables of the same name hide it, as in rule_rewrite
+fn substitute(expr: &mut Expression, parameter: &str, value: &Expression) {
+    match expr {
+        Expression::Identifier(name) | Expression::Variable(name) => {
+            if name == parameter {
+                *expr = value.clone();
+            }
+        }
+        Expression::Lambda { param, body } => {
+            if param != parameter {
+                substitute(body, parameter, value);
+            }
+        }
+        Expression::Comprehension { element, var, source, condition } => {
+            substitute(source, parameter, value);
+            if var != parameter {
+                substitute(element, parameter, value);
+                if let Some(condition) = condition {
+                    substitute(condition, parameter, value);
+                }
+            }
+        }
+        Expression::Assignment { value: assigned, .. } => substitute(assigned, parameter, value),
+        Expression::BinaryOp { left, right, .. } | Expression::Range { start: left, end: right, .. } => {
+            substitute(left, parameter, value);
+            substitute(right, parameter, value);
+        }
+        Expression::UnaryOp { operand, .. } => substitute(operand, parameter, value),
+        Expression::Cast { expr, .. } => substitute(expr, parameter, value),
+        Expression::Conditional { condition, then_expr, else_expr } => {
+            substitute(condition, parameter, value);
+            substitute(then_expr, parameter, value);
+            if let Some(else_expr) = else_expr {
+                substitute(else_expr, parameter, value);
+            }
+        }
+        Expression::Case { branches, else_expr } => {
+            for (condition, result) in branches {
+                substitute(condition, parameter, value);
+                substitute(result, parameter, value);
+            }
+            if let Some(else_expr) = else_expr {
+                substitute(else_expr, parameter, value);
+            }
+        }
+        Expression::FunctionCall { args: items, .. }
+        | Expression::List(items)
+        | Expression::ConfigureSystem { arguments: items, .. }
+        | Expression::Activate { arguments: items, .. }
+        | Expression::RunHealthCheck { arguments: items, .. }
+        | Expression::Workflow { steps: items, .. } => {
+            items.iter_mut().for_each(|item| substitute(item, parameter, value));
+        }
+        Expression::Literal(_) | Expression::SetStatus { .. } => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arguments(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_instances_substitute_their_arguments() {
+        let template = RuleTemplate::parse(
+            "RULE risk_check(threshold, currencies)\n    risk_flag = score > threshold AND currency IN currencies",
+        )
+        .unwrap();
+        assert_eq!(template.parameters, ["threshold", "currencies"]);
+        assert_eq!(template.target_attribute(), "risk_flag");
+
+        let instance = TemplateInstance {
+            template_name: "risk_check".to_string(),
+            scope: TemplateScope::Jurisdiction,
+            scope_value: "DE".to_string(),
+            arguments: arguments(&[("threshold", "700"), ("currencies", "[\"EUR\", \"CHF\"]")]),
+        };
+        let rule = template.instantiate_rule(&instance).unwrap();
+        assert_eq!(rule.rule_id, "risk_check[jurisdiction:DE]");
+        assert_eq!(rule.definition, "risk_flag = score > 700 AND currency IN [\"EUR\", \"CHF\"]");
+    }
+
+    #[test]
+    fn test_shadowed_parameters_are_left_alone() {
+        let template = RuleTemplate::parse("RULE large(limit) SUM(MAP(amounts, limit -> limit * 2)) > limit").unwrap();
+        let body = template.instantiate(&arguments(&[("limit", "-5")])).unwrap();
+        assert_eq!(format_expression(&body), "SUM(MAP(amounts, limit -> limit * 2)) > -5");
+    }
+
+    #[test]
+    fn test_arguments_must_match_the_parameters() {
+        assert_eq!(
+            RuleTemplate::parse("RULE fee(rate, cap) amount * rate").unwrap_err(),
+            "Parameter 'cap' is not used in the rule"
+        );
+        assert!(RuleTemplate::parse("RULE fee(rate, rate) amount * rate").is_err());
+
+        let template = RuleTemplate::parse("RULE fee(rate) amount * rate").unwrap();
+        assert_eq!(
+            template.instantiate(&BTreeMap::new()).unwrap_err(),
+            "Missing argument for parameter 'rate' of template 'fee'"
+        );
+        assert_eq!(
+            template.instantiate(&arguments(&[("rate", "1%"), ("cap", "10")])).unwrap_err(),
+            "Template 'fee' has no parameter 'cap'"
+        );
+        assert_eq!(
+            template.instantiate(&arguments(&[("rate", "base_rate")])).unwrap_err(),
+            "Argument 'rate': 'base_rate' is not a literal value"
+        );
+    }
+}
@@ -0,0 +1,491 @@
+//! Rule test cases: generating starter cases and running saved ones
+//!
+//! When a rule is saved, each of its dependencies is sampled at the
+//! boundaries its dictionary entry allows (min/max values and lengths,
+//! examples that match the pattern, domain codes) while the others stay at a
+//! typical value. The rule is evaluated for every sample and the result is
+//! recorded as the expected value. Generated cases are only a starting
+//! point: once saved they are edited like hand-written ones and are never
+//! regenerated over.
+//!
+//! `RuleTestRunner` evaluates a rule definition against its saved cases.
+//! Numbers compare within a tolerance so a rewrite that only changes
+//! floating-point rounding still passes.
+
+use crate::evaluator::{evaluate, evaluate_with_functions, Facts, FunctionLibrary};
+use crate::parser::parse_rule;
+use crate::models::Value;
+use crate::transpiler::{DslRule, DslTranspiler, TranspileError};
+use crate::type_checker::RuleType;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const MAX_CASES_PER_RULE: usize = 20;
+const DEFAULT_TOLERANCE: f64 = 1e-9;
+
+/// What the data dictionary knows about one attribute
+#[derive(Debug, Clone, Default)]
+pub struct AttributeConstraints {
+    pub rule_type: RuleType,
+    pub min_value: Option<String>,
+    pub max_value: Option<String>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    pub pattern: Option<String>,
+    pub examples: Vec<String>,
+    pub domain_values: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleTestCase {
+    pub rule: String,
+    pub name: String,
+    pub inputs: BTreeMap<String, serde_json::Value>,
+    pub expected: serde_json::Value,
+    /// Set instead of `expected` when the rule fails for these inputs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_error: Option<String>,
+    #[serde(default)]
+    pub generated: bool,
+}
+
+impl AttributeConstraints {
+    /// Examples that satisfy the pattern; an unparseable pattern filters nothing
+    fn valid_examples(&self) -> Vec<&str> {
+        let pattern = self.pattern.as_deref().and_then(|p| Regex::new(p).ok());
+        self.examples
+            .iter()
+            .map(String::as_str)
+            .filter(|example| pattern.as_ref().is_none_or(|re| re.is_match(example)))
+            .collect()
+    }
+
+    /// Value used for this attribute while another one is being varied
+    fn baseline(&self) -> serde_json::Value {
+        if let Some(example) = self.valid_examples().first() {
+            return self.typed(example);
+        }
+        if let Some(code) = self.domain_values.first() {
+            return serde_json::Value::String(code.clone());
+        }
+        self.samples()
+            .into_iter()
+            .next()
+            .map_or(serde_json::Value::Null, |(_, value)| value)
+    }
+
+    /// Boundary and representative values, labelled for the test name
+    fn samples(&self) -> Vec<(String, serde_json::Value)> {
+        if !self.domain_values.is_empty() {
+            return self
+                .domain_values
+                .iter()
+                .map(|code| (format!("= \"{}\"", code), serde_json::Value::String(code.clone())))
+                .collect();
+        }
+
+        let mut samples = Vec::new();
+        match self.rule_type {
+            RuleType::Number => {
+                let min = self.min_value.as_deref().and_then(|v| v.trim().parse::<f64>().ok());
+                let max = self.max_value.as_deref().and_then(|v| v.trim().parse::<f64>().ok());
+                if let Some(min) = min {
+                    samples.push(("at minimum".to_string(), number(min)));
+                }
+                if let Some(max) = max {
+                    samples.push(("at maximum".to_string(), number(max)));
+                }
+                if min.is_none_or(|min| min < 0.0) && max.is_none_or(|max| max > 0.0) {
+                    samples.push(("at zero".to_string(), number(0.0)));
+                }
+            }
+            RuleType::Boolean => {
+                samples.push(("= true".to_string(), serde_json::Value::Bool(true)));
+                samples.push(("= false".to_string(), serde_json::Value::Bool(false)));
+            }
+            RuleType::Date => {
+                if let Some(min) = &self.min_value {
+                    samples.push(("at minimum".to_string(), serde_json::Value::String(min.clone())));
+                }
+                if let Some(max) = &self.max_value {
+                    samples.push(("at maximum".to_string(), serde_json::Value::String(max.clone())));
+                }
+            }
+            RuleType::String if self.pattern.is_none() => {
+                // Without a pattern any characters are valid, so lengths can be probed directly
+                if let Some(min_length) = self.min_length {
+                    let label = if min_length == 0 { "empty".to_string() } else { "at minimum length".to_string() };
+                    samples.push((label, serde_json::Value::String("x".repeat(min_length))));
+                }
+                if let Some(max_length) = self.max_length {
+                    samples.push(("at maximum length".to_string(), serde_json::Value::String("x".repeat(max_length))));
+                }
+            }
+            _ => {}
+        }
+
+        for example in self.valid_examples() {
+            samples.push((format!("= {}", example), self.typed(example)));
+        }
+        samples
+    }
+
+    /// Dictionary examples are strings; give them the attribute's JSON type
+    fn typed(&self, example: &str) -> serde_json::Value {
+        match self.rule_type {
+            RuleType::Number => example.trim().parse::<f64>().map_or(serde_json::Value::Null, number),
+            RuleType::Boolean => serde_json::Value::Bool(example.trim().eq_ignore_ascii_case("true")),
+            RuleType::List | RuleType::Unknown => {
+                serde_json::from_str(example).unwrap_or_else(|_| serde_json::Value::String(example.to_string()))
+            }
+            _ => serde_json::Value::String(example.to_string()),
+        }
+    }
+}
+
+/// Starter tests for one rule: a baseline case, then one case per sample of
+/// each dependency. Dependencies the dictionary doesn't know are left null.
+pub fn generate_test_cases<F>(rule: &DslRule, lookup: F) -> Vec<RuleTestCase>
+where
+    F: Fn(&str) -> Option<AttributeConstraints>,
+{
+    let constraints: Vec<(&String, Option<AttributeConstraints>)> =
+        rule.dependencies.iter().map(|name| (name, lookup(name))).collect();
+    let baseline: BTreeMap<String, serde_json::Value> = constraints
+        .iter()
+        .map(|(name, constraints)| {
+            let value = constraints.as_ref().map_or(serde_json::Value::Null, |c| c.baseline());
+            ((*name).clone(), value)
+        })
+        .collect();
+
+    let mut variations = vec![("baseline".to_string(), baseline.clone())];
+    for (name, constraints) in &constraints {
+        let Some(constraints) = constraints else { continue };
+        for (label, value) in constraints.samples() {
+            let mut inputs = baseline.clone();
+            inputs.insert((*name).clone(), value);
+            variations.push((format!("{} {}", name, label), inputs));
+        }
+    }
+
+    let mut cases: Vec<RuleTestCase> = Vec::new();
+    for (name, inputs) in variations {
+        if cases.len() == MAX_CASES_PER_RULE {
+            break;
+        }
+        if cases.iter().any(|case| case.inputs == inputs) {
+            continue;
+        }
+        let facts: Facts = inputs.iter().map(|(k, v)| (k.clone(), Value::from_json(v))).collect();
+        let (expected, expected_error) = match evaluate(&rule.expression, &facts) {
+            Ok(value) => (value.to_json(), None),
+            Err(e) => (serde_json::Value::Null, Some(e.to_string())),
+        };
+        cases.push(RuleTestCase {
+            rule: rule.name.clone(),
+            name,
+            inputs,
+            expected,
+            expected_error,
+            generated: true,
+        });
+    }
+    cases
+}
+
+/// Starter tests for every named rule (`name = expression`) in a document
+pub fn generate_document_tests<F>(dsl_text: &str, lookup: F) -> Result<Vec<RuleTestCase>, Vec<TranspileError>>
+where
+    F: Fn(&str) -> Option<AttributeConstraints>,
+{
+    let transpiler = DslTranspiler {
+        validation_enabled: false,
+        dependency_analysis: false,
+    };
+    let rules = transpiler.transpile_dsl_to_rules(dsl_text)?;
+    Ok(rules
+        .iter()
+        // Anonymous rules get a random name, so their tests could never be matched up again
+        .filter(|rule| rule.definition.contains(" = "))
+        .flat_map(|rule| generate_test_cases(rule, &lookup))
+        .collect())
+}
+
+/// Add generated cases that aren't already saved; existing cases, edited or
+/// not, are kept as they are. Returns how many were added.
+pub fn merge_test_cases(existing: &mut Vec<RuleTestCase>, generated: Vec<RuleTestCase>) -> usize {
+    let before = existing.len();
+    for case in generated {
+        if !existing.iter().any(|saved| saved.rule == case.rule && saved.name == case.name) {
+            existing.push(case);
+        }
+    }
+    existing.len() - before
+}
+
+/// How one case went
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleTestOutcome {
+    pub name: String,
+    pub passed: bool,
+    pub actual: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actual_error: Option<String>,
+    /// Why the case failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Every case of one rule, run against one definition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleTestReport {
+    pub rule: String,
+    pub passed: usize,
+    pub failed: usize,
+    pub outcomes: Vec<RuleTestOutcome>,
+}
+
+impl RuleTestReport {
+    pub fn all_passed(&self) -> bool {
+        self.failed == 0
+    }
+
+    /// One line per failed case, for error messages
+    pub fn failure_summary(&self) -> String {
+        self.outcomes
+            .iter()
+            .filter(|outcome| !outcome.passed)
+            .map(|outcome| format!("{}: {}", outcome.name, outcome.message.as_deref().unwrap_or("failed")))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// Runs saved test cases against a rule definition
+pub struct RuleTestRunner {
+    tolerance: f64,
+    functions: FunctionLibrary,
+}
+
+impl Default for RuleTestRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RuleTestRunner {
+    pub fn new() -> Self {
+        Self { tolerance: DEFAULT_TOLERANCE, functions: FunctionLibrary::new() }
+    }
+
+    /// Numbers match when they differ by at most `tolerance`, or by that
+    /// fraction of the larger of the two when it is above 1
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance.abs();
+        self
+    }
+
+    pub fn with_functions(mut self, functions: FunctionLibrary) -> Self {
+        self.functions = functions;
+        self
+    }
+
+    /// Runs every case; fails only when the definition doesn't parse
+    pub fn run(&self, rule: &str, definition: &str, cases: &[RuleTestCase]) -> Result<RuleTestReport, String> {
+        let expression = match parse_rule(definition) {
+            Ok((rest, expression)) if rest.trim().is_empty() => expression,
+            Ok((rest, _)) => return Err(format!("Unexpected input after rule: {}", rest.trim())),
+            Err(e) => return Err(format!("Failed to parse rule: {}", e)),
+        };
+
+        let outcomes: Vec<RuleTestOutcome> = cases
+            .iter()
+            .map(|case| {
+                let facts: Facts = case.inputs.iter().map(|(k, v)| (k.clone(), Value::from_json(v))).collect();
+                let (actual, actual_error) = match evaluate_with_functions(&expression, &facts, &self.functions) {
+                    Ok(value) => (value.to_json(), None),
+                    Err(e) => (serde_json::Value::Null, Some(e.to_string())),
+                };
+                let message = match (&case.expected_error, &actual_error) {
+                    (Some(_), Some(_)) => None,
+                    (Some(expected), None) => Some(format!("expected an error ({}), got {}", expected, actual)),
+                    (None, Some(error)) => Some(format!("expected {}, got error: {}", case.expected, error)),
+                    (None, None) if self.matches(&case.expected, &actual) => None,
+                    (None, None) => Some(format!("expected {}, got {}", case.expected, actual)),
+                };
+                RuleTestOutcome {
+                    name: case.name.clone(),
+                    passed: message.is_none(),
+                    actual,
+                    actual_error,
+                    message,
+                }
+            })
+            .collect();
+
+        let passed = outcomes.iter().filter(|outcome| outcome.passed).count();
+        Ok(RuleTestReport {
+            rule: rule.to_string(),
+            passed,
+            failed: outcomes.len() - passed,
+            outcomes,
+        })
+    }
+
+    fn matches(&self, expected: &serde_json::Value, actual: &serde_json::Value) -> bool {
+        use serde_json::Value as Json;
+        match (expected, actual) {
+            (Json::Number(e), Json::Number(a)) => match (e.as_f64(), a.as_f64()) {
+                (Some(e), Some(a)) => (e - a).abs() <= self.tolerance * e.abs().max(a.abs()).max(1.0),
+                _ => e == a,
+            },
+            (Json::Array(e), Json::Array(a)) => {
+                e.len() == a.len() && e.iter().zip(a).all(|(e, a)| self.matches(e, a))
+            }
+            (Json::Object(e), Json::Object(a)) => {
+                e.len() == a.len() && e.iter().all(|(key, e)| a.get(key).is_some_and(|a| self.matches(e, a)))
+            }
+            _ => expected == actual,
+        }
+    }
+}
+
+fn number(n: f64) -> serde_json::Value {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        serde_json::json!(n as i64)
+    } else {
+        serde_json::json!(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn dictionary() -> HashMap<&'static str, AttributeConstraints> {
+        HashMap::from([
+            (
+                "aum_usd",
+                AttributeConstraints {
+                    rule_type: RuleType::Number,
+                    min_value: Some("0".to_string()),
+                    max_value: Some("1000000".to_string()),
+                    examples: vec!["250000".to_string()],
+                    ..Default::default()
+                },
+            ),
+            (
+                "risk_rating",
+                AttributeConstraints {
+                    rule_type: RuleType::String,
+                    domain_values: vec!["LOW".to_string(), "MEDIUM".to_string(), "HIGH".to_string()],
+                    ..Default::default()
+                },
+            ),
+            (
+                "client_id",
+                AttributeConstraints {
+                    rule_type: RuleType::String,
+                    pattern: Some(r"^[A-Z]{3}-\d{3,}$".to_string()),
+                    examples: vec!["CLT-001".to_string(), "not an id".to_string()],
+                    ..Default::default()
+                },
+            ),
+        ])
+    }
+
+    fn generate(dsl: &str) -> Vec<RuleTestCase> {
+        let dictionary = dictionary();
+        generate_document_tests(dsl, |name| dictionary.get(name).cloned()).unwrap()
+    }
+
+    #[test]
+    fn test_samples_boundaries_and_domain_values() {
+        let cases = generate(r#"fee = IF risk_rating == "HIGH" THEN aum_usd * 0.02 ELSE aum_usd * 0.01"#);
+        let names: Vec<&str> = cases.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "baseline",
+                "aum_usd at minimum",
+                "aum_usd at maximum",
+                "risk_rating = \"MEDIUM\"",
+                "risk_rating = \"HIGH\"",
+            ]
+        );
+
+        let baseline = &cases[0];
+        assert_eq!(baseline.inputs["aum_usd"], serde_json::json!(250000));
+        assert_eq!(baseline.inputs["risk_rating"], serde_json::json!("LOW"));
+        assert_eq!(baseline.expected, serde_json::json!(2500.0));
+        assert!(cases.iter().all(|c| c.rule == "fee" && c.generated));
+
+        let high = cases.iter().find(|c| c.name == "risk_rating = \"HIGH\"").unwrap();
+        assert_eq!(high.expected, serde_json::json!(5000.0));
+    }
+
+    #[test]
+    fn test_examples_must_match_the_pattern() {
+        let cases = generate("label = CONCAT(client_id, \"!\")");
+        assert_eq!(cases.len(), 1);
+        assert_eq!(cases[0].inputs["client_id"], serde_json::json!("CLT-001"));
+        assert_eq!(cases[0].expected, serde_json::json!("CLT-001!"));
+    }
+
+    #[test]
+    fn test_failures_are_recorded_as_expected_errors() {
+        let cases = generate("ratio = 100 / aum_usd");
+        let at_zero = cases.iter().find(|c| c.name == "aum_usd at minimum").unwrap();
+        assert!(at_zero.expected_error.is_some());
+    }
+
+    #[test]
+    fn test_runner_compares_numbers_within_tolerance() {
+        let mut cases = generate("fee = aum_usd * 0.01");
+        let runner = RuleTestRunner::new();
+        let report = runner.run("fee", "fee = aum_usd * 0.01", &cases).unwrap();
+        assert!(report.all_passed(), "{}", report.failure_summary());
+
+        // Same value up to rounding
+        let report = runner.run("fee", "fee = aum_usd / 100", &cases).unwrap();
+        assert!(report.all_passed(), "{}", report.failure_summary());
+
+        cases[0].expected = serde_json::json!(2500.5);
+        let report = runner.run("fee", "fee = aum_usd * 0.01", &cases).unwrap();
+        assert_eq!((report.passed, report.failed), (cases.len() - 1, 1));
+        assert!(report.failure_summary().starts_with("baseline: expected 2500.5"));
+        let report = runner.with_tolerance(1e-3).run("fee", "fee = aum_usd * 0.01", &cases).unwrap();
+        assert!(report.all_passed());
+
+        assert!(RuleTestRunner::new().run("fee", "fee = aum_usd *", &cases).is_err());
+    }
+
+    #[test]
+    fn test_runner_checks_expected_errors() {
+        let cases = generate("ratio = 100 / aum_usd");
+        let runner = RuleTestRunner::new();
+        assert!(runner.run("ratio", "ratio = 100 / aum_usd", &cases).unwrap().all_passed());
+
+        // The fix no longer divides by zero, so the case expecting an error fails
+        let report = runner.run("ratio", "ratio = IF aum_usd == 0 THEN 0 ELSE 100 / aum_usd", &cases).unwrap();
+        assert_eq!(report.failed, 1);
+        let failed = report.outcomes.iter().find(|o| !o.passed).unwrap();
+        assert_eq!(failed.name, "aum_usd at minimum");
+        assert_eq!(failed.actual, serde_json::json!(0));
+    }
+
+    #[test]
+    fn test_merge_keeps_edited_cases() {
+        let mut saved = generate("doubled = aum_usd * 2");
+        saved[0].expected = serde_json::json!("edited by hand");
+        saved.remove(1);
+
+        let added = merge_test_cases(&mut saved, generate("doubled = aum_usd * 2"));
+        assert_eq!(added, 1);
+        assert_eq!(saved[0].expected, serde_json::json!("edited by hand"));
+        assert_eq!(merge_test_cases(&mut saved, generate("doubled = aum_usd * 2")), 0);
+    }
+}
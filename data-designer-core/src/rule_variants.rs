@@ -0,0 +1,214 @@
+//! Variants of a rule for particular jurisdictions, product lines or CBU types
+//!
+//! Several rules may derive the same attribute, each tagged with where it
+//! applies. When evaluating, the context's `jurisdiction`, `product_line` and
+//! `cbu_type` facts pick the variant: the one tagged variant whose conditions
+//! all hold, else the untagged variant as the default. Two tagged variants
+//! that both apply, or two untagged ones, are a conflict rather than a silent
+//! choice between them.
+
+use crate::evaluator::Facts;
+use crate::models::{BinaryOperator, Expression, Value};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Where a rule variant applies; a variant with no conditions applies everywhere
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Applicability {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jurisdiction: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product_line: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cbu_type: Option<String>,
+}
+
+impl Applicability {
+    pub fn is_universal(&self) -> bool {
+        self.conditions().is_empty()
+    }
+
+    /// Each condition as the context fact it reads and the value it needs
+    pub fn conditions(&self) -> Vec<(&'static str, &str)> {
+        [("jurisdiction", &self.jurisdiction), ("product_line", &self.product_line), ("cbu_type", &self.cbu_type)]
+            .into_iter()
+            .filter_map(|(fact, value)| Some((fact, value.as_deref()?)))
+            .collect()
+    }
+
+    pub fn applies(&self, facts: &Facts) -> bool {
+        self.conditions()
+            .iter()
+            .all(|(fact, value)| matches!(facts.get(*fact), Some(Value::String(actual)) if actual == value))
+    }
+
+    /// Whether some context satisfies both
+    pub fn overlaps(&self, other: &Applicability) -> bool {
+        self.conditions().iter().all(|(fact, value)| {
+            other.conditions().iter().all(|(other_fact, other_value)| fact != other_fact || value == other_value)
+        })
+    }
+
+    // The conditions of both; only meaningful when they overlap
+    fn merged(&self, other: &Applicability) -> Applicability {
+        Applicability {
+            jurisdiction: self.jurisdiction.clone().or_else(|| other.jurisdiction.clone()),
+            product_line: self.product_line.clone().or_else(|| other.product_line.clone()),
+            cbu_type: self.cbu_type.clone().or_else(|| other.cbu_type.clone()),
+        }
+    }
+
+    /// The conditions as a DSL condition, `jurisdiction == "DE" AND cbu_type == "fund"`;
+    /// None for a variant that applies everywhere
+    pub fn condition(&self) -> Option<Expression> {
+        self.conditions()
+            .into_iter()
+            .map(|(fact, value)| Expression::BinaryOp {
+                left: Box::new(Expression::Identifier(fact.to_string())),
+                op: BinaryOperator::Equals,
+                right: Box::new(Expression::Literal(Value::String(value.to_string()))),
+            })
+            .reduce(|left, right| Expression::BinaryOp {
+                left: Box::new(left),
+                op: BinaryOperator::And,
+                right: Box::new(right),
+            })
+    }
+}
+
+impl fmt::Display for Applicability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let conditions = self.conditions();
+        if conditions.is_empty() {
+            return f.write_str("every context");
+        }
+        let parts: Vec<String> = conditions.iter().map(|(fact, value)| format!("{} {}", fact, value)).collect();
+        f.write_str(&parts.join(", "))
+    }
+}
+
+/// Variants of one attribute's rule that apply to the same context
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VariantConflict {
+    pub attribute: String,
+    pub rule_ids: Vec<String>,
+    /// Where they all apply
+    pub applies_to: Applicability,
+}
+
+impl fmt::Display for VariantConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Rule variants {} of '{}' all apply to {}",
+            self.rule_ids.join(", "),
+            self.attribute,
+            self.applies_to
+        )
+    }
+}
+
+impl std::error::Error for VariantConflict {}
+
+/// The variant of `attribute`'s rule that applies to `facts`: the tagged
+/// variant whose conditions hold, else the untagged one. None when neither
+/// exists; a conflict when more than one would do.
+pub fn resolve_variant<'a, T>(
+    attribute: &str,
+    variants: impl IntoIterator<Item = (&'a str, &'a Applicability, T)>,
+    facts: &Facts,
+) -> Result<Option<T>, VariantConflict> {
+    let (tagged, untagged): (Vec<_>, Vec<_>) = variants
+        .into_iter()
+        .filter(|(_, applies_to, _)| applies_to.applies(facts))
+        .partition(|(_, applies_to, _)| !applies_to.is_universal());
+    let candidates = if tagged.is_empty() { untagged } else { tagged };
+    if candidates.len() > 1 {
+        return Err(VariantConflict {
+            attribute: attribute.to_string(),
+            rule_ids: candidates.iter().map(|(rule_id, _, _)| rule_id.to_string()).collect(),
+            applies_to: candidates.iter().fold(Applicability::default(), |both, (_, variant, _)| both.merged(variant)),
+        });
+    }
+    Ok(candidates.into_iter().next().map(|(_, _, variant)| variant))
+}
+
+/// Pairs of variants of the same attribute that some context would make
+/// conflict, in the order given. Each variant is `(attribute, rule_id, applies_to)`.
+pub fn find_variant_conflicts<'a>(
+    variants: impl IntoIterator<Item = (&'a str, &'a str, &'a Applicability)>,
+) -> Vec<VariantConflict> {
+    let variants: Vec<_> = variants.into_iter().collect();
+    let mut conflicts = Vec::new();
+    for (index, (attribute, first, first_applies)) in variants.iter().enumerate() {
+        for (other_attribute, second, second_applies) in &variants[index + 1..] {
+            if attribute != other_attribute
+                || first_applies.is_universal() != second_applies.is_universal()
+                || !first_applies.overlaps(second_applies)
+            {
+                continue;
+            }
+            conflicts.push(VariantConflict {
+                attribute: attribute.to_string(),
+                rule_ids: vec![first.to_string(), second.to_string()],
+                applies_to: first_applies.merged(second_applies),
+            });
+        }
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn applies(jurisdiction: Option<&str>, product_line: Option<&str>) -> Applicability {
+        Applicability {
+            jurisdiction: jurisdiction.map(str::to_string),
+            product_line: product_line.map(str::to_string),
+            cbu_type: None,
+        }
+    }
+
+    fn context(pairs: &[(&str, &str)]) -> Facts {
+        pairs.iter().map(|(name, value)| (name.to_string(), Value::String(value.to_string()))).collect()
+    }
+
+    #[test]
+    fn test_the_matching_variant_wins_over_the_default() {
+        let default = applies(None, None);
+        let germany = applies(Some("DE"), None);
+        let france = applies(Some("FR"), None);
+        let variants = || [("fee", &default, 0), ("fee_de", &germany, 1), ("fee_fr", &france, 2)];
+
+        assert_eq!(resolve_variant("fee", variants(), &context(&[("jurisdiction", "FR")])), Ok(Some(2)));
+        assert_eq!(resolve_variant("fee", variants(), &context(&[("jurisdiction", "US")])), Ok(Some(0)));
+        assert_eq!(resolve_variant("fee", variants(), &Facts::new()), Ok(Some(0)));
+        assert_eq!(resolve_variant("fee", [("fee_de", &germany, 1)], &Facts::new()), Ok(None));
+    }
+
+    #[test]
+    fn test_variants_that_both_apply_conflict() {
+        let germany = applies(Some("DE"), None);
+        let custody = applies(None, Some("custody"));
+        let variants = [("fee_de", &germany, 1), ("fee_custody", &custody, 2)];
+
+        let facts = context(&[("jurisdiction", "DE"), ("product_line", "custody")]);
+        let conflict = resolve_variant("fee", variants, &facts).unwrap_err();
+        assert_eq!(
+            conflict.to_string(),
+            "Rule variants fee_de, fee_custody of 'fee' all apply to jurisdiction DE, product_line custody"
+        );
+        let facts = context(&[("jurisdiction", "DE"), ("product_line", "funds")]);
+        assert_eq!(resolve_variant("fee", variants, &facts), Ok(Some(1)));
+
+        let france = applies(Some("FR"), None);
+        let found = find_variant_conflicts([
+            ("fee", "fee_de", &germany),
+            ("fee", "fee_fr", &france),
+            ("fee", "fee_custody", &custody),
+        ]);
+        let pairs: Vec<Vec<String>> = found.into_iter().map(|conflict| conflict.rule_ids).collect();
+        assert_eq!(pairs, [["fee_de", "fee_custody"], ["fee_fr", "fee_custody"]]);
+    }
+}
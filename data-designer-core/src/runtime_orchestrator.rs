@@ -6,7 +6,12 @@ use chrono::{DateTime, Utc};
 use crate::db::DbPool;
 use crate::capability_engine::CapabilityEngine;
 use crate::dsl_utils;
+use crate::evaluator::Facts;
+use crate::models::Value;
+use crate::parser::parse_rule;
+use crate::rhai_runtime::{CompiledScript, RhaiSandbox};
 use sqlx::Row;
+use std::sync::OnceLock;
 
 /// Helper structs for template loading
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -278,6 +283,15 @@ pub struct CompiledRule {
     pub pattern: String,
     pub dependencies: Vec<String>,
     pub computation_logic: String,
+    /// Set when the rule is a DSL expression Rhai can express; it then runs
+    /// as a precompiled script instead of the keyword handlers
+    pub rhai_script: Option<CompiledScript>,
+}
+
+/// One sandbox for every executor; Rhai engines are built once and shared
+fn rule_sandbox() -> &'static RhaiSandbox {
+    static SANDBOX: OnceLock<RhaiSandbox> = OnceLock::new();
+    SANDBOX.get_or_init(RhaiSandbox::default)
 }
 
 /// Populate-data command structure for DSL parsing
@@ -1613,12 +1627,17 @@ impl EbnfRuleExecutor {
         // Parse the derivation rule to extract pattern and dependencies
         let dependencies = self.extract_dependencies(derivation_rule);
 
-        // For now, store the rule as computation logic
-        // In production, this would compile to AST or bytecode
+        // DSL expressions compile to a Rhai script; anything else keeps the keyword handlers
+        let rhai_script = match parse_rule(derivation_rule) {
+            Ok((remaining, expr)) if remaining.trim().is_empty() => rule_sandbox().compile_rule(&expr).ok(),
+            _ => None,
+        };
+
         let compiled_rule = CompiledRule {
             pattern: derivation_rule.to_string(),
             dependencies,
             computation_logic: derivation_rule.to_string(),
+            rhai_script,
         };
 
         Ok(compiled_rule)
@@ -1630,6 +1649,17 @@ impl EbnfRuleExecutor {
         compiled_rule: &CompiledRule,
         source_values: &HashMap<String, serde_json::Value>,
     ) -> Result<serde_json::Value, RuntimeError> {
+        if let Some(script) = &compiled_rule.rhai_script {
+            let facts: Facts = source_values
+                .iter()
+                .map(|(name, value)| (name.clone(), Value::from_json(value)))
+                .collect();
+            return rule_sandbox()
+                .execute_compiled(script, &facts)
+                .map(|value| value.to_json())
+                .map_err(|e| RuntimeError::RuleExecutionFailed(e.to_string()));
+        }
+
         let computation_logic = &compiled_rule.computation_logic;
 
         // Simple rule execution - in production would use proper expression evaluator
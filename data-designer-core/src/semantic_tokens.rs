@@ -0,0 +1,353 @@
+//! Semantic highlighting of rule documents, driven by the parser
+//!
+//! Literals, attributes and function names come from the spans the parser
+//! records for each rule's primary expressions, so a name is highlighted for
+//! what it parsed as rather than for what it contains: `END_DATE` is one
+//! attribute, not the keyword `END` and some more text. Keywords and operators
+//! fill the text between those spans, comments come from `source_structure`,
+//! and text no rule parsed from is lexed on its own so a rule still being
+//! typed is highlighted too. Columns are bytes; tokens never span lines.
+
+use crate::models::{Expression, Value};
+use crate::parser::{parse_rule_with_spans, parse_rules_recovering, ParsedRule};
+use crate::source_structure::{regions, RegionKind, RULE_SYNTAX};
+use std::cmp::Reverse;
+use std::ops::Range;
+
+/// Words the rule grammar reserves
+pub const KEYWORDS: &[&str] = &[
+    "IF", "THEN", "ELSE", "CASE", "WHEN", "END", "AND", "OR", "NOT", "IN", "NOT_IN", "FOR", "BETWEEN",
+    "BETWEEN_EXCLUSIVE", "MATCHES", "NOT_MATCHES", "CONTAINS", "STARTS_WITH", "ENDS_WITH", "RULE",
+    "CONFIGURE_SYSTEM", "ACTIVATE", "RUN_HEALTH_CHECK", "SET_STATUS", "WORKFLOW", "true", "false", "null",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Keyword,
+    Operator,
+    String,
+    Number,
+    Variable,
+    Function,
+    Comment,
+}
+
+impl TokenKind {
+    /// In legend order: a token's type is its index here
+    pub const ALL: [TokenKind; 7] = [
+        TokenKind::Keyword,
+        TokenKind::Operator,
+        TokenKind::String,
+        TokenKind::Number,
+        TokenKind::Variable,
+        TokenKind::Function,
+        TokenKind::Comment,
+    ];
+
+    pub fn index(&self) -> u32 {
+        *self as u32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceToken {
+    pub line: u32,
+    pub column: u32,
+    pub length: u32,
+    pub kind: TokenKind,
+}
+
+/// The tokens of a rule document, in document order
+pub fn tokenize(text: &str) -> Vec<SourceToken> {
+    let mut kinds: Vec<Option<TokenKind>> = vec![None; text.len()];
+    let (rules, _) = parse_rules_recovering(text);
+    for rule in &rules {
+        paint_rule(text, rule, &mut kinds);
+    }
+    for region in regions(text, &RULE_SYNTAX) {
+        if region.kind == RegionKind::Comment {
+            fill(&mut kinds, region.start..region.end, TokenKind::Comment);
+        }
+    }
+    lex_unpainted(text, &mut kinds);
+    collect(text, &kinds)
+}
+
+// Overwrites whatever was painted before
+fn paint(kinds: &mut [Option<TokenKind>], range: Range<usize>, kind: TokenKind) {
+    let end = range.end.min(kinds.len());
+    for slot in &mut kinds[range.start.min(end)..end] {
+        *slot = Some(kind);
+    }
+}
+
+// Paints only what nothing else has
+fn fill(kinds: &mut [Option<TokenKind>], range: Range<usize>, kind: TokenKind) {
+    for slot in &mut kinds[range] {
+        slot.get_or_insert(kind);
+    }
+}
+
+fn paint_rule(text: &str, rule: &ParsedRule, kinds: &mut [Option<TokenKind>]) {
+    let source = &text[rule.start..rule.end];
+    if let Expression::Assignment { target, .. } = &rule.expression {
+        if source.starts_with(target.as_str()) {
+            paint(kinds, rule.start..rule.start + target.len(), TokenKind::Variable);
+        }
+    }
+    let Ok((_, (_, mut spans))) = parse_rule_with_spans(source) else {
+        return;
+    };
+
+    // Spans nest, so the innermost is painted last
+    spans.sort_by_key(|span| Reverse(span.end - span.start));
+    for span in spans {
+        let range = rule.start + span.start..rule.start + span.end;
+        let span_text = &text[range.clone()];
+        if span_text.starts_with('`') {
+            paint_template(text, range, kinds);
+            continue;
+        }
+        let kind = match &span.expression {
+            Expression::Literal(Value::String(_) | Value::Regex(_)) => TokenKind::String,
+            Expression::Literal(Value::Boolean(_) | Value::Null) => TokenKind::Keyword,
+            Expression::Literal(
+                Value::Number(_) | Value::Integer(_) | Value::Float(_) | Value::Percent(_) | Value::Money { .. },
+            ) => TokenKind::Number,
+            Expression::Identifier(_) | Expression::Variable(_) => TokenKind::Variable,
+            Expression::FunctionCall { name, .. } if span_text.starts_with(name.as_str()) => {
+                paint(kinds, range.start..range.start + name.len(), TokenKind::Function);
+                continue;
+            }
+            _ => continue,
+        };
+        paint(kinds, range, kind);
+    }
+}
+
+// `text ${expr} text`: the literal text as a string, `${` and `}` as
+// operators, leaving the interpolated expression to its own spans
+fn paint_template(text: &str, range: Range<usize>, kinds: &mut [Option<TokenKind>]) {
+    let bytes = text.as_bytes();
+    let mut segment = range.start;
+    let mut i = range.start + 1;
+    while i < range.end {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'$' if bytes.get(i + 1) == Some(&b'{') => {
+                paint(kinds, segment..i, TokenKind::String);
+                paint(kinds, i..i + 2, TokenKind::Operator);
+                let mut depth = 1;
+                i += 2;
+                while i < range.end && depth > 0 {
+                    match bytes[i] {
+                        b'{' => depth += 1,
+                        b'}' => depth -= 1,
+                        _ => {}
+                    }
+                    i += 1;
+                }
+                if depth == 0 {
+                    paint(kinds, i - 1..i, TokenKind::Operator);
+                }
+                segment = i;
+            }
+            _ => i += 1,
+        }
+    }
+    paint(kinds, segment.min(range.end)..range.end, TokenKind::String);
+}
+
+// Whatever no span or comment covers: keywords, operators, lambda and
+// comprehension variables, and the text of rules that didn't parse
+fn lex_unpainted(text: &str, kinds: &mut [Option<TokenKind>]) {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if kinds[i].is_some() || c.is_whitespace() || "()[]{},;".contains(c) {
+            continue;
+        }
+        let rest = &text[i..];
+        let (end, kind) = if RULE_SYNTAX.quotes.contains(c) {
+            let mut escaped = false;
+            let close = rest[1..].find(|next: char| {
+                let closes = next == c && !escaped;
+                escaped = next == '\\' && !escaped;
+                closes
+            });
+            (close.map_or(text.len(), |close| i + 1 + close + 1), TokenKind::String)
+        } else if c.is_ascii_digit() {
+            let end = rest.find(|next: char| !(next.is_ascii_alphanumeric() || next == '_' || next == '.'));
+            (end.map_or(text.len(), |end| i + end), TokenKind::Number)
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest.find(|next: char| !is_word(next)).map_or(text.len(), |end| i + end);
+            let word = &text[i..end];
+            let kind = if KEYWORDS.contains(&word) {
+                TokenKind::Keyword
+            } else if text[end..].trim_start().starts_with('(') {
+                TokenKind::Function
+            } else {
+                TokenKind::Variable
+            };
+            (end, kind)
+        } else {
+            let end = rest
+                .char_indices()
+                .find(|(offset, next)| {
+                    kinds[i + offset].is_some()
+                        || next.is_whitespace()
+                        || next.is_alphanumeric()
+                        || "()[]{},;_".contains(*next)
+                        || RULE_SYNTAX.quotes.contains(*next)
+                })
+                .map_or(text.len(), |(offset, _)| i + offset);
+            (end.max(i + c.len_utf8()), TokenKind::Operator)
+        };
+        fill(kinds, i..end, kind);
+        while chars.next_if(|(index, _)| *index < end).is_some() {}
+    }
+}
+
+// Runs of one kind, split at line breaks
+fn collect(text: &str, kinds: &[Option<TokenKind>]) -> Vec<SourceToken> {
+    let mut tokens = Vec::new();
+    let mut line_start = 0;
+    for (line, raw_line) in text.split('\n').enumerate() {
+        let line_text = raw_line.strip_suffix('\r').unwrap_or(raw_line);
+        let mut column = 0;
+        while column < line_text.len() {
+            let Some(kind) = kinds[line_start + column] else {
+                column += 1;
+                continue;
+            };
+            let length = kinds[line_start + column..line_start + line_text.len()]
+                .iter()
+                .take_while(|next| **next == Some(kind))
+                .count();
+            tokens.push(SourceToken { line: line as u32, column: column as u32, length: length as u32, kind });
+            column += length;
+        }
+        line_start += raw_line.len() + 1;
+    }
+    tokens
+}
+
+/// Tokens in the relative form `textDocument/semanticTokens` responses use:
+/// five integers each, the line and start relative to the previous token
+pub fn encode(tokens: &[SourceToken]) -> Vec<u32> {
+    let mut data = Vec::with_capacity(tokens.len() * 5);
+    let (mut previous_line, mut previous_column) = (0, 0);
+    for token in tokens {
+        let delta_line = token.line - previous_line;
+        let delta_column = if delta_line == 0 { token.column - previous_column } else { token.column };
+        data.extend([delta_line, delta_column, token.length, token.kind.index(), 0]);
+        previous_line = token.line;
+        previous_column = token.column;
+    }
+    data
+}
+
+/// Replaces `delete_count` integers of the previous encoded tokens from `start` with `data`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenEdit {
+    pub start: u32,
+    pub delete_count: u32,
+    pub data: Vec<u32>,
+}
+
+/// The edit turning encoded tokens `previous` into `current`, keeping the
+/// whole tokens they start and end with in common; None if they're equal
+pub fn diff(previous: &[u32], current: &[u32]) -> Option<TokenEdit> {
+    if previous == current {
+        return None;
+    }
+    let prefix = previous.chunks(5).zip(current.chunks(5)).take_while(|(a, b)| a == b).count() * 5;
+    let suffix =
+        previous[prefix..].rchunks(5).zip(current[prefix..].rchunks(5)).take_while(|(a, b)| a == b).count() * 5;
+    Some(TokenEdit {
+        start: prefix as u32,
+        delete_count: (previous.len() - prefix - suffix) as u32,
+        data: current[prefix..current.len() - suffix].to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spelled(text: &str) -> Vec<(String, TokenKind)> {
+        let lines: Vec<&str> = text.lines().collect();
+        tokenize(text)
+            .into_iter()
+            .map(|token| {
+                let line = lines[token.line as usize];
+                let start = token.column as usize;
+                (line[start..start + token.length as usize].to_string(), token.kind)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_names_are_highlighted_for_what_they_parse_as() {
+        use TokenKind::*;
+        let text = "IS_ACTIVE = IF ORDER_TOTAL > 100 AND in_region THEN LENGTH(END_DATE) ELSE \"IF x\" # checked\n";
+        assert_eq!(
+            spelled(text),
+            vec![
+                ("IS_ACTIVE".to_string(), Variable),
+                ("=".to_string(), Operator),
+                ("IF".to_string(), Keyword),
+                ("ORDER_TOTAL".to_string(), Variable),
+                (">".to_string(), Operator),
+                ("100".to_string(), Number),
+                ("AND".to_string(), Keyword),
+                ("in_region".to_string(), Variable),
+                ("THEN".to_string(), Keyword),
+                ("LENGTH".to_string(), Function),
+                ("END_DATE".to_string(), Variable),
+                ("ELSE".to_string(), Keyword),
+                ("\"IF x\"".to_string(), String),
+                ("# checked".to_string(), Comment),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_templates_lambdas_and_unparsed_text() {
+        use TokenKind::*;
+        let text = "label = `Rate: ${rate * 100}%`\nbig = FILTER(amounts, a -> a > 1_000 USD)\nbroken = (1 +\n";
+        let tokens = spelled(text);
+        for expected in [
+            ("`Rate: ", String),
+            ("${", Operator),
+            ("rate", Variable),
+            ("*", Operator),
+            ("}", Operator),
+            ("%`", String),
+            ("FILTER", Function),
+            ("->", Operator),
+            ("1_000 USD", Number),
+            ("broken", Variable),
+            ("+", Operator),
+        ] {
+            assert!(tokens.contains(&(expected.0.to_string(), expected.1)), "{:?} missing from {:?}", expected, tokens);
+        }
+        let lambda_params = tokens.iter().filter(|(text, kind)| text == "a" && *kind == Variable).count();
+        assert_eq!(lambda_params, 2);
+    }
+
+    #[test]
+    fn test_diff_replaces_only_the_changed_tokens() {
+        let before = encode(&tokenize("a = 1\nb = 2\nc = 3\n"));
+        let after = encode(&tokenize("a = 1\nb = x + 2\nc = 3\n"));
+        let edit = diff(&before, &after).unwrap();
+        // `a = 1` and `b =` are kept
+        assert_eq!(edit.start, 25);
+
+        let mut applied = before.clone();
+        let start = edit.start as usize;
+        applied.splice(start..start + edit.delete_count as usize, edit.data);
+        assert_eq!(applied, after);
+        assert_eq!(diff(&after, &after), None);
+    }
+}
@@ -0,0 +1,330 @@
+//! Nested structure of DSL source text, for editor folding, expand-selection
+//! and bracket matching
+//!
+//! Rule documents and CBU S-expressions both nest in brackets; they differ in
+//! how they write strings, comments and keyword blocks, which a `Syntax`
+//! describes. The scan skips strings and comments, so brackets inside them
+//! don't count, and tolerates unbalanced text as it's being typed; the
+//! brackets it couldn't pair are reported by `unbalanced_delimiters`.
+//! Offsets are bytes; lines are zero-based.
+
+/// What a DSL's source looks like to the scan
+#[derive(Debug, Clone, Copy)]
+pub struct Syntax {
+    pub quotes: &'static str,
+    pub line_comments: &'static [&'static str],
+    pub block_comments: bool,
+    /// Keyword pairs that nest like brackets, such as `CASE` ... `END`
+    pub keyword_blocks: &'static [(&'static str, &'static str)],
+    /// Characters other than letters and digits that belong to a word
+    pub word_chars: &'static str,
+}
+
+/// The rule DSL: `"`, `'` and template strings, `//`, `#` and `/* */` comments
+pub const RULE_SYNTAX: Syntax = Syntax {
+    quotes: "\"'`",
+    line_comments: &["//", "#"],
+    block_comments: true,
+    keyword_blocks: &[("CASE", "END")],
+    word_chars: "_.",
+};
+
+/// The CBU S-expression DSL: `"` strings and `;` comments
+pub const LISP_SYNTAX: Syntax = Syntax {
+    quotes: "\"",
+    line_comments: &[";"],
+    block_comments: false,
+    keyword_blocks: &[],
+    word_chars: "-_?!*:",
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    /// A bracketed group, brackets included
+    Group,
+    /// A keyword block, or a span the caller knows about such as a whole rule
+    Block,
+    /// A comment, consecutive line comments merged
+    Comment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub start: usize,
+    pub end: usize,
+    pub kind: RegionKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fold {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub kind: RegionKind,
+}
+
+/// A bracket the scan couldn't pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnbalancedDelimiter {
+    pub offset: usize,
+    pub delimiter: char,
+    /// An opener never closed, rather than a closer never opened
+    pub unclosed: bool,
+}
+
+impl UnbalancedDelimiter {
+    pub fn message(&self) -> String {
+        if self.unclosed {
+            format!("Unclosed '{}': a '{}' is missing", self.delimiter, closing(self.delimiter).unwrap_or(self.delimiter))
+        } else {
+            format!("Unexpected '{}': no '{}' to close", self.delimiter, opening(self.delimiter).unwrap_or(self.delimiter))
+        }
+    }
+}
+
+/// The groups, keyword blocks and comments of `text`, ordered by start
+pub fn regions(text: &str, syntax: &Syntax) -> Vec<Region> {
+    scan(text, syntax).0
+}
+
+/// The brackets of `text` left without a partner, ordered by offset
+pub fn unbalanced_delimiters(text: &str, syntax: &Syntax) -> Vec<UnbalancedDelimiter> {
+    scan(text, syntax).1
+}
+
+/// The offsets of the bracket at `offset` and its partner, opener first.
+/// A bracket just before `offset` counts too, as editors put the cursor
+/// after the one just typed.
+pub fn matching_delimiters(offset: usize, regions: &[Region]) -> Option<(usize, usize)> {
+    let groups = || regions.iter().filter(|region| region.kind == RegionKind::Group);
+    let at = |offset: usize| groups().find(|group| group.start == offset || group.end - 1 == offset);
+    at(offset)
+        .or_else(|| offset.checked_sub(1).and_then(at))
+        .map(|group| (group.start, group.end - 1))
+}
+
+fn scan(text: &str, syntax: &Syntax) -> (Vec<Region>, Vec<UnbalancedDelimiter>) {
+    let is_word = |c: char| c.is_alphanumeric() || syntax.word_chars.contains(c);
+    let mut regions = Vec::new();
+    let mut comments: Vec<Region> = Vec::new();
+    let mut brackets: Vec<(char, usize)> = Vec::new();
+    let mut keywords: Vec<(usize, usize)> = Vec::new();
+    let mut unbalanced = Vec::new();
+
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let rest = &text[i..];
+        if syntax.quotes.contains(c) {
+            while let Some((_, next)) = chars.next() {
+                if next == c {
+                    break;
+                }
+                if next == '\\' {
+                    chars.next();
+                }
+            }
+        } else if syntax.line_comments.iter().any(|prefix| rest.starts_with(prefix)) {
+            let end = rest.find('\n').map_or(text.len(), |end| i + end);
+            while chars.next_if(|(index, _)| *index < end).is_some() {}
+            // A comment on the line after another one continues it
+            match comments.last_mut() {
+                Some(last) if last.kind == RegionKind::Comment && is_line_break(&text[last.end..i]) => last.end = end,
+                _ => comments.push(Region { start: i, end, kind: RegionKind::Comment }),
+            }
+        } else if syntax.block_comments && rest.starts_with("/*") {
+            let end = rest[2..].find("*/").map_or(text.len(), |end| i + 2 + end + 2);
+            while chars.next_if(|(index, _)| *index < end).is_some() {}
+            // Kept apart from line comments so neighbours don't merge into it
+            comments.push(Region { start: i, end, kind: RegionKind::Block });
+        } else if matches!(c, '(' | '[' | '{') {
+            brackets.push((c, i));
+        } else if let Some(open) = opening(c) {
+            // An unmatched closer is dropped; openers left unclosed inside the group are too
+            match brackets.iter().rposition(|(bracket, _)| *bracket == open) {
+                Some(depth) => {
+                    let (_, start) = brackets[depth];
+                    unbalanced.extend(brackets.drain(depth + 1..).map(|(delimiter, offset)| UnbalancedDelimiter { offset, delimiter, unclosed: true }));
+                    brackets.truncate(depth);
+                    regions.push(Region { start, end: i + 1, kind: RegionKind::Group });
+                }
+                None => unbalanced.push(UnbalancedDelimiter { offset: i, delimiter: c, unclosed: false }),
+            }
+        } else if is_word(c) && !text[..i].ends_with(is_word) {
+            let end = rest.find(|next: char| !is_word(next)).map_or(text.len(), |end| i + end);
+            let word = &text[i..end];
+            if syntax.keyword_blocks.iter().any(|(open, _)| *open == word) {
+                keywords.push((i, end));
+            } else if syntax.keyword_blocks.iter().any(|(_, close)| *close == word) {
+                if let Some((start, _)) = keywords.pop() {
+                    regions.push(Region { start, end, kind: RegionKind::Block });
+                }
+            }
+            while chars.next_if(|(index, _)| *index < end).is_some() {}
+        }
+    }
+
+    regions.extend(comments.into_iter().map(|comment| Region { kind: RegionKind::Comment, ..comment }));
+    regions.sort_by_key(|region| (region.start, std::cmp::Reverse(region.end)));
+    unbalanced.extend(brackets.into_iter().map(|(delimiter, offset)| UnbalancedDelimiter { offset, delimiter, unclosed: true }));
+    unbalanced.sort_by_key(|delimiter| delimiter.offset);
+    (regions, unbalanced)
+}
+
+/// Folds for the regions spanning more than one line. A closing bracket or
+/// keyword on a line of its own stays visible below the fold.
+pub fn folds(text: &str, regions: &[Region]) -> Vec<Fold> {
+    let mut folds: Vec<Fold> = Vec::new();
+    for region in regions {
+        let start_line = line_of(text, region.start);
+        let mut end_line = line_of(text, region.end);
+        let closer = match region.kind {
+            RegionKind::Group => Some(region.end - 1),
+            RegionKind::Block => text[..region.end].rfind(|c: char| !c.is_alphanumeric()).map(|i| i + 1),
+            RegionKind::Comment => None,
+        };
+        if let Some(closer) = closer {
+            let line_start = text[..closer].rfind('\n').map_or(0, |i| i + 1);
+            if text[line_start..closer].trim().is_empty() {
+                end_line = end_line.saturating_sub(1);
+            }
+        }
+        if end_line > start_line && !folds.iter().any(|fold| fold.start_line == start_line && fold.end_line == end_line) {
+            folds.push(Fold { start_line, end_line, kind: region.kind });
+        }
+    }
+    folds
+}
+
+/// Spans to grow a selection at `offset` through, innermost first: the word
+/// there, then each enclosing region, a group's contents before the group
+/// itself, and finally the whole document
+pub fn selection_spans(text: &str, offset: usize, syntax: &Syntax, regions: &[Region]) -> Vec<(usize, usize)> {
+    let is_word = |c: char| c.is_alphanumeric() || syntax.word_chars.contains(c);
+    let offset = offset.min(text.len());
+    let start = text[..offset].rfind(|c: char| !is_word(c)).map_or(0, |i| i + text[i..].chars().next().map_or(1, char::len_utf8));
+    let end = text[offset..].find(|c: char| !is_word(c)).map_or(text.len(), |i| offset + i);
+
+    let mut candidates = vec![(start, end)];
+    for region in regions.iter().filter(|region| region.start <= offset && offset <= region.end) {
+        if region.kind == RegionKind::Group {
+            let inner = &text[region.start + 1..region.end - 1];
+            let leading = inner.len() - inner.trim_start().len();
+            let contents = (region.start + 1 + leading, region.start + 1 + inner.trim_end().len());
+            if contents.0 <= offset && offset <= contents.1 {
+                candidates.push(contents);
+            }
+        }
+        candidates.push((region.start, region.end));
+    }
+    candidates.push((0, text.len()));
+    candidates.sort_by_key(|(start, end)| (end - start, std::cmp::Reverse(*start)));
+
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    for span in candidates {
+        let grows = match spans.last() {
+            Some(&(start, end)) => span.0 <= start && end <= span.1 && span != (start, end),
+            None => span.0 < span.1,
+        };
+        if grows {
+            spans.push(span);
+        }
+    }
+    spans
+}
+
+fn opening(close: char) -> Option<char> {
+    match close {
+        ')' => Some('('),
+        ']' => Some('['),
+        '}' => Some('{'),
+        _ => None,
+    }
+}
+
+fn closing(open: char) -> Option<char> {
+    match open {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        _ => None,
+    }
+}
+
+// Whitespace holding exactly one line break
+fn is_line_break(between: &str) -> bool {
+    between.trim().is_empty() && between.matches('\n').count() == 1
+}
+
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_regions_skip_strings_and_comments() {
+        let text = "// fee rules\n// by tier\nfee = CASE\n    WHEN tier == \"(gold\" THEN ROUND(\n        amount * 0.01, 2)\n    ELSE 0\nEND";
+        let regions = regions(text, &RULE_SYNTAX);
+        let kinds: Vec<(RegionKind, &str)> = regions.iter().map(|region| (region.kind, &text[region.start..region.end])).collect();
+        assert_eq!(kinds[0], (RegionKind::Comment, "// fee rules\n// by tier"));
+        assert_eq!(kinds[1].0, RegionKind::Block);
+        assert!(kinds[1].1.starts_with("CASE") && kinds[1].1.ends_with("END"));
+        assert_eq!(kinds[2], (RegionKind::Group, "(\n        amount * 0.01, 2)"));
+        assert_eq!(kinds.len(), 3);
+
+        let folds = folds(text, &regions);
+        assert_eq!(
+            folds,
+            [
+                Fold { start_line: 0, end_line: 1, kind: RegionKind::Comment },
+                Fold { start_line: 2, end_line: 5, kind: RegionKind::Block },
+                Fold { start_line: 3, end_line: 4, kind: RegionKind::Group },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_s_expression_groups_fold_and_grow_the_selection() {
+        let text = "(create-cbu \"Fund ;A\"\n  (entities\n    (entity \"E1\" asset-owner)\n    (entity \"E2\" custodian)))";
+        let regions = regions(text, &LISP_SYNTAX);
+        assert_eq!(regions.len(), 4);
+        let folds = folds(text, &regions);
+        assert_eq!(folds.iter().map(|fold| (fold.start_line, fold.end_line)).collect::<Vec<_>>(), [(0, 3), (1, 3)]);
+
+        let offset = text.find("asset-owner").unwrap() + 3;
+        let spans: Vec<&str> = selection_spans(text, offset, &LISP_SYNTAX, &regions)
+            .into_iter()
+            .map(|(start, end)| &text[start..end])
+            .collect();
+        assert_eq!(spans[0], "asset-owner");
+        assert_eq!(spans[1], "entity \"E1\" asset-owner");
+        assert_eq!(spans[2], "(entity \"E1\" asset-owner)");
+        assert!(spans[3].starts_with("entities"));
+        assert_eq!(*spans.last().unwrap(), text);
+    }
+
+    #[test]
+    fn test_unbalanced_brackets_are_located_and_pairs_matched() {
+        let text = "(create-cbu \"F)\"\n  (entities\n    (entity \"E1\" asset-owner)\n    (entity \"E2\" custodian)\n";
+        let unbalanced = unbalanced_delimiters(text, &LISP_SYNTAX);
+        let offsets: Vec<usize> = unbalanced.iter().map(|delimiter| delimiter.offset).collect();
+        assert_eq!(offsets, [0, text.find("(entities").unwrap()]);
+        assert!(unbalanced.iter().all(|delimiter| delimiter.unclosed));
+        assert_eq!(unbalanced[1].message(), "Unclosed '(': a ')' is missing");
+
+        let stray = unbalanced_delimiters("(a (b)) c)\n; (", &LISP_SYNTAX);
+        assert_eq!(stray, [UnbalancedDelimiter { offset: 9, delimiter: ')', unclosed: false }]);
+        assert_eq!(stray[0].message(), "Unexpected ')': no '(' to close");
+        let inner = unbalanced_delimiters("[a (b]", &RULE_SYNTAX);
+        assert_eq!(inner, [UnbalancedDelimiter { offset: 3, delimiter: '(', unclosed: true }]);
+        assert!(unbalanced_delimiters("(a \"(\" [b])", &RULE_SYNTAX).is_empty());
+
+        let text = "(a (b c) d)";
+        let regions = regions(text, &LISP_SYNTAX);
+        assert_eq!(matching_delimiters(3, &regions), Some((3, 7)));
+        assert_eq!(matching_delimiters(8, &regions), Some((3, 7)));
+        assert_eq!(matching_delimiters(11, &regions), Some((0, 10)));
+        assert_eq!(matching_delimiters(5, &regions), None);
+    }
+}
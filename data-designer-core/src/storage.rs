@@ -0,0 +1,209 @@
+//! Storage abstraction between the rule engine and where rules live
+//!
+//! The parser, evaluator and transpiler need no database. Everything that
+//! talks to Postgres is compiled only with the `postgres` feature (on by
+//! default); without it the crate builds without sqlx, for embedded and
+//! WASM-adjacent consumers. Code that stores or loads rules goes through
+//! `RuleStore`, implemented for Postgres by `db::PostgresRuleStore` and in
+//! memory by `InMemoryRuleStore`.
+
+use crate::rule_history::{validate_effective_period, versions_in_effect, RuleVersion};
+use crate::rule_variants::Applicability;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// The database connection pool. Without the `postgres` feature there is no
+/// database and the type has no values, so an `Option<DbPool>` is always `None`.
+#[cfg(feature = "postgres")]
+pub use crate::db::DbPool;
+#[cfg(not(feature = "postgres"))]
+pub type DbPool = std::convert::Infallible;
+
+/// A stored rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "postgres", derive(sqlx::FromRow))]
+pub struct Rule {
+    pub id: i32,
+    pub rule_id: String,
+    pub rule_name: String,
+    pub description: Option<String>,
+    pub category_id: Option<i32>,
+    pub target_attribute_id: Option<i32>,
+    pub rule_definition: String,
+    pub parsed_ast: Option<serde_json::Value>,
+    pub status: String,
+    pub version: i32,
+    pub tags: Option<Vec<String>>,
+    pub performance_metrics: Option<serde_json::Value>,
+    pub embedding_data: Option<serde_json::Value>,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_by: Option<String>,
+    pub updated_at: DateTime<Utc>,
+    #[cfg_attr(feature = "postgres", sqlx(default))]
+    pub valid_from: Option<DateTime<Utc>>,
+    #[cfg_attr(feature = "postgres", sqlx(default))]
+    pub valid_to: Option<DateTime<Utc>>,
+}
+
+/// Tag names are stored trimmed and lower-cased
+pub fn normalize_tag(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// Where rules and their version history are kept
+#[async_trait]
+pub trait RuleStore: Send + Sync {
+    /// Every stored rule, ordered by rule id
+    async fn list_rules(&self) -> Result<Vec<Rule>, String>;
+
+    async fn get_rule(&self, rule_id: &str) -> Result<Option<Rule>, String>;
+
+    /// Stores the rule as its next version and returns that version number
+    async fn save_rule(&self, rule: Rule) -> Result<i32, String>;
+
+    /// Every saved version of a rule, newest first
+    async fn rule_history(&self, rule_id: &str) -> Result<Vec<RuleVersion>, String>;
+
+    /// The version of every rule in effect at `at`
+    async fn rules_as_of(&self, at: DateTime<Utc>) -> Result<Vec<RuleVersion>, String> {
+        let mut versions = Vec::new();
+        for rule in self.list_rules().await? {
+            versions.extend(self.rule_history(&rule.rule_id).await?);
+        }
+        Ok(versions_in_effect(&versions, at).into_iter().cloned().collect())
+    }
+}
+
+/// Rules kept in memory, for tests and contexts without a database
+#[derive(Default)]
+pub struct InMemoryRuleStore {
+    state: Mutex<InMemoryRules>,
+}
+
+#[derive(Default)]
+struct InMemoryRules {
+    rules: BTreeMap<String, Rule>,
+    versions: Vec<RuleVersion>,
+}
+
+impl InMemoryRuleStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RuleStore for InMemoryRuleStore {
+    async fn list_rules(&self) -> Result<Vec<Rule>, String> {
+        Ok(self.state.lock().unwrap().rules.values().cloned().collect())
+    }
+
+    async fn get_rule(&self, rule_id: &str) -> Result<Option<Rule>, String> {
+        Ok(self.state.lock().unwrap().rules.get(rule_id).cloned())
+    }
+
+    async fn save_rule(&self, mut rule: Rule) -> Result<i32, String> {
+        validate_effective_period(rule.valid_from, rule.valid_to)?;
+        let mut state = self.state.lock().unwrap();
+        let now = Utc::now();
+        let next_id = state.rules.len() as i32 + 1;
+        let change = match state.rules.get(&rule.rule_id) {
+            Some(existing) => {
+                rule.id = existing.id;
+                rule.version = existing.version + 1;
+                rule.created_by = existing.created_by.clone();
+                rule.created_at = existing.created_at;
+                "Saved"
+            }
+            None => {
+                rule.id = next_id;
+                rule.version = 1;
+                rule.created_at = now;
+                "Created"
+            }
+        };
+        rule.updated_at = now;
+
+        state.versions.push(RuleVersion {
+            rule_id: rule.rule_id.clone(),
+            version: rule.version,
+            rule_name: rule.rule_name.clone(),
+            description: rule.description.clone(),
+            rule_definition: rule.rule_definition.clone(),
+            status: rule.status.clone(),
+            change_description: Some(change.to_string()),
+            created_by: rule.updated_by.clone().or_else(|| rule.created_by.clone()),
+            created_at: now,
+            valid_from: rule.valid_from,
+            valid_to: rule.valid_to,
+            applies_to: Applicability::default(),
+        });
+        let version = rule.version;
+        state.rules.insert(rule.rule_id.clone(), rule);
+        Ok(version)
+    }
+
+    async fn rule_history(&self, rule_id: &str) -> Result<Vec<RuleVersion>, String> {
+        let state = self.state.lock().unwrap();
+        Ok(state.versions.iter().rev().filter(|version| version.rule_id == rule_id).cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(rule_id: &str, definition: &str) -> Rule {
+        Rule {
+            id: 0,
+            rule_id: rule_id.to_string(),
+            rule_name: rule_id.to_string(),
+            description: None,
+            category_id: None,
+            target_attribute_id: None,
+            rule_definition: definition.to_string(),
+            parsed_ast: None,
+            status: "active".to_string(),
+            version: 0,
+            tags: None,
+            performance_metrics: None,
+            embedding_data: None,
+            created_by: Some("tester".to_string()),
+            created_at: Utc::now(),
+            updated_by: None,
+            updated_at: Utc::now(),
+            valid_from: None,
+            valid_to: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_store_versions_every_save() {
+        let store = InMemoryRuleStore::new();
+        assert_eq!(store.save_rule(rule("fee", "fee = volume * 2")).await.unwrap(), 1);
+        assert_eq!(store.save_rule(rule("tax", "tax = fee * 0.2")).await.unwrap(), 1);
+
+        let saved_at = Utc::now();
+        let scheduled = Rule { valid_from: Some(saved_at + chrono::Duration::days(30)), ..rule("fee", "fee = volume * 3") };
+        assert_eq!(store.save_rule(scheduled).await.unwrap(), 2);
+
+        let stored = store.get_rule("fee").await.unwrap().unwrap();
+        assert_eq!((stored.id, stored.version, stored.rule_definition.as_str()), (1, 2, "fee = volume * 3"));
+        let history = store.rule_history("fee").await.unwrap();
+        assert_eq!(history.iter().map(|v| v.version).collect::<Vec<_>>(), vec![2, 1]);
+
+        // The scheduled version isn't in effect yet
+        let now: Vec<_> = store.rules_as_of(saved_at).await.unwrap().into_iter().map(|v| (v.rule_id, v.version)).collect();
+        assert_eq!(now, vec![("fee".to_string(), 1), ("tax".to_string(), 1)]);
+        let later = store.rules_as_of(saved_at + chrono::Duration::days(31)).await.unwrap();
+        assert_eq!(later[0].version, 2);
+
+        let backwards = Rule { valid_from: Some(saved_at), valid_to: Some(saved_at), ..rule("fee", "fee = 1") };
+        assert!(store.save_rule(backwards).await.is_err());
+        assert_eq!(store.list_rules().await.unwrap().len(), 2);
+    }
+}
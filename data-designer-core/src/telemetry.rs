@@ -0,0 +1,178 @@
+//! Tracing subscriber setup with optional OpenTelemetry export
+//!
+//! The engine, db layer and server handlers emit ordinary `tracing` spans.
+//! `init` installs a stdout formatter and, in builds with the `otel` feature
+//! and `[telemetry] enabled = true`, OTLP exporters for those spans and for
+//! the `histogram.*` / `monotonic_counter.*` fields of metric events. A
+//! `TraceExporter` passed to `init` also gets the rule evaluation metrics.
+
+use crate::config::TelemetryConfig;
+use crate::trace_export::TraceExporter;
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use tracing::Subscriber;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Header carrying the caller's trace id (the test harness `TraceId`) into server spans
+pub const TRACE_ID_HEADER: &str = "x-trace-id";
+
+/// Stable, non-reversible CBU id for span attributes, so traces can be
+/// correlated per CBU without exporting client identifiers
+pub fn hash_cbu_id(cbu_id: &str) -> String {
+    let digest = Sha256::digest(cbu_id.as_bytes());
+    hex::encode(&digest[..8])
+}
+
+/// Flushes and shuts down the exporters when dropped; keep it alive for the
+/// lifetime of the process
+#[derive(Default)]
+pub struct TelemetryGuard {
+    #[cfg(feature = "otel")]
+    tracer_installed: bool,
+    #[cfg(feature = "otel")]
+    meter_provider: Option<opentelemetry_sdk::metrics::SdkMeterProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        {
+            if self.tracer_installed {
+                opentelemetry::global::shutdown_tracer_provider();
+            }
+            if let Some(meter_provider) = self.meter_provider.take() {
+                let _ = meter_provider.shutdown();
+            }
+        }
+    }
+}
+
+/// Install the global tracing subscriber. `RUST_LOG` controls what is
+/// printed; exported spans and metrics are filtered at DEBUG.
+pub fn init(config: &TelemetryConfig, trace_exporter: Option<&TraceExporter>) -> Result<TelemetryGuard> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(filter))
+        .with(trace_exporter.and_then(|exporter| exporter.metrics_layer()));
+    install(subscriber, config)
+}
+
+#[cfg(not(feature = "otel"))]
+fn install<S>(subscriber: S, config: &TelemetryConfig) -> Result<TelemetryGuard>
+where
+    S: Subscriber + for<'span> LookupSpan<'span> + Send + Sync + 'static,
+{
+    subscriber.try_init()?;
+    if config.enabled {
+        tracing::warn!("[telemetry] enabled is set but this build has no OTLP support; rebuild with --features otel");
+    }
+    Ok(TelemetryGuard::default())
+}
+
+#[cfg(feature = "otel")]
+fn install<S>(subscriber: S, config: &TelemetryConfig) -> Result<TelemetryGuard>
+where
+    S: Subscriber + for<'span> LookupSpan<'span> + Send + Sync + 'static,
+{
+    use opentelemetry::trace::TracerProvider as _;
+    use tracing_subscriber::filter::LevelFilter;
+
+    if !config.enabled {
+        subscriber.try_init()?;
+        return Ok(TelemetryGuard::default());
+    }
+
+    let tracer_provider = otlp::tracer_provider(config)?;
+    let tracer = tracer_provider.tracer(config.service_name.clone());
+    opentelemetry::global::set_tracer_provider(tracer_provider);
+
+    let meter_provider = if config.export_metrics {
+        Some(otlp::meter_provider(config)?)
+    } else {
+        None
+    };
+
+    subscriber
+        .with(
+            tracing_opentelemetry::layer()
+                .with_tracer(tracer)
+                .with_filter(LevelFilter::DEBUG),
+        )
+        .with(
+            meter_provider
+                .clone()
+                .map(|provider| tracing_opentelemetry::MetricsLayer::new(provider).with_filter(LevelFilter::DEBUG)),
+        )
+        .try_init()?;
+
+    tracing::info!(endpoint = %config.otlp_endpoint, "Exporting telemetry over OTLP");
+    Ok(TelemetryGuard {
+        tracer_installed: true,
+        meter_provider,
+    })
+}
+
+#[cfg(feature = "otel")]
+mod otlp {
+    use crate::config::TelemetryConfig;
+    use anyhow::{Context, Result};
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::metrics::SdkMeterProvider;
+    use opentelemetry_sdk::trace::{self, Sampler, TracerProvider};
+    use opentelemetry_sdk::{runtime, Resource};
+    use std::time::Duration;
+
+    fn resource(config: &TelemetryConfig) -> Resource {
+        Resource::new(vec![KeyValue::new("service.name", config.service_name.clone())])
+    }
+
+    pub(super) fn tracer_provider(config: &TelemetryConfig) -> Result<TracerProvider> {
+        let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio)));
+        opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(config.otlp_endpoint.clone()),
+            )
+            .with_trace_config(
+                trace::Config::default()
+                    .with_sampler(sampler)
+                    .with_resource(resource(config)),
+            )
+            .install_batch(runtime::Tokio)
+            .context("Failed to install OTLP span exporter")
+    }
+
+    pub(super) fn meter_provider(config: &TelemetryConfig) -> Result<SdkMeterProvider> {
+        opentelemetry_otlp::new_pipeline()
+            .metrics(runtime::Tokio)
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(config.otlp_endpoint.clone()),
+            )
+            .with_period(Duration::from_secs(config.metrics_interval_seconds))
+            .with_resource(resource(config))
+            .build()
+            .context("Failed to install OTLP metrics exporter")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cbu_id_hash_is_stable_and_opaque() {
+        let hashed = hash_cbu_id("CBU-000123");
+        assert_eq!(hashed, hash_cbu_id("CBU-000123"));
+        assert_ne!(hashed, hash_cbu_id("CBU-000124"));
+        assert_eq!(hashed.len(), 16);
+        assert!(!hashed.contains("000123"));
+    }
+}
@@ -127,4 +127,302 @@ mod tests {
 
         assert_eq!(result.get("full_name"), Some(&Value::String("John Doe".to_string())));
     }
-}
\ No newline at end of file
+
+    fn bundle_of(definitions: &[(&str, &str)]) -> crate::rule_bundle::RuleBundle {
+        use crate::rule_repository::{ExportedRule, RuleMetadata};
+        use crate::rule_variants::Applicability;
+
+        crate::rule_bundle::RuleBundle::new(definitions.iter().map(|(id, definition)| ExportedRule {
+            metadata: RuleMetadata {
+                rule_id: id.to_string(),
+                rule_name: id.to_string(),
+                description: None,
+                category_id: None,
+                target_attribute_id: None,
+                status: "active".to_string(),
+                version: 1,
+                tags: vec![],
+                valid_from: None,
+                valid_to: None,
+                applies_to: Applicability::default(),
+            },
+            definition: definition.to_string(),
+            tests: vec![],
+        }).collect())
+    }
+
+    fn empty_dictionary() -> DataDictionary {
+        DataDictionary {
+            datasets: vec![],
+            lookup_tables: HashMap::new(),
+            canonical_models: vec![],
+            derived_attributes: vec![],
+            solicitation_packs: vec![],
+            axes: vec![],
+        }
+    }
+
+    #[test]
+    fn test_engine_loads_signed_bundle() {
+        use crate::config::{SecurityConfig, TrustedKey};
+        use ed25519_dalek::SigningKey;
+
+        let signing_key = SigningKey::from_bytes(&[3; 32]);
+        let security = SecurityConfig {
+            require_signed_bundles: true,
+            trusted_keys: vec![TrustedKey {
+                name: "prod".to_string(),
+                public_key: crate::rule_bundle::public_key_hex(&signing_key),
+            }],
+            ..Default::default()
+        };
+        let bundle = bundle_of(&[("subtotal", "subtotal = price * quantity"), ("total", "total = subtotal + 5")]);
+
+        let mut engine = RulesEngine::new(empty_dictionary()).unwrap();
+        assert!(engine.load_unsigned_bundle(bundle.clone(), &security).is_err());
+
+        let mut tampered = bundle.sign("prod", &signing_key).unwrap();
+        tampered.payload = tampered.payload.replace("+ 5", "+ 500");
+        assert!(engine.load_bundle(&tampered, &security).is_err());
+
+        let signed = bundle.sign("prod", &signing_key).unwrap();
+        assert_eq!(engine.load_bundle(&signed, &security).unwrap(), 2);
+
+        let mut facts = HashMap::new();
+        facts.insert("price".to_string(), Value::Integer(10));
+        facts.insert("quantity".to_string(), Value::Integer(3));
+        let result = engine.evaluate_chain(&["total".to_string()], &facts).unwrap();
+        assert_eq!(result.get("total"), Some(&Value::Integer(35)));
+    }
+
+    #[test]
+    fn test_fail_soft_chain_keeps_the_rest_of_the_row() {
+        let security = crate::config::SecurityConfig::default();
+        let bundle = bundle_of(&[
+            ("ratio", "ratio = fee / volume"),
+            ("scaled", "scaled = ratio * 100"),
+            ("label", "label = UPPER(name)"),
+        ]);
+        let mut engine = RulesEngine::new(empty_dictionary()).unwrap();
+        engine.load_unsigned_bundle(bundle, &security).unwrap();
+
+        let mut facts = HashMap::new();
+        facts.insert("fee".to_string(), Value::Integer(10));
+        facts.insert("volume".to_string(), Value::Integer(0));
+        facts.insert("name".to_string(), Value::String("acme".to_string()));
+        let targets = ["scaled".to_string(), "label".to_string()];
+        assert!(engine.evaluate_chain(&targets, &facts).is_err());
+
+        let (result, errors) = engine.evaluate_chain_fail_soft(&targets, &facts).unwrap();
+        assert_eq!(result.get("label"), Some(&Value::String("ACME".to_string())));
+        assert!(!result.contains_key("ratio") && !result.contains_key("scaled"));
+        // `scaled` fails because `ratio` did, without an error of its own
+        assert_eq!(errors.keys().collect::<Vec<_>>(), vec!["ratio"]);
+        assert_eq!(errors["ratio"][0].message, "Division by zero");
+    }
+
+    #[test]
+    fn test_evaluate_all_runs_rules_in_dependency_order() {
+        let security = crate::config::SecurityConfig::default();
+        let bundle = bundle_of(&[
+            ("total", "total = subtotal + tax"),
+            ("tax", "tax = subtotal * 0.5"),
+            ("subtotal", "subtotal = price * quantity"),
+        ]);
+        let mut engine = RulesEngine::new(empty_dictionary()).unwrap();
+        engine.load_unsigned_bundle(bundle, &security).unwrap();
+
+        let mut facts = HashMap::new();
+        facts.insert("price".to_string(), Value::Integer(10));
+        facts.insert("quantity".to_string(), Value::Integer(3));
+        let result = engine.evaluate_all(&facts).unwrap();
+        assert_eq!(result.get("subtotal"), Some(&Value::Integer(30)));
+        assert_eq!(result.get("total"), Some(&Value::Float(45.0)));
+
+        let cyclic = bundle_of(&[("a", "a = b + 1"), ("b", "b = a * 2")]);
+        let error = engine.load_unsigned_bundle(cyclic, &security).unwrap_err();
+        assert_eq!(format!("{:#}", error), "Refusing to load rule bundle: Circular rule dependency: a -> b -> a");
+        // The previous rule set stays active
+        assert!(engine.evaluate_all(&facts).unwrap().contains_key("total"));
+    }
+
+    #[test]
+    fn test_materialize_records_the_inputs_of_each_derivation() {
+        let security = crate::config::SecurityConfig::default();
+        let bundle = bundle_of(&[
+            ("country_risk", r#"country_risk = IF domicile_country IN ["IR", "KP"] THEN "HIGH" ELSE "LOW""#),
+            ("risk_rating", r#"risk_rating = IF country_risk == "HIGH" OR is_pep THEN "HIGH" ELSE "STANDARD""#),
+        ]);
+        let mut engine = RulesEngine::new(empty_dictionary()).unwrap();
+        engine.load_unsigned_bundle(bundle, &security).unwrap();
+
+        let facts = HashMap::from([
+            ("domicile_country".to_string(), Value::String("IR".to_string())),
+            ("is_pep".to_string(), Value::Boolean(false)),
+        ]);
+        let (result, materialization) = engine.materialize(&facts).unwrap();
+        assert_eq!(result["risk_rating"], Value::String("HIGH".to_string()));
+
+        let rating = materialization.derivation("risk_rating").unwrap();
+        assert_eq!((rating.rule_id.as_str(), rating.rule_version), ("risk_rating", 1));
+        let inputs: Vec<(&str, bool)> = rating.inputs.iter().map(|i| (i.attribute.as_str(), i.derived)).collect();
+        assert_eq!(inputs, vec![("country_risk", true), ("is_pep", false)]);
+        assert_eq!(
+            materialization.source_values().into_keys().collect::<Vec<_>>(),
+            vec!["domicile_country", "is_pep"]
+        );
+    }
+
+    #[test]
+    fn test_evaluate_batch_isolates_failing_contexts() {
+        let security = crate::config::SecurityConfig::default();
+        let bundle = bundle_of(&[("ratio", "ratio = fee / volume"), ("label", "label = UPPER(name)")]);
+        let mut engine = RulesEngine::new(empty_dictionary()).unwrap();
+        engine.load_unsigned_bundle(bundle, &security).unwrap();
+
+        let contexts: Vec<HashMap<String, serde_json::Value>> = [(10, 2, "acme"), (10, 0, "globex"), (9, 3, "initech")]
+            .iter()
+            .map(|(fee, volume, name)| HashMap::from([
+                ("fee".to_string(), serde_json::json!(fee)),
+                ("volume".to_string(), serde_json::json!(volume)),
+                ("name".to_string(), serde_json::json!(name)),
+            ]))
+            .collect();
+        let results = engine.evaluate_batch(&contexts);
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap()["ratio"], serde_json::json!(5.0));
+        assert!(format!("{:#}", results[1].as_ref().unwrap_err()).contains("Division by zero"));
+        assert_eq!(results[2].as_ref().unwrap()["label"], serde_json::json!("INITECH"));
+    }
+
+    #[test]
+    fn test_registered_functions_are_callable_from_loaded_rules() {
+        let security = crate::config::SecurityConfig::default();
+        let bundle = bundle_of(&[("flagged", "flagged = SCREEN_SANCTIONS(name, country)")]);
+        let mut engine = RulesEngine::new(empty_dictionary()).unwrap();
+        engine.load_unsigned_bundle(bundle, &security).unwrap();
+
+        let facts = HashMap::from([
+            ("name".to_string(), Value::String("ACME".to_string())),
+            ("country".to_string(), Value::String("IR".to_string())),
+        ]);
+        assert!(engine.evaluate_all(&facts).is_err());
+
+        // Registering after the bundle loaded still reaches its rules
+        engine
+            .register_function("screen_sanctions", 2, |args| Ok(serde_json::json!(args[1] == "IR")))
+            .unwrap();
+        assert_eq!(engine.evaluate_all(&facts).unwrap()["flagged"], Value::Boolean(true));
+        assert_eq!(engine.function_registry().functions()[0].signature, "SCREEN_SANCTIONS(arg1, arg2)");
+        assert!(engine.register_function("CONCAT", 2, |_| Ok(serde_json::Value::Null)).is_err());
+    }
+
+    #[test]
+    fn test_evaluate_as_of_uses_the_version_in_effect() {
+        use chrono::{DateTime, Utc};
+
+        let at = |date: &str| DateTime::parse_from_rfc3339(&format!("{}T00:00:00Z", date)).unwrap().with_timezone(&Utc);
+        let security = crate::config::SecurityConfig::default();
+        let mut bundle = bundle_of(&[
+            ("fee", "fee = volume * 2"),
+            ("fee", "fee = volume * 3"),
+            ("fee", "fee = volume"),
+            ("discount", "discount = volume / 2"),
+        ]);
+        let dated = [
+            (1, None, Some(at("2025-01-01")), "active"),
+            (2, Some(at("2025-01-01")), None, "active"),
+            (3, Some(at("2025-07-01")), None, "deprecated"),
+        ];
+        for (rule, (version, valid_from, valid_to, status)) in bundle.rules.iter_mut().filter(|r| r.metadata.rule_id == "fee").zip(dated) {
+            rule.metadata.version = version;
+            rule.metadata.valid_from = valid_from;
+            rule.metadata.valid_to = valid_to;
+            rule.metadata.status = status.to_string();
+        }
+        let mut engine = RulesEngine::new(empty_dictionary()).unwrap();
+        assert_eq!(engine.load_unsigned_bundle(bundle, &security).unwrap(), 4);
+
+        let facts = HashMap::from([("volume".to_string(), Value::Integer(10))]);
+        let fee_on = |date: &str| engine.evaluate_as_of(at(date), &facts).unwrap().get("fee").cloned();
+        assert_eq!(fee_on("2024-06-30"), Some(Value::Integer(20)));
+        assert_eq!(fee_on("2025-03-31"), Some(Value::Integer(30)));
+        // Deprecated from July: the rule no longer runs, the others still do
+        assert_eq!(fee_on("2025-07-01"), None);
+        assert_eq!(engine.evaluate_as_of(at("2025-07-01"), &facts).unwrap()["discount"], Value::Float(5.0));
+    }
+
+    #[test]
+    fn test_evaluation_picks_the_variant_for_the_context() {
+        use crate::rule_variants::Applicability;
+
+        let security = crate::config::SecurityConfig::default();
+        let mut bundle = bundle_of(&[
+            ("fee", "fee = volume * 2"),
+            ("fee_de", "fee = volume * 3"),
+            ("fee_custody", "fee = volume * 4"),
+        ]);
+        for rule in &mut bundle.rules {
+            rule.metadata.applies_to = match rule.metadata.rule_id.as_str() {
+                "fee_de" => Applicability { jurisdiction: Some("DE".to_string()), ..Applicability::default() },
+                "fee_custody" => Applicability { product_line: Some("custody".to_string()), ..Applicability::default() },
+                _ => Applicability::default(),
+            };
+        }
+        let mut engine = RulesEngine::new(empty_dictionary()).unwrap();
+        engine.load_unsigned_bundle(bundle, &security).unwrap();
+
+        let fee_for = |jurisdiction: &str, product_line: &str| {
+            let facts = HashMap::from([
+                ("volume".to_string(), Value::Integer(10)),
+                ("jurisdiction".to_string(), Value::String(jurisdiction.to_string())),
+                ("product_line".to_string(), Value::String(product_line.to_string())),
+            ]);
+            engine.evaluate_all(&facts).map(|facts| facts["fee"].clone())
+        };
+        assert_eq!(fee_for("DE", "funds").unwrap(), Value::Integer(30));
+        assert_eq!(fee_for("FR", "custody").unwrap(), Value::Integer(40));
+        assert_eq!(fee_for("FR", "funds").unwrap(), Value::Integer(20));
+        let error = fee_for("DE", "custody").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Rule variants fee_custody, fee_de of 'fee' all apply to jurisdiction DE, product_line custody"
+        );
+
+        let conflicts = engine.variant_conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].rule_ids, ["fee_custody", "fee_de"]);
+    }
+
+    #[test]
+    fn test_coverage_report_finds_dead_rules_branches_and_attributes() {
+        let security = crate::config::SecurityConfig::default();
+        let bundle = bundle_of(&[
+            ("fee", "fee = IF volume > 1000 THEN volume * 0.01 ELSE 10"),
+            ("surcharge", "surcharge = IF fee > 100 THEN fee * 0.1"),
+            ("ratio", "ratio = fee / volume"),
+        ]);
+        let mut engine = RulesEngine::new(empty_dictionary()).unwrap();
+        engine.load_unsigned_bundle(bundle, &security).unwrap();
+
+        let contexts: Vec<HashMap<String, serde_json::Value>> = [50, 0, 200]
+            .iter()
+            .map(|volume| HashMap::from([
+                ("volume".to_string(), serde_json::json!(volume)),
+                ("region".to_string(), serde_json::json!("EU")),
+            ]))
+            .collect();
+        let report = engine.coverage_report(&contexts);
+
+        assert_eq!(report.contexts, 3);
+        let ratio = report.rules.iter().find(|r| r.rule == "ratio").unwrap();
+        assert_eq!((ratio.evaluated, ratio.fired, ratio.failed), (3, 2, 1));
+        // An IF without ELSE gives null when its condition fails, so surcharge never fired
+        assert_eq!(report.never_fired, vec!["surcharge"]);
+        let untaken: Vec<(&str, &str)> =
+            report.untaken_branches.iter().map(|b| (b.rule.as_str(), b.arm.as_str())).collect();
+        assert_eq!(untaken, vec![("fee", "THEN"), ("surcharge", "THEN")]);
+        assert_eq!(report.unreferenced_attributes, vec!["region"]);
+    }
+}
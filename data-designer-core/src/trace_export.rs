@@ -0,0 +1,378 @@
+//! Export of evaluation traces to Elasticsearch for production debugging
+//!
+//! With `[trace_export] enabled = true`, explain traces and the engine's
+//! per-rule evaluation metrics are indexed as documents in the test harness's
+//! event format (`TestEvent`): the configured `source` is the documents'
+//! `test_run_id` and the caller's `x-trace-id` their `trace_id`. Support can
+//! then load a production request with the same `RequestTrace` tooling the
+//! integration tests use (`ElasticsearchTestClient::for_exported_traces`).
+//!
+//! Documents are queued and sent with the bulk API a chunk at a time. Export
+//! never holds up evaluation: when the queue is full, documents are dropped
+//! and counted instead.
+
+use crate::batch_writer::BatchBuffer;
+use crate::config::{BatchWriteConfig, TraceExportConfig};
+use crate::evaluator::TraceNode;
+use anyhow::{bail, Context as _, Result};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value as JsonValue};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// The component exported documents are filed under
+pub const COMPONENT: &str = "rules-engine";
+
+/// Field mappings shared with the test harness's indices; `term` queries on
+/// ids need them as keywords rather than analysed text
+pub fn index_mappings() -> JsonValue {
+    json!({
+        "properties": {
+            "timestamp": { "type": "date" },
+            "test_run_id": { "type": "keyword" },
+            "trace_id": { "type": "keyword" },
+            "test_name": { "type": "keyword" },
+            "component": { "type": "keyword" },
+            "event_type": { "type": "keyword" },
+            "level": { "type": "keyword" },
+            "message": { "type": "text" },
+            "data": { "type": "object" },
+            "duration_ms": { "type": "long" }
+        }
+    })
+}
+
+/// An explain trace as one `EngineExecution` event; the whole tree goes in
+/// `data.trace`, and a trace whose rule failed is logged at `Error`
+pub fn explain_event(source: &str, trace_id: &str, rule: &str, trace: &TraceNode, at: DateTime<Utc>) -> JsonValue {
+    json!({
+        "timestamp": at,
+        "test_run_id": source,
+        "trace_id": trace_id,
+        "test_name": "explain",
+        "component": COMPONENT,
+        "event_type": "EngineExecution",
+        "level": if trace.error.is_some() { "Error" } else { "Info" },
+        "message": match &trace.error {
+            Some(error) => format!("{} failed: {}", trace.expression, error),
+            None => format!("Explained {}", trace.expression),
+        },
+        "data": { "rule": rule, "trace": trace },
+        "duration_ms": trace.duration_us / 1000,
+    })
+}
+
+/// One rule evaluation as a `Performance` event
+pub fn metric_event(source: &str, trace_id: &str, rule_id: &str, duration_ms: f64, ok: bool, at: DateTime<Utc>) -> JsonValue {
+    json!({
+        "timestamp": at,
+        "test_run_id": source,
+        "trace_id": trace_id,
+        "test_name": "rule.evaluate",
+        "component": COMPONENT,
+        "event_type": "Performance",
+        "level": if ok { "Info" } else { "Error" },
+        "message": format!("Evaluated rule {}", rule_id),
+        "data": { "rule_id": rule_id, "duration_ms": duration_ms, "ok": ok },
+        "duration_ms": duration_ms.round() as u64,
+    })
+}
+
+/// The index a document belongs to, by the month of its timestamp
+pub fn index_name(prefix: &str, at: DateTime<Utc>) -> String {
+    format!("{}-{}", prefix, at.format("%Y-%m"))
+}
+
+/// A `_bulk` request body indexing each document
+pub fn bulk_body(prefix: &str, documents: &[JsonValue]) -> String {
+    let mut body = String::new();
+    for document in documents {
+        let at = document["timestamp"]
+            .as_str()
+            .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+            .map_or_else(Utc::now, |at| at.with_timezone(&Utc));
+        body.push_str(&json!({ "index": { "_index": index_name(prefix, at) } }).to_string());
+        body.push('\n');
+        body.push_str(&document.to_string());
+        body.push('\n');
+    }
+    body
+}
+
+/// Queues documents for the export task; cheap to clone
+#[derive(Clone)]
+pub struct TraceExporter {
+    sender: mpsc::Sender<JsonValue>,
+    source: Arc<str>,
+    export_metrics: bool,
+    dropped: Arc<AtomicU64>,
+}
+
+impl TraceExporter {
+    /// Starts the export task; None when export is disabled. Must be called
+    /// inside a tokio runtime.
+    pub fn spawn(config: &TraceExportConfig) -> Result<Option<(Self, JoinHandle<()>)>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let batching = config.batching();
+        batching.validate().map_err(|e| anyhow::anyhow!("Invalid [trace_export]: {}", e))?;
+
+        let (sender, receiver) = mpsc::channel(config.max_pending);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let task = tokio::spawn(run_export(config.clone(), batching, receiver, dropped.clone()));
+        tracing::info!(url = %config.elasticsearch_url, "Exporting evaluation traces to Elasticsearch");
+        let exporter = TraceExporter {
+            sender,
+            source: config.source.as_str().into(),
+            export_metrics: config.export_metrics,
+            dropped,
+        };
+        Ok(Some((exporter, task)))
+    }
+
+    pub fn export_explain(&self, trace_id: &str, rule: &str, trace: &TraceNode) {
+        self.send(explain_event(&self.source, trace_id, rule, trace, Utc::now()));
+    }
+
+    /// A tracing layer exporting the `rule_evaluation_ms` metric events the
+    /// engine emits, under the trace id of the request span they happen in
+    pub fn metrics_layer(&self) -> Option<MetricsLayer> {
+        self.export_metrics.then(|| MetricsLayer { exporter: self.clone() })
+    }
+
+    fn send(&self, document: JsonValue) {
+        if self.sender.try_send(document).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl TraceExportConfig {
+    pub fn batching(&self) -> BatchWriteConfig {
+        BatchWriteConfig {
+            chunk_size: self.chunk_size,
+            flush_interval_ms: self.flush_interval_ms,
+            max_pending: self.max_pending,
+        }
+    }
+}
+
+async fn run_export(
+    config: TraceExportConfig,
+    batching: BatchWriteConfig,
+    mut receiver: mpsc::Receiver<JsonValue>,
+    dropped: Arc<AtomicU64>,
+) {
+    let client = reqwest::Client::new();
+    let url = config.elasticsearch_url.trim_end_matches('/').to_string();
+    if let Err(e) = put_index_template(&client, &url, &config.index_prefix).await {
+        tracing::warn!("Failed to install the trace index template: {:#}", e);
+    }
+
+    let mut buffer = BatchBuffer::new(&batching);
+    let mut ticker = tokio::time::interval(batching.flush_interval() / 2);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            document = receiver.recv() => match document {
+                Some(document) => {
+                    if buffer.push(document, Instant::now()) {
+                        send_buffered(&client, &url, &config.index_prefix, &mut buffer, &dropped).await;
+                    }
+                }
+                None => break,
+            },
+            _ = ticker.tick() => {
+                if buffer.is_due(Instant::now()) {
+                    send_buffered(&client, &url, &config.index_prefix, &mut buffer, &dropped).await;
+                }
+            }
+        }
+    }
+    send_buffered(&client, &url, &config.index_prefix, &mut buffer, &dropped).await;
+}
+
+// Maps every monthly index the same way, including ones created by a bulk request
+async fn put_index_template(client: &reqwest::Client, url: &str, prefix: &str) -> Result<()> {
+    let template = json!({
+        "index_patterns": [format!("{}-*", prefix)],
+        "template": { "mappings": index_mappings() }
+    });
+    let response = client
+        .put(format!("{}/_index_template/{}", url, prefix))
+        .json(&template)
+        .send()
+        .await
+        .context("Elasticsearch is unreachable")?;
+    if !response.status().is_success() {
+        bail!("Elasticsearch returned {}", response.status());
+    }
+    Ok(())
+}
+
+async fn send_buffered(
+    client: &reqwest::Client,
+    url: &str,
+    prefix: &str,
+    buffer: &mut BatchBuffer<JsonValue>,
+    dropped: &AtomicU64,
+) {
+    let lost = dropped.swap(0, Ordering::Relaxed);
+    if lost > 0 {
+        tracing::warn!("Dropped {} trace documents while the export queue was full", lost);
+    }
+    for chunk in buffer.take_chunks() {
+        let sent = client
+            .post(format!("{}/_bulk", url))
+            .header("Content-Type", "application/x-ndjson")
+            .body(bulk_body(prefix, &chunk))
+            .send()
+            .await;
+        match sent {
+            Ok(response) if response.status().is_success() => {}
+            Ok(response) => tracing::warn!("Trace export of {} documents failed: {}", chunk.len(), response.status()),
+            Err(e) => tracing::warn!("Trace export of {} documents failed: {}", chunk.len(), e),
+        }
+    }
+}
+
+/// See `TraceExporter::metrics_layer`
+pub struct MetricsLayer {
+    exporter: TraceExporter,
+}
+
+// The request trace id recorded on a span, kept in its extensions
+struct SpanTraceId(String);
+
+#[derive(Default)]
+struct FieldVisitor {
+    trace_id: Option<String>,
+    rule_id: Option<String>,
+    rule_evaluation_ms: Option<f64>,
+    ok: Option<bool>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "trace_id" => self.trace_id = Some(value.to_string()),
+            "rule_id" => self.rule_id = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if field.name() == "histogram.rule_evaluation_ms" {
+            self.rule_evaluation_ms = Some(value);
+        }
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        if field.name() == "ok" {
+            self.ok = Some(value);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+impl<S> Layer<S> for MetricsLayer
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = FieldVisitor::default();
+        attrs.record(&mut fields);
+        if let (Some(trace_id), Some(span)) = (fields.trace_id.filter(|id| !id.is_empty()), ctx.span(id)) {
+            span.extensions_mut().insert(SpanTraceId(trace_id));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = FieldVisitor::default();
+        event.record(&mut fields);
+        let (Some(duration_ms), Some(rule_id)) = (fields.rule_evaluation_ms, fields.rule_id) else {
+            return;
+        };
+        let trace_id = ctx
+            .event_scope(event)
+            .and_then(|mut scope| {
+                scope.find_map(|span| {
+                    let extensions = span.extensions();
+                    extensions.get::<SpanTraceId>().map(|id| id.0.clone())
+                })
+            })
+            .unwrap_or_default();
+        let document = metric_event(
+            &self.exporter.source,
+            &trace_id,
+            &rule_id,
+            duration_ms,
+            fields.ok.unwrap_or(true),
+            Utc::now(),
+        );
+        self.exporter.send(document);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::{evaluate_traced, Facts, FunctionLibrary};
+    use crate::parser::parse_expression;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_explain_traces_are_harness_events() {
+        let at = DateTime::parse_from_rfc3339("2026-03-04T10:00:00Z").unwrap().with_timezone(&Utc);
+        let expression = parse_expression("amount / count").unwrap().1;
+        let facts: Facts = [("amount".to_string(), crate::models::Value::Integer(10))].into_iter().collect();
+        let trace = evaluate_traced(&expression, &facts, &FunctionLibrary::new());
+
+        let event = explain_event("production", "trace-1", "ratio = amount / count", &trace, at);
+        assert_eq!(event["test_run_id"], "production");
+        assert_eq!(event["trace_id"], "trace-1");
+        assert_eq!(event["event_type"], "EngineExecution");
+        assert_eq!(event["level"], "Error");
+        assert_eq!(event["data"]["trace"]["expression"], "amount / count");
+
+        let body = bulk_body("evaluation-traces", &[event]);
+        let lines: Vec<&str> = body.lines().collect();
+        assert_eq!(lines[0], r#"{"index":{"_index":"evaluation-traces-2026-03"}}"#);
+        assert_eq!(serde_json::from_str::<JsonValue>(lines[1]).unwrap()["timestamp"], "2026-03-04T10:00:00Z");
+    }
+
+    #[test]
+    fn test_metric_events_carry_the_request_trace_id() {
+        let (sender, mut receiver) = mpsc::channel(10);
+        let exporter = TraceExporter {
+            sender,
+            source: "production".into(),
+            export_metrics: true,
+            dropped: Arc::default(),
+        };
+        let subscriber = tracing_subscriber::registry().with(exporter.metrics_layer());
+        tracing::subscriber::with_default(subscriber, || {
+            let _request = tracing::info_span!("http.command", trace_id = "trace-7").entered();
+            tracing::debug!(histogram.rule_evaluation_ms = 1.6, rule_id = "fee", ok = false);
+            tracing::debug!("not a metric");
+        });
+
+        let document = receiver.try_recv().unwrap();
+        assert_eq!(document["trace_id"], "trace-7");
+        assert_eq!(document["data"]["rule_id"], "fee");
+        assert_eq!(document["level"], "Error");
+        assert_eq!(document["duration_ms"], 2);
+        assert!(receiver.try_recv().is_err());
+    }
+}
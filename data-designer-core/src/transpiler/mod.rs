@@ -1,16 +1,27 @@
 use crate::models::{Expression, Value, BinaryOperator, UnaryOperator};
 use crate::parser::parse_expression;
 use crate::lisp_cbu_dsl::{LispCbuParser, LispValue};
-use crate::db::Rule;
+use crate::storage::Rule;
 use crate::dsl_utils;
+use crate::rule_graph::{extract_dependencies_from_ast, is_builtin_function, RuleGraph};
 use anyhow::{Result, bail};
 use serde_json;
 
+pub mod postgres;
+pub mod project;
+pub mod runtime;
+pub mod source_map;
+pub mod wasm;
+
+use postgres::ColumnMap;
+
 /// Transpiler pipeline: Parse -> Transform -> Generate
 /// Converts DSL expressions into optimized target code
 pub struct Transpiler {
     pub optimizations_enabled: bool,
     pub target_language: TargetLanguage,
+    /// Columns attributes are read from by the PostgresSql target
+    pub columns: ColumnMap,
 }
 
 #[derive(Debug, Clone)]
@@ -18,7 +29,12 @@ pub enum TargetLanguage {
     Rust,
     SQL,
     JavaScript,
+    /// JavaScript expressions, in modules typed against the TypeScript runtime shim
+    TypeScript,
     Python,
+    Rhai,
+    /// PostgreSQL expressions over the columns of persistence locators
+    PostgresSql,
 }
 
 #[derive(Debug, Clone)]
@@ -47,9 +63,16 @@ impl Transpiler {
         Self {
             optimizations_enabled: options.optimize,
             target_language: options.target,
+            columns: ColumnMap::new(),
         }
     }
 
+    /// Columns for the PostgresSql target to read attributes from
+    pub fn with_columns(mut self, columns: ColumnMap) -> Self {
+        self.columns = columns;
+        self
+    }
+
     /// Main transpiler pipeline
     pub fn transpile(&self, expr: &Expression) -> Result<String> {
         // Step 1: Transform AST (optimizations)
@@ -60,11 +83,26 @@ impl Transpiler {
         };
 
         // Step 2: Generate target code
+        self.generate(&optimized_expr)
+    }
+
+    fn generate(&self, expr: &Expression) -> Result<String> {
+        match self.target_language {
+            TargetLanguage::Rust => self.generate_rust(expr),
+            TargetLanguage::SQL => self.generate_sql(expr),
+            TargetLanguage::JavaScript | TargetLanguage::TypeScript => self.generate_javascript(expr),
+            TargetLanguage::Python => self.generate_python(expr),
+            TargetLanguage::Rhai => self.generate_rhai(expr),
+            TargetLanguage::PostgresSql => postgres::expression(expr, &self.columns),
+        }
+    }
+
+    /// Code for one sub-expression, without the helpers `generate` puts in
+    /// front of a whole rule
+    fn generate_fragment(&self, expr: &Expression) -> Result<String> {
         match self.target_language {
-            TargetLanguage::Rust => self.generate_rust(&optimized_expr),
-            TargetLanguage::SQL => self.generate_sql(&optimized_expr),
-            TargetLanguage::JavaScript => self.generate_javascript(&optimized_expr),
-            TargetLanguage::Python => self.generate_python(&optimized_expr),
+            TargetLanguage::Rhai => self.generate_rhai_expression(expr),
+            _ => self.generate(expr),
         }
     }
 
@@ -73,8 +111,10 @@ impl Transpiler {
         match self.target_language {
             TargetLanguage::Rust => self.generate_rust_from_s_expr(s_expr),
             TargetLanguage::SQL => self.generate_sql_from_s_expr(s_expr),
-            TargetLanguage::JavaScript => self.generate_js_from_s_expr(s_expr),
+            TargetLanguage::JavaScript | TargetLanguage::TypeScript => self.generate_js_from_s_expr(s_expr),
             TargetLanguage::Python => self.generate_python_from_s_expr(s_expr),
+            TargetLanguage::Rhai => bail!("S-expressions cannot be transpiled to Rhai"),
+            TargetLanguage::PostgresSql => bail!("S-expressions cannot be transpiled to PostgreSQL"),
         }
     }
 
@@ -99,13 +139,10 @@ impl Transpiler {
     fn optimize_expression(&self, expr: &Expression) -> Result<Expression> {
         let mut optimized = expr.clone();
 
-        // Pass 1: Constant folding
+        // Pass 1: Constant folding and dead branch elimination
         optimized = self.constant_folding(&optimized)?;
 
-        // Pass 2: Dead code elimination
-        optimized = self.dead_code_elimination(&optimized)?;
-
-        // Pass 3: Function inlining (for simple functions)
+        // Pass 2: Function inlining (for simple functions)
         optimized = self.inline_simple_functions(&optimized)?;
 
         Ok(optimized)
@@ -113,111 +150,7 @@ impl Transpiler {
 
     /// Constant folding optimization
     fn constant_folding(&self, expr: &Expression) -> Result<Expression> {
-        match expr {
-            Expression::BinaryOp { op, left, right } => {
-                let left_opt = self.constant_folding(left)?;
-                let right_opt = self.constant_folding(right)?;
-
-                // If both operands are literals, evaluate at compile time
-                if let (Expression::Literal(l_val), Expression::Literal(r_val)) = (&left_opt, &right_opt) {
-                    match self.evaluate_const_binary_op(op, l_val, r_val) {
-                        Ok(result) => return Ok(Expression::Literal(result)),
-                        Err(_) => {} // Fall back to original if evaluation fails
-                    }
-                }
-
-                Ok(Expression::BinaryOp {
-                    op: *op,
-                    left: Box::new(left_opt),
-                    right: Box::new(right_opt),
-                })
-            }
-            Expression::UnaryOp { op, operand } => {
-                let operand_opt = self.constant_folding(operand)?;
-
-                // If operand is literal, evaluate at compile time
-                if let Expression::Literal(val) = &operand_opt {
-                    match self.evaluate_const_unary_op(op, val) {
-                        Ok(result) => return Ok(Expression::Literal(result)),
-                        Err(_) => {} // Fall back to original
-                    }
-                }
-
-                Ok(Expression::UnaryOp {
-                    op: *op,
-                    operand: Box::new(operand_opt),
-                })
-            }
-            Expression::FunctionCall { name, args } => {
-                let optimized_args: Result<Vec<Expression>> = args.iter()
-                    .map(|arg| self.constant_folding(arg))
-                    .collect();
-
-                Ok(Expression::FunctionCall {
-                    name: name.clone(),
-                    args: optimized_args?,
-                })
-            }
-            Expression::Conditional { condition, then_expr, else_expr } => {
-                let cond_opt = self.constant_folding(condition)?;
-
-                // If condition is a constant boolean, eliminate branch
-                if let Expression::Literal(Value::Boolean(true)) = &cond_opt {
-                    return self.constant_folding(then_expr);
-                }
-                if let Expression::Literal(Value::Boolean(false)) = &cond_opt {
-                    if let Some(else_branch) = else_expr {
-                        return self.constant_folding(else_branch);
-                    } else {
-                        return Ok(Expression::Literal(Value::Null));
-                    }
-                }
-
-                let then_opt = self.constant_folding(then_expr)?;
-                let else_opt = if let Some(else_branch) = else_expr {
-                    Some(Box::new(self.constant_folding(else_branch)?))
-                } else {
-                    None
-                };
-
-                Ok(Expression::Conditional {
-                    condition: Box::new(cond_opt),
-                    then_expr: Box::new(then_opt),
-                    else_expr: else_opt,
-                })
-            }
-            Expression::List(items) => {
-                let optimized_items: Result<Vec<Expression>> = items.iter()
-                    .map(|item| self.constant_folding(item))
-                    .collect();
-
-                Ok(Expression::List(optimized_items?))
-            }
-            _ => Ok(expr.clone()), // Literals, identifiers, variables - no optimization
-        }
-    }
-
-    /// Dead code elimination
-    fn dead_code_elimination(&self, expr: &Expression) -> Result<Expression> {
-        match expr {
-            Expression::Conditional { condition, then_expr, else_expr } => {
-                // If we have unreachable branches after constant folding
-                let cond_opt = self.dead_code_elimination(condition)?;
-                let then_opt = self.dead_code_elimination(then_expr)?;
-                let else_opt = if let Some(else_branch) = else_expr {
-                    Some(Box::new(self.dead_code_elimination(else_branch)?))
-                } else {
-                    None
-                };
-
-                Ok(Expression::Conditional {
-                    condition: Box::new(cond_opt),
-                    then_expr: Box::new(then_opt),
-                    else_expr: else_opt,
-                })
-            }
-            _ => Ok(expr.clone()), // For now, just pass through
-        }
+        Ok(expr.optimize())
     }
 
     /// Inline simple functions
@@ -253,129 +186,132 @@ impl Transpiler {
         }
     }
 
-    /// Evaluate constant binary operations at compile time
-    fn evaluate_const_binary_op(&self, op: &BinaryOperator, left: &Value, right: &Value) -> Result<Value> {
-        match (left, right) {
-            (Value::Integer(l), Value::Integer(r)) => {
-                match op {
-                    BinaryOperator::Add => Ok(Value::Integer(l + r)),
-                    BinaryOperator::Subtract => Ok(Value::Integer(l - r)),
-                    BinaryOperator::Multiply => Ok(Value::Integer(l * r)),
-                    BinaryOperator::Divide => {
-                        if *r == 0 {
-                            bail!("Division by zero");
-                        }
-                        Ok(Value::Integer(l / r))
-                    }
-                    BinaryOperator::Equals => Ok(Value::Boolean(l == r)),
-                    BinaryOperator::NotEquals => Ok(Value::Boolean(l != r)),
-                    BinaryOperator::LessThan => Ok(Value::Boolean(l < r)),
-                    BinaryOperator::GreaterThan => Ok(Value::Boolean(l > r)),
-                    _ => bail!("Unsupported operation for integers"),
-                }
-            }
-            (Value::Float(l), Value::Float(r)) => {
-                match op {
-                    BinaryOperator::Add => Ok(Value::Float(l + r)),
-                    BinaryOperator::Subtract => Ok(Value::Float(l - r)),
-                    BinaryOperator::Multiply => Ok(Value::Float(l * r)),
-                    BinaryOperator::Divide => {
-                        if *r == 0.0 {
-                            bail!("Division by zero");
-                        }
-                        Ok(Value::Float(l / r))
-                    }
-                    BinaryOperator::Equals => Ok(Value::Boolean((l - r).abs() < f64::EPSILON)),
-                    BinaryOperator::NotEquals => Ok(Value::Boolean((l - r).abs() >= f64::EPSILON)),
-                    BinaryOperator::LessThan => Ok(Value::Boolean(l < r)),
-                    BinaryOperator::GreaterThan => Ok(Value::Boolean(l > r)),
-                    _ => bail!("Unsupported operation for floats"),
-                }
-            }
-            (Value::String(l), Value::String(r)) => {
-                match op {
-                    BinaryOperator::Concat => Ok(Value::String(format!("{}{}", l, r))),
-                    BinaryOperator::Equals => Ok(Value::Boolean(l == r)),
-                    BinaryOperator::NotEquals => Ok(Value::Boolean(l != r)),
-                    _ => bail!("Unsupported operation for strings"),
-                }
-            }
-            _ => bail!("Type mismatch in binary operation"),
-        }
-    }
-
-    /// Evaluate constant unary operations at compile time
-    fn evaluate_const_unary_op(&self, op: &UnaryOperator, operand: &Value) -> Result<Value> {
-        match (op, operand) {
-            (UnaryOperator::Minus, Value::Integer(i)) => Ok(Value::Integer(-i)),
-            (UnaryOperator::Minus, Value::Float(f)) => Ok(Value::Float(-f)),
-            (UnaryOperator::Plus, Value::Integer(i)) => Ok(Value::Integer(*i)),
-            (UnaryOperator::Plus, Value::Float(f)) => Ok(Value::Float(*f)),
-            (UnaryOperator::Not, Value::Boolean(b)) => Ok(Value::Boolean(!b)),
-            _ => bail!("Unsupported unary operation"),
-        }
-    }
-
-    /// Generate Rust code
+    /// Generate Rust code over the `Value` of the Rust runtime shim.
+    /// Comparisons, IN and the logical operators are plain `bool`s and lists
+    /// are `Vec`s; `generate_rust_value` and `generate_rust_condition` convert
+    /// where the other kind is needed.
     fn generate_rust(&self, expr: &Expression) -> Result<String> {
         match expr {
             Expression::Literal(val) => self.generate_rust_literal(val),
             Expression::Identifier(name) | Expression::Variable(name) => {
                 Ok(format!("ctx.get(\"{}\")", name))
             }
-            Expression::BinaryOp { op, left, right } => {
-                let left_code = self.generate_rust(left)?;
+            Expression::BinaryOp { op: op @ (BinaryOperator::In | BinaryOperator::NotIn | BinaryOperator::Between), left, right } => {
+                let left_code = self.generate_rust_value(left)?;
                 let right_code = self.generate_rust(right)?;
-                let op_code = self.generate_rust_binary_op(op);
-                Ok(format!("({} {} {})", left_code, op_code, right_code))
+                let negation = if *op == BinaryOperator::NotIn { "!" } else { "" };
+                Ok(format!("{}{}.contains(&{})", negation, right_code, left_code))
+            }
+            Expression::BinaryOp { op: op @ (BinaryOperator::And | BinaryOperator::Or), left, right } => {
+                let left_code = self.generate_rust_condition(left)?;
+                let right_code = self.generate_rust_condition(right)?;
+                Ok(format!("({} {} {})", left_code, self.generate_rust_binary_op(op), right_code))
+            }
+            Expression::BinaryOp { op, left, right } => {
+                let left_code = self.generate_rust_value(left)?;
+                let right_code = self.generate_rust_value(right)?;
+                match op {
+                    BinaryOperator::Concat => Ok(format!("concat(&[{}, {}])", left_code, right_code)),
+                    BinaryOperator::Power => Ok(format!("power(&[{}, {}])", left_code, right_code)),
+                    BinaryOperator::Contains => Ok(format!("text_contains(&[{}, {}])", left_code, right_code)),
+                    BinaryOperator::StartsWith => Ok(format!("starts_with(&[{}, {}])", left_code, right_code)),
+                    BinaryOperator::EndsWith => Ok(format!("ends_with(&[{}, {}])", left_code, right_code)),
+                    BinaryOperator::Matches | BinaryOperator::NotMatches => bail!("{:?} is not supported in Rust", op),
+                    _ => Ok(format!("({} {} {})", left_code, self.generate_rust_binary_op(op), right_code)),
+                }
+            }
+            Expression::UnaryOp { op: UnaryOperator::Not, operand } => {
+                Ok(format!("(!{})", self.generate_rust_condition(operand)?))
             }
             Expression::UnaryOp { op, operand } => {
-                let operand_code = self.generate_rust(operand)?;
+                let operand_code = self.generate_rust_value(operand)?;
                 let op_code = self.generate_rust_unary_op(op);
                 Ok(format!("({}{})", op_code, operand_code))
             }
             Expression::FunctionCall { name, args } => {
                 let arg_codes: Result<Vec<String>> = args.iter()
-                    .map(|arg| self.generate_rust(arg))
+                    .map(|arg| self.generate_rust_value(arg))
                     .collect();
                 let args_str = arg_codes?.join(", ");
-                Ok(format!("{}({})", name.to_lowercase(), args_str))
+                Ok(format!("{}(&[{}])", name.to_lowercase(), args_str))
             }
             Expression::Conditional { condition, then_expr, else_expr } => {
-                let cond_code = self.generate_rust(condition)?;
-                let then_code = self.generate_rust(then_expr)?;
+                let cond_code = self.generate_rust_condition(condition)?;
+                let then_code = self.generate_rust_value(then_expr)?;
                 let else_code = if let Some(else_branch) = else_expr {
-                    self.generate_rust(else_branch)?
+                    self.generate_rust_value(else_branch)?
                 } else {
                     "Value::Null".to_string()
                 };
                 Ok(format!("if {} {{ {} }} else {{ {} }}", cond_code, then_code, else_code))
             }
+            Expression::Case { branches, else_expr } => {
+                let mut code = String::new();
+                for (condition, result) in branches {
+                    let cond_code = self.generate_rust_condition(condition)?;
+                    let result_code = self.generate_rust_value(result)?;
+                    code.push_str(&format!("if {} {{ {} }} else ", cond_code, result_code));
+                }
+                let else_code = match else_expr {
+                    Some(else_branch) => self.generate_rust_value(else_branch)?,
+                    None => "Value::Null".to_string(),
+                };
+                code.push_str(&format!("{{ {} }}", else_code));
+                Ok(code)
+            }
             Expression::List(items) => {
                 let item_codes: Result<Vec<String>> = items.iter()
-                    .map(|item| self.generate_rust(item))
+                    .map(|item| self.generate_rust_value(item))
                     .collect();
                 Ok(format!("vec![{}]", item_codes?.join(", ")))
             }
+            Expression::Range { start, end, inclusive } => {
+                let start_code = self.generate_rust_value(start)?;
+                let end_code = self.generate_rust_value(end)?;
+                let dots = if *inclusive { "..=" } else { ".." };
+                Ok(format!("({}{}{})", start_code, dots, end_code))
+            }
             _ => bail!("Unsupported expression type for Rust generation"),
         }
     }
 
+    /// `expr` as a `Value`
+    fn generate_rust_value(&self, expr: &Expression) -> Result<String> {
+        let code = self.generate_rust(expr)?;
+        Ok(match expr {
+            Expression::List(_) => format!("Value::List({})", code),
+            _ if is_rust_bool(expr) => format!("Value::Boolean({})", code),
+            _ => code,
+        })
+    }
+
+    /// `expr` as a `bool`, true as the rules engine reads it
+    fn generate_rust_condition(&self, expr: &Expression) -> Result<String> {
+        if is_rust_bool(expr) {
+            return self.generate_rust(expr);
+        }
+        Ok(format!("truthy(&{})", self.generate_rust_value(expr)?))
+    }
+
     fn generate_rust_literal(&self, val: &Value) -> Result<String> {
         match val {
-            Value::String(s) => Ok(format!("Value::String(\"{}\".to_string())", s)),
+            Value::String(s) => Ok(format!("Value::String({:?}.to_string())", s)),
             Value::Integer(i) => Ok(format!("Value::Integer({})", i)),
-            Value::Float(f) => Ok(format!("Value::Float({})", f)),
-            Value::Number(n) => Ok(format!("Value::Number({})", n)),
+            // Debug keeps the decimal point, so 2.0 stays a float
+            Value::Float(f) | Value::Number(f) => Ok(format!("Value::Float({:?})", f)),
             Value::Boolean(b) => Ok(format!("Value::Boolean({})", b)),
             Value::Null => Ok("Value::Null".to_string()),
-            Value::Regex(pattern) => Ok(format!("Value::Regex(\"{}\".to_string())", pattern)),
+            // No units in the runtime: percentages become fractions, money its amount
+            Value::Percent(p) => Ok(format!("Value::Float({:?})", p / 100.0)),
+            Value::Money { amount, .. } => Ok(format!("Value::Float({:?})", amount)),
             Value::List(items) => {
                 let item_strings: Result<Vec<String>> = items.iter()
                     .map(|item| self.generate_rust_literal(item))
                     .collect();
                 Ok(format!("Value::List(vec![{}])", item_strings?.join(", ")))
             }
+            Value::Regex(_) => bail!("Regex literals are not supported in Rust"),
+            Value::Object(_) => bail!("Record literals are not supported in Rust"),
         }
     }
 
@@ -385,10 +321,13 @@ impl Transpiler {
             BinaryOperator::Subtract => "-",
             BinaryOperator::Multiply => "*",
             BinaryOperator::Divide => "/",
+            BinaryOperator::Modulo => "%",
             BinaryOperator::Equals => "==",
             BinaryOperator::NotEquals => "!=",
             BinaryOperator::LessThan => "<",
+            BinaryOperator::LessThanOrEqual => "<=",
             BinaryOperator::GreaterThan => ">",
+            BinaryOperator::GreaterThanOrEqual => ">=",
             BinaryOperator::And => "&&",
             BinaryOperator::Or => "||",
             _ => "/* unsupported op */",
@@ -398,7 +337,7 @@ impl Transpiler {
     fn generate_rust_unary_op(&self, op: &UnaryOperator) -> &'static str {
         match op {
             UnaryOperator::Minus => "-",
-            UnaryOperator::Plus => "+",
+            UnaryOperator::Plus => "",
             UnaryOperator::Not => "!",
         }
     }
@@ -410,6 +349,16 @@ impl Transpiler {
             Expression::Identifier(name) | Expression::Variable(name) => {
                 Ok(format!("\"{}\"", name))
             }
+            Expression::BinaryOp { op, left, right } if matches!(**right, Expression::Range { .. }) => {
+                let (left_code, start_code, end_code, inclusive) = self.range_parts(left, right, |e| self.generate_sql(e))?;
+                let check = if inclusive {
+                    format!("{} BETWEEN {} AND {}", left_code, start_code, end_code)
+                } else {
+                    format!("{} >= {} AND {} < {}", left_code, start_code, left_code, end_code)
+                };
+                let negation = if *op == BinaryOperator::NotIn { "NOT " } else { "" };
+                Ok(format!("({}({}))", negation, check))
+            }
             Expression::BinaryOp { op, left, right } => {
                 let left_code = self.generate_sql(left)?;
                 let right_code = self.generate_sql(right)?;
@@ -433,6 +382,23 @@ impl Transpiler {
                 };
                 Ok(format!("CASE WHEN {} THEN {} ELSE {} END", cond_code, then_code, else_code))
             }
+            Expression::Case { branches, else_expr } => {
+                let mut code = String::from("CASE");
+                for (condition, result) in branches {
+                    code.push_str(&format!(" WHEN {} THEN {}", self.generate_sql(condition)?, self.generate_sql(result)?));
+                }
+                if let Some(else_branch) = else_expr {
+                    code.push_str(&format!(" ELSE {}", self.generate_sql(else_branch)?));
+                }
+                code.push_str(" END");
+                Ok(code)
+            }
+            Expression::List(items) => {
+                let item_codes: Result<Vec<String>> = items.iter()
+                    .map(|item| self.generate_sql(item))
+                    .collect();
+                Ok(format!("({})", item_codes?.join(", ")))
+            }
             _ => bail!("Unsupported expression type for SQL generation"),
         }
     }
@@ -445,6 +411,15 @@ impl Transpiler {
             Value::Number(n) => Ok(n.to_string()),
             Value::Boolean(b) => Ok(if *b { "TRUE".to_string() } else { "FALSE".to_string() }),
             Value::Null => Ok("NULL".to_string()),
+            // No units in the target language: percentages become fractions, money its amount
+            Value::Percent(p) => Ok((p / 100.0).to_string()),
+            Value::Money { amount, .. } => Ok(amount.to_string()),
+            Value::List(items) => {
+                let item_strings: Result<Vec<String>> = items.iter()
+                    .map(|item| self.generate_sql_literal(item))
+                    .collect();
+                Ok(format!("({})", item_strings?.join(", ")))
+            }
             _ => bail!("Unsupported literal type for SQL"),
         }
     }
@@ -462,6 +437,8 @@ impl Transpiler {
             BinaryOperator::And => "AND",
             BinaryOperator::Or => "OR",
             BinaryOperator::Concat => "||",
+            BinaryOperator::In => "IN",
+            BinaryOperator::NotIn => "NOT IN",
             _ => "/* unsupported */",
         }
     }
@@ -473,12 +450,34 @@ impl Transpiler {
             Expression::Identifier(name) | Expression::Variable(name) => {
                 Ok(format!("ctx.get('{}')", name))
             }
+            Expression::BinaryOp { op, left, right } if matches!(**right, Expression::Range { .. }) => {
+                let (left_code, start_code, end_code, inclusive) = self.range_parts(left, right, |e| self.generate_javascript(e))?;
+                let end_op = if inclusive { "<=" } else { "<" };
+                let negation = if *op == BinaryOperator::NotIn { "!" } else { "" };
+                Ok(format!("{}({} >= {} && {} {} {})", negation, left_code, start_code, left_code, end_op, end_code))
+            }
+            Expression::BinaryOp { op: op @ (BinaryOperator::In | BinaryOperator::NotIn), left, right } => {
+                let left_code = self.generate_javascript(left)?;
+                let right_code = self.generate_javascript(right)?;
+                let negation = if *op == BinaryOperator::NotIn { "!" } else { "" };
+                Ok(format!("{}{}.includes({})", negation, right_code, left_code))
+            }
+            Expression::BinaryOp { op: BinaryOperator::Concat, left, right } => {
+                // The runtime shim's concat prints values as the rules engine does
+                let left_code = self.generate_javascript(left)?;
+                let right_code = self.generate_javascript(right)?;
+                Ok(format!("concat({}, {})", left_code, right_code))
+            }
             Expression::BinaryOp { op, left, right } => {
                 let left_code = self.generate_javascript(left)?;
                 let right_code = self.generate_javascript(right)?;
                 let op_code = self.generate_js_binary_op(op);
                 Ok(format!("({} {} {})", left_code, op_code, right_code))
             }
+            Expression::UnaryOp { op, operand } => {
+                let operand_code = self.generate_javascript(operand)?;
+                Ok(format!("({}{})", self.generate_rust_unary_op(op), operand_code))
+            }
             Expression::FunctionCall { name, args } => {
                 let arg_codes: Result<Vec<String>> = args.iter()
                     .map(|arg| self.generate_javascript(arg))
@@ -496,6 +495,24 @@ impl Transpiler {
                 };
                 Ok(format!("({} ? {} : {})", cond_code, then_code, else_code))
             }
+            Expression::Case { branches, else_expr } => {
+                let mut code = match else_expr {
+                    Some(else_branch) => self.generate_javascript(else_branch)?,
+                    None => "null".to_string(),
+                };
+                for (condition, result) in branches.iter().rev() {
+                    let cond_code = self.generate_javascript(condition)?;
+                    let result_code = self.generate_javascript(result)?;
+                    code = format!("({} ? {} : {})", cond_code, result_code, code);
+                }
+                Ok(code)
+            }
+            Expression::List(items) => {
+                let item_codes: Result<Vec<String>> = items.iter()
+                    .map(|item| self.generate_javascript(item))
+                    .collect();
+                Ok(format!("[{}]", item_codes?.join(", ")))
+            }
             _ => bail!("Unsupported expression type for JavaScript generation"),
         }
     }
@@ -508,6 +525,15 @@ impl Transpiler {
             Value::Number(n) => Ok(n.to_string()),
             Value::Boolean(b) => Ok(b.to_string()),
             Value::Null => Ok("null".to_string()),
+            // No units in the target language: percentages become fractions, money its amount
+            Value::Percent(p) => Ok((p / 100.0).to_string()),
+            Value::Money { amount, .. } => Ok(amount.to_string()),
+            Value::List(items) => {
+                let item_strings: Result<Vec<String>> = items.iter()
+                    .map(|item| self.generate_js_literal(item))
+                    .collect();
+                Ok(format!("[{}]", item_strings?.join(", ")))
+            }
             _ => bail!("Unsupported literal type for JavaScript"),
         }
     }
@@ -518,10 +544,14 @@ impl Transpiler {
             BinaryOperator::Subtract => "-",
             BinaryOperator::Multiply => "*",
             BinaryOperator::Divide => "/",
+            BinaryOperator::Power => "**",
+            BinaryOperator::Modulo => "%",
             BinaryOperator::Equals => "===",
             BinaryOperator::NotEquals => "!==",
             BinaryOperator::LessThan => "<",
+            BinaryOperator::LessThanOrEqual => "<=",
             BinaryOperator::GreaterThan => ">",
+            BinaryOperator::GreaterThanOrEqual => ">=",
             BinaryOperator::And => "&&",
             BinaryOperator::Or => "||",
             _ => "/* unsupported */",
@@ -535,12 +565,33 @@ impl Transpiler {
             Expression::Identifier(name) | Expression::Variable(name) => {
                 Ok(format!("ctx.get('{}')", name))
             }
+            Expression::BinaryOp { op, left, right } if matches!(**right, Expression::Range { .. }) => {
+                let (left_code, start_code, end_code, inclusive) = self.range_parts(left, right, |e| self.generate_python(e))?;
+                let end_op = if inclusive { "<=" } else { "<" };
+                let negation = if *op == BinaryOperator::NotIn { "not " } else { "" };
+                Ok(format!("({}{} <= {} {} {})", negation, start_code, left_code, end_op, end_code))
+            }
+            Expression::BinaryOp { op: BinaryOperator::Concat, left, right } => {
+                // The runtime shim's concat prints values as the rules engine does
+                let left_code = self.generate_python(left)?;
+                let right_code = self.generate_python(right)?;
+                Ok(format!("concat({}, {})", left_code, right_code))
+            }
             Expression::BinaryOp { op, left, right } => {
                 let left_code = self.generate_python(left)?;
                 let right_code = self.generate_python(right)?;
                 let op_code = self.generate_python_binary_op(op);
                 Ok(format!("({} {} {})", left_code, op_code, right_code))
             }
+            Expression::UnaryOp { op, operand } => {
+                let operand_code = self.generate_python(operand)?;
+                let op_code = match op {
+                    UnaryOperator::Not => "not ",
+                    UnaryOperator::Minus => "-",
+                    UnaryOperator::Plus => "+",
+                };
+                Ok(format!("({}{})", op_code, operand_code))
+            }
             Expression::FunctionCall { name, args } => {
                 let arg_codes: Result<Vec<String>> = args.iter()
                     .map(|arg| self.generate_python(arg))
@@ -558,6 +609,24 @@ impl Transpiler {
                 };
                 Ok(format!("({} if {} else {})", then_code, cond_code, else_code))
             }
+            Expression::Case { branches, else_expr } => {
+                let mut code = match else_expr {
+                    Some(else_branch) => self.generate_python(else_branch)?,
+                    None => "None".to_string(),
+                };
+                for (condition, result) in branches.iter().rev() {
+                    let cond_code = self.generate_python(condition)?;
+                    let result_code = self.generate_python(result)?;
+                    code = format!("({} if {} else {})", result_code, cond_code, code);
+                }
+                Ok(code)
+            }
+            Expression::List(items) => {
+                let item_codes: Result<Vec<String>> = items.iter()
+                    .map(|item| self.generate_python(item))
+                    .collect();
+                Ok(format!("[{}]", item_codes?.join(", ")))
+            }
             _ => bail!("Unsupported expression type for Python generation"),
         }
     }
@@ -570,25 +639,220 @@ impl Transpiler {
             Value::Number(n) => Ok(n.to_string()),
             Value::Boolean(b) => Ok(if *b { "True".to_string() } else { "False".to_string() }),
             Value::Null => Ok("None".to_string()),
+            // No units in the target language: percentages become fractions, money its amount
+            Value::Percent(p) => Ok((p / 100.0).to_string()),
+            Value::Money { amount, .. } => Ok(amount.to_string()),
+            Value::List(items) => {
+                let item_strings: Result<Vec<String>> = items.iter()
+                    .map(|item| self.generate_python_literal(item))
+                    .collect();
+                Ok(format!("[{}]", item_strings?.join(", ")))
+            }
             _ => bail!("Unsupported literal type for Python"),
         }
     }
 
+    /// Generated code for `x` and the bounds of a BETWEEN/IN range check
+    fn range_parts(
+        &self,
+        left: &Expression,
+        range: &Expression,
+        generate: impl Fn(&Expression) -> Result<String>,
+    ) -> Result<(String, String, String, bool)> {
+        let Expression::Range { start, end, inclusive } = range else {
+            bail!("Expected a range on the right of BETWEEN or IN");
+        };
+        Ok((generate(left)?, generate(start)?, generate(end)?, *inclusive))
+    }
+
     fn generate_python_binary_op(&self, op: &BinaryOperator) -> &'static str {
         match op {
             BinaryOperator::Add => "+",
             BinaryOperator::Subtract => "-",
             BinaryOperator::Multiply => "*",
             BinaryOperator::Divide => "/",
+            BinaryOperator::Power => "**",
+            BinaryOperator::Modulo => "%",
             BinaryOperator::Equals => "==",
             BinaryOperator::NotEquals => "!=",
             BinaryOperator::LessThan => "<",
+            BinaryOperator::LessThanOrEqual => "<=",
             BinaryOperator::GreaterThan => ">",
+            BinaryOperator::GreaterThanOrEqual => ">=",
             BinaryOperator::And => "and",
             BinaryOperator::Or => "or",
+            BinaryOperator::In => "in",
+            BinaryOperator::NotIn => "not in",
             _ => "# unsupported",
         }
     }
+
+    /// Generate a Rhai script for `rhai_runtime`. Facts are script variables
+    /// (see `script_variable_name`); a missing fact must be bound to `()`.
+    /// Functions other than UPPER, LOWER and ABS, and MATCHES, have no Rhai
+    /// equivalent and are rejected, so callers keep walking the AST for those.
+    fn generate_rhai(&self, expr: &Expression) -> Result<String> {
+        let code = self.generate_rhai_expression(expr)?;
+        // Division always yields a float and fails on zero, as in the evaluator
+        if code.contains("dd_div(") {
+            return Ok(format!(
+                "fn dd_div(a, b) {{ if b == 0 {{ throw \"Division by zero\"; }} a.to_float() / b }}\n{}",
+                code
+            ));
+        }
+        Ok(code)
+    }
+
+    fn generate_rhai_expression(&self, expr: &Expression) -> Result<String> {
+        match expr {
+            Expression::Literal(val) => self.generate_rhai_literal(val),
+            Expression::Identifier(name) | Expression::Variable(name) => Ok(name.replace('.', "_")),
+            Expression::Assignment { value, .. } => self.generate_rhai_expression(value),
+            Expression::BinaryOp { op, left, right } if matches!(**right, Expression::Range { .. }) => {
+                let (left_code, start_code, end_code, inclusive) =
+                    self.range_parts(left, right, |e| self.generate_rhai_expression(e))?;
+                let end_op = if inclusive { "<=" } else { "<" };
+                let negation = if *op == BinaryOperator::NotIn { "!" } else { "" };
+                Ok(format!("{}({} <= {} && {} {} {})", negation, start_code, left_code, left_code, end_op, end_code))
+            }
+            Expression::BinaryOp { op, left, right } => {
+                let left_code = self.generate_rhai_expression(left)?;
+                let right_code = self.generate_rhai_expression(right)?;
+                match op {
+                    BinaryOperator::Divide => Ok(format!("dd_div({}, {})", left_code, right_code)),
+                    BinaryOperator::Concat => Ok(format!("`${{{}}}${{{}}}`", left_code, right_code)),
+                    BinaryOperator::Contains => Ok(format!("{}.contains({})", left_code, right_code)),
+                    BinaryOperator::StartsWith => Ok(format!("{}.starts_with({})", left_code, right_code)),
+                    BinaryOperator::EndsWith => Ok(format!("{}.ends_with({})", left_code, right_code)),
+                    BinaryOperator::In => Ok(format!("{}.contains({})", right_code, left_code)),
+                    BinaryOperator::NotIn => Ok(format!("!{}.contains({})", right_code, left_code)),
+                    BinaryOperator::Matches | BinaryOperator::NotMatches | BinaryOperator::Between => {
+                        bail!("{:?} is not supported in Rhai", op)
+                    }
+                    _ => Ok(format!("({} {} {})", left_code, self.generate_rhai_binary_op(op), right_code)),
+                }
+            }
+            Expression::UnaryOp { op, operand } => {
+                let operand_code = self.generate_rhai_expression(operand)?;
+                Ok(match op {
+                    UnaryOperator::Not => format!("!{}", operand_code),
+                    UnaryOperator::Minus => format!("(-{})", operand_code),
+                    UnaryOperator::Plus => operand_code,
+                })
+            }
+            Expression::FunctionCall { name, args } if args.len() == 1 => {
+                let arg_code = self.generate_rhai_expression(&args[0])?;
+                match name.to_uppercase().as_str() {
+                    "UPPER" => Ok(format!("{}.to_upper()", arg_code)),
+                    "LOWER" => Ok(format!("{}.to_lower()", arg_code)),
+                    "ABS" => Ok(format!("abs({})", arg_code)),
+                    _ => bail!("Function '{}' is not supported in Rhai", name),
+                }
+            }
+            Expression::Conditional { condition, then_expr, else_expr } => {
+                let cond_code = self.generate_rhai_expression(condition)?;
+                let then_code = self.generate_rhai_expression(then_expr)?;
+                let else_code = match else_expr {
+                    Some(else_branch) => self.generate_rhai_expression(else_branch)?,
+                    None => "()".to_string(),
+                };
+                Ok(format!("(if {} {{ {} }} else {{ {} }})", cond_code, then_code, else_code))
+            }
+            Expression::Case { branches, else_expr } => {
+                let mut code = match else_expr {
+                    Some(else_branch) => self.generate_rhai_expression(else_branch)?,
+                    None => "()".to_string(),
+                };
+                for (condition, result) in branches.iter().rev() {
+                    let cond_code = self.generate_rhai_expression(condition)?;
+                    let result_code = self.generate_rhai_expression(result)?;
+                    code = format!("(if {} {{ {} }} else {{ {} }})", cond_code, result_code, code);
+                }
+                Ok(code)
+            }
+            Expression::List(items) => {
+                let item_codes: Result<Vec<String>> = items.iter()
+                    .map(|item| self.generate_rhai_expression(item))
+                    .collect();
+                Ok(format!("[{}]", item_codes?.join(", ")))
+            }
+            _ => bail!("Unsupported expression type for Rhai generation"),
+        }
+    }
+
+    fn generate_rhai_literal(&self, val: &Value) -> Result<String> {
+        match val {
+            Value::String(s) => Ok(format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))),
+            Value::Integer(i) => Ok(i.to_string()),
+            Value::Float(f) | Value::Number(f) => self.generate_rhai_float(*f),
+            Value::Boolean(b) => Ok(b.to_string()),
+            Value::Null => Ok("()".to_string()),
+            // No units in the target language: percentages become fractions, money its amount
+            Value::Percent(p) => self.generate_rhai_float(p / 100.0),
+            Value::Money { amount, .. } => self.generate_rhai_float(*amount),
+            Value::List(items) => {
+                let item_strings: Result<Vec<String>> = items.iter()
+                    .map(|item| self.generate_rhai_literal(item))
+                    .collect();
+                Ok(format!("[{}]", item_strings?.join(", ")))
+            }
+            Value::Regex(_) => bail!("Regex literals are not supported in Rhai"),
+            Value::Object(_) => bail!("Record literals are not supported in Rhai"),
+        }
+    }
+
+    // `5` would be an integer in Rhai; Debug formatting keeps the `.0`
+    fn generate_rhai_float(&self, f: f64) -> Result<String> {
+        if !f.is_finite() {
+            bail!("{} has no Rhai literal", f);
+        }
+        let code = format!("{:?}", f);
+        Ok(match code.split_once('e') {
+            Some((mantissa, exponent)) if !mantissa.contains('.') => format!("{}.0e{}", mantissa, exponent),
+            _ => code,
+        })
+    }
+
+    fn generate_rhai_binary_op(&self, op: &BinaryOperator) -> &'static str {
+        match op {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Modulo => "%",
+            BinaryOperator::Power => "**",
+            BinaryOperator::Equals => "==",
+            BinaryOperator::NotEquals => "!=",
+            BinaryOperator::LessThan => "<",
+            BinaryOperator::LessThanOrEqual => "<=",
+            BinaryOperator::GreaterThan => ">",
+            BinaryOperator::GreaterThanOrEqual => ">=",
+            BinaryOperator::And => "&&",
+            BinaryOperator::Or => "||",
+            _ => "/* unsupported */",
+        }
+    }
+}
+
+/// Whether generated Rust for `expr` is a `bool` rather than a `Value`
+fn is_rust_bool(expr: &Expression) -> bool {
+    match expr {
+        Expression::BinaryOp { op, .. } => matches!(
+            op,
+            BinaryOperator::Equals
+                | BinaryOperator::NotEquals
+                | BinaryOperator::LessThan
+                | BinaryOperator::LessThanOrEqual
+                | BinaryOperator::GreaterThan
+                | BinaryOperator::GreaterThanOrEqual
+                | BinaryOperator::And
+                | BinaryOperator::Or
+                | BinaryOperator::In
+                | BinaryOperator::NotIn
+                | BinaryOperator::Between
+        ),
+        Expression::UnaryOp { op, .. } => *op == UnaryOperator::Not,
+        _ => false,
+    }
 }
 
 /// DSL-to-Rules transpiler with detailed error reporting
@@ -762,6 +1026,9 @@ impl DslTranspiler {
                     });
                 }
 
+                // Simplify before analysis so dependencies of dead branches are dropped
+                let ast = ast.optimize();
+
                 // Extract dependencies from AST
                 let dependencies = self.extract_dependencies(&ast);
 
@@ -816,66 +1083,9 @@ impl DslTranspiler {
         }
     }
 
-    /// Extract variable dependencies from AST
-    fn extract_dependencies(&self, expr: &Expression) -> Vec<String> {
-        let mut deps = Vec::new();
-        self.collect_dependencies(expr, &mut deps);
-        deps.sort();
-        deps.dedup();
-        deps
-    }
-
-    /// Recursively collect dependencies from expression tree
-    fn collect_dependencies(&self, expr: &Expression, deps: &mut Vec<String>) {
-        match expr {
-            Expression::Variable(name) | Expression::Identifier(name) => {
-                // Skip built-in functions and literals
-                if !self.is_builtin_function(name) && !name.chars().all(|c| c.is_ascii_digit()) {
-                    deps.push(name.clone());
-                }
-            }
-            Expression::BinaryOp { left, right, .. } => {
-                self.collect_dependencies(left, deps);
-                self.collect_dependencies(right, deps);
-            }
-            Expression::UnaryOp { operand, .. } => {
-                self.collect_dependencies(operand, deps);
-            }
-            Expression::FunctionCall { args, .. } => {
-                for arg in args {
-                    self.collect_dependencies(arg, deps);
-                }
-            }
-            Expression::Conditional { condition, then_expr, else_expr } => {
-                self.collect_dependencies(condition, deps);
-                self.collect_dependencies(then_expr, deps);
-                if let Some(else_branch) = else_expr {
-                    self.collect_dependencies(else_branch, deps);
-                }
-            }
-            Expression::Assignment { value, .. } => {
-                self.collect_dependencies(value, deps);
-            }
-            Expression::List(items) => {
-                for item in items {
-                    self.collect_dependencies(item, deps);
-                }
-            }
-            Expression::Cast { expr, .. } => {
-                self.collect_dependencies(expr, deps);
-            }
-            _ => {} // Literals don't have dependencies
-        }
-    }
-
-    /// Check if a name is a built-in function
-    fn is_builtin_function(&self, name: &str) -> bool {
-        matches!(name.to_uppercase().as_str(),
-                "CONCAT" | "UPPER" | "LOWER" | "LENGTH" | "SUBSTRING" | "TRIM" |
-                "ABS" | "ROUND" | "CEIL" | "FLOOR" | "MIN" | "MAX" | "SUM" | "AVG" |
-                "IF" | "WHEN" | "THEN" | "ELSE" | "CASE" | "END" |
-                "MATCHES" | "CONTAINS" | "STARTS_WITH" | "ENDS_WITH" |
-                "TRUE" | "FALSE" | "NULL")
+    /// Extract variable dependencies from AST, sorted and without duplicates
+    pub fn extract_dependencies(&self, expr: &Expression) -> Vec<String> {
+        extract_dependencies_from_ast(expr)
     }
 
     /// Validate a parsed rule
@@ -935,6 +1145,15 @@ impl DslTranspiler {
                     self.validate_expression(else_branch)?;
                 }
             }
+            Expression::Case { branches, else_expr } => {
+                for (condition, result) in branches {
+                    self.validate_expression(condition)?;
+                    self.validate_expression(result)?;
+                }
+                if let Some(else_branch) = else_expr {
+                    self.validate_expression(else_branch)?;
+                }
+            }
             Expression::List(items) => {
                 for item in items {
                     self.validate_expression(item)?;
@@ -968,6 +1187,11 @@ impl DslTranspiler {
                     return Err(format!("SUBSTRING requires exactly 3 arguments, got {}", args.len()));
                 }
             }
+            "FORMAT_NUMBER" | "FORMAT_DATE" => {
+                if args.len() < 2 || args.len() > 3 {
+                    return Err(format!("{} requires 2 or 3 arguments, got {}", name.to_uppercase(), args.len()));
+                }
+            }
             "IF" => {
                 if args.len() != 3 {
                     return Err(format!("IF requires exactly 3 arguments (condition, then, else), got {}", args.len()));
@@ -989,7 +1213,7 @@ impl DslTranspiler {
         // Check for undefined dependencies
         for rule in rules.iter() {
             for dep in &rule.dependencies {
-                if !rule_names.contains(dep) && !self.is_builtin_function(dep) {
+                if !rule_names.contains(dep) && !is_builtin_function(dep) {
                     errors.push(TranspileError {
                         message: format!("Undefined dependency: '{}'", dep),
                         line: Some(rule.line_number),
@@ -1001,17 +1225,17 @@ impl DslTranspiler {
             }
         }
 
-        // Check for circular dependencies (simple implementation)
-        for rule in rules.iter() {
-            if rule.dependencies.contains(&rule.name) {
-                errors.push(TranspileError {
-                    message: "Rule cannot depend on itself".to_string(),
-                    line: Some(rule.line_number),
-                    column: None,
-                    rule_name: Some(rule.name.clone()),
-                    error_type: ErrorType::SemanticError,
-                });
-            }
+        // Check for circular dependencies, including through other rules
+        let graph = RuleGraph::new(rules.iter().map(|r| (r.name.as_str(), r.dependencies.as_slice())));
+        if let Err(cycle) = graph.execution_order() {
+            let first = rules.iter().find(|r| r.name == cycle.rules[0]);
+            errors.push(TranspileError {
+                message: cycle.to_string(),
+                line: first.map(|r| r.line_number),
+                column: None,
+                rule_name: first.map(|r| r.name.clone()),
+                error_type: ErrorType::SemanticError,
+            });
         }
 
         if errors.is_empty() {
@@ -1046,8 +1270,17 @@ impl DslTranspiler {
             created_at: Utc::now(),
             updated_by: Some("dsl_transpiler".to_string()),
             updated_at: Utc::now(),
+            valid_from: None,
+            valid_to: None,
         }
     }
+
+    /// PostgreSQL view with one column per rule, reading attributes from the
+    /// columns of their persistence locators
+    pub fn generate_postgres_view(&self, view_name: &str, key_column: &str, rules: &[DslRule], columns: &ColumnMap) -> Result<String> {
+        let rules: Vec<(&str, &Expression)> = rules.iter().map(|rule| (rule.name.as_str(), &rule.expression)).collect();
+        postgres::create_view(view_name, key_column, &rules, columns)
+    }
 }
 
 impl Default for DslTranspiler {
@@ -1065,8 +1298,10 @@ impl TranspilerValidator {
         match target {
             TargetLanguage::SQL => Self::validate_sql_compatibility(expr),
             TargetLanguage::Rust => Self::validate_rust_compatibility(expr),
-            TargetLanguage::JavaScript => Self::validate_js_compatibility(expr),
+            TargetLanguage::JavaScript | TargetLanguage::TypeScript => Self::validate_js_compatibility(expr),
             TargetLanguage::Python => Self::validate_python_compatibility(expr),
+            // generate_rhai and the postgres generator reject what they can't express
+            TargetLanguage::Rhai | TargetLanguage::PostgresSql => Ok(()),
         }
     }
 
@@ -1098,6 +1333,16 @@ impl TranspilerValidator {
                 }
                 Ok(())
             }
+            Expression::Case { branches, else_expr } => {
+                for (condition, result) in branches {
+                    Self::validate_sql_compatibility(condition)?;
+                    Self::validate_sql_compatibility(result)?;
+                }
+                if let Some(else_branch) = else_expr {
+                    Self::validate_sql_compatibility(else_branch)?;
+                }
+                Ok(())
+            }
             Expression::List(items) => {
                 for item in items {
                     Self::validate_sql_compatibility(item)?;
@@ -1367,6 +1612,99 @@ mod tests {
         assert_eq!(code, "UPPER(\"name\")");
     }
 
+    #[test]
+    fn test_case_generation() {
+        let transpiler = Transpiler::new(TranspilerOptions::default());
+        let (_, expr) = parse_expression("CASE WHEN x > 10 THEN 2 ELSE 1 END").unwrap();
+
+        assert_eq!(
+            transpiler.generate_sql(&expr).unwrap(),
+            "CASE WHEN (\"x\" > 10) THEN 2 ELSE 1 END"
+        );
+        assert_eq!(
+            transpiler.generate_rust(&expr).unwrap(),
+            "if (ctx.get(\"x\") > Value::Integer(10)) { Value::Integer(2) } else { Value::Integer(1) }"
+        );
+    }
+
+    #[test]
+    fn test_membership_generation() {
+        let transpiler = Transpiler::new(TranspilerOptions::default());
+        let (_, expr) = parse_expression("country IN [\"US\", \"GB\"]").unwrap();
+
+        assert_eq!(
+            transpiler.generate_rust(&expr).unwrap(),
+            "vec![Value::String(\"US\".to_string()), Value::String(\"GB\".to_string())].contains(&ctx.get(\"country\"))"
+        );
+        assert_eq!(transpiler.generate_sql(&expr).unwrap(), "(\"country\" IN ('US', 'GB'))");
+        assert_eq!(
+            transpiler.generate_javascript(&expr).unwrap(),
+            "[\"US\", \"GB\"].includes(ctx.get('country'))"
+        );
+        assert_eq!(
+            transpiler.generate_python(&expr).unwrap(),
+            "(ctx.get('country') in [\"US\", \"GB\"])"
+        );
+
+        let (_, expr) = parse_expression("tier NOT IN [1, 2]").unwrap();
+        assert_eq!(transpiler.generate_rust(&expr).unwrap(), "!vec![Value::Integer(1), Value::Integer(2)].contains(&ctx.get(\"tier\"))");
+        assert_eq!(transpiler.generate_sql(&expr).unwrap(), "(\"tier\" NOT IN (1, 2))");
+        assert_eq!(transpiler.generate_javascript(&expr).unwrap(), "![1, 2].includes(ctx.get('tier'))");
+        assert_eq!(transpiler.generate_python(&expr).unwrap(), "(ctx.get('tier') not in [1, 2])");
+    }
+
+    #[test]
+    fn test_range_generation() {
+        let transpiler = Transpiler::new(TranspilerOptions::default());
+        let (_, expr) = parse_expression("amount BETWEEN 1000 AND 5000").unwrap();
+
+        assert_eq!(
+            transpiler.generate_rust(&expr).unwrap(),
+            "(Value::Integer(1000)..=Value::Integer(5000)).contains(&ctx.get(\"amount\"))"
+        );
+        assert_eq!(transpiler.generate_sql(&expr).unwrap(), "((\"amount\" BETWEEN 1000 AND 5000))");
+        assert_eq!(
+            transpiler.generate_javascript(&expr).unwrap(),
+            "(ctx.get('amount') >= 1000 && ctx.get('amount') <= 5000)"
+        );
+        assert_eq!(transpiler.generate_python(&expr).unwrap(), "(1000 <= ctx.get('amount') <= 5000)");
+
+        let (_, expr) = parse_expression("amount NOT IN 1000..5000").unwrap();
+        assert_eq!(
+            transpiler.generate_rust(&expr).unwrap(),
+            "!(Value::Integer(1000)..Value::Integer(5000)).contains(&ctx.get(\"amount\"))"
+        );
+        assert_eq!(
+            transpiler.generate_sql(&expr).unwrap(),
+            "(NOT (\"amount\" >= 1000 AND \"amount\" < 5000))"
+        );
+        assert_eq!(
+            transpiler.generate_javascript(&expr).unwrap(),
+            "!(ctx.get('amount') >= 1000 && ctx.get('amount') < 5000)"
+        );
+        assert_eq!(transpiler.generate_python(&expr).unwrap(), "(not 1000 <= ctx.get('amount') < 5000)");
+    }
+
+    #[test]
+    fn test_rhai_generation() {
+        let transpiler = Transpiler::new(TranspilerOptions {
+            target: TargetLanguage::Rhai,
+            ..TranspilerOptions::default()
+        });
+
+        let (_, expr) = parse_expression("IF Client.tier IN [\"gold\"] THEN 5 ELSE amount * 0.5").unwrap();
+        assert_eq!(
+            transpiler.transpile(&expr).unwrap(),
+            "(if [\"gold\"].contains(Client_tier) { 5 } else { (amount * 0.5) })"
+        );
+
+        let (_, expr) = parse_expression("amount / 4.0").unwrap();
+        assert!(transpiler.transpile(&expr).unwrap().starts_with("fn dd_div(a, b)"));
+
+        let (_, expr) = parse_expression("LOOKUP(country, \"regions\")").unwrap();
+        assert!(transpiler.transpile(&expr).is_err());
+    }
+
     // S-expression transpiler tests
     #[test]
     fn test_s_expression_rust_generation() {
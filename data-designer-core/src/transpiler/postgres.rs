@@ -0,0 +1,403 @@
+//! PostgreSQL expressions and views generated from rule ASTs
+//!
+//! Attributes resolve through the persistence locators of a resource's
+//! attribute metadata (`system`, `entity`, `identifier`): an attribute kept in
+//! column `entity_name` of `legal_entities` is read as
+//! `"legal_entities"."entity_name"`. In a single expression, attributes
+//! without a locator are quoted as they are; a view needs a locator for every
+//! attribute its rules read, and inlines the rules that derive the others.
+//!
+//! Semantics follow the evaluator where PostgreSQL allows: division is done
+//! in numeric so integers don't truncate, SUBSTRING counts from 0, MIN and MAX
+//! of several values are LEAST and GREATEST, and `LOOKUP(key, "table")` reads
+//! the `value` column of a key/value relation named after the lookup table.
+
+use crate::models::{BinaryOperator, Expression, UnaryOperator, Value};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Where an attribute is stored
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnLocator {
+    /// Database holding the entity; one view reads from one system only
+    pub system: Option<String>,
+    /// Table or view
+    pub entity: String,
+    pub column: String,
+}
+
+/// Columns of attributes by attribute name, built from persistence locators
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ColumnMap {
+    columns: BTreeMap<String, ColumnLocator>,
+}
+
+impl ColumnMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, attribute: impl Into<String>, locator: ColumnLocator) {
+        self.columns.insert(attribute.into(), locator);
+    }
+
+    /// Adds an attribute's persistence locator; one without an entity or
+    /// identifier doesn't say where the attribute is stored and is skipped
+    pub fn add_locator(&mut self, attribute: &str, system: Option<&str>, entity: Option<&str>, identifier: Option<&str>) {
+        if let (Some(entity), Some(column)) = (entity, identifier) {
+            self.insert(
+                attribute,
+                ColumnLocator {
+                    system: system.map(str::to_string),
+                    entity: entity.to_string(),
+                    column: column.to_string(),
+                },
+            );
+        }
+    }
+
+    pub fn get(&self, attribute: &str) -> Option<&ColumnLocator> {
+        self.columns.get(attribute)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+}
+
+/// A PostgreSQL expression computing `expr`
+pub fn expression(expr: &Expression, columns: &ColumnMap) -> Result<String> {
+    let mut generator = Generator::new(columns, BTreeMap::new(), false);
+    match expr {
+        Expression::Assignment { target, value } => Ok(format!("{} AS {}", generator.expr(value)?, quote(target))),
+        _ => generator.expr(expr),
+    }
+}
+
+/// `CREATE OR REPLACE VIEW` with one column per rule, given as `(target,
+/// expression)`. The entities the rules read are joined on `key_column`,
+/// which the view also selects; the entity read most is the one joined to.
+pub fn create_view(view_name: &str, key_column: &str, rules: &[(&str, &Expression)], columns: &ColumnMap) -> Result<String> {
+    let derived = rules.iter().map(|(target, expr)| (*target, value_of(expr))).collect();
+    let mut generator = Generator::new(columns, derived, true);
+
+    let mut selected = Vec::new();
+    for (target, expr) in rules {
+        generator.expanding.push(target);
+        let sql = generator.expr(value_of(expr))?;
+        generator.expanding.pop();
+        selected.push(format!("    {} AS {}", sql, quote(target)));
+    }
+
+    if generator.systems.len() > 1 {
+        let systems: Vec<&str> = generator.systems.iter().map(String::as_str).collect();
+        bail!("The rules read attributes from more than one system ({}); a view reads from one database", systems.join(", "));
+    }
+    let Some(base) = generator.entities.iter().max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0))).map(|(entity, _)| *entity) else {
+        bail!("The rules read no stored attributes, so the view has nothing to select from");
+    };
+
+    let mut sql = format!("CREATE OR REPLACE VIEW {} AS\nSELECT\n    {}.{},\n", quote(view_name), quote(base), quote(key_column));
+    sql.push_str(&selected.join(",\n"));
+    sql.push_str(&format!("\nFROM {}", quote(base)));
+    for entity in generator.entities.keys().filter(|entity| **entity != base) {
+        sql.push_str(&format!(
+            "\nLEFT JOIN {} ON {}.{} = {}.{}",
+            quote(entity),
+            quote(entity),
+            quote(key_column),
+            quote(base),
+            quote(key_column)
+        ));
+    }
+    sql.push(';');
+    Ok(sql)
+}
+
+fn value_of(expr: &Expression) -> &Expression {
+    match expr {
+        Expression::Assignment { value, .. } => value,
+        _ => expr,
+    }
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+fn literal(value: &Value) -> Result<String> {
+    Ok(match value {
+        Value::String(s) | Value::Regex(s) => format!("'{}'", s.replace('\'', "''")),
+        Value::Integer(i) => i.to_string(),
+        // Debug keeps the decimal point, so 2.0 stays numeric rather than integer
+        Value::Float(f) | Value::Number(f) => format!("{:?}", f),
+        Value::Boolean(b) => if *b { "TRUE" } else { "FALSE" }.to_string(),
+        Value::Null => "NULL".to_string(),
+        Value::Percent(p) => format!("{:?}", p / 100.0),
+        Value::Money { amount, .. } => format!("{:?}", amount),
+        Value::List(items) => {
+            let items: Result<Vec<String>> = items.iter().map(literal).collect();
+            format!("({})", items?.join(", "))
+        }
+        Value::Object(_) => bail!("Record literals are not supported in SQL"),
+    })
+}
+
+struct Generator<'a> {
+    columns: &'a ColumnMap,
+    /// Rules of the view, inlined where another rule reads their target
+    derived: BTreeMap<&'a str, &'a Expression>,
+    /// Rules being inlined, innermost last, to catch cycles
+    expanding: Vec<&'a str>,
+    require_locators: bool,
+    /// Entities read, with the number of columns read from each
+    entities: BTreeMap<&'a str, usize>,
+    systems: BTreeSet<String>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(columns: &'a ColumnMap, derived: BTreeMap<&'a str, &'a Expression>, require_locators: bool) -> Self {
+        Generator {
+            columns,
+            derived,
+            expanding: Vec::new(),
+            require_locators,
+            entities: BTreeMap::new(),
+            systems: BTreeSet::new(),
+        }
+    }
+
+    fn attribute(&mut self, name: &'a str) -> Result<String> {
+        if let Some(derived) = self.derived.get(name).copied() {
+            if self.expanding.contains(&name) {
+                bail!("Rule '{}' depends on itself", name);
+            }
+            self.expanding.push(name);
+            let sql = self.expr(derived)?;
+            self.expanding.pop();
+            return Ok(format!("({})", sql));
+        }
+        match self.columns.get(name) {
+            Some(locator) => {
+                *self.entities.entry(&locator.entity).or_default() += 1;
+                self.systems.extend(locator.system.clone());
+                Ok(format!("{}.{}", quote(&locator.entity), quote(&locator.column)))
+            }
+            None if self.require_locators => bail!("Attribute '{}' has no persistence locator", name),
+            None => Ok(quote(name)),
+        }
+    }
+
+    fn expr(&mut self, expr: &'a Expression) -> Result<String> {
+        match expr {
+            Expression::Literal(value) => literal(value),
+            Expression::Identifier(name) | Expression::Variable(name) => self.attribute(name),
+            Expression::BinaryOp { left, op, right } => self.binary(left, op, right),
+            Expression::UnaryOp { op, operand } => {
+                let operand = self.expr(operand)?;
+                Ok(match op {
+                    UnaryOperator::Not => format!("(NOT {})", operand),
+                    UnaryOperator::Minus => format!("(-{})", operand),
+                    UnaryOperator::Plus => operand,
+                })
+            }
+            Expression::FunctionCall { name, args } => self.function(name, args),
+            Expression::Conditional { condition, then_expr, else_expr } => {
+                let condition = self.expr(condition)?;
+                let then_expr = self.expr(then_expr)?;
+                let else_expr = match else_expr {
+                    Some(else_expr) => self.expr(else_expr)?,
+                    None => "NULL".to_string(),
+                };
+                Ok(format!("CASE WHEN {} THEN {} ELSE {} END", condition, then_expr, else_expr))
+            }
+            Expression::Case { branches, else_expr } => {
+                let mut sql = String::from("CASE");
+                for (condition, result) in branches {
+                    sql.push_str(&format!(" WHEN {} THEN {}", self.expr(condition)?, self.expr(result)?));
+                }
+                if let Some(else_expr) = else_expr {
+                    sql.push_str(&format!(" ELSE {}", self.expr(else_expr)?));
+                }
+                sql.push_str(" END");
+                Ok(sql)
+            }
+            Expression::List(items) => Ok(format!("({})", self.list(items)?.join(", "))),
+            Expression::Cast { expr, data_type } => {
+                let sql_type = match data_type.to_lowercase().as_str() {
+                    "string" | "text" => "text",
+                    "number" | "float" | "decimal" => "numeric",
+                    "integer" | "int" => "bigint",
+                    "boolean" | "bool" => "boolean",
+                    other => bail!("Cast to '{}' has no PostgreSQL equivalent", other),
+                };
+                Ok(format!("({})::{}", self.expr(expr)?, sql_type))
+            }
+            _ => bail!("Unsupported expression type for PostgreSQL generation"),
+        }
+    }
+
+    fn list(&mut self, items: &'a [Expression]) -> Result<Vec<String>> {
+        items.iter().map(|item| self.expr(item)).collect()
+    }
+
+    fn binary(&mut self, left: &'a Expression, op: &BinaryOperator, right: &'a Expression) -> Result<String> {
+        if let Expression::Range { start, end, inclusive } = right {
+            let value = self.expr(left)?;
+            let (start, end) = (self.expr(start)?, self.expr(end)?);
+            let check = if *inclusive {
+                format!("{} BETWEEN {} AND {}", value, start, end)
+            } else {
+                format!("{} >= {} AND {} < {}", value, start, value, end)
+            };
+            let negation = if *op == BinaryOperator::NotIn { "NOT " } else { "" };
+            return Ok(format!("({}({}))", negation, check));
+        }
+
+        let (l, r) = (self.expr(left)?, self.expr(right)?);
+        let operator = match op {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => return Ok(format!("(({})::numeric / {})", l, r)),
+            BinaryOperator::Power => "^",
+            BinaryOperator::Modulo => "%",
+            BinaryOperator::Equals => "=",
+            BinaryOperator::NotEquals => "<>",
+            BinaryOperator::LessThan => "<",
+            BinaryOperator::LessThanOrEqual => "<=",
+            BinaryOperator::GreaterThan => ">",
+            BinaryOperator::GreaterThanOrEqual => ">=",
+            BinaryOperator::And => "AND",
+            BinaryOperator::Or => "OR",
+            BinaryOperator::Matches => "~",
+            BinaryOperator::NotMatches => "!~",
+            BinaryOperator::Concat => "||",
+            BinaryOperator::In => "IN",
+            BinaryOperator::NotIn => "NOT IN",
+            BinaryOperator::Contains => return Ok(format!("(strpos({}, {}) > 0)", l, r)),
+            BinaryOperator::StartsWith => return Ok(format!("starts_with({}, {})", l, r)),
+            BinaryOperator::EndsWith => return Ok(format!("(right({}, length({})) = {})", l, r, r)),
+            BinaryOperator::Between => bail!("BETWEEN needs a range on its right"),
+        };
+        Ok(format!("({} {} {})", l, operator, r))
+    }
+
+    fn function(&mut self, name: &str, args: &'a [Expression]) -> Result<String> {
+        let name = name.to_uppercase();
+        let arity = |expected: &[usize]| {
+            if expected.contains(&args.len()) {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!("{} takes {} arguments in PostgreSQL, not {}", name, expected.iter().map(usize::to_string).collect::<Vec<_>>().join(" or "), args.len()))
+            }
+        };
+        match name.as_str() {
+            "LOOKUP" => {
+                arity(&[2])?;
+                let Expression::Literal(Value::String(table)) = &args[1] else {
+                    bail!("LOOKUP needs a literal table name to become PostgreSQL");
+                };
+                let key = self.expr(&args[0])?;
+                Ok(format!("(SELECT \"value\" FROM {} WHERE \"key\" = ({})::text)", quote(table), key))
+            }
+            "SUBSTRING" => {
+                arity(&[2, 3])?;
+                let text = self.expr(&args[0])?;
+                let start = self.expr(&args[1])?;
+                match args.get(2) {
+                    Some(length) => Ok(format!("SUBSTRING({} FROM ({}) + 1 FOR {})", text, start, self.expr(length)?)),
+                    None => Ok(format!("SUBSTRING({} FROM ({}) + 1)", text, start)),
+                }
+            }
+            "ROUND" => {
+                arity(&[1, 2])?;
+                let args = self.list(args)?;
+                Ok(format!("ROUND(({})::numeric{})", args[0], args.get(1).map(|places| format!(", {}", places)).unwrap_or_default()))
+            }
+            "IS_NULL" => {
+                arity(&[1])?;
+                Ok(format!("({} IS NULL)", self.expr(&args[0])?))
+            }
+            "TO_STRING" => {
+                arity(&[1])?;
+                Ok(format!("({})::text", self.expr(&args[0])?))
+            }
+            "TO_NUMBER" => {
+                arity(&[1])?;
+                Ok(format!("({})::numeric", self.expr(&args[0])?))
+            }
+            "MIN" | "MAX" if args.len() > 1 => {
+                let function = if name == "MIN" { "LEAST" } else { "GREATEST" };
+                Ok(format!("{}({})", function, self.list(args)?.join(", ")))
+            }
+            "CONCAT" | "UPPER" | "LOWER" | "LENGTH" | "TRIM" | "ABS" | "FLOOR" | "CEIL" => {
+                Ok(format!("{}({})", name, self.list(args)?.join(", ")))
+            }
+            _ => bail!("{} has no PostgreSQL equivalent", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_rule;
+
+    fn rule(input: &str) -> Expression {
+        let (remaining, expr) = parse_rule(input).unwrap();
+        assert!(remaining.trim().is_empty(), "unparsed: {}", remaining);
+        expr
+    }
+
+    fn kyc_columns() -> ColumnMap {
+        let mut columns = ColumnMap::new();
+        columns.add_locator("legal_entity_name", Some("EntityMasterDB"), Some("legal_entities"), Some("entity_name"));
+        columns.add_locator("country", Some("EntityMasterDB"), Some("legal_entities"), Some("country_code"));
+        columns.add_locator("aum", Some("EntityMasterDB"), Some("fund_metrics"), Some("aum_usd"));
+        columns.add_locator("notes", None, None, None);
+        columns
+    }
+
+    #[test]
+    fn test_expressions_read_located_columns() {
+        let columns = kyc_columns();
+        assert_eq!(
+            expression(&rule(r#"label = CONCAT(UPPER(legal_entity_name), " (", country, ")")"#), &columns).unwrap(),
+            r#"CONCAT(UPPER("legal_entities"."entity_name"), ' (', "legal_entities"."country_code", ')') AS "label""#
+        );
+        assert_eq!(
+            expression(&rule(r#"LOOKUP(country, "risk_ratings") == "HIGH" AND aum >= 1000000"#), &columns).unwrap(),
+            r#"(((SELECT "value" FROM "risk_ratings" WHERE "key" = ("legal_entities"."country_code")::text) = 'HIGH') AND ("fund_metrics"."aum_usd" >= 1000000))"#
+        );
+        assert_eq!(expression(&rule("ratio = drawn / limit"), &ColumnMap::new()).unwrap(), r#"(("drawn")::numeric / "limit") AS "ratio""#);
+        assert!(expression(&rule("x = IS_EMAIL(notes)"), &columns).is_err());
+    }
+
+    #[test]
+    fn test_view_joins_entities_and_inlines_derived_attributes() {
+        let columns = kyc_columns();
+        let tier = rule(r#"tier = IF aum > 100000000 THEN "institutional" ELSE "retail""#);
+        let label = rule(r#"label = legal_entity_name & " - " & tier"#);
+        let sql = create_view("client_onboarding_kyc_derived", "entity_id", &[("tier", &tier), ("label", &label)], &columns).unwrap();
+        assert_eq!(
+            sql,
+            r#"CREATE OR REPLACE VIEW "client_onboarding_kyc_derived" AS
+SELECT
+    "fund_metrics"."entity_id",
+    CASE WHEN ("fund_metrics"."aum_usd" > 100000000) THEN 'institutional' ELSE 'retail' END AS "tier",
+    (("legal_entities"."entity_name" || ' - ') || (CASE WHEN ("fund_metrics"."aum_usd" > 100000000) THEN 'institutional' ELSE 'retail' END)) AS "label"
+FROM "fund_metrics"
+LEFT JOIN "legal_entities" ON "legal_entities"."entity_id" = "fund_metrics"."entity_id";"#
+        );
+
+        let unlocated = rule("score = risk_score * 2");
+        assert_eq!(
+            create_view("v", "entity_id", &[("score", &unlocated)], &columns).unwrap_err().to_string(),
+            "Attribute 'risk_score' has no persistence locator"
+        );
+        let cycle = rule("a = a + 1");
+        assert!(create_view("v", "entity_id", &[("a", &cycle)], &columns).is_err());
+    }
+}
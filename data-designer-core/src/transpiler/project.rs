@@ -0,0 +1,258 @@
+//! Standalone cargo projects of transpiled rules
+//!
+//! `generate_rust_project` orders a set of rules by dependency and writes a
+//! crate with one function per derived attribute in `src/rules.rs`, the Rust
+//! runtime shim as `src/runtime.rs`, and a binary that reads one JSON object
+//! of facts per line on stdin and writes it back with the derived attributes
+//! added. Rules the shim can't run are refused, as for rule modules.
+
+use super::runtime::{RUNTIME_FUNCTIONS, RUST_RUNTIME};
+use super::{TargetLanguage, Transpiler, TranspilerOptions};
+use crate::models::Expression;
+use crate::parser::parse_rule;
+use crate::rule_categories::called_functions;
+use crate::rule_graph::{extract_dependencies_from_ast, RuleGraph};
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// A rule going into a project, as stored
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectRule {
+    pub rule_id: String,
+    /// The derived attribute the rule is linked to; without one the rule
+    /// derives what it assigns, else an attribute named after the rule
+    pub attribute: Option<String>,
+    pub definition: String,
+}
+
+/// A generated cargo project: file contents by path relative to its root
+#[derive(Debug, Clone, PartialEq)]
+pub struct RustProject {
+    pub crate_name: String,
+    pub files: BTreeMap<String, String>,
+}
+
+impl RustProject {
+    pub fn write_to(&self, root: &Path) -> std::io::Result<()> {
+        for (path, contents) in &self.files {
+            let path = root.join(path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, contents)?;
+        }
+        Ok(())
+    }
+}
+
+struct PlannedRule<'a> {
+    rule_id: &'a str,
+    attribute: String,
+    expression: Expression,
+    dependencies: Vec<String>,
+}
+
+pub fn generate_rust_project(crate_name: &str, rules: &[ProjectRule]) -> Result<RustProject> {
+    let valid_name = crate_name.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+        && crate_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid_name {
+        bail!("'{}' is not a valid crate name", crate_name);
+    }
+
+    let mut planned: Vec<PlannedRule> = Vec::new();
+    for rule in rules {
+        let expression = match parse_rule(&rule.definition) {
+            Ok((remaining, _)) if !remaining.trim().is_empty() => {
+                bail!("Rule '{}' has unexpected input after it: {}", rule.rule_id, remaining.trim())
+            }
+            Ok((_, expression)) => expression,
+            Err(e) => bail!("Failed to parse rule '{}': {}", rule.rule_id, e),
+        };
+        let (attribute, expression) = match (&rule.attribute, expression) {
+            (Some(attribute), Expression::Assignment { value, .. }) => (attribute.clone(), *value),
+            (Some(attribute), expression) => (attribute.clone(), expression),
+            (None, Expression::Assignment { target, value }) => (target, *value),
+            (None, expression) => (rule.rule_id.clone(), expression),
+        };
+        if let Some(other) = planned.iter().find(|other| other.attribute == attribute) {
+            bail!("Rules '{}' and '{}' both derive {}", other.rule_id, rule.rule_id, attribute);
+        }
+        let dependencies = extract_dependencies_from_ast(&expression);
+        planned.push(PlannedRule { rule_id: &rule.rule_id, attribute, expression, dependencies });
+    }
+
+    let graph = RuleGraph::new(planned.iter().map(|rule| (rule.attribute.as_str(), rule.dependencies.as_slice())));
+    let order = graph.execution_order().context("Cannot order the rules of the project")?;
+    let by_attribute: HashMap<&str, &PlannedRule> = planned.iter().map(|rule| (rule.attribute.as_str(), rule)).collect();
+
+    let transpiler = Transpiler::new(TranspilerOptions { target: TargetLanguage::Rust, ..TranspilerOptions::default() });
+    let mut functions: Vec<(String, &PlannedRule, String)> = Vec::new();
+    for attribute in &order {
+        let rule = by_attribute[attribute.as_str()];
+        for function in called_functions(&rule.expression) {
+            if !RUNTIME_FUNCTIONS.contains(&function.as_str()) {
+                bail!("Rule '{}' calls {}, which the runtime doesn't provide", rule.rule_id, function);
+            }
+        }
+        let name = function_name(&rule.attribute);
+        if let Some((_, other, _)) = functions.iter().find(|(other, _, _)| *other == name) {
+            bail!("Attributes {} and {} would both generate {}", other.attribute, rule.attribute, name);
+        }
+        let expression = transpiler.optimize_expression(&rule.expression)?;
+        let code = transpiler
+            .generate_rust_value(&expression)
+            .with_context(|| format!("Failed to transpile rule '{}'", rule.rule_id))?;
+        functions.push((name, rule, code));
+    }
+
+    let library = crate_name.replace('-', "_");
+    let mut files = BTreeMap::new();
+    files.insert("Cargo.toml".to_string(), cargo_toml(crate_name));
+    files.insert(
+        "src/lib.rs".to_string(),
+        "pub mod rules;\npub mod runtime;\n\npub use rules::{evaluate, RULES};\npub use runtime::{Context, Value};\n".to_string(),
+    );
+    files.insert("src/runtime.rs".to_string(), RUST_RUNTIME.to_string());
+    files.insert("src/rules.rs".to_string(), rules_module(rules.len(), &functions));
+    files.insert("src/main.rs".to_string(), MAIN.replace("{library}", &library));
+    Ok(RustProject { crate_name: crate_name.to_string(), files })
+}
+
+// Attribute names may hold dots and other characters Rust identifiers can't
+fn function_name(attribute: &str) -> String {
+    let name: String = attribute
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    format!("derive_{}", name)
+}
+
+fn cargo_toml(crate_name: &str) -> String {
+    format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\nserde_json = \"1\"\n",
+        crate_name
+    )
+}
+
+fn rules_module(count: usize, functions: &[(String, &PlannedRule, String)]) -> String {
+    let mut module = format!(
+        "// Generated by data-designer from {} rule{}. Do not edit; regenerate instead.\n#![allow(unused_parens, clippy::all)]\n\nuse crate::runtime::*;\n",
+        count,
+        if count == 1 { "" } else { "s" }
+    );
+    for (name, rule, code) in functions {
+        module.push_str(&format!(
+            "\n/// {}, derived by rule {}\npub fn {}(ctx: &Context) -> Value {{\n    {}\n}}\n",
+            rule.attribute, rule.rule_id, name, code
+        ));
+    }
+    module.push_str("\n/// Every rule by the attribute it derives, in dependency order\npub const RULES: &[(&str, Rule)] = &[\n");
+    for (name, rule, _) in functions {
+        module.push_str(&format!("    ({:?}, {}),\n", rule.attribute, name));
+    }
+    module.push_str("];\n\n/// The facts in ctx with every derived attribute added\npub fn evaluate(ctx: &Context) -> Context {\n    evaluate_all(RULES, ctx)\n}\n");
+    module
+}
+
+const MAIN: &str = r#"//! Reads one JSON object of facts per line on stdin and writes each back
+//! with the derived attributes added.
+
+use serde_json::{Map, Value as Json};
+use std::io::{self, BufRead, Write};
+use {library}::{evaluate, Context, Value};
+
+fn from_json(json: &Json) -> Value {
+    match json {
+        Json::Null => Value::Null,
+        Json::Bool(b) => Value::Boolean(*b),
+        Json::Number(n) => n.as_i64().map_or_else(|| Value::Float(n.as_f64().unwrap_or(0.0)), Value::Integer),
+        Json::String(s) => Value::String(s.clone()),
+        Json::Array(items) => Value::List(items.iter().map(from_json).collect()),
+        Json::Object(_) => Value::String(json.to_string()),
+    }
+}
+
+fn to_json(value: &Value) -> Json {
+    match value {
+        Value::Null => Json::Null,
+        Value::Boolean(b) => Json::Bool(*b),
+        Value::Integer(i) => Json::from(*i),
+        Value::Float(f) => Json::from(*f),
+        Value::String(s) => Json::String(s.clone()),
+        Value::List(items) => Json::Array(items.iter().map(to_json).collect()),
+    }
+}
+
+fn main() {
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line.expect("Failed to read stdin");
+        if line.trim().is_empty() {
+            continue;
+        }
+        let facts: Map<String, Json> = serde_json::from_str(&line).expect("Each line must be a JSON object of facts");
+        let mut ctx = Context::new();
+        for (name, value) in &facts {
+            ctx.insert(name.clone(), from_json(value));
+        }
+        let derived: Map<String, Json> = evaluate(&ctx).facts().iter().map(|(name, value)| (name.clone(), to_json(value))).collect();
+        writeln!(stdout, "{}", Json::Object(derived)).expect("Failed to write stdout");
+    }
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(rule_id: &str, attribute: Option<&str>, definition: &str) -> ProjectRule {
+        ProjectRule {
+            rule_id: rule_id.to_string(),
+            attribute: attribute.map(str::to_string),
+            definition: definition.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_project_has_a_function_per_attribute_in_dependency_order() {
+        let rules = [
+            rule("R2", None, "fee = IF tier == \"gold\" THEN net * 0.01 ELSE ROUND(net * 0.02)"),
+            rule("R1", Some("net"), "gross - discount"),
+            rule("R3", Some("Client.label"), "UPPER(name) & \" (\" & tier & \")\""),
+        ];
+        let project = generate_rust_project("client-rules", &rules).unwrap();
+        assert_eq!(
+            project.files.keys().collect::<Vec<_>>(),
+            ["Cargo.toml", "src/lib.rs", "src/main.rs", "src/rules.rs", "src/runtime.rs"]
+        );
+        assert!(project.files["src/main.rs"].contains("use client_rules::{evaluate, Context, Value};"));
+
+        let module = &project.files["src/rules.rs"];
+        assert!(module.contains(
+            "/// net, derived by rule R1\npub fn derive_net(ctx: &Context) -> Value {\n    (ctx.get(\"gross\") - ctx.get(\"discount\"))\n}"
+        ));
+        assert!(module.contains(
+            "if (ctx.get(\"tier\") == Value::String(\"gold\".to_string())) { (ctx.get(\"net\") * Value::Float(0.01)) } else { round(&[(ctx.get(\"net\") * Value::Float(0.02))]) }"
+        ));
+        assert!(module.contains("pub fn derive_client_label(ctx: &Context) -> Value {"));
+        let table = &module[module.find("pub const RULES").unwrap()..];
+        let positions: Vec<usize> = ["\"Client.label\"", "\"net\"", "\"fee\""].iter().map(|name| table.find(name).unwrap()).collect();
+        assert!(positions[1] < positions[2], "net must come before fee:\n{}", table);
+    }
+
+    #[test]
+    fn test_rules_the_runtime_cannot_run_are_refused() {
+        let error = generate_rust_project("rules", &[rule("R1", Some("ok"), "IS_EMAIL(contact)")]).unwrap_err();
+        assert_eq!(error.to_string(), "Rule 'R1' calls IS_EMAIL, which the runtime doesn't provide");
+
+        let rules = [rule("R1", Some("a"), "b + 1"), rule("R2", Some("b"), "a + 1")];
+        assert!(generate_rust_project("rules", &rules).is_err());
+
+        let rules = [rule("R1", Some("a"), "1"), rule("R2", None, "a = 2")];
+        let error = generate_rust_project("rules", &rules).unwrap_err();
+        assert_eq!(error.to_string(), "Rules 'R1' and 'R2' both derive a");
+
+        assert!(generate_rust_project("1rules", &[]).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! Python and TypeScript modules of transpiled rules
+//!
+//! A module holds one function per rule, taking the context of facts and
+//! returning the rule's value, and a `RULES` table of them in dependency order
+//! for the runtime's `evaluate_all`. Function calls become calls into a
+//! runtime shim shipped with the generated code (`PYTHON_RUNTIME` as
+//! `dsl_runtime.py`, `TYPESCRIPT_RUNTIME` as `dsl_runtime.ts`), which gives
+//! CONCAT, SUBSTRING, LOOKUP and the other supported functions the semantics
+//! of the rules engine. Rules calling anything else are refused, rather than
+//! generating code that fails when it runs.
+
+use super::{DslRule, TargetLanguage, Transpiler};
+use crate::rule_categories::called_functions;
+use crate::rule_graph::RuleGraph;
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeSet, HashMap};
+
+pub const PYTHON_RUNTIME: &str = include_str!("runtime/dsl_runtime.py");
+pub const TYPESCRIPT_RUNTIME: &str = include_str!("runtime/dsl_runtime.ts");
+/// The Rust shim, shipped as `src/runtime.rs` of generated projects
+pub const RUST_RUNTIME: &str = include_str!("runtime/dsl_runtime.rs");
+
+/// Functions the runtime shims implement, as the rules engine names them
+pub const RUNTIME_FUNCTIONS: &[&str] = &[
+    "ABS", "CEIL", "CONCAT", "FLOOR", "LENGTH", "LOOKUP", "LOWER", "MAX", "MIN", "ROUND", "SUBSTRING", "TRIM", "UPPER",
+];
+
+/// The runtime shim a module for `target` imports, with its file name
+pub fn runtime_shim(target: &TargetLanguage) -> Option<(&'static str, &'static str)> {
+    match target {
+        TargetLanguage::Python => Some(("dsl_runtime.py", PYTHON_RUNTIME)),
+        TargetLanguage::TypeScript => Some(("dsl_runtime.ts", TYPESCRIPT_RUNTIME)),
+        _ => None,
+    }
+}
+
+impl Transpiler {
+    /// A Python or TypeScript module of `rules`, importing the runtime shim
+    /// from `dsl_runtime` next to it
+    pub fn generate_module(&self, rules: &[DslRule]) -> Result<String> {
+        if runtime_shim(&self.target_language).is_none() {
+            bail!("Rule modules are generated for Python and TypeScript only, not {:?}", self.target_language);
+        }
+
+        let reads: Vec<(&str, Vec<String>)> =
+            rules.iter().map(|rule| (rule.name.as_str(), rule.dependencies.clone())).collect();
+        let graph = RuleGraph::new(reads.iter().map(|(name, deps)| (*name, deps.as_slice())));
+        let order = graph.execution_order().context("Cannot order the rules of the module")?;
+        let by_name: HashMap<&str, &DslRule> = rules.iter().map(|rule| (rule.name.as_str(), rule)).collect();
+
+        let mut used = BTreeSet::new();
+        let mut functions = Vec::new();
+        for name in &order {
+            let rule = by_name[name.as_str()];
+            for function in called_functions(&rule.expression) {
+                if !RUNTIME_FUNCTIONS.contains(&function.as_str()) {
+                    bail!("Rule '{}' calls {}, which the runtime doesn't provide", rule.name, function);
+                }
+                used.insert(function.to_lowercase());
+            }
+            let code = self
+                .transpile(&rule.expression)
+                .with_context(|| format!("Failed to transpile rule '{}'", rule.name))?;
+            functions.push((rule.name.as_str(), code));
+        }
+
+        let header = format!(
+            "Generated by data-designer from {} rule{}. Do not edit; regenerate instead.",
+            rules.len(),
+            if rules.len() == 1 { "" } else { "s" }
+        );
+        Ok(match self.target_language {
+            TargetLanguage::Python => python_module(&header, &functions),
+            _ => typescript_module(&header, &used, &functions),
+        })
+    }
+}
+
+fn python_module(header: &str, functions: &[(&str, String)]) -> String {
+    let mut module = format!("# {}\nfrom dsl_runtime import *\n", header);
+    for (name, code) in functions {
+        module.push_str(&format!("\n\ndef {}(ctx):\n    return {}\n", name, code));
+    }
+    module.push_str("\n\nRULES = {\n");
+    for (name, _) in functions {
+        module.push_str(&format!("    \"{}\": {},\n", name, name));
+    }
+    module.push_str("}\n");
+    module
+}
+
+fn typescript_module(header: &str, used: &BTreeSet<String>, functions: &[(&str, String)]) -> String {
+    let imports: Vec<String> = ["type Context".to_string(), "type Rule".to_string()].into_iter().chain(used.iter().cloned()).collect();
+    let mut module = format!("// {}\nimport {{ {} }} from \"./dsl_runtime\";\n", header, imports.join(", "));
+    for (name, code) in functions {
+        module.push_str(&format!("\nexport function {}(ctx: Context): unknown {{\n  return {};\n}}\n", name, code));
+    }
+    module.push_str("\nexport const RULES: Record<string, Rule> = {\n");
+    for (name, _) in functions {
+        module.push_str(&format!("  {},\n", name));
+    }
+    module.push_str("};\n");
+    module
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transpiler::{DslTranspiler, TranspilerOptions};
+
+    const RULES: &str = include_str!("../../tests/golden/transpiler/rules.dsl");
+
+    // Source attributes aren't defined anywhere, so dependency analysis is off
+    fn rules(text: &str) -> Vec<DslRule> {
+        DslTranspiler { validation_enabled: true, dependency_analysis: false }.transpile_dsl_to_rules(text).unwrap()
+    }
+
+    fn module(target: TargetLanguage) -> String {
+        let rules = rules(RULES);
+        let transpiler = Transpiler::new(TranspilerOptions { target, ..TranspilerOptions::default() });
+        transpiler.generate_module(&rules).unwrap()
+    }
+
+    // Regenerate with UPDATE_GOLDEN=1 after an intended change to the output
+    fn assert_golden(generated: &str, file: &str) {
+        let path = format!("{}/tests/golden/transpiler/{}", env!("CARGO_MANIFEST_DIR"), file);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, generated).unwrap();
+        }
+        let expected = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(generated, expected, "{} is out of date; rerun with UPDATE_GOLDEN=1 if the change is intended", file);
+    }
+
+    #[test]
+    fn test_python_module_matches_golden_file() {
+        assert_golden(&module(TargetLanguage::Python), "rules.py");
+    }
+
+    #[test]
+    fn test_typescript_module_matches_golden_file() {
+        assert_golden(&module(TargetLanguage::TypeScript), "rules.ts");
+    }
+
+    #[test]
+    fn test_functions_missing_from_the_runtime_are_refused() {
+        let rules = rules("flagged = IS_EMAIL(contact)");
+        let transpiler = Transpiler::new(TranspilerOptions { target: TargetLanguage::Python, ..TranspilerOptions::default() });
+        let error = transpiler.generate_module(&rules).unwrap_err();
+        assert_eq!(error.to_string(), "Rule 'flagged' calls IS_EMAIL, which the runtime doesn't provide");
+
+        let transpiler = Transpiler::new(TranspilerOptions { target: TargetLanguage::SQL, ..TranspilerOptions::default() });
+        assert!(transpiler.generate_module(&[]).is_err());
+    }
+}
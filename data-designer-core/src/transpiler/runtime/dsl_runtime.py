@@ -0,0 +1,69 @@
+"""Runtime for rules transpiled to Python by data-designer.
+
+Generated rule modules import this file. Each rule is a function of a
+context dict holding the facts; missing facts read as None. Functions behave
+as in the rules engine: SUBSTRING counts from 0, CONCAT prints numbers and
+booleans the way the engine does, and LOOKUP returns None for a missing key.
+Register lookup tables in LOOKUP_TABLES before evaluating rules that use them.
+"""
+
+from math import ceil, floor
+
+LOOKUP_TABLES = {}
+
+
+def to_text(value):
+    if value is None:
+        return "null"
+    if isinstance(value, bool):
+        return "true" if value else "false"
+    if isinstance(value, float) and value.is_integer():
+        return str(int(value))
+    if isinstance(value, list):
+        return "[" + ", ".join(to_text(item) for item in value) + "]"
+    return str(value)
+
+
+def concat(*args):
+    return "".join(to_text(arg) for arg in args)
+
+
+def substring(text, start, length=None):
+    text = to_text(text)
+    if length is None:
+        return text[start:]
+    return text[start:start + length]
+
+
+def lookup(key, table):
+    if table not in LOOKUP_TABLES:
+        raise KeyError(f"Lookup table '{table}' not found")
+    return LOOKUP_TABLES[table].get(to_text(key))
+
+
+def upper(text):
+    return to_text(text).upper()
+
+
+def lower(text):
+    return to_text(text).lower()
+
+
+def length(value):
+    return len(value) if isinstance(value, list) else len(to_text(value))
+
+
+def trim(text):
+    return to_text(text).strip()
+
+
+def evaluate_all(rules, ctx):
+    """Runs rules, a dict of rule functions in dependency order, over a copy of ctx.
+
+    Facts already in ctx are kept rather than recomputed, as in the rules engine.
+    """
+    facts = dict(ctx)
+    for name, rule in rules.items():
+        if name not in facts:
+            facts[name] = rule(facts)
+    return facts
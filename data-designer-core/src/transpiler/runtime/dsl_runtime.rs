@@ -0,0 +1,383 @@
+//! Runtime for rules transpiled to Rust by data-designer.
+//!
+//! Generated rule modules import this file. Each rule is a function of a
+//! Context holding the facts; missing facts read as Null. Values and functions
+//! behave as in the rules engine: division always gives a float, SUBSTRING
+//! counts from 0 and LOOKUP returns Null for a missing key. Percentages arrive
+//! as fractions and money as its amount. Where the engine reports an error,
+//! such as a division by zero, the rule panics with the engine's message.
+//! Register lookup tables with register_lookup_table before evaluating rules
+//! that use them.
+
+#![allow(dead_code)]
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+use std::sync::RwLock;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    List(Vec<Value>),
+}
+
+impl Value {
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    /// Whether a list holds `item`, or a text holds it as a substring
+    pub fn contains(&self, item: &Value) -> bool {
+        match self {
+            Value::List(items) => items.contains(item),
+            Value::String(text) => text.contains(&item.to_string()),
+            _ => false,
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Boolean(b)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        Value::Integer(i)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(f: f64) -> Self {
+        Value::Float(f)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(items: Vec<Value>) -> Self {
+        Value::List(items)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::String(s) => write!(f, "{}", s),
+            Value::List(items) => {
+                let items: Vec<String> = items.iter().map(Value::to_string).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Integer(l), Value::Integer(r)) => l == r,
+            (Value::String(l), Value::String(r)) => l == r,
+            (Value::Boolean(l), Value::Boolean(r)) => l == r,
+            (Value::Null, Value::Null) => true,
+            (Value::List(l), Value::List(r)) => l == r,
+            (l, r) => match (l.as_f64(), r.as_f64()) {
+                (Some(l), Some(r)) => (l - r).abs() < f64::EPSILON,
+                _ => false,
+            },
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Integer(l), Value::Integer(r)) => Some(l.cmp(r)),
+            (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+            (l, r) => match (l.as_f64(), r.as_f64()) {
+                (Some(l), Some(r)) => Some(l.partial_cmp(&r).unwrap_or(Ordering::Equal)),
+                _ => panic!("Cannot compare {:?} and {:?}", l, r),
+            },
+        }
+    }
+}
+
+fn arithmetic(verb: &str, l: Value, r: Value, integers: fn(i64, i64) -> i64, floats: fn(f64, f64) -> f64) -> Value {
+    match (&l, &r) {
+        (Value::Integer(l), Value::Integer(r)) => Value::Integer(integers(*l, *r)),
+        _ => match (l.as_f64(), r.as_f64()) {
+            (Some(l), Some(r)) => Value::Float(floats(l, r)),
+            _ => panic!("Cannot {} {:?} and {:?}", verb, l, r),
+        },
+    }
+}
+
+impl Add for Value {
+    type Output = Value;
+
+    fn add(self, other: Value) -> Value {
+        arithmetic("add", self, other, |l, r| l + r, |l, r| l + r)
+    }
+}
+
+impl Sub for Value {
+    type Output = Value;
+
+    fn sub(self, other: Value) -> Value {
+        arithmetic("subtract", self, other, |l, r| l - r, |l, r| l - r)
+    }
+}
+
+impl Mul for Value {
+    type Output = Value;
+
+    fn mul(self, other: Value) -> Value {
+        arithmetic("multiply", self, other, |l, r| l * r, |l, r| l * r)
+    }
+}
+
+impl Div for Value {
+    type Output = Value;
+
+    fn div(self, other: Value) -> Value {
+        match (self.as_f64(), other.as_f64()) {
+            (Some(l), Some(r)) => {
+                if r == 0.0 {
+                    panic!("Division by zero");
+                }
+                Value::Float(l / r)
+            }
+            _ => panic!("Cannot divide {:?} and {:?}", self, other),
+        }
+    }
+}
+
+impl Rem for Value {
+    type Output = Value;
+
+    fn rem(self, other: Value) -> Value {
+        match (self, other) {
+            (Value::Integer(_), Value::Integer(0)) => panic!("Modulo by zero"),
+            (Value::Integer(l), Value::Integer(r)) => Value::Integer(l % r),
+            _ => panic!("Modulo operation requires integers"),
+        }
+    }
+}
+
+impl Neg for Value {
+    type Output = Value;
+
+    fn neg(self) -> Value {
+        match self {
+            Value::Integer(i) => Value::Integer(-i),
+            Value::Float(f) => Value::Float(-f),
+            other => panic!("Cannot apply unary minus to {:?}", other),
+        }
+    }
+}
+
+pub fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Boolean(b) => *b,
+        Value::Integer(i) => *i != 0,
+        Value::Float(f) => *f != 0.0,
+        Value::String(s) => !s.is_empty(),
+        Value::List(items) => !items.is_empty(),
+    }
+}
+
+/// The facts a rule reads and the attributes rules derive
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Context {
+    facts: HashMap<String, Value>,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, name: &str) -> Value {
+        self.facts.get(name).cloned().unwrap_or(Value::Null)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.facts.contains_key(name)
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<Value>) {
+        self.facts.insert(name.into(), value.into());
+    }
+
+    pub fn facts(&self) -> &HashMap<String, Value> {
+        &self.facts
+    }
+}
+
+pub type Rule = fn(&Context) -> Value;
+
+/// Runs rules, in dependency order, over a copy of ctx. Facts already in ctx
+/// are kept rather than recomputed, as in the rules engine.
+pub fn evaluate_all(rules: &[(&str, Rule)], ctx: &Context) -> Context {
+    let mut facts = ctx.clone();
+    for (name, rule) in rules {
+        if !facts.contains(name) {
+            let value = rule(&facts);
+            facts.insert(*name, value);
+        }
+    }
+    facts
+}
+
+static LOOKUP_TABLES: RwLock<Option<HashMap<String, HashMap<String, String>>>> = RwLock::new(None);
+
+pub fn register_lookup_table(name: &str, entries: HashMap<String, String>) {
+    let mut tables = LOOKUP_TABLES.write().unwrap();
+    tables.get_or_insert_with(HashMap::new).insert(name.to_string(), entries);
+}
+
+fn arity(name: &str, args: &[Value], counts: std::ops::RangeInclusive<usize>) {
+    if !counts.contains(&args.len()) {
+        panic!("{} takes {} to {} arguments, not {}", name, counts.start(), counts.end(), args.len());
+    }
+}
+
+fn number(name: &str, value: &Value) -> f64 {
+    value.as_f64().unwrap_or_else(|| panic!("{} requires a numeric argument", name))
+}
+
+pub fn concat(args: &[Value]) -> Value {
+    Value::String(args.iter().map(Value::to_string).collect())
+}
+
+pub fn power(args: &[Value]) -> Value {
+    arity("POWER", args, 2..=2);
+    match (&args[0], &args[1]) {
+        (Value::Integer(l), Value::Integer(r)) if *r >= 0 => Value::Integer(l.pow(*r as u32)),
+        (l, r) => Value::Float(number("POWER", l).powf(number("POWER", r))),
+    }
+}
+
+pub fn text_contains(args: &[Value]) -> Value {
+    arity("CONTAINS", args, 2..=2);
+    Value::Boolean(args[0].to_string().contains(&args[1].to_string()))
+}
+
+pub fn starts_with(args: &[Value]) -> Value {
+    arity("STARTS_WITH", args, 2..=2);
+    Value::Boolean(args[0].to_string().starts_with(&args[1].to_string()))
+}
+
+pub fn ends_with(args: &[Value]) -> Value {
+    arity("ENDS_WITH", args, 2..=2);
+    Value::Boolean(args[0].to_string().ends_with(&args[1].to_string()))
+}
+
+pub fn upper(args: &[Value]) -> Value {
+    arity("UPPER", args, 1..=1);
+    Value::String(args[0].to_string().to_uppercase())
+}
+
+pub fn lower(args: &[Value]) -> Value {
+    arity("LOWER", args, 1..=1);
+    Value::String(args[0].to_string().to_lowercase())
+}
+
+pub fn trim(args: &[Value]) -> Value {
+    arity("TRIM", args, 1..=1);
+    Value::String(args[0].to_string().trim().to_string())
+}
+
+pub fn length(args: &[Value]) -> Value {
+    arity("LENGTH", args, 1..=1);
+    match &args[0] {
+        Value::List(items) => Value::Integer(items.len() as i64),
+        other => Value::Integer(other.to_string().len() as i64),
+    }
+}
+
+pub fn substring(args: &[Value]) -> Value {
+    arity("SUBSTRING", args, 2..=3);
+    let start = number("SUBSTRING", &args[1]) as usize;
+    let text = args[0].to_string();
+    let chars = text.chars().skip(start);
+    Value::String(match args.get(2) {
+        Some(length) => chars.take(number("SUBSTRING", length) as usize).collect(),
+        None => chars.collect(),
+    })
+}
+
+pub fn abs(args: &[Value]) -> Value {
+    arity("ABS", args, 1..=1);
+    match &args[0] {
+        Value::Integer(i) => Value::Integer(i.abs()),
+        other => Value::Float(number("ABS", other).abs()),
+    }
+}
+
+fn to_integer(name: &str, args: &[Value], f: fn(f64) -> f64) -> Value {
+    arity(name, args, 1..=1);
+    match &args[0] {
+        Value::Integer(i) => Value::Integer(*i),
+        other => Value::Integer(f(number(name, other)) as i64),
+    }
+}
+
+pub fn round(args: &[Value]) -> Value {
+    to_integer("ROUND", args, f64::round)
+}
+
+pub fn floor(args: &[Value]) -> Value {
+    to_integer("FLOOR", args, f64::floor)
+}
+
+pub fn ceil(args: &[Value]) -> Value {
+    to_integer("CEIL", args, f64::ceil)
+}
+
+pub fn min(args: &[Value]) -> Value {
+    let first = args.first().unwrap_or_else(|| panic!("MIN requires at least 1 argument"));
+    args.iter().fold(first, |least, value| if value < least { value } else { least }).clone()
+}
+
+pub fn max(args: &[Value]) -> Value {
+    let first = args.first().unwrap_or_else(|| panic!("MAX requires at least 1 argument"));
+    args.iter().fold(first, |most, value| if value > most { value } else { most }).clone()
+}
+
+pub fn lookup(args: &[Value]) -> Value {
+    arity("LOOKUP", args, 2..=2);
+    let table = args[1].to_string();
+    let tables = LOOKUP_TABLES.read().unwrap();
+    let entries = tables
+        .as_ref()
+        .and_then(|tables| tables.get(&table))
+        .unwrap_or_else(|| panic!("Lookup table '{}' not found", table));
+    entries.get(&args[0].to_string()).map_or(Value::Null, |value| Value::String(value.clone()))
+}
@@ -0,0 +1,96 @@
+// Runtime for rules transpiled to TypeScript by data-designer.
+//
+// Generated rule modules import this file. Each rule is a function of a
+// context map holding the facts; missing facts read as undefined. Functions
+// behave as in the rules engine: SUBSTRING counts from 0, CONCAT prints
+// numbers and booleans the way the engine does, and LOOKUP returns null for a
+// missing key. Register lookup tables in lookupTables before evaluating rules
+// that use them.
+
+// eslint-disable-next-line @typescript-eslint/no-explicit-any
+export type Context = Map<string, any>;
+
+export type Rule = (ctx: Context) => unknown;
+
+export const lookupTables: Record<string, Record<string, string>> = {};
+
+export function toText(value: unknown): string {
+  if (value === null || value === undefined) {
+    return "null";
+  }
+  if (Array.isArray(value)) {
+    return `[${value.map(toText).join(", ")}]`;
+  }
+  return String(value);
+}
+
+export function concat(...args: unknown[]): string {
+  return args.map(toText).join("");
+}
+
+export function substring(text: unknown, start: number, length?: number): string {
+  const chars = Array.from(toText(text));
+  const end = length === undefined ? chars.length : start + length;
+  return chars.slice(start, end).join("");
+}
+
+export function lookup(key: unknown, table: string): string | null {
+  const entries = lookupTables[table];
+  if (entries === undefined) {
+    throw new Error(`Lookup table '${table}' not found`);
+  }
+  return Object.prototype.hasOwnProperty.call(entries, toText(key)) ? entries[toText(key)] : null;
+}
+
+export function upper(text: unknown): string {
+  return toText(text).toUpperCase();
+}
+
+export function lower(text: unknown): string {
+  return toText(text).toLowerCase();
+}
+
+export function length(value: unknown): number {
+  return Array.isArray(value) ? value.length : Array.from(toText(value)).length;
+}
+
+export function trim(text: unknown): string {
+  return toText(text).trim();
+}
+
+export function abs(value: number): number {
+  return Math.abs(value);
+}
+
+export function round(value: number, places = 0): number {
+  const factor = 10 ** places;
+  return Math.round(value * factor) / factor;
+}
+
+export function floor(value: number): number {
+  return Math.floor(value);
+}
+
+export function ceil(value: number): number {
+  return Math.ceil(value);
+}
+
+export function min(...values: number[]): number {
+  return Math.min(...values);
+}
+
+export function max(...values: number[]): number {
+  return Math.max(...values);
+}
+
+// Runs rules, in dependency order, over a copy of ctx. Facts already in ctx
+// are kept rather than recomputed, as in the rules engine.
+export function evaluateAll(rules: Record<string, Rule>, ctx: Context): Context {
+  const facts: Context = new Map(ctx);
+  for (const [name, rule] of Object.entries(rules)) {
+    if (!facts.has(name)) {
+      facts.set(name, rule(facts));
+    }
+  }
+  return facts;
+}
@@ -0,0 +1,251 @@
+//! Source maps from transpiled code back to the rule text
+//!
+//! `Transpiler::transpile_with_source_map` returns, next to the generated
+//! code, a side table of the generated line/column range of every
+//! sub-expression and the span of rule text it came from. When generated
+//! Rust or Rhai fails at some line and column, `SourceMaps` finds the
+//! innermost DSL fragment there so the error can name it.
+
+use super::Transpiler;
+use crate::models::Expression;
+use crate::parser::{parse_rule_with_spans, ExpressionSpan};
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// A position in a text; both parts count from 1 and columns in characters,
+/// as compilers and Rhai report them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LineColumn {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl LineColumn {
+    fn at(text: &str, offset: usize) -> Self {
+        let before = &text[..offset];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        LineColumn {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+/// Generated code from `generated_start` up to, not including,
+/// `generated_end` came from bytes `source_start..source_end` of the rule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mapping {
+    pub generated_start: LineColumn,
+    pub generated_end: LineColumn,
+    pub source_start: usize,
+    pub source_end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceMap {
+    pub rule_id: String,
+    /// The rule text the source offsets point into
+    pub source: String,
+    /// Outer expressions before the ones inside them
+    pub mappings: Vec<Mapping>,
+}
+
+/// The DSL fragment behind a generated location
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    pub rule_id: String,
+    pub start: LineColumn,
+    pub end: LineColumn,
+    pub text: String,
+}
+
+impl SourceMap {
+    /// The innermost rule fragment generated code at `line`/`column` came from
+    pub fn lookup(&self, line: usize, column: usize) -> Option<SourceLocation> {
+        let position = LineColumn { line, column };
+        let mapping = self
+            .mappings
+            .iter()
+            .rev()
+            .find(|mapping| mapping.generated_start <= position && position < mapping.generated_end)?;
+        Some(SourceLocation {
+            rule_id: self.rule_id.clone(),
+            start: LineColumn::at(&self.source, mapping.source_start),
+            end: LineColumn::at(&self.source, mapping.source_end),
+            text: self.source[mapping.source_start..mapping.source_end].to_string(),
+        })
+    }
+}
+
+/// Source maps of transpiled rules, by rule id
+#[derive(Debug, Clone, Default)]
+pub struct SourceMaps {
+    maps: HashMap<String, SourceMap>,
+}
+
+impl SourceMaps {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, map: SourceMap) {
+        self.maps.insert(map.rule_id.clone(), map);
+    }
+
+    pub fn get(&self, rule_id: &str) -> Option<&SourceMap> {
+        self.maps.get(rule_id)
+    }
+
+    /// Where in its rule's text generated code of `rule_id` at `line`/`column`
+    /// came from; None for unknown rules and for helpers the transpiler adds
+    pub fn map_generated_location(&self, rule_id: &str, line: usize, column: usize) -> Option<SourceLocation> {
+        self.get(rule_id)?.lookup(line, column)
+    }
+}
+
+impl Transpiler {
+    /// Parse `source` and transpile it like `transpile`, recording where each
+    /// sub-expression of the generated code came from in the rule text
+    pub fn transpile_with_source_map(&self, rule_id: &str, source: &str) -> Result<(String, SourceMap)> {
+        let (remaining, (expr, spans)) =
+            parse_rule_with_spans(source).map_err(|e| anyhow!("Failed to parse rule '{}': {}", rule_id, e))?;
+        if !remaining.trim().is_empty() {
+            bail!("Unexpected input after rule '{}': {}", rule_id, remaining.trim());
+        }
+
+        let expr = if self.optimizations_enabled { self.optimize_expression(&expr)? } else { expr };
+        let code = self.generate(&expr)?;
+
+        let mut mapper = Mapper { transpiler: self, code: &code, spans: &spans, found: Vec::new() };
+        mapper.visit(&expr, 0..code.len(), &mut 0, 0..source.len(), &mut 0);
+        let mappings = mapper
+            .found
+            .into_iter()
+            .filter_map(|(generated, source)| Some((generated?, source?)))
+            .map(|(generated, source)| Mapping {
+                generated_start: LineColumn::at(&code, generated.start),
+                generated_end: LineColumn::at(&code, generated.end),
+                source_start: source.start,
+                source_end: source.end,
+            })
+            .collect();
+
+        let map = SourceMap { rule_id: rule_id.to_string(), source: source.to_string(), mappings };
+        Ok((code, map))
+    }
+}
+
+type Found = (Option<Range<usize>>, Option<Range<usize>>);
+
+/// Pairs every sub-expression with its code, found inside its parent's code,
+/// and its rule text: the span the parser recorded for it, or else the spans
+/// of its parts. Optimized and folded sub-expressions have no recorded span,
+/// so lookups inside them land on the nearest enclosing one.
+struct Mapper<'a> {
+    transpiler: &'a Transpiler,
+    code: &'a str,
+    spans: &'a [ExpressionSpan],
+    found: Vec<Found>,
+}
+
+impl Mapper<'_> {
+    fn visit(
+        &mut self,
+        expr: &Expression,
+        code_within: Range<usize>,
+        code_cursor: &mut usize,
+        source_within: Range<usize>,
+        source_cursor: &mut usize,
+    ) -> Option<Range<usize>> {
+        let generated = self.transpiler.generate_fragment(expr).ok().filter(|fragment| !fragment.is_empty()).and_then(|fragment| {
+            // Parts usually come out in order, but not always: `x IN list` is `list.contains(&x)`
+            find(self.code, &fragment, *code_cursor..code_within.end)
+                .or_else(|| find(self.code, &fragment, code_within.clone()))
+        });
+        let recorded = self
+            .spans
+            .iter()
+            .find(|span| span.start >= *source_cursor && span.end <= source_within.end && span.expression == *expr)
+            .map(|span| span.start..span.end);
+
+        let index = self.found.len();
+        self.found.push((generated.clone(), None));
+
+        let child_code = generated.clone().unwrap_or(code_within);
+        let child_source = recorded.clone().unwrap_or(source_within);
+        let (mut child_code_cursor, mut child_source_cursor) = (child_code.start, child_source.start);
+        let mut parts: Option<Range<usize>> = None;
+        for child in expr.children() {
+            let span = self.visit(child, child_code.clone(), &mut child_code_cursor, child_source.clone(), &mut child_source_cursor);
+            if let Some(span) = span {
+                parts = Some(match parts {
+                    Some(parts) => parts.start.min(span.start)..parts.end.max(span.end),
+                    None => span,
+                });
+            }
+        }
+
+        let source = recorded.or(parts);
+        if let Some(span) = &source {
+            *source_cursor = span.end;
+        }
+        if let Some(generated) = &generated {
+            *code_cursor = generated.end;
+        }
+        self.found[index].1 = source.clone();
+        source
+    }
+}
+
+fn find(code: &str, fragment: &str, within: Range<usize>) -> Option<Range<usize>> {
+    let start = within.start + code.get(within.clone())?.find(fragment)?;
+    Some(start..start + fragment.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transpiler::{TargetLanguage, TranspilerOptions};
+
+    fn transpiler(target: TargetLanguage) -> Transpiler {
+        Transpiler::new(TranspilerOptions { target, ..TranspilerOptions::default() })
+    }
+
+    #[test]
+    fn test_generated_locations_map_to_the_innermost_fragment() {
+        let source = "IF tier == \"gold\"\n    THEN amount * 0.01\n    ELSE UPPER(name)";
+        let (code, map) = transpiler(TargetLanguage::Rust).transpile_with_source_map("fee", source).unwrap();
+        let mut maps = SourceMaps::new();
+        maps.insert(map);
+
+        let column = |fragment: &str| code.find(fragment).unwrap() + 1;
+        let location = maps.map_generated_location("fee", 1, column("ctx.get(\"amount\")")).unwrap();
+        assert_eq!(location.text, "amount");
+        assert_eq!((location.start, location.end), (LineColumn { line: 2, column: 10 }, LineColumn { line: 2, column: 16 }));
+
+        let location = maps.map_generated_location("fee", 1, column("upper(")).unwrap();
+        assert_eq!(location.text, "UPPER(name)");
+        assert_eq!(location.start, LineColumn { line: 3, column: 10 });
+
+        // The condition's operator belongs to the comparison, not either side
+        let location = maps.map_generated_location("fee", 1, column(" == ") + 1).unwrap();
+        assert_eq!(location.text, "tier == \"gold\"");
+
+        assert_eq!(maps.map_generated_location("fee", 1, 1).unwrap().text, source);
+        assert!(maps.map_generated_location("other", 1, 1).is_none());
+    }
+
+    #[test]
+    fn test_rhai_helpers_are_not_mapped() {
+        let (code, map) = transpiler(TargetLanguage::Rhai)
+            .transpile_with_source_map("ratio", "ratio = (paid - refunded) / count")
+            .unwrap();
+        assert!(code.starts_with("fn dd_div"));
+        assert!(map.lookup(1, 1).is_none());
+        assert_eq!(map.lookup(2, 1).unwrap().text, "(paid - refunded) / count");
+        assert_eq!(map.lookup(2, "dd_div(".len() + 1).unwrap().text, "(paid - refunded)");
+        assert_eq!(map.lookup(2, "dd_div((".len() + 1).unwrap().text, "paid");
+    }
+}
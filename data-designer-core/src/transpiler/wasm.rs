@@ -0,0 +1,638 @@
+//! Rules lowered to standalone WebAssembly modules
+//!
+//! web-ui and onboarding-ui evaluate a compiled rule client-side instead of
+//! calling the backend; `web-ui/src/wasm_utils.rs` has the loader. A module
+//! imports nothing. ABI version 1:
+//!
+//! - `memory`: fact `i` of the metadata's `facts` lives in a 16-byte slot at
+//!   `16 * i`, an `i32` tag at +0 and an `f64` payload at +8. Facts the caller
+//!   doesn't have are left as null (all zeroes).
+//! - Tags: 0 null, 1 boolean (payload 0 or 1), 2 integer, 3 float, 4 string.
+//!   A string's payload is its index in the metadata's `strings`, whose entry
+//!   0 is always `""`; strings not in the table get negative ids, distinct
+//!   per distinct string.
+//! - `dd_evaluate() -> i32`: 0, or the first error (1 type error, 2 division
+//!   by zero, 3 needs the server: comparing strings by order).
+//! - `dd_result_tag() -> i32` and `dd_result_value() -> f64`: the rule's value,
+//!   encoded as facts are.
+//! - `dd_abi_version() -> i32` and `dd_error() -> i32`.
+//! - A `dd_rule` custom section holds the metadata as JSON.
+//!
+//! Only what the module can answer exactly as the evaluator does is compiled:
+//! arithmetic, comparison, logic, IF/CASE, IN/NOT IN over lists and ranges,
+//! and BETWEEN. Function calls, MATCHES, string operators, units and list
+//! values are rejected, and those rules stay on the server.
+
+use crate::models::{BinaryOperator, Expression, UnaryOperator, Value};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use wasm_encoder::{
+    BlockType, CodeSection, ConstExpr, CustomSection, ExportKind, ExportSection, Function, FunctionSection,
+    GlobalSection, GlobalType, Instruction, MemArg, MemorySection, MemoryType, Module, TypeSection, ValType,
+};
+
+pub const ABI_VERSION: i32 = 1;
+pub const METADATA_SECTION: &str = "dd_rule";
+pub const SLOT_SIZE: u32 = 16;
+
+pub const TAG_NULL: i32 = 0;
+pub const TAG_BOOL: i32 = 1;
+pub const TAG_INT: i32 = 2;
+pub const TAG_FLOAT: i32 = 3;
+pub const TAG_STRING: i32 = 4;
+
+pub const ERROR_TYPE: i32 = 1;
+pub const ERROR_DIVISION_BY_ZERO: i32 = 2;
+pub const ERROR_UNSUPPORTED: i32 = 3;
+
+// One 64 KiB page of slots
+const MAX_FACTS: usize = 65536 / SLOT_SIZE as usize;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WasmRuleMetadata {
+    pub abi: i32,
+    pub facts: Vec<String>,
+    pub strings: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WasmRule {
+    /// The module, metadata section included
+    pub module: Vec<u8>,
+    pub metadata: WasmRuleMetadata,
+}
+
+// Type indices
+const TYPE_I32: u32 = 0;
+const TYPE_F64: u32 = 1;
+const TYPE_BINARY: u32 = 2;
+const TYPE_UNARY: u32 = 3;
+const TYPE_TRUTHY: u32 = 4;
+const TYPE_VALUE: u32 = 5;
+const TYPE_FAIL: u32 = 6;
+
+// Function indices, in the order they are defined
+const FAIL: u32 = 0;
+const TRUTHY: u32 = 1;
+const ADD: u32 = 2;
+const SUBTRACT: u32 = 3;
+const MULTIPLY: u32 = 4;
+const DIVIDE: u32 = 5;
+const LESS: u32 = 6;
+const LESS_OR_EQUAL: u32 = 7;
+const GREATER: u32 = 8;
+const GREATER_OR_EQUAL: u32 = 9;
+const EQUALS: u32 = 10;
+const BOTH: u32 = 11;
+const EITHER: u32 = 12;
+const NEGATE: u32 = 13;
+const PLUS: u32 = 14;
+const NOT: u32 = 15;
+const ABI: u32 = 16;
+const EVALUATE: u32 = 17;
+const RESULT_TAG: u32 = 18;
+const RESULT_VALUE: u32 = 19;
+const ERROR: u32 = 20;
+
+const GLOBAL_ERROR: u32 = 0;
+const GLOBAL_RESULT_TAG: u32 = 1;
+const GLOBAL_RESULT_VALUE: u32 = 2;
+
+/// Compile a parsed rule (an assignment compiles its value)
+pub fn compile(expr: &Expression) -> Result<WasmRule> {
+    let mut codegen = Codegen {
+        facts: Vec::new(),
+        strings: vec![String::new()],
+    };
+    let mut body = vec![Instruction::I32Const(0), Instruction::GlobalSet(GLOBAL_ERROR)];
+    codegen.emit(expr, &mut body)?;
+    if codegen.facts.len() > MAX_FACTS {
+        bail!("Rule reads {} facts; a WASM module holds at most {}", codegen.facts.len(), MAX_FACTS);
+    }
+    body.extend([
+        Instruction::GlobalSet(GLOBAL_RESULT_VALUE),
+        Instruction::GlobalSet(GLOBAL_RESULT_TAG),
+        Instruction::GlobalGet(GLOBAL_ERROR),
+        Instruction::End,
+    ]);
+
+    let metadata = WasmRuleMetadata {
+        abi: ABI_VERSION,
+        facts: codegen.facts,
+        strings: codegen.strings,
+    };
+    Ok(WasmRule {
+        module: assemble(&body, &metadata)?,
+        metadata,
+    })
+}
+
+struct Codegen {
+    facts: Vec<String>,
+    strings: Vec<String>,
+}
+
+impl Codegen {
+    fn fact(&mut self, name: &str) -> u32 {
+        let index = self.facts.iter().position(|f| f == name).unwrap_or_else(|| {
+            self.facts.push(name.to_string());
+            self.facts.len() - 1
+        });
+        index as u32 * SLOT_SIZE
+    }
+
+    fn string(&mut self, s: &str) -> f64 {
+        let index = self.strings.iter().position(|t| t == s).unwrap_or_else(|| {
+            self.strings.push(s.to_string());
+            self.strings.len() - 1
+        });
+        index as f64
+    }
+
+    /// Leaves the expression's tag and payload on the stack
+    fn emit(&mut self, expr: &Expression, out: &mut Vec<Instruction<'static>>) -> Result<()> {
+        match expr {
+            Expression::Literal(value) => {
+                let (tag, payload) = match value {
+                    Value::Null => (TAG_NULL, 0.0),
+                    Value::Boolean(b) => (TAG_BOOL, if *b { 1.0 } else { 0.0 }),
+                    Value::Integer(i) => (TAG_INT, *i as f64),
+                    Value::Float(f) | Value::Number(f) => (TAG_FLOAT, *f),
+                    Value::String(s) => (TAG_STRING, self.string(s)),
+                    other => bail!("{:?} literals are not supported in WASM modules", other),
+                };
+                out.extend([Instruction::I32Const(tag), Instruction::F64Const(payload)]);
+            }
+            Expression::Identifier(name) | Expression::Variable(name) => {
+                let address = self.fact(name) as i32;
+                out.extend([
+                    Instruction::I32Const(address),
+                    Instruction::I32Load(mem_arg(0, 2)),
+                    Instruction::I32Const(address),
+                    Instruction::F64Load(mem_arg(8, 3)),
+                ]);
+            }
+            Expression::Assignment { value, .. } => self.emit(value, out)?,
+            Expression::BinaryOp { op: op @ (BinaryOperator::And | BinaryOperator::Or), left, right } => {
+                // Short-circuits like the evaluator: the right side only runs when it decides
+                self.emit(left, out)?;
+                out.extend([Instruction::Call(TRUTHY), Instruction::If(BlockType::FunctionType(TYPE_VALUE))]);
+                if *op == BinaryOperator::And {
+                    self.emit_truth(right, out)?;
+                    out.extend([Instruction::Else, Instruction::I32Const(TAG_BOOL), Instruction::F64Const(0.0)]);
+                } else {
+                    out.extend([Instruction::I32Const(TAG_BOOL), Instruction::F64Const(1.0), Instruction::Else]);
+                    self.emit_truth(right, out)?;
+                }
+                out.push(Instruction::End);
+            }
+            Expression::BinaryOp { op, left, right } => {
+                if let Expression::Range { start, end, inclusive } = right.as_ref() {
+                    self.emit(left, out)?;
+                    self.emit(start, out)?;
+                    out.push(Instruction::Call(GREATER_OR_EQUAL));
+                    self.emit(left, out)?;
+                    self.emit(end, out)?;
+                    out.extend([
+                        Instruction::Call(if *inclusive { LESS_OR_EQUAL } else { LESS }),
+                        Instruction::Call(BOTH),
+                    ]);
+                    negate_if(*op == BinaryOperator::NotIn, out);
+                    return Ok(());
+                }
+                match op {
+                    BinaryOperator::In | BinaryOperator::NotIn => {
+                        let items: Vec<Expression> = match right.as_ref() {
+                            Expression::List(items) => items.clone(),
+                            Expression::Literal(Value::List(items)) => {
+                                items.iter().cloned().map(Expression::Literal).collect()
+                            }
+                            _ => bail!("IN needs a list literal in WASM modules"),
+                        };
+                        if items.is_empty() {
+                            out.extend([Instruction::I32Const(TAG_BOOL), Instruction::F64Const(0.0)]);
+                        }
+                        for (index, item) in items.iter().enumerate() {
+                            self.emit(left, out)?;
+                            self.emit(item, out)?;
+                            out.push(Instruction::Call(EQUALS));
+                            if index > 0 {
+                                out.push(Instruction::Call(EITHER));
+                            }
+                        }
+                        negate_if(*op == BinaryOperator::NotIn, out);
+                    }
+                    _ => {
+                        let (function, negate) = match op {
+                            BinaryOperator::Add => (ADD, false),
+                            BinaryOperator::Subtract => (SUBTRACT, false),
+                            BinaryOperator::Multiply => (MULTIPLY, false),
+                            BinaryOperator::Divide => (DIVIDE, false),
+                            BinaryOperator::LessThan => (LESS, false),
+                            BinaryOperator::LessThanOrEqual => (LESS_OR_EQUAL, false),
+                            BinaryOperator::GreaterThan => (GREATER, false),
+                            BinaryOperator::GreaterThanOrEqual => (GREATER_OR_EQUAL, false),
+                            BinaryOperator::Equals => (EQUALS, false),
+                            BinaryOperator::NotEquals => (EQUALS, true),
+                            other => bail!("{:?} is not supported in WASM modules", other),
+                        };
+                        self.emit(left, out)?;
+                        self.emit(right, out)?;
+                        out.push(Instruction::Call(function));
+                        negate_if(negate, out);
+                    }
+                }
+            }
+            Expression::UnaryOp { op, operand } => {
+                self.emit(operand, out)?;
+                out.push(Instruction::Call(match op {
+                    UnaryOperator::Not => NOT,
+                    UnaryOperator::Minus => NEGATE,
+                    UnaryOperator::Plus => PLUS,
+                }));
+            }
+            Expression::Conditional { condition, then_expr, else_expr } => {
+                self.emit(condition, out)?;
+                out.extend([Instruction::Call(TRUTHY), Instruction::If(BlockType::FunctionType(TYPE_VALUE))]);
+                self.emit(then_expr, out)?;
+                out.push(Instruction::Else);
+                match else_expr {
+                    Some(else_expr) => self.emit(else_expr, out)?,
+                    None => out.extend([Instruction::I32Const(TAG_NULL), Instruction::F64Const(0.0)]),
+                }
+                out.push(Instruction::End);
+            }
+            Expression::Case { branches, else_expr } => self.emit_case(branches, else_expr.as_deref(), out)?,
+            other => bail!("{} is not supported in WASM modules", expression_kind(other)),
+        }
+        Ok(())
+    }
+
+    /// An AND/OR operand reduced to a boolean
+    fn emit_truth(&mut self, expr: &Expression, out: &mut Vec<Instruction<'static>>) -> Result<()> {
+        out.push(Instruction::I32Const(TAG_BOOL));
+        self.emit(expr, out)?;
+        out.extend([Instruction::Call(TRUTHY), Instruction::F64ConvertI32U]);
+        Ok(())
+    }
+
+    fn emit_case(
+        &mut self,
+        branches: &[(Expression, Expression)],
+        else_expr: Option<&Expression>,
+        out: &mut Vec<Instruction<'static>>,
+    ) -> Result<()> {
+        let Some(((condition, result), rest)) = branches.split_first() else {
+            match else_expr {
+                Some(else_expr) => self.emit(else_expr, out)?,
+                None => out.extend([Instruction::I32Const(TAG_NULL), Instruction::F64Const(0.0)]),
+            }
+            return Ok(());
+        };
+        self.emit(condition, out)?;
+        out.extend([Instruction::Call(TRUTHY), Instruction::If(BlockType::FunctionType(TYPE_VALUE))]);
+        self.emit(result, out)?;
+        out.push(Instruction::Else);
+        self.emit_case(rest, else_expr, out)?;
+        out.push(Instruction::End);
+        Ok(())
+    }
+}
+
+fn negate_if(negate: bool, out: &mut Vec<Instruction<'static>>) {
+    if negate {
+        out.push(Instruction::Call(NOT));
+    }
+}
+
+fn expression_kind(expr: &Expression) -> &'static str {
+    match expr {
+        Expression::FunctionCall { .. } => "A function call",
+        Expression::List(_) => "A list value",
+        Expression::Cast { .. } => "CAST",
+        Expression::Lambda { .. } => "A lambda",
+        Expression::Range { .. } => "A range outside BETWEEN/IN",
+        _ => "This expression",
+    }
+}
+
+fn mem_arg(offset: u64, align: u32) -> MemArg {
+    MemArg {
+        offset,
+        align,
+        memory_index: 0,
+    }
+}
+
+/// `tag - TAG_INT <= 1`, unsigned: integer or float
+fn numeric(tag_local: u32) -> [Instruction<'static>; 5] {
+    [
+        Instruction::LocalGet(tag_local),
+        Instruction::I32Const(TAG_INT),
+        Instruction::I32Sub,
+        Instruction::I32Const(1),
+        Instruction::I32LeU,
+    ]
+}
+
+/// With an error code on the stack: record it and return null
+fn fail_and_return() -> [Instruction<'static>; 4] {
+    [
+        Instruction::Call(FAIL),
+        Instruction::I32Const(TAG_NULL),
+        Instruction::F64Const(0.0),
+        Instruction::Return,
+    ]
+}
+
+fn function(instructions: impl IntoIterator<Item = Instruction<'static>>) -> Function {
+    let mut function = Function::new([]);
+    for instruction in instructions {
+        function.instruction(&instruction);
+    }
+    function.instruction(&Instruction::End);
+    function
+}
+
+/// Type error unless both operands are numbers
+fn require_numbers(out: &mut Vec<Instruction<'static>>) {
+    out.extend(numeric(0));
+    out.extend(numeric(2));
+    out.extend([Instruction::I32And, Instruction::I32Eqz, Instruction::If(BlockType::Empty)]);
+    out.push(Instruction::I32Const(ERROR_TYPE));
+    out.extend(fail_and_return());
+    out.push(Instruction::End);
+}
+
+fn arithmetic(op: Instruction<'static>) -> Function {
+    let mut out = Vec::new();
+    require_numbers(&mut out);
+    // Integer unless either side is a float, as in the evaluator
+    out.extend([
+        Instruction::I32Const(TAG_FLOAT),
+        Instruction::I32Const(TAG_INT),
+        Instruction::LocalGet(0),
+        Instruction::I32Const(TAG_FLOAT),
+        Instruction::I32Eq,
+        Instruction::LocalGet(2),
+        Instruction::I32Const(TAG_FLOAT),
+        Instruction::I32Eq,
+        Instruction::I32Or,
+        Instruction::Select,
+        Instruction::LocalGet(1),
+        Instruction::LocalGet(3),
+        op,
+    ]);
+    function(out)
+}
+
+fn divide() -> Function {
+    let mut out = Vec::new();
+    require_numbers(&mut out);
+    out.extend([
+        Instruction::LocalGet(3),
+        Instruction::F64Const(0.0),
+        Instruction::F64Eq,
+        Instruction::If(BlockType::Empty),
+        Instruction::I32Const(ERROR_DIVISION_BY_ZERO),
+    ]);
+    out.extend(fail_and_return());
+    out.extend([
+        Instruction::End,
+        Instruction::I32Const(TAG_FLOAT),
+        Instruction::LocalGet(1),
+        Instruction::LocalGet(3),
+        Instruction::F64Div,
+    ]);
+    function(out)
+}
+
+fn comparison(op: Instruction<'static>) -> Function {
+    let mut out = Vec::new();
+    out.extend(numeric(0));
+    out.extend(numeric(2));
+    out.extend([Instruction::I32And, Instruction::I32Eqz, Instruction::If(BlockType::Empty)]);
+    // The evaluator orders strings; the module only knows their ids
+    out.extend([
+        Instruction::I32Const(ERROR_UNSUPPORTED),
+        Instruction::I32Const(ERROR_TYPE),
+        Instruction::LocalGet(0),
+        Instruction::I32Const(TAG_STRING),
+        Instruction::I32Eq,
+        Instruction::LocalGet(2),
+        Instruction::I32Const(TAG_STRING),
+        Instruction::I32Eq,
+        Instruction::I32And,
+        Instruction::Select,
+    ]);
+    out.extend(fail_and_return());
+    out.extend([
+        Instruction::End,
+        Instruction::I32Const(TAG_BOOL),
+        Instruction::LocalGet(1),
+        Instruction::LocalGet(3),
+        op,
+        Instruction::F64ConvertI32U,
+    ]);
+    function(out)
+}
+
+fn equals() -> Function {
+    let mut out = vec![Instruction::I32Const(TAG_BOOL)];
+    out.extend(numeric(0));
+    out.extend(numeric(2));
+    out.extend([
+        Instruction::I32And,
+        Instruction::If(BlockType::Result(ValType::F64)),
+        Instruction::LocalGet(1),
+        Instruction::LocalGet(3),
+        Instruction::F64Sub,
+        Instruction::F64Abs,
+        Instruction::F64Const(f64::EPSILON),
+        Instruction::F64Lt,
+        Instruction::F64ConvertI32U,
+        Instruction::Else,
+        // Other types are equal only to the same type and payload; nulls are all zeroes
+        Instruction::LocalGet(0),
+        Instruction::LocalGet(2),
+        Instruction::I32Eq,
+        Instruction::LocalGet(1),
+        Instruction::LocalGet(3),
+        Instruction::F64Eq,
+        Instruction::I32And,
+        Instruction::F64ConvertI32U,
+        Instruction::End,
+    ]);
+    function(out)
+}
+
+fn sign(negate: bool) -> Function {
+    let mut out = Vec::new();
+    out.extend(numeric(0));
+    out.extend([Instruction::I32Eqz, Instruction::If(BlockType::Empty), Instruction::I32Const(ERROR_TYPE)]);
+    out.extend(fail_and_return());
+    out.extend([Instruction::End, Instruction::LocalGet(0), Instruction::LocalGet(1)]);
+    if negate {
+        out.push(Instruction::F64Neg);
+    }
+    function(out)
+}
+
+fn assemble(evaluate: &[Instruction<'static>], metadata: &WasmRuleMetadata) -> Result<Vec<u8>> {
+    let mut types = TypeSection::new();
+    types.ty().function([], [ValType::I32]);
+    types.ty().function([], [ValType::F64]);
+    types.ty().function([ValType::I32, ValType::F64, ValType::I32, ValType::F64], [ValType::I32, ValType::F64]);
+    types.ty().function([ValType::I32, ValType::F64], [ValType::I32, ValType::F64]);
+    types.ty().function([ValType::I32, ValType::F64], [ValType::I32]);
+    types.ty().function([], [ValType::I32, ValType::F64]);
+    types.ty().function([ValType::I32], []);
+
+    // Both operands are booleans from a comparison or EQUALS
+    let boolean = |op| function([Instruction::I32Const(TAG_BOOL), Instruction::LocalGet(1), Instruction::LocalGet(3), op]);
+    let mut evaluate_function = Function::new([]);
+    for instruction in evaluate {
+        evaluate_function.instruction(instruction);
+    }
+    let functions = [
+        (
+            TYPE_FAIL,
+            function([
+                Instruction::GlobalGet(GLOBAL_ERROR),
+                Instruction::I32Eqz,
+                Instruction::If(BlockType::Empty),
+                Instruction::LocalGet(0),
+                Instruction::GlobalSet(GLOBAL_ERROR),
+                Instruction::End,
+            ]),
+        ),
+        // Every tag is truthy when its payload is non-zero: string 0 is ""
+        (TYPE_TRUTHY, function([Instruction::LocalGet(1), Instruction::F64Const(0.0), Instruction::F64Ne])),
+        (TYPE_BINARY, arithmetic(Instruction::F64Add)),
+        (TYPE_BINARY, arithmetic(Instruction::F64Sub)),
+        (TYPE_BINARY, arithmetic(Instruction::F64Mul)),
+        (TYPE_BINARY, divide()),
+        (TYPE_BINARY, comparison(Instruction::F64Lt)),
+        (TYPE_BINARY, comparison(Instruction::F64Le)),
+        (TYPE_BINARY, comparison(Instruction::F64Gt)),
+        (TYPE_BINARY, comparison(Instruction::F64Ge)),
+        (TYPE_BINARY, equals()),
+        (TYPE_BINARY, boolean(Instruction::F64Mul)),
+        (TYPE_BINARY, boolean(Instruction::F64Max)),
+        (TYPE_UNARY, sign(true)),
+        (TYPE_UNARY, sign(false)),
+        (
+            TYPE_UNARY,
+            function([
+                Instruction::I32Const(TAG_BOOL),
+                Instruction::LocalGet(1),
+                Instruction::F64Const(0.0),
+                Instruction::F64Eq,
+                Instruction::F64ConvertI32U,
+            ]),
+        ),
+        (TYPE_I32, function([Instruction::I32Const(ABI_VERSION)])),
+        (TYPE_I32, evaluate_function),
+        (TYPE_I32, function([Instruction::GlobalGet(GLOBAL_RESULT_TAG)])),
+        (TYPE_F64, function([Instruction::GlobalGet(GLOBAL_RESULT_VALUE)])),
+        (TYPE_I32, function([Instruction::GlobalGet(GLOBAL_ERROR)])),
+    ];
+
+    let mut function_types = FunctionSection::new();
+    let mut code = CodeSection::new();
+    for (ty, body) in &functions {
+        function_types.function(*ty);
+        code.function(body);
+    }
+
+    let mut memory = MemorySection::new();
+    memory.memory(MemoryType {
+        minimum: 1,
+        maximum: Some(1),
+        memory64: false,
+        shared: false,
+        page_size_log2: None,
+    });
+
+    let mut globals = GlobalSection::new();
+    let mutable = |val_type| GlobalType {
+        val_type,
+        mutable: true,
+        shared: false,
+    };
+    globals.global(mutable(ValType::I32), &ConstExpr::i32_const(0));
+    globals.global(mutable(ValType::I32), &ConstExpr::i32_const(TAG_NULL));
+    globals.global(mutable(ValType::F64), &ConstExpr::f64_const(0.0));
+
+    let mut exports = ExportSection::new();
+    exports.export("memory", ExportKind::Memory, 0);
+    exports.export("dd_abi_version", ExportKind::Func, ABI);
+    exports.export("dd_evaluate", ExportKind::Func, EVALUATE);
+    exports.export("dd_result_tag", ExportKind::Func, RESULT_TAG);
+    exports.export("dd_result_value", ExportKind::Func, RESULT_VALUE);
+    exports.export("dd_error", ExportKind::Func, ERROR);
+
+    let metadata_json = serde_json::to_vec(metadata)?;
+    let mut module = Module::new();
+    module
+        .section(&types)
+        .section(&function_types)
+        .section(&memory)
+        .section(&globals)
+        .section(&exports)
+        .section(&code)
+        .section(&CustomSection {
+            name: METADATA_SECTION.into(),
+            data: metadata_json.into(),
+        });
+    Ok(module.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_rule;
+
+    fn compile_rule(rule: &str) -> Result<WasmRule> {
+        let (remaining, expr) = parse_rule(rule).unwrap();
+        assert!(remaining.trim().is_empty(), "{}", rule);
+        compile(&expr)
+    }
+
+    #[test]
+    fn test_compiles_valid_modules_with_metadata() {
+        let rules = [
+            "fee = IF volume > 100 THEN volume * 0.01 ELSE 5",
+            r#"tier = CASE WHEN aum >= 1000 THEN "gold" WHEN aum >= 100 THEN "silver" END"#,
+            r#"eligible = country IN ["GB", "IE"] AND score BETWEEN 0 AND 60 OR NOT is_pep"#,
+            "ratio = -drawn / limit",
+        ];
+        for rule in rules {
+            let compiled = compile_rule(rule).unwrap();
+            wasmparser::validate(&compiled.module).unwrap_or_else(|e| panic!("{}: {}", rule, e));
+        }
+
+        let compiled = compile_rule(r#"tier = CASE WHEN aum >= 1000 THEN "gold" WHEN aum >= 100 THEN "silver" END"#).unwrap();
+        assert_eq!(compiled.metadata.facts, vec!["aum"]);
+        assert_eq!(compiled.metadata.strings, vec!["", "gold", "silver"]);
+
+        let section = wasmparser::Parser::new(0)
+            .parse_all(&compiled.module)
+            .find_map(|payload| match payload.unwrap() {
+                wasmparser::Payload::CustomSection(section) if section.name() == METADATA_SECTION => {
+                    Some(section.data().to_vec())
+                }
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(serde_json::from_slice::<WasmRuleMetadata>(&section).unwrap(), compiled.metadata);
+    }
+
+    #[test]
+    fn test_rejects_what_the_module_cannot_answer() {
+        assert!(compile_rule("name = UPPER(first_name)").is_err());
+        assert!(compile_rule(r#"valid = code MATCHES /^[A-Z]{2}$/"#).is_err());
+        assert!(compile_rule(r#"label = first & " " & last"#).is_err());
+        assert!(compile_rule("fee = amount * 5%").is_err());
+    }
+}
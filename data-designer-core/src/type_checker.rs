@@ -0,0 +1,568 @@
+//! Static type checking of parsed rules against the data dictionary
+//!
+//! Attribute types come from the dictionary's canonical models, derived
+//! attributes and dataset samples. Anything that cannot be resolved is
+//! `Unknown` and is compatible with every operator, so the checker only
+//! reports mismatches it can prove (e.g. `country_code * 2`).
+
+use crate::models::{BinaryOperator, DataDictionary, Expression, UnaryOperator, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RuleType {
+    String,
+    Number,
+    Boolean,
+    Date,
+    List,
+    Null,
+    #[default]
+    Unknown,
+}
+
+impl RuleType {
+    /// Map a dictionary data type name (`decimal`, `varchar(20)`, `timestamp`, ...) to a rule type
+    pub fn from_type_name(name: &str) -> Self {
+        let lower = name.trim().to_lowercase();
+        let base = lower.split('(').next().unwrap_or("").trim();
+        match base {
+            "string" | "text" | "varchar" | "char" | "email" | "url" | "uuid" | "enum" | "phone" => RuleType::String,
+            "number" | "integer" | "int" | "bigint" | "smallint" | "decimal" | "numeric" | "float"
+            | "double" | "real" | "currency" | "percentage" | "money" => RuleType::Number,
+            "boolean" | "bool" => RuleType::Boolean,
+            "date" | "datetime" | "timestamp" | "timestamptz" => RuleType::Date,
+            "array" | "list" => RuleType::List,
+            _ => RuleType::Unknown,
+        }
+    }
+
+    fn from_sample(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Bool(_) => RuleType::Boolean,
+            serde_json::Value::Number(_) => RuleType::Number,
+            serde_json::Value::String(_) => RuleType::String,
+            serde_json::Value::Array(_) => RuleType::List,
+            _ => RuleType::Unknown,
+        }
+    }
+
+    /// The type of a computed value; objects have none the checker knows
+    pub fn of_value(value: &Value) -> Self {
+        match value {
+            Value::String(_) | Value::Regex(_) => RuleType::String,
+            Value::Number(_) | Value::Integer(_) | Value::Float(_) | Value::Percent(_) | Value::Money { .. } => {
+                RuleType::Number
+            }
+            Value::Boolean(_) => RuleType::Boolean,
+            Value::List(_) => RuleType::List,
+            Value::Null => RuleType::Null,
+            Value::Object(_) => RuleType::Unknown,
+        }
+    }
+
+    fn is(self, expected: RuleType) -> bool {
+        self == expected || self == RuleType::Unknown || self == RuleType::Null
+    }
+}
+
+impl fmt::Display for RuleType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RuleType::String => "String",
+            RuleType::Number => "Number",
+            RuleType::Boolean => "Boolean",
+            RuleType::Date => "Date",
+            RuleType::List => "List",
+            RuleType::Null => "Null",
+            RuleType::Unknown => "Unknown",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Attribute name to type mapping used while checking
+#[derive(Debug, Clone, Default)]
+pub struct TypeEnv {
+    attributes: HashMap<String, RuleType>,
+}
+
+impl TypeEnv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_dictionary(dictionary: &DataDictionary) -> Self {
+        let mut env = Self::new();
+
+        for dataset in &dictionary.datasets {
+            for (name, sample) in &dataset.attributes {
+                env.insert(name, RuleType::from_sample(sample));
+            }
+        }
+        for model in &dictionary.canonical_models {
+            for attribute in &model.attributes {
+                let rule_type = RuleType::from_type_name(&attribute.data_type);
+                env.insert(&attribute.name, rule_type);
+                env.insert(&format!("{}.{}", model.entity_name, attribute.name), rule_type);
+            }
+        }
+        for derived in &dictionary.derived_attributes {
+            env.insert(&derived.name, RuleType::from_type_name(&derived.attribute_type));
+        }
+
+        env
+    }
+
+    /// Register an attribute; an `Unknown` type never overwrites a known one
+    pub fn insert(&mut self, name: &str, rule_type: RuleType) {
+        if rule_type == RuleType::Unknown && self.attributes.contains_key(name) {
+            return;
+        }
+        self.attributes.insert(name.to_string(), rule_type);
+    }
+
+    /// Look up an attribute by exact name, falling back to the last path segment
+    pub fn get(&self, name: &str) -> Option<RuleType> {
+        self.attributes.get(name).copied().or_else(|| {
+            name.rsplit_once('.')
+                .and_then(|(_, short)| self.attributes.get(short).copied())
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypeDiagnostic {
+    pub message: String,
+    /// Source text of the offending sub-expression, useful for locating it in the rule
+    pub expression: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypeCheckResult {
+    pub inferred: RuleType,
+    pub diagnostics: Vec<TypeDiagnostic>,
+}
+
+impl TypeCheckResult {
+    pub fn is_ok(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+}
+
+/// Infer the type of `ast` and report operator/function mismatches
+pub fn typecheck(ast: &Expression, dictionary: &DataDictionary) -> TypeCheckResult {
+    typecheck_with_env(ast, &TypeEnv::from_dictionary(dictionary))
+}
+
+pub fn typecheck_with_env(ast: &Expression, env: &TypeEnv) -> TypeCheckResult {
+    let mut checker = Checker { env, diagnostics: Vec::new() };
+    let inferred = checker.infer(ast);
+    TypeCheckResult {
+        inferred,
+        diagnostics: checker.diagnostics,
+    }
+}
+
+struct Checker<'a> {
+    env: &'a TypeEnv,
+    diagnostics: Vec<TypeDiagnostic>,
+}
+
+impl Checker<'_> {
+    fn infer(&mut self, expr: &Expression) -> RuleType {
+        match expr {
+            Expression::Literal(value) => RuleType::of_value(value),
+            Expression::Variable(name) | Expression::Identifier(name) => {
+                self.env.get(name).unwrap_or(RuleType::Unknown)
+            }
+            Expression::Assignment { value, .. } => self.infer(value),
+            Expression::List(items) => {
+                for item in items {
+                    self.infer(item);
+                }
+                RuleType::List
+            }
+            Expression::Comprehension { source, .. } => {
+                let source_type = self.infer(source);
+                self.expect(source, source_type, RuleType::List, "FOR ... IN");
+                RuleType::List
+            }
+            Expression::Cast { expr, data_type } => {
+                self.infer(expr);
+                RuleType::from_type_name(data_type)
+            }
+            Expression::UnaryOp { op, operand } => {
+                let operand_type = self.infer(operand);
+                match op {
+                    UnaryOperator::Not => {
+                        self.expect(operand, operand_type, RuleType::Boolean, "NOT");
+                        RuleType::Boolean
+                    }
+                    UnaryOperator::Minus | UnaryOperator::Plus => {
+                        self.expect(operand, operand_type, RuleType::Number, "unary sign");
+                        RuleType::Number
+                    }
+                }
+            }
+            Expression::BinaryOp { left, op, right } => self.infer_binary(expr, left, *op, right),
+            Expression::Conditional { condition, then_expr, else_expr } => {
+                let condition_type = self.infer(condition);
+                self.expect(condition, condition_type, RuleType::Boolean, "IF condition");
+                let then_type = self.infer(then_expr);
+                match else_expr {
+                    Some(else_expr) => {
+                        let else_type = self.infer(else_expr);
+                        if then_type == else_type || else_type == RuleType::Null {
+                            then_type
+                        } else if then_type == RuleType::Null {
+                            else_type
+                        } else {
+                            RuleType::Unknown
+                        }
+                    }
+                    None => then_type,
+                }
+            }
+            Expression::Case { branches, else_expr } => {
+                for (condition, _) in branches {
+                    let condition_type = self.infer(condition);
+                    self.expect(condition, condition_type, RuleType::Boolean, "CASE WHEN condition");
+                }
+                let results = branches.iter().map(|(_, result)| result).chain(else_expr.as_deref());
+                let mut case_type = RuleType::Null;
+                for result in results {
+                    let branch_type = self.infer(result);
+                    if case_type == RuleType::Null {
+                        case_type = branch_type;
+                    } else if branch_type != case_type && branch_type != RuleType::Null {
+                        case_type = RuleType::Unknown;
+                    }
+                }
+                case_type
+            }
+            Expression::FunctionCall { name, args } => self.infer_call(name, args),
+            // Workflow verbs are not value expressions
+            _ => RuleType::Unknown,
+        }
+    }
+
+    fn infer_binary(&mut self, whole: &Expression, left: &Expression, op: BinaryOperator, right: &Expression) -> RuleType {
+        if let Expression::Range { start, end, .. } = right {
+            return self.infer_range_check(whole, left, op, [start, end]);
+        }
+
+        let left_type = self.infer(left);
+        let right_type = self.infer(right);
+        let symbol = operator_symbol(op);
+
+        match op {
+            BinaryOperator::Add => {
+                // `+` also concatenates strings
+                if left_type == RuleType::String && right_type.is(RuleType::String) {
+                    return RuleType::String;
+                }
+                if right_type == RuleType::String && left_type.is(RuleType::String) {
+                    return RuleType::String;
+                }
+                self.expect(left, left_type, RuleType::Number, symbol);
+                self.expect(right, right_type, RuleType::Number, symbol);
+                RuleType::Number
+            }
+            BinaryOperator::Subtract
+            | BinaryOperator::Multiply
+            | BinaryOperator::Divide
+            | BinaryOperator::Power
+            | BinaryOperator::Modulo => {
+                self.expect(left, left_type, RuleType::Number, symbol);
+                self.expect(right, right_type, RuleType::Number, symbol);
+                RuleType::Number
+            }
+            BinaryOperator::Concat => RuleType::String,
+            BinaryOperator::Equals | BinaryOperator::NotEquals => RuleType::Boolean,
+            BinaryOperator::LessThan
+            | BinaryOperator::LessThanOrEqual
+            | BinaryOperator::GreaterThan
+            | BinaryOperator::GreaterThanOrEqual => {
+                if left_type != RuleType::Unknown
+                    && right_type != RuleType::Unknown
+                    && left_type != RuleType::Null
+                    && right_type != RuleType::Null
+                    && left_type != right_type
+                {
+                    self.report(
+                        whole,
+                        format!("Cannot compare {} with {} using '{}'", left_type, right_type, symbol),
+                    );
+                } else if matches!(left_type, RuleType::Boolean | RuleType::List) {
+                    self.report(whole, format!("'{}' is not defined for {} values", symbol, left_type));
+                }
+                RuleType::Boolean
+            }
+            BinaryOperator::And | BinaryOperator::Or => {
+                self.expect(left, left_type, RuleType::Boolean, symbol);
+                self.expect(right, right_type, RuleType::Boolean, symbol);
+                RuleType::Boolean
+            }
+            BinaryOperator::Matches
+            | BinaryOperator::NotMatches
+            | BinaryOperator::Contains
+            | BinaryOperator::StartsWith
+            | BinaryOperator::EndsWith => {
+                self.expect(left, left_type, RuleType::String, symbol);
+                self.expect(right, right_type, RuleType::String, symbol);
+                RuleType::Boolean
+            }
+            BinaryOperator::In | BinaryOperator::NotIn => {
+                self.expect(right, right_type, RuleType::List, symbol);
+                RuleType::Boolean
+            }
+            BinaryOperator::Between => {
+                self.report(whole, format!("BETWEEN expects a range but '{}' is {}", expression_text(right), right_type));
+                RuleType::Boolean
+            }
+        }
+    }
+
+    /// `x BETWEEN lo AND hi` and `x IN lo..hi`: both bounds must compare with `x`
+    fn infer_range_check(&mut self, whole: &Expression, left: &Expression, op: BinaryOperator, bounds: [&Expression; 2]) -> RuleType {
+        let left_type = self.infer(left);
+        let symbol = operator_symbol(op);
+        if !matches!(op, BinaryOperator::Between | BinaryOperator::In | BinaryOperator::NotIn) {
+            self.report(whole, format!("'{}' cannot be applied to a range", symbol));
+        }
+        for bound in bounds {
+            let bound_type = self.infer(bound);
+            if left_type != RuleType::Unknown
+                && bound_type != RuleType::Unknown
+                && left_type != RuleType::Null
+                && bound_type != RuleType::Null
+                && left_type != bound_type
+            {
+                self.report(
+                    whole,
+                    format!("Cannot compare {} with {} using '{}'", left_type, bound_type, symbol),
+                );
+            }
+        }
+        if matches!(left_type, RuleType::Boolean | RuleType::List) {
+            self.report(whole, format!("'{}' is not defined for {} values", symbol, left_type));
+        }
+        RuleType::Boolean
+    }
+
+    fn infer_call(&mut self, name: &str, args: &[Expression]) -> RuleType {
+        let arg_types: Vec<RuleType> = args.iter().map(|arg| self.infer(arg)).collect();
+        let upper = name.to_uppercase();
+
+        match upper.as_str() {
+            "UPPER" | "LOWER" | "TRIM" | "SUBSTRING" => {
+                if let (Some(arg), Some(arg_type)) = (args.first(), arg_types.first()) {
+                    self.expect(arg, *arg_type, RuleType::String, &upper);
+                }
+                RuleType::String
+            }
+            "CONCAT" | "TO_STRING" => RuleType::String,
+            "FORMAT_NUMBER" => {
+                if let (Some(arg), Some(arg_type)) = (args.first(), arg_types.first()) {
+                    self.expect(arg, *arg_type, RuleType::Number, &upper);
+                }
+                RuleType::String
+            }
+            "FORMAT_DATE" | "EXTRACT" => RuleType::String,
+            "EXTRACT_ALL" => RuleType::List,
+            "ABS" | "ROUND" | "FLOOR" | "CEIL" => {
+                if let (Some(arg), Some(arg_type)) = (args.first(), arg_types.first()) {
+                    self.expect(arg, *arg_type, RuleType::Number, &upper);
+                }
+                RuleType::Number
+            }
+            "LENGTH" | "MIN" | "MAX" | "SUM" | "AVG" | "COUNT" | "TO_NUMBER" | "MATCH_COUNT" | "TO_PCT"
+            | "TO_BASIS_POINTS" => RuleType::Number,
+            "HAS" | "IS_NULL" | "IS_EMPTY" | "TO_BOOLEAN" | "ANY" | "ALL" => RuleType::Boolean,
+            "MAP" | "FILTER" => RuleType::List,
+            "LOOKUP" => RuleType::String,
+            _ => RuleType::Unknown,
+        }
+    }
+
+    fn expect(&mut self, expr: &Expression, actual: RuleType, expected: RuleType, context: &str) {
+        if !actual.is(expected) {
+            self.report(
+                expr,
+                format!("{} expects {} but '{}' is {}", context, expected, expression_text(expr), actual),
+            );
+        }
+    }
+
+    fn report(&mut self, expr: &Expression, message: String) {
+        self.diagnostics.push(TypeDiagnostic {
+            message,
+            expression: expression_text(expr),
+        });
+    }
+}
+
+fn operator_symbol(op: BinaryOperator) -> &'static str {
+    match op {
+        BinaryOperator::Add => "+",
+        BinaryOperator::Subtract => "-",
+        BinaryOperator::Multiply => "*",
+        BinaryOperator::Divide => "/",
+        BinaryOperator::Power => "**",
+        BinaryOperator::Modulo => "%",
+        BinaryOperator::Equals => "==",
+        BinaryOperator::NotEquals => "!=",
+        BinaryOperator::LessThan => "<",
+        BinaryOperator::LessThanOrEqual => "<=",
+        BinaryOperator::GreaterThan => ">",
+        BinaryOperator::GreaterThanOrEqual => ">=",
+        BinaryOperator::And => "AND",
+        BinaryOperator::Or => "OR",
+        BinaryOperator::Matches => "MATCHES",
+        BinaryOperator::NotMatches => "NOT_MATCHES",
+        BinaryOperator::Concat => "&",
+        BinaryOperator::Contains => "CONTAINS",
+        BinaryOperator::StartsWith => "STARTS_WITH",
+        BinaryOperator::EndsWith => "ENDS_WITH",
+        BinaryOperator::In => "IN",
+        BinaryOperator::NotIn => "NOT_IN",
+        BinaryOperator::Between => "BETWEEN",
+    }
+}
+
+/// Compact source-like rendering of an expression for diagnostics
+fn expression_text(expr: &Expression) -> String {
+    match expr {
+        Expression::Literal(Value::String(s)) => format!("\"{}\"", s),
+        Expression::Literal(Value::Number(n)) | Expression::Literal(Value::Float(n)) => n.to_string(),
+        Expression::Literal(Value::Integer(i)) => i.to_string(),
+        Expression::Literal(Value::Boolean(b)) => b.to_string(),
+        Expression::Literal(Value::Null) => "null".to_string(),
+        Expression::Literal(Value::Regex(r)) => format!("/{}/", r),
+        Expression::Variable(name) | Expression::Identifier(name) => name.clone(),
+        Expression::BinaryOp { left, op: BinaryOperator::Between, right } => match right.as_ref() {
+            Expression::Range { start, end, inclusive } => format!(
+                "{} {} {} AND {}",
+                expression_text(left),
+                if *inclusive { "BETWEEN" } else { "BETWEEN_EXCLUSIVE" },
+                expression_text(start),
+                expression_text(end)
+            ),
+            _ => format!("{} BETWEEN {}", expression_text(left), expression_text(right)),
+        },
+        Expression::BinaryOp { left, op, right } => format!(
+            "{} {} {}",
+            expression_text(left),
+            operator_symbol(*op),
+            expression_text(right)
+        ),
+        Expression::UnaryOp { op, operand } => match op {
+            UnaryOperator::Not => format!("NOT {}", expression_text(operand)),
+            UnaryOperator::Minus => format!("-{}", expression_text(operand)),
+            UnaryOperator::Plus => format!("+{}", expression_text(operand)),
+        },
+        Expression::FunctionCall { name, args } => format!(
+            "{}({})",
+            name,
+            args.iter().map(expression_text).collect::<Vec<_>>().join(", ")
+        ),
+        Expression::Assignment { target, value } => format!("{} = {}", target, expression_text(value)),
+        Expression::Range { start, end, inclusive } => format!(
+            "{}{}{}",
+            expression_text(start),
+            if *inclusive { "..=" } else { ".." },
+            expression_text(end)
+        ),
+        _ => "<expression>".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_expression;
+
+    fn env() -> TypeEnv {
+        let mut env = TypeEnv::new();
+        env.insert("country_code", RuleType::String);
+        env.insert("balance", RuleType::Number);
+        env.insert("is_active", RuleType::Boolean);
+        env.insert("opened_on", RuleType::Date);
+        env
+    }
+
+    fn check(input: &str) -> TypeCheckResult {
+        let (_, ast) = parse_expression(input).unwrap();
+        typecheck_with_env(&ast, &env())
+    }
+
+    #[test]
+    fn test_string_times_number_is_reported() {
+        let result = check("country_code * 2");
+        assert_eq!(result.diagnostics.len(), 1);
+        assert_eq!(result.diagnostics[0].expression, "country_code");
+        assert!(result.diagnostics[0].message.contains("expects Number"));
+    }
+
+    #[test]
+    fn test_well_typed_rules_pass() {
+        assert!(check("balance * 2 + 10").is_ok());
+        assert_eq!(check("balance * 2 + 10").inferred, RuleType::Number);
+        assert_eq!(check("balance > 100 AND is_active").inferred, RuleType::Boolean);
+        assert_eq!(check("UPPER(country_code)").inferred, RuleType::String);
+    }
+
+    #[test]
+    fn test_case_branches() {
+        assert_eq!(check("CASE WHEN balance > 100 THEN 2 ELSE 1 END").inferred, RuleType::Number);
+        let result = check("CASE WHEN country_code THEN 2 END");
+        assert_eq!(result.diagnostics.len(), 1);
+        assert!(result.diagnostics[0].message.contains("CASE WHEN condition"));
+    }
+
+    #[test]
+    fn test_unknown_attributes_are_not_reported() {
+        assert!(check("mystery * 2").is_ok());
+    }
+
+    #[test]
+    fn test_mismatched_comparison_is_reported() {
+        let result = check("opened_on > balance");
+        assert_eq!(result.diagnostics.len(), 1);
+        assert!(result.diagnostics[0].message.contains("Cannot compare Date with Number"));
+    }
+
+    #[test]
+    fn test_range_bounds_must_match() {
+        assert!(check("balance BETWEEN 1000 AND 5000 AND balance IN 0..=1").is_ok());
+
+        let result = check("balance BETWEEN \"low\" AND 5000");
+        assert_eq!(result.diagnostics.len(), 1);
+        assert!(result.diagnostics[0].message.contains("Cannot compare Number with String using 'BETWEEN'"));
+        assert_eq!(result.diagnostics[0].expression, "balance BETWEEN \"low\" AND 5000");
+
+        assert!(!check("balance == 1..5").is_ok());
+    }
+
+    #[test]
+    fn test_types_from_data_dictionary() {
+        let json = r#"{
+            "datasets": [{"id": "d1", "name": "Clients", "description": "", "attributes": {"country_code": "GB"}}],
+            "lookup_tables": {},
+            "canonical_models": [{
+                "entity_name": "Account",
+                "description": "",
+                "attributes": [{"name": "balance", "data_type": "decimal", "description": "", "embedding": null,
+                                "governance": {"source_type": null, "authorized_source": null, "consumers": []}}]
+            }]
+        }"#;
+        let dictionary = DataDictionary::load_from_json(json).unwrap();
+        let (_, ast) = parse_expression("country_code * Account.balance").unwrap();
+
+        let result = typecheck(&ast, &dictionary);
+        assert_eq!(result.diagnostics.len(), 1);
+        assert_eq!(result.inferred, RuleType::Number);
+    }
+}
@@ -0,0 +1,41 @@
+# Generated by data-designer from 7 rules. Do not edit; regenerate instead.
+from dsl_runtime import *
+
+
+def client_label(ctx):
+    return concat(upper(ctx.get('legal_name')), " (", substring(ctx.get('lei'), 0, 4), ")")
+
+
+def country_risk(ctx):
+    return lookup(ctx.get('domicile_country'), "country_risk")
+
+
+def fee(ctx):
+    return round(max((ctx.get('aum') * 0.0015), 2500), 2)
+
+
+def review_band(ctx):
+    return ("annual" if (ctx.get('aum') >= 1000000000) else ("biennial" if (ctx.get('aum') >= 10000000) else "triennial"))
+
+
+def risk_rating(ctx):
+    return ("HIGH" if ((ctx.get('country_risk') == "HIGH") or ctx.get('is_pep')) else "STANDARD")
+
+
+def sanctioned_region(ctx):
+    return ((ctx.get('domicile_country') in ["IR", "KP", "SY"]) and (not ctx.get('is_exempt')))
+
+
+def summary(ctx):
+    return concat(concat(ctx.get('client_label'), ": "), ctx.get('risk_rating'))
+
+
+RULES = {
+    "client_label": client_label,
+    "country_risk": country_risk,
+    "fee": fee,
+    "review_band": review_band,
+    "risk_rating": risk_rating,
+    "sanctioned_region": sanctioned_region,
+    "summary": summary,
+}
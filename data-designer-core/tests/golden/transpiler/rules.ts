@@ -0,0 +1,40 @@
+// Generated by data-designer from 7 rules. Do not edit; regenerate instead.
+import { type Context, type Rule, concat, lookup, max, round, substring, upper } from "./dsl_runtime";
+
+export function client_label(ctx: Context): unknown {
+  return concat(upper(ctx.get('legal_name')), " (", substring(ctx.get('lei'), 0, 4), ")");
+}
+
+export function country_risk(ctx: Context): unknown {
+  return lookup(ctx.get('domicile_country'), "country_risk");
+}
+
+export function fee(ctx: Context): unknown {
+  return round(max((ctx.get('aum') * 0.0015), 2500), 2);
+}
+
+export function review_band(ctx: Context): unknown {
+  return ((ctx.get('aum') >= 1000000000) ? "annual" : ((ctx.get('aum') >= 10000000) ? "biennial" : "triennial"));
+}
+
+export function risk_rating(ctx: Context): unknown {
+  return (((ctx.get('country_risk') === "HIGH") || ctx.get('is_pep')) ? "HIGH" : "STANDARD");
+}
+
+export function sanctioned_region(ctx: Context): unknown {
+  return (["IR", "KP", "SY"].includes(ctx.get('domicile_country')) && (!ctx.get('is_exempt')));
+}
+
+export function summary(ctx: Context): unknown {
+  return concat(concat(ctx.get('client_label'), ": "), ctx.get('risk_rating'));
+}
+
+export const RULES: Record<string, Rule> = {
+  client_label,
+  country_risk,
+  fee,
+  review_band,
+  risk_rating,
+  sanctioned_region,
+  summary,
+};
@@ -53,7 +53,7 @@ mod simple_parser_tests {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "postgres"))]
 mod database_model_tests {
     use super::*;
     use data_designer_core::db::*;
@@ -0,0 +1,73 @@
+/*
+ * data-designer rules engine: C ABI
+ *
+ * Link against libdata_designer_ffi (.so/.dylib/.dll or the static .a).
+ *
+ * Ownership:
+ *   - Strings passed in are borrowed for the duration of the call and must be
+ *     NUL-terminated UTF-8.
+ *   - Strings returned through char** parameters belong to the caller; release
+ *     them with dd_free_string.
+ *   - Engines are released with dd_free_engine, once, after every other call on
+ *     them has returned.
+ *   - dd_last_error is owned by the library and valid until the next call on
+ *     the same thread.
+ *
+ * Threads: dd_evaluate_json may be called on one engine from many threads at
+ * once. dd_load_rules waits for running evaluations and swaps the rule set in
+ * one step.
+ *
+ * Every function returning int32_t returns DD_OK or one of the codes below;
+ * dd_last_error then describes the failure.
+ */
+
+#ifndef DATA_DESIGNER_H
+#define DATA_DESIGNER_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DD_ABI_VERSION 1
+
+#define DD_OK 0
+#define DD_NULL_POINTER 1
+#define DD_INVALID_UTF8 2
+#define DD_INVALID_JSON 3
+#define DD_INVALID_RULES 4
+#define DD_EVALUATION_FAILED 5
+#define DD_PANIC 6
+
+typedef struct DdEngine DdEngine;
+
+/* ABI version of the loaded library; compare with DD_ABI_VERSION */
+uint32_t dd_abi_version(void);
+
+/* Message for the last failed call on this thread, or NULL after a success */
+const char *dd_last_error(void);
+
+/* Creates an engine with no rules. dictionary_json is a data dictionary (for
+ * the lookup tables rules use) or NULL for an empty one. */
+int32_t dd_create_engine(const char *dictionary_json, DdEngine **out);
+
+/* Replaces the engine's rules with those of a rule document. On failure the
+ * previous rules stay loaded. */
+int32_t dd_load_rules(DdEngine *engine, const char *source);
+
+/* Evaluates every rule against facts_json, a JSON object of attribute values.
+ * *out_json receives the facts with every derived attribute added. */
+int32_t dd_evaluate_json(const DdEngine *engine, const char *facts_json, char **out_json);
+
+/* Releases a string returned by the library; NULL is ignored */
+void dd_free_string(char *s);
+
+/* Releases an engine; NULL is ignored */
+void dd_free_engine(DdEngine *engine);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* DATA_DESIGNER_H */
@@ -0,0 +1,331 @@
+//! C ABI for embedding the rules engine
+//!
+//! The onboarding platform's JVM services call these through JNA/Panama
+//! rather than a round trip to grpc-server. `include/data_designer.h` is the
+//! header and the contract: function signatures, `DD_*` error codes and
+//! ownership rules only ever change together with `DD_ABI_VERSION`.
+//!
+//! Ownership:
+//! - Strings passed in are borrowed for the duration of the call and must be
+//!   NUL-terminated UTF-8.
+//! - Strings handed out through `char **` parameters belong to the caller and
+//!   are released with `dd_free_string`.
+//! - Engines are released with `dd_free_engine`, once, after every other call
+//!   on them has returned.
+//! - `dd_last_error` is owned by the library and valid until the next call on
+//!   the same thread.
+//!
+//! An engine may be evaluated from many threads at once; `dd_load_rules`
+//! waits for running evaluations and swaps the rule set in one step. Panics
+//! never unwind into the caller, they come back as `DD_PANIC`.
+
+use data_designer_core::config::SecurityConfig;
+use data_designer_core::engine::RulesEngine;
+use data_designer_core::evaluator::Facts;
+use data_designer_core::models::{DataDictionary, Value};
+use data_designer_core::rule_bundle::RuleBundle;
+use data_designer_core::rule_repository::{ExportedRule, RuleMetadata};
+use data_designer_core::rule_variants::Applicability;
+use serde_json::Value as JsonValue;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::sync::RwLock;
+
+/// Bumped whenever a signature, error code or ownership rule changes
+pub const DD_ABI_VERSION: u32 = 1;
+
+pub const DD_OK: i32 = 0;
+pub const DD_NULL_POINTER: i32 = 1;
+pub const DD_INVALID_UTF8: i32 = 2;
+pub const DD_INVALID_JSON: i32 = 3;
+pub const DD_INVALID_RULES: i32 = 4;
+pub const DD_EVALUATION_FAILED: i32 = 5;
+pub const DD_PANIC: i32 = 6;
+
+/// Opaque to C callers
+pub struct DdEngine {
+    engine: RwLock<RulesEngine>,
+}
+
+struct Error {
+    code: i32,
+    message: String,
+}
+
+impl Error {
+    fn new(code: i32, message: impl std::fmt::Display) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: Option<String>) {
+    // Interior NULs can't cross the boundary; drop them rather than the message
+    let message = message.map(|m| CString::new(m.replace('\0', "")).unwrap_or_default());
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+/// Runs `f`, recording its error for `dd_last_error` and turning panics into `DD_PANIC`
+fn guard(f: impl FnOnce() -> Result<(), Error>) -> i32 {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => {
+            set_last_error(None);
+            DD_OK
+        }
+        Ok(Err(e)) => {
+            set_last_error(Some(e.message));
+            e.code
+        }
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            set_last_error(Some(format!("Panic in data-designer: {}", message)));
+            DD_PANIC
+        }
+    }
+}
+
+/// # Safety
+/// `s` is null or a NUL-terminated string valid for the call
+unsafe fn read_str<'a>(s: *const c_char, name: &str) -> Result<&'a str, Error> {
+    if s.is_null() {
+        return Err(Error::new(DD_NULL_POINTER, format!("{} is null", name)));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|e| Error::new(DD_INVALID_UTF8, format!("{} is not UTF-8: {}", name, e)))
+}
+
+fn empty_dictionary() -> DataDictionary {
+    DataDictionary {
+        datasets: vec![],
+        lookup_tables: HashMap::new(),
+        derived_attributes: vec![],
+        canonical_models: vec![],
+        solicitation_packs: vec![],
+        axes: vec![],
+    }
+}
+
+/// ABI version of the loaded library, to compare with the header's `DD_ABI_VERSION`
+#[no_mangle]
+pub extern "C" fn dd_abi_version() -> u32 {
+    DD_ABI_VERSION
+}
+
+/// Message for the last failed call on this thread, or null after a success
+#[no_mangle]
+pub extern "C" fn dd_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+}
+
+/// Creates an engine with no rules. `dictionary_json` is a data dictionary
+/// (for the lookup tables rules use) or null for an empty one.
+///
+/// # Safety
+/// `dictionary_json` is null or a NUL-terminated string; `out` is a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn dd_create_engine(dictionary_json: *const c_char, out: *mut *mut DdEngine) -> i32 {
+    guard(|| {
+        if out.is_null() {
+            return Err(Error::new(DD_NULL_POINTER, "out is null"));
+        }
+        *out = ptr::null_mut();
+
+        let dictionary = if dictionary_json.is_null() {
+            empty_dictionary()
+        } else {
+            serde_json::from_str(read_str(dictionary_json, "dictionary_json")?)
+                .map_err(|e| Error::new(DD_INVALID_JSON, format!("Invalid data dictionary: {}", e)))?
+        };
+        let engine = RulesEngine::new(dictionary).map_err(|e| Error::new(DD_INVALID_JSON, format!("{:#}", e)))?;
+
+        *out = Box::into_raw(Box::new(DdEngine {
+            engine: RwLock::new(engine),
+        }));
+        Ok(())
+    })
+}
+
+/// Replaces the engine's rules with those of the rule document `source`.
+/// On failure the previous rules stay loaded.
+///
+/// # Safety
+/// `engine` came from `dd_create_engine` and hasn't been freed; `source` is a
+/// NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn dd_load_rules(engine: *mut DdEngine, source: *const c_char) -> i32 {
+    guard(|| {
+        let engine = engine.as_ref().ok_or_else(|| Error::new(DD_NULL_POINTER, "engine is null"))?;
+        let source = read_str(source, "source")?;
+
+        let document = ExportedRule {
+            metadata: RuleMetadata {
+                rule_id: "ffi".to_string(),
+                rule_name: "ffi".to_string(),
+                description: None,
+                category_id: None,
+                target_attribute_id: None,
+                status: "active".to_string(),
+                version: 1,
+                tags: Vec::new(),
+                valid_from: None,
+                valid_to: None,
+                applies_to: Applicability::default(),
+            },
+            definition: source.to_string(),
+            tests: Vec::new(),
+        };
+
+        // A panic mid-evaluation poisons the lock but leaves the rules intact
+        let mut engine = engine.engine.write().unwrap_or_else(|e| e.into_inner());
+        engine
+            .load_unsigned_bundle(RuleBundle::new(vec![document]), &SecurityConfig::default())
+            .map(|_| ())
+            .map_err(|e| Error::new(DD_INVALID_RULES, format!("{:#}", e)))
+    })
+}
+
+/// Evaluates every rule against `facts_json`, a JSON object of attribute
+/// values, and writes the facts with every derived attribute added to `out_json`
+///
+/// # Safety
+/// `engine` came from `dd_create_engine` and hasn't been freed; `facts_json`
+/// is a NUL-terminated string; `out_json` is a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn dd_evaluate_json(
+    engine: *const DdEngine,
+    facts_json: *const c_char,
+    out_json: *mut *mut c_char,
+) -> i32 {
+    guard(|| {
+        if out_json.is_null() {
+            return Err(Error::new(DD_NULL_POINTER, "out_json is null"));
+        }
+        *out_json = ptr::null_mut();
+
+        let engine = engine.as_ref().ok_or_else(|| Error::new(DD_NULL_POINTER, "engine is null"))?;
+        let facts: JsonValue = serde_json::from_str(read_str(facts_json, "facts_json")?)
+            .map_err(|e| Error::new(DD_INVALID_JSON, format!("Invalid facts: {}", e)))?;
+        let facts: Facts = match facts {
+            JsonValue::Object(facts) => facts.iter().map(|(name, value)| (name.clone(), Value::from_json(value))).collect(),
+            _ => return Err(Error::new(DD_INVALID_JSON, "Facts must be a JSON object")),
+        };
+
+        let engine = engine.engine.read().unwrap_or_else(|e| e.into_inner());
+        let result = engine
+            .evaluate_all(&facts)
+            .map_err(|e| Error::new(DD_EVALUATION_FAILED, format!("{:#}", e)))?;
+
+        let result: serde_json::Map<String, JsonValue> =
+            result.into_iter().map(|(name, value)| (name, value.to_json())).collect();
+        let json = CString::new(JsonValue::Object(result).to_string())
+            .map_err(|e| Error::new(DD_EVALUATION_FAILED, e))?;
+        *out_json = json.into_raw();
+        Ok(())
+    })
+}
+
+/// Releases a string returned by this library; null is ignored
+///
+/// # Safety
+/// `s` came from this library and hasn't been freed
+#[no_mangle]
+pub unsafe extern "C" fn dd_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Releases an engine; null is ignored
+///
+/// # Safety
+/// `engine` came from `dd_create_engine`, hasn't been freed and no other call
+/// on it is running
+#[no_mangle]
+pub unsafe extern "C" fn dd_free_engine(engine: *mut DdEngine) {
+    if !engine.is_null() {
+        drop(Box::from_raw(engine));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(dd_last_error()).to_string_lossy().into_owned() }
+    }
+
+    #[test]
+    fn test_create_load_evaluate_and_free() {
+        unsafe {
+            let mut engine = ptr::null_mut();
+            assert_eq!(dd_create_engine(ptr::null(), &mut engine), DD_OK);
+            assert!(dd_last_error().is_null());
+
+            let rules = c("fee = volume * 0.01\ntax = fee * 0.2");
+            assert_eq!(dd_load_rules(engine, rules.as_ptr()), DD_OK);
+
+            let mut out = ptr::null_mut();
+            let facts = c(r#"{"volume": 1000}"#);
+            assert_eq!(dd_evaluate_json(engine, facts.as_ptr(), &mut out), DD_OK);
+            let result: JsonValue = serde_json::from_str(CStr::from_ptr(out).to_str().unwrap()).unwrap();
+            assert_eq!(result["fee"], 10.0);
+            assert_eq!(result["tax"], 2.0);
+
+            dd_free_string(out);
+            dd_free_engine(engine);
+        }
+    }
+
+    #[test]
+    fn test_errors_are_codes_with_a_message() {
+        unsafe {
+            let mut engine = ptr::null_mut();
+            let dictionary = c("{not json");
+            assert_eq!(dd_create_engine(dictionary.as_ptr(), &mut engine), DD_INVALID_JSON);
+            assert!(engine.is_null());
+            assert!(last_error().starts_with("Invalid data dictionary"));
+
+            assert_eq!(dd_create_engine(ptr::null(), &mut engine), DD_OK);
+            let rules = c("fee = volume * 0.01");
+            assert_eq!(dd_load_rules(engine, rules.as_ptr()), DD_OK);
+
+            // A bad document leaves the loaded rules in place
+            let broken = c("fee = volume *");
+            assert_eq!(dd_load_rules(engine, broken.as_ptr()), DD_INVALID_RULES);
+            assert!(!last_error().is_empty());
+
+            let mut out = ptr::null_mut();
+            assert_eq!(dd_evaluate_json(engine, ptr::null(), &mut out), DD_NULL_POINTER);
+            let facts = c("[1, 2]");
+            assert_eq!(dd_evaluate_json(engine, facts.as_ptr(), &mut out), DD_INVALID_JSON);
+            assert!(out.is_null());
+
+            let facts = c(r#"{"volume": 200}"#);
+            assert_eq!(dd_evaluate_json(engine, facts.as_ptr(), &mut out), DD_OK);
+            assert!(CStr::from_ptr(out).to_str().unwrap().contains(r#""fee":2.0"#));
+
+            dd_free_string(out);
+            dd_free_engine(engine);
+            assert_eq!(dd_abi_version(), DD_ABI_VERSION);
+        }
+    }
+}
@@ -0,0 +1,391 @@
+//! Python bindings for the rules engine
+//!
+//! Built as the `data_designer` extension module with maturin (see
+//! pyproject.toml), so notebooks can parse, validate, evaluate and transpile
+//! rules without a server:
+//!
+//! ```python
+//! import data_designer as dd
+//!
+//! dd.evaluate("fee = volume * 0.01", {"volume": 1000})      # 10.0
+//! df["fee"] = dd.evaluate_batch("fee = volume * 0.01", df)  # one value per row
+//!
+//! engine = dd.RulesEngine("fee = volume * 0.01\ntax = fee * 0.2")
+//! scored = engine.evaluate_batch(table)  # pyarrow Table in, pyarrow Table out
+//! ```
+//!
+//! Batches are lists of dicts, pandas DataFrames or pyarrow Tables and
+//! RecordBatches. Rule errors raise ValueError.
+
+// The code pyo3 generates for #[pyfunction] and #[pymethods] converts PyErr into itself
+#![allow(clippy::useless_conversion)]
+
+use data_designer_core::config::SecurityConfig;
+use data_designer_core::engine::RulesEngine;
+use data_designer_core::evaluator::{evaluate_with_functions, Facts, FunctionLibrary};
+use data_designer_core::models::{DataDictionary, Expression, Value};
+use data_designer_core::parser::parse_rule;
+use data_designer_core::rule_bundle::RuleBundle;
+use data_designer_core::rule_repository::{ExportedRule, RuleMetadata};
+use data_designer_core::rule_variants::Applicability;
+use data_designer_core::transpiler::{DslTranspiler, TargetLanguage, Transpiler, TranspilerOptions};
+use data_designer_core::type_checker::{typecheck_with_env, RuleType, TypeEnv};
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyList, PyString, PyType};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+
+fn value_error(e: impl std::fmt::Display) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+fn parse(rule: &str) -> PyResult<Expression> {
+    match parse_rule(rule) {
+        Ok((remaining, expression)) if remaining.trim().is_empty() => Ok(expression),
+        Ok((remaining, _)) => Err(value_error(format!("Unexpected input: {}", remaining.trim()))),
+        Err(e) => Err(value_error(format!("Rule failed to parse: {}", e))),
+    }
+}
+
+/// Python value to JSON. NaN, as pandas uses for missing values, becomes null;
+/// dates and numpy scalars are converted through `isoformat` and `item`.
+fn to_json(value: &Bound<'_, PyAny>) -> PyResult<JsonValue> {
+    if value.is_none() {
+        return Ok(JsonValue::Null);
+    }
+    if let Ok(b) = value.downcast::<PyBool>() {
+        return Ok(JsonValue::Bool(b.is_true()));
+    }
+    if let Ok(i) = value.extract::<i64>() {
+        return Ok(JsonValue::from(i));
+    }
+    if let Ok(f) = value.downcast::<PyFloat>() {
+        return Ok(serde_json::Number::from_f64(f.value()).map_or(JsonValue::Null, JsonValue::Number));
+    }
+    if let Ok(s) = value.downcast::<PyString>() {
+        return Ok(JsonValue::String(s.to_str()?.to_string()));
+    }
+    if let Ok(dict) = value.downcast::<PyDict>() {
+        return Ok(JsonValue::Object(record(dict)?.into_iter().collect()));
+    }
+    if value.hasattr("isoformat")? {
+        return to_json(&value.call_method0("isoformat")?);
+    }
+    if value.hasattr("item")? {
+        return to_json(&value.call_method0("item")?);
+    }
+    if let Ok(f) = value.extract::<f64>() {
+        return Ok(serde_json::Number::from_f64(f).map_or(JsonValue::Null, JsonValue::Number));
+    }
+    if let Ok(items) = value.iter() {
+        return items.map(|item| to_json(&item?)).collect::<PyResult<Vec<_>>>().map(JsonValue::Array);
+    }
+    Err(PyTypeError::new_err(format!("Unsupported value: {}", value.repr()?)))
+}
+
+fn to_py(py: Python<'_>, value: &JsonValue) -> PyResult<PyObject> {
+    Ok(match value {
+        JsonValue::Null => py.None(),
+        JsonValue::Bool(b) => b.into_py(py),
+        JsonValue::Number(n) => match n.as_i64() {
+            Some(i) => i.into_py(py),
+            None => n.as_f64().unwrap_or(f64::NAN).into_py(py),
+        },
+        JsonValue::String(s) => s.into_py(py),
+        JsonValue::Array(items) => {
+            let items = items.iter().map(|item| to_py(py, item)).collect::<PyResult<Vec<_>>>()?;
+            PyList::new_bound(py, items).into_py(py)
+        }
+        JsonValue::Object(map) => {
+            let dict = PyDict::new_bound(py);
+            for (key, value) in map {
+                dict.set_item(key, to_py(py, value)?)?;
+            }
+            dict.into_py(py)
+        }
+    })
+}
+
+fn record(dict: &Bound<'_, PyDict>) -> PyResult<HashMap<String, JsonValue>> {
+    dict.iter()
+        .map(|(key, value)| Ok((key.str()?.to_str()?.to_string(), to_json(&value)?)))
+        .collect()
+}
+
+fn facts(dict: &Bound<'_, PyDict>) -> PyResult<Facts> {
+    Ok(record(dict)?.iter().map(|(name, value)| (name.clone(), Value::from_json(value))).collect())
+}
+
+/// Where a batch came from, so results go back in the same kind of container
+enum Rows<'py> {
+    Records,
+    Arrow(Bound<'py, PyType>),
+    Pandas(Bound<'py, PyType>),
+}
+
+impl<'py> Rows<'py> {
+    fn read(rows: &Bound<'py, PyAny>) -> PyResult<(Self, Vec<HashMap<String, JsonValue>>)> {
+        let (source, records) = if rows.hasattr("to_pylist")? {
+            (Rows::Arrow(rows.get_type()), rows.call_method0("to_pylist")?)
+        } else if rows.hasattr("to_dict")? && rows.hasattr("columns")? {
+            (Rows::Pandas(rows.get_type()), rows.call_method1("to_dict", ("records",))?)
+        } else {
+            (Rows::Records, rows.clone())
+        };
+
+        let records = records
+            .iter()?
+            .map(|row| {
+                let row = row?;
+                let dict = row.downcast::<PyDict>().map_err(|_| PyTypeError::new_err("Each row must be a dict"))?;
+                record(dict)
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok((source, records))
+    }
+
+    fn wrap(&self, results: Bound<'py, PyList>) -> PyResult<PyObject> {
+        let py = results.py();
+        Ok(match self {
+            Rows::Records => results.into_py(py),
+            Rows::Arrow(table) => table.call_method1("from_pylist", (results,))?.into_py(py),
+            Rows::Pandas(frame) => frame.call1((results,))?.into_py(py),
+        })
+    }
+}
+
+/// The rule's AST as nested dicts
+#[pyfunction(name = "parse")]
+fn parse_py(py: Python<'_>, rule: &str) -> PyResult<PyObject> {
+    let expression = parse(rule)?;
+    to_py(py, &serde_json::to_value(&expression).map_err(value_error)?)
+}
+
+/// Parse and type check every rule of a document. `attribute_types` maps
+/// attribute names to dictionary type names ("number", "string", ...).
+/// Returns the problems found, each a dict with rule, line and message.
+#[pyfunction]
+#[pyo3(signature = (source, attribute_types = None))]
+fn validate(py: Python<'_>, source: &str, attribute_types: Option<HashMap<String, String>>) -> PyResult<PyObject> {
+    let mut type_env = TypeEnv::new();
+    for (name, data_type) in attribute_types.unwrap_or_default() {
+        type_env.insert(&name, RuleType::from_type_name(&data_type));
+    }
+
+    let transpiler = DslTranspiler {
+        validation_enabled: false,
+        dependency_analysis: false,
+    };
+    let diagnostics: Vec<JsonValue> = match transpiler.transpile_dsl_to_rules(source) {
+        Ok(rules) => rules
+            .iter()
+            .flat_map(|rule| {
+                typecheck_with_env(&rule.expression, &type_env).diagnostics.into_iter().map(|diagnostic| {
+                    serde_json::json!({
+                        "rule": rule.name,
+                        "line": rule.line_number,
+                        "expression": diagnostic.expression,
+                        "message": diagnostic.message,
+                    })
+                })
+            })
+            .collect(),
+        Err(errors) => errors
+            .iter()
+            .map(|e| serde_json::json!({
+                "rule": e.rule_name,
+                "line": e.line,
+                "message": e.to_string(),
+            }))
+            .collect(),
+    };
+    to_py(py, &JsonValue::Array(diagnostics))
+}
+
+/// The value of one rule for `facts`, a dict of attribute values
+#[pyfunction]
+fn evaluate(py: Python<'_>, rule: &str, facts: &Bound<'_, PyDict>) -> PyResult<PyObject> {
+    let expression = parse(rule)?;
+    let value = evaluate_with_functions(&expression, &self::facts(facts)?, &FunctionLibrary::new()).map_err(value_error)?;
+    to_py(py, &value.to_json())
+}
+
+/// The value of one rule for each row, as a list. With `strict` off a row the
+/// rule fails for gets None instead of raising.
+#[pyfunction]
+#[pyo3(signature = (rule, rows, strict = true))]
+fn evaluate_batch(py: Python<'_>, rule: &str, rows: &Bound<'_, PyAny>, strict: bool) -> PyResult<PyObject> {
+    let expression = parse(rule)?;
+    let (_, records) = Rows::read(rows)?;
+    // One library for the batch, so regex patterns compile once
+    let functions = FunctionLibrary::new();
+
+    let values = PyList::empty_bound(py);
+    for (index, record) in records.iter().enumerate() {
+        let facts: Facts = record.iter().map(|(name, value)| (name.clone(), Value::from_json(value))).collect();
+        match evaluate_with_functions(&expression, &facts, &functions) {
+            Ok(value) => values.append(to_py(py, &value.to_json())?)?,
+            Err(_) if !strict => values.append(py.None())?,
+            Err(e) => return Err(value_error(format!("Row {}: {}", index, e))),
+        }
+    }
+    Ok(values.into_py(py))
+}
+
+/// One rule's expression, the right-hand side of `name = ...`, as code for
+/// `target`: "python", "sql", "javascript", "typescript", "rust", "rhai" or "postgres"
+#[pyfunction]
+#[pyo3(signature = (rule, target = "python", optimize = true))]
+fn transpile(rule: &str, target: &str, optimize: bool) -> PyResult<String> {
+    let target = match target.to_lowercase().as_str() {
+        "python" => TargetLanguage::Python,
+        "sql" => TargetLanguage::SQL,
+        "javascript" | "js" => TargetLanguage::JavaScript,
+        "typescript" | "ts" => TargetLanguage::TypeScript,
+        "rust" => TargetLanguage::Rust,
+        "rhai" => TargetLanguage::Rhai,
+        "postgres" | "postgresql" => TargetLanguage::PostgresSql,
+        other => return Err(value_error(format!("Unknown transpile target: {}", other))),
+    };
+    let expression = match parse(rule)? {
+        Expression::Assignment { value, .. } => *value,
+        expression => expression,
+    };
+    let transpiler = Transpiler::new(TranspilerOptions {
+        target,
+        optimize,
+        ..TranspilerOptions::default()
+    });
+    transpiler.transpile(&expression).map_err(value_error)
+}
+
+/// A set of rules evaluated together in dependency order, each rule's
+/// output feeding the rules that read it
+#[pyclass(name = "RulesEngine", module = "data_designer")]
+struct PyRulesEngine {
+    engine: RulesEngine,
+}
+
+#[pymethods]
+impl PyRulesEngine {
+    /// `source` is a rule document; `dictionary` an optional data dictionary
+    /// (as JSON-like dicts) for the lookup tables rules use
+    #[new]
+    #[pyo3(signature = (source, dictionary = None))]
+    fn new(source: &str, dictionary: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        let dictionary = match dictionary {
+            Some(dictionary) => serde_json::from_value(to_json(dictionary.as_any())?).map_err(value_error)?,
+            None => DataDictionary {
+                datasets: vec![],
+                lookup_tables: HashMap::new(),
+                derived_attributes: vec![],
+                canonical_models: vec![],
+                solicitation_packs: vec![],
+                axes: vec![],
+            },
+        };
+        let document = ExportedRule {
+            metadata: RuleMetadata {
+                rule_id: "python".to_string(),
+                rule_name: "python".to_string(),
+                description: None,
+                category_id: None,
+                target_attribute_id: None,
+                status: "active".to_string(),
+                version: 1,
+                tags: Vec::new(),
+                valid_from: None,
+                valid_to: None,
+                applies_to: Applicability::default(),
+            },
+            definition: source.to_string(),
+            tests: Vec::new(),
+        };
+
+        let mut engine = RulesEngine::new(dictionary).map_err(value_error)?;
+        engine
+            .load_unsigned_bundle(RuleBundle::new(vec![document]), &SecurityConfig::default())
+            .map_err(|e| value_error(format!("{:#}", e)))?;
+        Ok(Self { engine })
+    }
+
+    /// `facts` with every derived attribute added
+    fn evaluate(&self, py: Python<'_>, facts: &Bound<'_, PyDict>) -> PyResult<PyObject> {
+        let result = self.engine.evaluate_all(&self::facts(facts)?).map_err(|e| value_error(format!("{:#}", e)))?;
+        to_py(py, &JsonValue::Object(result.into_iter().map(|(name, value)| (name, value.to_json())).collect()))
+    }
+
+    /// Every row with its derived attributes, in the container it came in.
+    /// Rows are evaluated in parallel without holding the GIL.
+    fn evaluate_batch(&self, py: Python<'_>, rows: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let (source, records) = Rows::read(rows)?;
+        let results = py.allow_threads(|| self.engine.evaluate_batch(&records));
+
+        let rows = PyList::empty_bound(py);
+        for (index, result) in results.into_iter().enumerate() {
+            let row = result.map_err(|e| value_error(format!("Row {}: {:#}", index, e)))?;
+            rows.append(to_py(py, &JsonValue::Object(row.into_iter().collect()))?)?;
+        }
+        source.wrap(rows)
+    }
+}
+
+#[pymodule]
+fn data_designer(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(parse_py, m)?)?;
+    m.add_function(wrap_pyfunction!(validate, m)?)?;
+    m.add_function(wrap_pyfunction!(evaluate, m)?)?;
+    m.add_function(wrap_pyfunction!(evaluate_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(transpile, m)?)?;
+    m.add_class::<PyRulesEngine>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(script: &str) {
+        Python::with_gil(|py| {
+            let module = PyModule::new_bound(py, "data_designer").unwrap();
+            data_designer(&module).unwrap();
+            let globals = PyDict::new_bound(py);
+            globals.set_item("dd", module).unwrap();
+            if let Err(e) = py.run_bound(script, Some(&globals), None) {
+                e.print(py);
+                panic!("script failed");
+            }
+        });
+    }
+
+    #[test]
+    fn test_parse_validate_evaluate_and_transpile() {
+        run(r#"
+assert dd.parse("fee = volume * 2")["Assignment"]["target"] == "fee"
+assert dd.validate("fee = volume * 2", {"volume": "number"}) == []
+problems = dd.validate('fee = volume * "x"', {"volume": "number"})
+assert problems[0]["rule"] == "fee", problems
+
+assert dd.evaluate("fee = IF volume > 100 THEN volume * 0.01 ELSE 5", {"volume": 1000}) == 10.0
+assert dd.evaluate_batch("fee = volume * 2", [{"volume": 1}, {"volume": 2.5}, {"volume": float("nan")}], strict=False) == [2, 5.0, None]
+try:
+    dd.evaluate("fee = volume / 0", {"volume": 1})
+    raise AssertionError("expected a ValueError")
+except ValueError:
+    pass
+
+assert "volume" in dd.transpile("fee = volume * 2", "sql")
+"#);
+    }
+
+    #[test]
+    fn test_engine_scores_rows_in_dependency_order() {
+        run(r#"
+engine = dd.RulesEngine("tax = fee * 0.5\nfee = volume * 2")
+assert engine.evaluate({"volume": 10}) == {"volume": 10, "fee": 20, "tax": 10.0}
+rows = engine.evaluate_batch([{"volume": 1}, {"volume": 2}])
+assert [row["tax"] for row in rows] == [1.0, 2.0], rows
+"#);
+    }
+}
@@ -0,0 +1,16 @@
+#!/bin/bash
+
+# Build the in-browser rule preview package for web-ui and onboarding-ui
+set -e
+
+export PATH="$HOME/.cargo/bin:$PATH"
+
+if ! command -v wasm-pack &> /dev/null; then
+    echo "❌ wasm-pack is not installed. Installing..."
+    cargo install wasm-pack
+fi
+
+echo "📦 Building rule preview WASM package..."
+wasm-pack build --target web --out-dir pkg --release
+
+echo "✅ Built pkg/data_designer_wasm.js and pkg/data_designer_wasm_bg.wasm"
@@ -0,0 +1,226 @@
+//! In-browser rule validation and preview
+//!
+//! The core parser, type checker, formatter and evaluator built for wasm32
+//! with `default-features = false`, so no sqlx, tokio or git. web-ui and
+//! onboarding-ui call these on every keystroke instead of the
+//! validate-rule-types, format-dsl, explain-rule-evaluation and
+//! run-rule-tests endpoints. Each function returns the JSON its endpoint
+//! does; the only thing the browser lacks is the data dictionary, so
+//! attribute types are passed in by the caller.
+//!
+//! The Rust functions work natively too; the `wasm_bindgen` exports in
+//! `bindings` wrap them for JavaScript (`validateRule`, `formatRule`, ...).
+
+use data_designer_core::evaluator::{evaluate_traced, Facts, FunctionLibrary};
+use data_designer_core::formatter::format_document;
+use data_designer_core::models::Value;
+use data_designer_core::parser::parse_rule;
+use data_designer_core::rule_tests::{RuleTestCase, RuleTestRunner};
+use data_designer_core::transpiler::DslTranspiler;
+use data_designer_core::type_checker::{typecheck_with_env, RuleType, TypeEnv};
+use serde_json::json;
+
+/// Parse and type check every rule of a document. `attribute_types` maps
+/// attribute names to dictionary type names (`"number"`, `"string"`, ...).
+pub fn validate_rule(rule_text: &str, attribute_types: &serde_json::Value) -> serde_json::Value {
+    if rule_text.trim().is_empty() {
+        return json!({
+            "success": false,
+            "message": "Rule text is required",
+            "diagnostics": []
+        });
+    }
+
+    let mut type_env = TypeEnv::new();
+    if let Some(types) = attribute_types.as_object() {
+        for (name, data_type) in types {
+            type_env.insert(name, RuleType::from_type_name(data_type.as_str().unwrap_or("")));
+        }
+    }
+
+    let transpiler = DslTranspiler {
+        validation_enabled: false,
+        dependency_analysis: false,
+    };
+    let rules = match transpiler.transpile_dsl_to_rules(rule_text) {
+        Ok(rules) => rules,
+        Err(errors) => {
+            return json!({
+                "success": false,
+                "message": "Rule failed to parse",
+                "diagnostics": errors.iter().map(|e| json!({
+                    "rule": e.rule_name,
+                    "line": e.line,
+                    "message": e.to_string(),
+                })).collect::<Vec<_>>()
+            });
+        }
+    };
+
+    let mut diagnostics = Vec::new();
+    let mut inferred_types = serde_json::Map::new();
+    for rule in &rules {
+        let result = typecheck_with_env(&rule.expression, &type_env);
+        inferred_types.insert(rule.name.clone(), json!(result.inferred.to_string()));
+        for diagnostic in result.diagnostics {
+            diagnostics.push(json!({
+                "rule": rule.name,
+                "line": rule.line_number,
+                "expression": diagnostic.expression,
+                "message": diagnostic.message,
+            }));
+        }
+    }
+
+    json!({
+        "success": diagnostics.is_empty(),
+        "message": format!("Type checked {} rules, {} issues", rules.len(), diagnostics.len()),
+        "inferred_types": inferred_types,
+        "diagnostics": diagnostics
+    })
+}
+
+/// Canonical formatting; unparseable text is returned unchanged
+pub fn format_rule(rule_text: &str) -> serde_json::Value {
+    match format_document(rule_text) {
+        Ok(formatted) => json!({
+            "success": true,
+            "message": "Formatted",
+            "formatted": formatted
+        }),
+        Err(e) => json!({
+            "success": false,
+            "message": e.to_string(),
+            "formatted": rule_text
+        }),
+    }
+}
+
+/// Evaluate one rule against `facts`, a JSON object of attribute values,
+/// with every sub-expression's inputs and value
+pub fn explain_rule(rule_text: &str, facts: &serde_json::Value) -> serde_json::Value {
+    let expression = match parse_rule(rule_text) {
+        Ok((remaining, expression)) if remaining.trim().is_empty() => expression,
+        Ok((remaining, _)) => {
+            return json!({
+                "success": false,
+                "message": format!("Unexpected input: {}", remaining.trim())
+            });
+        }
+        Err(e) => {
+            return json!({
+                "success": false,
+                "message": format!("Rule failed to parse: {}", e)
+            });
+        }
+    };
+
+    let facts: Facts = facts
+        .as_object()
+        .map(|facts| facts.iter().map(|(name, value)| (name.clone(), Value::from_json(value))).collect())
+        .unwrap_or_default();
+    let trace = evaluate_traced(&expression, &facts, &FunctionLibrary::new());
+
+    json!({
+        "success": trace.error.is_none(),
+        "message": trace.error.clone().unwrap_or_else(|| "Evaluated".to_string()),
+        "value": trace.value.as_ref().map(Value::to_json),
+        "trace": trace
+    })
+}
+
+/// Run test cases (as the rule-test-cases endpoints return them) against a
+/// definition that hasn't been saved yet
+pub fn run_rule_tests(rule_id: &str, definition: &str, cases: &serde_json::Value) -> serde_json::Value {
+    let cases: Vec<RuleTestCase> = match serde_json::from_value(cases.clone()) {
+        Ok(cases) => cases,
+        Err(e) => {
+            return json!({
+                "success": false,
+                "message": format!("Invalid test cases: {}", e)
+            });
+        }
+    };
+
+    match RuleTestRunner::new().run(rule_id, definition, &cases) {
+        Ok(report) => json!({
+            "success": report.all_passed(),
+            "message": format!("{} passed, {} failed", report.passed, report.failed),
+            "report": report
+        }),
+        Err(e) => json!({
+            "success": false,
+            "message": e
+        }),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod bindings {
+    use serde::Serialize;
+    use wasm_bindgen::prelude::*;
+
+    // Plain objects rather than Maps on the JavaScript side
+    fn to_js(value: serde_json::Value) -> JsValue {
+        value
+            .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+            .unwrap_or(JsValue::NULL)
+    }
+
+    fn from_js(value: JsValue) -> serde_json::Value {
+        serde_wasm_bindgen::from_value(value).unwrap_or(serde_json::Value::Null)
+    }
+
+    #[wasm_bindgen(js_name = validateRule)]
+    pub fn validate_rule(rule_text: &str, attribute_types: JsValue) -> JsValue {
+        to_js(super::validate_rule(rule_text, &from_js(attribute_types)))
+    }
+
+    #[wasm_bindgen(js_name = formatRule)]
+    pub fn format_rule(rule_text: &str) -> JsValue {
+        to_js(super::format_rule(rule_text))
+    }
+
+    #[wasm_bindgen(js_name = explainRule)]
+    pub fn explain_rule(rule_text: &str, facts: JsValue) -> JsValue {
+        to_js(super::explain_rule(rule_text, &from_js(facts)))
+    }
+
+    #[wasm_bindgen(js_name = runRuleTests)]
+    pub fn run_rule_tests(rule_id: &str, definition: &str, cases: JsValue) -> JsValue {
+        to_js(super::run_rule_tests(rule_id, definition, &from_js(cases)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_reports_type_errors_and_parse_errors() {
+        let result = validate_rule(r#"fee = volume * "high""#, &json!({ "volume": "number" }));
+        assert_eq!(result["success"], false);
+        assert_eq!(result["inferred_types"]["fee"], json!(RuleType::Number.to_string()));
+        assert_eq!(result["diagnostics"][0]["rule"], "fee");
+
+        let result = validate_rule("fee = volume *", &json!({}));
+        assert_eq!(result["message"], "Rule failed to parse");
+    }
+
+    #[test]
+    fn test_explain_and_run_tests_without_a_server() {
+        let result = explain_rule("fee = IF volume > 100 THEN volume * 0.01 ELSE 5", &json!({ "volume": 1000 }));
+        assert_eq!(result["success"], true);
+        assert_eq!(result["value"], json!(10.0));
+        assert_eq!(result["trace"]["inputs"][0]["inputs"][0]["expression"], "volume > 100");
+
+        let cases = json!([
+            { "rule": "fee", "name": "large", "inputs": { "volume": 1000 }, "expected": 10.0 },
+            { "rule": "fee", "name": "small", "inputs": { "volume": 10 }, "expected": 6 }
+        ]);
+        let result = run_rule_tests("fee", "fee = IF volume > 100 THEN volume * 0.01 ELSE 5", &cases);
+        assert_eq!(result["success"], false);
+        assert_eq!(result["report"]["passed"], 1);
+        assert_eq!(result["report"]["failed"], 1);
+    }
+}
@@ -0,0 +1,55 @@
+-- Migration 008: Hierarchical Rule Categories
+-- Categories nest (KYC Validation -> Screening -> Sanctions) and carry policies that
+-- RuleOperations::save_rule_with_validation enforces when a rule is saved.
+-- Unset policy columns (NULL) inherit from the parent category.
+
+-- rule_categories was dropped in migration 006; recreate it where missing
+CREATE TABLE IF NOT EXISTS rule_categories (
+    id SERIAL PRIMARY KEY,
+    category_key VARCHAR(50) UNIQUE NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    description TEXT,
+    color VARCHAR(7),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE rule_categories
+    ADD COLUMN IF NOT EXISTS parent_id INTEGER REFERENCES rule_categories(id) ON DELETE RESTRICT,
+    ADD COLUMN IF NOT EXISTS approval_required BOOLEAN,
+    ADD COLUMN IF NOT EXISTS allowed_functions TEXT[],
+    ADD COLUMN IF NOT EXISTS default_severity VARCHAR(20)
+        CHECK (default_severity IN ('info', 'warning', 'error', 'critical'));
+
+CREATE INDEX IF NOT EXISTS idx_rule_categories_parent ON rule_categories(parent_id);
+
+-- Rules record the severity they were saved with and may wait for approval
+ALTER TABLE rules ADD COLUMN IF NOT EXISTS severity VARCHAR(20);
+ALTER TABLE rules DROP CONSTRAINT IF EXISTS rules_status_check;
+ALTER TABLE rules ADD CONSTRAINT rules_status_check
+    CHECK (status IN ('draft', 'pending_approval', 'active', 'inactive', 'deprecated'));
+
+-- The foreign key went with the CASCADE drop in migration 006
+ALTER TABLE rules DROP CONSTRAINT IF EXISTS rules_category_id_fkey;
+ALTER TABLE rules ADD CONSTRAINT rules_category_id_fkey
+    FOREIGN KEY (category_id) REFERENCES rule_categories(id);
+
+-- Default categories
+INSERT INTO rule_categories (category_key, name, description, color, default_severity) VALUES
+('risk_assessment', 'Risk Assessment', 'Rules for calculating risk scores and metrics', '#ff6b6b', 'warning'),
+('validation', 'Data Validation', 'Rules for validating data formats and constraints', '#4ecdc4', 'error'),
+('compliance', 'Compliance', 'Rules for regulatory compliance checks', '#96ceb4', 'error'),
+('kyc_validation', 'KYC Validation', 'Rules for KYC completeness and compliance', '#45b7d1', 'error'),
+('classification', 'Classification', 'Rules for categorizing and tiering clients', '#ffeaa7', 'info')
+ON CONFLICT (category_key) DO NOTHING;
+
+INSERT INTO rule_categories (category_key, name, description, color, parent_id, approval_required)
+SELECT 'kyc_screening', 'Screening', 'Client and counterparty screening', '#45b7d1', id, TRUE
+FROM rule_categories WHERE category_key = 'kyc_validation'
+ON CONFLICT (category_key) DO NOTHING;
+
+INSERT INTO rule_categories (category_key, name, description, color, parent_id, allowed_functions, default_severity)
+SELECT 'kyc_sanctions', 'Sanctions', 'Sanctions list screening', '#45b7d1', id,
+       ARRAY['UPPER', 'LOWER', 'TRIM', 'LOOKUP', 'HAS', 'IS_NULL', 'IS_EMPTY'], 'critical'
+FROM rule_categories WHERE category_key = 'kyc_screening'
+ON CONFLICT (category_key) DO NOTHING;
+
@@ -0,0 +1,50 @@
+-- Migration 012: Tags and Saved Filters
+-- Tags are shared between data dictionary attributes and rules. Assignments
+-- reference their target by key (attribute full_path or rule_id) so that
+-- business, derived and system attributes can all be tagged.
+
+CREATE TABLE IF NOT EXISTS tags (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(100) UNIQUE NOT NULL,
+    color VARCHAR(7), -- hex color for UI
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS tag_assignments (
+    tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    target_type VARCHAR(20) NOT NULL CHECK (target_type IN ('attribute', 'rule')),
+    target_key VARCHAR(200) NOT NULL,
+    assigned_by VARCHAR(100),
+    assigned_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (tag_id, target_type, target_key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_tag_assignments_target ON tag_assignments(target_type, target_key);
+
+-- Filter presets saved per user for the dictionary and rule browsers
+CREATE TABLE IF NOT EXISTS saved_filters (
+    id SERIAL PRIMARY KEY,
+    user_id VARCHAR(100) NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    scope VARCHAR(20) NOT NULL CHECK (scope IN ('dictionary', 'rules')),
+    search_text TEXT,
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    match_all BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (user_id, scope, name)
+);
+
+-- Carry over the tags already stored on rules
+INSERT INTO tags (name)
+SELECT DISTINCT lower(trim(rule_tag.name))
+FROM rules CROSS JOIN LATERAL unnest(rules.tags) AS rule_tag(name)
+WHERE trim(rule_tag.name) <> ''
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO tag_assignments (tag_id, target_type, target_key)
+SELECT t.id, 'rule', r.rule_id
+FROM rules r
+CROSS JOIN LATERAL unnest(r.tags) AS rule_tag(name)
+JOIN tags t ON t.name = lower(trim(rule_tag.name))
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,20 @@
+-- Migration 013: Bulk Attribute Edits
+-- Each applied bulk edit is stored with the previous value of every row it
+-- changed, so the whole batch can be undone until undo_expires_at.
+
+CREATE TABLE IF NOT EXISTS bulk_edit_batches (
+    id SERIAL PRIMARY KEY,
+    change JSONB NOT NULL, -- {"kind": "add_tag", "tag": "pii"}, {"kind": "set_source_system", ...}
+    edited_rows JSONB NOT NULL, -- [{"full_path", "attribute_type", "before", "after"}]
+    applied_by VARCHAR(100),
+    applied_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    undo_expires_at TIMESTAMPTZ NOT NULL,
+    undone_at TIMESTAMPTZ,
+    undone_by VARCHAR(100)
+);
+
+CREATE INDEX IF NOT EXISTS idx_bulk_edit_batches_applied_at ON bulk_edit_batches(applied_at DESC);
+
+-- Categories set in bulk live in the attributes' metadata
+ALTER TABLE business_attributes ADD COLUMN IF NOT EXISTS metadata JSONB;
+ALTER TABLE derived_attributes ADD COLUMN IF NOT EXISTS metadata JSONB;
@@ -0,0 +1,14 @@
+-- Migration 014: Onboarding Instance Events
+-- Append-only log of every onboarding event. Replaying an instance's events
+-- up to a timestamp reconstructs its state at that time.
+
+CREATE TABLE IF NOT EXISTS onboarding_instance_events (
+    sequence BIGSERIAL PRIMARY KEY,
+    instance_id VARCHAR(100) NOT NULL,
+    event_type VARCHAR(50) NOT NULL, -- OnboardingCreated, PlanCompiled, TaskFailed, ...
+    event JSONB NOT NULL, -- the serialized OnboardingEvent
+    occurred_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_onboarding_instance_events_instance
+    ON onboarding_instance_events(instance_id, sequence);
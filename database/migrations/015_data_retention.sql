@@ -0,0 +1,38 @@
+-- Migration 015: Data Retention and GDPR Erasure
+-- One retention policy per kind of record; the purge deletes or anonymizes
+-- records older than retain_days. Erasures replace a data subject with a
+-- pseudonym and are logged here without any personal data.
+
+CREATE TABLE IF NOT EXISTS retention_policies (
+    entity_type VARCHAR(50) PRIMARY KEY
+        CHECK (entity_type IN ('evaluation_results', 'audit_logs', 'documents')),
+    retain_days INTEGER NOT NULL CHECK (retain_days > 0),
+    action VARCHAR(20) NOT NULL CHECK (action IN ('delete', 'anonymize')),
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    updated_by VARCHAR(100),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO retention_policies (entity_type, retain_days, action, enabled) VALUES
+    ('evaluation_results', 365, 'delete', TRUE),
+    ('audit_logs', 2555, 'anonymize', TRUE), -- seven years
+    ('documents', 1825, 'anonymize', TRUE) -- five years
+ON CONFLICT (entity_type) DO NOTHING;
+
+-- Set once a record's personal payload has been cleared by the purge
+ALTER TABLE dsl_execution_logs ADD COLUMN IF NOT EXISTS anonymized_at TIMESTAMPTZ;
+ALTER TABLE onboarding_execution_log ADD COLUMN IF NOT EXISTS anonymized_at TIMESTAMPTZ;
+ALTER TABLE resource_instances ADD COLUMN IF NOT EXISTS anonymized_at TIMESTAMPTZ;
+
+CREATE TABLE IF NOT EXISTS gdpr_erasures (
+    id SERIAL PRIMARY KEY,
+    pseudonym VARCHAR(50) NOT NULL, -- what the subject was replaced with
+    cbu_members BIGINT NOT NULL DEFAULT 0, -- rows changed per kind of record
+    documents BIGINT NOT NULL DEFAULT 0,
+    traces BIGINT NOT NULL DEFAULT 0,
+    audit_logs BIGINT NOT NULL DEFAULT 0,
+    requested_by VARCHAR(100),
+    completed_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_gdpr_erasures_completed_at ON gdpr_erasures(completed_at DESC);
@@ -0,0 +1,44 @@
+-- Migration 016: Rule Versions
+-- Every save of a rule is recorded as a new version. Versions are immutable:
+-- the trigger below rejects updates and deletes, and versions outlive the rule.
+-- (The rule_versions table dropped in migration 006 was never written to.)
+
+CREATE TABLE IF NOT EXISTS rule_versions (
+    id SERIAL PRIMARY KEY,
+    rule_id VARCHAR(50) NOT NULL, -- rules.rule_id; no foreign key so history survives deletion
+    version INTEGER NOT NULL,
+    rule_name VARCHAR(200) NOT NULL,
+    description TEXT,
+    rule_definition TEXT NOT NULL,
+    parsed_ast JSONB,
+    status VARCHAR(20),
+    change_description TEXT, -- "Created", "Saved", "Rewrite 'a' -> 'b'"
+    created_by VARCHAR(100),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(rule_id, version)
+);
+
+CREATE INDEX IF NOT EXISTS idx_rule_versions_rule ON rule_versions(rule_id, version DESC);
+
+CREATE OR REPLACE FUNCTION reject_rule_version_changes()
+RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'rule_versions is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS rule_versions_append_only ON rule_versions;
+CREATE TRIGGER rule_versions_append_only
+    BEFORE UPDATE OR DELETE ON rule_versions
+    FOR EACH ROW EXECUTE FUNCTION reject_rule_version_changes();
+
+-- Start every existing rule's history at its current definition
+INSERT INTO rule_versions (
+    rule_id, version, rule_name, description, rule_definition, parsed_ast, status,
+    change_description, created_by, created_at
+)
+SELECT rule_id, COALESCE(version, 1), rule_name, description, rule_definition, parsed_ast, status,
+       'Recorded when version history was introduced', COALESCE(updated_by, created_by),
+       COALESCE(updated_at, created_at, CURRENT_TIMESTAMP)
+FROM rules
+ON CONFLICT (rule_id, version) DO NOTHING;
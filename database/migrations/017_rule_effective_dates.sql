@@ -0,0 +1,19 @@
+-- Migration 017: Rule Effective Dates
+-- A rule version may be in effect for a set period: from valid_from
+-- (inclusive) to valid_to (exclusive). Without valid_from a version takes
+-- effect when it is saved; without valid_to it stays in effect until a newer
+-- version's period starts. The version in effect at an instant is the newest
+-- one whose period contains it.
+
+ALTER TABLE rules ADD COLUMN IF NOT EXISTS valid_from TIMESTAMPTZ;
+ALTER TABLE rules ADD COLUMN IF NOT EXISTS valid_to TIMESTAMPTZ;
+ALTER TABLE rules DROP CONSTRAINT IF EXISTS rules_effective_period;
+ALTER TABLE rules ADD CONSTRAINT rules_effective_period
+    CHECK (valid_from IS NULL OR valid_to IS NULL OR valid_to > valid_from);
+
+-- Versions keep the period they were saved with
+ALTER TABLE rule_versions ADD COLUMN IF NOT EXISTS valid_from TIMESTAMPTZ;
+ALTER TABLE rule_versions ADD COLUMN IF NOT EXISTS valid_to TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_rule_versions_effective
+    ON rule_versions(rule_id, (COALESCE(valid_from, created_at)), valid_to);
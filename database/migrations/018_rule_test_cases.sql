@@ -0,0 +1,21 @@
+-- Migration 018: Rule Test Cases
+-- Expected input/output cases saved with each rule. They run whenever the
+-- rule is saved, and a rule can't be made active while any of them fails.
+-- A case expects either a value (expected) or an evaluation error
+-- (expected_error).
+
+CREATE TABLE IF NOT EXISTS rule_test_cases (
+    id SERIAL PRIMARY KEY,
+    rule_id VARCHAR(50) NOT NULL REFERENCES rules(rule_id) ON DELETE CASCADE,
+    name VARCHAR(200) NOT NULL,
+    inputs JSONB NOT NULL DEFAULT '{}',
+    expected JSONB NOT NULL DEFAULT 'null',
+    expected_error TEXT,
+    generated BOOLEAN NOT NULL DEFAULT FALSE, -- starter case from dictionary constraints
+    created_by VARCHAR(100),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(rule_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_rule_test_cases_rule ON rule_test_cases(rule_id);
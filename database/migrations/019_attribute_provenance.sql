@@ -0,0 +1,42 @@
+-- Migration 019: Attribute Provenance
+-- Every materialization of a client's derived attributes records which
+-- values each derivation read. Source values are snapshotted as they were
+-- read; derived inputs point at the derivation that produced them, so a
+-- derived value can be explained down to the stored data behind it.
+
+CREATE TABLE IF NOT EXISTS attribute_value_snapshots (
+    id BIGSERIAL PRIMARY KEY,
+    cbu_id VARCHAR(50) NOT NULL,
+    attribute_name VARCHAR(200) NOT NULL,
+    value JSONB NOT NULL DEFAULT 'null',
+    captured_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_attribute_value_snapshots_cbu ON attribute_value_snapshots(cbu_id, attribute_name);
+
+CREATE TABLE IF NOT EXISTS attribute_derivations (
+    id BIGSERIAL PRIMARY KEY,
+    materialization_id UUID NOT NULL,
+    cbu_id VARCHAR(50) NOT NULL,
+    attribute_name VARCHAR(200) NOT NULL,
+    value JSONB NOT NULL DEFAULT 'null',
+    rule_id VARCHAR(50) NOT NULL,
+    rule_version INTEGER NOT NULL,
+    materialized_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_attribute_derivations_latest ON attribute_derivations(cbu_id, attribute_name, materialized_at DESC);
+CREATE INDEX IF NOT EXISTS idx_attribute_derivations_materialization ON attribute_derivations(materialization_id);
+
+-- Exactly one of snapshot_id and derived_from is set
+CREATE TABLE IF NOT EXISTS attribute_derivation_inputs (
+    id BIGSERIAL PRIMARY KEY,
+    derivation_id BIGINT NOT NULL REFERENCES attribute_derivations(id) ON DELETE CASCADE,
+    attribute_name VARCHAR(200) NOT NULL,
+    value JSONB NOT NULL DEFAULT 'null',
+    snapshot_id BIGINT REFERENCES attribute_value_snapshots(id),
+    derived_from BIGINT REFERENCES attribute_derivations(id),
+    CHECK ((snapshot_id IS NULL) <> (derived_from IS NULL))
+);
+
+CREATE INDEX IF NOT EXISTS idx_attribute_derivation_inputs_derivation ON attribute_derivation_inputs(derivation_id);
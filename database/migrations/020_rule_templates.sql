@@ -0,0 +1,30 @@
+-- Migration 020: Rule Templates
+-- Parameterized rules (`RULE risk_check(threshold) ...`) stored once, with
+-- the arguments each product or jurisdiction instantiates them with stored
+-- separately. Arguments are DSL literals keyed by parameter name, e.g.
+-- {"threshold": "700", "currencies": "[\"EUR\", \"CHF\"]"}.
+
+CREATE TABLE IF NOT EXISTS rule_templates (
+    id SERIAL PRIMARY KEY,
+    template_name VARCHAR(100) NOT NULL UNIQUE,
+    parameters TEXT[] NOT NULL DEFAULT '{}',
+    definition TEXT NOT NULL,
+    description TEXT,
+    created_by VARCHAR(100),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS rule_template_instances (
+    id SERIAL PRIMARY KEY,
+    template_id INTEGER NOT NULL REFERENCES rule_templates(id) ON DELETE CASCADE,
+    scope VARCHAR(20) NOT NULL CHECK (scope IN ('product', 'jurisdiction')),
+    scope_value VARCHAR(100) NOT NULL, -- product id or jurisdiction code
+    arguments JSONB NOT NULL DEFAULT '{}',
+    created_by VARCHAR(100),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(template_id, scope, scope_value)
+);
+
+CREATE INDEX IF NOT EXISTS idx_rule_template_instances_scope ON rule_template_instances(scope, scope_value);
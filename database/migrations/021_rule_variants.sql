@@ -0,0 +1,13 @@
+-- Migration 021: Rule Variants
+-- Several rules may derive the same attribute, each a variant for a
+-- jurisdiction, product line or CBU type. applies_to holds the conditions,
+-- e.g. {"jurisdiction": "DE"} or {"product_line": "custody", "cbu_type": "fund"};
+-- an empty object is the default variant. At evaluation time the context's
+-- facts pick the variant, and two that both apply are a conflict.
+
+ALTER TABLE rules ADD COLUMN IF NOT EXISTS applies_to JSONB NOT NULL DEFAULT '{}';
+
+-- Versions keep the conditions they were saved with
+ALTER TABLE rule_versions ADD COLUMN IF NOT EXISTS applies_to JSONB NOT NULL DEFAULT '{}';
+
+CREATE INDEX IF NOT EXISTS idx_rules_applies_to ON rules USING GIN (applies_to);
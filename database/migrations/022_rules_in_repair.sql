@@ -0,0 +1,10 @@
+-- Migration 022: Rules In Repair
+-- At startup every active rule is parsed and typechecked again against the
+-- current grammar and data dictionary. Rules that no longer validate are
+-- moved to in_repair rather than failing on their first evaluation; the
+-- rule version recorded with the move says what broke. Making one active
+-- again goes through the usual status change and its test cases.
+
+ALTER TABLE rules DROP CONSTRAINT IF EXISTS rules_status_check;
+ALTER TABLE rules ADD CONSTRAINT rules_status_check
+    CHECK (status IN ('draft', 'pending_approval', 'active', 'inactive', 'deprecated', 'in_repair'));
@@ -0,0 +1,145 @@
+-- Migration 023: Read Models
+-- The product hierarchy, taxonomy hierarchy and onboarding progress views
+-- are read on every UI tab switch, and each joins several tables. They are
+-- kept here as materialized views with unique keys, so they can be refreshed
+-- concurrently without blocking readers. A write to a view's source tables
+-- marks it stale and notifies read_model_changed; the server then refreshes
+-- the stale views and bumps their version, which clients key their caches by.
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS mv_product_hierarchy AS
+SELECT
+    p.product_id,
+    p.product_name,
+    p.line_of_business,
+    p.status as product_status,
+    s.service_id,
+    s.service_name,
+    s.service_category,
+    ps.is_required as service_required,
+    ps.display_order as service_order,
+    r.resource_id,
+    r.resource_name,
+    r.resource_type,
+    sr.resource_role,
+    sr.priority as resource_priority
+FROM products p
+JOIN product_services ps ON p.id = ps.product_id
+JOIN services s ON ps.service_id = s.id
+JOIN service_resources sr ON s.id = sr.service_id
+JOIN resources r ON sr.resource_id = r.id
+WHERE p.status = 'active' AND s.status = 'active' AND r.status = 'active';
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_mv_product_hierarchy_key
+    ON mv_product_hierarchy(product_id, service_id, resource_id);
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS mv_taxonomy_hierarchy AS
+SELECT p.product_id as item_id, p.product_name as item_name, p.description as item_description,
+       'product' as item_type, 1 as level
+FROM products p
+UNION ALL
+SELECT s.service_id as item_id, s.service_name as item_name, s.description as item_description,
+       'service' as item_type, 2 as level
+FROM services s;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_mv_taxonomy_hierarchy_key
+    ON mv_taxonomy_hierarchy(item_type, item_id);
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS mv_onboarding_progress AS
+SELECT
+    or_main.request_id,
+    cbu.cbu_id,
+    cbu.cbu_name,
+    p.product_id,
+    p.product_name,
+    or_main.request_status,
+    or_main.target_go_live_date,
+    or_main.created_at,
+    COUNT(ot.id) as total_tasks,
+    COUNT(CASE WHEN ot.task_status = 'completed' THEN 1 END) as completed_tasks,
+    COUNT(CASE WHEN ot.task_status = 'blocked' THEN 1 END) as blocked_tasks,
+    ROUND(
+        COUNT(CASE WHEN ot.task_status = 'completed' THEN 1 END)::numeric /
+        NULLIF(COUNT(ot.id), 0) * 100, 1
+    ) as completion_percentage
+FROM onboarding_requests or_main
+JOIN client_business_units cbu ON or_main.cbu_id = cbu.id
+JOIN products p ON or_main.product_id = p.id
+LEFT JOIN onboarding_tasks ot ON or_main.id = ot.onboarding_request_id
+GROUP BY or_main.id, or_main.request_id, cbu.cbu_id, cbu.cbu_name, p.product_id, p.product_name,
+         or_main.request_status, or_main.target_go_live_date, or_main.created_at;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_mv_onboarding_progress_key
+    ON mv_onboarding_progress(request_id);
+
+-- One row per read model; version goes up with every refresh
+CREATE TABLE IF NOT EXISTS read_model_versions (
+    read_model VARCHAR(50) PRIMARY KEY,
+    version BIGINT NOT NULL DEFAULT 1,
+    stale BOOLEAN NOT NULL DEFAULT FALSE,
+    refreshed_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO read_model_versions (read_model) VALUES
+    ('product_hierarchy'),
+    ('taxonomy_hierarchy'),
+    ('onboarding_progress')
+ON CONFLICT (read_model) DO NOTHING;
+
+-- Marks the read models named in the trigger's arguments stale. Notifications
+-- with the same payload in one transaction are delivered once, on commit.
+CREATE OR REPLACE FUNCTION mark_read_models_stale()
+RETURNS TRIGGER AS $$
+DECLARE
+    model TEXT;
+BEGIN
+    FOREACH model IN ARRAY TG_ARGV LOOP
+        UPDATE read_model_versions SET stale = TRUE WHERE read_model = model AND NOT stale;
+        PERFORM pg_notify('read_model_changed', model);
+    END LOOP;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS products_read_models ON products;
+CREATE TRIGGER products_read_models
+    AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON products
+    FOR EACH STATEMENT EXECUTE FUNCTION mark_read_models_stale('product_hierarchy', 'taxonomy_hierarchy', 'onboarding_progress');
+
+DROP TRIGGER IF EXISTS services_read_models ON services;
+CREATE TRIGGER services_read_models
+    AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON services
+    FOR EACH STATEMENT EXECUTE FUNCTION mark_read_models_stale('product_hierarchy', 'taxonomy_hierarchy');
+
+DROP TRIGGER IF EXISTS resources_read_models ON resources;
+CREATE TRIGGER resources_read_models
+    AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON resources
+    FOR EACH STATEMENT EXECUTE FUNCTION mark_read_models_stale('product_hierarchy');
+
+DROP TRIGGER IF EXISTS product_services_read_models ON product_services;
+CREATE TRIGGER product_services_read_models
+    AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON product_services
+    FOR EACH STATEMENT EXECUTE FUNCTION mark_read_models_stale('product_hierarchy');
+
+DROP TRIGGER IF EXISTS service_resources_read_models ON service_resources;
+CREATE TRIGGER service_resources_read_models
+    AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON service_resources
+    FOR EACH STATEMENT EXECUTE FUNCTION mark_read_models_stale('product_hierarchy');
+
+DROP TRIGGER IF EXISTS onboarding_requests_read_models ON onboarding_requests;
+CREATE TRIGGER onboarding_requests_read_models
+    AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON onboarding_requests
+    FOR EACH STATEMENT EXECUTE FUNCTION mark_read_models_stale('onboarding_progress');
+
+DROP TRIGGER IF EXISTS onboarding_tasks_read_models ON onboarding_tasks;
+CREATE TRIGGER onboarding_tasks_read_models
+    AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON onboarding_tasks
+    FOR EACH STATEMENT EXECUTE FUNCTION mark_read_models_stale('onboarding_progress');
+
+DROP TRIGGER IF EXISTS client_business_units_read_models ON client_business_units;
+CREATE TRIGGER client_business_units_read_models
+    AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON client_business_units
+    FOR EACH STATEMENT EXECUTE FUNCTION mark_read_models_stale('onboarding_progress');
+
+COMMENT ON MATERIALIZED VIEW mv_product_hierarchy IS 'Read model of v_product_hierarchy, refreshed on change';
+COMMENT ON MATERIALIZED VIEW mv_taxonomy_hierarchy IS 'Read model of the product and service taxonomy, refreshed on change';
+COMMENT ON MATERIALIZED VIEW mv_onboarding_progress IS 'Read model of v_onboarding_progress, refreshed on change';
@@ -0,0 +1,17 @@
+-- Migration 024: Field Encryption
+-- Attributes tagged "sensitive" have their values stored encrypted
+-- (AES-256-GCM envelopes naming their key) in the provenance tables.
+-- Rotating keys re-encrypts values under the current key, and encrypts
+-- values stored before their attribute was tagged; each run is logged here.
+
+INSERT INTO tags (name, color) VALUES ('sensitive', '#c0392b')
+ON CONFLICT (name) DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS field_key_rotations (
+    id BIGSERIAL PRIMARY KEY,
+    key_id VARCHAR(100) NOT NULL,
+    rows_reencrypted INTEGER NOT NULL DEFAULT 0,
+    rows_encrypted INTEGER NOT NULL DEFAULT 0,
+    rotated_by VARCHAR(100),
+    rotated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,14 @@
+-- Migration 025: Managed Lookup Tables
+-- Tables that rules read with LOOKUP(key, "table"). Extracting a long inline
+-- list from a rule registers its values here, each mapping to itself, and
+-- rewrites the rule to test membership with LOOKUP instead.
+
+CREATE TABLE IF NOT EXISTS lookup_tables (
+    name VARCHAR(100) PRIMARY KEY,
+    description TEXT,
+    entries JSONB NOT NULL DEFAULT '{}'::jsonb,
+    source_rule_id VARCHAR(100),
+    created_by VARCHAR(100),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
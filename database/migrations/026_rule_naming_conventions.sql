@@ -0,0 +1,17 @@
+-- Migration 026: Rule Naming Conventions
+-- Categories may require rule names to match a regex and give the prefix of
+-- their rule IDs; rules saved without an ID get one generated from it. Like
+-- the other policy columns, NULL inherits from the parent category.
+
+ALTER TABLE rule_categories
+    ADD COLUMN IF NOT EXISTS rule_name_pattern TEXT,
+    ADD COLUMN IF NOT EXISTS rule_id_prefix VARCHAR(20)
+        CHECK (rule_id_prefix ~ '^[A-Z][A-Z0-9_]*$'),
+    ADD COLUMN IF NOT EXISTS auto_correct_names BOOLEAN;
+
+-- KYC rules are named in Title Case and their IDs start with KYC_
+UPDATE rule_categories
+SET rule_name_pattern = '[A-Z][A-Za-z0-9]*( [A-Z][A-Za-z0-9]*)*',
+    rule_id_prefix = 'KYC',
+    auto_correct_names = TRUE
+WHERE category_key = 'kyc_validation' AND rule_name_pattern IS NULL;
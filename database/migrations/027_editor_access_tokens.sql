@@ -0,0 +1,19 @@
+-- Migration 027: Editor Access Tokens
+-- Tokens editors present to the language servers. Only a SHA-256 hash of each
+-- token is kept. The patterns scope the attributes, rules and commands a
+-- session may reach, '*' matching any run of characters.
+
+CREATE TABLE IF NOT EXISTS editor_access_tokens (
+    id SERIAL PRIMARY KEY,
+    token_hash CHAR(64) NOT NULL UNIQUE,
+    subject VARCHAR(100) NOT NULL,
+    attribute_patterns TEXT[] NOT NULL DEFAULT ARRAY['*'],
+    rule_patterns TEXT[] NOT NULL DEFAULT ARRAY['*'],
+    command_patterns TEXT[] NOT NULL DEFAULT ARRAY['*'],
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ,
+    last_used_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_editor_access_tokens_subject ON editor_access_tokens(subject);
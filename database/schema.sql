@@ -29,6 +29,14 @@ CREATE TABLE rule_categories (
     name VARCHAR(100) NOT NULL,
     description TEXT,
     color VARCHAR(7), -- hex color for UI
+    parent_id INTEGER REFERENCES rule_categories(id) ON DELETE RESTRICT,
+    -- Policy; NULL inherits from the parent category
+    approval_required BOOLEAN,
+    allowed_functions TEXT[],
+    default_severity VARCHAR(20) CHECK (default_severity IN ('info', 'warning', 'error', 'critical')),
+    rule_name_pattern TEXT, -- regex rule names must match in full
+    rule_id_prefix VARCHAR(20) CHECK (rule_id_prefix ~ '^[A-Z][A-Z0-9_]*$'),
+    auto_correct_names BOOLEAN,
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
 );
 
@@ -110,7 +118,8 @@ CREATE TABLE rules (
     embedding vector(1536), -- OpenAI ada-002 dimension, adjust as needed
 
     -- Metadata
-    status VARCHAR(20) DEFAULT 'draft' CHECK (status IN ('draft', 'active', 'inactive', 'deprecated')),
+    status VARCHAR(20) DEFAULT 'draft' CHECK (status IN ('draft', 'pending_approval', 'active', 'inactive', 'deprecated')),
+    severity VARCHAR(20),
     version INTEGER DEFAULT 1,
     tags TEXT[],
     performance_metrics JSONB, -- execution time, resource usage, etc.
@@ -184,6 +193,15 @@ INSERT INTO rule_categories (category_key, name, description, color) VALUES
 ('compliance', 'Compliance', 'Rules for regulatory compliance checks', '#96ceb4'),
 ('classification', 'Classification', 'Rules for categorizing and tiering clients', '#ffeaa7');
 
+INSERT INTO rule_categories (category_key, name, description, color, parent_id, approval_required, allowed_functions, default_severity) VALUES
+('kyc_screening', 'Screening', 'Client and counterparty screening', '#45b7d1',
+ (SELECT id FROM rule_categories WHERE category_key = 'kyc_validation'), TRUE, NULL, NULL);
+
+INSERT INTO rule_categories (category_key, name, description, color, parent_id, approval_required, allowed_functions, default_severity) VALUES
+('kyc_sanctions', 'Sanctions', 'Sanctions list screening', '#45b7d1',
+ (SELECT id FROM rule_categories WHERE category_key = 'kyc_screening'), NULL,
+ ARRAY['UPPER', 'LOWER', 'TRIM', 'LOOKUP', 'HAS', 'IS_NULL', 'IS_EMPTY'], 'critical');
+
 INSERT INTO attribute_sources (source_key, name, description, trust_level, requires_validation) VALUES
 ('client_provided', 'Client Provided', 'Data provided directly by the client', 'medium', true),
 ('internal_assessment', 'Internal Assessment', 'Data from internal risk assessment', 'high', false),
@@ -1,6 +1,7 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompletionRequest {
@@ -71,6 +72,11 @@ pub trait AIAgent: Send + Sync {
     async fn explain_rule(&self, rule: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>>;
     async fn optimize_rule(&self, rule: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>>;
     async fn generate_test_cases(&self, rule: &str) -> Result<Vec<TestCase>, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Answers without a network round trip, so completion can wait for it
+    fn is_local(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -347,10 +353,14 @@ impl AIAgent for MockAgent {
             }
         ])
     }
+
+    fn is_local(&self) -> bool {
+        true
+    }
 }
 
 pub struct AIAgentManager {
-    agents: HashMap<String, Box<dyn AIAgent>>,
+    agents: HashMap<String, Arc<dyn AIAgent>>,
     active_agent: String,
 }
 
@@ -365,10 +375,10 @@ impl std::fmt::Debug for AIAgentManager {
 
 impl AIAgentManager {
     pub fn new() -> Self {
-        let mut agents: HashMap<String, Box<dyn AIAgent>> = HashMap::new();
+        let mut agents: HashMap<String, Arc<dyn AIAgent>> = HashMap::new();
 
         // Add mock agent by default
-        agents.insert("mock".to_string(), Box::new(MockAgent));
+        agents.insert("mock".to_string(), Arc::new(MockAgent));
 
         AIAgentManager {
             agents,
@@ -377,7 +387,7 @@ impl AIAgentManager {
     }
 
     pub fn add_agent(&mut self, name: String, agent: Box<dyn AIAgent>) {
-        self.agents.insert(name, agent);
+        self.agents.insert(name, Arc::from(agent));
     }
 
     pub fn set_active_agent(&mut self, name: String) -> Result<(), String> {
@@ -389,10 +399,15 @@ impl AIAgentManager {
         }
     }
 
-    pub fn get_active_agent(&self) -> Option<&Box<dyn AIAgent>> {
+    pub fn get_active_agent(&self) -> Option<&Arc<dyn AIAgent>> {
         self.agents.get(&self.active_agent)
     }
 
+    /// The active agent, usable after the manager's lock is released
+    pub fn active_agent(&self) -> Option<Arc<dyn AIAgent>> {
+        self.get_active_agent().cloned()
+    }
+
     pub async fn get_completions(&self, request: CompletionRequest) -> Result<CompletionResponse, Box<dyn std::error::Error + Send + Sync>> {
         if let Some(agent) = self.get_active_agent() {
             agent.get_completions(request).await
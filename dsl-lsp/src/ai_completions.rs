@@ -0,0 +1,130 @@
+//! Debounced AI completion suggestions
+//!
+//! A remote agent takes far longer than a completion request should, so
+//! completion never waits for one. Each request notes the cursor it was made
+//! at; once typing has paused for `AI_DEBOUNCE` the latest of them is sent to
+//! the agent in the background, and the suggestions are offered the next time
+//! completion is asked at that cursor. Requests superseded while waiting are
+//! never sent, and answers to them are dropped.
+
+use crate::ai_agent::Suggestion;
+use std::sync::Mutex;
+use std::time::Duration;
+use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind};
+
+/// How long typing must pause before the agent is asked
+pub const AI_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// The line and cursor suggestions were asked for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionKey {
+    line: String,
+    character: usize,
+}
+
+impl CompletionKey {
+    pub fn new(line: &str, character: usize) -> Self {
+        Self { line: line.to_string(), character }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    generation: u64,
+    requested: Option<CompletionKey>,
+    ready: Option<(CompletionKey, Vec<CompletionItem>)>,
+}
+
+#[derive(Debug, Default)]
+pub struct AiSuggestions {
+    state: Mutex<State>,
+}
+
+impl AiSuggestions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Suggestions fetched for `key`, if the latest fetch was for it
+    pub fn ready(&self, key: &CompletionKey) -> Option<Vec<CompletionItem>> {
+        let state = self.state.lock().unwrap();
+        match &state.ready {
+            Some((ready, items)) if ready == key => Some(items.clone()),
+            _ => None,
+        }
+    }
+
+    /// Note a request for `key`, superseding any earlier one. Returns the
+    /// generation to fetch it under, or None if it is already being fetched.
+    pub fn request(&self, key: &CompletionKey) -> Option<u64> {
+        let mut state = self.state.lock().unwrap();
+        if state.requested.as_ref() == Some(key) {
+            return None;
+        }
+        state.generation += 1;
+        state.requested = Some(key.clone());
+        Some(state.generation)
+    }
+
+    /// Whether no request has come in since `generation`
+    pub fn is_current(&self, generation: u64) -> bool {
+        self.state.lock().unwrap().generation == generation
+    }
+
+    /// Store what the agent suggested for `key`, unless it was superseded meanwhile
+    pub fn complete(&self, generation: u64, key: CompletionKey, items: Vec<CompletionItem>) {
+        let mut state = self.state.lock().unwrap();
+        if state.generation == generation {
+            state.requested = None;
+            state.ready = Some((key, items));
+        }
+    }
+}
+
+/// Agent suggestions as completion items, sorted after everything else
+pub fn ai_completion_items(suggestions: Vec<Suggestion>) -> Vec<CompletionItem> {
+    suggestions
+        .into_iter()
+        .map(|suggestion| CompletionItem {
+            label: format!("🤖 {}", suggestion.text),
+            kind: Some(CompletionItemKind::TEXT),
+            detail: suggestion.description,
+            insert_text: Some(suggestion.text),
+            sort_text: Some(format!("zzz{}", suggestion.confidence)),
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(label: &str) -> CompletionItem {
+        CompletionItem { label: label.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_only_the_latest_request_is_fetched() {
+        let suggestions = AiSuggestions::new();
+        let typed = CompletionKey::new("IF age", 6);
+        let typing = CompletionKey::new("IF age ", 7);
+
+        let first = suggestions.request(&typed).unwrap();
+        assert_eq!(suggestions.request(&typed), None);
+        let second = suggestions.request(&typing).unwrap();
+        assert!(!suggestions.is_current(first));
+
+        // The answer to the superseded request arrives too late to be kept
+        suggestions.complete(first, typed.clone(), vec![item("stale")]);
+        assert_eq!(suggestions.ready(&typed), None);
+
+        suggestions.complete(second, typing.clone(), vec![item("THEN")]);
+        assert_eq!(suggestions.ready(&typing).unwrap()[0].label, "THEN");
+        assert_eq!(suggestions.ready(&typed), None);
+
+        // Asking again at the same cursor is served from what was fetched
+        assert!(suggestions.request(&typed).is_some());
+        assert_eq!(suggestions.ready(&typing).unwrap().len(), 1);
+    }
+}
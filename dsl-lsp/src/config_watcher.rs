@@ -0,0 +1,147 @@
+//! Hot reload of the data dictionary and the grammar
+//!
+//! `ConfigWatcher` watches the directory the dictionary was loaded from and
+//! the grammar_rules.json the grammar loader reads, and reports which of them
+//! changed once a burst of events settles, since editors often write a file
+//! several times per save. The server then reloads what changed and
+//! revalidates the open documents, so completion, hover and diagnostics pick
+//! up new attributes and functions without a restart.
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// How long events must pause before the files they touched are reloaded
+pub const RELOAD_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// The files `DataDictionary::load_from_directory` reads
+pub const DICTIONARY_FILES: [&str; 4] = ["entities.json", "domains.json", "lookups.json", "relationships.json"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConfigFile {
+    Dictionary,
+    Grammar,
+}
+
+/// What to watch, with directories resolved the way the watcher reports them
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchedPaths {
+    pub dictionary_dir: Option<PathBuf>,
+    pub grammar: PathBuf,
+}
+
+impl WatchedPaths {
+    pub fn new(dictionary_dir: Option<&Path>, grammar: &Path) -> Self {
+        let grammar = match (grammar.parent(), grammar.file_name()) {
+            (Some(dir), Some(name)) if dir.as_os_str().is_empty() => resolve(Path::new(".")).join(name),
+            (Some(dir), Some(name)) => resolve(dir).join(name),
+            _ => grammar.to_path_buf(),
+        };
+        Self { dictionary_dir: dictionary_dir.map(resolve), grammar }
+    }
+
+    /// Which of the files a changed `path` is, if either
+    pub fn classify(&self, path: &Path) -> Option<ConfigFile> {
+        if path == self.grammar {
+            return Some(ConfigFile::Grammar);
+        }
+        let in_dictionary = self.dictionary_dir.as_deref().is_some_and(|dir| path.parent() == Some(dir));
+        let name = path.file_name().and_then(|name| name.to_str());
+        (in_dictionary && name.is_some_and(|name| DICTIONARY_FILES.contains(&name))).then_some(ConfigFile::Dictionary)
+    }
+
+    // Directories rather than files, so a file replaced by a rename is still seen
+    fn directories(&self) -> BTreeSet<&Path> {
+        self.dictionary_dir.as_deref().into_iter().chain(self.grammar.parent()).collect()
+    }
+}
+
+// Watchers report absolute paths with symlinks resolved
+fn resolve(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Stops watching when dropped
+pub struct ConfigWatcher {
+    paths: WatchedPaths,
+    _watcher: RecommendedWatcher,
+}
+
+impl std::fmt::Debug for ConfigWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConfigWatcher").field("paths", &self.paths).finish_non_exhaustive()
+    }
+}
+
+impl ConfigWatcher {
+    /// Starts watching `paths`. The receiver yields the files changed in each
+    /// burst of events, and closes once the watcher is dropped.
+    pub fn start(paths: WatchedPaths) -> notify::Result<(Self, mpsc::UnboundedReceiver<BTreeSet<ConfigFile>>)> {
+        let (event_tx, mut events) = mpsc::unbounded_channel::<notify::Result<Event>>();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let _ = event_tx.send(event);
+        })?;
+        for dir in paths.directories() {
+            watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        }
+
+        let (changes_tx, changes) = mpsc::unbounded_channel();
+        let classifier = paths.clone();
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                let mut changed = BTreeSet::new();
+                collect_changed(&classifier, event, &mut changed);
+                while let Ok(Some(event)) = tokio::time::timeout(RELOAD_DEBOUNCE, events.recv()).await {
+                    collect_changed(&classifier, event, &mut changed);
+                }
+                if !changed.is_empty() && changes_tx.send(changed).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok((Self { paths, _watcher: watcher }, changes))
+    }
+
+    pub fn paths(&self) -> &WatchedPaths {
+        &self.paths
+    }
+}
+
+fn collect_changed(paths: &WatchedPaths, event: notify::Result<Event>, changed: &mut BTreeSet<ConfigFile>) {
+    let event = match event {
+        Ok(event) => event,
+        Err(e) => {
+            log::warn!("Watch error: {}", e);
+            return;
+        }
+    };
+    if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) {
+        changed.extend(event.paths.iter().filter_map(|path| paths.classify(path)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_the_dictionary_files_and_the_grammar_are_reloaded() {
+        let paths = WatchedPaths {
+            dictionary_dir: Some(PathBuf::from("/work/dictionary")),
+            grammar: PathBuf::from("/work/grammar_rules.json"),
+        };
+        assert_eq!(paths.classify(Path::new("/work/dictionary/entities.json")), Some(ConfigFile::Dictionary));
+        assert_eq!(paths.classify(Path::new("/work/dictionary/lookups.json")), Some(ConfigFile::Dictionary));
+        assert_eq!(paths.classify(Path::new("/work/grammar_rules.json")), Some(ConfigFile::Grammar));
+        assert_eq!(paths.classify(Path::new("/work/dictionary/.entities.json.swp")), None);
+        assert_eq!(paths.classify(Path::new("/work/dictionary/old/entities.json")), None);
+        assert_eq!(paths.classify(Path::new("/work/entities.json")), None);
+        assert_eq!(paths.directories().len(), 2);
+
+        let without_dictionary = WatchedPaths { dictionary_dir: None, ..paths };
+        assert_eq!(without_dictionary.classify(Path::new("/work/dictionary/entities.json")), None);
+    }
+}
@@ -2,6 +2,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use data_designer::rule_tests::AttributeConstraints;
+use data_designer::type_checker::{RuleType, TypeEnv};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DataDictionary {
@@ -60,6 +62,78 @@ pub enum DataType {
     Enum(Vec<String>),
 }
 
+impl DataType {
+    pub fn rule_type(&self) -> RuleType {
+        match self {
+            DataType::String | DataType::Varchar(_) | DataType::Char(_) | DataType::Uuid | DataType::Enum(_) => RuleType::String,
+            DataType::Number
+            | DataType::Decimal { .. }
+            | DataType::Integer
+            | DataType::BigInt
+            | DataType::SmallInt
+            | DataType::Float
+            | DataType::Double => RuleType::Number,
+            DataType::Boolean => RuleType::Boolean,
+            DataType::Date | DataType::DateTime => RuleType::Date,
+            DataType::Array(_) => RuleType::List,
+            DataType::Json => RuleType::Unknown,
+        }
+    }
+
+    /// Short name for inlay hints: `Decimal`, `Varchar`, `Array<String>`
+    pub fn label(&self) -> String {
+        match self {
+            DataType::String => "String".to_string(),
+            DataType::Number => "Number".to_string(),
+            DataType::Boolean => "Boolean".to_string(),
+            DataType::Date => "Date".to_string(),
+            DataType::DateTime => "DateTime".to_string(),
+            DataType::Json => "Json".to_string(),
+            DataType::Array(item) => format!("Array<{}>", item.label()),
+            DataType::Decimal { .. } => "Decimal".to_string(),
+            DataType::Varchar(_) => "Varchar".to_string(),
+            DataType::Char(_) => "Char".to_string(),
+            DataType::Integer => "Integer".to_string(),
+            DataType::BigInt => "BigInt".to_string(),
+            DataType::SmallInt => "SmallInt".to_string(),
+            DataType::Float => "Float".to_string(),
+            DataType::Double => "Double".to_string(),
+            DataType::Uuid => "Uuid".to_string(),
+            DataType::Enum(_) => "Enum".to_string(),
+        }
+    }
+}
+
+impl Attribute {
+    /// A bare attribute of the type a rule uses it as, for the author to fill in
+    pub fn new(name: &str, rule_type: RuleType) -> Self {
+        let data_type = match rule_type {
+            RuleType::Number => DataType::Number,
+            RuleType::Boolean => DataType::Boolean,
+            RuleType::Date => DataType::Date,
+            RuleType::List => DataType::Array(Box::new(DataType::String)),
+            RuleType::String | RuleType::Null | RuleType::Unknown => DataType::String,
+        };
+        Attribute {
+            name: name.to_string(),
+            data_type,
+            description: String::new(),
+            required: false,
+            validation_rules: Vec::new(),
+            domain: None,
+            examples: Vec::new(),
+            sql_type: None,
+            rust_type: None,
+            format_mask: None,
+            min_length: None,
+            max_length: None,
+            min_value: None,
+            max_value: None,
+            pattern: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Domain {
     pub name: String,
@@ -84,6 +158,19 @@ pub struct LookupTable {
     pub entries: HashMap<String, String>,
 }
 
+impl LookupTable {
+    /// A table of strings whose entries map each value to itself, for testing membership with LOOKUP
+    pub fn of_values<'a>(name: &str, description: &str, values: impl IntoIterator<Item = &'a str>) -> Self {
+        LookupTable {
+            name: name.to_string(),
+            description: description.to_string(),
+            key_type: DataType::String,
+            value_type: DataType::String,
+            entries: values.into_iter().map(|value| (value.to_string(), value.to_string())).collect(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Relationship {
     pub from_entity: String,
@@ -193,6 +280,26 @@ impl DataDictionary {
         None
     }
 
+    /// Add an attribute to an entity; false if there's no such entity or it
+    /// already has an attribute of that name
+    pub fn add_attribute(&mut self, entity: &str, attribute: Attribute) -> bool {
+        match self.entities.get_mut(entity) {
+            Some(entity) if !entity.attributes.iter().any(|a| a.name == attribute.name) => {
+                entity.attributes.push(attribute);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn add_lookup(&mut self, table: LookupTable) -> bool {
+        if self.lookups.contains_key(&table.name) {
+            return false;
+        }
+        self.lookups.insert(table.name.clone(), table);
+        true
+    }
+
     pub fn get_domain_values(&self, domain_name: &str) -> Vec<String> {
         self.domains.get(domain_name)
             .map(|d| d.values.iter().map(|v| v.code.clone()).collect())
@@ -205,6 +312,43 @@ impl DataDictionary {
             .unwrap_or_default()
     }
 
+    /// Attribute types for the rule type checker, keyed by both `Entity.attr` and `attr`
+    pub fn type_env(&self) -> TypeEnv {
+        let mut env = TypeEnv::new();
+        for (full_name, attribute) in self.get_all_attributes() {
+            let rule_type = attribute.data_type.rule_type();
+            env.insert(&full_name, rule_type);
+            env.insert(&attribute.name, rule_type);
+        }
+        env
+    }
+
+    /// Constraints used to sample starter test values for rules that read this attribute
+    pub fn constraints(&self, full_name: &str) -> Option<AttributeConstraints> {
+        let attribute = self.get_attribute_info(full_name)?;
+        let mut domain_values = attribute
+            .domain
+            .as_deref()
+            .map(|domain| self.get_domain_values(domain))
+            .unwrap_or_default();
+        if domain_values.is_empty() {
+            if let DataType::Enum(values) = &attribute.data_type {
+                domain_values = values.clone();
+            }
+        }
+
+        Some(AttributeConstraints {
+            rule_type: attribute.data_type.rule_type(),
+            min_value: attribute.min_value,
+            max_value: attribute.max_value,
+            min_length: attribute.min_length,
+            max_length: attribute.max_length,
+            pattern: attribute.pattern,
+            examples: attribute.examples,
+            domain_values,
+        })
+    }
+
     pub fn create_default_kyc_dictionary() -> Self {
         let mut dictionary = DataDictionary::new();
 
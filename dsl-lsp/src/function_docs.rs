@@ -0,0 +1,158 @@
+//! Function documentation pages served as virtual documents
+//!
+//! Hovers and completions for a function link to `dsl://docs/FUNCTION/<NAME>`;
+//! the client opens that URI as a read-only document whose content the
+//! server renders from the function catalogue: signature, description,
+//! examples and the workspace rules that call the function. Nothing is
+//! fetched, so the pages work offline. Diagnostic codes get pages the same
+//! way, at `dsl://docs/ERROR/<CODE>`, linked from each diagnostic.
+
+use data_designer::error_codes::ErrorCode;
+use tower_lsp::lsp_types::{SymbolInformation, Url};
+
+/// Prefix of a function's documentation URI; the function name follows
+pub const FUNCTION_DOCS_PREFIX: &str = "dsl://docs/FUNCTION/";
+
+/// The documentation URI of a function, e.g. `dsl://docs/FUNCTION/CONCAT`
+pub fn function_doc_uri(name: &str) -> Option<Url> {
+    Url::parse(&format!("{}{}", FUNCTION_DOCS_PREFIX, name.to_uppercase())).ok()
+}
+
+/// The function a documentation URI is about
+pub fn function_from_doc_uri(uri: &Url) -> Option<String> {
+    let name = uri.as_str().strip_prefix(FUNCTION_DOCS_PREFIX)?;
+    (!name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')).then(|| name.to_uppercase())
+}
+
+/// Prefix of a diagnostic code's documentation URI; the code follows
+pub const ERROR_DOCS_PREFIX: &str = "dsl://docs/ERROR/";
+
+/// The documentation URI of a diagnostic code, e.g. `dsl://docs/ERROR/DSL0006`
+pub fn error_doc_uri(code: ErrorCode) -> Option<Url> {
+    Url::parse(&format!("{}{}", ERROR_DOCS_PREFIX, code.code())).ok()
+}
+
+/// The diagnostic code a documentation URI is about
+pub fn error_from_doc_uri(uri: &Url) -> Option<ErrorCode> {
+    ErrorCode::parse(uri.as_str().strip_prefix(ERROR_DOCS_PREFIX)?)
+}
+
+/// A diagnostic code's page as Markdown
+pub fn render_error_doc(code: ErrorCode) -> String {
+    format!("# {}: {}
+
+{}
+", code.code(), code.title(), code.explanation())
+}
+
+/// A catalogue entry, ready to render
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionDoc {
+    pub name: String,
+    pub signature: String,
+    pub description: String,
+    pub examples: Vec<String>,
+}
+
+impl FunctionDoc {
+    /// Builds the page from a catalogue description, which often ends in an
+    /// example call: "Validates email format: IS_EMAIL(email)". Without a
+    /// declared signature, the first example stands in for one.
+    pub fn from_catalogue(name: &str, description: &str, signature: Option<&str>) -> Self {
+        let name = name.to_uppercase();
+        let call = format!("{}(", name);
+        let (description, examples) = match description.split_once(": ") {
+            Some((text, example)) if example.starts_with(&call) => (text.to_string(), vec![example.to_string()]),
+            _ => (description.to_string(), Vec::new()),
+        };
+        let signature = match signature {
+            Some(signature) => signature.to_string(),
+            None => examples
+                .first()
+                .and_then(|example| example.find(')').map(|end| example[..=end].to_string()))
+                .unwrap_or_else(|| format!("{}(...)", name)),
+        };
+        FunctionDoc { name, signature, description, examples }
+    }
+
+    /// The page as Markdown; `used_by` are the rules calling the function
+    pub fn render(&self, used_by: &[SymbolInformation]) -> String {
+        let mut page = format!("# {}\n\n```dsl\n{}\n```\n\n{}\n", self.name, self.signature, self.description);
+
+        if !self.examples.is_empty() {
+            page.push_str("\n## Examples\n\n```dsl\n");
+            for example in &self.examples {
+                page.push_str(example);
+                page.push('\n');
+            }
+            page.push_str("```\n");
+        }
+
+        page.push_str("\n## Used by\n\n");
+        if used_by.is_empty() {
+            page.push_str("No rules in this workspace call this function.\n");
+        }
+        for rule in used_by {
+            let mut target = rule.location.uri.clone();
+            target.set_fragment(Some(&format!("L{}", rule.location.range.start.line + 1)));
+            let file = rule.container_name.as_deref().unwrap_or("");
+            let line = format!("- [{}]({}) {}", rule.name, target, file);
+            page.push_str(line.trim_end());
+            page.push('\n');
+        }
+        page
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::lsp_types::{Location, Position, Range, SymbolKind};
+
+    #[test]
+    fn test_doc_uri_round_trips_function_names() {
+        let uri = function_doc_uri("concat").unwrap();
+        assert_eq!(uri.as_str(), "dsl://docs/FUNCTION/CONCAT");
+        assert_eq!(function_from_doc_uri(&uri).as_deref(), Some("CONCAT"));
+        assert_eq!(function_from_doc_uri(&Url::parse("dsl://docs/FUNCTION/").unwrap()), None);
+        assert_eq!(function_from_doc_uri(&Url::parse("file:///rules/kyc.dsl").unwrap()), None);
+    }
+
+    #[test]
+    fn test_error_doc_uri_round_trips_codes() {
+        let uri = error_doc_uri(ErrorCode::RuleConflict).unwrap();
+        assert_eq!(uri.as_str(), "dsl://docs/ERROR/DSL0006");
+        assert_eq!(error_from_doc_uri(&uri), Some(ErrorCode::RuleConflict));
+        assert_eq!(function_from_doc_uri(&uri), None);
+        assert_eq!(error_from_doc_uri(&function_doc_uri("concat").unwrap()), None);
+        assert!(render_error_doc(ErrorCode::RuleConflict).starts_with("# DSL0006: Conflicting rules\n\n"));
+    }
+
+    #[test]
+    fn test_render_lists_examples_and_calling_rules() {
+        let doc = FunctionDoc::from_catalogue("IS_EMAIL", "Validates email format: IS_EMAIL(email)", None);
+        assert_eq!(doc.signature, "IS_EMAIL(email)");
+        assert_eq!(doc.description, "Validates email format");
+
+        #[allow(deprecated)]
+        let rule = SymbolInformation {
+            name: "contact_ok".to_string(),
+            kind: SymbolKind::VARIABLE,
+            tags: None,
+            deprecated: None,
+            location: Location {
+                uri: Url::parse("file:///rules/kyc.dsl").unwrap(),
+                range: Range { start: Position::new(4, 0), end: Position::new(4, 10) },
+            },
+            container_name: Some("kyc.dsl".to_string()),
+        };
+        let page = doc.render(&[rule]);
+        assert!(page.starts_with("# IS_EMAIL\n\n```dsl\nIS_EMAIL(email)\n```\n\nValidates email format\n"));
+        assert!(page.contains("## Examples\n\n```dsl\nIS_EMAIL(email)\n```"));
+        assert!(page.contains("- [contact_ok](file:///rules/kyc.dsl#L5) kyc.dsl\n"));
+
+        let plain = FunctionDoc::from_catalogue("upper", "Converts string to uppercase", None);
+        assert_eq!(plain.signature, "UPPER(...)");
+        assert!(plain.render(&[]).contains("No rules in this workspace call this function."));
+    }
+}
@@ -56,6 +56,8 @@ pub struct GrammarFile {
 pub struct DynamicGrammar {
     pub keywords: Vec<String>,
     pub functions: Vec<(String, String)>,
+    /// Declared signatures by function name
+    pub signatures: HashMap<String, String>,
     pub operators: Vec<(String, String)>,
     pub grammar_rules: Vec<GrammarRule>,
 }
@@ -73,6 +75,7 @@ impl GrammarLoader {
         let initial_grammar = DynamicGrammar {
             keywords: vec![],
             functions: vec![],
+            signatures: HashMap::new(),
             operators: vec![],
             grammar_rules: vec![],
         };
@@ -111,6 +114,10 @@ impl GrammarLoader {
             .iter()
             .map(|f| (f.name.clone(), f.description.clone()))
             .collect();
+        let signatures: HashMap<String, String> = grammar_file.extensions.functions
+            .iter()
+            .map(|f| (f.name.to_uppercase(), f.signature.clone()))
+            .collect();
 
         // Build operators list from extensions
         let mut operators = Vec::new();
@@ -130,6 +137,7 @@ impl GrammarLoader {
         let dynamic_grammar = DynamicGrammar {
             keywords,
             functions,
+            signatures,
             operators,
             grammar_rules: grammar_file.grammar.rules,
         };
@@ -150,6 +158,11 @@ impl GrammarLoader {
         grammar.functions.clone()
     }
 
+    pub async fn get_function_signature(&self, name: &str) -> Option<String> {
+        let grammar = self.grammar.read().await;
+        grammar.signatures.get(&name.to_uppercase()).cloned()
+    }
+
     pub async fn get_operators(&self) -> Vec<(String, String)> {
         let grammar = self.grammar.read().await;
         grammar.operators.clone()
@@ -0,0 +1,119 @@
+//! Evaluation previews for hover
+//!
+//! With a test context attached to a document, hovering part of a rule
+//! evaluates just the sub-expression under the cursor and shows its value and
+//! type. The rules above it run first, as for inlay hints, so it sees their
+//! results. Attributes the context doesn't set are listed with what is left
+//! of the expression instead of being read as null.
+
+use crate::data_dictionary::DataDictionary;
+use crate::inlay_hints::context_facts;
+use data_designer::evaluator::{evaluate_partial, FunctionLibrary, PartialValue};
+use data_designer::formatter::format_expression;
+use data_designer::models::Expression;
+use data_designer::parser::{parse_rule_spanned, ParsedRule};
+use data_designer::type_checker::{typecheck_with_env, RuleType};
+use std::collections::BTreeMap;
+
+/// The sub-expression at `text[start..end]` and what it evaluates to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preview {
+    pub start: usize,
+    pub end: usize,
+    pub expression: String,
+    pub value: PartialValue,
+    pub rule_type: RuleType,
+}
+
+impl Preview {
+    pub fn markdown(&self) -> String {
+        let expression = self.expression.split_whitespace().collect::<Vec<_>>().join(" ");
+        match &self.value {
+            PartialValue::Known(value) => {
+                let rule_type = match self.rule_type {
+                    RuleType::Unknown => RuleType::of_value(value),
+                    rule_type => rule_type,
+                };
+                format!("**Test context:** `{}` = `{}` : {}", expression, value.to_json(), rule_type)
+            }
+            PartialValue::Residual { missing, residual } => {
+                let missing: Vec<String> = missing.iter().map(|name| format!("`{}`", name)).collect();
+                format!(
+                    "**Test context:** `{}` needs {}, which it doesn't set\n\nWith what it does set: `{}`",
+                    expression,
+                    missing.join(", "),
+                    format_expression(residual)
+                )
+            }
+            PartialValue::Failed(message) => format!("**Test context:** `{}` fails: {}", expression, message),
+        }
+    }
+}
+
+/// The innermost sub-expression of `rules` at byte `offset`, evaluated with
+/// `context` and the values of the rules before it
+pub fn evaluation_preview(
+    text: &str,
+    rules: &[ParsedRule],
+    offset: usize,
+    dictionary: &DataDictionary,
+    context: &BTreeMap<String, serde_json::Value>,
+) -> Option<Preview> {
+    let functions = FunctionLibrary::new();
+    let mut env = dictionary.type_env();
+    let mut facts = context_facts(context);
+
+    for rule in rules {
+        if (rule.start..rule.end).contains(&offset) {
+            let source = &text[rule.start..rule.end];
+            let (_, spanned) = parse_rule_spanned(source).ok()?;
+            let found = spanned.at(offset - rule.start)?;
+            return Some(Preview {
+                start: rule.start + found.start,
+                end: rule.start + found.end,
+                expression: source[found.start..found.end].to_string(),
+                value: evaluate_partial(&found.expression, &facts, &functions),
+                rule_type: typecheck_with_env(&found.expression, &env).inferred,
+            });
+        }
+        if let Expression::Assignment { target, .. } = &rule.expression {
+            env.insert(target, typecheck_with_env(&rule.expression, &env).inferred);
+            if let PartialValue::Known(value) = evaluate_partial(&rule.expression, &facts, &functions) {
+                facts.insert(target.clone(), value);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_designer::models::Value;
+    use data_designer::parser::parse_rules_recovering;
+
+    #[test]
+    fn test_preview_evaluates_the_sub_expression_under_the_cursor() {
+        let text = "fee = aum_usd * 0.01\nnet = (fee - rebate) * 2\n";
+        let (rules, _) = parse_rules_recovering(text);
+        let dictionary = DataDictionary::create_default_kyc_dictionary();
+        let context = BTreeMap::from([("Client.aum_usd".to_string(), serde_json::json!(25000))]);
+        let preview = |fragment: &str| {
+            evaluation_preview(text, &rules, text.rfind(fragment).unwrap(), &dictionary, &context).unwrap()
+        };
+
+        let fee = preview("fee -");
+        assert_eq!((fee.expression.as_str(), &fee.value), ("fee", &PartialValue::Known(Value::Float(250.0))));
+        assert_eq!(fee.rule_type, RuleType::Number);
+
+        let difference = preview("- rebate");
+        assert_eq!(difference.expression, "(fee - rebate)");
+        assert_eq!(
+            difference.markdown(),
+            "**Test context:** `(fee - rebate)` needs `rebate`, which it doesn't set\n\nWith what it does set: `250.0 - rebate`"
+        );
+
+        let product = preview("aum_usd *");
+        assert_eq!(product.markdown(), "**Test context:** `aum_usd` = `25000` : Number");
+    }
+}
@@ -0,0 +1,165 @@
+//! Inlay hints for a rule document
+//!
+//! Every attribute a rule reads is annotated with its dictionary type
+//! (`: Decimal`), and each rule's target with the type the checker infers
+//! for it. With a test context loaded, reads also show their value in the
+//! context and targets the value their rule evaluates to, the rules being
+//! run in document order so later ones see the earlier results. Hints come
+//! back as byte offsets; the inlay_hint handler turns them into positions.
+
+use crate::data_dictionary::DataDictionary;
+use crate::quick_fixes::name_sites;
+use data_designer::evaluator::{evaluate, Facts};
+use data_designer::models::{Expression, Value};
+use data_designer::parser::ParsedRule;
+use data_designer::rule_graph::extract_dependencies_from_ast;
+use data_designer::type_checker::{typecheck_with_env, RuleType};
+use std::collections::BTreeMap;
+
+/// Values longer than this are cut short, so a hint never crowds out the rule
+const MAX_VALUE_LENGTH: usize = 24;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HintKind {
+    Type,
+    Value,
+}
+
+/// A label shown right after `text[..offset]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hint {
+    pub offset: usize,
+    pub label: String,
+    pub kind: HintKind,
+}
+
+/// Hints for `rules`, in document order; `context` holds sample values by attribute name
+pub fn inlay_hints(
+    text: &str,
+    rules: &[ParsedRule],
+    dictionary: &DataDictionary,
+    context: Option<&BTreeMap<String, serde_json::Value>>,
+) -> Vec<Hint> {
+    let mut env = dictionary.type_env();
+    let mut facts = context.map(context_facts);
+
+    let mut hints = Vec::new();
+    for rule in rules {
+        let rule_text = &text[rule.start..rule.end];
+        let target = match &rule.expression {
+            Expression::Assignment { target, .. } => Some(target.as_str()),
+            _ => None,
+        };
+        // The target's own name is at the start; reads are looked for after the `=`
+        let body_start = target.and_then(|_| rule_text.find('=')).map_or(0, |i| i + 1);
+
+        let mut found = Vec::new();
+        for name in extract_dependencies_from_ast(&rule.expression) {
+            let type_label = match dictionary.get_attribute_info(&name) {
+                Some(attribute) => Some(attribute.data_type.label()),
+                None => env.get(&name).filter(|rule_type| *rule_type != RuleType::Unknown).map(|rule_type| rule_type.to_string()),
+            };
+            let value = facts.as_ref().and_then(|facts| fact(facts, &name));
+            for (_, end) in name_sites(&rule_text[body_start..], &name, false) {
+                let offset = rule.start + body_start + end;
+                if let Some(label) = &type_label {
+                    found.push(Hint { offset, label: format!(": {}", label), kind: HintKind::Type });
+                }
+                if let Some(value) = value {
+                    found.push(Hint { offset, label: format!("= {}", display_value(value)), kind: HintKind::Value });
+                }
+            }
+        }
+        found.sort_by_key(|hint| hint.offset);
+
+        if let Some(target) = target {
+            let inferred = typecheck_with_env(&rule.expression, &env).inferred;
+            env.insert(target, inferred);
+            let value = facts.as_mut().and_then(|facts| {
+                let value = evaluate(&rule.expression, facts).ok()?;
+                facts.insert(target.to_string(), value.clone());
+                Some(value)
+            });
+            if rule_text.starts_with(target) {
+                let offset = rule.start + target.len();
+                let mut target_hints = Vec::new();
+                if inferred != RuleType::Unknown {
+                    target_hints.push(Hint { offset, label: format!(": {}", inferred), kind: HintKind::Type });
+                }
+                if let Some(value) = value {
+                    target_hints.push(Hint { offset, label: format!("= {}", display_value(&value)), kind: HintKind::Value });
+                }
+                found.splice(0..0, target_hints);
+            }
+        }
+        hints.extend(found);
+    }
+    hints
+}
+
+// A context keyed `Entity.attr` also answers rules that read plain `attr`
+pub(crate) fn context_facts(context: &BTreeMap<String, serde_json::Value>) -> Facts {
+    let mut facts = Facts::new();
+    for (name, value) in context {
+        let value = Value::from_json(value);
+        if let Some((_, attribute)) = name.rsplit_once('.') {
+            facts.entry(attribute.to_string()).or_insert_with(|| value.clone());
+        }
+        facts.insert(name.clone(), value);
+    }
+    facts
+}
+
+// The context's value for `Entity.attr`, falling back to plain `attr`
+fn fact<'a>(facts: &'a Facts, name: &str) -> Option<&'a Value> {
+    facts.get(name).or_else(|| name.rsplit_once('.').and_then(|(_, attribute)| facts.get(attribute)))
+}
+
+fn display_value(value: &Value) -> String {
+    let shown = value.to_json().to_string();
+    if shown.chars().count() <= MAX_VALUE_LENGTH {
+        return shown;
+    }
+    let mut cut: String = shown.chars().take(MAX_VALUE_LENGTH - 1).collect();
+    cut.push('…');
+    cut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_designer::parser::parse_rules_recovering;
+
+    fn labels(text: &str, hints: &[Hint]) -> Vec<String> {
+        hints.iter().map(|hint| format!("{}{}", &text[..hint.offset].rsplit(char::is_whitespace).next().unwrap(), hint.label)).collect()
+    }
+
+    #[test]
+    fn test_reads_and_targets_are_annotated_with_types() {
+        let text = "fee = aum_usd * 0.01\nbig = fee > 100 AND risk_rating == \"HIGH\"";
+        let (rules, _) = parse_rules_recovering(text);
+        let dictionary = DataDictionary::create_default_kyc_dictionary();
+
+        let hints = inlay_hints(text, &rules, &dictionary, None);
+        assert_eq!(
+            labels(text, &hints),
+            ["fee: Number", "aum_usd: Decimal", "big: Boolean", "fee: Number", "risk_rating: Enum"]
+        );
+        assert!(hints.iter().all(|hint| hint.kind == HintKind::Type));
+    }
+
+    #[test]
+    fn test_context_values_flow_through_the_rules() {
+        let text = "fee = aum_usd * 0.01\nlabel = CONCAT(\"Fee for a very long client name \", fee)";
+        let (rules, _) = parse_rules_recovering(text);
+        let dictionary = DataDictionary::create_default_kyc_dictionary();
+        let context = BTreeMap::from([("Client.aum_usd".to_string(), serde_json::json!(25000))]);
+
+        let values: Vec<String> = inlay_hints(text, &rules, &dictionary, Some(&context))
+            .into_iter()
+            .filter(|hint| hint.kind == HintKind::Value)
+            .map(|hint| hint.label)
+            .collect();
+        assert_eq!(values, ["= 250.0", "= 25000", "= \"Fee for a very long cl…", "= 250.0"]);
+    }
+}
@@ -1,26 +1,77 @@
 pub mod data_dictionary;
 pub mod ai_agent;
+pub mod ai_completions;
+pub mod config_watcher;
+pub mod function_docs;
 pub mod grammar_loader;
+pub mod hover_preview;
+pub mod inlay_hints;
+pub mod quick_fixes;
+pub mod replay;
+pub mod session_recorder;
+pub mod settings;
+pub mod symbol_index;
+pub mod workspace_rules;
 
 use dashmap::DashMap;
 use lazy_static::lazy_static;
-use regex::Regex;
 use ropey::Rope;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
-use tower_lsp::{Client, LanguageServer, LspService, Server};
-use data_designer::parser::parse_rule;
-use crate::data_dictionary::DataDictionary;
-use crate::ai_agent::{AIAgentManager, CompletionRequest, CompletionContext, ValidationRequest};
+use tower_lsp::{Client, ClientSocket, LanguageServer, LspService, Server};
+use data_designer::capabilities::{self, Capability};
+use data_designer::db::{EditorDatabase, RuleOperations};
+use data_designer::error_codes::ErrorCode;
+use data_designer::evaluator::BUILTIN_FUNCTIONS;
+use data_designer::formatter::format_document;
+use data_designer::function_registry::FunctionRegistry;
+use data_designer::lookup_extraction::{extract_lookup_table, inline_lists_in, MIN_EXTRACTED_VALUES};
+use data_designer::models::Expression;
+use data_designer::parser::parse_rules_recovering;
+use data_designer::rule_conflicts::find_conflicts;
+use data_designer::rule_cost::{estimate_rule_cost, LatencyBudget};
+use data_designer::rule_rewrite::{rename_edits, RewriteMode, RuleRewrite};
+use data_designer::rule_sexpr::{from_sexpr_document, to_sexpr_document};
+use data_designer::rule_tests::{generate_document_tests, merge_test_cases, RuleTestCase};
+use data_designer::semantic_tokens::{diff, encode, tokenize};
+use data_designer::source_structure::{folds, regions, selection_spans, Region, RegionKind, RULE_SYNTAX};
+use data_designer::type_checker::{typecheck_with_env, RuleType};
+use crate::data_dictionary::{Attribute, DataDictionary, LookupTable};
+use crate::ai_agent::{AIAgent, AIAgentManager, CompletionRequest, CompletionContext, ValidationRequest};
+use crate::ai_completions::{ai_completion_items, AiSuggestions, CompletionKey, AI_DEBOUNCE};
+use crate::config_watcher::{ConfigFile, ConfigWatcher, WatchedPaths};
+use crate::function_docs::{error_doc_uri, error_from_doc_uri, function_doc_uri, function_from_doc_uri, render_error_doc, FunctionDoc};
 use crate::grammar_loader::GrammarLoader;
+use crate::hover_preview::evaluation_preview;
+use crate::inlay_hints::{inlay_hints, HintKind};
+use crate::quick_fixes::{add_attribute_to_entities, add_lookup_to_lookups, close_parens_fix, unknown_names, TextFix, UnknownKind, UnknownName};
+use crate::session_recorder::{SessionRecorder, SESSIONS_DIR};
+use crate::settings::{Settings, SECTION};
+use crate::symbol_index::{document_symbols, is_rule_file, stored_rule_from_uri, FileStamp, SymbolIndex};
+use crate::workspace_rules::WorkspaceRules;
 use tokio::sync::RwLock;
 
+/// Shown for functions registered at runtime, which carry no description of their own
+const REGISTERED_FUNCTION_DESCRIPTION: &str = "Registered by the host application";
+
 // DSL Keywords and functions based on EBNF
 lazy_static! {
     static ref DSL_KEYWORDS: Vec<&'static str> = vec![
-        "IF", "THEN", "ELSE", "AND", "OR", "NOT", "true", "false", "null"
+        "IF", "THEN", "ELSE", "CASE", "WHEN", "END", "AND", "OR", "NOT", "true", "false", "null"
+    ];
+
+    static ref DSL_SNIPPETS: Vec<(&'static str, &'static str, &'static str)> = vec![
+        ("IF", "IF condition THEN value ELSE value", "IF ${1:condition} THEN ${2:value} ELSE ${3:value}"),
+        (
+            "CASE",
+            "CASE WHEN condition THEN value ... ELSE value END",
+            "CASE\n    WHEN ${1:condition} THEN ${2:value}\n    WHEN ${3:condition} THEN ${4:value}\n    ELSE ${5:value}\nEND",
+        ),
     ];
 
     static ref DSL_FUNCTIONS: Vec<(&'static str, &'static str)> = vec![
@@ -34,13 +85,28 @@ lazy_static! {
         ("ABS", "Returns absolute value"),
         ("MAX", "Returns maximum of values"),
         ("MIN", "Returns minimum of values"),
+        // Locale formatting
+        ("FORMAT_NUMBER", "Formats a number for a locale: FORMAT_NUMBER(value, \"de-DE\", 2)"),
+        ("FORMAT_DATE", "Formats a date for a locale: FORMAT_DATE(value, \"%d %B %Y\", \"fr-FR\")"),
+        ("TO_NUMBER", "Parses a number, optionally locale-formatted: TO_NUMBER(text, \"de-DE\")"),
+        // Units
+        ("TO_PCT", "Converts a fraction to a percentage: TO_PCT(0.05) is 5%"),
+        ("TO_BASIS_POINTS", "Percentage or fraction in basis points: TO_BASIS_POINTS(1.25%) is 125"),
+        ("SUM", "Sums a list, optionally mapped by a lambda: SUM(items, x -> x * rate)"),
+        // Higher-order list functions
+        ("MAP", "Transforms each list item: MAP(items, x -> x * 2)"),
+        ("FILTER", "Keeps list items matching a lambda: FILTER(items, x -> x > 10)"),
+        ("ANY", "True if any list item matches: ANY(items, x -> x > 10)"),
+        ("ALL", "True if every list item matches: ALL(items, x -> x > 10)"),
         // Regex validation functions for KYC
         ("IS_EMAIL", "Validates email format: IS_EMAIL(email)"),
         ("IS_LEI", "Validates Legal Entity Identifier: IS_LEI(lei)"),
         ("IS_SWIFT", "Validates SWIFT/BIC code: IS_SWIFT(code)"),
         ("IS_PHONE", "Validates phone number: IS_PHONE(number)"),
         ("VALIDATE", "Generic pattern validation: VALIDATE(value, pattern)"),
-        ("EXTRACT", "Extract pattern matches: EXTRACT(value, pattern)"),
+        ("EXTRACT", "First pattern match, or a named or numbered group: EXTRACT(lei, /(?<prefix>\\w{4})/, \"prefix\")"),
+        ("EXTRACT_ALL", "Every pattern match as a list: EXTRACT_ALL(value, pattern, group)"),
+        ("MATCH_COUNT", "Number of pattern matches: MATCH_COUNT(value, pattern)"),
         ("MATCHES", "Pattern matching function: MATCHES(text, pattern)"),
     ];
 
@@ -51,6 +117,7 @@ lazy_static! {
         ("/", "Division"),
         ("%", "Modulo"),
         ("&", "String concatenation"),
+        ("->", "Lambda in list functions: x -> x > 10"),
         ("==", "Equality comparison"),
         ("!=", "Inequality comparison"),
         ("<", "Less than"),
@@ -59,31 +126,67 @@ lazy_static! {
         (">=", "Greater than or equal"),
         ("=", "Assignment"),
         ("MATCHES", "Regex pattern matching: text MATCHES /pattern/"),
+        ("IN", "Set membership: country IN [\"US\", \"GB\", \"DE\"]"),
+        ("NOT IN", "Negated set membership: country NOT IN [\"US\", \"GB\"]"),
+        ("BETWEEN", "Inclusive range check: amount BETWEEN 1000 AND 5000"),
+        ("BETWEEN_EXCLUSIVE", "Range check without the upper bound: amount BETWEEN_EXCLUSIVE 1000 AND 5000"),
         ("~", "Regex match shorthand: text ~ /pattern/"),
     ];
+}
 
-    static ref IDENTIFIER_PATTERN: Regex = Regex::new(r"\b[a-zA-Z_][a-zA-Z0-9_]*\b").unwrap();
-    static ref NUMBER_PATTERN: Regex = Regex::new(r"\b\d+(\.\d+)?\b").unwrap();
-    static ref STRING_PATTERN: Regex = Regex::new(r#"["'][^"']*["']"#).unwrap();
-    static ref REGEX_PATTERN: Regex = Regex::new(r#"/[^/]+/|r"[^"]+"#).unwrap();
+/// Params of the custom `dsl/setContext` request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetContextParams {
+    pub text_document: TextDocumentIdentifier,
+    /// As for `dsl.loadTestContext`: values by attribute name, or the path
+    /// of a JSON file of them or of a `.tests.json` list of cases
+    #[serde(default)]
+    pub context: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub case: Option<String>,
 }
 
-#[derive(Debug)]
+/// Clones share the same state, so background tasks can hold one
+#[derive(Debug, Clone)]
 pub struct Backend {
     client: Client,
     document_map: Arc<DashMap<Url, Rope>>,
-    semantic_tokens: Arc<DashMap<Url, Vec<DslSemanticToken>>>,
+    /// The encoded tokens last sent for each document and their result id,
+    /// which a delta request names to get only what changed since
+    semantic_tokens: Arc<DashMap<Url, (String, Vec<u32>)>>,
+    next_tokens_result: Arc<AtomicU64>,
     data_dictionary: Arc<RwLock<DataDictionary>>,
+    /// Where the dictionary was loaded from; None for the built-in KYC one.
+    /// The add-attribute quick fix edits the entities.json there.
+    data_dictionary_dir: Arc<RwLock<Option<PathBuf>>>,
     ai_agent_manager: Arc<RwLock<AIAgentManager>>,
+    /// Suggestions from a remote agent, fetched once typing pauses
+    ai_suggestions: Arc<AiSuggestions>,
     grammar_loader: Arc<GrammarLoader>,
-}
-
-#[derive(Debug, Clone)]
-struct DslSemanticToken {
-    line: u32,
-    start: u32,
-    length: u32,
-    token_type: u32,
+    /// Reloads the dictionary and the grammar when their files change
+    config_watcher: Arc<std::sync::Mutex<Option<ConfigWatcher>>>,
+    /// Root of the workspace the client opened, where the symbol index is saved
+    workspace_root: Arc<RwLock<Option<PathBuf>>>,
+    symbol_index: Arc<RwLock<SymbolIndex>>,
+    /// The names each open document assigns, kept up to date on every change
+    workspace_rules: Arc<RwLock<WorkspaceRules>>,
+    /// Functions the host application registered, offered alongside the built-ins
+    function_registry: FunctionRegistry,
+    /// Sample values loaded with `dsl.loadTestContext`, shown as inlay hints
+    test_context: Arc<RwLock<Option<BTreeMap<String, serde_json::Value>>>>,
+    /// Sample values attached to one document with `dsl/setContext`, used
+    /// there instead of `test_context` and previewed on hover
+    document_contexts: Arc<DashMap<Url, BTreeMap<String, serde_json::Value>>>,
+    /// Writes a redacted, replayable script of the session while switched on
+    /// with `dsl.startSessionRecording`
+    session_recorder: Arc<SessionRecorder>,
+    /// From `initializationOptions` and `workspace/didChangeConfiguration`
+    settings: Arc<RwLock<Settings>>,
+    /// Connected on startup to index the stored rules, and on rename to offer
+    /// renaming in them too, within what `authToken` grants; replaced when
+    /// either setting changes
+    database: Arc<RwLock<Arc<EditorDatabase>>>,
 }
 
 impl Backend {
@@ -101,16 +204,39 @@ impl Backend {
             client,
             document_map: Arc::new(DashMap::new()),
             semantic_tokens: Arc::new(DashMap::new()),
+            next_tokens_result: Arc::new(AtomicU64::new(0)),
             data_dictionary: Arc::new(RwLock::new(data_dictionary)),
+            data_dictionary_dir: Arc::new(RwLock::new(None)),
             ai_agent_manager: Arc::new(RwLock::new(ai_agent_manager)),
+            ai_suggestions: Arc::new(AiSuggestions::new()),
             grammar_loader,
+            config_watcher: Arc::new(std::sync::Mutex::new(None)),
+            workspace_root: Arc::new(RwLock::new(None)),
+            symbol_index: Arc::new(RwLock::new(SymbolIndex::default())),
+            workspace_rules: Arc::new(RwLock::new(WorkspaceRules::default())),
+            function_registry: FunctionRegistry::new(),
+            test_context: Arc::new(RwLock::new(None)),
+            document_contexts: Arc::new(DashMap::new()),
+            session_recorder: Arc::new(SessionRecorder::new()),
+            settings: Arc::new(RwLock::new(Settings::default())),
+            database: Arc::new(RwLock::new(Arc::new(EditorDatabase::default()))),
         }
     }
 
+    /// Shares the host's function registry, so its registered functions show
+    /// up in completion, documentation pages and workspace symbols
+    pub fn with_function_registry(mut self, registry: FunctionRegistry) -> Self {
+        self.function_registry = registry;
+        self
+    }
+
     pub async fn load_data_dictionary(&self, path: &str) -> std::result::Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let dictionary = DataDictionary::load_from_directory(path)?;
+        self.symbol_index.write().await.index_dictionary(&dictionary);
+        self.save_symbol_index().await;
         let mut dict_guard = self.data_dictionary.write().await;
         *dict_guard = dictionary;
+        *self.data_dictionary_dir.write().await = Some(PathBuf::from(path));
 
         self.client
             .log_message(MessageType::INFO, format!("Loaded data dictionary from {}", path))
@@ -119,6 +245,94 @@ impl Backend {
         Ok(())
     }
 
+    /// Takes on changed settings: a new dictionary path is loaded, a new
+    /// database URL is connected to on next use, and the open documents are
+    /// checked again under the rest
+    async fn apply_settings(&self, settings: Settings) {
+        let previous = std::mem::replace(&mut *self.settings.write().await, settings.clone());
+        if previous == settings {
+            return;
+        }
+        if settings.database_url != previous.database_url || settings.auth_token != previous.auth_token {
+            *self.database.write().await = Arc::new(EditorDatabase::new(settings.database_url.clone(), settings.auth_token.clone()));
+        }
+        if settings.dictionary_path != previous.dictionary_path {
+            self.load_configured_dictionary().await;
+        }
+        self.revalidate_open_documents().await;
+    }
+
+    /// Loads the dictionary `dictionaryPath` names, if it names one
+    async fn load_configured_dictionary(&self) {
+        let Some(path) = self.settings.read().await.dictionary_path.clone() else {
+            return;
+        };
+        match self.load_data_dictionary(&path.to_string_lossy()).await {
+            Ok(()) => self.watch_config_files().await,
+            Err(e) => {
+                self.client
+                    .show_message(MessageType::ERROR, format!("Failed to load data dictionary {}: {}", path.display(), e))
+                    .await;
+            }
+        }
+    }
+
+    /// Watches the loaded dictionary's directory and the grammar file, replacing
+    /// the watcher for a dictionary loaded before
+    async fn watch_config_files(&self) {
+        let dictionary_dir = self.data_dictionary_dir.read().await.clone();
+        let paths = WatchedPaths::new(dictionary_dir.as_deref(), self.grammar_loader.get_grammar_path());
+        let mut current = self.config_watcher.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if current.as_ref().is_some_and(|watcher| *watcher.paths() == paths) {
+            return;
+        }
+        match ConfigWatcher::start(paths) {
+            Ok((watcher, mut changes)) => {
+                *current = Some(watcher);
+                let backend = self.clone();
+                tokio::spawn(async move {
+                    while let Some(changed) = changes.recv().await {
+                        backend.reload_config(changed).await;
+                    }
+                });
+            }
+            Err(e) => log::warn!("Dictionary and grammar changes won't be picked up: {}", e),
+        }
+    }
+
+    /// Reloads the files that changed and revalidates the open documents. A
+    /// file that doesn't load, say because it is half written, leaves the
+    /// loaded version in place.
+    async fn reload_config(&self, changed: BTreeSet<ConfigFile>) {
+        if changed.contains(&ConfigFile::Dictionary) {
+            let dir = self.data_dictionary_dir.read().await.clone();
+            if let Some(dir) = dir {
+                if let Err(e) = self.load_data_dictionary(&dir.to_string_lossy()).await {
+                    self.client
+                        .log_message(MessageType::WARNING, format!("Data dictionary not reloaded: {}", e))
+                        .await;
+                }
+            }
+        }
+        if changed.contains(&ConfigFile::Grammar) {
+            match self.grammar_loader.load_grammar().await {
+                Ok(()) => {
+                    let functions = self.function_catalogue().await;
+                    self.symbol_index.write().await.index_functions(functions.iter().map(String::as_str));
+                    self.save_symbol_index().await;
+                    self.client.log_message(MessageType::INFO, "Grammar reloaded").await;
+                }
+                Err(e) => {
+                    self.client
+                        .log_message(MessageType::WARNING, format!("Grammar not reloaded: {}", e))
+                        .await;
+                }
+            }
+        }
+        self.revalidate_open_documents().await;
+        self.refresh_inlay_hints().await;
+    }
+
     pub async fn set_ai_agent(&self, agent_type: &str, config: Option<String>) -> std::result::Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let mut manager = self.ai_agent_manager.write().await;
 
@@ -149,106 +363,618 @@ impl Backend {
         Ok(())
     }
 
+    /// Add dictionary-sampled starter tests for the document's rules to the
+    /// `<name>.tests.json` file next to it. Cases already in the file are
+    /// left exactly as the user edited them.
+    async fn generate_rule_tests(&self, uri: &Url, text: &str) {
+        let Ok(path) = uri.to_file_path() else {
+            return;
+        };
+        let tests_path = path.with_extension("tests.json");
+
+        let generated = {
+            let dictionary = self.data_dictionary.read().await;
+            // Parse errors are already reported as diagnostics
+            match generate_document_tests(text, |name| dictionary.constraints(name)) {
+                Ok(cases) => cases,
+                Err(_) => return,
+            }
+        };
+
+        let mut cases: Vec<RuleTestCase> = match tokio::fs::read_to_string(&tests_path).await {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(cases) => cases,
+                Err(e) => {
+                    self.client
+                        .log_message(MessageType::WARNING, format!("Not updating {}: {}", tests_path.display(), e))
+                        .await;
+                    return;
+                }
+            },
+            Err(_) => Vec::new(),
+        };
+
+        let added = merge_test_cases(&mut cases, generated);
+        if added == 0 {
+            return;
+        }
+        let content = serde_json::to_string_pretty(&cases).unwrap();
+        match tokio::fs::write(&tests_path, content).await {
+            Ok(()) => {
+                self.client
+                    .log_message(MessageType::INFO, format!("Added {} starter test cases to {}", added, tests_path.display()))
+                    .await
+            }
+            Err(e) => {
+                self.client
+                    .log_message(MessageType::ERROR, format!("Failed to write {}: {}", tests_path.display(), e))
+                    .await
+            }
+        }
+    }
+
+    /// Brings the symbol index up to date with the dictionary, the function
+    /// catalogue and the workspace's rule files, re-parsing only the files
+    /// that changed since the index was saved
+    async fn refresh_symbol_index(&self) {
+        let functions = self.function_catalogue().await;
+
+        let root = self.workspace_root.read().await.clone();
+        let (parsed, symbols) = {
+            let mut index = self.symbol_index.write().await;
+            index.index_dictionary(&*self.data_dictionary.read().await);
+            index.index_functions(functions.iter().map(String::as_str));
+            let parsed = root.as_deref().map_or(0, |root| index.refresh_rule_files(root));
+            (parsed, index.len())
+        };
+        self.save_symbol_index().await;
+
+        self.client
+            .log_message(MessageType::INFO, format!("Symbol index: {} symbols, {} rule files re-parsed", symbols, parsed))
+            .await;
+    }
+
+    /// `.dsl-lsp/sessions/session-<seconds>.jsonl` in the workspace, or the temp directory without one
+    async fn default_session_path(&self) -> PathBuf {
+        let seconds = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        let dir = match self.workspace_root.read().await.clone() {
+            Some(root) => root.join(SESSIONS_DIR),
+            None => std::env::temp_dir(),
+        };
+        dir.join(format!("session-{}.jsonl", seconds))
+    }
+
+    /// Function names offered to rule authors: the DSL's own, the grammar's and the host's
+    async fn function_catalogue(&self) -> Vec<String> {
+        let mut functions: Vec<String> = DSL_FUNCTIONS.iter().map(|(name, _)| name.to_string()).collect();
+        functions.extend(self.grammar_loader.get_functions().await.into_iter().map(|(name, _)| name));
+        functions.extend(self.function_registry.functions().into_iter().map(|function| function.name));
+        functions
+    }
+
+    async fn save_symbol_index(&self) {
+        let Some(root) = self.workspace_root.read().await.clone() else {
+            return;
+        };
+        if let Err(e) = self.symbol_index.write().await.save(&root) {
+            self.client
+                .log_message(MessageType::WARNING, format!("Failed to save symbol index: {}", e))
+                .await;
+        }
+    }
+
+    /// Renders a `dsl://docs/FUNCTION/<NAME>` page from the function catalogue
+    async fn function_documentation(&self, uri: &Url) -> Option<String> {
+        let name = function_from_doc_uri(uri)?;
+        let registered = self.function_registry.functions().into_iter().find(|function| function.name == name);
+        let description = match DSL_FUNCTIONS.iter().find(|(func, _)| *func == name) {
+            Some((_, desc)) => desc.to_string(),
+            None if registered.is_some() => REGISTERED_FUNCTION_DESCRIPTION.to_string(),
+            None => self
+                .grammar_loader
+                .get_functions()
+                .await
+                .into_iter()
+                .find(|(func, _)| func.eq_ignore_ascii_case(&name))
+                .map(|(_, desc)| desc)?,
+        };
+        let signature = match registered {
+            Some(function) => Some(function.signature),
+            None => self.grammar_loader.get_function_signature(&name).await,
+        };
+        let doc = FunctionDoc::from_catalogue(&name, &description, signature.as_deref());
+        let used_by = self.symbol_index.read().await.rules_calling(&name);
+        Some(doc.render(&used_by))
+    }
+
+    /// Renders a `dsl://rules/<rule_id>` document: the stored rule's definition
+    async fn stored_rule_document(&self, uri: &Url) -> Option<String> {
+        let rule_id = stored_rule_from_uri(uri)?;
+        let database = self.database.read().await.clone();
+        let (pool, session) = database.session().await.ok()?;
+        if !session.scope.allows_rule(&rule_id) {
+            return Some(format!("// {} is outside what this editor's token grants\n", rule_id));
+        }
+        let rule = RuleOperations::get_rule_by_id(pool, &rule_id).await.ok()?;
+        Some(format!(
+            "// {} ({}, {})\n{}\n",
+            rule["rule_name"].as_str().unwrap_or(&rule_id),
+            rule_id,
+            rule["status"].as_str().unwrap_or("draft"),
+            rule["rule_definition"].as_str().unwrap_or("")
+        ))
+    }
+
+    /// The name under the cursor and its range, if it's one a rename can change
+    fn symbol_at(&self, uri: &Url, position: Position) -> Option<(String, Range)> {
+        let rope = self.document_map.get(uri)?;
+        let line = rope.get_line(position.line as usize)?.to_string();
+        let (start, end) = symbol_bounds(&line, position.character as usize)?;
+        let range = Range {
+            start: Position { line: position.line, character: start as u32 },
+            end: Position { line: position.line, character: end as u32 },
+        };
+        Some((line[start..end].to_string(), range))
+    }
+
+    /// Quick fixes for one of the diagnostics `validate_document` published for `text`
+    async fn quick_fixes(&self, uri: &Url, text: &str, diagnostic: &Diagnostic) -> Vec<CodeActionOrCommand> {
+        let Some(NumberOrString::String(code)) = &diagnostic.code else {
+            return Vec::new();
+        };
+        if matches!(ErrorCode::parse(code), Some(ErrorCode::Syntax | ErrorCode::UnexpectedText)) {
+            return close_parens_fix(text, diagnostic.range.start.line as usize)
+                .map(|fix| text_fix_action(uri, text, diagnostic, format!("Insert missing '{}'", fix.new_text), fix, true))
+                .into_iter()
+                .collect();
+        }
+
+        let Some(name) = diagnostic.data.clone().and_then(|data| serde_json::from_value::<UnknownName>(data).ok()) else {
+            return Vec::new();
+        };
+        // The document changed since the diagnostic was published
+        if text.get(name.start..name.end) != Some(name.name.as_str()) {
+            return Vec::new();
+        }
+        match &name.kind {
+            UnknownKind::Function { suggestion } => name
+                .rename_fix()
+                .map(|fix| {
+                    let title = format!("Change to {}", suggestion.as_deref().unwrap_or_default());
+                    text_fix_action(uri, text, diagnostic, title, fix, true)
+                })
+                .into_iter()
+                .collect(),
+            UnknownKind::Attribute { expected, entity } => {
+                let mut actions = Vec::new();
+                if !name.name.contains('.') {
+                    actions.push(text_fix_action(
+                        uri,
+                        text,
+                        diagnostic,
+                        format!("Quote '{}' as a string", name.name),
+                        name.quote_fix(),
+                        *expected == RuleType::String,
+                    ));
+                }
+                if let Some(entity) = entity {
+                    actions.extend(self.add_attribute_action(&name, entity, *expected, diagnostic).await);
+                }
+                actions
+            }
+        }
+    }
+
+    /// Adds an unknown attribute to the entities.json of the loaded dictionary,
+    /// and through `dsl.addDictionaryAttribute` to the dictionary in memory, so
+    /// the rule stops being flagged before the file is saved
+    async fn add_attribute_action(
+        &self,
+        name: &UnknownName,
+        entity: &str,
+        expected: RuleType,
+        diagnostic: &Diagnostic,
+    ) -> Option<CodeActionOrCommand> {
+        let path = self.data_dictionary_dir.read().await.as_ref()?.join("entities.json");
+        let content = tokio::fs::read_to_string(&path).await.ok()?;
+        let attribute_name = name.name.rsplit_once('.').map_or(name.name.as_str(), |(_, attribute)| attribute);
+        let attribute = Attribute::new(attribute_name, expected);
+        let updated = add_attribute_to_entities(&content, entity, &attribute).ok()?;
+
+        let edit = TextEdit {
+            range: Range {
+                start: Position::default(),
+                end: position_at(&content, content.len()),
+            },
+            new_text: updated,
+        };
+        let title = format!("Add '{}' to {} in the data dictionary", attribute_name, entity);
+        Some(CodeActionOrCommand::CodeAction(CodeAction {
+            title: title.clone(),
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: Some(vec![diagnostic.clone()]),
+            edit: Some(WorkspaceEdit {
+                changes: Some(HashMap::from([(Url::from_file_path(&path).ok()?, vec![edit])])),
+                ..Default::default()
+            }),
+            command: Some(Command {
+                title,
+                command: "dsl.addDictionaryAttribute".to_string(),
+                arguments: Some(vec![serde_json::Value::String(entity.to_string()), serde_json::to_value(&attribute).ok()?]),
+            }),
+            ..Default::default()
+        }))
+    }
+
+    /// Moves each long inline list in the rules overlapping `range` into a
+    /// lookup table. The action rewrites the rule to LOOKUP the table, adds the
+    /// table to the loaded dictionary's lookups.json when there is one, and
+    /// through `dsl.registerLookupTable` adds it to the dictionary in memory.
+    async fn extract_lookup_actions(&self, uri: &Url, text: &str, range: Range) -> Vec<CodeActionOrCommand> {
+        let (start, end) = (offset_at(text, range.start), offset_at(text, range.end));
+        let lookups_path = self.data_dictionary_dir.read().await.as_ref().map(|dir| dir.join("lookups.json"));
+        let lookups_json = match &lookups_path {
+            Some(path) => tokio::fs::read_to_string(path).await.ok(),
+            None => None,
+        };
+        let dictionary = self.data_dictionary.read().await;
+        let (rules, _) = parse_rules_recovering(text);
+
+        let mut actions = Vec::new();
+        for rule in rules.iter().filter(|rule| rule.start <= end && start <= rule.end) {
+            let source = &text[rule.start..rule.end];
+            let lists = inline_lists_in(&rule.expression, MIN_EXTRACTED_VALUES, |name| dictionary.lookups.contains_key(name));
+            for list in lists {
+                let Ok(extraction) = extract_lookup_table(source, list.index, &list.suggested_table, MIN_EXTRACTED_VALUES) else {
+                    continue;
+                };
+                let description = format!("Values of {} moved out of a rule", list.subject);
+                let table = LookupTable::of_values(&extraction.table, &description, list.values.iter().map(String::as_str));
+
+                let mut changes = HashMap::from([(
+                    uri.clone(),
+                    vec![TextEdit {
+                        range: Range { start: position_at(text, rule.start), end: position_at(text, rule.end) },
+                        new_text: extraction.definition,
+                    }],
+                )]);
+                if let (Some(path), Some(content)) = (&lookups_path, &lookups_json) {
+                    let updated = add_lookup_to_lookups(content, &table).ok();
+                    if let (Some(updated), Ok(lookups_uri)) = (updated, Url::from_file_path(path)) {
+                        let edit = TextEdit {
+                            range: Range { start: Position::default(), end: position_at(content, content.len()) },
+                            new_text: updated,
+                        };
+                        changes.insert(lookups_uri, vec![edit]);
+                    }
+                }
+
+                let title = format!(
+                    "Move the {} values {} is tested against into lookup table '{}'",
+                    table.entries.len(),
+                    list.subject,
+                    table.name
+                );
+                let Ok(argument) = serde_json::to_value(&table) else {
+                    continue;
+                };
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: title.clone(),
+                    kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+                    edit: Some(WorkspaceEdit { changes: Some(changes), ..Default::default() }),
+                    command: Some(Command {
+                        title,
+                        command: "dsl.registerLookupTable".to_string(),
+                        arguments: Some(vec![argument]),
+                    }),
+                    ..Default::default()
+                }));
+            }
+        }
+        actions
+    }
+
+    /// `dsl.convertSyntax`: the document as S-expressions for `"sexpr"`, or, for
+    /// `"infix"`, S-expression `text` written back into the document as
+    /// canonical infix, so either view edits the one stored form
+    async fn convert_syntax(&self, uri: &Url, to: &str, text: Option<&str>) -> std::result::Result<String, String> {
+        let (document, line_count) = self
+            .document_map
+            .get(uri)
+            .map(|rope| (rope.to_string(), rope.len_lines()))
+            .ok_or_else(|| format!("{} is not open", uri))?;
+        match to {
+            "sexpr" => to_sexpr_document(text.unwrap_or(&document)),
+            "infix" => {
+                let infix = from_sexpr_document(text.ok_or("No S-expression text to convert")?)?;
+                if infix != document {
+                    let edit = TextEdit {
+                        range: Range {
+                            start: Position { line: 0, character: 0 },
+                            end: Position { line: line_count as u32, character: 0 },
+                        },
+                        new_text: infix.clone(),
+                    };
+                    let changes = HashMap::from([(uri.clone(), vec![edit])]);
+                    self.client
+                        .apply_edit(WorkspaceEdit { changes: Some(changes), ..Default::default() })
+                        .await
+                        .map_err(|e| format!("Failed to update {}: {}", uri, e))?;
+                }
+                Ok(infix)
+            }
+            other => Err(format!("Unknown syntax '{}'; expected \"sexpr\" or \"infix\"", other)),
+        }
+    }
+
+    /// Sets the sample values inlay hints show in every document without one of
+    /// its own; see `read_test_context` for `source` and `case`
+    async fn load_test_context(&self, source: &serde_json::Value, case: Option<&str>) -> std::result::Result<(), String> {
+        let values = read_test_context(source, case).await?;
+        let message = match &values {
+            Some(values) => format!("Test context loaded with {} values", values.len()),
+            None => "Test context cleared".to_string(),
+        };
+        *self.test_context.write().await = values;
+        self.client.log_message(MessageType::INFO, message).await;
+        self.refresh_inlay_hints().await;
+        Ok(())
+    }
+
+    /// `dsl/setContext`: attaches sample values to one document, or with a
+    /// null `context` detaches them, so hovering its rules previews their values
+    pub async fn set_context(&self, params: SetContextParams) -> Result<()> {
+        let values = read_test_context(&params.context, params.case.as_deref())
+            .await
+            .map_err(tower_lsp::jsonrpc::Error::invalid_params)?;
+        let uri = params.text_document.uri;
+        match values {
+            Some(values) => {
+                self.document_contexts.insert(uri, values);
+            }
+            None => {
+                self.document_contexts.remove(&uri);
+            }
+        }
+        self.refresh_inlay_hints().await;
+        Ok(())
+    }
+
+    /// The document's own test context, else the one loaded for every document
+    async fn test_context_for(&self, uri: &Url) -> Option<BTreeMap<String, serde_json::Value>> {
+        match self.document_contexts.get(uri) {
+            Some(values) => Some(values.clone()),
+            None => self.test_context.read().await.clone(),
+        }
+    }
+
+    // Editors only ask for hints again when told to
+    async fn refresh_inlay_hints(&self) {
+        if let Err(e) = self.client.inlay_hint_refresh().await {
+            self.client.log_message(MessageType::WARNING, format!("Inlay hints not refreshed: {}", e)).await;
+        }
+    }
+
+    async fn revalidate_open_documents(&self) {
+        let documents: Vec<(Url, String)> = self
+            .document_map
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().to_string()))
+            .collect();
+        for (uri, text) in documents {
+            self.validate_document(uri, text).await;
+        }
+    }
+
     async fn on_change(&self, params: TextDocumentItem) {
         let rope = Rope::from_str(&params.text);
         self.document_map.insert(params.uri.clone(), rope);
+        let names_changed = self.workspace_rules.write().await.update(&params.uri, &params.text);
 
         // Perform diagnostics with nom parser
-        self.validate_document(params.uri, params.text).await;
+        self.validate_document(params.uri.clone(), params.text).await;
+
+        // The other documents may now know a name or have a duplicate of one
+        if names_changed {
+            self.revalidate_other_documents(&params.uri).await;
+        }
+    }
+
+    async fn revalidate_other_documents(&self, uri: &Url) {
+        let documents: Vec<(Url, String)> = self
+            .document_map
+            .iter()
+            .filter(|entry| entry.key() != uri)
+            .map(|entry| (entry.key().clone(), entry.value().to_string()))
+            .collect();
+        for (uri, text) in documents {
+            self.validate_document(uri, text).await;
+        }
     }
 
     async fn validate_document(&self, uri: Url, text: String) {
+        let diagnostics = self.document_diagnostics(&uri, &text).await;
+        self.client.publish_diagnostics(uri, diagnostics, None).await;
+    }
+
+    /// Everything wrong with the document at `uri`, for both publishing and the
+    /// pull request, at the severities the client configured
+    async fn document_diagnostics(&self, uri: &Url, text: &str) -> Vec<Diagnostic> {
+        let settings = self.settings.read().await.clone();
+        if let Some(too_large) = settings.document_too_large(text.len()) {
+            return vec![too_large];
+        }
+        settings.map_severities(self.check_document(uri, text).await)
+    }
+
+    async fn check_document(&self, uri: &Url, text: &str) -> Vec<Diagnostic> {
         let mut diagnostics = Vec::new();
+        let type_env = self.data_dictionary.read().await.type_env();
+        let location = |start: usize, end: usize| Location {
+            uri: uri.clone(),
+            range: Range { start: position_at(text, start), end: position_at(text, end) },
+        };
+
+        // Recovering parse: a broken rule is reported and skipped, so the
+        // rules after it still get type checked
+        let (rules, parse_errors) = parse_rules_recovering(text);
+        let assigned_by = |name: &str| {
+            rules.iter().find(|rule| matches!(&rule.expression, Expression::Assignment { target, .. } if target == name))
+        };
+        for rule in &rules {
+            let rule_text = &text[rule.start..rule.end];
+            for type_error in typecheck_with_env(&rule.expression, &type_env).diagnostics {
+                // Point at the offending sub-expression when it appears verbatim in the rule
+                let (start, end) = match rule_text.find(&type_error.expression) {
+                    Some(start) => (rule.start + start, rule.start + start + type_error.expression.len()),
+                    None => (rule.start, rule.end),
+                };
+                // A variable another rule assigns gets its type from that rule
+                let related = assigned_by(&type_error.expression)
+                    .filter(|assignment| assignment.start != rule.start)
+                    .map(|assignment| vec![DiagnosticRelatedInformation {
+                        location: location(assignment.start, assignment.end),
+                        message: format!("'{}' is assigned here", type_error.expression),
+                    }]);
+                diagnostics.push(Diagnostic {
+                    related_information: related,
+                    ..coded_diagnostic(ErrorCode::TypeMismatch, location(start, end).range, DiagnosticSeverity::ERROR, type_error.message)
+                });
+            }
+        }
 
-        // Parse line by line for better error reporting
-        for (line_num, line) in text.lines().enumerate() {
-            if line.trim().is_empty() || line.trim().starts_with('#') {
+        // Calls and attributes nothing defines, carrying what their quick fixes need as data
+        let mut functions: Vec<String> = BUILTIN_FUNCTIONS.iter().map(|name| name.to_string()).collect();
+        functions.extend(self.function_catalogue().await);
+        let (defined_elsewhere, duplicates) = {
+            let workspace_rules = self.workspace_rules.read().await;
+            let defined: BTreeSet<String> = workspace_rules.defined_elsewhere(uri).into_keys().map(str::to_string).collect();
+            (defined, workspace_rules.duplicates(uri))
+        };
+        let unknown = unknown_names(text, &rules, &*self.data_dictionary.read().await, &functions)
+            .into_iter()
+            .filter(|name| !(matches!(name.kind, UnknownKind::Attribute { .. }) && defined_elsewhere.contains(&name.name)));
+        for name in unknown {
+            let severity = match name.kind {
+                UnknownKind::Function { .. } => DiagnosticSeverity::WARNING,
+                UnknownKind::Attribute { .. } => DiagnosticSeverity::INFORMATION,
+            };
+            diagnostics.push(Diagnostic {
+                data: serde_json::to_value(&name).ok(),
+                ..coded_diagnostic(name.code(), location(name.start, name.end).range, severity, name.message())
+            });
+        }
+
+        // Names another open document also assigns
+        for duplicate in duplicates {
+            let related = duplicate.others.into_iter().map(|location| DiagnosticRelatedInformation {
+                location,
+                message: format!("'{}' is also assigned here", duplicate.name),
+            });
+            diagnostics.push(Diagnostic {
+                related_information: Some(related.collect()),
+                ..coded_diagnostic(
+                    ErrorCode::DuplicateRule,
+                    duplicate.range,
+                    DiagnosticSeverity::WARNING,
+                    format!("'{}' is also assigned in another file", duplicate.name),
+                )
+            });
+        }
+
+        // Rules assigning the same attribute for the same inputs; the warning goes
+        // on the later one and points at the earlier
+        let names: Vec<String> = rules
+            .iter()
+            .map(|rule| format!("the rule on line {}", position_at(text, rule.start).line + 1))
+            .collect();
+        let assignments = rules.iter().zip(&names).filter_map(|(rule, name)| match &rule.expression {
+            Expression::Assignment { target, .. } => Some((name.as_str(), target.as_str(), &rule.expression)),
+            _ => None,
+        });
+        let rule_named = |name: &str| names.iter().position(|candidate| candidate == name).map(|index| &rules[index]);
+        for conflict in find_conflicts(assignments) {
+            let Some(rule) = rule_named(&conflict.second) else {
                 continue;
-            }
+            };
+            let related = rule_named(&conflict.first).map(|first| vec![DiagnosticRelatedInformation {
+                location: location(first.start, first.end),
+                message: format!("'{}' is also assigned here", conflict.target),
+            }]);
+            diagnostics.push(Diagnostic {
+                related_information: related,
+                ..coded_diagnostic(ErrorCode::RuleConflict, location(rule.start, rule.end).range, DiagnosticSeverity::WARNING, conflict.message())
+            });
+        }
 
-            match parse_rule(line) {
-                Ok((remaining, _ast)) => {
-                    if !remaining.trim().is_empty() && !remaining.trim().starts_with('#') {
-                        diagnostics.push(Diagnostic {
-                            range: Range {
-                                start: Position {
-                                    line: line_num as u32,
-                                    character: (line.len() - remaining.len()) as u32,
-                                },
-                                end: Position {
-                                    line: line_num as u32,
-                                    character: line.len() as u32,
-                                },
-                            },
-                            severity: Some(DiagnosticSeverity::WARNING),
-                            code: Some(NumberOrString::String("unparsed".to_string())),
-                            source: Some("dsl-lsp".to_string()),
-                            message: format!("Unparsed content: '{}'", remaining.trim()),
-                            ..Default::default()
-                        });
-                    }
-                }
-                Err(e) => {
-                    diagnostics.push(Diagnostic {
-                        range: Range {
-                            start: Position {
-                                line: line_num as u32,
-                                character: 0,
-                            },
-                            end: Position {
-                                line: line_num as u32,
-                                character: line.len() as u32,
-                            },
-                        },
-                        severity: Some(DiagnosticSeverity::ERROR),
-                        code: Some(NumberOrString::String("parse_error".to_string())),
-                        source: Some("dsl-lsp".to_string()),
-                        message: format!("Parse error: {:?}", e),
-                        ..Default::default()
-                    });
-                }
+        // Rules too slow for the batch scoring path; flagged now rather than after activation
+        let budget = LatencyBudget::default();
+        for rule in &rules {
+            if let Some(message) = estimate_rule_cost(&rule.expression).budget_warning(&budget) {
+                diagnostics.push(coded_diagnostic(
+                    ErrorCode::LatencyBudget,
+                    location(rule.start, rule.end).range,
+                    DiagnosticSeverity::WARNING,
+                    message,
+                ));
             }
         }
 
+        for parse_error in parse_errors {
+            let line_end = text[parse_error.offset..].find('\n').map_or(text.len(), |i| parse_error.offset + i);
+            let range = Range {
+                start: Position {
+                    line: parse_error.line as u32,
+                    character: parse_error.column as u32,
+                },
+                end: position_at(text, line_end),
+            };
+            diagnostics.push(coded_diagnostic(
+                parse_error.code,
+                range,
+                DiagnosticSeverity::ERROR,
+                format!("Parse error: {}", parse_error.message),
+            ));
+        }
+
         // AI-based validation if available
-        let ai_manager = self.ai_agent_manager.read().await;
-        if let Some(_agent) = ai_manager.get_active_agent() {
+        let agent = self.ai_agent(Capability::Ai).await;
+        if let Some(agent) = agent {
             let validation_request = ValidationRequest {
-                rule: text.clone(),
+                rule: text.to_string(),
                 context: std::collections::HashMap::new(),
             };
 
-            if let Ok(validation_response) = ai_manager.validate_rule(validation_request).await {
+            if let Ok(validation_response) = agent.validate_rule(validation_request).await {
                 for issue in validation_response.issues {
-                    diagnostics.push(Diagnostic {
-                        range: Range {
-                            start: Position {
-                                line: issue.line.unwrap_or(0) as u32,
-                                character: issue.column.unwrap_or(0) as u32,
-                            },
-                            end: Position {
-                                line: issue.line.unwrap_or(0) as u32,
-                                character: issue.column.unwrap_or(100) as u32,
-                            },
+                    let range = Range {
+                        start: Position {
+                            line: issue.line.unwrap_or(0) as u32,
+                            character: issue.column.unwrap_or(0) as u32,
+                        },
+                        end: Position {
+                            line: issue.line.unwrap_or(0) as u32,
+                            character: issue.column.unwrap_or(100) as u32,
                         },
-                        severity: Some(match issue.severity {
-                            ai_agent::IssueSeverity::Error => DiagnosticSeverity::ERROR,
-                            ai_agent::IssueSeverity::Warning => DiagnosticSeverity::WARNING,
-                            ai_agent::IssueSeverity::Info => DiagnosticSeverity::INFORMATION,
-                        }),
-                        code: Some(NumberOrString::String("ai_validation".to_string())),
+                    };
+                    let severity = match issue.severity {
+                        ai_agent::IssueSeverity::Error => DiagnosticSeverity::ERROR,
+                        ai_agent::IssueSeverity::Warning => DiagnosticSeverity::WARNING,
+                        ai_agent::IssueSeverity::Info => DiagnosticSeverity::INFORMATION,
+                    };
+                    diagnostics.push(Diagnostic {
                         source: Some("ai-agent".to_string()),
-                        message: issue.message,
-                        ..Default::default()
+                        ..coded_diagnostic(ErrorCode::AiValidation, range, severity, issue.message)
                     });
                 }
             }
         }
 
-        self.client.publish_diagnostics(uri, diagnostics, None).await;
+        diagnostics
     }
 
-    async fn get_completions(&self, line: &str, character: usize) -> Vec<CompletionItem> {
+    /// `variables` are the names the open documents' rules assign, offered alongside the dictionary
+    async fn get_completions(&self, line: &str, character: usize, variables: &[String]) -> Vec<CompletionItem> {
         let mut completions = Vec::new();
 
         // Get the current word being typed
@@ -269,6 +995,32 @@ impl Backend {
             }
         }
 
+        // Add variables assigned elsewhere in the document or in another open one
+        for name in variables {
+            if name.to_lowercase().starts_with(&current_word.to_lowercase()) {
+                completions.push(CompletionItem {
+                    label: name.clone(),
+                    kind: Some(CompletionItemKind::VARIABLE),
+                    detail: Some("Rule variable".to_string()),
+                    ..Default::default()
+                });
+            }
+        }
+
+        // Add control-flow snippets
+        for (label, desc, body) in DSL_SNIPPETS.iter() {
+            if label.to_lowercase().starts_with(&current_word.to_lowercase()) {
+                completions.push(CompletionItem {
+                    label: format!("{} ...", label),
+                    kind: Some(CompletionItemKind::SNIPPET),
+                    detail: Some(desc.to_string()),
+                    insert_text: Some(body.to_string()),
+                    insert_text_format: Some(InsertTextFormat::SNIPPET),
+                    ..Default::default()
+                });
+            }
+        }
+
         // Add function completions
         for (func, desc) in DSL_FUNCTIONS.iter() {
             if func.to_lowercase().starts_with(&current_word.to_lowercase()) {
@@ -276,6 +1028,9 @@ impl Backend {
                     label: func.to_string(),
                     kind: Some(CompletionItemKind::FUNCTION),
                     detail: Some(desc.to_string()),
+                    documentation: function_doc_link(func).map(|link| {
+                        Documentation::MarkupContent(MarkupContent { kind: MarkupKind::Markdown, value: link })
+                    }),
                     insert_text: Some(format!("{}($1)", func)),
                     insert_text_format: Some(InsertTextFormat::SNIPPET),
                     ..Default::default()
@@ -283,23 +1038,47 @@ impl Backend {
             }
         }
 
-        // Add operator completions if appropriate
-        if current_word.is_empty() || "+-*/%&=<>!~".contains(current_word.chars().next().unwrap_or(' ')) {
-            for (op, desc) in DSL_OPERATORS.iter() {
-                if op.starts_with(current_word) {
-                    completions.push(CompletionItem {
-                        label: op.to_string(),
-                        kind: Some(CompletionItemKind::OPERATOR),
-                        detail: Some(desc.to_string()),
-                        insert_text: Some(format!("{} ", op)),
-                        ..Default::default()
-                    });
-                }
+        // Add functions registered by the host application
+        for function in self.function_registry.functions() {
+            if function.name.to_lowercase().starts_with(&current_word.to_lowercase())
+                && !DSL_FUNCTIONS.iter().any(|(func, _)| *func == function.name)
+            {
+                completions.push(CompletionItem {
+                    label: function.name.clone(),
+                    kind: Some(CompletionItemKind::FUNCTION),
+                    detail: Some(format!("{} - {}", function.signature, REGISTERED_FUNCTION_DESCRIPTION)),
+                    documentation: function_doc_link(&function.name).map(|link| {
+                        Documentation::MarkupContent(MarkupContent { kind: MarkupKind::Markdown, value: link })
+                    }),
+                    insert_text: Some(format!("{}($1)", function.name)),
+                    insert_text_format: Some(InsertTextFormat::SNIPPET),
+                    ..Default::default()
+                });
+            }
+        }
+
+        // Add operator completions if appropriate; word operators also complete from a prefix
+        let symbolic = current_word.is_empty() || "+-*/%&=<>!~".contains(current_word.chars().next().unwrap_or(' '));
+        for (op, desc) in DSL_OPERATORS.iter() {
+            let is_word_operator = op.starts_with(|c: char| c.is_ascii_alphabetic());
+            let matches = if is_word_operator {
+                op.starts_with(&current_word.to_uppercase())
+            } else {
+                symbolic && op.starts_with(current_word)
+            };
+            if matches {
+                completions.push(CompletionItem {
+                    label: op.to_string(),
+                    kind: Some(CompletionItemKind::OPERATOR),
+                    detail: Some(desc.to_string()),
+                    insert_text: Some(format!("{} ", op)),
+                    ..Default::default()
+                });
             }
         }
 
         // Add data dictionary completions
-        let dict = self.data_dictionary.blocking_read();
+        let dict = self.data_dictionary.read().await;
 
         // Add entity attributes
         for (full_name, attr) in dict.get_all_attributes() {
@@ -398,59 +1177,80 @@ impl Backend {
             }
         }
 
-        // Get AI-powered completions
-        let ai_manager = self.ai_agent_manager.blocking_read();
-        if let Some(_agent) = ai_manager.get_active_agent() {
-            let context = CompletionContext {
-                current_line: line.to_string(),
-                preceding_lines: vec![],
-                following_lines: vec![],
-                cursor_position: character,
-                file_path: None,
-                available_attributes: dict.get_all_attributes().iter()
-                    .map(|(name, _)| name.clone())
-                    .collect(),
-                available_functions: {
-                    let grammar_loader = self.grammar_loader.clone();
-                    tokio::task::block_in_place(|| {
-                        tokio::runtime::Handle::current().block_on(async move {
-                            grammar_loader.get_functions().await.iter()
-                                .map(|(name, _)| name.clone())
-                                .collect()
-                        })
-                    })
-                },
-                data_dictionary_context: None,
-            };
+        completions
+    }
 
-            let request = CompletionRequest {
-                prompt: format!("Complete DSL at position {} in line: {}", character, line),
-                context,
-                max_tokens: Some(50),
-                temperature: Some(0.3),
-            };
+    /// AI suggestions for the cursor, and whether a fetch for it is still pending.
+    /// A local agent answers inline; a remote one is asked in the background once
+    /// typing pauses, and its suggestions are offered when completion is asked again.
+    async fn get_ai_completions(&self, line: &str, character: usize) -> (Vec<CompletionItem>, bool) {
+        let Some(agent) = self.ai_agent(Capability::LspAiCompletions).await else {
+            return (Vec::new(), false);
+        };
+        if agent.is_local() {
+            let request = self.ai_completion_request(line, character).await;
+            let items = agent.get_completions(request).await
+                .map(|response| ai_completion_items(response.suggestions))
+                .unwrap_or_default();
+            return (items, false);
+        }
 
-            // Use blocking task for async operation
-            if let Ok(handle) = tokio::runtime::Handle::try_current() {
-                if let Ok(ai_response) = handle.block_on(ai_manager.get_completions(request)) {
-                    for suggestion in ai_response.suggestions {
-                        completions.push(CompletionItem {
-                            label: format!("🤖 {}", suggestion.text),
-                            kind: Some(CompletionItemKind::TEXT),
-                            detail: suggestion.description,
-                            insert_text: Some(suggestion.text),
-                            sort_text: Some(format!("zzz{}", suggestion.confidence)), // Sort AI suggestions last
-                            ..Default::default()
-                        });
-                    }
+        let key = CompletionKey::new(line, character);
+        if let Some(items) = self.ai_suggestions.ready(&key) {
+            return (items, false);
+        }
+        if let Some(generation) = self.ai_suggestions.request(&key) {
+            let request = self.ai_completion_request(line, character).await;
+            let suggestions = self.ai_suggestions.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(AI_DEBOUNCE).await;
+                if !suggestions.is_current(generation) {
+                    return;
                 }
-            }
+                let items = agent.get_completions(request).await
+                    .map(|response| ai_completion_items(response.suggestions))
+                    .unwrap_or_default();
+                suggestions.complete(generation, key, items);
+            });
         }
+        (Vec::new(), true)
+    }
 
-        completions
+    /// The active agent, unless this deployment has `capability` disabled or
+    /// the client switched AI off
+    async fn ai_agent(&self, capability: Capability) -> Option<Arc<dyn AIAgent>> {
+        if !capabilities::enabled(capability) || !self.settings.read().await.ai.enabled {
+            return None;
+        }
+        self.ai_agent_manager.read().await.active_agent()
+    }
+
+    async fn ai_completion_request(&self, line: &str, character: usize) -> CompletionRequest {
+        let available_attributes = self.data_dictionary.read().await.get_all_attributes().iter()
+            .map(|(name, _)| name.clone())
+            .collect();
+        let context = CompletionContext {
+            current_line: line.to_string(),
+            preceding_lines: vec![],
+            following_lines: vec![],
+            cursor_position: character,
+            file_path: None,
+            available_attributes,
+            available_functions: self.grammar_loader.get_functions().await.into_iter()
+                .map(|(name, _)| name)
+                .collect(),
+            data_dictionary_context: None,
+        };
+
+        CompletionRequest {
+            prompt: format!("Complete DSL at position {} in line: {}", character, line),
+            context,
+            max_tokens: Some(50),
+            temperature: Some(0.3),
+        }
     }
 
-    fn get_hover_info(&self, line: &str, character: usize) -> Option<Hover> {
+    async fn get_hover_info(&self, line: &str, character: usize) -> Option<Hover> {
         // Find the word at the cursor position
         let start = line[..character.min(line.len())]
             .rfind(|c: char| !c.is_alphanumeric() && c != '_' && c != '.')
@@ -465,7 +1265,7 @@ impl Backend {
         let word = &line[start..end];
 
         // Check if it's an attribute from the data dictionary
-        let dict = self.data_dictionary.blocking_read();
+        let dict = self.data_dictionary.read().await;
         if let Some(attr_info) = dict.get_attribute_info(word) {
             let mut hover_content = format!("### 📊 Attribute: `{}`\n\n", word);
             hover_content.push_str(&format!("**Description:** {}\n\n", attr_info.description));
@@ -547,10 +1347,14 @@ impl Backend {
         // Check if it's a function
         for (func, desc) in DSL_FUNCTIONS.iter() {
             if word == *func {
+                let mut value = format!("**Function: {}**\n\n{}", func, desc);
+                if let Some(link) = function_doc_link(func) {
+                    value.push_str(&format!("\n\n{}", link));
+                }
                 return Some(Hover {
                     contents: HoverContents::Markup(MarkupContent {
                         kind: MarkupKind::Markdown,
-                        value: format!("**Function: {}**\n\n{}", func, desc),
+                        value,
                     }),
                     range: None,
                 });
@@ -584,101 +1388,295 @@ impl Backend {
         None
     }
 
-    async fn get_semantic_tokens(&self, uri: &Url) -> Option<Vec<DslSemanticToken>> {
-        if let Some(rope) = self.document_map.get(uri) {
-            let mut tokens = Vec::new();
-
-            for (line_num, line) in rope.lines().enumerate() {
-                let line_str = line.to_string();
-
-                // Tokenize keywords
-                for keyword in DSL_KEYWORDS.iter() {
-                    let mut start = 0;
-                    while let Some(pos) = line_str[start..].find(keyword) {
-                        let abs_pos = start + pos;
-                        // Check it's a whole word
-                        let before_ok = abs_pos == 0 || !line_str.chars().nth(abs_pos - 1).unwrap().is_alphanumeric();
-                        let after_ok = abs_pos + keyword.len() >= line_str.len() ||
-                            !line_str.chars().nth(abs_pos + keyword.len()).unwrap().is_alphanumeric();
-
-                        if before_ok && after_ok {
-                            tokens.push(DslSemanticToken {
-                                line: line_num as u32,
-                                start: abs_pos as u32,
-                                length: keyword.len() as u32,
-                                token_type: 0, // KEYWORD
-                            });
-                        }
-                        start = abs_pos + keyword.len();
-                    }
-                }
+    // Tokenize the document under a new result id, remembered for the next delta request
+    fn semantic_token_data(&self, uri: &Url) -> Option<(String, Vec<u32>)> {
+        let text = self.document_map.get(uri)?.to_string();
+        let data = encode(&tokenize(&text));
+        let result_id = self.next_tokens_result.fetch_add(1, Ordering::Relaxed).to_string();
+        self.semantic_tokens.insert(uri.clone(), (result_id.clone(), data.clone()));
+        Some((result_id, data))
+    }
+}
 
-                // Tokenize functions
-                for (func, _) in DSL_FUNCTIONS.iter() {
-                    if let Some(pos) = line_str.find(func) {
-                        tokens.push(DslSemanticToken {
-                            line: line_num as u32,
-                            start: pos as u32,
-                            length: func.len() as u32,
-                            token_type: 5, // FUNCTION
-                        });
-                    }
-                }
+/// Encoded semantic token data, five integers a token, as lsp-types holds it
+fn lsp_tokens(data: &[u32]) -> Vec<SemanticToken> {
+    data.chunks_exact(5)
+        .map(|token| SemanticToken {
+            delta_line: token[0],
+            delta_start: token[1],
+            length: token[2],
+            token_type: token[3],
+            token_modifiers_bitset: token[4],
+        })
+        .collect()
+}
 
-                // Tokenize strings
-                for mat in STRING_PATTERN.find_iter(&line_str) {
-                    tokens.push(DslSemanticToken {
-                        line: line_num as u32,
-                        start: mat.start() as u32,
-                        length: mat.len() as u32,
-                        token_type: 2, // STRING
-                    });
-                }
+/// Markdown link to a function's documentation page
+fn function_doc_link(name: &str) -> Option<String> {
+    function_doc_uri(name).map(|uri| format!("[Go to documentation]({})", uri))
+}
 
-                // Tokenize numbers
-                for mat in NUMBER_PATTERN.find_iter(&line_str) {
-                    tokens.push(DslSemanticToken {
-                        line: line_num as u32,
-                        start: mat.start() as u32,
-                        length: mat.len() as u32,
-                        token_type: 3, // NUMBER
-                    });
-                }
+/// Zero-based line and byte column of a byte offset into `text`
+// Byte bounds of the name around `character`; None on anything else, numbers included
+fn symbol_bounds(line: &str, character: usize) -> Option<(usize, usize)> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.';
+    let character = character.min(line.len());
+    if !line.is_char_boundary(character) {
+        return None;
+    }
+    let start = line[..character]
+        .char_indices()
+        .rev()
+        .find(|(_, c)| !is_name_char(*c))
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    let end = line[character..].find(|c: char| !is_name_char(c)).map_or(line.len(), |i| i + character);
+    let name = line[start..end].trim_matches('.');
+    let start = start + line[start..end].find(name)?;
+    let first = name.chars().next()?;
+    (first.is_ascii_alphabetic() || first == '_').then_some((start, start + name.len()))
+}
 
-                // Tokenize regex patterns
-                for mat in REGEX_PATTERN.find_iter(&line_str) {
-                    tokens.push(DslSemanticToken {
-                        line: line_num as u32,
-                        start: mat.start() as u32,
-                        length: mat.len() as u32,
-                        token_type: 2, // STRING (for regex patterns)
-                    });
-                }
+/// Adds the database's stored rules to the symbol index, so `workspace/symbol`
+/// finds them by name. Runs apart from startup, as connecting may take a while;
+/// without a database the stored rules indexed last time are kept.
+async fn index_stored_rules(
+    client: Client,
+    database: Arc<EditorDatabase>,
+    symbol_index: Arc<RwLock<SymbolIndex>>,
+    workspace_root: Arc<RwLock<Option<PathBuf>>>,
+) {
+    let (pool, session) = match database.session().await {
+        Ok(session) => session,
+        Err(e) => {
+            client.log_message(MessageType::INFO, format!("Stored rules not indexed: {}", e)).await;
+            return;
+        }
+    };
+    let rules = match RuleOperations::get_existing_rules(pool).await {
+        Ok(rules) => rules,
+        Err(e) => {
+            client.log_message(MessageType::WARNING, format!("Failed to load stored rules: {}", e)).await;
+            return;
+        }
+    };
+
+    // Rules outside the editor's token are left out
+    let rules: Vec<(String, String)> = rules
+        .iter()
+        .filter_map(|rule| {
+            let rule_id = rule["rule_id"].as_str()?;
+            session.scope.allows_rule(rule_id).then(|| (rule_id.to_string(), rule["rule_name"].as_str().unwrap_or(rule_id).to_string()))
+        })
+        .collect();
+    let count = rules.len();
+    let mut index = symbol_index.write().await;
+    index.index_stored_rules(rules);
+    if let Some(root) = workspace_root.read().await.as_deref() {
+        if let Err(e) = index.save(root) {
+            client.log_message(MessageType::WARNING, format!("Failed to save symbol index: {}", e)).await;
+        }
+    }
+    drop(index);
+    client.log_message(MessageType::INFO, format!("Symbol index: {} stored rules", count)).await;
+}
 
-                // Tokenize comments
-                if let Some(comment_pos) = line_str.find('#') {
-                    tokens.push(DslSemanticToken {
-                        line: line_num as u32,
-                        start: comment_pos as u32,
-                        length: (line_str.len() - comment_pos) as u32,
-                        token_type: 6, // COMMENT
-                    });
-                }
-            }
+/// Offers to rename `old_name` in the stored rule definitions as well, once the
+/// editor's rename is done. Without a database the offer is skipped quietly.
+async fn offer_stored_rename(client: Client, database: Arc<EditorDatabase>, old_name: String, new_name: String) {
+    let (pool, session) = match database.session().await {
+        Ok(session) => session,
+        Err(e) => {
+            client
+                .log_message(MessageType::INFO, format!("Stored rules not checked for '{}': {}", old_name, e))
+                .await;
+            return;
+        }
+    };
+    if !session.scope.allows_attribute(&old_name) || !session.scope.allows_attribute(&new_name) {
+        client
+            .log_message(MessageType::INFO, format!("Stored rules not checked for '{}': outside this editor's token", old_name))
+            .await;
+        return;
+    }
+    let rewrite = RuleRewrite {
+        pattern: old_name.clone(),
+        replacement: new_name.clone(),
+        mode: RewriteMode::Ast,
+    };
+    let affected = match RuleOperations::preview_rule_rewrite(pool, rewrite).await {
+        Ok(plan) if plan.rules.is_empty() => return,
+        // The rename rewrites every rule using the name, so it needs them all
+        Ok(plan) if plan.rules.iter().any(|rule| !session.scope.allows_rule(&rule.rule_id)) => {
+            let message = format!("Some stored rules using '{}' are outside this editor's token; rename it there in the rules editor", old_name);
+            client.show_message(MessageType::INFO, message).await;
+            return;
+        }
+        Ok(plan) => plan.rules.len(),
+        Err(e) => {
+            client
+                .log_message(MessageType::WARNING, format!("Failed to look up stored rules using '{}': {}", old_name, e))
+                .await;
+            return;
+        }
+    };
+
+    let update = MessageActionItem { title: "Update stored rules".to_string(), properties: HashMap::new() };
+    let skip = MessageActionItem { title: "Skip".to_string(), properties: HashMap::new() };
+    let prompt = format!(
+        "{} stored rule{} use '{}'. Rename it to '{}' there too?",
+        affected,
+        if affected == 1 { "" } else { "s" },
+        old_name,
+        new_name
+    );
+    let choice = client.show_message_request(MessageType::INFO, prompt, Some(vec![update.clone(), skip])).await;
+    if !matches!(choice, Ok(Some(item)) if item.title == update.title) {
+        return;
+    }
 
-            return Some(tokens);
+    match RuleOperations::rename_attribute(pool, &old_name, &new_name, Some(affected), Some("dsl-lsp")).await {
+        Ok(plan) => {
+            client
+                .show_message(
+                    MessageType::INFO,
+                    format!("Renamed '{}' to '{}' in {} stored rules", old_name, new_name, plan.rules.len()),
+                )
+                .await
         }
-        None
+        Err(e) => client.show_message(MessageType::ERROR, format!("Stored rules not renamed: {}", e)).await,
     }
 }
 
+/// Brackets, CASE blocks and comments, plus each rule as a whole
+/// Sample values from `source`: an object of values, or the path of a JSON
+/// file holding one or a `.tests.json` list of cases, from which the case
+/// named `case` (else the first) is taken. Null gives none.
+async fn read_test_context(
+    source: &serde_json::Value,
+    case: Option<&str>,
+) -> std::result::Result<Option<BTreeMap<String, serde_json::Value>>, String> {
+    Ok(match source {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(path) => {
+            let content = tokio::fs::read_to_string(path).await.map_err(|e| format!("{}: {}", path, e))?;
+            let json: serde_json::Value = serde_json::from_str(&content).map_err(|e| format!("{}: {}", path, e))?;
+            Some(match json {
+                serde_json::Value::Array(_) => {
+                    let cases: Vec<RuleTestCase> = serde_json::from_value(json).map_err(|e| format!("{}: {}", path, e))?;
+                    let found = match case {
+                        Some(name) => cases.into_iter().find(|test_case| test_case.name == name),
+                        None => cases.into_iter().next(),
+                    };
+                    found.ok_or_else(|| format!("No test case {} in {}", case.unwrap_or_default(), path))?.inputs
+                }
+                json => serde_json::from_value(json).map_err(|e| format!("{}: {}", path, e))?,
+            })
+        }
+        values => Some(serde_json::from_value(values.clone()).map_err(|e| e.to_string())?),
+    })
+}
+
+fn document_regions(text: &str) -> Vec<Region> {
+    let (rules, _) = parse_rules_recovering(text);
+    let mut found = regions(text, &RULE_SYNTAX);
+    found.extend(rules.iter().map(|rule| Region { start: rule.start, end: rule.end, kind: RegionKind::Block }));
+    found.sort_by_key(|region| (region.start, std::cmp::Reverse(region.end)));
+    found
+}
+
+fn offset_at(text: &str, position: Position) -> usize {
+    let line_start = text.split_inclusive('\n').take(position.line as usize).map(str::len).sum::<usize>();
+    let line = text[line_start..].split('\n').next().unwrap_or_default();
+    let mut offset = line_start + (position.character as usize).min(line.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// A dsl-lsp diagnostic carrying `code`, linked to the code's documentation page
+fn coded_diagnostic(code: ErrorCode, range: Range, severity: DiagnosticSeverity, message: String) -> Diagnostic {
+    Diagnostic {
+        range,
+        severity: Some(severity),
+        code: Some(NumberOrString::String(code.code().to_string())),
+        code_description: error_doc_uri(code).map(|href| CodeDescription { href }),
+        source: Some("dsl-lsp".to_string()),
+        message,
+        ..Default::default()
+    }
+}
+
+fn position_at(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: (offset - line_start) as u32,
+    }
+}
+
+/// A quick fix making `fix` to the document at `uri`
+fn text_fix_action(uri: &Url, text: &str, diagnostic: &Diagnostic, title: String, fix: TextFix, is_preferred: bool) -> CodeActionOrCommand {
+    let edit = TextEdit {
+        range: Range {
+            start: position_at(text, fix.start),
+            end: position_at(text, fix.end),
+        },
+        new_text: fix.new_text,
+    };
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+            ..Default::default()
+        }),
+        is_preferred: Some(is_preferred),
+        ..Default::default()
+    })
+}
+
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        // Answer workspace/symbol from the saved index until the refresh in `initialized` is done
+        #[allow(deprecated)]
+        let root = params
+            .workspace_folders
+            .and_then(|folders| folders.into_iter().next())
+            .map(|folder| folder.uri)
+            .or(params.root_uri)
+            .and_then(|uri| uri.to_file_path().ok());
+        if let Some(root) = root {
+            *self.symbol_index.write().await = SymbolIndex::load(&root);
+            *self.workspace_root.write().await = Some(root);
+        }
+
+        // Applied in `initialized`, once the client can be told about a dictionary that fails to load
+        let settings = match params.initialization_options.as_ref().map(Settings::from_value) {
+            Some(Ok(settings)) => settings,
+            Some(Err(e)) => {
+                log::warn!("Ignoring invalid initializationOptions: {}", e);
+                Settings::default()
+            }
+            None => Settings::default(),
+        };
+        *self.database.write().await = Arc::new(EditorDatabase::new(settings.database_url.clone(), settings.auth_token.clone()));
+        *self.settings.write().await = settings;
+
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::FULL),
+                        save: Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions {
+                            include_text: Some(true),
+                        })),
+                        ..Default::default()
+                    },
                 )),
                 completion_provider: Some(CompletionOptions {
                     resolve_provider: Some(false),
@@ -691,6 +1689,11 @@ impl LanguageServer for Backend {
                     ..Default::default()
                 }),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
                 diagnostic_provider: Some(DiagnosticServerCapabilities::Options(
                     DiagnosticOptions {
                         identifier: None,
@@ -703,6 +1706,7 @@ impl LanguageServer for Backend {
                     SemanticTokensServerCapabilities::SemanticTokensOptions(
                         SemanticTokensOptions {
                             legend: SemanticTokensLegend {
+                                // In `TokenKind` order
                                 token_types: vec![
                                     SemanticTokenType::KEYWORD,
                                     SemanticTokenType::OPERATOR,
@@ -714,11 +1718,16 @@ impl LanguageServer for Backend {
                                 ],
                                 token_modifiers: vec![],
                             },
-                            full: Some(SemanticTokensFullOptions::Bool(true)),
+                            full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
                             ..Default::default()
                         },
                     ),
                 ),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
                 code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
                 execute_command_provider: Some(ExecuteCommandOptions {
                     commands: vec![
@@ -728,6 +1737,13 @@ impl LanguageServer for Backend {
                         "dsl.loadDataDictionary".to_string(),
                         "dsl.setAIAgent".to_string(),
                         "dsl.reloadGrammar".to_string(),
+                        "dsl.showDocumentation".to_string(),
+                        "dsl.addDictionaryAttribute".to_string(),
+                        "dsl.registerLookupTable".to_string(),
+                        "dsl.loadTestContext".to_string(),
+                        "dsl.convertSyntax".to_string(),
+                        "dsl.startSessionRecording".to_string(),
+                        "dsl.stopSessionRecording".to_string(),
                     ],
                     ..Default::default()
                 }),
@@ -745,12 +1761,50 @@ impl LanguageServer for Backend {
                 .await;
         }
 
+        self.load_configured_dictionary().await;
+        self.watch_config_files().await;
+
+        self.refresh_symbol_index().await;
+        tokio::spawn(index_stored_rules(
+            self.client.clone(),
+            self.database.read().await.clone(),
+            self.symbol_index.clone(),
+            self.workspace_root.clone(),
+        ));
+
         self.client
             .log_message(MessageType::INFO, "DSL Language Server initialized with AI support and dynamic grammar!")
             .await;
     }
 
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        // Clients on the pull model send null and expect the settings to be asked for
+        let value = if params.settings.is_null() {
+            let item = ConfigurationItem { scope_uri: None, section: Some(SECTION.to_string()) };
+            match self.client.configuration(vec![item]).await {
+                Ok(mut values) if !values.is_empty() => values.swap_remove(0),
+                Ok(_) => return,
+                Err(e) => {
+                    log::warn!("Failed to fetch the dsl settings: {}", e);
+                    return;
+                }
+            }
+        } else {
+            params.settings
+        };
+        match Settings::from_value(&value) {
+            Ok(settings) => self.apply_settings(settings).await,
+            Err(e) => {
+                self.client
+                    .show_message(MessageType::ERROR, format!("Invalid dsl settings: {}", e))
+                    .await;
+            }
+        }
+    }
+
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        let document = &params.text_document;
+        self.session_recorder.open(&document.uri, &document.language_id, document.version, &document.text);
         self.on_change(TextDocumentItem {
             uri: params.text_document.uri,
             language_id: params.text_document.language_id,
@@ -762,6 +1816,7 @@ impl LanguageServer for Backend {
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         if let Some(change) = params.content_changes.into_iter().next() {
+            self.session_recorder.change(&params.text_document.uri, params.text_document.version, &change.text);
             self.on_change(TextDocumentItem {
                 uri: params.text_document.uri,
                 language_id: "dsl".to_string(),
@@ -772,82 +1827,326 @@ impl LanguageServer for Backend {
         }
     }
 
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        self.session_recorder.save(&params.text_document.uri, params.text.as_deref());
+        let uri = params.text_document.uri;
+        let text = match params.text {
+            Some(text) => text,
+            None => match self.document_map.get(&uri) {
+                Some(rope) => rope.to_string(),
+                None => return,
+            },
+        };
+        self.generate_rule_tests(&uri, &text).await;
+
+        if let Ok(path) = uri.to_file_path() {
+            if is_rule_file(&path) {
+                self.symbol_index.write().await.index_rule_file(&uri, &text, FileStamp::of(&path));
+                self.save_symbol_index().await;
+            }
+        }
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        self.session_recorder.close(&uri);
+        if self.workspace_rules.write().await.remove(&uri) {
+            self.revalidate_other_documents(&uri).await;
+        }
+    }
+
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
         let uri = params.text_document_position.text_document.uri;
+        let line = params.text_document_position.position.line as usize;
+        let character = params.text_document_position.position.character as usize;
+
+        // Copied out, so the document map isn't locked while completion awaits
+        let Some((text, line_text)) = self.document_map.get(&uri)
+            .and_then(|rope| Some((rope.to_string(), rope.get_line(line)?.to_string())))
+        else {
+            return Ok(None);
+        };
+
+        // Rules after a broken one still contribute their variables
+        let (rules, _) = parse_rules_recovering(&text);
+        let mut variables: Vec<String> = rules
+            .into_iter()
+            .filter_map(|rule| match rule.expression {
+                Expression::Assignment { target, .. } => Some(target),
+                _ => None,
+            })
+            .collect();
+        // Names the other open documents assign are known here too
+        variables.extend(self.workspace_rules.read().await.defined_elsewhere(&uri).into_keys().map(str::to_string));
+        variables.sort();
+        variables.dedup();
+        let mut completions = self.get_completions(&line_text, character, &variables).await;
+
+        let (suggestions, pending) = self.get_ai_completions(&line_text, character).await;
+        completions.extend(suggestions);
+        if pending {
+            // The client asks again as the user types, by when the suggestions are ready
+            return Ok(Some(CompletionResponse::List(CompletionList { is_incomplete: true, items: completions })));
+        }
+        Ok(Some(CompletionResponse::Array(completions)))
+    }
 
-        if let Some(rope) = self.document_map.get(&uri) {
-            let line = params.text_document_position.position.line as usize;
-            let character = params.text_document_position.position.character as usize;
+    async fn symbol(&self, params: WorkspaceSymbolParams) -> Result<Option<Vec<SymbolInformation>>> {
+        Ok(Some(self.symbol_index.read().await.search(&params.query)))
+    }
 
-            if let Some(line_str) = rope.get_line(line) {
-                let line_text = line_str.to_string();
-                let completions = self.get_completions(&line_text, character).await;
-                return Ok(Some(CompletionResponse::Array(completions)));
-            }
-        }
+    async fn document_symbol(&self, params: DocumentSymbolParams) -> Result<Option<DocumentSymbolResponse>> {
+        let Some(text) = self.document_map.get(&params.text_document.uri).map(|rope| rope.to_string()) else {
+            return Ok(None);
+        };
+        Ok(Some(DocumentSymbolResponse::Nested(document_symbols(&text))))
+    }
 
-        Ok(None)
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let Some(text) = self.document_map.get(&params.text_document.uri).map(|rope| rope.to_string()) else {
+            return Ok(None);
+        };
+        let (rules, _) = parse_rules_recovering(&text);
+        let context = self.test_context_for(&params.text_document.uri).await;
+        let hints = inlay_hints(&text, &rules, &*self.data_dictionary.read().await, context.as_ref());
+
+        let hints = hints
+            .into_iter()
+            .map(|hint| (position_at(&text, hint.offset), hint))
+            .filter(|(position, _)| params.range.start <= *position && *position <= params.range.end)
+            .map(|(position, hint)| InlayHint {
+                position,
+                label: InlayHintLabel::String(hint.label),
+                kind: match hint.kind {
+                    HintKind::Type => Some(InlayHintKind::TYPE),
+                    HintKind::Value => None,
+                },
+                text_edits: None,
+                tooltip: None,
+                padding_left: Some(hint.kind == HintKind::Value),
+                padding_right: None,
+                data: None,
+            })
+            .collect();
+        Ok(Some(hints))
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        let Some(text) = self.document_map.get(&params.text_document.uri).map(|rope| rope.to_string()) else {
+            return Ok(None);
+        };
+        let ranges = folds(&text, &document_regions(&text))
+            .into_iter()
+            .map(|fold| FoldingRange {
+                start_line: fold.start_line as u32,
+                end_line: fold.end_line as u32,
+                kind: Some(match fold.kind {
+                    RegionKind::Comment => FoldingRangeKind::Comment,
+                    RegionKind::Group | RegionKind::Block => FoldingRangeKind::Region,
+                }),
+                ..Default::default()
+            })
+            .collect();
+        Ok(Some(ranges))
+    }
+
+    async fn selection_range(&self, params: SelectionRangeParams) -> Result<Option<Vec<SelectionRange>>> {
+        let Some(text) = self.document_map.get(&params.text_document.uri).map(|rope| rope.to_string()) else {
+            return Ok(None);
+        };
+        let structure = document_regions(&text);
+        let ranges = params
+            .positions
+            .into_iter()
+            .map(|position| {
+                let spans = selection_spans(&text, offset_at(&text, position), &RULE_SYNTAX, &structure);
+                // Built from the whole document inwards, each range the parent of the next
+                let mut selection: Option<SelectionRange> = None;
+                for (start, end) in spans.into_iter().rev() {
+                    selection = Some(SelectionRange {
+                        range: Range { start: position_at(&text, start), end: position_at(&text, end) },
+                        parent: selection.map(Box::new),
+                    });
+                }
+                selection.unwrap_or(SelectionRange { range: Range { start: position, end: position }, parent: None })
+            })
+            .collect();
+        Ok(Some(ranges))
     }
 
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
         let uri = params.text_document_position_params.text_document.uri;
 
-        if let Some(rope) = self.document_map.get(&uri) {
-            let line = params.text_document_position_params.position.line as usize;
-            let character = params.text_document_position_params.position.character as usize;
-
-            if let Some(line_str) = rope.get_line(line) {
-                let line_text = line_str.to_string();
-                return Ok(self.get_hover_info(&line_text, character));
-            }
+        let line = params.text_document_position_params.position.line as usize;
+        let character = params.text_document_position_params.position.character as usize;
+
+        let Some(line_text) = self.document_map.get(&uri).and_then(|rope| Some(rope.get_line(line)?.to_string())) else {
+            return Ok(None);
+        };
+        let hover = self.get_hover_info(&line_text, character).await;
+
+        // With a test context, the value of the sub-expression under the cursor comes first
+        let Some(context) = self.test_context_for(&uri).await else {
+            return Ok(hover);
+        };
+        let Some(text) = self.document_map.get(&uri).map(|rope| rope.to_string()) else {
+            return Ok(hover);
+        };
+        let (rules, _) = parse_rules_recovering(&text);
+        let offset = offset_at(&text, params.text_document_position_params.position);
+        let preview = evaluation_preview(&text, &rules, offset, &*self.data_dictionary.read().await, &context);
+        let Some(preview) = preview else {
+            return Ok(hover);
+        };
+        let mut value = preview.markdown();
+        if let Some(Hover { contents: HoverContents::Markup(markup), .. }) = hover {
+            value.push_str(&format!("\n\n---\n\n{}", markup.value));
         }
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value }),
+            range: Some(Range { start: position_at(&text, preview.start), end: position_at(&text, preview.end) }),
+        }))
+    }
 
-        Ok(None)
+    /// Pull diagnostics: the same ones `validate_document` publishes on every change
+    async fn diagnostic(&self, params: DocumentDiagnosticParams) -> Result<DocumentDiagnosticReportResult> {
+        let uri = params.text_document.uri;
+        let items = match self.document_map.get(&uri).map(|rope| rope.to_string()) {
+            Some(text) => self.document_diagnostics(&uri, &text).await,
+            None => Vec::new(),
+        };
+        Ok(DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(
+            RelatedFullDocumentDiagnosticReport {
+                related_documents: None,
+                full_document_diagnostic_report: FullDocumentDiagnosticReport { result_id: None, items },
+            },
+        )))
     }
 
     async fn semantic_tokens_full(
         &self,
         params: SemanticTokensParams,
     ) -> Result<Option<SemanticTokensResult>> {
-        let uri = params.text_document.uri;
+        Ok(self.semantic_token_data(&params.text_document.uri).map(|(result_id, data)| {
+            SemanticTokensResult::Tokens(SemanticTokens { result_id: Some(result_id), data: lsp_tokens(&data) })
+        }))
+    }
 
-        if let Some(tokens) = self.get_semantic_tokens(&uri).await {
-            let mut data = Vec::new();
-            let mut prev_line = 0;
-            let mut prev_start = 0;
+    async fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> Result<Option<SemanticTokensFullDeltaResult>> {
+        let uri = params.text_document.uri;
+        let previous = self
+            .semantic_tokens
+            .get(&uri)
+            .filter(|sent| sent.0 == params.previous_result_id)
+            .map(|sent| sent.1.clone());
+        let Some((result_id, data)) = self.semantic_token_data(&uri) else {
+            return Ok(None);
+        };
+
+        // Tokens the client no longer has, or never had, are sent in full
+        Ok(Some(match previous {
+            Some(previous) => SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+                result_id: Some(result_id),
+                edits: diff(&previous, &data)
+                    .map(|edit| SemanticTokensEdit {
+                        start: edit.start,
+                        delete_count: edit.delete_count,
+                        data: Some(lsp_tokens(&edit.data)),
+                    })
+                    .into_iter()
+                    .collect(),
+            }),
+            None => SemanticTokensFullDeltaResult::Tokens(SemanticTokens {
+                result_id: Some(result_id),
+                data: lsp_tokens(&data),
+            }),
+        }))
+    }
 
-            for token in tokens {
-                let delta_line = token.line - prev_line;
-                let delta_start = if delta_line == 0 {
-                    token.start - prev_start
-                } else {
-                    token.start
-                };
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let (text, line_count) = match self.document_map.get(&params.text_document.uri) {
+            Some(rope) => (rope.to_string(), rope.len_lines()),
+            None => return Ok(None),
+        };
+
+        match format_document(&text) {
+            Ok(formatted) if formatted == text => Ok(Some(vec![])),
+            Ok(formatted) => Ok(Some(vec![TextEdit {
+                range: Range {
+                    start: Position { line: 0, character: 0 },
+                    end: Position { line: line_count as u32, character: 0 },
+                },
+                new_text: formatted,
+            }])),
+            Err(e) => {
+                // Leave the document alone; the parse error is already reported as a diagnostic
+                self.client
+                    .log_message(MessageType::WARNING, format!("Formatting skipped: {}", e))
+                    .await;
+                Ok(None)
+            }
+        }
+    }
 
-                data.push(SemanticToken {
-                    delta_line,
-                    delta_start,
-                    length: token.length,
-                    token_type: token.token_type,
-                    token_modifiers_bitset: 0,
-                });
+    async fn prepare_rename(&self, params: TextDocumentPositionParams) -> Result<Option<PrepareRenameResponse>> {
+        Ok(self
+            .symbol_at(&params.text_document.uri, params.position)
+            .map(|(_, range)| PrepareRenameResponse::Range(range)))
+    }
 
-                prev_line = token.line;
-                prev_start = token.start;
+    /// Renames the symbol in every open document, then offers the same in the stored rules
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let position = params.text_document_position;
+        let Some((old_name, _)) = self.symbol_at(&position.text_document.uri, position.position) else {
+            return Ok(None);
+        };
+        let new_name = params.new_name.trim().to_string();
+        let rewrite = RuleRewrite {
+            pattern: old_name.clone(),
+            replacement: new_name.clone(),
+            mode: RewriteMode::Ast,
+        };
+        rewrite.validate().map_err(tower_lsp::jsonrpc::Error::invalid_params)?;
+
+        let documents: Vec<(Url, String)> = self
+            .document_map
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().to_string()))
+            .collect();
+        let mut changes = HashMap::new();
+        for (uri, text) in documents {
+            let edits: Vec<TextEdit> = rename_edits(&text, &old_name, &new_name)
+                .into_iter()
+                .map(|edit| TextEdit {
+                    range: Range { start: position_at(&text, edit.range.start), end: position_at(&text, edit.range.end) },
+                    new_text: edit.text,
+                })
+                .collect();
+            if !edits.is_empty() {
+                changes.insert(uri, edits);
             }
-
-            return Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
-                result_id: None,
-                data,
-            })));
         }
 
-        Ok(None)
+        let database = self.database.read().await.clone();
+        tokio::spawn(offer_stored_rename(self.client.clone(), database, old_name, new_name));
+        Ok(Some(WorkspaceEdit { changes: Some(changes), ..Default::default() }))
     }
 
     async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
         let mut actions = Vec::new();
 
+        // Fixes for the diagnostics in range, ahead of the rule-wide actions
+        let text = self.document_map.get(&params.text_document.uri).map(|rope| rope.to_string());
+        if let Some(text) = text {
+            for diagnostic in &params.context.diagnostics {
+                actions.extend(self.quick_fixes(&params.text_document.uri, &text, diagnostic).await);
+            }
+            actions.extend(self.extract_lookup_actions(&params.text_document.uri, &text, params.range).await);
+        }
+
         // Add explain rule action
         actions.push(CodeActionOrCommand::CodeAction(CodeAction {
             title: "Explain Rule".to_string(),
@@ -888,14 +2187,22 @@ impl LanguageServer for Backend {
     }
 
     async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<serde_json::Value>> {
+        let database = self.database.read().await.clone();
+        if !database.allows_command(&params.command).await {
+            let message = format!("{} is outside what this editor's token grants", params.command);
+            self.client.show_message(MessageType::WARNING, message).await;
+            return Ok(None);
+        }
+        if !matches!(params.command.as_str(), "dsl.startSessionRecording" | "dsl.stopSessionRecording") {
+            self.session_recorder.command(&params.command, &params.arguments);
+        }
         match params.command.as_str() {
             "dsl.explainRule" => {
                 if let Some(uri) = params.arguments.get(0).and_then(|v| v.as_str()) {
                     let uri = Url::parse(uri).unwrap();
-                    if let Some(rope) = self.document_map.get(&uri) {
-                        let content = rope.to_string();
-                        let ai_manager = self.ai_agent_manager.read().await;
-                        if let Some(agent) = ai_manager.get_active_agent() {
+                    if let Some(content) = self.document_map.get(&uri).map(|rope| rope.to_string()) {
+                        let agent = self.ai_agent(Capability::Ai).await;
+                        if let Some(agent) = agent {
                             if let Ok(explanation) = agent.explain_rule(&content).await {
                                 self.client
                                     .show_message(MessageType::INFO, explanation)
@@ -908,10 +2215,9 @@ impl LanguageServer for Backend {
             "dsl.optimizeRule" => {
                 if let Some(uri) = params.arguments.get(0).and_then(|v| v.as_str()) {
                     let uri = Url::parse(uri).unwrap();
-                    if let Some(rope) = self.document_map.get(&uri) {
-                        let content = rope.to_string();
-                        let ai_manager = self.ai_agent_manager.read().await;
-                        if let Some(agent) = ai_manager.get_active_agent() {
+                    if let Some(content) = self.document_map.get(&uri).map(|rope| rope.to_string()) {
+                        let agent = self.ai_agent(Capability::Ai).await;
+                        if let Some(agent) = agent {
                             if let Ok(optimized) = agent.optimize_rule(&content).await {
                                 self.client
                                     .show_message(MessageType::INFO, format!("Optimized:\n{}", optimized))
@@ -924,10 +2230,9 @@ impl LanguageServer for Backend {
             "dsl.generateTests" => {
                 if let Some(uri) = params.arguments.get(0).and_then(|v| v.as_str()) {
                     let uri = Url::parse(uri).unwrap();
-                    if let Some(rope) = self.document_map.get(&uri) {
-                        let content = rope.to_string();
-                        let ai_manager = self.ai_agent_manager.read().await;
-                        if let Some(agent) = ai_manager.get_active_agent() {
+                    if let Some(content) = self.document_map.get(&uri).map(|rope| rope.to_string()) {
+                        let agent = self.ai_agent(Capability::Ai).await;
+                        if let Some(agent) = agent {
                             if let Ok(tests) = agent.generate_test_cases(&content).await {
                                 let test_json = serde_json::to_string_pretty(&tests).unwrap();
                                 self.client
@@ -940,10 +2245,13 @@ impl LanguageServer for Backend {
             },
             "dsl.loadDataDictionary" => {
                 if let Some(path) = params.arguments.get(0).and_then(|v| v.as_str()) {
-                    if let Err(e) = self.load_data_dictionary(path).await {
-                        self.client
-                            .show_message(MessageType::ERROR, format!("Failed to load data dictionary: {}", e))
-                            .await;
+                    match self.load_data_dictionary(path).await {
+                        Ok(()) => self.watch_config_files().await,
+                        Err(e) => {
+                            self.client
+                                .show_message(MessageType::ERROR, format!("Failed to load data dictionary: {}", e))
+                                .await;
+                        }
                     }
                 }
             },
@@ -957,6 +2265,100 @@ impl LanguageServer for Backend {
                     }
                 }
             },
+            "dsl.showDocumentation" => {
+                // The client's content provider for `dsl://docs/...` and `dsl://rules/...`
+                // documents asks for the page here
+                if let Some(uri) = params.arguments.first().and_then(|v| v.as_str()).and_then(|uri| Url::parse(uri).ok()) {
+                    let page = if stored_rule_from_uri(&uri).is_some() {
+                        self.stored_rule_document(&uri).await
+                    } else if let Some(code) = error_from_doc_uri(&uri) {
+                        Some(render_error_doc(code))
+                    } else {
+                        self.function_documentation(&uri).await
+                    };
+                    return Ok(page.map(serde_json::Value::String));
+                }
+            },
+            "dsl.addDictionaryAttribute" => {
+                // Run by the add-attribute quick fix once its edit to entities.json is applied
+                let entity = params.arguments.first().and_then(|v| v.as_str());
+                let attribute = params.arguments.get(1).cloned().and_then(|v| serde_json::from_value::<Attribute>(v).ok());
+                if let (Some(entity), Some(attribute)) = (entity, attribute) {
+                    let added = self.data_dictionary.write().await.add_attribute(entity, attribute);
+                    if added {
+                        self.symbol_index.write().await.index_dictionary(&*self.data_dictionary.read().await);
+                        self.revalidate_open_documents().await;
+                    }
+                }
+            },
+            "dsl.registerLookupTable" => {
+                // Run by the extract-lookup-table action once its edits are applied
+                let table = params.arguments.first().cloned().and_then(|v| serde_json::from_value::<LookupTable>(v).ok());
+                if let Some(table) = table {
+                    let added = self.data_dictionary.write().await.add_lookup(table);
+                    if added {
+                        self.symbol_index.write().await.index_dictionary(&*self.data_dictionary.read().await);
+                        self.revalidate_open_documents().await;
+                    }
+                }
+            },
+            "dsl.loadTestContext" => {
+                let source = params.arguments.first().cloned().unwrap_or_default();
+                let case = params.arguments.get(1).and_then(|v| v.as_str());
+                if let Err(e) = self.load_test_context(&source, case).await {
+                    self.client
+                        .show_message(MessageType::ERROR, format!("Failed to load test context: {}", e))
+                        .await;
+                }
+            },
+            "dsl.convertSyntax" => {
+                let uri = params.arguments.first().and_then(|v| v.as_str()).and_then(|uri| Url::parse(uri).ok());
+                let to = params.arguments.get(1).and_then(|v| v.as_str()).unwrap_or("sexpr");
+                let text = params.arguments.get(2).and_then(|v| v.as_str());
+                if let Some(uri) = uri {
+                    match self.convert_syntax(&uri, to, text).await {
+                        Ok(converted) => return Ok(Some(serde_json::Value::String(converted))),
+                        Err(e) => {
+                            self.client
+                                .show_message(MessageType::ERROR, format!("Failed to convert syntax: {}", e))
+                                .await;
+                        }
+                    }
+                }
+            },
+            "dsl.startSessionRecording" => {
+                let path = match params.arguments.first().and_then(|v| v.as_str()) {
+                    Some(path) => PathBuf::from(path),
+                    None => self.default_session_path().await,
+                };
+                // Documents already open are recorded as opened first, so the script stands alone
+                let documents: Vec<(Url, String)> =
+                    self.document_map.iter().map(|entry| (entry.key().clone(), entry.value().to_string())).collect();
+                match self.session_recorder.start(&path, documents.iter().map(|(uri, text)| (uri, text.as_str()))) {
+                    Ok(()) => {
+                        self.client
+                            .show_message(MessageType::INFO, format!("Recording this session to {}", path.display()))
+                            .await;
+                        return Ok(Some(serde_json::Value::String(path.display().to_string())));
+                    }
+                    Err(e) => {
+                        self.client
+                            .show_message(MessageType::ERROR, format!("Failed to start recording: {}", e))
+                            .await;
+                    }
+                }
+            },
+            "dsl.stopSessionRecording" => {
+                if let Some(path) = self.session_recorder.stop() {
+                    self.client
+                        .show_message(
+                            MessageType::INFO,
+                            format!("Session recorded to {}; replay it with `dsl-lsp-server replay --realtime`", path.display()),
+                        )
+                        .await;
+                    return Ok(Some(serde_json::Value::String(path.display().to_string())));
+                }
+            },
             "dsl.reloadGrammar" => {
                 if let Err(e) = self.grammar_loader.reload_if_changed().await {
                     self.client
@@ -979,10 +2381,272 @@ impl LanguageServer for Backend {
     }
 }
 
+/// The language server with its custom requests registered
+pub fn service() -> (LspService<Backend>, ClientSocket) {
+    LspService::build(Backend::new).custom_method("dsl/setContext", Backend::set_context).finish()
+}
+
 pub async fn run_server() {
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
-    let (service, socket) = LspService::new(|client| Backend::new(client));
+    let (service, socket) = service();
     Server::new(stdin, stdout, socket).serve(service).await;
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_agent::{AIAgent, CompletionResponse as AgentCompletions, Suggestion, TestCase, ValidationResponse};
+    use futures_util::StreamExt;
+    use std::time::{Duration, Instant};
+
+    type AgentResult<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// A remote agent far slower to answer than completion may take
+    struct SlowAgent;
+
+    #[async_trait::async_trait]
+    impl AIAgent for SlowAgent {
+        async fn get_completions(&self, _request: CompletionRequest) -> AgentResult<AgentCompletions> {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok(AgentCompletions {
+                suggestions: vec![Suggestion { text: "THEN".to_string(), confidence: 0.9, description: None }],
+                explanation: None,
+            })
+        }
+
+        async fn validate_rule(&self, _request: ValidationRequest) -> AgentResult<ValidationResponse> {
+            Ok(ValidationResponse { is_valid: true, issues: vec![], suggestions: vec![] })
+        }
+
+        async fn explain_rule(&self, _rule: &str) -> AgentResult<String> {
+            Err("not supported".into())
+        }
+
+        async fn optimize_rule(&self, _rule: &str) -> AgentResult<String> {
+            Err("not supported".into())
+        }
+
+        async fn generate_test_cases(&self, _rule: &str) -> AgentResult<Vec<TestCase>> {
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_completion_answers_without_waiting_for_the_agent() {
+        let (service, socket) = LspService::new(Backend::new);
+        tokio::spawn(socket.for_each(|_| async {}));
+        let backend = service.inner();
+        {
+            let mut manager = backend.ai_agent_manager.write().await;
+            manager.add_agent("slow".to_string(), Box::new(SlowAgent));
+            manager.set_active_agent("slow".to_string()).unwrap();
+        }
+
+        let uri = Url::parse("file:///rules/age.dsl").unwrap();
+        backend.did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri.clone(), "dsl".to_string(), 1, "IF age > 18 ".to_string()),
+        }).await;
+        let params = CompletionParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position { line: 0, character: 12 },
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        };
+
+        let started = Instant::now();
+        let response = backend.completion(params.clone()).await.unwrap().unwrap();
+        assert!(started.elapsed() < Duration::from_millis(100), "completion took {:?}", started.elapsed());
+        let CompletionResponse::List(list) = response else {
+            panic!("completion should be incomplete while the agent is asked");
+        };
+        assert!(list.is_incomplete);
+        assert!(!list.items.is_empty());
+        assert!(list.items.iter().all(|item| !item.label.starts_with('🤖')));
+
+        // Once typing has paused and the agent answered, asking again offers its suggestions
+        tokio::time::sleep(AI_DEBOUNCE + Duration::from_millis(400)).await;
+        let started = Instant::now();
+        let Some(CompletionResponse::Array(items)) = backend.completion(params).await.unwrap() else {
+            panic!("completion should be complete once the agent answered");
+        };
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert!(items.iter().any(|item| item.label == "🤖 THEN"));
+    }
+
+    #[tokio::test]
+    async fn test_pulled_diagnostics_carry_codes_and_the_conflicting_rule() {
+        let (service, socket) = LspService::new(Backend::new);
+        tokio::spawn(socket.for_each(|_| async {}));
+        let backend = service.inner();
+
+        let uri = Url::parse("file:///rules/fees.dsl").unwrap();
+        let text = "fee = IF volume > 100 THEN 1\nfee = IF volume > 50 THEN 2\nlabel = 1 +\n";
+        backend.did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri.clone(), "dsl".to_string(), 1, text.to_string()),
+        }).await;
+        let report = backend.diagnostic(DocumentDiagnosticParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            identifier: None,
+            previous_result_id: None,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        }).await.unwrap();
+        let DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) = report else {
+            panic!("expected a full report");
+        };
+        let items = report.full_document_diagnostic_report.items;
+        let with_code = |code: ErrorCode| {
+            items.iter().find(|item| item.code == Some(NumberOrString::String(code.code().to_string())))
+        };
+
+        let conflict = with_code(ErrorCode::RuleConflict).expect("the second fee rule conflicts with the first");
+        assert_eq!(conflict.range.start.line, 1);
+        let related = conflict.related_information.as_ref().unwrap();
+        assert_eq!(related[0].location.uri, uri);
+        assert_eq!(related[0].location.range.start.line, 0);
+        assert_eq!(
+            conflict.code_description.as_ref().map(|description| description.href.as_str()),
+            Some("dsl://docs/ERROR/DSL0006")
+        );
+        assert_eq!(with_code(ErrorCode::UnexpectedText).unwrap().range.start.line, 2);
+    }
+
+    #[tokio::test]
+    async fn test_names_assigned_in_one_open_document_are_known_in_another() {
+        let (service, socket) = LspService::new(Backend::new);
+        tokio::spawn(socket.for_each(|_| async {}));
+        let backend = service.inner();
+
+        let fees = Url::parse("file:///rules/fees.rules").unwrap();
+        let risk = Url::parse("file:///rules/risk.rules").unwrap();
+        let open = |uri: &Url, text: &str| DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri.clone(), "dsl".to_string(), 1, text.to_string()),
+        };
+        let codes = |diagnostics: Vec<Diagnostic>| -> Vec<String> {
+            diagnostics.into_iter().filter_map(|diagnostic| match diagnostic.code {
+                Some(NumberOrString::String(code)) => Some(code),
+                _ => None,
+            }).collect()
+        };
+
+        backend.did_open(open(&risk, "score = IF net_fee > 150 THEN 1 ELSE 0\nrisk_fee = 10\n")).await;
+        let unknown = ErrorCode::UnknownAttribute.code().to_string();
+        assert!(codes(backend.check_document(&risk, "score = IF net_fee > 150 THEN 1 ELSE 0\n").await).contains(&unknown));
+
+        backend.did_open(open(&fees, "net_fee = 200\nrisk_fee = 20\n")).await;
+        let diagnostics = backend.check_document(&risk, "score = IF net_fee > 150 THEN 1 ELSE 0\nrisk_fee = 10\n").await;
+        let duplicate = diagnostics.iter().find(|d| d.code == Some(NumberOrString::String(ErrorCode::DuplicateRule.code().to_string())))
+            .expect("risk_fee is assigned in both files");
+        assert_eq!(duplicate.range.start.line, 1);
+        assert_eq!(duplicate.related_information.as_ref().unwrap()[0].location.uri, fees);
+        assert!(!codes(diagnostics).contains(&unknown));
+
+        backend.did_close(DidCloseTextDocumentParams { text_document: TextDocumentIdentifier { uri: fees } }).await;
+        assert!(codes(backend.check_document(&risk, "score = IF net_fee > 150 THEN 1 ELSE 0\n").await).contains(&unknown));
+    }
+
+    #[tokio::test]
+    async fn test_semantic_token_delta_replaces_only_the_edited_rule() {
+        let (service, socket) = LspService::new(Backend::new);
+        tokio::spawn(socket.for_each(|_| async {}));
+        let backend = service.inner();
+
+        let uri = Url::parse("file:///rules/flags.dsl").unwrap();
+        backend.did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri.clone(), "dsl".to_string(), 1, "a = 1\nb = 2\n".to_string()),
+        }).await;
+        let full = backend.semantic_tokens_full(SemanticTokensParams {
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+        }).await.unwrap();
+        let Some(SemanticTokensResult::Tokens(full)) = full else {
+            panic!("expected tokens");
+        };
+        assert_eq!(full.data.len(), 6);
+
+        backend.did_change(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier { uri: uri.clone(), version: 2 },
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "a = 1\nb = IS_SET + 2\n".to_string(),
+            }],
+        }).await;
+        let delta_params = |previous_result_id: String| SemanticTokensDeltaParams {
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            previous_result_id,
+        };
+        let delta = backend.semantic_tokens_full_delta(delta_params(full.result_id.unwrap())).await.unwrap();
+        let Some(SemanticTokensFullDeltaResult::TokensDelta(delta)) = delta else {
+            panic!("expected a delta against the tokens sent");
+        };
+        // Only `IS_SET +` is new: one variable, not a keyword, and an operator
+        let edit = &delta.edits[0];
+        assert_eq!((delta.edits.len(), edit.start, edit.delete_count), (1, 25, 0));
+        let inserted = edit.data.as_ref().unwrap();
+        assert_eq!(inserted.len(), 2);
+        assert_eq!((inserted[0].length, inserted[0].token_type), (6, 4));
+
+        // A result id the server no longer holds gets the tokens in full
+        let stale = backend.semantic_tokens_full_delta(delta_params("stale".to_string())).await.unwrap();
+        assert!(matches!(stale, Some(SemanticTokensFullDeltaResult::Tokens(_))));
+    }
+
+    #[tokio::test]
+    async fn test_hover_previews_values_once_a_context_is_set() {
+        let (service, socket) = service();
+        tokio::spawn(socket.for_each(|_| async {}));
+        let backend = service.inner();
+
+        let uri = Url::parse("file:///rules/net.dsl").unwrap();
+        let text = "fee = aum_usd * 0.01\nnet = (fee - rebate) * 2\n";
+        backend.did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri.clone(), "dsl".to_string(), 1, text.to_string()),
+        }).await;
+        let hover_at = |line: u32, character: u32| HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                position: Position { line, character },
+            },
+            work_done_progress_params: Default::default(),
+        };
+        let markdown = |hover: Option<Hover>| match hover.map(|hover| hover.contents) {
+            Some(HoverContents::Markup(markup)) => markup.value,
+            other => panic!("expected markdown, got {:?}", other),
+        };
+
+        // A number has nothing to show until there is a context to evaluate it in
+        assert!(backend.hover(hover_at(0, 17)).await.unwrap().is_none());
+
+        backend.set_context(SetContextParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            context: serde_json::json!({ "Client.aum_usd": 25000, "rebate": 50 }),
+            case: None,
+        }).await.unwrap();
+        let hover = backend.hover(hover_at(1, 11)).await.unwrap();
+        let range = hover.as_ref().and_then(|hover| hover.range).unwrap();
+        assert_eq!((range.start, range.end), (Position::new(1, 6), Position::new(1, 20)));
+        // Whatever the hover showed before follows the value
+        let hover = markdown(hover);
+        assert!(hover.starts_with("**Test context:** `(fee - rebate)` = `200.0` : Number\n\n---\n\n"), "{}", hover);
+        assert!(hover.ends_with("**Operator: -**\n\nSubtraction"));
+
+        let hover = markdown(backend.hover(hover_at(0, 8)).await.unwrap());
+        assert!(hover.starts_with("**Test context:** `aum_usd` = `25000`"), "{}", hover);
+        assert!(hover.contains("Attribute: `aum_usd`"));
+
+        let unreadable = backend.set_context(SetContextParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            context: serde_json::Value::String("/no/such/context.json".to_string()),
+            case: None,
+        }).await;
+        assert!(unreadable.is_err());
+    }
+}
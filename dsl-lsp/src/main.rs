@@ -38,6 +38,17 @@ enum Commands {
         address: String,
     },
 
+    /// Replay LSP traffic captured from an editor and diff the responses
+    Replay {
+        /// Output channel saved from an editing session with JSON tracing on,
+        /// or a session written by `dsl.startSessionRecording`
+        capture: String,
+
+        /// Keep the recorded pauses between messages, for bugs that depend on timing
+        #[arg(long)]
+        realtime: bool,
+    },
+
     /// Generate data dictionary from sample KYC data
     GenerateDict {
         /// Output path for the data dictionary
@@ -57,6 +68,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
     }
 
+    // Locked-down deployments switch AI off under [capabilities] in config.toml.
+    // Replays run with everything on, so they answer the same wherever they run.
+    if !matches!(cli.command, Some(Commands::Replay { .. }) | Some(Commands::GenerateDict { .. })) {
+        install_capabilities()?;
+    }
+
     match cli.command {
         None | Some(Commands::Stdio) => {
             log::info!("Starting DSL Language Server in stdio mode...");
@@ -77,6 +94,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             websocket_server::run_websocket_server(addr).await?;
         }
 
+        Some(Commands::Replay { capture, realtime }) => {
+            run_replay(&capture, realtime).await?;
+        }
+
         Some(Commands::GenerateDict { output }) => {
             log::info!("Generating data dictionary to {}", output);
             generate_data_dictionary(&output)?;
@@ -86,9 +107,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+fn install_capabilities() -> Result<(), Box<dyn std::error::Error>> {
+    use data_designer::capabilities::{self, Capabilities, DISABLE_ENV_VAR};
+
+    let config = data_designer::config::Config::load()?;
+    let (capabilities, unknown) = Capabilities::for_deployment(&config.capabilities);
+    for name in unknown {
+        log::warn!("Ignoring unknown capability '{}' in {}", name, DISABLE_ENV_VAR);
+    }
+    for (capability, enabled) in capabilities.matrix() {
+        if !enabled {
+            log::info!("Capability {} is disabled", capability.as_str());
+        }
+    }
+    capabilities::install(capabilities)?;
+    Ok(())
+}
+
 async fn run_tcp_server(addr: SocketAddr) -> Result<(), Box<dyn std::error::Error>> {
     use tokio::net::TcpListener;
-    use tower_lsp::{LspService, Server};
+    use tower_lsp::Server;
 
     let listener = TcpListener::bind(addr).await?;
     log::info!("TCP server listening on {}", addr);
@@ -100,7 +138,7 @@ async fn run_tcp_server(addr: SocketAddr) -> Result<(), Box<dyn std::error::Erro
         let (read_stream, write_stream) = tokio::io::split(stream);
 
         tokio::spawn(async move {
-            let (service, socket) = LspService::new(|client| dsl_lsp::Backend::new(client));
+            let (service, socket) = dsl_lsp::service();
             Server::new(read_stream, write_stream, socket)
                 .serve(service)
                 .await;
@@ -108,6 +146,40 @@ async fn run_tcp_server(addr: SocketAddr) -> Result<(), Box<dyn std::error::Erro
     }
 }
 
+async fn run_replay(path: &str, realtime: bool) -> Result<(), Box<dyn std::error::Error>> {
+    use dsl_lsp::replay::{parse_capture, replay_with, ReplayOptions};
+
+    let capture = parse_capture(&std::fs::read_to_string(path)?)?;
+    let (service, socket) = dsl_lsp::service();
+    let report = replay_with(service, socket, &capture, ReplayOptions { realtime }).await;
+
+    for mismatch in &report.mismatches {
+        println!("{}\n", mismatch);
+    }
+    for failure in &report.failures {
+        println!("{}\n", failure);
+    }
+    println!(
+        "Replayed {} messages, compared {} responses, {} differ, {} failed",
+        report.replayed,
+        report.compared,
+        report.mismatches.len(),
+        report.failures.len()
+    );
+
+    if report.is_clean() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} responses differ and {} requests failed replaying {}",
+            report.mismatches.len(),
+            report.failures.len(),
+            path
+        )
+        .into())
+    }
+}
+
 fn generate_data_dictionary(output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
     use dsl_lsp::data_dictionary::DataDictionary;
     use std::fs;
@@ -0,0 +1,490 @@
+//! Quick fixes for problems in a rule document
+//!
+//! `unknown_names` finds the function calls and attributes the catalogue and
+//! dictionary don't know, which `validate_document` reports as diagnostics.
+//! The fixes are worked out from the document text alone and come back as
+//! byte offsets and replacement text; the code_action handler turns them into
+//! WorkspaceEdits. Adding a missing attribute edits the dictionary's
+//! entities.json rather than the rule file, and moving an inline list into a
+//! lookup table adds the table to its lookups.json.
+
+use crate::data_dictionary::{Attribute, DataDictionary, LookupTable};
+use data_designer::error_codes::ErrorCode;
+use data_designer::models::{BinaryOperator, Expression};
+use data_designer::parser::{parse_rules_recovering, ParsedRule};
+use data_designer::rule_categories::called_functions;
+use data_designer::rule_graph::extract_dependencies_from_ast;
+use data_designer::type_checker::{typecheck_with_env, RuleType, TypeEnv};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Replaces `text[start..end]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextFix {
+    pub start: usize,
+    pub end: usize,
+    pub new_text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum UnknownKind {
+    /// The closest function in the catalogue, if any is close enough
+    Function { suggestion: Option<String> },
+    /// What the rule uses it as, and the entity it most likely belongs to
+    Attribute { expected: RuleType, entity: Option<String> },
+}
+
+/// A name the rule reads that nothing defines, at `text[start..end]`.
+/// Carried as the diagnostic's data so the code action can offer its fixes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnknownName {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+    #[serde(flatten)]
+    pub kind: UnknownKind,
+}
+
+impl UnknownName {
+    pub fn code(&self) -> ErrorCode {
+        match self.kind {
+            UnknownKind::Function { .. } => ErrorCode::UnknownFunction,
+            UnknownKind::Attribute { .. } => ErrorCode::UnknownAttribute,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match &self.kind {
+            UnknownKind::Function { suggestion: Some(suggestion) } => {
+                format!("Unknown function '{}'; did you mean {}?", self.name, suggestion)
+            }
+            UnknownKind::Function { suggestion: None } => format!("Unknown function '{}'", self.name),
+            UnknownKind::Attribute { .. } => {
+                format!("'{}' is not in the data dictionary and no rule in this document assigns it", self.name)
+            }
+        }
+    }
+
+    /// The name as a string literal, for a bare word meant as one: `status == Active`
+    pub fn quote_fix(&self) -> TextFix {
+        TextFix {
+            start: self.start,
+            end: self.end,
+            new_text: format!("\"{}\"", self.name),
+        }
+    }
+
+    /// The call renamed to the suggested function
+    pub fn rename_fix(&self) -> Option<TextFix> {
+        match &self.kind {
+            UnknownKind::Function { suggestion: Some(suggestion) } => Some(TextFix {
+                start: self.start,
+                end: self.end,
+                new_text: suggestion.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Calls to functions not in `functions` and reads of attributes that are
+/// neither in the dictionary nor assigned by one of `rules`, each occurrence
+/// separately, in document order
+pub fn unknown_names(text: &str, rules: &[ParsedRule], dictionary: &DataDictionary, functions: &[String]) -> Vec<UnknownName> {
+    let env = dictionary.type_env();
+    let assigned: HashSet<&str> = rules
+        .iter()
+        .filter_map(|rule| match &rule.expression {
+            Expression::Assignment { target, .. } => Some(target.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    let mut unknown = Vec::new();
+    for rule in rules {
+        let rule_text = &text[rule.start..rule.end];
+        let mut found = Vec::new();
+
+        for function in called_functions(&rule.expression) {
+            if functions.iter().any(|known| known.eq_ignore_ascii_case(&function)) {
+                continue;
+            }
+            let suggestion = nearest_name(&function, functions.iter().map(String::as_str)).map(str::to_string);
+            for (start, end) in name_sites(rule_text, &function, true) {
+                found.push(UnknownName {
+                    name: rule_text[start..end].to_string(),
+                    start: rule.start + start,
+                    end: rule.start + end,
+                    kind: UnknownKind::Function { suggestion: suggestion.clone() },
+                });
+            }
+        }
+
+        let reads = extract_dependencies_from_ast(&rule.expression);
+        let mut expected = HashMap::new();
+        expected_types(&rule.expression, &env, &mut expected);
+        for name in reads.iter().filter(|name| env.get(name).is_none() && !assigned.contains(name.as_str())) {
+            let kind = UnknownKind::Attribute {
+                expected: expected.get(name).copied().unwrap_or_default(),
+                entity: attribute_entity(name, &reads, dictionary).map(|(entity, _)| entity),
+            };
+            for (start, end) in name_sites(rule_text, name, false) {
+                found.push(UnknownName {
+                    name: name.clone(),
+                    start: rule.start + start,
+                    end: rule.start + end,
+                    kind: kind.clone(),
+                });
+            }
+        }
+
+        found.sort_by_key(|name| name.start);
+        unknown.extend(found);
+    }
+    unknown
+}
+
+/// Closing parens for the rule that failed to parse on `line`, appended
+/// after its last token; only offered when the document then parses further
+pub fn close_parens_fix(text: &str, line: usize) -> Option<TextFix> {
+    let (rules, errors) = parse_rules_recovering(text);
+    let error = errors.iter().find(|error| error.line == line)?;
+
+    // The failed statement runs from the end of whatever parsed before it to where parsing resumed
+    let start = rules
+        .iter()
+        .map(|rule| rule.end)
+        .chain(errors.iter().map(|error| error.resume_offset))
+        .filter(|end| *end <= error.offset)
+        .max()
+        .unwrap_or(0);
+    let statement = mask_literals(&text[start..error.resume_offset]);
+    let missing = statement.matches('(').count().checked_sub(statement.matches(')').count())?;
+    if missing == 0 {
+        return None;
+    }
+
+    let at = start + statement.trim_end().len();
+    let fix = TextFix { start: at, end: at, new_text: ")".repeat(missing) };
+    let fixed = format!("{}{}{}", &text[..at], fix.new_text, &text[at..]);
+    (parse_rules_recovering(&fixed).1.len() < errors.len()).then_some(fix)
+}
+
+/// `entities_json`, the dictionary's entities.json, with `attribute` added to
+/// `entity`. Everything else is kept, though the keys come out sorted.
+pub fn add_attribute_to_entities(entities_json: &str, entity: &str, attribute: &Attribute) -> Result<String, String> {
+    let mut entities: serde_json::Value =
+        serde_json::from_str(entities_json).map_err(|e| format!("Invalid entities.json: {}", e))?;
+    let attributes = entities
+        .get_mut(entity)
+        .and_then(|entity| entity.get_mut("attributes"))
+        .and_then(|attributes| attributes.as_array_mut())
+        .ok_or_else(|| format!("No entity '{}' in entities.json", entity))?;
+    if attributes.iter().any(|existing| existing["name"] == attribute.name.as_str()) {
+        return Err(format!("'{}' already has an attribute '{}'", entity, attribute.name));
+    }
+    attributes.push(serde_json::to_value(attribute).map_err(|e| e.to_string())?);
+    serde_json::to_string_pretty(&entities).map(|json| json + "\n").map_err(|e| e.to_string())
+}
+
+/// `lookups_json`, the dictionary's lookups.json, with `table` added under its name
+pub fn add_lookup_to_lookups(lookups_json: &str, table: &LookupTable) -> Result<String, String> {
+    let mut lookups: serde_json::Value =
+        serde_json::from_str(lookups_json).map_err(|e| format!("Invalid lookups.json: {}", e))?;
+    let lookups = lookups.as_object_mut().ok_or("lookups.json is not an object")?;
+    if lookups.contains_key(&table.name) {
+        return Err(format!("There is already a lookup table '{}'", table.name));
+    }
+    lookups.insert(table.name.clone(), serde_json::to_value(table).map_err(|e| e.to_string())?);
+    serde_json::to_string_pretty(lookups).map(|json| json + "\n").map_err(|e| e.to_string())
+}
+
+/// The entity to add an unknown attribute to, with the attribute's own name:
+/// the entity its prefix names (`Client.segment`), else the one holding most
+/// of the other attributes the rule reads
+pub fn attribute_entity(name: &str, reads: &[String], dictionary: &DataDictionary) -> Option<(String, String)> {
+    if let Some((prefix, attribute)) = name.rsplit_once('.') {
+        return dictionary
+            .entities
+            .keys()
+            .find(|entity| entity.eq_ignore_ascii_case(prefix))
+            .map(|entity| (entity.clone(), attribute.to_string()));
+    }
+
+    let holds = |entity: &str, read: &str| {
+        dictionary.entities[entity]
+            .attributes
+            .iter()
+            .any(|attribute| attribute.name == read || format!("{}.{}", entity, attribute.name) == read)
+    };
+    let mut entities: Vec<&String> = dictionary.entities.keys().collect();
+    entities.sort();
+    entities
+        .into_iter()
+        .map(|entity| (reads.iter().filter(|read| holds(entity, read)).count(), entity))
+        .filter(|(count, _)| *count > 0 || dictionary.entities.len() == 1)
+        .max_by(|(a, first), (b, second)| a.cmp(b).then(second.cmp(first)))
+        .map(|(_, entity)| (entity.clone(), name.to_string()))
+}
+
+/// The candidate closest to `name` ignoring case, if it's within a third of
+/// its length in edits (a swap of neighbours counts as one)
+pub fn nearest_name<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let name = name.to_uppercase();
+    let allowed = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(&name, &candidate.to_uppercase()), candidate))
+        .filter(|(distance, _)| *distance <= allowed)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut distances: Vec<Vec<usize>> = (0..=a.len()).map(|i| vec![i; b.len() + 1]).collect();
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = substitution.min(distances[i - 1][j] + 1).min(distances[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+// Where `name` occurs in `text` as a whole word outside strings and comments;
+// function calls match ignoring case and only when followed by `(`
+pub(crate) fn name_sites(text: &str, name: &str, call: bool) -> Vec<(usize, usize)> {
+    let masked = mask_literals(text);
+    let (haystack, needle) = if call {
+        (masked.to_ascii_uppercase(), name.to_ascii_uppercase())
+    } else {
+        (masked, name.to_string())
+    };
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+    haystack
+        .match_indices(&needle)
+        .map(|(start, _)| (start, start + needle.len()))
+        .filter(|&(start, end)| {
+            !haystack[..start].ends_with(is_word)
+                && !haystack[end..].starts_with(is_word)
+                && haystack[end..].trim_start().starts_with('(') == call
+        })
+        .collect()
+}
+
+// `text` with string contents and comments blanked out byte for byte, so
+// offsets still line up but nothing inside them looks like code
+fn mask_literals(text: &str) -> String {
+    let blank = |c: char| if c == '\n' { "\n".to_string() } else { " ".repeat(c.len_utf8()) };
+    let mut masked = String::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let rest = &text[i..];
+        if matches!(c, '"' | '\'' | '`') {
+            masked.push(c);
+            while let Some((_, next)) = chars.next() {
+                if next == c {
+                    masked.push(c);
+                    break;
+                }
+                masked.push_str(&blank(next));
+                if next == '\\' && c != '\'' {
+                    if let Some((_, escaped)) = chars.next() {
+                        masked.push_str(&blank(escaped));
+                    }
+                }
+            }
+        } else if rest.starts_with('#') || rest.starts_with("//") {
+            masked.push_str(&blank(c));
+            while let Some((_, next)) = chars.next_if(|(_, next)| *next != '\n') {
+                masked.push_str(&blank(next));
+            }
+        } else if rest.starts_with("/*") {
+            let end = rest.find("*/").map_or(text.len(), |end| i + end + 2);
+            masked.push_str(&blank(c));
+            while let Some((_, next)) = chars.next_if(|(index, _)| *index < end) {
+                masked.push_str(&blank(next));
+            }
+        } else {
+            masked.push(c);
+        }
+    }
+    masked
+}
+
+// The type each unknown name is used as, judged from what it's compared or
+// combined with: `status == Active` makes `Active` a String when `status` is one
+fn expected_types(expr: &Expression, env: &TypeEnv, expected: &mut HashMap<String, RuleType>) {
+    let unknown = |expr: &Expression| match expr {
+        Expression::Identifier(name) | Expression::Variable(name) if env.get(name).is_none() => Some(name.clone()),
+        _ => None,
+    };
+    let type_of = |expr: &Expression| typecheck_with_env(expr, env).inferred;
+    let mut expect = |expr: &Expression, rule_type: RuleType| {
+        if let Some(name) = unknown(expr) {
+            if matches!(rule_type, RuleType::String | RuleType::Number | RuleType::Boolean | RuleType::Date) {
+                expected.entry(name).or_insert(rule_type);
+            }
+        }
+    };
+    // The type of the first item that has one, for the others to match
+    let item_type = |items: &[Expression]| {
+        items
+            .iter()
+            .map(type_of)
+            .find(|rule_type| !matches!(rule_type, RuleType::Unknown | RuleType::Null))
+            .unwrap_or_default()
+    };
+
+    match expr {
+        Expression::BinaryOp { left, op: BinaryOperator::And | BinaryOperator::Or, right } => {
+            expect(left, RuleType::Boolean);
+            expect(right, RuleType::Boolean);
+        }
+        Expression::BinaryOp { left, op: BinaryOperator::In | BinaryOperator::NotIn, right } => {
+            if let Expression::List(items) = right.as_ref() {
+                expect(left, item_type(items));
+                let left_type = type_of(left);
+                items.iter().for_each(|item| expect(item, left_type));
+            }
+        }
+        Expression::BinaryOp { left, right, .. } => {
+            expect(left, type_of(right));
+            expect(right, type_of(left));
+        }
+        Expression::List(items) => {
+            let rule_type = item_type(items);
+            items.iter().for_each(|item| expect(item, rule_type));
+        }
+        Expression::UnaryOp { operand, .. } => expect(operand, type_of(expr)),
+        _ => {}
+    }
+    for child in children(expr) {
+        expected_types(child, env, expected);
+    }
+}
+
+fn children(expr: &Expression) -> Vec<&Expression> {
+    match expr {
+        Expression::BinaryOp { left, right, .. } => vec![left, right],
+        Expression::Range { start, end, .. } => vec![start, end],
+        Expression::UnaryOp { operand, .. } => vec![operand],
+        Expression::Assignment { value, .. } => vec![value],
+        Expression::Cast { expr, .. } => vec![expr],
+        Expression::Lambda { body, .. } => vec![body],
+        Expression::Conditional { condition, then_expr, else_expr } => {
+            let mut parts: Vec<&Expression> = vec![condition, then_expr];
+            parts.extend(else_expr.as_deref());
+            parts
+        }
+        Expression::Case { branches, else_expr } => {
+            let mut parts: Vec<&Expression> = branches.iter().flat_map(|(condition, result)| [condition, result]).collect();
+            parts.extend(else_expr.as_deref());
+            parts
+        }
+        Expression::Comprehension { element, source, condition, .. } => {
+            let mut parts: Vec<&Expression> = vec![element, source];
+            parts.extend(condition.as_deref());
+            parts
+        }
+        Expression::FunctionCall { args: items, .. }
+        | Expression::List(items)
+        | Expression::ConfigureSystem { arguments: items, .. }
+        | Expression::Activate { arguments: items, .. }
+        | Expression::RunHealthCheck { arguments: items, .. }
+        | Expression::Workflow { steps: items, .. } => items.iter().collect(),
+        Expression::Literal(_) | Expression::Variable(_) | Expression::Identifier(_) | Expression::SetStatus { .. } => {
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn functions() -> Vec<String> {
+        ["CONCAT", "UPPER", "LOOKUP", "ROUND"].iter().map(|name| name.to_string()).collect()
+    }
+
+    fn apply(text: &str, fix: &TextFix) -> String {
+        format!("{}{}{}", &text[..fix.start], fix.new_text, &text[fix.end..])
+    }
+
+    #[test]
+    fn test_unknown_function_suggests_the_nearest_name() {
+        let text = "greeting = CONCTA(\"Hello \", legal_entity_name) // not concta(\n";
+        let (rules, _) = parse_rules_recovering(text);
+        let dictionary = DataDictionary::create_default_kyc_dictionary();
+
+        let unknown = unknown_names(text, &rules, &dictionary, &functions());
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].message(), "Unknown function 'CONCTA'; did you mean CONCAT?");
+        let fix = unknown[0].rename_fix().unwrap();
+        assert_eq!(apply(text, &fix), text.replacen("CONCTA", "CONCAT", 1));
+
+        assert_eq!(nearest_name("rund", functions().iter().map(String::as_str)), Some("ROUND"));
+        assert_eq!(nearest_name("FOO", functions().iter().map(String::as_str)), None);
+    }
+
+    #[test]
+    fn test_unknown_attributes_carry_their_expected_type_and_entity() {
+        let text = "is_high = risk_rating == HIGH AND segment_code > 3\nflag = is_high OR Client.segment";
+        let (rules, _) = parse_rules_recovering(text);
+        let dictionary = DataDictionary::create_default_kyc_dictionary();
+
+        let unknown = unknown_names(text, &rules, &dictionary, &functions());
+        let found: Vec<(&str, &UnknownKind)> = unknown.iter().map(|name| (name.name.as_str(), &name.kind)).collect();
+        let client = Some("Client".to_string());
+        assert_eq!(
+            found,
+            [
+                ("HIGH", &UnknownKind::Attribute { expected: RuleType::String, entity: client.clone() }),
+                ("segment_code", &UnknownKind::Attribute { expected: RuleType::Number, entity: client.clone() }),
+                ("Client.segment", &UnknownKind::Attribute { expected: RuleType::Boolean, entity: client }),
+            ]
+        );
+        assert_eq!(apply(text, &unknown[0].quote_fix()), text.replacen("HIGH", "\"HIGH\"", 1));
+    }
+
+    #[test]
+    fn test_missing_close_parens_are_appended_after_the_last_token() {
+        let text = "total = ROUND((amount * rate), 2 # \"(\" ignored\n\nok = 1";
+        let fix = close_parens_fix(text, 0).unwrap();
+        assert_eq!(fix.new_text, ")");
+        assert_eq!(apply(text, &fix), "total = ROUND((amount * rate), 2) # \"(\" ignored\n\nok = 1");
+        assert!(close_parens_fix("ok = 1", 0).is_none());
+        assert!(close_parens_fix("bad = (1 +\n", 0).is_none());
+    }
+
+    #[test]
+    fn test_attribute_is_added_to_entities_json() {
+        let json = r#"{"Client": {"name": "Client", "description": "", "attributes": [], "business_rules": []}}"#;
+        let updated = add_attribute_to_entities(json, "Client", &Attribute::new("segment", RuleType::Number)).unwrap();
+        let entities: HashMap<String, crate::data_dictionary::Entity> = serde_json::from_str(&updated).unwrap();
+        assert_eq!(entities["Client"].attributes[0].data_type.rule_type(), RuleType::Number);
+
+        assert!(add_attribute_to_entities(&updated, "Client", &Attribute::new("segment", RuleType::String)).is_err());
+        assert!(add_attribute_to_entities(json, "Fund", &Attribute::new("segment", RuleType::String)).is_err());
+    }
+
+    #[test]
+    fn test_lookup_table_is_added_to_lookups_json() {
+        let table = LookupTable::of_values("sanctioned", "Sanctioned countries", ["RU", "IR"]);
+        let updated = add_lookup_to_lookups("{}", &table).unwrap();
+        let lookups: HashMap<String, LookupTable> = serde_json::from_str(&updated).unwrap();
+        assert_eq!(lookups["sanctioned"].entries["IR"], "IR");
+
+        assert!(add_lookup_to_lookups(&updated, &table).is_err());
+        assert!(add_lookup_to_lookups("[]", &table).is_err());
+    }
+}
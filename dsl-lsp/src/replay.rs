@@ -0,0 +1,341 @@
+//! Replays LSP traffic captured from real editing sessions and diffs the responses
+//!
+//! Captures come from the VS Code extension with JSON tracing switched on
+//! (`"dsl.trace.server": { "verbosity": "verbose", "format": "json" }`); the
+//! output channel then holds one message per line:
+//!
+//! ```text
+//! [LSP   - 10:41:07 AM] {"isLSPMessage":true,"type":"send-request","message":{...},"timestamp":...}
+//! ```
+//!
+//! The client's requests and notifications are sent to a fresh server in
+//! capture order, and each response is compared with the one the editor
+//! received. Anything that is not a traced message is skipped, so a whole
+//! output channel can be saved as is. Workspace roots are removed from
+//! `initialize`, so a replay never reads or writes the recording machine's
+//! folders and answers the same wherever it runs.
+//!
+//! Sessions written by `crate::session_recorder` use the same format but hold
+//! no responses. Replaying one reports the requests that failed, and with
+//! `realtime` it keeps the recorded pauses between messages.
+
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use tower::{Service, ServiceExt};
+use tower_lsp::jsonrpc::{Request, Response};
+use tower_lsp::{ClientSocket, LanguageServer, LspService};
+
+/// JSON-RPC error code of a request the editor cancelled; its timing can't be replayed
+const REQUEST_CANCELLED: i64 = -32800;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Direction {
+    SendRequest,
+    SendNotification,
+    SendResponse,
+    ReceiveRequest,
+    ReceiveNotification,
+    ReceiveResponse,
+}
+
+/// One traced message; `send-*` went from the editor to the server
+#[derive(Debug, Clone, Deserialize)]
+pub struct CapturedMessage {
+    #[serde(rename = "type")]
+    pub direction: Direction,
+    pub message: Value,
+    /// Milliseconds since the epoch when it was traced
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+}
+
+/// Reads the traced messages of a captured output channel
+pub fn parse_capture(capture: &str) -> Result<Vec<CapturedMessage>, String> {
+    let messages: Vec<CapturedMessage> = capture
+        .lines()
+        .filter_map(|line| line.find('{').map(|start| &line[start..]))
+        .filter_map(|json| serde_json::from_str(json).ok())
+        .collect();
+
+    if !messages.iter().any(|m| m.direction == Direction::SendRequest) {
+        return Err("Capture holds no client requests; record it with JSON tracing (\"format\": \"json\")".to_string());
+    }
+    Ok(messages)
+}
+
+/// A response that differs from the captured one
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub method: String,
+    pub id: Value,
+    /// JSON pointer to the first difference, e.g. `/result/contents/value`
+    pub path: String,
+    pub expected: Value,
+    pub actual: Value,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} (id {}) differs at {}", self.method, self.id, if self.path.is_empty() { "/" } else { self.path.as_str() })?;
+        writeln!(f, "--- captured")?;
+        writeln!(f, "{}", serde_json::to_string_pretty(&self.expected).unwrap_or_default())?;
+        writeln!(f, "+++ replayed")?;
+        write!(f, "{}", serde_json::to_string_pretty(&self.actual).unwrap_or_default())
+    }
+}
+
+/// A request answered with an error that the capture has no response to compare with
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub method: String,
+    pub id: Value,
+    pub error: Value,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (id {}) failed: {}", self.method, self.id, self.error)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReplayReport {
+    /// Client messages sent to the server
+    pub replayed: usize,
+    /// Responses compared with the capture
+    pub compared: usize,
+    pub mismatches: Vec<Mismatch>,
+    pub failures: Vec<Failure>,
+}
+
+impl ReplayReport {
+    pub fn is_clean(&self) -> bool {
+        self.mismatches.is_empty() && self.failures.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReplayOptions {
+    /// Wait between messages as long as the editor did, from their timestamps
+    pub realtime: bool,
+}
+
+/// Sends the client side of `capture` to `service` and diffs its responses
+pub async fn replay<S: LanguageServer>(
+    service: LspService<S>,
+    socket: ClientSocket,
+    capture: &[CapturedMessage],
+) -> ReplayReport {
+    replay_with(service, socket, capture, ReplayOptions::default()).await
+}
+
+pub async fn replay_with<S: LanguageServer>(
+    mut service: LspService<S>,
+    socket: ClientSocket,
+    capture: &[CapturedMessage],
+    options: ReplayOptions,
+) -> ReplayReport {
+    // The server's own requests (configuration, capability registration)
+    // get empty answers, and its notifications are dropped
+    let (mut server_requests, mut answers) = socket.split();
+    let editor = tokio::spawn(async move {
+        while let Some(request) = server_requests.next().await {
+            if let Some(id) = request.id().cloned() {
+                if answers.send(Response::from_ok(id, Value::Null)).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let captured_responses: HashMap<String, &Value> = capture
+        .iter()
+        .filter(|m| m.direction == Direction::ReceiveResponse)
+        .filter_map(|m| Some((m.message.get("id")?.to_string(), &m.message)))
+        .collect();
+
+    let mut report = ReplayReport::default();
+    let mut previous_timestamp = None;
+    for captured in capture {
+        if !matches!(captured.direction, Direction::SendRequest | Direction::SendNotification) {
+            continue;
+        }
+        if options.realtime {
+            if let (Some(previous), Some(timestamp)) = (previous_timestamp, captured.timestamp) {
+                tokio::time::sleep(std::time::Duration::from_millis(timestamp.saturating_sub(previous))).await;
+            }
+            previous_timestamp = captured.timestamp.or(previous_timestamp);
+        }
+        let mut message = captured.message.clone();
+        let method = message.get("method").and_then(Value::as_str).unwrap_or_default().to_string();
+        if method == "initialize" {
+            forget_workspace(&mut message);
+        }
+        let Ok(request) = serde_json::from_value::<Request>(message) else {
+            continue;
+        };
+
+        let response = match service.ready().await {
+            Ok(ready) => ready.call(request).await,
+            Err(e) => Err(e),
+        };
+        let Ok(response) = response else {
+            // The server exited; nothing after this can be answered
+            break;
+        };
+        report.replayed += 1;
+
+        let Some(id) = captured.message.get("id") else {
+            continue;
+        };
+        let Some(expected) = captured_responses.get(&id.to_string()) else {
+            let error = response.and_then(|r| serde_json::to_value(r).ok()).and_then(|r| r.get("error").cloned());
+            if let Some(error) = error {
+                report.failures.push(Failure { method, id: id.clone(), error });
+            }
+            continue;
+        };
+        if expected.pointer("/error/code").and_then(Value::as_i64) == Some(REQUEST_CANCELLED) {
+            continue;
+        }
+
+        let expected = normalise(response_body(expected));
+        let actual = normalise(response.map_or(Value::Null, |r| response_body(&serde_json::to_value(r).unwrap_or_default())));
+        report.compared += 1;
+        if let Some(path) = first_difference(&expected, &actual, String::new()) {
+            report.mismatches.push(Mismatch { method, id: id.clone(), path, expected, actual });
+        }
+    }
+
+    editor.abort();
+    report
+}
+
+fn forget_workspace(initialize: &mut Value) {
+    if let Some(params) = initialize.get_mut("params").and_then(Value::as_object_mut) {
+        for key in ["rootUri", "rootPath", "workspaceFolders"] {
+            if params.contains_key(key) {
+                params.insert(key.to_string(), Value::Null);
+            }
+        }
+    }
+}
+
+/// The `result` or `error` of a response, without the envelope
+fn response_body(response: &Value) -> Value {
+    let mut body = serde_json::Map::new();
+    for key in ["result", "error"] {
+        if let Some(value) = response.get(key) {
+            body.insert(key.to_string(), value.clone());
+        }
+    }
+    Value::Object(body)
+}
+
+/// Puts completion items in label order; the server builds some of them
+/// from hash maps, so their order isn't part of the behaviour under test
+fn normalise(value: Value) -> Value {
+    match value {
+        Value::Array(items) => {
+            let mut items: Vec<Value> = items.into_iter().map(normalise).collect();
+            if !items.is_empty() && items.iter().all(|item| item.get("label").is_some_and(Value::is_string)) {
+                items.sort_by_key(|item| (item["label"].as_str().unwrap_or_default().to_string(), item.to_string()));
+            }
+            Value::Array(items)
+        }
+        Value::Object(fields) => Value::Object(fields.into_iter().map(|(key, value)| (key, normalise(value))).collect()),
+        other => other,
+    }
+}
+
+/// JSON pointer to the first place `actual` departs from `expected`
+fn first_difference(expected: &Value, actual: &Value, path: String) -> Option<String> {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            let mut keys: Vec<&String> = expected.keys().chain(actual.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            keys.into_iter().find_map(|key| {
+                let path = format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"));
+                match (expected.get(key), actual.get(key)) {
+                    (Some(e), Some(a)) => first_difference(e, a, path),
+                    _ => Some(path),
+                }
+            })
+        }
+        (Value::Array(e), Value::Array(a)) => e
+            .iter()
+            .zip(a)
+            .enumerate()
+            .find_map(|(i, (e, a))| first_difference(e, a, format!("{}/{}", path, i)))
+            .or_else(|| (e.len() != a.len()).then(|| format!("{}/{}", path, e.len().min(a.len())))),
+        _ => (expected != actual).then_some(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Backend;
+
+    const COMPLETION_AND_HOVER: &str = include_str!("../tests/captures/completion_and_hover.log");
+
+    #[tokio::test]
+    async fn test_captured_session_replays_cleanly() {
+        let capture = parse_capture(COMPLETION_AND_HOVER).unwrap();
+        let (service, socket) = LspService::new(Backend::new);
+        let report = replay(service, socket, &capture).await;
+
+        let diffs: Vec<String> = report.mismatches.iter().map(|m| m.to_string()).collect();
+        assert!(report.is_clean(), "{}", diffs.join("\n\n"));
+        assert_eq!(report.compared, 6);
+    }
+
+    #[tokio::test]
+    async fn test_changed_hover_is_reported_with_its_path() {
+        let capture = COMPLETION_AND_HOVER.replace("**Keyword: IF**", "**Keyword: IF** (conditional)");
+        let capture = parse_capture(&capture).unwrap();
+        let (service, socket) = LspService::new(Backend::new);
+        let report = replay(service, socket, &capture).await;
+
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].method, "textDocument/hover");
+        assert_eq!(report.mismatches[0].path, "/result/contents/value");
+    }
+
+    #[test]
+    fn test_text_traces_are_rejected() {
+        let capture = "[Trace - 10:41:07 AM] Sending request 'textDocument/hover - (3)'.\nParams: {\n}\n";
+        assert!(parse_capture(capture).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_recorded_session_replays_and_reports_failed_requests() {
+        let path = std::env::temp_dir().join(format!("dsl-lsp-replay-{}.jsonl", std::process::id()));
+        let recorder = crate::session_recorder::SessionRecorder::new();
+        let uri = tower_lsp::lsp_types::Url::parse("file:///rules/fees.dsl").unwrap();
+        recorder.start(&path, std::iter::empty()).unwrap();
+        recorder.open(&uri, "dsl", 1, "fee = aum_usd * 0.01 # for \"Acme\"");
+        recorder.change(&uri, 2, "fee = aum_usd * 0.02");
+        recorder.command("dsl.loadTestContext", &[serde_json::json!({ "Client.aum_usd": 25000 })]);
+        recorder.stop();
+        let recorded = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // A request the server doesn't know, just before the recorded shutdown
+        let mut lines: Vec<&str> = recorded.lines().collect();
+        let unknown = r#"{"isLSPMessage":true,"type":"send-request","message":{"jsonrpc":"2.0","id":99,"method":"dsl/noSuchRequest","params":{}}}"#;
+        lines.insert(lines.len() - 1, unknown);
+
+        let (service, socket) = crate::service();
+        let capture = parse_capture(&lines.join("\n")).unwrap();
+        let report = replay_with(service, socket, &capture, ReplayOptions { realtime: true }).await;
+        assert_eq!((report.replayed, report.compared), (7, 0));
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].method, "dsl/noSuchRequest");
+    }
+}
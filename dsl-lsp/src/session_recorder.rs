@@ -0,0 +1,301 @@
+//! Opt-in recording of editing sessions, for reproducing bugs analysts report
+//!
+//! `dsl.startSessionRecording` starts writing the editor's side of the
+//! session (documents opened, edited, saved and closed, and the commands run)
+//! to a file in the format `replay` reads. A developer replays it against a
+//! fresh server with `dsl-lsp-server replay <file> --realtime`, which keeps
+//! the original pauses between messages so debounced work interleaves the
+//! same way. `dsl.stopSessionRecording` closes the file.
+//!
+//! Recordings are redacted as they are written. Documents get aliased URIs,
+//! letters and digits inside string literals and comments are masked, and so
+//! is every string and number in command arguments other than the URI of a
+//! recorded document, which becomes its alias. Attribute names, function names
+//! and the layout of each rule are kept, so the rules parse as they did.
+//! Commands that read a file named in their arguments fail on replay, because
+//! the path is masked too.
+
+use data_designer::semantic_tokens::{tokenize, TokenKind};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tower_lsp::lsp_types::Url;
+
+/// Where `dsl.startSessionRecording` writes when given no path, under the workspace root
+pub const SESSIONS_DIR: &str = ".dsl-lsp/sessions";
+
+#[derive(Debug, Default)]
+pub struct SessionRecorder {
+    recording: Mutex<Option<Recording>>,
+}
+
+#[derive(Debug)]
+struct Recording {
+    path: PathBuf,
+    out: BufWriter<File>,
+    next_id: i64,
+    /// Real document URIs and the aliases they are recorded under
+    aliases: HashMap<Url, Url>,
+}
+
+impl SessionRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.lock().map(|recording| recording.is_some()).unwrap_or(false)
+    }
+
+    /// Starts a recording at `path`, replacing any under way. It opens with an
+    /// `initialize` handshake and then opens the `documents` already open.
+    pub fn start<'a>(&self, path: &Path, documents: impl IntoIterator<Item = (&'a Url, &'a str)>) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut recording = Recording {
+            path: path.to_path_buf(),
+            out: BufWriter::new(File::create(path)?),
+            next_id: 0,
+            aliases: HashMap::new(),
+        };
+        recording.request("initialize", json!({ "processId": null, "rootUri": null, "capabilities": {} }))?;
+        recording.notification("initialized", json!({}))?;
+        for (uri, text) in documents {
+            recording.open(uri, "dsl", 0, text)?;
+        }
+        recording.out.flush()?;
+        *self.recording.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(recording);
+        Ok(())
+    }
+
+    /// Ends the recording under way, returning where it was written
+    pub fn stop(&self) -> Option<PathBuf> {
+        let mut recording = self.recording.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take()?;
+        if let Err(e) = recording.request("shutdown", Value::Null).and_then(|_| recording.out.flush()) {
+            log::warn!("Session recording {} is incomplete: {}", recording.path.display(), e);
+        }
+        Some(recording.path)
+    }
+
+    pub fn open(&self, uri: &Url, language_id: &str, version: i32, text: &str) {
+        self.write(|recording| recording.open(uri, language_id, version, text));
+    }
+
+    pub fn change(&self, uri: &Url, version: i32, text: &str) {
+        self.write(|recording| {
+            let uri = recording.alias(uri);
+            recording.notification(
+                "textDocument/didChange",
+                json!({
+                    "textDocument": { "uri": uri, "version": version },
+                    "contentChanges": [{ "text": redact_document(text) }],
+                }),
+            )
+        });
+    }
+
+    pub fn save(&self, uri: &Url, text: Option<&str>) {
+        self.write(|recording| {
+            let uri = recording.alias(uri);
+            let mut params = json!({ "textDocument": { "uri": uri } });
+            if let Some(text) = text {
+                params["text"] = Value::String(redact_document(text));
+            }
+            recording.notification("textDocument/didSave", params)
+        });
+    }
+
+    pub fn close(&self, uri: &Url) {
+        self.write(|recording| {
+            let uri = recording.alias(uri);
+            recording.notification("textDocument/didClose", json!({ "textDocument": { "uri": uri } }))
+        });
+    }
+
+    pub fn command(&self, command: &str, arguments: &[Value]) {
+        self.write(|recording| {
+            let arguments: Vec<Value> = arguments.iter().map(|argument| recording.redact_argument(argument)).collect();
+            recording.request("workspace/executeCommand", json!({ "command": command, "arguments": arguments }))
+        });
+    }
+
+    // A recording that can't be written is dropped rather than left half-written
+    fn write(&self, record: impl FnOnce(&mut Recording) -> std::io::Result<()>) {
+        let mut guard = self.recording.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(recording) = guard.as_mut() else {
+            return;
+        };
+        if let Err(e) = record(recording).and_then(|_| recording.out.flush()) {
+            log::warn!("Stopped recording session to {}: {}", recording.path.display(), e);
+            *guard = None;
+        }
+    }
+}
+
+impl Recording {
+    fn open(&mut self, uri: &Url, language_id: &str, version: i32, text: &str) -> std::io::Result<()> {
+        let uri = self.alias(uri);
+        self.notification(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": { "uri": uri, "languageId": language_id, "version": version, "text": redact_document(text) },
+            }),
+        )
+    }
+
+    // Null params are left out: servers reject any params on requests such as shutdown
+    fn request(&mut self, method: &str, params: Value) -> std::io::Result<()> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut message = json!({ "jsonrpc": "2.0", "id": id, "method": method });
+        if !params.is_null() {
+            message["params"] = params;
+        }
+        self.line("send-request", message)
+    }
+
+    fn notification(&mut self, method: &str, params: Value) -> std::io::Result<()> {
+        self.line("send-notification", json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    // One traced message per line, as the editor's JSON tracing writes them
+    fn line(&mut self, direction: &str, message: Value) -> std::io::Result<()> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_millis() as u64).unwrap_or(0);
+        let line = json!({ "isLSPMessage": true, "type": direction, "message": message, "timestamp": timestamp });
+        writeln!(self.out, "{}", line)
+    }
+
+    /// Strings and numbers masked, object keys and the shape kept
+    fn redact_argument(&self, value: &Value) -> Value {
+        match value {
+            Value::String(text) => match Url::parse(text).ok().and_then(|uri| self.aliases.get(&uri)) {
+                Some(alias) => Value::String(alias.to_string()),
+                None => Value::String(text.chars().map(mask).collect()),
+            },
+            Value::Number(_) => json!(0),
+            Value::Array(items) => Value::Array(items.iter().map(|item| self.redact_argument(item)).collect()),
+            Value::Object(fields) => {
+                Value::Object(fields.iter().map(|(key, value)| (key.clone(), self.redact_argument(value))).collect())
+            }
+            other => other.clone(),
+        }
+    }
+
+    /// `file:///session/3.dsl` for the third document seen, keeping the extension
+    /// the server decides file kinds by
+    fn alias(&mut self, uri: &Url) -> Url {
+        let count = self.aliases.len();
+        self.aliases
+            .entry(uri.clone())
+            .or_insert_with(|| {
+                let extension = Path::new(uri.path())
+                    .extension()
+                    .and_then(|extension| extension.to_str())
+                    .map(|extension| format!(".{}", extension))
+                    .unwrap_or_default();
+                Url::parse(&format!("file:///session/{}{}", count + 1, extension)).expect("alias is a valid URL")
+            })
+            .clone()
+    }
+}
+
+/// `text` with the letters and digits of its string literals and comments
+/// masked as `x` and `0`. Quotes, escapes, interpolated names and line
+/// breaks stay put, so the rules parse as before and keep their layout.
+pub fn redact_document(text: &str) -> String {
+    let line_starts: Vec<usize> =
+        std::iter::once(0).chain(text.match_indices('\n').map(|(newline, _)| newline + 1)).collect();
+    let mut masked = vec![false; text.len()];
+    for token in tokenize(text) {
+        if matches!(token.kind, TokenKind::String | TokenKind::Comment) {
+            let start = line_starts[token.line as usize] + token.column as usize;
+            let end = (start + token.length as usize).min(text.len());
+            masked[start..end].iter_mut().for_each(|slot| *slot = true);
+        }
+    }
+
+    let mut redacted = String::with_capacity(text.len());
+    let mut escaped = false;
+    for (offset, c) in text.char_indices() {
+        // The character after a backslash is kept: `\n` must not become `\x`
+        if !masked[offset] || escaped {
+            escaped = false;
+            redacted.push(c);
+            continue;
+        }
+        escaped = c == '\\';
+        redacted.push(mask(c));
+    }
+    redacted
+}
+
+fn mask(c: char) -> char {
+    if c.is_numeric() {
+        '0'
+    } else if c.is_alphabetic() {
+        'x'
+    } else {
+        c
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::{parse_capture, Direction};
+
+    #[test]
+    fn test_strings_and_comments_are_masked_but_still_parse() {
+        let text = "# fee for Acme Holdings\nlabel = IF name == \"Acme \\\"UK\\\" 2\" THEN `Client ${name}` ELSE 'n/a'\n";
+        let redacted = redact_document(text);
+        assert_eq!(
+            redacted,
+            "# xxx xxx xxxx xxxxxxxx\nlabel = IF name == \"xxxx \\\"xx\\\" 0\" THEN `xxxxxx ${name}` ELSE 'x/x'\n"
+        );
+        assert!(data_designer::parser::parse_rules_recovering(&redacted).1.is_empty());
+    }
+
+    #[test]
+    fn test_recording_is_a_replayable_capture() {
+        let path = std::env::temp_dir().join(format!("dsl-lsp-session-{}.jsonl", std::process::id()));
+        let recorder = SessionRecorder::new();
+        let uri = Url::parse("file:///Users/analyst/acme/fees.dsl").unwrap();
+        recorder.start(&path, [(&uri, "fee = 1")]).unwrap();
+        assert!(recorder.is_recording());
+        recorder.change(&uri, 2, "fee = \"Acme\"");
+        recorder.command("dsl.loadTestContext", &[json!({ "Client.name": "Acme", "Client.aum_usd": 25000 })]);
+        recorder.command("dsl.explainRule", &[json!(uri.to_string())]);
+        assert_eq!(recorder.stop(), Some(path.clone()));
+        recorder.change(&uri, 3, "fee = 2");
+
+        let recorded = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(!recorded.contains("analyst") && !recorded.contains("Acme"));
+
+        let messages = parse_capture(&recorded).unwrap();
+        let methods: Vec<&str> = messages.iter().map(|m| m.message["method"].as_str().unwrap()).collect();
+        assert_eq!(
+            methods,
+            [
+                "initialize",
+                "initialized",
+                "textDocument/didOpen",
+                "textDocument/didChange",
+                "workspace/executeCommand",
+                "workspace/executeCommand",
+                "shutdown",
+            ]
+        );
+        assert_eq!(messages[3].message["params"]["textDocument"]["uri"], "file:///session/1.dsl");
+        assert_eq!(messages[3].message["params"]["contentChanges"][0]["text"], "fee = \"xxxx\"");
+        assert_eq!(messages[4].message["params"]["arguments"][0]["Client.aum_usd"], 0);
+        assert_eq!(messages[4].direction, Direction::SendRequest);
+        assert_eq!(messages[5].message["params"]["arguments"][0], "file:///session/1.dsl");
+    }
+}
@@ -0,0 +1,172 @@
+//! Settings a client can change
+//!
+//! Read from `initializationOptions` on startup and again on every
+//! `workspace/didChangeConfiguration`. Clients may send the settings on their
+//! own or under a `dsl` section, as VS Code does; anything left out keeps its
+//! default.
+//!
+//! ```json
+//! { "dsl": { "ai": { "enabled": false }, "maxDocumentSize": 262144,
+//!            "diagnostics": { "severity": { "DSL0007": "off", "DSL0005": "warning" } },
+//!            "dictionaryPath": "config/dictionary", "databaseUrl": "postgresql://localhost/rules",
+//!            "authToken": "dde_..." } }
+//! ```
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range};
+
+/// The section clients keep the settings under
+pub const SECTION: &str = "dsl";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub ai: AiSettings,
+    pub diagnostics: DiagnosticSettings,
+    /// Documents larger than this many bytes are not checked
+    pub max_document_size: usize,
+    /// A dictionary directory to load in place of the built-in KYC one
+    pub dictionary_path: Option<PathBuf>,
+    /// The rule database; without one the app's configuration is used
+    pub database_url: Option<String>,
+    /// The editor token scoping what the database-backed features reach
+    /// (see `data_designer::editor_access`)
+    pub auth_token: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            ai: AiSettings::default(),
+            diagnostics: DiagnosticSettings::default(),
+            max_document_size: 1024 * 1024,
+            dictionary_path: None,
+            database_url: None,
+            auth_token: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AiSettings {
+    /// Off, no agent is asked for validation, completions or the AI commands
+    pub enabled: bool,
+}
+
+impl Default for AiSettings {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DiagnosticSettings {
+    /// Severity by diagnostic code, in place of the server's own
+    pub severity: BTreeMap<String, SeverityLevel>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeverityLevel {
+    Error,
+    Warning,
+    #[serde(alias = "info")]
+    Information,
+    Hint,
+    /// Not reported at all
+    Off,
+}
+
+impl SeverityLevel {
+    fn severity(self) -> Option<DiagnosticSeverity> {
+        match self {
+            SeverityLevel::Error => Some(DiagnosticSeverity::ERROR),
+            SeverityLevel::Warning => Some(DiagnosticSeverity::WARNING),
+            SeverityLevel::Information => Some(DiagnosticSeverity::INFORMATION),
+            SeverityLevel::Hint => Some(DiagnosticSeverity::HINT),
+            SeverityLevel::Off => None,
+        }
+    }
+}
+
+impl Settings {
+    /// The settings in `value`, either on their own or under the `dsl`
+    /// section; null gives the defaults
+    pub fn from_value(value: &serde_json::Value) -> Result<Self, serde_json::Error> {
+        let section = value.get(SECTION).unwrap_or(value);
+        if section.is_null() {
+            return Ok(Self::default());
+        }
+        Self::deserialize(section)
+    }
+
+    /// Applies the configured severities, dropping the diagnostics turned off
+    pub fn map_severities(&self, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        let levels = &self.diagnostics.severity;
+        if levels.is_empty() {
+            return diagnostics;
+        }
+        diagnostics
+            .into_iter()
+            .filter_map(|mut diagnostic| {
+                let level = match &diagnostic.code {
+                    Some(NumberOrString::String(code)) => levels.get(code),
+                    Some(NumberOrString::Number(code)) => levels.get(&code.to_string()),
+                    None => None,
+                };
+                if let Some(level) = level {
+                    diagnostic.severity = Some(level.severity()?);
+                }
+                Some(diagnostic)
+            })
+            .collect()
+    }
+
+    /// What is reported instead of checking a document over `max_document_size`
+    pub fn document_too_large(&self, size: usize) -> Option<Diagnostic> {
+        (size > self.max_document_size).then(|| Diagnostic {
+            range: Range { start: Position { line: 0, character: 0 }, end: Position { line: 0, character: 0 } },
+            severity: Some(DiagnosticSeverity::INFORMATION),
+            source: Some("dsl-lsp".to_string()),
+            message: format!(
+                "Not checked: the document is {} bytes, over the {} of dsl.maxDocumentSize",
+                size, self.max_document_size
+            ),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_settings_are_read_with_or_without_their_section() {
+        let options = json!({ "dsl": { "ai": { "enabled": false }, "maxDocumentSize": 10, "diagnostics": { "severity": { "DSL0007": "off", "DSL0005": "info" } } } });
+        let settings = Settings::from_value(&options).unwrap();
+        assert!(!settings.ai.enabled);
+        assert_eq!(settings.max_document_size, 10);
+        assert_eq!(settings.dictionary_path, None);
+        assert_eq!(Settings::from_value(&options["dsl"]).unwrap(), settings);
+        assert_eq!(Settings::from_value(&serde_json::Value::Null).unwrap(), Settings::default());
+        assert!(Settings::from_value(&json!({ "maxDocumentSize": "big" })).is_err());
+
+        let diagnostic = |code: &str| Diagnostic {
+            code: Some(NumberOrString::String(code.to_string())),
+            severity: Some(DiagnosticSeverity::WARNING),
+            ..Default::default()
+        };
+        let mapped = settings.map_severities(vec![diagnostic("DSL0005"), diagnostic("DSL0007"), diagnostic("DSL0003")]);
+        let severities: Vec<_> = mapped.iter().map(|d| d.severity.unwrap()).collect();
+        assert_eq!(severities, [DiagnosticSeverity::INFORMATION, DiagnosticSeverity::WARNING]);
+
+        assert!(settings.document_too_large(10).is_none());
+        assert!(settings.document_too_large(11).unwrap().message.contains("11 bytes"));
+    }
+}
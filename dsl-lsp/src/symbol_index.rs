@@ -0,0 +1,505 @@
+//! Workspace symbol index, persisted across language server restarts
+//!
+//! Holds the symbols `workspace/symbol` searches: dictionary attributes and
+//! lookup tables, functions, the rules assigned in the workspace's rule
+//! files and the rules stored in the database. The index is saved under the
+//! workspace root, so a restarted server answers from disk straight away,
+//! then refreshes incrementally: only rule files whose modification time or
+//! size changed are parsed again. Queries match fuzzily, `kycrsk` finds
+//! `kyc_risk_score`.
+
+use data_designer::models::Expression;
+use data_designer::parser::parse_rules_recovering;
+use data_designer::rule_categories::called_functions;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tower_lsp::lsp_types::{DocumentSymbol, Location, Position, Range, SymbolInformation, SymbolKind, Url};
+use crate::data_dictionary::DataDictionary;
+
+/// Bumped whenever the saved format changes; an index with another version is rebuilt
+pub const INDEX_VERSION: u32 = 3;
+
+/// Where the index is saved, relative to the workspace root
+pub const INDEX_PATH: &str = ".dsl-lsp/symbols.json";
+
+/// Extensions of the rule files indexed
+pub const RULE_FILE_EXTENSIONS: &[&str] = &["dsl", "rules"];
+
+/// Source key of the data dictionary's symbols
+pub const DICTIONARY_SOURCE: &str = "dsl://dictionary";
+
+/// Source key of the function catalogue's symbols
+pub const FUNCTIONS_SOURCE: &str = "dsl://functions";
+
+/// Source key of the database's stored rules
+pub const STORED_RULES_SOURCE: &str = "dsl://rules";
+
+/// Prefix of a stored rule's URI, which the client opens as a read-only
+/// document; the percent-encoded rule id follows
+pub const STORED_RULE_PREFIX: &str = "dsl://rules/";
+
+/// Directories never searched for rule files
+const SKIPPED_DIRECTORIES: &[&str] = &["target", "node_modules", "pkg", "dist"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexedKind {
+    Attribute,
+    LookupTable,
+    Function,
+    Rule,
+    StoredRule,
+}
+
+impl IndexedKind {
+    fn symbol_kind(self) -> SymbolKind {
+        match self {
+            IndexedKind::Attribute => SymbolKind::FIELD,
+            IndexedKind::LookupTable => SymbolKind::ENUM,
+            IndexedKind::Function => SymbolKind::FUNCTION,
+            IndexedKind::Rule | IndexedKind::StoredRule => SymbolKind::VARIABLE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedSymbol {
+    pub name: String,
+    pub kind: IndexedKind,
+    /// Entity of an attribute, the file of a rule, or the id of a stored rule
+    pub container: Option<String>,
+    /// Where a rule is assigned; dictionary and function symbols have no position
+    pub position: Option<(u32, u32)>,
+    /// Functions a rule calls, upper-cased
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<String>,
+}
+
+/// Modification time and size a rule file had when it was indexed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub modified_ms: u64,
+    pub len: u64,
+}
+
+impl FileStamp {
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(FileStamp { modified_ms: modified.as_millis() as u64, len: metadata.len() })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct IndexedSource {
+    /// Set for rule files; other sources are compared by their symbols
+    stamp: Option<FileStamp>,
+    symbols: Vec<IndexedSymbol>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SymbolIndex {
+    version: u32,
+    /// Symbols by source: a rule file's URI, `DICTIONARY_SOURCE` or `FUNCTIONS_SOURCE`
+    sources: BTreeMap<String, IndexedSource>,
+    /// Whether the index changed since it was loaded or saved
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl Default for SymbolIndex {
+    fn default() -> Self {
+        Self { version: INDEX_VERSION, sources: BTreeMap::new(), dirty: false }
+    }
+}
+
+impl SymbolIndex {
+    /// The index saved under `root`, or an empty one if there is none or it
+    /// can't be read; either way the next refresh brings it up to date
+    pub fn load(root: &Path) -> Self {
+        fs::read_to_string(root.join(INDEX_PATH))
+            .ok()
+            .and_then(|content| serde_json::from_str::<SymbolIndex>(&content).ok())
+            .filter(|index| index.version == INDEX_VERSION)
+            .unwrap_or_default()
+    }
+
+    /// Saves the index under `root` if it changed
+    pub fn save(&mut self, root: &Path) -> std::io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let path = root.join(INDEX_PATH);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string(self)?)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.sources.values().map(|source| source.symbols.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn replace(&mut self, key: &str, source: IndexedSource) {
+        let unchanged = self
+            .sources
+            .get(key)
+            .is_some_and(|old| old.stamp == source.stamp && old.symbols == source.symbols);
+        if !unchanged {
+            self.sources.insert(key.to_string(), source);
+            self.dirty = true;
+        }
+    }
+
+    /// Replaces the dictionary's attributes and lookup tables
+    pub fn index_dictionary(&mut self, dictionary: &DataDictionary) {
+        let attributes = dictionary.entities.iter().flat_map(|(entity_name, entity)| {
+            entity.attributes.iter().map(move |attribute| IndexedSymbol {
+                name: format!("{}.{}", entity_name, attribute.name),
+                kind: IndexedKind::Attribute,
+                container: Some(entity_name.clone()),
+                position: None,
+                calls: Vec::new(),
+            })
+        });
+        let lookups = dictionary.lookups.keys().map(|name| IndexedSymbol {
+            name: name.clone(),
+            kind: IndexedKind::LookupTable,
+            container: None,
+            position: None,
+            calls: Vec::new(),
+        });
+        let mut symbols: Vec<IndexedSymbol> = attributes.chain(lookups).collect();
+        symbols.sort_by(|a, b| a.name.cmp(&b.name));
+        self.replace(DICTIONARY_SOURCE, IndexedSource { stamp: None, symbols });
+    }
+
+    /// Replaces the function catalogue
+    pub fn index_functions<'a>(&mut self, names: impl IntoIterator<Item = &'a str>) {
+        let mut symbols: Vec<IndexedSymbol> = names
+            .into_iter()
+            .map(|name| IndexedSymbol {
+                name: name.to_string(),
+                kind: IndexedKind::Function,
+                container: None,
+                position: None,
+                calls: Vec::new(),
+            })
+            .collect();
+        symbols.sort_by(|a, b| a.name.cmp(&b.name));
+        symbols.dedup_by(|a, b| a.name == b.name);
+        self.replace(FUNCTIONS_SOURCE, IndexedSource { stamp: None, symbols });
+    }
+
+    /// Replaces the stored rules, given as `(rule_id, rule_name)`
+    pub fn index_stored_rules(&mut self, rules: impl IntoIterator<Item = (String, String)>) {
+        let mut symbols: Vec<IndexedSymbol> = rules
+            .into_iter()
+            .map(|(rule_id, rule_name)| IndexedSymbol {
+                name: rule_name,
+                kind: IndexedKind::StoredRule,
+                container: Some(rule_id),
+                position: None,
+                calls: Vec::new(),
+            })
+            .collect();
+        symbols.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.container.cmp(&b.container)));
+        self.replace(STORED_RULES_SOURCE, IndexedSource { stamp: None, symbols });
+    }
+
+    /// Re-indexes one rule file from its current text, e.g. after a save
+    pub fn index_rule_file(&mut self, uri: &Url, text: &str, stamp: Option<FileStamp>) {
+        let file_name = uri.path_segments().and_then(|mut segments| segments.next_back()).map(str::to_string);
+        self.replace(uri.as_str(), IndexedSource { stamp, symbols: rule_symbols(text, file_name) });
+    }
+
+    /// Brings the rule files under `root` up to date: new and changed files
+    /// are parsed, unchanged ones kept as they are and deleted ones dropped.
+    /// Returns how many files were parsed.
+    pub fn refresh_rule_files(&mut self, root: &Path) -> usize {
+        let mut files = Vec::new();
+        collect_rule_files(root, &mut files);
+
+        let mut seen = BTreeSet::new();
+        let mut parsed = 0;
+        for path in files {
+            let Ok(uri) = Url::from_file_path(&path) else {
+                continue;
+            };
+            let stamp = FileStamp::of(&path);
+            seen.insert(uri.to_string());
+            if stamp.is_some() && self.sources.get(uri.as_str()).is_some_and(|source| source.stamp == stamp) {
+                continue;
+            }
+            if let Ok(text) = fs::read_to_string(&path) {
+                self.index_rule_file(&uri, &text, stamp);
+                parsed += 1;
+            }
+        }
+
+        let before = self.sources.len();
+        self.sources
+            .retain(|key, _| [DICTIONARY_SOURCE, FUNCTIONS_SOURCE, STORED_RULES_SOURCE].contains(&key.as_str()) || seen.contains(key));
+        self.dirty |= self.sources.len() != before;
+        parsed
+    }
+
+    /// Symbols whose name fuzzily matches `query`, best matches first; an
+    /// empty query matches everything
+    pub fn search(&self, query: &str) -> Vec<SymbolInformation> {
+        let mut matches: Vec<(u32, SymbolInformation)> = self
+            .symbols()
+            .filter_map(|(key, symbol)| Some((fuzzy_score(query, &symbol.name)?, symbol_information(key, symbol)?)))
+            .collect();
+        matches.sort_by(|(a, _), (b, _)| b.cmp(a));
+        matches.into_iter().map(|(_, symbol)| symbol).collect()
+    }
+
+    /// Rules that call `function`, for its documentation page
+    pub fn rules_calling(&self, function: &str) -> Vec<SymbolInformation> {
+        let function = function.to_uppercase();
+        self.symbols()
+            .filter(|(_, symbol)| symbol.kind == IndexedKind::Rule && symbol.calls.contains(&function))
+            .filter_map(|(key, symbol)| symbol_information(key, symbol))
+            .collect()
+    }
+
+    fn symbols(&self) -> impl Iterator<Item = (&String, &IndexedSymbol)> {
+        self.sources
+            .iter()
+            .flat_map(|(key, source)| source.symbols.iter().map(move |symbol| (key, symbol)))
+    }
+}
+
+/// How well `query` matches `name` as a subsequence of its characters,
+/// ignoring case; None when it doesn't. Runs of consecutive characters and
+/// matches at the start of a word (after `_`, `.` or a space, or a capital
+/// in camelCase) score higher.
+pub fn fuzzy_score(query: &str, name: &str) -> Option<u32> {
+    let name: Vec<char> = name.chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous = None;
+    for wanted in query.chars().filter(|c| !c.is_whitespace()) {
+        let found = (next..name.len()).find(|&i| name[i].to_lowercase().eq(wanted.to_lowercase()))?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 2;
+        }
+        let word_start = found == 0
+            || matches!(name[found - 1], '_' | '.' | ' ')
+            || (name[found].is_uppercase() && name[found - 1].is_lowercase());
+        if word_start {
+            score += 3;
+        }
+        previous = Some(found);
+        next = found + 1;
+    }
+    Some(score)
+}
+
+/// The URI a stored rule opens as, e.g. `dsl://rules/kyc_risk_score`
+pub fn stored_rule_uri(rule_id: &str) -> Option<Url> {
+    let mut uri = Url::parse(STORED_RULE_PREFIX).ok()?;
+    uri.path_segments_mut().ok()?.pop_if_empty().push(rule_id);
+    Some(uri)
+}
+
+/// The stored rule a URI is about
+pub fn stored_rule_from_uri(uri: &Url) -> Option<String> {
+    let encoded = uri.as_str().strip_prefix(STORED_RULE_PREFIX)?;
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut rest = encoded.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' && tail.len() >= 2 {
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&tail[..2]).ok()?, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok().filter(|rule_id| !rule_id.is_empty())
+}
+
+fn symbol_information(key: &str, symbol: &IndexedSymbol) -> Option<SymbolInformation> {
+    let uri = match (symbol.kind, &symbol.container) {
+        (IndexedKind::StoredRule, Some(rule_id)) => stored_rule_uri(rule_id)?,
+        _ => Url::parse(key).ok()?,
+    };
+    let start = symbol.position.map_or(Position::new(0, 0), |(line, character)| Position::new(line, character));
+    let end = Position::new(start.line, start.character + symbol.name.len() as u32);
+    #[allow(deprecated)]
+    Some(SymbolInformation {
+        name: symbol.name.clone(),
+        kind: symbol.kind.symbol_kind(),
+        tags: None,
+        deprecated: None,
+        location: Location { uri, range: Range { start, end } },
+        container_name: symbol.container.clone(),
+    })
+}
+
+/// The rules a document assigns, at the position of their name. Rules after
+/// a broken one are still found.
+pub fn rule_symbols(text: &str, file_name: Option<String>) -> Vec<IndexedSymbol> {
+    let (rules, _) = parse_rules_recovering(text);
+    rules
+        .into_iter()
+        .filter_map(|rule| match &rule.expression {
+            Expression::Assignment { target, .. } => {
+                // The name is the first thing in the rule's text, after any indentation
+                let offset = rule.start + text[rule.start..].find(target.as_str()).unwrap_or(0);
+                let position = crate::position_at(text, offset);
+                Some(IndexedSymbol {
+                    name: target.clone(),
+                    kind: IndexedKind::Rule,
+                    container: file_name.clone(),
+                    position: Some((position.line, position.character)),
+                    calls: called_functions(&rule.expression).into_iter().collect(),
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The document's outline: each rule it assigns, spanning the whole rule with
+/// its name selected. Rules after a broken one are still listed.
+pub fn document_symbols(text: &str) -> Vec<DocumentSymbol> {
+    let (rules, _) = parse_rules_recovering(text);
+    rules
+        .into_iter()
+        .filter_map(|rule| match &rule.expression {
+            Expression::Assignment { target, .. } => {
+                let offset = rule.start + text[rule.start..].find(target.as_str()).unwrap_or(0);
+                #[allow(deprecated)]
+                Some(DocumentSymbol {
+                    name: target.clone(),
+                    detail: Some(text[rule.start..rule.end].lines().next().unwrap_or("").trim().to_string()),
+                    kind: SymbolKind::VARIABLE,
+                    tags: None,
+                    deprecated: None,
+                    range: Range { start: crate::position_at(text, rule.start), end: crate::position_at(text, rule.end) },
+                    selection_range: Range {
+                        start: crate::position_at(text, offset),
+                        end: crate::position_at(text, offset + target.len()),
+                    },
+                    children: None,
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether the file has one of the `RULE_FILE_EXTENSIONS`
+pub fn is_rule_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| RULE_FILE_EXTENSIONS.contains(&extension))
+}
+
+fn collect_rule_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if path.is_dir() {
+            if !name.starts_with('.') && !SKIPPED_DIRECTORIES.contains(&name.as_str()) {
+                collect_rule_files(&path, files);
+            }
+        } else if is_rule_file(&path) {
+            files.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_symbols_point_at_rule_names() {
+        let text = "risk = score * 2\n\n  label = UPPER(name)\nscore > 10\n";
+        let symbols = rule_symbols(text, Some("kyc.dsl".to_string()));
+        let found: Vec<_> = symbols.iter().map(|s| (s.name.as_str(), s.position)).collect();
+        assert_eq!(found, vec![("risk", Some((0, 0))), ("label", Some((2, 2)))]);
+        assert_eq!(symbols[1].calls, vec!["UPPER"]);
+    }
+
+    #[test]
+    fn test_document_symbols_span_whole_rules() {
+        let text = "risk = IF score > 10\n    THEN \"high\"\n    ELSE \"low\"\n\n  label = UPPER(name)\n";
+        let symbols = document_symbols(text);
+        let outline: Vec<_> = symbols
+            .iter()
+            .map(|s| (s.name.as_str(), s.range.start.line, s.range.end.line, s.selection_range.start.character))
+            .collect();
+        assert_eq!(outline, vec![("risk", 0, 2, 0), ("label", 4, 4, 2)]);
+    }
+
+    #[test]
+    fn test_search_ranks_fuzzy_matches_and_finds_stored_rules() {
+        let mut index = SymbolIndex::default();
+        index.index_functions(["IS_EMAIL", "SUM"]);
+        index.index_stored_rules([
+            ("kyc_risk_score".to_string(), "kyc_risk_score".to_string()),
+            ("risk_check[jurisdiction:DE]".to_string(), "risk check DE".to_string()),
+        ]);
+
+        let names: Vec<_> = index.search("rsk").into_iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["kyc_risk_score", "risk check DE"]);
+        let names: Vec<_> = index.search("kycrsk").into_iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["kyc_risk_score"]);
+        assert!(fuzzy_score("ie", "IS_EMAIL") > fuzzy_score("ie", "FILTER_EMPTY"));
+
+        let found = index.search("check de");
+        assert_eq!(found[0].location.uri.as_str(), "dsl://rules/risk_check[jurisdiction:DE]");
+        assert_eq!(stored_rule_from_uri(&found[0].location.uri).as_deref(), Some("risk_check[jurisdiction:DE]"));
+        let odd = stored_rule_uri("fee 5%/day").unwrap();
+        assert_eq!(stored_rule_from_uri(&odd).as_deref(), Some("fee 5%/day"));
+    }
+
+    #[test]
+    fn test_refresh_reparses_only_changed_files_and_survives_restart() {
+        let root = std::env::temp_dir().join(format!("dsl-lsp-index-{}", std::process::id()));
+        fs::create_dir_all(root.join("rules")).unwrap();
+        fs::write(root.join("rules/a.dsl"), "alpha = 1\n").unwrap();
+        fs::write(root.join("rules/b.dsl"), "beta = 2\n").unwrap();
+
+        let mut index = SymbolIndex::default();
+        index.index_functions(["CONCAT", "UPPER"]);
+        assert_eq!(index.refresh_rule_files(&root), 2);
+        assert_eq!(index.refresh_rule_files(&root), 0);
+        index.save(&root).unwrap();
+
+        let mut restarted = SymbolIndex::load(&root);
+        assert_eq!(restarted.len(), 4);
+        assert_eq!(restarted.search("ALP").len(), 1);
+
+        fs::write(root.join("rules/b.dsl"), "beta = 2\ngamma = beta + 1\n").unwrap();
+        fs::remove_file(root.join("rules/a.dsl")).unwrap();
+        restarted.refresh_rule_files(&root);
+        let names: Vec<_> = restarted.search("").into_iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["CONCAT", "UPPER", "beta", "gamma"]);
+
+        fs::write(root.join("rules/c.dsl"), "label = upper(name)\n").unwrap();
+        restarted.refresh_rule_files(&root);
+        let callers: Vec<_> = restarted.rules_calling("UPPER").into_iter().map(|s| s.name).collect();
+        assert_eq!(callers, vec!["label"]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
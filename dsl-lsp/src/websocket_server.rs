@@ -1,7 +1,7 @@
 use tokio::net::TcpListener;
 use tokio_tungstenite::accept_async;
 use futures_util::{StreamExt, SinkExt};
-use tower_lsp::{LspService, Server};
+use tower_lsp::Server;
 use std::net::SocketAddr;
 
 pub async fn run_websocket_server(addr: SocketAddr) -> Result<(), Box<dyn std::error::Error>> {
@@ -36,7 +36,7 @@ async fn handle_websocket_connection(
     let ws_writer = WebSocketWriter::new(ws_sender);
 
     // Create LSP service
-    let (service, socket) = LspService::new(|client| dsl_lsp::Backend::new(client));
+    let (service, socket) = dsl_lsp::service();
 
     // Serve LSP over WebSocket
     Server::new(ws_reader, ws_writer, socket)
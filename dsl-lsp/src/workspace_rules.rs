@@ -0,0 +1,137 @@
+//! Rules assigned across the open documents
+//!
+//! The workspace symbol index only catches up with a file when it is saved;
+//! this table follows every edit. It records the names each open rule
+//! document assigns, so a name assigned in one file is known in the others,
+//! and a name assigned in more than one file is reported as a duplicate.
+//! Stored rules opened from the database are left out: they are usually the
+//! deployed copy of a rule being edited in a file.
+
+use data_designer::models::Expression;
+use data_designer::parser::parse_rules_recovering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use tower_lsp::lsp_types::{Location, Range, Url};
+use crate::symbol_index::stored_rule_from_uri;
+
+/// A name a document assigns, located at the name in its rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub name: String,
+    pub range: Range,
+}
+
+/// A rule whose name other documents also assign
+#[derive(Debug, Clone, PartialEq)]
+pub struct Duplicate {
+    pub name: String,
+    pub range: Range,
+    pub others: Vec<Location>,
+}
+
+#[derive(Debug, Default)]
+pub struct WorkspaceRules {
+    documents: HashMap<Url, Vec<Definition>>,
+}
+
+impl WorkspaceRules {
+    /// Records the names `text` assigns. Returns whether the set of names
+    /// changed, when the other documents' diagnostics need refreshing.
+    pub fn update(&mut self, uri: &Url, text: &str) -> bool {
+        if stored_rule_from_uri(uri).is_some() {
+            return false;
+        }
+        let definitions = definitions(text);
+        let changed = self.documents.get(uri).is_none_or(|previous| names(previous) != names(&definitions));
+        self.documents.insert(uri.clone(), definitions);
+        changed
+    }
+
+    /// Forgets a closed document; returns whether it assigned anything
+    pub fn remove(&mut self, uri: &Url) -> bool {
+        self.documents.remove(uri).is_some_and(|definitions| !definitions.is_empty())
+    }
+
+    /// The names the documents other than `uri` assign, each with where
+    pub fn defined_elsewhere(&self, uri: &Url) -> BTreeMap<&str, Vec<Location>> {
+        let mut defined: BTreeMap<&str, Vec<Location>> = BTreeMap::new();
+        for (other, definitions) in self.documents.iter().filter(|(other, _)| *other != uri) {
+            for definition in definitions {
+                defined.entry(definition.name.as_str()).or_default().push(Location {
+                    uri: other.clone(),
+                    range: definition.range,
+                });
+            }
+        }
+        for locations in defined.values_mut() {
+            locations.sort_by(|a, b| a.uri.as_str().cmp(b.uri.as_str()).then(a.range.start.cmp(&b.range.start)));
+        }
+        defined
+    }
+
+    /// The rules in `uri` whose names other documents also assign
+    pub fn duplicates(&self, uri: &Url) -> Vec<Duplicate> {
+        let Some(definitions) = self.documents.get(uri) else {
+            return Vec::new();
+        };
+        let elsewhere = self.defined_elsewhere(uri);
+        definitions
+            .iter()
+            .filter_map(|definition| {
+                let others = elsewhere.get(definition.name.as_str())?;
+                Some(Duplicate { name: definition.name.clone(), range: definition.range, others: others.clone() })
+            })
+            .collect()
+    }
+}
+
+fn definitions(text: &str) -> Vec<Definition> {
+    let (rules, _) = parse_rules_recovering(text);
+    rules
+        .iter()
+        .filter_map(|rule| match &rule.expression {
+            Expression::Assignment { target, .. } => {
+                let start = rule.start + text[rule.start..].find(target.as_str()).unwrap_or(0);
+                Some(Definition {
+                    name: target.clone(),
+                    range: Range { start: crate::position_at(text, start), end: crate::position_at(text, start + target.len()) },
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn names(definitions: &[Definition]) -> BTreeSet<&str> {
+    definitions.iter().map(|definition| definition.name.as_str()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_are_shared_and_duplicates_found_across_documents() {
+        let fees = Url::parse("file:///rules/fees.rules").unwrap();
+        let risk = Url::parse("file:///rules/risk.rules").unwrap();
+        let mut rules = WorkspaceRules::default();
+        assert!(rules.update(&fees, "base_fee = 100\nfee = base_fee * 2\n"));
+        assert!(rules.update(&risk, "score = IF fee > 150 THEN 1 ELSE 0\n\nbase_fee = 50\n"));
+
+        let elsewhere = rules.defined_elsewhere(&risk);
+        assert_eq!(elsewhere.keys().copied().collect::<Vec<_>>(), ["base_fee", "fee"]);
+        assert_eq!(elsewhere["fee"][0].uri, fees);
+        assert_eq!(elsewhere["fee"][0].range.start.line, 1);
+
+        let duplicates = rules.duplicates(&risk);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].name, "base_fee");
+        assert_eq!(duplicates[0].range.start.line, 2);
+        assert_eq!(duplicates[0].others[0].uri, fees);
+
+        // Moving a rule about doesn't change the names; closing a document does
+        assert!(!rules.update(&risk, "base_fee = 50\nscore = IF fee > 150 THEN 1 ELSE 0\n"));
+        assert!(rules.remove(&fees));
+        assert!(rules.duplicates(&risk).is_empty());
+        assert!(!rules.update(&Url::parse("dsl://rules/fee").unwrap(), "fee = 1"));
+    }
+}
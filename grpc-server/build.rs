@@ -1,10 +1,31 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tonic_build::configure()
+    // The rules engine messages are also served as JSON over HTTP
+    let json_messages = [
+        "ParseRuleRequest",
+        "ParseRuleResponse",
+        "EvaluateRuleRequest",
+        "EvaluateRuleResponse",
+        "EvaluateBatchRequest",
+        "EvaluateBatchResponse",
+        "BatchEvaluationResult",
+        "TranspileRuleRequest",
+        "TranspileRuleResponse",
+        "ValidateCbuScriptRequest",
+        "ValidateCbuScriptResponse",
+        "CbuScriptDiagnostic",
+    ];
+    let mut builder = tonic_build::configure()
         .build_server(true)
-        .build_client(true)
-        .compile_protos(
-            &["../proto/financial_taxonomy.proto"],
-            &["../proto"],
-        )?;
+        .build_client(true);
+    for message in json_messages {
+        builder = builder.type_attribute(
+            format!(".financial_taxonomy.{}", message),
+            "#[derive(serde::Serialize, serde::Deserialize)] #[serde(default)]",
+        );
+    }
+    builder.compile_protos(
+        &["../proto/financial_taxonomy.proto"],
+        &["../proto"],
+    )?;
     Ok(())
-}
\ No newline at end of file
+}
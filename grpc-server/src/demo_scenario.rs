@@ -0,0 +1,320 @@
+//! Scripted end-to-end walkthroughs for demos
+//!
+//! A scenario creates a CBU, fills the onboarding forms for its product with
+//! generated answers, evaluates a few rules over them, subscribes the CBU to
+//! a catalogue product and executes the compiled onboarding plan. Every step
+//! reports its progress as a `DemoEvent`; the run stops at the first step
+//! that fails, so a demo never carries on from a half-built client.
+
+use chrono::{DateTime, Utc};
+use data_designer_core::db::{CreateCbuRequest, DbOperations, SubscribeCbuToProductRequest};
+use data_designer_core::evaluator::{evaluate, Facts};
+use data_designer_core::models::Value;
+use data_designer_core::parser::parse_rule;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use tracing::{error, info};
+
+use crate::template_api::{record_compile_events, record_onboarding_events, CompileWorkflowRequest};
+
+/// A walkthrough a sales engineer can run by name
+#[derive(Debug, Clone, Serialize)]
+pub struct DemoScenario {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub cbu_name: &'static str,
+    pub domicile_country: &'static str,
+    pub regulatory_jurisdiction: &'static str,
+    pub business_type: &'static str,
+    pub region: &'static str,
+    /// Product id in the onboarding catalog
+    pub onboarding_product: &'static str,
+    /// Line of business of the product to subscribe to
+    pub line_of_business: &'static str,
+    /// Form answers that make the walkthrough tell its story, over the generated ones
+    pub answers: &'static [(&'static str, &'static str)],
+    pub rules: &'static [&'static str],
+}
+
+pub const SCENARIOS: &[DemoScenario] = &[
+    DemoScenario {
+        name: "global-custody",
+        description: "Luxembourg UCITS fund onboarding for global custody with market access",
+        cbu_name: "Demo Lux Equity Fund",
+        domicile_country: "LU",
+        regulatory_jurisdiction: "CSSF",
+        business_type: "UCITS Fund",
+        region: "EU",
+        onboarding_product: "GlobalCustody@v3",
+        line_of_business: "Custody",
+        answers: &[("instructionMethod", "SWIFT")],
+        rules: &[
+            r#"risk_tier = IF domicile_country IN ["LU", "IE"] THEN "standard" ELSE "enhanced""#,
+            r#"onboarding_priority = IF risk_tier == "enhanced" OR instructionMethod == "ManualPlatform" THEN "high" ELSE "normal""#,
+        ],
+    },
+    DemoScenario {
+        name: "fund-accounting",
+        description: "US mutual fund onboarding for fund accounting with daily NAV",
+        cbu_name: "Demo US Growth Fund",
+        domicile_country: "US",
+        regulatory_jurisdiction: "SEC",
+        business_type: "Mutual Fund",
+        region: "US",
+        onboarding_product: "FundAccounting@v2",
+        line_of_business: "Fund",
+        answers: &[("navFrequency", "Daily"), ("base_currency", "USD")],
+        rules: &[
+            r#"risk_tier = IF domicile_country IN ["LU", "IE"] THEN "standard" ELSE "enhanced""#,
+            r#"onboarding_priority = IF risk_tier == "enhanced" AND navFrequency == "Daily" THEN "high" ELSE "normal""#,
+        ],
+    },
+];
+
+pub fn find_scenario(name: &str) -> Option<&'static DemoScenario> {
+    SCENARIOS.iter().find(|scenario| scenario.name == name)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DemoStep {
+    CreateCbu,
+    FillForms,
+    EvaluateRules,
+    SubscribeProduct,
+    ExecutePlan,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Started,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DemoEvent {
+    pub step: DemoStep,
+    pub status: StepStatus,
+    pub detail: String,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DemoRun {
+    pub scenario: String,
+    pub success: bool,
+    pub cbu_id: Option<String>,
+    pub instance_id: Option<String>,
+    pub subscribed_product: Option<String>,
+    pub forms: Vec<onboarding::runtime::solicit::Form>,
+    pub answers: BTreeMap<String, serde_json::Value>,
+    pub rule_results: BTreeMap<String, serde_json::Value>,
+    pub events: Vec<DemoEvent>,
+}
+
+impl DemoRun {
+    fn emit(&mut self, step: DemoStep, status: StepStatus, detail: impl Into<String>) {
+        let event = DemoEvent { step, status, detail: detail.into(), at: Utc::now() };
+        match status {
+            StepStatus::Failed => error!("❌ [DEMO] {:?}: {}", event.step, event.detail),
+            _ => info!("🎬 [DEMO] {:?} {:?}: {}", event.step, event.status, event.detail),
+        }
+        self.events.push(event);
+    }
+
+    // Record a step's outcome, returning its value when it succeeded
+    fn finish<T>(&mut self, step: DemoStep, outcome: Result<(T, String), String>) -> Option<T> {
+        match outcome {
+            Ok((value, detail)) => {
+                self.emit(step, StepStatus::Completed, detail);
+                Some(value)
+            }
+            Err(e) => {
+                self.emit(step, StepStatus::Failed, e);
+                None
+            }
+        }
+    }
+}
+
+/// Run `scenario` from start to finish; the CBU it creates is named with a
+/// timestamp so the same scenario can be run again in the same demo
+pub async fn run_scenario(pool: &PgPool, scenario: &DemoScenario) -> DemoRun {
+    let mut run = DemoRun { scenario: scenario.name.to_string(), ..Default::default() };
+    let stamp = Utc::now().format("%Y%m%d-%H%M%S").to_string();
+
+    // 1. Create the client business unit
+    run.emit(DemoStep::CreateCbu, StepStatus::Started, scenario.cbu_name);
+    let created = DbOperations::create_cbu(CreateCbuRequest {
+        cbu_name: format!("{} {}", scenario.cbu_name, stamp),
+        description: Some(scenario.description.to_string()),
+        primary_entity_id: None,
+        primary_lei: None,
+        domicile_country: Some(scenario.domicile_country.to_string()),
+        regulatory_jurisdiction: Some(scenario.regulatory_jurisdiction.to_string()),
+        business_type: Some(scenario.business_type.to_string()),
+        created_by: Some("demo".to_string()),
+    })
+    .await
+    .map(|cbu| {
+        let detail = format!("Created {} ({})", cbu.cbu_name, cbu.cbu_id);
+        (cbu.cbu_id, detail)
+    });
+    let Some(cbu_id) = run.finish(DemoStep::CreateCbu, created) else { return run };
+    run.cbu_id = Some(cbu_id.clone());
+
+    // 2. Compile the onboarding plan and answer its forms
+    run.emit(DemoStep::FillForms, StepStatus::Started, scenario.onboarding_product);
+    let request = CompileWorkflowRequest {
+        instance_id: format!("DEMO-{}-{}", scenario.name, stamp),
+        cbu_id: cbu_id.clone(),
+        products: vec![scenario.onboarding_product.to_string()],
+        team_users: vec![serde_json::json!({ "email": "demo.user@example.com", "role": "Administrator" })],
+        cbu_profile: serde_json::json!({ "region": scenario.region, "domicile": scenario.domicile_country }),
+    };
+    let compiled = compile(&request).map(|(outputs, forms)| {
+        let answers = answers_for(&forms, scenario);
+        let detail = format!(
+            "{} forms, {} answers generated for {} plan steps",
+            forms.len(),
+            answers.len(),
+            outputs.plan.steps.len()
+        );
+        ((outputs, forms, answers), detail)
+    });
+    let Some((outputs, forms, answers)) = run.finish(DemoStep::FillForms, compiled) else { return run };
+    record_compile_events(pool, &request, &outputs.plan, &outputs.idd).await;
+    run.instance_id = Some(request.instance_id.clone());
+    run.forms = forms;
+    run.answers = answers;
+
+    // 3. Evaluate the scenario's rules over the client and its answers
+    run.emit(DemoStep::EvaluateRules, StepStatus::Started, format!("{} rules", scenario.rules.len()));
+    let evaluated = evaluate_rules(scenario, &run.answers).map(|results| {
+        let detail = results.iter().map(|(target, value)| format!("{} = {}", target, value)).collect::<Vec<_>>().join(", ");
+        (results, detail)
+    });
+    let Some(rule_results) = run.finish(DemoStep::EvaluateRules, evaluated) else { return run };
+    run.rule_results = rule_results;
+
+    // 4. Subscribe the CBU to a catalogue product in the scenario's line of business
+    run.emit(DemoStep::SubscribeProduct, StepStatus::Started, scenario.line_of_business);
+    let subscribed = subscribe(&cbu_id, scenario).await;
+    let Some(product_id) = run.finish(DemoStep::SubscribeProduct, subscribed) else { return run };
+    run.subscribed_product = Some(product_id);
+
+    // 5. Execute the onboarding plan, recording its events in the instance history
+    run.emit(DemoStep::ExecutePlan, StepStatus::Started, format!("{} tasks", outputs.plan.steps.len()));
+    let executed = execute(pool, &outputs.plan).await;
+    run.success = run.finish(DemoStep::ExecutePlan, executed).is_some();
+    run
+}
+
+fn compile(
+    request: &CompileWorkflowRequest,
+) -> Result<(onboarding::CompileOutputs, Vec<onboarding::runtime::solicit::Form>), String> {
+    use onboarding::ast::oodl::OnboardIntent;
+    use onboarding::meta::loader::load_from_dir;
+    use onboarding::{compile_onboard, CompileInputs};
+
+    let meta = load_from_dir(std::path::Path::new("onboarding/metadata"))
+        .map_err(|e| format!("Failed to load onboarding metadata: {}", e))?;
+    let intent = OnboardIntent {
+        instance_id: request.instance_id.clone(),
+        cbu_id: request.cbu_id.clone(),
+        products: request.products.clone(),
+    };
+    let outputs = compile_onboard(CompileInputs {
+        intent: &intent,
+        meta: &meta,
+        team_users: request.team_users.clone(),
+        cbu_profile: request.cbu_profile.clone(),
+    })
+    .map_err(|e| format!("Compilation failed: {}", e))?;
+    let forms = onboarding::runtime::solicit::forms_for_plan(&outputs.plan, &outputs.idd, &meta);
+    Ok((outputs, forms))
+}
+
+// Answers keyed as rule attributes: `base-currency` becomes `base_currency`
+fn answers_for(forms: &[onboarding::runtime::solicit::Form], scenario: &DemoScenario) -> BTreeMap<String, serde_json::Value> {
+    let mut answers: BTreeMap<String, serde_json::Value> = forms
+        .iter()
+        .flat_map(|form| &form.fields)
+        .map(|field| (field.key.replace('-', "_"), field.sample_answer()))
+        .collect();
+    for (key, value) in scenario.answers {
+        if let Some(answer) = answers.get_mut(*key) {
+            *answer = match answer {
+                serde_json::Value::Array(_) => serde_json::json!([value]),
+                _ => serde_json::json!(value),
+            };
+        }
+    }
+    answers
+}
+
+fn evaluate_rules(
+    scenario: &DemoScenario,
+    answers: &BTreeMap<String, serde_json::Value>,
+) -> Result<BTreeMap<String, serde_json::Value>, String> {
+    let mut facts: Facts = answers.iter().map(|(key, value)| (key.clone(), Value::from_json(value))).collect();
+    facts.insert("domicile_country".to_string(), Value::String(scenario.domicile_country.to_string()));
+    facts.insert("business_type".to_string(), Value::String(scenario.business_type.to_string()));
+
+    let mut results = BTreeMap::new();
+    for rule in scenario.rules {
+        let (_, expression) = parse_rule(rule).map_err(|e| format!("Failed to parse `{}`: {}", rule, e))?;
+        let target = rule.split('=').next().unwrap_or_default().trim().to_string();
+        let value = evaluate(&expression, &facts).map_err(|e| format!("Failed to evaluate {}: {}", target, e))?;
+        results.insert(target.clone(), value.to_json());
+        // Later rules can build on earlier results
+        facts.insert(target, value);
+    }
+    Ok(results)
+}
+
+async fn subscribe(cbu_id: &str, scenario: &DemoScenario) -> Result<(String, String), String> {
+    let products = DbOperations::list_products(None).await?;
+    let product = products
+        .iter()
+        .find(|product| product.line_of_business.contains(scenario.line_of_business))
+        .or_else(|| products.first())
+        .ok_or_else(|| "No active products to subscribe to".to_string())?;
+    DbOperations::subscribe_cbu_to_product(SubscribeCbuToProductRequest {
+        cbu_id: cbu_id.to_string(),
+        product_id: product.product_id.clone(),
+        billing_arrangement: None,
+        contract_reference: Some(format!("DEMO-{}", scenario.name)),
+        primary_contact_role_code: None,
+        created_by: Some("demo".to_string()),
+    })
+    .await?;
+    Ok((product.product_id.clone(), format!("Subscribed to {} ({})", product.product_name, product.product_id)))
+}
+
+async fn execute(pool: &PgPool, plan: &onboarding::Plan) -> Result<((), String), String> {
+    use onboarding::{execute_plan_with_events, ExecutionConfig, OnboardingEvent};
+
+    let mut events = Vec::new();
+    let outcome = execute_plan_with_events(plan, &ExecutionConfig {}, |event| events.push((Utc::now(), event))).await;
+    if let Err(e) = &outcome {
+        let failed_task = events
+            .last()
+            .and_then(|(_, event)| match event {
+                OnboardingEvent::TaskStarted { task_id, .. } => Some(task_id.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(|| "plan".to_string());
+        events.push((
+            Utc::now(),
+            OnboardingEvent::TaskFailed { instance_id: plan.instance_id.clone(), task_id: failed_task, error: e.to_string() },
+        ));
+    }
+    record_onboarding_events(pool, &events).await;
+    outcome
+        .map(|_| ((), format!("Executed {} tasks", plan.steps.len())))
+        .map_err(|e| format!("Execution failed: {}", e))
+}
@@ -0,0 +1,456 @@
+// GraphQL API over rules, the data dictionary, CBUs and rule evaluation
+//
+// Lets the frontends assemble a view in one request instead of chaining REST
+// calls: a CBU with its members and their legal entities, or a rule with the
+// attributes it depends on and their dictionary definitions. Served on
+// /graphql (POST for queries, GET for the GraphiQL explorer).
+
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{
+    ComplexObject, Context, EmptySubscription, Error, InputObject, Json, Object, Result, Schema, SimpleObject,
+};
+use async_graphql_axum::GraphQL;
+use axum::{response::Html, routing::get, Router};
+use data_designer_core::db::{CreateRuleRequest, RuleOperations, TagFilter};
+use data_designer_core::evaluator::{evaluate_with_functions, Facts, FunctionLibrary};
+use data_designer_core::parser::parse_rule;
+use data_designer_core::rule_variants::Applicability;
+use data_designer_core::transpiler::DslTranspiler;
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+use tracing::error;
+
+pub fn create_graphql_router(db_pool: PgPool) -> Router {
+    let schema = Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+        .data(db_pool)
+        .finish();
+
+    Router::new().route("/graphql", get(graphiql).post_service(GraphQL::new(schema)))
+}
+
+async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+// Database errors are logged in full and surfaced to the client as a GraphQL error
+fn db_error(context: &str, e: impl std::fmt::Display) -> Error {
+    error!("{}: {}", context, e);
+    Error::new(format!("{}: {}", context, e))
+}
+
+fn parse_timestamp(field: &str, value: Option<&str>) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+    value
+        .map(|value| {
+            chrono::DateTime::parse_from_rfc3339(value)
+                .map(|at| at.with_timezone(&chrono::Utc))
+                .map_err(|e| Error::new(format!("Invalid {} '{}': {}", field, value, e)))
+        })
+        .transpose()
+}
+
+// ============================================
+// CBUs, members and legal entities
+// ============================================
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct Cbu {
+    pub cbu_id: String,
+    pub cbu_name: String,
+    pub description: Option<String>,
+    pub primary_lei: Option<String>,
+    pub domicile_country: Option<String>,
+    pub business_type: Option<String>,
+    pub status: String,
+}
+
+#[ComplexObject]
+impl Cbu {
+    /// Active members of the CBU, by role
+    async fn members(&self, ctx: &Context<'_>) -> Result<Vec<CbuMember>> {
+        let pool = ctx.data::<PgPool>()?;
+        let rows = sqlx::query("
+            SELECT role_code, role_name, entity_id, entity_name, entity_lei, is_primary,
+                   has_trading_authority, has_settlement_authority
+            FROM v_cbu_members_detail
+            WHERE cbu_id = $1 AND is_active = true
+            ORDER BY role_code, entity_name
+        ")
+            .bind(&self.cbu_id)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| db_error("Failed to get CBU members", e))?;
+
+        Ok(rows.iter().map(|row| CbuMember {
+            role_code: row.get("role_code"),
+            role_name: row.get("role_name"),
+            entity_id: row.get("entity_id"),
+            entity_name: row.get("entity_name"),
+            entity_lei: row.get("entity_lei"),
+            is_primary: row.get("is_primary"),
+            has_trading_authority: row.get("has_trading_authority"),
+            has_settlement_authority: row.get("has_settlement_authority"),
+        }).collect())
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct CbuMember {
+    pub role_code: String,
+    pub role_name: String,
+    pub entity_id: String,
+    pub entity_name: String,
+    pub entity_lei: Option<String>,
+    pub is_primary: bool,
+    pub has_trading_authority: bool,
+    pub has_settlement_authority: bool,
+}
+
+#[ComplexObject]
+impl CbuMember {
+    /// The member's legal entity record
+    async fn entity(&self, ctx: &Context<'_>) -> Result<Option<LegalEntity>> {
+        find_entity(ctx.data::<PgPool>()?, &self.entity_id).await
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct LegalEntity {
+    pub entity_id: String,
+    pub entity_name: String,
+    pub entity_type: String,
+    pub jurisdiction: Option<String>,
+    pub country_code: Option<String>,
+    pub lei_code: Option<String>,
+    pub status: String,
+}
+
+async fn find_entity(pool: &PgPool, entity_id: &str) -> Result<Option<LegalEntity>> {
+    let row = sqlx::query("
+        SELECT entity_id, entity_name, entity_type, incorporation_jurisdiction AS jurisdiction,
+               incorporation_country AS country_code, lei_code, status
+        FROM legal_entities
+        WHERE entity_id = $1
+    ")
+        .bind(entity_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| db_error("Failed to get legal entity", e))?;
+
+    Ok(row.map(|row| LegalEntity {
+        entity_id: row.get("entity_id"),
+        entity_name: row.get("entity_name"),
+        entity_type: row.get("entity_type"),
+        jurisdiction: row.get("jurisdiction"),
+        country_code: row.get("country_code"),
+        lei_code: row.get("lei_code"),
+        status: row.get("status"),
+    }))
+}
+
+// ============================================
+// Rules, dependencies and dictionary attributes
+// ============================================
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct Rule {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub description: Option<String>,
+    pub rule_definition: String,
+    pub status: String,
+}
+
+#[ComplexObject]
+impl Rule {
+    /// Attributes the rule's definition reads; empty when it no longer parses
+    async fn dependencies(&self) -> Vec<RuleDependency> {
+        match parse_rule(&self.rule_definition) {
+            Ok((_, ast)) => DslTranspiler::new()
+                .extract_dependencies(&ast)
+                .into_iter()
+                .map(|name| RuleDependency { name })
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct RuleDependency {
+    pub name: String,
+}
+
+#[ComplexObject]
+impl RuleDependency {
+    /// Dictionary attributes the name refers to: the attribute with that full
+    /// path, or every attribute of that name whatever its entity
+    async fn attributes(&self, ctx: &Context<'_>) -> Result<Vec<Attribute>> {
+        let pool = ctx.data::<PgPool>()?;
+        let rows = sqlx::query("
+            SELECT full_path, attribute_name, entity_name, attribute_type, data_type, description
+            FROM mv_data_dictionary
+            WHERE full_path = $1 OR attribute_name = $1
+            ORDER BY full_path
+        ")
+            .bind(&self.name)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| db_error("Failed to look up dependency attributes", e))?;
+
+        Ok(rows.iter().map(attribute_from_row).collect())
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Attribute {
+    pub full_path: String,
+    pub attribute_name: String,
+    pub entity_name: String,
+    pub attribute_type: String,
+    pub data_type: Option<String>,
+    pub description: Option<String>,
+}
+
+fn attribute_from_row(row: &sqlx::postgres::PgRow) -> Attribute {
+    Attribute {
+        full_path: row.get("full_path"),
+        attribute_name: row.get("attribute_name"),
+        entity_name: row.get("entity_name"),
+        attribute_type: row.get("attribute_type"),
+        data_type: row.get("data_type"),
+        description: row.get("description"),
+    }
+}
+
+async fn find_rule(pool: &PgPool, rule_id: &str) -> Result<Option<Rule>> {
+    let row = sqlx::query("
+        SELECT rule_id, rule_name, description, rule_definition, status
+        FROM rules
+        WHERE rule_id = $1
+    ")
+        .bind(rule_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| db_error("Failed to get rule", e))?;
+
+    Ok(row.map(|row| Rule {
+        rule_id: row.get("rule_id"),
+        rule_name: row.get("rule_name"),
+        description: row.get("description"),
+        rule_definition: row.get("rule_definition"),
+        status: row.get("status"),
+    }))
+}
+
+// ============================================
+// Queries
+// ============================================
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// All CBUs, by name
+    async fn cbus(&self, ctx: &Context<'_>) -> Result<Vec<Cbu>> {
+        let pool = ctx.data::<PgPool>()?;
+        let rows = sqlx::query("SELECT * FROM v_cbu_summary ORDER BY cbu_name")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| db_error("Failed to list CBUs", e))?;
+
+        Ok(rows.iter().map(cbu_from_row).collect())
+    }
+
+    async fn cbu(&self, ctx: &Context<'_>, cbu_id: String) -> Result<Option<Cbu>> {
+        let pool = ctx.data::<PgPool>()?;
+        let row = sqlx::query("SELECT * FROM client_business_units WHERE cbu_id = $1")
+            .bind(&cbu_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| db_error("Failed to get CBU", e))?;
+
+        Ok(row.as_ref().map(cbu_from_row))
+    }
+
+    async fn entity(&self, ctx: &Context<'_>, entity_id: String) -> Result<Option<LegalEntity>> {
+        find_entity(ctx.data::<PgPool>()?, &entity_id).await
+    }
+
+    /// Active rules matching the search text and tags, newest first
+    async fn rules(
+        &self,
+        ctx: &Context<'_>,
+        search: Option<String>,
+        #[graphql(default)] tags: Vec<String>,
+        #[graphql(default)] match_all_tags: bool,
+    ) -> Result<Vec<Rule>> {
+        let pool = ctx.data::<PgPool>()?;
+        let tag_filter = TagFilter { tags, match_all: match_all_tags };
+        let matches = RuleOperations::search_rules(pool, search.as_deref(), &tag_filter)
+            .await
+            .map_err(|e| db_error("Failed to search rules", e))?;
+
+        let mut rules = Vec::with_capacity(matches.len());
+        for summary in &matches {
+            if let Some(rule) = find_rule(pool, summary["rule_id"].as_str().unwrap_or_default()).await? {
+                rules.push(rule);
+            }
+        }
+        Ok(rules)
+    }
+
+    async fn rule(&self, ctx: &Context<'_>, rule_id: String) -> Result<Option<Rule>> {
+        find_rule(ctx.data::<PgPool>()?, &rule_id).await
+    }
+
+    /// Data dictionary attributes, optionally limited to one entity
+    async fn attributes(&self, ctx: &Context<'_>, entity_name: Option<String>) -> Result<Vec<Attribute>> {
+        let pool = ctx.data::<PgPool>()?;
+        let rows = sqlx::query("
+            SELECT full_path, attribute_name, entity_name, attribute_type, data_type, description
+            FROM mv_data_dictionary
+            WHERE ($1::text IS NULL OR entity_name = $1)
+            ORDER BY entity_name, attribute_name
+        ")
+            .bind(entity_name)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| db_error("Failed to get data dictionary", e))?;
+
+        Ok(rows.iter().map(attribute_from_row).collect())
+    }
+}
+
+fn cbu_from_row(row: &sqlx::postgres::PgRow) -> Cbu {
+    Cbu {
+        cbu_id: row.get("cbu_id"),
+        cbu_name: row.get("cbu_name"),
+        description: row.get("description"),
+        primary_lei: row.get("primary_lei"),
+        domicile_country: row.get("domicile_country"),
+        business_type: row.get("business_type"),
+        status: row.get("status"),
+    }
+}
+
+// ============================================
+// Mutations
+// ============================================
+
+#[derive(InputObject)]
+pub struct SaveRuleInput {
+    /// Generated from the category's naming convention when left out
+    pub rule_id: Option<String>,
+    pub rule_name: String,
+    pub description: Option<String>,
+    pub category_key: String,
+    pub target_attribute: String,
+    pub source_attributes: Vec<String>,
+    pub rule_definition: String,
+    pub tags: Option<Vec<String>>,
+    /// When this version takes effect (RFC 3339); defaults to when it is saved
+    pub valid_from: Option<String>,
+    /// When it stops being in effect (RFC 3339, exclusive); open-ended by default
+    pub valid_to: Option<String>,
+    /// The jurisdiction, product line or CBU type this variant of the rule is
+    /// for; a rule with none of them is the default for its attribute
+    pub jurisdiction: Option<String>,
+    pub product_line: Option<String>,
+    pub cbu_type: Option<String>,
+}
+
+#[derive(SimpleObject)]
+pub struct SaveRuleResult {
+    /// Status the rule was saved with under its category's policy
+    pub status: String,
+    pub severity: String,
+    pub rule: Option<Rule>,
+    /// Saved test cases of the rule, run against what was saved
+    pub tests_passed: i32,
+    pub tests_failed: i32,
+    /// The rule ID generated or the name corrected under the category's naming convention
+    pub naming_notes: Vec<String>,
+}
+
+#[derive(SimpleObject)]
+pub struct EvaluationResult {
+    pub value: Json<serde_json::Value>,
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    /// Validate and save a rule, returning it as stored
+    async fn save_rule(&self, ctx: &Context<'_>, input: SaveRuleInput) -> Result<SaveRuleResult> {
+        let pool = ctx.data::<PgPool>()?;
+        let request = CreateRuleRequest {
+            rule_id: input.rule_id.unwrap_or_default(),
+            rule_name: input.rule_name,
+            description: input.description,
+            category_key: input.category_key,
+            target_attribute: input.target_attribute,
+            source_attributes: input.source_attributes,
+            rule_definition: input.rule_definition,
+            tags: input.tags,
+            valid_from: parse_timestamp("validFrom", input.valid_from.as_deref())?,
+            valid_to: parse_timestamp("validTo", input.valid_to.as_deref())?,
+            applies_to: Applicability {
+                jurisdiction: input.jurisdiction,
+                product_line: input.product_line,
+                cbu_type: input.cbu_type,
+            },
+        };
+
+        let saved = RuleOperations::save_rule_with_validation(pool, request)
+            .await
+            .map_err(Error::new)?;
+
+        Ok(SaveRuleResult {
+            status: saved.status,
+            severity: saved.severity.as_str().to_string(),
+            rule: find_rule(pool, &saved.rule_id).await?,
+            tests_passed: saved.tests.passed as i32,
+            tests_failed: saved.tests.failed as i32,
+            naming_notes: saved.naming_notes,
+        })
+    }
+
+    /// Evaluate a stored rule, or an ad-hoc definition, against the given facts
+    async fn evaluate_rule(
+        &self,
+        ctx: &Context<'_>,
+        rule_id: Option<String>,
+        definition: Option<String>,
+        facts: Json<HashMap<String, serde_json::Value>>,
+    ) -> Result<EvaluationResult> {
+        let definition = match (rule_id, definition) {
+            (_, Some(definition)) => definition,
+            (Some(rule_id), None) => find_rule(ctx.data::<PgPool>()?, &rule_id)
+                .await?
+                .ok_or_else(|| Error::new(format!("Rule not found: {}", rule_id)))?
+                .rule_definition,
+            (None, None) => return Err(Error::new("Either ruleId or definition is required")),
+        };
+
+        let ast = match parse_rule(&definition) {
+            Ok(("", ast)) => ast,
+            Ok((rest, _)) => return Err(Error::new(format!("Unexpected input after rule: {}", rest))),
+            Err(e) => return Err(Error::new(format!("DSL parsing error: {}", e))),
+        };
+
+        let facts: Facts = facts.0
+            .into_iter()
+            .map(|(name, value)| (name, crate::convert_json_to_value(value)))
+            .collect();
+
+        let value = evaluate_with_functions(&ast, &facts, &FunctionLibrary::new())
+            .map_err(|e| Error::new(format!("Evaluation error: {}", e)))?;
+
+        Ok(EvaluationResult { value: Json(crate::convert_value_to_json(value)) })
+    }
+}
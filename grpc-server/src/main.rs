@@ -3,7 +3,7 @@ use sqlx::{PgPool, Row};
 use std::env;
 use std::process::Command;
 use std::sync::Arc;
-use tracing::{info, error};
+use tracing::{info, warn, error};
 use std::collections::HashMap;
 
 // Import the capability execution engine
@@ -19,8 +19,13 @@ use data_designer_core::onboarding_request_dsl::OnboardingRequestDslParser;
 use data_designer_core::parser::parse_expression;
 use data_designer_core::models::Value;
 use data_designer_core::runtime_orchestrator::ExecutionContext;
+use data_designer_core::batch_writer::{Backpressure, EvaluationResultRecord};
+use data_designer_core::db::{BatchWriter, RuleOperations};
 
 mod template_api;
+mod graphql_api;
+mod demo_scenario;
+mod rules_engine;
 
 // Generated protobuf code
 pub mod financial_taxonomy {
@@ -29,6 +34,7 @@ pub mod financial_taxonomy {
 
 use financial_taxonomy::{
     financial_taxonomy_service_server::{FinancialTaxonomyService, FinancialTaxonomyServiceServer},
+    rules_engine_service_server::RulesEngineServiceServer,
     *,
 };
 
@@ -95,7 +101,12 @@ fn convert_value_to_json(value: Value) -> serde_json::Value {
         Value::Boolean(b) => serde_json::Value::Bool(b),
         Value::Null => serde_json::Value::Null,
         Value::Regex(r) => serde_json::Value::String(r),
+        Value::Percent(p) => serde_json::Value::String(format!("{}%", p)),
+        Value::Money { amount, currency } => serde_json::Value::String(format!("{} {}", amount, currency)),
         Value::List(list) => serde_json::Value::Array(list.into_iter().map(convert_value_to_json).collect()),
+        Value::Object(fields) => {
+            serde_json::Value::Object(fields.into_iter().map(|(name, value)| (name, convert_value_to_json(value))).collect())
+        }
     }
 }
 
@@ -148,10 +159,11 @@ pub struct TaxonomyServer {
     db_pool: PgPool,
     pool: PgPool, // For AI assistant
     capability_engine: CapabilityExecutionEngine,
+    evaluation_log: BatchWriter<EvaluationResultRecord>,
 }
 
 impl TaxonomyServer {
-    pub fn new(db_pool: PgPool) -> Self {
+    pub fn new(db_pool: PgPool, evaluation_log: BatchWriter<EvaluationResultRecord>) -> Self {
         // Initialize capability engine with built-in capabilities
         let capability_engine = CapabilityExecutionEngine::new();
 
@@ -159,6 +171,7 @@ impl TaxonomyServer {
             db_pool: db_pool.clone(),
             pool: db_pool,
             capability_engine,
+            evaluation_log,
         }
     }
 
@@ -166,16 +179,10 @@ impl TaxonomyServer {
     async fn query_taxonomy_hierarchy(&self) -> Result<Vec<TaxonomyHierarchyItem>, sqlx::Error> {
         let rows = sqlx::query!(
             r#"
-            SELECT p.product_id as id, p.product_name as name, p.description as description,
-                   'product' as item_type, 1 as level, NULL::INTEGER as parent_id
-            FROM products p
-            WHERE p.product_id IS NOT NULL
-            UNION ALL
-            SELECT s.service_id as id, s.service_name as name, s.description as description,
-                   'service' as item_type, 2 as level, NULL::INTEGER as parent_id
-            FROM services s
-            WHERE s.service_id IS NOT NULL
-            ORDER BY level, id
+            SELECT item_id as id, item_name as name, item_description as description,
+                   item_type, level, NULL::INTEGER as parent_id
+            FROM mv_taxonomy_hierarchy
+            ORDER BY level, item_id
             LIMIT 100
             "#
         ).fetch_all(&self.pool).await?;
@@ -1091,27 +1098,29 @@ impl FinancialTaxonomyService for TaxonomyServer {
             .execute(&self.pool)
             .await;
 
-        // Log execution results
-        let log_query = r#"
-            INSERT INTO dsl_execution_logs (instance_id, execution_status, input_data, output_data, log_messages, error_details, execution_time_ms)
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
-        "#;
-
+        // Log execution results; buffered and written in chunks
         let input_data_json = req.input_data
             .as_ref()
-            .and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok())
-            .unwrap_or_default();
-
-        let _ = sqlx::query(log_query)
-            .bind(&req.instance_id)
-            .bind(&execution_status)
-            .bind(&input_data_json)
-            .bind(&output_data)
-            .bind(&log_messages)
-            .bind(&error_details)
-            .bind(execution_time)
-            .execute(&self.pool)
-            .await;
+            .and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok());
+
+        let log_record = EvaluationResultRecord {
+            instance_id: Some(req.instance_id.clone()),
+            execution_type: "dsl_execution".to_string(),
+            dsl_script: dsl_code.to_string(),
+            execution_status: if execution_status == "success" { "success" } else { "error" }.to_string(),
+            input_data: input_data_json,
+            output_data: Some(output_data.clone()),
+            log_messages: log_messages.clone(),
+            error_details: error_details.clone(),
+            execution_time_ms: execution_time as i32,
+            executed_by: None,
+            executed_at: chrono::Utc::now(),
+        };
+        match self.evaluation_log.write(log_record).await {
+            Ok(Backpressure::Clear) => {}
+            Ok(level) => warn!("DSL execution log backlog is {:?}: {} rows pending", level, self.evaluation_log.pending()),
+            Err(e) => error!("Failed to queue DSL execution log: {}", e),
+        }
 
         let result = DslExecutionResult {
             instance_id: req.instance_id.clone(),
@@ -2821,6 +2830,7 @@ impl SimpleAiAssistant {
         false
     }
 
+    #[tracing::instrument(name = "llm.request", skip_all, fields(llm.provider = "openai"))]
     async fn get_openai_suggestions(&self, query: &str) -> Vec<LocalAiSuggestion> {
         // Enhanced with capability context
         let capability_context = self.build_capability_context().await;
@@ -2831,6 +2841,7 @@ impl SimpleAiAssistant {
         self.get_capability_aware_suggestions(query, &capability_context)
     }
 
+    #[tracing::instrument(name = "llm.request", skip_all, fields(llm.provider = "anthropic"))]
     async fn get_anthropic_suggestions(&self, query: &str) -> Vec<LocalAiSuggestion> {
         // Enhanced with capability context
         let capability_context = self.build_capability_context().await;
@@ -3018,15 +3029,139 @@ impl SimpleAiAssistant {
 
 }
 
-// Helper function to create AI assistant
+/// Trace id propagated by the caller (see `telemetry::TRACE_ID_HEADER`), empty if absent
+pub fn request_trace_id(headers: &tonic::codegen::http::HeaderMap) -> &str {
+    headers
+        .get(data_designer_core::telemetry::TRACE_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("")
+}
+
+// Helper function to create AI assistant; offline when the deployment has AI disabled
 async fn create_ai_assistant(provider: AiProvider, pool: PgPool) -> SimpleAiAssistant {
+    use data_designer_core::capabilities::{self, Capability};
+
+    let provider = if capabilities::enabled(Capability::Ai) { provider } else { AiProvider::Offline };
     SimpleAiAssistant::new(provider, Some(pool)).await
 }
 
+/// Deletes or anonymizes records past their retention policy every `interval_hours`
+fn spawn_retention_purge(pool: PgPool, interval_hours: u64) {
+    use data_designer_core::db::RetentionOperations;
+
+    info!("Retention purge scheduled every {} hours", interval_hours);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_hours.max(1) * 3600));
+        loop {
+            interval.tick().await;
+            match RetentionOperations::purge_expired(&pool, chrono::Utc::now()).await {
+                Ok(reports) => {
+                    for report in reports {
+                        info!(
+                            "Retention purge: {} {} {} records older than {}",
+                            report.action.as_str(),
+                            report.rows,
+                            report.entity_type.as_str(),
+                            report.cutoff.to_rfc3339()
+                        );
+                    }
+                }
+                Err(e) => error!("Retention purge failed: {}", e),
+            }
+        }
+    });
+}
+
+fn install_capabilities(config: &data_designer_core::config::CapabilitiesConfig) -> Result<(), Box<dyn std::error::Error>> {
+    use data_designer_core::capabilities::{self, Capabilities, DISABLE_ENV_VAR};
+
+    let (capabilities, unknown) = Capabilities::for_deployment(config);
+    for name in unknown {
+        warn!("Ignoring unknown capability '{}' in {}", name, DISABLE_ENV_VAR);
+    }
+    for (capability, enabled) in capabilities.matrix() {
+        info!("Capability {}: {}", capability.as_str(), if enabled { "enabled" } else { "disabled" });
+    }
+    capabilities::install(capabilities)?;
+    Ok(())
+}
+
+fn install_field_cipher(config: &data_designer_core::config::EncryptionConfig) -> Result<(), Box<dyn std::error::Error>> {
+    use data_designer_core::config::KeySource;
+    use data_designer_core::field_encryption::{self, FieldCipher};
+
+    let cipher = FieldCipher::from_config(config, |key_id| match config.key_source {
+        KeySource::Keychain => Ok(keyring::Entry::new("data-designer", &field_encryption::keychain_entry(key_id))?.get_password()?),
+        KeySource::Env => Ok(env::var(field_encryption::env_var(key_id))?),
+    })?;
+    info!("Field encryption enabled with key {}", cipher.current_key_id());
+    field_encryption::install(cipher)?;
+    Ok(())
+}
+
+// Refresh read models as their source tables change. Triggers mark a model
+// stale and notify; the stale flags, not the notifications, decide what is
+// refreshed, so a burst of writes costs one refresh and a lost notification
+// is made up for by the next.
+fn spawn_read_model_refresher(pool: PgPool, cache: template_api::SharedReadModelCache) {
+    use data_designer_core::db::ReadModelOperations;
+    use data_designer_core::read_models::CHANGE_CHANNEL;
+    use sqlx::postgres::PgListener;
+
+    async fn refresh_stale(pool: &PgPool, cache: &template_api::SharedReadModelCache) {
+        match ReadModelOperations::refresh_stale(pool).await {
+            Ok(versions) => {
+                for version in versions {
+                    info!("Refreshed read model {} to version {}", version.read_model.as_str(), version.version);
+                    cache.lock().unwrap().invalidate(&version);
+                }
+            }
+            Err(e) => error!("Read model refresh failed: {}", e),
+        }
+    }
+
+    tokio::spawn(async move {
+        // Catch up on changes made while the server was down
+        refresh_stale(&pool, &cache).await;
+
+        let mut listener = match PgListener::connect_with(&pool).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Read model listener could not connect: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = listener.listen(CHANGE_CHANNEL).await {
+            error!("Read model listener could not listen on {}: {}", CHANGE_CHANNEL, e);
+            return;
+        }
+        info!("Read models refresh on {} notifications", CHANGE_CHANNEL);
+
+        loop {
+            if let Err(e) = listener.recv().await {
+                // The listener reconnects by itself; notifications sent meanwhile are lost
+                warn!("Read model listener interrupted: {}", e);
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            }
+            // Let the rest of a burst of writes land before refreshing
+            tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+            refresh_stale(&pool, &cache).await;
+        }
+    });
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
+    // Initialize tracing, with OTLP export when [telemetry] is enabled and
+    // evaluation traces sent to Elasticsearch when [trace_export] is
+    let config = data_designer_core::config::Config::load()?;
+    let trace_export = data_designer_core::trace_export::TraceExporter::spawn(&config.trace_export)?;
+    let trace_exporter = trace_export.as_ref().map(|(exporter, _task)| exporter.clone());
+    let _telemetry = data_designer_core::telemetry::init(&config.telemetry, trace_exporter.as_ref())?;
+
+    // Disabled capabilities fall back to their offline behaviour instead of
+    // reaching for services this deployment doesn't have
+    install_capabilities(&config.capabilities)?;
 
     // Database connection
     let database_url = env::var("DATABASE_URL")
@@ -3036,14 +3171,49 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let db_pool = PgPool::connect(&database_url).await?;
     info!("Database connection established");
 
+    // Values of sensitive attributes are encrypted at rest; without its keys the
+    // server would write them in the clear, so a key that fails to load is fatal
+    if config.encryption.enabled {
+        install_field_cipher(&config.encryption)?;
+    }
+
+    // Active rules that stopped validating against the current grammar or data
+    // dictionary are moved to in_repair now rather than failing when first evaluated
+    match RuleOperations::check_active_rules_integrity(&db_pool, Some("system")).await {
+        Ok(report) if report.is_clean() => info!("{}", report.summary()),
+        Ok(report) => warn!("{}", report.summary()),
+        Err(e) => error!("Startup rule integrity check failed: {}", e),
+    }
+
+    if config.retention.purge_enabled {
+        spawn_retention_purge(db_pool.clone(), config.retention.purge_interval_hours);
+    }
+
+    // Heavy UI views are served from materialized read models kept fresh in the background
+    let read_model_cache = template_api::SharedReadModelCache::default();
+    spawn_read_model_refresher(db_pool.clone(), read_model_cache.clone());
+
+    // Evaluation results are buffered and inserted in chunks, shared by both services
+    let (evaluation_log, _evaluation_log_task) =
+        BatchWriter::<EvaluationResultRecord>::spawn(db_pool.clone(), &config.batch_writes)?;
+
     // Create gRPC service (owned instance for gRPC server)
-    let taxonomy_service_grpc = TaxonomyServer::new(db_pool.clone());
+    let taxonomy_service_grpc = TaxonomyServer::new(db_pool.clone(), evaluation_log.clone());
+    let rules_engine_grpc = rules_engine::RulesEngineServer::new(db_pool.clone());
 
     // Create Arc-wrapped service for HTTP delegation
-    let taxonomy_service_http = Arc::new(TaxonomyServer::new(db_pool.clone()));
+    let taxonomy_service_http = Arc::new(TaxonomyServer::new(db_pool.clone(), evaluation_log));
 
     // Create HTTP template API router with Arc-wrapped gRPC service for delegation
-    let template_router = template_api::create_template_router(db_pool, taxonomy_service_http);
+    let template_router = template_api::create_template_router(
+        db_pool,
+        taxonomy_service_http,
+        config.latency_budget.clone(),
+        trace_exporter,
+        read_model_cache,
+        config.registry.clone(),
+        config.security.clone(),
+    );
 
     // Server addresses
     let grpc_addr = "0.0.0.0:50051".parse::<std::net::SocketAddr>()?;
@@ -3054,7 +3224,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Run both servers concurrently
     let grpc_server = Server::builder()
+        .trace_fn(|request| {
+            tracing::info_span!(
+                "grpc.command",
+                path = %request.uri().path(),
+                trace_id = request_trace_id(request.headers()),
+            )
+        })
         .add_service(FinancialTaxonomyServiceServer::new(taxonomy_service_grpc))
+        .add_service(RulesEngineServiceServer::new(rules_engine_grpc))
         .serve(grpc_addr);
 
     let http_server = axum::serve(
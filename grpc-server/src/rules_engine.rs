@@ -0,0 +1,219 @@
+//! The rules engine as a gRPC service
+//!
+//! Parses, evaluates and transpiles rules and checks CBU scripts for
+//! consumers that don't link data-designer-core. Facts and values travel as
+//! JSON strings. Evaluation uses the registered lookup tables, and CBU
+//! scripts are checked against the stored LEIs and role codes; when the
+//! database can't provide them the calls carry on without. The HTTP API
+//! serves the same calls under `/api/rules-engine/`.
+
+use data_designer_core::cbu_constraints::{CbuConstraintSet, ConstraintContext};
+use data_designer_core::db::{DbOperations, LookupTableOperations};
+use data_designer_core::error_codes::ErrorCode;
+use data_designer_core::evaluator::{evaluate_with_functions, Facts, FunctionLibrary};
+use data_designer_core::lisp_cbu_dsl::{LispCbuParser, LispDslError, PendingOperation};
+use data_designer_core::models::{Expression, Value};
+use data_designer_core::parser::parse_rule;
+use data_designer_core::rule_graph::extract_dependencies_from_ast;
+use data_designer_core::source_structure::{unbalanced_delimiters, LISP_SYNTAX};
+use data_designer_core::transpiler::{TargetLanguage, Transpiler, TranspilerOptions};
+use sqlx::PgPool;
+use tonic::{Request, Response, Status};
+use tracing::{info, warn};
+
+use crate::financial_taxonomy::rules_engine_service_server::RulesEngineService;
+use crate::financial_taxonomy::*;
+
+pub struct RulesEngineServer {
+    pool: PgPool,
+}
+
+impl RulesEngineServer {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    async fn functions(&self) -> FunctionLibrary {
+        let mut functions = FunctionLibrary::new();
+        match LookupTableOperations::load_lookup_tables(&self.pool).await {
+            Ok(tables) => functions.lookup_tables = tables,
+            Err(e) => warn!("Evaluating without registered lookup tables: {}", e),
+        }
+        functions
+    }
+
+    async fn constraint_context(&self) -> ConstraintContext {
+        let entity_leis = match DbOperations::list_entity_references(&self.pool).await {
+            Ok(entities) => Some(entities.into_iter().map(|entity| (entity.entity_id, entity.lei_code)).collect()),
+            Err(e) => {
+                warn!("Checking CBU scripts without stored LEIs: {}", e);
+                None
+            }
+        };
+        let role_codes = match DbOperations::list_active_role_codes(&self.pool).await {
+            Ok(codes) => Some(codes.into_iter().collect()),
+            Err(e) => {
+                warn!("Checking CBU scripts without stored role codes: {}", e);
+                None
+            }
+        };
+        ConstraintContext { entity_leis, role_codes }
+    }
+}
+
+#[tonic::async_trait]
+impl RulesEngineService for RulesEngineServer {
+    async fn parse_rule(&self, request: Request<ParseRuleRequest>) -> Result<Response<ParseRuleResponse>, Status> {
+        info!("gRPC ParseRule called");
+        let response = match parse(&request.into_inner().rule) {
+            Ok(expression) => ParseRuleResponse {
+                success: true,
+                message: "Parsed".to_string(),
+                ast_json: serde_json::to_string(&expression).ok(),
+                dependencies: extract_dependencies_from_ast(&expression),
+            },
+            Err(message) => ParseRuleResponse { success: false, message, ..Default::default() },
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn evaluate_rule(&self, request: Request<EvaluateRuleRequest>) -> Result<Response<EvaluateRuleResponse>, Status> {
+        info!("gRPC EvaluateRule called");
+        let request = request.into_inner();
+        let evaluated = match (parse(&request.rule), facts(&request.facts_json)) {
+            (Ok(expression), Ok(facts)) => evaluate_with_functions(&expression, &facts, &self.functions().await).map_err(|e| e.to_string()),
+            (Err(message), _) | (_, Err(message)) => Err(message),
+        };
+        let response = match evaluated {
+            Ok(value) => EvaluateRuleResponse { success: true, message: "Evaluated".to_string(), value_json: Some(value.to_json().to_string()) },
+            Err(message) => EvaluateRuleResponse { success: false, message, value_json: None },
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn evaluate_batch(&self, request: Request<EvaluateBatchRequest>) -> Result<Response<EvaluateBatchResponse>, Status> {
+        let request = request.into_inner();
+        info!("gRPC EvaluateBatch called with {} fact sets", request.facts_json.len());
+        let expression = match parse(&request.rule) {
+            Ok(expression) => expression,
+            Err(message) => return Ok(Response::new(EvaluateBatchResponse { success: false, message, results: Vec::new() })),
+        };
+
+        let functions = self.functions().await;
+        let results: Vec<BatchEvaluationResult> = request
+            .facts_json
+            .iter()
+            .map(|facts_json| {
+                match facts(facts_json).and_then(|facts| evaluate_with_functions(&expression, &facts, &functions).map_err(|e| e.to_string())) {
+                    Ok(value) => BatchEvaluationResult { value_json: Some(value.to_json().to_string()), error: None },
+                    Err(error) => BatchEvaluationResult { value_json: None, error: Some(error) },
+                }
+            })
+            .collect();
+        let failed = results.iter().filter(|result| result.error.is_some()).count();
+        Ok(Response::new(EvaluateBatchResponse {
+            success: true,
+            message: format!("Evaluated {} fact sets, {} failed", results.len(), failed),
+            results,
+        }))
+    }
+
+    async fn transpile_rule(&self, request: Request<TranspileRuleRequest>) -> Result<Response<TranspileRuleResponse>, Status> {
+        info!("gRPC TranspileRule called");
+        let request = request.into_inner();
+        let transpiled = target(&request.target).and_then(|target| {
+            // A rule's code is its expression, the right-hand side of `name = ...`
+            let expression = match parse(&request.rule)? {
+                Expression::Assignment { value, .. } => *value,
+                expression => expression,
+            };
+            let transpiler = Transpiler::new(TranspilerOptions { target, optimize: request.optimize, ..TranspilerOptions::default() });
+            transpiler.transpile(&expression).map_err(|e| e.to_string())
+        });
+        let response = match transpiled {
+            Ok(code) => TranspileRuleResponse { success: true, message: "Transpiled".to_string(), code: Some(code) },
+            Err(message) => TranspileRuleResponse { success: false, message, code: None },
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn validate_cbu_script(&self, request: Request<ValidateCbuScriptRequest>) -> Result<Response<ValidateCbuScriptResponse>, Status> {
+        info!("gRPC ValidateCbuScript called");
+        let script = request.into_inner().script;
+        let diagnostic = |code: ErrorCode, message: String, start: usize, end: usize| CbuScriptDiagnostic {
+            code: code.code().to_string(),
+            message,
+            start: start as u32,
+            end: end as u32,
+            constraint: None,
+        };
+
+        // Like the language server: unbalanced parentheses where they are, then
+        // evaluation errors, then the membership constraints
+        let mut diagnostics: Vec<CbuScriptDiagnostic> = unbalanced_delimiters(&script, &LISP_SYNTAX)
+            .iter()
+            .map(|delimiter| diagnostic(ErrorCode::CbuUnbalanced, delimiter.message(), delimiter.offset, delimiter.offset + 1))
+            .collect();
+        let mut planned_operations = Vec::new();
+        if diagnostics.is_empty() {
+            match LispCbuParser::new(None).eval_plan(&script) {
+                Ok(plan) => {
+                    planned_operations = plan.iter().flatten().map(PendingOperation::summary).collect();
+                    let context = self.constraint_context().await;
+                    diagnostics.extend(CbuConstraintSet::standard().check(&script, &context).into_iter().map(|violation| CbuScriptDiagnostic {
+                        constraint: Some(violation.constraint.to_string()),
+                        ..diagnostic(ErrorCode::CbuConstraint, violation.message, violation.start, violation.end)
+                    }));
+                }
+                Err(error) => {
+                    let code = match error {
+                        LispDslError::ParseError(_) => ErrorCode::CbuSyntax,
+                        _ => ErrorCode::CbuRejected,
+                    };
+                    diagnostics.push(diagnostic(code, error.to_string(), 0, script.len()));
+                }
+            }
+        }
+
+        Ok(Response::new(ValidateCbuScriptResponse {
+            success: diagnostics.is_empty(),
+            message: format!("{} problems", diagnostics.len()),
+            diagnostics,
+            planned_operations,
+        }))
+    }
+}
+
+// A whole rule, nothing left over
+fn parse(rule: &str) -> Result<Expression, String> {
+    match parse_rule(rule) {
+        Ok((remaining, expression)) if remaining.trim().is_empty() => Ok(expression),
+        Ok((remaining, _)) => Err(format!("Unexpected input: {}", remaining.trim())),
+        Err(e) => Err(format!("Rule failed to parse: {}", e)),
+    }
+}
+
+// A JSON object of facts; empty text is no facts
+fn facts(json: &str) -> Result<Facts, String> {
+    if json.trim().is_empty() {
+        return Ok(Facts::new());
+    }
+    match serde_json::from_str::<serde_json::Value>(json) {
+        Ok(serde_json::Value::Object(facts)) => Ok(facts.iter().map(|(name, value)| (name.clone(), Value::from_json(value))).collect()),
+        Ok(_) => Err("Facts must be a JSON object".to_string()),
+        Err(e) => Err(format!("Facts are not valid JSON: {}", e)),
+    }
+}
+
+fn target(name: &str) -> Result<TargetLanguage, String> {
+    match name.to_lowercase().as_str() {
+        "rust" => Ok(TargetLanguage::Rust),
+        "sql" => Ok(TargetLanguage::SQL),
+        "javascript" | "js" => Ok(TargetLanguage::JavaScript),
+        "typescript" | "ts" => Ok(TargetLanguage::TypeScript),
+        "python" => Ok(TargetLanguage::Python),
+        "rhai" => Ok(TargetLanguage::Rhai),
+        "postgres" | "postgresql" => Ok(TargetLanguage::PostgresSql),
+        other => Err(format!("Unknown transpile target: {}", other)),
+    }
+}
@@ -1,7 +1,8 @@
 use axum::{
-    extract::{Path, Json, State},
-    http::StatusCode,
-    response::Json as ResponseJson,
+    body::Bytes,
+    extract::{Extension, Path, Json, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json as ResponseJson, Response},
     routing::{get, post},
     Router,
 };
@@ -10,10 +11,40 @@ use std::collections::HashMap;
 use tokio::fs;
 use tracing::{info, error, warn};
 use tower_http::cors::CorsLayer;
+use tower_http::trace::TraceLayer;
 use sqlx::{PgPool, Row};
 use data_designer_core::cbu_dsl::CbuDslParser;
-use data_designer_core::lisp_cbu_dsl::LispCbuParser;
+use data_designer_core::cbu_diff::{diff_cbus, CbuDefinition};
+use data_designer_core::lisp_cbu_dsl::{format_cbu_dsl, CbuFormatOptions, LispCbuParser, PendingOperation};
 use data_designer_core::dsl_utils;
+use data_designer_core::editor_access::EditorScope;
+use data_designer_core::context_diff::{diff_contexts, diff_results_with};
+use data_designer_core::evaluator::{evaluate_traced, Facts, FunctionLibrary};
+use data_designer_core::formatter::format_document;
+use data_designer_core::lookup_extraction::MIN_EXTRACTED_VALUES;
+use data_designer_core::config::{LatencyBudget, RegistryConfig, SecurityConfig};
+use data_designer_core::engine::RulesEngine;
+use data_designer_core::models::{DataDictionary, Value};
+use data_designer_core::parser::parse_rule;
+use data_designer_core::bulk_edit::BulkChange;
+use data_designer_core::rule_graph::GraphScope;
+use data_designer_core::rule_naming::apply_naming;
+use data_designer_core::rule_variants::Applicability;
+use data_designer_core::rule_rewrite::RuleRewrite;
+use data_designer_core::rule_sexpr::{from_sexpr_document, to_sexpr_document};
+use data_designer_core::db::{
+    AttributeSelection, AttributeUsageOperations, BulkEditOperations, DataDictionaryOperations, DbOperations, EditorTokenOperations, EncryptionOperations, FilterScope,
+    LookupTableOperations, ProvenanceOperations, ReadModelOperations, RetentionOperations, RuleOperations, RuleTestOperations, SavedFilter, TagFilter, TagOperations, TagTarget,
+};
+use data_designer_core::read_models::{ReadModel, ReadModelCache};
+use data_designer_core::retention::RetentionPolicy;
+use data_designer_core::rule_registry::{self, RegistryClient};
+use data_designer_core::rule_repository::{CommitAuthor, RuleRepository};
+use data_designer_core::db::grammar::GrammarOperations;
+use data_designer_core::rule_tests::RuleTestCase;
+use data_designer_core::transpiler::{wasm as rule_wasm, DslTranspiler};
+use data_designer_core::type_checker::{typecheck_with_env, RuleType, TypeEnv};
+use data_designer_core::trace_export::TraceExporter;
 
 // Import gRPC types for HTTP endpoint compatibility
 pub mod financial_taxonomy {
@@ -24,6 +55,12 @@ pub mod financial_taxonomy {
 // Import the gRPC service implementation and trait
 use crate::TaxonomyServer;
 use crate::financial_taxonomy::financial_taxonomy_service_server::FinancialTaxonomyService;
+use crate::financial_taxonomy::rules_engine_service_server::RulesEngineService;
+use crate::financial_taxonomy::{
+    EvaluateBatchRequest, EvaluateBatchResponse, EvaluateRuleRequest, EvaluateRuleResponse, ParseRuleRequest, ParseRuleResponse,
+    TranspileRuleRequest, TranspileRuleResponse, ValidateCbuScriptRequest, ValidateCbuScriptResponse,
+};
+use crate::rules_engine::RulesEngineServer;
 
 // Template data structures matching the WASM client
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -139,12 +176,21 @@ pub struct AiSuggestion {
 
 const TEMPLATES_FILE_PATH: &str = "../resource_templates.json";
 
-pub fn create_template_router(db_pool: PgPool, taxonomy_server: std::sync::Arc<TaxonomyServer>) -> Router {
+pub fn create_template_router(
+    db_pool: PgPool,
+    taxonomy_server: std::sync::Arc<TaxonomyServer>,
+    latency_budget: LatencyBudget,
+    trace_exporter: Option<TraceExporter>,
+    read_model_cache: SharedReadModelCache,
+    registry: RegistryConfig,
+    security: SecurityConfig,
+) -> Router {
     Router::new()
         // ============================================================================
         // EXISTING WORKING ENDPOINTS - Keep current functionality
         // ============================================================================
         .route("/api/health", get(health_check))
+        .route("/api/capabilities", get(get_capabilities))
         .route("/api/templates", get(get_all_templates))
         .route("/api/templates/:id", get(get_template))
 
@@ -156,6 +202,73 @@ pub fn create_template_router(db_pool: PgPool, taxonomy_server: std::sync::Arc<T
         .route("/api/ai-suggestions", post(get_ai_suggestions))
         .route("/api/entities", post(get_entities))
         .route("/api/list-products", post(list_products))
+        .route("/api/validate-rule-types", post(validate_rule_types))
+        .route("/api/format-dsl", post(format_dsl))
+        .route("/api/format-cbu-dsl", post(format_cbu_dsl_script))
+        .route("/api/plan-cbu-dsl", post(plan_cbu_dsl_script))
+        .route("/api/export-cbu-dsl", post(export_cbu_dsl))
+        .route("/api/diff-cbu-dsl", post(diff_cbu_dsl))
+        // The rules engine gRPC service, as JSON
+        .route("/api/rules-engine/parse-rule", post(rules_engine_parse_rule))
+        .route("/api/rules-engine/evaluate-rule", post(rules_engine_evaluate_rule))
+        .route("/api/rules-engine/evaluate-batch", post(rules_engine_evaluate_batch))
+        .route("/api/rules-engine/transpile-rule", post(rules_engine_transpile_rule))
+        .route("/api/rules-engine/validate-cbu-script", post(rules_engine_validate_cbu_script))
+        .route("/api/rule-statistics", post(get_rule_statistics))
+        .route("/api/issue-editor-token", post(issue_editor_token))
+        .route("/api/revoke-editor-token", post(revoke_editor_token))
+        .route("/api/convert-rule-syntax", post(convert_rule_syntax))
+        .route("/api/explain-rule-evaluation", post(explain_rule_evaluation))
+        .route("/api/diff-evaluation-contexts", post(diff_evaluation_contexts))
+        .route("/api/compile-rule-wasm", post(compile_rule_wasm))
+
+        // Tags and saved filters for the dictionary and rule browsers
+        .route("/api/list-tags", post(list_tags))
+        .route("/api/assign-tags", post(assign_tags))
+        .route("/api/unassign-tags", post(unassign_tags))
+        .route("/api/search-dictionary", post(search_dictionary))
+        .route("/api/search-rules", post(search_rules))
+        .route("/api/list-saved-filters", post(list_saved_filters))
+        .route("/api/save-filter", post(save_filter))
+        .route("/api/delete-saved-filter", post(delete_saved_filter))
+
+        // Bulk attribute edits: preview, apply, and undo within the undo window
+        .route("/api/preview-bulk-edit", post(preview_bulk_edit))
+        .route("/api/apply-bulk-edit", post(apply_bulk_edit))
+        .route("/api/undo-bulk-edit", post(undo_bulk_edit))
+        .route("/api/list-bulk-edits", post(list_bulk_edits))
+        .route("/api/list-retention-policies", post(list_retention_policies))
+        .route("/api/set-retention-policy", post(set_retention_policy))
+        .route("/api/purge-expired-data", post(purge_expired_data))
+        .route("/api/erase-subject", post(erase_subject))
+        .route("/api/list-erasures", post(list_erasures))
+        .route("/api/check-rule-naming", post(check_rule_naming))
+        .route("/api/preview-rule-rewrite", post(preview_rule_rewrite))
+        .route("/api/apply-rule-rewrite", post(apply_rule_rewrite))
+        .route("/api/export-attribute-workbook", post(export_attribute_workbook))
+        .route("/api/preview-attribute-workbook-import", post(preview_attribute_workbook_import))
+        .route("/api/import-attribute-workbook", post(import_attribute_workbook))
+        .route("/api/attribute-usage-heatmap", post(attribute_usage_heatmap))
+        .route("/api/rule-dependency-graph", post(get_rule_dependency_graph))
+        .route("/api/get-rule-history", post(get_rule_history))
+        .route("/api/get-rules-as-of", post(get_rules_as_of))
+        .route("/api/diff-rule-versions", post(diff_rule_versions))
+        .route("/api/list-rule-test-cases", post(list_rule_test_cases))
+        .route("/api/save-rule-test-case", post(save_rule_test_case))
+        .route("/api/delete-rule-test-case", post(delete_rule_test_case))
+        .route("/api/run-rule-tests", post(run_rule_tests))
+        .route("/api/set-rule-status", post(set_rule_status))
+        .route("/api/estimate-rule-cost", post(estimate_rule_cost))
+        .route("/api/rule-coverage-report", post(rule_coverage_report))
+        .route("/api/materialize-attributes", post(materialize_attributes))
+        .route("/api/get-provenance", post(get_provenance))
+        .route("/api/check-rule-state", post(check_database_rule_state))
+        .route("/api/check-rule-integrity", post(check_rule_integrity))
+        .route("/api/plan-rule-repair", post(plan_rule_repair))
+        .route("/api/apply-rule-repair", post(apply_rule_repair))
+        .route("/api/list-lookup-tables", post(list_lookup_tables))
+        .route("/api/find-inline-lists", post(find_inline_lists))
+        .route("/api/extract-lookup-table", post(extract_lookup_table))
 
         // Resource DSL endpoints - EXISTING WORKING
         .route("/api/list-resources", post(list_resources))
@@ -179,8 +292,42 @@ pub fn create_template_router(db_pool: PgPool, taxonomy_server: std::sync::Arc<T
         .route("/api/onboarding/UpdateOnboardingRequestDsl", post(update_onboarding_request_dsl))
         .route("/api/onboarding/CompileOnboardingWorkflow", post(compile_onboarding_workflow_grpc))
         .route("/api/onboarding/ExecuteOnboardingWorkflow", post(execute_onboarding_workflow_grpc))
-
-        .with_state((db_pool, taxonomy_server))
+        .route("/api/onboarding/instance-history", post(get_onboarding_instance_history))
+        .route("/api/onboarding/instance-state-at", post(get_onboarding_instance_state_at))
+
+        // Scripted end-to-end walkthroughs for demos
+        .route("/api/list-demo-scenarios", post(list_demo_scenarios))
+        .route("/api/run-demo-scenario", post(run_demo_scenario))
+
+        // Moving sensitive attribute values onto the current field encryption key
+        .route("/api/rotate-field-keys", post(rotate_field_keys))
+
+        // Materialized read models behind the product, taxonomy and onboarding tabs
+        .route("/api/read-model-versions", post(get_read_model_versions))
+        .route("/api/read-model", post(read_model))
+        .route("/api/refresh-read-models", post(refresh_read_models))
+
+        // Shared rule packs from the configured registry
+        .route("/api/registry/packs", get(list_registry_packs))
+        .route("/api/registry/install", post(install_registry_pack))
+
+        .with_state((db_pool.clone(), taxonomy_server))
+
+        // GraphQL over rules, dictionary, CBUs and evaluation, for nested views in one request
+        .merge(crate::graphql_api::create_graphql_router(db_pool))
+        .layer(Extension(latency_budget))
+        .layer(Extension(trace_exporter))
+        .layer(Extension(read_model_cache))
+        .layer(Extension(registry))
+        .layer(Extension(security))
+        .layer(TraceLayer::new_for_http().make_span_with(|request: &axum::http::Request<axum::body::Body>| {
+            tracing::info_span!(
+                "http.command",
+                method = %request.method(),
+                path = %request.uri().path(),
+                trace_id = crate::request_trace_id(request.headers()),
+            )
+        }))
         .layer(CorsLayer::permissive()) // Enable CORS for browser requests
 }
 
@@ -195,6 +342,22 @@ async fn health_check() -> Result<ResponseJson<HealthCheckResponse>, StatusCode>
     Ok(ResponseJson(response))
 }
 
+/// Which optional capabilities this deployment runs with, so UIs can hide
+/// features that would only fall back to their offline behaviour
+async fn get_capabilities() -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    let capabilities: serde_json::Map<String, serde_json::Value> = data_designer_core::capabilities::current()
+        .matrix()
+        .into_iter()
+        .map(|(capability, enabled)| (capability.as_str().to_string(), serde_json::Value::Bool(enabled)))
+        .collect();
+
+    Ok(ResponseJson(serde_json::json!({
+        "success": true,
+        "message": "Capabilities of this deployment",
+        "capabilities": capabilities
+    })))
+}
+
 async fn get_all_templates() -> Result<ResponseJson<GetAllTemplatesResponse>, StatusCode> {
     info!("Getting all templates from file: {}", TEMPLATES_FILE_PATH);
 
@@ -282,14 +445,23 @@ async fn execute_cbu_dsl_http(
         info!("Detected LISP syntax, using LISP parser");
         let mut lisp_parser = LispCbuParser::new(Some(pool.clone()));
 
-        match lisp_parser.parse_and_eval(&dsl_script) {
-            Ok(result) => {
+        // All statements commit together or none do
+        match lisp_parser.execute_script(&dsl_script).await {
+            Ok(execution) => {
+                let errors: Vec<String> = execution.statements.iter().filter_map(|s| s.error.clone()).collect();
+                let message = if execution.committed {
+                    format!("LISP DSL executed successfully: {} statements committed", execution.statements.len())
+                } else {
+                    format!("LISP DSL rolled back: {}", errors.join("; "))
+                };
+                let cbu_id = execution.statements.iter().flat_map(|s| s.cbu_ids.first()).next().filter(|_| execution.committed);
                 let response = serde_json::json!({
-                    "success": true,
-                    "message": format!("LISP DSL executed successfully: {}", result.message),
-                    "cbu_id": result.cbu_id,
-                    "validation_errors": [],
-                    "data": result.data.map(|d| serde_json::to_string(&d).unwrap_or_else(|_| "null".to_string()))
+                    "success": execution.committed,
+                    "message": message,
+                    "cbu_id": cbu_id,
+                    "validation_errors": errors,
+                    "data": null,
+                    "statements": execution.statements
                 });
                 Ok(ResponseJson(response))
             }
@@ -573,68 +745,2096 @@ async fn list_products(
     }
 }
 
-async fn instantiate_resource(
-    State((_, taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+async fn instantiate_resource(
+    State((_, taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP InstantiateResource called - delegating to gRPC implementation");
+
+    // Convert JSON request to gRPC type
+    let grpc_request_data = crate::financial_taxonomy::InstantiateResourceRequest {
+        template_id: request.get("template_id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+        onboarding_request_id: request.get("onboarding_request_id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+        context: request.get("context").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        initial_data: request.get("initial_data").and_then(|v| v.as_str()).map(|s| s.to_string()),
+    };
+
+    let grpc_request = tonic::Request::new(grpc_request_data);
+
+    match taxonomy_server.instantiate_resource(grpc_request).await {
+        Ok(grpc_response) => {
+            let response = grpc_response.into_inner();
+            info!("gRPC InstantiateResource succeeded");
+            let json_response = serde_json::json!({
+                "success": response.success,
+                "message": response.message,
+                "instance": null
+            });
+            Ok(ResponseJson(json_response))
+        }
+        Err(status) => {
+            error!("gRPC InstantiateResource failed: {}", status);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn execute_dsl(
+    State((_, taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ExecuteDsl called - delegating to gRPC implementation");
+
+    // Convert JSON request to gRPC type
+    let grpc_request_data = crate::financial_taxonomy::ExecuteDslRequest {
+        instance_id: request.get("instance_id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+        execution_context: request.get("execution_context").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        input_data: request.get("input_data").and_then(|v| v.as_str()).map(|s| s.to_string()),
+    };
+
+    let grpc_request = tonic::Request::new(grpc_request_data);
+
+    match taxonomy_server.execute_dsl(grpc_request).await {
+        Ok(grpc_response) => {
+            let response = grpc_response.into_inner();
+            info!("gRPC ExecuteDsl succeeded");
+            let json_response = serde_json::json!({
+                "success": response.success,
+                "message": response.message,
+                "result": null
+            });
+            Ok(ResponseJson(json_response))
+        }
+        Err(status) => {
+            error!("gRPC ExecuteDsl failed: {}", status);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// ============================================
+// RULE VALIDATION ENDPOINTS
+// ============================================
+
+async fn validate_rule_types(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ValidateRuleTypes called");
+
+    let rule_text = request["rule"].as_str().unwrap_or("");
+    if rule_text.trim().is_empty() {
+        return Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": "Rule text is required",
+            "diagnostics": []
+        })));
+    }
+
+    // Attribute types come from the data dictionary, with optional per-request overrides
+    let mut type_env = TypeEnv::new();
+    match DataDictionaryOperations::get_data_dictionary(&pool, None).await {
+        Ok(dictionary) => {
+            for attribute in &dictionary.attributes {
+                let rule_type = RuleType::from_type_name(attribute["data_type"].as_str().unwrap_or(""));
+                for key in ["attribute_name", "full_path"] {
+                    if let Some(name) = attribute[key].as_str() {
+                        type_env.insert(name, rule_type);
+                    }
+                }
+            }
+        }
+        Err(e) => warn!("Type checking without data dictionary: {}", e),
+    }
+    if let Some(overrides) = request["attribute_types"].as_object() {
+        for (name, data_type) in overrides {
+            type_env.insert(name, RuleType::from_type_name(data_type.as_str().unwrap_or("")));
+        }
+    }
+
+    let transpiler = DslTranspiler {
+        validation_enabled: false,
+        dependency_analysis: false,
+    };
+    let rules = match transpiler.transpile_dsl_to_rules(rule_text) {
+        Ok(rules) => rules,
+        Err(errors) => {
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": "Rule failed to parse",
+                "diagnostics": errors.iter().map(|e| serde_json::json!({
+                    "rule": e.rule_name,
+                    "line": e.line,
+                    "message": e.to_string(),
+                })).collect::<Vec<_>>()
+            })));
+        }
+    };
+
+    let mut diagnostics = Vec::new();
+    let mut inferred_types = serde_json::Map::new();
+    for rule in &rules {
+        let result = typecheck_with_env(&rule.expression, &type_env);
+        inferred_types.insert(rule.name.clone(), serde_json::json!(result.inferred.to_string()));
+        for diagnostic in result.diagnostics {
+            diagnostics.push(serde_json::json!({
+                "rule": rule.name,
+                "line": rule.line_number,
+                "expression": diagnostic.expression,
+                "message": diagnostic.message,
+            }));
+        }
+    }
+
+    Ok(ResponseJson(serde_json::json!({
+        "success": diagnostics.is_empty(),
+        "message": format!("Type checked {} rules, {} issues", rules.len(), diagnostics.len()),
+        "inferred_types": inferred_types,
+        "diagnostics": diagnostics
+    })))
+}
+
+// Backs the rule editor's Format button; unparseable text is returned unchanged
+async fn format_dsl(
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP FormatDsl called");
+
+    let dsl_text = request["rule"].as_str().unwrap_or("");
+    match format_document(dsl_text) {
+        Ok(formatted) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": "Formatted",
+            "formatted": formatted
+        }))),
+        Err(e) => Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": e.to_string(),
+            "formatted": dsl_text
+        }))),
+    }
+}
+
+// Backs the CBU DSL editor's Format button; `width` and `indent` default to 80
+// and 2, and unparseable text is returned unchanged
+async fn format_cbu_dsl_script(
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP FormatCbuDsl called");
+
+    let dsl_script = request["dsl_script"].as_str().unwrap_or("");
+    let defaults = CbuFormatOptions::default();
+    let options = CbuFormatOptions {
+        width: request["width"].as_u64().map_or(defaults.width, |width| width as usize),
+        indent: request["indent"].as_u64().map_or(defaults.indent, |indent| indent as usize),
+    };
+    match format_cbu_dsl(dsl_script, &options) {
+        Ok(formatted) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": "Formatted",
+            "formatted": formatted
+        }))),
+        Err(e) => Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": e.to_string(),
+            "formatted": dsl_script
+        }))),
+    }
+}
+
+// Dry run of a CBU DSL script: the operations each top-level expression would
+// perform, in order, without touching the database
+async fn plan_cbu_dsl_script(
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP PlanCbuDsl called");
+
+    let dsl_script = request["dsl_script"].as_str().unwrap_or("");
+    match LispCbuParser::new(None).eval_plan(dsl_script) {
+        Ok(plan) => {
+            let operations: Vec<PendingOperation> = plan.into_iter().flatten().collect();
+            let summaries: Vec<String> = operations.iter().map(PendingOperation::summary).collect();
+            Ok(ResponseJson(serde_json::json!({
+                "success": true,
+                "message": format!("{} pending operations", operations.len()),
+                "operations": operations,
+                "summaries": summaries
+            })))
+        }
+        Err(e) => Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": e.to_string(),
+            "operations": [],
+            "summaries": []
+        }))),
+    }
+}
+
+// Backs the dashboard panel: rule counts, weekly changes, top authors, complexity and
+// rules in repair. `weeks` (default 12) is how far back change activity goes.
+async fn get_rule_statistics(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP GetRuleStatistics called");
+
+    let weeks = request["weeks"].as_u64().unwrap_or(12).clamp(1, 104) as usize;
+    match RuleOperations::get_rule_statistics(&pool, weeks).await {
+        Ok(statistics) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("{} rules", statistics.total_rules),
+            "statistics": statistics
+        }))),
+        Err(e) => {
+            error!("Failed to compute rule statistics: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// Issues a token for the language servers' initializationOptions.authToken.
+// `scope` defaults to everything; `expires_at` is RFC 3339, or absent for no expiry.
+async fn issue_editor_token(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP IssueEditorToken called");
+
+    let Some(subject) = request["subject"].as_str() else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let scope = match request.get("scope") {
+        Some(scope) => match serde_json::from_value::<EditorScope>(scope.clone()) {
+            Ok(scope) => scope,
+            Err(e) => {
+                return Ok(ResponseJson(serde_json::json!({
+                    "success": false,
+                    "message": format!("Invalid editor scope: {}", e)
+                })));
+            }
+        },
+        None => EditorScope::unrestricted(),
+    };
+    let expires_at = match request["expires_at"].as_str().map(::chrono::DateTime::parse_from_rfc3339) {
+        Some(Ok(expires_at)) => Some(expires_at.with_timezone(&::chrono::Utc)),
+        Some(Err(e)) => {
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": format!("Invalid expires_at: {}", e)
+            })));
+        }
+        None => None,
+    };
+
+    match EditorTokenOperations::issue_editor_token(&pool, subject, &scope, expires_at).await {
+        Ok(token) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Issued an editor token for {}", subject),
+            "token": token,
+            "scope": scope
+        }))),
+        Err(e) => {
+            error!("Failed to issue editor token: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn revoke_editor_token(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP RevokeEditorToken called");
+
+    let Some(token) = request["token"].as_str() else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    match EditorTokenOperations::revoke_editor_token(&pool, token).await {
+        Ok(revoked) => Ok(ResponseJson(serde_json::json!({
+            "success": revoked,
+            "message": if revoked { "Editor token revoked" } else { "Editor token is unknown or already revoked" }
+        }))),
+        Err(e) => {
+            error!("Failed to revoke editor token: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// A stored CBU as canonical CBU DSL, to edit and execute again
+async fn export_cbu_dsl(
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ExportCbuDsl called");
+
+    let Some(cbu_id) = request["cbu_id"].as_str() else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    match DbOperations::cbu_to_sexpr(cbu_id).await {
+        Ok(dsl_script) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Exported {}", cbu_id),
+            "dsl_script": dsl_script
+        }))),
+        Err(e) => Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": e,
+            "dsl_script": null
+        }))),
+    }
+}
+
+// ============================================
+// RULES ENGINE SERVICE OVER HTTP
+// ============================================
+
+// Each delegates to the gRPC implementation, taking and returning its messages as JSON
+async fn rules_engine_parse_rule(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<ParseRuleRequest>,
+) -> Result<ResponseJson<ParseRuleResponse>, StatusCode> {
+    let response = RulesEngineServer::new(pool).parse_rule(tonic::Request::new(request)).await;
+    response.map(|response| ResponseJson(response.into_inner())).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn rules_engine_evaluate_rule(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<EvaluateRuleRequest>,
+) -> Result<ResponseJson<EvaluateRuleResponse>, StatusCode> {
+    let response = RulesEngineServer::new(pool).evaluate_rule(tonic::Request::new(request)).await;
+    response.map(|response| ResponseJson(response.into_inner())).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn rules_engine_evaluate_batch(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<EvaluateBatchRequest>,
+) -> Result<ResponseJson<EvaluateBatchResponse>, StatusCode> {
+    let response = RulesEngineServer::new(pool).evaluate_batch(tonic::Request::new(request)).await;
+    response.map(|response| ResponseJson(response.into_inner())).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn rules_engine_transpile_rule(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<TranspileRuleRequest>,
+) -> Result<ResponseJson<TranspileRuleResponse>, StatusCode> {
+    let response = RulesEngineServer::new(pool).transpile_rule(tonic::Request::new(request)).await;
+    response.map(|response| ResponseJson(response.into_inner())).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn rules_engine_validate_cbu_script(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<ValidateCbuScriptRequest>,
+) -> Result<ResponseJson<ValidateCbuScriptResponse>, StatusCode> {
+    let response = RulesEngineServer::new(pool).validate_cbu_script(tonic::Request::new(request)).await;
+    response.map(|response| ResponseJson(response.into_inner())).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+// Compares `new_dsl` with `old_dsl`, or with the stored CBU `cbu_id`, and
+// returns the changes with the update-cbu script that makes them, for review
+// before it is executed. Comparing two documents needs `cbu_id` for the script.
+async fn diff_cbu_dsl(
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP DiffCbuDsl called");
+
+    let Some(new_dsl) = request["new_dsl"].as_str() else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let cbu_id = request["cbu_id"].as_str();
+    let (old, unexpressible) = match (request["old_dsl"].as_str(), cbu_id) {
+        (Some(old_dsl), _) => (CbuDefinition::parse(old_dsl).map_err(|e| format!("Old document: {}", e)), Vec::new()),
+        (None, Some(cbu_id)) => match DbOperations::cbu_definition(cbu_id).await {
+            Ok((definition, unexpressible)) => (Ok(definition), unexpressible),
+            Err(e) => (Err(e), Vec::new()),
+        },
+        (None, None) => return Err(StatusCode::BAD_REQUEST),
+    };
+    let compared = old.and_then(|old| {
+        let new = CbuDefinition::parse(new_dsl).map_err(|e| format!("New document: {}", e))?;
+        Ok(diff_cbus(&old, &new))
+    });
+
+    match compared {
+        Ok(diff) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": if diff.is_empty() { "No changes".to_string() } else { "Changes found".to_string() },
+            "patch_script": cbu_id.and_then(|cbu_id| diff.patch_script(cbu_id)),
+            "diff": diff,
+            // Stored members the DSL can't express are left alone by the script
+            "unexpressible": unexpressible
+        }))),
+        Err(e) => Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": e,
+            "diff": null,
+            "patch_script": null
+        }))),
+    }
+}
+
+// Backs the rule editor's syntax toggle: `to` is "sexpr" to show rules as
+// S-expressions or "infix" to turn edited S-expressions back into the canonical
+// infix that is saved
+async fn convert_rule_syntax(
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ConvertRuleSyntax called");
+
+    let rule_text = request["rule"].as_str().unwrap_or("");
+    let converted = match request["to"].as_str().unwrap_or("sexpr") {
+        "sexpr" => to_sexpr_document(rule_text),
+        "infix" => from_sexpr_document(rule_text),
+        other => Err(format!("Unknown syntax '{}'; expected \"sexpr\" or \"infix\"", other)),
+    };
+    match converted {
+        Ok(converted) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": "Converted",
+            "converted": converted
+        }))),
+        Err(e) => Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": e,
+            "converted": rule_text
+        }))),
+    }
+}
+
+// Backs the rule tester's Explain view: every sub-expression with its inputs and value.
+// With [trace_export] enabled the trace is also indexed under the caller's trace id.
+async fn explain_rule_evaluation(
+    headers: axum::http::HeaderMap,
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Extension(trace_exporter): Extension<Option<TraceExporter>>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ExplainRuleEvaluation called");
+
+    let rule_text = request["rule"].as_str().unwrap_or("");
+    let expression = match parse_rule(rule_text) {
+        Ok((remaining, expression)) if remaining.trim().is_empty() => expression,
+        Ok((remaining, _)) => {
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": format!("Unexpected input: {}", remaining.trim())
+            })));
+        }
+        Err(e) => {
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": format!("Rule failed to parse: {}", e)
+            })));
+        }
+    };
+
+    let facts: Facts = request["facts"]
+        .as_object()
+        .map(|facts| facts.iter().map(|(name, value)| (name.clone(), Value::from_json(value))).collect())
+        .unwrap_or_default();
+    let mut functions = FunctionLibrary::new();
+    match LookupTableOperations::load_lookup_tables(&pool).await {
+        Ok(tables) => functions.lookup_tables = tables,
+        Err(e) => warn!("Evaluating without registered lookup tables: {}", e),
+    }
+    let trace = evaluate_traced(&expression, &facts, &functions);
+    if let Some(exporter) = &trace_exporter {
+        // Exported traces are indexed for full-text search, so sensitive values stay out of them
+        match EncryptionOperations::sensitive_attributes(&pool).await {
+            Ok(sensitive) => {
+                let mut exported = trace.clone();
+                sensitive.redact_trace(&mut exported);
+                exporter.export_explain(crate::request_trace_id(&headers), rule_text, &exported);
+            }
+            Err(e) => warn!("Explain trace not exported: {}", e),
+        }
+    }
+
+    Ok(ResponseJson(serde_json::json!({
+        "success": trace.error.is_none(),
+        "message": trace.error.clone().unwrap_or_else(|| "Evaluated".to_string()),
+        "value": trace.value.as_ref().map(Value::to_json),
+        "trace": trace
+    })))
+}
+
+// Backs the Context Diff panel: the attributes two contexts (say UAT's and prod's
+// facts for one CBU) disagree on and, given a rule, the sub-expressions whose
+// values diverge between them. Without a rule only the contexts are compared.
+async fn diff_evaluation_contexts(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP DiffEvaluationContexts called");
+
+    let facts = |field: &str| -> Facts {
+        request[field]
+            .as_object()
+            .map(|facts| facts.iter().map(|(name, value)| (name.clone(), Value::from_json(value))).collect())
+            .unwrap_or_default()
+    };
+    let (facts_a, facts_b) = (facts("facts_a"), facts("facts_b"));
+
+    let attributes = diff_contexts(&facts_a, &facts_b);
+
+    let rule_text = request["rule"].as_str().unwrap_or("");
+    if rule_text.trim().is_empty() {
+        return Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("{} attributes differ", attributes.len()),
+            "attributes": attributes
+        })));
+    }
+    let expression = match parse_rule(rule_text) {
+        Ok((remaining, expression)) if remaining.trim().is_empty() => expression,
+        Ok((remaining, _)) => {
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": format!("Unexpected input: {}", remaining.trim())
+            })));
+        }
+        Err(e) => {
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": format!("Rule failed to parse: {}", e)
+            })));
+        }
+    };
+
+    let mut functions = FunctionLibrary::new();
+    match LookupTableOperations::load_lookup_tables(&pool).await {
+        Ok(tables) => functions.lookup_tables = tables,
+        Err(e) => warn!("Evaluating without registered lookup tables: {}", e),
+    }
+    let diff = diff_results_with(&expression, &facts_a, &facts_b, &functions);
+    let message = if diff.differs() {
+        let origins: Vec<&str> = diff.origins().map(|divergence| divergence.expression.as_str()).collect();
+        format!("Results differ, starting at {}", origins.join(", "))
+    } else {
+        "Same result in both contexts".to_string()
+    };
+
+    Ok(ResponseJson(serde_json::json!({
+        "success": true,
+        "message": message,
+        "attributes": attributes,
+        "diff": diff
+    })))
+}
+
+/// The rule as a WebAssembly module (`application/wasm`) that web-ui loads with
+/// `wasm_utils::load_rule_module`. Rules it can't express get a 422 and keep
+/// evaluating through explain-rule-evaluation.
+async fn compile_rule_wasm(Json(request): Json<serde_json::Value>) -> Response {
+    info!("HTTP CompileRuleWasm called");
+
+    let rule_text = request["rule"].as_str().unwrap_or("");
+    let compiled = match parse_rule(rule_text) {
+        Ok((remaining, expression)) if remaining.trim().is_empty() => rule_wasm::compile(&expression),
+        Ok((remaining, _)) => Err(anyhow::anyhow!("Unexpected input: {}", remaining.trim())),
+        Err(e) => Err(anyhow::anyhow!("Rule failed to parse: {}", e)),
+    };
+
+    match compiled {
+        Ok(compiled) => ([(header::CONTENT_TYPE, "application/wasm")], compiled.module).into_response(),
+        Err(e) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ResponseJson(serde_json::json!({
+                "success": false,
+                "message": e.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
+
+// ============================================
+// TAG AND SAVED FILTER ENDPOINTS
+// ============================================
+
+fn tag_target(request: &serde_json::Value) -> Option<(TagTarget, String)> {
+    let target = serde_json::from_value(request["target_type"].clone()).ok()?;
+    let key = request["target_key"].as_str().filter(|key| !key.is_empty())?;
+    Some((target, key.to_string()))
+}
+
+fn tag_filter(request: &serde_json::Value) -> TagFilter {
+    serde_json::from_value(request.clone()).unwrap_or_default()
+}
+
+fn tag_names(request: &serde_json::Value) -> Vec<String> {
+    tag_filter(request).tags
+}
+
+fn search_text(request: &serde_json::Value) -> Option<&str> {
+    request["search"].as_str().map(str::trim).filter(|text| !text.is_empty())
+}
+
+async fn list_tags(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ListTags called");
+
+    match TagOperations::list_tags(&pool).await {
+        Ok(tags) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Found {} tags", tags.len()),
+            "tags": tags
+        }))),
+        Err(e) => {
+            error!("Failed to list tags: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn assign_tags(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP AssignTags called");
+
+    let Some((target, key)) = tag_target(&request) else {
+        return Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": "target_type ('attribute' or 'rule') and target_key are required"
+        })));
+    };
+
+    let user_id = request["user_id"].as_str();
+    if let Err(e) = TagOperations::assign_tags(&pool, target, &key, &tag_names(&request), user_id).await {
+        error!("Failed to assign tags to {}: {}", key, e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    match TagOperations::get_tags_for(&pool, target, &key).await {
+        Ok(tags) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Tagged {}", key),
+            "tags": tags
+        }))),
+        Err(e) => {
+            error!("Failed to read tags for {}: {}", key, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn unassign_tags(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP UnassignTags called");
+
+    let Some((target, key)) = tag_target(&request) else {
+        return Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": "target_type ('attribute' or 'rule') and target_key are required"
+        })));
+    };
+
+    match TagOperations::unassign_tags(&pool, target, &key, &tag_names(&request)).await {
+        Ok(removed) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Removed {} tags from {}", removed, key)
+        }))),
+        Err(e) => {
+            error!("Failed to remove tags from {}: {}", key, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn search_dictionary(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP SearchDictionary called");
+
+    match DataDictionaryOperations::filter_data_dictionary(&pool, search_text(&request), &tag_filter(&request)).await {
+        Ok(dictionary) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Found {} attributes", dictionary.total_count),
+            "attributes": dictionary.attributes,
+            "total_count": dictionary.total_count
+        }))),
+        Err(e) => {
+            error!("Failed to search data dictionary: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn search_rules(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP SearchRules called");
+
+    match RuleOperations::search_rules(&pool, search_text(&request), &tag_filter(&request)).await {
+        Ok(rules) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Found {} rules", rules.len()),
+            "rules": rules
+        }))),
+        Err(e) => {
+            error!("Failed to search rules: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn list_saved_filters(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ListSavedFilters called");
+
+    let user_id = request["user_id"].as_str().unwrap_or("default");
+    let scope: Option<FilterScope> = serde_json::from_value(request["scope"].clone()).ok();
+
+    match TagOperations::list_saved_filters(&pool, user_id, scope).await {
+        Ok(filters) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Found {} saved filters", filters.len()),
+            "filters": filters
+        }))),
+        Err(e) => {
+            error!("Failed to list saved filters: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn save_filter(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP SaveFilter called");
+
+    let filter: SavedFilter = match serde_json::from_value(request) {
+        Ok(filter) => filter,
+        Err(e) => {
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": format!("Invalid filter: {}", e)
+            })));
+        }
+    };
+    if filter.name.trim().is_empty() {
+        return Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": "Filter name is required"
+        })));
+    }
+
+    match TagOperations::save_filter(&pool, &filter).await {
+        Ok(id) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Saved filter '{}'", filter.name),
+            "id": id
+        }))),
+        Err(e) => {
+            error!("Failed to save filter: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn delete_saved_filter(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP DeleteSavedFilter called");
+
+    let user_id = request["user_id"].as_str().unwrap_or("default");
+    let Some(filter_id) = request["id"].as_i64().and_then(|id| i32::try_from(id).ok()) else {
+        return Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": "Filter id is required"
+        })));
+    };
+
+    match TagOperations::delete_saved_filter(&pool, user_id, filter_id).await {
+        Ok(deleted) => {
+            let message = if deleted { "Filter deleted" } else { "Filter not found" };
+            Ok(ResponseJson(serde_json::json!({
+                "success": deleted,
+                "message": message
+            })))
+        }
+        Err(e) => {
+            error!("Failed to delete filter: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// ============================================
+// BULK ATTRIBUTE EDIT ENDPOINTS
+// ============================================
+
+/// Selection and change shared by preview and apply requests
+fn bulk_edit_request(request: &serde_json::Value) -> Result<(AttributeSelection, BulkChange), String> {
+    let selection = serde_json::from_value(request.clone())
+        .map_err(|e| format!("Invalid attribute selection: {}", e))?;
+    let change = serde_json::from_value(request["change"].clone())
+        .map_err(|e| format!("Invalid change: {}", e))?;
+    Ok((selection, change))
+}
+
+async fn preview_bulk_edit(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP PreviewBulkEdit called");
+
+    let (selection, change) = match bulk_edit_request(&request) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": message
+            })));
+        }
+    };
+    if let Err(message) = change.validate() {
+        return Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": message
+        })));
+    }
+
+    match BulkEditOperations::preview(&pool, &selection, change).await {
+        Ok(preview) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("{}: {} attributes change, {} skipped", preview.change, preview.rows.len(), preview.skipped.len()),
+            "preview": preview
+        }))),
+        Err(e) => {
+            error!("Failed to preview bulk edit: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn apply_bulk_edit(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ApplyBulkEdit called");
+
+    let (selection, change) = match bulk_edit_request(&request) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": message
+            })));
+        }
+    };
+    let expected_rows = request["expected_rows"].as_u64().map(|n| n as usize);
+    let applied_by = request["user_id"].as_str();
+
+    match BulkEditOperations::apply(&pool, &selection, change, expected_rows, applied_by).await {
+        Ok(batch) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!(
+                "Applied '{}' to {} attributes; undo available until {}",
+                batch.change,
+                batch.rows.len(),
+                batch.undo_expires_at.to_rfc3339()
+            ),
+            "batch": batch
+        }))),
+        Err(e) => {
+            warn!("Bulk edit not applied: {}", e);
+            Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": e
+            })))
+        }
+    }
+}
+
+async fn undo_bulk_edit(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP UndoBulkEdit called");
+
+    let Some(batch_id) = request["batch_id"].as_i64().and_then(|id| i32::try_from(id).ok()) else {
+        return Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": "batch_id is required"
+        })));
+    };
+
+    match BulkEditOperations::undo(&pool, batch_id, request["user_id"].as_str()).await {
+        Ok(result) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!(
+                "Restored {} attributes, {} left unchanged",
+                result.restored.len(),
+                result.skipped.len()
+            ),
+            "result": result
+        }))),
+        Err(e) => {
+            warn!("Bulk edit {} not undone: {}", batch_id, e);
+            Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": e
+            })))
+        }
+    }
+}
+
+async fn list_bulk_edits(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ListBulkEdits called");
+
+    let limit = request["limit"].as_i64().unwrap_or(20).clamp(1, 200);
+
+    match BulkEditOperations::list_batches(&pool, limit).await {
+        Ok(batches) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Found {} bulk edits", batches.len()),
+            "batches": batches
+        }))),
+        Err(e) => {
+            error!("Failed to list bulk edits: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// ============================================
+// DATA RETENTION AND ERASURE ENDPOINTS
+// ============================================
+
+async fn list_retention_policies(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ListRetentionPolicies called");
+
+    match RetentionOperations::list_policies(&pool).await {
+        Ok(policies) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Found {} retention policies", policies.len()),
+            "policies": policies
+        }))),
+        Err(e) => {
+            error!("Failed to list retention policies: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn set_retention_policy(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP SetRetentionPolicy called");
+
+    let policy: RetentionPolicy = match serde_json::from_value(request["policy"].clone()) {
+        Ok(policy) => policy,
+        Err(e) => {
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": format!("Invalid retention policy: {}", e)
+            })));
+        }
+    };
+
+    match RetentionOperations::set_policy(&pool, &policy, request["user_id"].as_str()).await {
+        Ok(policy) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Keeping {} for {} days", policy.entity_type.as_str(), policy.retain_days),
+            "policy": policy
+        }))),
+        Err(e) => {
+            warn!("Retention policy not saved: {}", e);
+            Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": e
+            })))
+        }
+    }
+}
+
+/// Runs the purge now rather than waiting for the scheduler
+async fn purge_expired_data(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP PurgeExpiredData called");
+
+    match RetentionOperations::purge_expired(&pool, chrono::Utc::now()).await {
+        Ok(reports) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Purged {} records", reports.iter().map(|report| report.rows).sum::<u64>()),
+            "reports": reports
+        }))),
+        Err(e) => {
+            error!("Failed to purge expired data: {}", e);
+            Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": e
+            })))
+        }
+    }
+}
+
+async fn erase_subject(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    // The subject's id is deliberately not logged
+    info!("HTTP EraseSubject called");
+
+    let entity_id = request["entity_id"].as_str().unwrap_or("");
+
+    match RetentionOperations::erase_subject(&pool, entity_id, request["user_id"].as_str()).await {
+        Ok(report) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!(
+                "Replaced the subject with {} in {} CBU memberships, {} documents, {} traces and {} audit logs",
+                report.pseudonym, report.cbu_members, report.documents, report.traces, report.audit_logs
+            ),
+            "erasure": report
+        }))),
+        Err(e) => {
+            warn!("Erasure not completed: {}", e);
+            Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": e
+            })))
+        }
+    }
+}
+
+async fn list_erasures(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ListErasures called");
+
+    let limit = request["limit"].as_i64().unwrap_or(20).clamp(1, 200);
+
+    match RetentionOperations::list_erasures(&pool, limit).await {
+        Ok(erasures) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Found {} erasures", erasures.len()),
+            "erasures": erasures
+        }))),
+        Err(e) => {
+            error!("Failed to list erasures: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// ============================================
+// RULE NAMING ENDPOINTS
+// ============================================
+
+/// The ID and name a rule would be saved under in its category, or why it
+/// can't be, so the editor can show the convention before the rule is saved
+async fn check_rule_naming(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP CheckRuleNaming called");
+
+    let categories = match RuleOperations::get_rule_categories(&pool).await {
+        Ok(categories) => categories,
+        Err(e) => {
+            error!("Failed to load rule categories: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let category_key = request["category_key"].as_str().unwrap_or("");
+    let Some(category) = categories.find_by_key(category_key) else {
+        return Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": format!("Unknown rule category: {}", category_key)
+        })));
+    };
+    let applies_to: Applicability = serde_json::from_value(request["applies_to"].clone()).unwrap_or_default();
+
+    match apply_naming(
+        &categories,
+        category.id,
+        request["rule_id"].as_str().unwrap_or(""),
+        request["rule_name"].as_str().unwrap_or(""),
+        request["target_attribute"].as_str().unwrap_or(""),
+        &applies_to,
+    ) {
+        Ok(naming) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": if naming.notes.is_empty() { "Follows the naming convention".to_string() } else { naming.notes.join("; ") },
+            "rule_id": naming.rule_id,
+            "rule_name": naming.rule_name,
+            "notes": naming.notes
+        }))),
+        Err(violations) => {
+            let violations: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+            Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": violations.join("; "),
+                "violations": violations
+            })))
+        }
+    }
+}
+
+// ============================================
+// RULE REWRITE ENDPOINTS
+// ============================================
+
+async fn preview_rule_rewrite(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP PreviewRuleRewrite called");
+
+    let rewrite: RuleRewrite = match serde_json::from_value(request) {
+        Ok(rewrite) => rewrite,
+        Err(e) => {
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": format!("Invalid rewrite: {}", e)
+            })));
+        }
+    };
+    if let Err(message) = rewrite.validate() {
+        return Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": message
+        })));
+    }
+
+    match RuleOperations::preview_rule_rewrite(&pool, rewrite).await {
+        Ok(preview) => {
+            let invalid = preview.rules.iter().filter(|rule| !rule.errors.is_empty()).count();
+            Ok(ResponseJson(serde_json::json!({
+                "success": true,
+                "message": format!(
+                    "{} rules change ({} invalid after the rewrite), {} skipped",
+                    preview.rules.len(),
+                    invalid,
+                    preview.skipped.len()
+                ),
+                "preview": preview
+            })))
+        }
+        Err(e) => {
+            error!("Failed to preview rule rewrite: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn apply_rule_rewrite(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ApplyRuleRewrite called");
+
+    let rewrite: RuleRewrite = match serde_json::from_value(request.clone()) {
+        Ok(rewrite) => rewrite,
+        Err(e) => {
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": format!("Invalid rewrite: {}", e)
+            })));
+        }
+    };
+    let expected_rules = request["expected_rules"].as_u64().map(|n| n as usize);
+    let applied_by = request["user_id"].as_str();
+
+    match RuleOperations::apply_rule_rewrite(&pool, rewrite, expected_rules, applied_by).await {
+        Ok(plan) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!(
+                "Replaced '{}' with '{}' in {} rules",
+                plan.rewrite.pattern,
+                plan.rewrite.replacement,
+                plan.rules.len()
+            ),
+            "rewrite": plan
+        }))),
+        Err(e) => {
+            warn!("Rule rewrite not applied: {}", e);
+            Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": e
+            })))
+        }
+    }
+}
+
+// ============================================
+// ATTRIBUTE WORKBOOK ENDPOINTS
+// ============================================
+
+/// Every derived attribute's formula as an XLSX workbook for business sign-off
+async fn export_attribute_workbook(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+) -> Response {
+    info!("HTTP ExportAttributeWorkbook called");
+
+    match RuleOperations::export_attribute_workbook(&pool).await {
+        Ok(workbook) => {
+            let disposition = format!(
+                "attachment; filename=\"derived-attributes-{}.xlsx\"",
+                chrono::Utc::now().format("%Y-%m-%d")
+            );
+            (
+                [
+                    (header::CONTENT_TYPE, "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet".to_string()),
+                    (header::CONTENT_DISPOSITION, disposition),
+                ],
+                workbook,
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!("Failed to export attribute workbook: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// The edits in a reviewed workbook (the request body) and any problems with them
+async fn preview_attribute_workbook_import(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    workbook: Bytes,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP PreviewAttributeWorkbookImport called");
+
+    match RuleOperations::preview_attribute_workbook_import(&pool, &workbook).await {
+        Ok(review) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!(
+                "{} rules edited, {} unchanged, {} problems",
+                review.edits.len(),
+                review.unchanged,
+                review.problems().len()
+            ),
+            "review": review
+        }))),
+        Err(e) => Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": e
+        }))),
+    }
+}
+
+/// Saves the reviewed workbook's edits; `expected_edits` is the count the preview showed
+async fn import_attribute_workbook(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Query(params): Query<HashMap<String, String>>,
+    workbook: Bytes,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ImportAttributeWorkbook called");
+
+    let expected_edits = params.get("expected_edits").and_then(|n| n.parse().ok());
+    let imported_by = params.get("user_id").map(String::as_str);
+
+    match RuleOperations::import_attribute_workbook(&pool, &workbook, expected_edits, imported_by).await {
+        Ok(review) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Imported edits to {} rules", review.edits.len()),
+            "review": review
+        }))),
+        Err(e) => {
+            warn!("Attribute workbook not imported: {}", e);
+            Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": e
+            })))
+        }
+    }
+}
+
+// ============================================
+// ATTRIBUTE USAGE ENDPOINTS
+// ============================================
+
+async fn attribute_usage_heatmap(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP AttributeUsageHeatmap called");
+
+    let entity_name = request["entity_name"].as_str();
+    let limit = request["limit"].as_u64().map(|n| n as usize);
+
+    match AttributeUsageOperations::usage_heatmap(&pool, entity_name).await {
+        Ok(mut usage) => {
+            let unused = usage.iter().filter(|u| u.total == 0).count();
+            let attribute_count = usage.len();
+            if let Some(limit) = limit {
+                usage.truncate(limit);
+            }
+            Ok(ResponseJson(serde_json::json!({
+                "success": true,
+                "message": format!("{} attributes, {} not referenced anywhere", attribute_count, unused),
+                "max_total": usage.first().map_or(0, |u| u.total),
+                "usage": usage
+            })))
+        }
+        Err(e) => {
+            error!("Failed to compute attribute usage: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// Backs the IDE's dependency graph panel; `scope` is {"type": "all"} or
+// {"type": "around", "name": "<rule id, attribute or lookup table>"}
+async fn get_rule_history(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP GetRuleHistory called");
+
+    let Some(rule_id) = request["rule_id"].as_str().filter(|id| !id.is_empty()) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    match RuleOperations::get_rule_history(&pool, rule_id).await {
+        Ok(versions) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Found {} versions of {}", versions.len(), rule_id),
+            "versions": versions
+        }))),
+        Err(e) => {
+            error!("Failed to load history of {}: {}", rule_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// The version of every rule in effect at `at` (RFC 3339), defaulting to now
+async fn get_rules_as_of(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP GetRulesAsOf called");
+
+    let at = match request["at"].as_str() {
+        Some(at) => match chrono::DateTime::parse_from_rfc3339(at) {
+            Ok(at) => at.with_timezone(&chrono::Utc),
+            Err(e) => {
+                return Ok(ResponseJson(serde_json::json!({
+                    "success": false,
+                    "message": format!("Invalid timestamp '{}': {}", at, e)
+                })));
+            }
+        },
+        None => chrono::Utc::now(),
+    };
+
+    match RuleOperations::get_rules_as_of(&pool, at).await {
+        Ok(rules) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("{} rules in effect at {}", rules.len(), at.to_rfc3339()),
+            "at": at.to_rfc3339(),
+            "rules": rules
+        }))),
+        Err(e) => {
+            error!("Failed to load rules as of {}: {}", at.to_rfc3339(), e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Structural diff between two versions; `to_version` defaults to the one after `from_version`
+async fn diff_rule_versions(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP DiffRuleVersions called");
+
+    let Some(rule_id) = request["rule_id"].as_str().filter(|id| !id.is_empty()) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let Some(from_version) = request["from_version"].as_i64().and_then(|v| i32::try_from(v).ok()) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let to_version = request["to_version"]
+        .as_i64()
+        .and_then(|v| i32::try_from(v).ok())
+        .unwrap_or(from_version + 1);
+
+    match RuleOperations::diff_rule_versions(&pool, rule_id, from_version, to_version).await {
+        Ok(diff) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("{} changes from version {} to {}", diff.changes, from_version, to_version),
+            "diff": diff
+        }))),
+        Err(e) => {
+            warn!("Rule versions not compared: {}", e);
+            Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": e
+            })))
+        }
+    }
+}
+
+async fn list_rule_test_cases(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ListRuleTestCases called");
+
+    let Some(rule_id) = request["rule_id"].as_str().filter(|id| !id.is_empty()) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    match RuleTestOperations::list_test_cases(&pool, rule_id).await {
+        Ok(cases) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Found {} test cases for {}", cases.len(), rule_id),
+            "cases": cases
+        }))),
+        Err(e) => {
+            error!("Failed to load test cases of {}: {}", rule_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Add a test case to a rule, or replace its case with the same name
+async fn save_rule_test_case(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP SaveRuleTestCase called");
+
+    let Some(rule_id) = request["rule_id"].as_str().filter(|id| !id.is_empty()) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let mut case = request["case"].clone();
+    if let Some(fields) = case.as_object_mut() {
+        fields.entry("rule").or_insert_with(|| serde_json::json!(rule_id));
+    }
+    let case: RuleTestCase = match serde_json::from_value(case) {
+        Ok(case) => case,
+        Err(e) => {
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": format!("Invalid test case: {}", e)
+            })));
+        }
+    };
+
+    match RuleTestOperations::save_test_case(&pool, rule_id, &case, request["saved_by"].as_str()).await {
+        Ok(case) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Saved test case '{}' for {}", case.name, rule_id),
+            "case": case
+        }))),
+        Err(e) => {
+            warn!("Test case not saved: {}", e);
+            Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": e
+            })))
+        }
+    }
+}
+
+async fn delete_rule_test_case(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP DeleteRuleTestCase called");
+
+    let (Some(rule_id), Some(name)) = (request["rule_id"].as_str(), request["name"].as_str()) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    match RuleTestOperations::delete_test_case(&pool, rule_id, name).await {
+        Ok(deleted) => Ok(ResponseJson(serde_json::json!({
+            "success": deleted,
+            "message": if deleted {
+                format!("Deleted test case '{}' of {}", name, rule_id)
+            } else {
+                format!("{} has no test case '{}'", rule_id, name)
+            }
+        }))),
+        Err(e) => {
+            error!("Failed to delete test case '{}' of {}: {}", name, rule_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Run every test case of a rule against its current definition
+async fn run_rule_tests(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP RunRuleTests called");
+
+    let Some(rule_id) = request["rule_id"].as_str().filter(|id| !id.is_empty()) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    match RuleTestOperations::run_rule_tests(&pool, rule_id).await {
+        Ok(report) => Ok(ResponseJson(serde_json::json!({
+            "success": report.all_passed(),
+            "message": format!("{} passed, {} failed", report.passed, report.failed),
+            "report": report
+        }))),
+        Err(e) => {
+            warn!("Tests of {} not run: {}", rule_id, e);
+            Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": e
+            })))
+        }
+    }
+}
+
+/// Change a rule's status; becoming `active` is refused while any test case
+/// fails, and reports the rule's cost warning when it is over the batch
+/// scoring latency budget
+async fn set_rule_status(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Extension(budget): Extension<LatencyBudget>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP SetRuleStatus called");
+
+    let (Some(rule_id), Some(status)) = (request["rule_id"].as_str(), request["status"].as_str()) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    match RuleOperations::set_rule_status(&pool, rule_id, status, request["changed_by"].as_str()).await {
+        Ok(version) => {
+            let cost_warning = match status {
+                "active" => RuleOperations::estimate_rule_cost(&pool, rule_id)
+                    .await
+                    .ok()
+                    .and_then(|cost| cost.budget_warning(&budget)),
+                _ => None,
+            };
+            Ok(ResponseJson(serde_json::json!({
+                "success": true,
+                "message": format!("{} is now {} (version {})", rule_id, status, version),
+                "version": version,
+                "cost_warning": cost_warning
+            })))
+        }
+        Err(e) => {
+            warn!("Status of {} not changed: {}", rule_id, e);
+            Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": e
+            })))
+        }
+    }
+}
+
+/// Estimated cost of a rule's current definition (LOOKUPs, regex matches,
+/// host function calls and latency), to show before it is activated
+async fn estimate_rule_cost(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Extension(budget): Extension<LatencyBudget>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP EstimateRuleCost called");
+
+    let Some(rule_id) = request["rule_id"].as_str().filter(|id| !id.is_empty()) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    match RuleOperations::estimate_rule_cost(&pool, rule_id).await {
+        Ok(cost) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "warning": cost.budget_warning(&budget),
+            "expected_latency_us": cost.expected_latency_us(),
+            "budget_us": budget.batch_scoring_us,
+            "cost": cost
+        }))),
+        Err(e) => {
+            warn!("Cost of {} not estimated: {}", rule_id, e);
+            Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": e
+            })))
+        }
+    }
+}
+
+/// Evaluate the rules in effect over a sample dataset (`contexts`, one object
+/// of attribute values per record) and report rules that never fired, IF and
+/// CASE arms never taken and attributes no rule reads
+async fn rule_coverage_report(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP RuleCoverageReport called");
+
+    let Ok(contexts) = serde_json::from_value::<Vec<HashMap<String, serde_json::Value>>>(request["contexts"].clone()) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    let bundle = match RuleOperations::get_dated_rule_bundle(&pool).await {
+        Ok(bundle) => bundle,
+        Err(e) => {
+            error!("Failed to load rules for coverage: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let dictionary = DataDictionary {
+        datasets: vec![],
+        lookup_tables: HashMap::new(),
+        derived_attributes: vec![],
+        canonical_models: vec![],
+        solicitation_packs: vec![],
+        axes: vec![],
+    };
+    let mut engine = RulesEngine::new(dictionary).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // The rules come from our own database rather than a shipped bundle
+    if let Err(e) = engine.load_unsigned_bundle(bundle, &SecurityConfig::default()) {
+        warn!("Rules not loaded for coverage: {:#}", e);
+        return Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": format!("{:#}", e)
+        })));
+    }
+
+    let report = engine.coverage_report(&contexts);
+    Ok(ResponseJson(serde_json::json!({
+        "success": true,
+        "message": format!(
+            "{} rules over {} records: {} never fired, {} branches never taken, {} attributes unreferenced",
+            report.rules.len(),
+            report.contexts,
+            report.never_fired.len(),
+            report.untaken_branches.len(),
+            report.unreferenced_attributes.len()
+        ),
+        "report": report
+    })))
+}
+
+/// Evaluates the rules in effect over a client's source values and records
+/// the derived attributes with the inputs each was computed from
+async fn materialize_attributes(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP MaterializeAttributes called");
+
+    let Some(cbu_id) = request["cbu_id"].as_str().filter(|id| !id.is_empty()) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let Ok(source) = serde_json::from_value::<HashMap<String, serde_json::Value>>(request["facts"].clone()) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let captured_at = chrono::Utc::now();
+
+    let bundle = match RuleOperations::get_dated_rule_bundle(&pool).await {
+        Ok(bundle) => bundle,
+        Err(e) => {
+            error!("Failed to load rules for materialization: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let dictionary = DataDictionary {
+        datasets: vec![],
+        lookup_tables: HashMap::new(),
+        derived_attributes: vec![],
+        canonical_models: vec![],
+        solicitation_packs: vec![],
+        axes: vec![],
+    };
+    let mut engine = RulesEngine::new(dictionary).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // The rules come from our own database rather than a shipped bundle
+    if let Err(e) = engine.load_unsigned_bundle(bundle, &SecurityConfig::default()) {
+        warn!("Rules not loaded for materialization: {:#}", e);
+        return Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": format!("{:#}", e)
+        })));
+    }
+
+    let facts: Facts = source.iter().map(|(name, value)| (name.clone(), Value::from_json(value))).collect();
+    let (_, materialization) = match engine.materialize(&facts) {
+        Ok(result) => result,
+        Err(e) => {
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": format!("{:#}", e)
+            })));
+        }
+    };
+
+    match ProvenanceOperations::record_materialization(&pool, cbu_id, &materialization, captured_at).await {
+        Ok(materialization_id) => {
+            let derived: serde_json::Map<String, serde_json::Value> = materialization
+                .derivations
+                .iter()
+                .map(|d| (d.attribute.clone(), d.value.clone()))
+                .collect();
+            Ok(ResponseJson(serde_json::json!({
+                "success": true,
+                "message": format!("Materialized {} attributes for {}", derived.len(), cbu_id),
+                "materialization_id": materialization_id,
+                "derived": derived
+            })))
+        }
+        Err(e) => {
+            error!("Failed to record materialization for {}: {}", cbu_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Why a client's derived attribute has its value: the rule version and the
+/// exact inputs of its latest materialization, traced back to source snapshots
+async fn get_provenance(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP GetProvenance called");
+
+    let (Some(cbu_id), Some(attribute)) = (request["cbu_id"].as_str(), request["attribute"].as_str()) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    match ProvenanceOperations::get_provenance(&pool, cbu_id, attribute).await {
+        Ok(Some(provenance)) => {
+            let sources: serde_json::Map<String, serde_json::Value> = provenance
+                .source_inputs()
+                .into_iter()
+                .map(|(name, input)| (name.to_string(), input.value.clone()))
+                .collect();
+            Ok(ResponseJson(serde_json::json!({
+                "success": true,
+                "provenance": provenance,
+                "source_values": sources
+            })))
+        }
+        Ok(None) => Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": format!("{} has no recorded derivation of {}", cbu_id, attribute)
+        }))),
+        Err(e) => {
+            error!("Failed to load provenance of {} for {}: {}", attribute, cbu_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Active rules that don't parse and pairs of active rules writing the same
+/// attribute for the same inputs; both come back as `warnings`
+async fn check_database_rule_state(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP CheckDatabaseRuleState called");
+
+    match RuleOperations::check_database_rule_state(&pool).await {
+        Ok(report) => {
+            let warnings = report.warnings();
+            for warning in &warnings {
+                warn!("Rule state: {}", warning);
+            }
+            Ok(ResponseJson(serde_json::json!({
+                "success": warnings.is_empty(),
+                "message": format!(
+                    "{} active rules: {} invalid, {} conflicts",
+                    report.active_rules,
+                    report.invalid_rules.len(),
+                    report.conflicts.len()
+                ),
+                "warnings": warnings,
+                "report": report
+            })))
+        }
+        Err(e) => {
+            error!("Failed to check rule state: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Re-validates the active rules against the current grammar and data dictionary,
+/// as at startup; the ones that no longer do are moved to in_repair
+async fn check_rule_integrity(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP CheckRuleIntegrity called");
+
+    match RuleOperations::check_active_rules_integrity(&pool, None).await {
+        Ok(report) => {
+            if !report.is_clean() {
+                warn!("{}", report.summary());
+            }
+            Ok(ResponseJson(serde_json::json!({
+                "success": report.is_clean(),
+                "message": report.summary(),
+                "report": report
+            })))
+        }
+        Err(e) => {
+            error!("Failed to check rule integrity: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// A stepwise repair plan for a rule: its parse and type errors, its most
+/// similar earlier version that still validates, and the AI assistant's fixes,
+/// each with a diff from the current definition
+async fn plan_rule_repair(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP PlanRuleRepair called");
+
+    let Some(rule_id) = request["rule_id"].as_str() else {
+        return Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": "rule_id is required"
+        })));
+    };
+
+    let type_env = match RuleOperations::get_dictionary_type_env(&pool).await {
+        Ok(type_env) => type_env,
+        Err(e) => {
+            error!("Failed to load data dictionary types: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let mut plan = match RuleOperations::plan_rule_repair(&pool, rule_id, &type_env).await {
+        Ok(plan) => plan,
+        Err(e) => {
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": e
+            })));
+        }
+    };
+
+    if !plan.problems.is_empty() {
+        let provider = match request.get("ai_provider") {
+            Some(provider) if !provider.is_null() => {
+                let api_key = provider["api_key"].as_str().map(|key| key.to_string());
+                match provider["provider_type"].as_i64() {
+                    Some(1) => crate::AiProvider::Anthropic { api_key },
+                    Some(2) => crate::AiProvider::Offline,
+                    _ => crate::AiProvider::OpenAI { api_key },
+                }
+            }
+            _ => crate::AiProvider::Offline,
+        };
+        let query = format!(
+            "Fix this rule so it parses and typechecks, keeping its intent:\n{}\nProblems:\n- {}",
+            plan.definition,
+            plan.problems.join("\n- ")
+        );
+        let mut assistant = crate::create_ai_assistant(provider, pool.clone()).await;
+        let suggestions = assistant.get_suggestions(&query).await;
+        plan.propose_fixes(
+            suggestions
+                .into_iter()
+                .filter_map(|suggestion| Some((suggestion.title, suggestion.confidence, suggestion.code_snippet?))),
+            &type_env,
+        );
+    }
+
+    Ok(ResponseJson(serde_json::json!({
+        "success": true,
+        "message": format!(
+            "{} problems, {} proposed fixes ({} validate)",
+            plan.problems.len(),
+            plan.steps.len(),
+            plan.steps.iter().filter(|step| step.validates()).count()
+        ),
+        "plan": plan
+    })))
+}
+
+/// Saves one step of a repair plan as the rule's new definition; the plan's
+/// `expected_definition` guards against repairing a rule that changed since
+async fn apply_rule_repair(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ApplyRuleRepair called");
+
+    let (Some(rule_id), Some(expected_definition), Some(definition)) = (
+        request["rule_id"].as_str(),
+        request["expected_definition"].as_str(),
+        request["definition"].as_str(),
+    ) else {
+        return Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": "rule_id, expected_definition and definition are required"
+        })));
+    };
+    let description = request["description"].as_str().unwrap_or("repair step applied");
+
+    match RuleOperations::apply_rule_repair(
+        &pool,
+        rule_id,
+        expected_definition,
+        definition,
+        description,
+        request["user_id"].as_str(),
+    )
+    .await
+    {
+        Ok(version) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Rule {} repaired as version {}", rule_id, version),
+            "version": version
+        }))),
+        Err(e) => Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": e
+        }))),
+    }
+}
+
+// ============================================
+// LOOKUP TABLE ENDPOINTS
+// ============================================
+
+async fn list_lookup_tables(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(_request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ListLookupTables called");
+
+    match LookupTableOperations::list_lookup_tables(&pool).await {
+        Ok(tables) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("{} lookup tables", tables.len()),
+            "tables": tables
+        }))),
+        Err(e) => {
+            error!("Failed to list lookup tables: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// The long inline lists in a rule that could move into lookup tables, each
+/// with a suggested table name; `min_values` defaults to MIN_EXTRACTED_VALUES
+async fn find_inline_lists(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
     Json(request): Json<serde_json::Value>,
 ) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
-    info!("HTTP InstantiateResource called - delegating to gRPC implementation");
+    info!("HTTP FindInlineLists called");
 
-    // Convert JSON request to gRPC type
-    let grpc_request_data = crate::financial_taxonomy::InstantiateResourceRequest {
-        template_id: request.get("template_id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-        onboarding_request_id: request.get("onboarding_request_id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-        context: request.get("context").and_then(|v| v.as_str()).map(|s| s.to_string()),
-        initial_data: request.get("initial_data").and_then(|v| v.as_str()).map(|s| s.to_string()),
+    let Some(rule_id) = request["rule_id"].as_str() else {
+        return Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": "rule_id is required"
+        })));
     };
+    let min_values = request["min_values"].as_u64().map_or(MIN_EXTRACTED_VALUES, |n| n as usize);
+
+    match LookupTableOperations::find_inline_lists(&pool, rule_id, min_values).await {
+        Ok((definition, lists)) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("{} lists of {} or more values", lists.len(), min_values),
+            "definition": definition,
+            "lists": lists
+        }))),
+        Err(e) => Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": e
+        }))),
+    }
+}
 
-    let grpc_request = tonic::Request::new(grpc_request_data);
-
-    match taxonomy_server.instantiate_resource(grpc_request).await {
-        Ok(grpc_response) => {
-            let response = grpc_response.into_inner();
-            info!("gRPC InstantiateResource succeeded");
-            let json_response = serde_json::json!({
-                "success": response.success,
-                "message": response.message,
-                "instance": null
-            });
-            Ok(ResponseJson(json_response))
-        }
-        Err(status) => {
-            error!("gRPC InstantiateResource failed: {}", status);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+/// Moves one of the lists find_inline_lists returned into a registered lookup
+/// table and saves the rewritten rule; `expected_definition` is the definition
+/// it returned, so a rule that changed since is left alone
+async fn extract_lookup_table(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ExtractLookupTable called");
+
+    let (Some(rule_id), Some(expected_definition), Some(index), Some(table)) = (
+        request["rule_id"].as_str(),
+        request["expected_definition"].as_str(),
+        request["index"].as_u64(),
+        request["table"].as_str(),
+    ) else {
+        return Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": "rule_id, expected_definition, index and table are required"
+        })));
+    };
+    let min_values = request["min_values"].as_u64().map_or(MIN_EXTRACTED_VALUES, |n| n as usize);
+
+    match LookupTableOperations::extract_lookup_table(
+        &pool,
+        rule_id,
+        expected_definition,
+        index as usize,
+        table,
+        request["description"].as_str(),
+        min_values,
+        request["user_id"].as_str(),
+    )
+    .await
+    {
+        Ok(extraction) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("Moved {} values of rule {} into lookup table '{}'", extraction.entries.len(), rule_id, table),
+            "extraction": extraction
+        }))),
+        Err(e) => Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": e
+        }))),
     }
 }
 
-async fn execute_dsl(
-    State((_, taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+async fn get_rule_dependency_graph(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
     Json(request): Json<serde_json::Value>,
 ) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
-    info!("HTTP ExecuteDsl called - delegating to gRPC implementation");
+    info!("HTTP GetRuleDependencyGraph called");
 
-    // Convert JSON request to gRPC type
-    let grpc_request_data = crate::financial_taxonomy::ExecuteDslRequest {
-        instance_id: request.get("instance_id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-        execution_context: request.get("execution_context").and_then(|v| v.as_str()).map(|s| s.to_string()),
-        input_data: request.get("input_data").and_then(|v| v.as_str()).map(|s| s.to_string()),
+    let scope: GraphScope = match request.get("scope") {
+        Some(scope) if !scope.is_null() => match serde_json::from_value(scope.clone()) {
+            Ok(scope) => scope,
+            Err(e) => {
+                return Ok(ResponseJson(serde_json::json!({
+                    "success": false,
+                    "message": format!("Invalid scope: {}", e)
+                })));
+            }
+        },
+        _ => GraphScope::All,
     };
 
-    let grpc_request = tonic::Request::new(grpc_request_data);
-
-    match taxonomy_server.execute_dsl(grpc_request).await {
-        Ok(grpc_response) => {
-            let response = grpc_response.into_inner();
-            info!("gRPC ExecuteDsl succeeded");
-            let json_response = serde_json::json!({
-                "success": response.success,
-                "message": response.message,
-                "result": null
-            });
-            Ok(ResponseJson(json_response))
-        }
-        Err(status) => {
-            error!("gRPC ExecuteDsl failed: {}", status);
+    match RuleOperations::get_rule_dependency_graph(&pool, &scope).await {
+        Ok(graph) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("{} nodes, {} edges, {} cycles", graph.nodes.len(), graph.edges.len(), graph.cycles.len()),
+            "nodes": graph.nodes,
+            "edges": graph.edges,
+            "cycles": graph.cycles
+        }))),
+        Err(e) => {
+            error!("Failed to build rule dependency graph: {}", e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
@@ -979,6 +3179,7 @@ async fn update_onboarding_metadata(
 }
 
 async fn compile_onboarding_workflow(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
     Json(request): Json<CompileWorkflowRequest>,
 ) -> Result<ResponseJson<CompileWorkflowResponse>, StatusCode> {
     info!("⚙️ [COMPILE] Starting workflow compilation");
@@ -1024,6 +3225,8 @@ async fn compile_onboarding_workflow(
             info!("  IDD gaps: {}", outputs.idd.gaps.len());
             info!("  Bindings tasks: {}", outputs.bindings.tasks.len());
 
+            record_compile_events(&pool, &request, &outputs.plan, &outputs.idd).await;
+
             let plan_json = serde_json::to_value(&outputs.plan)
                 .map_err(|e| {
                     error!("❌ [COMPILE] Failed to serialize plan: {}", e);
@@ -1066,11 +3269,12 @@ async fn compile_onboarding_workflow(
 }
 
 async fn execute_onboarding_workflow(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
     Json(request): Json<ExecuteWorkflowRequest>,
 ) -> Result<ResponseJson<ExecuteWorkflowResponse>, StatusCode> {
     info!("▶️ [EXECUTE] Starting workflow execution");
 
-    use onboarding::{execute_plan, ExecutionConfig};
+    use onboarding::{execute_plan_with_events, ExecutionConfig, OnboardingEvent};
     use onboarding::ir::Plan;
 
     // Deserialize plan from JSON
@@ -1086,7 +3290,25 @@ async fn execute_onboarding_workflow(
     let config = ExecutionConfig {};
 
     info!("🚀 [EXECUTE] Executing plan with {} steps", plan.steps.len());
-    match execute_plan(&plan, &config).await {
+    let mut events = Vec::new();
+    let outcome = execute_plan_with_events(&plan, &config, |event| events.push((chrono::Utc::now(), event))).await;
+    if let Err(e) = &outcome {
+        // The task that was running when execution stopped is the one that failed
+        let failed_task = events
+            .last()
+            .and_then(|(_, event)| match event {
+                OnboardingEvent::TaskStarted { task_id, .. } => Some(task_id.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(|| "plan".to_string());
+        events.push((
+            chrono::Utc::now(),
+            OnboardingEvent::TaskFailed { instance_id: plan.instance_id.clone(), task_id: failed_task, error: e.to_string() },
+        ));
+    }
+    record_onboarding_events(&pool, &events).await;
+
+    match outcome {
         Ok(_) => {
             info!("✅ [EXECUTE] Workflow executed successfully");
             let response = ExecuteWorkflowResponse {
@@ -1112,6 +3334,362 @@ async fn execute_onboarding_workflow(
     }
 }
 
+// ========== ONBOARDING INSTANCE HISTORY ==========
+
+/// Appends events to the instance event log. A failure is logged rather than
+/// failing the workflow step that produced the events.
+pub(crate) async fn record_onboarding_events(pool: &PgPool, events: &[(chrono::DateTime<chrono::Utc>, onboarding::OnboardingEvent)]) {
+    for (occurred_at, event) in events {
+        let payload = match serde_json::to_value(event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("⚠️ [HISTORY] Failed to serialize {} event: {}", event.event_type(), e);
+                continue;
+            }
+        };
+        let result = sqlx::query(
+            "INSERT INTO onboarding_instance_events (instance_id, event_type, event, occurred_at) VALUES ($1, $2, $3, $4)"
+        )
+        .bind(event.instance_id())
+        .bind(event.event_type())
+        .bind(payload)
+        .bind(occurred_at)
+        .execute(pool)
+        .await;
+        if let Err(e) = result {
+            warn!("⚠️ [HISTORY] Failed to record {} for {}: {}", event.event_type(), event.instance_id(), e);
+        }
+    }
+}
+
+async fn load_onboarding_events(pool: &PgPool, instance_id: &str) -> Result<Vec<onboarding::RecordedEvent>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT sequence, event, occurred_at FROM onboarding_instance_events WHERE instance_id = $1 ORDER BY sequence"
+    )
+    .bind(instance_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let event = serde_json::from_value(row.get("event")).ok()?;
+            Some(onboarding::RecordedEvent { sequence: row.get("sequence"), occurred_at: row.get("occurred_at"), event })
+        })
+        .collect())
+}
+
+/// Records what a successful compile changed: the instance's creation on its
+/// first compile, a CBU or product set that differs from the last one
+/// recorded, and the compiled plan
+pub(crate) async fn record_compile_events(pool: &PgPool, request: &CompileWorkflowRequest, plan: &onboarding::Plan, idd: &onboarding::Idd) {
+    use onboarding::OnboardingEvent;
+
+    let previous = match load_onboarding_events(pool, &request.instance_id).await {
+        Ok(events) => onboarding::history::state_at(&events, chrono::Utc::now()),
+        Err(e) => {
+            warn!("⚠️ [HISTORY] Failed to load events for {}: {}", request.instance_id, e);
+            None
+        }
+    };
+
+    let instance_id = request.instance_id.clone();
+    let mut events = Vec::new();
+    if previous.is_none() {
+        events.push(OnboardingEvent::OnboardingCreated { instance_id: instance_id.clone() });
+    }
+    if previous.as_ref().and_then(|snapshot| snapshot.cbu_id.as_deref()) != Some(request.cbu_id.as_str()) {
+        events.push(OnboardingEvent::CBUAttached { instance_id: instance_id.clone(), cbu_id: request.cbu_id.clone() });
+    }
+    if previous.as_ref().map(|snapshot| &snapshot.products) != Some(&request.products) {
+        events.push(OnboardingEvent::ProductsAttached { instance_id: instance_id.clone(), product_ids: request.products.clone() });
+    }
+    events.push(OnboardingEvent::PlanCompiled {
+        instance_id,
+        steps: plan.steps.iter().map(|task| task.id.clone()).collect(),
+        idd_gaps: idd.gaps.clone(),
+    });
+
+    let now = chrono::Utc::now();
+    let events: Vec<_> = events.into_iter().map(|event| (now, event)).collect();
+    record_onboarding_events(pool, &events).await;
+}
+
+fn history_instance_id(request: &serde_json::Value) -> Option<&str> {
+    request["instance_id"].as_str().map(str::trim).filter(|id| !id.is_empty())
+}
+
+/// Every event of an instance with the state it left behind, for scrubbing
+async fn get_onboarding_instance_history(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP GetOnboardingInstanceHistory called");
+
+    let Some(instance_id) = history_instance_id(&request) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    match load_onboarding_events(&pool, instance_id).await {
+        Ok(events) => {
+            let history = onboarding::history::history(&events);
+            Ok(ResponseJson(serde_json::json!({
+                "success": true,
+                "message": format!("{} events recorded for {}", history.len(), instance_id),
+                "instance_id": instance_id,
+                "history": history
+            })))
+        }
+        Err(e) => {
+            error!("Failed to load events for {}: {}", instance_id, e);
+            Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": format!("Failed to load instance history: {}", e)
+            })))
+        }
+    }
+}
+
+/// The instance's state as it was at `at` (RFC 3339), replayed from its events
+async fn get_onboarding_instance_state_at(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP GetOnboardingInstanceStateAt called");
+
+    let Some(instance_id) = history_instance_id(&request) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let at = match request["at"].as_str() {
+        Some(at) => match chrono::DateTime::parse_from_rfc3339(at) {
+            Ok(at) => at.with_timezone(&chrono::Utc),
+            Err(e) => {
+                return Ok(ResponseJson(serde_json::json!({
+                    "success": false,
+                    "message": format!("Invalid timestamp '{}': {}", at, e)
+                })));
+            }
+        },
+        None => chrono::Utc::now(),
+    };
+
+    match load_onboarding_events(&pool, instance_id).await {
+        Ok(events) => {
+            let snapshot = onboarding::history::state_at(&events, at);
+            Ok(ResponseJson(serde_json::json!({
+                "success": snapshot.is_some(),
+                "message": match &snapshot {
+                    Some(snapshot) => format!("{} as of event {} of {}", instance_id, snapshot.events_applied, events.len()),
+                    None => format!("{} had no recorded events at {}", instance_id, at.to_rfc3339()),
+                },
+                "at": at.to_rfc3339(),
+                "snapshot": snapshot
+            })))
+        }
+        Err(e) => {
+            error!("Failed to load events for {}: {}", instance_id, e);
+            Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": format!("Failed to load instance state: {}", e)
+            })))
+        }
+    }
+}
+
+// ========== DEMO SCENARIOS ==========
+
+async fn list_demo_scenarios(
+    State((_pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(_request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ListDemoScenarios called");
+
+    Ok(ResponseJson(serde_json::json!({
+        "success": true,
+        "message": format!("{} demo scenarios", crate::demo_scenario::SCENARIOS.len()),
+        "scenarios": crate::demo_scenario::SCENARIOS
+    })))
+}
+
+/// Runs a demo scenario end to end, returning the progress events of each step
+async fn run_demo_scenario(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP RunDemoScenario called");
+
+    let Some(name) = request["name"].as_str() else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let Some(scenario) = crate::demo_scenario::find_scenario(name) else {
+        return Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": format!("Unknown demo scenario: {}", name)
+        })));
+    };
+
+    let run = crate::demo_scenario::run_scenario(&pool, scenario).await;
+    let message = match run.events.iter().find(|event| event.status == crate::demo_scenario::StepStatus::Failed) {
+        Some(failed) => format!("Demo {} stopped at {:?}: {}", name, failed.step, failed.detail),
+        None => format!("Demo {} completed for {}", name, run.cbu_id.as_deref().unwrap_or_default()),
+    };
+    Ok(ResponseJson(serde_json::json!({
+        "success": run.success,
+        "message": message,
+        "run": run
+    })))
+}
+
+// ========== FIELD ENCRYPTION ENDPOINTS ==========
+
+/// Re-encrypts sensitive attribute values under the current field key, and
+/// encrypts those stored before their attribute was tagged sensitive
+async fn rotate_field_keys(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP RotateFieldKeys called");
+
+    let Some(cipher) = data_designer_core::field_encryption::installed() else {
+        return Ok(ResponseJson(serde_json::json!({
+            "success": false,
+            "message": "Field encryption is not enabled"
+        })));
+    };
+    match EncryptionOperations::rotate_keys(&pool, cipher, request["rotated_by"].as_str()).await {
+        Ok(report) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!(
+                "Re-encrypted {} and encrypted {} values under key {}",
+                report.reencrypted(),
+                report.encrypted(),
+                report.key_id
+            ),
+            "report": report
+        }))),
+        Err(e) => {
+            error!("Field key rotation failed: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// ========== READ MODEL ENDPOINTS ==========
+
+/// Rows of each read model by version, shared by the handlers and the refresher
+pub type SharedReadModelCache = std::sync::Arc<std::sync::Mutex<ReadModelCache>>;
+
+fn requested_read_model(request: &serde_json::Value) -> Result<ReadModel, StatusCode> {
+    request["read_model"].as_str().and_then(ReadModel::parse).ok_or(StatusCode::BAD_REQUEST)
+}
+
+/// The current version of every read model, for clients to check their caches against
+async fn get_read_model_versions(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Json(_request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP GetReadModelVersions called");
+
+    match ReadModelOperations::versions(&pool).await {
+        Ok(versions) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("{} read models", versions.len()),
+            "versions": versions
+        }))),
+        Err(e) => {
+            error!("Failed to get read model versions: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Rows of a read model, optionally narrowed by `filter`. A client passing the
+/// version it already holds as `known_version` gets `unchanged` instead of rows.
+async fn read_model(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Extension(cache): Extension<SharedReadModelCache>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    let model = requested_read_model(&request)?;
+    let filter = request["filter"].as_str();
+    info!("HTTP ReadModel called for {}", model.as_str());
+
+    let version = match ReadModelOperations::version(&pool, model).await {
+        Ok(version) => version,
+        Err(e) => {
+            error!("Failed to get read model version: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let cache_key = version.cache_key(filter);
+    if request["known_version"].as_i64() == Some(version.version) {
+        return Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("{} unchanged", cache_key),
+            "version": version,
+            "cache_key": cache_key,
+            "unchanged": true
+        })));
+    }
+
+    let cached = cache.lock().unwrap().get(&version, filter).cloned();
+    let rows = match cached {
+        Some(rows) => rows,
+        None => match ReadModelOperations::read(&pool, model, filter).await {
+            Ok(rows) => {
+                let rows = serde_json::Value::Array(rows);
+                cache.lock().unwrap().insert(&version, filter, rows.clone());
+                rows
+            }
+            Err(e) => {
+                error!("Failed to read {}: {}", model.as_str(), e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        },
+    };
+    Ok(ResponseJson(serde_json::json!({
+        "success": true,
+        "message": format!("{} rows for {}", rows.as_array().map_or(0, Vec::len), cache_key),
+        "version": version,
+        "cache_key": cache_key,
+        "unchanged": false,
+        "rows": rows
+    })))
+}
+
+/// Refreshes one read model, or all of them without `read_model`, rather than
+/// waiting for a change notification
+async fn refresh_read_models(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Extension(cache): Extension<SharedReadModelCache>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP RefreshReadModels called");
+
+    let models = match request.get("read_model") {
+        None | Some(serde_json::Value::Null) => ReadModel::ALL.to_vec(),
+        Some(_) => vec![requested_read_model(&request)?],
+    };
+    let mut versions = Vec::new();
+    for model in models {
+        match ReadModelOperations::refresh(&pool, model).await {
+            Ok(version) => {
+                cache.lock().unwrap().invalidate(&version);
+                versions.push(version);
+            }
+            Err(e) => {
+                error!("Failed to refresh {}: {}", model.as_str(), e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+    }
+    Ok(ResponseJson(serde_json::json!({
+        "success": true,
+        "message": format!("Refreshed {} read models", versions.len()),
+        "versions": versions
+    })))
+}
+
 // ========== ONBOARDING REQUEST MANAGEMENT ENDPOINTS ==========
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1651,6 +4229,7 @@ async fn update_onboarding_request_status_grpc(
 
 /// CompileOnboardingWorkflow gRPC-style endpoint - accepts JSON payload
 async fn compile_onboarding_workflow_grpc(
+    State((pool, taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
     Json(request): Json<serde_json::Value>,
 ) -> Result<ResponseJson<CompileWorkflowResponse>, StatusCode> {
     info!("⚙️ [GRPC] CompileOnboardingWorkflow called via JSON payload");
@@ -1665,11 +4244,12 @@ async fn compile_onboarding_workflow_grpc(
     info!("  → Delegating to compile_onboarding_workflow");
 
     // Delegate to existing function
-    compile_onboarding_workflow(Json(compile_request)).await
+    compile_onboarding_workflow(State((pool, taxonomy_server)), Json(compile_request)).await
 }
 
 /// ExecuteOnboardingWorkflow gRPC-style endpoint - accepts JSON payload
 async fn execute_onboarding_workflow_grpc(
+    State((pool, taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
     Json(request): Json<serde_json::Value>,
 ) -> Result<ResponseJson<ExecuteWorkflowResponse>, StatusCode> {
     info!("▶️ [GRPC] ExecuteOnboardingWorkflow called via JSON payload");
@@ -1684,7 +4264,7 @@ async fn execute_onboarding_workflow_grpc(
     info!("  → Delegating to execute_onboarding_workflow");
 
     // Delegate to existing function
-    execute_onboarding_workflow(Json(execute_request)).await
+    execute_onboarding_workflow(State((pool, taxonomy_server)), Json(execute_request)).await
 }
 
 // ============================================================================
@@ -1779,4 +4359,115 @@ async fn update_onboarding_request_dsl(
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
-}
\ No newline at end of file
+}
+
+/// Rule packs offered by the configured registry
+async fn list_registry_packs(
+    Extension(registry): Extension<RegistryConfig>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP ListRegistryPacks called");
+
+    let index = match RegistryClient::new(&registry) {
+        Ok(client) => client.fetch_index().await,
+        Err(e) => Err(e),
+    };
+    match index {
+        Ok(index) => Ok(ResponseJson(serde_json::json!({
+            "success": true,
+            "message": format!("{} packs in the registry", index.packs.len()),
+            "packs": index.packs
+        }))),
+        Err(e) => {
+            warn!("Registry index unavailable: {:#}", e);
+            Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": format!("{:#}", e)
+            })))
+        }
+    }
+}
+
+/// Download a pack from the registry, verify its digest and signature, commit
+/// its rules to the rule repository and store its grammar extensions.
+/// `version` defaults to the latest.
+async fn install_registry_pack(
+    State((pool, _taxonomy_server)): State<(PgPool, std::sync::Arc<TaxonomyServer>)>,
+    Extension(registry): Extension<RegistryConfig>,
+    Extension(security): Extension<SecurityConfig>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    info!("HTTP InstallRegistryPack called");
+
+    let Some(name) = request["name"].as_str() else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let author = CommitAuthor {
+        name: request["installed_by"].as_str().unwrap_or("rule-registry").to_string(),
+        email: request["email"].as_str().unwrap_or("rule-registry@localhost").to_string(),
+    };
+
+    let verified = async {
+        let client = RegistryClient::new(&registry)?;
+        let index = client.fetch_index().await?;
+        let pack = index
+            .find(name, request["version"].as_str())
+            .ok_or_else(|| anyhow::anyhow!("Pack '{}' is not in the registry", name))?;
+        client.download(pack, &security.trusted_keys).await
+    }
+    .await;
+    let verified = match verified {
+        Ok(verified) => verified,
+        Err(e) => {
+            warn!("Pack {} not installed: {:#}", name, e);
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": format!("{:#}", e)
+            })));
+        }
+    };
+
+    // libgit2 blocks, so the commit happens off the async workers
+    let repository_path = registry.repository_path.clone();
+    let trusted_keys = security.trusted_keys.clone();
+    let to_install = verified.clone();
+    let commit = tokio::task::spawn_blocking(move || {
+        let repository = RuleRepository::open_or_init(&repository_path)?;
+        rule_registry::install(&repository, &to_install, &trusted_keys, &author)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let commit = match commit {
+        Ok(commit) => commit,
+        Err(e) => {
+            error!("Pack {} verified but not committed: {:#}", name, e);
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": format!("{:#}", e)
+            })));
+        }
+    };
+
+    for extension in verified.bundle.grammar_extensions.iter().cloned() {
+        if let Err(e) = GrammarOperations::upsert_extension(&pool, extension.into()).await {
+            error!("Grammar extension from pack {} not stored: {}", name, e);
+            return Ok(ResponseJson(serde_json::json!({
+                "success": false,
+                "message": format!("Rules installed, but a grammar extension was not: {}", e)
+            })));
+        }
+    }
+
+    Ok(ResponseJson(serde_json::json!({
+        "success": true,
+        "message": format!(
+            "Installed {} {} from {}: {} rules, {} grammar extensions",
+            verified.pack.name,
+            verified.pack.version,
+            verified.pack.publisher,
+            verified.bundle.rules.len(),
+            verified.bundle.grammar_extensions.len()
+        ),
+        "commit": commit.map(|oid| oid.to_string()),
+        "signed_by": verified.signed.key_name
+    })))
+}
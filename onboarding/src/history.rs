@@ -0,0 +1,184 @@
+//! Instance history reconstructed from the event log
+//!
+//! Every `OnboardingEvent` is appended to `onboarding_instance_events` with
+//! the time it happened. Folding an instance's events in order gives its
+//! state after each of them, so the state at any past timestamp is the fold
+//! of the events recorded up to then. Used for post-incident analysis: the
+//! onboarding monitor scrubs through `history` with a slider.
+
+use crate::{InstanceState, OnboardingEvent};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// An event as stored in the log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// Position in the log; events are replayed in this order
+    pub sequence: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub event: OnboardingEvent,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum TaskStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// An instance as it stood after some prefix of its events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstanceSnapshot {
+    pub instance_id: String,
+    pub state: InstanceState,
+    pub cbu_id: Option<String>,
+    pub products: Vec<String>,
+    pub idd_gaps: Vec<String>,
+    /// Plan steps in plan order, with where each had got to
+    pub tasks: Vec<(String, TaskStatus)>,
+    pub last_error: Option<String>,
+    pub events_applied: usize,
+    /// When the last applied event happened
+    pub as_of: DateTime<Utc>,
+}
+
+/// One tick of the history slider: an event and the state it left behind
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub sequence: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub event_type: String,
+    pub snapshot: InstanceSnapshot,
+}
+
+impl OnboardingEvent {
+    pub fn instance_id(&self) -> &str {
+        match self {
+            OnboardingEvent::OnboardingCreated { instance_id }
+            | OnboardingEvent::CBUAttached { instance_id, .. }
+            | OnboardingEvent::ProductsAttached { instance_id, .. }
+            | OnboardingEvent::PlanCompiled { instance_id, .. }
+            | OnboardingEvent::TaskStarted { instance_id, .. }
+            | OnboardingEvent::TaskSucceeded { instance_id, .. }
+            | OnboardingEvent::TaskFailed { instance_id, .. } => instance_id,
+        }
+    }
+
+    /// The variant name, stored alongside the payload for querying
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            OnboardingEvent::OnboardingCreated { .. } => "OnboardingCreated",
+            OnboardingEvent::CBUAttached { .. } => "CBUAttached",
+            OnboardingEvent::ProductsAttached { .. } => "ProductsAttached",
+            OnboardingEvent::PlanCompiled { .. } => "PlanCompiled",
+            OnboardingEvent::TaskStarted { .. } => "TaskStarted",
+            OnboardingEvent::TaskSucceeded { .. } => "TaskSucceeded",
+            OnboardingEvent::TaskFailed { .. } => "TaskFailed",
+        }
+    }
+}
+
+impl InstanceSnapshot {
+    fn new(instance_id: &str, as_of: DateTime<Utc>) -> Self {
+        Self {
+            instance_id: instance_id.to_string(),
+            state: InstanceState::Draft,
+            cbu_id: None,
+            products: Vec::new(),
+            idd_gaps: Vec::new(),
+            tasks: Vec::new(),
+            last_error: None,
+            events_applied: 0,
+            as_of,
+        }
+    }
+
+    /// Applies one event, following the same transitions as the command handlers
+    pub fn apply(&mut self, recorded: &RecordedEvent) {
+        match &recorded.event {
+            OnboardingEvent::OnboardingCreated { .. } => self.state = InstanceState::Draft,
+            OnboardingEvent::CBUAttached { cbu_id, .. } => {
+                self.cbu_id = Some(cbu_id.clone());
+                self.mark_ready();
+            }
+            OnboardingEvent::ProductsAttached { product_ids, .. } => {
+                self.products = product_ids.clone();
+                self.mark_ready();
+            }
+            OnboardingEvent::PlanCompiled { steps, idd_gaps, .. } => {
+                self.tasks = steps.iter().map(|step| (step.clone(), TaskStatus::Pending)).collect();
+                self.idd_gaps = idd_gaps.clone();
+                self.last_error = None;
+                self.state = InstanceState::Compiled;
+            }
+            OnboardingEvent::TaskStarted { task_id, .. } => {
+                self.set_task(task_id, TaskStatus::Running);
+                self.state = InstanceState::Executing;
+            }
+            OnboardingEvent::TaskSucceeded { task_id, .. } => {
+                self.set_task(task_id, TaskStatus::Succeeded);
+                if self.tasks.iter().all(|(_, status)| *status == TaskStatus::Succeeded) {
+                    self.state = InstanceState::Completed;
+                }
+            }
+            OnboardingEvent::TaskFailed { task_id, error, .. } => {
+                self.set_task(task_id, TaskStatus::Failed);
+                self.last_error = Some(format!("{}: {}", task_id, error));
+                self.state = InstanceState::Failed;
+            }
+        }
+        self.events_applied += 1;
+        self.as_of = recorded.occurred_at;
+    }
+
+    fn mark_ready(&mut self) {
+        if self.state == InstanceState::Draft && self.cbu_id.is_some() && !self.products.is_empty() {
+            self.state = InstanceState::ReadyToCompile;
+        }
+    }
+
+    fn set_task(&mut self, task_id: &str, status: TaskStatus) {
+        match self.tasks.iter_mut().find(|(id, _)| id == task_id) {
+            Some((_, current)) => *current = status,
+            // A task the compiled plan didn't list still shows up
+            None => self.tasks.push((task_id.to_string(), status)),
+        }
+    }
+}
+
+fn in_order(events: &[RecordedEvent]) -> Vec<&RecordedEvent> {
+    let mut ordered: Vec<&RecordedEvent> = events.iter().collect();
+    ordered.sort_by_key(|recorded| recorded.sequence);
+    ordered
+}
+
+/// The instance's state after every event recorded at or before `at`;
+/// None if the instance had no events by then
+pub fn state_at(events: &[RecordedEvent], at: DateTime<Utc>) -> Option<InstanceSnapshot> {
+    let mut snapshot: Option<InstanceSnapshot> = None;
+    for recorded in in_order(events).into_iter().filter(|recorded| recorded.occurred_at <= at) {
+        snapshot
+            .get_or_insert_with(|| InstanceSnapshot::new(recorded.event.instance_id(), recorded.occurred_at))
+            .apply(recorded);
+    }
+    snapshot
+}
+
+/// The state after each event in turn, one entry per event
+pub fn history(events: &[RecordedEvent]) -> Vec<HistoryEntry> {
+    let mut entries = Vec::with_capacity(events.len());
+    let mut snapshot: Option<InstanceSnapshot> = None;
+    for recorded in in_order(events) {
+        let current = snapshot
+            .get_or_insert_with(|| InstanceSnapshot::new(recorded.event.instance_id(), recorded.occurred_at));
+        current.apply(recorded);
+        entries.push(HistoryEntry {
+            sequence: recorded.sequence,
+            occurred_at: recorded.occurred_at,
+            event_type: recorded.event.event_type().to_string(),
+            snapshot: current.clone(),
+        });
+    }
+    entries
+}
@@ -6,10 +6,12 @@ pub mod runtime;
 pub mod util;
 pub mod api;
 pub mod persistence;
+pub mod history;
 
 pub use planner::compile::{compile_onboard, CompileInputs, CompileOutputs};
-pub use runtime::scheduler::{execute_plan, ExecutionConfig};
+pub use runtime::scheduler::{execute_plan, execute_plan_with_events, ExecutionConfig};
 pub use ir::{Plan, Idd, Bindings};
 pub use api::{InstanceState, OnboardingInstance, OnboardingEvent};
+pub use history::{RecordedEvent, InstanceSnapshot, HistoryEntry};
 pub use api::{CreateOnboarding, AttachCBU, AttachProducts, Compile};
 pub use meta::loader::MetaBundle;
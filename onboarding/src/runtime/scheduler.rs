@@ -1,13 +1,25 @@
 use crate::ir::{Plan, TaskKind};
+use crate::OnboardingEvent;
 use anyhow::Result;
 use tracing::{info, warn};
 
 #[derive(Debug, Clone)]
 pub struct ExecutionConfig {}
 
-pub async fn execute_plan(plan: &Plan, _cfg: &ExecutionConfig) -> Result<()> {
+pub async fn execute_plan(plan: &Plan, cfg: &ExecutionConfig) -> Result<()> {
+    execute_plan_with_events(plan, cfg, |_| {}).await
+}
+
+/// Executes the plan, reporting each task's start and outcome to `on_event`
+/// so the caller can append them to the instance's event log
+pub async fn execute_plan_with_events(
+    plan: &Plan,
+    _cfg: &ExecutionConfig,
+    mut on_event: impl FnMut(OnboardingEvent),
+) -> Result<()> {
     info!(instance=%plan.instance_id, "starting execution");
     for t in &plan.steps {
+        on_event(OnboardingEvent::TaskStarted { instance_id: plan.instance_id.clone(), task_id: t.id.clone() });
         match &t.kind {
             TaskKind::SolicitData { options, attrs, audience } => {
                 warn!(?options, ?attrs, %audience, "PAUSE: solicit data (stub)");
@@ -16,6 +28,7 @@ pub async fn execute_plan(plan: &Plan, _cfg: &ExecutionConfig) -> Result<()> {
                 info!(%resource, %op, "execute resource op (stub)");
             }
         }
+        on_event(OnboardingEvent::TaskSucceeded { instance_id: plan.instance_id.clone(), task_id: t.id.clone() });
     }
     Ok(())
 }
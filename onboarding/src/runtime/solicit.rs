@@ -1 +1,96 @@
-// placeholder for form emission + case_facts writeback
+use crate::ir::{Idd, Plan, TaskKind};
+use crate::meta::loader::MetaBundle;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// One question on a solicitation form, described from the product catalog
+/// (service options) or the resource dictionaries (attrs)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormField {
+    pub key: String,
+    pub prompt: String,
+    pub kind: String, // select, multiselect, enum, iso-4217, ...
+    pub choices: Vec<String>,
+    pub required: bool,
+    pub default: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Form {
+    pub task_id: String,
+    pub audience: String,
+    pub fields: Vec<FormField>,
+}
+
+/// The forms for every SolicitData task in the plan, in plan order
+pub fn forms_for_plan(plan: &Plan, idd: &Idd, meta: &MetaBundle) -> Vec<Form> {
+    plan.steps.iter().filter_map(|t| match &t.kind {
+        TaskKind::SolicitData { options, attrs, audience } => Some(Form {
+            task_id: t.id.clone(),
+            audience: audience.clone(),
+            fields: options.iter().map(|o| option_field(o, idd, meta))
+                .chain(attrs.iter().map(|a| attr_field(a, idd, meta)))
+                .collect(),
+        }),
+        TaskKind::ResourceOp { .. } => None,
+    }).collect()
+}
+
+fn option_field(id: &str, idd: &Idd, meta: &MetaBundle) -> FormField {
+    let option = meta.product_catalog.products.iter()
+        .flat_map(|p| &p.services)
+        .flat_map(|s| &s.options)
+        .find(|o| o.id == id);
+    match option {
+        Some(o) => FormField {
+            key: o.id.clone(),
+            prompt: o.prompt.clone(),
+            kind: o.kind.clone(),
+            choices: o.choices.clone(),
+            required: true,
+            default: None,
+        },
+        None => attr_field(id, idd, meta),
+    }
+}
+
+fn attr_field(key: &str, idd: &Idd, meta: &MetaBundle) -> FormField {
+    let attr = meta.resource_dicts.resource_types.iter()
+        .flat_map(|rt| &rt.dictionary.attrs)
+        .find(|a| a.key == key);
+    let schema = idd.schema.get(key);
+    FormField {
+        key: key.to_string(),
+        prompt: key.replace('-', " "),
+        kind: schema.map(|s| s.r#type.clone())
+            .or_else(|| attr.map(|a| a.r#type.clone()))
+            .unwrap_or_else(|| "string".into()),
+        choices: attr.and_then(|a| a.values.clone()).unwrap_or_default(),
+        required: schema.map(|s| s.required).unwrap_or(false),
+        default: schema.and_then(|s| s.default.clone()).or_else(|| attr.and_then(|a| a.default.clone())),
+    }
+}
+
+impl FormField {
+    /// A plausible answer for generated data: the default, else the first choice,
+    /// else a value shaped like the field's type
+    pub fn sample_answer(&self) -> Value {
+        if let Some(default) = &self.default {
+            return default.clone();
+        }
+        if let Some(first) = self.choices.first() {
+            return if self.kind == "multiselect" || self.kind.starts_with("array[") {
+                Value::Array(vec![Value::String(first.clone())])
+            } else {
+                Value::String(first.clone())
+            };
+        }
+        match self.kind.as_str() {
+            "iso-4217" => Value::String("EUR".into()),
+            "iso-3166-1" => Value::String("LU".into()),
+            "boolean" => Value::Bool(true),
+            k if k.starts_with("array[") => Value::Array(vec![]),
+            _ => Value::String(format!("demo-{}", self.key)),
+        }
+    }
+}
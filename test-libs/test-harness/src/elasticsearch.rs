@@ -6,6 +6,8 @@ use serde_json::{json, Value};
 use std::collections::HashMap;
 
 use crate::{TestEvent, TestMetrics, ComponentMetrics, trace::RequestTrace};
+use data_designer_core::config::TraceExportConfig;
+use data_designer_core::trace_export::index_mappings;
 
 /// Elasticsearch client for test logging and debugging
 pub struct ElasticsearchTestClient {
@@ -17,16 +19,8 @@ pub struct ElasticsearchTestClient {
 impl ElasticsearchTestClient {
     /// Create new Elasticsearch test client
     pub async fn new(test_run_id: &str) -> Result<Self> {
-        let transport = Transport::single_node("http://localhost:9200")?;
-        let client = Elasticsearch::new(transport);
-
         let index_prefix = format!("test-logs-{}", chrono::Utc::now().format("%Y-%m"));
-
-        let instance = Self {
-            client,
-            test_run_id: test_run_id.to_string(),
-            index_prefix,
-        };
+        let instance = Self::connect("http://localhost:9200", &index_prefix, test_run_id)?;
 
         // Ensure index exists with proper mapping
         instance.ensure_index_exists().await?;
@@ -34,27 +28,34 @@ impl ElasticsearchTestClient {
         Ok(instance)
     }
 
+    /// Client over the evaluation traces a server exports with `[trace_export]`,
+    /// across every monthly index; the config's `source` stands in for the test run
+    pub fn for_exported_traces(config: &TraceExportConfig) -> Result<Self> {
+        Self::connect(
+            &config.elasticsearch_url,
+            &format!("{}-*", config.index_prefix),
+            &config.source,
+        )
+    }
+
+    /// Client over existing indices, without creating any
+    pub fn connect(url: &str, index_prefix: &str, test_run_id: &str) -> Result<Self> {
+        let transport = Transport::single_node(url)?;
+
+        Ok(Self {
+            client: Elasticsearch::new(transport),
+            test_run_id: test_run_id.to_string(),
+            index_prefix: index_prefix.to_string(),
+        })
+    }
+
     /// Ensure the test index exists with proper mapping
     async fn ensure_index_exists(&self) -> Result<()> {
         let index_name = &self.index_prefix;
 
         // Create index if it doesn't exist
-        let mapping = json!({
-            "mappings": {
-                "properties": {
-                    "timestamp": { "type": "date" },
-                    "test_run_id": { "type": "keyword" },
-                    "trace_id": { "type": "keyword" },
-                    "test_name": { "type": "keyword" },
-                    "component": { "type": "keyword" },
-                    "event_type": { "type": "keyword" },
-                    "level": { "type": "keyword" },
-                    "message": { "type": "text" },
-                    "data": { "type": "object" },
-                    "duration_ms": { "type": "long" }
-                }
-            }
-        });
+        // Shared with exported evaluation traces, so both are queried the same way
+        let mapping = json!({ "mappings": index_mappings() });
 
         // Check if index exists
         let exists_response = self.client
@@ -5,7 +5,8 @@ use uuid::Uuid;
 
 use data_designer_core::{
     models::{DataDictionary, DerivedAttribute, Rule, Value},
-    db::{rules::CreateRuleRequest, attributes::CreateDerivedAttributeRequest}
+    db::{rules::CreateRuleRequest, attributes::CreateDerivedAttributeRequest},
+    rule_variants::Applicability,
 };
 
 /// Test fixtures for consistent test data
@@ -41,6 +42,9 @@ impl TestFixtures {
                 source_attributes: vec!["age".to_string()],
                 rule_definition: "if age >= 18 then \"adult\" else \"minor\"".to_string(),
                 tags: Some(vec!["demographic".to_string(), "classification".to_string()]),
+                valid_from: None,
+                valid_to: None,
+                applies_to: Applicability::default(),
             },
             CreateRuleRequest {
                 rule_id: "risk_calculation_rule".to_string(),
@@ -51,6 +55,9 @@ impl TestFixtures {
                 source_attributes: vec!["age".to_string(), "income".to_string(), "country".to_string()],
                 rule_definition: "if country == \"US\" && income > 50000 && age > 25 then \"LOW\" else \"HIGH\"".to_string(),
                 tags: Some(vec!["risk".to_string(), "calculation".to_string()]),
+                valid_from: None,
+                valid_to: None,
+                applies_to: Applicability::default(),
             },
             CreateRuleRequest {
                 rule_id: "total_compensation_rule".to_string(),
@@ -61,6 +68,9 @@ impl TestFixtures {
                 source_attributes: vec!["base_salary".to_string(), "bonus_rate".to_string()],
                 rule_definition: "base_salary + (base_salary * bonus_rate)".to_string(),
                 tags: Some(vec!["financial".to_string(), "calculation".to_string()]),
+                valid_from: None,
+                valid_to: None,
+                applies_to: Applicability::default(),
             },
             CreateRuleRequest {
                 rule_id: "full_name_rule".to_string(),
@@ -71,6 +81,9 @@ impl TestFixtures {
                 source_attributes: vec!["first_name".to_string(), "last_name".to_string()],
                 rule_definition: "CONCAT(first_name, \" \", last_name)".to_string(),
                 tags: Some(vec!["text".to_string(), "concatenation".to_string()]),
+                valid_from: None,
+                valid_to: None,
+                applies_to: Applicability::default(),
             },
         ]
     }
@@ -192,6 +205,9 @@ impl TestFixtures {
                 source_attributes: vec![format!("input_{}", i)],
                 rule_definition: format!("if input_{} > {} then \"high\" else \"low\"", i, i * 10),
                 tags: Some(vec!["generated".to_string(), "performance".to_string()]),
+                valid_from: None,
+                valid_to: None,
+                applies_to: Applicability::default(),
             });
         }
         rules
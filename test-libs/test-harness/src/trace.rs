@@ -28,6 +28,11 @@ impl TraceId {
     pub fn as_str(&self) -> &str {
         &self.id
     }
+
+    /// Header to attach to server requests so their spans carry this trace ID
+    pub fn header(&self) -> (&'static str, &str) {
+        (data_designer_core::telemetry::TRACE_ID_HEADER, &self.id)
+    }
 }
 
 /// Complete trace of a request through the system
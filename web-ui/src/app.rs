@@ -7,12 +7,21 @@ use crate::resource_dsl_ide::ResourceDslIDE;
 use crate::resource_state_manager::ResourceStateManager;
 use crate::onboarding_ide::OnboardingIDE;
 use crate::onboarding_state_manager::OnboardingStateManager;
+use crate::tag_browser_ide::TagBrowserIDE;
+use crate::tag_state_manager::TagStateManager;
+use crate::context_diff_ide::ContextDiffIDE;
+use crate::context_diff_state_manager::ContextDiffStateManager;
+use crate::rule_stats_ide::RuleStatsIDE;
+use crate::rule_stats_state_manager::RuleStatsStateManager;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum ActiveView {
     Cbu,
     Resource,
     Onboarding,
+    Tags,
+    ContextDiff,
+    RuleStats,
 }
 
 /// Data Designer Application - CBU, Resource DSL, and Onboarding Workflow Management
@@ -24,11 +33,17 @@ pub struct DataDesignerWebApp {
     cbu_state: CbuStateManager,
     resource_state: ResourceStateManager,
     onboarding_state: OnboardingStateManager,
+    tag_state: TagStateManager,
+    context_diff_state: ContextDiffStateManager,
+    rule_stats_state: RuleStatsStateManager,
 
     // IDE components - UI only, references state
     cbu_dsl_ide: CbuDslIDE,
     resource_dsl_ide: ResourceDslIDE,
     onboarding_ide: OnboardingIDE,
+    tag_browser_ide: TagBrowserIDE,
+    context_diff_ide: ContextDiffIDE,
+    rule_stats_ide: RuleStatsIDE,
 }
 
 impl DataDesignerWebApp {
@@ -43,10 +58,16 @@ impl DataDesignerWebApp {
             active_view: ActiveView::Cbu,
             cbu_state: CbuStateManager::new(Some(grpc_client.clone())),
             resource_state: ResourceStateManager::new(Some(grpc_client.clone())),
-            onboarding_state: OnboardingStateManager::new(Some(grpc_client)),
+            onboarding_state: OnboardingStateManager::new(Some(grpc_client.clone())),
+            tag_state: TagStateManager::new(Some(grpc_client.clone())),
+            context_diff_state: ContextDiffStateManager::new(Some(grpc_client.clone())),
+            rule_stats_state: RuleStatsStateManager::new(Some(grpc_client)),
             cbu_dsl_ide: CbuDslIDE::new(),
             resource_dsl_ide: ResourceDslIDE::new(),
             onboarding_ide: OnboardingIDE::new(),
+            tag_browser_ide: TagBrowserIDE::new(),
+            context_diff_ide: ContextDiffIDE::new(),
+            rule_stats_ide: RuleStatsIDE::new(),
         }
     }
 }
@@ -60,6 +81,9 @@ impl eframe::App for DataDesignerWebApp {
         self.cbu_state.update_from_async();
         self.resource_state.update_from_async();
         self.onboarding_state.update_from_async();
+        self.tag_state.update_from_async();
+        self.context_diff_state.update_from_async();
+        self.rule_stats_state.update_from_async();
 
         // Top panel with title and view tabs
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
@@ -71,6 +95,9 @@ impl eframe::App for DataDesignerWebApp {
                 ui.selectable_value(&mut self.active_view, ActiveView::Cbu, "📋 CBU DSL");
                 ui.selectable_value(&mut self.active_view, ActiveView::Resource, "🔧 Resource DSL");
                 ui.selectable_value(&mut self.active_view, ActiveView::Onboarding, "🚀 Onboarding Workflows");
+                ui.selectable_value(&mut self.active_view, ActiveView::Tags, "🏷️ Dictionary & Rules");
+                ui.selectable_value(&mut self.active_view, ActiveView::ContextDiff, "🔀 Context Diff");
+                ui.selectable_value(&mut self.active_view, ActiveView::RuleStats, "📊 Rule Statistics");
             });
             ui.separator();
         });
@@ -87,6 +114,15 @@ impl eframe::App for DataDesignerWebApp {
                 ActiveView::Onboarding => {
                     self.onboarding_ide.render(ui, &mut self.onboarding_state);
                 }
+                ActiveView::Tags => {
+                    self.tag_browser_ide.render(ui, &mut self.tag_state);
+                }
+                ActiveView::ContextDiff => {
+                    self.context_diff_ide.render(ui, &mut self.context_diff_state);
+                }
+                ActiveView::RuleStats => {
+                    self.rule_stats_ide.render(ui, &mut self.rule_stats_state);
+                }
             }
         });
     }
@@ -738,6 +738,18 @@ impl CbuDslIDE {
                     if ui.button("📄 Paste").on_hover_text("Paste from clipboard").clicked() {
                         self.paste_from_clipboard();
                     }
+                    let format_button = ui.add_enabled(
+                        !state.formatting_dsl && !state.dsl_script.trim().is_empty(),
+                        egui::Button::new("🧹 Format"),
+                    ).on_hover_text("Re-indent the S-expressions");
+                    if format_button.clicked() {
+                        state.format_dsl_script();
+                    }
+                    if state.formatting_dsl {
+                        ui.spinner();
+                    } else if let Some(error) = &state.last_error {
+                        ui.colored_label(egui::Color32::RED, format!("❌ {}", error));
+                    }
                     // Clear button - REMOVED DEFAULT ACTION
                     // if ui.button("🗑️ Clear").on_hover_text("Clear DSL editor").clicked() {
                     //     state.dsl_script.clear(); // REMOVED: default action that bypassed gRPC state management
@@ -1,7 +1,7 @@
 // CBU State Manager - Central state management for all CBU/Entity data
 // Separates state from UI rendering for clean architecture
 
-use crate::grpc_client::{GrpcClient, CbuRecord};
+use crate::grpc_client::{GrpcClient, CbuRecord, FormatCbuDslRequest, FormatCbuDslResponse};
 use crate::wasm_utils;
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
@@ -56,6 +56,7 @@ pub struct CbuStateManager {
     pub loading_entities: bool,
     pub executing_dsl: bool,
     pub creating_cbu: bool,
+    pub formatting_dsl: bool,
 
     // ---- Results ----
     pub execution_result: Option<CbuDslResponse>,
@@ -67,6 +68,7 @@ pub struct CbuStateManager {
     // Async state bridges (only used internally for async->sync updates)
     entities_loading_state: Option<Arc<Mutex<Vec<EntityInfo>>>>,
     cbus_loading_state: Option<Arc<Mutex<Vec<CbuRecord>>>>,
+    format_state: Option<Arc<Mutex<Option<Result<FormatCbuDslResponse, String>>>>>,
 }
 
 impl CbuStateManager {
@@ -88,6 +90,7 @@ impl CbuStateManager {
             loading_entities: false,
             executing_dsl: false,
             creating_cbu: false,
+            formatting_dsl: false,
 
             execution_result: None,
             last_error: None,
@@ -95,6 +98,7 @@ impl CbuStateManager {
             grpc_client,
             entities_loading_state: None,
             cbus_loading_state: None,
+            format_state: None,
         }
     }
 
@@ -199,6 +203,28 @@ impl CbuStateManager {
         self.dsl_script = content;
     }
 
+    /// Re-indent the DSL script with the server's CBU DSL formatter
+    pub fn format_dsl_script(&mut self) {
+        if self.formatting_dsl || self.dsl_script.trim().is_empty() {
+            return;
+        }
+        let Some(client) = self.grpc_client.clone() else {
+            self.last_error = Some("No gRPC client available".to_string());
+            return;
+        };
+
+        self.formatting_dsl = true;
+        self.last_error = None;
+        let slot = Arc::new(Mutex::new(None));
+        self.format_state = Some(slot.clone());
+        let request = FormatCbuDslRequest { dsl_script: self.dsl_script.clone(), width: None };
+
+        wasm_utils::spawn_async(async move {
+            let result = client.format_cbu_dsl(request).await.map_err(|e| format!("Formatting failed: {}", e));
+            *slot.lock().unwrap() = Some(result);
+        });
+    }
+
     /// Clear all state
     pub fn clear(&mut self) {
         self.dsl_script.clear();
@@ -262,6 +288,21 @@ impl CbuStateManager {
         if should_clear_entities {
             self.entities_loading_state = None;
         }
+
+        // Apply the formatted script, unless the formatter couldn't parse it
+        let formatted = self.format_state.as_ref().and_then(|slot| slot.try_lock().ok().and_then(|mut ready| ready.take()));
+        if let Some(result) = formatted {
+            self.format_state = None;
+            self.formatting_dsl = false;
+            match result {
+                Ok(response) if response.success => self.dsl_script = response.formatted,
+                Ok(response) => self.last_error = Some(response.message),
+                Err(e) => {
+                    wasm_utils::console_log(&format!("❌ State Manager: {}", e));
+                    self.last_error = Some(e);
+                }
+            }
+        }
     }
 
     // ============================================
@@ -269,7 +310,7 @@ impl CbuStateManager {
     // ============================================
 
     pub fn is_loading(&self) -> bool {
-        self.loading_cbus || self.loading_entities || self.executing_dsl || self.creating_cbu
+        self.loading_cbus || self.loading_entities || self.executing_dsl || self.creating_cbu || self.formatting_dsl
     }
 
     pub fn get_available_cbus(&self) -> &[CbuRecord] {
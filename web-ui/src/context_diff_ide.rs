@@ -0,0 +1,154 @@
+// Context Diff IDE - Pure UI Component comparing a rule's evaluation in two contexts
+// State lives in ContextDiffStateManager; this component only renders it
+
+use eframe::egui;
+use crate::context_diff_state_manager::ContextDiffStateManager;
+use crate::grpc_client::{AttributeDiffRecord, OutcomeRecord};
+
+pub struct ContextDiffIDE {
+    // UI state only - no business logic
+    origins_only: bool,
+}
+
+impl ContextDiffIDE {
+    pub fn new() -> Self {
+        Self { origins_only: false }
+    }
+
+    pub fn render(&mut self, ui: &mut egui::Ui, state: &mut ContextDiffStateManager) {
+        // Poll async updates
+        state.update_from_async();
+
+        ui.horizontal(|ui| {
+            ui.heading("🔀 Context Diff");
+            ui.separator();
+            ui.label("Why does this rule give a different result in two environments?");
+        });
+        ui.separator();
+
+        ui.label("Rule (leave empty to compare the contexts alone):");
+        ui.add(egui::TextEdit::multiline(&mut state.rule_text)
+            .code_editor()
+            .desired_rows(3)
+            .desired_width(f32::INFINITY));
+
+        ui.columns(2, |columns| {
+            columns[0].add(egui::TextEdit::singleline(&mut state.label_a).desired_width(120.0));
+            columns[0].add(egui::TextEdit::multiline(&mut state.facts_a_text)
+                .code_editor()
+                .desired_rows(8)
+                .desired_width(f32::INFINITY));
+            columns[1].add(egui::TextEdit::singleline(&mut state.label_b).desired_width(120.0));
+            columns[1].add(egui::TextEdit::multiline(&mut state.facts_b_text)
+                .code_editor()
+                .desired_rows(8)
+                .desired_width(f32::INFINITY));
+        });
+
+        ui.horizontal(|ui| {
+            if ui.add_enabled(!state.comparing, egui::Button::new("🔍 Compare")).clicked() {
+                state.compare();
+            }
+            if ui.button("⇄ Swap").clicked() {
+                state.swap();
+            }
+            if state.comparing {
+                ui.spinner();
+            }
+        });
+
+        if let Some(error) = &state.last_error {
+            ui.colored_label(egui::Color32::RED, format!("❌ {}", error));
+        }
+
+        let Some(result) = &state.result else { return };
+        ui.separator();
+        ui.label(&result.message);
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            if let Some(diff) = &result.diff {
+                ui.horizontal(|ui| {
+                    ui.strong("Result:");
+                    ui.label(format!("{} = {}", state.label_a, outcome_text(&diff.a)));
+                    ui.separator();
+                    ui.label(format!("{} = {}", state.label_b, outcome_text(&diff.b)));
+                });
+
+                ui.add_space(6.0);
+                ui.horizontal(|ui| {
+                    ui.strong(format!("Diverging sub-expressions ({})", diff.divergences.len()));
+                    ui.checkbox(&mut self.origins_only, "Where differences start only");
+                });
+                egui::Grid::new("context_diff_divergences")
+                    .num_columns(3)
+                    .spacing([10.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Expression");
+                        ui.strong(&state.label_a);
+                        ui.strong(&state.label_b);
+                        ui.end_row();
+
+                        for divergence in diff.divergences.iter().filter(|d| d.origin || !self.origins_only) {
+                            let indent = if self.origins_only { String::new() } else { "  ".repeat(divergence.depth) };
+                            let text = format!("{}{}", indent, divergence.expression);
+                            if divergence.origin {
+                                ui.colored_label(egui::Color32::from_rgb(230, 140, 0), format!("▶ {}", text))
+                                    .on_hover_text("The difference starts here");
+                            } else {
+                                ui.monospace(text);
+                            }
+                            ui.label(outcome_text(&divergence.a));
+                            ui.label(outcome_text(&divergence.b));
+                            ui.end_row();
+                        }
+                    });
+            }
+
+            ui.add_space(6.0);
+            ui.strong(format!("Attributes that differ ({})", result.attributes.len()));
+            let read_by_rule = |attribute: &AttributeDiffRecord| {
+                result.diff.as_ref().is_some_and(|diff| diff.attributes.iter().any(|read| read.attribute == attribute.attribute))
+            };
+            egui::Grid::new("context_diff_attributes")
+                .num_columns(3)
+                .spacing([10.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong("Attribute");
+                    ui.strong(&state.label_a);
+                    ui.strong(&state.label_b);
+                    ui.end_row();
+
+                    for attribute in &result.attributes {
+                        if read_by_rule(attribute) {
+                            ui.strong(&attribute.attribute).on_hover_text("Read by the rule");
+                        } else {
+                            ui.label(&attribute.attribute);
+                        }
+                        ui.label(value_text(attribute.a.as_ref()));
+                        ui.label(value_text(attribute.b.as_ref()));
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+}
+
+fn outcome_text(outcome: &OutcomeRecord) -> String {
+    match (&outcome.value, &outcome.error) {
+        (Some(value), _) => value.to_string(),
+        (None, Some(error)) => format!("error: {}", error),
+        (None, None) => "error".to_string(),
+    }
+}
+
+fn value_text(value: Option<&serde_json::Value>) -> String {
+    value.map_or_else(|| "(missing)".to_string(), |value| value.to_string())
+}
+
+impl Default for ContextDiffIDE {
+    fn default() -> Self {
+        Self::new()
+    }
+}
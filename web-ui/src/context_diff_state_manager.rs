@@ -0,0 +1,104 @@
+// Context Diff State Manager - Compare a rule's evaluation in two environments
+// Same async bridge pattern as TagStateManager: the task fills a slot, update_from_async moves it into state
+
+use crate::grpc_client::{DiffContextsRequest, DiffContextsResponse, GrpcClient};
+use crate::wasm_utils;
+use std::sync::{Arc, Mutex};
+
+/// Central state for "why does this rule give a different answer in UAT and prod"
+pub struct ContextDiffStateManager {
+    // ---- Inputs ----
+    pub rule_text: String,
+    pub label_a: String,
+    pub label_b: String,
+    /// Facts as JSON objects, e.g. pasted from each environment's explain request
+    pub facts_a_text: String,
+    pub facts_b_text: String,
+
+    // ---- Data ----
+    pub result: Option<DiffContextsResponse>,
+
+    // ---- Loading States ----
+    pub comparing: bool,
+    pub last_error: Option<String>,
+
+    // ---- Internal ----
+    grpc_client: Option<GrpcClient>,
+    result_state: Option<Arc<Mutex<Option<Result<DiffContextsResponse, String>>>>>,
+}
+
+impl ContextDiffStateManager {
+    pub fn new(grpc_client: Option<GrpcClient>) -> Self {
+        Self {
+            rule_text: String::new(),
+            label_a: "UAT".to_string(),
+            label_b: "Prod".to_string(),
+            facts_a_text: "{}".to_string(),
+            facts_b_text: "{}".to_string(),
+            result: None,
+            comparing: false,
+            last_error: None,
+            grpc_client,
+            result_state: None,
+        }
+    }
+
+    // ============================================
+    // PUBLIC API - UI calls these methods
+    // ============================================
+
+    pub fn compare(&mut self) {
+        let Some(client) = self.grpc_client.clone() else {
+            self.last_error = Some("No gRPC client available".to_string());
+            return;
+        };
+        let facts = |label: &str, text: &str| match serde_json::from_str::<serde_json::Value>(text) {
+            Ok(facts) if facts.is_object() => Ok(facts),
+            Ok(_) => Err(format!("{} facts must be a JSON object", label)),
+            Err(e) => Err(format!("{} facts are not valid JSON: {}", label, e)),
+        };
+        let request = match (facts(&self.label_a, &self.facts_a_text), facts(&self.label_b, &self.facts_b_text)) {
+            (Ok(facts_a), Ok(facts_b)) => DiffContextsRequest { rule: self.rule_text.clone(), facts_a, facts_b },
+            (Err(e), _) | (_, Err(e)) => {
+                self.last_error = Some(e);
+                return;
+            }
+        };
+
+        self.comparing = true;
+        self.last_error = None;
+        let slot = Arc::new(Mutex::new(None));
+        self.result_state = Some(slot.clone());
+
+        wasm_utils::spawn_async(async move {
+            let result = client.diff_evaluation_contexts(request).await.map_err(|e| format!("Comparison failed: {}", e));
+            *slot.lock().unwrap() = Some(result);
+        });
+    }
+
+    /// Swap the two contexts, so A is always the one being investigated
+    pub fn swap(&mut self) {
+        std::mem::swap(&mut self.label_a, &mut self.label_b);
+        std::mem::swap(&mut self.facts_a_text, &mut self.facts_b_text);
+        self.result = None;
+    }
+
+    /// Move the finished comparison into state
+    pub fn update_from_async(&mut self) {
+        let Some(slot) = &self.result_state else { return };
+        let Some(result) = slot.try_lock().ok().and_then(|mut ready| ready.take()) else { return };
+        self.result_state = None;
+        self.comparing = false;
+        match result {
+            Ok(response) if response.success => self.result = Some(response),
+            Ok(response) => {
+                self.result = None;
+                self.last_error = Some(response.message);
+            }
+            Err(e) => {
+                wasm_utils::console_log(&format!("❌ Context Diff State Manager: {}", e));
+                self.last_error = Some(e);
+            }
+        }
+    }
+}
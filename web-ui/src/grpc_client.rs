@@ -68,6 +68,19 @@ pub struct ExecuteCbuDslResponse {
     pub data: Option<String>, // JSON string
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormatCbuDslRequest {
+    pub dsl_script: String,
+    pub width: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormatCbuDslResponse {
+    pub success: bool,
+    pub message: String,
+    pub formatted: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListCbusRequest {
     pub status_filter: Option<String>,
@@ -422,6 +435,273 @@ pub struct ExecuteWorkflowResponse {
     pub execution_log: Vec<String>,
 }
 
+// Tag and saved filter types
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagInfo {
+    pub id: i32,
+    pub name: String,
+    pub color: Option<String>,
+    pub usage_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListTagsResponse {
+    pub success: bool,
+    pub message: String,
+    pub tags: Vec<TagInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssignTagsRequest {
+    pub target_type: String, // "attribute" or "rule"
+    pub target_key: String,  // attribute full_path or rule_id
+    pub tags: Vec<String>,
+    pub user_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssignTagsResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TagSearchRequest {
+    pub search: Option<String>,
+    pub tags: Vec<String>,
+    pub match_all: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchDictionaryResponse {
+    pub success: bool,
+    pub message: String,
+    pub attributes: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchRulesResponse {
+    pub success: bool,
+    pub message: String,
+    pub rules: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedTagFilter {
+    pub tags: Vec<String>,
+    pub match_all: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedFilterRecord {
+    pub id: Option<i32>,
+    pub user_id: String,
+    pub name: String,
+    pub scope: String, // "dictionary" or "rules"
+    pub search_text: Option<String>,
+    pub tag_filter: SavedTagFilter,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListSavedFiltersRequest {
+    pub user_id: String,
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListSavedFiltersResponse {
+    pub success: bool,
+    pub message: String,
+    pub filters: Vec<SavedFilterRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteSavedFilterRequest {
+    pub user_id: String,
+    pub id: i32,
+}
+
+// Rule statistics dashboard types
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleStatisticsRequest {
+    pub weeks: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeeklyChangesRecord {
+    pub week_start: String, // the Monday, YYYY-MM-DD
+    pub changes: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorChangesRecord {
+    pub author: String,
+    pub changes: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepairAgingRecord {
+    pub rule_id: String,
+    pub since: String,
+    pub days: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleStatisticsRecord {
+    pub total_rules: usize,
+    pub by_status: std::collections::BTreeMap<String, usize>,
+    pub by_category: std::collections::BTreeMap<String, usize>,
+    pub changes_per_week: Vec<WeeklyChangesRecord>,
+    pub top_authors: Vec<AuthorChangesRecord>,
+    pub average_complexity: Option<f64>,
+    pub unparsed_rules: usize,
+    pub in_repair: Vec<RepairAgingRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleStatisticsResponse {
+    pub success: bool,
+    pub message: String,
+    pub statistics: Option<RuleStatisticsRecord>,
+}
+
+// Rules engine service types, facts and values as JSON text
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParseRuleRequest {
+    pub rule: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParseRuleResponse {
+    pub success: bool,
+    pub message: String,
+    pub ast_json: Option<String>,
+    pub dependencies: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluateRuleRequest {
+    pub rule: String,
+    pub facts_json: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluateRuleResponse {
+    pub success: bool,
+    pub message: String,
+    pub value_json: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluateBatchRequest {
+    pub rule: String,
+    pub facts_json: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchEvaluationResult {
+    pub value_json: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluateBatchResponse {
+    pub success: bool,
+    pub message: String,
+    pub results: Vec<BatchEvaluationResult>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranspileRuleRequest {
+    pub rule: String,
+    pub target: String, // "rust", "sql", "javascript", "typescript", "python", "rhai" or "postgres"
+    pub optimize: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranspileRuleResponse {
+    pub success: bool,
+    pub message: String,
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidateCbuScriptRequest {
+    pub script: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CbuScriptDiagnostic {
+    pub code: String,
+    pub message: String,
+    pub start: u32, // byte offsets into the script
+    pub end: u32,
+    pub constraint: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidateCbuScriptResponse {
+    pub success: bool,
+    pub message: String,
+    pub diagnostics: Vec<CbuScriptDiagnostic>,
+    pub planned_operations: Vec<String>,
+}
+
+// Evaluation context diff types
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffContextsRequest {
+    pub rule: String, // empty to compare the contexts alone
+    pub facts_a: serde_json::Value,
+    pub facts_b: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttributeDiffRecord {
+    pub attribute: String,
+    pub a: Option<serde_json::Value>, // None where the context lacks the attribute
+    pub b: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutcomeRecord {
+    pub value: Option<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DivergenceRecord {
+    pub expression: String,
+    pub depth: usize,
+    pub a: OutcomeRecord,
+    pub b: OutcomeRecord,
+    pub origin: bool, // the difference starts here
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultDiffRecord {
+    pub a: OutcomeRecord,
+    pub b: OutcomeRecord,
+    pub attributes: Vec<AttributeDiffRecord>, // the differing attributes the rule reads
+    pub divergences: Vec<DivergenceRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffContextsResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(default)]
+    pub attributes: Vec<AttributeDiffRecord>,
+    pub diff: Option<ResultDiffRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimpleResponse {
+    pub success: bool,
+    pub message: String,
+}
+
 #[derive(Clone)]
 // Unified HTTP client for both platforms
 pub struct GrpcClient {
@@ -490,6 +770,12 @@ impl GrpcClient {
             // Onboarding operations (mapping to gRPC services)
             "financial_taxonomy.FinancialTaxonomyService/CompileOnboardingWorkflow" => "/api/onboarding/compile",
             "financial_taxonomy.FinancialTaxonomyService/ExecuteOnboardingWorkflow" => "/api/onboarding/execute",
+            // Rules engine service
+            "financial_taxonomy.RulesEngineService/ParseRule" => "/api/rules-engine/parse-rule",
+            "financial_taxonomy.RulesEngineService/EvaluateRule" => "/api/rules-engine/evaluate-rule",
+            "financial_taxonomy.RulesEngineService/EvaluateBatch" => "/api/rules-engine/evaluate-batch",
+            "financial_taxonomy.RulesEngineService/TranspileRule" => "/api/rules-engine/transpile-rule",
+            "financial_taxonomy.RulesEngineService/ValidateCbuScript" => "/api/rules-engine/validate-cbu-script",
             _ => {
                 wasm_utils::console_log(&format!("❌ Unknown service method: '{}'", service_method));
                 return Err(make_error(&format!("Unknown service method: {}", service_method)));
@@ -655,6 +941,10 @@ impl GrpcClient {
             .await
     }
 
+    pub async fn format_cbu_dsl(&self, request: FormatCbuDslRequest) -> Result<FormatCbuDslResponse> {
+        self.post_request("/api/format-cbu-dsl", &request).await
+    }
+
     pub async fn list_cbus(
         &self,
         request: ListCbusRequest,
@@ -723,6 +1013,74 @@ impl GrpcClient {
             .await
     }
 
+    // ============================================
+    // Tags and Saved Filters
+    // ============================================
+
+    pub async fn list_tags(&self) -> Result<ListTagsResponse> {
+        self.post_request("/api/list-tags", &serde_json::json!({})).await
+    }
+
+    pub async fn assign_tags(&self, request: AssignTagsRequest) -> Result<AssignTagsResponse> {
+        self.post_request("/api/assign-tags", &request).await
+    }
+
+    pub async fn unassign_tags(&self, request: AssignTagsRequest) -> Result<SimpleResponse> {
+        self.post_request("/api/unassign-tags", &request).await
+    }
+
+    pub async fn search_dictionary(&self, request: TagSearchRequest) -> Result<SearchDictionaryResponse> {
+        self.post_request("/api/search-dictionary", &request).await
+    }
+
+    pub async fn search_rules(&self, request: TagSearchRequest) -> Result<SearchRulesResponse> {
+        self.post_request("/api/search-rules", &request).await
+    }
+
+    pub async fn list_saved_filters(&self, request: ListSavedFiltersRequest) -> Result<ListSavedFiltersResponse> {
+        self.post_request("/api/list-saved-filters", &request).await
+    }
+
+    pub async fn save_filter(&self, filter: SavedFilterRecord) -> Result<SimpleResponse> {
+        self.post_request("/api/save-filter", &filter).await
+    }
+
+    pub async fn delete_saved_filter(&self, request: DeleteSavedFilterRequest) -> Result<SimpleResponse> {
+        self.post_request("/api/delete-saved-filter", &request).await
+    }
+
+    pub async fn get_rule_statistics(&self, request: RuleStatisticsRequest) -> Result<RuleStatisticsResponse> {
+        self.post_request("/api/rule-statistics", &request).await
+    }
+
+    pub async fn diff_evaluation_contexts(&self, request: DiffContextsRequest) -> Result<DiffContextsResponse> {
+        self.post_request("/api/diff-evaluation-contexts", &request).await
+    }
+
+    // ============================================
+    // Rules Engine Service
+    // ============================================
+
+    pub async fn parse_rule(&self, request: ParseRuleRequest) -> Result<ParseRuleResponse> {
+        self.grpc_call("financial_taxonomy.RulesEngineService/ParseRule", &request).await
+    }
+
+    pub async fn evaluate_rule(&self, request: EvaluateRuleRequest) -> Result<EvaluateRuleResponse> {
+        self.grpc_call("financial_taxonomy.RulesEngineService/EvaluateRule", &request).await
+    }
+
+    pub async fn evaluate_batch(&self, request: EvaluateBatchRequest) -> Result<EvaluateBatchResponse> {
+        self.grpc_call("financial_taxonomy.RulesEngineService/EvaluateBatch", &request).await
+    }
+
+    pub async fn transpile_rule(&self, request: TranspileRuleRequest) -> Result<TranspileRuleResponse> {
+        self.grpc_call("financial_taxonomy.RulesEngineService/TranspileRule", &request).await
+    }
+
+    pub async fn validate_cbu_script(&self, request: ValidateCbuScriptRequest) -> Result<ValidateCbuScriptResponse> {
+        self.grpc_call("financial_taxonomy.RulesEngineService/ValidateCbuScript", &request).await
+    }
+
     // ============================================
     // Unified Onboarding API (maps to gRPC)
     // ============================================
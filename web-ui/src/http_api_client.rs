@@ -392,6 +392,32 @@ impl DataDesignerHttpClient {
         wasm_utils::console_log(&format!("✅ Deleted private attribute {}", id));
         Ok(())
     }
+
+    /// A rule compiled to a WebAssembly module, for `wasm_utils::load_rule_module`.
+    /// Rules the module can't express come back as a 422.
+    pub async fn compile_rule_wasm(&self, rule: &str) -> Result<Vec<u8>> {
+        if !self.connected {
+            return Err(HttpApiError::NotConnected);
+        }
+
+        let url = format!("{}/api/compile-rule-wasm", self.base_url);
+
+        wasm_utils::console_log(&format!("🧩 Compiling rule to WASM: {}", url));
+
+        let response = self.client
+            .post(&url)
+            .json(&serde_json::json!({ "rule": rule }))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(HttpApiError::ServerError {
+                status: response.status().as_u16(),
+            });
+        }
+
+        Ok(response.bytes().await?.to_vec())
+    }
 }
 
 // Test helper for WASM
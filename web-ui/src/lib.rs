@@ -8,9 +8,15 @@ mod grpc_client;
 mod cbu_state_manager;
 mod resource_state_manager;
 mod onboarding_state_manager;
+mod tag_state_manager;
+mod context_diff_state_manager;
+mod rule_stats_state_manager;
 mod cbu_dsl_ide;
 mod resource_dsl_ide;
 mod onboarding_ide;
+mod tag_browser_ide;
+mod context_diff_ide;
+mod rule_stats_ide;
 mod dsl_syntax_highlighter;
 mod dsl_state_manager;
 mod call_tracer;
@@ -12,6 +12,12 @@ mod cbu_state_manager;
 mod resource_state_manager;
 mod cbu_dsl_ide;
 mod resource_dsl_ide;
+mod tag_state_manager;
+mod tag_browser_ide;
+mod context_diff_state_manager;
+mod context_diff_ide;
+mod rule_stats_state_manager;
+mod rule_stats_ide;
 mod dsl_syntax_highlighter;
 mod dsl_state_manager;
 mod call_tracer;
@@ -21,12 +27,21 @@ use cbu_dsl_ide::CbuDslIDE;
 use cbu_state_manager::CbuStateManager;
 use resource_dsl_ide::ResourceDslIDE;
 use resource_state_manager::ResourceStateManager;
+use tag_browser_ide::TagBrowserIDE;
+use tag_state_manager::TagStateManager;
+use context_diff_ide::ContextDiffIDE;
+use context_diff_state_manager::ContextDiffStateManager;
+use rule_stats_ide::RuleStatsIDE;
+use rule_stats_state_manager::RuleStatsStateManager;
 use grpc_client::GrpcClient;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum ActiveView {
     Cbu,
     Resource,
+    Tags,
+    ContextDiff,
+    RuleStats,
 }
 
 #[tokio::main]
@@ -59,10 +74,16 @@ struct DataDesignerApp {
     // Dual state managers - single source of truth
     cbu_state: CbuStateManager,
     resource_state: ResourceStateManager,
+    tag_state: TagStateManager,
+    context_diff_state: ContextDiffStateManager,
+    rule_stats_state: RuleStatsStateManager,
 
     // IDE components - UI only
     cbu_dsl_ide: CbuDslIDE,
     resource_dsl_ide: ResourceDslIDE,
+    tag_browser_ide: TagBrowserIDE,
+    context_diff_ide: ContextDiffIDE,
+    rule_stats_ide: RuleStatsIDE,
 
     grpc_endpoint: String,
     connection_status: String,
@@ -77,9 +98,15 @@ impl DataDesignerApp {
         Self {
             active_view: ActiveView::Cbu,
             cbu_state: CbuStateManager::new(Some(grpc_client.clone())),
-            resource_state: ResourceStateManager::new(Some(grpc_client)),
+            resource_state: ResourceStateManager::new(Some(grpc_client.clone())),
+            tag_state: TagStateManager::new(Some(grpc_client.clone())),
+            context_diff_state: ContextDiffStateManager::new(Some(grpc_client.clone())),
+            rule_stats_state: RuleStatsStateManager::new(Some(grpc_client)),
             cbu_dsl_ide: CbuDslIDE::new(),
             resource_dsl_ide: ResourceDslIDE::new(),
+            tag_browser_ide: TagBrowserIDE::new(),
+            context_diff_ide: ContextDiffIDE::new(),
+            rule_stats_ide: RuleStatsIDE::new(),
             grpc_endpoint,
             connection_status: "Connected to localhost:8080 (HTTP/gRPC bridge)".to_string(),
         }
@@ -91,6 +118,9 @@ impl eframe::App for DataDesignerApp {
         // Update state from async operations for both managers
         self.cbu_state.update_from_async();
         self.resource_state.update_from_async();
+        self.tag_state.update_from_async();
+        self.context_diff_state.update_from_async();
+        self.rule_stats_state.update_from_async();
 
         // Top panel with connection info and view tabs
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
@@ -108,13 +138,20 @@ impl eframe::App for DataDesignerApp {
                     if ui.button("Reconnect").clicked() {
                         let grpc_client = GrpcClient::new(&self.grpc_endpoint);
                         self.cbu_state = CbuStateManager::new(Some(grpc_client.clone()));
-                        self.resource_state = ResourceStateManager::new(Some(grpc_client));
+                        self.resource_state = ResourceStateManager::new(Some(grpc_client.clone()));
+                        self.tag_state = TagStateManager::new(Some(grpc_client.clone()));
+                        self.tag_browser_ide = TagBrowserIDE::new();
+                        self.context_diff_state = ContextDiffStateManager::new(Some(grpc_client.clone()));
+                        self.rule_stats_state = RuleStatsStateManager::new(Some(grpc_client));
                         self.connection_status = format!("Connected to {}", self.grpc_endpoint);
                     }
 
                     if ui.button("Disconnect").clicked() {
                         self.cbu_state = CbuStateManager::new(None);
                         self.resource_state = ResourceStateManager::new(None);
+                        self.tag_state = TagStateManager::new(None);
+                        self.context_diff_state = ContextDiffStateManager::new(None);
+                        self.rule_stats_state = RuleStatsStateManager::new(None);
                         self.connection_status = "Disconnected".to_string();
                     }
                 });
@@ -146,6 +183,27 @@ impl eframe::App for DataDesignerApp {
                 ).clicked() {
                     self.active_view = ActiveView::Resource;
                 }
+
+                if ui.selectable_label(
+                    self.active_view == ActiveView::Tags,
+                    "🏷️ Dictionary & Rules"
+                ).clicked() {
+                    self.active_view = ActiveView::Tags;
+                }
+
+                if ui.selectable_label(
+                    self.active_view == ActiveView::ContextDiff,
+                    "🔀 Context Diff"
+                ).clicked() {
+                    self.active_view = ActiveView::ContextDiff;
+                }
+
+                if ui.selectable_label(
+                    self.active_view == ActiveView::RuleStats,
+                    "📊 Rule Statistics"
+                ).clicked() {
+                    self.active_view = ActiveView::RuleStats;
+                }
             });
         });
 
@@ -158,6 +216,15 @@ impl eframe::App for DataDesignerApp {
                 ActiveView::Resource => {
                     self.resource_dsl_ide.render(ui, &mut self.resource_state);
                 }
+                ActiveView::Tags => {
+                    self.tag_browser_ide.render(ui, &mut self.tag_state);
+                }
+                ActiveView::ContextDiff => {
+                    self.context_diff_ide.render(ui, &mut self.context_diff_state);
+                }
+                ActiveView::RuleStats => {
+                    self.rule_stats_ide.render(ui, &mut self.rule_stats_state);
+                }
             }
         });
 
@@ -181,6 +248,9 @@ impl eframe::App for DataDesignerApp {
                 match self.active_view {
                     ActiveView::Cbu => ui.label("Active: CBU DSL"),
                     ActiveView::Resource => ui.label("Active: Resource DSL"),
+                    ActiveView::Tags => ui.label("Active: Dictionary & Rules"),
+                    ActiveView::ContextDiff => ui.label("Active: Context Diff"),
+                    ActiveView::RuleStats => ui.label("Active: Rule Statistics"),
                 };
             });
         });
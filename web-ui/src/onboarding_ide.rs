@@ -6,6 +6,7 @@ pub struct OnboardingIDE {
     show_yaml_editor: bool,
     show_intent_editor: bool,
     show_output: bool,
+    show_history: bool,
 }
 
 impl OnboardingIDE {
@@ -14,6 +15,7 @@ impl OnboardingIDE {
             show_yaml_editor: true,
             show_intent_editor: true,
             show_output: true,
+            show_history: false,
         }
     }
 
@@ -54,6 +56,7 @@ impl OnboardingIDE {
                 ui.checkbox(&mut self.show_yaml_editor, "YAML");
                 ui.checkbox(&mut self.show_intent_editor, "Intent");
                 ui.checkbox(&mut self.show_output, "Output");
+                ui.checkbox(&mut self.show_history, "History");
             });
         });
 
@@ -75,6 +78,11 @@ impl OnboardingIDE {
 
         ui.separator();
 
+        if self.show_history {
+            self.render_history_monitor(ui, state);
+            ui.separator();
+        }
+
         // Main content - three panels
         let panel_count = [self.show_yaml_editor, self.show_intent_editor, self.show_output]
             .iter()
@@ -301,4 +309,89 @@ impl OnboardingIDE {
                 }
             });
     }
+
+    /// Scrubs through the instance's recorded events; each slider position
+    /// shows the state the instance was in right after that event
+    fn render_history_monitor(&mut self, ui: &mut egui::Ui, state: &mut OnboardingStateManager) {
+        ui.horizontal(|ui| {
+            ui.heading("Instance History");
+            ui.label(format!("Instance: {}", state.instance_id));
+
+            if ui.add_enabled(!state.history_loading, egui::Button::new("🔄 Load History")).clicked() {
+                state.load_instance_history();
+            }
+            if state.history_loading {
+                ui.spinner();
+            }
+            if let Some(message) = &state.history_message {
+                ui.label(message);
+            }
+        });
+
+        if state.history.is_empty() {
+            ui.label("No recorded events. Compile or execute the workflow to record some.");
+            return;
+        }
+
+        let last = state.history.len() - 1;
+        ui.horizontal(|ui| {
+            if ui.add_enabled(state.history_position > 0, egui::Button::new("⏮")).clicked() {
+                state.history_position -= 1;
+            }
+            ui.add(
+                egui::Slider::new(&mut state.history_position, 0..=last)
+                    .custom_formatter(|position, _| format!("event {}", position as usize + 1))
+            );
+            if ui.add_enabled(state.history_position < last, egui::Button::new("⏭")).clicked() {
+                state.history_position += 1;
+            }
+            ui.label(format!("of {}", last + 1));
+        });
+
+        let Some(entry) = state.history_entry() else {
+            return;
+        };
+        let snapshot = &entry.snapshot;
+
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                ui.strong(&entry.event_type);
+                ui.label(format!("at {}", entry.occurred_at));
+            });
+            ui.separator();
+
+            let state_color = match snapshot.state.as_str() {
+                "Completed" => egui::Color32::GREEN,
+                "Failed" => egui::Color32::RED,
+                "Executing" => egui::Color32::YELLOW,
+                _ => ui.visuals().text_color(),
+            };
+            ui.horizontal(|ui| {
+                ui.label("State:");
+                ui.colored_label(state_color, &snapshot.state);
+            });
+            ui.label(format!("CBU: {}", snapshot.cbu_id.as_deref().unwrap_or("-")));
+            ui.label(format!("Products: {}", if snapshot.products.is_empty() { "-".to_string() } else { snapshot.products.join(", ") }));
+            if !snapshot.idd_gaps.is_empty() {
+                ui.label(format!("IDD gaps: {}", snapshot.idd_gaps.join(", ")));
+            }
+            if let Some(error) = &snapshot.last_error {
+                ui.colored_label(egui::Color32::RED, format!("Error: {}", error));
+            }
+
+            if !snapshot.tasks.is_empty() {
+                ui.separator();
+                ui.label("Tasks:");
+                for (task_id, status) in &snapshot.tasks {
+                    let icon = match status.as_str() {
+                        "Succeeded" => "✓",
+                        "Failed" => "✗",
+                        "Running" => "▶",
+                        _ => "○",
+                    };
+                    ui.label(format!("  {} {} ({})", icon, task_id, status));
+                }
+            }
+        });
+    }
 }
@@ -11,6 +11,35 @@ pub struct OnboardingMetadata {
     pub resource_dicts: HashMap<String, String>,
 }
 
+/// An instance as it stood after one of its recorded events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstanceSnapshot {
+    pub instance_id: String,
+    pub state: String,
+    pub cbu_id: Option<String>,
+    pub products: Vec<String>,
+    pub idd_gaps: Vec<String>,
+    pub tasks: Vec<(String, String)>,
+    pub last_error: Option<String>,
+    pub events_applied: usize,
+    pub as_of: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstanceHistoryEntry {
+    pub sequence: i64,
+    pub occurred_at: String,
+    pub event_type: String,
+    pub snapshot: InstanceSnapshot,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstanceHistoryResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(default)]
+    pub history: Vec<InstanceHistoryEntry>,
+}
 
 pub struct OnboardingStateManager {
     client: Option<GrpcClient>,
@@ -45,10 +74,17 @@ pub struct OnboardingStateManager {
     pub execute_result: Option<ExecuteWorkflowResponse>,
     pub executing: bool,
 
+    // Instance history replayed from the event log; the slider picks an entry
+    pub history: Vec<InstanceHistoryEntry>,
+    pub history_position: usize,
+    pub history_loading: bool,
+    pub history_message: Option<String>,
+
     // Async state bridges (Arc<Mutex<>> for thread-safe async updates)
     metadata_state: Option<Arc<Mutex<Option<OnboardingMetadata>>>>,
     compile_state: Option<Arc<Mutex<Option<CompileWorkflowResponse>>>>,
     execute_state: Option<Arc<Mutex<Option<ExecuteWorkflowResponse>>>>,
+    history_state: Option<Arc<Mutex<Option<InstanceHistoryResponse>>>>,
     error_state: Option<Arc<Mutex<Option<String>>>>,
 }
 
@@ -77,9 +113,14 @@ impl OnboardingStateManager {
             compiling: false,
             execute_result: None,
             executing: false,
+            history: Vec::new(),
+            history_position: 0,
+            history_loading: false,
+            history_message: None,
             metadata_state: None,
             compile_state: None,
             execute_state: None,
+            history_state: None,
             error_state: None,
         }
     }
@@ -301,6 +342,49 @@ impl OnboardingStateManager {
         });
     }
 
+    // === Instance History ===
+
+    pub fn load_instance_history(&mut self) {
+        if self.history_loading {
+            return;
+        }
+
+        let client = match &self.client {
+            Some(c) => c.clone(),
+            None => {
+                self.history_message = Some("No client available".to_string());
+                return;
+            }
+        };
+
+        self.history_loading = true;
+        self.history_message = None;
+
+        let history_state = Arc::new(Mutex::new(None));
+        self.history_state = Some(history_state.clone());
+
+        let request = serde_json::json!({ "instance_id": self.instance_id });
+
+        wasm_utils::spawn_async(async move {
+            let response = client
+                .post_request::<_, InstanceHistoryResponse>("/api/onboarding/instance-history", &request)
+                .await
+                .unwrap_or_else(|e| InstanceHistoryResponse {
+                    success: false,
+                    message: format!("Failed to load history: {}", e),
+                    history: Vec::new(),
+                });
+            if let Ok(mut state) = history_state.lock() {
+                *state = Some(response);
+            }
+        });
+    }
+
+    /// The entry the history slider is on
+    pub fn history_entry(&self) -> Option<&InstanceHistoryEntry> {
+        self.history.get(self.history_position)
+    }
+
     // === Async Result Processing ===
 
     pub fn update_from_async(&mut self) {
@@ -339,6 +423,19 @@ impl OnboardingStateManager {
             self.execute_state = None; // Clear after scope ends
         }
 
+        // Check history loading state; keep polling until the response arrives
+        if let Some(state) = &self.history_state {
+            let response = state.lock().ok().and_then(|mut guard| guard.take());
+            if let Some(response) = response {
+                self.history = response.history;
+                // Start at the latest state and scrub back from there
+                self.history_position = self.history.len().saturating_sub(1);
+                self.history_message = Some(response.message);
+                self.history_loading = false;
+                self.history_state = None;
+            }
+        }
+
         // Check error state
         if let Some(state) = &self.error_state {
             if let Ok(mut guard) = state.lock() {
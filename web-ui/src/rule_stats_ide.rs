@@ -0,0 +1,130 @@
+// Rule Statistics IDE - Pure UI Component for the workspace rule dashboard
+// State lives in RuleStatsStateManager; this component only renders it
+
+use eframe::egui;
+use crate::rule_stats_state_manager::RuleStatsStateManager;
+use std::collections::BTreeMap;
+
+pub struct RuleStatsIDE;
+
+impl RuleStatsIDE {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn render(&mut self, ui: &mut egui::Ui, state: &mut RuleStatsStateManager) {
+        // Poll async updates
+        state.update_from_async();
+        state.ensure_loaded();
+
+        ui.horizontal(|ui| {
+            ui.heading("📊 Rule Statistics");
+            ui.separator();
+            ui.label("Weeks:");
+            ui.add(egui::DragValue::new(&mut state.weeks).range(1..=104));
+            if ui.add_enabled(!state.loading, egui::Button::new("🔄 Refresh")).clicked() {
+                state.refresh();
+            }
+            if state.loading {
+                ui.spinner();
+            }
+        });
+        ui.separator();
+
+        if let Some(error) = &state.last_error {
+            ui.colored_label(egui::Color32::RED, format!("❌ {}", error));
+        }
+
+        let Some(stats) = &state.statistics else { return };
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.strong(format!("{} rules", stats.total_rules));
+                ui.separator();
+                match stats.average_complexity {
+                    Some(average) => ui.label(format!("Average complexity: {:.1} AST nodes", average)),
+                    None => ui.label("Average complexity: n/a"),
+                };
+                if stats.unparsed_rules > 0 {
+                    ui.separator();
+                    ui.colored_label(egui::Color32::from_rgb(230, 140, 0), format!("{} don't parse", stats.unparsed_rules));
+                }
+            });
+
+            ui.add_space(6.0);
+            ui.columns(2, |columns| {
+                count_grid(&mut columns[0], "rule_stats_status", "Status", &stats.by_status);
+                count_grid(&mut columns[1], "rule_stats_category", "Category", &stats.by_category);
+            });
+
+            ui.add_space(6.0);
+            ui.strong(format!("Changes per week (last {})", stats.changes_per_week.len()));
+            let busiest = stats.changes_per_week.iter().map(|week| week.changes).max().unwrap_or(0).max(1);
+            egui::Grid::new("rule_stats_weeks")
+                .num_columns(3)
+                .spacing([10.0, 2.0])
+                .show(ui, |ui| {
+                    for week in &stats.changes_per_week {
+                        ui.monospace(&week.week_start);
+                        ui.add(egui::ProgressBar::new(week.changes as f32 / busiest as f32).desired_width(200.0));
+                        ui.label(week.changes.to_string());
+                        ui.end_row();
+                    }
+                });
+
+            ui.add_space(6.0);
+            ui.strong("Top authors");
+            egui::Grid::new("rule_stats_authors")
+                .num_columns(2)
+                .spacing([10.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    for author in &stats.top_authors {
+                        ui.label(&author.author);
+                        ui.label(author.changes.to_string());
+                        ui.end_row();
+                    }
+                });
+
+            ui.add_space(6.0);
+            ui.strong(format!("In repair ({})", stats.in_repair.len()));
+            egui::Grid::new("rule_stats_repair")
+                .num_columns(2)
+                .spacing([10.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    for rule in &stats.in_repair {
+                        ui.monospace(&rule.rule_id).on_hover_text(format!("In repair since {}", rule.since));
+                        let days = format!("{} days", rule.days);
+                        if rule.days >= 7 {
+                            ui.colored_label(egui::Color32::RED, days);
+                        } else {
+                            ui.label(days);
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+}
+
+fn count_grid(ui: &mut egui::Ui, id: &str, heading: &str, counts: &BTreeMap<String, usize>) {
+    ui.strong(heading);
+    egui::Grid::new(id)
+        .num_columns(2)
+        .spacing([10.0, 4.0])
+        .striped(true)
+        .show(ui, |ui| {
+            for (name, count) in counts {
+                ui.label(name);
+                ui.label(count.to_string());
+                ui.end_row();
+            }
+        });
+}
+
+impl Default for RuleStatsIDE {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,82 @@
+// Rule Statistics State Manager - Workspace-wide rule statistics for the dashboard
+// Same async bridge pattern as ContextDiffStateManager: the task fills a slot, update_from_async moves it into state
+
+use crate::grpc_client::{GrpcClient, RuleStatisticsRecord, RuleStatisticsRequest, RuleStatisticsResponse};
+use crate::wasm_utils;
+use std::sync::{Arc, Mutex};
+
+/// Central state for the rule statistics dashboard
+pub struct RuleStatsStateManager {
+    // ---- Inputs ----
+    /// How many weeks of change activity to show
+    pub weeks: usize,
+
+    // ---- Data ----
+    pub statistics: Option<RuleStatisticsRecord>,
+
+    // ---- Loading States ----
+    pub loading: bool,
+    pub last_error: Option<String>,
+
+    // ---- Internal ----
+    grpc_client: Option<GrpcClient>,
+    result_state: Option<Arc<Mutex<Option<Result<RuleStatisticsResponse, String>>>>>,
+}
+
+impl RuleStatsStateManager {
+    pub fn new(grpc_client: Option<GrpcClient>) -> Self {
+        Self {
+            weeks: 12,
+            statistics: None,
+            loading: false,
+            last_error: None,
+            grpc_client,
+            result_state: None,
+        }
+    }
+
+    // ============================================
+    // PUBLIC API - UI calls these methods
+    // ============================================
+
+    pub fn refresh(&mut self) {
+        let Some(client) = self.grpc_client.clone() else {
+            self.last_error = Some("No gRPC client available".to_string());
+            return;
+        };
+
+        self.loading = true;
+        self.last_error = None;
+        let slot = Arc::new(Mutex::new(None));
+        self.result_state = Some(slot.clone());
+
+        let request = RuleStatisticsRequest { weeks: self.weeks };
+        wasm_utils::spawn_async(async move {
+            let result = client.get_rule_statistics(request).await.map_err(|e| format!("Loading statistics failed: {}", e));
+            *slot.lock().unwrap() = Some(result);
+        });
+    }
+
+    /// Load the statistics the first time the panel is shown
+    pub fn ensure_loaded(&mut self) {
+        if self.statistics.is_none() && !self.loading && self.last_error.is_none() {
+            self.refresh();
+        }
+    }
+
+    /// Move the finished request into state
+    pub fn update_from_async(&mut self) {
+        let Some(slot) = &self.result_state else { return };
+        let Some(result) = slot.try_lock().ok().and_then(|mut ready| ready.take()) else { return };
+        self.result_state = None;
+        self.loading = false;
+        match result {
+            Ok(response) if response.success => self.statistics = response.statistics,
+            Ok(response) => self.last_error = Some(response.message),
+            Err(e) => {
+                wasm_utils::console_log(&format!("❌ Rule Stats State Manager: {}", e));
+                self.last_error = Some(e);
+            }
+        }
+    }
+}
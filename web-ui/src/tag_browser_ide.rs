@@ -0,0 +1,234 @@
+// Tag Browser IDE - Pure UI Component for tag-filtered dictionary and rule search
+// State lives in TagStateManager; this component only renders it
+
+use eframe::egui;
+use crate::tag_state_manager::{TagScope, TagStateManager};
+
+pub struct TagBrowserIDE {
+    // UI state only - no business logic
+    new_filter_name: String,
+    new_tag_text: String,
+    selected_row_key: Option<String>,
+    initialized: bool,
+}
+
+impl TagBrowserIDE {
+    pub fn new() -> Self {
+        Self {
+            new_filter_name: String::new(),
+            new_tag_text: String::new(),
+            selected_row_key: None,
+            initialized: false,
+        }
+    }
+
+    pub fn render(&mut self, ui: &mut egui::Ui, state: &mut TagStateManager) {
+        // Load tags, presets and an unfiltered result set on first render
+        if !self.initialized {
+            self.initialized = true;
+            state.load_tags();
+            state.load_saved_filters();
+            state.search();
+        }
+
+        // Poll async updates
+        state.update_from_async();
+
+        ui.horizontal(|ui| {
+            ui.heading("🏷️ Dictionary & Rules");
+            ui.separator();
+
+            if ui.selectable_label(state.scope == TagScope::Dictionary, "📚 Data Dictionary").clicked() {
+                state.set_scope(TagScope::Dictionary);
+                self.selected_row_key = None;
+            }
+            if ui.selectable_label(state.scope == TagScope::Rules, "📏 Rules").clicked() {
+                state.set_scope(TagScope::Rules);
+                self.selected_row_key = None;
+            }
+
+            ui.separator();
+            if state.is_loading() {
+                ui.spinner();
+            } else {
+                ui.label(format!("{} results", state.results.len()));
+            }
+        });
+
+        ui.separator();
+
+        self.render_filter_bar(ui, state);
+        self.render_saved_filters(ui, state);
+
+        if let Some(error) = &state.last_error {
+            ui.colored_label(egui::Color32::RED, format!("❌ {}", error));
+        } else if let Some(message) = &state.status_message {
+            ui.colored_label(egui::Color32::GREEN, format!("✅ {}", message));
+        }
+
+        ui.separator();
+
+        self.render_results(ui, state);
+    }
+
+    fn render_filter_bar(&mut self, ui: &mut egui::Ui, state: &mut TagStateManager) {
+        ui.horizontal(|ui| {
+            ui.label("🔍");
+            let response = ui.text_edit_singleline(&mut state.search_text);
+            if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                state.search();
+            }
+            if ui.button("Search").clicked() {
+                state.search();
+            }
+            if ui.checkbox(&mut state.match_all, "Match all tags").changed() && !state.selected_tags.is_empty() {
+                state.search();
+            }
+            if ui.button("Clear").clicked() {
+                state.clear_filter();
+            }
+        });
+
+        // Tag chips - click to toggle
+        let mut toggled = None;
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Tags:");
+            if state.available_tags.is_empty() {
+                ui.weak("No tags yet");
+            }
+            for tag in &state.available_tags {
+                let selected = state.selected_tags.contains(&tag.name);
+                if ui.selectable_label(selected, format!("{} ({})", tag.name, tag.usage_count)).clicked() {
+                    toggled = Some(tag.name.clone());
+                }
+            }
+        });
+        if let Some(tag) = toggled {
+            state.toggle_tag(&tag);
+        }
+    }
+
+    fn render_saved_filters(&mut self, ui: &mut egui::Ui, state: &mut TagStateManager) {
+        let mut apply = None;
+        let mut delete = None;
+
+        ui.horizontal_wrapped(|ui| {
+            ui.label("User:");
+            if ui.add(egui::TextEdit::singleline(&mut state.user_id).desired_width(100.0)).lost_focus() {
+                state.load_saved_filters();
+            }
+
+            ui.separator();
+            ui.label("Saved filters:");
+            for filter in state.filters_for_scope() {
+                if ui.button(&filter.name).clicked() {
+                    apply = Some(filter.clone());
+                }
+                if let Some(id) = filter.id {
+                    if ui.small_button("🗑").on_hover_text("Delete this filter").clicked() {
+                        delete = Some(id);
+                    }
+                }
+            }
+
+            ui.separator();
+            ui.add(egui::TextEdit::singleline(&mut self.new_filter_name)
+                .hint_text("Filter name")
+                .desired_width(120.0));
+            if ui.button("💾 Save current").clicked() {
+                state.save_current_filter(&self.new_filter_name);
+                self.new_filter_name.clear();
+            }
+        });
+
+        if let Some(filter) = apply {
+            state.apply_saved_filter(&filter);
+        }
+        if let Some(id) = delete {
+            state.delete_saved_filter(id);
+        }
+    }
+
+    fn render_results(&mut self, ui: &mut egui::Ui, state: &mut TagStateManager) {
+        let key_field = state.scope.key_field();
+        let mut add_tag = None;
+        let mut remove_tag = None;
+        let mut select = None;
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("tag_browser_results")
+                .num_columns(4)
+                .spacing([10.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    match state.scope {
+                        TagScope::Dictionary => {
+                            ui.strong("Attribute");
+                            ui.strong("Type");
+                        }
+                        TagScope::Rules => {
+                            ui.strong("Rule");
+                            ui.strong("Status");
+                        }
+                    }
+                    ui.strong("Description");
+                    ui.strong("Tags");
+                    ui.end_row();
+
+                    for row in &state.results {
+                        let key = row[key_field].as_str().unwrap_or_default().to_string();
+                        let secondary = match state.scope {
+                            TagScope::Dictionary => row["data_type"].as_str(),
+                            TagScope::Rules => row["status"].as_str(),
+                        };
+
+                        let selected = self.selected_row_key.as_deref() == Some(key.as_str());
+                        if ui.selectable_label(selected, &key).clicked() {
+                            select = Some(key.clone());
+                        }
+                        ui.label(secondary.unwrap_or_default());
+                        ui.label(row["description"].as_str().unwrap_or_default());
+
+                        ui.horizontal_wrapped(|ui| {
+                            for tag in row["tags"].as_array().into_iter().flatten().filter_map(|t| t.as_str()) {
+                                if selected {
+                                    if ui.small_button(format!("{} ✖", tag)).clicked() {
+                                        remove_tag = Some((key.clone(), tag.to_string()));
+                                    }
+                                } else {
+                                    ui.label(format!("#{}", tag));
+                                }
+                            }
+                            if selected {
+                                ui.add(egui::TextEdit::singleline(&mut self.new_tag_text)
+                                    .hint_text("add tag")
+                                    .desired_width(80.0));
+                                if ui.small_button("➕").clicked() && !self.new_tag_text.trim().is_empty() {
+                                    add_tag = Some((key.clone(), self.new_tag_text.trim().to_string()));
+                                }
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+        });
+
+        if let Some(key) = select {
+            self.selected_row_key = if self.selected_row_key.as_ref() == Some(&key) { None } else { Some(key) };
+            self.new_tag_text.clear();
+        }
+        if let Some((key, tag)) = add_tag {
+            state.add_tag(&key, &tag);
+            self.new_tag_text.clear();
+        }
+        if let Some((key, tag)) = remove_tag {
+            state.remove_tag(&key, &tag);
+        }
+    }
+}
+
+impl Default for TagBrowserIDE {
+    fn default() -> Self {
+        Self::new()
+    }
+}
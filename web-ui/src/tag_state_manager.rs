@@ -0,0 +1,368 @@
+// Tag State Manager - Tag filtering and saved filter presets for the dictionary and rules
+// Same async bridge pattern as ResourceStateManager: tasks fill a slot, update_from_async moves it into state
+
+use crate::grpc_client::{
+    AssignTagsRequest, DeleteSavedFilterRequest, GrpcClient, ListSavedFiltersRequest, SavedFilterRecord,
+    SavedTagFilter, TagInfo, TagSearchRequest,
+};
+use crate::wasm_utils;
+use std::sync::{Arc, Mutex};
+
+type Bridge<T> = Option<Arc<Mutex<Option<T>>>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TagScope {
+    Dictionary,
+    Rules,
+}
+
+impl TagScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TagScope::Dictionary => "dictionary",
+            TagScope::Rules => "rules",
+        }
+    }
+
+    /// Tag target type used by the assign/unassign endpoints
+    pub fn target_type(&self) -> &'static str {
+        match self {
+            TagScope::Dictionary => "attribute",
+            TagScope::Rules => "rule",
+        }
+    }
+
+    /// Result field that identifies a row for tagging
+    pub fn key_field(&self) -> &'static str {
+        match self {
+            TagScope::Dictionary => "full_path",
+            TagScope::Rules => "rule_id",
+        }
+    }
+}
+
+/// Central state for tag-based search across the data dictionary and rules
+pub struct TagStateManager {
+    // ---- Filter State ----
+    pub scope: TagScope,
+    pub user_id: String,
+    pub search_text: String,
+    pub selected_tags: Vec<String>,
+    pub match_all: bool,
+
+    // ---- Data ----
+    pub available_tags: Vec<TagInfo>,
+    pub results: Vec<serde_json::Value>,
+    pub saved_filters: Vec<SavedFilterRecord>,
+
+    // ---- Loading States ----
+    pub loading_tags: bool,
+    pub searching: bool,
+    pub loading_filters: bool,
+
+    pub last_error: Option<String>,
+    pub status_message: Option<String>,
+
+    // ---- Internal ----
+    grpc_client: Option<GrpcClient>,
+    tags_state: Bridge<Vec<TagInfo>>,
+    results_state: Bridge<Vec<serde_json::Value>>,
+    filters_state: Bridge<Vec<SavedFilterRecord>>,
+    // Set by mutations; the next update reloads tags, filters and results
+    refresh_state: Bridge<String>,
+    error_state: Arc<Mutex<Option<String>>>,
+}
+
+impl TagStateManager {
+    pub fn new(grpc_client: Option<GrpcClient>) -> Self {
+        Self {
+            scope: TagScope::Dictionary,
+            user_id: "default".to_string(),
+            search_text: String::new(),
+            selected_tags: Vec::new(),
+            match_all: false,
+
+            available_tags: Vec::new(),
+            results: Vec::new(),
+            saved_filters: Vec::new(),
+
+            loading_tags: false,
+            searching: false,
+            loading_filters: false,
+
+            last_error: None,
+            status_message: None,
+
+            grpc_client,
+            tags_state: None,
+            results_state: None,
+            filters_state: None,
+            refresh_state: None,
+            error_state: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    // ============================================
+    // PUBLIC API - UI calls these methods
+    // ============================================
+
+    pub fn load_tags(&mut self) {
+        let Some(client) = self.grpc_client.clone() else { return };
+        if self.loading_tags {
+            return;
+        }
+        self.loading_tags = true;
+
+        let slot = Arc::new(Mutex::new(None));
+        self.tags_state = Some(slot.clone());
+        let errors = self.error_state.clone();
+
+        wasm_utils::spawn_async(async move {
+            match client.list_tags().await {
+                Ok(response) => *slot.lock().unwrap() = Some(response.tags),
+                Err(e) => {
+                    *slot.lock().unwrap() = Some(Vec::new());
+                    *errors.lock().unwrap() = Some(format!("Failed to load tags: {}", e));
+                }
+            }
+        });
+    }
+
+    /// Run the current search text and tag filter against the active scope
+    pub fn search(&mut self) {
+        let Some(client) = self.grpc_client.clone() else {
+            self.last_error = Some("No gRPC client available".to_string());
+            return;
+        };
+        self.searching = true;
+        self.last_error = None;
+
+        let request = self.current_request();
+        let scope = self.scope;
+        let slot = Arc::new(Mutex::new(None));
+        self.results_state = Some(slot.clone());
+        let errors = self.error_state.clone();
+
+        wasm_utils::spawn_async(async move {
+            let result = match scope {
+                TagScope::Dictionary => client.search_dictionary(request).await.map(|r| r.attributes),
+                TagScope::Rules => client.search_rules(request).await.map(|r| r.rules),
+            };
+            match result {
+                Ok(rows) => {
+                    wasm_utils::console_log(&format!("✅ Tag State Manager: {} {} results", rows.len(), scope.as_str()));
+                    *slot.lock().unwrap() = Some(rows);
+                }
+                Err(e) => {
+                    *slot.lock().unwrap() = Some(Vec::new());
+                    *errors.lock().unwrap() = Some(format!("Search failed: {}", e));
+                }
+            }
+        });
+    }
+
+    pub fn set_scope(&mut self, scope: TagScope) {
+        if self.scope != scope {
+            self.scope = scope;
+            self.results.clear();
+            self.search();
+        }
+    }
+
+    pub fn toggle_tag(&mut self, tag: &str) {
+        if let Some(index) = self.selected_tags.iter().position(|t| t == tag) {
+            self.selected_tags.remove(index);
+        } else {
+            self.selected_tags.push(tag.to_string());
+        }
+        self.search();
+    }
+
+    pub fn clear_filter(&mut self) {
+        self.search_text.clear();
+        self.selected_tags.clear();
+        self.match_all = false;
+        self.search();
+    }
+
+    pub fn load_saved_filters(&mut self) {
+        let Some(client) = self.grpc_client.clone() else { return };
+        self.loading_filters = true;
+
+        let request = ListSavedFiltersRequest {
+            user_id: self.user_id.clone(),
+            scope: None,
+        };
+        let slot = Arc::new(Mutex::new(None));
+        self.filters_state = Some(slot.clone());
+        let errors = self.error_state.clone();
+
+        wasm_utils::spawn_async(async move {
+            match client.list_saved_filters(request).await {
+                Ok(response) => *slot.lock().unwrap() = Some(response.filters),
+                Err(e) => {
+                    *slot.lock().unwrap() = Some(Vec::new());
+                    *errors.lock().unwrap() = Some(format!("Failed to load saved filters: {}", e));
+                }
+            }
+        });
+    }
+
+    /// Restore a preset's scope, text and tags, then search
+    pub fn apply_saved_filter(&mut self, filter: &SavedFilterRecord) {
+        self.scope = if filter.scope == "rules" { TagScope::Rules } else { TagScope::Dictionary };
+        self.search_text = filter.search_text.clone().unwrap_or_default();
+        self.selected_tags = filter.tag_filter.tags.clone();
+        self.match_all = filter.tag_filter.match_all;
+        self.search();
+    }
+
+    pub fn save_current_filter(&mut self, name: &str) {
+        let name = name.trim();
+        if name.is_empty() {
+            self.last_error = Some("Enter a name for the filter".to_string());
+            return;
+        }
+
+        let request = self.current_request();
+        let filter = SavedFilterRecord {
+            id: None,
+            user_id: self.user_id.clone(),
+            name: name.to_string(),
+            scope: self.scope.as_str().to_string(),
+            search_text: request.search,
+            tag_filter: SavedTagFilter {
+                tags: request.tags,
+                match_all: request.match_all,
+            },
+        };
+        self.run_mutation(format!("Saved filter '{}'", name), Mutation::SaveFilter(filter));
+    }
+
+    pub fn delete_saved_filter(&mut self, id: i32) {
+        let request = DeleteSavedFilterRequest {
+            user_id: self.user_id.clone(),
+            id,
+        };
+        self.run_mutation("Filter deleted".to_string(), Mutation::DeleteFilter(request));
+    }
+
+    pub fn add_tag(&mut self, target_key: &str, tag: &str) {
+        let request = self.assign_request(target_key, tag);
+        self.run_mutation(format!("Tagged {} with '{}'", target_key, tag.trim()), Mutation::AddTag(request));
+    }
+
+    pub fn remove_tag(&mut self, target_key: &str, tag: &str) {
+        let request = self.assign_request(target_key, tag);
+        self.run_mutation(format!("Removed '{}' from {}", tag, target_key), Mutation::RemoveTag(request));
+    }
+
+    /// Move finished async results into state
+    pub fn update_from_async(&mut self) {
+        if let Some(tags) = take_ready(&mut self.tags_state) {
+            self.available_tags = tags;
+            self.loading_tags = false;
+        }
+        if let Some(results) = take_ready(&mut self.results_state) {
+            self.results = results;
+            self.searching = false;
+        }
+        if let Some(filters) = take_ready(&mut self.filters_state) {
+            self.saved_filters = filters;
+            self.loading_filters = false;
+        }
+        if let Some(message) = take_ready(&mut self.refresh_state) {
+            self.status_message = Some(message);
+            self.load_tags();
+            self.load_saved_filters();
+            self.search();
+        }
+        if let Ok(mut error) = self.error_state.try_lock() {
+            if let Some(error) = error.take() {
+                wasm_utils::console_log(&format!("❌ Tag State Manager: {}", error));
+                self.last_error = Some(error);
+            }
+        }
+    }
+
+    // ============================================
+    // READ-ONLY GETTERS
+    // ============================================
+
+    pub fn is_loading(&self) -> bool {
+        self.loading_tags || self.searching || self.loading_filters
+    }
+
+    pub fn filters_for_scope(&self) -> impl Iterator<Item = &SavedFilterRecord> {
+        let scope = self.scope.as_str();
+        self.saved_filters.iter().filter(move |f| f.scope == scope)
+    }
+
+    // ============================================
+    // INTERNAL
+    // ============================================
+
+    fn current_request(&self) -> TagSearchRequest {
+        let search = self.search_text.trim();
+        TagSearchRequest {
+            search: (!search.is_empty()).then(|| search.to_string()),
+            tags: self.selected_tags.clone(),
+            match_all: self.match_all,
+        }
+    }
+
+    fn assign_request(&self, target_key: &str, tag: &str) -> AssignTagsRequest {
+        AssignTagsRequest {
+            target_type: self.scope.target_type().to_string(),
+            target_key: target_key.to_string(),
+            tags: vec![tag.trim().to_string()],
+            user_id: Some(self.user_id.clone()),
+        }
+    }
+
+    fn run_mutation(&mut self, success_message: String, mutation: Mutation) {
+        let Some(client) = self.grpc_client.clone() else {
+            self.last_error = Some("No gRPC client available".to_string());
+            return;
+        };
+        self.last_error = None;
+
+        let slot = Arc::new(Mutex::new(None));
+        self.refresh_state = Some(slot.clone());
+        let errors = self.error_state.clone();
+
+        wasm_utils::spawn_async(async move {
+            match mutation.run(&client).await {
+                Ok((true, _)) => *slot.lock().unwrap() = Some(success_message),
+                Ok((false, message)) => *errors.lock().unwrap() = Some(message),
+                Err(e) => *errors.lock().unwrap() = Some(e),
+            }
+        });
+    }
+}
+
+/// Writes that change tags or presets; a successful one triggers a refresh
+enum Mutation {
+    SaveFilter(SavedFilterRecord),
+    DeleteFilter(DeleteSavedFilterRequest),
+    AddTag(AssignTagsRequest),
+    RemoveTag(AssignTagsRequest),
+}
+
+impl Mutation {
+    async fn run(self, client: &GrpcClient) -> std::result::Result<(bool, String), String> {
+        let result = match self {
+            Mutation::SaveFilter(filter) => client.save_filter(filter).await.map(|r| (r.success, r.message)),
+            Mutation::DeleteFilter(request) => client.delete_saved_filter(request).await.map(|r| (r.success, r.message)),
+            Mutation::AddTag(request) => client.assign_tags(request).await.map(|r| (r.success, r.message)),
+            Mutation::RemoveTag(request) => client.unassign_tags(request).await.map(|r| (r.success, r.message)),
+        };
+        result.map_err(|e| e.to_string())
+    }
+}
+
+fn take_ready<T>(bridge: &mut Bridge<T>) -> Option<T> {
+    let value = bridge.as_ref()?.try_lock().ok()?.take()?;
+    *bridge = None;
+    Some(value)
+}
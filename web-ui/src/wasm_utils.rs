@@ -36,6 +36,133 @@ mod wasm_impl {
     {
         wasm_bindgen_futures::spawn_local(future);
     }
+
+    use js_sys::{Function, Object, Reflect, Uint8Array, WebAssembly};
+    use serde_json::Value;
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    const ABI_VERSION: i32 = 1;
+    const SLOT_SIZE: usize = 16;
+    const TAG_NULL: i32 = 0;
+    const TAG_BOOL: i32 = 1;
+    const TAG_INT: i32 = 2;
+    const TAG_FLOAT: i32 = 3;
+    const TAG_STRING: i32 = 4;
+
+    #[derive(serde::Deserialize)]
+    struct RuleModuleMetadata {
+        abi: i32,
+        facts: Vec<String>,
+        strings: Vec<String>,
+    }
+
+    /// A rule compiled by `/api/compile-rule-wasm`, evaluated in the browser.
+    /// The ABI is documented in `data_designer_core::transpiler::wasm`.
+    pub struct RuleModule {
+        exports: Object,
+        memory: WebAssembly::Memory,
+        metadata: RuleModuleMetadata,
+    }
+
+    fn js_error(e: JsValue) -> String {
+        e.as_string().unwrap_or_else(|| format!("{:?}", e))
+    }
+
+    pub async fn load_rule_module(bytes: &[u8]) -> Result<RuleModule, String> {
+        let module: WebAssembly::Module = JsFuture::from(WebAssembly::compile(&Uint8Array::from(bytes).into()))
+            .await
+            .map_err(js_error)?
+            .unchecked_into();
+
+        let sections = WebAssembly::Module::custom_sections(&module, "dd_rule");
+        if sections.length() == 0 {
+            return Err("Not a compiled rule module: no dd_rule section".to_string());
+        }
+        let metadata: RuleModuleMetadata = serde_json::from_slice(&Uint8Array::new(&sections.get(0)).to_vec())
+            .map_err(|e| format!("Invalid rule module metadata: {}", e))?;
+        if metadata.abi != ABI_VERSION {
+            return Err(format!("Rule module ABI {} is not supported (expected {})", metadata.abi, ABI_VERSION));
+        }
+
+        let instance: WebAssembly::Instance = JsFuture::from(WebAssembly::instantiate_module(&module, &Object::new()))
+            .await
+            .map_err(js_error)?
+            .unchecked_into();
+        let exports = instance.exports();
+        let memory = Reflect::get(&exports, &"memory".into())
+            .map_err(js_error)?
+            .dyn_into::<WebAssembly::Memory>()
+            .map_err(|_| "Rule module exports no memory".to_string())?;
+
+        Ok(RuleModule { exports, memory, metadata })
+    }
+
+    impl RuleModule {
+        /// Names of the facts the rule reads
+        pub fn facts(&self) -> &[String] {
+            &self.metadata.facts
+        }
+
+        /// The rule's value for `facts`; missing facts are null, as on the server
+        pub fn evaluate(&self, facts: &serde_json::Map<String, Value>) -> Result<Value, String> {
+            let view = js_sys::DataView::new(&self.memory.buffer().unchecked_into(), 0, self.metadata.facts.len() * SLOT_SIZE);
+            // Strings the module has never seen get negative ids, one per distinct string
+            let mut unknown: Vec<String> = Vec::new();
+            for (index, name) in self.metadata.facts.iter().enumerate() {
+                let (tag, payload) = match facts.get(name).unwrap_or(&Value::Null) {
+                    Value::Null => (TAG_NULL, 0.0),
+                    Value::Bool(b) => (TAG_BOOL, if *b { 1.0 } else { 0.0 }),
+                    Value::Number(n) if n.is_i64() || n.is_u64() => (TAG_INT, n.as_f64().unwrap_or_default()),
+                    Value::Number(n) => (TAG_FLOAT, n.as_f64().unwrap_or_default()),
+                    Value::String(s) => {
+                        let id = match self.metadata.strings.iter().position(|known| known == s) {
+                            Some(id) => id as f64,
+                            None => {
+                                let position = unknown.iter().position(|u| u == s).unwrap_or_else(|| {
+                                    unknown.push(s.clone());
+                                    unknown.len() - 1
+                                });
+                                -(position as f64) - 1.0
+                            }
+                        };
+                        (TAG_STRING, id)
+                    }
+                    other => return Err(format!("Fact '{}' is not a scalar: {}", name, other)),
+                };
+                view.set_int32_endian(index * SLOT_SIZE, tag, true);
+                view.set_float64_endian(index * SLOT_SIZE + 8, payload, true);
+            }
+
+            let error = self.call("dd_evaluate")?.as_f64().unwrap_or_default() as i32;
+            match error {
+                0 => {}
+                1 => return Err("Type error".to_string()),
+                2 => return Err("Division by zero".to_string()),
+                3 => return Err("Rule needs the server to evaluate these facts".to_string()),
+                other => return Err(format!("Rule module failed with error {}", other)),
+            }
+
+            let tag = self.call("dd_result_tag")?.as_f64().unwrap_or_default() as i32;
+            let value = self.call("dd_result_value")?.as_f64().unwrap_or_default();
+            Ok(match tag {
+                TAG_BOOL => Value::Bool(value != 0.0),
+                TAG_INT => Value::from(value as i64),
+                TAG_FLOAT => serde_json::Number::from_f64(value).map_or(Value::Null, Value::Number),
+                TAG_STRING if value >= 0.0 => Value::String(self.metadata.strings[value as usize].clone()),
+                TAG_STRING => Value::String(unknown[(-value - 1.0) as usize].clone()),
+                _ => Value::Null,
+            })
+        }
+
+        fn call(&self, export: &str) -> Result<JsValue, String> {
+            let function: Function = Reflect::get(&self.exports, &export.into())
+                .map_err(js_error)?
+                .dyn_into()
+                .map_err(|_| format!("Rule module exports no {}", export))?;
+            function.call0(&JsValue::NULL).map_err(js_error)
+        }
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -69,6 +196,24 @@ mod native_impl {
     {
         tokio::spawn(future);
     }
+
+    /// Compiled rule modules only run in the browser; the desktop app
+    /// evaluates rules through the server
+    pub struct RuleModule;
+
+    pub async fn load_rule_module(_bytes: &[u8]) -> Result<RuleModule, String> {
+        Err("Compiled rule modules run in the browser build only".to_string())
+    }
+
+    impl RuleModule {
+        pub fn facts(&self) -> &[String] {
+            &[]
+        }
+
+        pub fn evaluate(&self, _facts: &serde_json::Map<String, serde_json::Value>) -> Result<serde_json::Value, String> {
+            Err("Compiled rule modules run in the browser build only".to_string())
+        }
+    }
 }
 
 // Re-export the appropriate implementation